@@ -0,0 +1,114 @@
+#include "inc/samplers.hlsl"
+#include "inc/frame_constants.hlsl"
+#include "inc/working_primaries.hlsl"
+#include "inc/pack_unpack.hlsl"
+#include "inc/gbuffer.hlsl"
+#include "inc/bindless_textures.hlsl"
+#include "inc/uv.hlsl"
+
+// Must match `GpuDecal` in `decals.rs`
+struct Decal {
+    row_major float3x4 world_to_decal;
+    float4 albedo_mult;
+    uint albedo_map;
+    uint normal_map;
+    uint roughness_map;
+    float roughness_mult;
+};
+
+static const uint DECAL_NO_MAP = 0xffffffff;
+
+[[vk::binding(0)]] RWTexture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] StructuredBuffer<Decal> decals_dyn;
+[[vk::binding(3)]] cbuffer _ {
+    float4 gbuffer_tex_size;
+    uint decal_count;
+};
+
+// Without derivatives in compute, the mip is derived from the pixel's footprint in decal UV space.
+float4 sample_decal_map(uint map, float2 uv, float footprint_uv) {
+    const float2 wh = bindless_texture_sizes[map].xy;
+    const float lod = log2(footprint_uv * max(wh.x, wh.y));
+
+    return bindless_textures[NonUniformResourceIndex(map)].SampleLevel(sampler_llc, uv, lod);
+}
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float depth = depth_tex[px];
+    if (depth == 0.0) {
+        return;
+    }
+
+    const float2 uv = get_uv(px, gbuffer_tex_size);
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 pt_ws = view_ray_context.ray_hit_ws();
+    const float pixel_width_ws =
+        pixel_cone_spread_angle_from_image_height(gbuffer_tex_size.y) * length(view_ray_context.ray_hit_vs());
+
+    GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+    bool modified = false;
+
+    // Later decals are applied over earlier ones.
+    for (uint decal_idx = 0; decal_idx < decal_count; ++decal_idx) {
+        const Decal decal = decals_dyn[decal_idx];
+        const float3 pt_ds = mul(decal.world_to_decal, float4(pt_ws, 1.0));
+
+        if (any(abs(pt_ds) > 0.5)) {
+            continue;
+        }
+
+        // Rows of the inverse transform point along the decal's axes, scaled by the inverse of its size.
+        const float3 tangent = normalize(decal.world_to_decal[0].xyz);
+        const float3 up = normalize(decal.world_to_decal[1].xyz);
+
+        // Fade out on surfaces facing away from the projection axis, and at the ends of the box along it.
+        float coverage = decal.albedo_mult.a
+            * smoothstep(0.0, 0.5, dot(gbuffer.normal, up))
+            * smoothstep(0.5, 0.4, abs(pt_ds.y));
+
+        const float2 decal_uv = float2(pt_ds.x + 0.5, 0.5 - pt_ds.z);
+        const float footprint_uv = pixel_width_ws * length(decal.world_to_decal[0].xyz)
+            / max(0.1, abs(dot(view_ray_context.ray_dir_ws(), gbuffer.normal)));
+
+        float3 albedo = gbuffer.albedo;
+        if (decal.albedo_map != DECAL_NO_MAP) {
+            const float4 albedo_texel = sample_decal_map(decal.albedo_map, decal_uv, footprint_uv);
+            coverage *= albedo_texel.a;
+            albedo = rec709_to_working(albedo_texel.rgb * decal.albedo_mult.rgb);
+        }
+
+        if (coverage <= 0.0) {
+            continue;
+        }
+
+        gbuffer.albedo = lerp(gbuffer.albedo, albedo, coverage);
+
+        if (decal.normal_map != DECAL_NO_MAP) {
+            float3 ts_normal = float3(sample_decal_map(decal.normal_map, decal_uv, footprint_uv).xy * 2.0 - 1.0, 0);
+            ts_normal.z = sqrt(max(0.01, 1.0 - dot(ts_normal.xy, ts_normal.xy)));
+
+            // The decal's tangent frame, re-oriented around the surface normal
+            const float3 t = normalize(tangent - gbuffer.normal * dot(tangent, gbuffer.normal));
+            const float3 b = cross(gbuffer.normal, t);
+            const float3 decal_normal = mul(ts_normal, float3x3(t, b, gbuffer.normal));
+
+            gbuffer.normal = normalize(lerp(gbuffer.normal, decal_normal, coverage));
+        }
+
+        if (decal.roughness_map != DECAL_NO_MAP) {
+            const float perceptual_roughness =
+                sample_decal_map(decal.roughness_map, decal_uv, footprint_uv).x * decal.roughness_mult;
+            const float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
+
+            gbuffer.roughness = lerp(gbuffer.roughness, roughness, coverage);
+        }
+
+        modified = true;
+    }
+
+    if (modified) {
+        gbuffer_tex[px] = asfloat(gbuffer.pack().data0);
+    }
+}
@@ -0,0 +1,97 @@
+#include "inc/frame_constants.hlsl"
+
+// Joint bilateral upsampling of a reduced-resolution signal, weighted by the depth and normals
+// of the full-res G-buffer to avoid bleeding across edges. See `bilateral_upsample.rs`.
+//
+// The guides of each low-res texel are read from the full-res pixel it was computed for,
+// so the low-res depth and normals aren't needed.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> geometric_normal_tex;
+[[vk::binding(3)]] RWTexture2D<float4> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 input_tex_size;
+    float4 output_tex_size;
+    float4 sky_value;
+    uint divisor;
+    uint use_halfres_subsample_offset;
+    float depth_sharpness;
+    float normal_power;
+};
+
+[numthreads(8, 8, 1)]
+void main(int2 px: SV_DispatchThreadID) {
+    if (any(px >= int2(output_tex_size.xy))) {
+        return;
+    }
+
+    const float center_depth = depth_tex[px];
+    if (center_depth == 0.0) {
+        output_tex[px] = sky_value;
+        return;
+    }
+
+    const float3 center_normal = geometric_normal_tex[px].xyz * 2.0 - 1.0;
+
+    // The full-res pixel which each low-res texel was computed for
+    const int2 src_offset = use_halfres_subsample_offset != 0
+        ? int2(HALFRES_SUBSAMPLE_OFFSET)
+        : int2(divisor / 2, divisor / 2);
+
+    // Position in low-res texels. Both pixel centers are half a texel in, so they cancel out.
+    const float2 lowres_pos = float2(px - src_offset) / divisor;
+    const int2 base_px = int2(floor(lowres_pos));
+    const float2 frac_pos = lowres_pos - base_px;
+
+    const int2 max_lowres_px = int2(input_tex_size.xy) - 1;
+    const int2 max_px = int2(output_tex_size.xy) - 1;
+
+    float4 result = 0.0;
+    float weight_sum = 0.0;
+
+    // Fallback for when all the bilateral weights vanish: the closest sample in depth.
+    float4 closest_value = 0.0;
+    float closest_depth_diff = 1e10;
+
+    for (int y = 0; y <= 1; ++y) {
+        for (int x = 0; x <= 1; ++x) {
+            const int2 sample_px = clamp(base_px + int2(x, y), 0, max_lowres_px);
+            const int2 sample_src_px = min(sample_px * int(divisor) + src_offset, max_px);
+
+            const float sample_depth = depth_tex[sample_src_px];
+            const float4 value = input_tex[sample_px];
+
+            // Sky samples carry no signal.
+            if (sample_depth == 0.0) {
+                continue;
+            }
+
+            const float3 sample_normal = geometric_normal_tex[sample_src_px].xyz * 2.0 - 1.0;
+
+            // Reverse-Z: the ratio of depths is the inverse ratio of view-space distances.
+            const float depth_diff = abs(1.0 - sample_depth / center_depth);
+
+            const float2 bilinear = lerp(1.0 - frac_pos, frac_pos, float2(x, y));
+            const float weight = bilinear.x * bilinear.y
+                * exp2(-depth_diff * depth_sharpness)
+                * pow(saturate(dot(sample_normal, center_normal)), normal_power);
+
+            result += value * weight;
+            weight_sum += weight;
+
+            if (depth_diff < closest_depth_diff) {
+                closest_depth_diff = depth_diff;
+                closest_value = value;
+            }
+        }
+    }
+
+    if (weight_sum > 1e-5) {
+        output_tex[px] = result / weight_sum;
+    } else if (closest_depth_diff < 1e10) {
+        output_tex[px] = closest_value;
+    } else {
+        output_tex[px] = input_tex[clamp(base_px, 0, max_lowres_px)];
+    }
+}
@@ -0,0 +1,20 @@
+#ifndef BLOOM_COMMON_HLSL
+#define BLOOM_COMMON_HLSL
+
+// The part of `col` which blooms: everything above `threshold`, with a quadratic
+// transition `knee` wide on either side of it. Zero threshold lets everything through.
+float3 bloom_threshold(float3 col, float threshold, float knee) {
+    if (threshold <= 0.0) {
+        return col;
+    }
+
+    const float brightness = max(col.r, max(col.g, col.b));
+
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-5);
+
+    const float contribution = max(soft, brightness - threshold) / max(brightness, 1e-5);
+    return col * contribution;
+}
+
+#endif  // BLOOM_COMMON_HLSL
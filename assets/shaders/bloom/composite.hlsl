@@ -0,0 +1,31 @@
+#include "../inc/samplers.hlsl"
+#include "bloom_common.hlsl"
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> bloom_tex;
+[[vk::binding(2)]] Texture2D<float4> lens_dirt_tex;
+[[vk::binding(3)]] RWTexture2D<float4> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+    float bloom_intensity;
+    // Pre-divided by the exposure multiplier, so that they apply to the exposed image
+    float bloom_threshold_scaled;
+    float bloom_knee_scaled;
+    float lens_dirt_intensity;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+
+    float3 glare = bloom_tex.SampleLevel(sampler_lnc, uv, 0).rgb;
+    glare *= 1.0 + lens_dirt_intensity * lens_dirt_tex.SampleLevel(sampler_llc, uv, 0).rgb;
+
+    float3 col = input_tex[px].rgb;
+
+    // The energy which goes into the bloom is taken from the image, so that without a threshold,
+    // it's only redistributed, like glare in the eye.
+    col += (glare - bloom_threshold(col, bloom_threshold_scaled, bloom_knee_scaled)) * bloom_intensity;
+
+    output_tex[px] = float4(max(0.0, col), 1);
+}
@@ -0,0 +1,41 @@
+#include "../inc/samplers.hlsl"
+#include "../inc/color/srgb.hlsl"
+#include "bloom_common.hlsl"
+
+// Downsamples the input to half resolution, keeping only what's above the bloom threshold.
+// The four bilinear taps are weighted by inverse luminance to keep fireflies from flickering.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 input_tex_size;
+    float4 output_tex_size;
+    float threshold;
+    float knee;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+
+    const float2 offsets[4] = {
+        float2(-1, -1), float2(1, -1), float2(-1, 1), float2(1, 1)
+    };
+
+    float3 sum = 0.0;
+    float w_sum = 0.0;
+
+    for (uint i = 0; i < 4; ++i) {
+        const float3 col = bloom_threshold(
+            input_tex.SampleLevel(sampler_lnc, uv + offsets[i] * input_tex_size.zw, 0).rgb,
+            threshold,
+            knee
+        );
+
+        const float w = 1.0 / (1.0 + sRGB_to_luminance(col));
+        sum += col * w;
+        w_sum += w;
+    }
+
+    output_tex[px] = float4(sum / w_sum, 1);
+}
@@ -0,0 +1,37 @@
+#include "../inc/samplers.hlsl"
+
+// Accumulates the bloom pyramid from coarse to fine: each level is its own mip,
+// scaled by that mip's intensity, plus a tent-filtered upsample of the level below.
+
+[[vk::binding(0)]] Texture2D<float4> pyramid_tex;
+[[vk::binding(1)]] Texture2D<float4> coarser_tex;
+[[vk::binding(2)]] RWTexture2D<float4> output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float4 output_tex_size;
+    float4 coarser_tex_size;
+    float self_weight;
+    float coarser_weight;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+
+    float3 coarser = 0.0;
+    if (coarser_weight > 0.0) {
+        const float2 o = coarser_tex_size.zw;
+
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(-o.x, -o.y), 0).rgb;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(0, -o.y), 0).rgb * 2;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(o.x, -o.y), 0).rgb;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(-o.x, 0), 0).rgb * 2;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv, 0).rgb * 4;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(o.x, 0), 0).rgb * 2;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(-o.x, o.y), 0).rgb;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(0, o.y), 0).rgb * 2;
+        coarser += coarser_tex.SampleLevel(sampler_lnc, uv + float2(o.x, o.y), 0).rgb;
+        coarser /= 16.0;
+    }
+
+    output_tex[px] = float4(pyramid_tex[px].rgb * self_weight + coarser * coarser_weight, 1);
+}
@@ -0,0 +1,112 @@
+#include "inc/frame_constants.hlsl"
+#include "inc/uv.hlsl"
+#include "inc/sun.hlsl"
+#include "inc/blue_noise.hlsl"
+
+// Short screen-space rays towards the sun, darkening the shadow mask where they pass
+// behind the depth buffer. Recovers the contact shadows of small features which
+// the ray-traced mask loses to its low sample count and denoising.
+
+[[vk::binding(0)]] Texture2D<float> depth_tex;
+// See `HizPyramid` in `hiz.rs`
+[[vk::binding(1)]] Texture2D<float2> hiz_tex;
+[[vk::binding(2)]] Texture2D<float> shadow_mask_tex;
+[[vk::binding(3)]] RWTexture2D<float> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+    uint hiz_mip_count;
+    uint step_count;
+    float length_ws;
+    float thickness_ws;
+};
+
+// `xy` in UV, `z` in depth buffer units
+float3 view_to_uvz(float3 pt_vs) {
+    const float4 pt_cs = mul(frame_constants.view_constants.view_to_sample, float4(pt_vs, 1));
+    return float3(cs_to_uv(pt_cs.xy / pt_cs.w), pt_cs.z / pt_cs.w);
+}
+
+// Whether the depth buffer could have anything in front of a ray whose screen bounds are
+// `px_min`..`px_max`, and whose farthest depth is `ray_min_depth`. Conservative.
+bool may_be_occluded(uint2 px_min, uint2 px_max, float ray_min_depth) {
+    // Pyramid level 0 is half the resolution of the depth buffer. Find the finest level
+    // at which the bounds span at most 2x2 texels.
+    uint mip = 0;
+    while (mip + 1 < hiz_mip_count && any(((px_max >> (mip + 1)) - (px_min >> (mip + 1))) > 1)) {
+        ++mip;
+    }
+
+    const uint2 texel_min = px_min >> (mip + 1);
+    const uint2 texel_max = px_max >> (mip + 1);
+
+    const float nearest_depth = max(
+        max(hiz_tex.Load(int3(texel_min.x, texel_min.y, mip)).y, hiz_tex.Load(int3(texel_max.x, texel_min.y, mip)).y),
+        max(hiz_tex.Load(int3(texel_min.x, texel_max.y, mip)).y, hiz_tex.Load(int3(texel_max.x, texel_max.y, mip)).y));
+
+    // Depth is reversed
+    return nearest_depth > ray_min_depth;
+}
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float shadow = shadow_mask_tex[px];
+    const float depth = depth_tex[px];
+
+    // Sky, or already fully shadowed
+    if (0.0 == depth || 0.0 == shadow) {
+        output_tex[px] = shadow;
+        return;
+    }
+
+    const float2 uv = get_uv(px, output_tex_size);
+    const float3 origin_vs = ViewRayContext::from_uv_and_depth(uv, depth).ray_hit_vs();
+    const float3 dir_vs = mul(frame_constants.view_constants.world_to_view, float4(SUN_DIRECTION, 0)).xyz;
+
+    // Stop short of the camera plane
+    float ray_length = length_ws;
+    if (dir_vs.z > 0.0) {
+        ray_length = min(ray_length, 0.99 * -origin_vs.z / dir_vs.z);
+    }
+
+    const float3 origin_uvz = view_to_uvz(origin_vs);
+    const float3 end_uvz = view_to_uvz(origin_vs + dir_vs * ray_length);
+
+    // Depth is linear in screen space, so the extremes are at the ends.
+    const uint2 extent = uint2(output_tex_size.xy);
+    const uint2 px_min = min(uint2(saturate(min(origin_uvz.xy, end_uvz.xy)) * output_tex_size.xy), extent - 1);
+    const uint2 px_max = min(uint2(saturate(max(origin_uvz.xy, end_uvz.xy)) * output_tex_size.xy), extent - 1);
+
+    if (!may_be_occluded(px_min, px_max, min(origin_uvz.z, end_uvz.z))) {
+        output_tex[px] = shadow;
+        return;
+    }
+
+    const float jitter = blue_noise_for_pixel(px, frame_constants.frame_index).x;
+
+    for (uint step_i = 0; step_i < step_count; ++step_i) {
+        const float t = (step_i + jitter) / step_count;
+        const float3 sample_uvz = lerp(origin_uvz, end_uvz, t);
+
+        if (any(sample_uvz.xy < 0.0) || any(sample_uvz.xy >= 1.0)) {
+            break;
+        }
+
+        const uint2 sample_px = uint2(sample_uvz.xy * output_tex_size.xy);
+        if (all(sample_px == px)) {
+            continue;
+        }
+
+        const float scene_depth = depth_tex[sample_px];
+
+        // In front of the ray, but not so far in front that the ray could be passing behind it.
+        if (scene_depth > sample_uvz.z) {
+            const float depth_difference_vs = depth_to_view_z(scene_depth) - depth_to_view_z(sample_uvz.z);
+            if (depth_difference_vs < thickness_ws) {
+                output_tex[px] = 0.0;
+                return;
+            }
+        }
+    }
+
+    output_tex[px] = shadow;
+}
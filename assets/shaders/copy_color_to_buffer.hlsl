@@ -0,0 +1,15 @@
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWStructuredBuffer<float4> output_buf;
+[[vk::binding(2)]] cbuffer _ {
+    uint2 input_extent;
+};
+
+// Writes the image to a tightly packed buffer, in row-major order.
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    if (any(px >= input_extent)) {
+        return;
+    }
+
+    output_buf[px.y * input_extent.x + px.x] = input_tex[px];
+}
@@ -0,0 +1,142 @@
+#include "inc/frame_constants.hlsl"
+#include "inc/uv.hlsl"
+
+// Must match `GpuCullInstance` in `culling.rs`
+struct CullInstance {
+    // World-space; center in xyz, radius in w
+    float4 bounding_sphere;
+    uint first_index;
+    uint index_count;
+    uint double_sided;
+    uint pad;
+};
+
+// `VkDrawIndexedIndirectCommand`
+struct DrawIndexedIndirectArgs {
+    uint index_count;
+    uint instance_count;
+    uint first_index;
+    int vertex_offset;
+    uint first_instance;
+};
+
+[[vk::binding(0)]] StructuredBuffer<CullInstance> instances_dyn;
+// Min depth in `x`; see `HizPyramid` in `hiz.rs`
+[[vk::binding(1)]] Texture2D<float2> hiz_tex;
+[[vk::binding(2)]] RWStructuredBuffer<DrawIndexedIndirectArgs> draw_args_buf;
+[[vk::binding(3)]] RWByteAddressBuffer draw_counts_buf;
+[[vk::binding(4)]] cbuffer _ {
+    uint instance_count;
+    uint max_draw_count;
+    uint2 depth_extent;
+    uint hiz_mip_count;
+    uint use_occlusion;
+};
+
+bool is_outside_plane(float4 plane, float4 sphere) {
+    return dot(plane.xyz, sphere.xyz) + plane.w < -sphere.w * length(plane.xyz);
+}
+
+// Tests against the planes of `world_to_clip`, so that any projection works;
+// the same ones as `Frustum::from_world_to_clip` in `math.rs`.
+// The far plane is at infinity, so it's not tested.
+bool is_outside_frustum(float4 sphere) {
+    const float4x4 world_to_clip = mul(
+        frame_constants.view_constants.view_to_clip,
+        frame_constants.view_constants.world_to_view);
+
+    // Depth is reversed: z/w = 1 at the near plane.
+    return is_outside_plane(world_to_clip[3] + world_to_clip[0], sphere)
+        || is_outside_plane(world_to_clip[3] - world_to_clip[0], sphere)
+        || is_outside_plane(world_to_clip[3] + world_to_clip[1], sphere)
+        || is_outside_plane(world_to_clip[3] - world_to_clip[1], sphere)
+        || is_outside_plane(world_to_clip[3] - world_to_clip[2], sphere);
+}
+
+// Tests against the depth pyramid of the previous frame, with the previous frame's view.
+bool is_occluded(float4 sphere) {
+    const float3 center_vs = mul(
+        frame_constants.view_constants.prev_world_to_prev_view,
+        float4(sphere.xyz, 1)).xyz;
+
+    float2 uv_min = 1.0.xx;
+    float2 uv_max = 0.0.xx;
+    float nearest_depth = 0.0;
+
+    // Screen bounds and nearest depth of the view-space box around the sphere
+    for (uint corner = 0; corner < 8; ++corner) {
+        const float3 offset = float3(
+            (corner & 1) != 0 ? 1.0 : -1.0,
+            (corner & 2) != 0 ? 1.0 : -1.0,
+            (corner & 4) != 0 ? 1.0 : -1.0);
+        const float4 cs = mul(
+            frame_constants.view_constants.prev_view_to_prev_clip,
+            float4(center_vs + offset * sphere.w, 1));
+
+        // Crosses the camera plane
+        if (cs.w <= 0.0) {
+            return false;
+        }
+
+        const float2 uv = cs_to_uv(cs.xy / cs.w);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest_depth = max(nearest_depth, cs.z / cs.w);
+    }
+
+    if (nearest_depth >= 1.0) {
+        return false;
+    }
+
+    const uint2 px_min = min(uint2(saturate(uv_min) * float2(depth_extent)), depth_extent - 1);
+    const uint2 px_max = min(uint2(saturate(uv_max) * float2(depth_extent)), depth_extent - 1);
+
+    // Pyramid level 0 is half the resolution of the depth buffer. Find the finest level
+    // at which the bounds span at most 2x2 texels.
+    uint mip = 0;
+    while (mip + 1 < hiz_mip_count && any(((px_max >> (mip + 1)) - (px_min >> (mip + 1))) > 1)) {
+        ++mip;
+    }
+
+    const uint2 texel_min = px_min >> (mip + 1);
+    const uint2 texel_max = px_max >> (mip + 1);
+
+    const float farthest_depth = min(
+        min(hiz_tex.Load(int3(texel_min.x, texel_min.y, mip)).x, hiz_tex.Load(int3(texel_max.x, texel_min.y, mip)).x),
+        min(hiz_tex.Load(int3(texel_min.x, texel_max.y, mip)).x, hiz_tex.Load(int3(texel_max.x, texel_max.y, mip)).x));
+
+    return nearest_depth < farthest_depth;
+}
+
+[numthreads(64, 1, 1)]
+void main(uint instance_idx: SV_DispatchThreadID) {
+    if (instance_idx >= instance_count) {
+        return;
+    }
+
+    const CullInstance instance = instances_dyn[instance_idx];
+
+    if (is_outside_frustum(instance.bounding_sphere)) {
+        return;
+    }
+
+    if (use_occlusion != 0 && is_occluded(instance.bounding_sphere)) {
+        return;
+    }
+
+    // Double-sided meshes are drawn with a separate pipeline, from the first range.
+    const uint range_idx = instance.double_sided != 0 ? 0 : 1;
+
+    uint slot;
+    draw_counts_buf.InterlockedAdd(range_idx * 4, 1, slot);
+
+    DrawIndexedIndirectArgs args;
+    args.index_count = instance.index_count;
+    args.instance_count = 1;
+    args.first_index = instance.first_index;
+    args.vertex_offset = 0;
+    // Picked up by `raster_simple_vs.hlsl` as the draw index
+    args.first_instance = instance_idx;
+
+    draw_args_buf[range_idx * max_draw_count + slot] = args;
+}
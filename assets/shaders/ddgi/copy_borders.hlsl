@@ -0,0 +1,38 @@
+// Fills the one-texel border around each probe's octahedral map in an atlas,
+// so that bilinear filtering wraps around the octahedron's edges.
+
+[[vk::binding(0)]] RWTexture2D<float4> atlas_tex;
+[[vk::binding(1)]] cbuffer _ {
+    uint probe_res;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const uint tile_res = probe_res + 2;
+    const uint2 tile_origin = px / tile_res * tile_res;
+    const uint2 tile_px = px % tile_res;
+
+    const bool is_border_x = tile_px.x == 0 || tile_px.x == tile_res - 1;
+    const bool is_border_y = tile_px.y == 0 || tile_px.y == tile_res - 1;
+    if (!is_border_x && !is_border_y) {
+        return;
+    }
+
+    uint2 src_px;
+
+    if (is_border_x && is_border_y) {
+        // Corners take the diagonally opposite interior corner.
+        src_px = uint2(
+            tile_px.x == 0 ? probe_res : 1,
+            tile_px.y == 0 ? probe_res : 1
+        );
+    } else if (is_border_x) {
+        // Left and right edges are mirrored vertically.
+        src_px = uint2(tile_px.x == 0 ? 1 : probe_res, tile_res - 1 - tile_px.y);
+    } else {
+        // Top and bottom edges are mirrored horizontally.
+        src_px = uint2(tile_res - 1 - tile_px.x, tile_px.y == 0 ? 1 : probe_res);
+    }
+
+    atlas_tex[px] = atlas_tex[tile_origin + src_px];
+}
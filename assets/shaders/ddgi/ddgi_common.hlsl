@@ -0,0 +1,116 @@
+#ifndef DDGI_COMMON_HLSL
+#define DDGI_COMMON_HLSL
+
+#include "../inc/math.hlsl"
+#include "../inc/math_const.hlsl"
+#include "../inc/hash.hlsl"
+#include "../inc/pack_unpack.hlsl"
+
+// Must match `ddgi.rs`
+#define DDGI_IRRADIANCE_PROBE_RES 8
+#define DDGI_DEPTH_PROBE_RES 16
+
+// Probes are relocated by at most this fraction of the probe spacing along each axis.
+static const float DDGI_MAX_PROBE_OFFSET = 0.45;
+
+// Must match `GpuDdgiConstants` in `ddgi.rs`
+//
+// Probes are addressed in three ways:
+// * world coord: integer position in the infinite grid, in units of `probe_spacing`;
+// * storage coord: world coord modulo `probe_counts`, so that probes stay put as the grid scrolls;
+// * probe index: flattened storage coord, which selects a row in the ray texture.
+struct DdgiConstants {
+    int3 grid_origin;
+    float probe_spacing;
+
+    int3 prev_grid_origin;
+    uint history_valid;
+
+    uint3 probe_counts;
+    uint rays_per_probe;
+
+    float hysteresis;
+
+    uint probe_count() {
+        return probe_counts.x * probe_counts.y * probe_counts.z;
+    }
+
+    int3 wrap(int3 v) {
+        const int3 counts = int3(probe_counts);
+        return ((v % counts) + counts) % counts;
+    }
+
+    uint3 probe_idx_to_storage_coord(uint probe_idx) {
+        return uint3(
+            probe_idx % probe_counts.x,
+            (probe_idx / probe_counts.x) % probe_counts.y,
+            probe_idx / (probe_counts.x * probe_counts.y)
+        );
+    }
+
+    uint storage_coord_to_probe_idx(uint3 storage_coord) {
+        return storage_coord.x + (storage_coord.y + storage_coord.z * probe_counts.y) * probe_counts.x;
+    }
+
+    uint3 world_coord_to_storage_coord(int3 world_coord) {
+        return uint3(wrap(world_coord));
+    }
+
+    int3 storage_coord_to_world_coord(uint3 storage_coord) {
+        return grid_origin + wrap(int3(storage_coord) - grid_origin);
+    }
+
+    // Top-left texel of the probe's tile in the atlases, and in the probe offset texture.
+    uint2 storage_coord_to_atlas_tile(uint3 storage_coord) {
+        return uint2(storage_coord.x + storage_coord.y * probe_counts.x, storage_coord.z);
+    }
+
+    uint3 atlas_tile_to_storage_coord(uint2 tile) {
+        return uint3(tile.x % probe_counts.x, tile.x / probe_counts.x, tile.y);
+    }
+
+    // Probes which scrolled into the volume this frame have no valid history.
+    bool is_probe_new(int3 world_coord) {
+        return history_valid == 0
+            || any(world_coord < prev_grid_origin)
+            || any(world_coord >= prev_grid_origin + int3(probe_counts));
+    }
+
+    float3 probe_position(int3 world_coord, float3 offset) {
+        return (float3(world_coord) + offset) * probe_spacing;
+    }
+
+    // Misses and distant hits are clamped to this in the depth maps.
+    float max_probe_distance() {
+        return probe_spacing * 1.5 * sqrt(3.0);
+    }
+};
+
+// Rays are distributed on a spherical Fibonacci spiral, randomly rotated every frame.
+float3 ddgi_ray_direction(uint ray_idx, uint rays_per_probe, uint frame_index) {
+    const float golden_angle = M_PI * (3.0 - sqrt(5.0));
+    const float z = 1.0 - (2.0 * ray_idx + 1.0) / rays_per_probe;
+    const float r = sqrt(max(0.0, 1.0 - z * z));
+    const float phi = ray_idx * golden_angle;
+    const float3 dir = float3(cos(phi) * r, sin(phi) * r, z);
+
+    uint rng = hash1(frame_index);
+    const float3 axis = uniform_sample_sphere(float2(
+        uint_to_u01_float(hash1_mut(rng)),
+        uint_to_u01_float(hash1_mut(rng))
+    ));
+    const float angle = uint_to_u01_float(hash1_mut(rng)) * M_TAU;
+
+    // Rodrigues' rotation formula
+    const float c = cos(angle);
+    const float s = sin(angle);
+    return dir * c + cross(axis, dir) * s + axis * dot(axis, dir) * (1.0 - c);
+}
+
+// Texel in an atlas of octahedral maps with `probe_res` interior texels, and a one-texel border.
+float2 ddgi_atlas_uv(uint2 tile, float3 dir, uint probe_res, float2 atlas_size) {
+    const float2 px = tile * (probe_res + 2) + 1 + octa_encode(dir) * probe_res;
+    return px / atlas_size;
+}
+
+#endif  // DDGI_COMMON_HLSL
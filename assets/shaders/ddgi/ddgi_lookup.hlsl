@@ -0,0 +1,102 @@
+#ifndef DDGI_LOOKUP_HLSL
+#define DDGI_LOOKUP_HLSL
+
+#include "../inc/samplers.hlsl"
+#include "ddgi_common.hlsl"
+
+// Expects the following to be declared by the includer:
+// * `DdgiConstants ddgi`
+// * `Texture2D<float4> ddgi_irradiance_atlas_tex`
+// * `Texture2D<float2> ddgi_depth_atlas_tex`
+// * `Texture2D<float4> ddgi_probe_offset_tex`
+
+// Moves the lookup point off the surface, towards the viewer,
+// to avoid self-shadowing from the depth maps.
+static const float DDGI_LOOKUP_BIAS = 0.3;
+
+float3 ddgi_probe_offset(int3 world_coord) {
+    if (ddgi.is_probe_new(world_coord)) {
+        return 0.0.xxx;
+    }
+
+    const uint3 storage_coord = ddgi.world_coord_to_storage_coord(world_coord);
+    return ddgi_probe_offset_tex[ddgi.storage_coord_to_atlas_tile(storage_coord)].xyz;
+}
+
+// Irradiance at `pos_ws`, interpolated between the eight surrounding probes,
+// and weighted by their visibility of the point.
+//
+// Returns the cosine-weighted average of incident radiance, as with the other diffuse GI.
+float3 ddgi_lookup_irradiance(float3 pos_ws, float3 normal_ws, float3 dir_to_viewer_ws) {
+    float2 irradiance_atlas_size;
+    ddgi_irradiance_atlas_tex.GetDimensions(irradiance_atlas_size.x, irradiance_atlas_size.y);
+    float2 depth_atlas_size;
+    ddgi_depth_atlas_tex.GetDimensions(depth_atlas_size.x, depth_atlas_size.y);
+
+    const float3 bias_dir = normal_ws * 0.2 + dir_to_viewer_ws * 0.8;
+    const float3 biased_pos_ws = pos_ws + bias_dir * (DDGI_LOOKUP_BIAS * ddgi.probe_spacing);
+
+    const float3 grid_pos = biased_pos_ws / ddgi.probe_spacing;
+    const int3 base_coord = clamp(
+        int3(floor(grid_pos)),
+        ddgi.grid_origin,
+        ddgi.grid_origin + int3(ddgi.probe_counts) - 2
+    );
+    const float3 alpha = saturate(grid_pos - base_coord);
+
+    float3 irradiance_sum = 0.0;
+    float weight_sum = 0.0;
+
+    for (uint i = 0; i < 8; ++i) {
+        const int3 offset = int3(i, i >> 1, i >> 2) & 1;
+        const int3 world_coord = base_coord + offset;
+        const uint2 tile = ddgi.storage_coord_to_atlas_tile(ddgi.world_coord_to_storage_coord(world_coord));
+        const float3 probe_pos_ws = ddgi.probe_position(world_coord, ddgi_probe_offset(world_coord));
+
+        const float3 trilinear = lerp(1.0 - alpha, alpha, float3(offset));
+        float weight = 1.0;
+
+        // Smooth backface test
+        const float3 dir_to_probe = normalize(probe_pos_ws - pos_ws);
+        weight *= square(max(0.0001, (dot(dir_to_probe, normal_ws) + 1.0) * 0.5)) + 0.2;
+
+        // Chebyshev visibility test
+        {
+            const float3 probe_to_point = biased_pos_ws - probe_pos_ws;
+            const float dist_to_probe = length(probe_to_point);
+            const float2 moments = ddgi_depth_atlas_tex.SampleLevel(
+                sampler_llc,
+                ddgi_atlas_uv(tile, probe_to_point / max(1e-5, dist_to_probe), DDGI_DEPTH_PROBE_RES, depth_atlas_size),
+                0
+            );
+
+            if (dist_to_probe > moments.x) {
+                const float variance = abs(square(moments.x) - moments.y);
+                const float chebyshev = variance / (variance + square(dist_to_probe - moments.x));
+                weight *= max(0.05, chebyshev * chebyshev * chebyshev);
+            }
+        }
+
+        // Crush tiny weights, which would otherwise produce light leaks.
+        weight = max(1e-6, weight);
+        const float crush_threshold = 0.2;
+        if (weight < crush_threshold) {
+            weight *= square(weight) / square(crush_threshold);
+        }
+
+        weight *= trilinear.x * trilinear.y * trilinear.z;
+
+        const float3 irradiance = ddgi_irradiance_atlas_tex.SampleLevel(
+            sampler_llc,
+            ddgi_atlas_uv(tile, normal_ws, DDGI_IRRADIANCE_PROBE_RES, irradiance_atlas_size),
+            0
+        ).rgb;
+
+        irradiance_sum += irradiance * weight;
+        weight_sum += weight;
+    }
+
+    return irradiance_sum / max(1e-5, weight_sum);
+}
+
+#endif  // DDGI_LOOKUP_HLSL
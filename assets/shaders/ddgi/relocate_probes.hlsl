@@ -0,0 +1,69 @@
+#include "../inc/frame_constants.hlsl"
+#include "ddgi_common.hlsl"
+
+// Moves probes out of geometry, and away from surfaces which are too close to them,
+// based on the rays they just traced.
+
+[[vk::binding(0)]] Texture2D<float4> ray_tex;
+[[vk::binding(1)]] RWTexture2D<float4> probe_offset_tex;
+[[vk::binding(2)]] cbuffer _ {
+    DdgiConstants ddgi;
+};
+
+// Probes which see more backfaces than this fraction of their rays are considered inside geometry.
+static const float BACKFACE_FRACTION_THRESHOLD = 0.25;
+
+// Probes try to stay at least this far from surfaces, relative to the probe spacing.
+static const float MIN_FRONTFACE_DISTANCE = 0.2;
+
+// Must match `trace.rgen.hlsl`
+static const float BACKFACE_DISTANCE_SCALE = 0.2;
+
+[numthreads(64, 1, 1)]
+void main(uint probe_idx: SV_DispatchThreadID) {
+    if (probe_idx >= ddgi.probe_count()) {
+        return;
+    }
+
+    const uint3 storage_coord = ddgi.probe_idx_to_storage_coord(probe_idx);
+    const int3 world_coord = ddgi.storage_coord_to_world_coord(storage_coord);
+    const uint2 tile = ddgi.storage_coord_to_atlas_tile(storage_coord);
+
+    // In units of the probe spacing
+    float3 offset = ddgi.is_probe_new(world_coord) ? 0.0.xxx : probe_offset_tex[tile].xyz;
+
+    uint backface_count = 0;
+    float closest_backface_dist = FLT_MAX;
+    float3 closest_backface_dir = 0.0;
+    float closest_frontface_dist = FLT_MAX;
+    float3 closest_frontface_dir = 0.0;
+
+    for (uint ray_idx = 0; ray_idx < ddgi.rays_per_probe; ++ray_idx) {
+        const float hit_t = ray_tex[uint2(ray_idx, probe_idx)].a;
+        const float3 dir = ddgi_ray_direction(ray_idx, ddgi.rays_per_probe, frame_constants.frame_index);
+
+        if (hit_t < 0.0) {
+            backface_count += 1;
+
+            const float dist = -hit_t / BACKFACE_DISTANCE_SCALE;
+            if (dist < closest_backface_dist) {
+                closest_backface_dist = dist;
+                closest_backface_dir = dir;
+            }
+        } else if (hit_t < closest_frontface_dist) {
+            closest_frontface_dist = hit_t;
+            closest_frontface_dir = dir;
+        }
+    }
+
+    const float min_frontface_dist = MIN_FRONTFACE_DISTANCE * ddgi.probe_spacing;
+
+    if (backface_count > ddgi.rays_per_probe * BACKFACE_FRACTION_THRESHOLD) {
+        // Push the probe through the closest backface, which is likely the way out.
+        offset += closest_backface_dir * (closest_backface_dist + min_frontface_dist * 0.5) / ddgi.probe_spacing;
+    } else if (closest_frontface_dist < min_frontface_dist) {
+        offset -= closest_frontface_dir * (min_frontface_dist - closest_frontface_dist) / ddgi.probe_spacing;
+    }
+
+    probe_offset_tex[tile] = float4(clamp(offset, -DDGI_MAX_PROBE_OFFSET, DDGI_MAX_PROBE_OFFSET), 1.0);
+}
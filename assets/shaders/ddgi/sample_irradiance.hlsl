@@ -0,0 +1,39 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "ddgi_common.hlsl"
+
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> ddgi_irradiance_atlas_tex;
+[[vk::binding(3)]] Texture2D<float2> ddgi_depth_atlas_tex;
+[[vk::binding(4)]] Texture2D<float4> ddgi_probe_offset_tex;
+[[vk::binding(5)]] RWTexture2D<float4> output_tex;
+[[vk::binding(6)]] cbuffer _ {
+    float4 output_tex_size;
+    DdgiConstants ddgi;
+};
+
+#include "ddgi_lookup.hlsl"
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float depth = depth_tex[px];
+
+    if (0.0 == depth) {
+        output_tex[px] = 0.0;
+        return;
+    }
+
+    const float2 uv = get_uv(px, output_tex_size);
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 normal_ws = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack_normal();
+
+    const float3 irradiance = ddgi_lookup_irradiance(
+        view_ray_context.ray_hit_ws(),
+        normal_ws,
+        -view_ray_context.ray_dir_ws()
+    );
+
+    output_tex[px] = float4(irradiance, 1.0);
+}
@@ -0,0 +1,108 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/pack_unpack.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/atmosphere.hlsl"
+#include "../inc/sun.hlsl"
+#include "ddgi_common.hlsl"
+
+// Traces `rays_per_probe` rays from every probe, and shades the hit points with the sun,
+// emissive surfaces, and the previous frame's probes for multiple bounces.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(1)]] Texture2D<float4> ddgi_irradiance_atlas_tex;
+[[vk::binding(2)]] Texture2D<float2> ddgi_depth_atlas_tex;
+[[vk::binding(3)]] Texture2D<float4> ddgi_probe_offset_tex;
+[[vk::binding(4)]] RWTexture2D<float4> ray_out_tex;
+[[vk::binding(5)]] cbuffer _ {
+    DdgiConstants ddgi;
+};
+
+#include "ddgi_lookup.hlsl"
+
+// Backface hits are stored with their distance scaled by this, and negated.
+// See `relocate_probes.hlsl` and `update_depth.hlsl`.
+static const float BACKFACE_DISTANCE_SCALE = 0.2;
+
+[shader("raygeneration")]
+void main() {
+    const uint ray_idx = DispatchRaysIndex().x;
+    const uint probe_idx = DispatchRaysIndex().y;
+
+    const uint3 storage_coord = ddgi.probe_idx_to_storage_coord(probe_idx);
+    const int3 world_coord = ddgi.storage_coord_to_world_coord(storage_coord);
+
+    RayDesc outgoing_ray = new_ray(
+        ddgi.probe_position(world_coord, ddgi_probe_offset(world_coord)),
+        ddgi_ray_direction(ray_idx, ddgi.rays_per_probe, frame_constants.frame_index),
+        0.0,
+        FLT_MAX
+    );
+
+    const GbufferPathVertex primary_hit = GbufferRaytrace::with_ray(outgoing_ray)
+        .with_instance_mask(RT_INSTANCE_MASK_GI)
+        .with_cone(RayCone::from_spread_angle(0.1))
+        .with_cull_back_faces(false)
+        .with_path_length(1)
+        .trace(acceleration_structure);
+
+    if (!primary_hit.is_hit) {
+        ray_out_tex[uint2(ray_idx, probe_idx)] = float4(
+            sky_cube_tex.SampleLevel(sampler_llr, outgoing_ray.Direction, 0).rgb,
+            ddgi.max_probe_distance()
+        );
+        return;
+    }
+
+    GbufferData gbuffer = primary_hit.gbuffer_packed.unpack();
+
+    if (dot(gbuffer.normal, outgoing_ray.Direction) > 0.0) {
+        // Inside geometry, or looking at a single-sided surface from behind.
+        // Don't let any light in, and flag the hit for probe relocation.
+        ray_out_tex[uint2(ray_idx, probe_idx)] = float4(0.0.xxx, -primary_hit.ray_t * BACKFACE_DISTANCE_SCALE);
+        return;
+    }
+
+    gbuffer.roughness = lerp(gbuffer.roughness, 1.0, 0.5);
+    const float3x3 tangent_to_world = build_orthonormal_basis(gbuffer.normal);
+    const float3 wo = mul(-outgoing_ray.Direction, tangent_to_world);
+    const LayeredBrdf brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
+
+    float3 radiance = gbuffer.emissive;
+
+    {
+        const float3 to_light_norm = SUN_DIRECTION;
+        const float3 wi = mul(to_light_norm, tangent_to_world);
+
+        if (wi.z > 0.0) {
+            const bool is_shadowed = rt_is_shadowed(
+                acceleration_structure,
+                new_ray(
+                    primary_hit.position,
+                    to_light_norm,
+                    1e-4,
+                    FLT_MAX
+            ));
+
+            if (!is_shadowed) {
+                radiance += brdf.evaluate(wo, wi) * wi.z * SUN_COLOR;
+            }
+        }
+    }
+
+    if (ddgi.history_valid) {
+        const float3 irradiance = ddgi_lookup_irradiance(
+            primary_hit.position,
+            gbuffer.normal,
+            -outgoing_ray.Direction
+        ) * frame_constants.pre_exposure_delta;
+
+        radiance += irradiance * gbuffer.albedo;
+    }
+
+    ray_out_tex[uint2(ray_idx, probe_idx)] = float4(radiance, primary_hit.ray_t);
+}
@@ -0,0 +1,58 @@
+#include "../inc/frame_constants.hlsl"
+#include "ddgi_common.hlsl"
+
+// Blends the hit distances of the rays just traced into the probes' octahedral maps
+// of the mean distance and mean squared distance, used for Chebyshev visibility tests.
+
+[[vk::binding(0)]] Texture2D<float4> ray_tex;
+[[vk::binding(1)]] RWTexture2D<float2> depth_atlas_tex;
+[[vk::binding(2)]] cbuffer _ {
+    DdgiConstants ddgi;
+};
+
+// Must match `trace.rgen.hlsl`
+static const float BACKFACE_DISTANCE_SCALE = 0.2;
+
+// Concentrates the weights around each texel's direction, keeping the depth maps sharp.
+static const float DEPTH_SHARPNESS = 50.0;
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const uint2 tile = px / DDGI_DEPTH_PROBE_RES;
+    const uint2 probe_px = px % DDGI_DEPTH_PROBE_RES;
+    const uint3 storage_coord = ddgi.atlas_tile_to_storage_coord(tile);
+
+    if (storage_coord.z >= ddgi.probe_counts.z) {
+        return;
+    }
+
+    const uint probe_idx = ddgi.storage_coord_to_probe_idx(storage_coord);
+    const int3 world_coord = ddgi.storage_coord_to_world_coord(storage_coord);
+    const float3 texel_dir = octa_decode((probe_px + 0.5) / DDGI_DEPTH_PROBE_RES);
+    const float max_dist = ddgi.max_probe_distance();
+
+    float2 moments_sum = 0.0;
+    float weight_sum = 0.0;
+
+    for (uint ray_idx = 0; ray_idx < ddgi.rays_per_probe; ++ray_idx) {
+        const float hit_t = ray_tex[uint2(ray_idx, probe_idx)].a;
+        const float dist = min(max_dist, hit_t < 0.0 ? -hit_t / BACKFACE_DISTANCE_SCALE : hit_t);
+
+        const float3 dir = ddgi_ray_direction(ray_idx, ddgi.rays_per_probe, frame_constants.frame_index);
+        const float weight = pow(max(0.0, dot(texel_dir, dir)), DEPTH_SHARPNESS);
+
+        moments_sum += float2(dist, dist * dist) * weight;
+        weight_sum += weight;
+    }
+
+    const float2 moments = weight_sum > 1e-5
+        ? moments_sum / weight_sum
+        : float2(max_dist, max_dist * max_dist);
+    const uint2 atlas_px = tile * (DDGI_DEPTH_PROBE_RES + 2) + 1 + probe_px;
+
+    if (ddgi.is_probe_new(world_coord)) {
+        depth_atlas_tex[atlas_px] = moments;
+    } else {
+        depth_atlas_tex[atlas_px] = lerp(moments, depth_atlas_tex[atlas_px], ddgi.hysteresis);
+    }
+}
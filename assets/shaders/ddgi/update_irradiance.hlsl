@@ -0,0 +1,53 @@
+#include "../inc/frame_constants.hlsl"
+#include "ddgi_common.hlsl"
+
+// Blends the radiance of the rays just traced into the probes' octahedral irradiance maps.
+
+[[vk::binding(0)]] Texture2D<float4> ray_tex;
+[[vk::binding(1)]] RWTexture2D<float4> irradiance_atlas_tex;
+[[vk::binding(2)]] cbuffer _ {
+    DdgiConstants ddgi;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const uint2 tile = px / DDGI_IRRADIANCE_PROBE_RES;
+    const uint2 probe_px = px % DDGI_IRRADIANCE_PROBE_RES;
+    const uint3 storage_coord = ddgi.atlas_tile_to_storage_coord(tile);
+
+    if (storage_coord.z >= ddgi.probe_counts.z) {
+        return;
+    }
+
+    const uint probe_idx = ddgi.storage_coord_to_probe_idx(storage_coord);
+    const int3 world_coord = ddgi.storage_coord_to_world_coord(storage_coord);
+    const float3 texel_dir = octa_decode((probe_px + 0.5) / DDGI_IRRADIANCE_PROBE_RES);
+
+    float3 irradiance_sum = 0.0;
+    float weight_sum = 0.0;
+
+    for (uint ray_idx = 0; ray_idx < ddgi.rays_per_probe; ++ray_idx) {
+        const float4 ray = ray_tex[uint2(ray_idx, probe_idx)];
+
+        // Backfaces don't contribute; they only drive relocation.
+        if (ray.a < 0.0) {
+            continue;
+        }
+
+        const float3 dir = ddgi_ray_direction(ray_idx, ddgi.rays_per_probe, frame_constants.frame_index);
+        const float weight = max(0.0, dot(texel_dir, dir));
+
+        irradiance_sum += ray.rgb * weight;
+        weight_sum += weight;
+    }
+
+    const float3 irradiance = irradiance_sum / max(1e-5, weight_sum);
+    const uint2 atlas_px = tile * (DDGI_IRRADIANCE_PROBE_RES + 2) + 1 + probe_px;
+
+    if (ddgi.is_probe_new(world_coord)) {
+        irradiance_atlas_tex[atlas_px] = float4(irradiance, 1.0);
+    } else {
+        const float3 prev_irradiance = irradiance_atlas_tex[atlas_px].rgb * frame_constants.pre_exposure_delta;
+        irradiance_atlas_tex[atlas_px] = float4(lerp(irradiance, prev_irradiance, ddgi.hysteresis), 1.0);
+    }
+}
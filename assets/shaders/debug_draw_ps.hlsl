@@ -0,0 +1,3 @@
+float4 main([[vk::location(0)]] float4 color: TEXCOORD0): SV_TARGET0 {
+    return float4(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,26 @@
+#include "inc/frame_constants.hlsl"
+
+// Must match `DebugVertex` in `debug_draw.rs`
+struct DebugVertex {
+    float4 position;
+    float4 color;
+};
+
+[[vk::binding(0)]] StructuredBuffer<DebugVertex> vertices_dyn;
+
+struct VsOut {
+	float4 position: SV_Position;
+    [[vk::location(0)]] float4 color: TEXCOORD0;
+};
+
+VsOut main(uint vid: SV_VertexID) {
+    const DebugVertex v = vertices_dyn[vid];
+
+    // Drawn over the final image, so without the sub-pixel jitter.
+    const float4 vs_pos = mul(frame_constants.view_constants.world_to_view, float4(v.position.xyz, 1.0));
+
+    VsOut vsout;
+    vsout.position = mul(frame_constants.view_constants.view_to_clip, vs_pos);
+    vsout.color = v.color;
+    return vsout;
+}
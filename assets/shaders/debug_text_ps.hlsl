@@ -0,0 +1,31 @@
+// All the glyphs in a row; see `DebugTextRenderer::new`.
+[[vk::binding(1)]] Texture2D<float> font_atlas;
+
+static const int2 GLYPH_SIZE = int2(5, 7);
+
+bool is_glyph_texel_set(uint glyph, int2 texel) {
+    if (any(texel < 0) || any(texel >= GLYPH_SIZE)) {
+        return false;
+    }
+
+    return font_atlas[uint2(glyph * GLYPH_SIZE.x + texel.x, texel.y)] > 0.5;
+}
+
+float4 main(
+    [[vk::location(0)]] float2 texel_pos: TEXCOORD0,
+    [[vk::location(1)]] nointerpolation uint glyph: TEXCOORD1
+): SV_TARGET0 {
+    const int2 texel = int2(floor(texel_pos));
+
+    if (is_glyph_texel_set(glyph, texel)) {
+        return 1.0.xxxx;
+    }
+
+    // Drop shadow, to stay readable over bright backgrounds.
+    if (is_glyph_texel_set(glyph, texel - 1)) {
+        return float4(0.0, 0.0, 0.0, 0.8);
+    }
+
+    discard;
+    return 0.0.xxxx;
+}
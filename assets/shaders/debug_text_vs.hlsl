@@ -0,0 +1,44 @@
+// Must match `GlyphInstance` in `debug_text.rs`
+struct GlyphInstance {
+    float2 position;
+    uint glyph;
+    uint pad;
+};
+
+[[vk::binding(0)]] StructuredBuffer<GlyphInstance> glyphs_dyn;
+
+// Must match `DebugTextConstants` in `debug_text.rs`
+[[vk::push_constant]]
+struct {
+    float2 inv_output_size;
+    float glyph_scale;
+    uint pad;
+} push_constants;
+
+// The glyph, plus a texel on the right and bottom for its shadow.
+static const float2 CELL_SIZE = float2(6, 8);
+
+struct VsOut {
+	float4 position: SV_Position;
+    [[vk::location(0)]] float2 texel: TEXCOORD0;
+    [[vk::location(1)]] nointerpolation uint glyph: TEXCOORD1;
+};
+
+VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
+    const GlyphInstance glyph = glyphs_dyn[instance_index];
+
+    // Two triangles per quad.
+    const float2 corner = float2(
+        (vid == 1 || vid == 2 || vid == 4) ? 1.0 : 0.0,
+        (vid == 2 || vid == 4 || vid == 5) ? 1.0 : 0.0
+    );
+
+    const float2 texel = corner * CELL_SIZE;
+    const float2 px = glyph.position + texel * push_constants.glyph_scale;
+
+    VsOut vsout;
+    vsout.position = float4(px * push_constants.inv_output_size * 2.0 - 1.0, 0.0, 1.0);
+    vsout.texel = texel;
+    vsout.glyph = glyph.glyph;
+    return vsout;
+}
@@ -0,0 +1,129 @@
+#include "inc/samplers.hlsl"
+#include "inc/gbuffer.hlsl"
+#include "inc/color/srgb.hlsl"
+#include "inc/frame_constants.hlsl"
+
+// Must match `DebugView::shader_mode` in `debug_view.rs`
+#define DEBUG_VIEW_ALBEDO 0
+#define DEBUG_VIEW_NORMALS 1
+#define DEBUG_VIEW_ROUGHNESS_METALNESS 2
+#define DEBUG_VIEW_MOTION_VECTORS 3
+#define DEBUG_VIEW_RADIANCE 4
+#define DEBUG_VIEW_SCALAR 5
+#define DEBUG_VIEW_OVERDRAW 6
+#define DEBUG_VIEW_LUMINANCE 7
+#define DEBUG_VIEW_GI_VARIANCE 8
+#define DEBUG_VIEW_GI_HISTORY_LENGTH 9
+
+// Must match the `max_sample_count` of `rtdgi/temporal_filter.hlsl`
+static const float GI_MAX_HISTORY_LENGTH = 32.0;
+
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> input_tex;
+[[vk::binding(3)]] RWTexture2D<float4> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+    uint mode;
+    float nits_per_unit;
+    float min_ev;
+    float max_ev;
+};
+
+// The output is linear, and gets encoded by the final blit. Values meant to be
+// shown as-is (such as normals) are decoded first, so they survive the round trip.
+float3 as_display_value(float3 v) {
+    return sRGB_OETF(saturate(v));
+}
+
+float3 overdraw_heatmap(float count) {
+    // One layer is blue; green, yellow, and red are reached at 4, 7, and 10 layers.
+    static const float3 STOPS[5] = {
+        float3(0, 0, 0),
+        float3(0, 0, 1),
+        float3(0, 1, 0),
+        float3(1, 1, 0),
+        float3(1, 0, 0),
+    };
+
+    const float t = clamp(count <= 1.0 ? count : 1.0 + (count - 1.0) / 3.0, 0.0, 4.0);
+    const uint idx = min(uint(t), 3);
+    return lerp(STOPS[idx], STOPS[idx + 1], t - idx);
+}
+
+// Blue, cyan, green, yellow, and red over `t` from 0 to 1; black below, and white above.
+float3 false_color(float t) {
+    static const float3 STOPS[5] = {
+        float3(0, 0, 1),
+        float3(0, 1, 1),
+        float3(0, 1, 0),
+        float3(1, 1, 0),
+        float3(1, 0, 0),
+    };
+
+    if (t < 0.0) {
+        return 0.0;
+    } else if (t > 1.0) {
+        return 1.0;
+    }
+
+    const float s = t * 4.0;
+    const uint idx = min(uint(s), 3);
+    return lerp(STOPS[idx], STOPS[idx + 1], s - idx);
+}
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+    const float depth = depth_tex[px];
+
+    float3 result = 0.0;
+
+    if (mode == DEBUG_VIEW_MOTION_VECTORS) {
+        // The reprojection map holds the UV offset to the previous frame
+        const float2 motion_px = input_tex[px].xy * output_tex_size.xy;
+        result = as_display_value(float3(0.5 + 0.5 * clamp(motion_px / 16.0, -1.0, 1.0), 0.5));
+    } else if (mode == DEBUG_VIEW_OVERDRAW) {
+        result = as_display_value(overdraw_heatmap(input_tex[px].r));
+    } else if (mode == DEBUG_VIEW_LUMINANCE) {
+        // The lit scene is pre-exposed; undone here, so that the colors stay put as exposure adapts.
+        const float nits = sRGB_to_luminance(input_tex[px].rgb) / frame_constants.pre_exposure * nits_per_unit;
+        const float ev100 = log2(max(1e-10, nits * 8.0));
+        result = as_display_value(false_color((ev100 - min_ev) / (max_ev - min_ev)));
+    } else if (depth != 0.0) {
+        const GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+        const float4 input = input_tex.SampleLevel(sampler_lnc, uv, 0);
+
+        if (mode == DEBUG_VIEW_ALBEDO) {
+            result = gbuffer.albedo;
+        } else if (mode == DEBUG_VIEW_NORMALS) {
+            result = as_display_value(gbuffer.normal * 0.5 + 0.5);
+        } else if (mode == DEBUG_VIEW_ROUGHNESS_METALNESS) {
+            result = as_display_value(float3(
+                roughness_to_perceptual_roughness(gbuffer.roughness),
+                gbuffer.metalness,
+                0.0
+            ));
+        } else if (mode == DEBUG_VIEW_RADIANCE) {
+            result = input.rgb / (1.0 + sRGB_to_luminance(input.rgb));
+        } else if (mode == DEBUG_VIEW_SCALAR) {
+            result = as_display_value(input.xxx);
+        } else if (mode == DEBUG_VIEW_GI_VARIANCE) {
+            // Moments of the square root of luminance, whose relative deviation doesn't change
+            // with exposure. Up to a deviation of the mean itself, in red.
+            const float2 moments = input.xy;
+            if (moments.x > 0.0) {
+                const float dev = sqrt(max(0.0, moments.y - moments.x * moments.x));
+                result = as_display_value(false_color(saturate(dev / moments.x)));
+            }
+        } else if (mode == DEBUG_VIEW_GI_HISTORY_LENGTH) {
+            // Black where there's no history at all, such as without ReSTIR GI.
+            const float sample_count = input.a;
+            if (sample_count > 0.0) {
+                result = as_display_value(false_color(saturate(1.0 - sample_count / GI_MAX_HISTORY_LENGTH)));
+            }
+        }
+    }
+
+    output_tex[px] = float4(result, 1);
+}
@@ -1,30 +1,57 @@
 #include "../inc/frame_constants.hlsl"
 #include "../inc/samplers.hlsl"
+#include "dof_common.hlsl"
+
+// Signed circle of confusion radius, in output pixels: negative in front of the focal plane,
+// and positive behind it. Also reduces the max absolute radius per 8x8 tile.
 
 [[vk::binding(0)]] Texture2D<float> depth_tex;
 [[vk::binding(1)]] RWTexture2D<float> output_tex;
 [[vk::binding(2)]] RWTexture2D<float> tile_output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float4 input_tex_size;
+    float4 output_tex_size;
+    DofConstants dof;
+};
 
 groupshared uint max_abs_coc_asuint;
 
-float coc_size(float depth, float focal_point, float focus_scale) {
-    //return 0;
-	float coc = clamp((1.0 / focal_point - 1.0 / depth) * focus_scale, -0.3, 0.3);
-	return coc * 20.0;
+float focus_distance() {
+    if (dof.auto_focus) {
+        const float center_depth = depth_tex.SampleLevel(sampler_nnc, 0.5, 0);
+        if (center_depth != 0.0) {
+            return -depth_to_view_z(center_depth);
+        }
+    }
+
+    return dof.focus_distance;
 }
 
 [numthreads(8, 8, 1)]
 void main(uint2 px: SV_DispatchThreadID, uint idx_within_group: SV_GroupIndex) {
-    max_abs_coc_asuint = asuint(0.0);
+    if (0 == idx_within_group) {
+        max_abs_coc_asuint = asuint(0.0);
+    }
     GroupMemoryBarrierWithGroupSync();
 
-    float linear_depth = -depth_to_view_z(depth_tex[px]);
-    float max_coc = 20.0;
+    float coc = 0.0;
 
-    float focus = -depth_to_view_z(depth_tex.SampleLevel(sampler_nnc, 0.5, 0));
-    
-    //float coc = clamp((linear_depth - 1) * 20.0, -max_coc, max_coc);
-    float coc = coc_size(linear_depth, focus, 0.7);
+    if (all(px < uint2(input_tex_size.xy))) {
+        // Thin lens, with the focal length following from the field of view and the sensor size.
+        const float focal_length = 0.5 * dof.sensor_height * frame_constants.view_constants.view_to_clip[1][1];
+        const float focus = max(focus_distance(), focal_length * 1.01);
+        const float aperture_diameter = focal_length / dof.f_stop;
+
+        // (D - S) / D, which approaches one for the sky
+        const float depth = depth_tex[px];
+        const float defocus = depth != 0.0 ? 1.0 - focus / -depth_to_view_z(depth) : 1.0;
+
+        const float coc_diameter_on_sensor = aperture_diameter * focal_length / (focus - focal_length) * defocus;
+        coc = 0.5 * coc_diameter_on_sensor / dof.sensor_height * output_tex_size.y;
+        coc = clamp(coc, -dof.max_coc_radius_px, dof.max_coc_radius_px);
+
+        output_tex[px] = coc;
+    }
 
     InterlockedMax(max_abs_coc_asuint, asuint(abs(coc)));
     GroupMemoryBarrierWithGroupSync();
@@ -32,6 +59,4 @@ void main(uint2 px: SV_DispatchThreadID, uint idx_within_group: SV_GroupIndex) {
     if (0 == idx_within_group) {
         tile_output_tex[px / 8] = asfloat(max_abs_coc_asuint);
     }
-
-    output_tex[px] = coc;
 }
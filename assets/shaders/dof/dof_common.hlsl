@@ -0,0 +1,13 @@
+#ifndef DOF_COMMON_HLSL
+#define DOF_COMMON_HLSL
+
+// Must match `GpuDofConstants` in `dof.rs`
+struct DofConstants {
+    float f_stop;
+    float focus_distance;
+    float sensor_height;
+    float max_coc_radius_px;
+    uint auto_focus;
+};
+
+#endif  // DOF_COMMON_HLSL
@@ -1,83 +1,101 @@
-#include "../inc/frame_constants.hlsl"
+#include "../inc/math_const.hlsl"
 #include "../inc/samplers.hlsl"
+#include "dof_common.hlsl"
 
-[[vk::binding(0)]] Texture2D<float> depth_tex;
-[[vk::binding(1)]] Texture2D<float3> color_tex;
-[[vk::binding(2)]] Texture2D<float> coc_tex;
-[[vk::binding(3)]] Texture2D<float> coc_tiles_tex;
-[[vk::binding(4)]] RWTexture2D<float3> output_tex;
-[[vk::binding(5)]] cbuffer _ {
+// Scatter-as-gather bokeh, sampling a golden-angle spiral out to the largest circle of confusion nearby.
+//
+// The background and in-focus field is a running average of samples whose circles of confusion
+// reach this pixel, with background ones not allowed to bleed over anything in front of them.
+// The near field is gathered separately, along with its coverage, and composited on top,
+// so that out-of-focus foreground objects blur over whatever's behind them.
+
+[[vk::binding(0)]] Texture2D<float3> color_tex;
+[[vk::binding(1)]] Texture2D<float> coc_tex;
+[[vk::binding(2)]] Texture2D<float> coc_tiles_tex;
+[[vk::binding(3)]] RWTexture2D<float3> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 coc_tex_size;
     float4 output_tex_size;
+    DofConstants dof;
 };
 
-static const float GOLDEN_ANGLE = 2.39996323; 
-static const float MAX_BLUR_SIZE = 20.0; 
-static const float RAD_SCALE = 0.4; // Smaller = nicer blur, larger = faster
+// Smaller = nicer blur, larger = faster. Each sample of the spiral covers `2 * PI * RAD_SCALE` pixels.
+static const float RAD_SCALE = 0.5;
+
+static const uint COC_TILE_SIZE = 8;
 
 [numthreads(8, 8, 1)]
 void main(uint2 px: SV_DispatchThreadID) {
-    float2 uv = float2(px + 0.5) * output_tex_size.zw;
-    float3 color = color_tex[px];
-
-	float center_depth = depth_tex[px];
-	float center_size = abs(coc_tex[px]);
-	float tot = 1.0;
-	float radius = RAD_SCALE;
-
-    //float max_blur_size = MAX_BLUR_SIZE;
-    float max_blur_size = 0;
-    int k = 2;
-    for (int y = -k; y <= k; ++y) {
-        for (int x = -k; x <= k; ++x) {
-            max_blur_size = max(max_blur_size, coc_tiles_tex[px / 8 + int2(x, y)]);
+    const float2 uv = float2(px + 0.5) * output_tex_size.zw;
+    const float3 center_color = color_tex[px];
+    const float center_coc = coc_tex.SampleLevel(sampler_nnc, uv, 0);
+
+    // Find the largest circle of confusion which could reach this pixel.
+    float max_radius = 0.0;
+    {
+        const int2 center_tile = int2(uv * coc_tex_size.xy) / COC_TILE_SIZE;
+        const int2 tile_count = (int2(coc_tex_size.xy) + COC_TILE_SIZE - 1) / COC_TILE_SIZE;
+        const int k = int(ceil(dof.max_coc_radius_px * coc_tex_size.y * output_tex_size.w / COC_TILE_SIZE));
+
+        for (int y = -k; y <= k; ++y) {
+            for (int x = -k; x <= k; ++x) {
+                const int2 tile = clamp(center_tile + int2(x, y), 0, tile_count - 1);
+                max_radius = max(max_radius, coc_tiles_tex[tile]);
+            }
         }
     }
-    
-#if 1
-	for (float ang = 0.0; radius < max_blur_size; ang += GOLDEN_ANGLE) {
-		float2 tc = uv + float2(cos(ang), sin(ang)) * output_tex_size.zw * radius;
-		float3 sampleColor = color_tex.SampleLevel(sampler_lnc, tc, 0);
-		float sampleDepth = depth_tex.SampleLevel(sampler_lnc, tc, 0);
-		float sampleSize = abs(coc_tex.SampleLevel(sampler_lnc, tc, 0));
-
-		if (sampleDepth < center_depth) {
-			sampleSize = clamp(sampleSize, 0.0, center_size*2.0);
+
+    if (max_radius < RAD_SCALE) {
+        output_tex[px] = center_color;
+        return;
+    }
+
+    float3 far_color = center_color;
+    float far_count = 1.0;
+
+    float3 near_color = 0.0;
+    float near_weight = 0.0;
+    float near_coverage = 0.0;
+
+    // Coverage of a single spiral sample, relative to a circle of confusion of unit radius
+    const float sample_area = 2.0 * RAD_SCALE;
+
+    if (center_coc < 0.0) {
+        const float w = sample_area / max(1.0, center_coc * center_coc);
+        near_color += center_color * w;
+        near_weight += w;
+        near_coverage += w;
+    }
+
+    float radius = RAD_SCALE;
+    for (float ang = 0.0; radius < max_radius; ang += GOLDEN_ANGLE) {
+        const float2 sample_uv = uv + float2(cos(ang), sin(ang)) * output_tex_size.zw * radius;
+        const float3 sample_color = color_tex.SampleLevel(sampler_lnc, sample_uv, 0);
+        const float sample_coc = coc_tex.SampleLevel(sampler_nnc, sample_uv, 0);
+
+        if (sample_coc < 0.0) {
+            const float m = smoothstep(radius - 0.5, radius + 0.5, -sample_coc);
+            const float w = m * sample_area / max(1.0, sample_coc * sample_coc);
+            near_color += sample_color * w;
+            near_weight += w;
+            near_coverage += w;
         }
 
-		float m = smoothstep(radius-0.5, radius+0.5, sampleSize);
-		color += lerp(color/tot, sampleColor, m);
-		tot += 1.0;
-        radius += RAD_SCALE/radius;
-	}
-#else
-    static const int sample_count = int(max_blur_size) * 6;
-
-    float ang = 0.0;
-	for (float i = 0; i < sample_count; ++i) {
-        float r = (i + 0.5) / sample_count;
-        r = sqrt(r);
-        r *= max_blur_size;
-
-		float2 tc = uv + float2(cos(ang), sin(ang)) * output_tex_size.zw * r;
-		float3 sampleColor = color_tex.SampleLevel(sampler_lnc, tc, 0);
-		float sampleDepth = depth_tex.SampleLevel(sampler_lnc, tc, 0);
-		float sampleSize = abs(coc_tex.SampleLevel(sampler_lnc, tc, 0));
-
-		if (sampleDepth < center_depth) {
-			sampleSize = clamp(sampleSize, 0.0, center_size*2.0);
+        // Don't let the background bleed over what's in front of it.
+        float far_size = max(0.0, sample_coc);
+        if (sample_coc > center_coc) {
+            far_size = min(far_size, max(0.0, center_coc) * 2.0);
         }
 
-		float m = smoothstep(r-0.5, r+0.5, sampleSize);
-		color += lerp(color/tot, sampleColor, m);
-		tot += 1.0;
-        ang += GOLDEN_ANGLE;
-	}
-#endif
+        const float m = smoothstep(radius - 0.5, radius + 0.5, far_size);
+        far_color += lerp(far_color / far_count, sample_color, m);
+        far_count += 1.0;
+
+        radius += RAD_SCALE / radius;
+    }
 
-    color /= tot;
-    //color = neutral_tonemap(color);
-    //color = 1 - exp(-color);
-    //color = pow(color, 1.05);
+    far_color /= far_count;
+    near_color = near_weight > 0.0 ? near_color / near_weight : 0.0;
 
-    output_tex[px] = color;
+    output_tex[px] = lerp(far_color, near_color, saturate(near_coverage));
 }
@@ -0,0 +1,12 @@
+#include "../inc/samplers.hlsl"
+
+[[vk::binding(1)]] Texture2D<float4> egui_tex;
+
+struct PsIn {
+    [[vk::location(0)]] float4 color: TEXCOORD0;
+    [[vk::location(1)]] float2 uv: TEXCOORD1;
+};
+
+float4 main(PsIn ps): SV_TARGET0 {
+    return ps.color * egui_tex.Sample(sampler_llc, ps.uv);
+}
@@ -0,0 +1,44 @@
+// Must match the vertex layout written by `kajiya-egui`: position and UV in points,
+// followed by a premultiplied RGBA8 color in gamma space.
+[[vk::binding(0)]] ByteAddressBuffer vertices;
+
+[[vk::push_constant]]
+struct {
+    // In points
+    float2 screen_size;
+} push_constants;
+
+struct VsOut {
+    float4 position: SV_Position;
+    [[vk::location(0)]] float4 color: TEXCOORD0;
+    [[vk::location(1)]] float2 uv: TEXCOORD1;
+};
+
+float4 unpack_color(uint packed) {
+    return float4(
+        packed & 0xff,
+        (packed >> 8) & 0xff,
+        (packed >> 16) & 0xff,
+        packed >> 24
+    ) / 255.0;
+}
+
+VsOut main(uint vid: SV_VertexID) {
+    const uint4 pos_uv = vertices.Load4(vid * 20);
+    const uint color = vertices.Load(vid * 20 + 16);
+
+    const float2 pos = asfloat(pos_uv.xy);
+
+    VsOut vsout;
+    // The viewport is flipped, so that +Y is up in clip space; egui has +Y down.
+    vsout.position = float4(
+        pos.x / push_constants.screen_size.x * 2.0 - 1.0,
+        1.0 - pos.y / push_constants.screen_size.y * 2.0,
+        0.0,
+        1.0
+    );
+    vsout.color = unpack_color(color);
+    vsout.uv = asfloat(pos_uv.zw);
+
+    return vsout;
+}
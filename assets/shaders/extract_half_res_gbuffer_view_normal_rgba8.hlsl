@@ -1,5 +1,5 @@
 #include "inc/frame_constants.hlsl"
-#include "inc/pack_unpack.hlsl"
+#include "inc/gbuffer.hlsl"
 
 [[vk::binding(0)]] Texture2D<float4> input_tex;
 [[vk::binding(1)]] RWTexture2D<float4> output_tex;
@@ -9,7 +9,7 @@
 };
 
 float3 normal_ws_at_px(int2 px) {
-    return unpack_normal_11_10_11_no_normalize(input_tex[px].y);
+    return GbufferDataPacked::from_uint4(asuint(input_tex[px])).unpack_normal();
 }
 
 [numthreads(8, 8, 1)]
@@ -4,10 +4,31 @@
 [[vk::binding(3)]] cbuffer _ {
     float4 main_tex_size;
     float4 output_tex_size;
+    // The part of `output_tex` covered by `main_tex`: x, y, width, height
+    float4 main_rect;
+    uint output_encoding;
+    uint output_gamut;
+    float paper_white_nits;
+    float max_luminance_nits;
+    uint output_filter;
 };
 
 #include "inc/image.hlsl"
 #include "inc/color/srgb.hlsl"
+#include "inc/color/ictcp.hlsl"
+
+// Must match `OutputEncoding::shader_index`
+#define OUTPUT_ENCODING_SRGB 0
+#define OUTPUT_ENCODING_PQ 1
+
+// Must match `OutputGamut::shader_index`
+#define OUTPUT_GAMUT_REC709 0
+#define OUTPUT_GAMUT_DISPLAY_P3 1
+#define OUTPUT_GAMUT_REC2020 2
+
+// Must match `OutputFilter::shader_index`
+#define OUTPUT_FILTER_NEAREST 0
+#define OUTPUT_FILTER_BICUBIC 1
 
 struct LinearToSrgbRemap {
     static LinearToSrgbRemap create() {
@@ -20,26 +41,77 @@ struct LinearToSrgbRemap {
     }
 };
 
+float3 rec709_to_output_gamut(float3 col) {
+    switch (output_gamut) {
+        case OUTPUT_GAMUT_DISPLAY_P3:
+            return mul(float3x3(
+                0.8224621, 0.1775380, 0.0000000,
+                0.0331941, 0.9668058, 0.0000000,
+                0.0170827, 0.0723974, 0.9105199
+            ), col);
+        case OUTPUT_GAMUT_REC2020:
+            return BT709_to_BT2020(col);
+        default:
+            return col;
+    }
+}
+
+// PQ output is always in the Rec.2020 container.
+float3 output_gamut_to_rec2020(float3 col) {
+    switch (output_gamut) {
+        case OUTPUT_GAMUT_REC709:
+            return BT709_to_BT2020(col);
+        case OUTPUT_GAMUT_DISPLAY_P3:
+            return mul(float3x3(
+                 0.7538330, 0.1985974, 0.0475696,
+                 0.0457438, 0.9417772, 0.0124789,
+                -0.0012103, 0.0176017, 0.9836086
+            ), col);
+        default:
+            return col;
+    }
+}
+
 [numthreads(8, 8, 1)]
 void main(in uint2 px : SV_DispatchThreadID) {
-    #if 1
-    float3 main;
-    if (any(main_tex_size.xy != output_tex_size.xy)) {
-        main = image_sample_catmull_rom(
-            TextureImage::from_parts(main_tex, main_tex_size.xy),
-            (px + 0.5) / output_tex_size.xy,
-            LinearToSrgbRemap::create()
-        ).rgb;
-    } else {
-        main = sRGB_EOTF(saturate(main_tex[px].rgb));
+    // Linear Rec.709, with paper white at 1.0; black in the letterbox bars
+    float3 main = 0.0;
+    const float2 main_uv = (px + 0.5 - main_rect.xy) / main_rect.zw;
+
+    if (all(main_uv >= 0.0) && all(main_uv < 1.0)) {
+        if (all(main_tex_size.xy == main_rect.zw)) {
+            main = main_tex[px - uint2(main_rect.xy)].rgb;
+        } else if (OUTPUT_FILTER_NEAREST == output_filter) {
+            main = main_tex[uint2(main_uv * main_tex_size.xy)].rgb;
+        } else {
+            // Resample in perceptual space
+            main = sRGB_OETF(image_sample_catmull_rom(
+                TextureImage::from_parts(main_tex, main_tex_size.xy),
+                main_uv,
+                LinearToSrgbRemap::create()
+            ).rgb);
+        }
     }
-    float4 gui = gui_tex[px];
 
-    float3 result = main.rgb * (1.0 - gui.a) + gui.rgb;
-    //float3 result = lerp(main, gui.rgb, gui.a);
-    #else
-    float3 result = float3(0.7, 0.4, 0.1);
-    #endif
+    // Clipped to the gamut and the peak of the display
+    const float3 main_nits = clamp(
+        rec709_to_output_gamut(max(0.0, main)) * paper_white_nits,
+        0.0,
+        max_luminance_nits);
+
+    // sRGB-encoded, premultiplied
+    const float4 gui = gui_tex[px];
+
+    float3 result;
+    if (OUTPUT_ENCODING_PQ == output_encoding) {
+        const float3 gui_nits = gui.a > 0.0
+            ? BT709_to_BT2020(sRGB_OETF(gui.rgb / gui.a)) * gui.a * paper_white_nits
+            : 0.0;
+
+        result = linear_to_PQ(output_gamut_to_rec2020(main_nits) * (1.0 - gui.a) + gui_nits);
+    } else {
+        result = sRGB_EOTF(main_nits / max_luminance_nits) * (1.0 - gui.a) + gui.rgb;
+    }
 
     output_tex[px] = float4(result, 1);
 }
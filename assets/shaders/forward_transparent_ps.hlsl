@@ -0,0 +1,235 @@
+#include "inc/math.hlsl"
+#include "inc/samplers.hlsl"
+#include "inc/frame_constants.hlsl"
+#include "inc/working_primaries.hlsl"
+#include "inc/mesh.hlsl"
+#include "inc/pack_unpack.hlsl"
+#include "inc/bindless.hlsl"
+#include "inc/gbuffer.hlsl"
+#include "inc/brdf.hlsl"
+#include "inc/brdf_lut.hlsl"
+#include "inc/layered_brdf.hlsl"
+#include "inc/lights/punctual.hlsl"
+#include "inc/lights/rect.hlsl"
+#include "inc/atmosphere.hlsl"
+#include "inc/sun.hlsl"
+#include "inc/parallax.hlsl"
+
+// Lighting of alpha-blended surfaces, composited over the output of `light_gbuffer`.
+//
+// There is no ray-traced GI, reflections, or shadowing here: the sun, punctual and rect lights
+// are unshadowed, and indirect lighting comes from the sky cubes.
+//
+// With `OPAQUE_MATERIALS`, the other materials are shaded instead, with depth writes;
+// that's how secondary world views are lit, as they don't have a G-buffer.
+
+struct PsIn {
+    [[vk::location(0)]] float4 color: TEXCOORD0;
+    [[vk::location(1)]] float2 uv: TEXCOORD1;
+    [[vk::location(2)]] float3 normal: TEXCOORD2;
+    [[vk::location(3)]] nointerpolation uint material_id: TEXCOORD3;
+    [[vk::location(4)]] float3 tangent: TEXCOORD4;
+    [[vk::location(5)]] float3 bitangent: TEXCOORD5;
+    [[vk::location(6)]] float3 vs_pos: TEXCOORD6;
+    [[vk::location(7)]] float3 prev_vs_pos: TEXCOORD7;
+    [[vk::location(8)]] nointerpolation uint draw_index: TEXCOORD8;
+    [[vk::location(9)]] nointerpolation uint mesh_index: TEXCOORD9;
+};
+
+// Must match `GpuInstanceTransform` in `raster_meshes.rs`
+struct InstanceTransform {
+    row_major float3x4 current;
+    row_major float3x4 previous;
+    uint mesh_index;
+    uint pad0;
+    uint pad1;
+    uint pad2;
+};
+
+[[vk::constant_id(0)]] const bool OPAQUE_MATERIALS = false;
+
+[[vk::binding(0)]] StructuredBuffer<InstanceTransform> instance_transforms_dyn;
+[[vk::binding(1)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(2)]] TextureCube<float4> prefiltered_sky_cube_tex;
+
+float3 sample_prefiltered_sky(float3 dir, float roughness) {
+    uint cube_width, cube_height, cube_levels;
+    prefiltered_sky_cube_tex.GetDimensions(0, cube_width, cube_height, cube_levels);
+
+    return prefiltered_sky_cube_tex.SampleLevel(sampler_llr, dir, sqrt(roughness) * (cube_levels - 1)).rgb;
+}
+
+struct PsOut {
+    // Premultiplied alpha
+    float4 color: SV_TARGET0;
+
+    // Blended into the TAA responsive mask, so that transparent surfaces don't ghost.
+    float4 responsive: SV_TARGET1;
+};
+
+PsOut main(PsIn ps, bool is_front_face: SV_IsFrontFace) {
+    Mesh mesh = meshes[ps.mesh_index];
+    MeshMaterial material = vertices.Load<MeshMaterial>(mesh.mat_data_offset + ps.material_id * sizeof(MeshMaterial));
+    const InstanceDynamicConstants instance_params = instance_dynamic_parameters_dyn[ps.draw_index];
+
+    // Opaque and alpha-tested materials of the same mesh were drawn into the G-buffer,
+    // or are in the opaque pass; alpha-blended ones are drawn after it.
+    if (is_material_alpha_blended(material) == OPAQUE_MATERIALS) {
+        discard;
+    }
+
+    if (!is_front_face && !is_material_double_sided(material)) {
+        discard;
+    }
+
+    const float lod_bias = -0.5;
+    const float3x4 object_to_world = instance_transforms_dyn[ps.draw_index].current;
+
+    const float2 uv_ddx = ddx(ps.uv) * exp2(lod_bias);
+    const float2 uv_ddy = ddy(ps.uv) * exp2(lod_bias);
+    float2 uv = ps.uv;
+
+    [branch]
+    if (is_material_parallax_mapped(material) && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        const float3 to_eye_ws = -normalize(direction_view_to_world(ps.vs_pos));
+        const float3 view_dir_ts = float3(
+            dot(to_eye_ws, normalize(mul(object_to_world, float4(ps.tangent, 0.0)))),
+            dot(to_eye_ws, normalize(mul(object_to_world, float4(ps.bitangent, 0.0)))),
+            dot(to_eye_ws, normalize(mul(object_to_world, float4(is_front_face ? ps.normal : -ps.normal, 0.0)))));
+
+        uv = parallax_occlusion_map(material, ps.uv, uv_ddx, uv_ddy, view_dir_ts, 32).uv;
+    }
+
+    float2 albedo_uv = transform_material_uv(material, uv, 0);
+    Texture2D albedo_tex = bindless_textures[NonUniformResourceIndex(material.albedo_map)];
+    float4 albedo_texel = albedo_tex.SampleBias(sampler_llr, albedo_uv, lod_bias);
+
+    const float alpha = albedo_texel.a * material.base_color_mult[3] * ps.color.a;
+
+    if (OPAQUE_MATERIALS && is_material_alpha_tested(material) && albedo_texel.a * material.base_color_mult[3] < material.alpha_cutoff) {
+        discard;
+    }
+    float3 albedo = rec709_to_working(
+        albedo_texel.xyz * float4(material.base_color_mult).xyz * ps.color.xyz * instance_params.base_color_tint);
+
+    float2 spec_uv = transform_material_uv(material, uv, 2);
+    Texture2D spec_tex = bindless_textures[NonUniformResourceIndex(material.spec_map)];
+    const float4 metalness_roughness = spec_tex.SampleBias(sampler_llr, spec_uv, lod_bias);
+    float perceptual_roughness = material.roughness_mult * instance_params.roughness_multiplier * metalness_roughness.x;
+    float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
+    float metalness = metalness_roughness.y * material.metalness_factor;
+
+    if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_METAL)) {
+        metalness = 0;
+    }
+
+    float3 normal_os = is_front_face ? ps.normal : -ps.normal;
+
+    [branch]
+    if (!frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_NORMAL_MAPS) && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        Texture2D normal_tex = bindless_textures[NonUniformResourceIndex(material.normal_map)];
+        const float2 normal_uv = transform_material_uv(material, uv, 1);
+
+        float3 ts_normal = float3(normal_tex.SampleBias(sampler_llr, normal_uv, lod_bias).xy * 2.0 - 1.0, 0);
+        ts_normal.z = sqrt(max(0.01, 1.0 - dot(ts_normal.xy, ts_normal.xy)));
+
+        if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::FLIP_NORMAL_MAP_YZ)) {
+            ts_normal.zy *= -1;
+        }
+
+        normal_os = mul(ts_normal, float3x3(ps.tangent, ps.bitangent, normal_os));
+    }
+
+    float3 normal_ws = normalize(mul(object_to_world, float4(normal_os, 0.0)));
+
+    const float3 eye_to_pt_ws = direction_view_to_world(ps.vs_pos);
+    const float3 view_dir_ws = normalize(eye_to_pt_ws);
+    const float3 pt_ws = get_eye_position() + eye_to_pt_ws;
+
+    // Fix invalid normals
+    if (dot(normal_ws, view_dir_ws) > 0.0) {
+        normal_ws *= -1;
+    }
+
+    GbufferData gbuffer = GbufferData::create_zero();
+    gbuffer.albedo = albedo;
+    gbuffer.normal = normal_ws;
+    gbuffer.roughness = roughness;
+    gbuffer.metalness = metalness;
+    gbuffer.clearcoat = material.clearcoat;
+    gbuffer.clearcoat_roughness = perceptual_roughness_to_roughness(material.clearcoat_roughness);
+    gbuffer.transmission = material.transmission;
+
+    if (material.anisotropy > 0.0 && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        const float3 anisotropy_dir_os =
+            cos(material.anisotropy_rotation) * ps.tangent
+            + sin(material.anisotropy_rotation) * ps.bitangent;
+        gbuffer.anisotropy = material.anisotropy;
+        gbuffer.set_anisotropy_direction(mul(object_to_world, float4(anisotropy_dir_os, 0.0)));
+    }
+
+    const float3x3 tangent_to_world = build_orthonormal_basis(gbuffer.normal);
+    const float3 wo = mul(-view_dir_ws, tangent_to_world);
+
+    LayeredBrdf brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
+
+    const float3 sun_wi = mul(SUN_DIRECTION, tangent_to_world);
+    float3 total_radiance = brdf.evaluate_directional_light(wo, sun_wi) * max(0.0, sun_wi.z) * SUN_COLOR;
+
+    for (uint light_idx = 0; light_idx < frame_constants.punctual_light_count; ++light_idx) {
+        const PunctualLight light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+        const PunctualLightSample light_sample = light.sample(pt_ws);
+        const float3 light_wi = mul(light_sample.wi, tangent_to_world);
+
+        total_radiance +=
+            brdf.evaluate_directional_light(wo, light_wi)
+            * max(0.0, light_wi.z)
+            * light_sample.radiance
+            * frame_constants.pre_exposure;
+    }
+
+    for (uint light_idx = 0; light_idx < frame_constants.rect_light_count; ++light_idx) {
+        const RectLight light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+
+        total_radiance +=
+            light.evaluate_ltc(brdf, pt_ws, gbuffer.normal, -view_dir_ws)
+            * frame_constants.pre_exposure;
+    }
+
+    total_radiance += sky_cube_tex.SampleLevel(sampler_llr, gbuffer.normal, 0).rgb
+        * brdf.diffuse_brdf.albedo
+        * brdf.energy_preservation.preintegrated_transmission_fraction
+        * (1.0 - brdf.clearcoat_preintegrated_reflection);
+
+    const float3 reflected_dir = reflect(view_dir_ws, gbuffer.normal);
+    total_radiance += sample_prefiltered_sky(reflected_dir, gbuffer.roughness)
+        * brdf.energy_preservation.preintegrated_reflection
+        * (1.0 - brdf.clearcoat_preintegrated_reflection);
+
+    [branch]
+    if (brdf.clearcoat > 0.0) {
+        total_radiance += sample_prefiltered_sky(reflected_dir, brdf.clearcoat_brdf.roughness)
+            * brdf.clearcoat_preintegrated_reflection;
+    }
+
+    float2 emissive_uv = transform_material_uv(material, uv, 3);
+    Texture2D emissive_tex = bindless_textures[NonUniformResourceIndex(material.emissive_map)];
+    total_radiance += rec709_to_working(emissive_tex.SampleBias(sampler_llr, emissive_uv, lod_bias).rgb
+        * float3(material.emissive))
+        * instance_params.emissive_multiplier
+        * frame_constants.pre_exposure;
+
+    // Thin transmission lets the background through without being occluded by the diffuse layer,
+    // so it only reduces coverage. Reflections are added on top either way.
+    const float coverage = alpha * (1.0 - material.transmission * (1.0 - metalness));
+
+    PsOut ps_out;
+    if (OPAQUE_MATERIALS) {
+        ps_out.color = float4(total_radiance, 1);
+        ps_out.responsive = 0;
+    } else {
+        ps_out.color = float4(total_radiance * alpha, coverage);
+        ps_out.responsive = alpha;
+    }
+    return ps_out;
+}
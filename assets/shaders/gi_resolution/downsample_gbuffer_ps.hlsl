@@ -0,0 +1,29 @@
+// Point-samples the G-buffer, geometric normal, and depth at a reduced resolution,
+// so that the GI and reflection passes can run on the result as if it were full-res.
+// Depth can't be written from compute, hence this being a raster pass.
+
+[[vk::binding(0)]] Texture2D<float4> geometric_normal_tex;
+[[vk::binding(1)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(2)]] Texture2D<float> depth_tex;
+
+[[vk::push_constant]]
+struct {
+    uint divisor;
+} push_constants;
+
+struct PsOut {
+    float4 geometric_normal: SV_TARGET0;
+    float4 gbuffer: SV_TARGET1;
+    float depth: SV_Depth;
+};
+
+PsOut main(float4 position: SV_Position) {
+    // Must match `downsample_inputs.hlsl`
+    const int2 src_px = int2(position.xy) * push_constants.divisor + push_constants.divisor / 2;
+
+    PsOut psout;
+    psout.geometric_normal = geometric_normal_tex[src_px];
+    psout.gbuffer = gbuffer_tex[src_px];
+    psout.depth = depth_tex[src_px];
+    return psout;
+}
@@ -0,0 +1,35 @@
+#include "../inc/frame_constants.hlsl"
+
+[[vk::binding(0)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(1)]] Texture2D<float4> ssao_tex;
+[[vk::binding(2)]] Texture2D<float4> prev_radiance_tex;
+[[vk::binding(3)]] RWTexture2D<float4> reprojection_output_tex;
+[[vk::binding(4)]] RWTexture2D<float4> ssao_output_tex;
+[[vk::binding(5)]] RWTexture2D<float4> prev_radiance_output_tex;
+[[vk::binding(6)]] cbuffer _ {
+    float4 output_tex_size;
+    uint divisor;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    if (any(px >= uint2(output_tex_size.xy))) {
+        return;
+    }
+
+    // Must match `downsample_gbuffer_ps.hlsl`, so the reprojection and SSAO
+    // correspond to the surfaces in the reduced G-buffer.
+    const uint2 src_px = px * divisor + divisor / 2;
+
+    reprojection_output_tex[px] = reprojection_tex[src_px];
+    ssao_output_tex[px] = ssao_tex[src_px];
+
+    // Only used for screen-space lookups of previously lit surfaces; a box filter is fine.
+    float3 radiance_sum = 0;
+    for (uint y = 0; y < divisor; ++y) {
+        for (uint x = 0; x < divisor; ++x) {
+            radiance_sum += prev_radiance_tex[px * divisor + uint2(x, y)].rgb;
+        }
+    }
+    prev_radiance_output_tex[px] = float4(radiance_sum / (divisor * divisor), 1.0);
+}
@@ -0,0 +1,78 @@
+#include "../inc/samplers.hlsl"
+#include "../inc/color.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/frame_constants.hlsl"
+
+// Full-resolution temporal accumulation of a GI signal, with its own reprojection,
+// and a clamp box widened by the temporal variance of the signal, so that residual
+// noise gets averaged out rather than clipped away into flicker.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> history_tex;
+[[vk::binding(2)]] Texture2D<float2> moments_history_tex;
+[[vk::binding(3)]] Texture2D<float4> reprojection_tex;
+// See `reactive_mask.hlsl`
+[[vk::binding(4)]] Texture2D<float> reactive_mask_tex;
+[[vk::binding(5)]] RWTexture2D<float4> output_tex;
+[[vk::binding(6)]] RWTexture2D<float2> moments_output_tex;
+[[vk::binding(7)]] cbuffer _ {
+    float4 output_tex_size;
+    float max_sample_count;
+    float clamp_sigma;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = get_uv(px, output_tex_size);
+    const int2 max_px = int2(output_tex_size.xy) - 1;
+
+    // Spatial moments of the input, in YCbCr
+    float3 ex = 0.0;
+    float3 ex2 = 0.0;
+    {for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            const int2 sample_px = clamp(int2(px) + int2(x, y), 0, max_px);
+            const float3 neigh = sRGB_to_YCbCr(max(0.0, input_tex[sample_px].rgb));
+            ex += neigh;
+            ex2 += neigh * neigh;
+        }
+    }}
+    ex /= 9.0;
+    ex2 /= 9.0;
+
+    const float3 center = sRGB_to_YCbCr(max(0.0, input_tex[px].rgb));
+
+    const float4 reproj = reprojection_tex[px];
+    const float pre_exposure_delta = frame_constants.pre_exposure_delta;
+
+    float4 history = history_tex.SampleLevel(sampler_lnc, uv + reproj.xy, 0);
+    history.rgb *= pre_exposure_delta;
+
+    const float2 moments_history = moments_history_tex.SampleLevel(sampler_lnc, uv + reproj.xy, 0)
+        * float2(pre_exposure_delta, pre_exposure_delta * pre_exposure_delta);
+
+    // Off-screen, disoccluded, and partially invalid history counts for less.
+    float history_confidence = reproj.w < 0.0 ? 0.0 : saturate(reproj.z);
+    history_confidence *= 1.0 - reactive_mask_tex[px];
+    // So is history lit differently than the current frame.
+    history_confidence *= 1.0 - frame_constants.lighting_change;
+
+    const float sample_count = min(history.a * history_confidence, max_sample_count);
+    const float blend = 1.0 / (1.0 + sample_count);
+
+    const float2 moments = lerp(moments_history, float2(center.x, center.x * center.x), blend);
+    const float temporal_dev = sqrt(max(0.0, moments.y - moments.x * moments.x));
+
+    float3 dev = sqrt(max(0.0, ex2 - ex * ex));
+    dev.x = max(dev.x, temporal_dev);
+
+    const float3 clamped_history = clamp(
+        sRGB_to_YCbCr(max(0.0, history.rgb)),
+        ex - dev * clamp_sigma,
+        ex + dev * clamp_sigma);
+
+    const float3 result = lerp(clamped_history, center, blend);
+
+    output_tex[px] = float4(max(0.0, YCbCr_to_sRGB(result)), sample_count + 1.0);
+    moments_output_tex[px] = max(0.0, moments);
+}
@@ -0,0 +1,141 @@
+#include "../inc/math_const.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/blue_noise.hlsl"
+
+// Ground-truth ambient occlusion, after "Practical Realtime Strategies for Accurate Indirect Occlusion"
+// by Jimenez et al. Finds the horizons in a few slices around the view vector, and integrates
+// the cosine-weighted visibility between them analytically, along with the bent normal.
+//
+// Outputs the visibility in R, and the view-space bent normal in GBA.
+
+[[vk::binding(0)]] Texture2D<float> half_depth_tex;
+[[vk::binding(1)]] Texture2D<float4> half_view_normal_tex;
+[[vk::binding(2)]] RWTexture2D<float4> output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float4 input_tex_size;
+    float4 output_tex_size;
+    uint slice_count;
+    uint step_count;
+    float radius_ws;
+};
+
+// Keeps the kernel from thrashing the cache when the camera is right next to a surface.
+static const float MAX_RADIUS_PX = 256.0;
+
+float3 fetch_position_vs(float2 uv) {
+    const int2 px = clamp(int2(uv * output_tex_size.xy), 0, int2(output_tex_size.xy) - 1);
+    const float depth = half_depth_tex[px];
+    if (0.0 == depth) {
+        return FLT_MAX;
+    }
+    return ViewRayContext::from_uv_and_depth(uv, depth).ray_hit_vs();
+}
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float depth = half_depth_tex[px];
+    if (0.0 == depth) {
+        output_tex[px] = float4(1, 0, 0, 1);
+        return;
+    }
+
+    const float2 uv = get_uv(px * 2 + HALFRES_SUBSAMPLE_OFFSET, input_tex_size);
+    const float3 center_vs = ViewRayContext::from_uv_and_depth(uv, depth).ray_hit_vs();
+    const float3 v_vs = -normalize(center_vs);
+    const float3 normal_vs = normalize(half_view_normal_tex[px].xyz);
+
+    // Projected size of the kernel, in full-res pixels
+    const float radius_px = min(
+        MAX_RADIUS_PX,
+        radius_ws * frame_constants.view_constants.view_to_clip[1][1] * 0.5 * input_tex_size.y / -center_vs.z
+    );
+
+    if (radius_px < 1.0) {
+        output_tex[px] = float4(1, normal_vs);
+        return;
+    }
+
+    const float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index).xy;
+
+    float visibility = 0.0;
+    float3 bent_normal_vs = 0.0;
+
+    for (uint slice_i = 0; slice_i < slice_count; ++slice_i) {
+        const float phi = (slice_i + urand.x) * M_PI / slice_count;
+
+        // With square pixels, a direction on the screen is the same in view space, with Y flipped.
+        const float2 dir_px = float2(cos(phi), sin(phi));
+        const float3 dir_vs = float3(dir_px.x, -dir_px.y, 0.0);
+
+        const float3 ortho_dir_vs = normalize(dir_vs - dot(dir_vs, v_vs) * v_vs);
+        const float3 axis_vs = cross(ortho_dir_vs, v_vs);
+
+        const float3 proj_normal_vs = normal_vs - axis_vs * dot(normal_vs, axis_vs);
+        const float proj_normal_len = length(proj_normal_vs);
+
+        const float cos_n = clamp(dot(proj_normal_vs, v_vs) / max(1e-5, proj_normal_len), -1.0, 1.0);
+        const float n = sign(dot(proj_normal_vs, ortho_dir_vs)) * acos(cos_n);
+
+        // Cosines of the horizon angles towards +dir and -dir
+        float2 horizon_cos = -1.0;
+
+        for (uint step_i = 0; step_i < step_count; ++step_i) {
+            // Quadratic distribution, which puts more samples closer to the center
+            float s = (step_i + urand.y) / step_count;
+            s *= s;
+
+            const float2 offset_uv = dir_px * max(1.0, s * radius_px) * input_tex_size.zw;
+
+            [unroll] for (uint side = 0; side < 2; ++side) {
+                const float3 sample_vs = fetch_position_vs(uv + (side == 0 ? offset_uv : -offset_uv));
+                if (sample_vs.x == FLT_MAX) {
+                    continue;
+                }
+
+                const float3 delta = sample_vs - center_vs;
+                const float dist2 = dot(delta, delta);
+                const float sample_cos = dot(delta, v_vs) * rsqrt(max(1e-10, dist2));
+
+                // Fade out occluders towards the edge of the kernel
+                const float falloff = saturate(1.0 - dist2 / (radius_ws * radius_ws));
+                horizon_cos[side] = max(horizon_cos[side], lerp(-1.0, sample_cos, falloff));
+            }
+        }
+
+        // Horizon angles relative to the view vector, clamped to the hemisphere around the normal
+        float h0 = -acos(horizon_cos.y);
+        float h1 = acos(horizon_cos.x);
+        h0 = n + max(h0 - n, -M_FRAC_PI_2);
+        h1 = n + min(h1 - n, M_FRAC_PI_2);
+
+        const float sin_n = sin(n);
+
+        visibility += proj_normal_len * 0.25 * (
+            (-cos(2.0 * h0 - n) + cos_n + 2.0 * h0 * sin_n)
+            + (-cos(2.0 * h1 - n) + cos_n + 2.0 * h1 * sin_n)
+        );
+
+        const float t0 = (
+            6.0 * sin(h0 - n) - sin(3.0 * h0 - n)
+            + 6.0 * sin(h1 - n) - sin(3.0 * h1 - n)
+            + 16.0 * sin_n
+            - 3.0 * (sin(h0 + n) + sin(h1 + n))
+        ) / 12.0;
+
+        const float t1 = (
+            -cos(3.0 * h0 - n) - cos(3.0 * h1 - n)
+            + 8.0 * cos_n
+            - 3.0 * (cos(h0 + n) + cos(h1 + n))
+        ) / 12.0;
+
+        bent_normal_vs += (ortho_dir_vs * t0 + v_vs * t1) * proj_normal_len;
+    }
+
+    visibility = saturate(visibility / slice_count);
+
+    const float bent_normal_len = length(bent_normal_vs);
+    bent_normal_vs = bent_normal_len > 1e-5 ? bent_normal_vs / bent_normal_len : normal_vs;
+
+    output_tex[px] = float4(visibility, bent_normal_vs);
+}
@@ -0,0 +1,60 @@
+#include "../inc/uv.hlsl"
+
+// Accumulates the visibility and bent normal over time, with history clamped
+// to the local neighborhood statistics.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> history_tex;
+[[vk::binding(2)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(3)]] RWTexture2D<float4> final_output_tex;
+[[vk::binding(4)]] RWTexture2D<float4> history_output_tex;
+[[vk::binding(5)]] cbuffer _ {
+    float4 output_tex_size;
+};
+SamplerState sampler_lnc;
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = get_uv(px, output_tex_size);
+
+    const float4 center = input_tex[px];
+    const float4 reproj = reprojection_tex[px];
+    const float4 history = history_tex.SampleLevel(sampler_lnc, uv + reproj.xy, 0);
+
+    float4 vsum = 0.0;
+    float4 vsum2 = 0.0;
+    float wsum = 0.0;
+
+    const int k = 2;
+    for (int y = -k; y <= k; ++y) {
+        for (int x = -k; x <= k; ++x) {
+            const float4 neigh = input_tex[px + int2(x, y) * 2];
+            const float w = exp(-3.0 * float(x * x + y * y) / float((k + 1.) * (k + 1.)));
+            vsum += neigh * w;
+            vsum2 += neigh * neigh * w;
+            wsum += w;
+        }
+    }
+
+    const float4 ex = vsum / wsum;
+    const float4 ex2 = vsum2 / wsum;
+    const float4 dev = sqrt(max(0.0, ex2 - ex * ex));
+
+    const float box_size = 0.5;
+    const float n_deviations = 5.0;
+
+    const float4 nmin = lerp(center, ex, box_size * box_size) - dev * box_size * n_deviations;
+    const float4 nmax = lerp(center, ex, box_size * box_size) + dev * box_size * n_deviations;
+
+    const float4 clamped_history = clamp(history, nmin, nmax);
+
+    // Disocclusions start over from the current frame.
+    const float blend = reproj.z > 0.0 ? 1.0 / 8.0 : 1.0;
+    float4 res = lerp(clamped_history, center, blend);
+
+    const float bent_normal_len = length(res.gba);
+    res.gba = bent_normal_len > 1e-5 ? res.gba / bent_normal_len : center.gba;
+
+    history_output_tex[px] = res;
+    final_output_tex[px] = res;
+}
@@ -0,0 +1,15 @@
+[[vk::binding(0)]] Texture2D<float2> input_tex;
+[[vk::binding(1)]] RWTexture2D<float2> output_tex;
+
+// Builds a level of the depth pyramid from the one below it, which is exactly twice its size.
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 a = input_tex[px * 2 + uint2(0, 0)];
+    const float2 b = input_tex[px * 2 + uint2(1, 0)];
+    const float2 c = input_tex[px * 2 + uint2(0, 1)];
+    const float2 d = input_tex[px * 2 + uint2(1, 1)];
+
+    output_tex[px] = float2(
+        min(min(a.x, b.x), min(c.x, d.x)),
+        max(max(a.y, b.y), max(c.y, d.y)));
+}
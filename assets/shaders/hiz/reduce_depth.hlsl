@@ -0,0 +1,26 @@
+[[vk::binding(0)]] Texture2D<float> depth_tex;
+[[vk::binding(1)]] RWTexture2D<float2> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    uint2 depth_extent;
+};
+
+// Builds the first level of the depth pyramid from the depth buffer.
+// Depth is reversed, so the minimum in `x` is the farthest surface, and the maximum in `y` the nearest.
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    float2 result = float2(1.0, 0.0);
+
+    // The pyramid is padded to a power of two; texels past the edge of the depth buffer
+    // neither occlude nor get occluded.
+    for (uint y = 0; y < 2; ++y) {
+        for (uint x = 0; x < 2; ++x) {
+            const uint2 src_px = px * 2 + uint2(x, y);
+            if (all(src_px < depth_extent)) {
+                const float depth = depth_tex[src_px];
+                result = float2(min(result.x, depth), max(result.y, depth));
+            }
+        }
+    }
+
+    output_tex[px] = result;
+}
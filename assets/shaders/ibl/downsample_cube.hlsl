@@ -0,0 +1,14 @@
+[[vk::binding(0)]] Texture2DArray<float4> input_tex;
+[[vk::binding(1)]] RWTexture2DArray<float4> output_tex;
+
+[numthreads(8, 8, 1)]
+void main(in uint3 px : SV_DispatchThreadID) {
+    const uint3 src_px = uint3(px.xy * 2, px.z);
+
+    output_tex[px] = 0.25 * (
+        input_tex[src_px + uint3(0, 0, 0)] +
+        input_tex[src_px + uint3(1, 0, 0)] +
+        input_tex[src_px + uint3(0, 1, 0)] +
+        input_tex[src_px + uint3(1, 1, 0)]
+    );
+}
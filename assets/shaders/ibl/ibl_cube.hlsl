@@ -2,6 +2,7 @@
 #include "../inc/sun.hlsl"
 #include "../inc/cube_map.hlsl"
 #include "../inc/samplers.hlsl"
+#include "../inc/working_primaries.hlsl"
 
 [[vk::binding(0)]] Texture2D<float4> input_tex;
 [[vk::binding(1)]] RWTexture2DArray<float4> output_tex;
@@ -28,5 +29,5 @@ void main(in uint3 px : SV_DispatchThreadID) {
 
     float3 output = input_tex.SampleLevel(sampler_llr, uv, 0).rgb;
 
-    output_tex[px] = float4(frame_constants.pre_exposure * output, 1);
+    output_tex[px] = float4(frame_constants.pre_exposure * rec709_to_working(output), 1);
 }
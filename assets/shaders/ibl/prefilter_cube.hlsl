@@ -0,0 +1,72 @@
+#include "../inc/math.hlsl"
+#include "../inc/quasi_random.hlsl"
+#include "../inc/samplers.hlsl"
+#include "../inc/cube_map.hlsl"
+
+[[vk::binding(0)]] TextureCube<float4> input_tex;
+[[vk::binding(1)]] RWTexture2DArray<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    uint face_width;
+    float roughness;
+}
+
+static const uint sample_count = 64;
+
+float ggx_ndf(float a2, float cos_theta) {
+    float denom_sqrt = cos_theta * cos_theta * (a2 - 1.0) + 1.0;
+    return a2 / (M_PI * denom_sqrt * denom_sqrt);
+}
+
+// Pre-filtering for split-sum specular, with the usual n = v = r approximation.
+// Uses filtered importance sampling to keep the sample count low:
+// https://developer.nvidia.com/gpugems/gpugems3/part-iii-rendering/chapter-20-gpu-based-importance-sampling
+[numthreads(8, 8, 1)]
+void main(in uint3 px : SV_DispatchThreadID) {
+    uint face = px.z;
+    float2 uv = (px.xy + 0.5) / face_width;
+
+    const float3 normal = normalize(mul(CUBE_MAP_FACE_ROTATIONS[face], float3(uv * 2 - 1, -1.0)));
+
+    if (roughness == 0.0) {
+        output_tex[px] = input_tex.SampleLevel(sampler_llr, normal, 0);
+        return;
+    }
+
+    uint input_width, input_height, input_levels;
+    input_tex.GetDimensions(0, input_width, input_height, input_levels);
+
+    const float3x3 basis = build_orthonormal_basis(normal);
+    const float a2 = roughness * roughness;
+    const float texel_solid_angle = 4.0 * M_PI / (6.0 * input_width * input_width);
+
+    float4 result = 0;
+    float weight_sum = 0;
+
+    for (uint i = 0; i < sample_count; ++i) {
+        const float2 urand = hammersley(i, sample_count);
+
+        // Sample the GGX half vector
+        const float cos_theta = sqrt((1.0 - urand.y) / (1.0 + (a2 - 1.0) * urand.y));
+        const float sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        const float phi = urand.x * M_TAU;
+        const float3 h = mul(basis, float3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta));
+        const float3 l = reflect(-normal, h);
+
+        const float ndotl = dot(normal, l);
+        if (ndotl > 0.0) {
+            // With n = v, the pdf of `l` is D(h) / 4
+            const float pdf = ggx_ndf(a2, cos_theta) * 0.25;
+            const float sample_solid_angle = 1.0 / (sample_count * pdf + 1e-5);
+            const float lod = clamp(
+                0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0,
+                0.0,
+                input_levels - 1.0
+            );
+
+            result += input_tex.SampleLevel(sampler_llr, l, lod) * ndotl;
+            weight_sum += ndotl;
+        }
+    }
+
+    output_tex[px] = result / max(1e-5, weight_sum);
+}
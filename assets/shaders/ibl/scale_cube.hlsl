@@ -0,0 +1,10 @@
+[[vk::binding(0)]] Texture2DArray<float4> input_tex;
+[[vk::binding(1)]] RWTexture2DArray<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float multiplier;
+}
+
+[numthreads(8, 8, 1)]
+void main(in uint3 px : SV_DispatchThreadID) {
+    output_tex[px] = float4(input_tex[px].rgb * multiplier, 1);
+}
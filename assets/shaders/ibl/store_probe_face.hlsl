@@ -0,0 +1,19 @@
+// Copies a face rendered by an environment probe into its cube.
+//
+// The face cameras look the same way as `CUBE_MAP_FACE_ROTATIONS`, but their images are
+// mirrored horizontally in cube map space, which is undone here. A mirrored projection would
+// instead flip the winding of triangles, and thus which of their faces get culled.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2DArray<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    uint face_width;
+    uint face;
+    float multiplier;
+}
+
+[numthreads(8, 8, 1)]
+void main(in uint2 px : SV_DispatchThreadID) {
+    const float3 radiance = input_tex[uint2(face_width - 1 - px.x, px.y)].rgb;
+    output_tex[uint3(px, face)] = float4(radiance * multiplier, 1);
+}
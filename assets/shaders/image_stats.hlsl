@@ -0,0 +1,72 @@
+#include "inc/frame_constants.hlsl"
+#include "inc/color/srgb.hlsl"
+#include "inc/math_const.hlsl"
+
+// Must match `IMAGE_STATS_BIN_COUNT` in `image_stats.rs`
+#define IMAGE_STATS_BIN_COUNT 256
+
+#define GROUP_SIZE 16
+#define GROUP_THREAD_COUNT (GROUP_SIZE * GROUP_SIZE)
+
+#define FLAG_REMOVE_PRE_EXPOSURE 1
+#define FLAG_LOG_HISTOGRAM 2
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+
+// The histogram bins, followed by (min, max, sum, count) per group, as floats.
+[[vk::binding(1)]] RWByteAddressBuffer output_buf;
+
+[[vk::binding(2)]] cbuffer _ {
+    uint2 input_extent;
+    // 0-3: a single channel; 4: luminance
+    uint channel;
+    uint flags;
+    float histogram_min;
+    float histogram_max;
+    uint group_count_x;
+};
+
+groupshared float4 partials[GROUP_THREAD_COUNT];
+
+float4 combine(float4 a, float4 b) {
+    return float4(min(a.x, b.x), max(a.y, b.y), a.z + b.z, a.w + b.w);
+}
+
+[numthreads(GROUP_SIZE, GROUP_SIZE, 1)]
+void main(uint2 px: SV_DispatchThreadID, uint2 group_id: SV_GroupID, uint idx_within_group: SV_GroupIndex) {
+    float4 stats = float4(FLT_MAX, -FLT_MAX, 0, 0);
+
+    if (all(px < input_extent)) {
+        const float4 texel = input_tex[px];
+        float value = channel < 4 ? texel[channel] : sRGB_to_luminance(texel.rgb);
+
+        if (flags & FLAG_REMOVE_PRE_EXPOSURE) {
+            value /= frame_constants.pre_exposure;
+        }
+
+        if (!isnan(value) && !isinf(value)) {
+            stats = float4(value, value, value, 1);
+
+            const float binned = (flags & FLAG_LOG_HISTOGRAM) ? log2(max(1e-20, value)) : value;
+            const float t = saturate((binned - histogram_min) / (histogram_max - histogram_min));
+            const uint bin = min(uint(t * IMAGE_STATS_BIN_COUNT), IMAGE_STATS_BIN_COUNT - 1);
+
+            output_buf.InterlockedAdd(bin * 4, 1);
+        }
+    }
+
+    partials[idx_within_group] = stats;
+    GroupMemoryBarrierWithGroupSync();
+
+    for (uint stride = GROUP_THREAD_COUNT / 2; stride > 0; stride /= 2) {
+        if (idx_within_group < stride) {
+            partials[idx_within_group] = combine(partials[idx_within_group], partials[idx_within_group + stride]);
+        }
+        GroupMemoryBarrierWithGroupSync();
+    }
+
+    if (idx_within_group == 0) {
+        const uint group_idx = group_id.y * group_count_x + group_id.x;
+        output_buf.Store4((IMAGE_STATS_BIN_COUNT + group_idx * 4) * 4, asuint(partials[0]));
+    }
+}
@@ -1,27 +1,226 @@
 #ifndef ATMOSPHERE_HLSL
 #define ATMOSPHERE_HLSL
 
-#include "atmosphere_felix.hlsl"
 #include "frame_constants.hlsl"
+#include "working_primaries.hlsl"
+#include "bindless_textures.hlsl"
+#include "samplers.hlsl"
+#include "math_const.hlsl"
 
+// Physically based atmosphere with precomputed transmittance and multiple scattering, after
+// "A Scalable and Production Ready Sky and Atmosphere Rendering Technique" by Sébastien Hillaire.
+//
+// The planet is centered at the origin of "atmosphere space", with distances in meters.
+// The viewer stands on the ground, so the sky doesn't depend on the camera position.
+
+static const uint2 ATMOSPHERE_TRANSMITTANCE_LUT_DIMS = uint2(256, 64);
+static const uint2 ATMOSPHERE_MULTIPLE_SCATTERING_LUT_DIMS = uint2(32, 32);
+
+// Illuminance of the sun at the top of the atmosphere, before `sun_color_multiplier`.
+static const float ATMOSPHERE_SUN_ILLUMINANCE = 20.0;
+
+static const float ATMOSPHERE_VIEWER_ALTITUDE = 1.0;
+
+float atmosphere_bottom_radius() {
+    return frame_constants.atmosphere.planet_radius;
+}
+
+float atmosphere_top_radius() {
+    return frame_constants.atmosphere.planet_radius + frame_constants.atmosphere.atmosphere_height;
+}
+
+float3 atmosphere_viewer_position() {
+    return float3(0, atmosphere_bottom_radius() + ATMOSPHERE_VIEWER_ALTITUDE, 0);
+}
+
+// Distances to the entry and exit points of a sphere at the origin, or negative if missed.
+float2 atmosphere_ray_sphere(float3 origin, float3 dir, float radius) {
+    const float b = dot(origin, dir);
+    const float c = dot(origin, origin) - radius * radius;
+    const float d = b * b - c;
+
+    if (d < 0.0) {
+        return -1.0;
+    }
+
+    const float sqrt_d = sqrt(d);
+    return float2(-b - sqrt_d, -b + sqrt_d);
+}
+
+bool atmosphere_ray_hits_ground(float3 origin, float3 dir) {
+    return atmosphere_ray_sphere(origin, dir, atmosphere_bottom_radius()).x > 0.0;
+}
+
+// Distance to the ground, or to the top of the atmosphere if the ground is not hit.
+float atmosphere_ray_length(float3 origin, float3 dir) {
+    const float2 ground = atmosphere_ray_sphere(origin, dir, atmosphere_bottom_radius());
+    if (ground.x > 0.0) {
+        return ground.x;
+    }
+
+    return max(0.0, atmosphere_ray_sphere(origin, dir, atmosphere_top_radius()).y);
+}
+
+struct AtmosphereMedium {
+    float3 rayleigh_scattering;
+    float3 mie_scattering;
+    float3 extinction;
+
+    float3 scattering() {
+        return rayleigh_scattering + mie_scattering;
+    }
+
+    static AtmosphereMedium at(float3 pos) {
+        const AtmosphereConstants atmosphere = frame_constants.atmosphere;
+        const float altitude = max(0.0, length(pos) - atmosphere.planet_radius);
+
+        const float rayleigh_density = exp(-altitude / atmosphere.rayleigh_scale_height);
+        const float mie_density = exp(-altitude / atmosphere.mie_scale_height);
+        const float ozone_density = max(0.0, 1.0 - abs(altitude - atmosphere.ozone_center_altitude) / atmosphere.ozone_half_width);
+
+        AtmosphereMedium res;
+        res.rayleigh_scattering = atmosphere.rayleigh_scattering.rgb * rayleigh_density;
+        res.mie_scattering = atmosphere.mie_scattering.rgb * mie_density;
+        res.extinction =
+            res.rayleigh_scattering
+            + res.mie_scattering
+            + atmosphere.mie_absorption.rgb * mie_density
+            + atmosphere.ozone_absorption.rgb * ozone_density;
+        return res;
+    }
+};
+
+float atmosphere_rayleigh_phase(float cos_theta) {
+    return 3.0 * (1.0 + cos_theta * cos_theta) / (16.0 * M_PI);
+}
+
+// Cornette-Shanks
+float atmosphere_mie_phase(float cos_theta, float g) {
+    const float g2 = g * g;
+    const float k = 3.0 / (8.0 * M_PI) * (1.0 - g2) / (2.0 + g2);
+    return k * (1.0 + cos_theta * cos_theta) / pow(max(1e-5, 1.0 + g2 - 2.0 * g * cos_theta), 1.5);
+}
+
+// Mapping of the transmittance LUT from "Precomputed Atmospheric Scattering" by Bruneton and Neyret.
+// Only covers rays which don't hit the ground.
+float2 atmosphere_transmittance_lut_uv(float r, float mu) {
+    const float bottom = atmosphere_bottom_radius();
+    const float top = atmosphere_top_radius();
+
+    const float h = sqrt(max(0.0, top * top - bottom * bottom));
+    const float rho = sqrt(max(0.0, r * r - bottom * bottom));
+
+    const float discriminant = r * r * (mu * mu - 1.0) + top * top;
+    const float d = max(0.0, -r * mu + sqrt(max(0.0, discriminant)));
+
+    const float d_min = top - r;
+    const float d_max = rho + h;
+
+    return float2((d - d_min) / max(1e-5, d_max - d_min), rho / h);
+}
+
+// Returns the radius and the cosine of the zenith angle.
+float2 atmosphere_transmittance_lut_r_mu(float2 uv) {
+    const float bottom = atmosphere_bottom_radius();
+    const float top = atmosphere_top_radius();
+
+    const float h = sqrt(max(0.0, top * top - bottom * bottom));
+    const float rho = h * uv.y;
+    const float r = sqrt(rho * rho + bottom * bottom);
+
+    const float d_min = top - r;
+    const float d_max = rho + h;
+    const float d = d_min + uv.x * (d_max - d_min);
+
+    const float mu = d == 0.0 ? 1.0 : (h * h - rho * rho - d * d) / (2.0 * r * d);
+    return float2(r, clamp(mu, -1.0, 1.0));
+}
+
+// Transmittance from `pos` to the top of the atmosphere along `dir`, by ray marching.
+// Used to compute the LUT; `atmosphere_transmittance` samples it instead.
+float3 atmosphere_integrate_transmittance(float3 pos, float3 dir) {
+    static const uint STEP_COUNT = 40;
+
+    const float ray_length = max(0.0, atmosphere_ray_sphere(pos, dir, atmosphere_top_radius()).y);
+    const float dt = ray_length / STEP_COUNT;
+
+    float3 optical_depth = 0.0;
+    for (uint i = 0; i < STEP_COUNT; ++i) {
+        optical_depth += AtmosphereMedium::at(pos + dir * (i + 0.5) * dt).extinction * dt;
+    }
+
+    return exp(-optical_depth);
+}
+
+// Transmittance from `pos` towards a light in direction `dir`, which is zero if the planet is in the way.
+float3 atmosphere_transmittance(float3 pos, float3 dir) {
+    if (atmosphere_ray_hits_ground(pos, dir)) {
+        return 0.0;
+    }
+
+    const float r = length(pos);
+    const float2 uv = atmosphere_transmittance_lut_uv(r, dot(pos, dir) / r);
+    return bindless_textures[BINDLESS_LUT_ATMOSPHERE_TRANSMITTANCE].SampleLevel(sampler_llc, uv, 0).rgb;
+}
+
+float2 atmosphere_multiple_scattering_lut_uv(float3 pos, float3 sun_dir) {
+    const float r = length(pos);
+    const float altitude = r - atmosphere_bottom_radius();
+    return saturate(float2(
+        dot(pos / r, sun_dir) * 0.5 + 0.5,
+        altitude / frame_constants.atmosphere.atmosphere_height
+    ));
+}
+
+// Radiance scattered towards `pos` by all orders of scattering past the first,
+// for unit illuminance, and per unit of the scattering coefficient.
+float3 atmosphere_multiple_scattering(float3 pos, float3 sun_dir) {
+    const float2 uv = atmosphere_multiple_scattering_lut_uv(pos, sun_dir);
+    return bindless_textures[BINDLESS_LUT_ATMOSPHERE_MULTIPLE_SCATTERING].SampleLevel(sampler_llc, uv, 0).rgb;
+}
+
+// Radiance of the sky in direction `wi` as seen from the ground,
+// with the sun in direction `light_dir`, and including the ambient term.
 float3 atmosphere_default(float3 wi, float3 light_dir) {
-    //return max(0.0, normalize(wi) * 0.5 + 0.5);
-    //return 0.5 * frame_constants.pre_exposure;
-    //return 0;
+    static const uint STEP_COUNT = 32;
+
+    const float3 ray_start = atmosphere_viewer_position();
+    const float3 ray_dir = normalize(wi);
+    const float ray_length = atmosphere_ray_length(ray_start, ray_dir);
+
+    const float cos_theta = dot(ray_dir, light_dir);
+    const float rayleigh_phase = atmosphere_rayleigh_phase(cos_theta);
+    const float mie_phase = atmosphere_mie_phase(cos_theta, frame_constants.atmosphere.mie_g);
+
+    float3 radiance = 0.0;
+    float3 transmittance = 1.0;
+    float prev_t = 0.0;
+
+    for (uint i = 1; i <= STEP_COUNT; ++i) {
+        // Distribute the samples quadratically, as most of the scattering happens close to the viewer.
+        const float t = ray_length * (float(i) / STEP_COUNT) * (float(i) / STEP_COUNT);
+        const float dt = t - prev_t;
+        const float3 pos = ray_start + ray_dir * lerp(prev_t, t, 0.5);
+        prev_t = t;
+
+        const AtmosphereMedium medium = AtmosphereMedium::at(pos);
+        const float3 sun_transmittance = atmosphere_transmittance(pos, light_dir);
 
-    float3 _WorldSpaceCameraPos = float3(0, 0, 0);
-    float3 rayStart  = _WorldSpaceCameraPos;
-    float3 rayDir    = wi;
-    float  rayLength = INFINITY;
+        const float3 single_scattering =
+            (medium.rayleigh_scattering * rayleigh_phase + medium.mie_scattering * mie_phase) * sun_transmittance;
+        const float3 multiple_scattering = medium.scattering() * atmosphere_multiple_scattering(pos, light_dir);
 
-    float3 lightDir   = light_dir;
-    float3 lightColor = 1.0.xxx;
+        // Analytic integration of the in-scattering over the step, assuming constant density.
+        const float3 step_transmittance = exp(-medium.extinction * dt);
+        const float3 in_scattering = single_scattering + multiple_scattering;
+        radiance += transmittance * (in_scattering - in_scattering * step_transmittance) / max(1e-10, medium.extinction);
+        transmittance *= step_transmittance;
+    }
 
-    float3 transmittance;
-    return
-        (frame_constants.sky_ambient.rgb +
-        frame_constants.sun_color_multiplier.rgb *
-            IntegrateScattering(rayStart, rayDir, rayLength, lightDir, lightColor, transmittance)) * frame_constants.pre_exposure;
+    return rec709_to_working(
+        frame_constants.sky_ambient.rgb
+        + frame_constants.sun_color_multiplier.rgb * ATMOSPHERE_SUN_ILLUMINANCE * radiance)
+        * frame_constants.pre_exposure;
 }
 
-#endif
\ No newline at end of file
+#endif
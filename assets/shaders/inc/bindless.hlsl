@@ -2,4 +2,5 @@
 
 [[vk::binding(0, 1)]] StructuredBuffer<Mesh> meshes;
 [[vk::binding(1, 1)]] ByteAddressBuffer vertices;
+#include "mesh_vertex.hlsl"
 #include "bindless_textures.hlsl"
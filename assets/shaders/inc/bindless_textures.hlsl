@@ -11,4 +11,11 @@ static const uint BINDLESS_LUT_BLUE_NOISE_256_LDR_RGBA_0 = 1;
 
 static const uint BINDLESS_LUT_BEZOLD_BRUCKE = 2;
 
+// Inverse LTC matrices for the GGX BRDF; see `ltc.hlsl`
+static const uint BINDLESS_LUT_LTC_GGX_INV_MATRIX = 3;
+
+// Precomputed atmosphere; see `atmosphere.hlsl`
+static const uint BINDLESS_LUT_ATMOSPHERE_TRANSMITTANCE = 4;
+static const uint BINDLESS_LUT_ATMOSPHERE_MULTIPLE_SCATTERING = 5;
+
 #endif
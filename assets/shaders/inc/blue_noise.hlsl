@@ -14,6 +14,12 @@ float4 blue_noise_for_pixel(uint2 px, uint n) {
     ] * 255.0 / 256.0 + 0.5 / 256.0;
 }
 
+// Pass `frame_constants.blue_noise_rotation`. Unlike shifting the texture every frame,
+// this keeps each pixel's values over time well distributed, which temporal filters converge on faster.
+float4 blue_noise_for_pixel_animated(uint2 px, float rotation) {
+    return frac(blue_noise_for_pixel(px, 0) + rotation);
+}
+
 // ----
 // https://crates.io/crates/blue-noise-sampler
 
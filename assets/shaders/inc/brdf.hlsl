@@ -307,6 +307,44 @@ struct SpecularBrdf {
 
         return res;
 	}
+
+    static float ggx_aniso_lambda(float3 w, float alpha_x, float alpha_y) {
+        const float a2_tan2 = (square(alpha_x * w.x) + square(alpha_y * w.y)) / square(w.z);
+        return 0.5 * (sqrt(1.0 + a2_tan2) - 1.0);
+    }
+
+    // Like `evaluate`, but with the roughness stretched along the tangent-space X axis,
+    // and shrunk along Y, by `anisotropy` in the [0, 1] range.
+    // https://blog.selfshadow.com/publications/s2017-shading-course/imageworks/s2017_pbs_imageworks_slides_v2.pdf
+    BrdfValue evaluate_anisotropic(float3 wo, float3 wi, float anisotropy) {
+        if (wi.z <= 0.0 || wo.z <= 0.0) {
+            return BrdfValue::invalid();
+        }
+
+        const float alpha_x = clamp(roughness * (1.0 + anisotropy), 1e-4, 1.0);
+        const float alpha_y = clamp(roughness * (1.0 - anisotropy), 1e-4, 1.0);
+
+        const float3 m = normalize(wo + wi);
+        const float3 m_stretched = float3(m.x / alpha_x, m.y / alpha_y, m.z);
+        const float ndf_denom_sqrt = dot(m_stretched, m_stretched);
+        const float ndf = 1.0 / (M_PI * alpha_x * alpha_y * ndf_denom_sqrt * ndf_denom_sqrt);
+
+        const float lambda_wo = ggx_aniso_lambda(wo, alpha_x, alpha_y);
+        const float lambda_wi = ggx_aniso_lambda(wi, alpha_x, alpha_y);
+        const float g1_wo = 1.0 / (1.0 + lambda_wo);
+        const float g = 1.0 / (1.0 + lambda_wo + lambda_wi);
+
+        const float jacobian = 1.0 / (4.0 * dot(wi, m));
+        const float3 fresnel = eval_fresnel_schlick(albedo, 1.0, dot(m, wi));
+
+        BrdfValue res;
+        res.pdf = g1_wo * ndf * max(0.0, dot(wo, m)) / wo.z * jacobian / wi.z;
+        res.transmission_fraction = 1.0.xxx - fresnel;
+        res.value_over_pdf = fresnel * g / g1_wo;
+        res.value = fresnel * g * ndf / (4 * wo.z * wi.z);
+
+        return res;
+    }
 };
 
 // https://seblagarde.files.wordpress.com/2015/07/course_notes_moving_frostbite_to_pbr_v32.pdf
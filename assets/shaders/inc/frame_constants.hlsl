@@ -22,6 +22,9 @@ struct ViewConstants {
 
     float2 sample_offset_pixels;
     float2 sample_offset_clip;
+
+    // World-space; zero disables clipping.
+    float4 clip_plane;
 };
 
 struct GiCascadeConstants {
@@ -57,6 +60,24 @@ struct RenderOverrides {
     }
 };
 
+// Must match `AtmosphereConstants` in `atmosphere.rs`. Distances are in meters.
+struct AtmosphereConstants {
+    float4 rayleigh_scattering;
+    float4 mie_scattering;
+    float4 mie_absorption;
+    float4 ozone_absorption;
+
+    float planet_radius;
+    float atmosphere_height;
+    float rayleigh_scale_height;
+    float mie_scale_height;
+
+    float mie_g;
+    float ozone_center_altitude;
+    float ozone_half_width;
+    float ground_albedo;
+};
+
 struct FrameConstants {
     ViewConstants view_constants;
 
@@ -73,10 +94,18 @@ struct FrameConstants {
     float pre_exposure;
     float pre_exposure_prev;
     float pre_exposure_delta;
-    float pad0;
+    uint punctual_light_count;
 
     RenderOverrides render_overrides;
 
+    uint rect_light_count;
+    float blue_noise_rotation;
+    uint working_color_space;
+    // How much the lighting changed since the previous frame, from zero to one.
+    float lighting_change;
+
+    AtmosphereConstants atmosphere;
+
     float4 ircache_grid_center;
     IrcacheCascadeConstants ircache_cascades[12];
 };
@@ -84,11 +113,24 @@ struct FrameConstants {
 [[vk::binding(0, 2)]] ConstantBuffer<FrameConstants> frame_constants;
 
 struct InstanceDynamicConstants {
+    float3 base_color_tint;
     float emissive_multiplier;
+    float roughness_multiplier;
+    float3 pad;
 };
 
 [[vk::binding(1, 2)]] StructuredBuffer<InstanceDynamicConstants> instance_dynamic_parameters_dyn;
 [[vk::binding(2, 2)]] StructuredBuffer<TriangleLightPacked> triangle_lights_dyn;
+[[vk::binding(3, 2)]] StructuredBuffer<PunctualLightPacked> punctual_lights_dyn;
+[[vk::binding(4, 2)]] StructuredBuffer<RectLightPacked> rect_lights_dyn;
+
+// Direction from the near to the far point of a ray, scaled to a view-space Z of -1.
+// Valid for any projection: the far point is at infinity in perspective ones,
+// but not in orthographic ones.
+float4 ray_dir_vs_between(float4 near_vs_h, float4 far_vs_h) {
+    const float3 dir = far_vs_h.xyz * near_vs_h.w - near_vs_h.xyz * far_vs_h.w;
+    return float4(dir / -dir.z, 0.0);
+}
 
 struct ViewRayContext {
     float4 ray_dir_cs;
@@ -144,14 +186,14 @@ struct ViewRayContext {
         ViewConstants view_constants = frame_constants.view_constants;
 
         ViewRayContext res;
-        res.ray_dir_cs = float4(uv_to_cs(uv), 0.0, 1.0);
-        res.ray_dir_vs_h = mul(view_constants.sample_to_view, res.ray_dir_cs);
-        res.ray_dir_ws_h = mul(view_constants.view_to_world, res.ray_dir_vs_h);
-
         res.ray_origin_cs = float4(uv_to_cs(uv), 1.0, 1.0);
         res.ray_origin_vs_h = mul(view_constants.sample_to_view, res.ray_origin_cs);
         res.ray_origin_ws_h = mul(view_constants.view_to_world, res.ray_origin_vs_h);
 
+        res.ray_dir_cs = float4(uv_to_cs(uv), 0.0, 1.0);
+        res.ray_dir_vs_h = ray_dir_vs_between(res.ray_origin_vs_h, mul(view_constants.sample_to_view, res.ray_dir_cs));
+        res.ray_dir_ws_h = mul(view_constants.view_to_world, res.ray_dir_vs_h);
+
         return res;
     }
 
@@ -159,14 +201,14 @@ struct ViewRayContext {
         ViewConstants view_constants = frame_constants.view_constants;
 
         ViewRayContext res;
-        res.ray_dir_cs = float4(uv_to_cs(uv), 0.0, 1.0);
-        res.ray_dir_vs_h = mul(view_constants.sample_to_view, res.ray_dir_cs);
-        res.ray_dir_ws_h = mul(view_constants.view_to_world, res.ray_dir_vs_h);
-
         res.ray_origin_cs = float4(uv_to_cs(uv), 1.0, 1.0);
         res.ray_origin_vs_h = mul(view_constants.sample_to_view, res.ray_origin_cs);
         res.ray_origin_ws_h = mul(view_constants.view_to_world, res.ray_origin_vs_h);
 
+        res.ray_dir_cs = float4(uv_to_cs(uv), 0.0, 1.0);
+        res.ray_dir_vs_h = ray_dir_vs_between(res.ray_origin_vs_h, mul(view_constants.sample_to_view, res.ray_dir_cs));
+        res.ray_dir_ws_h = mul(view_constants.view_to_world, res.ray_dir_vs_h);
+
         res.ray_hit_cs = float4(uv_to_cs(uv), depth, 1.0);
         res.ray_hit_vs_h = mul(view_constants.sample_to_view, res.ray_hit_cs);
         res.ray_hit_ws_h = mul(view_constants.view_to_world, res.ray_hit_vs_h);
@@ -190,7 +232,18 @@ float3 get_prev_eye_position() {
 }
 
 float depth_to_view_z(float depth) {
-    return rcp(depth * -frame_constants.view_constants.clip_to_view._43);
+    const float2 view_zw = mul(frame_constants.view_constants.clip_to_view, float4(0.0, 0.0, depth, 1.0)).zw;
+    return view_zw.x / view_zw.y;
+}
+
+// Clip-space W doesn't depend on view-space Z in parallel projections.
+bool is_orthographic_projection() {
+    return frame_constants.view_constants.view_to_clip._43 == 0.0;
+}
+
+// For `SV_ClipDistance`; negative on the side of the clip plane which isn't rendered.
+float view_clip_distance(float3 pos_ws) {
+    return dot(frame_constants.view_constants.clip_plane, float4(pos_ws, 1.0));
 }
 
 float3 direction_view_to_world(float3 v) {
@@ -222,12 +275,18 @@ float3 position_world_to_sample(float3 v) {
 }
 
 float pixel_cone_spread_angle_from_image_height(float image_height) {
+    if (is_orthographic_projection()) {
+        return 0.0;
+    }
+
     return atan(2.0 * frame_constants.view_constants.clip_to_view._11 / image_height);
 }
 
 RayCone pixel_ray_cone_from_image_height(float image_height) {
     RayCone res;
-    res.width = 0.0;
+    res.width = is_orthographic_projection()
+        ? 2.0 * frame_constants.view_constants.clip_to_view._22 / image_height
+        : 0.0;
     res.spread_angle = pixel_cone_spread_angle_from_image_height(image_height);
     return res;
 }
@@ -1,8 +1,18 @@
 #ifndef GBUFFER_HLSL
 #define GBUFFER_HLSL
 
+#include "math.hlsl"
 #include "pack_unpack.hlsl"
 
+// `GBUFFER_NORMAL_ENCODING`, `GBUFFER_ROUGHNESS_BITS`, `GBUFFER_METALNESS_BITS`, and
+// `GBUFFER_MATERIAL_ID`, generated from `GbufferLayout` in `gbuffer_layout.rs`.
+#include "/generated/gbuffer_layout.hlsl"
+
+// Must match `GbufferNormalEncoding::shader_index`
+#define GBUFFER_NORMAL_ENCODING_XYZ_11_10_11 0
+#define GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_16 1
+#define GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_32 2
+
 struct GbufferData;
 
 struct GbufferDataPacked {
@@ -18,6 +28,8 @@ struct GbufferDataPacked {
     float3 unpack_normal();
     float3 unpack_albedo();
     float3 unpack_emissive();
+    float unpack_subsurface();
+    uint unpack_subsurface_profile();
 };
 
 struct GbufferData {
@@ -26,6 +38,19 @@ struct GbufferData {
     float3 normal;
     float roughness;
     float metalness;
+    float clearcoat;
+    float clearcoat_roughness;
+    float transmission;
+    float anisotropy;
+
+    // Direction of anisotropy, as an angle in the `build_orthonormal_basis(normal)` frame.
+    // Only defined modulo PI.
+    float anisotropy_angle;
+
+    // Strength of the screen-space subsurface scattering, and the index of its profile;
+    // see `subsurface_scattering.hlsl`.
+    float subsurface;
+    uint subsurface_profile;
 
     static GbufferData create_zero() {
         GbufferData res;
@@ -34,9 +59,19 @@ struct GbufferData {
         res.normal = 0;
         res.roughness = 0;
         res.metalness = 0;
+        res.clearcoat = 0;
+        res.clearcoat_roughness = 0;
+        res.transmission = 0;
+        res.anisotropy = 0;
+        res.anisotropy_angle = 0;
+        res.subsurface = 0;
+        res.subsurface_profile = 0;
         return res;
     }
 
+    // Sets `anisotropy_angle`; must be called after `normal` is assigned.
+    void set_anisotropy_direction(float3 direction_ws);
+
     GbufferDataPacked pack();
 };
 
@@ -48,13 +83,70 @@ float perceptual_roughness_to_roughness(float r) {
     return r * r;
 }
 
+// Like `pack_unorm`, but rounds to the nearest representable value.
+uint pack_unorm_rounded(float val, uint bit_count) {
+    const uint max_val = (1u << bit_count) - 1;
+    return uint(saturate(val) * max_val + 0.5);
+}
+
+static const uint GBUFFER_ANISOTROPY_ANGLE_BITS = 6;
+static const uint GBUFFER_SUBSURFACE_PROFILE_BITS = 3;
+static const uint GBUFFER_SUBSURFACE_PROFILE_COUNT = 1u << GBUFFER_SUBSURFACE_PROFILE_BITS;
+
+uint gbuffer_pack_normal(float3 normal) {
+#if GBUFFER_NORMAL_ENCODING == GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_16
+    const float2 oct = octa_encode(normal);
+    return pack_unorm_rounded(oct.x, 8) | (pack_unorm_rounded(oct.y, 8) << 8);
+#elif GBUFFER_NORMAL_ENCODING == GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_32
+    const float2 oct = octa_encode(normal);
+    return pack_unorm_rounded(oct.x, 16) | (pack_unorm_rounded(oct.y, 16) << 16);
+#else
+    return asuint(pack_normal_11_10_11(normal));
+#endif
+}
+
+float3 gbuffer_unpack_normal(uint packed) {
+#if GBUFFER_NORMAL_ENCODING == GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_16
+    return octa_decode(float2(unpack_unorm(packed, 8), unpack_unorm(packed >> 8, 8)));
+#elif GBUFFER_NORMAL_ENCODING == GBUFFER_NORMAL_ENCODING_OCTAHEDRAL_32
+    return octa_decode(float2(unpack_unorm(packed, 16), unpack_unorm(packed >> 16, 16)));
+#else
+    return unpack_normal_11_10_11(asfloat(packed));
+#endif
+}
+
+void GbufferData::set_anisotropy_direction(float3 direction_ws) {
+    const float2 dir = mul(direction_ws, build_orthonormal_basis(normal)).xy;
+    anisotropy_angle = atan2(dir.y, dir.x);
+    if (anisotropy_angle < 0.0) {
+        anisotropy_angle += M_PI;
+    }
+}
+
 GbufferDataPacked GbufferData::pack() {
     float4 res = 0.0.xxxx;
-    res.x = asfloat(pack_color_888(albedo));
-    res.y = pack_normal_11_10_11(normal);
-
-    float2 roughness_metalness = float2(roughness_to_perceptual_roughness(roughness), metalness);
-    res.z = asfloat(pack_2x16f_uint(roughness_metalness));
+    // Subsurface scattering in the spare top byte: strength: 5, profile: 3
+    res.x = asfloat(
+        pack_color_888(albedo)
+        | (pack_unorm_rounded(subsurface, 5) << 24)
+        | (min(subsurface_profile, GBUFFER_SUBSURFACE_PROFILE_COUNT - 1) << 29)
+    );
+    res.y = asfloat(gbuffer_pack_normal(normal));
+
+    // Material parameters packed into 32 bits:
+    // perceptual roughness and metalness: 14 between them (8 and 6 by default), clearcoat: 3,
+    // clearcoat perceptual roughness: 3, transmission: 3, anisotropy: 3, anisotropy angle: 6
+    const uint angle_steps = 1u << GBUFFER_ANISOTROPY_ANGLE_BITS;
+    const uint packed_anisotropy_angle = uint(anisotropy_angle * M_FRAC_1_PI * angle_steps + 0.5) % angle_steps;
+    res.z = asfloat(
+        pack_unorm_rounded(roughness_to_perceptual_roughness(roughness), GBUFFER_ROUGHNESS_BITS)
+        | (pack_unorm_rounded(metalness, GBUFFER_METALNESS_BITS) << GBUFFER_ROUGHNESS_BITS)
+        | (pack_unorm_rounded(clearcoat, 3) << 14)
+        | (pack_unorm_rounded(roughness_to_perceptual_roughness(clearcoat_roughness), 3) << 17)
+        | (pack_unorm_rounded(transmission, 3) << 20)
+        | (pack_unorm_rounded(anisotropy, 3) << 23)
+        | (packed_anisotropy_angle << 26)
+    );
     res.w = asfloat(float3_to_rgb9e5(emissive));
 
    GbufferDataPacked packed;
@@ -67,16 +159,23 @@ GbufferData GbufferDataPacked::unpack() {
     res.albedo = unpack_albedo();
     res.normal = unpack_normal();
 
-    float2 roughness_metalness = unpack_2x16f_uint(data0.z);
-    res.roughness = perceptual_roughness_to_roughness(roughness_metalness.x);
-    res.metalness = roughness_metalness.y;
+    const uint material = data0.z;
+    res.roughness = perceptual_roughness_to_roughness(unpack_unorm(material, GBUFFER_ROUGHNESS_BITS));
+    res.metalness = unpack_unorm(material >> GBUFFER_ROUGHNESS_BITS, GBUFFER_METALNESS_BITS);
+    res.clearcoat = unpack_unorm(material >> 14, 3);
+    res.clearcoat_roughness = perceptual_roughness_to_roughness(unpack_unorm(material >> 17, 3));
+    res.transmission = unpack_unorm(material >> 20, 3);
+    res.anisotropy = unpack_unorm(material >> 23, 3);
+    res.anisotropy_angle = float(material >> 26) * M_PI / (1u << GBUFFER_ANISOTROPY_ANGLE_BITS);
     res.emissive = unpack_emissive();
+    res.subsurface = unpack_subsurface();
+    res.subsurface_profile = unpack_subsurface_profile();
 
     return res;
 }
 
 float3 GbufferDataPacked::unpack_normal() {
-    return unpack_normal_11_10_11(asfloat(data0.y));
+    return gbuffer_unpack_normal(data0.y);
 }
 
 float3 GbufferDataPacked::unpack_albedo() {
@@ -87,4 +186,12 @@ float3 GbufferDataPacked::unpack_emissive() {
     return rgb9e5_to_float3(data0.w);
 }
 
+float GbufferDataPacked::unpack_subsurface() {
+    return unpack_unorm(data0.x >> 24, 5);
+}
+
+uint GbufferDataPacked::unpack_subsurface_profile() {
+    return data0.x >> 29;
+}
+
 #endif
\ No newline at end of file
@@ -0,0 +1,48 @@
+#ifndef GPU_DEBUG_HLSL
+#define GPU_DEBUG_HLSL
+
+// Defined by the shader compiler; `printf` needs the validation layers, and a debug build.
+#ifndef KAJIYA_DEBUG_PRINTF
+    #define KAJIYA_DEBUG_PRINTF 0
+#endif
+
+// Logged along with the name of the render graph pass, e.g.:
+//     DEBUG_PRINTF("sample count %d, radiance %f", sample_count, radiance.x);
+// Mind the volume: print from a single pixel or thread.
+#if KAJIYA_DEBUG_PRINTF
+    #define DEBUG_PRINTF(...) printf(__VA_ARGS__)
+#else
+    #define DEBUG_PRINTF(...)
+#endif
+
+// Checked by the CPU once the frame is done; see `gpu_asserts.rs`.
+[[vk::binding(5, 2)]] RWByteAddressBuffer gpu_asserts_dyn;
+
+// Must match `gpu_asserts.rs`
+#define GPU_ASSERT_FAILURE_COUNT_OFFSET 0
+#define GPU_ASSERT_FIRST_LINE_OFFSET 4
+#define GPU_ASSERT_FIRST_VALUE_OFFSET 8
+
+void gpu_assert_failed(uint line, int value) {
+    uint prev_failure_count;
+    gpu_asserts_dyn.InterlockedAdd(GPU_ASSERT_FAILURE_COUNT_OFFSET, 1, prev_failure_count);
+
+    if (0 == prev_failure_count) {
+        gpu_asserts_dyn.Store(GPU_ASSERT_FIRST_LINE_OFFSET, line);
+        gpu_asserts_dyn.Store(GPU_ASSERT_FIRST_VALUE_OFFSET, asuint(value));
+    }
+}
+
+// Counts the failures of `cond`, and keeps the line and `value` of the first one in the frame.
+// Lines are of the shader source with its includes expanded.
+#define GPU_ASSERT_VALUE(cond, value) \
+    do { \
+        if (!(cond)) { \
+            gpu_assert_failed(__LINE__, int(value)); \
+            DEBUG_PRINTF("GPU assert failed at line %d, value %d", __LINE__, int(value)); \
+        } \
+    } while (false)
+
+#define GPU_ASSERT(cond) GPU_ASSERT_VALUE(cond, 0)
+
+#endif  // GPU_DEBUG_HLSL
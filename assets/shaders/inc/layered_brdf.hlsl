@@ -37,11 +37,23 @@ void apply_metalness_to_brdfs(inout SpecularBrdf specular_brdf, inout DiffuseBrd
     #endif
 }
 
+// Sampling only considers the base specular and diffuse layers, ignoring anisotropy and the clearcoat.
 struct LayeredBrdf {
     SpecularBrdf specular_brdf;
     DiffuseBrdf diffuse_brdf;
     SpecularBrdfEnergyPreservation energy_preservation;
 
+    // Dielectric coat on top of the specular and diffuse layers, scaled by `clearcoat`.
+    float clearcoat;
+    SpecularBrdf clearcoat_brdf;
+
+    // Directional albedo of the coat. The layers underneath receive the complement.
+    float3 clearcoat_preintegrated_reflection;
+
+    // Stretches the specular lobe along `anisotropy_direction`, in the tangent frame.
+    float anisotropy;
+    float2 anisotropy_direction;
+
     static LayeredBrdf from_gbuffer_ndotv(
         GbufferData gbuffer,
         float ndotv
@@ -55,15 +67,59 @@ struct LayeredBrdf {
 
         apply_metalness_to_brdfs(specular_brdf, diffuse_brdf, gbuffer.metalness);
 
+        // Thin-walled transmission: light which would scatter diffusely passes through the surface instead.
+        diffuse_brdf.albedo *= 1.0 - gbuffer.transmission;
+
         LayeredBrdf res;
         res.energy_preservation =
             SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, ndotv);
 
         res.specular_brdf = specular_brdf;
         res.diffuse_brdf = diffuse_brdf;
+
+        res.clearcoat = gbuffer.clearcoat;
+        res.clearcoat_brdf.albedo = 0.04;
+        res.clearcoat_brdf.roughness = max(1e-3, gbuffer.clearcoat_roughness);
+        res.clearcoat_preintegrated_reflection = 0.0;
+
+        [branch]
+        if (gbuffer.clearcoat > 0.0) {
+            res.clearcoat_preintegrated_reflection = gbuffer.clearcoat
+                * SpecularBrdfEnergyPreservation::from_brdf_ndotv(res.clearcoat_brdf, ndotv).preintegrated_reflection;
+        }
+
+        res.anisotropy = gbuffer.anisotropy;
+        res.anisotropy_direction = float2(cos(gbuffer.anisotropy_angle), sin(gbuffer.anisotropy_angle));
+
         return res;
     }
 
+    BrdfValue evaluate_specular(float3 wo, float3 wi) {
+        [branch]
+        if (anisotropy > 0.0) {
+            // Rotate into a frame where the anisotropy direction is the X axis.
+            const float2x2 to_aniso_frame = float2x2(
+                anisotropy_direction.x, anisotropy_direction.y,
+                -anisotropy_direction.y, anisotropy_direction.x
+            );
+            wo.xy = mul(to_aniso_frame, wo.xy);
+            wi.xy = mul(to_aniso_frame, wi.xy);
+            return specular_brdf.evaluate_anisotropic(wo, wi, anisotropy);
+        }
+
+        return specular_brdf.evaluate(wo, wi);
+    }
+
+    float3 add_clearcoat(float3 base_value, float3 wo, float3 wi) {
+        [branch]
+        if (clearcoat > 0.0) {
+            const BrdfValue coat = clearcoat_brdf.evaluate(wo, wi);
+            return base_value * (1.0 - clearcoat_preintegrated_reflection) + clearcoat * coat.value;
+        }
+
+        return base_value;
+    }
+
     float3 evaluate(float3 wo, float3 wi) {
         if (wo.z <= 0 || wi.z <= 0) {
             return 0;
@@ -75,15 +131,16 @@ struct LayeredBrdf {
             return diff.value;
         #endif
 
-        const BrdfValue spec = specular_brdf.evaluate(wo, wi);
+        const BrdfValue spec = evaluate_specular(wo, wi);
 
         #if LAYERED_BRDF_FORCE_SPECULAR_ONLY
             return spec.value;
         #endif
 
-        return (
+        return add_clearcoat(
             spec.value * energy_preservation.preintegrated_reflection_mult +
-            diff.value * spec.transmission_fraction
+            diff.value * spec.transmission_fraction,
+            wo, wi
         );
     }
 
@@ -98,7 +155,7 @@ struct LayeredBrdf {
             return diff.value;
         #endif
 
-        const BrdfValue spec = specular_brdf.evaluate(wo, wi);
+        const BrdfValue spec = evaluate_specular(wo, wi);
 
         #if LAYERED_BRDF_FORCE_SPECULAR_ONLY
             return spec.value;
@@ -113,9 +170,10 @@ struct LayeredBrdf {
             //energy_preservation.preintegrated_reflection_mult;
             lerp(1.0, energy_preservation.preintegrated_reflection_mult, sqrt(abs(wi.z)));
 
-        return (
+        return add_clearcoat(
             spec.value * preintegrated_reflection_mult_directional +
-            diff.value * spec.transmission_fraction
+            diff.value * spec.transmission_fraction,
+            wo, wi
         );
     }
 
@@ -0,0 +1,40 @@
+#ifndef LIGHTS_CLUSTERS_HLSL
+#define LIGHTS_CLUSTERS_HLSL
+
+// Must match `LIGHT_CLUSTER_MAX_LIGHTS` in `light_clusters.rs`
+#define LIGHT_CLUSTER_MAX_LIGHTS 127
+
+// Each cluster's list is its light count, followed by the indices into `punctual_lights_dyn`.
+#define LIGHT_CLUSTER_STRIDE (LIGHT_CLUSTER_MAX_LIGHTS + 1)
+
+// Must match `GpuLightClusterConstants` in `light_clusters.rs`
+struct LightClusterConstants {
+    uint3 dims;
+    uint pad0;
+
+    float near_distance;
+    float far_distance;
+    uint2 pad1;
+
+    // Slices are distributed exponentially in view-space depth. The first one starts at the eye,
+    // and the last one at `far_distance`, extending to infinity.
+    uint depth_to_slice(float view_depth) {
+        const float w = log(max(view_depth, near_distance) / near_distance) / log(far_distance / near_distance);
+        return min(uint(max(0.0, w) * (dims.z - 1)), dims.z - 1);
+    }
+
+    float slice_start_depth(uint slice) {
+        return slice == 0 ? 0.0 : near_distance * pow(far_distance / near_distance, float(slice) / (dims.z - 1));
+    }
+
+    float slice_end_depth(uint slice) {
+        return slice + 1 >= dims.z ? 1e20 : slice_start_depth(slice + 1);
+    }
+
+    uint cluster_index(float2 uv, float view_depth) {
+        const uint2 tile = min(uint2(saturate(uv) * dims.xy), dims.xy - 1);
+        return (depth_to_slice(view_depth) * dims.y + tile.y) * dims.x + tile.x;
+    }
+};
+
+#endif  // LIGHTS_CLUSTERS_HLSL
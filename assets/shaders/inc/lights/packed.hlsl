@@ -1,8 +1,29 @@
 #ifndef LIGHTS_PACKED_HLSL
 #define LIGHTS_PACKED_HLSL
 
+// Must match `GpuTriangleLight` in `triangle_lights.rs`
 struct TriangleLightPacked {
     float packed[12];
+
+    // Power-proportional alias table entry; see `triangle_selection.hlsl`.
+    float alias_prob;
+    uint alias_idx;
+    float selection_pmf;
+    uint pad;
+};
+
+struct PunctualLightPacked {
+    float4 position_kind;
+    float4 direction_range;
+    float4 color_spot_scale;
+    float4 spot_offset_pad;
+};
+
+struct RectLightPacked {
+    float4 center_two_sided;
+    float4 half_x_axis;
+    float4 half_y_axis;
+    float4 radiance;
 };
 
 #endif
\ No newline at end of file
@@ -0,0 +1,129 @@
+#ifndef LIGHTS_PUNCTUAL_HLSL
+#define LIGHTS_PUNCTUAL_HLSL
+
+#include "packed.hlsl"
+#include "../math.hlsl"
+#include "../bindless_textures.hlsl"
+#include "../samplers.hlsl"
+
+static const uint PUNCTUAL_LIGHT_DIRECTIONAL = 0;
+static const uint PUNCTUAL_LIGHT_POINT = 1;
+static const uint PUNCTUAL_LIGHT_SPOT = 2;
+
+// Must match `PUNCTUAL_LIGHT_NO_GOBO_MAP` in `mesh.rs`
+static const uint PUNCTUAL_LIGHT_NO_GOBO_MAP = 0xffffffff;
+
+struct PunctualLightSample {
+    // Normalized direction from the shaded point towards the light
+    float3 wi;
+    float distance;
+    float3 radiance;
+};
+
+struct PunctualLight {
+    float3 position;
+    uint kind;
+    float3 direction;
+    float range;
+    float3 color;
+    float spot_angle_scale;
+    float spot_angle_offset;
+    float radius;
+    uint gobo_map;
+
+    static PunctualLight from_packed(PunctualLightPacked p) {
+        PunctualLight res;
+        res.position = p.position_kind.xyz;
+        res.kind = asuint(p.position_kind.w);
+        res.direction = p.direction_range.xyz;
+        res.range = p.direction_range.w;
+        res.color = p.color_spot_scale.xyz;
+        res.spot_angle_scale = p.color_spot_scale.w;
+        res.spot_angle_offset = p.spot_offset_pad.x;
+        res.radius = p.spot_offset_pad.y;
+        res.gobo_map = asuint(p.spot_offset_pad.z);
+        return res;
+    }
+
+    // Attenuation recommended by the `KHR_lights_punctual` spec.
+    // Inverse-square falloff is clamped inside the light's radius.
+    float range_attenuation(float dist) {
+        const float min_dist2 = max(1e-4, radius * radius);
+
+        if (range <= 0.0) {
+            return 1.0 / max(min_dist2, dist * dist);
+        }
+
+        const float ratio = dist / range;
+        const float window = saturate(1.0 - ratio * ratio * ratio * ratio);
+        return window * window / max(min_dist2, dist * dist);
+    }
+
+    float spot_attenuation(float3 wi) {
+        const float cd = dot(direction, -wi);
+        const float att = saturate(cd * spot_angle_scale + spot_angle_offset);
+        return att * att;
+    }
+
+    // Projects the gobo map across the outer cone of the spot light, with a square aspect.
+    float3 gobo_attenuation(float3 wi) {
+        const float3 local_dir = mul(-wi, build_orthonormal_basis(direction));
+        if (local_dir.z <= 0.0) {
+            return 0.0;
+        }
+
+        const float cos_outer = -spot_angle_offset / max(1e-5, spot_angle_scale);
+        const float tan_outer = sqrt(max(0.0, 1.0 - cos_outer * cos_outer)) / max(1e-5, cos_outer);
+        const float2 uv = local_dir.xy / (local_dir.z * max(1e-5, tan_outer)) * 0.5 + 0.5;
+
+        return bindless_textures[NonUniformResourceIndex(gobo_map)].SampleLevel(sampler_llc, uv, 0).rgb;
+    }
+
+    PunctualLightSample sample(float3 pt_ws) {
+        PunctualLightSample res;
+
+        if (PUNCTUAL_LIGHT_DIRECTIONAL == kind) {
+            res.wi = -direction;
+            res.distance = 1e10;
+            res.radiance = color;
+            return res;
+        }
+
+        const float3 to_light = position - pt_ws;
+        res.distance = length(to_light);
+        res.wi = to_light / max(1e-5, res.distance);
+        res.radiance = color * range_attenuation(res.distance);
+
+        if (PUNCTUAL_LIGHT_SPOT == kind) {
+            res.radiance *= spot_attenuation(res.wi);
+
+            if (gobo_map != PUNCTUAL_LIGHT_NO_GOBO_MAP) {
+                res.radiance *= gobo_attenuation(res.wi);
+            }
+        }
+
+        return res;
+    }
+
+    // Like `sample`, but aimed at a random point on the disk of the light's radius facing `pt_ws`,
+    // so that visibility rays towards it produce soft shadows. Attenuation is still from the center.
+    PunctualLightSample sample_with_radius(float3 pt_ws, float2 urand) {
+        PunctualLightSample res = sample(pt_ws);
+
+        if (PUNCTUAL_LIGHT_DIRECTIONAL == kind || radius <= 0.0) {
+            return res;
+        }
+
+        const float3x3 basis = build_orthonormal_basis(res.wi);
+        const float r = sqrt(urand.x) * radius;
+        const float phi = urand.y * M_TAU;
+
+        const float3 to_light = res.wi * res.distance + mul(basis, float3(r * cos(phi), r * sin(phi), 0.0));
+        res.distance = length(to_light);
+        res.wi = to_light / max(1e-5, res.distance);
+
+        return res;
+    }
+};
+
+#endif  // LIGHTS_PUNCTUAL_HLSL
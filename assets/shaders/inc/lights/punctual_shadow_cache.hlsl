@@ -0,0 +1,30 @@
+#ifndef PUNCTUAL_SHADOW_CACHE_HLSL
+#define PUNCTUAL_SHADOW_CACHE_HLSL
+
+#include "../pack_unpack.hlsl"
+
+// Must match `PUNCTUAL_SHADOW_CACHE_RESOLUTION` in `punctual_shadow_cache.rs`
+#define PUNCTUAL_SHADOW_CACHE_RESOLUTION 256
+
+// Of direction `dir` from the light, in the distance map of cache slot `slot`.
+uint2 punctual_shadow_cache_texel(uint slot, float3 dir) {
+    const uint2 texel = min(
+        uint2(octa_encode(dir) * PUNCTUAL_SHADOW_CACHE_RESOLUTION),
+        PUNCTUAL_SHADOW_CACHE_RESOLUTION - 1);
+    return texel + uint2(0, slot * PUNCTUAL_SHADOW_CACHE_RESOLUTION);
+}
+
+// From the light through the center of `texel` of a distance map.
+float3 punctual_shadow_cache_dir(uint2 texel) {
+    return octa_decode((texel + 0.5) / PUNCTUAL_SHADOW_CACHE_RESOLUTION);
+}
+
+// Whether static geometry blocks the way from the light towards `dir` before `distance`.
+// The bias grows with the texels' footprint, over which the distances of slanted surfaces vary.
+bool punctual_shadow_cache_is_shadowed(Texture2D<float> distances_tex, uint slot, float3 dir, float distance) {
+    const float cached_distance = distances_tex[punctual_shadow_cache_texel(slot, dir)];
+    const float bias = 1e-2 + distance * (8.0 / PUNCTUAL_SHADOW_CACHE_RESOLUTION);
+    return cached_distance < distance - bias;
+}
+
+#endif
@@ -0,0 +1,75 @@
+#ifndef LIGHTS_RECT_HLSL
+#define LIGHTS_RECT_HLSL
+
+#include "packed.hlsl"
+#include "../ltc.hlsl"
+
+// Expects `layered_brdf.hlsl` to be included first.
+
+// A rectangle with uniform emitted radiance, on the side opposite to `cross(half_x_axis, half_y_axis)`,
+// or on both sides if `two_sided`.
+struct RectLight {
+    float3 center;
+    bool two_sided;
+    float3 half_x_axis;
+    float3 half_y_axis;
+    float3 radiance;
+
+    static RectLight from_packed(RectLightPacked p) {
+        RectLight res;
+        res.center = p.center_two_sided.xyz;
+        res.two_sided = asuint(p.center_two_sided.w) != 0;
+        res.half_x_axis = p.half_x_axis.xyz;
+        res.half_y_axis = p.half_y_axis.xyz;
+        res.radiance = p.radiance.rgb;
+        return res;
+    }
+
+    float area() {
+        return 4.0 * length(cross(half_x_axis, half_y_axis));
+    }
+
+    // Cosine between the emitting side of the light and `dir_from_light`; zero behind one-sided lights.
+    float emission_cos(float3 dir_from_light) {
+        const float cos_theta = dot(dir_from_light, -normalize(cross(half_x_axis, half_y_axis)));
+        return two_sided ? abs(cos_theta) : max(0.0, cos_theta);
+    }
+
+    // Uniformly distributed over the area of the light
+    float3 sample_point(float2 urand) {
+        return center + half_x_axis * (urand.x * 2.0 - 1.0) + half_y_axis * (urand.y * 2.0 - 1.0);
+    }
+
+    // Unshadowed radiance reflected by `brdf` towards `wo_ws`, the normalized direction from `pt_ws` towards the eye.
+    //
+    // Ignores the clearcoat and anisotropy of the BRDF.
+    float3 evaluate_ltc(LayeredBrdf brdf, float3 pt_ws, float3 normal_ws, float3 wo_ws) {
+        // The LTC tangent frame has the view direction in the XZ plane.
+        float3 t1 = wo_ws - normal_ws * dot(wo_ws, normal_ws);
+        t1 = dot(t1, t1) > 1e-10
+            ? normalize(t1)
+            : normalize(cross(normal_ws, abs(normal_ws.x) > 0.9 ? float3(0, 1, 0) : float3(1, 0, 0)));
+        const float3 t2 = cross(normal_ws, t1);
+        const float3x3 world_to_tangent = float3x3(t1, t2, normal_ws);
+
+        // Clockwise when viewed from the emitting side.
+        float3 verts[4];
+        verts[0] = mul(world_to_tangent, center + half_x_axis + half_y_axis - pt_ws);
+        verts[1] = mul(world_to_tangent, center - half_x_axis + half_y_axis - pt_ws);
+        verts[2] = mul(world_to_tangent, center - half_x_axis - half_y_axis - pt_ws);
+        verts[3] = mul(world_to_tangent, center + half_x_axis - half_y_axis - pt_ws);
+
+        const float ndotv = clamp(dot(normal_ws, wo_ws), 1e-3, 1.0);
+
+        const float diffuse = ltc_evaluate_quad(LTC_IDENTITY, verts, two_sided);
+        const float specular = ltc_evaluate_quad(
+            ltc_ggx_inverse_matrix(ndotv, brdf.specular_brdf.roughness), verts, two_sided);
+
+        return radiance * (
+            brdf.diffuse_brdf.albedo * brdf.energy_preservation.preintegrated_transmission_fraction * diffuse
+            + brdf.energy_preservation.preintegrated_reflection * specular
+        );
+    }
+};
+
+#endif  // LIGHTS_RECT_HLSL
@@ -1,3 +1,6 @@
+#ifndef LIGHTS_TRIANGLE_HLSL
+#define LIGHTS_TRIANGLE_HLSL
+
 struct Triangle {
     float3 v;
     float3 e0;
@@ -179,3 +182,5 @@ LightSampleResultSolAng sample_light_sol_ang(float3 pt, Triangle tri, float2 ura
 
     return res;
 }
+
+#endif  // LIGHTS_TRIANGLE_HLSL
@@ -0,0 +1,37 @@
+#ifndef LIGHTS_TRIANGLE_SELECTION_HLSL
+#define LIGHTS_TRIANGLE_SELECTION_HLSL
+
+#include "../frame_constants.hlsl"
+#include "triangle.hlsl"
+
+struct TriangleLightSelection {
+    TriangleLight light;
+
+    // Probability of this light having been picked
+    float pmf;
+};
+
+// Picks one of the triangle lights with probability proportional to its power
+// (emitted luminance times area) via the alias table built on the CPU.
+//
+// The light count must be non-zero.
+TriangleLightSelection select_triangle_light(float urand) {
+    const uint light_count = frame_constants.triangle_light_count;
+
+    // The fractional part of the scaled random number decides between the slot and its alias.
+    const float slot_f = urand * light_count;
+    const uint slot = min(uint(slot_f), light_count - 1);
+    const float alias_urand = slot_f - slot;
+
+    TriangleLightPacked packed = triangle_lights_dyn[slot];
+    if (alias_urand >= packed.alias_prob) {
+        packed = triangle_lights_dyn[packed.alias_idx];
+    }
+
+    TriangleLightSelection res;
+    res.light = TriangleLight::from_packed(packed);
+    res.pmf = packed.selection_pmf;
+    return res;
+}
+
+#endif  // LIGHTS_TRIANGLE_SELECTION_HLSL
@@ -0,0 +1,87 @@
+#ifndef LTC_HLSL
+#define LTC_HLSL
+
+#include "samplers.hlsl"
+#include "bindless_textures.hlsl"
+
+// Linearly transformed cosines, for shading with polygonal lights.
+// "Real-Time Polygonal-Light Shading with Linearly Transformed Cosines" by Heitz et al.
+// https://eheitzresearch.wordpress.com/415-2/
+
+static const uint2 LTC_LUT_DIMS = uint2(64, 64);
+static const float2 LTC_LUT_UV_SCALE = (LTC_LUT_DIMS - 1.0) / LTC_LUT_DIMS;
+static const float2 LTC_LUT_UV_BIAS = 0.5.xx / LTC_LUT_DIMS;
+
+static const float3x3 LTC_IDENTITY = float3x3(1, 0, 0, 0, 1, 0, 0, 0, 1);
+
+// Inverse of the transform fitted to the cosine-weighted GGX BRDF by `lut/ltc_fit.hlsl`.
+// Expects vectors in a tangent frame which has the view direction in the XZ plane, facing +X.
+float3x3 ltc_ggx_inverse_matrix(float ndotv, float roughness) {
+    const float2 uv = float2(ndotv, roughness) * LTC_LUT_UV_SCALE + LTC_LUT_UV_BIAS;
+    const float4 m = bindless_textures[BINDLESS_LUT_LTC_GGX_INV_MATRIX].SampleLevel(sampler_lnc, uv, 0);
+
+    return float3x3(
+        m.x, 0, m.y,
+        0, 1, 0,
+        m.z, 0, m.w
+    );
+}
+
+// Integral of the clamped cosine over the edge between the normalized `v1` and `v2`, divided by 2π.
+// Uses the fit of `acos(x) / sin(acos(x))` from "Real-Time Area Lighting: a Journey from Research to Production".
+float ltc_integrate_edge(float3 v1, float3 v2) {
+    const float x = dot(v1, v2);
+    const float y = abs(x);
+
+    const float a = 0.8543985 + (0.4965155 + 0.0145206 * y) * y;
+    const float b = 3.4175940 + (4.1616724 + y) * y;
+    const float v = a / b;
+
+    const float theta_sintheta = (x > 0.0) ? v : 0.5 * rsqrt(max(1.0 - x * x, 1e-7)) - v;
+    return cross(v1, v2).z * theta_sintheta;
+}
+
+// Integral of the cosine distribution transformed by `minv` over the quad `verts`,
+// which are relative to the shaded point, and in the tangent frame of the surface.
+//
+// The result is positive for quads wound clockwise when viewed from the shaded point.
+// Counter-clockwise ones only contribute if `two_sided` is true.
+float ltc_evaluate_quad(float3x3 minv, float3 verts[4], bool two_sided) {
+    float3 transformed[4];
+    for (uint i = 0; i < 4; ++i) {
+        transformed[i] = mul(minv, verts[i]);
+    }
+
+    // Clip to the upper hemisphere. A planar quad clipped by a plane has at most five vertices.
+    float3 clipped[5];
+    uint clipped_count = 0;
+
+    for (uint i = 0; i < 4; ++i) {
+        const float3 a = transformed[i];
+        const float3 b = transformed[(i + 1) % 4];
+
+        if (a.z > 0.0) {
+            clipped[clipped_count++] = a;
+        }
+
+        if ((a.z > 0.0) != (b.z > 0.0)) {
+            clipped[clipped_count++] = lerp(a, b, a.z / (a.z - b.z));
+        }
+    }
+
+    if (clipped_count < 3) {
+        return 0.0;
+    }
+
+    float sum = 0.0;
+    for (uint i = 0; i < clipped_count; ++i) {
+        sum += ltc_integrate_edge(
+            normalize(clipped[i]),
+            normalize(clipped[(i + 1) % clipped_count])
+        );
+    }
+
+    return two_sided ? abs(sum) : max(0.0, sum);
+}
+
+#endif  // LTC_HLSL
@@ -82,6 +82,13 @@ float3 uniform_sample_hemisphere(float2 urand) {
      return float3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
 }
 
+// Malley's method: uniform on the disk, projected up onto the hemisphere.
+float3 cosine_sample_hemisphere(float2 urand) {
+    const float r = sqrt(urand.x);
+    const float phi = urand.y * M_TAU;
+    return float3(r * cos(phi), r * sin(phi), sqrt(max(0.0, 1.0 - urand.x)));
+}
+
 float3 uniform_sample_sphere(float2 urand) {
     float z = 1.0 - 2.0 * urand.x;
     float xy = sqrt(max(0.0, 1.0 - z * z));
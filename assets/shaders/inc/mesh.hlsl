@@ -15,6 +15,15 @@ struct Mesh {
     uint vertex_tangent_offset;
     uint mat_data_offset;
     uint index_offset;
+    uint vertex_prev_core_offset;
+    // Non-zero for curves, whose raster indices refer to ribbons; see `raster_simple_vs.hlsl`.
+    uint curve_tube_sides;
+    // Of meshes baked with `VertexQuantization`; fetch vertices with `mesh_vertex.hlsl`.
+    uint vertex_quantization_flags;
+    float position_scale[3];
+    float position_offset[3];
+    float uv_scale[2];
+    float uv_offset[2];
 };
 
 struct Vertex {
@@ -46,6 +55,10 @@ VertexPacked pack_vertex(Vertex v) {
 }
 
 static const uint MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT = 1;
+static const uint MESH_MATERIAL_FLAG_ALPHA_MASK = 2;
+static const uint MESH_MATERIAL_FLAG_ALPHA_BLEND = 4;
+static const uint MESH_MATERIAL_FLAG_DOUBLE_SIDED = 8;
+static const uint MESH_MATERIAL_FLAG_TERRAIN_LAYERS = 16;
 
 struct MeshMaterial {
     float base_color_mult[4];
@@ -53,13 +66,46 @@ struct MeshMaterial {
     uint spec_map;
     uint albedo_map;
     uint emissive_map;
+    uint height_map;
     float roughness_mult;
     float metalness_factor;
     float emissive[3];
     uint flags;
     float map_transforms[6 * 4];
+    float ior;
+    float clearcoat;
+    float clearcoat_roughness;
+    float transmission;
+    float anisotropy;
+    float anisotropy_rotation;
+    float alpha_cutoff;
+    float subsurface;
+    uint subsurface_profile;
+    float parallax_scale;
 };
 
+// Blended materials are skipped by the G-buffer pass, and drawn in the forward transparent pass.
+bool is_material_alpha_blended(MeshMaterial mat) {
+    return (mat.flags & MESH_MATERIAL_FLAG_ALPHA_BLEND) != 0 || mat.transmission > 0.0;
+}
+
+bool is_material_alpha_tested(MeshMaterial mat) {
+    return (mat.flags & MESH_MATERIAL_FLAG_ALPHA_MASK) != 0;
+}
+
+bool is_material_parallax_mapped(MeshMaterial mat) {
+    return mat.parallax_scale > 0.0;
+}
+
+bool is_material_double_sided(MeshMaterial mat) {
+    return (mat.flags & MESH_MATERIAL_FLAG_DOUBLE_SIDED) != 0;
+}
+
+// The material is followed by the other terrain layers; see `terrain_layers.hlsl`.
+bool is_material_terrain_layered(MeshMaterial mat) {
+    return (mat.flags & MESH_MATERIAL_FLAG_TERRAIN_LAYERS) != 0;
+}
+
 float2 transform_material_uv(MeshMaterial mat, float2 uv, uint map_idx) {
     uint xo = map_idx * 6;
     float2x2 rot_scl = float2x2(mat.map_transforms[xo+0], mat.map_transforms[xo+1], mat.map_transforms[xo+2], mat.map_transforms[xo+3]);
@@ -67,5 +113,11 @@ float2 transform_material_uv(MeshMaterial mat, float2 uv, uint map_idx) {
     return mul(rot_scl, uv) + offset;
 }
 
+float2 transform_material_uv_gradient(MeshMaterial mat, float2 duv, uint map_idx) {
+    uint xo = map_idx * 6;
+    float2x2 rot_scl = float2x2(mat.map_transforms[xo+0], mat.map_transforms[xo+1], mat.map_transforms[xo+2], mat.map_transforms[xo+3]);
+    return mul(rot_scl, duv);
+}
+
 
 #endif
\ No newline at end of file
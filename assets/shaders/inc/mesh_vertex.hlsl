@@ -0,0 +1,54 @@
+#ifndef MESH_VERTEX_HLSL
+#define MESH_VERTEX_HLSL
+
+// Vertex fetch from the `vertices` buffer, for meshes with and without quantized vertices.
+
+// Must match `VertexQuantizationFlags` in `mesh.rs`
+static const uint MESH_VERTEX_QUANTIZED_POSITIONS = 1;
+static const uint MESH_VERTEX_QUANTIZED_UVS = 2;
+
+// Of the lower 16 bits
+float unpack_snorm16(uint packed) {
+    return max(float(int(packed << 16u) >> 16) / 32767.0, -1.0);
+}
+
+// `core_offset` is either `vertex_core_offset` or `vertex_prev_core_offset` of the mesh.
+Vertex load_mesh_vertex_at(Mesh mesh, uint core_offset, uint vid) {
+    if ((mesh.vertex_quantization_flags & MESH_VERTEX_QUANTIZED_POSITIONS) != 0) {
+        // `QuantizedVertex`: three snorm16 position components, and the octahedral normal.
+        const uint2 packed = vertices.Load2(vid * sizeof(uint2) + core_offset);
+        const float3 unit_pos = float3(
+            unpack_snorm16(packed.x),
+            unpack_snorm16(packed.x >> 16u),
+            unpack_snorm16(packed.y)
+        );
+
+        Vertex res;
+        res.position = unit_pos
+            * float3(mesh.position_scale[0], mesh.position_scale[1], mesh.position_scale[2])
+            + float3(mesh.position_offset[0], mesh.position_offset[1], mesh.position_offset[2]);
+        res.normal = octa_decode(float2(unpack_unorm(packed.y >> 16u, 8), unpack_unorm(packed.y >> 24u, 8)));
+        return res;
+    }
+
+    // TODO: replace with Load<float4> once there's a fast path for NV
+    // https://github.com/microsoft/DirectXShaderCompiler/issues/2193
+    return unpack_vertex(VertexPacked(asfloat(vertices.Load4(vid * sizeof(float4) + core_offset))));
+}
+
+Vertex load_mesh_vertex(Mesh mesh, uint vid) {
+    return load_mesh_vertex_at(mesh, mesh.vertex_core_offset, vid);
+}
+
+float2 load_mesh_uv(Mesh mesh, uint vid) {
+    if ((mesh.vertex_quantization_flags & MESH_VERTEX_QUANTIZED_UVS) != 0) {
+        const uint packed = vertices.Load(vid * sizeof(uint) + mesh.vertex_uv_offset);
+        return float2(unpack_unorm(packed, 16), unpack_unorm(packed >> 16u, 16))
+            * float2(mesh.uv_scale[0], mesh.uv_scale[1])
+            + float2(mesh.uv_offset[0], mesh.uv_offset[1]);
+    }
+
+    return asfloat(vertices.Load2(vid * sizeof(float2) + mesh.vertex_uv_offset));
+}
+
+#endif
@@ -0,0 +1,65 @@
+#ifndef PARALLAX_HLSL
+#define PARALLAX_HLSL
+
+#include "mesh.hlsl"
+#include "bindless.hlsl"
+#include "samplers.hlsl"
+
+struct ParallaxHit {
+    // In the mesh's UV space, before the material's map transforms.
+    float2 uv;
+
+    // Below the surface, in UV units.
+    float depth;
+};
+
+// Parallax occlusion mapping: marches the view ray down through the material's height field,
+// and finds where it first goes below it.
+//
+// `view_dir_ts` points from the surface towards the eye, in tangent space.
+// Heights of one are at the surface, and zero at `parallax_scale` below it.
+//
+// UV gradients are explicit, so that ray hits can derive them from their cone footprint.
+ParallaxHit parallax_occlusion_map(MeshMaterial mat, float2 uv, float2 uv_ddx, float2 uv_ddy, float3 view_dir_ts, uint max_step_count) {
+    Texture2D height_tex = bindless_textures[NonUniformResourceIndex(mat.height_map)];
+
+    // The height map shares the albedo's UV transform.
+    const float2 height_ddx = transform_material_uv_gradient(mat, uv_ddx, 0);
+    const float2 height_ddy = transform_material_uv_gradient(mat, uv_ddy, 0);
+
+    // More steps at grazing angles, where the ray crosses more of the height field.
+    const float cos_theta = saturate(view_dir_ts.z);
+    const uint step_count = max(1u, uint(lerp(float(max_step_count), max_step_count * 0.25, cos_theta)));
+    const float step_size = 1.0 / step_count;
+
+    // UV offset of the ray at the full depth; clamped at grazing angles, where it'd go off to infinity.
+    const float2 full_depth_offset = -view_dir_ts.xy / max(cos_theta, 0.1) * mat.parallax_scale;
+
+    // Depths in units of `parallax_scale`
+    float ray_depth = 0.0;
+    float height_depth = 1.0 - height_tex.SampleGrad(sampler_llr, transform_material_uv(mat, uv, 0), height_ddx, height_ddy).r;
+    float prev_ray_depth = ray_depth;
+    float prev_height_depth = height_depth;
+
+    for (uint i = 0; i < step_count && ray_depth < height_depth; ++i) {
+        prev_ray_depth = ray_depth;
+        prev_height_depth = height_depth;
+
+        ray_depth += step_size;
+        const float2 step_uv = uv + full_depth_offset * ray_depth;
+        height_depth = 1.0 - height_tex.SampleGrad(sampler_llr, transform_material_uv(mat, step_uv, 0), height_ddx, height_ddy).r;
+    }
+
+    // Intersect the ray with a linear approximation of the height field between the last two steps.
+    const float above_before = prev_height_depth - prev_ray_depth;
+    const float above_after = height_depth - ray_depth;
+    const float t = above_before / max(1e-5, above_before - above_after);
+    const float hit_depth = saturate(lerp(prev_ray_depth, ray_depth, saturate(t)));
+
+    ParallaxHit hit;
+    hit.uv = uv + full_depth_offset * hit_depth;
+    hit.depth = hit_depth * mat.parallax_scale;
+    return hit;
+}
+
+#endif
@@ -0,0 +1,21 @@
+#ifndef RENDER_QUALITY_HLSL
+#define RENDER_QUALITY_HLSL
+
+// Specialization constants set from `RenderQuality` on the CPU.
+// The IDs must match `renderers/render_quality.rs`. The defaults apply
+// to pipelines which don't specialize them.
+
+// Shadow rays per pixel towards the sun, averaged.
+[[vk::constant_id(0)]] const uint SUN_SHADOW_RAY_COUNT = 1;
+
+// Diffuse GI candidate rays per (half-res) pixel, resampled down to one.
+[[vk::constant_id(1)]] const uint DIFFUSE_GI_RAY_COUNT = 1;
+
+// Reflection candidate rays per (half-res) pixel, resampled down to one.
+[[vk::constant_id(2)]] const uint REFLECTION_RAY_COUNT = 1;
+
+// Max number of frames of history in the ReSTIR temporal reservoirs.
+[[vk::constant_id(3)]] const float DIFFUSE_GI_HISTORY_LENGTH = 20.0;
+[[vk::constant_id(4)]] const float REFLECTION_HISTORY_LENGTH = 8.0;
+
+#endif  // RENDER_QUALITY_HLSL
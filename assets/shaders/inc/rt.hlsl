@@ -2,6 +2,7 @@
 #define RT_HLSL
 
 #include "math_const.hlsl"
+#include "frame_constants.hlsl"
 #include "gbuffer.hlsl"
 #include "ray_cone.hlsl"
 
@@ -46,6 +47,15 @@ struct ShadowRayPayload {
     }
 };
 
+// Which rays see which instances; must match `RayVisibility` in `world_renderer.rs`.
+// Shadow casters are split by whether they're static, so that static shadows can be cached.
+static const uint RT_INSTANCE_MASK_SHADOW_DYNAMIC = 1;
+static const uint RT_INSTANCE_MASK_REFLECTION = 2;
+static const uint RT_INSTANCE_MASK_GI = 4;
+static const uint RT_INSTANCE_MASK_SHADOW_STATIC = 8;
+static const uint RT_INSTANCE_MASK_SHADOW = RT_INSTANCE_MASK_SHADOW_DYNAMIC | RT_INSTANCE_MASK_SHADOW_STATIC;
+static const uint RT_INSTANCE_MASK_ALL = 0xff;
+
 RayDesc new_ray(float3 origin, float3 direction, float tmin, float tmax) {
     RayDesc ray;
     ray.Origin = origin;
@@ -55,20 +65,61 @@ RayDesc new_ray(float3 origin, float3 direction, float tmin, float tmax) {
     return ray;
 }
 
-bool rt_is_shadowed(
+// The ray tracing counterpart of the raster clip distance: shortens the ray to the side
+// of the view's clip plane which is rendered, so that clipped geometry doesn't show up
+// in reflections, or cast shadows. Rays entirely on the other side become empty, and miss.
+RayDesc clip_ray_to_view_plane(RayDesc ray) {
+    const float4 plane = frame_constants.view_constants.clip_plane;
+    if (all(plane == 0.0)) {
+        return ray;
+    }
+
+    const float origin_dist = dot(plane, float4(ray.Origin, 1.0));
+    const float dir_dot = dot(plane.xyz, ray.Direction);
+
+    if (dir_dot == 0.0) {
+        if (origin_dist < 0.0) {
+            ray.TMax = ray.TMin;
+        }
+        return ray;
+    }
+
+    const float plane_t = -origin_dist / dir_dot;
+    if (dir_dot > 0.0) {
+        ray.TMin = max(ray.TMin, plane_t);
+    } else {
+        ray.TMax = min(ray.TMax, plane_t);
+    }
+    ray.TMax = max(ray.TMin, ray.TMax);
+
+    return ray;
+}
+
+bool rt_is_occluded(
     RaytracingAccelerationStructure acceleration_structure,
-    RayDesc ray
+    RayDesc ray,
+    uint instance_mask
 ) {
     ShadowRayPayload shadow_payload = ShadowRayPayload::new_hit();
+
+    // Hit group 1 only has an any hit shader for alpha testing, matching the payload.
     TraceRay(
         acceleration_structure,
         RAY_FLAG_ACCEPT_FIRST_HIT_AND_END_SEARCH | RAY_FLAG_SKIP_CLOSEST_HIT_SHADER,
-        0xff, 0, 0, 1, ray, shadow_payload
+        instance_mask, 1, 0, 1, clip_ray_to_view_plane(ray), shadow_payload
     );
 
     return shadow_payload.is_shadowed;
 }
 
+// Only instances which cast shadows block the ray.
+bool rt_is_shadowed(
+    RaytracingAccelerationStructure acceleration_structure,
+    RayDesc ray
+) {
+    return rt_is_occluded(acceleration_structure, ray, RT_INSTANCE_MASK_SHADOW);
+}
+
 struct GbufferPathVertex {
     bool is_hit;
     GbufferDataPacked gbuffer_packed;
@@ -81,6 +132,7 @@ struct GbufferRaytrace {
     RayCone ray_cone;
     uint path_length;
     bool cull_back_faces;
+    uint instance_mask;
 
     static GbufferRaytrace with_ray(RayDesc ray) {
         GbufferRaytrace res;
@@ -88,6 +140,7 @@ struct GbufferRaytrace {
         res.ray_cone = RayCone::from_spread_angle(1.0);
         res.path_length = 0;
         res.cull_back_faces = true;
+        res.instance_mask = RT_INSTANCE_MASK_ALL;
         return res;
     }
 
@@ -109,6 +162,13 @@ struct GbufferRaytrace {
         return res;
     }
 
+    // One of the `RT_INSTANCE_MASK_*` constants, for the effect the ray is traced for.
+    GbufferRaytrace with_instance_mask(uint v) {
+        GbufferRaytrace res = this;
+        res.instance_mask = v;
+        return res;
+    }
+
     GbufferPathVertex trace(RaytracingAccelerationStructure acceleration_structure) {
         GbufferRayPayload payload = GbufferRayPayload::new_miss();
         payload.ray_cone = this.ray_cone;
@@ -119,7 +179,7 @@ struct GbufferRaytrace {
             trace_flags |= RAY_FLAG_CULL_BACK_FACING_TRIANGLES;
         }
 
-        TraceRay(acceleration_structure, trace_flags, 0xff, 0, 0, 0, this.ray, payload);
+        TraceRay(acceleration_structure, trace_flags, this.instance_mask, 0, 0, 0, clip_ray_to_view_plane(this.ray), payload);
 
         if (payload.is_hit()) {
             GbufferPathVertex res;
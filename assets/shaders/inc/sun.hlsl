@@ -3,6 +3,7 @@
 
 #include "frame_constants.hlsl"
 #include "math.hlsl"
+#include "atmosphere.hlsl"
 
 // static const float3 SUN_DIRECTION = normalize(float3(1, 1.6, -0.2));
 // static const float3 SUN_DIRECTION = normalize(float3(-0.8, 0.3, 1.0));
@@ -19,11 +20,11 @@
     }
 #else
     float3 sun_color_in_direction(float3 dir) {
-        return
-            20.0 *
+        return rec709_to_working(
+            ATMOSPHERE_SUN_ILLUMINANCE *
             frame_constants.sun_color_multiplier.rgb *
-            frame_constants.pre_exposure *
-            Absorb(IntegrateOpticalDepth(0.0.xxx, dir));
+            atmosphere_transmittance(atmosphere_viewer_position(), dir))
+            * frame_constants.pre_exposure;
     }
 
 #endif
@@ -0,0 +1,65 @@
+#ifndef TERRAIN_LAYERS_HLSL
+#define TERRAIN_LAYERS_HLSL
+
+#include "mesh.hlsl"
+#include "bindless.hlsl"
+#include "samplers.hlsl"
+
+// Must match `MAX_TERRAIN_LAYERS` in `terrain.rs`. Padded with zero-weight layers if fewer are used.
+static const uint TERRAIN_LAYER_COUNT = 4;
+
+struct TerrainLayersSample {
+    float3 albedo;
+    float perceptual_roughness;
+    float metalness;
+    float3 ts_normal;
+};
+
+float4 sample_terrain_layer_map(uint map, MeshMaterial layer, uint map_idx, float2 uv, float2 uv_ddx, float2 uv_ddy) {
+    return bindless_textures[NonUniformResourceIndex(map)].SampleGrad(
+        sampler_llr,
+        transform_material_uv(layer, uv, map_idx),
+        transform_material_uv_gradient(layer, uv_ddx, map_idx),
+        transform_material_uv_gradient(layer, uv_ddy, map_idx));
+}
+
+// Blends the layer materials starting at `material_id`, with `weights` taken from the vertex colors.
+//
+// UV gradients are explicit, so that ray hits can derive them from their cone footprint.
+TerrainLayersSample sample_terrain_layers(Mesh mesh, uint material_id, float4 weights, float2 uv, float2 uv_ddx, float2 uv_ddy) {
+    TerrainLayersSample res;
+    res.albedo = 0.0;
+    res.perceptual_roughness = 0.0;
+    res.metalness = 0.0;
+    res.ts_normal = float3(0.0, 0.0, 1e-5);
+
+    weights /= max(1e-5, dot(weights, 1.0.xxxx));
+
+    for (uint layer_idx = 0; layer_idx < TERRAIN_LAYER_COUNT; ++layer_idx) {
+        const float weight = weights[layer_idx];
+
+        [branch]
+        if (weight <= 0.0) {
+            continue;
+        }
+
+        const MeshMaterial layer = vertices.Load<MeshMaterial>(mesh.mat_data_offset + (material_id + layer_idx) * sizeof(MeshMaterial));
+
+        const float4 albedo_texel = sample_terrain_layer_map(layer.albedo_map, layer, 0, uv, uv_ddx, uv_ddy);
+        res.albedo += weight * albedo_texel.rgb * float4(layer.base_color_mult).rgb;
+
+        const float4 metalness_roughness = sample_terrain_layer_map(layer.spec_map, layer, 2, uv, uv_ddx, uv_ddy);
+        res.perceptual_roughness += weight * layer.roughness_mult * metalness_roughness.x;
+        res.metalness += weight * layer.metalness_factor * metalness_roughness.y;
+
+        float3 ts_normal = float3(sample_terrain_layer_map(layer.normal_map, layer, 1, uv, uv_ddx, uv_ddy).xy * 2.0 - 1.0, 0);
+        ts_normal.z = sqrt(max(0.01, 1.0 - dot(ts_normal.xy, ts_normal.xy)));
+        res.ts_normal += weight * ts_normal;
+    }
+
+    res.ts_normal = normalize(res.ts_normal);
+
+    return res;
+}
+
+#endif
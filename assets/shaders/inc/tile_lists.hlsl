@@ -0,0 +1,33 @@
+#ifndef TILE_LISTS_HLSL
+#define TILE_LISTS_HLSL
+
+// Must match `tile_lists.rs`
+#define TILE_LIST_TILE_SIZE 8
+#define TILE_CLASS_SIMPLE 0
+#define TILE_CLASS_COMPLEX 1
+#define TILE_CLASS_COUNT 2
+#define TILE_LIST_DISPATCH_ARGS_STRIDE 16
+
+// For the classification pass: appends `tile` to `tiles`, the list of `tile_class`.
+void tile_lists_push(RWByteAddressBuffer dispatch_args, RWStructuredBuffer<uint> tiles, uint tile_class, uint2 tile) {
+    const uint args_offset = tile_class * TILE_LIST_DISPATCH_ARGS_STRIDE;
+
+    // The arguments start out cleared; the first tile fills in the group counts along Y and Z.
+    if (all(tile == 0)) {
+        for (uint i = 0; i < TILE_CLASS_COUNT; ++i) {
+            dispatch_args.Store2(i * TILE_LIST_DISPATCH_ARGS_STRIDE + 4, uint2(1, 1));
+        }
+    }
+
+    uint tile_idx;
+    dispatch_args.InterlockedAdd(args_offset, 1, tile_idx);
+    tiles[tile_idx] = tile.x | (tile.y << 16);
+}
+
+// For the passes dispatched over a list: the tile of the group with `SV_GroupID.x` of `group_idx`.
+uint2 tile_lists_get(StructuredBuffer<uint> tiles, uint group_idx) {
+    const uint packed = tiles[group_idx];
+    return uint2(packed & 0xffff, packed >> 16);
+}
+
+#endif  // TILE_LISTS_HLSL
@@ -0,0 +1,83 @@
+#ifndef TONEMAP_HLSL
+#define TONEMAP_HLSL
+
+#include "color/srgb.hlsl"
+
+// All operators take exposed linear sRGB, and return linear sRGB in [0, 1].
+
+// Stephen Hill's fit of the ACES RRT+ODT, from
+// https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+float3 aces_rrt_and_odt_fit(float3 v) {
+    float3 a = v * (v + 0.0245786) - 0.000090537;
+    float3 b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return a / b;
+}
+
+float3 tonemap_aces_fitted(float3 col) {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    static const float3x3 aces_input_mat = {
+        {0.59719, 0.35458, 0.04823},
+        {0.07600, 0.90834, 0.01566},
+        {0.02840, 0.13383, 0.83777}
+    };
+
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    static const float3x3 aces_output_mat = {
+        { 1.60475, -0.53108, -0.07367},
+        {-0.10208,  1.10813, -0.00605},
+        {-0.00327, -0.07276,  1.07602}
+    };
+
+    col = mul(aces_input_mat, col);
+    col = aces_rrt_and_odt_fit(col);
+    col = mul(aces_output_mat, col);
+    return saturate(col);
+}
+
+// Minimal AgX by Benjamin Wrensch, with a polynomial fit of the default contrast curve:
+// https://iolite-engine.com/blog_posts/minimal_agx_implementation
+float3 agx_default_contrast_approx(float3 x) {
+    float3 x2 = x * x;
+    float3 x4 = x2 * x2;
+
+    return 15.5 * x4 * x2
+        - 40.14 * x4 * x
+        + 31.96 * x4
+        - 6.868 * x2 * x
+        + 0.4298 * x2
+        + 0.1191 * x
+        - 0.00232;
+}
+
+float3 tonemap_agx(float3 col) {
+    // Column-major in the original; `mul(v, M)` below compensates.
+    static const float3x3 agx_mat = {
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104
+    };
+    static const float3x3 agx_mat_inv = {
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+    };
+
+    const float min_ev = -12.47393;
+    const float max_ev = 4.026069;
+
+    col = mul(col, agx_mat);
+    col = clamp(log2(max(1e-10, col)), min_ev, max_ev);
+    col = (col - min_ev) / (max_ev - min_ev);
+    col = agx_default_contrast_approx(col);
+    col = mul(col, agx_mat_inv);
+
+    // The curve outputs display-encoded values; go back to linear.
+    return pow(saturate(col), 2.2);
+}
+
+float3 tonemap_reinhard(float3 col) {
+    const float lum = sRGB_to_luminance(col);
+    return saturate(col / (1.0 + lum));
+}
+
+#endif  // TONEMAP_HLSL
@@ -0,0 +1,42 @@
+#ifndef WORKING_PRIMARIES_HLSL
+#define WORKING_PRIMARIES_HLSL
+
+#include "frame_constants.hlsl"
+
+// Must match `WorkingColorSpace::shader_index`
+#define WORKING_COLOR_SPACE_LINEAR_SRGB 0
+#define WORKING_COLOR_SPACE_ACESCG 1
+
+// Bradford-adapted from D65 to the ACES white point, as in the ACES CSC transforms.
+// Must match `working_color_space.rs`
+static const float3x3 REC709_TO_ACESCG = float3x3(
+    0.6130974, 0.3395231, 0.0473794,
+    0.0701937, 0.9163539, 0.0134524,
+    0.0206156, 0.1095698, 0.8698147
+);
+
+static const float3x3 ACESCG_TO_REC709 = float3x3(
+     1.7050510, -0.6217921, -0.0832589,
+    -0.1302564,  1.1408047, -0.0105483,
+    -0.0240033, -0.1289690,  1.1529723
+);
+
+// Colors of textures, materials, and the sky are authored in linear Rec.709,
+// and converted on their way into lighting.
+float3 rec709_to_working(float3 col) {
+    if (frame_constants.working_color_space == WORKING_COLOR_SPACE_ACESCG) {
+        return mul(REC709_TO_ACESCG, col);
+    }
+    return col;
+}
+
+// For the output transform, which expects Rec.709. Colors outside of the Rec.709 gamut
+// come out with negative components.
+float3 working_to_rec709(float3 col) {
+    if (frame_constants.working_color_space == WORKING_COLOR_SPACE_ACESCG) {
+        return mul(ACESCG_TO_REC709, col);
+    }
+    return col;
+}
+
+#endif  // WORKING_PRIMARIES_HLSL
@@ -80,6 +80,7 @@ IrcacheTraceResult ircache_trace(Vertex entry, DiffuseBrdf brdf, SampleParams sa
 
     for (uint path_length = 0; path_length < MAX_PATH_LENGTH; ++path_length) {
         const GbufferPathVertex primary_hit = GbufferRaytrace::with_ray(outgoing_ray)
+            .with_instance_mask(RT_INSTANCE_MASK_GI)
             .with_cone(RayCone::from_spread_angle(0.1))
             .with_cull_back_faces(false)
             .with_path_length(path_length + 1)  // +1 because this is indirect light
@@ -135,17 +136,15 @@ IrcacheTraceResult ircache_trace(Vertex entry, DiffuseBrdf brdf, SampleParams sa
             }
 
             if (USE_LIGHTS && frame_constants.triangle_light_count > 0/* && path_length > 0*/) {   // rtr comp
-                const float light_selection_pmf = 1.0 / frame_constants.triangle_light_count;
-                const uint light_idx = hash1_mut(rng) % frame_constants.triangle_light_count;
-                //const float light_selection_pmf = 1;
-                //for (uint light_idx = 0; light_idx < frame_constants.triangle_light_count; light_idx += 1)
+                const TriangleLightSelection light_selection = select_triangle_light(uint_to_u01_float(hash1_mut(rng)));
+                const float light_selection_pmf = light_selection.pmf;
                 {
                     const float2 urand = float2(
                         uint_to_u01_float(hash1_mut(rng)),
                         uint_to_u01_float(hash1_mut(rng))
                     );
 
-                    TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+                    TriangleLight triangle_light = light_selection.light;
                     LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
                     const float3 shadow_ray_origin = primary_hit.position;
                     const float3 to_light_ws = light_sample.pos - primary_hit.position;
@@ -177,6 +176,75 @@ IrcacheTraceResult ircache_trace(Vertex entry, DiffuseBrdf brdf, SampleParams sa
                 }
             }
             
+            if (USE_LIGHTS && frame_constants.punctual_light_count > 0) {
+                // One stochastically selected light per vertex keeps the cost flat in the light count.
+                const float light_selection_pmf = 1.0 / frame_constants.punctual_light_count;
+                const uint light_idx = hash1_mut(rng) % frame_constants.punctual_light_count;
+
+                const float2 urand = float2(
+                    uint_to_u01_float(hash1_mut(rng)),
+                    uint_to_u01_float(hash1_mut(rng))
+                );
+
+                const PunctualLight punctual_light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+                const PunctualLightSample light_sample = punctual_light.sample_with_radius(primary_hit.position, urand);
+                const float3 wi = mul(light_sample.wi, tangent_to_world);
+
+                if (wi.z > 0.0 && any(light_sample.radiance > 0.0)) {
+                    const bool is_shadowed =
+                        rt_is_shadowed(
+                            acceleration_structure,
+                            new_ray(
+                                primary_hit.position,
+                                light_sample.wi,
+                                1e-3,
+                                light_sample.distance - 2e-3
+                        ));
+
+                    irradiance_sum +=
+                        is_shadowed ? 0 :
+                            throughput * light_sample.radiance * brdf.evaluate_directional_light(wo, wi) * wi.z / light_selection_pmf;
+                }
+            }
+
+            if (USE_LIGHTS && frame_constants.rect_light_count > 0) {
+                const float light_selection_pmf = 1.0 / frame_constants.rect_light_count;
+                const uint light_idx = hash1_mut(rng) % frame_constants.rect_light_count;
+
+                const float2 urand = float2(
+                    uint_to_u01_float(hash1_mut(rng)),
+                    uint_to_u01_float(hash1_mut(rng))
+                );
+
+                const RectLight rect_light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+                const float3 to_light_ws = rect_light.sample_point(urand) - primary_hit.position;
+                const float dist_to_light2 = dot(to_light_ws, to_light_ws);
+                const float3 to_light_norm_ws = to_light_ws * rsqrt(dist_to_light2);
+
+                const float to_psa_metric =
+                    max(0.0, dot(to_light_norm_ws, gbuffer.normal))
+                    * rect_light.emission_cos(-to_light_norm_ws)
+                    / dist_to_light2;
+
+                if (to_psa_metric > 0.0) {
+                    const float3 wi = mul(to_light_norm_ws, tangent_to_world);
+
+                    const bool is_shadowed =
+                        rt_is_shadowed(
+                            acceleration_structure,
+                            new_ray(
+                                primary_hit.position,
+                                to_light_norm_ws,
+                                1e-3,
+                                sqrt(dist_to_light2) - 2e-3
+                        ));
+
+                    irradiance_sum +=
+                        is_shadowed ? 0 :
+                            throughput * rect_light.radiance * brdf.evaluate(wo, wi) * rect_light.area() * to_psa_metric / light_selection_pmf;
+                }
+            }
+
             if (SAMPLE_IRCACHE_AT_LAST_VERTEX && path_length + 1 == MAX_PATH_LENGTH) {
                 irradiance_sum +=
                     IrcacheLookupParams::create(entry.position, primary_hit.position, gbuffer.normal)
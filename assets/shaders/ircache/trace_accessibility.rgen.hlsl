@@ -52,14 +52,16 @@ void main() {
     Vertex prev_entry = unpack_vertex(VertexPacked(ircache_aux_buf[output_idx + IRCACHE_OCTA_DIMS2 * 2]));
 
     // Reduce weight of samples whose trace origins are not accessible now
-    if (rt_is_shadowed(
+    if (rt_is_occluded(
         acceleration_structure,
         new_ray(
             entry.position,
             prev_entry.position - entry.position,
             0.001,
             0.999
-    ))) {
+        ),
+        RT_INSTANCE_MASK_GI
+    )) {
         r.M *= 0.8;
         ircache_aux_buf[output_idx].xy = asfloat(r.as_raw());
     }
@@ -13,6 +13,9 @@
 #include "../inc/atmosphere.hlsl"
 #include "../inc/mesh.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/rect.hlsl"
 #include "../wrc/bindings.hlsl"
 #include "../inc/color.hlsl"
 
@@ -15,6 +15,9 @@
 
 #include "inc/hash.hlsl"
 #include "inc/color.hlsl"
+#include "inc/lights/punctual.hlsl"
+#include "inc/lights/rect.hlsl"
+#include "inc/lights/clusters.hlsl"
 
 #define USE_RTR 1
 #define USE_RTDGI 1
@@ -36,10 +39,18 @@ DEFINE_WRC_BINDINGS(14)
 [[vk::binding(16)]] RWTexture2D<float4> output_tex;
 [[vk::binding(17)]] TextureCube<float4> unconvolved_sky_cube_tex;
 [[vk::binding(18)]] TextureCube<float4> sky_cube_tex;
-[[vk::binding(19)]] cbuffer _ {
+[[vk::binding(19)]] TextureCube<float4> prefiltered_sky_cube_tex;
+[[vk::binding(20)]] Texture2D<float4> punctual_lighting_tex;
+[[vk::binding(21)]] Texture2D<float4> rect_lighting_tex;
+[[vk::binding(22)]] StructuredBuffer<uint> light_cluster_lists_buf;
+[[vk::binding(23)]] cbuffer _ {
+    LightClusterConstants light_clusters;
     float4 output_tex_size;
     uint debug_shading_mode;
     uint debug_show_wrc;
+    uint use_traced_punctual_lighting;
+    uint use_traced_rect_lighting;
+    float reflection_roughness_cutoff;
 };
 
 #define IRCACHE_LOOKUP_DONT_KEEP_ALIVE
@@ -93,7 +104,7 @@ void main(in uint2 px : SV_DispatchThreadID) {
 
         // Allow the size to be changed, but don't go below the real sun's size,
         // so that we have something in the sky.
-        const float real_sun_angular_radius = 0.53 * 0.5 * PI / 180.0;
+        const float real_sun_angular_radius = 0.53 * 0.5 * M_PI / 180.0;
         const float sun_angular_radius_cos = min(cos(real_sun_angular_radius), frame_constants.sun_angular_radius_cos);
 
         // Conserve the sun's energy by making it dimmer as it increases in size
@@ -153,6 +164,43 @@ void main(in uint2 px : SV_DispatchThreadID) {
     const float3 light_radiance = shadow_mask * SUN_COLOR;
     float3 total_radiance = brdf_value * light_radiance;
 
+    [branch]
+    if (use_traced_punctual_lighting && debug_shading_mode != SHADING_MODE_RTX_OFF) {
+        // From `trace_punctual_lighting.rgen.hlsl`, with denoised ray-traced shadows
+        total_radiance += punctual_lighting_tex[px].rgb;
+    } else {
+        const uint cluster_offset =
+            light_clusters.cluster_index(uv, -depth_to_view_z(depth)) * LIGHT_CLUSTER_STRIDE;
+        const uint cluster_light_count = light_cluster_lists_buf[cluster_offset];
+
+        for (uint cluster_light_idx = 0; cluster_light_idx < cluster_light_count; ++cluster_light_idx) {
+            const uint light_idx = light_cluster_lists_buf[cluster_offset + 1 + cluster_light_idx];
+            const PunctualLight light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+            const PunctualLightSample light_sample = light.sample(pt_ws.xyz);
+            const float3 light_wi = mul(light_sample.wi, tangent_to_world);
+
+            total_radiance +=
+                brdf.evaluate_directional_light(wo, light_wi)
+                * max(0.0, light_wi.z)
+                * light_sample.radiance
+                * frame_constants.pre_exposure;
+        }
+    }
+
+    [branch]
+    if (use_traced_rect_lighting && debug_shading_mode != SHADING_MODE_RTX_OFF) {
+        // From `trace_rect_lighting.rgen.hlsl`, with denoised ray-traced shadows
+        total_radiance += rect_lighting_tex[px].rgb;
+    } else {
+        for (uint light_idx = 0; light_idx < frame_constants.rect_light_count; ++light_idx) {
+            const RectLight light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+
+            total_radiance +=
+                light.evaluate_ltc(brdf, pt_ws.xyz, gbuffer.normal, -outgoing_ray.Direction)
+                * frame_constants.pre_exposure;
+        }
+    }
+
     total_radiance += gbuffer.emissive;
 
     float3 gi_irradiance = 0.0.xxx;
@@ -165,11 +213,17 @@ void main(in uint2 px : SV_DispatchThreadID) {
 
     total_radiance += gi_irradiance
         * brdf.diffuse_brdf.albedo
+        * (1.0 - brdf.clearcoat_preintegrated_reflection)
         #if !LAYERED_BRDF_FORCE_DIFFUSE_ONLY
             * brdf.energy_preservation.preintegrated_transmission_fraction
         #endif
         ;
 
+    // Reflections are missing above the roughness cutoff.
+    const float rtr_fallback_wt = rtr_roughness_cutoff_fallback_weight(
+        rtr_cutoff_roughness(gbuffer.roughness, gbuffer.clearcoat, gbuffer.clearcoat_roughness),
+        reflection_roughness_cutoff);
+
     if (USE_RTR && !LAYERED_BRDF_FORCE_DIFFUSE_ONLY && debug_shading_mode != SHADING_MODE_RTX_OFF) {
         float3 rtr_radiance;
 
@@ -186,14 +240,54 @@ void main(in uint2 px : SV_DispatchThreadID) {
                 smoothstep(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, lerp(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, 1.0, 0.5), gbuffer.roughness));
         }
 
+        rtr_radiance = lerp(
+            rtr_radiance,
+            gi_irradiance * brdf.energy_preservation.preintegrated_reflection,
+            rtr_fallback_wt);
+
         [branch]
         if (debug_shading_mode == SHADING_MODE_NO_TEXTURES) {
             GbufferData true_gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
             LayeredBrdf true_brdf = LayeredBrdf::from_gbuffer_ndotv(true_gbuffer, wo.z);
             rtr_radiance /= true_brdf.energy_preservation.preintegrated_reflection;
         }
+
+        rtr_radiance *= 1.0 - brdf.clearcoat_preintegrated_reflection;
+
+        #if !RTR_RENDER_SCALED_BY_FG
+            // The coat reflects the same incident radiance as the base layer, ignoring its own roughness.
+            rtr_radiance += rtr_tex[px].xyz * brdf.clearcoat_preintegrated_reflection;
+        #endif
         
         total_radiance += rtr_radiance;
+    } else if (!LAYERED_BRDF_FORCE_DIFFUSE_ONLY) {
+        // No ray-traced reflections; fall back to the pre-filtered sky.
+        const float3 reflected_dir = reflect(outgoing_ray.Direction, gbuffer.normal);
+        const float perceptual_roughness = sqrt(gbuffer.roughness);
+
+        uint cube_width, cube_height, cube_levels;
+        prefiltered_sky_cube_tex.GetDimensions(0, cube_width, cube_height, cube_levels);
+
+        const float3 sky_radiance = prefiltered_sky_cube_tex.SampleLevel(
+            sampler_llr,
+            reflected_dir,
+            perceptual_roughness * (cube_levels - 1)
+        ).rgb;
+
+        total_radiance += sky_radiance
+            * brdf.energy_preservation.preintegrated_reflection
+            * (1.0 - brdf.clearcoat_preintegrated_reflection);
+
+        [branch]
+        if (brdf.clearcoat > 0.0) {
+            const float3 clearcoat_sky_radiance = prefiltered_sky_cube_tex.SampleLevel(
+                sampler_llr,
+                reflected_dir,
+                sqrt(brdf.clearcoat_brdf.roughness) * (cube_levels - 1)
+            ).rgb;
+
+            total_radiance += clearcoat_sky_radiance * brdf.clearcoat_preintegrated_reflection;
+        }
     }
 
     temporal_output_tex[px] = float4(total_radiance, 1.0);
@@ -215,6 +309,11 @@ void main(in uint2 px : SV_DispatchThreadID) {
                 smoothstep(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, 1.0, gbuffer.roughness));
         }
 
+        output = lerp(
+            output,
+            gi_irradiance * brdf.energy_preservation.preintegrated_reflection,
+            rtr_fallback_wt);
+
         GbufferData true_gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
         LayeredBrdf true_brdf = LayeredBrdf::from_gbuffer_ndotv(true_gbuffer, wo.z);
         output /= true_brdf.energy_preservation.preintegrated_reflection;
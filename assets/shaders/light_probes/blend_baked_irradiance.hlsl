@@ -0,0 +1,80 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/sh.hlsl"
+#include "../inc/math_const.hlsl"
+
+// Must match `GpuLightProbe` in `light_probes.rs`
+struct LightProbe {
+    float4 position_radius;
+    float4 sh_r;
+    float4 sh_g;
+    float4 sh_b;
+};
+
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> gi_tex;
+[[vk::binding(3)]] StructuredBuffer<LightProbe> probes_dyn;
+[[vk::binding(4)]] RWTexture2D<float4> output_tex;
+[[vk::binding(5)]] cbuffer _ {
+    float4 output_tex_size;
+    uint probe_count;
+    float base_layer_weight;
+    uint has_gi;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float3 gi = has_gi ? gi_tex[px].rgb : 0.0.xxx;
+    const float depth = depth_tex[px];
+
+    if (0.0 == depth) {
+        output_tex[px] = float4(gi, 1.0);
+        return;
+    }
+
+    const float2 uv = get_uv(px, output_tex_size);
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 pos_ws = view_ray_context.ray_hit_ws();
+    const float3 normal_ws = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack_normal();
+
+    // Clamped cosine lobe around the normal, so that the dot product with the radiance
+    // gives irradiance.
+    const float4 cosine_lobe = sh_eval_cosine_lobe(normal_ws);
+
+    float3 irradiance_sum = 0.0;
+    float weight_sum = 0.0;
+
+    for (uint probe_idx = 0; probe_idx < probe_count; ++probe_idx) {
+        const LightProbe probe = probes_dyn[probe_idx];
+
+        float weight = saturate(1.0 - length(pos_ws - probe.position_radius.xyz) / probe.position_radius.w);
+        weight *= weight;
+
+        if (weight > 0.0) {
+            const float3 irradiance = float3(
+                dot(probe.sh_r, cosine_lobe),
+                dot(probe.sh_g, cosine_lobe),
+                dot(probe.sh_b, cosine_lobe)
+            );
+
+            irradiance_sum += max(0.0, irradiance) * weight;
+            weight_sum += weight;
+        }
+    }
+
+    if (0.0 == weight_sum) {
+        output_tex[px] = float4(gi, 1.0);
+        return;
+    }
+
+    // The cosine-weighted average of incident radiance, as with the other diffuse GI
+    const float3 baked = irradiance_sum / weight_sum / M_PI * frame_constants.pre_exposure;
+
+    // Fades out towards the edges of the probes' coverage.
+    const float coverage = saturate(weight_sum);
+    const float blend = has_gi ? base_layer_weight * coverage : coverage;
+
+    output_tex[px] = float4(lerp(gi, baked, blend), 1.0);
+}
@@ -0,0 +1,67 @@
+// Projects the radiance in a cube map rendered by a light probe bake onto the first two
+// spherical harmonics bands, weighting every texel by the solid angle it subtends.
+
+#include "../inc/cube_map.hlsl"
+#include "../inc/sh.hlsl"
+
+[[vk::binding(0)]] Texture2DArray<float4> input_tex;
+// Three coefficient vectors per probe: red, green, blue
+[[vk::binding(1)]] RWStructuredBuffer<float4> output_buf;
+[[vk::binding(2)]] cbuffer _ {
+    uint face_width;
+    uint output_idx;
+}
+
+#define GROUP_SIZE 64
+
+groupshared float4 sh_r[GROUP_SIZE];
+groupshared float4 sh_g[GROUP_SIZE];
+groupshared float4 sh_b[GROUP_SIZE];
+
+[numthreads(GROUP_SIZE, 1, 1)]
+void main(uint thread_idx: SV_GroupIndex) {
+    const uint face_texel_count = face_width * face_width;
+
+    float4 r = 0;
+    float4 g = 0;
+    float4 b = 0;
+
+    for (uint i = thread_idx; i < face_texel_count * 6; i += GROUP_SIZE) {
+        const uint face = i / face_texel_count;
+        const uint face_texel = i % face_texel_count;
+        const uint2 px = uint2(face_texel % face_width, face_texel / face_width);
+
+        const float2 uv = (px + 0.5) / face_width * 2 - 1;
+        const float3 dir = normalize(mul(CUBE_MAP_FACE_ROTATIONS[face], float3(uv, -1.0)));
+
+        // Projected area of the texel on the unit sphere
+        const float solid_angle = 4.0 / face_texel_count / pow(1.0 + dot(uv, uv), 1.5);
+
+        const float3 radiance = input_tex[uint3(px, face)].rgb;
+        const float4 basis = sh_eval(dir) * solid_angle;
+
+        r += radiance.r * basis;
+        g += radiance.g * basis;
+        b += radiance.b * basis;
+    }
+
+    sh_r[thread_idx] = r;
+    sh_g[thread_idx] = g;
+    sh_b[thread_idx] = b;
+    GroupMemoryBarrierWithGroupSync();
+
+    for (uint stride = GROUP_SIZE / 2; stride > 0; stride /= 2) {
+        if (thread_idx < stride) {
+            sh_r[thread_idx] += sh_r[thread_idx + stride];
+            sh_g[thread_idx] += sh_g[thread_idx + stride];
+            sh_b[thread_idx] += sh_b[thread_idx + stride];
+        }
+        GroupMemoryBarrierWithGroupSync();
+    }
+
+    if (0 == thread_idx) {
+        output_buf[output_idx * 3 + 0] = sh_r[0];
+        output_buf[output_idx * 3 + 1] = sh_g[0];
+        output_buf[output_idx * 3 + 2] = sh_b[0];
+    }
+}
@@ -0,0 +1,70 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/clusters.hlsl"
+
+// Lists the punctual lights whose range spheres overlap each cluster's view-space bounding box.
+
+[[vk::binding(0)]] RWStructuredBuffer<uint> cluster_lists_buf;
+[[vk::binding(1)]] cbuffer _ {
+    LightClusterConstants light_clusters;
+};
+
+// Where the view ray through `uv` is `view_depth` in front of the eye.
+float3 point_at_view_depth(float2 uv, float view_depth) {
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv(uv);
+    const float3 origin_vs = view_ray_context.ray_origin_vs();
+
+    // The direction is scaled to a view-space Z of -1.
+    return origin_vs + view_ray_context.ray_dir_vs_h.xyz * (view_depth + origin_vs.z);
+}
+
+[numthreads(4, 4, 4)]
+void main(uint3 cluster : SV_DispatchThreadID) {
+    if (any(cluster >= light_clusters.dims)) {
+        return;
+    }
+
+    const float2 uv_min = float2(cluster.xy) / light_clusters.dims.xy;
+    const float2 uv_max = float2(cluster.xy + 1) / light_clusters.dims.xy;
+    const float depth_start = light_clusters.slice_start_depth(cluster.z);
+    const float depth_end = light_clusters.slice_end_depth(cluster.z);
+
+    float3 aabb_min = FLT_MAX;
+    float3 aabb_max = -FLT_MAX;
+
+    for (uint corner = 0; corner < 8; ++corner) {
+        const float2 uv = float2(
+            (corner & 1) ? uv_max.x : uv_min.x,
+            (corner & 2) ? uv_max.y : uv_min.y);
+        const float3 pt = point_at_view_depth(uv, (corner & 4) ? depth_end : depth_start);
+
+        aabb_min = min(aabb_min, pt);
+        aabb_max = max(aabb_max, pt);
+    }
+
+    const uint list_offset =
+        ((cluster.z * light_clusters.dims.y + cluster.y) * light_clusters.dims.x + cluster.x)
+        * LIGHT_CLUSTER_STRIDE;
+    uint count = 0;
+
+    for (uint light_idx = 0; light_idx < frame_constants.punctual_light_count && count < LIGHT_CLUSTER_MAX_LIGHTS; ++light_idx) {
+        const PunctualLight light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+
+        bool overlaps = true;
+
+        if (PUNCTUAL_LIGHT_DIRECTIONAL != light.kind && light.range > 0.0) {
+            const float3 center = position_world_to_view(light.position);
+            const float3 closest = clamp(center, aabb_min, aabb_max);
+            const float3 offset = center - closest;
+            overlaps = dot(offset, offset) <= light.range * light.range;
+        }
+
+        if (overlaps) {
+            cluster_lists_buf[list_offset + 1 + count] = light_idx;
+            ++count;
+        }
+    }
+
+    cluster_lists_buf[list_offset] = count;
+}
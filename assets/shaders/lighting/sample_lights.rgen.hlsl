@@ -4,6 +4,7 @@
 #include "../inc/blue_noise.hlsl"
 #include "../inc/rt.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
 
 [[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
 
@@ -33,13 +34,10 @@ void main() {
     const float3 urand3 = blue_noise_for_pixel(px, frame_constants.frame_index).xyz;
     const float2 urand = urand3.xy;
 
-    const uint light_count = frame_constants.triangle_light_count;
-    const uint light_idx = uint(urand3.z * light_count) % light_count;
-    //uint rng = hash3(uint3(px, frame_constants.frame_index));
-    //const uint light_idx = rng % light_count;
-    const float light_choice_pmf = 1.0 / light_count;
+    const TriangleLightSelection light_selection = select_triangle_light(urand3.z);
+    const float light_choice_pmf = light_selection.pmf;
 
-    TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+    TriangleLight triangle_light = light_selection.light;
     LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
     const float3 to_light_ws = light_sample.pos - shadow_ray_origin;
     const float dist_to_light = length(to_light_ws);
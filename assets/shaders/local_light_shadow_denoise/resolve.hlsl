@@ -0,0 +1,10 @@
+// Applies the denoised visibility to the unshadowed lighting.
+
+[[vk::binding(0)]] Texture2D<float4> lighting_tex;
+[[vk::binding(1)]] Texture2D<float2> visibility_tex;
+[[vk::binding(2)]] RWTexture2D<float4> output_tex;
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    output_tex[px] = float4(lighting_tex[px].rgb * saturate(visibility_tex[px].x), 1.0);
+}
@@ -0,0 +1,71 @@
+#include "../inc/frame_constants.hlsl"
+
+// One iteration of an edge-avoiding à-trous filter, as in "Spatiotemporal Variance-Guided Filtering"
+// by Schied et al. Visibility differences are tolerated in proportion to the estimated standard deviation,
+// so noisy regions are blurred more, while converged shadow edges are kept sharp.
+
+[[vk::binding(0)]] Texture2D<float2> input_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float3> geometric_normal_tex;
+[[vk::binding(3)]] RWTexture2D<float2> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+    uint step_size;
+};
+
+static const float KERNEL_WEIGHTS[3] = { 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0 };
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 center = input_tex[px];
+    const float center_depth = depth_tex[px];
+
+    if (0.0 == center_depth) {
+        output_tex[px] = center;
+        return;
+    }
+
+    const float3 center_normal_vs = geometric_normal_tex[px] * 2.0 - 1.0;
+
+    // Prefilter the variance, since it's noisy itself.
+    float blurred_variance = 0.0;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            const float w = KERNEL_WEIGHTS[abs(x) + 1] * KERNEL_WEIGHTS[abs(y) + 1];
+            blurred_variance += input_tex[int2(px) + int2(x, y)].y * w;
+        }
+    }
+    blurred_variance /= (KERNEL_WEIGHTS[1] + 2.0 * KERNEL_WEIGHTS[2]) * (KERNEL_WEIGHTS[1] + 2.0 * KERNEL_WEIGHTS[2]);
+
+    const float visibility_sigma = 4.0 * sqrt(max(1e-8, blurred_variance));
+
+    float visibility_sum = 0.0;
+    float variance_sum = 0.0;
+    float weight_sum = 0.0;
+
+    for (int y = -2; y <= 2; ++y) {
+        for (int x = -2; x <= 2; ++x) {
+            const int2 sample_px = int2(px) + int2(x, y) * int(step_size);
+            const float sample_depth = depth_tex[sample_px];
+
+            if (0.0 == sample_depth) {
+                continue;
+            }
+
+            const float2 sample_val = input_tex[sample_px];
+            const float3 sample_normal_vs = geometric_normal_tex[sample_px] * 2.0 - 1.0;
+
+            float w = KERNEL_WEIGHTS[abs(x)] * KERNEL_WEIGHTS[abs(y)];
+            w *= exp2(-100.0 * abs(center_normal_vs.z * (center_depth / sample_depth - 1.0)));
+            w *= pow(saturate(dot(center_normal_vs, sample_normal_vs)), 32.0);
+            w *= exp(-abs(sample_val.x - center.x) / visibility_sigma);
+
+            visibility_sum += sample_val.x * w;
+            variance_sum += sample_val.y * w * w;
+            weight_sum += w;
+        }
+    }
+
+    // The center sample always contributes with a positive weight.
+    output_tex[px] = float2(visibility_sum / weight_sum, variance_sum / (weight_sum * weight_sum));
+}
@@ -0,0 +1,98 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/bilinear.hlsl"
+
+// Temporal accumulation of the 1-spp visibility in the alpha channel of the traced lighting.
+// Also tracks the first two moments of the visibility, whose variance guides the spatial filter.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> prev_moments_tex;
+[[vk::binding(2)]] Texture2D<float2> prev_accum_tex;
+[[vk::binding(3)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(4)]] Texture2D<float> depth_tex;
+[[vk::binding(5)]] RWTexture2D<float4> output_moments_tex;
+[[vk::binding(6)]] RWTexture2D<float2> output_tex;
+[[vk::binding(7)]] cbuffer _ {
+    float4 output_tex_size;
+};
+
+// Caps the history length, so that moving shadows don't trail for too long.
+static const float MAX_SAMPLE_COUNT = 32.0;
+
+// Until this many samples have been accumulated, the variance is estimated spatially.
+static const float MIN_SAMPLE_COUNT_FOR_TEMPORAL_VARIANCE = 4.0;
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    if (0.0 == depth_tex[px]) {
+        output_moments_tex[px] = 0.0;
+        output_tex[px] = 0.0;
+        return;
+    }
+
+    const float visibility = input_tex[px].a;
+
+    float2 spatial_moments = 0.0;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            const float v = input_tex[int2(px) + int2(x, y)].a;
+            spatial_moments += float2(v, v * v);
+        }
+    }
+    spatial_moments /= 9.0;
+
+    const float spatial_mean = spatial_moments.x;
+    const float spatial_dev = sqrt(max(0.0, spatial_moments.y - spatial_mean * spatial_mean));
+
+    const float4 reproj = reprojection_tex[px];
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+    const float2 history_uv = uv + reproj.xy;
+
+    // Only take history from the texels which `calculate_reprojection_map.hlsl` found to be the same surface.
+    const uint quad_reproj_valid_packed = uint(reproj.z * 15.0 + 0.5);
+    const float4 quad_reproj_valid = (quad_reproj_valid_packed & uint4(1, 2, 4, 8)) != 0;
+
+    const Bilinear bilinear = get_bilinear_filter(history_uv, output_tex_size.xy);
+    const float4 history_weights = get_bilinear_custom_weights(bilinear, quad_reproj_valid);
+
+    const bool is_disoccluded =
+        frame_constants.frame_index == 0
+        || reproj.w < 0.0
+        || dot(history_weights, 1.0.xxxx) < 1e-3;
+
+    float4 prev_moments = 0.0;
+    float prev_visibility = visibility;
+
+    if (!is_disoccluded) {
+        prev_moments = apply_bilinear_custom_weights(
+            prev_moments_tex[bilinear.px0()],
+            prev_moments_tex[bilinear.px1()],
+            prev_moments_tex[bilinear.px2()],
+            prev_moments_tex[bilinear.px3()],
+            history_weights
+        );
+
+        prev_visibility = apply_bilinear_custom_weights(
+            prev_accum_tex[bilinear.px0()].xxxx,
+            prev_accum_tex[bilinear.px1()].xxxx,
+            prev_accum_tex[bilinear.px2()].xxxx,
+            prev_accum_tex[bilinear.px3()].xxxx,
+            history_weights
+        ).x;
+
+        // Reject history which strays too far from the current neighborhood, e.g. due to moving occluders.
+        prev_visibility = clamp(prev_visibility, spatial_mean - spatial_dev, spatial_mean + spatial_dev);
+    }
+
+    const float sample_count = min(prev_moments.z + 1.0, MAX_SAMPLE_COUNT);
+    const float blend = 1.0 / sample_count;
+
+    const float2 moments = lerp(prev_moments.xy, float2(visibility, visibility * visibility), blend);
+    float variance = max(0.0, moments.y - moments.x * moments.x);
+
+    if (sample_count < MIN_SAMPLE_COUNT_FOR_TEMPORAL_VARIANCE) {
+        variance = max(variance, spatial_dev * spatial_dev);
+    }
+
+    output_moments_tex[px] = float4(moments, sample_count, 0.0);
+    output_tex[px] = float2(lerp(prev_visibility, visibility, blend), variance);
+}
@@ -0,0 +1,87 @@
+#include "../inc/atmosphere.hlsl"
+
+// Contribution of all scattering orders past the first, approximated as in section 5.5 of
+// "A Scalable and Production Ready Sky and Atmosphere Rendering Technique" by Sébastien Hillaire:
+// second-order scattering is integrated assuming an isotropic phase function, and higher orders
+// are a geometric series of the fraction of light which gets scattered again.
+//
+// Indexed by the cosine of the sun's zenith angle, and altitude; see `atmosphere_multiple_scattering_lut_uv`.
+// Transmittance is ray marched rather than read from its LUT, so that both can be computed in any order.
+
+[[vk::binding(0)]] RWTexture2D<float4> output_tex;
+
+static const uint SQRT_DIRECTION_COUNT = 8;
+static const uint STEP_COUNT = 20;
+
+float3 sun_transmittance(float3 pos, float3 sun_dir) {
+    if (atmosphere_ray_hits_ground(pos, sun_dir)) {
+        return 0.0;
+    }
+
+    return atmosphere_integrate_transmittance(pos, sun_dir);
+}
+
+[numthreads(8, 8, 1)]
+void main(in uint2 px : SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) / ATMOSPHERE_MULTIPLE_SCATTERING_LUT_DIMS;
+
+    const float sun_cos_zenith = uv.x * 2.0 - 1.0;
+    const float3 sun_dir = float3(sqrt(max(0.0, 1.0 - sun_cos_zenith * sun_cos_zenith)), sun_cos_zenith, 0);
+
+    const float altitude = max(ATMOSPHERE_VIEWER_ALTITUDE, uv.y * frame_constants.atmosphere.atmosphere_height);
+    const float3 pos = float3(0, atmosphere_bottom_radius() + altitude, 0);
+
+    static const float ISOTROPIC_PHASE = 1.0 / (4.0 * M_PI);
+    static const uint DIRECTION_COUNT = SQRT_DIRECTION_COUNT * SQRT_DIRECTION_COUNT;
+
+    float3 second_order = 0.0;
+    float3 scattered_fraction = 0.0;
+
+    for (uint i = 0; i < DIRECTION_COUNT; ++i) {
+        // Stratified uniform sphere directions
+        const float2 u = (float2(i % SQRT_DIRECTION_COUNT, i / SQRT_DIRECTION_COUNT) + 0.5) / SQRT_DIRECTION_COUNT;
+        const float cos_theta = 1.0 - 2.0 * u.x;
+        const float sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        const float phi = u.y * M_TAU;
+        const float3 dir = float3(cos(phi) * sin_theta, cos_theta, sin(phi) * sin_theta);
+
+        const float ray_length = atmosphere_ray_length(pos, dir);
+        const float dt = ray_length / STEP_COUNT;
+
+        float3 radiance = 0.0;
+        float3 fraction = 0.0;
+        float3 transmittance = 1.0;
+
+        for (uint step_idx = 0; step_idx < STEP_COUNT; ++step_idx) {
+            const float3 sample_pos = pos + dir * (step_idx + 0.5) * dt;
+            const AtmosphereMedium medium = AtmosphereMedium::at(sample_pos);
+
+            const float3 step_transmittance = exp(-medium.extinction * dt);
+            const float3 integral = transmittance * (1.0 - step_transmittance) / max(1e-10, medium.extinction);
+
+            radiance += integral * medium.scattering() * ISOTROPIC_PHASE * sun_transmittance(sample_pos, sun_dir);
+            fraction += integral * medium.scattering();
+            transmittance *= step_transmittance;
+        }
+
+        // Light bouncing off the ground, which is Lambertian.
+        if (atmosphere_ray_hits_ground(pos, dir)) {
+            const float3 ground_pos = pos + dir * ray_length;
+            const float3 ground_normal = normalize(ground_pos);
+            const float ndotl = saturate(dot(ground_normal, sun_dir));
+
+            radiance +=
+                transmittance
+                * sun_transmittance(ground_pos + ground_normal * ATMOSPHERE_VIEWER_ALTITUDE, sun_dir)
+                * ndotl
+                * frame_constants.atmosphere.ground_albedo
+                * M_FRAC_1_PI;
+        }
+
+        // Integrated against the isotropic phase function
+        second_order += radiance / DIRECTION_COUNT;
+        scattered_fraction += fraction / DIRECTION_COUNT;
+    }
+
+    output_tex[px] = float4(second_order / max(1e-5, 1.0 - scattered_fraction), 1);
+}
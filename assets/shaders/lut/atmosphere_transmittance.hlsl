@@ -0,0 +1,16 @@
+#include "../inc/atmosphere.hlsl"
+
+// Transmittance to the top of the atmosphere, parametrized as in `atmosphere_transmittance_lut_uv`.
+
+[[vk::binding(0)]] RWTexture2D<float4> output_tex;
+
+[numthreads(8, 8, 1)]
+void main(in uint2 px : SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) / ATMOSPHERE_TRANSMITTANCE_LUT_DIMS;
+    const float2 r_mu = atmosphere_transmittance_lut_r_mu(uv);
+
+    const float3 pos = float3(0, r_mu.x, 0);
+    const float3 dir = float3(sqrt(max(0.0, 1.0 - r_mu.y * r_mu.y)), r_mu.y, 0);
+
+    output_tex[px] = float4(atmosphere_integrate_transmittance(pos, dir), 1);
+}
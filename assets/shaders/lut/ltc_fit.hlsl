@@ -0,0 +1,218 @@
+#include "../inc/brdf.hlsl"
+#include "../inc/quasi_random.hlsl"
+#include "../inc/ltc.hlsl"
+
+// Fits linearly transformed cosines to the cosine-weighted GGX BRDF, following
+// "Real-Time Polygonal-Light Shading with Linearly Transformed Cosines" by Heitz et al.
+//
+// Each texel is fitted independently with Nelder-Mead, minimizing the cubed difference
+// between the normalized distributions, estimated with samples from both of them.
+
+[[vk::binding(0)]] RWTexture2D<float4> output_tex;
+
+static const uint NUM_SAMPLES = 512;
+static const uint MAX_ITERATIONS = 128;
+static const float MIN_ALPHA = 1e-3;
+
+float3x3 inverse_3x3(float3x3 m) {
+    const float3 c0 = cross(m[1], m[2]);
+    const float3 c1 = cross(m[2], m[0]);
+    const float3 c2 = cross(m[0], m[1]);
+    const float det = dot(m[0], c0);
+    return transpose(float3x3(c0, c1, c2)) / det;
+}
+
+struct FitTarget {
+    SpecularBrdf brdf;
+    float3 wo;
+
+    // Basis of the fitted distribution, with the average direction of the BRDF lobe as Z
+    float3x3 frame;
+
+    // Directional albedo, normalizing the BRDF into a distribution
+    float norm;
+};
+
+struct Ltc {
+    float3x3 m;
+    float3x3 minv;
+    float det_minv;
+
+    static Ltc from_params(FitTarget target, float3 params) {
+        const float3x3 scale_skew = float3x3(
+            max(params.x, MIN_ALPHA), 0, params.z,
+            0, max(params.y, MIN_ALPHA), 0,
+            0, 0, 1
+        );
+
+        Ltc res;
+        res.m = mul(target.frame, scale_skew);
+        res.minv = inverse_3x3(res.m);
+        res.det_minv = abs(determinant(res.minv));
+        return res;
+    }
+
+    // Probability density wrt solid angle
+    float eval(float3 l) {
+        const float3 lo = mul(minv, l);
+        const float len = length(lo);
+        const float d = max(0.0, lo.z / len) * M_FRAC_1_PI;
+        return d * det_minv / (len * len * len);
+    }
+
+    float3 sample(float2 urand) {
+        const float phi = urand.x * M_TAU;
+        const float cos_theta = sqrt(max(0.0, 1.0 - urand.y));
+        const float sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        const float3 lo = float3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+        return normalize(mul(m, lo));
+    }
+};
+
+// Normalized cosine-weighted BRDF, and its sampling density; both wrt solid angle
+float2 eval_target(FitTarget target, float3 l) {
+    if (l.z <= 0.0) {
+        return 0.0;
+    }
+
+    const BrdfValue value = target.brdf.evaluate(target.wo, l);
+    return float2(value.value.x * l.z / target.norm, value.pdf * l.z);
+}
+
+float fit_error(FitTarget target, float3 params) {
+    const Ltc ltc = Ltc::from_params(target, params);
+    float error = 0.0;
+
+    for (uint i = 0; i < NUM_SAMPLES; ++i) {
+        const float2 urand = hammersley(i, NUM_SAMPLES);
+
+        {
+            const float3 l = ltc.sample(urand);
+            const float2 brdf = eval_target(target, l);
+            const float ltc_pdf = ltc.eval(l);
+            const float diff = abs(brdf.x - ltc_pdf);
+            error += diff * diff * diff / max(1e-10, brdf.y + ltc_pdf);
+        }
+
+        {
+            const BrdfSample brdf_sample = target.brdf.sample(target.wo, urand);
+            if (brdf_sample.is_valid()) {
+                const float3 l = brdf_sample.wi;
+                const float2 brdf = eval_target(target, l);
+                const float ltc_pdf = ltc.eval(l);
+                const float diff = abs(brdf.x - ltc_pdf);
+                error += diff * diff * diff / max(1e-10, brdf.y + ltc_pdf);
+            }
+        }
+    }
+
+    return error / NUM_SAMPLES;
+}
+
+float3 nelder_mead(FitTarget target, float3 start, float delta) {
+    float3 x[4];
+    float f[4];
+
+    x[0] = start;
+    x[1] = start + float3(delta, 0, 0);
+    x[2] = start + float3(0, delta, 0);
+    x[3] = start + float3(0, 0, delta);
+
+    for (uint i = 0; i < 4; ++i) {
+        f[i] = fit_error(target, x[i]);
+    }
+
+    uint lo = 0;
+
+    for (uint iter = 0; iter < MAX_ITERATIONS; ++iter) {
+        lo = 0;
+        uint hi = 0;
+        for (uint i = 1; i < 4; ++i) {
+            lo = f[i] < f[lo] ? i : lo;
+            hi = f[i] > f[hi] ? i : hi;
+        }
+
+        uint next_hi = lo;
+        for (uint i = 0; i < 4; ++i) {
+            next_hi = (i != hi && f[i] > f[next_hi]) ? i : next_hi;
+        }
+
+        if (abs(f[hi] - f[lo]) <= 1e-5 * abs(f[lo])) {
+            break;
+        }
+
+        const float3 centroid = (x[0] + x[1] + x[2] + x[3] - x[hi]) / 3.0;
+
+        const float3 xr = centroid + (centroid - x[hi]);
+        const float fr = fit_error(target, xr);
+
+        if (fr < f[lo]) {
+            const float3 xe = centroid + 2.0 * (centroid - x[hi]);
+            const float fe = fit_error(target, xe);
+            x[hi] = fe < fr ? xe : xr;
+            f[hi] = fe < fr ? fe : fr;
+        } else if (fr < f[next_hi]) {
+            x[hi] = xr;
+            f[hi] = fr;
+        } else {
+            const float3 xc = fr < f[hi]
+                ? centroid + 0.5 * (xr - centroid)
+                : centroid + 0.5 * (x[hi] - centroid);
+            const float fc = fit_error(target, xc);
+
+            if (fc < min(fr, f[hi])) {
+                x[hi] = xc;
+                f[hi] = fc;
+            } else {
+                for (uint i = 0; i < 4; ++i) {
+                    if (i != lo) {
+                        x[i] = x[lo] + 0.5 * (x[i] - x[lo]);
+                        f[i] = fit_error(target, x[i]);
+                    }
+                }
+            }
+        }
+    }
+
+    return x[lo];
+}
+
+[numthreads(8, 8, 1)]
+void main(in uint2 pix : SV_DispatchThreadID) {
+    // Same parametrization as `brdf_fg.hlsl`
+    const float ndotv = (pix.x / (LTC_LUT_DIMS.x - 1.0)) * (1.0 - 1e-3) + 1e-3;
+    const float roughness = max(MIN_ALPHA, pix.y / (LTC_LUT_DIMS.y - 1.0));
+
+    FitTarget target;
+    target.brdf.roughness = roughness;
+    target.brdf.albedo = 1.0;
+    target.wo = float3(sqrt(1.0 - ndotv * ndotv), 0, ndotv);
+
+    float norm = 0.0;
+    float3 average_dir = 0.0;
+    for (uint i = 0; i < NUM_SAMPLES; ++i) {
+        const BrdfSample brdf_sample = target.brdf.sample(target.wo, hammersley(i, NUM_SAMPLES));
+        if (brdf_sample.is_valid()) {
+            norm += brdf_sample.value_over_pdf.x;
+            average_dir += brdf_sample.value_over_pdf.x * brdf_sample.wi;
+        }
+    }
+
+    target.norm = max(1e-5, norm / NUM_SAMPLES);
+
+    // The lobe is symmetric about the plane of incidence
+    const float3 z = normalize(float3(average_dir.x, 0, max(1e-5, average_dir.z)));
+    const float3 y = float3(0, 1, 0);
+    const float3 x = cross(y, z);
+    target.frame = float3x3(
+        x.x, y.x, z.x,
+        x.y, y.y, z.y,
+        x.z, y.z, z.z
+    );
+
+    const float3 params = nelder_mead(target, float3(roughness, roughness, 0), 0.05);
+    const float3x3 minv = Ltc::from_params(target, params).minv;
+
+    // Only the direction of the transformed vectors matters, so the scale is normalized away.
+    output_tex[pix] = float4(minv[0][0], minv[0][2], minv[2][0], minv[2][2]) / minv[1][1];
+}
@@ -10,6 +10,8 @@
 [[vk::binding(5)]] cbuffer _ {
     float4 depth_tex_size;
     float4 output_tex_size;
+    // Fraction of the frame time during which the shutter is open
+    float shutter_fraction;
 };
 
 float2 depth_cmp(float center_depth, float sample_depth, float depth_scale) {
@@ -34,10 +36,10 @@ void main(uint2 px: SV_DispatchThreadID) {
 #endif
 
     const float2 uv = get_uv(px, output_tex_size);
-    float blur_scale = 0.5;
 
-    // TODO: Fixed shutter time (add time_delta_seconds)
-    blur_scale /= frame_constants.delta_time_seconds * 60.0;
+    // Velocities are in uv per frame, so a shutter open for a fraction of the frame
+    // smears by that fraction, regardless of the frame rate.
+    const float blur_scale = shutter_fraction;
 
     // Scramble tile coordinates to diffuse the tile quantization in noise
     int noise1;
@@ -0,0 +1,70 @@
+#include "../inc/samplers.hlsl"
+#include "../inc/bindless_textures.hlsl"
+#include "../volumetric_fog/fog_common.hlsl"
+#include "particle_common.hlsl"
+
+// Shades the particle quads with their per-particle lighting, and fogs them the same way
+// as `volumetric_fog/apply.hlsl` does the opaque surfaces.
+
+[[vk::binding(2)]] StructuredBuffer<ParticleEmitter> emitters_dyn;
+[[vk::binding(3)]] Texture3D<float4> fog_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+    FogConstants fog;
+    uint fog_enabled;
+};
+
+struct PsIn {
+    float4 position: SV_Position;
+    [[vk::location(0)]] float3 radiance: TEXCOORD0;
+    [[vk::location(1)]] float opacity: TEXCOORD1;
+    [[vk::location(2)]] float2 uv: TEXCOORD2;
+    [[vk::location(3)]] float3 vs_pos: TEXCOORD3;
+    [[vk::location(4)]] nointerpolation uint emitter_idx: TEXCOORD4;
+};
+
+struct PsOut {
+    // Premultiplied alpha
+    float4 color: SV_TARGET0;
+
+    // Blended into the TAA responsive mask, as particles move without motion vectors.
+    float4 responsive: SV_TARGET1;
+};
+
+PsOut main(PsIn ps) {
+    const ParticleEmitter emitter = emitters_dyn[ps.emitter_idx];
+
+    float4 shape;
+    if (emitter.texture != PARTICLE_NO_TEXTURE) {
+        shape = bindless_textures[NonUniformResourceIndex(emitter.texture)].Sample(sampler_llr, ps.uv);
+    } else {
+        const float2 from_center = ps.uv * 2.0 - 1.0;
+        const float falloff = saturate(1.0 - dot(from_center, from_center));
+        shape = float4(1.0.xxx, falloff * falloff);
+    }
+
+    const float opacity = saturate(ps.opacity * shape.a);
+    const float3 radiance = ps.radiance * shape.rgb;
+
+    // Light scattered by the fog in front of the particle, and transmittance through it
+    float4 fog_value = float4(0, 0, 0, 1);
+    if (fog_enabled) {
+        const float2 uv = ps.position.xy * output_tex_size.zw;
+
+        // Froxels store values at their far ends, so the first one is faded in from clear air.
+        const float slice = fog.depth_to_w(-ps.vs_pos.z) * fog.froxel_dims.z;
+        const float w = (max(1.0, slice) - 0.5) / fog.froxel_dims.z;
+
+        fog_value = fog_tex.SampleLevel(sampler_llc, float3(uv, w), 0);
+        fog_value = lerp(float4(0, 0, 0, 1), fog_value, saturate(slice));
+    }
+
+    // The fog in front was already added over what's behind, but only shows through
+    // the uncovered part now. Additive particles don't cover anything.
+    const float coverage = emitter.blend_mode == PARTICLE_BLEND_MODE_ADDITIVE ? 0.0 : opacity;
+
+    PsOut ps_out;
+    ps_out.color = float4(radiance * opacity * fog_value.a + fog_value.rgb * coverage, coverage);
+    ps_out.responsive = opacity;
+    return ps_out;
+}
@@ -0,0 +1,66 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/working_primaries.hlsl"
+#include "particle_common.hlsl"
+
+// Expands the particles into camera-facing quads, back to front.
+
+[[vk::binding(0)]] StructuredBuffer<Particle> particles_buf;
+[[vk::binding(1)]] StructuredBuffer<uint2> sort_keys_buf;
+[[vk::binding(2)]] StructuredBuffer<ParticleEmitter> emitters_dyn;
+
+struct VsOut {
+    float4 position: SV_Position;
+    [[vk::location(0)]] float3 radiance: TEXCOORD0;
+    [[vk::location(1)]] float opacity: TEXCOORD1;
+    [[vk::location(2)]] float2 uv: TEXCOORD2;
+    [[vk::location(3)]] float3 vs_pos: TEXCOORD3;
+    [[vk::location(4)]] nointerpolation uint emitter_idx: TEXCOORD4;
+    float clip_distance: SV_ClipDistance0;
+};
+
+static const float2 QUAD_CORNERS[6] = {
+    float2(-1, -1), float2(1, -1), float2(1, 1),
+    float2(-1, -1), float2(1, 1), float2(-1, 1),
+};
+
+VsOut main(uint vid: SV_VertexID, uint instance_idx: SV_InstanceID) {
+    VsOut vsout;
+
+    const uint2 sort_key = sort_keys_buf[instance_idx];
+
+    // Dead; collapse the quad.
+    if (0 == sort_key.x) {
+        vsout.position = 0.0;
+        vsout.radiance = 0.0;
+        vsout.opacity = 0.0;
+        vsout.uv = 0.0;
+        vsout.vs_pos = 0.0;
+        vsout.emitter_idx = 0;
+        vsout.clip_distance = 0.0;
+        return vsout;
+    }
+
+    const Particle p = particles_buf[sort_key.y];
+    const ParticleEmitter emitter = emitters_dyn[p.emitter_idx];
+
+    const float t = p.life_fraction();
+    const float size = lerp(emitter.size_start, emitter.size_end, t);
+    const float4 color = lerp(emitter.color_start, emitter.color_end, t);
+
+    const float2 corner = QUAD_CORNERS[vid];
+    const float3 corner_offset_vs = float3(corner * size * 0.5, 0.0);
+    const float3 vs_pos =
+        mul(frame_constants.view_constants.world_to_view, float4(p.position, 1.0)).xyz
+        + corner_offset_vs;
+
+    vsout.position = mul(frame_constants.view_constants.view_to_sample, float4(vs_pos, 1.0));
+    vsout.radiance = rec709_to_working(color.rgb) * p.lighting
+        + rec709_to_working(emitter.emissive) * frame_constants.pre_exposure;
+    vsout.opacity = saturate(color.a);
+    vsout.uv = corner * float2(0.5, -0.5) + 0.5;
+    vsout.vs_pos = vs_pos;
+    vsout.emitter_idx = p.emitter_idx;
+    vsout.clip_distance = view_clip_distance(p.position + direction_view_to_world(corner_offset_vs));
+
+    return vsout;
+}
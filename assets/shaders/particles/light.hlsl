@@ -0,0 +1,24 @@
+#include "../inc/samplers.hlsl"
+#include "lighting.hlsl"
+#include "particle_common.hlsl"
+
+// Lights the particles without ray tracing: unshadowed, with the sky for indirect lighting.
+// See `light.rgen.hlsl` for the ray traced version.
+
+[[vk::binding(0)]] RWStructuredBuffer<Particle> particles_buf;
+[[vk::binding(1)]] TextureCube<float4> sky_cube_tex;
+
+[numthreads(64, 1, 1)]
+void main(uint slot: SV_DispatchThreadID) {
+    Particle p = particles_buf[slot];
+    if (!p.is_alive()) {
+        return;
+    }
+
+    const float3 normal_ws = normalize(get_eye_position() - p.position);
+
+    p.lighting = particle_direct_lighting(p.position, normal_ws, 1.0)
+        + sky_cube_tex.SampleLevel(sampler_llr, normal_ws, 0).rgb;
+
+    particles_buf[slot] = p;
+}
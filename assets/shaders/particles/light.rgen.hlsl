@@ -0,0 +1,41 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/hash.hlsl"
+#include "../ircache/bindings.hlsl"
+#include "lighting.hlsl"
+#include "particle_common.hlsl"
+
+// Lights the particles with a shadow ray towards the sun, and indirect lighting
+// from the irradiance cache, which also gets entries allocated for the particles this way.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] RWStructuredBuffer<Particle> particles_buf;
+DEFINE_IRCACHE_BINDINGS(1, 2, 3, 4, 5, 6, 7, 8, 9)
+
+#include "../ircache/lookup.hlsl"
+
+[shader("raygeneration")]
+void main() {
+    const uint slot = DispatchRaysIndex().x;
+
+    Particle p = particles_buf[slot];
+    if (!p.is_alive()) {
+        return;
+    }
+
+    uint rng = hash2(uint2(slot, frame_constants.frame_index));
+
+    const float3 eye_pos = get_eye_position();
+    const float3 normal_ws = normalize(eye_pos - p.position);
+
+    const float sun_visibility = rt_is_shadowed(
+        acceleration_structure,
+        new_ray(p.position, SUN_DIRECTION, 1e-3, FLT_MAX)) ? 0.0 : 1.0;
+
+    const float3 gi = IrcacheLookupParams::create(eye_pos, p.position, normal_ws)
+        .with_query_rank(1)
+        .lookup(rng);
+
+    p.lighting = particle_direct_lighting(p.position, normal_ws, sun_visibility) + gi;
+    particles_buf[slot] = p;
+}
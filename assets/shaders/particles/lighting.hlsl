@@ -0,0 +1,47 @@
+#ifndef PARTICLES_LIGHTING_HLSL
+#define PARTICLES_LIGHTING_HLSL
+
+#include "../inc/frame_constants.hlsl"
+#include "../inc/math.hlsl"
+#include "../inc/sun.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/rect.hlsl"
+
+// Particles are lit as camera-facing cards. Light from behind gets through them too,
+// as with thin smoke and dust, hence the wrapped cosine.
+float particle_wrapped_cos(float3 normal_ws, float3 wi) {
+    return saturate(dot(normal_ws, wi) * 0.5 + 0.5);
+}
+
+// Direct light reflected by a particle per unit of albedo. Local lights are unshadowed.
+float3 particle_direct_lighting(float3 pos_ws, float3 normal_ws, float sun_visibility) {
+    float3 irradiance = SUN_COLOR * particle_wrapped_cos(normal_ws, SUN_DIRECTION) * sun_visibility;
+
+    for (uint light_idx = 0; light_idx < frame_constants.punctual_light_count; ++light_idx) {
+        const PunctualLight light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+        const PunctualLightSample light_sample = light.sample(pos_ws);
+
+        irradiance +=
+            light_sample.radiance
+            * frame_constants.pre_exposure
+            * particle_wrapped_cos(normal_ws, light_sample.wi);
+    }
+
+    // Rect lights as seen from their centers
+    for (uint light_idx = 0; light_idx < frame_constants.rect_light_count; ++light_idx) {
+        const RectLight light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+        const float3 to_light = light.sample_point(0.5.xx) - pos_ws;
+        const float dist2 = max(1e-5, dot(to_light, to_light));
+        const float3 wi = to_light * rsqrt(dist2);
+
+        irradiance +=
+            light.radiance
+            * frame_constants.pre_exposure
+            * max(0.0, light.emission_cos(-wi)) * light.area() / dist2
+            * particle_wrapped_cos(normal_ws, wi);
+    }
+
+    return irradiance / M_PI;
+}
+
+#endif  // PARTICLES_LIGHTING_HLSL
@@ -0,0 +1,60 @@
+#ifndef PARTICLES_PARTICLE_COMMON_HLSL
+#define PARTICLES_PARTICLE_COMMON_HLSL
+
+#define PARTICLE_BLEND_MODE_ALPHA 0
+#define PARTICLE_BLEND_MODE_ADDITIVE 1
+
+#define PARTICLE_NO_TEXTURE 0xffffffff
+
+// Must match `GpuParticleEmitter` in `particles.rs`
+struct ParticleEmitter {
+    float3 position;
+    float spawn_radius;
+    float3 velocity;
+    float velocity_randomness;
+    float3 acceleration;
+    float drag;
+    float4 color_start;
+    float4 color_end;
+    float3 emissive;
+    float lifetime;
+    float size_start;
+    float size_end;
+    uint first_slot;
+    uint slot_count;
+    // Relative to `first_slot`; wraps around `slot_count`.
+    uint spawn_slot;
+    uint spawn_count;
+    uint blend_mode;
+    uint texture;
+    uint reset;
+    uint pad0;
+    uint pad1;
+    uint pad2;
+
+    bool contains_slot(uint slot) {
+        return slot >= first_slot && slot - first_slot < slot_count;
+    }
+};
+
+// Must match `PARTICLE_STRIDE` in `particles.rs`
+struct Particle {
+    float3 position;
+    float age;
+    float3 velocity;
+    float lifetime;
+    // Reflected towards the eye per unit of albedo, pre-exposed.
+    float3 lighting;
+    uint emitter_idx;
+
+    bool is_alive() {
+        return age < lifetime;
+    }
+
+    // 0 when spawned, 1 when about to die.
+    float life_fraction() {
+        return saturate(age / lifetime);
+    }
+};
+
+#endif  // PARTICLES_PARTICLE_COMMON_HLSL
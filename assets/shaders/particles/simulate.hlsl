@@ -0,0 +1,89 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/hash.hlsl"
+#include "../inc/math.hlsl"
+#include "particle_common.hlsl"
+
+// Spawns and moves the particles of all the emitters, one thread per slot of the pool,
+// and outputs the keys to sort them by.
+//
+// Lighting is left to `light.hlsl` or `light.rgen.hlsl`.
+
+[[vk::binding(0)]] RWStructuredBuffer<Particle> particles_buf;
+// Distance to the eye, and slot; zero distance for dead particles, which sorts them last.
+[[vk::binding(1)]] RWStructuredBuffer<uint2> sort_keys_buf;
+[[vk::binding(2)]] StructuredBuffer<ParticleEmitter> emitters_dyn;
+[[vk::binding(3)]] cbuffer _ {
+    uint emitter_count;
+    float dt;
+};
+
+Particle dead_particle() {
+    Particle p;
+    p.position = 0.0;
+    p.age = 0.0;
+    p.velocity = 0.0;
+    p.lifetime = 0.0;
+    p.lighting = 0.0;
+    p.emitter_idx = 0;
+    return p;
+}
+
+[numthreads(64, 1, 1)]
+void main(uint slot: SV_DispatchThreadID) {
+    uint emitter_idx = 0;
+    for (; emitter_idx < emitter_count; ++emitter_idx) {
+        if (emitters_dyn[emitter_idx].contains_slot(slot)) {
+            break;
+        }
+    }
+
+    // Not owned by any emitter
+    if (emitter_idx == emitter_count) {
+        particles_buf[slot] = dead_particle();
+        sort_keys_buf[slot] = uint2(0, slot);
+        return;
+    }
+
+    const ParticleEmitter emitter = emitters_dyn[emitter_idx];
+    Particle p = emitter.reset ? dead_particle() : particles_buf[slot];
+
+    const uint local_slot = slot - emitter.first_slot;
+    const uint spawn_rank = (local_slot + emitter.slot_count - emitter.spawn_slot) % emitter.slot_count;
+
+    if (spawn_rank < emitter.spawn_count) {
+        uint rng = hash2(uint2(slot, frame_constants.frame_index));
+        const float4 urand = float4(
+            uint_to_u01_float(hash1_mut(rng)),
+            uint_to_u01_float(hash1_mut(rng)),
+            uint_to_u01_float(hash1_mut(rng)),
+            uint_to_u01_float(hash1_mut(rng)));
+        const float2 urand2 = float2(uint_to_u01_float(hash1_mut(rng)), uint_to_u01_float(hash1_mut(rng)));
+
+        // Uniform within a sphere
+        const float3 offset = uniform_sample_sphere(urand.xy) * emitter.spawn_radius * pow(urand.z, 1.0 / 3.0);
+        const float3 velocity_offset = uniform_sample_sphere(urand2) * emitter.velocity_randomness * urand.w;
+
+        p.position = emitter.position + offset;
+        p.velocity = emitter.velocity + velocity_offset;
+        p.lifetime = emitter.lifetime;
+        p.lighting = 0.0;
+
+        // Spread the spawns over the frame, so that they don't come out in clumps;
+        // the first ones in the ring are the oldest.
+        const float age = dt * (1.0 - (spawn_rank + 0.5) / emitter.spawn_count);
+        p.position += (p.velocity + 0.5 * emitter.acceleration * age) * age;
+        p.velocity += emitter.acceleration * age;
+        p.age = age;
+    } else if (p.is_alive()) {
+        p.velocity += emitter.acceleration * dt;
+        p.velocity *= exp(-emitter.drag * dt);
+        p.position += p.velocity * dt;
+        p.age += dt;
+    }
+
+    p.emitter_idx = emitter_idx;
+    particles_buf[slot] = p;
+
+    const float dist = max(1e-5, length(p.position - get_eye_position()));
+    sort_keys_buf[slot] = uint2(p.is_alive() ? asuint(dist) : 0, slot);
+}
@@ -0,0 +1,19 @@
+#ifndef PARTICLES_SORT_COMMON_HLSL
+#define PARTICLES_SORT_COMMON_HLSL
+
+// Bitonic sort into decreasing keys, by the distance to the eye in `x`.
+//
+// Compares the elements at `i` and `i + compare_distance`, as part of merging sequences
+// of `merge_size`; `element_idx` is the index of `i` in the whole array.
+// Returns whether they need swapping.
+bool bitonic_needs_swap(uint2 a, uint2 b, uint element_idx, uint merge_size) {
+    const bool descending = (element_idx & merge_size) == 0;
+    return descending ? a.x < b.x : a.x > b.x;
+}
+
+// The first element of the pair which thread `thread_idx` compares.
+uint bitonic_pair_start(uint thread_idx, uint compare_distance) {
+    return (thread_idx / compare_distance) * 2 * compare_distance + thread_idx % compare_distance;
+}
+
+#endif  // PARTICLES_SORT_COMMON_HLSL
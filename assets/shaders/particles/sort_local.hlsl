@@ -0,0 +1,53 @@
+#include "sort_common.hlsl"
+
+// Sorts blocks of the keys in groupshared memory. With a zero `merge_size`, fully sorts each
+// block; otherwise, finishes merging sequences of `merge_size` after `sort_step.hlsl` compared
+// the elements further apart than a block.
+
+#define BLOCK_SIZE 1024
+
+[[vk::binding(0)]] RWStructuredBuffer<uint2> keys_buf;
+[[vk::binding(1)]] cbuffer _ {
+    uint merge_size;
+};
+
+groupshared uint2 keys[BLOCK_SIZE];
+
+void compare_and_swap(uint thread_idx, uint block_start, uint merge_size, uint compare_distance) {
+    const uint i = bitonic_pair_start(thread_idx, compare_distance);
+    const uint l = i + compare_distance;
+
+    const uint2 a = keys[i];
+    const uint2 b = keys[l];
+
+    if (bitonic_needs_swap(a, b, block_start + i, merge_size)) {
+        keys[i] = b;
+        keys[l] = a;
+    }
+
+    GroupMemoryBarrierWithGroupSync();
+}
+
+[numthreads(BLOCK_SIZE / 2, 1, 1)]
+void main(uint thread_idx: SV_GroupIndex, uint group_idx: SV_GroupID) {
+    const uint block_start = group_idx * BLOCK_SIZE;
+
+    keys[thread_idx] = keys_buf[block_start + thread_idx];
+    keys[thread_idx + BLOCK_SIZE / 2] = keys_buf[block_start + thread_idx + BLOCK_SIZE / 2];
+    GroupMemoryBarrierWithGroupSync();
+
+    if (0 == merge_size) {
+        for (uint size = 2; size <= BLOCK_SIZE; size *= 2) {
+            for (uint dist = size / 2; dist > 0; dist /= 2) {
+                compare_and_swap(thread_idx, block_start, size, dist);
+            }
+        }
+    } else {
+        for (uint dist = BLOCK_SIZE / 2; dist > 0; dist /= 2) {
+            compare_and_swap(thread_idx, block_start, merge_size, dist);
+        }
+    }
+
+    keys_buf[block_start + thread_idx] = keys[thread_idx];
+    keys_buf[block_start + thread_idx + BLOCK_SIZE / 2] = keys[thread_idx + BLOCK_SIZE / 2];
+}
@@ -0,0 +1,23 @@
+#include "sort_common.hlsl"
+
+// One step of the bitonic sort, for elements too far apart for `sort_local.hlsl`.
+
+[[vk::binding(0)]] RWStructuredBuffer<uint2> keys_buf;
+[[vk::binding(1)]] cbuffer _ {
+    uint merge_size;
+    uint compare_distance;
+};
+
+[numthreads(64, 1, 1)]
+void main(uint thread_idx: SV_DispatchThreadID) {
+    const uint i = bitonic_pair_start(thread_idx, compare_distance);
+    const uint l = i + compare_distance;
+
+    const uint2 a = keys_buf[i];
+    const uint2 b = keys_buf[l];
+
+    if (bitonic_needs_swap(a, b, i, merge_size)) {
+        keys_buf[i] = b;
+        keys_buf[l] = a;
+    }
+}
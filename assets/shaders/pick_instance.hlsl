@@ -0,0 +1,10 @@
+[[vk::binding(0)]] Texture2D<uint> instance_id_tex;
+[[vk::binding(1)]] RWStructuredBuffer<uint> output_buf;
+[[vk::binding(2)]] cbuffer _ {
+    uint2 pick_px;
+};
+
+[numthreads(1, 1, 1)]
+void main() {
+    output_buf[0] = instance_id_tex[pick_px];
+}
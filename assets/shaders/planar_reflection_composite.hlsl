@@ -0,0 +1,59 @@
+#include "inc/frame_constants.hlsl"
+#include "inc/uv.hlsl"
+#include "inc/gbuffer.hlsl"
+#include "inc/samplers.hlsl"
+
+// Replaces the reflected radiance of the smooth surfaces lying on a planar reflection's plane
+// with its mirrored view, which `light_gbuffer` then shades like the traced reflections.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(2)]] Texture2D<float> depth_tex;
+[[vk::binding(3)]] Texture2D<float4> reflection_tex;
+[[vk::binding(4)]] RWTexture2D<float4> output_tex;
+[[vk::binding(5)]] cbuffer _ {
+    float4 output_tex_size;
+    float4 plane;
+    float max_distance;
+    float max_roughness;
+    float normal_distortion;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float4 input = input_tex[px];
+    const float depth = depth_tex[px];
+
+    if (0.0 == depth) {
+        output_tex[px] = input;
+        return;
+    }
+
+    const float2 uv = get_uv(px, output_tex_size);
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 pos_ws = view_ray_context.ray_hit_ws();
+    const GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+
+    const float plane_distance = abs(dot(plane, float4(pos_ws, 1.0)));
+    const float alignment = dot(gbuffer.normal, plane.xyz);
+
+    const float weight =
+        (1.0 - smoothstep(0.5 * max_distance, max_distance, plane_distance))
+        * (1.0 - smoothstep(0.5 * max_roughness, max_roughness, gbuffer.roughness))
+        * smoothstep(0.7, 0.9, alignment);
+
+    if (weight <= 0.0) {
+        output_tex[px] = input;
+        return;
+    }
+
+    // Bumps on the surface shift what it reflects, roughly as much as they tilt it.
+    const float3 normal_delta_vs = direction_world_to_view(gbuffer.normal - plane.xyz);
+    float2 reflection_uv = uv + float2(normal_delta_vs.x, -normal_delta_vs.y) * normal_distortion;
+
+    // The mirrored view is also mirrored horizontally; see `mirrored_camera_matrices`.
+    reflection_uv.x = 1.0 - reflection_uv.x;
+
+    const float3 reflected = reflection_tex.SampleLevel(sampler_llc, reflection_uv, 0).rgb;
+    output_tex[px] = float4(lerp(input.rgb, reflected, weight), input.a);
+}
@@ -0,0 +1,26 @@
+#include "../inc/samplers.hlsl"
+
+// Lateral chromatic aberration: red and blue are magnified slightly differently than green,
+// with the fringing growing towards the edges of the screen.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 output_tex_size;
+    // Offset of red and blue at the corners, in fractions of the screen size
+    float intensity;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+    const float2 from_center = uv - 0.5;
+
+    const float2 offset = from_center * intensity * 2.0;
+
+    const float r = input_tex.SampleLevel(sampler_lnc, uv - offset, 0).r;
+    const float g = input_tex[px].g;
+    const float b = input_tex.SampleLevel(sampler_lnc, uv + offset, 0).b;
+
+    output_tex[px] = float4(r, g, b, 1);
+}
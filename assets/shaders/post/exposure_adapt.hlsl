@@ -0,0 +1,80 @@
+#include "../inc/frame_constants.hlsl"
+
+#include "luminance_histogram_common.hlsl"
+
+// Finds the mean log2 luminance of the histogram, rejecting outliers at both ends,
+// and adapts the exposure towards it over time.
+
+[[vk::binding(0)]] StructuredBuffer<uint> histogram_buffer;
+// x: image log2 luminance; y: fast-adapting EV; z: slow-adapting EV
+[[vk::binding(1)]] RWStructuredBuffer<float4> exposure_state_buffer;
+[[vk::binding(2)]] cbuffer _ {
+    float histogram_low_clip;
+    float histogram_high_clip;
+    float speed_log2;
+    float ev_bias;
+    float ev_min;
+    float ev_max;
+    uint reset_state;
+};
+
+groupshared uint histogram[LUMINANCE_HISTOGRAM_BIN_COUNT];
+
+// Must match LUMINANCE_HISTOGRAM_BIN_COUNT
+[numthreads(256, 1, 1)]
+void main(uint bin: SV_GroupIndex) {
+    histogram[bin] = histogram_buffer[bin];
+    GroupMemoryBarrierWithGroupSync();
+
+    if (bin != 0) {
+        return;
+    }
+
+    float total_entry_count = 0;
+    for (uint i = 0; i < LUMINANCE_HISTOGRAM_BIN_COUNT; ++i) {
+        total_entry_count += histogram[i];
+    }
+
+    // Reject this much from the bottom and top end
+    const float outlier_frac_lo = min(histogram_low_clip, 1.0);
+    const float outlier_frac_hi = min(histogram_high_clip, 1.0 - outlier_frac_lo);
+
+    float left_to_reject = total_entry_count * outlier_frac_lo;
+    float left_to_use = total_entry_count * (1.0 - outlier_frac_lo - outlier_frac_hi);
+
+    float sum = 0;
+    float used_count = 0;
+
+    for (uint i = 0; i < LUMINANCE_HISTOGRAM_BIN_COUNT; ++i) {
+        const float t = (i + 0.5) / LUMINANCE_HISTOGRAM_BIN_COUNT;
+        const float count = histogram[i];
+
+        const float count_to_use = min(max(0.0, count - left_to_reject), left_to_use);
+        left_to_reject = max(0.0, left_to_reject - count);
+        left_to_use = max(0.0, left_to_use - count_to_use);
+
+        sum += t * count_to_use;
+        used_count += count_to_use;
+    }
+
+    const float mean = sum / max(1.0, used_count);
+    const float image_log2_lum = lerp(LUMINANCE_HISTOGRAM_MIN_LOG2, LUMINANCE_HISTOGRAM_MAX_LOG2, mean);
+
+    const float target_ev = clamp(ev_bias - image_log2_lum, ev_min, ev_max);
+
+    float4 state = exposure_state_buffer[0];
+
+    if (reset_state) {
+        state.yz = target_ev;
+    } else {
+        const float dt = frame_constants.delta_time_seconds * exp2(speed_log2);
+
+        const float t_fast = 1.0 - exp(-1.0 * dt);
+        const float t_slow = 1.0 - exp(-0.25 * dt);
+        state.y = lerp(state.y, target_ev, t_fast);
+        state.z = lerp(state.z, target_ev, t_slow);
+    }
+
+    state.x = image_log2_lum;
+    exposure_state_buffer[0] = state;
+}
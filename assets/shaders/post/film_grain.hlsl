@@ -0,0 +1,31 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/hash.hlsl"
+
+// Multiplicative grain, stronger in the shadows, like silver halide crystals in film.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 output_tex_size;
+    float intensity;
+    // Converts the input to exposed values, where 1 is white
+    float input_multiplier;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float3 col = input_tex[px].rgb;
+
+    // Sum of uniforms, for a roughly Gaussian distribution
+    uint seed = hash3(uint3(px, frame_constants.frame_index));
+    float noise = 0.0;
+    for (uint i = 0; i < 4; ++i) {
+        noise += uint_to_u01_float(hash1_mut(seed));
+    }
+    noise = noise * 0.5 - 1.0;
+
+    const float exposed_luminance = dot(col, float3(0.2126, 0.7152, 0.0722)) * input_multiplier;
+    const float shadow_boost = rsqrt(max(exposed_luminance, 0.01) + 0.5);
+
+    output_tex[px] = float4(col * max(0.0, 1.0 + noise * intensity * shadow_boost), 1);
+}
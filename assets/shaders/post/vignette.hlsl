@@ -0,0 +1,14 @@
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 output_tex_size;
+    float intensity;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) * output_tex_size.zw;
+
+    const float3 col = input_tex[px].rgb * exp(-intensity * pow(length(uv - 0.5), 3));
+    output_tex[px] = float4(col, 1);
+}
@@ -1,6 +1,7 @@
 #include "inc/samplers.hlsl"
 #include "inc/uv.hlsl"
 #include "inc/frame_constants.hlsl"
+#include "inc/working_primaries.hlsl"
 #include "inc/bindless_textures.hlsl"
 #include "post/luminance_histogram_common.hlsl"
 
@@ -9,30 +10,38 @@ static float2 SAMPLE_BEZOLD_BRUCKE_LUT(float coord) {
     return bindless_textures[BINDLESS_LUT_BEZOLD_BRUCKE].SampleLevel(sampler_llr, float2(coord, 0.5), 0).xy;
 }
 #include "inc/color/display_transform.hlsl"
+#include "inc/tonemap.hlsl"
 
 [[vk::binding(0)]] Texture2D<float4> input_tex;
 //[[vk::binding(1)]] Texture2D<float4> debug_input_tex;
 [[vk::binding(1)]] Texture2D<float4> blur_pyramid_tex;
-[[vk::binding(2)]] Texture2D<float4> rev_blur_pyramid_tex;
-[[vk::binding(3)]] StructuredBuffer<uint> histogram_buffer;
+[[vk::binding(2)]] StructuredBuffer<uint> histogram_buffer;
+[[vk::binding(3)]] Texture3D<float4> grading_lut_tex;
 [[vk::binding(4)]] RWTexture2D<float4> output_tex;
 [[vk::binding(5)]] cbuffer _ {
     float4 output_tex_size;
     float input_multiplier;
     float contrast;
+    uint tonemap_operator;
+    uint grading_lut_enabled;
+    float4 grading_lut_domain_min;
+    float4 grading_lut_domain_max;
 };
 
+// Must match `TonemapOperator::shader_index`
+#define TONEMAP_NEUTRAL 0
+#define TONEMAP_ACES_FITTED 1
+#define TONEMAP_AGX 2
+#define TONEMAP_REINHARD 3
+
 #define USE_GRADE 0
 #define USE_DISPLAY_TRANSFORM 1
 #define USE_DITHER 1
 #define USE_SHARPEN 0
-#define USE_VIGNETTE 1
 
 #define DEBUG_HISTOGRAM 0
 
 static const float sharpen_amount = 0.1;
-static const float glare_amount = 0.05;
-//static const float glare_amount = 0.0;
 
 float sharpen_remap(float l) {
     return sqrt(l);
@@ -66,6 +75,21 @@ float3 push_down_black_point(float3 x, float m, float c) {
     return T * w0 + L * w1;
 }
 
+// `.cube` LUTs are authored for display-encoded values.
+float3 apply_grading_lut(float3 col) {
+    float3 lut_size;
+    grading_lut_tex.GetDimensions(lut_size.x, lut_size.y, lut_size.z);
+
+    float3 coord = (sRGB_EOTF(saturate(col)) - grading_lut_domain_min.xyz)
+        / (grading_lut_domain_max.xyz - grading_lut_domain_min.xyz);
+
+    // Sample texel centers at the ends of the domain
+    coord = saturate(coord) * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+
+    const float3 graded = grading_lut_tex.SampleLevel(sampler_lnc, coord, 0).rgb;
+    return sRGB_OETF(saturate(graded));
+}
+
 groupshared uint max_histogram_bin;
 void debug_histogram(int2 px, uint idx_within_group, inout float3 color) {
     const uint bins = LUMINANCE_HISTOGRAM_BIN_COUNT;
@@ -114,7 +138,6 @@ void main(uint2 px: SV_DispatchThreadID, uint idx_within_group: SV_GroupIndex) {
     return;
 #endif
 
-    float3 glare = rev_blur_pyramid_tex.SampleLevel(sampler_lnc, uv, 0).rgb;
     float3 col = input_tex[px].rgb;
 
 #if USE_SHARPEN
@@ -146,16 +169,12 @@ void main(uint2 px: SV_DispatchThreadID, uint idx_within_group: SV_GroupIndex) {
 	col.rgb *= max(0.0, sharpened_luma / max(1e-5, sRGB_to_luminance(col.rgb)));
 #endif
 
-    col = lerp(col, glare, glare_amount);
-    col = max(0.0, col);
+    // The tonemappers expect Rec.709; clip what's outside of it.
+    col = max(0.0, working_to_rec709(col));
     //col = col * (1.0 - debug_input_tex[px].a) + debug_input_tex[px].rgb;
 
     col *= input_multiplier;
 
-#if USE_VIGNETTE
-    col *= exp(-2 * pow(length(uv - 0.5), 3));
-#endif
-
 #if USE_GRADE
     // Lift mids
     col = pow(col, 0.9);
@@ -165,13 +184,30 @@ void main(uint2 px: SV_DispatchThreadID, uint idx_within_group: SV_GroupIndex) {
 #endif
 
 #if USE_DISPLAY_TRANSFORM
-    // Apply a perceptually neutral display transform
-    col = display_transform_sRGB(col);
+    switch (tonemap_operator) {
+        case TONEMAP_ACES_FITTED:
+            col = tonemap_aces_fitted(col);
+            break;
+        case TONEMAP_AGX:
+            col = tonemap_agx(col);
+            break;
+        case TONEMAP_REINHARD:
+            col = tonemap_reinhard(col);
+            break;
+        default:
+            // Apply a perceptually neutral display transform
+            col = display_transform_sRGB(col);
+            break;
+    }
 #endif
 
     // Crank up the contrast
     col = pow(col, contrast);
 
+    if (grading_lut_enabled) {
+        col = apply_grading_lut(col);
+    }
+
     // Dither
 #if USE_DITHER
     const uint urand_idx = frame_constants.frame_index;
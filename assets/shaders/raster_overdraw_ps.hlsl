@@ -0,0 +1,5 @@
+// Counts the fragments written by the G-buffer pass. Blended additively,
+// since a zero alpha makes the premultiplied "over" operator a plain sum.
+float4 main(): SV_TARGET0 {
+    return float4(1, 0, 0, 0);
+}
@@ -1,10 +1,13 @@
 #include "inc/math.hlsl"
 #include "inc/samplers.hlsl"
 #include "inc/frame_constants.hlsl"
+#include "inc/working_primaries.hlsl"
 #include "inc/mesh.hlsl"
 #include "inc/pack_unpack.hlsl"
 #include "inc/bindless.hlsl"
 #include "inc/gbuffer.hlsl"
+#include "inc/terrain_layers.hlsl"
+#include "inc/parallax.hlsl"
 
 struct PsIn {
     [[vk::location(0)]] float4 color: TEXCOORD0;
@@ -15,49 +18,129 @@ struct PsIn {
     [[vk::location(5)]] float3 bitangent: TEXCOORD5;
     [[vk::location(6)]] float3 vs_pos: TEXCOORD6;
     [[vk::location(7)]] float3 prev_vs_pos: TEXCOORD7;
+    [[vk::location(8)]] nointerpolation uint draw_index: TEXCOORD8;
+    [[vk::location(9)]] nointerpolation uint mesh_index: TEXCOORD9;
 };
 
-[[vk::push_constant]]
-struct {
-    uint draw_index;
-    uint mesh_index;
-} push_constants;
-
+// Must match `GpuInstanceTransform` in `raster_meshes.rs`
 struct InstanceTransform {
     row_major float3x4 current;
     row_major float3x4 previous;
+    uint mesh_index;
+    uint pad0;
+    uint pad1;
+    uint pad2;
 };
 
 [[vk::binding(0)]] StructuredBuffer<InstanceTransform> instance_transforms_dyn;
 
+// For the depth pre-pass, which only needs the discards to match the G-buffer pass.
+[[vk::constant_id(0)]] const bool DEPTH_ONLY = false;
+
 struct PsOut {
     float3 geometric_normal: SV_TARGET0;
     float4 gbuffer: SV_TARGET1;
     float4 velocity: SV_TARGET2;
+    uint instance_id: SV_TARGET3;
+#if GBUFFER_MATERIAL_ID
+    uint material_id: SV_TARGET4;
+#endif
 };
 
-PsOut main(PsIn ps) {
-    Mesh mesh = meshes[push_constants.mesh_index];
+PsOut main(PsIn ps, bool is_front_face: SV_IsFrontFace) {
+    Mesh mesh = meshes[ps.mesh_index];
     MeshMaterial material = vertices.Load<MeshMaterial>(mesh.mat_data_offset + ps.material_id * sizeof(MeshMaterial));
+    const InstanceDynamicConstants instance_params = instance_dynamic_parameters_dyn[ps.draw_index];
 
     const float lod_bias = -0.5;
+    const float2 uv_ddx = ddx(ps.uv) * exp2(lod_bias);
+    const float2 uv_ddy = ddy(ps.uv) * exp2(lod_bias);
+
+    // Outside of the branch below, where they'd be undefined.
+    const float3 pos_ws_ddx = direction_view_to_world(ddx(ps.vs_pos));
+    const float3 pos_ws_ddy = direction_view_to_world(ddy(ps.vs_pos));
+    const float uv_area = abs(ddx(ps.uv).x * ddy(ps.uv).y - ddx(ps.uv).y * ddy(ps.uv).x);
+
+    float2 uv = ps.uv;
+
+    // From the polygon to the parallax-mapped point seen through it. The depth buffer keeps
+    // the polygon's depth, so that the depth pre-pass can stay cheap, but the motion vectors
+    // follow the point, so that it doesn't smear as the object rotates.
+    float3 parallax_offset_os = 0.0;
+
+    [branch]
+    if (is_material_parallax_mapped(material) && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        const float3x4 object_to_world = instance_transforms_dyn[ps.draw_index].current;
+        const float3 normal_os = is_front_face ? ps.normal : -ps.normal;
+
+        const float3 tangent_ws = mul(object_to_world, float4(ps.tangent, 0.0));
+        const float3 bitangent_ws = mul(object_to_world, float4(ps.bitangent, 0.0));
+        const float3 normal_ws = mul(object_to_world, float4(normal_os, 0.0));
+
+        const float3 to_eye_ws = -normalize(direction_view_to_world(ps.vs_pos));
+        const float3 view_dir_ts = float3(
+            dot(to_eye_ws, normalize(tangent_ws)),
+            dot(to_eye_ws, normalize(bitangent_ws)),
+            dot(to_eye_ws, normalize(normal_ws)));
+
+        const ParallaxHit hit = parallax_occlusion_map(material, ps.uv, uv_ddx, uv_ddy, view_dir_ts, 32);
+        uv = hit.uv;
+
+        const float world_units_per_uv = sqrt(length(cross(pos_ws_ddx, pos_ws_ddy)) / max(1e-20, uv_area));
+        const float offset_length_ws = hit.depth * world_units_per_uv / max(view_dir_ts.z, 0.1);
+
+        // Away from the eye; expressed in the object's frame, so that it can be moved with it.
+        parallax_offset_os = -offset_length_ws * (
+            view_dir_ts.x * ps.tangent / length(tangent_ws)
+            + view_dir_ts.y * ps.bitangent / length(bitangent_ws)
+            + view_dir_ts.z * normal_os / length(normal_ws));
+    }
 
-    float2 albedo_uv = transform_material_uv(material, ps.uv, 0);
+    float2 albedo_uv = transform_material_uv(material, uv, 0);
     Texture2D albedo_tex = bindless_textures[NonUniformResourceIndex(material.albedo_map)];
     float4 albedo_texel = albedo_tex.SampleBias(sampler_llr, albedo_uv, lod_bias);
-    if (albedo_texel.a < 0.5) {
+
+    // Blended materials are drawn by the forward transparent pass instead.
+    if (is_material_alpha_blended(material)) {
         discard;
     }
 
-    float3 albedo = albedo_texel.xyz * float4(material.base_color_mult).xyz * ps.color.xyz;
+    // Culling is disabled for meshes with any double-sided materials; cull the rest here.
+    if (!is_front_face && !is_material_double_sided(material)) {
+        discard;
+    }
 
-    float2 spec_uv = transform_material_uv(material, ps.uv, 2);
+    if (is_material_alpha_tested(material) && albedo_texel.a * material.base_color_mult[3] < material.alpha_cutoff) {
+        discard;
+    }
+
+    if (DEPTH_ONLY) {
+        return (PsOut)0;
+    }
+
+    float3 albedo = albedo_texel.xyz * float4(material.base_color_mult).xyz * ps.color.xyz * instance_params.base_color_tint;
+
+    float2 spec_uv = transform_material_uv(material, uv, 2);
     Texture2D spec_tex = bindless_textures[NonUniformResourceIndex(material.spec_map)];
     const float4 metalness_roughness = spec_tex.SampleBias(sampler_llr, spec_uv, lod_bias);
-    float perceptual_roughness = material.roughness_mult * metalness_roughness.x;
+    float perceptual_roughness = material.roughness_mult * instance_params.roughness_multiplier * metalness_roughness.x;
     float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
     float metalness = metalness_roughness.y * material.metalness_factor;
 
+    // Terrain layer weights are stored in the vertex colors.
+    const bool is_terrain = is_material_terrain_layered(material);
+    float3 terrain_ts_normal = float3(0, 0, 1);
+
+    [branch]
+    if (is_terrain) {
+        const TerrainLayersSample terrain = sample_terrain_layers(mesh, ps.material_id, ps.color, uv, uv_ddx, uv_ddy);
+        albedo = terrain.albedo * instance_params.base_color_tint;
+        perceptual_roughness = terrain.perceptual_roughness * instance_params.roughness_multiplier;
+        roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
+        metalness = terrain.metalness;
+        terrain_ts_normal = terrain.ts_normal;
+    }
+
     if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_METAL)) {
         metalness = 0;
     }
@@ -69,31 +152,36 @@ PsOut main(PsIn ps) {
     }
 
     float3 normal_ws; {
-        float3 normal_os = ps.normal;
+        float3 normal_os = is_front_face ? ps.normal : -ps.normal;
 
         [branch]
         if (!frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_NORMAL_MAPS)) {
             Texture2D normal_tex = bindless_textures[NonUniformResourceIndex(material.normal_map)];
+            const float2 normal_uv = transform_material_uv(material, uv, 1);
 
 #if 1
-            float3 ts_normal = float3(normal_tex.SampleBias(sampler_llr, ps.uv, lod_bias).xy * 2.0 - 1.0, 0);
+            float3 ts_normal = float3(normal_tex.SampleBias(sampler_llr, normal_uv, lod_bias).xy * 2.0 - 1.0, 0);
             ts_normal.z = sqrt(max(0.01, 1.0 - dot(ts_normal.xy, ts_normal.xy)));
 #else
-            float3 ts_normal = normal_tex.SampleBias(sampler_llr, ps.uv, lod_bias).xyz * 2.0 - 1.0;
+            float3 ts_normal = normal_tex.SampleBias(sampler_llr, normal_uv, lod_bias).xyz * 2.0 - 1.0;
 #endif
 
+            if (is_terrain) {
+                ts_normal = terrain_ts_normal;
+            }
+
             if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::FLIP_NORMAL_MAP_YZ)) {
                 ts_normal.zy *= -1;
             }
 
             if (dot(ps.bitangent, ps.bitangent) > 0.0) {
-                float3x3 tbn = float3x3(ps.tangent, ps.bitangent, ps.normal);
+                float3x3 tbn = float3x3(ps.tangent, ps.bitangent, normal_os);
                 normal_os = mul(ts_normal, tbn);
             }
         }
 
         // Transform to world space
-        normal_ws = normalize(mul(instance_transforms_dyn[push_constants.draw_index].current, float4(normal_os, 0.0)));
+        normal_ws = normalize(mul(instance_transforms_dyn[ps.draw_index].current, float4(normal_os, 0.0)));
     }
 
     // Derive normal from depth
@@ -113,28 +201,50 @@ PsOut main(PsIn ps) {
         normal_ws = geometric_normal_ws;
     }
 
-    float2 emissive_uv = transform_material_uv(material, ps.uv, 3);
+    float2 emissive_uv = transform_material_uv(material, uv, 3);
     Texture2D emissive_tex = bindless_textures[NonUniformResourceIndex(material.emissive_map)];
     float3 emissive = 1.0.xxx
         * emissive_tex.SampleBias(sampler_llr, emissive_uv, lod_bias).rgb
         * float3(material.emissive)
-        * instance_dynamic_parameters_dyn[push_constants.draw_index].emissive_multiplier
+        * instance_params.emissive_multiplier
         * frame_constants.pre_exposure;
 
     //albedo = float3(0.966653, 0.802156, 0.323968); // Au from Mitsuba
 
     GbufferData gbuffer = GbufferData::create_zero();
-    gbuffer.albedo = albedo;
+    gbuffer.albedo = rec709_to_working(albedo);
     gbuffer.normal = normal_ws;
     gbuffer.roughness = roughness;
     //gbuffer.roughness = lerp(0.05, 0.15, roughness);  // kitchen hack
     gbuffer.metalness = metalness;
-    gbuffer.emissive = emissive;
+    gbuffer.emissive = rec709_to_working(emissive);
+    gbuffer.clearcoat = material.clearcoat;
+    gbuffer.clearcoat_roughness = perceptual_roughness_to_roughness(material.clearcoat_roughness);
+    gbuffer.transmission = material.transmission;
+    gbuffer.subsurface = material.subsurface;
+    gbuffer.subsurface_profile = material.subsurface_profile;
+
+    if (material.anisotropy > 0.0 && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        const float3 anisotropy_dir_os =
+            cos(material.anisotropy_rotation) * ps.tangent
+            + sin(material.anisotropy_rotation) * ps.bitangent;
+        gbuffer.anisotropy = material.anisotropy;
+        gbuffer.set_anisotropy_direction(
+            mul(instance_transforms_dyn[ps.draw_index].current, float4(anisotropy_dir_os, 0.0)));
+    }
 
     PsOut ps_out;
     ps_out.geometric_normal = geometric_normal_vs * 0.5 + 0.5;
     ps_out.gbuffer = asfloat(gbuffer.pack().data0);
-    ps_out.velocity = float4(ps.prev_vs_pos - ps.vs_pos, 0);
+    const float3 parallax_motion_ws =
+        mul(instance_transforms_dyn[ps.draw_index].previous, float4(parallax_offset_os, 0.0))
+        - mul(instance_transforms_dyn[ps.draw_index].current, float4(parallax_offset_os, 0.0));
+    ps_out.velocity = float4(ps.prev_vs_pos - ps.vs_pos + direction_world_to_view(parallax_motion_ws), 0);
+    // Zero is reserved for the background
+    ps_out.instance_id = ps.draw_index + 1;
+#if GBUFFER_MATERIAL_ID
+    ps_out.material_id = (ps.mesh_index << 16) | (ps.material_id & 0xffff);
+#endif
 
     return ps_out;
 }
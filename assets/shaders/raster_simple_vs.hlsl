@@ -1,3 +1,4 @@
+#include "inc/math.hlsl"
 #include "inc/frame_constants.hlsl"
 #include "inc/mesh.hlsl"
 #include "inc/bindless.hlsl"
@@ -8,9 +9,18 @@ struct {
     uint mesh_index;
 } push_constants;
 
+// Set for draws from the arguments written by `cull_instances.hlsl`. The push constants
+// are unused then; the instance index comes in `firstInstance`, and the mesh index with it.
+[[vk::constant_id(0)]] const bool INDIRECT_DRAWS = false;
+
+// Must match `GpuInstanceTransform` in `raster_meshes.rs`
 struct InstanceTransform {
     row_major float3x4 current;
     row_major float3x4 previous;
+    uint mesh_index;
+    uint pad0;
+    uint pad1;
+    uint pad2;
 };
 
 [[vk::binding(0)]] StructuredBuffer<InstanceTransform> instance_transforms_dyn;
@@ -25,17 +35,91 @@ struct VsOut {
     [[vk::location(5)]] float3 bitangent: TEXCOORD5;
     [[vk::location(6)]] float3 vs_pos: TEXCOORD6;
     [[vk::location(7)]] float3 prev_vs_pos: TEXCOORD7;
+    [[vk::location(8)]] nointerpolation uint draw_index: TEXCOORD8;
+    [[vk::location(9)]] nointerpolation uint mesh_index: TEXCOORD9;
+    float clip_distance: SV_ClipDistance0;
 };
 
+// Curves from `add_curves` are drawn as ribbons turned towards the eye, with normals
+// bent around to look round. `vid` picks a point of a curve, and the left, middle, or right
+// of the ribbon through it; the point's center and radius come from two opposite vertices
+// of its tube, which rays hit instead. The middle is brought forward to the tube's front.
+VsOut curve_ribbon_vertex(Mesh mesh, uint vid, uint draw_index, uint mesh_index) {
+    const uint point_index = vid / 3;
+    const float side = float(vid % 3) - 1.0;
+
+    const uint ring_vertex = point_index * mesh.curve_tube_sides;
+    const uint opposite_ring_vertex = ring_vertex + mesh.curve_tube_sides / 2;
+
+    const float3x4 current = instance_transforms_dyn[draw_index].current;
+    const float3x4 previous = instance_transforms_dyn[draw_index].previous;
+
+    const float3 a_ws = mul(current, float4(load_mesh_vertex(mesh, ring_vertex).position, 1.0));
+    const float3 b_ws = mul(current, float4(load_mesh_vertex(mesh, opposite_ring_vertex).position, 1.0));
+    const float3 center_ws = 0.5 * (a_ws + b_ws);
+    const float radius_ws = 0.5 * length(a_ws - b_ws);
+
+    const float3 tangent_os = asfloat(vertices.Load4(ring_vertex * sizeof(float4) + mesh.vertex_tangent_offset)).xyz;
+    const float3 tangent_ws = normalize(mul(current, float4(tangent_os, 0.0)));
+
+    const float3 to_eye_ws = is_orthographic_projection()
+        ? direction_view_to_world(float3(0, 0, 1))
+        : normalize(get_eye_position() - center_ws);
+
+    float3 side_ws = cross(tangent_ws, to_eye_ws);
+    side_ws = dot(side_ws, side_ws) > 1e-8
+        ? normalize(side_ws)
+        : build_orthonormal_basis(tangent_ws)[0];
+    const float3 facing_ws = cross(side_ws, tangent_ws);
+
+    const float3 offset_ws = (side_ws * side + facing_ws * (1.0 - abs(side))) * radius_ws;
+    const float3 ws_pos = center_ws + offset_ws;
+    const float3 normal_ws = side != 0.0 ? side_ws * side : facing_ws;
+
+    // Deformed curves have their previous frame's rings elsewhere.
+    const float3 prev_center_os = 0.5 * (
+        load_mesh_vertex_at(mesh, mesh.vertex_prev_core_offset, ring_vertex).position
+        + load_mesh_vertex_at(mesh, mesh.vertex_prev_core_offset, opposite_ring_vertex).position);
+    const float3 prev_ws_pos = mul(previous, float4(prev_center_os, 1.0)) + offset_ws;
+
+    const float4 vs_pos = mul(frame_constants.view_constants.world_to_view, float4(ws_pos, 1.0));
+    const float4 prev_vs_pos = mul(frame_constants.view_constants.world_to_view, float4(prev_ws_pos, 1.0));
+
+    // Back in object space, like the other meshes' normals. The transpose undoes rotations
+    // and uniform scales, up to a scale which the pixel shader normalizes away.
+    const float3 normal_os = mul(normal_ws, (float3x3)current);
+
+    VsOut vsout;
+    vsout.position = mul(frame_constants.view_constants.view_to_sample, vs_pos);
+    vsout.color = 1.0.xxxx;
+    vsout.uv = float2(side * 0.5 + 0.5, load_mesh_uv(mesh, ring_vertex).y);
+    vsout.normal = normal_os;
+    vsout.material_id = vertices.Load(ring_vertex * sizeof(uint) + mesh.vertex_mat_offset);
+    vsout.tangent = tangent_os;
+    vsout.bitangent = normalize(cross(normal_os, tangent_os));
+    vsout.vs_pos = vs_pos.xyz / vs_pos.w;
+    vsout.prev_vs_pos = prev_vs_pos.xyz / prev_vs_pos.w;
+    vsout.draw_index = draw_index;
+    vsout.mesh_index = mesh_index;
+    vsout.clip_distance = view_clip_distance(ws_pos);
+    return vsout;
+}
+
 VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
     VsOut vsout;
 
-    const Mesh mesh = meshes[push_constants.mesh_index];
+    const uint draw_index = INDIRECT_DRAWS ? instance_index : push_constants.draw_index;
+    const uint mesh_index = INDIRECT_DRAWS
+        ? instance_transforms_dyn[draw_index].mesh_index
+        : push_constants.mesh_index;
+
+    const Mesh mesh = meshes[mesh_index];
 
-    // TODO: replace with Load<float4> once there's a fast path for NV
-    // https://github.com/microsoft/DirectXShaderCompiler/issues/2193
-    VertexPacked vp = VertexPacked(asfloat(vertices.Load4(vid * sizeof(float4) + mesh.vertex_core_offset)));
-    Vertex v = unpack_vertex(vp);
+    if (mesh.curve_tube_sides != 0) {
+        return curve_ribbon_vertex(mesh, vid, draw_index, mesh_index);
+    }
+
+    Vertex v = load_mesh_vertex(mesh, vid);
 
     float4 v_color =
         mesh.vertex_aux_offset != 0
@@ -47,16 +131,22 @@ VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
             ? asfloat(vertices.Load4(vid * sizeof(float4) + mesh.vertex_tangent_offset))
             : float4(1, 0, 0, 1);            
 
-    float2 uv = asfloat(vertices.Load2(vid * sizeof(float2) + mesh.vertex_uv_offset));
+    float2 uv = load_mesh_uv(mesh, vid);
     uint material_id = vertices.Load(vid * sizeof(uint) + mesh.vertex_mat_offset);
 
     //float3 ws_pos = v.position + float3(push_constants.instance_position);
-    float3 ws_pos = mul(instance_transforms_dyn[push_constants.draw_index].current, float4(v.position, 1.0));
+    float3 ws_pos = mul(instance_transforms_dyn[draw_index].current, float4(v.position, 1.0));
     
     float4 vs_pos = mul(frame_constants.view_constants.world_to_view, float4(ws_pos, 1.0));
     float4 cs_pos = mul(frame_constants.view_constants.view_to_sample, vs_pos);
 
-    float3 prev_ws_pos = mul(instance_transforms_dyn[push_constants.draw_index].previous, float4(v.position, 1.0));
+    // Deformed meshes have their previous frame's vertices elsewhere.
+    float3 prev_position = v.position;
+    if (mesh.vertex_prev_core_offset != mesh.vertex_core_offset) {
+        prev_position = load_mesh_vertex_at(mesh, mesh.vertex_prev_core_offset, vid).position;
+    }
+
+    float3 prev_ws_pos = mul(instance_transforms_dyn[draw_index].previous, float4(prev_position, 1.0));
     float4 prev_vs_pos = mul(frame_constants.view_constants.world_to_view, float4(prev_ws_pos, 1.0));
     //float4 prev_cs_pos = mul(frame_constants.view_constants.view_to_sample, prev_vs_pos);
 
@@ -70,6 +160,9 @@ VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
 
     vsout.vs_pos = vs_pos.xyz / vs_pos.w;
     vsout.prev_vs_pos = prev_vs_pos.xyz / prev_vs_pos.w;
+    vsout.draw_index = draw_index;
+    vsout.mesh_index = mesh_index;
+    vsout.clip_distance = view_clip_distance(ws_pos);
 
     return vsout;
 }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// Flat color for the wireframe overlay; drawn with a line polygon mode.
+float4 main(): SV_TARGET0 {
+    return float4(0.1, 1.0, 0.3, 1.0);
+}
@@ -0,0 +1,47 @@
+#include "inc/uv.hlsl"
+#include "inc/bilinear.hlsl"
+
+// How much of their history the temporal accumulators should drop at each pixel:
+// where the surface under it was hidden or off-screen in the previous frame.
+
+[[vk::binding(0)]] Texture2D<uint> instance_id_tex;
+[[vk::binding(1)]] Texture2D<uint> prev_instance_id_tex;
+[[vk::binding(2)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(3)]] RWTexture2D<float> output_tex;
+[[vk::binding(4)]] cbuffer _ {
+    float4 output_tex_size;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float4 reproj = reprojection_tex[px];
+
+    // Off-screen in the previous frame
+    if (reproj.w < 0.0) {
+        output_tex[px] = 1.0;
+        return;
+    }
+
+    const uint instance_id = instance_id_tex[px];
+    const float2 prev_uv = get_uv(px, output_tex_size) + reproj.xy;
+    const Bilinear bilinear_at_prev = get_bilinear_filter(prev_uv, output_tex_size.xy);
+    const int2 max_px = int2(output_tex_size.xy) - 1;
+
+    // Instance ID discontinuities: a different instance, or the sky, was there in the previous frame.
+    const int2 prev_px[4] = {
+        bilinear_at_prev.px0(), bilinear_at_prev.px1(), bilinear_at_prev.px2(), bilinear_at_prev.px3()
+    };
+    uint matching_id_count = 0;
+    for (uint i = 0; i < 4; ++i) {
+        matching_id_count += prev_instance_id_tex[clamp(prev_px[i], 0, max_px)] == instance_id ? 1 : 0;
+    }
+
+    // Depth discontinuities, as found by `calculate_reprojection_map.hlsl`. Not tracked for the sky.
+    uint valid_depth_count = 4;
+    if (instance_id != 0) {
+        const uint quad_reproj_valid_packed = uint(reproj.z * 15.0 + 0.5);
+        valid_depth_count = countbits(quad_reproj_valid_packed);
+    }
+
+    output_tex[px] = 1.0 - min(matching_id_count, valid_depth_count) / 4.0;
+}
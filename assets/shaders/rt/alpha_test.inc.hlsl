@@ -0,0 +1,44 @@
+#ifndef RT_ALPHA_TEST_HLSL
+#define RT_ALPHA_TEST_HLSL
+
+#include "../inc/samplers.hlsl"
+#include "../inc/mesh.hlsl"
+#include "../inc/bindless.hlsl"
+
+// Called from any hit shaders. Alpha-blended surfaces are not visible to rays at all,
+// and alpha-tested ones are only visible where they pass the cutoff.
+bool rt_hit_passes_alpha_test(float2 bary) {
+    Mesh mesh = meshes[InstanceID()];
+
+    uint3 ind = uint3(
+        vertices.Load((PrimitiveIndex() * 3 + 0) * sizeof(uint) + mesh.index_offset),
+        vertices.Load((PrimitiveIndex() * 3 + 1) * sizeof(uint) + mesh.index_offset),
+        vertices.Load((PrimitiveIndex() * 3 + 2) * sizeof(uint) + mesh.index_offset)
+    );
+
+    uint material_id = vertices.Load(ind.x * sizeof(uint) + mesh.vertex_mat_offset);
+    MeshMaterial material = vertices.Load<MeshMaterial>(mesh.mat_data_offset + material_id * sizeof(MeshMaterial));
+
+    if (is_material_alpha_blended(material)) {
+        return false;
+    }
+
+    if (!is_material_alpha_tested(material)) {
+        return true;
+    }
+
+    float3 barycentrics = float3(1.0 - bary.x - bary.y, bary.x, bary.y);
+    float2 uv0 = load_mesh_uv(mesh, ind.x);
+    float2 uv1 = load_mesh_uv(mesh, ind.y);
+    float2 uv2 = load_mesh_uv(mesh, ind.z);
+    float2 uv = uv0 * barycentrics.x + uv1 * barycentrics.y + uv2 * barycentrics.z;
+
+    // No ray cones in any hit shaders; the top mip keeps thin features such as leaves intact.
+    float2 albedo_uv = transform_material_uv(material, uv, 0);
+    Texture2D albedo_tex = bindless_textures[NonUniformResourceIndex(material.albedo_map)];
+    const float alpha = albedo_tex.SampleLevel(sampler_llr, albedo_uv, 0).a * material.base_color_mult[3];
+
+    return alpha >= material.alpha_cutoff;
+}
+
+#endif  // RT_ALPHA_TEST_HLSL
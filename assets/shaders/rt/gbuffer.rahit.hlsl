@@ -0,0 +1,13 @@
+#include "../inc/rt.hlsl"
+#include "alpha_test.inc.hlsl"
+
+struct RayHitAttrib {
+    float2 bary;
+};
+
+[shader("anyhit")]
+void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib: SV_IntersectionAttributes) {
+    if (!rt_hit_passes_alpha_test(attrib.bary)) {
+        IgnoreHit();
+    }
+}
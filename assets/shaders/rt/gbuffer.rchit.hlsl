@@ -3,8 +3,11 @@
 #include "../inc/mesh.hlsl"
 #include "../inc/pack_unpack.hlsl"
 #include "../inc/frame_constants.hlsl"
+#include "../inc/working_primaries.hlsl"
 #include "../inc/bindless.hlsl"
 #include "../inc/rt.hlsl"
+#include "../inc/terrain_layers.hlsl"
+#include "../inc/parallax.hlsl"
 
 [[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
 
@@ -60,9 +63,9 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
         vertices.Load((PrimitiveIndex() * 3 + 2) * sizeof(uint) + mesh.index_offset)
     );
 
-    Vertex v0 = unpack_vertex(VertexPacked(asfloat(vertices.Load4(ind.x * sizeof(float4) + mesh.vertex_core_offset))));
-    Vertex v1 = unpack_vertex(VertexPacked(asfloat(vertices.Load4(ind.y * sizeof(float4) + mesh.vertex_core_offset))));
-    Vertex v2 = unpack_vertex(VertexPacked(asfloat(vertices.Load4(ind.z * sizeof(float4) + mesh.vertex_core_offset))));
+    Vertex v0 = load_mesh_vertex(mesh, ind.x);
+    Vertex v1 = load_mesh_vertex(mesh, ind.y);
+    Vertex v2 = load_mesh_vertex(mesh, ind.z);
     float3 normal = v0.normal * barycentrics.x + v1.normal * barycentrics.y + v2.normal * barycentrics.z;
 
     const float3 surf_normal = normalize(cross(v1.position - v0.position, v2.position - v0.position));
@@ -79,9 +82,9 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
         v_color = vc0 * barycentrics.x + vc1 * barycentrics.y + vc2 * barycentrics.z;
     }
 
-    float2 uv0 = asfloat(vertices.Load2(ind.x * sizeof(float2) + mesh.vertex_uv_offset));
-    float2 uv1 = asfloat(vertices.Load2(ind.y * sizeof(float2) + mesh.vertex_uv_offset));
-    float2 uv2 = asfloat(vertices.Load2(ind.z * sizeof(float2) + mesh.vertex_uv_offset));
+    float2 uv0 = load_mesh_uv(mesh, ind.x);
+    float2 uv1 = load_mesh_uv(mesh, ind.y);
+    float2 uv2 = load_mesh_uv(mesh, ind.z);
     float2 uv = uv0 * barycentrics.x + uv1 * barycentrics.y + uv2 * barycentrics.z;
 
     const float cone_width = payload.ray_cone.width_at_t(hit_dist);
@@ -92,6 +95,34 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
 
     uint material_id = vertices.Load(ind.x * sizeof(uint) + mesh.vertex_mat_offset);
     MeshMaterial material = vertices.Load<MeshMaterial>(mesh.mat_data_offset + material_id * sizeof(MeshMaterial));
+    const InstanceDynamicConstants instance_params = instance_dynamic_parameters_dyn[InstanceIndex()];
+
+    // Object-space tangent frame; zero without tangents.
+    float3 tangent = 0.0;
+    float3 bitangent = 0.0;
+    if (mesh.vertex_tangent_offset != 0) {
+        const float4 t0 = asfloat(vertices.Load4(ind.x * sizeof(float4) + mesh.vertex_tangent_offset));
+        const float4 t1 = asfloat(vertices.Load4(ind.y * sizeof(float4) + mesh.vertex_tangent_offset));
+        const float4 t2 = asfloat(vertices.Load4(ind.z * sizeof(float4) + mesh.vertex_tangent_offset));
+        tangent = t0.xyz * barycentrics.x + t1.xyz * barycentrics.y + t2.xyz * barycentrics.z;
+        bitangent = cross(normal, tangent) * t0.w;
+    }
+
+    // Isotropic footprint of the ray cone in UV space; the gradient equivalent of `compute_texture_lod`.
+    const float uv_footprint =
+        exp2(lod_triangle_constant) * abs(cone_width) / abs(dot(normalize(WorldRayDirection()), surf_normal));
+
+    // Fewer steps than in raster, as these are mostly seen in reflections and GI.
+    [branch]
+    if (is_material_parallax_mapped(material) && dot(bitangent, bitangent) > 0.0) {
+        const float3 to_eye_os = mul(WorldToObject3x4(), float4(-WorldRayDirection(), 0.0));
+        const float3 view_dir_ts = normalize(float3(
+            dot(to_eye_os, normalize(tangent)),
+            dot(to_eye_os, normalize(bitangent)),
+            dot(to_eye_os, normalize(normal))));
+
+        uv = parallax_occlusion_map(material, uv, float2(uv_footprint, 0.0), float2(0.0, uv_footprint), view_dir_ts, 8).uv;
+    }
 
     float2 albedo_uv = transform_material_uv(material, uv, 0);
     const BindlessTextureWithLod albedo_tex =
@@ -100,16 +131,27 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     float3 albedo =
         albedo_tex.tex.SampleLevel(sampler_llr, albedo_uv, albedo_tex.lod).xyz
         * float4(material.base_color_mult).xyz
-        * v_color.rgb;
+        * v_color.rgb
+        * instance_params.base_color_tint;
 
     float2 spec_uv = transform_material_uv(material, uv, 2);
     const BindlessTextureWithLod spec_tex =
         compute_texture_lod(material.spec_map, lod_triangle_constant, WorldRayDirection(), surf_normal, cone_width);
     float4 metalness_roughness = spec_tex.tex.SampleLevel(sampler_llr, spec_uv, spec_tex.lod);
-    float perceptual_roughness = material.roughness_mult * metalness_roughness.x;
+    float perceptual_roughness = material.roughness_mult * instance_params.roughness_multiplier * metalness_roughness.x;
     float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
     float metalness = metalness_roughness.y * material.metalness_factor;
 
+    [branch]
+    if (is_material_terrain_layered(material)) {
+        const TerrainLayersSample terrain = sample_terrain_layers(
+            mesh, material_id, v_color, uv, float2(uv_footprint, 0.0), float2(0.0, uv_footprint));
+        albedo = terrain.albedo * instance_params.base_color_tint;
+        perceptual_roughness = terrain.perceptual_roughness * instance_params.roughness_multiplier;
+        roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
+        metalness = terrain.metalness;
+    }
+
     if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_METAL)) {
         metalness = 0;
     }
@@ -147,7 +189,7 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
         float3 tangent = tangent0 * barycentrics.x + tangent1 * barycentrics.y + tangent2 * barycentrics.z;
         float3 bitangent = bitangent0 * barycentrics.x + bitangent1 * barycentrics.y + bitangent2 * barycentrics.z;
 
-        float2 normal_uv = transform_material_uv(material, uv, 0);
+        float2 normal_uv = transform_material_uv(material, uv, 1);
         const BindlessTextureWithLod normal_tex =
             compute_texture_lod(material.normal_map, lod_triangle_constant, WorldRayDirection(), surf_normal, cone_width);
 
@@ -178,22 +220,33 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
         emissive = 1.0.xxx
             * emissive_tex.tex.SampleLevel(sampler_llr, emissive_uv, emissive_tex.lod).rgb
             * float3(material.emissive)
-            * instance_dynamic_parameters_dyn[InstanceIndex()].emissive_multiplier
+            * instance_params.emissive_multiplier
             * frame_constants.pre_exposure;
     }
 
     GbufferData gbuffer = GbufferData::create_zero();
-    gbuffer.albedo = albedo;
+    gbuffer.albedo = rec709_to_working(albedo);
     gbuffer.normal = normalize(mul(ObjectToWorld3x4(), float4(normal, 0.0)));
     gbuffer.roughness = roughness;
     gbuffer.metalness = metalness;
-    gbuffer.emissive = emissive;
+    gbuffer.emissive = rec709_to_working(emissive);
+    gbuffer.clearcoat = material.clearcoat;
+    gbuffer.clearcoat_roughness = perceptual_roughness_to_roughness(material.clearcoat_roughness);
+    gbuffer.transmission = material.transmission;
 
-    // Force double-sided
-    if (dot(WorldRayDirection(), gbuffer.normal) > 0) {
+    // Back faces of single-sided materials keep facing away, e.g. when seen from inside a closed mesh.
+    if (is_material_double_sided(material) && dot(WorldRayDirection(), gbuffer.normal) > 0) {
         gbuffer.normal *= -1;
     }
 
+    if (material.anisotropy > 0.0 && dot(bitangent, bitangent) > 0.0) {
+        const float3 anisotropy_dir_os =
+            cos(material.anisotropy_rotation) * tangent
+            + sin(material.anisotropy_rotation) * bitangent;
+        gbuffer.anisotropy = material.anisotropy;
+        gbuffer.set_anisotropy_direction(mul(ObjectToWorld3x4(), float4(anisotropy_dir_os, 0.0)));
+    }
+
     //gbuffer.albedo = float3(0.966653, 0.802156, 0.323968); // Au from Mitsuba
 
     payload.gbuffer_packed = gbuffer.pack();
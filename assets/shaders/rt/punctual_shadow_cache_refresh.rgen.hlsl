@@ -0,0 +1,38 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/math_const.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/lights/punctual_shadow_cache.hlsl"
+
+// Traces the distances from the lights to the static shadow casters around them,
+// for the cache slots refreshed this frame; one slot per Z of the dispatch.
+
+// Must match `GpuRefreshedSlot` in `punctual_shadow_cache.rs`
+struct RefreshedSlot {
+    float3 position;
+    // Zero for infinite
+    float max_distance;
+    uint slot;
+    uint3 pad;
+};
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] RWTexture2D<float> distances_tex;
+[[vk::binding(1)]] StructuredBuffer<RefreshedSlot> refreshed_slots_dyn;
+
+[shader("raygeneration")]
+void main() {
+    const uint3 idx = DispatchRaysIndex().xyz;
+    const RefreshedSlot refreshed = refreshed_slots_dyn[idx.z];
+
+    const float3 dir = punctual_shadow_cache_dir(idx.xy);
+    const float max_distance = refreshed.max_distance > 0.0 ? refreshed.max_distance : FLT_MAX;
+
+    const GbufferPathVertex hit = GbufferRaytrace::with_ray(new_ray(refreshed.position, dir, 0.0, max_distance))
+        .with_instance_mask(RT_INSTANCE_MASK_SHADOW_STATIC)
+        .with_cull_back_faces(false)
+        .with_path_length(1)
+        .trace(acceleration_structure);
+
+    distances_tex[idx.xy + uint2(0, refreshed.slot * PUNCTUAL_SHADOW_CACHE_RESOLUTION)] =
+        hit.is_hit ? hit.ray_t : FLT_MAX;
+}
@@ -11,6 +11,7 @@
 #include "../inc/atmosphere.hlsl"
 #include "../inc/sun.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
 
 [[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
 
@@ -139,7 +140,9 @@ void main() {
                     outgoing_ray.TMax = MAX_RAY_LENGTH;
                 }
 
+                // Past the camera rays, everything the reference sees is indirect light.
                 GbufferPathVertex primary_hit = GbufferRaytrace::with_ray(outgoing_ray)
+                    .with_instance_mask(path_length == 0 ? RT_INSTANCE_MASK_ALL : RT_INSTANCE_MASK_GI)
                     .with_cone(ray_cone)
                     //.with_cull_back_faces(true || 0 == path_length)
                     .with_cull_back_faces(false)
@@ -256,17 +259,15 @@ void main() {
                         }
                         
                         if (USE_LIGHTS && frame_constants.triangle_light_count > 0/* && path_length > 0*/) {   // rtr comp
-                            const float light_selection_pmf = 1.0 / frame_constants.triangle_light_count;
-                            const uint light_idx = hash1_mut(rng) % frame_constants.triangle_light_count;
-                            //const float light_selection_pmf = 1;
-                            //for (uint light_idx = 0; light_idx < frame_constants.triangle_light_count; light_idx += 1)
+                            const TriangleLightSelection light_selection = select_triangle_light(uint_to_u01_float(hash1_mut(rng)));
+                            const float light_selection_pmf = light_selection.pmf;
                             {
                                 const float2 urand = float2(
                                     uint_to_u01_float(hash1_mut(rng)),
                                     uint_to_u01_float(hash1_mut(rng))
                                 );
 
-                                TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+                                TriangleLight triangle_light = light_selection.light;
                                 LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
                                 const float3 shadow_ray_origin = primary_hit.position;
                                 const float3 to_light_ws = light_sample.pos - primary_hit.position;
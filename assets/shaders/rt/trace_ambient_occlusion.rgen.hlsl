@@ -0,0 +1,49 @@
+#include "../inc/uv.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/math.hlsl"
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] Texture2D<float> depth_tex;
+[[vk::binding(1)]] Texture2D<float3> geometric_normal_tex;
+[[vk::binding(2)]] RWTexture2D<float> output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float ray_length;
+};
+
+// One ray per pixel, so the visibility is binary; the shadow denoiser turns it into occlusion.
+[shader("raygeneration")]
+void main() {
+    const uint2 px = DispatchRaysIndex().xy;
+
+    const float2 pixel_center = px + 0.5.xx;
+    const float2 uv = pixel_center / DispatchRaysDimensions().xy;
+
+    const float depth = depth_tex[px];
+    if (0.0 == depth) {
+        output_tex[px] = 1.0;
+        return;
+    }
+
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_biased_depth(uv, depth);
+
+    const float3 normal_vs = geometric_normal_tex[px] * 2.0 - 1.0;
+    const float3 normal_ws = direction_view_to_world(normal_vs);
+
+    const float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index).xy;
+    const float3 dir_ws = mul(build_orthonormal_basis(normal_ws), cosine_sample_hemisphere(urand));
+
+    const bool is_occluded = rt_is_occluded(
+        acceleration_structure,
+        new_ray(
+            view_ray_context.biased_secondary_ray_origin_ws_with_normal(normal_ws),
+            dir_ws,
+            0,
+            ray_length
+        ),
+        RT_INSTANCE_MASK_GI
+    );
+
+    output_tex[px] = is_occluded ? 0.0 : 1.0;
+}
@@ -0,0 +1,125 @@
+#include "../inc/uv.hlsl"
+#include "../inc/pack_unpack.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/bindless_textures.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/math.hlsl"
+#include "../inc/color/srgb.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/punctual_shadow_cache.hlsl"
+#include "../inc/lights/clusters.hlsl"
+
+// Direct lighting from the punctual lights in the pixel's light cluster,
+// with one visibility ray per light per pixel.
+// Lights with a radius get soft shadows by aiming the rays at random points on them.
+//
+// Outputs the unshadowed lighting in RGB, and the fraction of it which is visible in alpha,
+// so that only the noisy visibility needs to go through `LocalLightShadowDenoiseRenderer`.
+//
+// Lights in the shadow cache look up the static shadow casters in their distance maps,
+// and only trace the rest.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float3> geometric_normal_tex;
+[[vk::binding(3)]] RWTexture2D<float4> output_tex;
+[[vk::binding(4)]] Texture2D<float> shadow_cache_distances_tex;
+[[vk::binding(5)]] StructuredBuffer<uint> shadow_cache_slots_dyn;
+[[vk::binding(6)]] StructuredBuffer<uint> light_cluster_lists_buf;
+[[vk::binding(7)]] cbuffer _ {
+    LightClusterConstants light_clusters;
+};
+
+[shader("raygeneration")]
+void main() {
+    const uint2 px = DispatchRaysIndex().xy;
+
+    const float2 pixel_center = px + 0.5.xx;
+    const float2 uv = pixel_center / DispatchRaysDimensions().xy;
+
+    const float depth = depth_tex[px];
+    if (0.0 == depth) {
+        output_tex[px] = 0.0;
+        return;
+    }
+
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 pt_ws = view_ray_context.ray_hit_ws();
+
+    const float3 geometric_normal_vs = geometric_normal_tex[px] * 2.0 - 1.0;
+    const float3 geometric_normal_ws = direction_view_to_world(geometric_normal_vs);
+    const float3 ray_origin = view_ray_context.biased_secondary_ray_origin_ws_with_normal(geometric_normal_ws);
+
+    GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+
+    const float3x3 tangent_to_world = build_orthonormal_basis(gbuffer.normal);
+    float3 wo = mul(-view_ray_context.ray_dir_ws(), tangent_to_world);
+
+    // Same hack as in `light_gbuffer.hlsl`, for shading normals facing away from the eye.
+    if (wo.z < 0.0) {
+        wo.z *= -0.25;
+        wo = normalize(wo);
+    }
+
+    LayeredBrdf brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
+
+    float3 unshadowed_radiance = 0.0;
+    float3 shadowed_radiance = 0.0;
+
+    const uint cluster_offset =
+        light_clusters.cluster_index(uv, -view_ray_context.ray_hit_vs().z) * LIGHT_CLUSTER_STRIDE;
+    const uint cluster_light_count = light_cluster_lists_buf[cluster_offset];
+
+    for (uint cluster_light_idx = 0; cluster_light_idx < cluster_light_count; ++cluster_light_idx) {
+        const uint light_idx = light_cluster_lists_buf[cluster_offset + 1 + cluster_light_idx];
+        const PunctualLight light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+        const float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index + light_idx * 4099).xy;
+        const PunctualLightSample light_sample = light.sample(pt_ws);
+        const float3 light_wi = mul(light_sample.wi, tangent_to_world);
+
+        if (light_wi.z <= 0.0 || all(light_sample.radiance == 0.0)) {
+            continue;
+        }
+
+        const float3 radiance =
+            brdf.evaluate_directional_light(wo, light_wi)
+            * light_wi.z
+            * light_sample.radiance
+            * frame_constants.pre_exposure;
+
+        unshadowed_radiance += radiance;
+
+        const PunctualLightSample shadow_sample = light.sample_with_radius(pt_ws, urand);
+        const RayDesc shadow_ray = new_ray(ray_origin, shadow_sample.wi, 0.0, shadow_sample.distance * 0.999);
+        const uint shadow_cache_slot = shadow_cache_slots_dyn[light_idx];
+
+        bool is_shadowed;
+        if (shadow_cache_slot != 0) {
+            is_shadowed = punctual_shadow_cache_is_shadowed(
+                    shadow_cache_distances_tex,
+                    shadow_cache_slot - 1,
+                    -shadow_sample.wi,
+                    shadow_sample.distance)
+                || rt_is_occluded(acceleration_structure, shadow_ray, RT_INSTANCE_MASK_SHADOW_DYNAMIC);
+        } else {
+            is_shadowed = rt_is_shadowed(acceleration_structure, shadow_ray);
+        }
+
+        if (!is_shadowed) {
+            shadowed_radiance += radiance;
+        }
+    }
+
+    const float unshadowed_luminance = sRGB_to_luminance(unshadowed_radiance);
+    const float visibility = unshadowed_luminance > 0.0
+        ? sRGB_to_luminance(shadowed_radiance) / unshadowed_luminance
+        : 1.0;
+
+    output_tex[px] = float4(unshadowed_radiance, visibility);
+}
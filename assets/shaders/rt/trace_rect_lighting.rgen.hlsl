@@ -0,0 +1,86 @@
+#include "../inc/uv.hlsl"
+#include "../inc/pack_unpack.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/bindless_textures.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/color/srgb.hlsl"
+#include "../inc/lights/rect.hlsl"
+
+// Direct lighting from rectangular lights. The unshadowed lighting is analytic (LTC),
+// and gets multiplied by the visibility of a random point on the light. That's the ratio
+// estimator from "Combining Analytic Direct Illumination and Stochastic Shadows" by Heitz et al.
+// with a single sample.
+//
+// Outputs the unshadowed lighting in RGB, and the fraction of it which is visible in alpha,
+// so that only the noisy visibility needs to go through `LocalLightShadowDenoiseRenderer`.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float3> geometric_normal_tex;
+[[vk::binding(3)]] RWTexture2D<float4> output_tex;
+
+[shader("raygeneration")]
+void main() {
+    const uint2 px = DispatchRaysIndex().xy;
+
+    const float2 pixel_center = px + 0.5.xx;
+    const float2 uv = pixel_center / DispatchRaysDimensions().xy;
+
+    const float depth = depth_tex[px];
+    if (0.0 == depth) {
+        output_tex[px] = 0.0;
+        return;
+    }
+
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 pt_ws = view_ray_context.ray_hit_ws();
+    const float3 wo_ws = -view_ray_context.ray_dir_ws();
+
+    const float3 geometric_normal_vs = geometric_normal_tex[px] * 2.0 - 1.0;
+    const float3 geometric_normal_ws = direction_view_to_world(geometric_normal_vs);
+    const float3 ray_origin = view_ray_context.biased_secondary_ray_origin_ws_with_normal(geometric_normal_ws);
+
+    GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+    LayeredBrdf brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, max(1e-3, dot(gbuffer.normal, wo_ws)));
+
+    float3 unshadowed_radiance = 0.0;
+    float3 shadowed_radiance = 0.0;
+
+    for (uint light_idx = 0; light_idx < frame_constants.rect_light_count; ++light_idx) {
+        const RectLight light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+        const float3 radiance =
+            light.evaluate_ltc(brdf, pt_ws, gbuffer.normal, wo_ws)
+            * frame_constants.pre_exposure;
+
+        if (all(radiance == 0.0)) {
+            continue;
+        }
+
+        unshadowed_radiance += radiance;
+
+        const float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index + light_idx * 4099).xy;
+        const float3 to_light = light.sample_point(urand) - ray_origin;
+        const float dist_to_light = length(to_light);
+
+        const bool is_shadowed = rt_is_shadowed(
+            acceleration_structure,
+            new_ray(ray_origin, to_light / max(1e-5, dist_to_light), 0.0, dist_to_light * 0.999));
+
+        if (!is_shadowed) {
+            shadowed_radiance += radiance;
+        }
+    }
+
+    const float unshadowed_luminance = sRGB_to_luminance(unshadowed_radiance);
+    const float visibility = unshadowed_luminance > 0.0
+        ? sRGB_to_luminance(shadowed_radiance) / unshadowed_luminance
+        : 1.0;
+
+    output_tex[px] = float4(unshadowed_radiance, visibility);
+}
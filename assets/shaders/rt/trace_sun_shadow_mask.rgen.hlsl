@@ -8,6 +8,8 @@
 
 #include "../inc/blue_noise.hlsl"
 #include "../inc/math.hlsl"
+#include "../inc/hash.hlsl"
+#include "../inc/render_quality.hlsl"
 
 #define USE_SOFT_SHADOWS 1
 
@@ -44,17 +46,31 @@ void main() {
     const float bias_amount = (-pt_vs.z + length(pt_ws.xyz)) * 1e-5;
     const float3 ray_origin = pt_ws.xyz + bias_dir * bias_amount;
 
-    const bool is_shadowed = rt_is_shadowed(
-        acceleration_structure,
-        new_ray(
-            ray_origin,
-            sample_sun_direction(
-                blue_noise_for_pixel(px, frame_constants.frame_index).xy,
-                USE_SOFT_SHADOWS
-            ),
-            0,
-            FLT_MAX
-        ));
-
-    output_tex[px] = is_shadowed ? 0.0 : 1.0;
+    const float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index).xy;
+    uint rng = hash3(uint3(px, frame_constants.frame_index));
+
+    uint lit_count = 0;
+    for (uint ray_i = 0; ray_i < SUN_SHADOW_RAY_COUNT; ++ray_i) {
+        // Additional rays rotate the blue noise sample by a random offset.
+        float2 ray_urand = urand;
+        if (ray_i > 0) {
+            ray_urand = frac(ray_urand + float2(
+                uint_to_u01_float(hash1_mut(rng)),
+                uint_to_u01_float(hash1_mut(rng))
+            ));
+        }
+
+        const bool is_shadowed = rt_is_shadowed(
+            acceleration_structure,
+            new_ray(
+                ray_origin,
+                sample_sun_direction(ray_urand, USE_SOFT_SHADOWS),
+                0,
+                FLT_MAX
+            ));
+
+        lit_count += is_shadowed ? 0 : 1;
+    }
+
+    output_tex[px] = float(lit_count) / SUN_SHADOW_RAY_COUNT;
 }
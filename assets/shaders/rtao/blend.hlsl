@@ -0,0 +1,16 @@
+[[vk::binding(0)]] Texture2D<float4> screen_space_tex;
+[[vk::binding(1)]] Texture2D<float2> ray_traced_tex;
+[[vk::binding(2)]] RWTexture2D<float4> output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float ray_traced_weight;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    float4 result = screen_space_tex[px];
+
+    // Only the visibility; the bent normal of GTAO, if any, is kept.
+    result.r = lerp(result.r, ray_traced_tex[px].r, ray_traced_weight);
+
+    output_tex[px] = result;
+}
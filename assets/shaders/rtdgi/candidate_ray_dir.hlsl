@@ -1,5 +1,9 @@
-float3 rtdgi_candidate_ray_dir(uint2 px, float3x3 tangent_to_world) {
+#include "../inc/quasi_random.hlsl"
+
+// `candidate_idx` > 0 selects additional directions for the same pixel and frame.
+float3 rtdgi_candidate_ray_dir(uint2 px, uint candidate_idx, float3x3 tangent_to_world) {
     float2 urand = blue_noise_for_pixel(px, frame_constants.frame_index).xy;
+    urand = frac(urand + (r2_sequence(candidate_idx) - 0.5));
 
     #if 0
         const uint salt = 35110969;
@@ -71,6 +71,7 @@ TraceResult do_the_thing(uint2 px, float3 normal_ws, inout uint rng, RayDesc out
         .propagate(reflected_cone_spread_angle, length(outgoing_ray.Origin - get_eye_position()));
 
     const GbufferPathVertex primary_hit = GbufferRaytrace::with_ray(outgoing_ray)
+        .with_instance_mask(RT_INSTANCE_MASK_GI)
         .with_cone(ray_cone)
         .with_cull_back_faces(false)
         .with_path_length(1)
@@ -148,8 +149,9 @@ TraceResult do_the_thing(uint2 px, float3 normal_ws, inout uint rng, RayDesc out
                     uint_to_u01_float(hash1_mut(rng))
                 );
 
-                for (uint light_idx = 0; light_idx < frame_constants.triangle_light_count; light_idx += 1) {
-                    TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+                if (frame_constants.triangle_light_count > 0) {
+                    const TriangleLightSelection light_selection = select_triangle_light(uint_to_u01_float(hash1_mut(rng)));
+                    TriangleLight triangle_light = light_selection.light;
                     LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
                     const float3 shadow_ray_origin = primary_hit.position;
                     const float3 to_light_ws = light_sample.pos - shadow_ray_origin;
@@ -181,11 +183,78 @@ TraceResult do_the_thing(uint2 px, float3 normal_ws, inout uint rng, RayDesc out
                         #endif
 
                         total_radiance +=
-                            !is_shadowed ? (triangle_light.radiance() * brdf_value / light_sample.pdf.value) : 0;
+                            !is_shadowed ? (triangle_light.radiance() * brdf_value / (light_sample.pdf.value * light_selection.pmf)) : 0;
                     }
                 }
             }
 
+            if (USE_LIGHTS && frame_constants.punctual_light_count > 0) {
+                const float light_selection_pmf = 1.0 / frame_constants.punctual_light_count;
+                const uint light_idx = hash1_mut(rng) % frame_constants.punctual_light_count;
+
+                const float2 urand = float2(
+                    uint_to_u01_float(hash1_mut(rng)),
+                    uint_to_u01_float(hash1_mut(rng))
+                );
+
+                const PunctualLight punctual_light = PunctualLight::from_packed(punctual_lights_dyn[light_idx]);
+                const PunctualLightSample light_sample = punctual_light.sample_with_radius(primary_hit.position, urand);
+                const float3 wi = mul(light_sample.wi, tangent_to_world);
+
+                if (wi.z > 0.0 && any(light_sample.radiance > 0.0)) {
+                    const bool is_shadowed =
+                        rt_is_shadowed(
+                            acceleration_structure,
+                            new_ray(
+                                primary_hit.position,
+                                light_sample.wi,
+                                1e-3,
+                                light_sample.distance - 2e-3
+                        ));
+
+                    const float3 brdf_value = brdf.evaluate(wo, wi) * wi.z;
+                    total_radiance +=
+                        !is_shadowed ? (light_sample.radiance * brdf_value / light_selection_pmf) : 0;
+                }
+            }
+
+            if (USE_LIGHTS && frame_constants.rect_light_count > 0) {
+                const float light_selection_pmf = 1.0 / frame_constants.rect_light_count;
+                const uint light_idx = hash1_mut(rng) % frame_constants.rect_light_count;
+
+                const float2 urand = float2(
+                    uint_to_u01_float(hash1_mut(rng)),
+                    uint_to_u01_float(hash1_mut(rng))
+                );
+
+                const RectLight rect_light = RectLight::from_packed(rect_lights_dyn[light_idx]);
+                const float3 to_light_ws = rect_light.sample_point(urand) - primary_hit.position;
+                const float dist_to_light2 = dot(to_light_ws, to_light_ws);
+                const float3 to_light_norm_ws = to_light_ws * rsqrt(dist_to_light2);
+
+                const float to_psa_metric =
+                    max(0.0, dot(to_light_norm_ws, gbuffer.normal))
+                    * rect_light.emission_cos(-to_light_norm_ws)
+                    / dist_to_light2;
+
+                if (to_psa_metric > 0.0) {
+                    const bool is_shadowed =
+                        rt_is_shadowed(
+                            acceleration_structure,
+                            new_ray(
+                                primary_hit.position,
+                                to_light_norm_ws,
+                                1e-3,
+                                sqrt(dist_to_light2) - 2e-3
+                        ));
+
+                    const float3 wi = mul(to_light_norm_ws, tangent_to_world);
+                    const float3 brdf_value = brdf.evaluate(wo, wi) * to_psa_metric;
+                    total_radiance +=
+                        !is_shadowed ? (rect_light.radiance * brdf_value * rect_light.area() / light_selection_pmf) : 0;
+                }
+            }
+
             if (USE_IRCACHE) {
                 const float3 gi = IrcacheLookupParams::create(
                     outgoing_ray.Origin,
@@ -0,0 +1,69 @@
+#include "../inc/samplers.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "rtdgi_restir_settings.hlsl"
+
+[[vk::binding(0)]] Texture2D<float> depth_tex;
+[[vk::binding(1)]] Texture2D<float4> reprojected_history_tex;
+[[vk::binding(2)]] Texture2D<float2> variance_history_tex;
+[[vk::binding(3)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(4)]] RWTexture2D<uint> ray_count_tex;
+[[vk::binding(5)]] RWByteAddressBuffer extra_ray_args_buf;
+[[vk::binding(6)]] RWStructuredBuffer<uint> extra_ray_px_buf;
+[[vk::binding(7)]] cbuffer _ {
+    float4 gbuffer_tex_size;
+    float4 output_tex_size;
+    uint max_ray_count;
+    float noise_threshold;
+};
+
+// With fewer frames than this in the temporal filter's history, the variance estimate
+// can't be trusted yet, and pixels get extra rays regardless.
+static const float TRUSTED_HISTORY_SAMPLE_COUNT = 8.0;
+
+// Decides how many candidate rays each half-res pixel traces this frame, from the noise
+// the temporal filter measured there in the previous one. The pixels getting more than
+// the one traced by `trace_diffuse.rgen.hlsl` are appended to `extra_ray_px_buf`,
+// and counted in the indirect trace arguments of `trace_diffuse_extra.rgen.hlsl`.
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    // The arguments start out cleared; the first thread fills in the ray counts along Y and Z.
+    if (all(px == 0)) {
+        extra_ray_args_buf.Store2(4, uint2(1, 1));
+    }
+
+    if (any(px >= uint2(output_tex_size.xy))) {
+        return;
+    }
+
+    const uint2 hi_px = px * 2 + HALFRES_SUBSAMPLE_OFFSET;
+    uint ray_count = 1;
+
+    // Validation frames don't trace candidates at all.
+    if (is_rtdgi_tracing_frame() && depth_tex[hi_px] != 0.0) {
+        const float2 uv = get_uv(hi_px, gbuffer_tex_size);
+        const float4 reproj = reprojection_tex[hi_px];
+
+        // Of the square root of luminance, so the relative deviation doesn't depend on exposure.
+        const float2 moments = variance_history_tex.SampleLevel(sampler_lnc, uv + reproj.xy, 0);
+        const float dev = sqrt(max(0.0, moments.y - moments.x * moments.x));
+        const float relative_dev = dev / max(1e-5, moments.x);
+
+        const float sample_count = reprojected_history_tex[hi_px].a;
+
+        const float noise = max(
+            relative_dev / max(1e-5, noise_threshold),
+            1.0 - sample_count / TRUSTED_HISTORY_SAMPLE_COUNT
+        );
+
+        ray_count = 1 + uint(round(saturate(noise) * (max_ray_count - 1)));
+    }
+
+    ray_count_tex[px] = ray_count;
+
+    if (ray_count > 1) {
+        uint list_idx;
+        extra_ray_args_buf.InterlockedAdd(0, 1, list_idx);
+        extra_ray_px_buf[list_idx] = px.x | (px.y << 16);
+    }
+}
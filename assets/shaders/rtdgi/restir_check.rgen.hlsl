@@ -49,14 +49,16 @@ void main() {
 
     const float3 trace_vec = hit_ws - trace_origin_ws;
 
-    if (rt_is_shadowed(
+    if (rt_is_occluded(
         acceleration_structure,
         new_ray(
             trace_origin_ws,
             normalize(trace_vec),
             0.0,
             min(5 *length(spx_pos_ws - trace_origin_ws), length(trace_vec) * 0.999)
-    ))) {
+        ),
+        RT_INSTANCE_MASK_GI
+    )) {
         r.W = 0;
         reservoir_input_tex[px] = r.as_raw();
     }
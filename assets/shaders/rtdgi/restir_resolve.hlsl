@@ -28,6 +28,7 @@
 [[vk::binding(12)]] cbuffer _ {
     float4 gbuffer_tex_size;
     float4 output_tex_size;
+    uint use_ssao_bent_normal;
 };
 
 static float ggx_ndf_unnorm(float a2, float cos_theta) {
@@ -62,7 +63,12 @@ void main(uint2 px : SV_DispatchThreadID) {
     const float center_depth = depth;
     const float center_ssao = ssao_tex[px].r;
 
-    //const float3 center_bent_normal_ws = normalize(direction_view_to_world(ssao_tex[px * 2].gba));
+
+    // When the AO provides a bent normal, limit the GI to the unoccluded cone around it,
+    // with the cone's angle derived from the visibility. Darkens small-scale contact areas
+    // which the traced rays are too coarse to resolve.
+    const float3 center_bent_normal_ws = normalize(direction_view_to_world(ssao_tex[px].gba));
+    const float bent_cone_cos = sqrt(saturate(1.0 - center_ssao));
 
     const uint frame_hash = hash1(frame_constants.frame_index);
     const uint px_idx_in_quad = (((px.x & 1) | (px.y & 1) * 2) + frame_hash) & 3;
@@ -109,7 +115,10 @@ void main(uint2 px : SV_DispatchThreadID) {
 
             const float geometric_term =
                 // TODO: fold the 2 into the PDF
-                2 * max(0.0, dot(center_normal_ws, sample_dir));
+                2 * max(0.0, dot(center_normal_ws, sample_dir))
+                * (use_ssao_bent_normal
+                    ? smoothstep(bent_cone_cos - 0.2, bent_cone_cos, dot(center_bent_normal_ws, sample_dir))
+                    : 1.0);
 
             const float atten = smoothstep(NEAR_FIELD_FADE_OUT_END, NEAR_FIELD_FADE_OUT_START, sample_dist);
             sharpen_gi_kernel |= atten > 0.9;
@@ -169,7 +178,10 @@ void main(uint2 px : SV_DispatchThreadID) {
 
             const float geometric_term =
                 // TODO: fold the 2 into the PDF
-                2 * max(0.0, dot(center_normal_ws, sample_dir));
+                2 * max(0.0, dot(center_normal_ws, sample_dir))
+                * (use_ssao_bent_normal
+                    ? smoothstep(bent_cone_cos - 0.2, bent_cone_cos, dot(center_bent_normal_ws, sample_dir))
+                    : 1.0);
 
             float3 radiance;
             if (RTDGI_RESTIR_SPATIAL_USE_RAYMARCH_COLOR_BOUNCE) {
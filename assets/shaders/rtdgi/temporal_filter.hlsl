@@ -25,10 +25,12 @@
 [[vk::binding(2)]] Texture2D<float2> variance_history_tex;
 [[vk::binding(3)]] Texture2D<float4> reprojection_tex;
 [[vk::binding(4)]] Texture2D<float2> rt_history_invalidity_tex;
-[[vk::binding(5)]] RWTexture2D<float4> output_tex;
-[[vk::binding(6)]] RWTexture2D<float4> history_output_tex;
-[[vk::binding(7)]] RWTexture2D<float2> variance_history_output_tex;
-[[vk::binding(8)]] cbuffer _ {
+// Full-res; see `reactive_mask.hlsl`
+[[vk::binding(5)]] Texture2D<float> reactive_mask_tex;
+[[vk::binding(6)]] RWTexture2D<float4> output_tex;
+[[vk::binding(7)]] RWTexture2D<float4> history_output_tex;
+[[vk::binding(8)]] RWTexture2D<float2> variance_history_output_tex;
+[[vk::binding(9)]] cbuffer _ {
     float4 output_tex_size;
     float4 gbuffer_tex_size;
 };
@@ -196,6 +198,13 @@ void main(uint2 px: SV_DispatchThreadID) {
     //max_sample_count = lerp(max_sample_count, 1, smoothstep(0.01, 0.6, 10 * temporal_change * (center_dev / max(1e-5, center_luma))));
     max_sample_count *= lerp(1.0, 0.5, rt_invalid);
 
+    // Disocclusions restart accumulation from a couple of samples.
+    const float reactive = reactive_mask_tex.SampleLevel(sampler_lnc, uv, 0);
+    max_sample_count = lerp(max_sample_count, min(max_sample_count, 2), reactive);
+
+    // As does a change in the lighting, such as the sun moving.
+    max_sample_count = lerp(max_sample_count, min(max_sample_count, 2), frame_constants.lighting_change);
+
 // hax
 //max_sample_count = 32;
 
@@ -10,6 +10,9 @@
 #include "../inc/atmosphere.hlsl"
 #include "../inc/sun.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/rect.hlsl"
 #include "../inc/reservoir.hlsl"
 #include "../ircache/bindings.hlsl"
 #include "../wrc/bindings.hlsl"
@@ -74,10 +77,8 @@ void main() {
         const float3 normal_vs = half_view_normal_tex[px];
         const float3 normal_ws = direction_view_to_world(normal_vs);
         const float3x3 tangent_to_world = build_orthonormal_basis(normal_ws);
-        const float3 outgoing_dir = rtdgi_candidate_ray_dir(px, tangent_to_world);
 
         RayDesc outgoing_ray;
-        outgoing_ray.Direction = outgoing_dir;
         outgoing_ray.Origin = view_ray_context.biased_secondary_ray_origin_ws_with_normal(normal_ws);
         outgoing_ray.TMin = 0;
 
@@ -88,7 +89,38 @@ void main() {
         }
 
         uint rng = hash3(uint3(px, frame_constants.frame_index & 31));
-        TraceResult result = do_the_thing(px, normal_ws, rng, outgoing_ray);
+
+        // With multiple candidates, pick one proportionally to its luminance (RIS),
+        // and pre-multiply its radiance by the resampling weight. All candidates
+        // come from the same distribution, so the weight is `mean(p_hat) / p_hat(selected)`.
+        const uint candidate_count = is_rtdgi_tracing_frame() ? DIFFUSE_GI_RAY_COUNT : 1;
+        uint ris_rng = hash3(uint3(px, frame_constants.frame_index + 0x9e3779b9));
+
+        float3 outgoing_dir = 0;
+        TraceResult result;
+        float p_hat_sum = 0;
+        float p_hat_sel = 0;
+
+        for (uint candidate_idx = 0; candidate_idx < candidate_count; ++candidate_idx) {
+            const float3 candidate_dir = rtdgi_candidate_ray_dir(px, candidate_idx, tangent_to_world);
+            outgoing_ray.Direction = candidate_dir;
+
+            const TraceResult candidate = do_the_thing(px, normal_ws, rng, outgoing_ray);
+            const float p_hat = max(0.0, sRGB_to_luminance(candidate.out_value));
+            p_hat_sum += p_hat;
+
+            if (0 == candidate_idx || p_hat_sum * uint_to_u01_float(hash1_mut(ris_rng)) < p_hat) {
+                outgoing_dir = candidate_dir;
+                result = candidate;
+                p_hat_sel = p_hat;
+            }
+        }
+
+        if (candidate_count > 1) {
+            result.out_value *= p_hat_sel > 0 ? p_hat_sum / (candidate_count * p_hat_sel) : 0.0;
+        }
+
+        outgoing_ray.Direction = outgoing_dir;
 
         #if RTDGI_INTERLEAVED_VALIDATION_ALWAYS_TRACE_NEAR_FIELD
             if (!is_rtdgi_tracing_frame() && !result.is_hit) {
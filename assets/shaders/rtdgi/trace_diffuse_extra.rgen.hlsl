@@ -0,0 +1,112 @@
+#include "../inc/uv.hlsl"
+#include "../inc/pack_unpack.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/atmosphere.hlsl"
+#include "../inc/sun.hlsl"
+#include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/rect.hlsl"
+#include "../inc/reservoir.hlsl"
+#include "../ircache/bindings.hlsl"
+#include "../wrc/bindings.hlsl"
+#include "../rtr/rtr_settings.hlsl" // for rtr_encode_cos_theta_for_fp16. consider moving out.
+#include "rtdgi_restir_settings.hlsl"
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+
+[[vk::binding(0)]] Texture2D<float3> half_view_normal_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> reprojected_gi_tex;
+DEFINE_IRCACHE_BINDINGS(3, 4, 5, 6, 7, 8, 9, 10, 11)
+DEFINE_WRC_BINDINGS(12)
+[[vk::binding(13)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(14)]] Texture2D<uint> ray_count_tex;
+[[vk::binding(15)]] StructuredBuffer<uint> extra_ray_px_buf;
+[[vk::binding(16)]] RWTexture2D<float4> candidate_irradiance_out_tex;
+[[vk::binding(17)]] RWTexture2D<float4> candidate_normal_out_tex;
+[[vk::binding(18)]] RWTexture2D<float4> candidate_hit_out_tex;
+[[vk::binding(19)]] cbuffer _ {
+    float4 gbuffer_tex_size;
+};
+
+#include "../ircache/lookup.hlsl"
+#include "../wrc/lookup.hlsl"
+#include "candidate_ray_dir.hlsl"
+
+#include "diffuse_trace_common.inc.hlsl"
+
+// Traces the rest of the candidates of the pixels which `ray_budget.hlsl` found noisy,
+// one ray per pixel of `extra_ray_px_buf`, and resamples them together with the candidate
+// `trace_diffuse.rgen.hlsl` already wrote. That one was traced with a single ray per pixel,
+// so its radiance is still unweighted.
+[shader("raygeneration")]
+void main() {
+    const uint packed_px = extra_ray_px_buf[DispatchRaysIndex().x];
+    const uint2 px = uint2(packed_px & 0xffff, packed_px >> 16);
+    const uint2 hi_px = px * 2 + HALFRES_SUBSAMPLE_OFFSET;
+
+    const float depth = depth_tex[hi_px];
+    const float2 uv = get_uv(hi_px, gbuffer_tex_size);
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_biased_depth(uv, depth);
+
+    const float3 normal_vs = half_view_normal_tex[px];
+    const float3 normal_ws = direction_view_to_world(normal_vs);
+    const float3x3 tangent_to_world = build_orthonormal_basis(normal_ws);
+
+    RayDesc outgoing_ray;
+    outgoing_ray.Origin = view_ray_context.biased_secondary_ray_origin_ws_with_normal(normal_ws);
+    outgoing_ray.TMin = 0;
+    outgoing_ray.TMax = SKY_DIST;
+
+    // Salted differently than in `trace_diffuse.rgen.hlsl`, so that the candidates
+    // aren't correlated with the one traced there.
+    uint rng = hash3(uint3(px, (frame_constants.frame_index & 31) + 0x85ebca6b));
+    uint ris_rng = hash3(uint3(px, frame_constants.frame_index + 0xc2b2ae35));
+
+    const uint candidate_count = ray_count_tex[px];
+
+    // Continue the streaming RIS from the first candidate, as it was written.
+    const float4 first_irradiance = candidate_irradiance_out_tex[px];
+    float p_hat_sum = max(0.0, sRGB_to_luminance(first_irradiance.rgb));
+    float p_hat_sel = p_hat_sum;
+
+    float3 outgoing_dir = 0;
+    TraceResult result;
+    bool first_selected = true;
+
+    for (uint candidate_idx = 1; candidate_idx < candidate_count; ++candidate_idx) {
+        const float3 candidate_dir = rtdgi_candidate_ray_dir(px, candidate_idx, tangent_to_world);
+        outgoing_ray.Direction = candidate_dir;
+
+        const TraceResult candidate = do_the_thing(px, normal_ws, rng, outgoing_ray);
+        const float p_hat = max(0.0, sRGB_to_luminance(candidate.out_value));
+        p_hat_sum += p_hat;
+
+        if (p_hat_sum * uint_to_u01_float(hash1_mut(ris_rng)) < p_hat) {
+            outgoing_dir = candidate_dir;
+            result = candidate;
+            p_hat_sel = p_hat;
+            first_selected = false;
+        }
+    }
+
+    const float ris_weight = p_hat_sel > 0 ? p_hat_sum / (candidate_count * p_hat_sel) : 0.0;
+
+    if (first_selected) {
+        candidate_irradiance_out_tex[px] = float4(first_irradiance.rgb * ris_weight, first_irradiance.a);
+    } else {
+        const float3 hit_offset_ws = outgoing_dir * result.hit_t;
+        const float cos_theta = dot(normalize(outgoing_dir - view_ray_context.ray_dir_ws()), normal_ws);
+
+        candidate_irradiance_out_tex[px] = float4(result.out_value * ris_weight, rtr_encode_cos_theta_for_fp16(cos_theta));
+        candidate_hit_out_tex[px] = float4(hit_offset_ws, result.pdf);
+        candidate_normal_out_tex[px] = float4(direction_world_to_view(result.hit_normal_ws), 0);
+    }
+}
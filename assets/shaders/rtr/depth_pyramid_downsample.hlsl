@@ -0,0 +1,45 @@
+// Builds the next mip of a pyramid of the closest depth, which is the max with reverse Z.
+// Odd-sized inputs have their last row and column folded into the edge texels,
+// so that every output texel is conservative.
+
+[[vk::binding(0)]] Texture2D<float> input_tex;
+[[vk::binding(1)]] RWTexture2D<float> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    uint2 input_size;
+    uint2 output_size;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    if (any(px >= output_size)) {
+        return;
+    }
+
+    const uint2 src_px = px * 2;
+    const uint2 last_src_px = input_size - 1;
+
+    float closest = 0.0;
+    closest = max(closest, input_tex[min(src_px + uint2(0, 0), last_src_px)]);
+    closest = max(closest, input_tex[min(src_px + uint2(1, 0), last_src_px)]);
+    closest = max(closest, input_tex[min(src_px + uint2(0, 1), last_src_px)]);
+    closest = max(closest, input_tex[min(src_px + uint2(1, 1), last_src_px)]);
+
+    const bool extra_col = (input_size.x & 1) != 0 && px.x == output_size.x - 1;
+    const bool extra_row = (input_size.y & 1) != 0 && px.y == output_size.y - 1;
+
+    if (extra_col) {
+        closest = max(closest, input_tex[min(src_px + uint2(2, 0), last_src_px)]);
+        closest = max(closest, input_tex[min(src_px + uint2(2, 1), last_src_px)]);
+    }
+
+    if (extra_row) {
+        closest = max(closest, input_tex[min(src_px + uint2(0, 2), last_src_px)]);
+        closest = max(closest, input_tex[min(src_px + uint2(1, 2), last_src_px)]);
+    }
+
+    if (extra_col && extra_row) {
+        closest = max(closest, input_tex[min(src_px + uint2(2, 2), last_src_px)]);
+    }
+
+    output_tex[px] = closest;
+}
@@ -6,13 +6,16 @@
 #include "../inc/brdf_lut.hlsl"
 #include "../inc/layered_brdf.hlsl"
 #include "../inc/blue_noise.hlsl"
+#include "../inc/quasi_random.hlsl"
 #include "../inc/rt.hlsl"
 #include "../inc/atmosphere.hlsl"
 #include "../inc/sun.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
 #include "../ircache/bindings.hlsl"
 #include "../wrc/bindings.hlsl"
 #include "rtr_settings.hlsl"
+#include "ssr.inc.hlsl"
 
 [[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
 
@@ -27,9 +30,15 @@ DEFINE_WRC_BINDINGS(16)
 [[vk::binding(18)]] RWTexture2D<float4> out1_tex;
 [[vk::binding(19)]] RWTexture2D<float4> out2_tex;
 [[vk::binding(20)]] RWTexture2D<uint> rng_out_tex;
-[[vk::binding(21)]] cbuffer _ {
+[[vk::binding(21)]] Texture2D<float> depth_pyramid_tex;
+[[vk::binding(22)]] Texture2D<float4> prev_radiance_tex;
+[[vk::binding(23)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(24)]] cbuffer _ {
     float4 gbuffer_tex_size;
     uint reuse_rtdgi_rays;
+    uint use_ssr_first_hit;
+    uint depth_pyramid_mip_count;
+    float roughness_cutoff;
 };
 
 //#define IRCACHE_LOOKUP_KEEP_ALIVE_PROB 0.125
@@ -55,6 +64,13 @@ void main() {
     GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_packed)).unpack();
     gbuffer.roughness = max(gbuffer.roughness, RTR_ROUGHNESS_CLAMP);
 
+    if (rtr_cutoff_roughness(gbuffer.roughness, gbuffer.clearcoat, gbuffer.clearcoat_roughness) > roughness_cutoff) {
+        // `light_gbuffer.hlsl` uses diffuse GI instead.
+        out0_tex[px] = float4(float3(1, 0, 1), 0);
+        out1_tex[px] = 0.0.xxxx;
+        return;
+    }
+
     // Initially, the candidate buffers contain candidates generated via diffuse tracing.
     // For rough surfaces we can skip generating new candidates just for reflections.
     // TODO: make this metric depend on spec contrast
@@ -100,27 +116,46 @@ void main() {
 #endif
 
     const float sampling_bias = SAMPLING_BIAS;
-    urand.x = lerp(urand.x, 0.0, sampling_bias);
-
-    BrdfSample brdf_sample = specular_brdf.sample(wo, urand);
-    
-// VNDF still returns a lot of invalid samples on rough surfaces at F0 angles!
-// TODO: move this to a separate sample preparation compute shader
-#if USE_TEMPORAL_JITTER// && !USE_GGX_VNDF_SAMPLING
-    [loop] for (uint retry_i = 0; retry_i < 4 && !brdf_sample.is_valid(); ++retry_i) {
-        urand = float2(
-            uint_to_u01_float(hash1_mut(rng)),
-            uint_to_u01_float(hash1_mut(rng))
-        );
-        urand.x = lerp(urand.x, 0.0, sampling_bias);
-
-        brdf_sample = specular_brdf.sample(wo, urand);
-    }
-#endif
 
-    const float cos_theta = normalize(wo + brdf_sample.wi).z;
+    SpecularBrdfEnergyPreservation brdf_lut = SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, wo.z);
+
+    // With multiple candidates, pick one proportionally to `luminance / pdf` (RIS),
+    // and scale its radiance such that `radiance / pdf` becomes the RIS estimator.
+    uint ris_rng = hash3(uint3(px, noise_offset + 0x9e3779b9));
+    uint valid_count = 0;
+    float w_sum = 0;
+    float w_sel = 0;
+
+    float3 outgoing_dir_sel = 0;
+    float cos_theta_sel = 0;
+    float pdf_sel = 0;
+    uint rng_sel = 0;
+    RtrTraceResult result_sel;
+
+    for (uint candidate_idx = 0; candidate_idx < REFLECTION_RAY_COUNT; ++candidate_idx) {
+        float2 candidate_urand = frac(urand + (r2_sequence(candidate_idx) - 0.5));
+        candidate_urand.x = lerp(candidate_urand.x, 0.0, sampling_bias);
+
+        BrdfSample brdf_sample = specular_brdf.sample(wo, candidate_urand);
+
+    // VNDF still returns a lot of invalid samples on rough surfaces at F0 angles!
+    // TODO: move this to a separate sample preparation compute shader
+    #if USE_TEMPORAL_JITTER// && !USE_GGX_VNDF_SAMPLING
+        [loop] for (uint retry_i = 0; retry_i < 4 && !brdf_sample.is_valid(); ++retry_i) {
+            candidate_urand = float2(
+                uint_to_u01_float(hash1_mut(rng)),
+                uint_to_u01_float(hash1_mut(rng))
+            );
+            candidate_urand.x = lerp(candidate_urand.x, 0.0, sampling_bias);
+
+            brdf_sample = specular_brdf.sample(wo, candidate_urand);
+        }
+    #endif
+
+        if (!brdf_sample.is_valid()) {
+            continue;
+        }
 
-    if (brdf_sample.is_valid()) {
         //const bool use_short_ray = gbuffer.roughness > 0.55 && USE_SHORT_RAYS_FOR_ROUGH;
 
         RayDesc outgoing_ray;
@@ -130,8 +165,8 @@ void main() {
         outgoing_ray.TMax = SKY_DIST;
 
         //uint rng = hash2(px);
-        rng_out_tex[px] = rng;
-        RtrTraceResult result = do_the_thing(px, gbuffer.normal, gbuffer.roughness, rng, outgoing_ray);
+        const uint candidate_rng = rng;
+        RtrTraceResult result = rtr_trace(px, gbuffer.normal, gbuffer.roughness, rng, outgoing_ray);
 
         const float3 direction_vs = direction_world_to_view(outgoing_ray.Direction);
         const float to_surface_area_measure =
@@ -142,10 +177,6 @@ void main() {
             #endif
             / max(1e-10, result.hit_t * result.hit_t);
 
-        const float3 hit_offset_ws = outgoing_ray.Direction * result.hit_t;
-
-        SpecularBrdfEnergyPreservation brdf_lut = SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, wo.z);
-
         const float pdf =
             #if RTR_PDF_STORED_WITH_SURFACE_AREA_METRIC
                 to_surface_area_measure *
@@ -159,9 +190,31 @@ void main() {
             // Here we adjust the value back to what it would be if a fraction was returned invalid.
             brdf_lut.valid_sample_fraction;
 
-        out0_tex[px] = float4(result.total_radiance, rtr_encode_cos_theta_for_fp16(cos_theta));
-        out1_tex[px] = float4(hit_offset_ws, pdf);
-        out2_tex[px] = float4(result.hit_normal_vs, 0);
+        const float w = max(0.0, sRGB_to_luminance(result.total_radiance)) / pdf;
+        w_sum += w;
+        valid_count += 1;
+
+        if (1 == valid_count || w_sum * uint_to_u01_float(hash1_mut(ris_rng)) < w) {
+            w_sel = w;
+            outgoing_dir_sel = outgoing_ray.Direction;
+            cos_theta_sel = normalize(wo + brdf_sample.wi).z;
+            pdf_sel = pdf;
+            rng_sel = candidate_rng;
+            result_sel = result;
+        }
+    }
+
+    if (valid_count > 0) {
+        if (valid_count > 1) {
+            result_sel.total_radiance *= w_sel > 0 ? w_sum / (valid_count * w_sel) : 0.0;
+        }
+
+        const float3 hit_offset_ws = outgoing_dir_sel * result_sel.hit_t;
+
+        rng_out_tex[px] = rng_sel;
+        out0_tex[px] = float4(result_sel.total_radiance, rtr_encode_cos_theta_for_fp16(cos_theta_sel));
+        out1_tex[px] = float4(hit_offset_ws, pdf_sel);
+        out2_tex[px] = float4(result_sel.hit_normal_vs, 0);
     } else {
         out0_tex[px] = float4(float3(1, 0, 1), 0);
         out1_tex[px] = 0.0.xxxx;
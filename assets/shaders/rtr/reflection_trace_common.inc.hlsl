@@ -80,6 +80,7 @@ RtrTraceResult do_the_thing(uint2 px, float3 normal_ws, float roughness, inout u
 
     if (!LAYERED_BRDF_FORCE_DIFFUSE_ONLY) {
         const GbufferPathVertex primary_hit = GbufferRaytrace::with_ray(outgoing_ray)
+            .with_instance_mask(RT_INSTANCE_MASK_REFLECTION)
             .with_cone(ray_cone)
             .with_cull_back_faces(false)
             .with_path_length(1)
@@ -163,8 +164,9 @@ RtrTraceResult do_the_thing(uint2 px, float3 normal_ws, float roughness, inout u
                             uint_to_u01_float(hash1_mut(rng))
                         );
 
-                        for (uint light_idx = 0; light_idx < frame_constants.triangle_light_count; light_idx += 1) {
-                            TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+                        if (frame_constants.triangle_light_count > 0) {
+                            const TriangleLightSelection light_selection = select_triangle_light(uint_to_u01_float(hash1_mut(rng)));
+                            TriangleLight triangle_light = light_selection.light;
                             LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
                             const float3 shadow_ray_origin = primary_hit.position;
                             const float3 to_light_ws = light_sample.pos - shadow_ray_origin;
@@ -196,7 +198,7 @@ RtrTraceResult do_the_thing(uint2 px, float3 normal_ws, float roughness, inout u
                                 #endif
 
                                 total_radiance +=
-                                    !is_shadowed ? (triangle_light.radiance() * brdf_value / light_sample.pdf.value) : 0;
+                                    !is_shadowed ? (triangle_light.radiance() * brdf_value / (light_sample.pdf.value * light_selection.pmf)) : 0;
                             }
                         }
                     }
@@ -255,3 +257,41 @@ RtrTraceResult do_the_thing(uint2 px, float3 normal_ws, float roughness, inout u
 
     return result;
 }
+
+// When `use_ssr_first_hit` is set, marches the depth buffer first, and only traces a ray if that misses.
+// Validation must make the same choice, or it would keep rejecting screen-space hits.
+RtrTraceResult rtr_trace(uint2 px, float3 normal_ws, float roughness, inout uint rng, RayDesc outgoing_ray) {
+    if (use_ssr_first_hit) {
+        const SsrHit ssr_hit = ssr_trace(
+            depth_pyramid_tex,
+            uint2(gbuffer_tex_size.xy),
+            depth_pyramid_mip_count,
+            outgoing_ray.Origin,
+            outgoing_ray.Direction,
+            SKY_DIST
+        );
+
+        if (ssr_hit.is_hit) {
+            float3 hit_normal_ws;
+            const float4 radiance = ssr_hit_radiance(
+                ssr_hit,
+                outgoing_ray.Direction,
+                gbuffer_tex,
+                prev_radiance_tex,
+                reprojection_tex,
+                gbuffer_tex_size,
+                hit_normal_ws
+            );
+
+            if (radiance.a > 0.5) {
+                RtrTraceResult result;
+                result.total_radiance = radiance.rgb;
+                result.hit_t = length(ssr_hit.position_ws - outgoing_ray.Origin);
+                result.hit_normal_vs = direction_world_to_view(hit_normal_ws);
+                return result;
+            }
+        }
+    }
+
+    return do_the_thing(px, normal_ws, roughness, rng, outgoing_ray);
+}
@@ -10,10 +10,12 @@
 #include "../inc/atmosphere.hlsl"
 #include "../inc/sun.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
 #include "../inc/reservoir.hlsl"
 #include "../ircache/bindings.hlsl"
 #include "../wrc/bindings.hlsl"
 #include "rtr_settings.hlsl"
+#include "ssr.inc.hlsl"
 
 [[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
 
@@ -29,8 +31,14 @@ DEFINE_WRC_BINDINGS(14)
 [[vk::binding(17)]] Texture2D<uint> rng_history_tex;
 [[vk::binding(18)]] RWTexture2D<float4> irradiance_history_tex;
 [[vk::binding(19)]] RWTexture2D<uint2> reservoir_history_tex;
-[[vk::binding(20)]] cbuffer _ {
+[[vk::binding(20)]] Texture2D<float> depth_pyramid_tex;
+[[vk::binding(21)]] Texture2D<float4> prev_radiance_tex;
+[[vk::binding(22)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(23)]] cbuffer _ {
     float4 gbuffer_tex_size;
+    uint use_ssr_first_hit;
+    uint depth_pyramid_mip_count;
+    float roughness_cutoff;
 };
 
 //#define IRCACHE_LOOKUP_KEEP_ALIVE_PROB 0.125
@@ -66,6 +74,12 @@ void main() {
     GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_packed)).unpack();
     gbuffer.roughness = max(gbuffer.roughness, RTR_ROUGHNESS_CLAMP);
 
+    // Not traced in the first place; see `reflection.rgen.hlsl`.
+    if (rtr_cutoff_roughness(gbuffer.roughness, gbuffer.clearcoat, gbuffer.clearcoat_roughness) > roughness_cutoff) {
+        refl_restir_invalidity_tex[px] = 1;
+        return;
+    }
+
     const float3x3 tangent_to_world = build_orthonormal_basis(gbuffer.normal);
 
 #if RTR_USE_TIGHTER_RAY_BIAS
@@ -90,7 +104,7 @@ void main() {
 
     //uint rng = hash2(px);
     uint rng = rng_history_tex[px];
-    RtrTraceResult result = do_the_thing(px, gbuffer.normal, gbuffer.roughness, rng, outgoing_ray);
+    RtrTraceResult result = rtr_trace(px, gbuffer.normal, gbuffer.roughness, rng, outgoing_ray);
 
     Reservoir1spp r = Reservoir1spp::from_raw(reservoir_history_tex[px]);
 
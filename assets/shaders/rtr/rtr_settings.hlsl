@@ -6,8 +6,10 @@
 // HACK: must be 1 if jointly filtering with specular lighting
 #define RTR_RENDER_SCALED_BY_FG 0
 
+#include "../inc/render_quality.hlsl"
+
 #define RTR_USE_RESTIR true
-#define RTR_RESTIR_TEMPORAL_M_CLAMP 8.0
+#define RTR_RESTIR_TEMPORAL_M_CLAMP REFLECTION_HISTORY_LENGTH
 #define RTR_RESTIR_USE_PATH_VALIDATION true
 
 // At 0.0 uses the center pixel's position when calculating the ray direction for a neighbor sample.
@@ -49,6 +51,22 @@
 
 #define RTR_USE_TIGHTER_RAY_BIAS 1
 
+// Reflections are not traced for surfaces rougher than the `roughness_cutoff` passed to
+// the reflection passes. `light_gbuffer.hlsl` fades over to diffuse GI over this range below it.
+// Cutoffs of 1 and above disable this.
+#define RTR_ROUGHNESS_CUTOFF_FADE 0.1
+
+// The roughness compared against the cutoff. A smooth clearcoat keeps the reflections.
+float rtr_cutoff_roughness(float roughness, float clearcoat, float clearcoat_roughness) {
+    return clearcoat > 0 ? min(roughness, clearcoat_roughness) : roughness;
+}
+
+float rtr_roughness_cutoff_fallback_weight(float cutoff_roughness, float roughness_cutoff) {
+    return roughness_cutoff < 1.0
+        ? smoothstep(roughness_cutoff - RTR_ROUGHNESS_CUTOFF_FADE, roughness_cutoff, cutoff_roughness)
+        : 0.0;
+}
+
 float rtr_encode_cos_theta_for_fp16(float x) {
     return 1 - x;
     //return log(x);
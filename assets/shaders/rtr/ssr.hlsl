@@ -0,0 +1,147 @@
+#include "../inc/uv.hlsl"
+#include "../inc/pack_unpack.hlsl"
+#include "../inc/frame_constants.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/samplers.hlsl"
+#include "../inc/hash.hlsl"
+#include "rtr_settings.hlsl"
+#include "ssr.inc.hlsl"
+
+// Generates reflection candidates by marching the depth buffer, for when ray tracing is not available.
+// The outputs match those of `reflection.rgen.hlsl`, so they go through the same ReSTIR and resolve passes.
+// Rays which leave the screen see the sky.
+
+[[vk::binding(0)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float> depth_pyramid_tex;
+[[vk::binding(3)]] Texture2D<float4> prev_radiance_tex;
+[[vk::binding(4)]] Texture2D<float4> reprojection_tex;
+[[vk::binding(5)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(6)]] RWTexture2D<float4> out0_tex;
+[[vk::binding(7)]] RWTexture2D<float4> out1_tex;
+[[vk::binding(8)]] RWTexture2D<float4> out2_tex;
+[[vk::binding(9)]] RWTexture2D<uint> rng_out_tex;
+[[vk::binding(10)]] cbuffer _ {
+    float4 gbuffer_tex_size;
+    uint depth_pyramid_mip_count;
+    float roughness_cutoff;
+};
+
+static const float SKY_DIST = 1e4;
+
+#define USE_TEMPORAL_JITTER 1
+#define SAMPLING_BIAS 0.15
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const uint2 hi_px = px * 2 + HALFRES_SUBSAMPLE_OFFSET;
+    float depth = depth_tex[hi_px];
+
+    if (0.0 == depth) {
+        out0_tex[px] = float4(0.0.xxx, -SKY_DIST);
+        return;
+    }
+
+    const float2 uv = get_uv(hi_px, gbuffer_tex_size);
+
+    float4 gbuffer_packed = gbuffer_tex[hi_px];
+    GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_packed)).unpack();
+    gbuffer.roughness = max(gbuffer.roughness, RTR_ROUGHNESS_CLAMP);
+
+    if (rtr_cutoff_roughness(gbuffer.roughness, gbuffer.clearcoat, gbuffer.clearcoat_roughness) > roughness_cutoff) {
+        // `light_gbuffer.hlsl` uses diffuse GI instead.
+        out0_tex[px] = float4(float3(1, 0, 1), 0);
+        out1_tex[px] = 0.0.xxxx;
+        return;
+    }
+
+    const float3x3 tangent_to_world = build_orthonormal_basis(gbuffer.normal);
+
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_biased_depth(uv, depth);
+    const float3 refl_ray_origin_ws = view_ray_context.biased_secondary_ray_origin_ws_with_normal(gbuffer.normal);
+
+    float3 wo = mul(-view_ray_context.ray_dir_ws(), tangent_to_world);
+
+    // See `reflection.rgen.hlsl`
+    if (wo.z < 0.0) {
+        wo.z *= -0.25;
+        wo = normalize(wo);
+    }
+
+    SpecularBrdf specular_brdf;
+    specular_brdf.albedo = lerp(0.04, gbuffer.albedo, gbuffer.metalness);
+    specular_brdf.roughness = gbuffer.roughness;
+
+    const uint noise_offset = frame_constants.frame_index * (USE_TEMPORAL_JITTER ? 1 : 0);
+    uint rng = hash3(uint3(px, noise_offset));
+
+    float2 urand = blue_noise_for_pixel(px, noise_offset).xy;
+    urand.x = lerp(urand.x, 0.0, SAMPLING_BIAS);
+
+    BrdfSample brdf_sample = specular_brdf.sample(wo, urand);
+
+    [loop] for (uint retry_i = 0; retry_i < 4 && !brdf_sample.is_valid(); ++retry_i) {
+        urand = float2(
+            uint_to_u01_float(hash1_mut(rng)),
+            uint_to_u01_float(hash1_mut(rng))
+        );
+        urand.x = lerp(urand.x, 0.0, SAMPLING_BIAS);
+
+        brdf_sample = specular_brdf.sample(wo, urand);
+    }
+
+    if (!brdf_sample.is_valid()) {
+        out0_tex[px] = float4(float3(1, 0, 1), 0);
+        out1_tex[px] = 0.0.xxxx;
+        return;
+    }
+
+    const float cos_theta = normalize(wo + brdf_sample.wi).z;
+    const float3 outgoing_dir = mul(tangent_to_world, brdf_sample.wi);
+
+    rng_out_tex[px] = rng;
+
+    float3 radiance = sky_cube_tex.SampleLevel(sampler_llr, outgoing_dir, 0).rgb;
+    float hit_t = SKY_DIST;
+    float3 hit_normal_vs = -direction_world_to_view(outgoing_dir);
+
+    const SsrHit ssr_hit = ssr_trace(
+        depth_pyramid_tex,
+        uint2(gbuffer_tex_size.xy),
+        depth_pyramid_mip_count,
+        refl_ray_origin_ws,
+        outgoing_dir,
+        SKY_DIST
+    );
+
+    if (ssr_hit.is_hit) {
+        float3 hit_normal_ws;
+        const float4 ssr_radiance = ssr_hit_radiance(
+            ssr_hit,
+            outgoing_dir,
+            gbuffer_tex,
+            prev_radiance_tex,
+            reprojection_tex,
+            gbuffer_tex_size,
+            hit_normal_ws
+        );
+
+        radiance = lerp(radiance, ssr_radiance.rgb, ssr_radiance.a);
+
+        if (ssr_radiance.a > 0.5) {
+            hit_t = length(ssr_hit.position_ws - refl_ray_origin_ws);
+            hit_normal_vs = direction_world_to_view(hit_normal_ws);
+        }
+    }
+
+    SpecularBrdfEnergyPreservation brdf_lut = SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, wo.z);
+    const float pdf = brdf_sample.pdf / brdf_lut.valid_sample_fraction;
+
+    out0_tex[px] = float4(radiance, rtr_encode_cos_theta_for_fp16(cos_theta));
+    out1_tex[px] = float4(outgoing_dir * hit_t, pdf);
+    out2_tex[px] = float4(hit_normal_vs, 0);
+}
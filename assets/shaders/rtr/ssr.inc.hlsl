@@ -0,0 +1,173 @@
+#ifndef RTR_SSR_INC_HLSL
+#define RTR_SSR_INC_HLSL
+
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/gbuffer.hlsl"
+#include "../inc/math_const.hlsl"
+
+// Screen-space reflection rays, marched hierarchically against a pyramid of the closest depth,
+// as in "Hi-Z Screen-Space Cone-Traced Reflections" by Yasin Uludag.
+//
+// Hits are shaded with the previous frame's lighting, reprojected.
+
+static const uint SSR_MAX_ITERATIONS = 96;
+
+// How far behind the depth buffer a ray can be, relative to the view-space depth there,
+// before it's considered to be passing behind an object rather than hitting it.
+static const float SSR_RELATIVE_THICKNESS = 0.03;
+static const float SSR_MIN_THICKNESS = 0.05;
+
+struct SsrHit {
+    bool is_hit;
+    float2 uv;
+    float3 position_ws;
+};
+
+SsrHit ssr_miss() {
+    SsrHit res;
+    res.is_hit = false;
+    res.uv = 0.0;
+    res.position_ws = 0.0;
+    return res;
+}
+
+SsrHit ssr_trace(
+    Texture2D<float> depth_pyramid_tex,
+    uint2 depth_size,
+    uint depth_mip_count,
+    float3 origin_ws,
+    float3 dir_ws,
+    float max_t
+) {
+    const float3 origin_vs = position_world_to_view(origin_ws);
+    const float3 dir_vs = direction_world_to_view(dir_ws);
+
+    // Keep the end of the ray well in front of the camera, where the projection behaves.
+    if (dir_vs.z > 0.0) {
+        max_t = min(max_t, -0.5 * origin_vs.z / dir_vs.z);
+    }
+
+    const float3 start_cs = position_world_to_sample(origin_ws);
+    const float3 end_cs = position_world_to_sample(origin_ws + dir_ws * max_t);
+
+    // Screen-space uv, and the non-linear depth, both of which are linear along the ray.
+    const float3 start = float3(cs_to_uv(start_cs.xy), start_cs.z);
+    const float3 delta = float3(cs_to_uv(end_cs.xy), end_cs.z) - start;
+
+    const float delta_px_len = length(delta.xy * depth_size);
+    if (delta_px_len < 1.0) {
+        // Going (almost) straight into the screen; nothing to march.
+        return ssr_miss();
+    }
+
+    // Clip to the screen
+    float t_max = 1.0;
+    if (delta.x != 0.0) {
+        t_max = min(t_max, ((delta.x > 0.0 ? 1.0 : 0.0) - start.x) / delta.x);
+    }
+    if (delta.y != 0.0) {
+        t_max = min(t_max, ((delta.y > 0.0 ? 1.0 : 0.0) - start.y) / delta.y);
+    }
+
+    const float2 inv_delta = float2(
+        delta.x != 0.0 ? rcp(delta.x) : FLT_MAX,
+        delta.y != 0.0 ? rcp(delta.y) : FLT_MAX
+    );
+    const uint2 cell_step = uint2(delta.xy > 0.0);
+
+    // Nudges across cell boundaries, so that rounding doesn't keep the ray in the same cell.
+    const float t_nudge = 0.01 / delta_px_len;
+
+    // Start from the next pixel over, to avoid hitting the surface the ray leaves.
+    float t = 1.0 / delta_px_len;
+    int level = 0;
+
+    for (uint i = 0; i < SSR_MAX_ITERATIONS && t < t_max; ++i) {
+        const float3 p = start + delta * t;
+        const uint2 px = min(uint2(p.xy * depth_size), depth_size - 1);
+        // Mips are rounded down, with odd rows and columns folded into the last cell.
+        const uint2 cell = min(px >> level, max(1u, depth_size >> level) - 1);
+
+        // Where the ray leaves the cell
+        const float2 boundary_uv = float2((cell + cell_step) << level) / depth_size;
+        const float2 t_boundary = abs(inv_delta) < FLT_MAX ? (boundary_uv - start.xy) * inv_delta : FLT_MAX;
+        const float t_exit = min(min(t_boundary.x, t_boundary.y), t_max);
+
+        const float cell_depth = depth_pyramid_tex.Load(int3(cell, level));
+
+        // Reverse Z: larger depth is closer.
+        const float ray_far_depth = min(p.z, start.z + delta.z * t_exit);
+
+        if (ray_far_depth > cell_depth) {
+            // The ray is in front of everything in this cell; skip it, and take bigger steps.
+            t = t_exit + t_nudge;
+            level = min(level + 1, int(depth_mip_count) - 1);
+            continue;
+        }
+
+        // Advance to where the ray goes behind the closest surface in the cell, if it's still in front.
+        const float t_cross = (p.z > cell_depth && delta.z < 0.0) ? (cell_depth - start.z) / delta.z : t;
+
+        if (level > 0) {
+            t = max(t, t_cross);
+            level -= 1;
+            continue;
+        }
+
+        const float surface_dist = -depth_to_view_z(cell_depth);
+        const float ray_dist = -depth_to_view_z(start.z + delta.z * t_cross);
+
+        if (cell_depth != 0.0
+            && ray_dist - surface_dist < max(SSR_MIN_THICKNESS, surface_dist * SSR_RELATIVE_THICKNESS)) {
+            const float2 hit_uv = start.xy + delta.xy * t_cross;
+
+            SsrHit res;
+            res.is_hit = true;
+            res.uv = hit_uv;
+            res.position_ws = ViewRayContext::from_uv_and_depth(hit_uv, cell_depth).ray_hit_ws();
+            return res;
+        }
+
+        // Passing behind an object
+        t = t_exit + t_nudge;
+    }
+
+    return ssr_miss();
+}
+
+// Radiance leaving the hit surface towards the ray's origin, approximated by the previous frame's lighting.
+// Returns zero weight where that isn't available, or if the ray hit the back of the surface.
+float4 ssr_hit_radiance(
+    SsrHit hit,
+    float3 dir_ws,
+    Texture2D<float4> gbuffer_tex,
+    Texture2D<float4> prev_radiance_tex,
+    Texture2D<float4> reprojection_tex,
+    float4 tex_size,
+    out float3 hit_normal_ws
+) {
+    const int2 hit_px = min(int2(hit.uv * tex_size.xy), int2(tex_size.xy) - 1);
+
+    hit_normal_ws = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[hit_px])).unpack_normal();
+    if (dot(hit_normal_ws, dir_ws) >= 0.0) {
+        return 0.0;
+    }
+
+    // Disoccluded, or off-screen in the previous frame
+    const float4 reproj = reprojection_tex[hit_px];
+    if (reproj.z == 0.0 || reproj.w < 0.0) {
+        return 0.0;
+    }
+
+    const int2 prev_px = int2((hit.uv + reproj.xy) * tex_size.xy);
+    const float3 radiance = prev_radiance_tex[prev_px].rgb * frame_constants.pre_exposure_delta;
+
+    // Fade out towards the edges of the screen, where the march is more likely to miss.
+    const float2 edge_dist = min(hit.uv, 1.0 - hit.uv);
+    const float edge_fade = smoothstep(0.0, 0.05, min(edge_dist.x, edge_dist.y));
+
+    return float4(radiance, edge_fade);
+}
+
+#endif  // RTR_SSR_INC_HLSL
@@ -1,3 +1,5 @@
+#include "../inc/hash.hlsl"
+
 [[vk::binding(0)]] Texture2D<float> input_tex;
 [[vk::binding(1)]] RWTexture2D<uint> output_tex;
 [[vk::binding(2)]] cbuffer _ {
@@ -10,7 +12,9 @@ uint2 FFX_DNSR_Shadows_GetBufferDimensions() {
 }
 
 bool FFX_DNSR_Shadows_HitsLight(uint2 px, uint2 gtid, uint2 gid) {
-    return input_tex[px] > 0.5;
+    // The mask can be fractional when tracing multiple rays per pixel. Dithering it keeps
+    // penumbrae from being classified as fully lit or shadowed.
+    return input_tex[px] > uint_to_u01_float(hash2(px));
 }
 
 void FFX_DNSR_Shadows_WriteMask(uint linear_tile_index, uint value) {
@@ -0,0 +1,28 @@
+#include "../inc/tile_lists.hlsl"
+#include "ffx/ffx_denoiser_shadows_util.hlsl"
+
+[[vk::binding(0)]] Texture2D<uint> meta_tex;
+[[vk::binding(1)]] RWByteAddressBuffer dispatch_args;
+[[vk::binding(2)]] RWStructuredBuffer<uint> simple_tiles;
+[[vk::binding(3)]] RWStructuredBuffer<uint> complex_tiles;
+[[vk::binding(4)]] cbuffer _ {
+    uint2 tile_count;
+};
+
+// Tiles which the temporal pass found to be uniformly lit or shadowed are simple;
+// the spatial filter only needs to run on the rest.
+[numthreads(8, 8, 1)]
+void main(uint2 tile: SV_DispatchThreadID) {
+    if (any(tile >= tile_count)) {
+        return;
+    }
+
+    // Indexed by the 8x8 tile, as `FFX_DNSR_Shadows_ReadTileMetaData` does in the filter.
+    const uint meta_data = meta_tex[tile];
+
+    if (meta_data & TILE_META_DATA_CLEAR_MASK) {
+        tile_lists_push(dispatch_args, simple_tiles, TILE_CLASS_SIMPLE, tile);
+    } else {
+        tile_lists_push(dispatch_args, complex_tiles, TILE_CLASS_COMPLEX, tile);
+    }
+}
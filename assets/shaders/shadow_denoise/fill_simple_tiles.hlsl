@@ -0,0 +1,16 @@
+#include "../inc/tile_lists.hlsl"
+#include "ffx/ffx_denoiser_shadows_util.hlsl"
+
+[[vk::binding(0)]] Texture2D<uint> meta_tex;
+[[vk::binding(1)]] RWTexture2D<float2> output_tex;
+[[vk::binding(2)]] StructuredBuffer<uint> tile_list;
+
+// What the spatial filter outputs for cleared tiles, without loading its neighborhood.
+[numthreads(8, 8, 1)]
+void main(uint2 gtid: SV_GroupThreadID, uint2 gid: SV_GroupID) {
+    const uint2 tile = tile_lists_get(tile_list, gid.x);
+    const uint2 px = tile * TILE_LIST_TILE_SIZE + gtid;
+
+    const bool all_in_light = meta_tex[tile] & TILE_META_DATA_LIGHT_MASK;
+    output_tex[px] = float2(all_in_light ? 1.0 : 0.0, 0.0);
+}
@@ -1,4 +1,5 @@
 #include "../inc/frame_constants.hlsl"
+#include "../inc/tile_lists.hlsl"
 
 [[vk::binding(0)]] Texture2D<float2> input_tex;
 [[vk::binding(1)]] Texture2D<uint> meta_tex;
@@ -10,6 +11,7 @@
     uint2 bitpacked_shadow_mask_extent;
     uint step_size;
 };
+[[vk::binding(6)]] StructuredBuffer<uint> tile_list;
 
 // Would be nice, but not suppored on GTX1xxx hardware
 // according to https://vulkan.gpuinfo.org/listdevicescoverage.php?core=1.2&feature=shaderFloat16&platform=windows
@@ -60,8 +62,12 @@ uint FFX_DNSR_Shadows_ReadTileMetaData(uint linear_tile_index) {
 
 #include "ffx/ffx_denoiser_shadows_filter.hlsl"
 
+// Dispatched over the complex tiles only; see `classify_tiles.hlsl`.
 [numthreads(8, 8, 1)]
-void main(uint2 px: SV_DispatchThreadID, uint2 gtid: SV_GroupThreadID, uint2 gid: SV_GroupID) {
+void main(uint2 gtid: SV_GroupThreadID, uint2 group_id: SV_GroupID) {
+    const uint2 gid = tile_lists_get(tile_list, group_id.x);
+    const uint2 px = gid * TILE_LIST_TILE_SIZE + gtid;
+
     const uint pass_idx = 0;
 
     bool write_results = true;
@@ -0,0 +1,68 @@
+#include "inc/mesh.hlsl"
+
+// Must match `skinning.rs`
+struct JointMatrix {
+    row_major float3x4 m;
+};
+
+[[vk::binding(0)]] RWByteAddressBuffer vertices_rw;
+[[vk::binding(1)]] StructuredBuffer<JointMatrix> joint_matrices_dyn;
+[[vk::binding(2)]] cbuffer _ {
+    uint vertex_count;
+    uint joint_count;
+    uint rest_core_offset;
+    uint rest_tangent_offset;
+    uint skin_offset;
+    uint output_core_offset;
+    uint output_tangent_offset;
+};
+
+// Per vertex: four `u16` joint indices, then four weights.
+static const uint SKIN_VERTEX_SIZE = 24;
+
+[numthreads(64, 1, 1)]
+void main(uint vertex_idx: SV_DispatchThreadID) {
+    if (vertex_idx >= vertex_count) {
+        return;
+    }
+
+    const uint skin_addr = skin_offset + vertex_idx * SKIN_VERTEX_SIZE;
+    const uint2 joints_packed = vertices_rw.Load2(skin_addr);
+    const uint4 joints = min(
+        uint4(
+            joints_packed.x & 0xffff, joints_packed.x >> 16,
+            joints_packed.y & 0xffff, joints_packed.y >> 16
+        ),
+        joint_count - 1
+    );
+
+    float4 weights = asfloat(vertices_rw.Load4(skin_addr + 8));
+    const float weight_sum = dot(weights, 1.0);
+    weights = weight_sum > 0.0 ? weights / weight_sum : float4(1, 0, 0, 0);
+
+    const float3x4 m =
+        joint_matrices_dyn[joints.x].m * weights.x
+        + joint_matrices_dyn[joints.y].m * weights.y
+        + joint_matrices_dyn[joints.z].m * weights.z
+        + joint_matrices_dyn[joints.w].m * weights.w;
+
+    VertexPacked packed;
+    packed.data0 = asfloat(vertices_rw.Load4(rest_core_offset + vertex_idx * sizeof(VertexPacked)));
+    Vertex v = unpack_vertex(packed);
+
+    v.position = mul(m, float4(v.position, 1.0));
+    // Exact for rigid and uniformly scaled joints.
+    v.normal = normalize(mul(m, float4(v.normal, 0.0)));
+
+    packed = pack_vertex(v);
+    vertices_rw.Store4(output_core_offset + vertex_idx * sizeof(VertexPacked), asuint(packed.data0));
+
+    if (rest_tangent_offset != 0) {
+        const float4 tangent = asfloat(vertices_rw.Load4(rest_tangent_offset + vertex_idx * sizeof(float4)));
+        const float3 skinned_tangent = normalize(mul(m, float4(tangent.xyz, 0.0)));
+        vertices_rw.Store4(
+            output_tangent_offset + vertex_idx * sizeof(float4),
+            asuint(float4(skinned_tangent, tangent.w))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+#include "inc/frame_constants.hlsl"
+#include "inc/gbuffer.hlsl"
+
+// One direction of the separable screen-space subsurface scattering blur.
+//
+// Each channel uses a Gaussian of the profile's radius over the world-space distance
+// between the center and the samples, including their difference in view depth,
+// so that light doesn't bleed across silhouettes and depth discontinuities.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float4> gbuffer_tex;
+[[vk::binding(2)]] Texture2D<float> depth_tex;
+// Per-channel scattering radius in world units; indexed by the G-buffer subsurface profile.
+[[vk::binding(3)]] StructuredBuffer<float4> profile_radii_dyn;
+[[vk::binding(4)]] RWTexture2D<float4> output_tex;
+[[vk::binding(5)]] cbuffer _ {
+    float4 output_tex_size;
+    int2 direction;
+    uint sample_count;
+};
+
+// How far out the kernel reaches, in standard deviations of the widest channel.
+static const float KERNEL_EXTENT_SIGMAS = 3.0;
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float4 center = input_tex[px];
+    const float depth = depth_tex[px];
+
+    const GbufferDataPacked gbuffer_packed = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px]));
+    const float strength = gbuffer_packed.unpack_subsurface();
+
+    if (0.0 == depth || 0.0 == strength) {
+        output_tex[px] = center;
+        return;
+    }
+
+    const uint profile = gbuffer_packed.unpack_subsurface_profile();
+    const float3 radius = profile_radii_dyn[profile].rgb;
+    const float max_radius = max(radius.r, max(radius.g, radius.b));
+
+    const float view_z = depth_to_view_z(depth);
+    const float dist_to_point = is_orthographic_projection() ? 1.0 : -view_z;
+    const float px_per_unit =
+        frame_constants.view_constants.view_to_clip[1][1] * 0.5 * output_tex_size.y / dist_to_point;
+
+    const float kernel_extent_px = max_radius * KERNEL_EXTENT_SIGMAS * px_per_unit;
+
+    // The scattering doesn't reach the neighbors.
+    if (kernel_extent_px < 1.0 || sample_count < 2) {
+        output_tex[px] = center;
+        return;
+    }
+
+    const int2 max_px = int2(output_tex_size.xy) - 1;
+    const float3 inv_radius_sq = 1.0 / (radius * radius);
+
+    float3 sum = 0.0;
+    float3 weight_sum = 0.0;
+
+    for (uint i = 0; i < sample_count; ++i) {
+        // -1..1
+        const float t = float(i) / float(sample_count - 1) * 2.0 - 1.0;
+        const float offset_px = t * kernel_extent_px;
+        const int2 sample_px = clamp(int2(px) + direction * int(round(offset_px)), 0, max_px);
+
+        float3 sample_color = input_tex[sample_px].rgb;
+        float sample_view_z = view_z;
+
+        const float sample_depth = depth_tex[sample_px];
+        const GbufferDataPacked sample_gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[sample_px]));
+
+        // Light only scatters within the same material; elsewhere, the center stands in.
+        if (0.0 == sample_depth
+            || 0.0 == sample_gbuffer.unpack_subsurface()
+            || sample_gbuffer.unpack_subsurface_profile() != profile
+        ) {
+            sample_color = center.rgb;
+        } else {
+            sample_view_z = depth_to_view_z(sample_depth);
+        }
+
+        const float offset_ws = offset_px / px_per_unit;
+        const float depth_diff_ws = sample_view_z - view_z;
+        const float dist_sq = offset_ws * offset_ws + depth_diff_ws * depth_diff_ws;
+
+        const float3 w = exp(-0.5 * dist_sq * inv_radius_sq);
+        sum += sample_color * w;
+        weight_sum += w;
+    }
+
+    const float3 scattered = sum / max(1e-8, weight_sum);
+    output_tex[px] = float4(lerp(center.rgb, scattered, strength), center.a);
+}
@@ -0,0 +1,43 @@
+#include "../inc/color/srgb.hlsl"
+
+// A lightweight take on AMD's Contrast Adaptive Sharpening: the negative lobe
+// shrinks where the local neighborhood already has high contrast, to avoid ringing.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 output_tex_size;
+    float sharpening;
+};
+
+[numthreads(8, 8, 1)]
+void main(int2 px: SV_DispatchThreadID) {
+    const int2 max_px = int2(output_tex_size.xy) - 1;
+
+    const float4 center = input_tex[px];
+    const float3 n = input_tex[clamp(px + int2(0, -1), 0, max_px)].rgb;
+    const float3 s = input_tex[clamp(px + int2(0, 1), 0, max_px)].rgb;
+    const float3 w = input_tex[clamp(px + int2(-1, 0), 0, max_px)].rgb;
+    const float3 e = input_tex[clamp(px + int2(1, 0), 0, max_px)].rgb;
+
+    // Work on a compressed version of the HDR input, so that highlights don't dominate.
+    const float c_lum = sRGB_to_luminance(center.rgb);
+    const float l_c = c_lum / (1.0 + c_lum);
+    float l_n = sRGB_to_luminance(n); l_n /= 1.0 + l_n;
+    float l_s = sRGB_to_luminance(s); l_s /= 1.0 + l_s;
+    float l_w = sRGB_to_luminance(w); l_w /= 1.0 + l_w;
+    float l_e = sRGB_to_luminance(e); l_e /= 1.0 + l_e;
+
+    const float min_l = min(l_c, min(min(l_n, l_s), min(l_w, l_e)));
+    const float max_l = max(l_c, max(max(l_n, l_s), max(l_w, l_e)));
+
+    // Headroom to the nearest end of the range determines how much sharpening is safe.
+    const float amp = sqrt(saturate(min(min_l, 1.0 - max_l) / max(1e-5, max_l)));
+
+    // Negative lobe weight, between -1/8 and -1/5 as in CAS.
+    const float peak = -1.0 / lerp(8.0, 5.0, saturate(sharpening));
+    const float lobe = amp * peak;
+
+    const float3 result = (center.rgb + (n + s + w + e) * lobe) / (1.0 + 4.0 * lobe);
+    output_tex[px] = float4(max(0.0, result), center.a);
+}
@@ -15,13 +15,18 @@
 [[vk::binding(5)]] Texture2D<float> depth_tex;
 [[vk::binding(6)]] Texture2D<float3> smooth_var_history_tex;
 [[vk::binding(7)]] Texture2D<float> input_prob_tex;
-[[vk::binding(8)]] RWTexture2D<float4> temporal_output_tex;
-[[vk::binding(9)]] RWTexture2D<float4> output_tex;
-[[vk::binding(10)]] RWTexture2D<float3> smooth_var_output_tex;
-[[vk::binding(11)]] RWTexture2D<float2> velocity_output_tex;
-[[vk::binding(12)]] cbuffer _ {
+// Pixels which should not accumulate history, such as ones covered by particles
+[[vk::binding(8)]] Texture2D<float> responsive_mask_tex;
+// Disoccluded pixels, which should only keep a little history; see `reactive_mask.hlsl`
+[[vk::binding(9)]] Texture2D<float> reactive_mask_tex;
+[[vk::binding(10)]] RWTexture2D<float4> temporal_output_tex;
+[[vk::binding(11)]] RWTexture2D<float4> output_tex;
+[[vk::binding(12)]] RWTexture2D<float3> smooth_var_output_tex;
+[[vk::binding(13)]] RWTexture2D<float2> velocity_output_tex;
+[[vk::binding(14)]] cbuffer _ {
     float4 input_tex_size;
     float4 output_tex_size;
+    float clamp_box_scale;
 };
 
 // Apply at spatial kernel to the current frame, "un-jittering" it.
@@ -198,6 +203,8 @@ void main(uint2 px: SV_DispatchThreadID) {
             box_n_deviations = lerp(box_n_deviations, 3, input_prob);
         }
 
+        box_n_deviations *= clamp_box_scale;
+
     	float3 nmin = ex - input_dev * box_n_deviations;
     	float3 nmax = ex + input_dev * box_n_deviations;
 
@@ -307,6 +314,11 @@ void main(uint2 px: SV_DispatchThreadID) {
         history_coverage = 0;
     #endif
 
+        history_coverage *= 1.0 - responsive_mask_tex[reproj_px];
+
+        // Down to about as much as the current frame; the neighborhood clamp takes care of the rest.
+        history_coverage = min(history_coverage, lerp(history_coverage, coverage, reactive_mask_tex[reproj_px]));
+
         float total_coverage = max(1e-5, history_coverage + coverage);
         float3 temporal_result = (clamped_history * history_coverage + center) / total_coverage;
 
@@ -0,0 +1,32 @@
+#include "inc/samplers.hlsl"
+#include "inc/frame_constants.hlsl"
+#include "inc/math.hlsl"
+#include "inc/uv.hlsl"
+#include "inc/atmosphere.hlsl"
+#include "inc/sun.hlsl"
+
+// The background of secondary world views, which are shaded forward, and thus don't go
+// through `light_gbuffer`. Matches what it outputs where there's no geometry.
+
+[[vk::binding(0)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(1)]] RWTexture2D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    float4 output_tex_size;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = get_uv(px, output_tex_size);
+    const float3 dir = ViewRayContext::from_uv(uv).ray_dir_ws();
+
+    const float real_sun_angular_radius = 0.53 * 0.5 * M_PI / 180.0;
+    const float sun_angular_radius_cos = min(cos(real_sun_angular_radius), frame_constants.sun_angular_radius_cos);
+    const float sun_radius_ratio = real_sun_angular_radius / acos(sun_angular_radius_cos);
+
+    float3 output = sky_cube_tex.SampleLevel(sampler_llr, dir, 0).rgb;
+    if (dot(dir, SUN_DIRECTION) > sun_angular_radius_cos) {
+        output += 800 * sun_color_in_direction(dir) * sun_radius_ratio * sun_radius_ratio;
+    }
+
+    output_tex[px] = float4(output, 1);
+}
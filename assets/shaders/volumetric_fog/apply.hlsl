@@ -0,0 +1,31 @@
+#include "../inc/samplers.hlsl"
+#include "fog_common.hlsl"
+
+// Attenuates the lit scene by the fog in front of it, and adds the light scattered towards the eye.
+
+[[vk::binding(0)]] Texture3D<float4> integrated_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] RWTexture2D<float4> output_tex;
+[[vk::binding(3)]] cbuffer _ {
+    float4 output_tex_size;
+    FogConstants fog;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = get_uv(px, output_tex_size);
+    const float depth = depth_tex[px];
+
+    // The sky is fogged up to the far end of the volume.
+    const float view_depth = 0.0 == depth ? fog.max_distance : -depth_to_view_z(depth);
+
+    // Froxels store values at their far ends, so the first one is faded in from clear air.
+    const float slice = fog.depth_to_w(view_depth) * fog.froxel_dims.z;
+    const float w = (max(1.0, slice) - 0.5) / fog.froxel_dims.z;
+
+    float4 fog_value = integrated_tex.SampleLevel(sampler_llc, float3(uv, w), 0);
+    fog_value = lerp(float4(0, 0, 0, 1), fog_value, saturate(slice));
+
+    const float4 color = output_tex[px];
+    output_tex[px] = float4(color.rgb * fog_value.a + fog_value.rgb, color.a);
+}
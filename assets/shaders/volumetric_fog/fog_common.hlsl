@@ -0,0 +1,57 @@
+#ifndef VOLUMETRIC_FOG_COMMON_HLSL
+#define VOLUMETRIC_FOG_COMMON_HLSL
+
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/math_const.hlsl"
+
+// Must match `GpuFogConstants` in `volumetric_fog.rs`
+struct FogConstants {
+    float3 albedo;
+    float anisotropy;
+
+    float density;
+    float height_falloff;
+    float base_height;
+    float max_distance;
+
+    uint3 froxel_dims;
+    float near_distance;
+
+    // Extinction coefficient at `pos_ws`
+    float extinction_at(float3 pos_ws) {
+        return density * exp(-max(0.0, pos_ws.y - base_height) * height_falloff);
+    }
+
+    // Froxel slices are distributed exponentially in view-space depth,
+    // with `w` going from 0 at `near_distance` to 1 at `max_distance`.
+    float w_to_depth(float w) {
+        return near_distance * pow(max_distance / near_distance, w);
+    }
+
+    float depth_to_w(float depth) {
+        return log(max(depth, 1e-5) / near_distance) / log(max_distance / near_distance);
+    }
+
+    float3 uvw_to_ws(float3 uvw) {
+        const float3 ray_dir_vs = ViewRayContext::from_uv(uvw.xy).ray_dir_vs();
+        const float3 pos_vs = ray_dir_vs * (w_to_depth(uvw.z) / -ray_dir_vs.z);
+        return position_view_to_world(pos_vs);
+    }
+
+    // Where `pos_ws` was in the froxel volume of the previous frame.
+    float3 ws_to_prev_uvw(float3 pos_ws) {
+        const float3 pos_pvs = mul(frame_constants.view_constants.prev_world_to_prev_view, float4(pos_ws, 1)).xyz;
+        const float4 pos_pcs = mul(frame_constants.view_constants.prev_view_to_prev_clip, float4(pos_pvs, 1));
+        return float3(cs_to_uv(pos_pcs.xy / pos_pcs.w), depth_to_w(-pos_pvs.z));
+    }
+};
+
+// Henyey-Greenstein, with `cos_theta` between the directions towards the light and towards the eye reversed;
+// that is, between the direction of propagation of light and the view ray.
+float fog_phase_hg(float cos_theta, float g) {
+    const float g2 = g * g;
+    return (1.0 - g2) / (4.0 * M_PI * pow(max(1e-5, 1.0 + g2 - 2.0 * g * cos_theta), 1.5));
+}
+
+#endif  // VOLUMETRIC_FOG_COMMON_HLSL
@@ -0,0 +1,121 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/math.hlsl"
+#include "../inc/samplers.hlsl"
+#include "../inc/rt.hlsl"
+#include "../inc/blue_noise.hlsl"
+#include "../inc/atmosphere.hlsl"
+#include "../inc/sun.hlsl"
+#include "../inc/brdf.hlsl"
+#include "../inc/brdf_lut.hlsl"
+#include "../inc/layered_brdf.hlsl"
+#include "../inc/lights/punctual.hlsl"
+#include "../inc/lights/rect.hlsl"
+#include "../inc/lights/clusters.hlsl"
+#include "fog_common.hlsl"
+
+// Injects the fog's media into the froxel volume, and lights it.
+//
+// Each froxel traces a visibility ray towards the sun, one towards the sky, and one towards
+// a random local light; a rect light, or a punctual one from the froxel's light cluster. The results are reprojected and blended with the previous frame's,
+// which also resolves the jittering of the sample positions within the froxels.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+[[vk::binding(0)]] Texture3D<float4> prev_scattering_tex;
+[[vk::binding(1)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(2)]] RWTexture3D<float4> output_tex;
+[[vk::binding(3)]] StructuredBuffer<uint> light_cluster_lists_buf;
+[[vk::binding(4)]] cbuffer _ {
+    FogConstants fog;
+    LightClusterConstants light_clusters;
+};
+
+static const float HISTORY_WEIGHT = 0.9;
+
+bool is_visible(float3 origin, float3 dir, float max_t) {
+    return !rt_is_shadowed(acceleration_structure, new_ray(origin, dir, 1e-3, max_t));
+}
+
+[shader("raygeneration")]
+void main() {
+    const uint3 px = DispatchRaysIndex().xyz;
+
+    const float4 urand = blue_noise_for_pixel(px.xy + px.z * uint2(23, 59), frame_constants.frame_index);
+    const float3 uvw = (px + float3(0.5, 0.5, urand.w)) / float3(fog.froxel_dims);
+    const float3 pos_ws = fog.uvw_to_ws(uvw);
+
+    const float extinction = fog.extinction_at(pos_ws);
+    if (extinction <= 0.0) {
+        output_tex[px] = 0.0;
+        return;
+    }
+
+    // From the eye towards the froxel
+    const float3 view_dir = normalize(pos_ws - get_eye_position());
+
+    float3 radiance = 0.0;
+
+    {
+        const float3 sun_dir = sample_sun_direction(urand.xy, true);
+        if (is_visible(pos_ws, sun_dir, FLT_MAX)) {
+            radiance += SUN_COLOR * fog_phase_hg(dot(SUN_DIRECTION, view_dir), fog.anisotropy);
+        }
+    }
+
+    {
+        // Isotropic approximation of the sky's contribution over the upper hemisphere
+        const float3 sky_dir = mul(build_orthonormal_basis(float3(0, 1, 0)), uniform_sample_hemisphere(urand.yz));
+        if (is_visible(pos_ws, sky_dir, FLT_MAX)) {
+            radiance += sky_cube_tex.SampleLevel(sampler_llr, float3(0, 1, 0), 0).rgb * 0.5;
+        }
+    }
+
+    const uint cluster_offset =
+        light_clusters.cluster_index(uvw.xy, fog.w_to_depth(uvw.z)) * LIGHT_CLUSTER_STRIDE;
+    const uint cluster_light_count = light_cluster_lists_buf[cluster_offset];
+
+    const uint local_light_count = cluster_light_count + frame_constants.rect_light_count;
+    if (local_light_count > 0) {
+        const uint light_idx = min(uint(urand.z * local_light_count), local_light_count - 1);
+
+        if (light_idx < cluster_light_count) {
+            const PunctualLight light = PunctualLight::from_packed(
+                punctual_lights_dyn[light_cluster_lists_buf[cluster_offset + 1 + light_idx]]);
+            const PunctualLightSample light_sample = light.sample_with_radius(pos_ws, urand.xy);
+
+            if (any(light_sample.radiance > 0.0) && is_visible(pos_ws, light_sample.wi, light_sample.distance * 0.999)) {
+                radiance +=
+                    light_sample.radiance
+                    * frame_constants.pre_exposure
+                    * fog_phase_hg(dot(light_sample.wi, view_dir), fog.anisotropy)
+                    * local_light_count;
+            }
+        } else {
+            const RectLight light = RectLight::from_packed(rect_lights_dyn[light_idx - cluster_light_count]);
+            const float3 to_light = light.sample_point(urand.xy) - pos_ws;
+            const float dist2 = max(1e-5, dot(to_light, to_light));
+            const float3 wi = to_light * rsqrt(dist2);
+            const float emission_cos = light.emission_cos(-wi);
+
+            if (emission_cos > 0.0 && is_visible(pos_ws, wi, sqrt(dist2) * 0.999)) {
+                radiance +=
+                    light.radiance
+                    * frame_constants.pre_exposure
+                    * emission_cos * light.area() / dist2
+                    * fog_phase_hg(dot(wi, view_dir), fog.anisotropy)
+                    * local_light_count;
+            }
+        }
+    }
+
+    // Scattering in RGB, extinction in alpha
+    float4 result = float4(radiance * fog.albedo * extinction, extinction);
+
+    const float3 prev_uvw = fog.ws_to_prev_uvw(pos_ws);
+    if (frame_constants.frame_index != 0 && all(prev_uvw == saturate(prev_uvw))) {
+        float4 history = prev_scattering_tex.SampleLevel(sampler_lnc, prev_uvw, 0);
+        history.rgb *= frame_constants.pre_exposure_delta;
+        result = lerp(result, history, HISTORY_WEIGHT);
+    }
+
+    output_tex[px] = result;
+}
@@ -0,0 +1,39 @@
+#include "fog_common.hlsl"
+
+// Accumulates the in-scattered light and transmittance front-to-back along each froxel column,
+// integrating analytically within the froxels, as in "Physically Based and Unified Volumetric Rendering
+// in Frostbite" by Sébastien Hillaire. Each output froxel holds the values at its far end.
+
+[[vk::binding(0)]] Texture3D<float4> scattering_tex;
+[[vk::binding(1)]] RWTexture3D<float4> output_tex;
+[[vk::binding(2)]] cbuffer _ {
+    FogConstants fog;
+};
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    const float2 uv = (px + 0.5) / float2(fog.froxel_dims.xy);
+
+    // Distance along the ray per unit of view-space depth
+    const float3 ray_dir_vs = ViewRayContext::from_uv(uv).ray_dir_vs();
+    const float ray_length_per_depth = 1.0 / -ray_dir_vs.z;
+
+    float3 in_scattering = 0.0;
+    float transmittance = 1.0;
+    float prev_depth = 0.0;
+
+    for (uint z = 0; z < fog.froxel_dims.z; ++z) {
+        const float depth = fog.w_to_depth(float(z + 1) / fog.froxel_dims.z);
+        const float step_length = (depth - prev_depth) * ray_length_per_depth;
+        prev_depth = depth;
+
+        const float4 scattering_extinction = scattering_tex[uint3(px, z)];
+        const float extinction = max(1e-7, scattering_extinction.a);
+        const float step_transmittance = exp(-extinction * step_length);
+
+        in_scattering += transmittance * scattering_extinction.rgb * (1.0 - step_transmittance) / extinction;
+        transmittance *= step_transmittance;
+
+        output_tex[uint3(px, z)] = float4(in_scattering, transmittance);
+    }
+}
@@ -0,0 +1,30 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/samplers.hlsl"
+#include "../inc/sun.hlsl"
+#include "../inc/math_const.hlsl"
+#include "water_common.hlsl"
+
+// Without ray tracing; reflects the sky where there's no planar reflection.
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> planar_reflection_tex;
+[[vk::binding(3)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(4)]] TextureCube<float4> prefiltered_sky_cube_tex;
+[[vk::binding(5)]] RWTexture2D<float4> output_tex;
+[[vk::binding(6)]] RWTexture2D<float> responsive_mask_tex;
+[[vk::binding(7)]] cbuffer _ {
+    WaterConstants water;
+};
+
+float3 water_reflection(float3 origin, float3 dir, uint2 px) {
+    return water_sky_reflection(dir);
+}
+
+#include "water_shade.inc.hlsl"
+
+[numthreads(8, 8, 1)]
+void main(uint2 px: SV_DispatchThreadID) {
+    water_main(px);
+}
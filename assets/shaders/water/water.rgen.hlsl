@@ -0,0 +1,70 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/uv.hlsl"
+#include "../inc/samplers.hlsl"
+#include "../inc/sun.hlsl"
+#include "../inc/math_const.hlsl"
+#include "../inc/hash.hlsl"
+#include "../inc/rt.hlsl"
+#include "../ircache/bindings.hlsl"
+#include "water_common.hlsl"
+
+// Traces the reflections where there's no planar reflection, and lights their hits
+// with the sun and the irradiance cache.
+
+[[vk::binding(0, 3)]] RaytracingAccelerationStructure acceleration_structure;
+
+[[vk::binding(0)]] Texture2D<float4> input_tex;
+[[vk::binding(1)]] Texture2D<float> depth_tex;
+[[vk::binding(2)]] Texture2D<float4> planar_reflection_tex;
+[[vk::binding(3)]] TextureCube<float4> sky_cube_tex;
+[[vk::binding(4)]] TextureCube<float4> prefiltered_sky_cube_tex;
+[[vk::binding(5)]] RWTexture2D<float4> output_tex;
+[[vk::binding(6)]] RWTexture2D<float> responsive_mask_tex;
+DEFINE_IRCACHE_BINDINGS(7, 8, 9, 10, 11, 12, 13, 14, 15)
+[[vk::binding(16)]] cbuffer _ {
+    WaterConstants water;
+};
+
+#include "../ircache/lookup.hlsl"
+
+static const float SKY_DIST = 1e4;
+
+float3 water_reflection(float3 origin, float3 dir, uint2 px) {
+    const RayCone ray_cone =
+        pixel_ray_cone_from_image_height(water.output_tex_size.y)
+        .propagate(sqrt(water.roughness) * 0.05, length(origin - get_eye_position()));
+
+    const GbufferPathVertex hit = GbufferRaytrace::with_ray(new_ray(origin, dir, 1e-4, SKY_DIST))
+        .with_instance_mask(RT_INSTANCE_MASK_REFLECTION)
+        .with_cone(ray_cone)
+        .with_cull_back_faces(false)
+        .with_path_length(1)
+        .trace(acceleration_structure);
+
+    if (!hit.is_hit) {
+        return water_sky_reflection(dir);
+    }
+
+    const GbufferData gbuffer = hit.gbuffer_packed.unpack();
+    uint rng = hash3(uint3(px, frame_constants.frame_index));
+
+    const bool is_shadowed = rt_is_shadowed(
+        acceleration_structure,
+        new_ray(hit.position, SUN_DIRECTION, 1e-4, SKY_DIST));
+    const float3 sun_irradiance = is_shadowed
+        ? 0.0.xxx
+        : SUN_COLOR * max(0.0, dot(gbuffer.normal, SUN_DIRECTION));
+
+    const float3 gi = IrcacheLookupParams::create(origin, hit.position, gbuffer.normal)
+        .with_query_rank(1)
+        .lookup(rng);
+
+    return gbuffer.emissive + gbuffer.albedo * (sun_irradiance / M_PI + gi);
+}
+
+#include "water_shade.inc.hlsl"
+
+[shader("raygeneration")]
+void main() {
+    water_main(DispatchRaysIndex().xy);
+}
@@ -0,0 +1,93 @@
+#ifndef WATER_COMMON_HLSL
+#define WATER_COMMON_HLSL
+
+// Must match `water.rs`
+#define WATER_WAVE_COUNT 4
+
+// Uniform steps through the slab spanned by the waves, before refining the hit.
+static const uint WATER_MARCH_STEPS = 24;
+
+// Must match `GpuWaterConstants` in `water.rs`
+struct WaterConstants {
+    float4 output_tex_size;
+
+    float3 center;
+    float max_amplitude;
+
+    float2 half_extent;
+    float roughness;
+    float refraction_strength;
+
+    float4 absorption;
+    float4 scatter_color;
+
+    // xy: wave vector in the XZ plane; z: amplitude; w: angular frequency
+    float4 waves[WATER_WAVE_COUNT];
+
+    float time;
+    uint has_planar_reflection;
+    float planar_reflection_distortion;
+    uint pad;
+
+    // Above the mean level
+    float height(float2 xz) {
+        float res = 0.0;
+        for (uint i = 0; i < WATER_WAVE_COUNT; ++i) {
+            const float4 wave = waves[i];
+            res += wave.z * sin(dot(wave.xy, xz - center.xz) - wave.w * time);
+        }
+        return res;
+    }
+
+    float3 normal(float2 xz) {
+        float2 slope = 0.0;
+        for (uint i = 0; i < WATER_WAVE_COUNT; ++i) {
+            const float4 wave = waves[i];
+            slope += wave.xy * wave.z * cos(dot(wave.xy, xz - center.xz) - wave.w * time);
+        }
+        return normalize(float3(-slope.x, 1.0, -slope.y));
+    }
+
+    bool contains(float2 xz) {
+        return all(abs(xz - center.xz) <= half_extent);
+    }
+
+    // Distance along the ray to where it first goes under the surface, before `max_t`,
+    // or -1 if it doesn't. Rays starting under the surface miss it.
+    float intersect(float3 origin, float3 dir, float max_t) {
+        if (dir.y > -1e-5) {
+            return -1.0;
+        }
+
+        const float t0 = max(0.0, (center.y + max_amplitude - origin.y) / dir.y);
+        const float t1 = min(max_t, (center.y - max_amplitude - origin.y) / dir.y);
+        if (t0 > t1) {
+            return -1.0;
+        }
+
+        float prev_t = t0;
+        float prev_above = origin.y + dir.y * t0 - center.y - height(origin.xz + dir.xz * t0);
+        if (prev_above < 0.0) {
+            return -1.0;
+        }
+
+        for (uint step = 1; step <= WATER_MARCH_STEPS; ++step) {
+            const float t = lerp(t0, t1, float(step) / WATER_MARCH_STEPS);
+            const float3 pos = origin + dir * t;
+            const float above = pos.y - center.y - height(pos.xz);
+
+            if (above <= 0.0) {
+                // Linear approximation of the surface between the last two steps
+                const float hit_t = lerp(prev_t, t, prev_above / max(1e-10, prev_above - above));
+                return contains((origin + dir * hit_t).xz) ? hit_t : -1.0;
+            }
+
+            prev_t = t;
+            prev_above = above;
+        }
+
+        return -1.0;
+    }
+};
+
+#endif
@@ -0,0 +1,99 @@
+// Draws a water surface over the lit scene.
+//
+// Expects the following to be declared by the includer:
+// * `WaterConstants water`
+// * `Texture2D<float4> input_tex`, the lit scene
+// * `Texture2D<float> depth_tex`
+// * `Texture2D<float4> planar_reflection_tex`
+// * `TextureCube<float4> sky_cube_tex`, convolved
+// * `TextureCube<float4> prefiltered_sky_cube_tex`
+// * `RWTexture2D<float4> output_tex`
+// * `RWTexture2D<float> responsive_mask_tex`
+// * `float3 water_reflection(float3 origin, float3 dir, uint2 px)`, used without a planar reflection
+
+// Stands in for the distance through the water to the sky.
+static const float WATER_FAR_T = 1e4;
+
+// Schlick's reflectance of water at normal incidence
+static const float WATER_F0 = 0.02;
+
+float3 water_sky_reflection(float3 dir) {
+    uint cube_width, cube_height, cube_levels;
+    prefiltered_sky_cube_tex.GetDimensions(0, cube_width, cube_height, cube_levels);
+
+    return prefiltered_sky_cube_tex.SampleLevel(
+        sampler_llr,
+        dir,
+        sqrt(water.roughness) * (cube_levels - 1)
+    ).rgb;
+}
+
+void water_main(uint2 px) {
+    const float4 input = input_tex[px];
+    const float depth = depth_tex[px];
+    const float2 uv = get_uv(px, water.output_tex_size);
+
+    const ViewRayContext view_ray_context = ViewRayContext::from_uv_and_depth(uv, depth);
+    const float3 origin = view_ray_context.ray_origin_ws();
+    const float3 dir = view_ray_context.ray_dir_ws();
+    const float scene_t = 0.0 == depth ? WATER_FAR_T : length(view_ray_context.ray_hit_ws() - origin);
+
+    const float water_t = water.intersect(origin, dir, scene_t);
+    if (water_t < 0.0) {
+        output_tex[px] = input;
+        return;
+    }
+
+    const float3 pos_ws = origin + dir * water_t;
+    const float3 normal_ws = water.normal(pos_ws.xz);
+
+    // How the waves tilt the surface, on screen.
+    const float3 tilt_vs = direction_world_to_view(normal_ws - float3(0.0, 1.0, 0.0));
+    const float2 tilt_uv = float2(tilt_vs.x, -tilt_vs.y);
+
+    // Shifted samples of the scene in front of the surface would show through it,
+    // so those keep to the scene right under the pixel.
+    float2 refracted_uv = uv + tilt_uv * water.refraction_strength;
+    float refracted_depth = depth_tex.SampleLevel(sampler_nnc, refracted_uv, 0);
+    ViewRayContext refracted_ray = ViewRayContext::from_uv_and_depth(refracted_uv, refracted_depth);
+
+    if (0.0 != refracted_depth
+        && length(refracted_ray.ray_hit_ws() - refracted_ray.ray_origin_ws()) < water_t) {
+        refracted_uv = uv;
+        refracted_depth = depth;
+        refracted_ray = view_ray_context;
+    }
+
+    const float3 refracted = input_tex.SampleLevel(sampler_lnc, refracted_uv, 0).rgb;
+
+    // Beer-Lambert, through the water between the surface and what's under it
+    const float thickness = 0.0 == refracted_depth
+        ? WATER_FAR_T
+        : length(refracted_ray.ray_hit_ws() - pos_ws);
+    const float3 transmittance = exp(-water.absorption.rgb * thickness);
+
+    const float3 ambient =
+        sky_cube_tex.SampleLevel(sampler_llr, float3(0.0, 1.0, 0.0), 0).rgb
+        + SUN_COLOR * saturate(SUN_DIRECTION.y) / M_PI;
+    const float3 under_water = lerp(water.scatter_color.rgb * ambient, refracted, transmittance);
+
+    float3 reflected;
+    if (water.has_planar_reflection) {
+        // The planar reflection's view is mirrored horizontally; see `mirrored_camera_matrices`.
+        float2 reflection_uv = uv + tilt_uv * water.planar_reflection_distortion;
+        reflection_uv.x = 1.0 - reflection_uv.x;
+        reflected = planar_reflection_tex.SampleLevel(sampler_llc, reflection_uv, 0).rgb;
+    } else {
+        // Steep waves can reflect downwards, into the water; bounce those back up.
+        float3 reflected_dir = reflect(dir, normal_ws);
+        reflected_dir.y = abs(reflected_dir.y);
+        reflected = water_reflection(pos_ws, reflected_dir, px);
+    }
+
+    const float fresnel = WATER_F0 + (1.0 - WATER_F0) * pow(1.0 - saturate(dot(normal_ws, -dir)), 5.0);
+
+    output_tex[px] = float4(lerp(under_water, reflected, fresnel), input.a);
+
+    // The waves move without motion vectors.
+    responsive_mask_tex[px] = 1.0;
+}
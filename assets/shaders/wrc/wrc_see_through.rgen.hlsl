@@ -12,6 +12,7 @@
 #include "../inc/atmosphere.hlsl"
 #include "../inc/sun.hlsl"
 #include "../inc/lights/triangle.hlsl"
+#include "../inc/lights/triangle_selection.hlsl"
 
 #include "bindings.hlsl"
 #include "../ircache/bindings.hlsl"
@@ -130,8 +131,9 @@ void main() {
                 uint_to_u01_float(hash1_mut(rng))
             );
 
-            for (uint light_idx = 0; light_idx < frame_constants.triangle_light_count; light_idx += 1) {
-                TriangleLight triangle_light = TriangleLight::from_packed(triangle_lights_dyn[light_idx]);
+            if (frame_constants.triangle_light_count > 0) {
+                const TriangleLightSelection light_selection = select_triangle_light(uint_to_u01_float(hash1_mut(rng)));
+                TriangleLight triangle_light = light_selection.light;
                 LightSampleResultArea light_sample = sample_triangle_light(triangle_light.as_triangle(), urand);
                 const float3 shadow_ray_origin = primary_hit.position;
                 const float3 to_light_ws = light_sample.pos - shadow_ray_origin;
@@ -163,7 +165,7 @@ void main() {
                     #endif
 
                     total_radiance +=
-                        !is_shadowed ? (triangle_light.radiance() * brdf_value / light_sample.pdf.value) : 0;
+                        !is_shadowed ? (triangle_light.radiance() * brdf_value / (light_sample.pdf.value * light_selection.pmf)) : 0;
                 }
             }
 
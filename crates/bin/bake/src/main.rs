@@ -1,4 +1,5 @@
 use anyhow::Result;
+use kajiya_asset::mesh::VertexQuantization;
 use kajiya_asset_pipe::*;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -14,6 +15,14 @@ struct Opt {
 
     #[structopt(short = "o")]
     output_name: String,
+
+    /// Stores positions and normals in 8 bytes per vertex rather than 16.
+    #[structopt(long)]
+    quantize_positions: bool,
+
+    /// Stores UVs in 4 bytes per vertex rather than 8, unless they span a wide range.
+    #[structopt(long)]
+    quantize_uvs: bool,
 }
 
 fn main() -> Result<()> {
@@ -25,5 +34,9 @@ fn main() -> Result<()> {
         path: opt.scene,
         output_name: opt.output_name,
         scale: opt.scale,
+        quantization: VertexQuantization {
+            positions: opt.quantize_positions,
+            uvs: opt.quantize_uvs,
+        },
     })
 }
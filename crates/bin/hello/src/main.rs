@@ -1,45 +1,54 @@
 use kajiya::world_renderer::AddMeshOptions;
-use kajiya_simple::*;
+use kajiya_simple::{scene::SceneDesc, *};
 
 fn main() -> anyhow::Result<()> {
-    let mut kajiya = SimpleMainLoop::builder().resolution([1920, 1080]).build(
+    let config = RendererConfig::from_args();
+
+    let mut kajiya = config.main_loop_builder().build(
         WindowBuilder::new()
             .with_title("hello-kajiya")
-            .with_resizable(false),
+            .with_resizable(false)
+            .with_decorations(config.window_decorations),
     )?;
 
-    let camera = (
-        Vec3::new(0.0, 1.0, 2.5),
-        Quat::from_rotation_x(-18.0f32.to_radians()),
-    );
+    let scene_path = match config.scene {
+        Some(scene) => scene,
+        None => canonical_path_from_vfs("/kajiya/assets/scenes/hello.ron")?,
+    };
+    let scene = SceneDesc::load(scene_path)?;
+    let loaded_scene = scene.instantiate(&mut kajiya.world_renderer, |world_renderer, mesh| {
+        world_renderer.add_baked_mesh(mesh, AddMeshOptions::new())
+    })?;
+
+    let camera = scene
+        .cameras
+        .first()
+        .map_or((Vec3::ZERO, Quat::IDENTITY), |camera| {
+            (camera.position(), camera.rotation())
+        });
 
     let lens = CameraLens {
         aspect_ratio: kajiya.window_aspect_ratio(),
         ..Default::default()
     };
 
-    let car_mesh = kajiya
-        .world_renderer
-        .add_baked_mesh("/cache/336_lrm.mesh", AddMeshOptions::new())?;
-
-    let car_inst = kajiya.world_renderer.add_instance(
-        car_mesh,
-        Affine3A::from_rotation_translation(Quat::IDENTITY, Vec3::ZERO),
-    );
+    let sun_direction = scene.sun.as_ref().map_or(Vec3::Y, |sun| sun.towards_sun());
 
+    let car_inst = loaded_scene.instances[0];
+    let car_transform = scene.instances[0].affine_transform();
     let mut car_rot = 0.0f32;
 
     kajiya.run(move |ctx| {
         car_rot += 0.5 * ctx.dt_filtered;
-        ctx.world_renderer.set_instance_transform(
-            car_inst,
-            Affine3A::from_rotation_translation(Quat::from_rotation_y(car_rot), Vec3::ZERO),
-        );
+        ctx.world_renderer
+            .set_instance_transform(car_inst, Affine3A::from_rotation_y(car_rot) * car_transform);
+
+        kajiya::debug_text!(8.0, 8.0, "{:.2} ms", ctx.dt_filtered * 1000.0);
 
         WorldFrameDesc {
             camera_matrices: camera.through(&lens),
             render_extent: ctx.render_extent,
-            sun_direction: Vec3::new(4.0, 1.0, 1.0).normalize(),
+            sun_direction,
         }
     })
 }
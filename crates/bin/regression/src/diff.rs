@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use crate::suite::DiffThreshold;
+
+/// Linear HDR image, as fed to the tonemapper.
+pub struct HdrImage {
+    pub extent: [u32; 2],
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl HdrImage {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        use exr::prelude::*;
+
+        let image = read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| (resolution.width(), vec![[0.0f32; 3]; resolution.area()]),
+            |(width, pixels): &mut (usize, Vec<[f32; 3]>),
+             pos: Vec2<usize>,
+             (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[pos.y() * *width + pos.x()] = [r, g, b];
+            },
+        )?;
+
+        let size = image.layer_data.size;
+        let (_, pixels) = image.layer_data.channel_data.pixels;
+
+        Ok(Self {
+            extent: [size.width() as u32, size.height() as u32],
+            pixels,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let width = self.extent[0] as usize;
+        exr::prelude::write_rgb_file(path, width, self.extent[1] as usize, |x, y| {
+            let [r, g, b] = self.pixels[y * width + x];
+            (r, g, b)
+        })?;
+
+        Ok(())
+    }
+}
+
+pub struct DiffResult {
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32,
+    pub differing_pixels: f32,
+    pub passed: bool,
+
+    /// Per-pixel CIELAB distance, in all three channels.
+    pub diff_image: HdrImage,
+}
+
+/// Compares the images as they'd be displayed, after a simple tonemap,
+/// using the CIE76 color difference.
+pub fn compare(reference: &HdrImage, current: &HdrImage, threshold: DiffThreshold) -> DiffResult {
+    assert_eq!(reference.extent, current.extent);
+
+    let delta_e: Vec<f32> = reference
+        .pixels
+        .iter()
+        .zip(&current.pixels)
+        .map(|(a, b)| {
+            let a = display_lab(*a);
+            let b = display_lab(*b);
+            ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+        })
+        .collect();
+
+    let pixel_count = delta_e.len().max(1) as f32;
+    let mean_delta_e = delta_e.iter().sum::<f32>() / pixel_count;
+    let max_delta_e = delta_e.iter().copied().fold(0.0, f32::max);
+    let differing_pixels = delta_e
+        .iter()
+        .filter(|&&d| d > threshold.pixel_delta_e)
+        .count() as f32
+        / pixel_count;
+
+    DiffResult {
+        mean_delta_e,
+        max_delta_e,
+        differing_pixels,
+        passed: differing_pixels <= threshold.max_differing_pixels,
+        diff_image: HdrImage {
+            extent: reference.extent,
+            pixels: delta_e.into_iter().map(|d| [d, d, d]).collect(),
+        },
+    }
+}
+
+/// Reinhard-tonemapped linear sRGB to CIELAB, with a D65 white point.
+fn display_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c.max(0.0);
+        if c.is_finite() {
+            c / (1.0 + c)
+        } else {
+            1.0
+        }
+    });
+
+    let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+
+    fn f(t: f32) -> f32 {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    }
+
+    let fx = f(x / 0.9505);
+    let fy = f(y);
+    let fz = f(z / 1.089);
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
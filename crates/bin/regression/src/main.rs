@@ -0,0 +1,293 @@
+mod diff;
+mod suite;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context as _;
+use diff::*;
+use kajiya::{
+    renderers::hdr_capture::{HdrCapture, HdrCaptureSource},
+    world_renderer::AddMeshOptions,
+};
+use kajiya_simple::{scene::SceneDesc, *};
+use suite::*;
+
+/// Exit code of a single test run whose image doesn't match the reference.
+/// Other errors exit with `1`.
+const EXIT_CODE_MISMATCH: i32 = 2;
+
+/// How long to wait for the capture to be read back before giving up.
+const CAPTURE_TIMEOUT_FRAMES: u32 = 16;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "regression",
+    about = "Renders the scenes of a test suite, and compares them against reference images."
+)]
+struct Opt {
+    #[structopt(long, default_value = "assets/regression/suite.ron")]
+    suite: PathBuf,
+
+    /// Directory of the reference images, named after the tests.
+    #[structopt(long, default_value = "regression/references")]
+    references: PathBuf,
+
+    /// Directory to save the images and difference maps of failed tests to.
+    #[structopt(long, default_value = "regression/output")]
+    output: PathBuf,
+
+    /// Saves the rendered images as the new references instead of comparing.
+    #[structopt(long)]
+    update_references: bool,
+
+    /// Only runs the tests whose names contain this string.
+    #[structopt(long)]
+    filter: Option<String>,
+
+    #[structopt(long)]
+    physical_device_index: Option<usize>,
+
+    #[structopt(long)]
+    graphics_debugging: bool,
+
+    /// Runs one test in this process. The harness starts a process per test,
+    /// so that no state carries over between them, and crashes are contained.
+    #[structopt(long, hidden = true)]
+    run_single: Option<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::from_args();
+    let suite = TestSuite::load(&opt.suite)?;
+
+    if let Some(name) = &opt.run_single {
+        return run_single(&opt, &suite, suite.test(name)?);
+    }
+
+    let exe = std::env::current_exe()?;
+    let mut failed = Vec::new();
+    let mut test_count = 0;
+
+    for test in &suite.tests {
+        if let Some(filter) = &opt.filter {
+            if !test.name.contains(filter.as_str()) {
+                continue;
+            }
+        }
+
+        test_count += 1;
+
+        let status = Command::new(&exe)
+            .args(std::env::args_os().skip(1))
+            .arg("--run-single")
+            .arg(&test.name)
+            .status()
+            .context("Starting the test process")?;
+
+        match status.code() {
+            Some(0) => {}
+            Some(EXIT_CODE_MISMATCH) => failed.push((test.name.as_str(), "image mismatch")),
+            _ => {
+                println!("{}: FAILED to run ({})", test.name, status);
+                failed.push((test.name.as_str(), "error"));
+            }
+        }
+    }
+
+    println!();
+    if failed.is_empty() {
+        println!("All {} tests passed", test_count);
+        Ok(())
+    } else {
+        println!("{} of {} tests failed:", failed.len(), test_count);
+        for (name, reason) in &failed {
+            println!("    {} ({})", name, reason);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run_single(opt: &Opt, suite: &TestSuite, test: &TestDesc) -> anyhow::Result<()> {
+    let config = RendererConfig {
+        width: suite.resolution[0],
+        height: suite.resolution[1],
+        physical_device_index: opt.physical_device_index,
+        graphics_debugging: opt.graphics_debugging,
+        vsync: false,
+        deterministic_seed: Some(test.seed),
+        deterministic_fps: suite.fps,
+        ..Default::default()
+    };
+
+    // There's no headless mode; render to a hidden window instead.
+    let mut kajiya = config
+        .main_loop_builder()
+        .default_log_level(log::LevelFilter::Warn)
+        .build(
+            WindowBuilder::new()
+                .with_title(format!("kajiya regression: {}", test.name))
+                .with_resizable(false)
+                .with_visible(false),
+        )?;
+
+    let scene = SceneDesc::load(canonical_path_from_vfs(&test.scene)?)?;
+    if scene.terrain.is_some() {
+        log::warn!("Scene terrain is not supported by the regression tests, and is skipped");
+    }
+
+    scene.instantiate(&mut kajiya.world_renderer, |world_renderer, mesh| {
+        let path = baked_mesh_path(mesh)?;
+        world_renderer.add_baked_mesh(path, AddMeshOptions::new())
+    })?;
+
+    let camera = test.camera.resolve(&scene)?;
+    let lens = CameraLens {
+        aspect_ratio: kajiya.window_aspect_ratio(),
+        vertical_fov: camera
+            .vertical_fov
+            .unwrap_or(CameraLens::default().vertical_fov),
+        ..Default::default()
+    };
+    let camera_matrices = (camera.position, camera.rotation).through(&lens);
+    let sun_direction = scene.sun.as_ref().map_or(Vec3::Y, |sun| sun.towards_sun());
+
+    let warmup_frames = suite.warmup_frames(test);
+    let mut frame = 0;
+
+    kajiya.run(move |ctx| {
+        if frame == warmup_frames {
+            ctx.world_renderer
+                .request_hdr_capture(HdrCaptureSource::PreTonemap);
+        }
+
+        if let Some(capture) = ctx.world_renderer.take_hdr_capture() {
+            let exit_code = match evaluate(opt, suite, test, capture) {
+                Ok(true) => 0,
+                Ok(false) => EXIT_CODE_MISMATCH,
+                Err(err) => {
+                    println!("{}: FAILED: {:?}", test.name, err);
+                    1
+                }
+            };
+
+            std::process::exit(exit_code);
+        }
+
+        if frame > warmup_frames + CAPTURE_TIMEOUT_FRAMES {
+            println!("{}: FAILED: the capture was never read back", test.name);
+            std::process::exit(1);
+        }
+
+        frame += 1;
+
+        WorldFrameDesc {
+            camera_matrices,
+            render_extent: ctx.render_extent,
+            sun_direction,
+        }
+    })?;
+
+    anyhow::bail!("The window was closed before the test finished")
+}
+
+/// Scenes refer to either baked meshes, or source ones, baked here like in the `view` app.
+fn baked_mesh_path(mesh: &str) -> anyhow::Result<PathBuf> {
+    if mesh.ends_with(".mesh") {
+        return Ok(PathBuf::from(mesh));
+    }
+
+    let path = canonical_path_from_vfs(mesh)?;
+    let cached_mesh_name = kajiya_asset_pipe::mesh_cache_key(&path, 1.0, Default::default())?;
+    let cached_mesh_path = PathBuf::from(format!("/cache/{}.mesh", cached_mesh_name));
+
+    if !canonical_path_from_vfs(&cached_mesh_path).map_or(false, |path| path.exists()) {
+        kajiya_asset_pipe::process_mesh_asset(kajiya_asset_pipe::MeshAssetProcessParams {
+            path,
+            output_name: cached_mesh_name,
+            scale: 1.0,
+            quantization: Default::default(),
+        })?;
+    }
+
+    Ok(cached_mesh_path)
+}
+
+/// Returns whether the test passed.
+fn evaluate(
+    opt: &Opt,
+    suite: &TestSuite,
+    test: &TestDesc,
+    capture: HdrCapture,
+) -> anyhow::Result<bool> {
+    let current = HdrImage {
+        extent: capture.extent,
+        pixels: capture
+            .pixels
+            .iter()
+            .map(|&[r, g, b, _]| [r, g, b].map(|c| c * capture.exposure))
+            .collect(),
+    };
+
+    let reference_path = opt.references.join(format!("{}.exr", test.name));
+
+    if opt.update_references {
+        std::fs::create_dir_all(&opt.references)?;
+        current.save(&reference_path)?;
+        println!("{}: reference saved to {:?}", test.name, reference_path);
+        return Ok(true);
+    }
+
+    let reference = HdrImage::load(&reference_path).with_context(|| {
+        format!(
+            "Loading the reference image {:?}; run with --update-references to create it",
+            reference_path
+        )
+    })?;
+
+    if reference.extent != current.extent {
+        println!(
+            "{}: FAILED: rendered at {:?}, but the reference is {:?}",
+            test.name, current.extent, reference.extent
+        );
+        save_failure(&opt.output, test, &current, None)?;
+        return Ok(false);
+    }
+
+    let threshold = suite.threshold(test);
+    let result = compare(&reference, &current, threshold);
+
+    println!(
+        "{}: {} (mean dE {:.3}, max dE {:.2}, {:.4}% of pixels above dE {})",
+        test.name,
+        if result.passed { "passed" } else { "FAILED" },
+        result.mean_delta_e,
+        result.max_delta_e,
+        result.differing_pixels * 100.0,
+        threshold.pixel_delta_e,
+    );
+
+    if !result.passed {
+        save_failure(&opt.output, test, &current, Some(&result.diff_image))?;
+    }
+
+    Ok(result.passed)
+}
+
+fn save_failure(
+    output: &Path,
+    test: &TestDesc,
+    current: &HdrImage,
+    diff_image: Option<&HdrImage>,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(output)?;
+    current.save(output.join(format!("{}.exr", test.name)))?;
+
+    if let Some(diff_image) = diff_image {
+        diff_image.save(output.join(format!("{}-diff.exr", test.name)))?;
+    }
+
+    Ok(())
+}
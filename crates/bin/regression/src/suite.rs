@@ -0,0 +1,167 @@
+use std::{fs::File, path::Path};
+
+use anyhow::Context as _;
+use kajiya_simple::{
+    camera_controller::look_towards,
+    scene::{SceneCameraDesc, SceneDesc},
+    Quat, Vec3,
+};
+
+/// A list of scenes and views to render, and how closely they must match their references.
+#[derive(serde::Deserialize)]
+pub struct TestSuite {
+    #[serde(default = "default_resolution")]
+    pub resolution: [u32; 2],
+
+    /// Frames rendered before the capture, letting the temporal effects converge.
+    #[serde(default = "default_warmup_frames")]
+    pub warmup_frames: u32,
+
+    /// Time step of the deterministic mode.
+    #[serde(default = "default_fps")]
+    pub fps: f32,
+
+    #[serde(default)]
+    pub threshold: DiffThreshold,
+
+    pub tests: Vec<TestDesc>,
+}
+
+fn default_resolution() -> [u32; 2] {
+    [1280, 720]
+}
+
+fn default_warmup_frames() -> u32 {
+    64
+}
+
+fn default_fps() -> f32 {
+    60.0
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestDesc {
+    /// Also names the reference image.
+    pub name: String,
+
+    /// VFS path of the scene `.ron` file.
+    pub scene: String,
+
+    pub camera: TestCamera,
+
+    /// Seed for the deterministic mode.
+    #[serde(default)]
+    pub seed: u32,
+
+    /// Overrides the suite's
+    #[serde(default)]
+    pub warmup_frames: Option<u32>,
+
+    /// Overrides the suite's
+    #[serde(default)]
+    pub threshold: Option<DiffThreshold>,
+}
+
+#[derive(serde::Deserialize)]
+pub enum TestCamera {
+    /// One of the scene's `cameras`, by name.
+    Preset(String),
+
+    LookAt {
+        position: [f32; 3],
+        target: [f32; 3],
+        #[serde(default)]
+        vertical_fov: Option<f32>,
+    },
+}
+
+pub struct ResolvedCamera {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub vertical_fov: Option<f32>,
+}
+
+impl TestCamera {
+    pub fn resolve(&self, scene: &SceneDesc) -> anyhow::Result<ResolvedCamera> {
+        match self {
+            TestCamera::Preset(name) => {
+                let camera: &SceneCameraDesc = scene
+                    .cameras
+                    .iter()
+                    .find(|camera| &camera.name == name)
+                    .with_context(|| format!("The scene has no camera named {:?}", name))?;
+
+                Ok(ResolvedCamera {
+                    position: camera.position(),
+                    rotation: camera.rotation(),
+                    vertical_fov: camera.vertical_fov,
+                })
+            }
+            TestCamera::LookAt {
+                position,
+                target,
+                vertical_fov,
+            } => {
+                let position = Vec3::from(*position);
+
+                Ok(ResolvedCamera {
+                    position,
+                    rotation: look_towards(Vec3::from(*target) - position),
+                    vertical_fov: *vertical_fov,
+                })
+            }
+        }
+    }
+}
+
+/// Pixels differ if their CIELAB distance after tonemapping is above `pixel_delta_e`.
+/// A test fails if the fraction of differing pixels is above `max_differing_pixels`.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+pub struct DiffThreshold {
+    pub pixel_delta_e: f32,
+    pub max_differing_pixels: f32,
+}
+
+impl Default for DiffThreshold {
+    fn default() -> Self {
+        Self {
+            // About a just-noticeable difference
+            pixel_delta_e: 2.3,
+            max_differing_pixels: 0.001,
+        }
+    }
+}
+
+impl TestSuite {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Opening test suite {:?}", path))?;
+        let suite: Self =
+            ron::de::from_reader(file).with_context(|| format!("Parsing test suite {:?}", path))?;
+
+        for (i, test) in suite.tests.iter().enumerate() {
+            anyhow::ensure!(
+                suite.tests[..i].iter().all(|other| other.name != test.name),
+                "Duplicate test name {:?}",
+                test.name
+            );
+        }
+
+        Ok(suite)
+    }
+
+    pub fn test(&self, name: &str) -> anyhow::Result<&TestDesc> {
+        self.tests
+            .iter()
+            .find(|test| test.name == name)
+            .with_context(|| format!("No test named {:?}", name))
+    }
+
+    pub fn warmup_frames(&self, test: &TestDesc) -> u32 {
+        test.warmup_frames.unwrap_or(self.warmup_frames)
+    }
+
+    pub fn threshold(&self, test: &TestDesc) -> DiffThreshold {
+        test.threshold.unwrap_or(self.threshold)
+    }
+}
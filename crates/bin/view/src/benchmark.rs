@@ -0,0 +1,212 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write as _},
+    path::PathBuf,
+    time::Instant,
+};
+
+use anyhow::Context as _;
+use kajiya_simple::*;
+
+/// Records per-frame timings while the benchmark camera path plays back.
+pub struct Benchmark {
+    output: PathBuf,
+    camera_path: String,
+    warmup_frames_left: u32,
+    last_frame_instant: Option<Instant>,
+    frames: Vec<BenchmarkFrame>,
+}
+
+#[derive(serde::Serialize)]
+struct BenchmarkFrame {
+    /// Wall-clock time since the previous frame, as seen by the CPU.
+    cpu_ms: f64,
+    gpu_ms: f64,
+    /// Render graph passes in submission order. Passes recorded more than once are summed.
+    passes: Vec<(String, f64)>,
+}
+
+#[derive(serde::Serialize)]
+struct TimingSummary {
+    mean: f64,
+    median: f64,
+    p95: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TimingSummary {
+    fn new(mut values: Vec<f64>) -> Self {
+        if values.is_empty() {
+            values.push(0.0);
+        }
+
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            min: values[0],
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+impl Benchmark {
+    pub fn new(output: PathBuf, camera_path: String, warmup_frames: u32) -> Self {
+        Self {
+            output,
+            camera_path,
+            warmup_frames_left: warmup_frames,
+            last_frame_instant: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// The camera path is held at its start until this is done.
+    pub fn is_warming_up(&self) -> bool {
+        self.warmup_frames_left > 0
+    }
+
+    /// Call once per frame. The GPU timings are those of the latest frame the GPU finished.
+    pub fn record_frame(&mut self) {
+        let now = Instant::now();
+        let prev_frame_instant = self.last_frame_instant.replace(now);
+
+        if self.warmup_frames_left > 0 {
+            self.warmup_frames_left -= 1;
+            return;
+        }
+
+        let cpu_ms = match prev_frame_instant {
+            Some(prev) => (now - prev).as_secs_f64() * 1000.0,
+            None => return,
+        };
+
+        let mut passes: Vec<(String, f64)> = Vec::new();
+        for (scope, ms) in gpu_profiler::get_stats().get_ordered() {
+            if scope.name.starts_with('_') {
+                continue;
+            }
+
+            match passes.iter_mut().find(|(name, _)| *name == scope.name) {
+                Some((_, total)) => *total += ms,
+                None => passes.push((scope.name.clone(), ms)),
+            }
+        }
+
+        self.frames.push(BenchmarkFrame {
+            cpu_ms,
+            gpu_ms: passes.iter().map(|(_, ms)| ms).sum(),
+            passes,
+        });
+    }
+
+    /// Writes the per-frame timings to `<output>.csv`, and the same along with
+    /// a summary to `<output>.json`.
+    pub fn write(&self, render_extent: [u32; 2]) -> anyhow::Result<()> {
+        let csv_path = self.output.with_extension("csv");
+        self.write_csv(&csv_path)
+            .with_context(|| format!("Writing {:?}", csv_path))?;
+
+        let json_path = self.output.with_extension("json");
+        self.write_json(&json_path, render_extent)
+            .with_context(|| format!("Writing {:?}", json_path))?;
+
+        log::info!(
+            "Benchmarked {} frames; saved the results to {:?} and {:?}",
+            self.frames.len(),
+            csv_path,
+            json_path
+        );
+
+        Ok(())
+    }
+
+    /// Names of all passes seen, in the order they first appeared.
+    fn pass_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for frame in &self.frames {
+            for (name, _) in &frame.passes {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// A row per frame, with a column per pass; empty if the pass didn't run that frame.
+    fn write_csv(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let pass_names = self.pass_names();
+        let mut out = BufWriter::new(File::create(path)?);
+
+        write!(out, "frame,cpu_ms,gpu_ms")?;
+        for name in &pass_names {
+            write!(out, ",\"{}\"", name.replace('"', "\"\""))?;
+        }
+        writeln!(out)?;
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(out, "{},{:.4},{:.4}", i, frame.cpu_ms, frame.gpu_ms)?;
+            for name in &pass_names {
+                match frame.passes.iter().find(|(pass, _)| pass == name) {
+                    Some((_, ms)) => write!(out, ",{:.4}", ms)?,
+                    None => write!(out, ",")?,
+                }
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_json(&self, path: &std::path::Path, render_extent: [u32; 2]) -> anyhow::Result<()> {
+        #[derive(serde::Serialize)]
+        struct Report<'a> {
+            camera_path: &'a str,
+            render_extent: [u32; 2],
+            frame_count: usize,
+            cpu_ms: TimingSummary,
+            gpu_ms: TimingSummary,
+            /// Mean over the frames the pass ran in
+            pass_mean_ms: BTreeMap<&'a str, f64>,
+            frames: &'a [BenchmarkFrame],
+        }
+
+        let pass_mean_ms = self
+            .pass_names()
+            .into_iter()
+            .map(|name| {
+                let times: Vec<f64> = self
+                    .frames
+                    .iter()
+                    .filter_map(|frame| {
+                        frame
+                            .passes
+                            .iter()
+                            .find(|(pass, _)| pass == name)
+                            .map(|(_, ms)| *ms)
+                    })
+                    .collect();
+                (name, times.iter().sum::<f64>() / times.len().max(1) as f64)
+            })
+            .collect();
+
+        let report = Report {
+            camera_path: &self.camera_path,
+            render_extent,
+            frame_count: self.frames.len(),
+            cpu_ms: TimingSummary::new(self.frames.iter().map(|frame| frame.cpu_ms).collect()),
+            gpu_ms: TimingSummary::new(self.frames.iter().map(|frame| frame.gpu_ms).collect()),
+            pass_mean_ms,
+            frames: &self.frames,
+        };
+
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &report)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,26 @@
-use imgui::im_str;
-use kajiya::RenderOverrideFlags;
-use kajiya_simple::*;
+use imgui::{im_str, ImString};
+use kajiya::{
+    renderers::{
+        ddgi::GiMode,
+        debug_view::DebugView,
+        gi_resolution::GiResolution,
+        gtao::GtaoQuality,
+        image_stats::SCENE_IMAGE_STATS,
+        output_calibration::OutputGamut,
+        post::{BloomFx, TonemapOperator},
+        post_fx::{ChromaticAberrationFx, FilmGrainFx, VignetteFx},
+        render_target_formats::{ColorPrecision, GiHistoryPrecision, NormalEncoding},
+        rtao::AmbientOcclusionMode,
+        rtr::ReflectionQuality,
+        taa::TaaJitterSequence,
+        working_color_space::WorkingColorSpace,
+    },
+    RenderOverrideFlags,
+};
+use kajiya_simple::{camera_path::CameraPathTimeStep, *};
 
 use crate::{
+    persisted::CameraControllerKind,
     runtime::{RuntimeState, MAX_FPS_LIMIT},
     PersistedState,
 };
@@ -15,8 +33,20 @@ impl RuntimeState {
 
         ctx.world_renderer.rg_debug_hook = self.locked_rg_debug_hook.clone();
 
-        if self.show_gui {
+        // The overlay stays up with the rest of the GUI hidden.
+        if self.show_gui || self.show_gpu_resource_overlay {
             ctx.imgui.take().unwrap().frame(|ui| {
+                if self.show_gpu_resource_overlay {
+                    self.gpu_resource_overlay.draw(ui, ctx.world_renderer);
+                }
+
+                if !self.show_gui {
+                    return;
+                }
+
+                #[cfg(feature = "imgui-docking")]
+                kajiya_imgui::dockspace_over_main_viewport(ui);
+
                 if imgui::CollapsingHeader::new(im_str!("Tweaks"))
                     .default_open(true)
                     .build(ui)
@@ -26,6 +56,61 @@ impl RuntimeState {
                         .speed(0.01)
                         .build(ui, &mut persisted.exposure.ev_shift);
 
+                    ui.checkbox(
+                        im_str!("Physical camera"),
+                        &mut persisted.exposure.physical_camera.enabled,
+                    );
+
+                    if persisted.exposure.physical_camera.enabled {
+                        let physical_camera = &mut persisted.exposure.physical_camera;
+
+                        imgui::Drag::<f32>::new(im_str!("Camera f-stop"))
+                            .range(0.7..=64.0)
+                            .speed(0.01)
+                            .build(ui, &mut physical_camera.f_stop);
+
+                        let mut shutter_speed = 1.0 / physical_camera.shutter_time_seconds;
+                        imgui::Drag::<f32>::new(im_str!("Shutter speed (1/s)"))
+                            .range(1.0..=8000.0)
+                            .speed(1.0)
+                            .flags(imgui::SliderFlags::LOGARITHMIC)
+                            .build(ui, &mut shutter_speed);
+                        physical_camera.shutter_time_seconds = 1.0 / shutter_speed.max(1.0);
+
+                        imgui::Drag::<f32>::new(im_str!("ISO"))
+                            .range(25.0..=102400.0)
+                            .speed(1.0)
+                            .flags(imgui::SliderFlags::LOGARITHMIC)
+                            .build(ui, &mut physical_camera.iso);
+
+                        let mut focal_length_mm = physical_camera.focal_length * 1000.0;
+                        imgui::Drag::<f32>::new(im_str!("Focal length (mm)"))
+                            .range(4.0..=1200.0)
+                            .speed(0.25)
+                            .flags(imgui::SliderFlags::LOGARITHMIC)
+                            .build(ui, &mut focal_length_mm);
+                        physical_camera.focal_length = focal_length_mm.max(1.0) / 1000.0;
+
+                        let mut sensor_size_mm = (physical_camera.sensor_size * 1000.0).to_array();
+                        imgui::Drag::<f32>::new(im_str!("Sensor size (mm)"))
+                            .range(1.0..=100.0)
+                            .speed(0.1)
+                            .build_array(ui, &mut sensor_size_mm);
+                        physical_camera.sensor_size =
+                            Vec2::from(sensor_size_mm).max(Vec2::ONE) / 1000.0;
+
+                        ui.text(im_str!(
+                            "EV100: {:.2}",
+                            ctx.world_renderer.physical_camera.ev100()
+                        ));
+
+                        if persisted.exposure.use_dynamic_adaptation {
+                            ui.text(im_str!(
+                                "Exposure is dynamic; the settings drive DOF and motion blur"
+                            ));
+                        }
+                    }
+
                     ui.checkbox(
                         im_str!("Use dynamic exposure"),
                         &mut persisted.exposure.use_dynamic_adaptation,
@@ -54,11 +139,102 @@ impl RuntimeState {
                         .dynamic_adaptation_high_clip
                         .clamp(0.0, 1.0);
 
+                    imgui::Drag::<f32>::new(im_str!("Dynamic exposure min EV"))
+                        .range(-16.0..=16.0)
+                        .speed(0.01)
+                        .build(ui, &mut persisted.exposure.dynamic_adaptation_ev_min);
+
+                    imgui::Drag::<f32>::new(im_str!("Dynamic exposure max EV"))
+                        .range(-16.0..=16.0)
+                        .speed(0.01)
+                        .build(ui, &mut persisted.exposure.dynamic_adaptation_ev_max);
+                    persisted.exposure.dynamic_adaptation_ev_max = persisted
+                        .exposure
+                        .dynamic_adaptation_ev_max
+                        .max(persisted.exposure.dynamic_adaptation_ev_min);
+
                     imgui::Drag::<f32>::new(im_str!("Contrast"))
                         .range(1.0..=1.5)
                         .speed(0.001)
                         .build(ui, &mut persisted.exposure.contrast);
 
+                    {
+                        let mut tonemap_idx = match ctx.world_renderer.post.tonemap {
+                            TonemapOperator::Neutral => 0,
+                            TonemapOperator::AcesFitted => 1,
+                            TonemapOperator::AgX => 2,
+                            TonemapOperator::Reinhard => 3,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Tonemapper")).build_simple_string(
+                            ui,
+                            &mut tonemap_idx,
+                            &[
+                                im_str!("Neutral"),
+                                im_str!("ACES (fitted)"),
+                                im_str!("AgX"),
+                                im_str!("Reinhard"),
+                            ],
+                        ) {
+                            ctx.world_renderer.post.tonemap = match tonemap_idx {
+                                0 => TonemapOperator::Neutral,
+                                1 => TonemapOperator::AcesFitted,
+                                2 => TonemapOperator::AgX,
+                                _ => TonemapOperator::Reinhard,
+                            };
+                        }
+                    }
+
+                    {
+                        let output = &mut ctx.world_renderer.output_calibration;
+
+                        imgui::Drag::<f32>::new(im_str!("Paper white (nits)"))
+                            .range(10.0..=1000.0)
+                            .speed(1.0)
+                            .build(ui, &mut output.paper_white_nits);
+
+                        imgui::Drag::<f32>::new(im_str!("Max luminance (nits)"))
+                            .range(10.0..=10000.0)
+                            .speed(5.0)
+                            .build(ui, &mut output.max_luminance_nits);
+                        output.max_luminance_nits =
+                            output.max_luminance_nits.max(output.paper_white_nits);
+
+                        let mut gamut_idx = match output.gamut {
+                            OutputGamut::Rec709 => 0,
+                            OutputGamut::DisplayP3 => 1,
+                            OutputGamut::Rec2020 => 2,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Output gamut")).build_simple_string(
+                            ui,
+                            &mut gamut_idx,
+                            &[
+                                im_str!("Rec.709"),
+                                im_str!("Display P3"),
+                                im_str!("Rec.2020"),
+                            ],
+                        ) {
+                            output.gamut = match gamut_idx {
+                                0 => OutputGamut::Rec709,
+                                1 => OutputGamut::DisplayP3,
+                                _ => OutputGamut::Rec2020,
+                            };
+                        }
+                    }
+
+                    {
+                        let mut acescg =
+                            ctx.world_renderer.working_color_space == WorkingColorSpace::AcesCg;
+                        if ui.checkbox(im_str!("ACEScg working space"), &mut acescg) {
+                            ctx.world_renderer.working_color_space = if acescg {
+                                WorkingColorSpace::AcesCg
+                            } else {
+                                WorkingColorSpace::LinearSrgb
+                            };
+                        }
+                    }
+
                     imgui::Drag::<f32>::new(im_str!("Emissive multiplier"))
                         .range(0.0..=10.0)
                         .speed(0.1)
@@ -74,6 +250,26 @@ impl RuntimeState {
                         .speed(1.0)
                         .build(ui, &mut persisted.light.local_lights.multiplier);
 
+                    {
+                        let mut controller_idx = match persisted.movement.camera_controller {
+                            CameraControllerKind::Fly => 0,
+                            CameraControllerKind::FirstPerson => 1,
+                            CameraControllerKind::Orbit => 2,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Camera controller")).build_simple_string(
+                            ui,
+                            &mut controller_idx,
+                            &[im_str!("Fly"), im_str!("First person"), im_str!("Orbit")],
+                        ) {
+                            persisted.movement.camera_controller = match controller_idx {
+                                0 => CameraControllerKind::Fly,
+                                1 => CameraControllerKind::FirstPerson,
+                                _ => CameraControllerKind::Orbit,
+                            };
+                        }
+                    }
+
                     imgui::Drag::<f32>::new(im_str!("Camera speed"))
                         .range(0.0..=10.0)
                         .speed(0.025)
@@ -89,15 +285,257 @@ impl RuntimeState {
                         .speed(0.1)
                         .build(ui, &mut persisted.movement.sun_rotation_smoothness);
 
-                    imgui::Drag::<f32>::new(im_str!("Field of view"))
-                        .range(1.0..=120.0)
-                        .speed(0.25)
-                        .build(ui, &mut persisted.camera.vertical_fov);
+                    if !persisted.exposure.physical_camera.enabled {
+                        imgui::Drag::<f32>::new(im_str!("Field of view"))
+                            .range(1.0..=120.0)
+                            .speed(0.25)
+                            .build(ui, &mut persisted.camera.vertical_fov);
+                    }
 
-                    imgui::Drag::<f32>::new(im_str!("Sun size"))
+                    imgui::Drag::<f32>::new(im_str!("Sun angular diameter (deg)"))
                         .range(0.0..=10.0)
+                        .speed(0.01)
+                        .build(ui, &mut persisted.light.sun.angular_diameter_degrees);
+
+                    ui.checkbox(
+                        im_str!("Time of day"),
+                        &mut ctx.world_renderer.time_of_day.enabled,
+                    );
+
+                    if ctx.world_renderer.time_of_day.enabled {
+                        imgui::Drag::<f32>::new(im_str!("Hour"))
+                            .range(0.0..=24.0)
+                            .speed(0.01)
+                            .build(ui, &mut ctx.world_renderer.time_of_day.hour);
+
+                        imgui::Drag::<f32>::new(im_str!("Hours per second"))
+                            .range(-2.0..=2.0)
+                            .speed(0.001)
+                            .build(ui, &mut ctx.world_renderer.time_of_day.hours_per_second);
+                    }
+
+                    ui.checkbox(
+                        im_str!("Contact shadows"),
+                        &mut ctx.world_renderer.contact_shadows.enabled,
+                    );
+
+                    if ctx.world_renderer.contact_shadows.enabled {
+                        imgui::Drag::<f32>::new(im_str!("Contact shadow length"))
+                            .range(0.0..=2.0)
+                            .speed(0.005)
+                            .build(ui, &mut ctx.world_renderer.contact_shadows.length);
+
+                        imgui::Drag::<f32>::new(im_str!("Contact shadow thickness"))
+                            .range(0.0..=1.0)
+                            .speed(0.002)
+                            .build(ui, &mut ctx.world_renderer.contact_shadows.thickness);
+
+                        imgui::Drag::<u32>::new(im_str!("Contact shadow steps"))
+                            .range(1..=64)
+                            .build(ui, &mut ctx.world_renderer.contact_shadows.step_count);
+                    }
+
+                    ui.checkbox(
+                        im_str!("Punctual shadow cache"),
+                        &mut ctx.world_renderer.punctual_shadow_cache.enabled,
+                    );
+
+                    imgui::Drag::<f32>::new(im_str!("Atmosphere turbidity"))
+                        .range(0.0..=20.0)
                         .speed(0.02)
-                        .build(ui, &mut persisted.light.sun.size_multiplier);
+                        .build(ui, &mut ctx.world_renderer.atmosphere.turbidity);
+
+                    imgui::Drag::<f32>::new(im_str!("Ozone density"))
+                        .range(0.0..=10.0)
+                        .speed(0.01)
+                        .build(ui, &mut ctx.world_renderer.atmosphere.ozone_density);
+
+                    imgui::Drag::<f32>::new(im_str!("Planet radius (km)"))
+                        .range(100.0..=100000.0)
+                        .speed(10.0)
+                        .build(ui, &mut ctx.world_renderer.atmosphere.planet_radius_km);
+
+                    imgui::Drag::<f32>::new(im_str!("Atmosphere height (km)"))
+                        .range(1.0..=1000.0)
+                        .speed(0.5)
+                        .build(ui, &mut ctx.world_renderer.atmosphere.atmosphere_height_km);
+
+                    imgui::Drag::<f32>::new(im_str!("Fog density"))
+                        .range(0.0..=1.0)
+                        .speed(0.0005)
+                        .build(ui, &mut ctx.world_renderer.fog.density);
+
+                    imgui::Drag::<f32>::new(im_str!("Fog anisotropy"))
+                        .range(-0.95..=0.95)
+                        .speed(0.01)
+                        .build(ui, &mut ctx.world_renderer.fog.anisotropy);
+
+                    imgui::Drag::<f32>::new(im_str!("Fog height falloff"))
+                        .range(0.0..=10.0)
+                        .speed(0.005)
+                        .build(ui, &mut ctx.world_renderer.fog.height_falloff);
+
+                    ui.checkbox(
+                        im_str!("Depth of field"),
+                        &mut ctx.world_renderer.dof.enabled,
+                    );
+
+                    if ctx.world_renderer.dof.enabled {
+                        if !ctx.world_renderer.physical_camera.enabled {
+                            imgui::Drag::<f32>::new(im_str!("f-stop"))
+                                .range(0.7..=32.0)
+                                .speed(0.01)
+                                .build(ui, &mut ctx.world_renderer.dof.f_stop);
+                        }
+
+                        ui.checkbox(
+                            im_str!("Auto focus"),
+                            &mut ctx.world_renderer.dof.auto_focus,
+                        );
+
+                        if !ctx.world_renderer.dof.auto_focus {
+                            imgui::Drag::<f32>::new(im_str!("Focus distance"))
+                                .range(0.05..=1000.0)
+                                .speed(0.01)
+                                .build(ui, &mut ctx.world_renderer.dof.focus_distance);
+                        }
+                    }
+
+                    imgui::TreeNode::new(im_str!("Post-processing stack")).build(ui, || {
+                        let stack = &mut ctx.world_renderer.post.stack;
+                        let entry_count = stack.entries().len();
+
+                        let mut entry_to_move = None;
+                        for (idx, entry) in stack.entries_mut().iter_mut().enumerate() {
+                            let id_token = ui.push_id(idx as i32);
+                            ui.checkbox(&im_str!("{}", entry.name), &mut entry.enabled);
+
+                            if idx > 0 {
+                                ui.same_line(0.0);
+                                if ui.button(im_str!("Up"), [0.0, 0.0]) {
+                                    entry_to_move = Some((idx, idx - 1));
+                                }
+                            }
+
+                            if idx + 1 < entry_count {
+                                ui.same_line(0.0);
+                                if ui.button(im_str!("Down"), [0.0, 0.0]) {
+                                    entry_to_move = Some((idx, idx + 1));
+                                }
+                            }
+                            id_token.pop(ui);
+                        }
+
+                        if let Some((from, to)) = entry_to_move {
+                            stack.move_entry(from, to);
+                        }
+                    });
+
+                    if let Some(bloom) = ctx.world_renderer.post.stack.get_mut::<BloomFx>() {
+                        let bloom = &mut bloom.params;
+
+                        imgui::Drag::<f32>::new(im_str!("Bloom intensity"))
+                            .range(0.0..=1.0)
+                            .speed(0.001)
+                            .build(ui, &mut bloom.intensity);
+
+                        imgui::Drag::<f32>::new(im_str!("Bloom threshold"))
+                            .range(0.0..=16.0)
+                            .speed(0.01)
+                            .build(ui, &mut bloom.threshold);
+
+                        if bloom.threshold > 0.0 {
+                            imgui::Drag::<f32>::new(im_str!("Bloom knee"))
+                                .range(0.0..=4.0)
+                                .speed(0.01)
+                                .build(ui, &mut bloom.knee);
+                        }
+
+                        imgui::Drag::<f32>::new(im_str!("Lens dirt intensity"))
+                            .range(0.0..=16.0)
+                            .speed(0.01)
+                            .build(ui, &mut bloom.lens_dirt_intensity);
+
+                        imgui::TreeNode::new(im_str!("Bloom mip intensities")).build(ui, || {
+                            for (mip, intensity) in bloom.mip_intensities.iter_mut().enumerate() {
+                                imgui::Drag::<f32>::new(&im_str!("Mip {}", mip))
+                                    .range(0.0..=4.0)
+                                    .speed(0.01)
+                                    .build(ui, intensity);
+                            }
+                        });
+                    }
+
+                    if let Some(ca) = ctx
+                        .world_renderer
+                        .post
+                        .stack
+                        .get_mut::<ChromaticAberrationFx>()
+                    {
+                        imgui::Drag::<f32>::new(im_str!("Chromatic aberration"))
+                            .range(0.0..=0.05)
+                            .speed(0.0001)
+                            .build(ui, &mut ca.intensity);
+                    }
+
+                    if let Some(vignette) = ctx.world_renderer.post.stack.get_mut::<VignetteFx>() {
+                        imgui::Drag::<f32>::new(im_str!("Vignette intensity"))
+                            .range(0.0..=16.0)
+                            .speed(0.01)
+                            .build(ui, &mut vignette.intensity);
+                    }
+
+                    if let Some(grain) = ctx.world_renderer.post.stack.get_mut::<FilmGrainFx>() {
+                        imgui::Drag::<f32>::new(im_str!("Film grain intensity"))
+                            .range(0.0..=1.0)
+                            .speed(0.001)
+                            .build(ui, &mut grain.intensity);
+                    }
+
+                    ui.checkbox(
+                        im_str!("Motion blur"),
+                        &mut ctx.world_renderer.motion_blur.enabled,
+                    );
+
+                    if ctx.world_renderer.motion_blur.enabled
+                        && !ctx.world_renderer.physical_camera.enabled
+                    {
+                        imgui::Drag::<f32>::new(im_str!("Shutter angle (deg)"))
+                            .range(0.0..=360.0)
+                            .speed(1.0)
+                            .build(
+                                ui,
+                                &mut ctx.world_renderer.motion_blur.shutter_angle_degrees,
+                            );
+                    }
+
+                    {
+                        let mut jitter_idx = match ctx.world_renderer.taa.jitter_sequence {
+                            TaaJitterSequence::Halton => 0,
+                            TaaJitterSequence::R2 => 1,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("TAA jitter")).build_simple_string(
+                            ui,
+                            &mut jitter_idx,
+                            &[im_str!("Halton"), im_str!("R2")],
+                        ) {
+                            ctx.world_renderer.taa.jitter_sequence = match jitter_idx {
+                                0 => TaaJitterSequence::Halton,
+                                _ => TaaJitterSequence::R2,
+                            };
+                        }
+                    }
+
+                    imgui::Drag::<f32>::new(im_str!("TAA clamp box scale"))
+                        .range(0.25..=4.0)
+                        .speed(0.01)
+                        .build(ui, &mut ctx.world_renderer.taa.clamp_box_scale);
+
+                    imgui::Drag::<f32>::new(im_str!("TAA sharpening"))
+                        .range(0.0..=1.0)
+                        .speed(0.01)
+                        .build(ui, &mut ctx.world_renderer.taa.sharpening);
 
                     /*ui.checkbox(
                         im_str!("Show world radiance cache"),
@@ -142,21 +580,302 @@ impl RuntimeState {
                         &mut ctx.world_renderer.rtdgi.use_raytraced_reservoir_visibility,
                     );
 
+                    ui.checkbox(
+                        im_str!("Adaptive GI ray count"),
+                        &mut ctx.world_renderer.rtdgi.adaptive_ray_count,
+                    );
+
+                    if ctx.world_renderer.rtdgi.adaptive_ray_count {
+                        imgui::Drag::<u32>::new(im_str!("Max GI rays per pixel"))
+                            .range(1..=8)
+                            .build(
+                                ui,
+                                &mut ctx.world_renderer.rtdgi.adaptive_max_rays_per_pixel,
+                            );
+
+                        imgui::Drag::<f32>::new(im_str!("GI noise threshold"))
+                            .range(0.01..=1.0)
+                            .speed(0.001)
+                            .build(ui, &mut ctx.world_renderer.rtdgi.adaptive_noise_threshold);
+                    }
+
                     ui.checkbox(
                         im_str!("Allow diffuse ray reuse for reflections"),
                         &mut ctx.world_renderer.rtr.reuse_rtdgi_rays,
                     );
 
+                    {
+                        let mut quality_idx = match ctx.world_renderer.rtr.quality {
+                            ReflectionQuality::Low => 0,
+                            ReflectionQuality::Medium => 1,
+                            ReflectionQuality::High => 2,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Reflections")).build_simple_string(
+                            ui,
+                            &mut quality_idx,
+                            &[
+                                im_str!("Screen-space"),
+                                im_str!("Screen-space, then ray-traced"),
+                                im_str!("Ray-traced"),
+                            ],
+                        ) {
+                            ctx.world_renderer.rtr.quality = match quality_idx {
+                                0 => ReflectionQuality::Low,
+                                1 => ReflectionQuality::Medium,
+                                _ => ReflectionQuality::High,
+                            };
+                        }
+                    }
+
+                    {
+                        let mut mode_idx = match ctx.world_renderer.gi_mode {
+                            GiMode::Rtdgi => 0,
+                            GiMode::Ddgi => 1,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Diffuse GI")).build_simple_string(
+                            ui,
+                            &mut mode_idx,
+                            &[
+                                im_str!("Screen-space ReSTIR"),
+                                im_str!("Probe volume (DDGI)"),
+                            ],
+                        ) {
+                            ctx.world_renderer.gi_mode = match mode_idx {
+                                0 => GiMode::Rtdgi,
+                                _ => GiMode::Ddgi,
+                            };
+                        }
+                    }
+
+                    if ctx.world_renderer.gi_mode == GiMode::Ddgi {
+                        let ddgi = &mut ctx.world_renderer.ddgi;
+
+                        imgui::Drag::<f32>::new(im_str!("Probe spacing"))
+                            .range(0.25..=8.0)
+                            .speed(0.01)
+                            .build(ui, &mut ddgi.probe_spacing);
+
+                        imgui::Drag::<u32>::new(im_str!("Rays per probe"))
+                            .range(16..=512)
+                            .build(ui, &mut ddgi.rays_per_probe);
+
+                        imgui::Drag::<f32>::new(im_str!("Probe hysteresis"))
+                            .range(0.0..=0.999)
+                            .speed(0.001)
+                            .build(ui, &mut ddgi.hysteresis);
+                    }
+
+                    {
+                        let camera_position = self.camera.position();
+                        let light_probes = &mut ctx.world_renderer.light_probes;
+
+                        ui.checkbox(im_str!("Baked light probes"), &mut light_probes.enabled);
+
+                        imgui::Drag::<f32>::new(im_str!("Light probe weight"))
+                            .range(0.0..=1.0)
+                            .speed(0.01)
+                            .build(ui, &mut light_probes.base_layer_weight);
+
+                        ui.text(format!(
+                            "{} light probes{}",
+                            light_probes.len(),
+                            if light_probes.is_baking() {
+                                ", baking"
+                            } else {
+                                ""
+                            }
+                        ));
+
+                        if ui.button(im_str!("Add probe grid around camera"), [0.0, 0.0]) {
+                            let half_extent = Vec3::new(8.0, 4.0, 8.0);
+                            for probe in light_probes.add_grid(
+                                camera_position - half_extent,
+                                camera_position + half_extent,
+                                [9, 5, 9],
+                            ) {
+                                light_probes.bake(probe);
+                            }
+                        }
+
+                        if ui.button(im_str!("Bake light probes"), [0.0, 0.0]) {
+                            light_probes.bake_all();
+                        }
+
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("Clear light probes"), [0.0, 0.0]) {
+                            let handles: Vec<_> =
+                                light_probes.iter().map(|(handle, _)| handle).collect();
+                            for handle in handles {
+                                light_probes.remove(handle);
+                            }
+                        }
+                    }
+
+                    {
+                        let time_slicer = &mut ctx.world_renderer.time_slicer;
+
+                        ui.checkbox(
+                            im_str!("Time-sliced probe updates"),
+                            &mut time_slicer.enabled,
+                        );
+
+                        imgui::Drag::<f32>::new(im_str!("Time slice budget (us)"))
+                            .range(50.0..=20000.0)
+                            .speed(10.0)
+                            .build(ui, &mut time_slicer.budget_us);
+                    }
+
+                    ui.checkbox(
+                        im_str!("Particles"),
+                        &mut ctx.world_renderer.particles.enabled,
+                    );
+
+                    {
+                        let planar_reflections = &mut ctx.world_renderer.planar_reflections;
+
+                        ui.checkbox(
+                            im_str!("Planar reflections"),
+                            &mut planar_reflections.enabled,
+                        );
+
+                        imgui::Drag::<f32>::new(im_str!("Planar reflection scale"))
+                            .range(0.05..=1.0)
+                            .speed(0.01)
+                            .build(ui, &mut planar_reflections.resolution_scale);
+                    }
+
+                    ui.checkbox(im_str!("Water"), &mut ctx.world_renderer.water.enabled);
+
+                    ui.checkbox(im_str!("Use GTAO"), &mut ctx.world_renderer.use_gtao);
+
+                    if ctx.world_renderer.use_gtao {
+                        let mut quality_idx = match ctx.world_renderer.gtao.quality {
+                            GtaoQuality::Low => 0,
+                            GtaoQuality::Medium => 1,
+                            GtaoQuality::High => 2,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("GTAO quality")).build_simple_string(
+                            ui,
+                            &mut quality_idx,
+                            &[im_str!("Low"), im_str!("Medium"), im_str!("High")],
+                        ) {
+                            ctx.world_renderer.gtao.quality = match quality_idx {
+                                0 => GtaoQuality::Low,
+                                1 => GtaoQuality::Medium,
+                                _ => GtaoQuality::High,
+                            };
+                        }
+
+                        imgui::Drag::<f32>::new(im_str!("GTAO radius"))
+                            .range(0.05..=8.0)
+                            .speed(0.01)
+                            .build(ui, &mut ctx.world_renderer.gtao.radius);
+                    }
+
+                    {
+                        let mut mode_idx = match ctx.world_renderer.ao_mode {
+                            AmbientOcclusionMode::ScreenSpace => 0,
+                            AmbientOcclusionMode::RayTraced => 1,
+                            AmbientOcclusionMode::Blended => 2,
+                        };
+
+                        if imgui::ComboBox::new(im_str!("Ambient occlusion")).build_simple_string(
+                            ui,
+                            &mut mode_idx,
+                            &[
+                                im_str!("Screen-space"),
+                                im_str!("Ray traced"),
+                                im_str!("Blended"),
+                            ],
+                        ) {
+                            ctx.world_renderer.ao_mode = match mode_idx {
+                                0 => AmbientOcclusionMode::ScreenSpace,
+                                1 => AmbientOcclusionMode::RayTraced,
+                                _ => AmbientOcclusionMode::Blended,
+                            };
+                        }
+                    }
+
+                    if ctx.world_renderer.ao_mode != AmbientOcclusionMode::ScreenSpace {
+                        let rtao = &mut ctx.world_renderer.rtao;
+
+                        imgui::Drag::<f32>::new(im_str!("RTAO ray length"))
+                            .range(0.05..=8.0)
+                            .speed(0.01)
+                            .build(ui, &mut rtao.ray_length);
+
+                        if ctx.world_renderer.ao_mode == AmbientOcclusionMode::Blended {
+                            imgui::Drag::<f32>::new(im_str!("RTAO blend weight"))
+                                .range(0.0..=1.0)
+                                .speed(0.01)
+                                .build(ui, &mut rtao.blend_weight);
+                        }
+                    }
+
+                    ui.checkbox(
+                        im_str!("GPU-driven draws"),
+                        &mut ctx.world_renderer.use_gpu_driven_draws,
+                    );
+
+                    if ctx.world_renderer.use_gpu_driven_draws {
+                        ui.checkbox(
+                            im_str!("Occlusion culling"),
+                            &mut ctx.world_renderer.use_occlusion_culling,
+                        );
+                    }
+
+                    ui.checkbox(
+                        im_str!("Depth pre-pass"),
+                        &mut ctx.world_renderer.use_depth_prepass,
+                    );
+
                     #[cfg(feature = "dlss")]
                     {
                         ui.checkbox(im_str!("Use DLSS"), &mut ctx.world_renderer.use_dlss);
                     }
+
+                    #[cfg(feature = "fsr2")]
+                    {
+                        ui.checkbox(im_str!("Use FSR 2"), &mut ctx.world_renderer.use_fsr2);
+                    }
+
+                    if let Some(dynamic_resolution) = ctx.dynamic_resolution.as_mut() {
+                        ui.text(format!(
+                            "Render extent: {}x{} ({:.0}%)",
+                            ctx.render_extent[0],
+                            ctx.render_extent[1],
+                            dynamic_resolution.scale() * 100.0
+                        ));
+
+                        imgui::Drag::<f32>::new(im_str!("Target GPU frame time (ms)"))
+                            .range(1.0..=100.0)
+                            .speed(0.05)
+                            .build(ui, &mut dynamic_resolution.config.target_frame_time_ms);
+
+                        imgui::Drag::<f32>::new(im_str!("Min render scale"))
+                            .range(0.25..=1.0)
+                            .speed(0.005)
+                            .build(ui, &mut dynamic_resolution.config.min_scale);
+                    }
                 }
 
                 if imgui::CollapsingHeader::new(im_str!("Scene"))
                     .default_open(true)
                     .build(ui)
                 {
+                    let load_progress = ctx.world_renderer.load_progress();
+                    if !load_progress.is_done() {
+                        ui.text(format!(
+                            "Streaming textures: {} left ({:.1} MB)",
+                            load_progress.items_remaining(),
+                            load_progress.bytes_remaining() as f64 / (1024.0 * 1024.0)
+                        ));
+                        imgui::ProgressBar::new(load_progress.fraction()).build(ui);
+                    }
+
                     if let Some(ibl) = persisted.scene.ibl.as_ref() {
                         ui.text(im_str!("IBL: {:?}", ibl));
                         if ui.button(im_str!("Unload"), [0.0, 0.0]) {
@@ -167,6 +886,59 @@ impl RuntimeState {
                         ui.text(im_str!("Drag a sphere-mapped .hdr/.exr to load as IBL"));
                     }
 
+                    if let Some(lens_dirt) = persisted.scene.lens_dirt.as_ref() {
+                        ui.text(im_str!("Lens dirt: {:?}", lens_dirt));
+                        if ui.button(im_str!("Unload lens dirt"), [0.0, 0.0]) {
+                            if let Some(bloom) = ctx.world_renderer.post.stack.get_mut::<BloomFx>()
+                            {
+                                bloom.unload_lens_dirt();
+                            }
+                            persisted.scene.lens_dirt = None;
+                        }
+                    } else {
+                        ui.text(im_str!("Drag a .png/.jpg to load as lens dirt"));
+                    }
+
+                    if let Some(grading_lut) = persisted.scene.grading_lut.as_ref() {
+                        ui.text(im_str!("Grading LUT: {:?}", grading_lut));
+                        if ui.button(im_str!("Unload grading LUT"), [0.0, 0.0]) {
+                            ctx.world_renderer.post.unload_grading_lut();
+                            persisted.scene.grading_lut = None;
+                        }
+                    } else {
+                        ui.text(im_str!("Drag a .cube to load as a grading LUT"));
+                    }
+
+                    let mut camera_preset_to_apply = None;
+                    for (idx, camera) in persisted.scene.camera_presets.iter().enumerate() {
+                        let id_token = ui.push_id(idx as i32);
+                        if ui.button(&im_str!("Camera: {}", camera.name), [0.0, 0.0]) {
+                            camera_preset_to_apply = Some(camera.clone());
+                        }
+                        id_token.pop(ui);
+                    }
+
+                    if let Some(camera) = camera_preset_to_apply {
+                        self.apply_camera_preset(persisted, &camera);
+                    }
+
+                    let mut camera_path_to_play = None;
+                    for (idx, path) in persisted.scene.camera_paths.iter().enumerate() {
+                        let id_token = ui.push_id(idx as i32);
+                        if ui.button(&im_str!("Camera path: {}", path.name), [0.0, 0.0]) {
+                            camera_path_to_play = Some(path.name.clone());
+                        }
+                        id_token.pop(ui);
+                    }
+
+                    if let Some(name) = camera_path_to_play {
+                        if let Err(err) =
+                            self.play_camera_path(persisted, &name, CameraPathTimeStep::RealTime)
+                        {
+                            log::error!("{:#}", err);
+                        }
+                    }
+
                     let mut element_to_remove = None;
                     for (idx, elem) in persisted.scene.elements.iter_mut().enumerate() {
                         ui.dummy([0.0, 10.0]);
@@ -237,6 +1009,25 @@ impl RuntimeState {
                                 .build(ui, &mut elem.transform.rotation_euler_degrees.z);
                         }
 
+                        // Material overrides
+                        {
+                            let overrides = &mut elem.material_overrides;
+
+                            ui.set_next_item_width(200.0);
+                            let mut tint: [f32; 3] = overrides.base_color_tint.into();
+                            if imgui::ColorEdit::new(im_str!("tint"), &mut tint).build(ui) {
+                                overrides.base_color_tint = tint.into();
+                            }
+
+                            ui.same_line(0.0);
+
+                            ui.set_next_item_width(100.0);
+                            imgui::Drag::<f32>::new(im_str!("roughness"))
+                                .range(0.0..=4.0)
+                                .speed(0.01)
+                                .build(ui, &mut overrides.roughness_multiplier);
+                        }
+
                         id_token.pop(ui);
                     }
 
@@ -246,6 +1037,133 @@ impl RuntimeState {
                     }
                 }
 
+                if imgui::CollapsingHeader::new(im_str!("Quality"))
+                    .default_open(false)
+                    .build(ui)
+                {
+                    let quality = &mut ctx.world_renderer.render_quality;
+
+                    imgui::Drag::<u32>::new(im_str!("Sun shadow rays"))
+                        .range(1..=16)
+                        .build(ui, &mut quality.sun_shadow_rays_per_pixel);
+
+                    imgui::Drag::<u32>::new(im_str!("Diffuse GI rays"))
+                        .range(1..=8)
+                        .build(ui, &mut quality.diffuse_gi_rays_per_pixel);
+
+                    imgui::Drag::<u32>::new(im_str!("Reflection rays"))
+                        .range(1..=8)
+                        .build(ui, &mut quality.reflection_rays_per_pixel);
+
+                    imgui::Drag::<f32>::new(im_str!("Diffuse GI history"))
+                        .range(1.0..=64.0)
+                        .speed(0.1)
+                        .build(ui, &mut quality.diffuse_gi_history_length);
+
+                    imgui::Drag::<f32>::new(im_str!("Reflection history"))
+                        .range(1.0..=64.0)
+                        .speed(0.1)
+                        .build(ui, &mut quality.reflection_history_length);
+
+                    imgui::Drag::<f32>::new(im_str!("Reflection roughness cutoff"))
+                        .range(0.0..=1.0)
+                        .speed(0.005)
+                        .build(ui, &mut quality.reflection_roughness_cutoff);
+
+                    let mut resolution_idx = match quality.gi_resolution {
+                        GiResolution::Full => 0,
+                        GiResolution::Half => 1,
+                        GiResolution::Quarter => 2,
+                    };
+
+                    if imgui::ComboBox::new(im_str!("GI resolution")).build_simple_string(
+                        ui,
+                        &mut resolution_idx,
+                        &[im_str!("Full"), im_str!("Half"), im_str!("Quarter")],
+                    ) {
+                        quality.gi_resolution = match resolution_idx {
+                            0 => GiResolution::Full,
+                            1 => GiResolution::Half,
+                            _ => GiResolution::Quarter,
+                        };
+                    }
+
+                    if ui.button(im_str!("Reset quality"), [0.0, 0.0]) {
+                        *quality = Default::default();
+                    }
+
+                    let upsample = &mut ctx.world_renderer.bilateral_upsample;
+
+                    imgui::Drag::<f32>::new(im_str!("Upsample depth sharpness"))
+                        .range(0.0..=256.0)
+                        .speed(0.5)
+                        .build(ui, &mut upsample.depth_sharpness);
+
+                    imgui::Drag::<f32>::new(im_str!("Upsample normal power"))
+                        .range(0.0..=64.0)
+                        .speed(0.1)
+                        .build(ui, &mut upsample.normal_power);
+
+                    let gi_temporal = &mut ctx.world_renderer.gi_temporal;
+
+                    ui.checkbox(
+                        im_str!("GI temporal accumulation"),
+                        &mut gi_temporal.enabled,
+                    );
+
+                    imgui::Drag::<f32>::new(im_str!("GI accumulation frames"))
+                        .range(1.0..=64.0)
+                        .speed(0.1)
+                        .build(ui, &mut gi_temporal.diffuse_max_sample_count);
+
+                    imgui::Drag::<f32>::new(im_str!("Reflection accumulation frames"))
+                        .range(1.0..=64.0)
+                        .speed(0.1)
+                        .build(ui, &mut gi_temporal.reflection_max_sample_count);
+
+                    imgui::Drag::<f32>::new(im_str!("GI accumulation clamp sigma"))
+                        .range(0.5..=8.0)
+                        .speed(0.01)
+                        .build(ui, &mut gi_temporal.clamp_sigma);
+
+                    let subsurface = &mut ctx.world_renderer.subsurface;
+
+                    ui.checkbox(im_str!("Subsurface scattering"), &mut subsurface.enabled);
+
+                    imgui::Drag::<u32>::new(im_str!("Subsurface samples"))
+                        .range(3..=31)
+                        .build(ui, &mut subsurface.sample_count);
+
+                    let formats = &mut ctx.world_renderer.render_target_formats;
+
+                    let mut packed_color = formats.color == ColorPrecision::R11G11B10Float;
+                    if ui.checkbox(im_str!("R11G11B10 scene color"), &mut packed_color) {
+                        formats.color = if packed_color {
+                            ColorPrecision::R11G11B10Float
+                        } else {
+                            ColorPrecision::Rgba16Float
+                        };
+                    }
+
+                    let mut full_gi_history = formats.gi_history == GiHistoryPrecision::Full;
+                    if ui.checkbox(im_str!("32-bit GI history"), &mut full_gi_history) {
+                        formats.gi_history = if full_gi_history {
+                            GiHistoryPrecision::Full
+                        } else {
+                            GiHistoryPrecision::Half
+                        };
+                    }
+
+                    let mut unorm16_normals = formats.normals == NormalEncoding::Unorm16;
+                    if ui.checkbox(im_str!("16-bit normals"), &mut unorm16_normals) {
+                        formats.normals = if unorm16_normals {
+                            NormalEncoding::Unorm16
+                        } else {
+                            NormalEncoding::Unorm10
+                        };
+                    }
+                }
+
                 if imgui::CollapsingHeader::new(im_str!("Overrides"))
                     .default_open(false)
                     .build(ui)
@@ -277,6 +1195,29 @@ impl RuntimeState {
                             ui,
                             &mut ctx.world_renderer.render_overrides.material_roughness_scale,
                         );
+
+                    imgui::Drag::<f32>::new(im_str!("Mesh LOD pixel error"))
+                        .range(0.0..=16.0)
+                        .speed(0.01)
+                        .build(ui, &mut ctx.world_renderer.mesh_lod_max_pixel_error);
+
+                    imgui::Drag::<f32>::new(im_str!("Texture full-res distance"))
+                        .range(0.0..=1000.0)
+                        .speed(0.05)
+                        .build(
+                            ui,
+                            &mut ctx.world_renderer.texture_streaming_full_res_distance,
+                        );
+
+                    let mut budget_mb =
+                        (ctx.world_renderer.texture_streaming_budget_bytes >> 20) as i32;
+                    if imgui::Drag::<i32>::new(im_str!("Texture budget (MB)"))
+                        .range(0..=32768)
+                        .build(ui, &mut budget_mb)
+                    {
+                        ctx.world_renderer.texture_streaming_budget_bytes =
+                            (budget_mb.max(0) as u64) << 20;
+                    }
                 }
 
                 if imgui::CollapsingHeader::new(im_str!("Sequence"))
@@ -388,6 +1329,84 @@ impl RuntimeState {
                         ctx.world_renderer.debug_mode = RenderDebugMode::WorldRadianceCache;
                     }*/
 
+                    {
+                        let view_names: Vec<ImString> = DebugView::ALL
+                            .iter()
+                            .map(|view| ImString::new(view.name()))
+                            .collect();
+                        let view_names: Vec<&ImString> = view_names.iter().collect();
+
+                        let mut view_idx = DebugView::ALL
+                            .iter()
+                            .position(|view| *view == ctx.world_renderer.debug_view)
+                            .unwrap_or(0);
+
+                        if imgui::ComboBox::new(im_str!("View")).build_simple_string(
+                            ui,
+                            &mut view_idx,
+                            &view_names,
+                        ) {
+                            ctx.world_renderer.debug_view = DebugView::ALL[view_idx];
+                        }
+
+                        if ctx.world_renderer.debug_view == DebugView::Luminance {
+                            let heatmap = &mut ctx.world_renderer.luminance_heatmap;
+
+                            imgui::Drag::<f32>::new(im_str!("Nits per unit"))
+                                .range(1e-3..=1e5)
+                                .speed(0.01)
+                                .flags(imgui::SliderFlags::LOGARITHMIC)
+                                .build(ui, &mut heatmap.nits_per_unit);
+
+                            imgui::Drag::<f32>::new(im_str!("Min EV"))
+                                .range(-20.0..=30.0)
+                                .speed(0.05)
+                                .build(ui, &mut heatmap.min_ev);
+
+                            imgui::Drag::<f32>::new(im_str!("Max EV"))
+                                .range(-20.0..=30.0)
+                                .speed(0.05)
+                                .build(ui, &mut heatmap.max_ev);
+                        }
+                    }
+
+                    {
+                        let image_stats = &mut ctx.world_renderer.image_stats;
+                        let mut watched = image_stats.is_watched(SCENE_IMAGE_STATS);
+
+                        if ui.checkbox(im_str!("Scene luminance stats"), &mut watched) {
+                            if watched {
+                                image_stats.watch(SCENE_IMAGE_STATS, Default::default());
+                            } else {
+                                image_stats.unwatch(SCENE_IMAGE_STATS);
+                            }
+                        }
+
+                        if let Some(stats) = image_stats.latest(SCENE_IMAGE_STATS) {
+                            ui.text(format!(
+                                "Min {:.3e}, max {:.3e}, mean {:.3e}",
+                                stats.min, stats.max, stats.mean
+                            ));
+                            ui.text(format!(
+                                "Median log2 {:.2}, {} non-finite pixels",
+                                stats.percentile(0.5),
+                                stats.non_finite_count
+                            ));
+                        }
+                    }
+
+                    ui.checkbox(im_str!("Wireframe"), &mut ctx.world_renderer.show_wireframe);
+
+                    ui.checkbox(
+                        im_str!("Freeze frame"),
+                        &mut ctx.world_renderer.freeze_frame,
+                    );
+
+                    ui.checkbox(
+                        im_str!("GPU resource overlay"),
+                        &mut self.show_gpu_resource_overlay,
+                    );
+
                     imgui::ComboBox::new(im_str!("Shading")).build_simple_string(
                         ui,
                         &mut ctx.world_renderer.debug_shading_mode,
@@ -408,6 +1427,58 @@ impl RuntimeState {
                     ui.checkbox(im_str!("Allow pass overlap"), unsafe {
                         &mut kajiya::rg::RG_ALLOW_PASS_OVERLAP
                     });
+
+                    let mut recording_threads = kajiya::rg::recording_thread_count() as u32;
+                    if imgui::Drag::<u32>::new(im_str!("Recording threads"))
+                        .range(
+                            1..=kajiya::backend::vulkan::device::MAX_PARALLEL_COMMAND_BUFFERS
+                                as u32,
+                        )
+                        .build(ui, &mut recording_threads)
+                    {
+                        kajiya::rg::set_recording_thread_count(recording_threads as usize);
+                    }
+
+                    let mut split_submit = kajiya::rg::is_submit_split();
+                    if ui.checkbox(im_str!("Split queue submits"), &mut split_submit) {
+                        kajiya::rg::set_split_submit(split_submit);
+                    }
+
+                    let mut reorder_passes = kajiya::rg::is_pass_reordering_enabled();
+                    if ui.checkbox(im_str!("Reorder passes"), &mut reorder_passes) {
+                        kajiya::rg::set_pass_reordering(reorder_passes);
+                    }
+
+                    let submit_stats = kajiya::rg::frame_submit_stats();
+                    ui.text(format!(
+                        "Queue submits: {} ({:.3}ms)",
+                        submit_stats.submit_count,
+                        submit_stats.cpu_time.as_secs_f64() * 1000.0
+                    ));
+                    for batch in &submit_stats.batches {
+                        ui.text(format!(
+                            "  #{} {}: {} command buffers, {} waits, {} signals",
+                            batch.submit_idx,
+                            batch.name,
+                            batch.command_buffer_count,
+                            batch.wait_semaphores.len(),
+                            batch.signal_semaphores.len()
+                        ));
+                    }
+
+                    let mut log_barriers = kajiya::rg::is_barrier_logging_enabled();
+                    if ui.checkbox(im_str!("Log barriers"), &mut log_barriers) {
+                        kajiya::rg::set_barrier_logging_enabled(log_barriers);
+                    }
+
+                    if log_barriers {
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("Dump to log"), [0.0, 0.0]) {
+                            for entry in kajiya::rg::barrier_log_entries() {
+                                log::info!("{}", entry);
+                            }
+                        }
+                    }
                 }
 
                 if imgui::CollapsingHeader::new(im_str!("GPU passes"))
@@ -460,6 +1531,38 @@ impl RuntimeState {
                         }
                     }
                 }
+
+                if imgui::CollapsingHeader::new(im_str!("Pass toggles")).build(ui) {
+                    if ui.button(im_str!("Enable all"), [0.0, 0.0]) {
+                        kajiya::rg::enable_all_passes();
+                    }
+
+                    for (name, mut enabled) in kajiya::rg::pass_toggles() {
+                        if ui.checkbox(&im_str!("{}", name), &mut enabled) {
+                            kajiya::rg::set_pass_enabled(&name, enabled);
+                        }
+                    }
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Transient memory")).build(ui) {
+                    let stats = kajiya::rg::transient_memory_stats();
+                    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+                    ui.text(format!(
+                        "This frame: {:.1}MB, peak: {:.1}MB",
+                        mb(stats.watermark_bytes),
+                        mb(stats.peak_watermark_bytes)
+                    ));
+
+                    for resource in &stats.resources {
+                        ui.text(format!(
+                            "{:.2}MB: {} ({})",
+                            mb(resource.size_bytes),
+                            resource.first_pass.as_deref().unwrap_or("unwritten"),
+                            resource.desc
+                        ));
+                    }
+                }
             });
         }
     }
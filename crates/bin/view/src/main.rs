@@ -1,16 +1,17 @@
+mod benchmark;
 mod gui;
 mod misc;
 mod opt;
 mod persisted;
+mod remote;
+mod resource_overlay;
 mod runtime;
-mod scene;
 mod sequence;
+mod video;
 
-use std::{
-    fs::File,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
+use anyhow::Context as _;
 use kajiya_simple::*;
 use opt::*;
 use persisted::*;
@@ -18,6 +19,12 @@ use runtime::*;
 
 use structopt::StructOpt;
 
+// Custom pass plugins share kajiya with the viewer through the dylib.
+#[cfg(feature = "plugins")]
+#[allow(unused_imports)]
+#[allow(clippy::single_component_path_imports)]
+use kajiya_dylib;
+
 struct AppState {
     persisted: PersistedState,
     runtime: RuntimeState,
@@ -26,19 +33,16 @@ struct AppState {
 
 impl AppState {
     fn new(mut persisted: PersistedState, opt: &Opt) -> anyhow::Result<Self> {
-        let mut kajiya = SimpleMainLoop::builder()
-            .resolution([opt.width, opt.height])
-            .vsync(!opt.no_vsync)
-            .graphics_debugging(opt.graphics_debugging)
-            .physical_device_index(opt.physical_device_index)
-            .temporal_upsampling(opt.temporal_upsampling)
+        let mut kajiya = opt
+            .renderer
+            .main_loop_builder()
             .default_log_level(log::LevelFilter::Info)
-            .fullscreen(opt.fullscreen.then(|| FullscreenMode::Exclusive))
+            .deterministic(opt.deterministic_mode())
             .build(
                 WindowBuilder::new()
                     .with_title("kajiya")
                     .with_resizable(false)
-                    .with_decorations(!opt.no_window_decorations),
+                    .with_decorations(opt.renderer.window_decorations),
             )?;
 
         let runtime = RuntimeState::new(&mut persisted, &mut kajiya.world_renderer, opt);
@@ -87,35 +91,98 @@ impl AppState {
 const APP_STATE_CONFIG_FILE_PATH: &str = "view_state.ron";
 
 fn main() -> anyhow::Result<()> {
-    set_vfs_mount_point("/meshes", "assets/meshes");
-
     let opt = Opt::from_args();
 
-    let mut persisted: PersistedState = File::open(APP_STATE_CONFIG_FILE_PATH)
-        .map_err(|err| anyhow::anyhow!(err))
-        .and_then(|file| Ok(ron::de::from_reader(file)?))
-        .unwrap_or_default();
+    // Logging is only set up along with the renderer, so report any problem with the file later.
+    let (mut persisted, persisted_load_error) = if Path::new(APP_STATE_CONFIG_FILE_PATH).exists() {
+        match PersistedState::load(APP_STATE_CONFIG_FILE_PATH) {
+            Ok(persisted) => (persisted, None),
+            Err(err) => (PersistedState::default(), Some(err)),
+        }
+    } else {
+        (PersistedState::default(), None)
+    };
 
     // If supplying a new scene, clear the previous one.
-    if opt.scene.is_some() || opt.mesh.is_some() {
+    if opt.renderer.scene.is_some() || opt.mesh.is_some() {
         persisted.scene = SceneState::default();
     }
 
     let mut state = AppState::new(persisted, &opt)?;
 
-    if let Some(scene) = opt.scene.as_ref() {
+    if let Some(err) = persisted_load_error {
+        log::warn!(
+            "Could not restore the app state; starting from scratch: {:#}",
+            err
+        );
+    }
+
+    if let Some(scene) = opt.renderer.scene.as_ref() {
         state.load_scene(scene)?;
     } else if let Some(mesh) = opt.mesh.as_ref() {
         state.add_standalone_mesh(mesh.clone(), opt.mesh_scale)?;
     }
 
+    if let Some(output) = opt.record_video.as_ref() {
+        let camera_path = opt
+            .camera_path
+            .as_ref()
+            .context("Recording a video needs a --camera-path to play back")?;
+
+        state.runtime.start_video_recording(
+            &state.persisted,
+            camera_path,
+            opt.camera_path_time_step(),
+            video::VideoRecorder::new(
+                output.clone(),
+                opt.video_fps,
+                opt.video_codec.clone(),
+                opt.video_warmup_frames,
+            ),
+        )?;
+    } else if let Some(output) = opt.benchmark.as_ref() {
+        let camera_path = opt
+            .camera_path
+            .as_ref()
+            .context("A benchmark needs a --camera-path to play back")?;
+
+        state.runtime.start_benchmark(
+            &state.persisted,
+            camera_path,
+            opt.camera_path_time_step(),
+            benchmark::Benchmark::new(
+                output.clone(),
+                camera_path.clone(),
+                opt.benchmark_warmup_frames,
+            ),
+        )?;
+    } else if let Some(camera_path) = opt.camera_path.as_ref() {
+        state.runtime.play_camera_path(
+            &state.persisted,
+            camera_path,
+            opt.camera_path_time_step(),
+        )?;
+    }
+
+    #[cfg(feature = "plugins")]
+    for plugin in &opt.custom_pass_plugin {
+        state.runtime.load_custom_pass_plugin(
+            &mut state.kajiya.world_renderer,
+            plugin.point,
+            &plugin.path,
+        )?;
+    }
+
+    if let Some(address) = opt.remote_control.as_ref() {
+        state.runtime.start_remote_control(address)?;
+    }
+
     let state = state.run()?;
 
-    ron::ser::to_writer_pretty(
-        File::create(APP_STATE_CONFIG_FILE_PATH)?,
-        &state,
-        Default::default(),
-    )?;
+    // Benchmarks and videos leave the camera at the end of the path; don't make that stick.
+    if opt.benchmark.is_none() && opt.record_video.is_none() {
+        state.save(APP_STATE_CONFIG_FILE_PATH)?;
+    }
 
     Ok(())
 }
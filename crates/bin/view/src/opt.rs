@@ -1,40 +1,119 @@
 use std::path::PathBuf;
 
+#[cfg(feature = "plugins")]
+use kajiya::renderers::custom_passes::CustomPassPoint;
+use kajiya_simple::{camera_path::CameraPathTimeStep, DeterministicMode, RendererConfig};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "view", about = "Kajiya scene viewer.")]
 pub struct Opt {
-    #[structopt(long, default_value = "1920")]
-    pub width: u32,
+    #[structopt(flatten)]
+    pub renderer: RendererConfig,
 
-    #[structopt(long, default_value = "1080")]
-    pub height: u32,
-
-    #[structopt(long, default_value = "1.0")]
-    pub temporal_upsampling: f32,
+    /// Plays back the camera path of this name from the scene on startup.
+    #[structopt(long)]
+    pub camera_path: Option<String>,
 
+    /// Plays back the camera path with a fixed time step of one frame at this rate,
+    /// rather than in real time, so that each frame sees the same camera on every run.
     #[structopt(long)]
-    pub scene: Option<PathBuf>,
+    pub camera_path_fps: Option<f32>,
 
+    /// Plays back `--camera-path` once, recording GPU pass and CPU frame times, then writes
+    /// them to `<BENCHMARK>.csv` and `<BENCHMARK>.json`, and exits.
     #[structopt(long)]
-    pub mesh: Option<PathBuf>,
+    pub benchmark: Option<PathBuf>,
 
-    #[structopt(long, default_value = "1.0")]
-    pub mesh_scale: f32,
+    /// Frames to render at the start of the camera path before recording the benchmark,
+    /// so that shader compilation and temporal accumulation don't skew it.
+    #[structopt(long, default_value = "120")]
+    pub benchmark_warmup_frames: u32,
 
+    /// Plays back `--camera-path` once, encoding it to this video file with `ffmpeg`, and exits.
+    /// Every frame advances time by one video frame, however long it takes to render.
     #[structopt(long)]
-    pub no_vsync: bool,
+    pub record_video: Option<PathBuf>,
 
+    #[structopt(long, default_value = "60")]
+    pub video_fps: f32,
+
+    /// `ffmpeg` encoder, e.g. `h264_nvenc` or `hevc_nvenc` for hardware encoding on NVIDIA GPUs.
+    #[structopt(long, default_value = "libx264")]
+    pub video_codec: String,
+
+    /// Frames to render at the start of the camera path before recording, letting the
+    /// temporal effects converge.
+    #[structopt(long, default_value = "60")]
+    pub video_warmup_frames: u32,
+
+    /// Listens for WebSocket connections on this address (e.g. `127.0.0.1:9001`), through which
+    /// the camera, sun, quality, and exposure can be controlled, and captures taken.
     #[structopt(long)]
-    pub no_window_decorations: bool,
+    pub remote_control: Option<String>,
 
+    /// Loads a custom pass from a dynamic library, as `<point>=<path>`, with the point one of
+    /// `after-gbuffer`, `before-post`, or `after-post`. Reloaded whenever the library is rebuilt.
+    #[cfg(feature = "plugins")]
     #[structopt(long)]
-    pub fullscreen: bool,
+    pub custom_pass_plugin: Vec<CustomPassPluginOpt>,
 
     #[structopt(long)]
-    pub graphics_debugging: bool,
+    pub mesh: Option<PathBuf>,
+
+    #[structopt(long, default_value = "1.0")]
+    pub mesh_scale: f32,
 
+    /// Bakes meshes with quantized positions, normals, and UVs, for about half the vertex memory.
     #[structopt(long)]
-    pub physical_device_index: Option<usize>,
+    pub quantize_meshes: bool,
+}
+
+impl Opt {
+    /// Benchmarks default to 60 frames per second of the path, so that runs render the same frames.
+    /// Videos advance by one video frame per rendered frame.
+    pub fn camera_path_time_step(&self) -> CameraPathTimeStep {
+        match self.camera_path_fps {
+            Some(fps) => CameraPathTimeStep::fixed_fps(fps),
+            None if self.record_video.is_some() => CameraPathTimeStep::fixed_fps(self.video_fps),
+            None if self.benchmark.is_some() => CameraPathTimeStep::fixed_fps(60.0),
+            None => CameraPathTimeStep::RealTime,
+        }
+    }
+
+    /// Video frames are rendered with a fixed time step, so that the animation and the temporal
+    /// effects play back at the video's rate, rather than the rendering one.
+    pub fn deterministic_mode(&self) -> Option<DeterministicMode> {
+        if self.record_video.is_some() {
+            Some(DeterministicMode {
+                seed: self.renderer.deterministic_seed.unwrap_or(0),
+                delta_time_seconds: 1.0 / self.video_fps,
+            })
+        } else {
+            self.renderer.deterministic_mode()
+        }
+    }
+}
+
+#[cfg(feature = "plugins")]
+#[derive(Debug)]
+pub struct CustomPassPluginOpt {
+    pub point: CustomPassPoint,
+    pub path: PathBuf,
+}
+
+#[cfg(feature = "plugins")]
+impl std::str::FromStr for CustomPassPluginOpt {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (point, path) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected <point>=<path>, got {:?}", s))?;
+
+        Ok(Self {
+            point: point.parse()?,
+            path: path.into(),
+        })
+    }
 }
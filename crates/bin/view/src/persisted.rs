@@ -1,21 +1,34 @@
-use std::path::PathBuf;
-
-use kajiya::world_renderer::InstanceHandle;
-use kajiya_simple::{Affine3A, EulerRot, Mat2, Quat, Vec2, Vec3, Vec3Swizzles};
+use std::{fs::File, path::PathBuf};
+
+use anyhow::Context;
+use kajiya::{
+    renderers::{
+        bilateral_upsample::BilateralUpsampleParams,
+        debug_view::DebugView,
+        gi_resolution::GiResolution,
+        output_calibration::{OutputCalibration, OutputGamut},
+    },
+    world_renderer::{InstanceHandle, WorldRenderer, EARTH_SUN_ANGULAR_DIAMETER_DEGREES},
+};
+use kajiya_simple::{
+    camera_controller::{CameraController, FirstPersonCamera, FlyCamera, OrbitCamera},
+    scene::{SceneCameraDesc, SceneCameraPathDesc, SceneLightDesc, SceneLightProbeDesc},
+    Affine3A, EulerRot, Mat2, PhysicalCamera, Quat, RenderQuality, Vec2, Vec3, Vec3Swizzles,
+};
 
 use crate::{misc::smoothstep, sequence::Sequence};
 
 #[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct SunState {
     pub controller: SunController,
-    pub size_multiplier: f32,
+    pub angular_diameter_degrees: f32,
 }
 
 impl Default for SunState {
     fn default() -> Self {
         Self {
             controller: SunController::default(),
-            size_multiplier: 1.0,
+            angular_diameter_degrees: EARTH_SUN_ANGULAR_DIAMETER_DEGREES,
         }
     }
 }
@@ -201,8 +214,34 @@ impl ShouldResetPathTracer for LightState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CameraControllerKind {
+    Fly,
+    FirstPerson,
+    Orbit,
+}
+
+impl Default for CameraControllerKind {
+    fn default() -> Self {
+        Self::Fly
+    }
+}
+
+impl CameraControllerKind {
+    /// Creates the controller starting out at the given camera pose.
+    pub fn create(self, position: Vec3, rotation: Quat) -> Box<dyn CameraController> {
+        match self {
+            Self::Fly => Box::new(FlyCamera::new(position, rotation)),
+            Self::FirstPerson => Box::new(FirstPersonCamera::new(position, rotation)),
+            Self::Orbit => Box::new(OrbitCamera::from_position_rotation(position, rotation, 5.0)),
+        }
+    }
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct MovementState {
+    #[serde(default)]
+    pub camera_controller: CameraControllerKind,
     pub camera_speed: f32,
     pub camera_smoothness: f32,
     pub sun_rotation_smoothness: f32,
@@ -211,6 +250,7 @@ pub struct MovementState {
 impl Default for MovementState {
     fn default() -> Self {
         Self {
+            camera_controller: CameraControllerKind::default(),
             camera_speed: 2.5,
             camera_smoothness: 1.0,
             sun_rotation_smoothness: 0.0,
@@ -224,6 +264,52 @@ fn default_contrast() -> f32 {
     1.0
 }
 
+fn default_dynamic_adaptation_ev_min() -> f32 {
+    -16.0
+}
+
+fn default_dynamic_adaptation_ev_max() -> f32 {
+    16.0
+}
+
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PhysicalCameraState {
+    pub enabled: bool,
+    pub f_stop: f32,
+    pub shutter_time_seconds: f32,
+    pub iso: f32,
+    pub focal_length: f32,
+    pub sensor_size: Vec2,
+}
+
+impl Default for PhysicalCameraState {
+    fn default() -> Self {
+        let defaults = PhysicalCamera::default();
+        Self {
+            enabled: defaults.enabled,
+            f_stop: defaults.f_stop,
+            shutter_time_seconds: defaults.shutter_time_seconds,
+            iso: defaults.iso,
+            focal_length: defaults.focal_length,
+            sensor_size: defaults.sensor_size,
+        }
+    }
+}
+
+impl PhysicalCameraState {
+    pub fn to_physical_camera(self) -> PhysicalCamera {
+        PhysicalCamera {
+            enabled: self.enabled,
+            f_stop: self.f_stop,
+            shutter_time_seconds: self.shutter_time_seconds,
+            iso: self.iso,
+            focal_length: self.focal_length,
+            sensor_size: self.sensor_size,
+        }
+    }
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct ExposureState {
     pub ev_shift: f32,
@@ -235,8 +321,14 @@ pub struct ExposureState {
     pub dynamic_adaptation_low_clip: f32,
     #[serde(default)]
     pub dynamic_adaptation_high_clip: f32,
+    #[serde(default = "default_dynamic_adaptation_ev_min")]
+    pub dynamic_adaptation_ev_min: f32,
+    #[serde(default = "default_dynamic_adaptation_ev_max")]
+    pub dynamic_adaptation_ev_max: f32,
     #[serde(default = "default_contrast")]
     pub contrast: f32,
+    #[serde(default)]
+    pub physical_camera: PhysicalCameraState,
 }
 
 impl Default for ExposureState {
@@ -247,13 +339,191 @@ impl Default for ExposureState {
             dynamic_adaptation_speed: 0.0,
             dynamic_adaptation_low_clip: 0.0,
             dynamic_adaptation_high_clip: 0.0,
+            dynamic_adaptation_ev_min: default_dynamic_adaptation_ev_min(),
+            dynamic_adaptation_ev_max: default_dynamic_adaptation_ev_max(),
             contrast: default_contrast(),
+            physical_camera: Default::default(),
         }
     }
 }
 
 impl ShouldResetPathTracer for ExposureState {}
 
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GiResolutionState {
+    Full,
+    Half,
+    Quarter,
+}
+
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RenderQualityState {
+    pub sun_shadow_rays_per_pixel: u32,
+    pub diffuse_gi_rays_per_pixel: u32,
+    pub reflection_rays_per_pixel: u32,
+    pub diffuse_gi_history_length: f32,
+    pub reflection_history_length: f32,
+    pub reflection_roughness_cutoff: f32,
+    pub gi_resolution: GiResolutionState,
+}
+
+impl Default for RenderQualityState {
+    fn default() -> Self {
+        Self::from_render_quality(&RenderQuality::default())
+    }
+}
+
+impl RenderQualityState {
+    pub fn from_render_quality(quality: &RenderQuality) -> Self {
+        Self {
+            sun_shadow_rays_per_pixel: quality.sun_shadow_rays_per_pixel,
+            diffuse_gi_rays_per_pixel: quality.diffuse_gi_rays_per_pixel,
+            reflection_rays_per_pixel: quality.reflection_rays_per_pixel,
+            diffuse_gi_history_length: quality.diffuse_gi_history_length,
+            reflection_history_length: quality.reflection_history_length,
+            reflection_roughness_cutoff: quality.reflection_roughness_cutoff,
+            gi_resolution: match quality.gi_resolution {
+                GiResolution::Full => GiResolutionState::Full,
+                GiResolution::Half => GiResolutionState::Half,
+                GiResolution::Quarter => GiResolutionState::Quarter,
+            },
+        }
+    }
+
+    pub fn to_render_quality(self) -> RenderQuality {
+        RenderQuality {
+            sun_shadow_rays_per_pixel: self.sun_shadow_rays_per_pixel,
+            diffuse_gi_rays_per_pixel: self.diffuse_gi_rays_per_pixel,
+            reflection_rays_per_pixel: self.reflection_rays_per_pixel,
+            diffuse_gi_history_length: self.diffuse_gi_history_length,
+            reflection_history_length: self.reflection_history_length,
+            reflection_roughness_cutoff: self.reflection_roughness_cutoff,
+            gi_resolution: match self.gi_resolution {
+                GiResolutionState::Full => GiResolution::Full,
+                GiResolutionState::Half => GiResolution::Half,
+                GiResolutionState::Quarter => GiResolution::Quarter,
+            },
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BilateralUpsampleState {
+    pub depth_sharpness: f32,
+    pub normal_power: f32,
+}
+
+impl Default for BilateralUpsampleState {
+    fn default() -> Self {
+        Self::from_params(&BilateralUpsampleParams::default())
+    }
+}
+
+impl BilateralUpsampleState {
+    pub fn from_params(params: &BilateralUpsampleParams) -> Self {
+        Self {
+            depth_sharpness: params.depth_sharpness,
+            normal_power: params.normal_power,
+        }
+    }
+
+    pub fn to_params(&self) -> BilateralUpsampleParams {
+        BilateralUpsampleParams {
+            depth_sharpness: self.depth_sharpness,
+            normal_power: self.normal_power,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OutputGamutState {
+    Rec709,
+    DisplayP3,
+    Rec2020,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct OutputCalibrationState {
+    pub paper_white_nits: f32,
+    pub max_luminance_nits: f32,
+    pub gamut: OutputGamutState,
+}
+
+impl Default for OutputCalibrationState {
+    fn default() -> Self {
+        Self::from_calibration(&OutputCalibration::default())
+    }
+}
+
+impl OutputCalibrationState {
+    pub fn from_calibration(calibration: &OutputCalibration) -> Self {
+        Self {
+            paper_white_nits: calibration.paper_white_nits,
+            max_luminance_nits: calibration.max_luminance_nits,
+            gamut: match calibration.gamut {
+                OutputGamut::Rec709 => OutputGamutState::Rec709,
+                OutputGamut::DisplayP3 => OutputGamutState::DisplayP3,
+                OutputGamut::Rec2020 => OutputGamutState::Rec2020,
+            },
+        }
+    }
+
+    pub fn to_calibration(&self) -> OutputCalibration {
+        OutputCalibration {
+            paper_white_nits: self.paper_white_nits,
+            max_luminance_nits: self.max_luminance_nits,
+            gamut: match self.gamut {
+                OutputGamutState::Rec709 => OutputGamut::Rec709,
+                OutputGamutState::DisplayP3 => OutputGamut::DisplayP3,
+                OutputGamutState::Rec2020 => OutputGamut::Rec2020,
+            },
+        }
+    }
+}
+
+/// Renderer settings tweaked in the UI, rather than stored in `PersistedState` directly.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RenderSettingsState {
+    pub quality: RenderQualityState,
+    pub bilateral_upsample: BilateralUpsampleState,
+    pub output_calibration: OutputCalibrationState,
+
+    /// Variant name of the `DebugView`
+    pub debug_view: Option<String>,
+}
+
+impl RenderSettingsState {
+    pub fn from_world_renderer(world_renderer: &WorldRenderer) -> Self {
+        Self {
+            quality: RenderQualityState::from_render_quality(&world_renderer.render_quality),
+            bilateral_upsample: BilateralUpsampleState::from_params(
+                &world_renderer.bilateral_upsample,
+            ),
+            output_calibration: OutputCalibrationState::from_calibration(
+                &world_renderer.output_calibration,
+            ),
+            debug_view: (world_renderer.debug_view != DebugView::None)
+                .then(|| format!("{:?}", world_renderer.debug_view)),
+        }
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+            .as_ref()
+            .and_then(|name| {
+                DebugView::ALL
+                    .iter()
+                    .copied()
+                    .find(|view| format!("{:?}", view) == *name)
+            })
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct SceneElementTransform {
     pub position: Vec3,
@@ -282,12 +552,27 @@ impl SceneElementTransform {
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub enum MeshSource {
     File(PathBuf),
     Cache(PathBuf),
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct SceneElementMaterialOverrides {
+    pub base_color_tint: Vec3,
+    pub roughness_multiplier: f32,
+}
+
+impl Default for SceneElementMaterialOverrides {
+    fn default() -> Self {
+        Self {
+            base_color_tint: Vec3::ONE,
+            roughness_multiplier: 1.0,
+        }
+    }
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct SceneElement {
     #[serde(skip)]
@@ -295,6 +580,9 @@ pub struct SceneElement {
 
     pub source: MeshSource,
     pub transform: SceneElementTransform,
+
+    #[serde(default)]
+    pub material_overrides: SceneElementMaterialOverrides,
 }
 
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
@@ -303,16 +591,42 @@ pub struct SceneState {
 
     #[serde(default)]
     pub ibl: Option<PathBuf>,
+
+    #[serde(default)]
+    pub lens_dirt: Option<PathBuf>,
+
+    #[serde(default)]
+    pub grading_lut: Option<PathBuf>,
+
+    #[serde(default)]
+    pub lights: Vec<SceneLightDesc>,
+
+    #[serde(default)]
+    pub camera_presets: Vec<SceneCameraDesc>,
+
+    #[serde(default)]
+    pub camera_paths: Vec<SceneCameraPathDesc>,
+
+    /// Mirrors `WorldRenderer::light_probes`, including the baked irradiance.
+    #[serde(default)]
+    pub light_probes: Vec<SceneLightProbeDesc>,
 }
 
 impl ShouldResetPathTracer for SceneState {
     fn should_reset_path_tracer(&self, other: &Self) -> bool {
-        self.elements != other.elements
+        self.elements != other.elements || self.lights != other.lights
     }
 }
 
-#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+/// Version of the `PersistedState` format. Bump it when a change can't be handled
+/// by `#[serde(default)]` alone, and convert the older data in `PersistedState::migrate`.
+pub const PERSISTED_STATE_VERSION: u32 = 1;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct PersistedState {
+    /// Zero in files saved before the format was versioned.
+    #[serde(default)]
+    pub version: u32,
     pub camera: CameraState,
     pub light: LightState,
     pub exposure: ExposureState,
@@ -320,6 +634,52 @@ pub struct PersistedState {
     pub sequence: Sequence,
     #[serde(default)]
     pub scene: SceneState,
+    #[serde(default)]
+    pub render: RenderSettingsState,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            version: PERSISTED_STATE_VERSION,
+            camera: Default::default(),
+            light: Default::default(),
+            exposure: Default::default(),
+            movement: Default::default(),
+            sequence: Default::default(),
+            scene: Default::default(),
+            render: Default::default(),
+        }
+    }
+}
+
+impl PersistedState {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening {:?}", path))?;
+        let state: Self =
+            ron::de::from_reader(file).with_context(|| format!("Parsing {:?}", path))?;
+
+        anyhow::ensure!(
+            state.version <= PERSISTED_STATE_VERSION,
+            "{:?} was saved by a newer version of the app (format version {}, expected at most {})",
+            path,
+            state.version,
+            PERSISTED_STATE_VERSION
+        );
+
+        Ok(state.migrate())
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        ron::ser::to_writer_pretty(File::create(path)?, self, Default::default())?;
+        Ok(())
+    }
+
+    fn migrate(mut self) -> Self {
+        // Version 0 only lacks fields which have defaults.
+        self.version = PERSISTED_STATE_VERSION;
+        self
+    }
 }
 
 impl ShouldResetPathTracer for PersistedState {
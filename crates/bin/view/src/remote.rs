@@ -0,0 +1,189 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::mpsc,
+};
+
+use anyhow::Context as _;
+use serde_json::json;
+use tungstenite::Message;
+
+/// A message from a remote client. Sent as JSON text, tagged by `command`, e.g.
+/// `{"command": "set_sun", "towards_sun": [0.0, 1.0, 0.2]}`.
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum RemoteCommand {
+    GetCamera,
+
+    /// Fields left out keep their current values. `look_at` takes precedence over `rotation`.
+    SetCamera {
+        #[serde(default)]
+        position: Option<[f32; 3]>,
+        /// Quaternion, `[x, y, z, w]`
+        #[serde(default)]
+        rotation: Option<[f32; 4]>,
+        #[serde(default)]
+        look_at: Option<[f32; 3]>,
+        #[serde(default)]
+        vertical_fov: Option<f32>,
+    },
+
+    GetSun,
+
+    SetSun {
+        towards_sun: [f32; 3],
+    },
+
+    /// One of `low`, `medium`, `high`, `ultra`.
+    SetQuality {
+        preset: String,
+    },
+
+    SetExposure {
+        ev_shift: f32,
+    },
+
+    /// Saves an EXR capture once the GPU has rendered the next frame, to `path` within
+    /// `SCREENSHOT_DIR`. Replied to once the file is written.
+    Screenshot {
+        path: String,
+        /// One of `pre_tonemap` (the default), `pre_taa`, `post_tonemap`.
+        #[serde(default)]
+        source: Option<String>,
+    },
+}
+
+/// Where remote clients' screenshots go, relative to the working directory.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Resolves a screenshot path sent by a client within `SCREENSHOT_DIR`, so that clients
+/// can't write files elsewhere: absolute paths, and ones with `..` or `.`, are rejected.
+pub fn screenshot_path(path: &str) -> anyhow::Result<PathBuf> {
+    let path = Path::new(path);
+    anyhow::ensure!(
+        path.file_name().is_some()
+            && path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_))),
+        "Screenshot paths must be relative to {:?}, without `..` or `.`",
+        SCREENSHOT_DIR
+    );
+
+    Ok(Path::new(SCREENSHOT_DIR).join(path))
+}
+
+/// A command, and where to send the reply to it: a JSON object with `"ok": true` and
+/// any requested values, or `"ok": false` with an `error` message.
+pub struct RemoteRequest {
+    pub command: RemoteCommand,
+    reply: mpsc::Sender<serde_json::Value>,
+}
+
+impl RemoteRequest {
+    pub fn reply(self, result: anyhow::Result<serde_json::Value>) {
+        let response = match result {
+            Ok(mut value) => {
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("ok".into(), true.into());
+                }
+                value
+            }
+            Err(err) => json!({ "ok": false, "error": format!("{:#}", err) }),
+        };
+
+        // The client may have disconnected in the meantime.
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Accepts WebSocket connections on a background thread. Commands are queued
+/// for the main thread, which handles them once per frame via `poll`.
+pub struct RemoteControlServer {
+    requests: mpsc::Receiver<RemoteRequest>,
+}
+
+impl RemoteControlServer {
+    pub fn start(address: &str) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Binding the remote control server to {}", address))?;
+        log::info!("Remote control server listening on ws://{}", address);
+
+        let (sender, requests) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        log::warn!("Remote control connection failed: {}", err);
+                        continue;
+                    }
+                };
+
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    let peer = stream.peer_addr().ok();
+                    if let Err(err) = serve_connection(stream, sender) {
+                        log::warn!("Remote control connection {:?} closed: {:#}", peer, err);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { requests })
+    }
+
+    pub fn poll(&self) -> impl Iterator<Item = RemoteRequest> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+fn serve_connection(stream: TcpStream, sender: mpsc::Sender<RemoteRequest>) -> anyhow::Result<()> {
+    let mut socket =
+        tungstenite::accept(stream).map_err(|err| anyhow::anyhow!("Handshake failed: {}", err))?;
+
+    loop {
+        let text = match socket.read_message()? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+
+        let response = match serde_json::from_str::<RemoteCommand>(&text) {
+            Ok(command) => {
+                let (reply, response) = mpsc::channel();
+                sender
+                    .send(RemoteRequest { command, reply })
+                    .ok()
+                    .context("The viewer has shut down")?;
+                response.recv().context("The viewer has shut down")?
+            }
+            Err(err) => json!({ "ok": false, "error": format!("Invalid command: {}", err) }),
+        };
+
+        socket.write_message(Message::Text(response.to_string()))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_paths_stay_in_the_screenshot_dir() {
+        assert_eq!(
+            screenshot_path("shot.exr").unwrap(),
+            Path::new(SCREENSHOT_DIR).join("shot.exr")
+        );
+        assert_eq!(
+            screenshot_path("a/b.exr").unwrap(),
+            Path::new(SCREENSHOT_DIR).join("a").join("b.exr")
+        );
+
+        assert!(screenshot_path("").is_err());
+        assert!(screenshot_path("/etc/shot.exr").is_err());
+        assert!(screenshot_path("../shot.exr").is_err());
+        assert!(screenshot_path("a/../../shot.exr").is_err());
+        assert!(screenshot_path("./shot.exr").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use imgui::im_str;
+use kajiya::{rg::GpuResourceStats, world_renderer::WorldRenderer};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A summary of the GPU resources the renderer holds, refreshed once per second,
+/// so that it can be read while the numbers change every frame.
+#[derive(Default)]
+pub struct GpuResourceOverlay {
+    // Of the latest refresh, for the rates over the interval
+    last_refresh: Option<(Instant, GpuResourceStats)>,
+    lines: Vec<String>,
+}
+
+impl GpuResourceOverlay {
+    pub fn draw(&mut self, ui: &imgui::Ui, world_renderer: &WorldRenderer) {
+        let now = Instant::now();
+        if self
+            .last_refresh
+            .map_or(true, |(refreshed, _)| now - refreshed >= REFRESH_INTERVAL)
+        {
+            self.refresh(now, world_renderer);
+        }
+
+        imgui::Window::new(im_str!("GPU resources"))
+            .position([16.0, 16.0], imgui::Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .bg_alpha(0.6)
+            .build(ui, || {
+                for line in &self.lines {
+                    ui.text(line);
+                }
+            });
+    }
+
+    fn refresh(&mut self, now: Instant, world_renderer: &WorldRenderer) {
+        let stats = kajiya::rg::gpu_resource_stats();
+        let transients = kajiya::rg::transient_memory_stats();
+        let (vertex_bytes, vertex_capacity) = world_renderer.vertex_buffer_usage();
+
+        let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        let device = &stats.device;
+        let pipelines = &stats.pipelines;
+
+        let mut lines = vec![
+            format!(
+                "Images: {:.1}MB in {}",
+                mb(device.image_bytes),
+                device.image_count
+            ),
+            format!(
+                "Buffers: {:.1}MB in {}",
+                mb(device.buffer_bytes),
+                device.buffer_count
+            ),
+            format!(
+                "  meshes: {:.1}MB of {:.0}MB",
+                mb(vertex_bytes),
+                mb(vertex_capacity)
+            ),
+            format!(
+                "Acceleration structures: {:.1}MB in {}",
+                mb(device.acceleration_structure_bytes),
+                device.acceleration_structure_count
+            ),
+            format!(
+                "Graph transients: {:.1}MB, peak {:.1}MB",
+                mb(transients.watermark_bytes),
+                mb(transients.peak_watermark_bytes)
+            ),
+            format!(
+                "Pipelines: {} compute, {} raster, {} ray tracing",
+                pipelines.compute_pipelines,
+                pipelines.raster_pipelines,
+                pipelines.ray_tracing_pipelines
+            ),
+        ];
+
+        // Rates over the refresh interval, rather than since startup
+        let (prev_device, prev_pipelines, prev_frame_count) = match &self.last_refresh {
+            Some((_, prev)) => (prev.device, prev.pipelines, prev.frame_count),
+            None => Default::default(),
+        };
+        let frames = stats.frame_count.saturating_sub(prev_frame_count).max(1);
+        let lookups = pipelines.lookups - prev_pipelines.lookups;
+        let hits = pipelines.hits - prev_pipelines.hits;
+
+        lines.push(format!(
+            "Descriptor pools: {} live, {:.1} created per frame",
+            device.live_descriptor_pools,
+            (device.total_descriptor_pools_created - prev_device.total_descriptor_pools_created)
+                as f64
+                / frames as f64
+        ));
+
+        lines.push(if lookups > 0 {
+            format!(
+                "Pipeline cache hit rate: {:.1}%",
+                100.0 * hits as f64 / lookups as f64
+            )
+        } else {
+            "Pipeline cache hit rate: no lookups".to_owned()
+        });
+
+        self.lines = lines;
+        self.last_refresh = Some((now, stats));
+    }
+}
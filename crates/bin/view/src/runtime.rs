@@ -1,38 +1,53 @@
 #![allow(clippy::single_match)]
 
-use anyhow::Context;
-
-use dolly::prelude::*;
+use anyhow::Context as _;
 use kajiya::{
+    asset::mesh::VertexQuantization,
+    renderers::{hdr_capture::HdrCaptureSource, post::BloomFx},
     rg::GraphDebugHook,
-    world_renderer::{AddMeshOptions, MeshHandle, WorldRenderer},
+    world_renderer::{AddMeshOptions, MeshHandle, PunctualLightHandle, WorldRenderer},
+};
+use kajiya_simple::{
+    camera_controller::{
+        look_towards, CameraController, CameraInput, CameraInputMap, SmoothedCamera,
+    },
+    camera_path::{CameraPath, CameraPathPlayback, CameraPathTimeStep},
+    scene::{SceneCameraDesc, SceneDesc, SceneTerrainDesc},
+    *,
 };
-use kajiya_simple::*;
 
 use crate::{
+    benchmark::Benchmark,
     opt::Opt,
-    persisted::{MeshSource, SceneElement, SceneElementTransform, ShouldResetPathTracer as _},
-    scene::SceneDesc,
+    persisted::{
+        CameraControllerKind, MeshSource, RenderSettingsState, SceneElement, SceneElementTransform,
+        ShouldResetPathTracer as _,
+    },
+    remote::{screenshot_path, RemoteCommand, RemoteControlServer, RemoteRequest},
+    resource_overlay::GpuResourceOverlay,
     sequence::{CameraPlaybackSequence, MemOption, SequenceValue},
+    video::VideoRecorder,
     PersistedState,
 };
 
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    fs::File,
-    hash::{Hash, Hasher},
+    collections::{HashMap, HashSet},
     path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
 };
 
 pub const MAX_FPS_LIMIT: u32 = 256;
 
 pub struct RuntimeState {
-    pub camera: CameraRig,
+    pub camera: SmoothedCamera<Box<dyn CameraController>>,
+    camera_controller_kind: CameraControllerKind,
     pub mouse: MouseState,
     pub keyboard: KeyboardState,
-    pub keymap: KeyboardMap,
+    pub camera_input: CameraInputMap,
 
     pub show_gui: bool,
+    pub show_gpu_resource_overlay: bool,
+    pub gpu_resource_overlay: GpuResourceOverlay,
     pub sun_direction_interp: Vec3,
     pub left_click_edit_mode: LeftClickEditMode,
 
@@ -46,7 +61,27 @@ pub struct RuntimeState {
     sequence_playback_state: SequencePlaybackState,
     pub sequence_playback_speed: f32,
 
-    known_meshes: HashMap<PathBuf, MeshHandle>,
+    // Ends along with the camera path playback
+    benchmark: Option<Benchmark>,
+    video: Option<VideoRecorder>,
+    remote_control: Option<RemoteControlServer>,
+    // Replied to once the captures are written
+    pending_remote_screenshots: Vec<(RemoteRequest, mpsc::Receiver<anyhow::Result<()>>)>,
+
+    #[cfg(feature = "plugins")]
+    custom_pass_plugins: kajiya::renderers::custom_pass_plugins::CustomPassPlugins,
+
+    known_meshes: HashMap<MeshSource, MeshHandle>,
+    // Of meshes baked from source files
+    mesh_quantization: VertexQuantization,
+
+    // Created from `persisted.scene.lights`
+    scene_lights: Vec<PunctualLightHandle>,
+
+    // Source mesh files which use each watched file (the mesh itself, its buffers, textures, etc.)
+    watched_mesh_files: HashMap<PathBuf, Vec<PathBuf>>,
+    // Written to by the file watcher
+    changed_mesh_files: Arc<Mutex<HashSet<PathBuf>>>,
 }
 
 enum SequencePlaybackState {
@@ -55,19 +90,28 @@ enum SequencePlaybackState {
         t: f32,
         sequence: CameraPlaybackSequence,
     },
+    PlayingCameraPath(CameraPathPlayback),
 }
 
 impl RuntimeState {
     pub fn new(
         persisted: &mut PersistedState,
         world_renderer: &mut WorldRenderer,
-        _opt: &Opt,
+        opt: &Opt,
     ) -> Self {
-        let camera: CameraRig = CameraRig::builder()
-            .with(Position::new(persisted.camera.position))
-            .with(YawPitch::new().rotation_quat(persisted.camera.rotation))
-            .with(Smooth::default())
-            .build();
+        // An explicit preset on the command line wins over the settings from the last session.
+        if opt.renderer.quality_preset.is_none() {
+            world_renderer.render_quality = persisted.render.quality.to_render_quality();
+        }
+        world_renderer.bilateral_upsample = persisted.render.bilateral_upsample.to_params();
+        world_renderer.output_calibration = persisted.render.output_calibration.to_calibration();
+        world_renderer.debug_view = persisted.render.debug_view();
+
+        let camera_controller_kind = persisted.movement.camera_controller;
+        let camera = SmoothedCamera::new(
+            camera_controller_kind.create(persisted.camera.position, persisted.camera.rotation),
+            persisted.movement.camera_smoothness,
+        );
 
         // Mitsuba match
         /*let mut camera = camera::FirstPersonCamera::new(Vec3::new(-2.0, 4.0, 8.0));
@@ -77,35 +121,26 @@ impl RuntimeState {
         let mouse: MouseState = Default::default();
         let keyboard: KeyboardState = Default::default();
 
-        let keymap = KeyboardMap::new()
-            .bind(VirtualKeyCode::W, KeyMap::new("move_fwd", 1.0))
-            .bind(VirtualKeyCode::S, KeyMap::new("move_fwd", -1.0))
-            .bind(VirtualKeyCode::A, KeyMap::new("move_right", -1.0))
-            .bind(VirtualKeyCode::D, KeyMap::new("move_right", 1.0))
-            .bind(VirtualKeyCode::Q, KeyMap::new("move_up", -1.0))
-            .bind(VirtualKeyCode::E, KeyMap::new("move_up", 1.0))
-            .bind(
-                VirtualKeyCode::LShift,
-                KeyMap::new("boost", 1.0).activation_time(0.25),
-            )
-            .bind(
-                VirtualKeyCode::LControl,
-                KeyMap::new("boost", -1.0).activation_time(0.5),
-            );
+        let camera_input = CameraInputMap::wasd();
 
         let sun_direction_interp = persisted.light.sun.controller.towards_sun();
 
         let mut res = Self {
             camera,
+            camera_controller_kind,
             mouse,
             keyboard,
-            keymap,
+            camera_input,
 
             show_gui: false,
+            show_gpu_resource_overlay: false,
+            gpu_resource_overlay: Default::default(),
             sun_direction_interp,
             left_click_edit_mode: LeftClickEditMode::MoveSun,
 
-            max_fps: MAX_FPS_LIMIT,
+            max_fps: opt.renderer.max_fps.map_or(MAX_FPS_LIMIT, |max_fps| {
+                (max_fps as u32).clamp(1, MAX_FPS_LIMIT)
+            }),
             locked_rg_debug_hook: None,
             grab_cursor_pos: Default::default(),
 
@@ -115,7 +150,22 @@ impl RuntimeState {
             sequence_playback_state: SequencePlaybackState::NotPlaying,
             sequence_playback_speed: 1.0,
 
+            benchmark: None,
+            video: None,
+            remote_control: None,
+            pending_remote_screenshots: Vec::new(),
+
+            #[cfg(feature = "plugins")]
+            custom_pass_plugins: Default::default(),
+
             known_meshes: Default::default(),
+            mesh_quantization: VertexQuantization {
+                positions: opt.quantize_meshes,
+                uvs: opt.quantize_meshes,
+            },
+            scene_lights: Default::default(),
+            watched_mesh_files: Default::default(),
+            changed_mesh_files: Default::default(),
         };
 
         // Load meshes that the persisted scene was referring to
@@ -133,6 +183,20 @@ impl RuntimeState {
             }
         });
 
+        res.scene_lights = persisted
+            .scene
+            .lights
+            .iter()
+            .map(|light| world_renderer.add_punctual_light(light.punctual_light()))
+            .collect();
+
+        for probe in &persisted.scene.light_probes {
+            let handle = world_renderer.light_probes.add(probe.light_probe());
+            if probe.irradiance.is_none() {
+                world_renderer.light_probes.bake(handle);
+            }
+        }
+
         // Load the IBL too
         if let Some(ibl) = persisted.scene.ibl.as_ref() {
             if world_renderer.ibl.load_image(ibl).is_err() {
@@ -140,6 +204,24 @@ impl RuntimeState {
             }
         }
 
+        if let Some(lens_dirt) = persisted.scene.lens_dirt.as_ref() {
+            let loaded = world_renderer
+                .post
+                .stack
+                .get_mut::<BloomFx>()
+                .map_or(false, |bloom| bloom.load_lens_dirt(lens_dirt).is_ok());
+
+            if !loaded {
+                persisted.scene.lens_dirt = None;
+            }
+        }
+
+        if let Some(grading_lut) = persisted.scene.grading_lut.as_ref() {
+            if world_renderer.post.load_grading_lut(grading_lut).is_err() {
+                persisted.scene.grading_lut = None;
+            }
+        }
+
         res
     }
 
@@ -151,6 +233,24 @@ impl RuntimeState {
         for elem in persisted.scene.elements.drain(..) {
             world_renderer.remove_instance(elem.instance);
         }
+
+        for light in self.scene_lights.drain(..) {
+            world_renderer.remove_punctual_light(light);
+        }
+
+        let light_probes: Vec<_> = world_renderer
+            .light_probes
+            .iter()
+            .map(|(handle, _)| handle)
+            .collect();
+        for probe in light_probes {
+            world_renderer.light_probes.remove(probe);
+        }
+
+        persisted.scene.lights.clear();
+        persisted.scene.light_probes.clear();
+        persisted.scene.camera_presets.clear();
+        persisted.scene.camera_paths.clear();
     }
 
     pub fn load_scene(
@@ -159,72 +259,123 @@ impl RuntimeState {
         world_renderer: &mut WorldRenderer,
         scene_path: impl Into<PathBuf>,
     ) -> anyhow::Result<()> {
-        let scene_path = scene_path.into();
-        let scene_desc: SceneDesc = ron::de::from_reader(
-            File::open(&scene_path)
-                .with_context(|| format!("Opening scene file {:?}", scene_path))?,
-        )?;
+        let scene_desc = SceneDesc::load(scene_path.into())?;
 
         self.clear_scene(persisted, world_renderer);
 
-        for instance in scene_desc.instances {
-            let mesh_path = canonical_path_from_vfs(&instance.mesh)
-                .with_context(|| format!("Mesh path: {:?}", instance.mesh))
-                .expect("valid mesh path");
-
-            let mesh = self
-                .load_mesh(world_renderer, &MeshSource::File(mesh_path.clone()))
-                .with_context(|| format!("Mesh path: {:?}", instance.mesh))
-                .expect("valid mesh");
-
-            let transform = SceneElementTransform {
-                position: instance.position.into(),
-                rotation_euler_degrees: instance.rotation.into(),
-                scale: instance.scale.into(),
-            };
-
-            let render_instance = world_renderer.add_instance(mesh, transform.affine_transform());
+        let loaded = scene_desc.instantiate(world_renderer, |world_renderer, mesh| {
+            let mesh_path = canonical_path_from_vfs(mesh)?;
+            self.load_mesh(world_renderer, &MeshSource::File(mesh_path))
+        })?;
 
+        for (instance, render_instance) in scene_desc.instances.iter().zip(loaded.instances) {
             persisted.scene.elements.push(SceneElement {
-                source: MeshSource::File(mesh_path),
+                source: MeshSource::File(canonical_path_from_vfs(&instance.mesh)?),
                 instance: render_instance,
-                transform,
+                transform: SceneElementTransform {
+                    position: instance.position.into(),
+                    rotation_euler_degrees: instance.rotation.into(),
+                    scale: instance.scale.into(),
+                },
+                material_overrides: Default::default(),
             });
         }
 
+        self.scene_lights = loaded.lights;
+        persisted.scene.lights = scene_desc.lights;
+        persisted.scene.light_probes = scene_desc.light_probes;
+
+        // Terrain chunks become regular scene elements referring to their baked meshes.
+        if let Some(terrain) = &scene_desc.terrain {
+            for chunk_mesh_path in self.bake_terrain(terrain)? {
+                let source = MeshSource::Cache(chunk_mesh_path);
+                let mesh = self.load_mesh(world_renderer, &source)?;
+
+                persisted.scene.elements.push(SceneElement {
+                    source,
+                    instance: world_renderer.add_instance(mesh, terrain.affine_transform()),
+                    transform: SceneElementTransform {
+                        position: terrain.position.into(),
+                        ..SceneElementTransform::IDENTITY
+                    },
+                    material_overrides: Default::default(),
+                });
+            }
+        }
+
+        if let Some(sun) = &scene_desc.sun {
+            persisted
+                .light
+                .sun
+                .controller
+                .set_towards_sun(sun.towards_sun());
+            persisted.light.sun.angular_diameter_degrees = sun.angular_diameter_degrees;
+        }
+
+        if let Some(ibl) = scene_desc.sky.as_ref().and_then(|sky| sky.ibl.as_ref()) {
+            persisted.scene.ibl = Some(canonical_path_from_vfs(ibl)?);
+        }
+
+        if let Some(camera) = scene_desc.cameras.first() {
+            self.apply_camera_preset(persisted, camera);
+        }
+        persisted.scene.camera_presets = scene_desc.cameras;
+        persisted.scene.camera_paths = scene_desc.camera_paths;
+
         Ok(())
     }
 
+    pub fn apply_camera_preset(
+        &mut self,
+        persisted: &mut PersistedState,
+        camera: &SceneCameraDesc,
+    ) {
+        self.camera
+            .set_position_rotation(camera.position(), camera.rotation());
+
+        if let Some(vertical_fov) = camera.vertical_fov {
+            persisted.camera.vertical_fov = vertical_fov;
+        }
+    }
+
     fn update_camera(&mut self, persisted: &mut PersistedState, ctx: &FrameContext) {
-        let smooth = self.camera.driver_mut::<Smooth>();
-        if ctx.world_renderer.render_mode == RenderMode::Reference {
-            smooth.position_smoothness = 0.0;
-            smooth.rotation_smoothness = 0.0;
-        } else {
-            smooth.position_smoothness = persisted.movement.camera_smoothness;
-            smooth.rotation_smoothness = persisted.movement.camera_smoothness;
+        // Switch controllers in place, picking up from the current pose.
+        if persisted.movement.camera_controller != self.camera_controller_kind {
+            self.camera_controller_kind = persisted.movement.camera_controller;
+            self.camera.controller = self
+                .camera_controller_kind
+                .create(self.camera.position(), self.camera.rotation());
         }
 
+        let smoothness = if ctx.world_renderer.render_mode == RenderMode::Reference {
+            0.0
+        } else {
+            persisted.movement.camera_smoothness
+        };
+        self.camera.position_smoothness = smoothness;
+        self.camera.rotation_smoothness = smoothness;
+
+        let look_button = 1 << self.camera_input.look_button;
+
         // When starting camera rotation, hide the mouse cursor, and capture it to the window.
-        if (self.mouse.buttons_pressed & (1 << 2)) != 0 {
+        if (self.mouse.buttons_pressed & look_button) != 0 {
             let _ = ctx.window.set_cursor_grab(true);
             self.grab_cursor_pos = self.mouse.physical_position;
             ctx.window.set_cursor_visible(false);
         }
 
         // When ending camera rotation, release the cursor.
-        if (self.mouse.buttons_released & (1 << 2)) != 0 {
+        if (self.mouse.buttons_released & look_button) != 0 {
             let _ = ctx.window.set_cursor_grab(false);
             ctx.window.set_cursor_visible(true);
         }
 
-        let input = self.keymap.map(&self.keyboard, ctx.dt_filtered);
-        let move_vec = self.camera.final_transform.rotation
-            * Vec3::new(input["move_right"], input["move_up"], -input["move_fwd"])
-                .clamp_length_max(1.0)
-            * 4.0f32.powf(input["boost"]);
+        self.camera_input.move_speed = persisted.movement.camera_speed;
+        let mut input = self
+            .camera_input
+            .map(&self.keyboard, &self.mouse, ctx.dt_filtered);
 
-        if (self.mouse.buttons_held & (1 << 2)) != 0 {
+        if self.camera_input.is_looking(&self.mouse) {
             // While we're rotating, the cursor should not move, so that upon revealing it,
             // it will be where we started the rotation motion at.
             let _ = ctx
@@ -233,35 +384,22 @@ impl RuntimeState {
                     self.grab_cursor_pos.x,
                     self.grab_cursor_pos.y,
                 ));
-
-            let sensitivity = 0.1;
-            self.camera.driver_mut::<YawPitch>().rotate_yaw_pitch(
-                -sensitivity * self.mouse.delta.x,
-                -sensitivity * self.mouse.delta.y,
-            );
         }
 
-        self.camera
-            .driver_mut::<Position>()
-            .translate(move_vec * ctx.dt_filtered * persisted.movement.camera_speed);
-
         if let SequencePlaybackState::Playing { t, sequence } = &mut self.sequence_playback_state {
-            let smooth = self.camera.driver_mut::<Smooth>();
             if *t <= 0.0 {
-                smooth.position_smoothness = 0.0;
-                smooth.rotation_smoothness = 0.0;
-            } else {
-                smooth.position_smoothness = persisted.movement.camera_smoothness;
-                smooth.rotation_smoothness = persisted.movement.camera_smoothness;
+                self.camera.position_smoothness = 0.0;
+                self.camera.rotation_smoothness = 0.0;
             }
 
             if let Some(value) = sequence.sample(t.max(0.0)) {
-                self.camera.driver_mut::<Position>().position = value.camera_position;
-                self.camera
-                    .driver_mut::<YawPitch>()
-                    .set_rotation_quat(dolly::util::look_at::<dolly::handedness::RightHanded>(
-                        value.camera_direction,
-                    ));
+                // The sequence drives the camera; ignore the user's input.
+                input = CameraInput::default();
+
+                self.camera.set_position_rotation(
+                    value.camera_position,
+                    look_towards(value.camera_direction),
+                );
                 persisted
                     .light
                     .sun
@@ -274,10 +412,42 @@ impl RuntimeState {
             }
         }
 
-        self.camera.update(ctx.dt_filtered);
+        if let SequencePlaybackState::PlayingCameraPath(playback) =
+            &mut self.sequence_playback_state
+        {
+            if self
+                .benchmark
+                .as_ref()
+                .map_or(false, Benchmark::is_warming_up)
+                || self
+                    .video
+                    .as_ref()
+                    .map_or(false, VideoRecorder::is_warming_up)
+            {
+                playback.seek(0.0);
+            }
+
+            if let Some(sample) = playback.advance(ctx.dt_filtered * self.sequence_playback_speed) {
+                // Follow the path exactly, so that playback is repeatable.
+                self.camera.position_smoothness = 0.0;
+                self.camera.rotation_smoothness = 0.0;
+                input = CameraInput::default();
+
+                self.camera
+                    .set_position_rotation(sample.position, sample.rotation);
+
+                if let Some(vertical_fov) = sample.vertical_fov {
+                    persisted.camera.vertical_fov = vertical_fov;
+                }
+            } else {
+                self.sequence_playback_state = SequencePlaybackState::NotPlaying;
+            }
+        }
+
+        self.camera.update(&input, ctx.dt_filtered);
 
-        persisted.camera.position = self.camera.final_transform.position;
-        persisted.camera.rotation = self.camera.final_transform.rotation;
+        persisted.camera.position = self.camera.position();
+        persisted.camera.rotation = self.camera.rotation();
 
         if self.keyboard.was_just_pressed(VirtualKeyCode::C) {
             println!(
@@ -324,6 +494,12 @@ impl RuntimeState {
             self.reset_path_tracer = true;
         }
 
+        // Overrides the sun direction, and moves it by itself.
+        let time_of_day = &ctx.world_renderer.time_of_day;
+        if time_of_day.enabled && time_of_day.hours_per_second != 0.0 {
+            self.reset_path_tracer = true;
+        }
+
         let sun_interp_t = if ctx.world_renderer.render_mode == RenderMode::Reference {
             1.0
         } else {
@@ -333,7 +509,8 @@ impl RuntimeState {
         self.sun_direction_interp =
             Vec3::lerp(self.sun_direction_interp, sun_direction, sun_interp_t).normalize();
 
-        ctx.world_renderer.sun_size_multiplier = persisted.light.sun.size_multiplier;
+        ctx.world_renderer.sun_angular_diameter_degrees =
+            persisted.light.sun.angular_diameter_degrees;
     }
 
     fn update_lights(&mut self, persisted: &mut PersistedState, ctx: &mut FrameContext) {
@@ -350,6 +527,27 @@ impl RuntimeState {
             };
         }
 
+        if self.keyboard.was_just_pressed(VirtualKeyCode::F12) {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |t| t.as_secs());
+
+            let (prefix, source) = if ctx.world_renderer.render_mode == RenderMode::Reference {
+                ("reference", HdrCaptureSource::PreTonemap)
+            } else if self.keyboard.is_down(VirtualKeyCode::LShift) {
+                ("capture-pre-taa", HdrCaptureSource::PreTaa)
+            } else {
+                ("capture", HdrCaptureSource::PreTonemap)
+            };
+
+            ctx.world_renderer
+                .capture_hdr_exr(format!("{}-{}.exr", prefix, timestamp), source);
+        }
+
+        if self.keyboard.was_just_pressed(VirtualKeyCode::F) {
+            ctx.world_renderer.freeze_frame = !ctx.world_renderer.freeze_frame;
+        }
+
         if self.keyboard.was_just_pressed(VirtualKeyCode::L) {
             persisted.light.enable_emissive = !persisted.light.enable_emissive;
         }
@@ -395,6 +593,16 @@ impl RuntimeState {
         }*/
     }
 
+    // Picks up probes added in the GUI, and the results of bakes.
+    fn update_light_probes(&mut self, persisted: &mut PersistedState, ctx: &mut FrameContext) {
+        persisted.scene.light_probes = ctx
+            .world_renderer
+            .light_probes
+            .iter()
+            .map(|(_, probe)| probe.into())
+            .collect();
+    }
+
     fn update_objects(&mut self, persisted: &mut PersistedState, ctx: &mut FrameContext) {
         let emissive_toggle_mult = if persisted.light.enable_emissive {
             1.0
@@ -403,9 +611,13 @@ impl RuntimeState {
         };
 
         for elem in persisted.scene.elements.iter() {
-            ctx.world_renderer
-                .get_instance_dynamic_parameters_mut(elem.instance)
-                .emissive_multiplier = persisted.light.emissive_multiplier * emissive_toggle_mult;
+            let params = ctx
+                .world_renderer
+                .get_instance_dynamic_parameters_mut(elem.instance);
+            params.emissive_multiplier = persisted.light.emissive_multiplier * emissive_toggle_mult;
+            params.base_color_tint = elem.material_overrides.base_color_tint.into();
+            params.roughness_multiplier = elem.material_overrides.roughness_multiplier;
+
             ctx.world_renderer
                 .set_instance_transform(elem.instance, elem.transform.affine_transform());
         }
@@ -416,27 +628,45 @@ impl RuntimeState {
         mut ctx: FrameContext,
         persisted: &mut PersistedState,
     ) -> WorldFrameDesc {
-        // Limit framerate. Not particularly precise.
-        if self.max_fps != MAX_FPS_LIMIT {
-            std::thread::sleep(std::time::Duration::from_micros(
-                1_000_000 / self.max_fps as u64,
-            ));
+        ctx.frame_timing.max_fps = (self.max_fps != MAX_FPS_LIMIT).then(|| self.max_fps as f32);
+
+        if let Some(benchmark) = self.benchmark.as_mut() {
+            benchmark.record_frame();
         }
 
         self.keyboard.update(ctx.events);
         self.mouse.update(ctx.events);
         self.handle_file_drop_events(persisted, ctx.world_renderer, ctx.events);
+        self.reload_changed_meshes(persisted, ctx.world_renderer);
+
+        #[cfg(feature = "plugins")]
+        self.custom_pass_plugins
+            .reload_changed(&mut ctx.world_renderer.custom_passes);
 
         let orig_persisted_state = persisted.clone();
         let orig_render_overrides = ctx.world_renderer.render_overrides;
 
         self.do_gui(persisted, &mut ctx);
+        self.handle_remote_commands(persisted, &mut ctx);
         self.update_lights(persisted, &mut ctx);
+        self.update_light_probes(persisted, &mut ctx);
         self.update_objects(persisted, &mut ctx);
         self.update_sun(persisted, &mut ctx);
 
         self.update_camera(persisted, &ctx);
 
+        persisted.render = RenderSettingsState::from_world_renderer(ctx.world_renderer);
+
+        if self.benchmark.is_some() && !self.is_sequence_playing() {
+            let benchmark = self.benchmark.take().unwrap();
+            if let Err(err) = benchmark.write(ctx.render_extent) {
+                log::error!("Failed to save the benchmark results: {:#}", err);
+            }
+            ctx.request_exit();
+        }
+
+        self.update_video_recording(&mut ctx);
+
         if self.keyboard.was_just_pressed(VirtualKeyCode::K)
             || (self.mouse.buttons_pressed & (1 << 1)) != 0
         {
@@ -463,6 +693,11 @@ impl RuntimeState {
             persisted.exposure.dynamic_adaptation_low_clip;
         ctx.world_renderer.dynamic_exposure.histogram_clipping.high =
             persisted.exposure.dynamic_adaptation_high_clip;
+        ctx.world_renderer.dynamic_exposure.ev_min = persisted.exposure.dynamic_adaptation_ev_min;
+        ctx.world_renderer.dynamic_exposure.ev_max = persisted.exposure.dynamic_adaptation_ev_max;
+
+        ctx.world_renderer.physical_camera =
+            persisted.exposure.physical_camera.to_physical_camera();
 
         if persisted.should_reset_path_tracer(&orig_persisted_state)
             || ctx.world_renderer.render_overrides != orig_render_overrides
@@ -478,18 +713,21 @@ impl RuntimeState {
             self.reset_path_tracer = false;
         }
 
+        let physical_camera = &ctx.world_renderer.physical_camera;
+        let vertical_fov = if physical_camera.enabled {
+            physical_camera.vertical_fov(ctx.aspect_ratio())
+        } else {
+            persisted.camera.vertical_fov
+        };
+
         let lens = CameraLens {
             aspect_ratio: ctx.aspect_ratio(),
-            vertical_fov: persisted.camera.vertical_fov,
+            vertical_fov,
             ..Default::default()
         };
 
         WorldFrameDesc {
-            camera_matrices: self
-                .camera
-                .final_transform
-                .into_position_rotation()
-                .through(&lens),
+            camera_matrices: (self.camera.position(), self.camera.rotation()).through(&lens),
             render_extent: ctx.render_extent,
             sun_direction: self.sun_direction_interp,
         }
@@ -498,7 +736,7 @@ impl RuntimeState {
     pub fn is_sequence_playing(&self) -> bool {
         matches!(
             &self.sequence_playback_state,
-            SequencePlaybackState::Playing { .. }
+            SequencePlaybackState::Playing { .. } | SequencePlaybackState::PlayingCameraPath(_)
         )
     }
 
@@ -521,6 +759,222 @@ impl RuntimeState {
         };
     }
 
+    /// Plays back one of the camera paths of the loaded scene.
+    pub fn play_camera_path(
+        &mut self,
+        persisted: &PersistedState,
+        name: &str,
+        time_step: CameraPathTimeStep,
+    ) -> anyhow::Result<()> {
+        let desc = persisted
+            .scene
+            .camera_paths
+            .iter()
+            .find(|path| path.name == name)
+            .with_context(|| format!("No camera path named {:?} in the scene", name))?;
+
+        self.sequence_playback_state = SequencePlaybackState::PlayingCameraPath(
+            CameraPathPlayback::new(CameraPath::from_desc(desc)?, time_step),
+        );
+
+        Ok(())
+    }
+
+    /// Plays back a camera path once, recording the frame timings, and then exits.
+    pub fn start_benchmark(
+        &mut self,
+        persisted: &PersistedState,
+        camera_path: &str,
+        time_step: CameraPathTimeStep,
+        benchmark: Benchmark,
+    ) -> anyhow::Result<()> {
+        self.play_camera_path(persisted, camera_path, time_step)?;
+        self.benchmark = Some(benchmark);
+        Ok(())
+    }
+
+    #[cfg(feature = "plugins")]
+    pub fn load_custom_pass_plugin(
+        &mut self,
+        world_renderer: &mut WorldRenderer,
+        point: kajiya::renderers::custom_passes::CustomPassPoint,
+        path: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        self.custom_pass_plugins
+            .load(&mut world_renderer.custom_passes, point, path)
+            .with_context(|| format!("Loading the custom pass plugin {:?}", path))
+    }
+
+    pub fn start_remote_control(&mut self, address: &str) -> anyhow::Result<()> {
+        self.remote_control = Some(RemoteControlServer::start(address)?);
+        Ok(())
+    }
+
+    fn handle_remote_commands(&mut self, persisted: &mut PersistedState, ctx: &mut FrameContext) {
+        for (request, written) in std::mem::take(&mut self.pending_remote_screenshots) {
+            match written.try_recv() {
+                Ok(result) => request.reply(result.map(|()| serde_json::json!({}))),
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.pending_remote_screenshots.push((request, written))
+                }
+                Err(mpsc::TryRecvError::Disconnected) => request.reply(Err(anyhow::anyhow!(
+                    "The capture was dropped before being written"
+                ))),
+            }
+        }
+
+        let requests: Vec<_> = if let Some(remote_control) = self.remote_control.as_ref() {
+            remote_control.poll().collect()
+        } else {
+            return;
+        };
+
+        for request in requests {
+            match &request.command {
+                RemoteCommand::Screenshot { path, source } => {
+                    match Self::start_remote_screenshot(path, source.as_deref(), ctx) {
+                        Ok(written) => self.pending_remote_screenshots.push((request, written)),
+                        Err(err) => request.reply(Err(err)),
+                    }
+                }
+                command => {
+                    let result = self.handle_remote_command(command, persisted, ctx);
+                    request.reply(result);
+                }
+            }
+        }
+    }
+
+    fn start_remote_screenshot(
+        path: &str,
+        source: Option<&str>,
+        ctx: &mut FrameContext,
+    ) -> anyhow::Result<mpsc::Receiver<anyhow::Result<()>>> {
+        let source = match source {
+            None | Some("pre_tonemap") => HdrCaptureSource::PreTonemap,
+            Some("pre_taa") => HdrCaptureSource::PreTaa,
+            Some("post_tonemap") => HdrCaptureSource::PostTonemap,
+            Some(other) => anyhow::bail!("Unknown capture source {:?}", other),
+        };
+
+        let path = screenshot_path(path)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Creating {:?}", dir))?;
+        }
+
+        Ok(ctx.world_renderer.capture_hdr_exr(path, source))
+    }
+
+    fn handle_remote_command(
+        &mut self,
+        command: &RemoteCommand,
+        persisted: &mut PersistedState,
+        ctx: &mut FrameContext,
+    ) -> anyhow::Result<serde_json::Value> {
+        match command {
+            RemoteCommand::GetCamera => Ok(serde_json::json!({
+                "position": persisted.camera.position.to_array(),
+                "rotation": <[f32; 4]>::from(persisted.camera.rotation),
+                "vertical_fov": persisted.camera.vertical_fov,
+            })),
+            RemoteCommand::SetCamera {
+                position,
+                rotation,
+                look_at,
+                vertical_fov,
+            } => {
+                let position = position.map_or(self.camera.position(), Vec3::from);
+                let rotation = match (look_at, rotation) {
+                    (Some(look_at), _) => {
+                        let direction = Vec3::from(*look_at) - position;
+                        anyhow::ensure!(
+                            direction.length_squared() > 0.0,
+                            "look_at must differ from the camera position"
+                        );
+                        look_towards(direction)
+                    }
+                    (None, Some(rotation)) => Quat::from_array(*rotation).normalize(),
+                    (None, None) => self.camera.rotation(),
+                };
+
+                // Skip the smoothing, so that the next frame is rendered from exactly here.
+                self.camera.set_position_rotation(position, rotation);
+                self.camera.snap();
+
+                if let Some(vertical_fov) = vertical_fov {
+                    persisted.camera.vertical_fov = *vertical_fov;
+                }
+
+                Ok(serde_json::json!({}))
+            }
+            RemoteCommand::GetSun => Ok(serde_json::json!({
+                "towards_sun": persisted.light.sun.controller.towards_sun().to_array(),
+            })),
+            RemoteCommand::SetSun { towards_sun } => {
+                let towards_sun = Vec3::from(*towards_sun);
+                anyhow::ensure!(
+                    towards_sun.length_squared() > 0.0,
+                    "The sun direction must not be zero"
+                );
+                persisted
+                    .light
+                    .sun
+                    .controller
+                    .set_towards_sun(towards_sun.normalize());
+                Ok(serde_json::json!({}))
+            }
+            RemoteCommand::SetQuality { preset } => {
+                ctx.world_renderer.render_quality = RenderQuality::from_preset(preset.parse()?);
+                Ok(serde_json::json!({}))
+            }
+            RemoteCommand::SetExposure { ev_shift } => {
+                persisted.exposure.ev_shift = *ev_shift;
+                Ok(serde_json::json!({}))
+            }
+            RemoteCommand::Screenshot { .. } => {
+                unreachable!("screenshots are replied to once written; see handle_remote_commands")
+            }
+        }
+    }
+
+    /// Plays back a camera path once, encoding it to a video, and then exits.
+    pub fn start_video_recording(
+        &mut self,
+        persisted: &PersistedState,
+        camera_path: &str,
+        time_step: CameraPathTimeStep,
+        video: VideoRecorder,
+    ) -> anyhow::Result<()> {
+        self.play_camera_path(persisted, camera_path, time_step)?;
+        self.video = Some(video);
+        Ok(())
+    }
+
+    fn update_video_recording(&mut self, ctx: &mut FrameContext) {
+        let is_playing = self.is_sequence_playing();
+        let video = if let Some(video) = self.video.as_mut() {
+            video
+        } else {
+            return;
+        };
+
+        if let Err(err) = video.receive_frames(ctx.world_renderer) {
+            log::error!("Failed to record the video: {:#}", err);
+            self.video = None;
+            ctx.request_exit();
+            return;
+        }
+
+        if is_playing {
+            video.request_frame(ctx.world_renderer);
+        } else if !video.is_waiting_for_frames() {
+            if let Err(err) = self.video.take().unwrap().finish() {
+                log::error!("Failed to save the video: {:#}", err);
+            }
+            ctx.request_exit();
+        }
+    }
+
     pub fn add_sequence_keyframe(&mut self, persisted: &mut PersistedState) {
         persisted.sequence.add_keyframe(
             self.active_camera_key,
@@ -544,20 +998,19 @@ impl RuntimeState {
         };
 
         if let Some(value) = persisted.sequence.to_playback().sample(exact_item.t) {
-            self.camera.driver_mut::<Position>().position = exact_item
-                .value
-                .camera_position
-                .unwrap_or(value.camera_position);
-            self.camera
-                .driver_mut::<YawPitch>()
-                .set_rotation_quat(dolly::util::look_at::<dolly::handedness::RightHanded>(
+            self.camera.set_position_rotation(
+                exact_item
+                    .value
+                    .camera_position
+                    .unwrap_or(value.camera_position),
+                look_towards(
                     exact_item
                         .value
                         .camera_direction
                         .unwrap_or(value.camera_direction),
-                ));
-
-            self.camera.update(1e10);
+                ),
+            );
+            self.camera.snap();
 
             persisted
                 .light
@@ -588,49 +1041,162 @@ impl RuntimeState {
         self.active_camera_key = None;
     }
 
+    /// Starts loading the mesh in the background, unless it's been loaded before.
+    /// Its instances show up once it's loaded; see `WorldRenderer::add_mesh_in_background`.
     pub(crate) fn load_mesh(
         &mut self,
         world_renderer: &mut WorldRenderer,
         source: &MeshSource,
     ) -> anyhow::Result<MeshHandle> {
+        if let Some(&mesh) = self.known_meshes.get(source) {
+            return Ok(mesh);
+        }
+
         log::info!("Loading a mesh from {:?}", source);
 
-        let path = match source {
+        let mesh = match source {
             MeshSource::File(path) => {
-                fn calculate_hash(t: &PathBuf) -> u64 {
-                    let mut s = DefaultHasher::new();
-                    t.hash(&mut s);
-                    s.finish()
-                }
+                anyhow::ensure!(path.exists(), "File not found: {:?}", path);
+                self.watch_mesh_source(path);
+
+                let path = path.clone();
+                let quantization = self.mesh_quantization;
+
+                world_renderer.add_baked_mesh_in_background(
+                    move || {
+                        // Keyed by contents, so edited source files get re-baked
+                        let cached_mesh_name =
+                            kajiya_asset_pipe::mesh_cache_key(&path, 1.0, quantization)?;
+                        let cached_mesh_path =
+                            PathBuf::from(format!("/cache/{}.mesh", cached_mesh_name));
+
+                        if !canonical_path_from_vfs(&cached_mesh_path)
+                            .map_or(false, |path| path.exists())
+                        {
+                            kajiya_asset_pipe::process_mesh_asset(
+                                kajiya_asset_pipe::MeshAssetProcessParams {
+                                    path,
+                                    output_name: cached_mesh_name,
+                                    scale: 1.0,
+                                    quantization,
+                                },
+                            )?;
+                        }
 
-                let path_hash = match path.canonicalize() {
-                    Ok(canonical) => calculate_hash(&canonical),
-                    Err(_) => calculate_hash(path),
-                };
+                        Ok(cached_mesh_path)
+                    },
+                    AddMeshOptions::new(),
+                )
+            }
+            MeshSource::Cache(path) => {
+                canonical_path_from_vfs(path)?;
 
-                let cached_mesh_name = format!("{:8.8x}", path_hash);
-                let cached_mesh_path = PathBuf::from(format!("/cache/{}.mesh", cached_mesh_name));
-
-                if !canonical_path_from_vfs(&cached_mesh_path).map_or(false, |path| path.exists()) {
-                    kajiya_asset_pipe::process_mesh_asset(
-                        kajiya_asset_pipe::MeshAssetProcessParams {
-                            path: path.clone(),
-                            output_name: cached_mesh_name,
-                            scale: 1.0,
-                        },
-                    )?;
-                }
+                let path = path.clone();
+                world_renderer.add_baked_mesh_in_background(move || Ok(path), AddMeshOptions::new())
+            }
+        };
+
+        self.known_meshes.insert(source.clone(), mesh);
+        Ok(mesh)
+    }
+
+    /// Returns the paths of the baked chunk meshes.
+    fn bake_terrain(&self, terrain: &SceneTerrainDesc) -> anyhow::Result<Vec<PathBuf>> {
+        let desc = terrain.terrain_desc()?;
+
+        // Keyed by contents, like meshes
+        let cached_terrain_name = kajiya_asset_pipe::terrain_cache_key(&desc)?;
+        let chunk_mesh_paths: Vec<PathBuf> =
+            kajiya_asset_pipe::terrain_chunk_output_names(&desc, &cached_terrain_name)
+                .into_iter()
+                .map(|name| PathBuf::from(format!("/cache/{}.mesh", name)))
+                .collect();
+
+        if !chunk_mesh_paths
+            .iter()
+            .all(|path| canonical_path_from_vfs(path).map_or(false, |path| path.exists()))
+        {
+            kajiya_asset_pipe::process_terrain_asset(
+                kajiya_asset_pipe::TerrainAssetProcessParams {
+                    desc,
+                    output_name: cached_terrain_name,
+                },
+            )?;
+        }
 
-                cached_mesh_path
+        Ok(chunk_mesh_paths)
+    }
+
+    fn watch_mesh_source(&mut self, mesh_path: &PathBuf) {
+        let files = match kajiya::asset::mesh::mesh_source_files(mesh_path) {
+            Ok(files) => files,
+            Err(err) => {
+                log::warn!("Not watching {:?} for changes: {:#}", mesh_path, err);
+                return;
             }
-            MeshSource::Cache(path) => path.clone(),
         };
 
-        Ok(*self.known_meshes.entry(path.clone()).or_insert_with(|| {
-            world_renderer
-                .add_baked_mesh(path, AddMeshOptions::new())
-                .unwrap()
-        }))
+        for file in files {
+            let meshes = self.watched_mesh_files.entry(file.clone()).or_default();
+            if meshes.contains(mesh_path) {
+                continue;
+            }
+
+            // Only the first mesh using a file needs to register the watch
+            if meshes.is_empty() {
+                let changed_mesh_files = self.changed_mesh_files.clone();
+                let changed_file = file.clone();
+
+                if let Err(err) = kajiya::backend::file::watch_file(&file, move || {
+                    changed_mesh_files
+                        .lock()
+                        .unwrap()
+                        .insert(changed_file.clone());
+                }) {
+                    log::warn!("{:#}", err);
+                }
+            }
+
+            meshes.push(mesh_path.clone());
+        }
+    }
+
+    /// Re-bake meshes whose source files changed, and point their instances at the new versions.
+    fn reload_changed_meshes(
+        &mut self,
+        persisted: &mut PersistedState,
+        world_renderer: &mut WorldRenderer,
+    ) {
+        let changed_files: Vec<PathBuf> = self.changed_mesh_files.lock().unwrap().drain().collect();
+
+        let mut changed_meshes: Vec<PathBuf> = changed_files
+            .iter()
+            .filter_map(|file| self.watched_mesh_files.get(file))
+            .flatten()
+            .cloned()
+            .collect();
+        changed_meshes.sort();
+        changed_meshes.dedup();
+
+        for mesh_path in changed_meshes {
+            log::info!("Reloading {:?}", mesh_path);
+
+            let source = MeshSource::File(mesh_path);
+            self.known_meshes.remove(&source);
+            let mesh = match self.load_mesh(world_renderer, &source) {
+                Ok(mesh) => mesh,
+                Err(err) => {
+                    log::error!("Failed to reload mesh {:?}: {:#}", source, err);
+                    continue;
+                }
+            };
+
+            for elem in persisted.scene.elements.iter() {
+                if elem.source == source {
+                    world_renderer.set_instance_mesh(elem.instance, mesh);
+                }
+            }
+        }
     }
 
     pub(crate) fn add_mesh_instance(
@@ -647,6 +1213,7 @@ impl RuntimeState {
             source,
             instance: inst,
             transform,
+            material_overrides: Default::default(),
         });
 
         Ok(())
@@ -680,13 +1247,40 @@ impl RuntimeState {
                                 }
                             }
                         }
+                        "png" | "jpg" | "jpeg" => {
+                            // Lens dirt
+                            let result = match world_renderer.post.stack.get_mut::<BloomFx>() {
+                                Some(bloom) => bloom.load_lens_dirt(path),
+                                None => Err(anyhow::anyhow!("Bloom is not in the post stack")),
+                            };
+
+                            match result {
+                                Ok(_) => {
+                                    persisted.scene.lens_dirt = Some(path.clone());
+                                }
+                                Err(err) => {
+                                    log::error!("{:#}", err);
+                                }
+                            }
+                        }
+                        "cube" => {
+                            // Color grading LUT
+                            match world_renderer.post.load_grading_lut(path) {
+                                Ok(_) => {
+                                    persisted.scene.grading_lut = Some(path.clone());
+                                }
+                                Err(err) => {
+                                    log::error!("{:#}", err);
+                                }
+                            }
+                        }
                         "ron" => {
                             // Scene
                             if let Err(err) = self.load_scene(persisted, world_renderer, path) {
                                 log::error!("Failed to load scene: {:#}", err);
                             }
                         }
-                        "gltf" | "glb" => {
+                        "gltf" | "glb" | "obj" => {
                             // Mesh
                             if let Err(err) = self.add_mesh_instance(
                                 persisted,
@@ -0,0 +1,192 @@
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc,
+    thread::JoinHandle,
+};
+
+use anyhow::Context as _;
+use kajiya::{
+    renderers::hdr_capture::{HdrCapture, HdrCaptureSource},
+    world_renderer::WorldRenderer,
+};
+
+/// Frames queued for the encoder before rendering waits for it.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+/// Encodes the rendered frames to a video by piping them to `ffmpeg`.
+///
+/// Frames are read back from the GPU a few frames after being rendered,
+/// so they keep arriving for a bit after the last one is requested.
+pub struct VideoRecorder {
+    output: PathBuf,
+    fps: f32,
+    codec: String,
+    warmup_frames_left: u32,
+    frames_requested: u32,
+    frames_received: u32,
+    encoder: Option<Encoder>,
+}
+
+struct Encoder {
+    extent: [u32; 2],
+    frame_sender: mpsc::SyncSender<Vec<u8>>,
+    writer_thread: JoinHandle<std::io::Result<()>>,
+    ffmpeg: Child,
+}
+
+impl VideoRecorder {
+    pub fn new(output: PathBuf, fps: f32, codec: String, warmup_frames: u32) -> Self {
+        Self {
+            output,
+            fps,
+            codec,
+            warmup_frames_left: warmup_frames,
+            frames_requested: 0,
+            frames_received: 0,
+            encoder: None,
+        }
+    }
+
+    /// The camera path is held at its start until this is done.
+    pub fn is_warming_up(&self) -> bool {
+        self.warmup_frames_left > 0
+    }
+
+    /// Call once per frame while recording, before the frame is rendered.
+    pub fn request_frame(&mut self, world_renderer: &mut WorldRenderer) {
+        if self.warmup_frames_left > 0 {
+            self.warmup_frames_left -= 1;
+            return;
+        }
+
+        world_renderer.request_hdr_capture(HdrCaptureSource::PostTonemap);
+        self.frames_requested += 1;
+    }
+
+    /// Sends the frames the GPU has finished to the encoder. Call once per frame.
+    pub fn receive_frames(&mut self, world_renderer: &mut WorldRenderer) -> anyhow::Result<()> {
+        while let Some(capture) = world_renderer.take_hdr_capture() {
+            self.frames_received += 1;
+
+            if self.encoder.is_none() {
+                self.encoder = Some(self.start_encoder(capture.extent)?);
+            }
+
+            let encoder = self.encoder.as_ref().unwrap();
+            if capture.extent != encoder.extent {
+                log::warn!(
+                    "Skipping a {:?} frame in a {:?} video",
+                    capture.extent,
+                    encoder.extent
+                );
+                continue;
+            }
+
+            encoder
+                .frame_sender
+                .send(to_srgb_rgba8(&capture))
+                .ok()
+                .context("The video encoder has stopped")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_waiting_for_frames(&self) -> bool {
+        self.frames_received < self.frames_requested
+    }
+
+    /// Waits for `ffmpeg` to encode the remaining frames, and finalize the file.
+    pub fn finish(self) -> anyhow::Result<()> {
+        let Encoder {
+            frame_sender,
+            writer_thread,
+            mut ffmpeg,
+            ..
+        } = if let Some(encoder) = self.encoder {
+            encoder
+        } else {
+            anyhow::bail!("No frames were recorded");
+        };
+
+        // Closes ffmpeg's input once the queued frames are written.
+        drop(frame_sender);
+
+        writer_thread
+            .join()
+            .map_err(|_| anyhow::anyhow!("The video writer thread panicked"))?
+            .context("Writing frames to ffmpeg")?;
+
+        let status = ffmpeg.wait()?;
+        anyhow::ensure!(status.success(), "ffmpeg failed: {}", status);
+
+        log::info!("Saved {} frames to {:?}", self.frames_received, self.output);
+
+        Ok(())
+    }
+
+    fn start_encoder(&self, extent: [u32; 2]) -> anyhow::Result<Encoder> {
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s")
+            .arg(format!("{}x{}", extent[0], extent[1]))
+            .arg("-r")
+            .arg(self.fps.to_string())
+            .args(["-i", "-"])
+            .arg("-c:v")
+            .arg(&self.codec)
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(&self.output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Starting ffmpeg; is it installed, and in PATH?")?;
+
+        let stdin: ChildStdin = ffmpeg.stdin.take().unwrap();
+        let (frame_sender, frame_receiver) = mpsc::sync_channel::<Vec<u8>>(MAX_QUEUED_FRAMES);
+
+        // Keeps the pipe from stalling rendering, up to `MAX_QUEUED_FRAMES`.
+        let writer_thread = std::thread::spawn(move || {
+            let mut stdin = std::io::BufWriter::new(stdin);
+            for frame in frame_receiver {
+                stdin.write_all(&frame)?;
+            }
+            stdin.flush()
+        });
+
+        log::info!(
+            "Recording a {}x{} video at {} fps to {:?}",
+            extent[0],
+            extent[1],
+            self.fps,
+            self.output
+        );
+
+        Ok(Encoder {
+            extent,
+            frame_sender,
+            writer_thread,
+            ffmpeg,
+        })
+    }
+}
+
+fn to_srgb_rgba8(capture: &HdrCapture) -> Vec<u8> {
+    fn encode(linear: f32) -> u8 {
+        let linear = linear.clamp(0.0, 1.0);
+        let srgb = if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        (srgb * 255.0 + 0.5) as u8
+    }
+
+    capture
+        .pixels
+        .iter()
+        .flat_map(|&[r, g, b, _]| [encode(r), encode(g), encode(b), 255])
+        .collect()
+}
@@ -0,0 +1,41 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+fn main() {
+    // Find the FSR2 libs in this crate rather than in the including project
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!(
+        "cargo:rustc-link-search=native={}",
+        Path::new(&dir).join("FSR2/lib").display()
+    );
+
+    println!("cargo:rustc-link-lib=ffx_fsr2_api_x64");
+    println!("cargo:rustc-link-lib=ffx_fsr2_api_vk_x64");
+
+    // Tell cargo to invalidate the built crate whenever the wrapper changes
+    println!("cargo:rerun-if-changed=wrapper.h");
+
+    let vulkan_sdk = env::var("VULKAN_SDK").unwrap_or_else(|_| {
+        panic!("The environment variable `VULKAN_SDK` was not found. Is the Vulkan SDK installed?")
+    });
+
+    let bindings = bindgen::Builder::default()
+        .clang_arg(format!("-I{}/Include/Vulkan", vulkan_sdk))
+        // The FSR2 headers use C++ default arguments
+        .clang_arg("-xc++")
+        .header("wrapper.h")
+        .allowlist_function("ffx.*")
+        .allowlist_type("Ffx.*")
+        .allowlist_var("FFX_.*")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+        .generate()
+        .expect("Unable to generate bindings");
+
+    // Write the bindings to the $OUT_DIR/bindings.rs file.
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    bindings
+        .write_to_file(out_path.join("bindings.rs"))
+        .expect("Couldn't write bindings!");
+}
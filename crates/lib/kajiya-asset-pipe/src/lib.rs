@@ -2,18 +2,87 @@ use async_channel::unbounded;
 use async_executor::Executor;
 use easy_parallel::Parallel;
 use glam::Quat;
-use kajiya_asset::mesh::{pack_triangle_mesh, GpuImage, LoadGltfScene, PackedTriMesh};
+use kajiya_asset::{
+    mesh::{
+        mesh_source_files, pack_triangle_mesh_quantized, GpuImage, MeshFormat, PackedTriMesh,
+        TriangleMesh, VertexQuantization,
+    },
+    parallel::HelperThreads,
+    terrain::{build_terrain_chunk, Heightfield, TerrainDesc},
+};
 use smol::future;
-use std::{collections::HashSet, fs::File, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use wyhash::WyHash;
 
 use turbosloth::*;
 
 use anyhow::Result;
 
+/// Version of the importers and of the baked asset format.
+///
+/// Bump this whenever either changes, so that stale entries in the bake cache are not used.
+pub const BAKE_FORMAT_VERSION: u32 = 8;
+
+/// Content-addressed name for the baked version of the mesh at `path`.
+///
+/// The key covers the contents of the source file and everything it references,
+/// the import parameters, and `BAKE_FORMAT_VERSION`; the source path itself doesn't matter.
+pub fn mesh_cache_key(path: &Path, scale: f32, quantization: VertexQuantization) -> Result<String> {
+    let mut hasher = WyHash::with_seed(0);
+    BAKE_FORMAT_VERSION.hash(&mut hasher);
+    scale.to_bits().hash(&mut hasher);
+    quantization.hash(&mut hasher);
+
+    for file in mesh_source_files(path)? {
+        hash_file_contents(&file, &mut hasher)
+            .map_err(|err| anyhow::anyhow!("Failed to read {:?}: {}", file, err))?;
+    }
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+// Source files are hashed in chunks of this size rather than read whole, as they can be
+// gigabytes. The chunking must not depend on how much each read returns, since `WyHash`
+// gives different results for the same bytes written in different pieces.
+const HASH_CHUNK_BYTES: u64 = 1 << 20;
+
+fn hash_file_contents(path: &Path, hasher: &mut impl Hasher) -> std::io::Result<()> {
+    let file = File::open(path)?;
+
+    // Length first, like `Hash` for slices, so that the boundaries between files count.
+    file.metadata()?.len().hash(hasher);
+
+    let mut reader = BufReader::new(file);
+    let mut chunk = Vec::with_capacity(HASH_CHUNK_BYTES as usize);
+
+    loop {
+        chunk.clear();
+        (&mut reader)
+            .take(HASH_CHUNK_BYTES)
+            .read_to_end(&mut chunk)?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        hasher.write(&chunk);
+    }
+
+    Ok(())
+}
+
 pub struct MeshAssetProcessParams {
     pub path: PathBuf,
     pub output_name: String,
     pub scale: f32,
+    pub quantization: VertexQuantization,
 }
 
 pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
@@ -24,78 +93,166 @@ pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
     {
         println!("Loading {:?}...", opt.path);
 
-        let mesh = LoadGltfScene {
-            path: opt.path,
-            scale: opt.scale,
-            //rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
-            rotation: Quat::IDENTITY,
-        }
-        .into_lazy();
+        let format = MeshFormat::from_path(&opt.path);
+        format.ensure_supported(&opt.path)?;
+
+        let mesh: Lazy<TriangleMesh> = match format {
+            #[cfg(feature = "obj-import")]
+            MeshFormat::Obj => kajiya_asset::mesh::LoadObjScene {
+                path: opt.path,
+                scale: opt.scale,
+                rotation: Quat::IDENTITY,
+            }
+            .into_lazy(),
+            #[cfg(feature = "gltf-import")]
+            MeshFormat::Gltf => kajiya_asset::mesh::LoadGltfScene {
+                path: opt.path,
+                scale: opt.scale,
+                //rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                rotation: Quat::IDENTITY,
+            }
+            .into_lazy(),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("checked by `ensure_supported`"),
+        };
 
         let mesh = &*smol::block_on(mesh.eval(&lazy_cache))?;
 
         println!("Packing the mesh...");
-        let mesh: PackedTriMesh::Proto = pack_triangle_mesh(mesh);
+        let mesh: PackedTriMesh::Proto = pack_triangle_mesh_quantized(mesh, opt.quantization);
 
         mesh.flatten_into(&mut File::create(format!(
             "cache/{}.mesh",
             opt.output_name
         ))?);
-        let unique_images: Vec<Lazy<GpuImage::Proto>> = mesh
-            .maps
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect::<Vec<_>>();
-
-        let ex = &Executor::new();
-        let (signal, shutdown) = unbounded::<()>();
-
-        // Prepare tasks for processing all images
-        let lazy_cache = &lazy_cache;
-        let images = unique_images.iter().cloned().map(|img| async move {
-            let loaded = img.eval(lazy_cache).await?;
-            let img_dst = PathBuf::from(format!("cache/{:8.8x}.image", img.identity()));
-
-            match File::create(&img_dst) {
-                Ok(mut file) => loaded.flatten_into(&mut file),
-                Err(err) => {
-                    if img_dst.exists() {
-                        log::info!("Could not create {:?}; ignoring", img_dst);
-                    } else {
-                        anyhow::anyhow!(err);
-                    }
-                }
-            };
-
-            anyhow::Result::<()>::Ok(())
-        });
-
-        // Now spawn them onto the executor
-        let images = images.map(|task| ex.spawn(task));
-        let image_count = images.len();
-
-        if image_count > 0 {
-            // A task to join them all
-            let all_images = futures::future::try_join_all(images);
-
-            println!("Processing {} images...", image_count);
-
-            // Now spawn threads for the executor and run it to completion
-            Parallel::new()
-                .each(0..num_cpus::get(), |_| {
-                    future::block_on(ex.run(shutdown.recv()))
-                })
-                .finish(|| {
-                    future::block_on(async {
-                        all_images.await.expect("Failed to load mesh images");
-                        drop(signal);
-                    })
-                });
-        }
+        bake_images(mesh.maps, &lazy_cache);
 
         println!("Done.");
     }
 
     Ok(())
 }
+
+/// Content-addressed name prefix for the baked chunks of a terrain; see `mesh_cache_key`.
+pub fn terrain_cache_key(desc: &TerrainDesc) -> Result<String> {
+    let mut hasher = WyHash::with_seed(0);
+    BAKE_FORMAT_VERSION.hash(&mut hasher);
+    desc.hash(&mut hasher);
+
+    for file in desc.source_files() {
+        hash_file_contents(&file, &mut hasher)
+            .map_err(|err| anyhow::anyhow!("Failed to read {:?}: {}", file, err))?;
+    }
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// Names of the baked meshes of the terrain chunks, in the order of `TerrainDesc::chunks`.
+pub fn terrain_chunk_output_names(desc: &TerrainDesc, output_name: &str) -> Vec<String> {
+    desc.chunks()
+        .map(|[x, z]| format!("{}_{}_{}", output_name, x, z))
+        .collect()
+}
+
+pub struct TerrainAssetProcessParams {
+    pub desc: TerrainDesc,
+    pub output_name: String,
+}
+
+/// Bakes every chunk of the terrain as a separate mesh, named as per `terrain_chunk_output_names`.
+pub fn process_terrain_asset(opt: TerrainAssetProcessParams) -> Result<()> {
+    let lazy_cache = LazyCache::create();
+
+    std::fs::create_dir_all("cache")?;
+
+    println!("Loading {:?}...", opt.desc.heightmap);
+    let heightfield = Heightfield::load(&opt.desc.heightmap)?;
+
+    println!("Building the terrain chunks...");
+    let mut maps = Vec::new();
+
+    for (chunk, output_name) in opt
+        .desc
+        .chunks()
+        .zip(terrain_chunk_output_names(&opt.desc, &opt.output_name))
+    {
+        let mesh: PackedTriMesh::Proto = build_terrain_chunk(&opt.desc, &heightfield, chunk);
+        mesh.flatten_into(&mut File::create(format!("cache/{}.mesh", output_name))?);
+
+        // All chunks share the same layers
+        maps.extend(mesh.maps);
+    }
+
+    bake_images(maps, &lazy_cache);
+
+    println!("Done.");
+
+    Ok(())
+}
+
+/// Writes the images used by baked meshes to the cache, processing them in parallel.
+fn bake_images(maps: Vec<Lazy<GpuImage::Proto>>, lazy_cache: &Arc<LazyCache>) {
+    let unique_images: Vec<Lazy<GpuImage::Proto>> = maps
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let ex = &Executor::new();
+    let (signal, shutdown) = unbounded::<()>();
+
+    // Prepare tasks for processing all images
+    let images = unique_images.iter().cloned().map(|img| async move {
+        let loaded = img.eval(lazy_cache).await?;
+        let img_dst = PathBuf::from(format!("cache/{:8.8x}.image", img.identity()));
+
+        // Write to a temporary file first, so that the image can be replaced while mapped.
+        let img_tmp = img_dst.with_extension("image.tmp");
+        match File::create(&img_tmp) {
+            Ok(mut file) => {
+                loaded.flatten_into(&mut file);
+                drop(file);
+
+                if std::fs::rename(&img_tmp, &img_dst).is_err() {
+                    log::info!("Could not replace {:?}; ignoring", img_dst);
+                    let _ = std::fs::remove_file(&img_tmp);
+                }
+            }
+            Err(err) => {
+                if img_dst.exists() {
+                    log::info!("Could not create {:?}; ignoring", img_tmp);
+                } else {
+                    anyhow::anyhow!(err);
+                }
+            }
+        };
+
+        anyhow::Result::<()>::Ok(())
+    });
+
+    // Now spawn them onto the executor
+    let images = images.map(|task| ex.spawn(task));
+    let image_count = images.len();
+
+    if image_count > 0 {
+        // A task to join them all
+        let all_images = futures::future::try_join_all(images);
+
+        println!("Processing {} images...", image_count);
+
+        // Run the executor on this thread, and on helpers from the budget which compressing
+        // the images draws from too, so that the two don't each start a thread per core.
+        let helpers = HelperThreads::reserve(num_cpus::get().saturating_sub(1));
+
+        Parallel::new()
+            .each(0..helpers.count(), |_| {
+                future::block_on(ex.run(shutdown.recv()))
+            })
+            .finish(|| {
+                future::block_on(ex.run(async {
+                    all_images.await.expect("Failed to load mesh images");
+                    drop(signal);
+                }))
+            });
+    }
+}
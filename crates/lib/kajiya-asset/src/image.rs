@@ -180,17 +180,17 @@ impl CreateGpuImage {
                 data: &mip,
             };
 
-            let mut compressed_bytes = vec![0u8; block_count as usize * block_bytes];
-
             log::info!("Compressing to {:?}...", bc_mode);
-            match bc_mode {
+            let compressed_bytes = match bc_mode {
                 BcMode::Bc5 => {
                     format = match self.params.gamma {
                         crate::mesh::TexGamma::Linear => vk::Format::BC5_UNORM_BLOCK,
                         crate::mesh::TexGamma::Srgb => unimplemented!(),
                     };
 
-                    bc5::compress_blocks_into(&surface, &mut compressed_bytes)
+                    compress_blocks_parallel(&surface, block_bytes, |strip, output| {
+                        bc5::compress_blocks_into(strip, output)
+                    })
                 }
                 BcMode::Bc7 => {
                     format = match self.params.gamma {
@@ -204,10 +204,13 @@ impl CreateGpuImage {
                         bc7::opaque_basic_settings()
                     };
 
-                    bc7::compress_blocks_into(&settings, &surface, &mut compressed_bytes);
+                    compress_blocks_parallel(&surface, block_bytes, |strip, output| {
+                        bc7::compress_blocks_into(&settings, strip, output)
+                    })
                 }
-            }
+            };
 
+            debug_assert_eq!(compressed_bytes.len(), block_count as usize * block_bytes);
             compressed_bytes
         };
 
@@ -251,13 +254,19 @@ impl CreateGpuImage {
         let mips: Vec<Vec<u8>> = if self.params.use_mips {
             desc = desc.all_mip_levels();
 
+            let gamma = self.params.gamma;
             let downsample = |image: &DynamicImage| {
-                // TODO: gamma-correct resize
-                image.resize_exact(
-                    round_up_to_block(image.dimensions().0 / 2),
-                    round_up_to_block(image.dimensions().1 / 2),
-                    FilterType::Lanczos3,
-                )
+                let width = round_up_to_block(image.dimensions().0 / 2);
+                let height = round_up_to_block(image.dimensions().1 / 2);
+
+                match gamma {
+                    crate::mesh::TexGamma::Linear => {
+                        image.resize_exact(width, height, FilterType::Lanczos3)
+                    }
+                    crate::mesh::TexGamma::Srgb => {
+                        resize_srgb_in_linear_space(image, width, height)
+                    }
+                }
             };
 
             let mut mips;
@@ -281,6 +290,8 @@ impl CreateGpuImage {
         Ok(super::mesh::GpuImage::Proto {
             format,
             extent: desc.extent,
+            array_layers: 1,
+            is_cube: false,
             mips,
         })
     }
@@ -290,53 +301,228 @@ impl CreateGpuImage {
             anyhow::bail!("Not pitch available for DDS image");
         }
 
-        let dds_data = dds.get_data(0).unwrap();
-        let mut byte_offset = 0usize;
+        if dds.get_depth() > 1 {
+            anyhow::bail!("Volume DDS images are not supported");
+        }
+
+        let format = dds_util::vk_format(dds, self.params.gamma)?;
+
+        // DX10 cube maps store the number of cubes rather than faces
+        let is_dx10_cube = dds.header10.as_ref().map_or(false, |h10| {
+            h10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE)
+        });
+        let is_cube = is_dx10_cube || dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP);
+        let array_layers = dds.get_num_array_layers() * if is_dx10_cube { 6 } else { 1 };
 
         // 1 for regular, 4 for BC
         let pitch_height = dds.get_pitch_height();
 
-        let mips: Vec<Vec<u8>> = (0..dds.get_num_mipmap_levels())
-            .map(|mip| -> Vec<u8> {
+        let mip_sizes: Vec<usize> = (0..dds.get_num_mipmap_levels())
+            .map(|mip| {
                 let width = (dds.get_width() >> mip).max(pitch_height);
                 let height = (dds.get_height() >> mip).max(pitch_height);
                 let pitch = dds_util::get_pitch(dds, width).unwrap();
+                dds_util::get_texture_size(pitch, pitch_height, height, 1)
+            })
+            .collect();
 
-                let mip_size_bytes = dds_util::get_texture_size(pitch, pitch_height, height, 1);
-
-                let mip_data = &dds_data[byte_offset..byte_offset + mip_size_bytes];
-
-                byte_offset += mip_size_bytes;
+        let layer_size_bytes: usize = mip_sizes.iter().sum();
+        if dds.data.len() < layer_size_bytes * array_layers as usize {
+            anyhow::bail!(
+                "DDS data too short: expected {} layers of {} bytes, got {} bytes",
+                array_layers,
+                layer_size_bytes,
+                dds.data.len()
+            );
+        }
 
-                mip_data.to_owned()
+        // DDS stores all the mips of each layer together; we want the layers of each mip together.
+        let mut mip_offset = 0usize;
+        let mips: Vec<Vec<u8>> = mip_sizes
+            .iter()
+            .map(|&mip_size_bytes| {
+                let mip_data = (0..array_layers as usize)
+                    .flat_map(|layer| {
+                        let offset = layer * layer_size_bytes + mip_offset;
+                        dds.data[offset..offset + mip_size_bytes].iter().copied()
+                    })
+                    .collect();
+
+                mip_offset += mip_size_bytes;
+                mip_data
             })
             .collect();
 
-        assert_eq!(byte_offset, dds_data.len());
-
-        let format = match dds.get_dxgi_format() {
-            Some(ddsfile::DxgiFormat::BC1_UNorm_sRGB) => vk::Format::BC1_RGB_SRGB_BLOCK,
-            Some(ddsfile::DxgiFormat::BC3_UNorm) => vk::Format::BC3_UNORM_BLOCK,
-            Some(ddsfile::DxgiFormat::BC3_UNorm_sRGB) => vk::Format::BC3_SRGB_BLOCK,
-            Some(ddsfile::DxgiFormat::BC5_UNorm) => vk::Format::BC5_UNORM_BLOCK,
-            Some(ddsfile::DxgiFormat::BC5_SNorm) => vk::Format::BC5_SNORM_BLOCK,
-            _ => todo!(
-                "DDS format dxgi:{:?} d3d:{:?} not supported yet",
-                dds.get_dxgi_format(),
-                dds.get_d3d_format()
-            ),
-        };
-
         Ok(super::mesh::GpuImage::Proto {
             format,
-            extent: [dds.get_width(), dds.get_height(), dds.get_depth()],
+            extent: [dds.get_width(), dds.get_height(), 1],
+            array_layers,
+            is_cube,
             mips,
         })
     }
 }
 
+// Rows of blocks per compression task
+const COMPRESSION_STRIP_BLOCK_ROWS: u32 = 16;
+
+/// Compresses `surface`, whose dimensions are multiples of the block size, in strips of block rows
+/// on the idle cores; see `parallel_map`. Blocks are stored row by row, so the blocks of the strips concatenate into
+/// those of the whole surface.
+fn compress_blocks_parallel(
+    surface: &intel_tex_2::RgbaSurface,
+    block_bytes: usize,
+    compress_strip: impl Fn(&intel_tex_2::RgbaSurface, &mut [u8]) + Sync,
+) -> Vec<u8> {
+    let block_rows = surface.height / 4;
+    let blocks_per_row = surface.width / 4;
+    let strips: Vec<u32> = (0..block_rows)
+        .step_by(COMPRESSION_STRIP_BLOCK_ROWS as usize)
+        .collect();
+
+    crate::parallel::parallel_map(&strips, |&first_block_row| {
+        let strip_block_rows = (block_rows - first_block_row).min(COMPRESSION_STRIP_BLOCK_ROWS);
+        let first_byte = (first_block_row * 4 * surface.stride) as usize;
+        let end_byte = ((first_block_row + strip_block_rows) * 4 * surface.stride) as usize;
+
+        let strip = intel_tex_2::RgbaSurface {
+            width: surface.width,
+            height: strip_block_rows * 4,
+            stride: surface.stride,
+            data: &surface.data[first_byte..end_byte],
+        };
+
+        let mut output = vec![0u8; (strip_block_rows * blocks_per_row) as usize * block_bytes];
+        compress_strip(&strip, &mut output);
+        output
+    })
+    .concat()
+}
+
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.max(0.0).min(1.0);
+    let v = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (v * 255.0 + 0.5) as u8
+}
+
+/// Resize an sRGB image, filtering the color channels in linear space,
+/// so that mips don't get darker than the top level.
+fn resize_srgb_in_linear_space(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let src = image.to_rgba8();
+    let linear = ImageBuffer::<Rgba<f32>, Vec<f32>>::from_fn(src.width(), src.height(), |x, y| {
+        let px = src.get_pixel(x, y).0;
+        Rgba([
+            srgb_to_linear(px[0]),
+            srgb_to_linear(px[1]),
+            srgb_to_linear(px[2]),
+            px[3] as f32 / 255.0,
+        ])
+    });
+
+    let resized = image::imageops::resize(&linear, width, height, FilterType::Lanczos3);
+
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+        let px = resized.get_pixel(x, y).0;
+        Rgba([
+            linear_to_srgb(px[0]),
+            linear_to_srgb(px[1]),
+            linear_to_srgb(px[2]),
+            (px[3].max(0.0).min(1.0) * 255.0 + 0.5) as u8,
+        ])
+    }))
+}
+
 // From `ddsfile`, with some modifications
 mod dds_util {
+    use crate::mesh::TexGamma;
+    use kajiya_backend::ash::vk;
+
+    /// DXGI formats with explicit sRGB flags are used as-is; others follow the requested gamma.
+    pub fn vk_format(dds: &ddsfile::Dds, gamma: TexGamma) -> anyhow::Result<vk::Format> {
+        use ddsfile::{D3DFormat, DxgiFormat};
+
+        let with_gamma = |unorm: vk::Format, srgb: vk::Format| match gamma {
+            TexGamma::Linear => unorm,
+            TexGamma::Srgb => srgb,
+        };
+
+        if let Some(dxgi) = dds.get_dxgi_format() {
+            Ok(match dxgi {
+                DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => with_gamma(
+                    vk::Format::BC1_RGBA_UNORM_BLOCK,
+                    vk::Format::BC1_RGBA_SRGB_BLOCK,
+                ),
+                DxgiFormat::BC1_UNorm_sRGB => vk::Format::BC1_RGBA_SRGB_BLOCK,
+                DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => {
+                    with_gamma(vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK)
+                }
+                DxgiFormat::BC2_UNorm_sRGB => vk::Format::BC2_SRGB_BLOCK,
+                DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => {
+                    with_gamma(vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK)
+                }
+                DxgiFormat::BC3_UNorm_sRGB => vk::Format::BC3_SRGB_BLOCK,
+                DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => vk::Format::BC4_UNORM_BLOCK,
+                DxgiFormat::BC4_SNorm => vk::Format::BC4_SNORM_BLOCK,
+                DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => vk::Format::BC5_UNORM_BLOCK,
+                DxgiFormat::BC5_SNorm => vk::Format::BC5_SNORM_BLOCK,
+                DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => vk::Format::BC6H_UFLOAT_BLOCK,
+                DxgiFormat::BC6H_SF16 => vk::Format::BC6H_SFLOAT_BLOCK,
+                DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => {
+                    with_gamma(vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK)
+                }
+                DxgiFormat::BC7_UNorm_sRGB => vk::Format::BC7_SRGB_BLOCK,
+                DxgiFormat::R8G8B8A8_Typeless | DxgiFormat::R8G8B8A8_UNorm => {
+                    with_gamma(vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB)
+                }
+                DxgiFormat::R8G8B8A8_UNorm_sRGB => vk::Format::R8G8B8A8_SRGB,
+                DxgiFormat::B8G8R8A8_Typeless | DxgiFormat::B8G8R8A8_UNorm => {
+                    with_gamma(vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB)
+                }
+                DxgiFormat::B8G8R8A8_UNorm_sRGB => vk::Format::B8G8R8A8_SRGB,
+                DxgiFormat::R16G16B16A16_Float => vk::Format::R16G16B16A16_SFLOAT,
+                DxgiFormat::R32G32B32A32_Float => vk::Format::R32G32B32A32_SFLOAT,
+                other => anyhow::bail!("DDS format dxgi:{:?} not supported yet", other),
+            })
+        } else if let Some(d3d) = dds.get_d3d_format() {
+            Ok(match d3d {
+                D3DFormat::DXT1 => with_gamma(
+                    vk::Format::BC1_RGBA_UNORM_BLOCK,
+                    vk::Format::BC1_RGBA_SRGB_BLOCK,
+                ),
+                D3DFormat::DXT2 | D3DFormat::DXT3 => {
+                    with_gamma(vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK)
+                }
+                D3DFormat::DXT4 | D3DFormat::DXT5 => {
+                    with_gamma(vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK)
+                }
+                D3DFormat::A8B8G8R8 => {
+                    with_gamma(vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB)
+                }
+                D3DFormat::A8R8G8B8 => {
+                    with_gamma(vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB)
+                }
+                D3DFormat::A16B16G16R16F => vk::Format::R16G16B16A16_SFLOAT,
+                D3DFormat::A32B32G32R32F => vk::Format::R32G32B32A32_SFLOAT,
+                other => anyhow::bail!("DDS format d3d:{:?} not supported yet", other),
+            })
+        } else {
+            anyhow::bail!("Unknown DDS format")
+        }
+    }
+
     pub fn get_texture_size(pitch: u32, pitch_height: u32, height: u32, depth: u32) -> usize {
         let row_height = (height + (pitch_height - 1)) / pitch_height;
         pitch as usize * row_height as usize * depth as usize
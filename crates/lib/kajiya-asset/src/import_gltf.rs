@@ -1,8 +1,12 @@
 // Based on `import.rs` in the `gltf` crate, but modified not to load images (we do that separately).
 
+use anyhow::Context as _;
 use bytes::Bytes;
 use gltf::{buffer, image, Document, Error, Gltf, Result};
-use std::{fs, io, path::Path};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use crate::image::ImageSource;
 
@@ -88,11 +92,19 @@ where
 /// Import the buffer data referenced by a glTF document.
 pub fn import_buffer_data(
     document: &Document,
+    raw_json: &serde_json::Value,
     base: Option<&Path>,
     mut blob: Option<Vec<u8>>,
 ) -> Result<Vec<Bytes>> {
     let mut buffers = Vec::new();
     for buffer in document.buffers() {
+        // Fallback buffers of `EXT_meshopt_compression` don't need to be loaded;
+        // their contents are decoded from the compressed buffers instead.
+        if is_meshopt_fallback_buffer(raw_json, buffer.index()) {
+            buffers.push(Bytes::from(vec![0u8; buffer.length()]));
+            continue;
+        }
+
         let mut data = match buffer.source() {
             buffer::Source::Uri(uri) => Scheme::read(base, uri),
             buffer::Source::Bin => blob.take().ok_or(Error::MissingBlob),
@@ -154,24 +166,328 @@ pub fn import_image_data(
     Ok(images)
 }
 
-fn import_impl(Gltf { document, blob }: Gltf, base: Option<&Path>) -> Result<Import> {
-    let buffer_data = import_buffer_data(&document, base, blob)?;
+fn is_meshopt_fallback_buffer(raw_json: &serde_json::Value, buffer_index: usize) -> bool {
+    raw_json
+        .get("buffers")
+        .and_then(|buffers| buffers.get(buffer_index))
+        .and_then(|buffer| buffer.pointer("/extensions/EXT_meshopt_compression/fallback"))
+        .and_then(serde_json::Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Decode buffer views compressed with `EXT_meshopt_compression` into their target buffers.
+fn decode_meshopt_buffer_views(
+    raw_json: &serde_json::Value,
+    buffers: Vec<Bytes>,
+) -> anyhow::Result<Vec<Bytes>> {
+    let views = if let Some(views) = raw_json.get("bufferViews").and_then(|v| v.as_array()) {
+        views
+    } else {
+        return Ok(buffers);
+    };
+
+    let mut decoded_buffers: Vec<Option<Vec<u8>>> = vec![None; buffers.len()];
+
+    for (view_index, view) in views.iter().enumerate() {
+        let ext = if let Some(ext) = view.pointer("/extensions/EXT_meshopt_compression") {
+            ext
+        } else {
+            continue;
+        };
+
+        let get_usize = |value: &serde_json::Value, name: &str| -> Option<usize> {
+            value.get(name).and_then(|v| v.as_u64()).map(|v| v as usize)
+        };
+
+        let src_buffer = get_usize(ext, "buffer").context("buffer")?;
+        let src_offset = get_usize(ext, "byteOffset").unwrap_or(0);
+        let src_length = get_usize(ext, "byteLength").context("byteLength")?;
+        let stride = get_usize(ext, "byteStride").context("byteStride")?;
+        let count = get_usize(ext, "count").context("count")?;
+        let mode = ext.get("mode").and_then(|v| v.as_str()).unwrap_or("");
+        let filter = ext.get("filter").and_then(|v| v.as_str()).unwrap_or("NONE");
+
+        let dst_buffer = get_usize(view, "buffer").context("buffer")?;
+        let dst_offset = get_usize(view, "byteOffset").unwrap_or(0);
+
+        validate_meshopt_view(mode, filter, stride, count)
+            .with_context(|| format!("Buffer view {}", view_index))?;
+
+        let src = src_offset
+            .checked_add(src_length)
+            .and_then(|src_end| buffers.get(src_buffer)?.get(src_offset..src_end))
+            .with_context(|| {
+                format!("Compressed data of buffer view {} out of range", view_index)
+            })?;
+
+        let dst_end = count
+            .checked_mul(stride)
+            .and_then(|size| size.checked_add(dst_offset))
+            .with_context(|| format!("Buffer view {} out of range", view_index))?;
+
+        let dst = decoded_buffers
+            .get_mut(dst_buffer)
+            .context("Invalid buffer index")?
+            .get_or_insert_with(|| buffers[dst_buffer].to_vec())
+            .get_mut(dst_offset..dst_end)
+            .with_context(|| format!("Buffer view {} out of range", view_index))?;
+
+        let dst_ptr = dst.as_mut_ptr() as *mut std::os::raw::c_void;
+        let src_ptr = src.as_ptr() as *const std::os::raw::c_uchar;
+
+        // Safety: `dst` has room for `count * stride` bytes, and the sizes the decoders
+        // assert on have been validated. They bounds-check the compressed data themselves.
+        let result = unsafe {
+            match mode {
+                "ATTRIBUTES" => meshopt::ffi::meshopt_decodeVertexBuffer(
+                    dst_ptr,
+                    count,
+                    stride,
+                    src_ptr,
+                    src.len(),
+                ),
+                "TRIANGLES" => meshopt::ffi::meshopt_decodeIndexBuffer(
+                    dst_ptr,
+                    count,
+                    stride,
+                    src_ptr,
+                    src.len(),
+                ),
+                "INDICES" => meshopt::ffi::meshopt_decodeIndexSequence(
+                    dst_ptr,
+                    count,
+                    stride,
+                    src_ptr,
+                    src.len(),
+                ),
+                _ => unreachable!(),
+            }
+        };
+
+        if result != 0 {
+            anyhow::bail!(
+                "Failed to decode meshopt-compressed buffer view {}: error {}",
+                view_index,
+                result
+            );
+        }
+
+        unsafe {
+            match filter {
+                "NONE" => {}
+                "OCTAHEDRAL" => meshopt::ffi::meshopt_decodeFilterOct(dst_ptr, count, stride),
+                "QUATERNION" => meshopt::ffi::meshopt_decodeFilterQuat(dst_ptr, count, stride),
+                "EXPONENTIAL" => meshopt::ffi::meshopt_decodeFilterExp(dst_ptr, count, stride),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    Ok(buffers
+        .into_iter()
+        .zip(decoded_buffers)
+        .map(|(original, decoded)| decoded.map_or(original, Bytes::from))
+        .collect())
+}
+
+/// Checks the sizes of an `EXT_meshopt_compression` buffer view against the limits
+/// of its mode and filter. The decoders abort the process on sizes they don't support,
+/// so this must pass before calling them.
+fn validate_meshopt_view(
+    mode: &str,
+    filter: &str,
+    stride: usize,
+    count: usize,
+) -> anyhow::Result<()> {
+    match mode {
+        "ATTRIBUTES" => anyhow::ensure!(
+            stride > 0 && stride % 4 == 0 && stride <= 256,
+            "byteStride {} of ATTRIBUTES must be a positive multiple of 4, at most 256",
+            stride
+        ),
+        "TRIANGLES" => {
+            anyhow::ensure!(
+                count % 3 == 0,
+                "count {} of TRIANGLES must be a multiple of 3",
+                count
+            );
+            anyhow::ensure!(
+                stride == 2 || stride == 4,
+                "byteStride {} of TRIANGLES must be 2 or 4",
+                stride
+            );
+        }
+        "INDICES" => anyhow::ensure!(
+            stride == 2 || stride == 4,
+            "byteStride {} of INDICES must be 2 or 4",
+            stride
+        ),
+        _ => anyhow::bail!("Unknown EXT_meshopt_compression mode {:?}", mode),
+    }
+
+    match filter {
+        "NONE" => {}
+        "OCTAHEDRAL" => anyhow::ensure!(
+            stride == 4 || stride == 8,
+            "byteStride {} of the OCTAHEDRAL filter must be 4 or 8",
+            stride
+        ),
+        "QUATERNION" => anyhow::ensure!(
+            stride == 8,
+            "byteStride {} of the QUATERNION filter must be 8",
+            stride
+        ),
+        "EXPONENTIAL" => anyhow::ensure!(
+            stride > 0 && stride % 4 == 0,
+            "byteStride {} of the EXPONENTIAL filter must be a positive multiple of 4",
+            stride
+        ),
+        _ => anyhow::bail!("Unknown EXT_meshopt_compression filter {:?}", filter),
+    }
+
+    Ok(())
+}
+
+fn import_impl(
+    Gltf { document, blob }: Gltf,
+    raw_json: &serde_json::Value,
+    base: Option<&Path>,
+) -> anyhow::Result<Import> {
+    // Draco needs a decoder we don't have; the mesh data is unusable if it's required.
+    if document
+        .extensions_required()
+        .any(|ext| ext == "KHR_draco_mesh_compression")
+    {
+        anyhow::bail!(
+            "KHR_draco_mesh_compression is not supported. Re-export the asset without Draco, \
+            or with EXT_meshopt_compression instead"
+        );
+    }
+
+    let buffer_data = import_buffer_data(&document, raw_json, base, blob)?;
+    let buffer_data = decode_meshopt_buffer_views(raw_json, buffer_data)
+        .context("Decoding EXT_meshopt_compression buffers")?;
     let image_data = import_image_data(&document, base, &buffer_data)?;
     let import = (document, buffer_data, image_data);
     Ok(import)
 }
 
-fn import_path(path: &Path) -> Result<Import> {
+fn raw_json_from_slice(data: &[u8]) -> anyhow::Result<serde_json::Value> {
+    if data.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(data)?;
+        Ok(serde_json::from_slice(&glb.json)?)
+    } else {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+fn import_path(path: &Path) -> anyhow::Result<(Import, serde_json::Value)> {
     let base = path.parent().unwrap_or_else(|| Path::new("./"));
-    let file = fs::File::open(path).map_err(Error::Io)?;
-    let reader = io::BufReader::new(file);
-    import_impl(Gltf::from_reader_without_validation(reader)?, Some(base))
+    let data = read_to_end(path)?;
+    let raw_json = raw_json_from_slice(&data)?;
+    let import = import_impl(
+        Gltf::from_slice_without_validation(&data)?,
+        &raw_json,
+        Some(base),
+    )?;
+    Ok((import, raw_json))
 }
 
 /// Import some glTF 2.0 from the file system.
-pub fn import<P>(path: P) -> Result<Import>
+pub fn import<P>(path: P) -> anyhow::Result<Import>
+where
+    P: AsRef<Path>,
+{
+    Ok(import_path(path.as_ref())?.0)
+}
+
+/// Like `import`, also returning the raw JSON, as `import_raw_json` would,
+/// without reading and parsing the file again.
+pub fn import_with_raw_json<P>(path: P) -> anyhow::Result<(Import, serde_json::Value)>
 where
     P: AsRef<Path>,
 {
     import_path(path.as_ref())
 }
+
+/// Load the raw JSON of a glTF or GLB file.
+///
+/// The `gltf` crate drops extensions it doesn't know about; this allows reading those.
+pub fn import_raw_json<P>(path: P) -> anyhow::Result<serde_json::Value>
+where
+    P: AsRef<Path>,
+{
+    raw_json_from_slice(&read_to_end(path.as_ref())?)
+}
+
+/// List the external files referenced by a glTF or GLB file: buffers and images.
+///
+/// Embedded (`data:`) resources are skipped, and so are files which don't exist;
+/// the latter will be reported by the import itself.
+pub fn external_files<P>(path: P) -> anyhow::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let raw_json = import_raw_json(path)?;
+
+    let uris = |key: &str| -> Vec<String> {
+        raw_json
+            .get(key)
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item.get("uri").and_then(serde_json::Value::as_str))
+            .map(str::to_owned)
+            .collect()
+    };
+
+    // Images have their URIs percent-decoded by the importer, but buffers don't.
+    let mut all_uris = uris("buffers");
+    for uri in uris("images") {
+        all_uris.push(urlencoding::decode(&uri)?.into_owned());
+    }
+
+    Ok(all_uris
+        .iter()
+        .filter_map(|uri| match Scheme::parse(uri) {
+            Scheme::File(path) => Some(PathBuf::from(path)),
+            Scheme::Relative => Some(base.join(uri)),
+            _ => None,
+        })
+        .filter(|path| path.exists())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meshopt_view_sizes() {
+        assert!(validate_meshopt_view("ATTRIBUTES", "NONE", 12, 100).is_ok());
+        assert!(validate_meshopt_view("ATTRIBUTES", "NONE", 0, 100).is_err());
+        assert!(validate_meshopt_view("ATTRIBUTES", "NONE", 6, 100).is_err());
+        assert!(validate_meshopt_view("ATTRIBUTES", "NONE", 260, 100).is_err());
+
+        assert!(validate_meshopt_view("TRIANGLES", "NONE", 2, 99).is_ok());
+        assert!(validate_meshopt_view("TRIANGLES", "NONE", 4, 100).is_err());
+        assert!(validate_meshopt_view("TRIANGLES", "NONE", 8, 99).is_err());
+
+        assert!(validate_meshopt_view("INDICES", "NONE", 4, 100).is_ok());
+        assert!(validate_meshopt_view("INDICES", "NONE", 1, 100).is_err());
+
+        assert!(validate_meshopt_view("VERTICES", "NONE", 4, 100).is_err());
+    }
+
+    #[test]
+    fn meshopt_filter_sizes() {
+        assert!(validate_meshopt_view("ATTRIBUTES", "OCTAHEDRAL", 4, 100).is_ok());
+        assert!(validate_meshopt_view("ATTRIBUTES", "OCTAHEDRAL", 12, 100).is_err());
+        assert!(validate_meshopt_view("ATTRIBUTES", "QUATERNION", 8, 100).is_ok());
+        assert!(validate_meshopt_view("ATTRIBUTES", "QUATERNION", 4, 100).is_err());
+        assert!(validate_meshopt_view("ATTRIBUTES", "EXPONENTIAL", 12, 100).is_ok());
+        assert!(validate_meshopt_view("ATTRIBUTES", "COLOR", 4, 100).is_err());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod image;
 pub mod mesh;
+pub mod parallel;
+pub mod terrain;
 
+#[cfg(feature = "gltf-import")]
 mod import_gltf;
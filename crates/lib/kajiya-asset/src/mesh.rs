@@ -2,7 +2,8 @@
 #![allow(unused_imports)]
 
 use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
-use glam::{Mat4, Quat, Vec3, Vec4};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+#[cfg(feature = "gltf-import")]
 use gltf::texture::TextureTransform;
 use kajiya_backend::bytes::into_byte_vec;
 /*use render_core::{
@@ -68,18 +69,178 @@ pub enum MeshMaterialMap {
 pub struct MeshMaterialFlags;
 impl MeshMaterialFlags {
     pub const MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT: u32 = 1;
+    /// Discard where the base color alpha is below `MeshMaterial::alpha_cutoff`.
+    pub const MESH_MATERIAL_FLAG_ALPHA_MASK: u32 = 2;
+    /// Composite over the lit scene in the forward transparent pass.
+    pub const MESH_MATERIAL_FLAG_ALPHA_BLEND: u32 = 4;
+    /// Back faces are rendered too, shaded with a flipped normal.
+    pub const MESH_MATERIAL_FLAG_DOUBLE_SIDED: u32 = 8;
+    /// The material is followed by `MAX_TERRAIN_LAYERS - 1` more, blended together
+    /// with per-vertex weights stored in the vertex colors. See `terrain.rs`.
+    pub const MESH_MATERIAL_FLAG_TERRAIN_LAYERS: u32 = 16;
 }
 
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct MeshMaterial {
     pub base_color_mult: [f32; 4],
-    pub maps: [u32; 4],
+    /// Normal, metalness-roughness, albedo, emissive, and height.
+    pub maps: [u32; 5],
     pub roughness_mult: f32,
     pub metalness_factor: f32,
     pub emissive: [f32; 3],
     pub flags: u32,
     pub map_transforms: [[f32; 6]; 4],
+    pub ior: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    /// Thin-walled transmission; light passes through the surface without refraction.
+    pub transmission: f32,
+    pub anisotropy: f32,
+    /// Rotation of the anisotropy direction from the tangent towards the bitangent, in radians.
+    pub anisotropy_rotation: f32,
+    /// Only used with `MESH_MATERIAL_FLAG_ALPHA_MASK`.
+    pub alpha_cutoff: f32,
+    /// Strength of the screen-space subsurface scattering; zero for none.
+    pub subsurface: f32,
+    /// Which of the renderer's subsurface profiles sets the scattering radii.
+    pub subsurface_profile: u32,
+    /// Depth of the height map's zero below the surface, in texture coordinates;
+    /// zero disables parallax occlusion mapping. The height map uses the albedo's UV transform.
+    pub parallax_scale: f32,
+}
+
+impl MeshMaterial {
+    /// Thin-transmissive materials are blended too, as there's no refraction.
+    pub fn is_alpha_blended(&self) -> bool {
+        (self.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_BLEND) != 0
+            || self.transmission > 0.0
+    }
+
+    pub fn is_alpha_tested(&self) -> bool {
+        (self.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK) != 0
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        (self.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED) != 0
+    }
+
+    pub fn has_subsurface(&self) -> bool {
+        self.subsurface > 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PunctualLightKind {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+/// A `KHR_lights_punctual` light, flattened into the space of the mesh which contains it.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct PunctualLight {
+    pub position: [f32; 3],
+    pub kind: u32,
+
+    /// Direction the light is pointing at; unused by point lights.
+    pub direction: [f32; 3],
+
+    /// Distance at which the light's contribution is cut off. Zero means infinite.
+    pub range: f32,
+
+    /// Color premultiplied by the intensity.
+    pub color: [f32; 3],
+
+    /// Spot cone falloff is `saturate(cos_angle * spot_angle_scale + spot_angle_offset)^2`
+    pub spot_angle_scale: f32,
+    pub spot_angle_offset: f32,
+
+    /// Radius of the spherical light source, softening its ray-traced shadows. Zero for a point source.
+    pub radius: f32,
+
+    /// Bindless image index (see `BindlessImageHandle`) of a texture which spot lights
+    /// project across their outer cone, or `PUNCTUAL_LIGHT_NO_GOBO_MAP`.
+    pub gobo_map: u32,
+
+    pub pad: f32,
+}
+
+pub const PUNCTUAL_LIGHT_NO_GOBO_MAP: u32 = !0;
+
+impl PunctualLight {
+    pub fn point(position: Vec3, color: Vec3, range: f32) -> Self {
+        Self {
+            position: position.into(),
+            kind: PunctualLightKind::Point as u32,
+            direction: [0.0, 0.0, -1.0],
+            range,
+            color: color.into(),
+            spot_angle_scale: 0.0,
+            spot_angle_offset: 1.0,
+            radius: 0.0,
+            gobo_map: PUNCTUAL_LIGHT_NO_GOBO_MAP,
+            pad: 0.0,
+        }
+    }
+
+    /// Cone angles are in radians, measured from the direction of the light.
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        range: f32,
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    ) -> Self {
+        // As recommended by the `KHR_lights_punctual` spec
+        let cos_outer = outer_cone_angle.cos();
+        let cos_inner = inner_cone_angle.cos();
+        let scale = 1.0 / (cos_inner - cos_outer).max(0.001);
+
+        Self {
+            position: position.into(),
+            kind: PunctualLightKind::Spot as u32,
+            direction: direction.normalize_or_zero().into(),
+            range,
+            color: color.into(),
+            spot_angle_scale: scale,
+            spot_angle_offset: -cos_outer * scale,
+            radius: 0.0,
+            gobo_map: PUNCTUAL_LIGHT_NO_GOBO_MAP,
+            pad: 0.0,
+        }
+    }
+
+    pub fn transform(self, xform: Mat4) -> Self {
+        let position = (xform * Vec3::from(self.position).extend(1.0)).truncate();
+        let direction = (xform * Vec3::from(self.direction).extend(0.0))
+            .truncate()
+            .normalize_or_zero();
+
+        Self {
+            position: position.into(),
+            direction: direction.into(),
+            ..self
+        }
+    }
+
+    pub fn scale_intensity(self, scale: f32) -> Self {
+        Self {
+            color: (Vec3::from(self.color) * scale).into(),
+            ..self
+        }
+    }
+
+    pub fn with_radius(self, radius: f32) -> Self {
+        Self { radius, ..self }
+    }
+
+    pub fn with_gobo_map(self, gobo_map: u32) -> Self {
+        Self { gobo_map, ..self }
+    }
 }
 
 #[derive(Clone, Default)]
@@ -94,8 +255,10 @@ pub struct TriangleMesh {
     pub materials: Vec<MeshMaterial>, // global
     pub maps: Vec<MeshMaterialMap>,   // global
     pub images: Vec<ImageSource>,
+    pub lights: Vec<PunctualLight>,
 }
 
+#[cfg(feature = "gltf-import")]
 fn iter_gltf_node_tree<F: FnMut(&gltf::scene::Node, Mat4)>(
     node: &gltf::scene::Node,
     xform: Mat4,
@@ -110,6 +273,35 @@ fn iter_gltf_node_tree<F: FnMut(&gltf::scene::Node, Mat4)>(
     }
 }
 
+#[cfg(feature = "gltf-import")]
+fn load_gltf_light(light: &gltf::khr_lights_punctual::Light, xform: Mat4) -> PunctualLight {
+    use gltf::khr_lights_punctual::Kind;
+
+    let color = Vec3::from(light.color()) * light.intensity();
+    let range = light.range().unwrap_or(0.0);
+
+    match light.kind() {
+        Kind::Directional => PunctualLight {
+            kind: PunctualLightKind::Directional as u32,
+            ..PunctualLight::point(Vec3::ZERO, color, range)
+        },
+        Kind::Point => PunctualLight::point(Vec3::ZERO, color, range),
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => PunctualLight::spot(
+            Vec3::ZERO,
+            -Vec3::Z,
+            color,
+            range,
+            inner_cone_angle,
+            outer_cone_angle,
+        ),
+    }
+    .transform(xform)
+}
+
+#[cfg(feature = "gltf-import")]
 fn get_gltf_texture_source(tex: gltf::texture::Texture) -> Option<String> {
     match tex.source().source() {
         gltf::image::Source::Uri { uri, .. } => Some(uri.to_string()),
@@ -117,10 +309,135 @@ fn get_gltf_texture_source(tex: gltf::texture::Texture) -> Option<String> {
     }
 }
 
+// Extensions not supported by the `gltf` crate, read directly from the material's JSON.
+#[cfg(feature = "gltf-import")]
+struct GltfRawMaterialExtensions {
+    emissive_strength: f32,
+    normal_texture_transform: Option<[f32; 6]>,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    anisotropy: f32,
+    anisotropy_rotation: f32,
+    subsurface: f32,
+    subsurface_profile: u32,
+    height_image: Option<usize>,
+    parallax_scale: f32,
+}
+
+#[cfg(feature = "gltf-import")]
+impl GltfRawMaterialExtensions {
+    fn new(raw_json: &serde_json::Value, mat: &gltf::material::Material) -> Self {
+        let raw_mat = mat
+            .index()
+            .and_then(|idx| raw_json.get("materials")?.get(idx))
+            .unwrap_or(&serde_json::Value::Null);
+
+        let get_f32 = |pointer: &str, default: f32| -> f32 {
+            raw_mat
+                .pointer(pointer)
+                .and_then(serde_json::Value::as_f64)
+                .map_or(default, |v| v as f32)
+        };
+
+        let emissive_strength = get_f32(
+            "/extensions/KHR_materials_emissive_strength/emissiveStrength",
+            1.0,
+        );
+
+        // Only the scalar factors are imported; the extensions' textures are ignored.
+        let clearcoat = get_f32("/extensions/KHR_materials_clearcoat/clearcoatFactor", 0.0);
+        let clearcoat_roughness = get_f32(
+            "/extensions/KHR_materials_clearcoat/clearcoatRoughnessFactor",
+            0.0,
+        );
+        let transmission = get_f32(
+            "/extensions/KHR_materials_transmission/transmissionFactor",
+            0.0,
+        );
+        let anisotropy = get_f32(
+            "/extensions/KHR_materials_anisotropy/anisotropyStrength",
+            0.0,
+        );
+        let anisotropy_rotation = get_f32(
+            "/extensions/KHR_materials_anisotropy/anisotropyRotation",
+            0.0,
+        );
+
+        // There's no glTF extension for subsurface scattering; these come from the material's extras.
+        let subsurface = get_f32("/extras/subsurface", 0.0);
+        let subsurface_profile = raw_mat
+            .pointer("/extras/subsurface_profile")
+            .and_then(serde_json::Value::as_u64)
+            .map_or(0, |v| v as u32);
+
+        // Nor for height maps; `height_texture` is a texture index, like in the standard texture infos.
+        let height_image = raw_mat
+            .pointer("/extras/height_texture")
+            .and_then(serde_json::Value::as_u64)
+            .and_then(|texture| raw_json.pointer(&format!("/textures/{}/source", texture)))
+            .and_then(serde_json::Value::as_u64)
+            .map(|image| image as usize);
+        let parallax_scale = get_f32("/extras/parallax_scale", 0.0);
+
+        let normal_texture_transform = raw_mat
+            .pointer("/normalTexture/extensions/KHR_texture_transform")
+            .map(|xform| {
+                let get_f32s = |name: &str, default: &[f32]| -> Vec<f32> {
+                    xform
+                        .get(name)
+                        .and_then(serde_json::Value::as_array)
+                        .map(|arr| {
+                            arr.iter()
+                                .filter_map(|v| v.as_f64())
+                                .map(|v| v as f32)
+                                .collect()
+                        })
+                        .filter(|arr: &Vec<f32>| arr.len() == default.len())
+                        .unwrap_or_else(|| default.to_vec())
+                };
+
+                let o = get_f32s("offset", &[0.0, 0.0]);
+                let s = get_f32s("scale", &[1.0, 1.0]);
+                let r = xform
+                    .get("rotation")
+                    .and_then(serde_json::Value::as_f64)
+                    .unwrap_or(0.0) as f32;
+
+                [
+                    r.cos() * s[0],
+                    r.sin() * s[1],
+                    -r.sin() * s[0],
+                    r.cos() * s[1],
+                    o[0],
+                    o[1],
+                ]
+            });
+
+        Self {
+            emissive_strength,
+            normal_texture_transform,
+            clearcoat,
+            clearcoat_roughness,
+            transmission,
+            anisotropy,
+            anisotropy_rotation,
+            subsurface,
+            subsurface_profile,
+            height_image,
+            parallax_scale,
+        }
+    }
+}
+
+#[cfg(feature = "gltf-import")]
 fn load_gltf_material(
     mat: &gltf::material::Material,
     document_images: &[ImageSource],
+    raw_json: &serde_json::Value,
 ) -> (Vec<MeshMaterialMap>, MeshMaterial) {
+    let raw_extensions = GltfRawMaterialExtensions::new(raw_json, mat);
+
     const DEFAULT_MAP_TRANSFORM: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
     let mut map_transforms: [[f32; 6]; 4] = [DEFAULT_MAP_TRANSFORM; 4];
 
@@ -172,7 +489,11 @@ fn load_gltf_material(
 
     map_transforms[0] = albedo_map_transform;
 
-    // TODO: add texture transform to the normal map in the `gltf` crate
+    // The `gltf` crate doesn't expose texture transforms on normal maps; use the raw JSON.
+    if let Some(xform) = raw_extensions.normal_texture_transform {
+        map_transforms[1] = xform;
+    }
+
     let normal_map =
         mat.normal_texture()
             .map_or(MeshMaterialMap::Placeholder([127, 127, 255, 255]), |tex| {
@@ -231,28 +552,222 @@ fn load_gltf_material(
         }
     }
 
-    let emissive = mat.emissive_factor();
+    let height_map = raw_extensions
+        .height_image
+        .and_then(|image| document_images.get(image))
+        .map_or(
+            MeshMaterialMap::Placeholder([255, 255, 255, 255]),
+            |source| MeshMaterialMap::Image {
+                source: source.clone(),
+                params: TexParams {
+                    gamma: TexGamma::Linear,
+                    use_mips: true,
+                    compression: TexCompressionMode::Rg,
+                    channel_swizzle: None,
+                },
+            },
+        );
+    let parallax_scale = if raw_extensions.height_image.is_some() {
+        raw_extensions.parallax_scale
+    } else {
+        0.0
+    };
+
+    let emissive = (Vec3::from(mat.emissive_factor()) * raw_extensions.emissive_strength).into();
 
     let base_color_mult = mat.pbr_metallic_roughness().base_color_factor();
     let roughness_mult = mat.pbr_metallic_roughness().roughness_factor();
     let metalness_factor = mat.pbr_metallic_roughness().metallic_factor();
 
-    //mata.normal_texture().and_then(|tex| tex.transform())
+    // 1.5 is the glTF default, corresponding to the F0 of 0.04 assumed by the shaders.
+    let ior = mat.ior().unwrap_or(1.5);
+
+    let mut flags = match mat.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => 0,
+        gltf::material::AlphaMode::Mask => MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK,
+        gltf::material::AlphaMode::Blend => MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_BLEND,
+    };
+
+    if mat.double_sided() {
+        flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED;
+    }
+    let alpha_cutoff = mat.alpha_cutoff().unwrap_or(0.5);
 
     (
-        vec![normal_map, spec_map, albedo_map, emissive_map],
+        vec![normal_map, spec_map, albedo_map, emissive_map, height_map],
         MeshMaterial {
             base_color_mult,
-            maps: [0, 1, 2, 3],
+            maps: [0, 1, 2, 3, 4],
             roughness_mult,
             metalness_factor,
             emissive,
-            flags: 0,
+            flags,
             map_transforms,
+            ior,
+            clearcoat: raw_extensions.clearcoat,
+            clearcoat_roughness: raw_extensions.clearcoat_roughness,
+            transmission: raw_extensions.transmission,
+            anisotropy: raw_extensions.anisotropy,
+            anisotropy_rotation: raw_extensions.anisotropy_rotation,
+            alpha_cutoff,
+            subsurface: raw_extensions.subsurface,
+            subsurface_profile: raw_extensions.subsurface_profile,
+            parallax_scale,
         },
     )
 }
 
+// The attributes of a glTF primitive, as read from its buffers.
+#[cfg(feature = "gltf-import")]
+struct GltfPrimitive {
+    xform: Mat4,
+    material_index: u32,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tangents: Option<Vec<[f32; 4]>>,
+    uvs: Option<Vec<[f32; 2]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    indices: Vec<u32>,
+}
+
+// A primitive ready to be appended to a `TriangleMesh`, in its space.
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+struct ProcessedPrimitive {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tangents: Vec<[f32; 4]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    material_ids: Vec<u32>,
+    // Relative to the primitive's first vertex
+    indices: Vec<u32>,
+}
+
+#[cfg(feature = "gltf-import")]
+impl GltfPrimitive {
+    // Fills in the missing attributes, generates tangents, and transforms the vertices.
+    fn process(&self) -> ProcessedPrimitive {
+        let xform = self.xform;
+        let flip_winding_order = xform.determinant() < 0.0;
+
+        let mut positions = self.positions.clone();
+        let mut normals = self.normals.clone();
+        let vertex_count = positions.len();
+
+        // Collect tangents (optional)
+        let (mut tangents, tangents_found) = match &self.tangents {
+            Some(tangents) => (tangents.clone(), true),
+            None => (vec![[1.0, 0.0, 0.0, 0.0]; vertex_count], false),
+        };
+
+        // Collect uvs (optional)
+        let (mut uvs, uvs_found) = match &self.uvs {
+            Some(uvs) => (uvs.clone(), true),
+            None => (vec![[0.0, 0.0]; vertex_count], false),
+        };
+
+        // Collect colors (optional)
+        let mut colors = self
+            .colors
+            .clone()
+            .unwrap_or_else(|| vec![[1.0, 1.0, 1.0, 1.0]; vertex_count]);
+
+        // Collect material ids
+        let mut material_ids = vec![self.material_index; vertex_count];
+
+        let mut indices = self.indices.clone();
+        if flip_winding_order {
+            for tri in indices.chunks_exact_mut(3) {
+                tri.swap(0, 2);
+            }
+        }
+
+        let tangents_degenerate = tangents_found
+            && tangents
+                .iter()
+                .zip(&normals)
+                .any(|(t, n)| is_tangent_degenerate(*t, *n));
+
+        if uvs_found && (!tangents_found || tangents_degenerate) {
+            if tangents_degenerate {
+                log::trace!("Mesh had degenerate tangents. Re-calculating the tangents...");
+            } else {
+                log::trace!("Mesh had UVs but no tangents. Calculating the tangents...");
+            }
+
+            let (new_tangents, vertex_remap) = generate_mikktspace_tangents(
+                indices.as_mut_slice(),
+                positions.as_slice(),
+                normals.as_slice(),
+                uvs.as_slice(),
+            );
+
+            tangents = new_tangents;
+            apply_vertex_remap(&mut positions, &vertex_remap);
+            apply_vertex_remap(&mut normals, &vertex_remap);
+            apply_vertex_remap(&mut uvs, &vertex_remap);
+            apply_vertex_remap(&mut colors, &vertex_remap);
+            apply_vertex_remap(&mut material_ids, &vertex_remap);
+        }
+
+        fix_degenerate_tangents(&mut tangents, &normals);
+
+        let positions = positions
+            .into_iter()
+            .map(|v| (xform * Vec3::from(v).extend(1.0)).truncate().into())
+            .collect();
+
+        let normals = normals
+            .into_iter()
+            .map(|v| {
+                (xform * Vec3::from(v).extend(0.0))
+                    .truncate()
+                    .normalize()
+                    .into()
+            })
+            .collect();
+
+        let tangents = tangents
+            .into_iter()
+            .map(|v| {
+                let v = Vec4::from(v);
+                let t = (xform * v.truncate().extend(0.0)).truncate().normalize();
+                t.extend(v.w * if flip_winding_order { -1.0 } else { 1.0 })
+                    .into()
+            })
+            .collect();
+
+        ProcessedPrimitive {
+            positions,
+            normals,
+            tangents,
+            uvs,
+            colors,
+            material_ids,
+            indices,
+        }
+    }
+}
+
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+impl TriangleMesh {
+    fn append_primitive(&mut self, mut prim: ProcessedPrimitive) {
+        let base_index = self.positions.len() as u32;
+        for i in &mut prim.indices {
+            *i += base_index;
+        }
+
+        self.indices.append(&mut prim.indices);
+        self.positions.append(&mut prim.positions);
+        self.normals.append(&mut prim.normals);
+        self.tangents.append(&mut prim.tangents);
+        self.uvs.append(&mut prim.uvs);
+        self.colors.append(&mut prim.colors);
+        self.material_ids.append(&mut prim.material_ids);
+    }
+}
+
+#[cfg(feature = "gltf-import")]
 #[derive(Clone)]
 pub struct LoadGltfScene {
     pub path: PathBuf,
@@ -260,6 +775,7 @@ pub struct LoadGltfScene {
     pub rotation: Quat,
 }
 
+#[cfg(feature = "gltf-import")]
 impl Hash for LoadGltfScene {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.path.hash(state);
@@ -271,21 +787,29 @@ impl Hash for LoadGltfScene {
     }
 }
 
+#[cfg(feature = "gltf-import")]
 #[async_trait]
 impl LazyWorker for LoadGltfScene {
     type Output = anyhow::Result<TriangleMesh>;
 
     async fn run(self, _ctx: RunContext) -> Self::Output {
-        let (gltf, buffers, imgs) = crate::import_gltf::import(&self.path)
-            .with_context(|| format!("Loading GLTF scene from {:?}", self.path))?;
+        let ((gltf, buffers, imgs), raw_json) =
+            crate::import_gltf::import_with_raw_json(&self.path)
+                .with_context(|| format!("Loading GLTF scene from {:?}", self.path))?;
 
         if let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) {
             let mut res: TriangleMesh = TriangleMesh::default();
 
+            // Materials and lights are gathered, and the attributes read, in scene order.
+            // The primitives are then processed in parallel, and appended in the same order.
+            let mut primitives: Vec<GltfPrimitive> = Vec::new();
+
             let mut process_node = |node: &gltf::scene::Node, xform: Mat4| {
-                if let Some(mesh) = node.mesh() {
-                    let flip_winding_order = xform.determinant() < 0.0;
+                if let Some(light) = node.light() {
+                    res.lights.push(load_gltf_light(&light, xform));
+                }
 
+                if let Some(mesh) = node.mesh() {
                     for prim in mesh.primitives() {
                         let reader = prim.reader(|buffer| Some(&buffers[buffer.index()]));
 
@@ -293,7 +817,7 @@ impl LazyWorker for LoadGltfScene {
 
                         {
                             let (mut maps, mut material) =
-                                load_gltf_material(&prim.material(), imgs.as_slice());
+                                load_gltf_material(&prim.material(), imgs.as_slice(), &raw_json);
 
                             let map_base = res.maps.len() as u32;
                             for id in material.maps.iter_mut() {
@@ -318,110 +842,39 @@ impl LazyWorker for LoadGltfScene {
                             return;
                         };
 
-                        // Collect tangents (optional)
-                        let (mut tangents, tangents_found) =
-                            if let Some(iter) = reader.read_tangents() {
-                                (iter.collect::<Vec<_>>(), true)
-                            } else {
-                                (vec![[1.0, 0.0, 0.0, 0.0]; positions.len()], false)
-                            };
-
-                        // Collect uvs (optional)
-                        let (mut uvs, uvs_found) = if let Some(iter) = reader.read_tex_coords(0) {
-                            (iter.into_f32().collect::<Vec<_>>(), true)
-                        } else {
-                            (vec![[0.0, 0.0]; positions.len()], false)
-                        };
-
-                        // Collect colors (optional)
-                        let mut colors = if let Some(iter) = reader.read_colors(0) {
-                            iter.into_rgba_f32().collect::<Vec<_>>()
-                        } else {
-                            vec![[1.0, 1.0, 1.0, 1.0]; positions.len()]
-                        };
-
-                        // Collect material ids
-                        let mut material_ids = vec![res_material_index; positions.len()];
-
                         // Collect indices
-                        let mut indices: Vec<u32>;
+                        let indices: Vec<u32> = if let Some(indices_reader) = reader.read_indices()
                         {
-                            if let Some(indices_reader) = reader.read_indices() {
-                                indices = indices_reader.into_u32().collect();
-                            } else {
-                                if positions.is_empty() {
-                                    return;
-                                }
-
-                                match prim.mode() {
-                                    gltf::mesh::Mode::Triangles => {
-                                        indices = (0..positions.len() as u32).collect();
-                                    }
-                                    _ => {
-                                        panic!(
-                                            "Primitive mode {:?} not supported yet",
-                                            prim.mode()
-                                        );
-                                    }
-                                }
+                            indices_reader.into_u32().collect()
+                        } else {
+                            if positions.is_empty() {
+                                return;
                             }
 
-                            if flip_winding_order {
-                                for tri in indices.chunks_exact_mut(3) {
-                                    tri.swap(0, 2);
+                            match prim.mode() {
+                                gltf::mesh::Mode::Triangles => {
+                                    (0..positions.len() as u32).collect()
+                                }
+                                _ => {
+                                    panic!("Primitive mode {:?} not supported yet", prim.mode());
                                 }
                             }
-                        }
-
-                        if !tangents_found && uvs_found {
-                            log::trace!(
-                                "Mesh had UVs but no tangents. Calculating the tangents..."
-                            );
-
-                            mikktspace::generate_tangents(&mut TangentCalcContext {
-                                indices: indices.as_slice(),
-                                positions: positions.as_slice(),
-                                normals: normals.as_slice(),
-                                uvs: uvs.as_slice(),
-                                tangents: tangents.as_mut_slice(),
-                            });
-                        }
-
-                        // --------------------------------------------------------
-                        // Write it all to the output
-
-                        {
-                            // log::info!("Loading a mesh with {} indices", indices.len());
-                            let base_index = res.positions.len() as u32;
-                            for i in &mut indices {
-                                *i += base_index;
-                            }
-
-                            res.indices.append(&mut indices);
-                            res.colors.append(&mut colors);
-                            res.material_ids.append(&mut material_ids);
-                        }
-
-                        for v in positions {
-                            let pos = (xform * Vec3::from(v).extend(1.0)).truncate();
-                            res.positions.push(pos.into());
-                        }
-
-                        for v in normals {
-                            let norm = (xform * Vec3::from(v).extend(0.0)).truncate().normalize();
-                            res.normals.push(norm.into());
-                        }
-
-                        for v in tangents {
-                            let v = Vec4::from(v);
-                            let t = (xform * v.truncate().extend(0.0)).truncate().normalize();
-                            res.tangents.push(
-                                t.extend(v.w * if flip_winding_order { -1.0 } else { 1.0 })
-                                    .into(),
-                            );
-                        }
+                        };
 
-                        res.uvs.append(&mut uvs);
+                        primitives.push(GltfPrimitive {
+                            xform,
+                            material_index: res_material_index,
+                            positions,
+                            normals,
+                            tangents: reader.read_tangents().map(|iter| iter.collect()),
+                            uvs: reader
+                                .read_tex_coords(0)
+                                .map(|iter| iter.into_f32().collect()),
+                            colors: reader
+                                .read_colors(0)
+                                .map(|iter| iter.into_rgba_f32().collect()),
+                            indices,
+                        });
                     }
                 }
             };
@@ -435,6 +888,11 @@ impl LazyWorker for LoadGltfScene {
                 iter_gltf_node_tree(&node, xform, &mut process_node);
             }
 
+            let primitives = crate::parallel::parallel_map(&primitives, GltfPrimitive::process);
+            for prim in primitives {
+                res.append_primitive(prim);
+            }
+
             Ok(res)
         } else {
             Err(anyhow::anyhow!("No default scene found in gltf"))
@@ -442,6 +900,446 @@ impl LazyWorker for LoadGltfScene {
     }
 }
 
+#[cfg(feature = "obj-import")]
+fn load_obj_material(
+    mat: &tobj::Material,
+    base_dir: &Path,
+) -> (Vec<MeshMaterialMap>, MeshMaterial) {
+    const DEFAULT_MAP_TRANSFORM: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+    let texture_map = |path: &str, gamma: TexGamma, compression: TexCompressionMode| {
+        // Skip options such as `-bm 1.0` preceding the file name
+        let path = if path.starts_with('-') {
+            path.split_whitespace().last().unwrap_or_default()
+        } else {
+            path
+        };
+
+        (!path.is_empty()).then(|| MeshMaterialMap::Image {
+            source: ImageSource::File(base_dir.join(path.replace('\\', "/"))),
+            params: TexParams {
+                gamma,
+                use_mips: true,
+                compression,
+                channel_swizzle: None,
+            },
+        })
+    };
+
+    let param = |name: &str| -> Option<f32> {
+        mat.unknown_param
+            .get(name)
+            .and_then(|val| val.split_whitespace().next()?.parse().ok())
+    };
+
+    let normal_map = mat
+        .unknown_param
+        .get("norm")
+        .map(String::as_str)
+        .or_else(|| (!mat.normal_texture.is_empty()).then(|| mat.normal_texture.as_str()))
+        .and_then(|path| texture_map(path, TexGamma::Linear, TexCompressionMode::Rg))
+        .unwrap_or(MeshMaterialMap::Placeholder([127, 127, 255, 255]));
+
+    let albedo_map = texture_map(
+        &mat.diffuse_texture,
+        TexGamma::Srgb,
+        TexCompressionMode::Rgba,
+    )
+    .unwrap_or(MeshMaterialMap::Placeholder([255, 255, 255, 255]));
+
+    let emissive_map = mat
+        .unknown_param
+        .get("map_Ke")
+        .and_then(|path| texture_map(path, TexGamma::Srgb, TexCompressionMode::Rgba))
+        .unwrap_or(MeshMaterialMap::Placeholder([255, 255, 255, 255]));
+
+    // Height maps for parallax occlusion mapping; the scale comes from the gain of `-mm base gain`.
+    let height_source = mat.unknown_param.get("disp");
+    let height_map = height_source
+        .and_then(|path| texture_map(path, TexGamma::Linear, TexCompressionMode::Rg))
+        .unwrap_or(MeshMaterialMap::Placeholder([255, 255, 255, 255]));
+    let parallax_scale = height_source.map_or(0.0, |options| {
+        options
+            .split_whitespace()
+            .skip_while(|&token| token != "-mm")
+            .nth(2)
+            .and_then(|gain| gain.parse().ok())
+            .unwrap_or(0.05)
+    });
+
+    // Roughness and metalness are scalar factors applied to this
+    let spec_map = MeshMaterialMap::Placeholder([255, 255, 127, 255]);
+
+    // Prefer the PBR extension to MTL if present; otherwise approximate roughness from the Phong exponent.
+    let roughness_mult = param("Pr").unwrap_or_else(|| (2.0 / (mat.shininess + 2.0)).sqrt());
+    let metalness_factor = param("Pm").unwrap_or(0.0);
+
+    let emissive = mat
+        .unknown_param
+        .get("Ke")
+        .map(|val| {
+            let mut rgb = val.split_whitespace().filter_map(|v| v.parse::<f32>().ok());
+            let r = rgb.next().unwrap_or(0.0);
+            [r, rgb.next().unwrap_or(r), rgb.next().unwrap_or(r)]
+        })
+        .unwrap_or([0.0; 3]);
+
+    let ior = if mat.optical_density > 1.0 {
+        mat.optical_density
+    } else {
+        1.5
+    };
+
+    // From the PBR extension to MTL; `anisor` is a fraction of a full turn.
+    let clearcoat = param("Pc").unwrap_or(0.0);
+    let clearcoat_roughness = param("Pcr").unwrap_or(0.0);
+    let anisotropy = param("aniso").unwrap_or(0.0);
+    let anisotropy_rotation = param("anisor").unwrap_or(0.0) * std::f32::consts::TAU;
+
+    // MTL has no alpha modes. Partial dissolve is blended, and otherwise the diffuse
+    // texture's alpha is used as a mask, which is what most OBJ foliage expects.
+    let alpha_flags = if mat.dissolve < 1.0 {
+        MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_BLEND
+    } else if !mat.diffuse_texture.is_empty() {
+        MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK
+    } else {
+        0
+    };
+
+    // Nor is there a notion of sidedness, and OBJ winding is often inconsistent.
+    let flags = alpha_flags | MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED;
+
+    let [r, g, b] = mat.diffuse;
+
+    (
+        vec![normal_map, spec_map, albedo_map, emissive_map, height_map],
+        MeshMaterial {
+            base_color_mult: [r, g, b, mat.dissolve],
+            maps: [0, 1, 2, 3, 4],
+            roughness_mult,
+            metalness_factor,
+            emissive,
+            flags,
+            map_transforms: [DEFAULT_MAP_TRANSFORM; 4],
+            ior,
+            clearcoat,
+            clearcoat_roughness,
+            transmission: 0.0,
+            anisotropy,
+            anisotropy_rotation,
+            alpha_cutoff: 0.5,
+            subsurface: 0.0,
+            subsurface_profile: 0,
+            parallax_scale,
+        },
+    )
+}
+
+#[cfg(feature = "obj-import")]
+fn default_obj_material() -> tobj::Material {
+    tobj::Material {
+        diffuse: [1.0; 3],
+        shininess: 0.0,
+        dissolve: 1.0,
+        ..Default::default()
+    }
+}
+
+/// Material libraries referenced by an OBJ file, and the textures referenced by those.
+fn obj_external_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("./"));
+    let obj_source = std::fs::read_to_string(path)?;
+
+    let mtl_paths: Vec<PathBuf> = obj_source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("mtllib "))
+        .flat_map(str::split_whitespace)
+        .map(|name| base_dir.join(name))
+        .filter(|path| path.exists())
+        .collect();
+
+    let mut files = mtl_paths.clone();
+    for mtl_path in mtl_paths {
+        let mtl_source = std::fs::read_to_string(&mtl_path)?;
+
+        // Texture statements end with the file name, possibly preceded by options
+        for line in mtl_source.lines() {
+            let mut tokens = line.split_whitespace();
+            let is_texture = tokens.next().map_or(false, |keyword| {
+                keyword.starts_with("map_") || matches!(keyword, "bump" | "norm" | "disp")
+            });
+
+            if let Some(name) = tokens.last().filter(|_| is_texture) {
+                let texture_path = base_dir.join(name.replace('\\', "/"));
+                if texture_path.exists() {
+                    files.push(texture_path);
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// The formats meshes are imported from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshFormat {
+    /// glTF and GLB; assumed for any extension other than `.obj`.
+    Gltf,
+    Obj,
+}
+
+impl MeshFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let is_obj = path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("obj"));
+
+        if is_obj {
+            Self::Obj
+        } else {
+            Self::Gltf
+        }
+    }
+
+    /// The Cargo feature the importer of this format is compiled in with.
+    pub fn feature(self) -> &'static str {
+        match self {
+            Self::Gltf => "gltf-import",
+            Self::Obj => "obj-import",
+        }
+    }
+
+    /// Whether this build can import the format.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Gltf => cfg!(feature = "gltf-import"),
+            Self::Obj => cfg!(feature = "obj-import"),
+        }
+    }
+
+    pub fn ensure_supported(self, path: &Path) -> anyhow::Result<()> {
+        if self.is_supported() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Can't import {:?}: built without the `{}` feature of kajiya-asset",
+                path,
+                self.feature()
+            ))
+        }
+    }
+}
+
+/// All the files read when importing the mesh at `path`: the scene itself,
+/// plus any external buffers, textures, and material libraries it references.
+pub fn mesh_source_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let format = MeshFormat::from_path(path);
+    format.ensure_supported(path)?;
+
+    let mut files = vec![path.to_owned()];
+    match format {
+        MeshFormat::Obj => files.extend(obj_external_files(path)?),
+        MeshFormat::Gltf => {
+            #[cfg(feature = "gltf-import")]
+            files.extend(crate::import_gltf::external_files(path)?);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(feature = "obj-import")]
+#[derive(Clone)]
+pub struct LoadObjScene {
+    pub path: PathBuf,
+    pub scale: f32,
+    pub rotation: Quat,
+}
+
+#[cfg(feature = "obj-import")]
+impl Hash for LoadObjScene {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.scale.to_ne_bytes().hash(state);
+        self.rotation.x.to_ne_bytes().hash(state);
+        self.rotation.y.to_ne_bytes().hash(state);
+        self.rotation.z.to_ne_bytes().hash(state);
+        self.rotation.w.to_ne_bytes().hash(state);
+    }
+}
+
+#[cfg(feature = "obj-import")]
+#[async_trait]
+impl LazyWorker for LoadObjScene {
+    type Output = anyhow::Result<TriangleMesh>;
+
+    async fn run(self, _ctx: RunContext) -> Self::Output {
+        let (models, materials) = tobj::load_obj(&self.path, &tobj::GPU_LOAD_OPTIONS)
+            .with_context(|| format!("Loading OBJ scene from {:?}", self.path))?;
+
+        let mut materials = materials.unwrap_or_else(|err| {
+            log::warn!("Failed to load the materials of {:?}: {}", self.path, err);
+            Vec::new()
+        });
+
+        // Meshes without a material reference this one
+        let default_material_idx = materials.len();
+        materials.push(default_obj_material());
+
+        let base_dir = self.path.parent().unwrap_or_else(|| Path::new("./"));
+        let mut res: TriangleMesh = TriangleMesh::default();
+
+        for mat in &materials {
+            let (mut maps, mut material) = load_obj_material(mat, base_dir);
+
+            let map_base = res.maps.len() as u32;
+            for id in material.maps.iter_mut() {
+                *id += map_base;
+            }
+
+            res.materials.push(material);
+            res.maps.append(&mut maps);
+        }
+
+        let xform = Mat4::from_scale_rotation_translation(
+            Vec3::splat(self.scale),
+            self.rotation,
+            Vec3::ZERO,
+        );
+
+        // Models are processed in parallel, and appended in order.
+        let primitives = crate::parallel::parallel_map(&models, |model| {
+            let mesh = &model.mesh;
+
+            let mut positions: Vec<[f32; 3]> = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|v| [v[0], v[1], v[2]])
+                .collect();
+
+            if positions.is_empty() {
+                return None;
+            }
+
+            let mut indices = mesh.indices.clone();
+
+            let mut normals: Vec<[f32; 3]> = if mesh.normals.len() == mesh.positions.len() {
+                mesh.normals
+                    .chunks_exact(3)
+                    .map(|v| [v[0], v[1], v[2]])
+                    .collect()
+            } else {
+                calculate_smooth_normals(&positions, &indices)
+            };
+
+            // OBJ has the V coordinate pointing up; we use the glTF convention.
+            let (mut uvs, uvs_found) = if mesh.texcoords.len() / 2 == positions.len() {
+                (
+                    mesh.texcoords
+                        .chunks_exact(2)
+                        .map(|v| [v[0], 1.0 - v[1]])
+                        .collect::<Vec<_>>(),
+                    true,
+                )
+            } else {
+                (vec![[0.0, 0.0]; positions.len()], false)
+            };
+
+            let mut colors: Vec<[f32; 4]> = if mesh.vertex_color.len() == mesh.positions.len() {
+                mesh.vertex_color
+                    .chunks_exact(3)
+                    .map(|v| [v[0], v[1], v[2], 1.0])
+                    .collect()
+            } else {
+                vec![[1.0, 1.0, 1.0, 1.0]; positions.len()]
+            };
+
+            let mut tangents = vec![[1.0, 0.0, 0.0, 0.0]; positions.len()];
+            if uvs_found {
+                let (new_tangents, vertex_remap) = generate_mikktspace_tangents(
+                    indices.as_mut_slice(),
+                    positions.as_slice(),
+                    normals.as_slice(),
+                    uvs.as_slice(),
+                );
+
+                tangents = new_tangents;
+                apply_vertex_remap(&mut positions, &vertex_remap);
+                apply_vertex_remap(&mut normals, &vertex_remap);
+                apply_vertex_remap(&mut uvs, &vertex_remap);
+                apply_vertex_remap(&mut colors, &vertex_remap);
+            }
+
+            fix_degenerate_tangents(&mut tangents, &normals);
+
+            let material_id = mesh.material_id.unwrap_or(default_material_idx) as u32;
+
+            Some(ProcessedPrimitive {
+                material_ids: vec![material_id; positions.len()],
+                positions: positions
+                    .into_iter()
+                    .map(|v| (xform * Vec3::from(v).extend(1.0)).truncate().into())
+                    .collect(),
+                normals: normals
+                    .into_iter()
+                    .map(|v| {
+                        (xform * Vec3::from(v).extend(0.0))
+                            .truncate()
+                            .normalize_or_zero()
+                            .into()
+                    })
+                    .collect(),
+                tangents: tangents
+                    .into_iter()
+                    .map(|v| {
+                        let v = Vec4::from(v);
+                        let t = (xform * v.truncate().extend(0.0))
+                            .truncate()
+                            .normalize_or_zero();
+                        t.extend(v.w).into()
+                    })
+                    .collect(),
+                uvs,
+                colors,
+                indices,
+            })
+        });
+
+        for prim in primitives.into_iter().flatten() {
+            res.append_primitive(prim);
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+fn calculate_smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[tri[i] as usize]));
+
+        // Area-weighted
+        let n = (b - a).cross(c - a);
+        for &i in tri {
+            normals[i as usize] += n;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|n| {
+            if n.length_squared() > 0.0 {
+                n.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct PackedVertex {
@@ -449,6 +1347,15 @@ pub struct PackedVertex {
     normal: u32,
 }
 
+impl PackedVertex {
+    pub fn new(pos: [f32; 3], normal: [f32; 3]) -> Self {
+        Self {
+            pos,
+            normal: pack_unit_direction_11_10_11(normal[0], normal[1], normal[2]),
+        }
+    }
+}
+
 fn pack_unit_direction_11_10_11(x: f32, y: f32, z: f32) -> u32 {
     let x = ((x.max(-1.0).min(1.0) * 0.5 + 0.5) * ((1u32 << 11u32) - 1u32) as f32) as u32;
     let y = ((y.max(-1.0).min(1.0) * 0.5 + 0.5) * ((1u32 << 10u32) - 1u32) as f32) as u32;
@@ -457,6 +1364,228 @@ fn pack_unit_direction_11_10_11(x: f32, y: f32, z: f32) -> u32 {
     (z << 21) | (y << 11) | x
 }
 
+/// Lossy compression of the vertex streams of a baked mesh, for large scenes where vertex
+/// memory and bandwidth matter more than the last bits of precision.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct VertexQuantization {
+    /// 16-bit positions within the bounds of the mesh, and octahedral normals with 8 bits
+    /// per component: `QuantizedVertex`, half the size of `PackedVertex`.
+    pub positions: bool,
+    /// 16-bit UVs within the UV range of the mesh. Meshes whose UVs span more than
+    /// `MAX_QUANTIZED_UV_EXTENT` keep them as they are, as texels would drift.
+    pub uvs: bool,
+}
+
+pub const MAX_QUANTIZED_UV_EXTENT: f32 = 4.0;
+
+pub struct VertexQuantizationFlags;
+impl VertexQuantizationFlags {
+    /// The vertices are in `PackedTriMesh::quantized_verts` rather than `verts`.
+    pub const QUANTIZED_POSITIONS: u32 = 1;
+    /// The UVs are in `PackedTriMesh::quantized_uvs` rather than `uvs`.
+    pub const QUANTIZED_UVS: u32 = 2;
+}
+
+/// How to get the quantized vertex data of a mesh back: `value * scale + offset`, with positions
+/// as signed-normalized, and UVs as unsigned-normalized values.
+///
+/// Laid out as in the `Mesh` of `inc/mesh.hlsl`, which the vertex fetch helpers dequantize with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct VertexDequantization {
+    /// `VertexQuantizationFlags`
+    pub flags: u32,
+    pub position_scale: [f32; 3],
+    pub position_offset: [f32; 3],
+    pub uv_scale: [f32; 2],
+    pub uv_offset: [f32; 2],
+}
+
+impl Default for VertexDequantization {
+    fn default() -> Self {
+        Self {
+            flags: 0,
+            position_scale: [1.0; 3],
+            position_offset: [0.0; 3],
+            uv_scale: [1.0; 2],
+            uv_offset: [0.0; 2],
+        }
+    }
+}
+
+impl VertexDequantization {
+    pub fn has_quantized_positions(&self) -> bool {
+        (self.flags & VertexQuantizationFlags::QUANTIZED_POSITIONS) != 0
+    }
+
+    pub fn has_quantized_uvs(&self) -> bool {
+        (self.flags & VertexQuantizationFlags::QUANTIZED_UVS) != 0
+    }
+
+    /// The 3x4 row-major matrix taking quantized positions to mesh space.
+    pub fn position_transform(&self) -> [[f32; 4]; 3] {
+        let [sx, sy, sz] = self.position_scale;
+        let [ox, oy, oz] = self.position_offset;
+        [[sx, 0.0, 0.0, ox], [0.0, sy, 0.0, oy], [0.0, 0.0, sz, oz]]
+    }
+}
+
+/// A vertex with quantized positions, in the `R16G16B16A16_SNORM` layout which acceleration
+/// structures can be built from directly; the fourth component holds the normal.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct QuantizedVertex {
+    pub pos: [i16; 3],
+    normal: u16,
+}
+
+impl QuantizedVertex {
+    pub fn position(&self, dequantization: &VertexDequantization) -> [f32; 3] {
+        let mut res = [0.0; 3];
+        for i in 0..3 {
+            let unit = (self.pos[i] as f32 / i16::MAX as f32).max(-1.0);
+            res[i] = unit * dequantization.position_scale[i] + dequantization.position_offset[i];
+        }
+        res
+    }
+}
+
+fn octahedral_encode(n: Vec3) -> Vec2 {
+    let n = n / (n.x.abs() + n.y.abs() + n.z.abs());
+    let xy = Vec2::new(n.x, n.y);
+
+    if n.z < 0.0 {
+        let sign = Vec2::new(xy.x.signum(), xy.y.signum());
+        (Vec2::ONE - Vec2::new(xy.y.abs(), xy.x.abs())) * sign
+    } else {
+        xy
+    }
+}
+
+fn octahedral_decode(v: Vec2) -> Vec3 {
+    let mut n = Vec3::new(v.x, v.y, 1.0 - v.x.abs() - v.y.abs());
+    let t = (-n.z).max(0.0);
+    n.x -= t * n.x.signum();
+    n.y -= t * n.y.signum();
+    n.normalize()
+}
+
+/// 8 bits per component, as unorms of the encoding remapped to [0, 1]; the same as the `oct16`
+/// G-buffer normals. Of the four nearest encodings, picks whichever decodes closest to `normal`.
+fn pack_octahedral_normal_16(normal: [f32; 3]) -> u16 {
+    const MAX: f32 = 255.0;
+
+    let n = Vec3::from(normal).normalize_or_zero();
+    if n == Vec3::ZERO {
+        return pack_octahedral_normal_16([0.0, 0.0, 1.0]);
+    }
+
+    let unorm = (octahedral_encode(n) * 0.5 + 0.5) * MAX;
+    let decode = |x: f32, y: f32| octahedral_decode(Vec2::new(x, y) / MAX * 2.0 - 1.0);
+
+    let mut best = (unorm.x.round(), unorm.y.round());
+    let mut best_dot = decode(best.0, best.1).dot(n);
+
+    for x in [unorm.x.floor(), unorm.x.ceil()] {
+        for y in [unorm.y.floor(), unorm.y.ceil()] {
+            let (x, y) = (x.clamp(0.0, MAX), y.clamp(0.0, MAX));
+            let dot = decode(x, y).dot(n);
+            if dot > best_dot {
+                best = (x, y);
+                best_dot = dot;
+            }
+        }
+    }
+
+    best.0 as u16 | ((best.1 as u16) << 8)
+}
+
+// Per component, the center of the values, and half their extent.
+fn component_bounds<const N: usize>(values: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let mut min = [f32::MAX; N];
+    let mut max = [f32::MIN; N];
+
+    for v in values {
+        for i in 0..N {
+            min[i] = min[i].min(v[i]);
+            max[i] = max[i].max(v[i]);
+        }
+    }
+
+    let mut center = [0.0; N];
+    let mut half_extent = [0.0; N];
+    for i in 0..N {
+        center[i] = 0.5 * (min[i] + max[i]);
+        half_extent[i] = 0.5 * (max[i] - min[i]);
+    }
+
+    (center, half_extent)
+}
+
+fn quantize_positions(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    dequantization: &mut VertexDequantization,
+) -> Vec<QuantizedVertex> {
+    let (center, half_extent) = component_bounds(positions);
+
+    dequantization.flags |= VertexQuantizationFlags::QUANTIZED_POSITIONS;
+    dequantization.position_scale = half_extent;
+    dequantization.position_offset = center;
+
+    positions
+        .iter()
+        .zip(normals)
+        .map(|(pos, normal)| {
+            let mut q = [0i16; 3];
+            for i in 0..3 {
+                // Flat along this axis otherwise, with every vertex at the center.
+                if half_extent[i] > 0.0 {
+                    let unit = ((pos[i] - center[i]) / half_extent[i]).clamp(-1.0, 1.0);
+                    q[i] = (unit * i16::MAX as f32).round() as i16;
+                }
+            }
+
+            QuantizedVertex {
+                pos: q,
+                normal: pack_octahedral_normal_16(*normal),
+            }
+        })
+        .collect()
+}
+
+fn quantize_uvs(
+    uvs: &[[f32; 2]],
+    dequantization: &mut VertexDequantization,
+) -> Option<Vec<[u16; 2]>> {
+    let (center, half_extent) = component_bounds(uvs);
+    let extent = [half_extent[0] * 2.0, half_extent[1] * 2.0];
+    let min = [center[0] - half_extent[0], center[1] - half_extent[1]];
+
+    if !(extent[0] <= MAX_QUANTIZED_UV_EXTENT && extent[1] <= MAX_QUANTIZED_UV_EXTENT) {
+        return None;
+    }
+
+    dequantization.flags |= VertexQuantizationFlags::QUANTIZED_UVS;
+    dequantization.uv_scale = extent;
+    dequantization.uv_offset = min;
+
+    Some(
+        uvs.iter()
+            .map(|uv| {
+                let mut q = [0u16; 2];
+                for i in 0..2 {
+                    if extent[i] > 0.0 {
+                        let unit = ((uv[i] - min[i]) / extent[i]).clamp(0.0, 1.0);
+                        q[i] = (unit * u16::MAX as f32).round() as u16;
+                    }
+                }
+                q
+            })
+            .collect(),
+    )
+}
+
 #[repr(packed)]
 pub struct FlatVec<T> {
     len: u64,
@@ -788,6 +1917,9 @@ def_asset! {
     GpuImage {
         format { kajiya_backend::ash::vk::Format }
         extent { [u32; 3] }
+        // Each mip contains the data of all array layers, one after another.
+        array_layers { u32 }
+        is_cube { bool }
         mips { Vec(Vec(u8)) }
     }
 }
@@ -796,14 +1928,24 @@ def_asset! {
 def_asset! {
     #[derive(Clone)]
     PackedTriMesh {
+        // Unless quantized; see `vertex_dequantization`.
         verts { Vec(PackedVertex) }
         uvs { Vec([f32; 2]) }
+        quantized_verts { Vec(QuantizedVertex) }
+        quantized_uvs { Vec([u16; 2]) }
+        vertex_dequantization { VertexDequantization }
         tangents { Vec([f32; 4]) }
         colors { Vec([f32; 4]) }
         indices { Vec(u32) }
         material_ids { Vec(u32) }
         materials { Vec(MeshMaterial) }
         maps { Vec(Asset(GpuImage)) }
+        lights { Vec(PunctualLight) }
+        lods { Vec(MeshLod) }
+        lod_indices { Vec(u32) }
+        meshlets { Vec(Meshlet) }
+        meshlet_vertices { Vec(u32) }
+        meshlet_triangles { Vec(u8) }
     }
 }
 
@@ -821,16 +1963,267 @@ pub struct PackedTriangleMesh {
 
 pub type PackedTriangleMesh = PackedTriMesh::Proto;
 
+impl PackedTriMesh::Flat {
+    pub fn vertex_count(&self) -> usize {
+        if self.vertex_dequantization().has_quantized_positions() {
+            self.quantized_verts.len()
+        } else {
+            self.verts.len()
+        }
+    }
+
+    /// In mesh space, whether quantized or not.
+    pub fn vertex_position(&self, idx: usize) -> [f32; 3] {
+        let dequantization = self.vertex_dequantization();
+        if dequantization.has_quantized_positions() {
+            self.quantized_verts[idx].position(&dequantization)
+        } else {
+            self.verts[idx].pos
+        }
+    }
+
+    // By value, as the field of the packed struct may be unaligned.
+    pub fn vertex_dequantization(&self) -> VertexDequantization {
+        self.vertex_dequantization
+    }
+}
+
+/// A simplified version of the mesh, indexing the same vertices as the full-detail one.
+///
+/// The full-detail mesh is the implicit LOD 0, and isn't stored in the LOD list.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct MeshLod {
+    /// Offset into `lod_indices`
+    pub index_offset: u32,
+    pub index_count: u32,
+
+    /// Maximum deviation from the full-detail mesh, in mesh space units.
+    pub error: f32,
+    pub pad: u32,
+}
+
+pub(crate) const MAX_MESH_LOD_COUNT: usize = 8;
+
+// Don't bother simplifying meshes which are already cheap.
+const MIN_MESH_LOD_INDEX_COUNT: usize = 3 * 256;
+
+/// A small cluster of triangles of the full-detail mesh.
+///
+/// Vertices are indices into the mesh's vertex arrays, stored in `meshlet_vertices`.
+/// Triangles are triplets of `u8` indices into the meshlet's own vertex list,
+/// stored in `meshlet_triangles`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Meshlet {
+    pub vertex_offset: u32,
+    pub triangle_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+
+    /// Bounding sphere, in mesh space
+    pub center: [f32; 3],
+    pub radius: f32,
+
+    /// The meshlet is backfacing if `dot(normalize(cone_apex - camera_position), cone_axis) >= cone_cutoff`
+    pub cone_apex: [f32; 3],
+    pub cone_cutoff: f32,
+    pub cone_axis: [f32; 3],
+    pub pad: u32,
+}
+
+pub const MESHLET_MAX_VERTICES: usize = 64;
+pub const MESHLET_MAX_TRIANGLES: usize = 124;
+
+fn generate_meshlets(positions: &[[f32; 3]], indices: &[u32]) -> (Vec<Meshlet>, Vec<u32>, Vec<u8>) {
+    if positions.is_empty() || indices.is_empty() {
+        return Default::default();
+    }
+
+    let positions_ptr = positions.as_ptr() as *const f32;
+    let positions_stride = std::mem::size_of::<[f32; 3]>();
+
+    let max_meshlets = unsafe {
+        meshopt::ffi::meshopt_buildMeshletsBound(
+            indices.len(),
+            MESHLET_MAX_VERTICES,
+            MESHLET_MAX_TRIANGLES,
+        )
+    };
+
+    let mut raw_meshlets: Vec<meshopt::ffi::meshopt_Meshlet> = vec![
+        meshopt::ffi::meshopt_Meshlet {
+            vertex_offset: 0,
+            triangle_offset: 0,
+            vertex_count: 0,
+            triangle_count: 0,
+        };
+        max_meshlets
+    ];
+    let mut meshlet_vertices = vec![0u32; max_meshlets * MESHLET_MAX_VERTICES];
+    let mut meshlet_triangles = vec![0u8; max_meshlets * MESHLET_MAX_TRIANGLES * 3];
+
+    let meshlet_count = unsafe {
+        meshopt::ffi::meshopt_buildMeshlets(
+            raw_meshlets.as_mut_ptr(),
+            meshlet_vertices.as_mut_ptr(),
+            meshlet_triangles.as_mut_ptr(),
+            indices.as_ptr(),
+            indices.len(),
+            positions_ptr,
+            positions.len(),
+            positions_stride,
+            MESHLET_MAX_VERTICES,
+            MESHLET_MAX_TRIANGLES,
+            // Cone weight; favor tighter cones a bit for backface culling.
+            0.25,
+        )
+    };
+    raw_meshlets.truncate(meshlet_count);
+
+    if let Some(last) = raw_meshlets.last() {
+        meshlet_vertices.truncate((last.vertex_offset + last.vertex_count) as usize);
+
+        // Keep the triangle data 4-byte aligned for GPU access
+        let triangle_bytes = (last.triangle_offset + last.triangle_count * 3) as usize;
+        meshlet_triangles.truncate((triangle_bytes + 3) & !3);
+    }
+
+    let meshlets = raw_meshlets
+        .iter()
+        .map(|m| {
+            let bounds = unsafe {
+                meshopt::ffi::meshopt_computeMeshletBounds(
+                    meshlet_vertices.as_ptr().add(m.vertex_offset as usize),
+                    meshlet_triangles.as_ptr().add(m.triangle_offset as usize),
+                    m.triangle_count as usize,
+                    positions_ptr,
+                    positions.len(),
+                    positions_stride,
+                )
+            };
+
+            Meshlet {
+                vertex_offset: m.vertex_offset,
+                triangle_offset: m.triangle_offset,
+                vertex_count: m.vertex_count,
+                triangle_count: m.triangle_count,
+                center: bounds.center,
+                radius: bounds.radius,
+                cone_apex: bounds.cone_apex,
+                cone_cutoff: bounds.cone_cutoff,
+                cone_axis: bounds.cone_axis,
+                pad: 0,
+            }
+        })
+        .collect();
+
+    (meshlets, meshlet_vertices, meshlet_triangles)
+}
+
+fn generate_mesh_lods(positions: &[[f32; 3]], indices: &[u32]) -> (Vec<MeshLod>, Vec<u32>) {
+    let mut lods: Vec<MeshLod> = Vec::new();
+    let mut lod_indices: Vec<u32> = Vec::new();
+
+    if positions.is_empty() || indices.len() < MIN_MESH_LOD_INDEX_COUNT {
+        return (lods, lod_indices);
+    }
+
+    let positions_ptr = positions.as_ptr() as *const f32;
+    let positions_stride = std::mem::size_of::<[f32; 3]>();
+
+    let error_scale = unsafe {
+        meshopt::ffi::meshopt_simplifyScale(positions_ptr, positions.len(), positions_stride)
+    };
+
+    let mut prev_index_count = indices.len();
+    let mut dst = vec![0u32; indices.len()];
+
+    while lods.len() < MAX_MESH_LOD_COUNT {
+        let target_index_count = (prev_index_count / 2) / 3 * 3;
+        if target_index_count < MIN_MESH_LOD_INDEX_COUNT / 2 {
+            break;
+        }
+
+        // Always simplify the full-detail mesh, so that the error is relative to it.
+        let mut relative_error = 0.0f32;
+        let index_count = unsafe {
+            meshopt::ffi::meshopt_simplify(
+                dst.as_mut_ptr(),
+                indices.as_ptr(),
+                indices.len(),
+                positions_ptr,
+                positions.len(),
+                positions_stride,
+                target_index_count,
+                0.1,
+                0,
+                &mut relative_error,
+            )
+        };
+
+        // Stop once the simplifier can't make meaningful progress anymore.
+        if index_count == 0 || index_count as f32 > prev_index_count as f32 * 0.85 {
+            break;
+        }
+
+        lods.push(MeshLod {
+            index_offset: lod_indices.len() as u32,
+            index_count: index_count as u32,
+            error: relative_error * error_scale,
+            pad: 0,
+        });
+        lod_indices.extend_from_slice(&dst[..index_count]);
+
+        prev_index_count = index_count;
+    }
+
+    (lods, lod_indices)
+}
+
 pub fn pack_triangle_mesh(mesh: &TriangleMesh) -> PackedTriangleMesh {
+    let (lods, lod_indices) = generate_mesh_lods(&mesh.positions, &mesh.indices);
+    pack_triangle_mesh_with_lods(mesh, lods, lod_indices)
+}
+
+/// Like `pack_triangle_mesh`, with the vertex streams selected by `quantization` quantized.
+pub fn pack_triangle_mesh_quantized(
+    mesh: &TriangleMesh,
+    quantization: VertexQuantization,
+) -> PackedTriangleMesh {
+    let mut packed = pack_triangle_mesh(mesh);
+    let dequantization = &mut packed.vertex_dequantization;
+
+    if quantization.positions && !mesh.positions.is_empty() {
+        packed.quantized_verts = quantize_positions(&mesh.positions, &mesh.normals, dequantization);
+        packed.verts = Vec::new();
+    }
+
+    if quantization.uvs && !mesh.uvs.is_empty() {
+        if let Some(quantized_uvs) = quantize_uvs(&mesh.uvs, dequantization) {
+            packed.quantized_uvs = quantized_uvs;
+            packed.uvs = Vec::new();
+        }
+    }
+
+    packed
+}
+
+/// Like `pack_triangle_mesh`, but with LODs built by the caller instead of the mesh simplifier.
+pub fn pack_triangle_mesh_with_lods(
+    mesh: &TriangleMesh,
+    lods: Vec<MeshLod>,
+    lod_indices: Vec<u32>,
+) -> PackedTriangleMesh {
+    let (meshlets, meshlet_vertices, meshlet_triangles) =
+        generate_meshlets(&mesh.positions, &mesh.indices);
+
     let mut verts: Vec<PackedVertex> = Vec::with_capacity(mesh.positions.len());
 
     for (i, pos) in mesh.positions.iter().enumerate() {
         let n = mesh.normals[i];
 
-        verts.push(PackedVertex {
-            pos: *pos,
-            normal: pack_unit_direction_11_10_11(n[0], n[1], n[2]),
-        });
+        verts.push(PackedVertex::new(*pos, n));
     }
 
     let maps = mesh
@@ -860,12 +2253,21 @@ pub fn pack_triangle_mesh(mesh: &TriangleMesh) -> PackedTriangleMesh {
     PackedTriangleMesh {
         verts,
         uvs: mesh.uvs.clone(),
+        quantized_verts: Vec::new(),
+        quantized_uvs: Vec::new(),
+        vertex_dequantization: Default::default(),
         tangents: mesh.tangents.clone(),
         colors: mesh.colors.clone(),
         indices: mesh.indices.clone(),
         material_ids: mesh.material_ids.clone(),
         materials: mesh.materials.clone(),
         maps,
+        lights: mesh.lights.clone(),
+        lods,
+        lod_indices,
+        meshlets,
+        meshlet_vertices,
+        meshlet_triangles,
     }
 }
 
@@ -885,14 +2287,17 @@ impl Default for GpuMaterial {
     }
 }
 
+// Tangents are written per face corner, as they can differ between faces sharing a vertex.
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
 struct TangentCalcContext<'a> {
     indices: &'a [u32],
     positions: &'a [[f32; 3]],
     normals: &'a [[f32; 3]],
     uvs: &'a [[f32; 2]],
-    tangents: &'a mut [[f32; 4]],
+    corner_tangents: &'a mut [[f32; 4]],
 }
 
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
 impl<'a> mikktspace::Geometry for TangentCalcContext<'a> {
     fn num_faces(&self) -> usize {
         self.indices.len() / 3
@@ -915,6 +2320,107 @@ impl<'a> mikktspace::Geometry for TangentCalcContext<'a> {
     }
 
     fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
-        self.tangents[self.indices[face * 3 + vert] as usize] = tangent;
+        self.corner_tangents[face * 3 + vert] = tangent;
+    }
+}
+
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+fn is_tangent_degenerate(tangent: [f32; 4], normal: [f32; 3]) -> bool {
+    let t = Vec3::new(tangent[0], tangent[1], tangent[2]);
+    let n = Vec3::from(normal);
+    let len = t.length();
+
+    !len.is_finite() || len < 1e-3 || tangent[3] == 0.0 || t.dot(n).abs() > 0.999 * len * n.length()
+}
+
+/// Replace tangents which can't be used for normal mapping with an arbitrary
+/// vector perpendicular to the normal. Happens with degenerate UVs.
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+fn fix_degenerate_tangents(tangents: &mut [[f32; 4]], normals: &[[f32; 3]]) {
+    for (tangent, normal) in tangents.iter_mut().zip(normals) {
+        if is_tangent_degenerate(*tangent, *normal) {
+            let n = Vec3::from(*normal);
+            let t = if n.x.abs() < 0.9 {
+                n.cross(Vec3::X)
+            } else {
+                n.cross(Vec3::Y)
+            }
+            .normalize_or_zero();
+
+            *tangent = t.extend(1.0).into();
+        }
+    }
+}
+
+/// Calculates MikkTSpace tangents, modifying `indices` to refer to new vertices wherever
+/// faces sharing a vertex disagree about its tangent (e.g. at mirrored UV seams).
+///
+/// Returns the per-vertex tangents, and the source vertex of every vertex in the result.
+/// New vertices are appended after the original ones; see `apply_vertex_remap`.
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+fn generate_mikktspace_tangents(
+    indices: &mut [u32],
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+) -> (Vec<[f32; 4]>, Vec<u32>) {
+    let mut corner_tangents = vec![[0.0f32; 4]; indices.len()];
+
+    mikktspace::generate_tangents(&mut TangentCalcContext {
+        indices,
+        positions,
+        normals,
+        uvs,
+        corner_tangents: corner_tangents.as_mut_slice(),
+    });
+
+    fn tangents_match(a: [f32; 4], b: [f32; 4]) -> bool {
+        a[3] == b[3] && Vec3::new(a[0], a[1], a[2]).dot(Vec3::new(b[0], b[1], b[2])) > 0.999
+    }
+
+    let mut tangents: Vec<[f32; 4]> = vec![[1.0, 0.0, 0.0, 0.0]; positions.len()];
+    let mut assigned = vec![false; positions.len()];
+    let mut vertex_remap: Vec<u32> = (0..positions.len() as u32).collect();
+
+    // Vertices split off from each of the original ones
+    let mut splits: std::collections::HashMap<u32, Vec<u32>> = Default::default();
+
+    for (index, tangent) in indices.iter_mut().zip(corner_tangents) {
+        let src = *index as usize;
+
+        if !assigned[src] {
+            assigned[src] = true;
+            tangents[src] = tangent;
+            continue;
+        }
+
+        if tangents_match(tangents[src], tangent) {
+            continue;
+        }
+
+        let vertex_splits = splits.entry(*index).or_default();
+        if let Some(existing) = vertex_splits
+            .iter()
+            .copied()
+            .find(|&v| tangents_match(tangents[v as usize], tangent))
+        {
+            *index = existing;
+        } else {
+            let new_vertex = vertex_remap.len() as u32;
+            vertex_remap.push(*index);
+            tangents.push(tangent);
+            vertex_splits.push(new_vertex);
+            *index = new_vertex;
+        }
+    }
+
+    (tangents, vertex_remap)
+}
+
+/// Append copies of vertices split by `generate_mikktspace_tangents`.
+#[cfg(any(feature = "gltf-import", feature = "obj-import"))]
+fn apply_vertex_remap<T: Copy>(stream: &mut Vec<T>, vertex_remap: &[u32]) {
+    for &src in &vertex_remap[stream.len()..] {
+        stream.push(stream[src as usize]);
     }
 }
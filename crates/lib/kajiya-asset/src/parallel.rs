@@ -0,0 +1,118 @@
+//! Spreading the CPU-heavy parts of importing and baking over the cores, such as
+//! tangent generation per primitive, and texture compression.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use easy_parallel::Parallel;
+
+// Helper threads currently running, across all the users of `HelperThreads` in the process.
+// Parallel work gets nested, e.g. compressing the strips of images which are being baked
+// in parallel, so without a shared budget each level would start a thread per core,
+// for cores² in total.
+static HELPER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// A share of the budget of a thread per core, for threads started to help the calling one.
+/// Returned to the budget when dropped, including on panics.
+pub struct HelperThreads(usize);
+
+impl HelperThreads {
+    /// Up to `wanted`, as many as the budget has left; possibly none, in which case
+    /// the calling thread must do the work by itself.
+    pub fn reserve(wanted: usize) -> Self {
+        let limit = num_cpus::get().saturating_sub(1);
+        let mut reserved = 0;
+
+        let _ = HELPER_THREADS.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            reserved = wanted.min(limit.saturating_sub(used));
+            Some(used + reserved)
+        });
+
+        Self(reserved)
+    }
+
+    pub fn count(&self) -> usize {
+        self.0
+    }
+}
+
+impl Drop for HelperThreads {
+    fn drop(&mut self) {
+        HELPER_THREADS.fetch_sub(self.0, Ordering::Relaxed);
+    }
+}
+
+/// Maps `items` with `f` on the calling thread, and on as many more as there are idle cores,
+/// returning the results in the order of the items, so that the output doesn't depend on scheduling.
+///
+/// Items are handed out one at a time, as their costs tend to vary wildly.
+pub(crate) fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let helpers = HelperThreads::reserve(items.len().saturating_sub(1));
+    if helpers.count() == 0 {
+        return items.iter().map(f).collect();
+    }
+
+    let next_item = AtomicUsize::new(0);
+    let worker = || {
+        let mut results = Vec::new();
+        loop {
+            let idx = next_item.fetch_add(1, Ordering::Relaxed);
+            if idx >= items.len() {
+                break results;
+            }
+            results.push((idx, f(&items[idx])));
+        }
+    };
+
+    let (helper_results, own_results) = Parallel::new()
+        .each(0..helpers.count(), |_| worker())
+        .finish(&worker);
+
+    let mut results: Vec<(usize, R)> = helper_results
+        .into_iter()
+        .flatten()
+        .chain(own_results)
+        .collect();
+
+    results.sort_unstable_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let items: Vec<u32> = (0..1000).collect();
+
+        // Uneven costs, so that the items finish out of order.
+        let results = parallel_map(&items, |&i| {
+            if i % 7 == 0 {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+            }
+            i * 2
+        });
+
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parallel_map_nested() {
+        let items: Vec<u32> = (0..64).collect();
+
+        let results = parallel_map(&items, |&i| {
+            let inner: Vec<u32> = (0..i).collect();
+            parallel_map(&inner, |&j| j + 1).into_iter().sum::<u32>()
+        });
+
+        assert_eq!(
+            results,
+            items.iter().map(|i| i * (i + 1) / 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parallel_map_empty() {
+        assert!(parallel_map(&[] as &[u32], |&i| i).is_empty());
+    }
+}
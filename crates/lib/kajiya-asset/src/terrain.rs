@@ -0,0 +1,462 @@
+//! Heightfield terrain, imported from a grayscale heightmap, and split into square chunks.
+//!
+//! Every chunk is a separate mesh, so it gets its own BLAS, and its LOD is selected
+//! independently. The LODs of a chunk are its grid at successively halved resolutions,
+//! that is the levels of a quadtree over it, and each one is surrounded by a skirt
+//! which hides the cracks between neighbors at different LODs.
+
+use std::{
+    hash::Hash,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use glam::{Vec2, Vec3};
+
+use crate::{
+    image::ImageSource,
+    mesh::{
+        pack_triangle_mesh_with_lods, MeshLod, MeshMaterial, MeshMaterialFlags, MeshMaterialMap,
+        PackedTriangleMesh, TexCompressionMode, TexGamma, TexParams, TriangleMesh,
+        MAX_MESH_LOD_COUNT,
+    },
+};
+
+/// Layers are blended with weights stored in the four vertex color channels.
+pub const MAX_TERRAIN_LAYERS: usize = 4;
+
+// Width of the transitions between layers
+const LAYER_HEIGHT_BLEND_WIDTH: f32 = 0.05;
+const LAYER_SLOPE_BLEND_WIDTH_DEGREES: f32 = 5.0;
+
+#[derive(Clone)]
+pub struct TerrainLayerDesc {
+    pub albedo_map: Option<PathBuf>,
+    /// Tangent-space, in the red and green channels.
+    pub normal_map: Option<PathBuf>,
+    pub base_color: [f32; 3],
+    pub roughness: f32,
+    /// World-space size of one repetition of the maps.
+    pub tile_size: f32,
+    /// Range of normalized heightmap values in which the layer appears.
+    pub height_range: [f32; 2],
+    /// Range of slopes in which the layer appears, in degrees.
+    pub slope_range: [f32; 2],
+}
+
+impl Default for TerrainLayerDesc {
+    fn default() -> Self {
+        Self {
+            albedo_map: None,
+            normal_map: None,
+            base_color: [0.5; 3],
+            roughness: 1.0,
+            tile_size: 1.0,
+            height_range: [0.0, 1.0],
+            slope_range: [0.0, 90.0],
+        }
+    }
+}
+
+impl Hash for TerrainLayerDesc {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.albedo_map.hash(state);
+        self.normal_map.hash(state);
+        self.base_color.map(f32::to_ne_bytes).hash(state);
+        self.roughness.to_ne_bytes().hash(state);
+        self.tile_size.to_ne_bytes().hash(state);
+        self.height_range.map(f32::to_ne_bytes).hash(state);
+        self.slope_range.map(f32::to_ne_bytes).hash(state);
+    }
+}
+
+#[derive(Clone)]
+pub struct TerrainDesc {
+    pub heightmap: PathBuf,
+    /// Extent along the X and Z axes. The terrain is centered on the origin.
+    pub size: [f32; 2],
+    /// Height corresponding to the maximum value of the heightmap.
+    pub height_scale: f32,
+    /// Number of chunks along each side.
+    pub chunk_count: u32,
+    /// Number of quads along each side of a chunk at full detail. Rounded up to a power of two.
+    pub chunk_resolution: u32,
+    /// Painted over each other in order, so the first one is the base covering all of the terrain.
+    /// Up to `MAX_TERRAIN_LAYERS` are used.
+    pub layers: Vec<TerrainLayerDesc>,
+}
+
+impl Hash for TerrainDesc {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.heightmap.hash(state);
+        self.size.map(f32::to_ne_bytes).hash(state);
+        self.height_scale.to_ne_bytes().hash(state);
+        self.chunk_count.hash(state);
+        self.chunk_resolution.hash(state);
+        self.layers.hash(state);
+    }
+}
+
+impl TerrainDesc {
+    /// The heightmap, followed by all the maps of the layers.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.heightmap.clone())
+            .chain(self.used_layers().iter().flat_map(|layer| {
+                layer
+                    .albedo_map
+                    .iter()
+                    .chain(layer.normal_map.iter())
+                    .cloned()
+            }))
+            .collect()
+    }
+
+    /// Coordinates of all the chunks, in row-major order.
+    pub fn chunks(&self) -> impl Iterator<Item = [u32; 2]> {
+        let chunk_count = self.chunk_count.max(1);
+        (0..chunk_count).flat_map(move |z| (0..chunk_count).map(move |x| [x, z]))
+    }
+
+    fn used_layers(&self) -> &[TerrainLayerDesc] {
+        &self.layers[..self.layers.len().min(MAX_TERRAIN_LAYERS)]
+    }
+
+    fn quads_per_chunk(&self) -> u32 {
+        self.chunk_resolution.max(1).next_power_of_two()
+    }
+}
+
+/// Heights in the `[0, 1]` range.
+pub struct Heightfield {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Heightfield {
+    /// Loads the luminance of an image. 16-bit grayscale PNGs avoid terracing.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("Loading heightmap {:?}", path))?
+            .to_luma16();
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            samples: image
+                .into_raw()
+                .into_iter()
+                .map(|h| h as f32 / u16::MAX as f32)
+                .collect(),
+        })
+    }
+
+    /// Bilinearly filtered, with `uv` clamped to the `[0, 1]` range.
+    pub fn sample(&self, uv: Vec2) -> f32 {
+        let max_xy = Vec2::new(self.width as f32 - 1.0, self.height as f32 - 1.0);
+        let xy = (uv.clamp(Vec2::ZERO, Vec2::ONE) * max_xy).max(Vec2::ZERO);
+        let xy0 = xy.floor();
+        let t = xy - xy0;
+
+        let texel = |x: f32, y: f32| {
+            let x = (x as u32).min(self.width - 1);
+            let y = (y as u32).min(self.height - 1);
+            self.samples[(y * self.width + x) as usize]
+        };
+
+        let h0 = texel(xy0.x, xy0.y) * (1.0 - t.x) + texel(xy0.x + 1.0, xy0.y) * t.x;
+        let h1 = texel(xy0.x, xy0.y + 1.0) * (1.0 - t.x) + texel(xy0.x + 1.0, xy0.y + 1.0) * t.x;
+
+        h0 * (1.0 - t.y) + h1 * t.y
+    }
+}
+
+fn range_coverage(value: f32, range: [f32; 2], blend_width: f32) -> f32 {
+    fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+        let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    smoothstep(range[0] - blend_width, range[0], value)
+        * (1.0 - smoothstep(range[1], range[1] + blend_width, value))
+}
+
+/// Per-layer weights summing up to one, with later layers painted over earlier ones.
+fn layer_weights(layers: &[TerrainLayerDesc], height: f32, slope_degrees: f32) -> [f32; 4] {
+    let mut weights = [0.0f32; MAX_TERRAIN_LAYERS];
+
+    for (i, layer) in layers.iter().enumerate() {
+        let coverage = if i == 0 {
+            1.0
+        } else {
+            range_coverage(height, layer.height_range, LAYER_HEIGHT_BLEND_WIDTH)
+                * range_coverage(
+                    slope_degrees,
+                    layer.slope_range,
+                    LAYER_SLOPE_BLEND_WIDTH_DEGREES,
+                )
+        };
+
+        for w in &mut weights[..i] {
+            *w *= 1.0 - coverage;
+        }
+        weights[i] = coverage;
+    }
+
+    weights
+}
+
+/// One material per layer, padded to `MAX_TERRAIN_LAYERS`. Only the first one is referenced
+/// by the vertices, and the shaders pick up the rest following it.
+fn terrain_materials(layers: &[TerrainLayerDesc]) -> (Vec<MeshMaterial>, Vec<MeshMaterialMap>) {
+    let texture_map = |path: &Option<PathBuf>, gamma: TexGamma, compression: TexCompressionMode| {
+        path.as_ref().map(|path| MeshMaterialMap::Image {
+            source: ImageSource::File(path.clone()),
+            params: TexParams {
+                gamma,
+                use_mips: true,
+                compression,
+                channel_swizzle: None,
+            },
+        })
+    };
+
+    let mut materials = Vec::with_capacity(MAX_TERRAIN_LAYERS);
+    let mut maps = Vec::with_capacity(MAX_TERRAIN_LAYERS * 5);
+
+    for i in 0..MAX_TERRAIN_LAYERS {
+        // The padding layers have zero weights everywhere.
+        let layer = &layers[if i < layers.len() { i } else { 0 }];
+
+        let normal_map = texture_map(&layer.normal_map, TexGamma::Linear, TexCompressionMode::Rg)
+            .unwrap_or(MeshMaterialMap::Placeholder([127, 127, 255, 255]));
+        let albedo_map = texture_map(&layer.albedo_map, TexGamma::Srgb, TexCompressionMode::Rgba)
+            .unwrap_or(MeshMaterialMap::Placeholder([255, 255, 255, 255]));
+        let spec_map = MeshMaterialMap::Placeholder([255, 255, 127, 255]);
+        let emissive_map = MeshMaterialMap::Placeholder([255, 255, 255, 255]);
+        let height_map = MeshMaterialMap::Placeholder([255, 255, 255, 255]);
+
+        let map_base = maps.len() as u32;
+        maps.extend([normal_map, spec_map, albedo_map, emissive_map, height_map]);
+
+        // UVs are in world units, and tiled by the map transforms.
+        let uv_scale = 1.0 / layer.tile_size.max(1e-3);
+        let map_transform = [uv_scale, 0.0, 0.0, uv_scale, 0.0, 0.0];
+
+        // Skirts can be seen from either side.
+        let flags = if i == 0 {
+            MeshMaterialFlags::MESH_MATERIAL_FLAG_TERRAIN_LAYERS
+                | MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED
+        } else {
+            0
+        };
+
+        let [r, g, b] = layer.base_color;
+
+        materials.push(MeshMaterial {
+            base_color_mult: [r, g, b, 1.0],
+            maps: [
+                map_base,
+                map_base + 1,
+                map_base + 2,
+                map_base + 3,
+                map_base + 4,
+            ],
+            roughness_mult: layer.roughness,
+            metalness_factor: 0.0,
+            emissive: [0.0; 3],
+            flags,
+            map_transforms: [map_transform; 4],
+            ior: 1.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            transmission: 0.0,
+            anisotropy: 0.0,
+            anisotropy_rotation: 0.0,
+            alpha_cutoff: 0.5,
+            subsurface: 0.0,
+            subsurface_profile: 0,
+            parallax_scale: 0.0,
+        });
+    }
+
+    (materials, maps)
+}
+
+/// Builds the mesh of one chunk, with its vertices relative to the center of the terrain.
+pub fn build_terrain_chunk(
+    desc: &TerrainDesc,
+    heightfield: &Heightfield,
+    chunk: [u32; 2],
+) -> PackedTriangleMesh {
+    let default_layers = [TerrainLayerDesc::default()];
+    let layers = if desc.layers.is_empty() {
+        &default_layers[..]
+    } else {
+        desc.used_layers()
+    };
+
+    let chunk_count = desc.chunk_count.max(1);
+    let quads = desc.quads_per_chunk();
+    let verts_per_side = quads + 1;
+    let total_quads = (chunk_count * quads) as f32;
+
+    let size = Vec2::from(desc.size);
+    let quad_size = size / total_quads;
+    let chunk_origin = Vec2::new((chunk[0] * quads) as f32, (chunk[1] * quads) as f32);
+
+    // In units of quads from the corner of the terrain
+    let height_at = |grid_xy: Vec2| heightfield.sample(grid_xy / total_quads);
+    let position_at = |grid_xy: Vec2, height: f32| {
+        let xz = grid_xy * quad_size - size * 0.5;
+        Vec3::new(xz.x, height * desc.height_scale, xz.y)
+    };
+
+    let mut mesh = TriangleMesh::default();
+    let mut heights = Vec::with_capacity((verts_per_side * verts_per_side) as usize);
+
+    for z in 0..verts_per_side {
+        for x in 0..verts_per_side {
+            let grid_xy = chunk_origin + Vec2::new(x as f32, z as f32);
+            let height = height_at(grid_xy);
+            let pos = position_at(grid_xy, height);
+
+            let dh_dx = (height_at(grid_xy + Vec2::X) - height_at(grid_xy - Vec2::X))
+                * desc.height_scale
+                / (2.0 * quad_size.x);
+            let dh_dz = (height_at(grid_xy + Vec2::Y) - height_at(grid_xy - Vec2::Y))
+                * desc.height_scale
+                / (2.0 * quad_size.y);
+
+            let normal = Vec3::new(-dh_dx, 1.0, -dh_dz).normalize();
+            let tangent = Vec3::new(1.0, dh_dx, 0.0).normalize();
+            let slope_degrees = normal.y.clamp(-1.0, 1.0).acos().to_degrees();
+
+            heights.push(pos.y);
+            mesh.positions.push(pos.into());
+            mesh.normals.push(normal.into());
+            // With `v` along +Z, the bitangent is `-cross(normal, tangent)`.
+            mesh.tangents.push(tangent.extend(-1.0).into());
+            mesh.uvs.push([pos.x, pos.z]);
+            mesh.colors
+                .push(layer_weights(layers, height, slope_degrees));
+        }
+    }
+
+    let vertex = |x: u32, z: u32| z * verts_per_side + x;
+
+    // Every LOD but the full-detail one is stored in `lods`.
+    let mut lod_strides = Vec::new();
+    let mut lods = Vec::new();
+    let mut stride = 2;
+    while stride <= quads && lods.len() < MAX_MESH_LOD_COUNT {
+        lod_strides.push(stride);
+        lods.push(MeshLod {
+            index_offset: 0,
+            index_count: 0,
+            error: lod_height_error(&heights, verts_per_side, stride),
+            pad: 0,
+        });
+        stride *= 2;
+    }
+
+    // Deep enough to cover the height difference between any two LODs of neighbors.
+    let max_error = lods.iter().map(|lod| lod.error).fold(0.0f32, f32::max);
+    let skirt_depth = max_error + 0.1 * quad_size.min_element();
+
+    // Skirt vertices under each of the edge vertices, going around the chunk
+    let edge_vertices: Vec<u32> = (0..quads)
+        .map(|i| vertex(i, 0))
+        .chain((0..quads).map(|i| vertex(quads, i)))
+        .chain((0..quads).map(|i| vertex(quads - i, quads)))
+        .chain((0..quads).map(|i| vertex(0, quads - i)))
+        .collect();
+
+    let skirt_base = mesh.positions.len() as u32;
+    for &v in &edge_vertices {
+        let v = v as usize;
+        let mut pos = mesh.positions[v];
+        pos[1] -= skirt_depth;
+
+        mesh.positions.push(pos);
+        mesh.normals.push(mesh.normals[v]);
+        mesh.tangents.push(mesh.tangents[v]);
+        mesh.uvs.push(mesh.uvs[v]);
+        mesh.colors.push(mesh.colors[v]);
+    }
+
+    let lod_triangles = |stride: u32| -> Vec<u32> {
+        let mut indices = Vec::new();
+
+        for z in (0..quads).step_by(stride as usize) {
+            for x in (0..quads).step_by(stride as usize) {
+                let v00 = vertex(x, z);
+                let v10 = vertex(x + stride, z);
+                let v01 = vertex(x, z + stride);
+                let v11 = vertex(x + stride, z + stride);
+
+                indices.extend([v00, v01, v10, v10, v01, v11]);
+            }
+        }
+
+        let edge_vertex_count = edge_vertices.len() as u32;
+        for i in (0..edge_vertex_count).step_by(stride as usize) {
+            let next = (i + stride) % edge_vertex_count;
+            let (top0, top1) = (edge_vertices[i as usize], edge_vertices[next as usize]);
+            let (bottom0, bottom1) = (skirt_base + i, skirt_base + next);
+
+            indices.extend([top0, top1, bottom0, bottom0, top1, bottom1]);
+        }
+
+        indices
+    };
+
+    mesh.indices = lod_triangles(1);
+    mesh.material_ids = vec![0; mesh.positions.len()];
+
+    let mut lod_indices = Vec::new();
+    for (lod, stride) in lods.iter_mut().zip(lod_strides) {
+        let indices = lod_triangles(stride);
+        lod.index_offset = lod_indices.len() as u32;
+        lod.index_count = indices.len() as u32;
+        lod_indices.extend(indices);
+    }
+
+    let (materials, maps) = terrain_materials(layers);
+    mesh.materials = materials;
+    mesh.maps = maps;
+
+    pack_triangle_mesh_with_lods(&mesh, lods, lod_indices)
+}
+
+/// Maximum height difference between the full-detail grid, and one with quads `stride` times larger.
+fn lod_height_error(heights: &[f32], verts_per_side: u32, stride: u32) -> f32 {
+    let quads = verts_per_side - 1;
+    let height = |x: u32, z: u32| heights[(z * verts_per_side + x) as usize];
+
+    let mut error = 0.0f32;
+    for z in 0..verts_per_side {
+        for x in 0..verts_per_side {
+            let x0 = (x / stride * stride).min(quads - stride);
+            let z0 = (z / stride * stride).min(quads - stride);
+            let u = (x - x0) as f32 / stride as f32;
+            let v = (z - z0) as f32 / stride as f32;
+
+            let h00 = height(x0, z0);
+            let h10 = height(x0 + stride, z0);
+            let h01 = height(x0, z0 + stride);
+            let h11 = height(x0 + stride, z0 + stride);
+
+            // Same diagonal as the triangles in `build_terrain_chunk`
+            let coarse = if u + v <= 1.0 {
+                h00 + u * (h10 - h00) + v * (h01 - h00)
+            } else {
+                h11 + (1.0 - u) * (h01 - h11) + (1.0 - v) * (h10 - h11)
+            };
+
+            error = error.max((height(x, z) - coarse).abs());
+        }
+    }
+
+    error
+}
@@ -2,7 +2,10 @@ use crate::{bytes::as_byte_slice, vulkan};
 use ash::vk;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use std::mem::{align_of, size_of};
+use std::{
+    mem::{align_of, size_of},
+    sync::Arc,
+};
 use vulkan::buffer::Buffer;
 
 pub const DYNAMIC_CONSTANTS_SIZE_BYTES: usize = 1024 * 1024 * 16;
@@ -21,21 +24,52 @@ pub const DYNAMIC_CONSTANTS_ALIGNMENT: usize = 256;
 pub const MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES: usize = 1024 * 1024;
 
 pub struct DynamicConstants {
-    pub buffer: Buffer,
+    pub buffer: Arc<Buffer>,
     frame_offset_bytes: usize,
     frame_parity: usize,
+    // Less than `DYNAMIC_CONSTANTS_SIZE_BYTES` for forks, which only own a sub-range of the frame.
+    frame_end_bytes: usize,
 }
 
 impl DynamicConstants {
     pub fn new(buffer: Buffer) -> Self {
         Self {
-            buffer,
+            buffer: Arc::new(buffer),
             frame_offset_bytes: 0,
             frame_parity: 0,
+            frame_end_bytes: DYNAMIC_CONSTANTS_SIZE_BYTES,
         }
     }
 
+    /// Reserves `size_bytes` of the current frame for a separate allocator, so that
+    /// constants can be pushed from several threads at once.
+    ///
+    /// The fork is only valid for the current frame, and must not be advanced.
+    pub fn fork(&mut self, size_bytes: usize) -> Self {
+        let fork_end_bytes =
+            fork_end_bytes(self.frame_offset_bytes, self.frame_end_bytes, size_bytes);
+
+        let fork = Self {
+            buffer: self.buffer.clone(),
+            frame_offset_bytes: self.frame_offset_bytes,
+            frame_parity: self.frame_parity,
+            frame_end_bytes: fork_end_bytes,
+        };
+
+        self.frame_offset_bytes = fork_end_bytes;
+        fork
+    }
+
+    pub fn remaining_frame_bytes(&self) -> usize {
+        self.frame_end_bytes - self.frame_offset_bytes
+    }
+
     pub fn advance_frame(&mut self) {
+        assert_eq!(
+            self.frame_end_bytes, DYNAMIC_CONSTANTS_SIZE_BYTES,
+            "Only the root allocator can advance frames"
+        );
+
         self.frame_parity = (self.frame_parity + 1) % DYNAMIC_CONSTANTS_BUFFER_COUNT;
         self.frame_offset_bytes = 0;
     }
@@ -50,13 +84,11 @@ impl DynamicConstants {
 
     pub fn push<T: Copy>(&mut self, t: &T) -> u32 {
         let t_size = size_of::<T>();
-        assert!(self.frame_offset_bytes + t_size < DYNAMIC_CONSTANTS_SIZE_BYTES);
+        assert!(self.frame_offset_bytes + t_size < self.frame_end_bytes);
 
         let buffer_offset = self.current_offset() as usize;
-        let dst = &mut self.buffer.allocation.mapped_slice_mut().unwrap()
-            [buffer_offset..buffer_offset + t_size];
-
-        dst.copy_from_slice(as_byte_slice(t));
+        self.mapped_slice_mut(buffer_offset, t_size)
+            .copy_from_slice(as_byte_slice(t));
 
         let t_size_aligned =
             (t_size + DYNAMIC_CONSTANTS_ALIGNMENT - 1) & !(DYNAMIC_CONSTANTS_ALIGNMENT - 1);
@@ -69,7 +101,7 @@ impl DynamicConstants {
         let t_size = size_of::<T>();
         let t_align = align_of::<T>();
 
-        assert!(self.frame_offset_bytes + t_size < DYNAMIC_CONSTANTS_SIZE_BYTES);
+        assert!(self.frame_offset_bytes + t_size < self.frame_end_bytes);
         assert!(DYNAMIC_CONSTANTS_ALIGNMENT % t_align == 0);
 
         let buffer_offset = self.current_offset() as usize;
@@ -77,9 +109,8 @@ impl DynamicConstants {
 
         let mut dst_offset = buffer_offset;
         for t in iter {
-            let dst = &mut self.buffer.allocation.mapped_slice_mut().unwrap()
-                [dst_offset..dst_offset + t_size];
-            dst.copy_from_slice(as_byte_slice(&t));
+            self.mapped_slice_mut(dst_offset, t_size)
+                .copy_from_slice(as_byte_slice(&t));
             dst_offset += t_size + t_align - 1;
             dst_offset &= !(t_align - 1);
         }
@@ -90,4 +121,77 @@ impl DynamicConstants {
 
         buffer_offset as _
     }
+
+    // The buffer is shared with forks, but each only writes within its own part of the frame.
+    fn mapped_slice_mut(&mut self, buffer_offset: usize, size: usize) -> &mut [u8] {
+        let frame_begin = self.frame_parity * DYNAMIC_CONSTANTS_SIZE_BYTES;
+        assert!(buffer_offset + size <= frame_begin + self.frame_end_bytes);
+
+        unsafe {
+            let mapped = self.buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut u8;
+            std::slice::from_raw_parts_mut(mapped.add(buffer_offset), size)
+        }
+    }
+}
+
+// Where a fork of `size_bytes`, rounded down to the alignment, ends within the frame
+// of an allocator at `frame_offset_bytes`, whose own part of the frame ends at `frame_end_bytes`.
+fn fork_end_bytes(frame_offset_bytes: usize, frame_end_bytes: usize, size_bytes: usize) -> usize {
+    let size_bytes = size_bytes & !(DYNAMIC_CONSTANTS_ALIGNMENT - 1);
+    let fork_end_bytes = frame_offset_bytes + size_bytes;
+    assert!(
+        fork_end_bytes <= frame_end_bytes,
+        "Forking {} bytes at offset {}, past the end of the frame at {}",
+        size_bytes,
+        frame_offset_bytes,
+        frame_end_bytes
+    );
+    fork_end_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fork_rounds_down_to_alignment() {
+        assert_eq!(
+            fork_end_bytes(
+                0,
+                DYNAMIC_CONSTANTS_SIZE_BYTES,
+                3 * DYNAMIC_CONSTANTS_ALIGNMENT - 1
+            ),
+            2 * DYNAMIC_CONSTANTS_ALIGNMENT
+        );
+        assert_eq!(fork_end_bytes(512, DYNAMIC_CONSTANTS_SIZE_BYTES, 100), 512);
+    }
+
+    #[test]
+    fn fork_up_to_end_of_frame() {
+        assert_eq!(
+            fork_end_bytes(
+                1024,
+                DYNAMIC_CONSTANTS_SIZE_BYTES,
+                DYNAMIC_CONSTANTS_SIZE_BYTES - 1024
+            ),
+            DYNAMIC_CONSTANTS_SIZE_BYTES
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn fork_past_end_of_frame() {
+        fork_end_bytes(
+            1024,
+            DYNAMIC_CONSTANTS_SIZE_BYTES,
+            DYNAMIC_CONSTANTS_SIZE_BYTES,
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn fork_past_end_of_parent_fork() {
+        // A fork of a fork is bounded by the parent's part of the frame, not the whole frame.
+        fork_end_bytes(256, 1024, 1024);
+    }
 }
@@ -13,6 +13,36 @@ pub enum BackendError {
 
     #[error("Invalid resource access: {info:?}")]
     ResourceAccess { info: String },
+
+    #[error("Format {format:?} does not support {usage:?} with {tiling:?} tiling")]
+    UnsupportedFormat {
+        format: ash::vk::Format,
+        usage: ash::vk::ImageUsageFlags,
+        tiling: ash::vk::ImageTiling,
+    },
+
+    #[error("The device does not support sharing memory and semaphores with other APIs")]
+    ExternalInteropUnsupported,
+
+    #[error("Failed to create pipeline {name:?}: {err:?}")]
+    PipelineCreation { name: String, err: ash::vk::Result },
+}
+
+impl BackendError {
+    /// Whether the device is gone, and nothing more can be done with it. Other errors
+    /// fail what was being attempted, and the renderer can carry on, e.g. by skipping the frame.
+    pub fn is_device_lost(&self) -> bool {
+        matches!(
+            self,
+            Self::Vulkan {
+                err: ash::vk::Result::ERROR_DEVICE_LOST,
+                ..
+            } | Self::PipelineCreation {
+                err: ash::vk::Result::ERROR_DEVICE_LOST,
+                ..
+            }
+        )
+    }
 }
 
 impl From<ash::vk::Result> for BackendError {
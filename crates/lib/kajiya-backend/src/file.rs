@@ -4,7 +4,11 @@ use hotwatch::Hotwatch;
 use lazy_static::lazy_static;
 use normpath::PathExt;
 use parking_lot::Mutex;
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
 use turbosloth::*;
 
 lazy_static! {
@@ -12,21 +16,76 @@ lazy_static! {
         Mutex::new(Hotwatch::new_with_custom_delay(std::time::Duration::from_millis(100)).unwrap());
 }
 
+/// Overrides the root directory found by `resolve_kajiya_root`, and any passed to it
+/// or to `set_standard_vfs_mount_points`.
+pub const KAJIYA_ROOT_ENV_VAR: &str = "KAJIYA_ROOT";
+
+/// Overrides where `/shaders` is mounted, `assets/shaders` under the root directory otherwise.
+pub const KAJIYA_SHADER_ROOT_ENV_VAR: &str = "KAJIYA_SHADER_ROOT";
+
+lazy_static! {
+    // Apps set these up with `mount_kajiya_roots`; the defaults are for tools and tests
+    // which use the vfs without doing so. The cache stays in the working directory,
+    // as the root may not be writable.
+    static ref VFS_MOUNT_POINTS: Mutex<HashMap<String, PathBuf>> = {
+        let kajiya_path = resolve_kajiya_root(None).unwrap_or_else(|_| PathBuf::from("."));
+
+        Mutex::new(
+            standard_vfs_mount_points(&kajiya_path, env_path(KAJIYA_SHADER_ROOT_ENV_VAR))
+                .into_iter()
+                .map(|(mount_point, path)| (mount_point.to_owned(), path))
+                .chain(std::iter::once(("/cache".to_owned(), PathBuf::from("cache"))))
+                .collect(),
+        )
+    };
+}
+
+// Generated in memory, and served by `LoadFile` in place of files on disk.
+struct GeneratedFile {
+    contents: Bytes,
+    // Of the `LoadFile`s which returned the current contents
+    invalidation_triggers: Vec<Box<dyn FnMut() + Send>>,
+}
+
 lazy_static! {
-    static ref VFS_MOUNT_POINTS: Mutex<HashMap<String, PathBuf>> = Mutex::new(
-        vec![
-            ("/kajiya".to_owned(), PathBuf::from(".")),
-            ("/shaders".to_owned(), PathBuf::from("assets/shaders")),
-            (
-                "/rust-shaders-compiled".to_owned(),
-                PathBuf::from("assets/rust-shaders-compiled")
-            ),
-            ("/images".to_owned(), PathBuf::from("assets/images")),
-            ("/cache".to_owned(), PathBuf::from("cache"))
-        ]
-        .into_iter()
-        .collect()
-    );
+    static ref GENERATED_FILES: Mutex<HashMap<PathBuf, GeneratedFile>> = Default::default();
+}
+
+/// Serves `contents` at the vfs `path` to `LoadFile`, and so to shader includes, in place
+/// of anything on disk. Setting different contents later invalidates whatever was built
+/// from the previous ones, like writing to a watched file does.
+pub fn set_generated_file(path: impl Into<PathBuf>, contents: impl Into<Bytes>) {
+    let path = path.into();
+    let contents = contents.into();
+
+    let mut generated_files = GENERATED_FILES.lock();
+    match generated_files.get_mut(&path) {
+        Some(file) if file.contents == contents => {}
+        Some(file) => {
+            file.contents = contents;
+            for mut trigger in file.invalidation_triggers.drain(..) {
+                trigger();
+            }
+        }
+        None => {
+            generated_files.insert(
+                path,
+                GeneratedFile {
+                    contents,
+                    invalidation_triggers: Vec::new(),
+                },
+            );
+        }
+    }
+}
+
+/// Stops serving the generated file at `path`, invalidating whatever was built from it.
+pub fn remove_generated_file(path: impl Into<PathBuf>) {
+    if let Some(file) = GENERATED_FILES.lock().remove(&path.into()) {
+        for mut trigger in file.invalidation_triggers {
+            trigger();
+        }
+    }
 }
 
 pub fn set_vfs_mount_point(mount_point: impl Into<String>, path: impl Into<PathBuf>) {
@@ -35,15 +94,123 @@ pub fn set_vfs_mount_point(mount_point: impl Into<String>, path: impl Into<PathB
         .insert(mount_point.into(), path.into());
 }
 
+/// Mounts `/kajiya` at `kajiya_path`, and the standard directories under it. Relative paths
+/// are resolved against the working directory, once.
 pub fn set_standard_vfs_mount_points(kajiya_path: impl Into<PathBuf>) {
-    let kajiya_path = kajiya_path.into();
-    set_vfs_mount_point("/kajiya", &kajiya_path);
-    set_vfs_mount_point("/shaders", kajiya_path.join("assets/shaders"));
-    set_vfs_mount_point(
-        "/rust-shaders-compiled",
-        kajiya_path.join("assets/rust-shaders-compiled"),
-    );
-    set_vfs_mount_point("/images", kajiya_path.join("assets/images"));
+    let kajiya_path =
+        env_path(KAJIYA_ROOT_ENV_VAR).unwrap_or_else(|| absolute_path(kajiya_path.into()));
+
+    for (mount_point, path) in
+        standard_vfs_mount_points(&kajiya_path, env_path(KAJIYA_SHADER_ROOT_ENV_VAR))
+    {
+        set_vfs_mount_point(mount_point, path);
+    }
+}
+
+/// Mounts the standard directories under `resolve_kajiya_root(kajiya_root)`, with `/shaders`
+/// at `shader_root` if given, and returns the root. `KAJIYA_ROOT` and `KAJIYA_SHADER_ROOT`
+/// take precedence over the arguments.
+pub fn mount_kajiya_roots(
+    kajiya_root: Option<PathBuf>,
+    shader_root: Option<PathBuf>,
+) -> anyhow::Result<PathBuf> {
+    let kajiya_path = resolve_kajiya_root(kajiya_root)?;
+
+    let shader_path =
+        env_path(KAJIYA_SHADER_ROOT_ENV_VAR).or_else(|| shader_root.map(absolute_path));
+    if let Some(shader_path) = &shader_path {
+        anyhow::ensure!(
+            shader_path.is_dir(),
+            "The shader directory {:?} doesn't exist",
+            shader_path
+        );
+    }
+
+    for (mount_point, path) in standard_vfs_mount_points(&kajiya_path, shader_path) {
+        set_vfs_mount_point(mount_point, path);
+    }
+
+    Ok(kajiya_path)
+}
+
+/// Finds kajiya's root directory: the one with `assets/shaders` in it.
+///
+/// That's `KAJIYA_ROOT` if set, or else `preferred` if given; relative paths are resolved
+/// against the working directory. Otherwise it's searched for in the working directory,
+/// the directory of the executable and its ancestors, and the source tree kajiya was
+/// built from, so that installed binaries and tests find it wherever they run.
+pub fn resolve_kajiya_root(preferred: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    if let Some(path) = env_path(KAJIYA_ROOT_ENV_VAR).or_else(|| preferred.map(absolute_path)) {
+        anyhow::ensure!(
+            is_kajiya_root(&path),
+            "{:?} isn't kajiya's root directory; it has no `assets/shaders`",
+            path
+        );
+        return Ok(path);
+    }
+
+    let mut candidates: Vec<PathBuf> = std::env::current_dir().into_iter().collect();
+
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_owned))
+    {
+        candidates.extend(exe_dir.ancestors().map(Path::to_owned));
+    }
+
+    candidates.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("../../.."));
+
+    candidates
+        .iter()
+        .find(|path| is_kajiya_root(path))
+        .map(|path| absolute_path(path.clone()))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Couldn't find kajiya's root directory in any of {:#?}. Set {} to it.",
+                candidates,
+                KAJIYA_ROOT_ENV_VAR
+            )
+        })
+}
+
+fn is_kajiya_root(path: &Path) -> bool {
+    path.join("assets/shaders").is_dir()
+}
+
+fn standard_vfs_mount_points(
+    kajiya_path: &Path,
+    shader_path: Option<PathBuf>,
+) -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("/kajiya", kajiya_path.to_owned()),
+        (
+            "/shaders",
+            shader_path.unwrap_or_else(|| kajiya_path.join("assets/shaders")),
+        ),
+        (
+            "/rust-shaders-compiled",
+            kajiya_path.join("assets/rust-shaders-compiled"),
+        ),
+        ("/images", kajiya_path.join("assets/images")),
+        ("/meshes", kajiya_path.join("assets/meshes")),
+    ]
+}
+
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|value| !value.is_empty())
+        .map(|value| absolute_path(PathBuf::from(value)))
+}
+
+// Normalized if it exists
+fn absolute_path(path: PathBuf) -> PathBuf {
+    let path = match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        Err(_) => path,
+    };
+
+    let normalized = path.normalize();
+    normalized.map_or(path, |normalized| normalized.as_path().to_owned())
 }
 
 pub fn canonical_path_from_vfs(path: impl Into<PathBuf>) -> anyhow::Result<PathBuf> {
@@ -105,6 +272,29 @@ pub fn normalized_path_from_vfs(path: impl Into<PathBuf>) -> anyhow::Result<Path
     Ok(path)
 }
 
+/// Invoke `on_write` whenever the file at `path` is written to or re-created.
+///
+/// Watching the same path again replaces the previous callback.
+pub fn watch_file(
+    path: impl Into<PathBuf>,
+    on_write: impl Fn() + Send + 'static,
+) -> anyhow::Result<()> {
+    let path = canonical_path_from_vfs(path)?;
+
+    FILE_WATCHER
+        .lock()
+        .watch(path.clone(), move |event| {
+            // Some editors save by replacing the file
+            if matches!(
+                event,
+                hotwatch::Event::Write(_) | hotwatch::Event::Create(_)
+            ) {
+                on_write();
+            }
+        })
+        .with_context(|| format!("watch_file: trying to watch {:?}", path))
+}
+
 #[derive(Clone, Hash)]
 pub struct LoadFile {
     path: PathBuf,
@@ -112,6 +302,11 @@ pub struct LoadFile {
 
 impl LoadFile {
     pub fn new(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        if GENERATED_FILES.lock().contains_key(&path) {
+            return Ok(Self { path });
+        }
+
         let path = canonical_path_from_vfs(path)?;
         Ok(Self { path })
     }
@@ -124,6 +319,12 @@ impl LazyWorker for LoadFile {
     async fn run(self, ctx: RunContext) -> Self::Output {
         let invalidation_trigger = ctx.get_invalidation_trigger();
 
+        if let Some(file) = GENERATED_FILES.lock().get_mut(&self.path) {
+            file.invalidation_triggers
+                .push(Box::new(invalidation_trigger));
+            return Ok(file.contents.clone());
+        }
+
         FILE_WATCHER
             .lock()
             .watch(self.path.clone(), move |event| {
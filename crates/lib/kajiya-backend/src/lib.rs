@@ -7,13 +7,18 @@ pub mod gpu_profiler;
 pub mod pipeline_cache;
 pub mod rust_shader_compiler;
 pub mod shader_compiler;
+pub mod shader_layout;
 pub mod transient_resource_cache;
 pub mod vulkan;
 
 pub use ash;
 pub use error::BackendError;
-pub use file::{canonical_path_from_vfs, normalized_path_from_vfs, set_vfs_mount_point};
+pub use file::{
+    canonical_path_from_vfs, mount_kajiya_roots, normalized_path_from_vfs, remove_generated_file,
+    resolve_kajiya_root, set_generated_file, set_vfs_mount_point,
+};
 pub use gpu_allocator;
 pub use rspirv_reflect;
+pub use shader_compiler::{add_shader_include_root, remove_shader_include_root};
 pub use vk_sync;
 pub use vulkan::{device::Device, image::*, shader::MAX_DESCRIPTOR_SETS, RenderBackend};
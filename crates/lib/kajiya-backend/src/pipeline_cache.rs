@@ -1,6 +1,7 @@
 use crate::{
     rust_shader_compiler::CompileRustShader,
     shader_compiler::{CompileShader, CompiledShader},
+    shader_layout::validate_uniform_buffer_layouts,
     vulkan::{
         ray_tracing::{create_ray_tracing_pipeline, RayTracingPipeline, RayTracingPipelineDesc},
         shader::*,
@@ -8,9 +9,24 @@ use crate::{
 };
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 use turbosloth::*;
 
+static SHADER_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Bumped whenever pipelines are invalidated because their shaders changed on disk.
+/// Lets the results of one-off GPU work, such as baked lookup tables, be redone
+/// after shader hot-reloading.
+pub fn shader_generation() -> u64 {
+    SHADER_GENERATION.load(Ordering::Relaxed)
+}
+
 #[derive(Clone, Copy, Hash, Eq, PartialEq)]
 pub struct ComputePipelineHandle(usize);
 
@@ -54,7 +70,8 @@ impl LazyWorker for CompilePipelineShaders {
                         ShaderPipelineStage::Pixel => "ps".to_owned(),
                         ShaderPipelineStage::RayGen
                         | ShaderPipelineStage::RayMiss
-                        | ShaderPipelineStage::RayClosestHit => "lib".to_owned(),
+                        | ShaderPipelineStage::RayClosestHit
+                        | ShaderPipelineStage::RayAnyHit => "lib".to_owned(),
                     },
                 }
                 .into_lazy()
@@ -88,6 +105,49 @@ struct RtPipelineCacheEntry {
     pipeline: Option<Arc<RayTracingPipeline>>,
 }
 
+/// The fixed-function state of a `RasterPipelineDesc`, so that the same shaders can be
+/// used with different render passes, culling, or depth tests.
+#[derive(Clone, Hash, Eq, PartialEq)]
+struct RasterPipelineStateKey {
+    render_pass: ash::vk::RenderPass,
+    face_cull: bool,
+    depth_write: bool,
+    depth_compare_op: ash::vk::CompareOp,
+    topology: ash::vk::PrimitiveTopology,
+    vertex_input: VertexInputDesc,
+    polygon_mode: ash::vk::PolygonMode,
+    alpha_blend: bool,
+    push_constants_bytes: usize,
+}
+
+impl RasterPipelineStateKey {
+    fn new(desc: &RasterPipelineDesc) -> Self {
+        Self {
+            render_pass: desc.render_pass.raw,
+            face_cull: desc.face_cull,
+            depth_write: desc.depth_write,
+            depth_compare_op: desc.depth_compare_op,
+            topology: desc.topology,
+            vertex_input: desc.vertex_input.clone(),
+            polygon_mode: desc.polygon_mode,
+            alpha_blend: desc.alpha_blend,
+            push_constants_bytes: desc.push_constants_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PipelineCacheStats {
+    /// Created, and not invalidated since.
+    pub compute_pipelines: usize,
+    pub raster_pipelines: usize,
+    pub ray_tracing_pipelines: usize,
+
+    /// Calls to `register_*` so far, and how many of them found the pipeline registered already.
+    pub lookups: u64,
+    pub hits: u64,
+}
+
 pub struct PipelineCache {
     lazy_cache: Arc<LazyCache>,
 
@@ -95,9 +155,14 @@ pub struct PipelineCache {
     raster_entries: HashMap<RasterPipelineHandle, RasterPipelineCacheEntry>,
     rt_entries: HashMap<RtPipelineHandle, RtPipelineCacheEntry>,
 
-    compute_shader_to_handle: HashMap<ShaderSource, ComputePipelineHandle>,
-    raster_shaders_to_handle: HashMap<Vec<PipelineShaderDesc>, RasterPipelineHandle>,
+    compute_shader_to_handle:
+        HashMap<(ShaderSource, SpecializationConstants), ComputePipelineHandle>,
+    raster_shaders_to_handle:
+        HashMap<(Vec<PipelineShaderDesc>, RasterPipelineStateKey), RasterPipelineHandle>,
     rt_shaders_to_handle: HashMap<Vec<PipelineShaderDesc>, RtPipelineHandle>,
+
+    lookups: u64,
+    hits: u64,
 }
 
 impl PipelineCache {
@@ -113,13 +178,46 @@ impl PipelineCache {
 
             raster_shaders_to_handle: Default::default(),
             rt_shaders_to_handle: Default::default(),
+
+            lookups: 0,
+            hits: 0,
+        }
+    }
+
+    pub fn stats(&self) -> PipelineCacheStats {
+        PipelineCacheStats {
+            compute_pipelines: self
+                .compute_entries
+                .values()
+                .filter(|entry| entry.pipeline.is_some())
+                .count(),
+            raster_pipelines: self
+                .raster_entries
+                .values()
+                .filter(|entry| entry.pipeline.is_some())
+                .count(),
+            ray_tracing_pipelines: self
+                .rt_entries
+                .values()
+                .filter(|entry| entry.pipeline.is_some())
+                .count(),
+            lookups: self.lookups,
+            hits: self.hits,
         }
     }
 
     // TODO: should probably use the `desc` as key as well
     pub fn register_compute(&mut self, desc: &ComputePipelineDesc) -> ComputePipelineHandle {
-        match self.compute_shader_to_handle.entry(desc.source.clone()) {
-            std::collections::hash_map::Entry::Occupied(occupied) => *occupied.get(),
+        self.lookups += 1;
+
+        match self
+            .compute_shader_to_handle
+            .entry((desc.source.clone(), desc.specialization_constants.clone()))
+        {
+            std::collections::hash_map::Entry::Occupied(occupied) => {
+                self.hits += 1;
+                *occupied.get()
+            }
             std::collections::hash_map::Entry::Vacant(vacant) => {
                 let handle = ComputePipelineHandle(self.compute_entries.len());
                 let compile_task = match &desc.source {
@@ -162,13 +260,16 @@ impl PipelineCache {
         shaders: &[PipelineShaderDesc],
         desc: &RasterPipelineDesc,
     ) -> RasterPipelineHandle {
-        if let Some(handle) = self.raster_shaders_to_handle.get(shaders) {
+        self.lookups += 1;
+
+        let key = (shaders.to_owned(), RasterPipelineStateKey::new(desc));
+        if let Some(handle) = self.raster_shaders_to_handle.get(&key) {
+            self.hits += 1;
             return *handle;
         }
 
         let handle = RasterPipelineHandle(self.raster_entries.len());
-        self.raster_shaders_to_handle
-            .insert(shaders.to_owned(), handle);
+        self.raster_shaders_to_handle.insert(key, handle);
         self.raster_entries.insert(
             handle,
             RasterPipelineCacheEntry {
@@ -197,7 +298,10 @@ impl PipelineCache {
         shaders: &[PipelineShaderDesc],
         desc: &RayTracingPipelineDesc,
     ) -> RtPipelineHandle {
+        self.lookups += 1;
+
         if let Some(handle) = self.rt_shaders_to_handle.get(shaders) {
+            self.hits += 1;
             return *handle;
         }
 
@@ -227,10 +331,13 @@ impl PipelineCache {
     }
 
     fn invalidate_stale_pipelines(&mut self) {
+        let mut any_invalidated = false;
+
         for entry in self.compute_entries.values_mut() {
             if entry.pipeline.is_some() && entry.lazy_handle.is_stale() {
                 // TODO: release
                 entry.pipeline = None;
+                any_invalidated = true;
             }
         }
 
@@ -238,6 +345,7 @@ impl PipelineCache {
             if entry.pipeline.is_some() && entry.lazy_handle.is_stale() {
                 // TODO: release
                 entry.pipeline = None;
+                any_invalidated = true;
             }
         }
 
@@ -245,8 +353,13 @@ impl PipelineCache {
             if entry.pipeline.is_some() && entry.lazy_handle.is_stale() {
                 // TODO: release
                 entry.pipeline = None;
+                any_invalidated = true;
             }
         }
+
+        if any_invalidated {
+            SHADER_GENERATION.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
     pub fn parallel_compile_shaders(
@@ -304,11 +417,13 @@ impl PipelineCache {
                             compiled.name,
                             entry.desc.source.entry(),
                         );
+                        validate_uniform_buffer_layouts(&compiled.name, &compiled.spirv)?;
+
                         entry.pipeline = Some(Arc::new(create_compute_pipeline(
                             &*device,
                             &compiled.spirv,
                             &entry.desc,
-                        )));
+                        )?));
                     }
                     CompileTaskOutput::Raster { handle, compiled } => {
                         let entry = self.raster_entries.get_mut(&handle).unwrap();
@@ -334,11 +449,23 @@ impl PipelineCache {
                             })
                             .collect::<Vec<_>>();
 
-                        // TODO: defer and handle the error
-                        entry.pipeline = Some(Arc::new(
-                            create_raster_pipeline(&*device, &compiled_shaders, &entry.desc)
-                                .expect("create_raster_pipeline"),
-                        ));
+                        for shader in &compiled.shaders {
+                            validate_uniform_buffer_layouts(&shader.code.name, &shader.code.spirv)?;
+
+                            if shader.desc.stage == ShaderPipelineStage::Vertex {
+                                entry.desc.vertex_input.validate(
+                                    &shader.code.name,
+                                    &shader.code.spirv,
+                                    &shader.desc.entry,
+                                )?;
+                            }
+                        }
+
+                        entry.pipeline = Some(Arc::new(create_raster_pipeline(
+                            &*device,
+                            &compiled_shaders,
+                            &entry.desc,
+                        )?));
                     }
                     CompileTaskOutput::Rt { handle, compiled } => {
                         let entry = self.rt_entries.get_mut(&handle).unwrap();
@@ -364,11 +491,15 @@ impl PipelineCache {
                             })
                             .collect::<Vec<_>>();
 
-                        // TODO: defer and handle the error
-                        entry.pipeline = Some(Arc::new(
-                            create_ray_tracing_pipeline(&*device, &compiled_shaders, &entry.desc)
-                                .expect("create_ray_tracing_pipeline"),
-                        ));
+                        for shader in &compiled.shaders {
+                            validate_uniform_buffer_layouts(&shader.code.name, &shader.code.spirv)?;
+                        }
+
+                        entry.pipeline = Some(Arc::new(create_ray_tracing_pipeline(
+                            &*device,
+                            &compiled_shaders,
+                            &entry.desc,
+                        )?));
                     }
                 }
             }
@@ -1,8 +1,16 @@
 use crate::file::LoadFile;
 use anyhow::{anyhow, bail, Context, Result};
 use bytes::Bytes;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
 use relative_path::RelativePathBuf;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use turbosloth::*;
 
 pub struct CompiledShader {
@@ -107,10 +115,68 @@ impl LazyWorker for CompileRayTracingShader {
     }
 }
 
+lazy_static! {
+    static ref SHADER_INCLUDE_ROOTS: RwLock<Vec<String>> = Default::default();
+}
+
+/// Makes `#include "foo.hlsl"` find `<root>/foo.hlsl` when there's no `foo.hlsl` next to
+/// the including file. Roots are vfs paths, searched in the order added. They may hold
+/// generated files too, such as headers served by `set_generated_file`.
+///
+/// Shaders which already resolved an include elsewhere won't notice a new root until
+/// they're rebuilt for another reason.
+pub fn add_shader_include_root(root: impl Into<String>) {
+    let root = root.into();
+    let mut roots = SHADER_INCLUDE_ROOTS.write();
+    if !roots.contains(&root) {
+        roots.push(root);
+    }
+}
+
+pub fn remove_shader_include_root(root: &str) {
+    SHADER_INCLUDE_ROOTS.write().retain(|r| r != root);
+}
+
 struct ShaderIncludeProvider {
     ctx: RunContext,
 }
 
+impl ShaderIncludeProvider {
+    // Next to the including file first, then in the include roots.
+    fn resolve_include(path: &str, parent_file: &str) -> Result<(String, LoadFile)> {
+        if let Some('/') = path.chars().next() {
+            let file = LoadFile::new(path)?;
+            return Ok((path.to_owned(), file));
+        }
+
+        let mut folder: RelativePathBuf = parent_file.into();
+        folder.pop();
+        let next_to_parent = folder.join(path).as_str().to_string();
+
+        let first_error = match LoadFile::new(&next_to_parent) {
+            Ok(file) => return Ok((next_to_parent, file)),
+            Err(err) => err,
+        };
+
+        let roots = SHADER_INCLUDE_ROOTS.read();
+        for root in roots.iter() {
+            let candidate = RelativePathBuf::from(root.as_str())
+                .join(path)
+                .as_str()
+                .to_string();
+
+            if let Ok(file) = LoadFile::new(&candidate) {
+                return Ok((candidate, file));
+            }
+        }
+
+        Err(first_error.context(format!(
+            "Not found next to {:?}, nor in the shader include roots {:?}",
+            parent_file, *roots
+        )))
+    }
+}
+
 impl shader_prepper::IncludeProvider for ShaderIncludeProvider {
     type IncludeContext = String;
 
@@ -122,20 +188,10 @@ impl shader_prepper::IncludeProvider for ShaderIncludeProvider {
         (String, Self::IncludeContext),
         shader_prepper::BoxedIncludeProviderError,
     > {
-        let resolved_path = if let Some('/') = path.chars().next() {
-            path.to_owned()
-        } else {
-            let mut folder: RelativePathBuf = parent_file.into();
-            folder.pop();
-            folder.join(path).as_str().to_string()
-        };
+        let (resolved_path, file) = Self::resolve_include(path, parent_file)
+            .with_context(|| format!("Failed loading shader include {}", path))?;
 
-        let blob: Arc<Bytes> = smol::block_on(
-            crate::file::LoadFile::new(&resolved_path)
-                .with_context(|| format!("Failed loading shader include {}", path))?
-                .into_lazy()
-                .eval(&self.ctx),
-        )?;
+        let blob: Arc<Bytes> = smol::block_on(file.into_lazy().eval(&self.ctx))?;
 
         Ok((String::from_utf8(blob.to_vec())?, resolved_path))
     }
@@ -163,6 +219,14 @@ pub fn get_cs_local_size_from_spirv(spirv: &[u32]) -> Result<[u32; 3]> {
     Err(anyhow!("Could not find a ExecutionMode SPIR-V op"))
 }
 
+static DEBUG_PRINTF_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Compiles `DEBUG_PRINTF` in HLSL shaders to `printf`, rather than to nothing.
+/// Set by the device before any shaders are compiled.
+pub(crate) fn set_debug_printf_enabled(enabled: bool) {
+    DEBUG_PRINTF_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
 fn compile_generic_shader_hlsl_impl(
     name: &str,
     source: &[shader_prepper::SourceChunk],
@@ -187,7 +251,14 @@ fn compile_generic_shader_hlsl_impl(
             "-WX",  // warnings as errors
             "-Ges", // strict mode
         ],
-        &[],
+        &[(
+            "KAJIYA_DEBUG_PRINTF",
+            Some(if DEBUG_PRINTF_ENABLED.load(Ordering::Relaxed) {
+                "1"
+            } else {
+                "0"
+            }),
+        )],
     )
     .map_err(|err| anyhow!("{}", err))?;
 
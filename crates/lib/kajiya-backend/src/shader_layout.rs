@@ -0,0 +1,211 @@
+//! Checks of the structs shared between the CPU and shaders against the compiled shaders.
+//!
+//! Structs such as the frame constants are declared twice, in Rust and in HLSL, and nothing
+//! else catches the two drifting apart; the shaders would then silently read garbage.
+//! Layouts registered with `register_uniform_buffer_layout` are compared to the member
+//! offsets of every shader compiled afterwards, and pipelines with mismatching shaders
+//! fail to be created.
+
+use anyhow::{bail, Result};
+use byte_slice_cast::AsSliceOf as _;
+use parking_lot::RwLock;
+use rspirv::dr::Operand;
+use std::collections::HashMap;
+
+/// The byte offsets of the members of a `#[repr(C)]` struct, by name.
+#[derive(Clone, Debug)]
+pub struct StructLayout {
+    pub name: &'static str,
+    pub members: Vec<(&'static str, usize)>,
+}
+
+/// Makes a `StructLayout` of the listed fields of a struct, e.g.
+/// `struct_layout!(FrameConstants { view_constants, sun_direction })`.
+#[macro_export]
+macro_rules! struct_layout {
+    ($ty:ty { $($field:ident),* $(,)? }) => {{
+        let uninit = ::std::mem::MaybeUninit::<$ty>::uninit();
+        let base = uninit.as_ptr();
+
+        $crate::shader_layout::StructLayout {
+            name: stringify!($ty),
+            members: vec![$((
+                stringify!($field),
+                // Only the address is taken; the memory is never read.
+                unsafe { ::std::ptr::addr_of!((*base).$field) as usize - base as usize },
+            )),*],
+        }
+    }};
+}
+
+lazy_static::lazy_static! {
+    static ref UNIFORM_BUFFER_LAYOUTS: RwLock<HashMap<(u32, u32), StructLayout>> = Default::default();
+}
+
+/// Expects shaders which use the uniform buffer at `set` and `binding` to declare a struct
+/// with the members of `layout` at the same offsets. Members named `pad*` in the shaders
+/// may be left out of `layout`.
+///
+/// Only the top-level members are compared, so a change in the size of the last one,
+/// or of nested structs after their last member, goes unnoticed.
+pub fn register_uniform_buffer_layout(set: u32, binding: u32, layout: StructLayout) {
+    UNIFORM_BUFFER_LAYOUTS
+        .write()
+        .insert((set, binding), layout);
+}
+
+/// Compares the registered layouts to the ones of the uniform buffers in `spirv`.
+pub(crate) fn validate_uniform_buffer_layouts(shader_name: &str, spirv: &[u8]) -> Result<()> {
+    let layouts = UNIFORM_BUFFER_LAYOUTS.read();
+    if layouts.is_empty() {
+        return Ok(());
+    }
+
+    let mut loader = rspirv::dr::Loader::new();
+    rspirv::binary::parse_words(spirv.as_slice_of::<u32>().unwrap(), &mut loader).unwrap();
+    let module = loader.module();
+
+    for (&(set, binding), layout) in layouts.iter() {
+        let shader_members = match spirv_uniform_buffer_members(&module, set, binding) {
+            Some(members) => members,
+            None => continue,
+        };
+
+        // Without debug names, there's nothing to match the members by.
+        if shader_members.iter().all(|(name, _)| name.is_empty()) {
+            continue;
+        }
+
+        let mut mismatches = Vec::new();
+
+        for (name, offset) in &layout.members {
+            match shader_members
+                .iter()
+                .find(|(shader_name, _)| shader_name == name)
+            {
+                Some((_, shader_offset)) if *shader_offset as usize != *offset => {
+                    mismatches.push(format!(
+                        "`{}` is at offset {} on the CPU, but {} in the shader",
+                        name, offset, shader_offset
+                    ));
+                }
+                Some(_) => {}
+                None => mismatches.push(format!("`{}` is missing in the shader", name)),
+            }
+        }
+
+        for (shader_name, _) in &shader_members {
+            if !shader_name.starts_with("pad")
+                && !layout.members.iter().any(|(name, _)| name == shader_name)
+            {
+                mismatches.push(format!("`{}` is missing on the CPU", shader_name));
+            }
+        }
+
+        if !mismatches.is_empty() {
+            bail!(
+                "The uniform buffer at set {}, binding {} of {} doesn't match `{}`:\n{}",
+                set,
+                binding,
+                shader_name,
+                layout.name,
+                mismatches.join("\n")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// SPIR-V opcodes and decorations
+const OP_MEMBER_NAME: u32 = 6;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+/// The names and offsets of the members of the struct in the buffer at `set` and `binding`,
+/// in order, or `None` if the shader doesn't use the binding.
+fn spirv_uniform_buffer_members(
+    module: &rspirv::dr::Module,
+    set: u32,
+    binding: u32,
+) -> Option<Vec<(String, u32)>> {
+    let decoration_of = |target: u32, decoration: u32| {
+        module.global_inst_iter().find_map(|inst| {
+            if inst.class.opcode as u32 != OP_DECORATE {
+                return None;
+            }
+
+            match inst.operands.as_slice() {
+                [Operand::IdRef(id), Operand::Decoration(dec), Operand::LiteralInt32(value), ..]
+                    if *id == target && *dec as u32 == decoration =>
+                {
+                    Some(*value)
+                }
+                _ => None,
+            }
+        })
+    };
+
+    let variable = module.global_inst_iter().find(|inst| {
+        inst.class.opcode as u32 == OP_VARIABLE
+            && inst.result_id.map_or(false, |id| {
+                decoration_of(id, DECORATION_DESCRIPTOR_SET) == Some(set)
+                    && decoration_of(id, DECORATION_BINDING) == Some(binding)
+            })
+    })?;
+
+    let struct_id = module.global_inst_iter().find_map(|inst| {
+        if inst.class.opcode as u32 != OP_TYPE_POINTER || inst.result_id != variable.result_type {
+            return None;
+        }
+
+        match inst.operands.as_slice() {
+            [_, Operand::IdRef(pointee)] => Some(*pointee),
+            _ => None,
+        }
+    })?;
+
+    let mut members: Vec<(u32, String, u32)> = module
+        .global_inst_iter()
+        .filter(|inst| inst.class.opcode as u32 == OP_MEMBER_DECORATE)
+        .filter_map(|inst| match inst.operands.as_slice() {
+            [Operand::IdRef(id), Operand::LiteralInt32(member), Operand::Decoration(dec), Operand::LiteralInt32(offset), ..]
+                if *id == struct_id && *dec as u32 == DECORATION_OFFSET =>
+            {
+                Some((*member, String::new(), *offset))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let member_names = module
+        .global_inst_iter()
+        .filter(|inst| inst.class.opcode as u32 == OP_MEMBER_NAME)
+        .filter_map(|inst| match inst.operands.as_slice() {
+            [Operand::IdRef(id), Operand::LiteralInt32(member), Operand::LiteralString(name)]
+                if *id == struct_id =>
+            {
+                Some((*member, name))
+            }
+            _ => None,
+        });
+
+    for (member, name) in member_names {
+        if let Some(entry) = members.iter_mut().find(|(idx, _, _)| *idx == member) {
+            entry.1 = name.clone();
+        }
+    }
+
+    members.sort_by_key(|(idx, _, _)| *idx);
+    Some(
+        members
+            .into_iter()
+            .map(|(_, name, offset)| (name, offset))
+            .collect(),
+    )
+}
@@ -1,5 +1,6 @@
 use crate::vulkan::{
     buffer::{Buffer, BufferDesc},
+    device::Device,
     image::{Image, ImageDesc},
 };
 use std::collections::HashMap;
@@ -42,4 +43,15 @@ impl TransientResourceCache {
             self.buffers.insert(buffer.desc, vec![buffer]);
         }
     }
+
+    /// The GPU must be done with the cached resources.
+    pub fn immediate_destroy_all(&mut self, device: &Device) {
+        for image in self.images.drain().flat_map(|(_, images)| images) {
+            device.immediate_destroy_image(image);
+        }
+
+        for buffer in self.buffers.drain().flat_map(|(_, buffers)| buffers) {
+            device.immediate_destroy_buffer(buffer);
+        }
+    }
 }
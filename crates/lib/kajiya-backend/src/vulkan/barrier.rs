@@ -68,6 +68,21 @@ pub struct AccessInfo {
     pub image_layout: vk::ImageLayout,
 }
 
+/// Whether `access_type` reads, and doesn't write. `Nothing` doesn't count as either.
+pub fn is_read_only_access(access_type: AccessType) -> bool {
+    let write_mask = vk::AccessFlags::SHADER_WRITE
+        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+        | vk::AccessFlags::TRANSFER_WRITE
+        | vk::AccessFlags::HOST_WRITE
+        | vk::AccessFlags::MEMORY_WRITE
+        | vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR
+        | vk::AccessFlags::COMMAND_PREPROCESS_WRITE_NV;
+
+    let access_mask = get_access_info(access_type).access_mask;
+    !access_mask.is_empty() && !access_mask.intersects(write_mask)
+}
+
 pub fn get_access_info(access_type: AccessType) -> AccessInfo {
     match access_type {
         AccessType::Nothing => AccessInfo {
@@ -1,6 +1,6 @@
 use crate::BackendError;
 
-use super::device::Device;
+use super::{device::Device, resource_tracking::ResourceKind};
 use ash::vk;
 use gpu_allocator::{AllocationCreateDesc, MemoryLocation};
 
@@ -9,6 +9,8 @@ pub struct Buffer {
     pub desc: BufferDesc,
     pub allocation: gpu_allocator::SubAllocation,
 }
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
 
 impl Buffer {
     pub fn device_address(&self, device: &Device) -> u64 {
@@ -76,10 +78,7 @@ impl Device {
             ..Default::default()
         };
 
-        let buffer = unsafe {
-            raw.create_buffer(&buffer_info, None)
-                .expect("create_buffer")
-        };
+        let buffer = unsafe { raw.create_buffer(&buffer_info, None)? };
         let mut requirements = unsafe { raw.get_buffer_memory_requirements(buffer) };
 
         if let Some(alignment) = desc.alignment {
@@ -95,23 +94,32 @@ impl Device {
             requirements.alignment = requirements.alignment.max(64);
         }
 
-        let allocation = allocator
-            .allocate(&AllocationCreateDesc {
-                name,
-                requirements,
-                location: desc.memory_location,
-                linear: true, // Buffers are always linear
-            })
-            .map_err(move |err| BackendError::Allocation {
-                inner: err,
-                name: name.to_owned(),
-            })?;
+        let allocation = match allocator.allocate(&AllocationCreateDesc {
+            name,
+            requirements,
+            location: desc.memory_location,
+            linear: true, // Buffers are always linear
+        }) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { raw.destroy_buffer(buffer, None) };
+                return Err(BackendError::Allocation {
+                    inner: err,
+                    name: name.to_owned(),
+                });
+            }
+        };
 
         // Bind memory to the buffer
-        unsafe {
-            raw.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
-                .expect("bind_buffer_memory")
-        };
+        if let Err(err) =
+            unsafe { raw.bind_buffer_memory(buffer, allocation.memory(), allocation.offset()) }
+        {
+            unsafe { raw.destroy_buffer(buffer, None) };
+            if let Err(free_err) = allocator.free(allocation) {
+                log::error!("Failed to free the memory of {:?}: {:?}", name, free_err);
+            }
+            return Err(err.into());
+        }
 
         Ok(Buffer {
             raw: buffer,
@@ -133,6 +141,9 @@ impl Device {
         }
         let buffer =
             Self::create_buffer_impl(&self.raw, &mut self.global_allocator.lock(), desc, &name)?;
+        self.track_created(ResourceKind::Buffer, buffer.raw, || name.clone());
+        self.stats
+            .buffer_created(self.buffer_memory_bytes(buffer.raw));
 
         if let Some(initial_data) = initial_data {
             let scratch_desc =
@@ -144,11 +155,13 @@ impl Device {
                 scratch_desc,
                 &format!("Initial data for {:?}", name),
             )?;
+            self.stats
+                .buffer_created(self.buffer_memory_bytes(scratch_buffer.raw));
 
             scratch_buffer.allocation.mapped_slice_mut().unwrap()[0..initial_data.len()]
                 .copy_from_slice(initial_data);
 
-            self.with_setup_cb(|cb| unsafe {
+            let copy_result = self.with_setup_cb(|cb| unsafe {
                 self.raw.cmd_copy_buffer(
                     cb,
                     scratch_buffer.raw,
@@ -159,19 +172,31 @@ impl Device {
                         .size(desc.size as u64)
                         .build()],
                 );
-            })?;
+            });
+
+            self.immediate_destroy_buffer(scratch_buffer);
+
+            copy_result?;
         }
 
         Ok(buffer)
     }
 
     pub fn immediate_destroy_buffer(&self, buffer: Buffer) {
+        self.stats
+            .buffer_destroyed(self.buffer_memory_bytes(buffer.raw));
+
         unsafe {
             self.raw.destroy_buffer(buffer.raw, None);
         }
+        self.track_destroyed(ResourceKind::Buffer, buffer.raw);
         self.global_allocator
             .lock()
             .free(buffer.allocation)
             .expect("buffer memory deallocated");
     }
+
+    pub(crate) fn buffer_memory_bytes(&self, buffer: vk::Buffer) -> u64 {
+        unsafe { self.raw.get_buffer_memory_requirements(buffer) }.size
+    }
 }
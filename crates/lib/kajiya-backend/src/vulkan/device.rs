@@ -2,9 +2,17 @@ use crate::{vulkan::buffer::BufferDesc, BackendError};
 
 use super::{
     buffer::Buffer,
+    device_stats::{DeviceStats, DeviceStatsCounters},
+    dynamic_rendering::{DynamicRenderingFns, RenderingInfoKHR},
     error::CrashMarkerNames,
+    external::ExternalInteropFns,
+    image::Image,
     physical_device::{PhysicalDevice, QueueFamily},
     profiler::VkProfilerData,
+    ray_tracing::RayTracingAcceleration,
+    resource_tracking::{LiveResource, ResourceCreatorScope, ResourceKind, ResourceTracker},
+    shader::{RenderPass, RenderPassCacheKey},
+    staging_belt::{StagingBelt, StagingSlice},
 };
 use anyhow::Result;
 use ash::{
@@ -17,8 +25,12 @@ use log::{debug, error, info, trace, warn};
 use parking_lot::Mutex;
 use std::{
     collections::{HashMap, HashSet},
+    ffi::CStr,
     os::raw::c_char,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 /// Descriptor count to subtract from the max bindless descriptor count,
@@ -26,12 +38,32 @@ use std::{
 /// in the same shader stage.
 pub const RESERVED_DESCRIPTOR_COUNT: u32 = 32;
 
+/// Loaded when the device is created, and written by `Device::save_pipeline_cache`.
+const PIPELINE_CACHE_FILE_NAME: &str = "vk_pipeline_cache.bin";
+
+/// Optional capabilities a device was created with.
+pub(super) struct EnabledFeatures {
+    pub ray_tracing: bool,
+    pub draw_indirect_count: bool,
+    pub fill_mode_non_solid: bool,
+    pub portability_subset: bool,
+    pub shader_debug_printf: bool,
+    pub external_interop: bool,
+    pub buffer_device_address: bool,
+    pub dynamic_rendering: bool,
+}
+
 pub struct Queue {
     pub raw: vk::Queue,
     pub family: QueueFamily,
+
+    /// Access to the queue must be externally synchronized; this must be held
+    /// around submissions and presentation, since resources can be uploaded from any thread.
+    pub submit_lock: Mutex<()>,
 }
 
-pub trait DeferredRelease: Copy {
+/// Resources which `Device::defer_release` can destroy once the GPU is done with them.
+pub trait DeferredRelease {
     fn enqueue_release(self, pending: &mut PendingResourceReleases);
 }
 
@@ -41,27 +73,80 @@ impl DeferredRelease for vk::DescriptorPool {
     }
 }
 
+impl DeferredRelease for Image {
+    fn enqueue_release(self, pending: &mut PendingResourceReleases) {
+        pending.images.push(self);
+    }
+}
+
+impl DeferredRelease for Buffer {
+    fn enqueue_release(self, pending: &mut PendingResourceReleases) {
+        pending.buffers.push(self);
+    }
+}
+
+impl DeferredRelease for RayTracingAcceleration {
+    fn enqueue_release(self, pending: &mut PendingResourceReleases) {
+        pending.acceleration_structures.push(self);
+    }
+}
+
+// For those the renderer may still share, e.g. with a frame's TLAS instances.
+// Destroyed once the last other reference is gone.
+impl DeferredRelease for Arc<RayTracingAcceleration> {
+    fn enqueue_release(self, pending: &mut PendingResourceReleases) {
+        pending.shared_acceleration_structures.push(self);
+    }
+}
+
 #[derive(Default)]
 pub struct PendingResourceReleases {
     pub descriptor_pools: Vec<vk::DescriptorPool>,
+    pub images: Vec<Image>,
+    pub buffers: Vec<Buffer>,
+    pub acceleration_structures: Vec<RayTracingAcceleration>,
+    pub shared_acceleration_structures: Vec<Arc<RayTracingAcceleration>>,
 }
 
 impl PendingResourceReleases {
-    fn release_all(&mut self, device: &ash::Device) {
-        unsafe {
-            for res in self.descriptor_pools.drain(..) {
-                device.destroy_descriptor_pool(res, None);
+    // Acceleration structures go before buffers, since they live in them.
+    fn release_all(&mut self, device: &Device) {
+        for accel in self.acceleration_structures.drain(..) {
+            device.immediate_destroy_acceleration(accel);
+        }
+
+        for accel in std::mem::take(&mut self.shared_acceleration_structures) {
+            match Arc::try_unwrap(accel) {
+                Ok(accel) => device.immediate_destroy_acceleration(accel),
+                Err(accel) => self.shared_acceleration_structures.push(accel),
             }
         }
+
+        for pool in self.descriptor_pools.drain(..) {
+            device.immediate_destroy_descriptor_pool(pool);
+        }
+
+        for image in self.images.drain(..) {
+            device.immediate_destroy_image(image);
+        }
+
+        for buffer in self.buffers.drain(..) {
+            device.immediate_destroy_buffer(buffer);
+        }
     }
 }
 
+/// How many command buffers each frame has for parallel recording.
+pub const MAX_PARALLEL_COMMAND_BUFFERS: usize = 8;
+
 pub struct DeviceFrame {
     //pub(crate) linear_allocator_pool: vk_mem::AllocatorPool,
     pub swapchain_acquired_semaphore: Option<vk::Semaphore>,
     pub rendering_complete_semaphore: Option<vk::Semaphore>,
     pub main_command_buffer: CommandBuffer,
     pub presentation_command_buffer: CommandBuffer,
+    /// Recorded on worker threads, and submitted right after `main_command_buffer`.
+    pub parallel_command_buffers: Vec<CommandBuffer>,
     pub pending_resource_releases: Mutex<PendingResourceReleases>,
     pub profiler_data: VkProfilerData,
 }
@@ -73,23 +158,19 @@ pub struct CommandBuffer {
 }
 
 impl CommandBuffer {
-    fn new(device: &ash::Device, queue_family: &QueueFamily) -> Result<Self> {
+    fn new(device: &ash::Device, queue_family: &QueueFamily) -> Result<Self, BackendError> {
         let pool_create_info = vk::CommandPoolCreateInfo::builder()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
             .queue_family_index(queue_family.index);
 
-        let pool = unsafe { device.create_command_pool(&pool_create_info, None).unwrap() };
+        let pool = unsafe { device.create_command_pool(&pool_create_info, None)? };
 
         let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
             .command_buffer_count(1)
             .command_pool(pool)
             .level(vk::CommandBufferLevel::PRIMARY);
 
-        let cb = unsafe {
-            device
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .unwrap()
-        }[0];
+        let cb = unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? }[0];
 
         let submit_done_fence = unsafe {
             device.create_fence(
@@ -126,6 +207,9 @@ impl DeviceFrame {
             rendering_complete_semaphore: None,
             main_command_buffer: CommandBuffer::new(device, queue_family).unwrap(),
             presentation_command_buffer: CommandBuffer::new(device, queue_family).unwrap(),
+            parallel_command_buffers: (0..MAX_PARALLEL_COMMAND_BUFFERS)
+                .map(|_| CommandBuffer::new(device, queue_family).unwrap())
+                .collect(),
             pending_resource_releases: Default::default(),
             profiler_data: VkProfilerData::new(device, global_allocator),
         }
@@ -139,7 +223,8 @@ pub struct Device {
     pub universal_queue: Queue,
     pub(crate) global_allocator: Arc<Mutex<VulkanAllocator>>,
     pub(crate) immutable_samplers: HashMap<SamplerDesc, vk::Sampler>,
-    pub(crate) setup_cb: Mutex<CommandBuffer>,
+    // Recycled by `with_setup_cb`; grows to the number of threads uploading at once.
+    setup_cbs: Mutex<Vec<CommandBuffer>>,
 
     pub(crate) crash_tracking_buffer: Buffer,
     pub(crate) crash_marker_names: Mutex<CrashMarkerNames>,
@@ -148,10 +233,26 @@ pub struct Device {
     pub ray_tracing_pipeline_ext: khr::RayTracingPipeline,
     // pub ray_query_ext: khr::RayQuery,
     pub ray_tracing_pipeline_properties: vk::PhysicalDeviceRayTracingPipelinePropertiesKHR,
+    pub draw_indirect_count_ext: khr::DrawIndirectCount,
+    pub(crate) external_interop_fns: Option<ExternalInteropFns>,
+    pub(crate) dynamic_rendering_fns: Option<DynamicRenderingFns>,
+    pub(crate) render_pass_cache: Mutex<HashMap<RenderPassCacheKey, Arc<RenderPass>>>,
+    pub(crate) pipeline_cache: vk::PipelineCache,
+
+    // Only with graphics debugging
+    resource_tracker: Option<ResourceTracker>,
+    pub(crate) stats: DeviceStatsCounters,
 
     frames: [Mutex<Arc<DeviceFrame>>; 2],
+    // Incremented by `begin_frame`
+    frame_value: AtomicU64,
+    staging_belt: Mutex<StagingBelt>,
 
     ray_tracing_enabled: bool,
+    draw_indirect_count_enabled: bool,
+    fill_mode_non_solid_enabled: bool,
+    portability_subset_enabled: bool,
+    shader_debug_printf_enabled: bool,
 }
 
 // Allowing `Send` on `frames` is technically unsound. There are some checks
@@ -163,6 +264,42 @@ unsafe impl Send for Device {}
 unsafe impl Sync for Device {}
 
 impl Device {
+    /// The device extensions the renderer can't do without.
+    pub fn required_extensions() -> Vec<&'static CStr> {
+        vec![
+            vk::ExtDescriptorIndexingFn::name(),
+            vk::ExtScalarBlockLayoutFn::name(),
+            vk::KhrMaintenance1Fn::name(),
+            vk::KhrMaintenance2Fn::name(),
+            vk::KhrMaintenance3Fn::name(),
+            vk::KhrGetMemoryRequirements2Fn::name(),
+            vk::KhrImagelessFramebufferFn::name(),
+            vk::KhrImageFormatListFn::name(),
+            vk::KhrDescriptorUpdateTemplateFn::name(),
+            // Rust-GPU
+            vk::KhrShaderFloat16Int8Fn::name(),
+            // DLSS
+            #[cfg(feature = "dlss")]
+            CStr::from_bytes_with_nul(b"VK_NVX_binary_import\0").unwrap(),
+            #[cfg(feature = "dlss")]
+            CStr::from_bytes_with_nul(b"VK_KHR_push_descriptor\0").unwrap(),
+            #[cfg(feature = "dlss")]
+            vk::NvxImageViewHandleFn::name(),
+        ]
+    }
+
+    /// The device extensions enabling ray tracing, all of which are needed for it.
+    pub fn ray_tracing_extensions() -> [&'static CStr; 6] {
+        [
+            vk::KhrVulkanMemoryModelFn::name(), // used in ray tracing shaders
+            vk::KhrPipelineLibraryFn::name(),   // rt dep
+            vk::KhrDeferredHostOperationsFn::name(), // rt dep
+            vk::KhrBufferDeviceAddressFn::name(), // rt dep
+            vk::KhrAccelerationStructureFn::name(),
+            vk::KhrRayTracingPipelineFn::name(),
+        ]
+    }
+
     pub fn create(pdevice: &Arc<PhysicalDevice>) -> Result<Arc<Self>> {
         let supported_extensions: HashSet<String> = unsafe {
             let extension_properties = pdevice
@@ -182,44 +319,16 @@ impl Device {
                 .collect()
         };
 
-        let mut device_extension_names = vec![
-            vk::ExtDescriptorIndexingFn::name().as_ptr(),
-            vk::ExtScalarBlockLayoutFn::name().as_ptr(),
-            vk::KhrMaintenance1Fn::name().as_ptr(),
-            vk::KhrMaintenance2Fn::name().as_ptr(),
-            vk::KhrMaintenance3Fn::name().as_ptr(),
-            vk::KhrGetMemoryRequirements2Fn::name().as_ptr(),
-            vk::ExtDescriptorIndexingFn::name().as_ptr(),
-            vk::KhrImagelessFramebufferFn::name().as_ptr(),
-            vk::KhrImageFormatListFn::name().as_ptr(),
-            vk::KhrDescriptorUpdateTemplateFn::name().as_ptr(),
-            // Rust-GPU
-            vk::KhrShaderFloat16Int8Fn::name().as_ptr(),
-            // DLSS
-            #[cfg(feature = "dlss")]
-            {
-                b"VK_NVX_binary_import\0".as_ptr() as *const i8
-            },
-            #[cfg(feature = "dlss")]
-            {
-                b"VK_KHR_push_descriptor\0".as_ptr() as *const i8
-            },
-            #[cfg(feature = "dlss")]
-            vk::NvxImageViewHandleFn::name().as_ptr(),
-        ];
+        let mut device_extension_names: Vec<*const c_char> = Self::required_extensions()
+            .iter()
+            .map(|ext| ext.as_ptr())
+            .collect();
 
-        let ray_tracing_extensions = [
-            vk::KhrVulkanMemoryModelFn::name().as_ptr(), // used in ray tracing shaders
-            vk::KhrPipelineLibraryFn::name().as_ptr(),   // rt dep
-            vk::KhrDeferredHostOperationsFn::name().as_ptr(), // rt dep
-            vk::KhrBufferDeviceAddressFn::name().as_ptr(), // rt dep
-            vk::KhrAccelerationStructureFn::name().as_ptr(),
-            vk::KhrRayTracingPipelineFn::name().as_ptr(),
-        ];
+        let ray_tracing_extensions = Self::ray_tracing_extensions();
 
-        let ray_tracing_enabled = unsafe {
+        let ray_tracing_extensions_supported = if cfg!(feature = "ray-tracing") {
             ray_tracing_extensions.iter().all(|ext| {
-                let ext = std::ffi::CStr::from_ptr(*ext).to_string_lossy();
+                let ext = ext.to_string_lossy();
 
                 let supported = supported_extensions.contains(ext.as_ref());
 
@@ -229,23 +338,76 @@ impl Device {
 
                 supported
             })
+        } else {
+            log::info!("Built without the `ray-tracing` feature; ray tracing is unavailable");
+            false
         };
 
-        if ray_tracing_enabled {
-            log::info!("All ray tracing extensions are supported");
+        // Non-conformant implementations layered on other APIs (e.g. MoltenVK on Metal)
+        // expose this extension, and require it to be enabled.
+        let portability_subset_enabled = supported_extensions.contains(
+            vk::KhrPortabilitySubsetFn::name()
+                .to_string_lossy()
+                .as_ref(),
+        );
+
+        if portability_subset_enabled {
+            log::info!("The device is a portability subset implementation");
+            device_extension_names.push(vk::KhrPortabilitySubsetFn::name().as_ptr());
+        }
+
+        // Used by GPU-driven draws; the renderer falls back to CPU-driven draws without it.
+        let draw_indirect_count_enabled = supported_extensions
+            .contains(khr::DrawIndirectCount::name().to_string_lossy().as_ref());
+
+        if draw_indirect_count_enabled {
+            device_extension_names.push(khr::DrawIndirectCount::name().as_ptr());
+        } else {
+            log::info!("Draw indirect count not supported; GPU-driven draws are unavailable");
+        }
+
+        // `printf` in shaders; its messages are routed into the log by the instance.
+        let shader_debug_printf_enabled = pdevice.instance.shader_debug_printf
+            && supported_extensions.contains(
+                vk::KhrShaderNonSemanticInfoFn::name()
+                    .to_string_lossy()
+                    .as_ref(),
+            );
+
+        if shader_debug_printf_enabled {
+            device_extension_names.push(vk::KhrShaderNonSemanticInfoFn::name().as_ptr());
+            crate::shader_compiler::set_debug_printf_enabled(true);
+        }
 
-            device_extension_names.extend(ray_tracing_extensions.iter());
+        // Lets images and semaphores be shared with other APIs, e.g. CUDA.
+        let external_interop_enabled = super::external::required_extensions()
+            .iter()
+            .all(|ext| supported_extensions.contains(ext.to_string_lossy().as_ref()));
+
+        if external_interop_enabled {
+            device_extension_names.extend(
+                super::external::required_extensions()
+                    .iter()
+                    .map(|ext| ext.as_ptr()),
+            );
+        } else {
+            log::info!("External memory and semaphores not supported; interop is unavailable");
         }
 
         if pdevice.presentation_requested {
             device_extension_names.push(khr::Swapchain::name().as_ptr());
         }
 
+        // Lets single-subpass render passes skip the render pass and framebuffer objects;
+        // enabled below if the feature is there too.
+        let dynamic_rendering_supported = supported_extensions
+            .contains(super::dynamic_rendering::name().to_string_lossy().as_ref());
+
         unsafe {
             for &ext in &device_extension_names {
                 let ext = std::ffi::CStr::from_ptr(ext).to_string_lossy();
                 if !supported_extensions.contains(ext.as_ref()) {
-                    panic!("Device extension not supported: {}", ext);
+                    anyhow::bail!("Device extension not supported: {}", ext);
                 }
             }
         }
@@ -285,6 +447,12 @@ impl Device {
         let mut ray_tracing_pipeline_features =
             ash::vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
 
+        let mut portability_subset_features =
+            vk::PhysicalDevicePortabilitySubsetFeaturesKHR::default();
+
+        let mut dynamic_rendering_features =
+            super::dynamic_rendering::PhysicalDeviceDynamicRenderingFeaturesKHR::default();
+
         unsafe {
             let instance = &pdevice.instance.raw;
 
@@ -296,18 +464,28 @@ impl Device {
                 .push_next(&mut vulkan_memory_model)
                 .push_next(&mut get_buffer_device_address_features);
 
-            if ray_tracing_enabled {
+            if ray_tracing_extensions_supported {
                 features2 = features2
                     .push_next(&mut acceleration_structure_features)
                     .push_next(&mut ray_tracing_pipeline_features);
             }
 
+            if portability_subset_enabled {
+                features2 = features2.push_next(&mut portability_subset_features);
+            }
+
+            if dynamic_rendering_supported {
+                features2 = features2.push_next(&mut dynamic_rendering_features);
+            }
+
             let mut features2 = features2.build();
 
             instance
                 .fp_v1_1()
                 .get_physical_device_features2(pdevice.raw, &mut features2);
 
+            let fill_mode_non_solid_enabled = features2.features.fill_mode_non_solid != 0;
+
             debug!("{:#?}", &scalar_block);
             debug!("{:#?}", &descriptor_indexing);
             debug!("{:#?}", &imageless_framebuffer);
@@ -315,118 +493,340 @@ impl Device {
             debug!("{:#?}", &vulkan_memory_model);
             debug!("{:#?}", &get_buffer_device_address_features);
 
+            if portability_subset_enabled {
+                info!(
+                    "Portability subset features: {:#?}",
+                    &portability_subset_features
+                );
+            }
+
+            macro_rules! find_missing_features {
+                ($missing:ident: $($features:ident.$feature:ident),* $(,)?) => {
+                    $(
+                        if $features.$feature == 0 {
+                            $missing.push(concat!(stringify!($features), ".", stringify!($feature)));
+                        }
+                    )*
+                };
+            }
+
+            let mut missing_features: Vec<&str> = Vec::new();
+            let core_features = features2.features;
+
             // The suggested `#[rustfmt::skip]` is not stable
             #[allow(clippy::deprecated_cfg_attr)]
             #[cfg_attr(rustfmt, rustfmt_skip)]
             {
-                assert!(scalar_block.scalar_block_layout != 0);
-
-                assert!(descriptor_indexing.shader_uniform_texel_buffer_array_dynamic_indexing != 0);
-                assert!(descriptor_indexing.shader_storage_texel_buffer_array_dynamic_indexing != 0);
-                assert!(descriptor_indexing.shader_sampled_image_array_non_uniform_indexing != 0);
-                assert!(descriptor_indexing.shader_storage_image_array_non_uniform_indexing != 0);
-                assert!(descriptor_indexing.shader_uniform_texel_buffer_array_non_uniform_indexing != 0);
-                assert!(descriptor_indexing.shader_storage_texel_buffer_array_non_uniform_indexing != 0);
-                assert!(descriptor_indexing.descriptor_binding_sampled_image_update_after_bind != 0);
-                assert!(descriptor_indexing.descriptor_binding_update_unused_while_pending != 0);
-                assert!(descriptor_indexing.descriptor_binding_partially_bound != 0);
-                assert!(descriptor_indexing.descriptor_binding_variable_descriptor_count != 0);
-                assert!(descriptor_indexing.runtime_descriptor_array != 0);
+                find_missing_features!(missing_features:
+                    scalar_block.scalar_block_layout,
+
+                    descriptor_indexing.shader_uniform_texel_buffer_array_dynamic_indexing,
+                    descriptor_indexing.shader_storage_texel_buffer_array_dynamic_indexing,
+                    descriptor_indexing.shader_sampled_image_array_non_uniform_indexing,
+                    descriptor_indexing.shader_storage_image_array_non_uniform_indexing,
+                    descriptor_indexing.shader_uniform_texel_buffer_array_non_uniform_indexing,
+                    descriptor_indexing.shader_storage_texel_buffer_array_non_uniform_indexing,
+                    descriptor_indexing.descriptor_binding_sampled_image_update_after_bind,
+                    descriptor_indexing.descriptor_binding_update_unused_while_pending,
+                    descriptor_indexing.descriptor_binding_partially_bound,
+                    descriptor_indexing.descriptor_binding_variable_descriptor_count,
+                    descriptor_indexing.runtime_descriptor_array,
+
+                    imageless_framebuffer.imageless_framebuffer,
+
+                    shader_float16_int8.shader_int8,
+
+                    // User clip planes
+                    core_features.shader_clip_distance,
+                );
+            }
 
-                assert!(imageless_framebuffer.imageless_framebuffer != 0);
+            if !missing_features.is_empty() {
+                anyhow::bail!(
+                    "The device lacks required features: {}",
+                    missing_features.join(", ")
+                );
+            }
 
-                assert!(shader_float16_int8.shader_int8 != 0);
+            // Without these, the device can still run the rasterized path.
+            let mut missing_ray_tracing_features: Vec<&str> = Vec::new();
 
-                if ray_tracing_enabled {
-                    assert!(descriptor_indexing.shader_uniform_buffer_array_non_uniform_indexing != 0);
-                    assert!(descriptor_indexing.shader_storage_buffer_array_non_uniform_indexing != 0);
+            if ray_tracing_extensions_supported {
+                #[allow(clippy::deprecated_cfg_attr)]
+                #[cfg_attr(rustfmt, rustfmt_skip)]
+                {
+                    find_missing_features!(missing_ray_tracing_features:
+                        descriptor_indexing.shader_uniform_buffer_array_non_uniform_indexing,
+                        descriptor_indexing.shader_storage_buffer_array_non_uniform_indexing,
 
-                    assert!(vulkan_memory_model.vulkan_memory_model != 0);
+                        vulkan_memory_model.vulkan_memory_model,
 
-                    assert!(acceleration_structure_features.acceleration_structure != 0);
-                    assert!(acceleration_structure_features.descriptor_binding_acceleration_structure_update_after_bind != 0);
+                        acceleration_structure_features.acceleration_structure,
+                        acceleration_structure_features.descriptor_binding_acceleration_structure_update_after_bind,
 
-                    assert!(ray_tracing_pipeline_features.ray_tracing_pipeline != 0);
-                    assert!(ray_tracing_pipeline_features.ray_tracing_pipeline_trace_rays_indirect != 0);
+                        ray_tracing_pipeline_features.ray_tracing_pipeline,
+                        ray_tracing_pipeline_features.ray_tracing_pipeline_trace_rays_indirect,
 
-                    assert!(get_buffer_device_address_features.buffer_device_address != 0);
+                        get_buffer_device_address_features.buffer_device_address,
+                    );
                 }
             }
 
+            let ray_tracing_enabled =
+                ray_tracing_extensions_supported && missing_ray_tracing_features.is_empty();
+
+            if ray_tracing_enabled {
+                log::info!("All ray tracing extensions are supported");
+                device_extension_names
+                    .extend(ray_tracing_extensions.iter().map(|ext| ext.as_ptr()));
+            } else if ray_tracing_extensions_supported {
+                log::warn!(
+                    "Ray tracing extensions are supported, but not these features: {}. Disabling ray tracing.",
+                    missing_ray_tracing_features.join(", ")
+                );
+            }
+
+            let dynamic_rendering_enabled =
+                dynamic_rendering_supported && dynamic_rendering_features.dynamic_rendering != 0;
+
+            if dynamic_rendering_enabled {
+                device_extension_names.push(super::dynamic_rendering::name().as_ptr());
+            } else {
+                log::info!("Dynamic rendering not supported; using render pass objects");
+            }
+
+            // The queried structs are still linked to each other; unlink them so that
+            // only the features of enabled extensions get chained for device creation.
+            for p_next in [
+                &mut scalar_block.p_next,
+                &mut descriptor_indexing.p_next,
+                &mut imageless_framebuffer.p_next,
+                &mut shader_float16_int8.p_next,
+                &mut vulkan_memory_model.p_next,
+                &mut get_buffer_device_address_features.p_next,
+                &mut acceleration_structure_features.p_next,
+                &mut ray_tracing_pipeline_features.p_next,
+                &mut portability_subset_features.p_next,
+                &mut dynamic_rendering_features.p_next,
+            ] {
+                *p_next = std::ptr::null_mut();
+            }
+
+            let mut enabled_features2 = vk::PhysicalDeviceFeatures2::builder()
+                .features(features2.features)
+                .push_next(&mut scalar_block)
+                .push_next(&mut descriptor_indexing)
+                .push_next(&mut imageless_framebuffer)
+                .push_next(&mut shader_float16_int8)
+                .push_next(&mut vulkan_memory_model)
+                .push_next(&mut get_buffer_device_address_features);
+
+            if ray_tracing_enabled {
+                enabled_features2 = enabled_features2
+                    .push_next(&mut acceleration_structure_features)
+                    .push_next(&mut ray_tracing_pipeline_features);
+            }
+
+            if portability_subset_enabled {
+                enabled_features2 = enabled_features2.push_next(&mut portability_subset_features);
+            }
+
+            if dynamic_rendering_enabled {
+                enabled_features2 = enabled_features2.push_next(&mut dynamic_rendering_features);
+            }
+
+            let mut enabled_features2 = enabled_features2.build();
+
             let device_create_info = vk::DeviceCreateInfo::builder()
                 .queue_create_infos(&universal_queue_info)
                 .enabled_extension_names(&device_extension_names)
-                .push_next(&mut features2)
+                .push_next(&mut enabled_features2)
                 .build();
 
-            let device = instance
-                .create_device(pdevice.raw, &device_create_info, None)
-                .unwrap();
+            let device = instance.create_device(pdevice.raw, &device_create_info, None)?;
 
             info!("Created a Vulkan device");
 
-            let mut global_allocator = VulkanAllocator::new(&VulkanAllocatorCreateDesc {
-                instance: instance.clone(),
-                device: device.clone(),
-                physical_device: pdevice.raw,
-                debug_settings: AllocatorDebugSettings {
-                    log_leaks_on_shutdown: false,
-                    log_memory_information: true,
-                    log_allocations: true,
-                    ..Default::default()
+            Self::from_raw_parts(
+                pdevice,
+                device,
+                universal_queue,
+                0,
+                EnabledFeatures {
+                    ray_tracing: ray_tracing_enabled,
+                    draw_indirect_count: draw_indirect_count_enabled,
+                    fill_mode_non_solid: fill_mode_non_solid_enabled,
+                    portability_subset: portability_subset_enabled,
+                    shader_debug_printf: shader_debug_printf_enabled,
+                    external_interop: external_interop_enabled,
+                    buffer_device_address: get_buffer_device_address_features.buffer_device_address
+                        != 0,
+                    dynamic_rendering: dynamic_rendering_enabled,
                 },
-                buffer_device_address: true,
-            });
+            )
+        }
+    }
 
-            let universal_queue = Queue {
-                raw: device.get_device_queue(universal_queue.index, 0),
-                family: universal_queue,
-            };
-
-            let frame0 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
-            let frame1 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
-            //let frame2 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
-
-            let immutable_samplers = Self::create_samplers(&device);
-            let setup_cb = CommandBuffer::new(&device, &universal_queue.family).unwrap();
-
-            let acceleration_structure_ext =
-                khr::AccelerationStructure::new(&pdevice.instance.raw, &device);
-            let ray_tracing_pipeline_ext =
-                khr::RayTracingPipeline::new(&pdevice.instance.raw, &device);
-            //let ray_query_ext = khr::RayQuery::new(&pdevice.instance.raw, &device);
-            let ray_tracing_pipeline_properties =
-                khr::RayTracingPipeline::get_properties(&pdevice.instance.raw, pdevice.raw);
-
-            let crash_tracking_buffer = Self::create_buffer_impl(
-                &device,
-                &mut global_allocator,
-                BufferDesc::new_gpu_to_cpu(4, vk::BufferUsageFlags::TRANSFER_DST),
-                "crash tracking buffer",
-            )?;
+    /// Sets up everything the renderer needs around a created device: the allocator,
+    /// the frames, samplers, and the extension function tables. Submits to the queue
+    /// `queue_index` of `universal_queue`.
+    pub(super) unsafe fn from_raw_parts(
+        pdevice: &Arc<PhysicalDevice>,
+        device: ash::Device,
+        universal_queue: QueueFamily,
+        queue_index: u32,
+        enabled: EnabledFeatures,
+    ) -> Result<Arc<Self>> {
+        let mut global_allocator = VulkanAllocator::new(&VulkanAllocatorCreateDesc {
+            instance: pdevice.instance.raw.clone(),
+            device: device.clone(),
+            physical_device: pdevice.raw,
+            debug_settings: AllocatorDebugSettings {
+                log_leaks_on_shutdown: false,
+                log_memory_information: true,
+                log_allocations: true,
+                ..Default::default()
+            },
+            buffer_device_address: enabled.buffer_device_address,
+        });
 
-            Ok(Arc::new(Device {
-                pdevice: pdevice.clone(),
-                instance: pdevice.instance.clone(),
-                raw: device,
-                universal_queue,
-                global_allocator: Arc::new(Mutex::new(global_allocator)),
-                immutable_samplers,
-                setup_cb: Mutex::new(setup_cb),
-                crash_tracking_buffer,
-                crash_marker_names: Default::default(),
-                acceleration_structure_ext,
-                ray_tracing_pipeline_ext,
-                // ray_query_ext,
-                ray_tracing_pipeline_properties,
-                frames: [
-                    Mutex::new(Arc::new(frame0)),
-                    Mutex::new(Arc::new(frame1)),
-                    //Mutex::new(Arc::new(frame2)),
-                ],
-                ray_tracing_enabled,
-            }))
+        let universal_queue = Queue {
+            raw: device.get_device_queue(universal_queue.index, queue_index),
+            family: universal_queue,
+            submit_lock: Mutex::new(()),
+        };
+
+        let frame0 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
+        let frame1 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
+        //let frame2 = DeviceFrame::new(&device, &mut global_allocator, &universal_queue.family);
+
+        let immutable_samplers = Self::create_samplers(&device);
+        let setup_cb = CommandBuffer::new(&device, &universal_queue.family).unwrap();
+        let pipeline_cache = Self::create_pipeline_cache(&device, pdevice)?;
+
+        let acceleration_structure_ext =
+            khr::AccelerationStructure::new(&pdevice.instance.raw, &device);
+        let ray_tracing_pipeline_ext = khr::RayTracingPipeline::new(&pdevice.instance.raw, &device);
+        //let ray_query_ext = khr::RayQuery::new(&pdevice.instance.raw, &device);
+        let ray_tracing_pipeline_properties =
+            khr::RayTracingPipeline::get_properties(&pdevice.instance.raw, pdevice.raw);
+        let draw_indirect_count_ext = khr::DrawIndirectCount::new(&pdevice.instance.raw, &device);
+        let external_interop_fns = enabled
+            .external_interop
+            .then(|| ExternalInteropFns::new(&pdevice.instance.raw, &device));
+        let dynamic_rendering_fns = enabled
+            .dynamic_rendering
+            .then(|| DynamicRenderingFns::new(&pdevice.instance.raw, &device))
+            .flatten();
+
+        let crash_tracking_buffer = Self::create_buffer_impl(
+            &device,
+            &mut global_allocator,
+            BufferDesc::new_gpu_to_cpu(4, vk::BufferUsageFlags::TRANSFER_DST),
+            "crash tracking buffer",
+        )?;
+
+        Ok(Arc::new(Device {
+            pdevice: pdevice.clone(),
+            instance: pdevice.instance.clone(),
+            raw: device,
+            universal_queue,
+            global_allocator: Arc::new(Mutex::new(global_allocator)),
+            immutable_samplers,
+            setup_cbs: Mutex::new(vec![setup_cb]),
+            crash_tracking_buffer,
+            crash_marker_names: Default::default(),
+            acceleration_structure_ext,
+            ray_tracing_pipeline_ext,
+            // ray_query_ext,
+            ray_tracing_pipeline_properties,
+            draw_indirect_count_ext,
+            external_interop_fns,
+            dynamic_rendering_fns,
+            render_pass_cache: Default::default(),
+            pipeline_cache,
+            resource_tracker: pdevice
+                .instance
+                .debug_utils
+                .is_some()
+                .then(ResourceTracker::default),
+            stats: Default::default(),
+            frames: [
+                Mutex::new(Arc::new(frame0)),
+                Mutex::new(Arc::new(frame1)),
+                //Mutex::new(Arc::new(frame2)),
+            ],
+            frame_value: AtomicU64::new(0),
+            staging_belt: Default::default(),
+            ray_tracing_enabled: enabled.ray_tracing,
+            draw_indirect_count_enabled: enabled.draw_indirect_count,
+            fill_mode_non_solid_enabled: enabled.fill_mode_non_solid,
+            portability_subset_enabled: enabled.portability_subset,
+            shader_debug_printf_enabled: enabled.shader_debug_printf,
+        }))
+    }
+
+    fn pipeline_cache_path() -> anyhow::Result<std::path::PathBuf> {
+        Ok(crate::file::normalized_path_from_vfs("/cache")?.join(PIPELINE_CACHE_FILE_NAME))
+    }
+
+    fn create_pipeline_cache(
+        device: &ash::Device,
+        pdevice: &PhysicalDevice,
+    ) -> Result<vk::PipelineCache> {
+        let initial_data = Self::pipeline_cache_path()
+            .ok()
+            .and_then(|path| std::fs::read(path).ok())
+            .filter(|data| Self::is_pipeline_cache_compatible(data, &pdevice.properties))
+            .unwrap_or_default();
+
+        if !initial_data.is_empty() {
+            info!("Loaded {} bytes of pipeline cache", initial_data.len());
+        }
+
+        Ok(unsafe {
+            device.create_pipeline_cache(
+                &vk::PipelineCacheCreateInfo::builder().initial_data(&initial_data),
+                None,
+            )?
+        })
+    }
+
+    /// Checks the header of the cache data, since not all drivers reject data
+    /// from other devices or driver versions gracefully.
+    fn is_pipeline_cache_compatible(
+        data: &[u8],
+        properties: &vk::PhysicalDeviceProperties,
+    ) -> bool {
+        const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+        if data.len() < HEADER_SIZE {
+            return false;
         }
+
+        let read_u32 = |offset: usize| {
+            u32::from_ne_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+
+        read_u32(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && read_u32(8) == properties.vendor_id
+            && read_u32(12) == properties.device_id
+            && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+    }
+
+    /// Writes the pipelines compiled so far to disk, so that the next run can skip compiling them.
+    pub fn save_pipeline_cache(&self) -> anyhow::Result<()> {
+        let data = unsafe { self.raw.get_pipeline_cache_data(self.pipeline_cache)? };
+        let path = Self::pipeline_cache_path()?;
+        std::fs::write(&path, &data)?;
+
+        info!("Saved {} bytes of pipeline cache to {:?}", data.len(), path);
+        Ok(())
     }
 
     fn create_samplers(device: &ash::Device) -> HashMap<SamplerDesc, vk::Sampler> {
@@ -536,54 +936,200 @@ impl Device {
             }
 
             puffin::profile_scope!("release pending resources");
-            frame0
-                .pending_resource_releases
-                .get_mut()
-                .release_all(&self.raw);
+            frame0.pending_resource_releases.get_mut().release_all(self);
         }
 
+        self.frame_value.fetch_add(1, Ordering::SeqCst);
+        self.staging_belt
+            .lock()
+            .recycle(self, self.completed_frame_value());
+
         frame0.clone()
     }
 
+    /// The number of frames begun so far.
+    pub fn current_frame_value(&self) -> u64 {
+        self.frame_value.load(Ordering::SeqCst)
+    }
+
+    /// The GPU is done with the frames up to, and including, this one.
+    pub fn completed_frame_value(&self) -> u64 {
+        self.current_frame_value()
+            .saturating_sub(self.frames.len() as u64)
+    }
+
+    /// Allocates `size` bytes of host-visible memory, at an offset which is a multiple
+    /// of `alignment`, for the GPU to read in the frame being prepared or recorded.
+    /// The memory is reused once the frame is done; see `StagingBelt`.
+    pub fn allocate_staging(
+        &self,
+        size: usize,
+        alignment: usize,
+    ) -> Result<StagingSlice, BackendError> {
+        // Frames are prepared before they begin, so this might be for the next one.
+        let frame_value = self.current_frame_value() + 1;
+
+        self.staging_belt
+            .lock()
+            .allocate(self, size, alignment, frame_value)
+    }
+
+    /// Waits for the GPU to finish all the submitted frames, and destroys the resources
+    /// whose release was deferred, along with the staging belt.
+    pub fn drain_frames(&self) {
+        unsafe {
+            puffin::profile_scope!("device_wait_idle");
+            self.raw
+                .device_wait_idle()
+                .map_err(|err| self.report_error(err.into()))
+                .expect("device_wait_idle failed");
+        }
+
+        self.release_all_pending_resources();
+        self.staging_belt.lock().release_all(self);
+    }
+
+    // The GPU must be idle.
+    fn release_all_pending_resources(&self) {
+        for frame in &self.frames {
+            frame
+                .lock()
+                .pending_resource_releases
+                .lock()
+                .release_all(self);
+        }
+    }
+
+    /// Destroys the resource once the GPU is done with the frames which could still be using it.
     pub fn defer_release(&self, resource: impl DeferredRelease) {
         resource.enqueue_release(&mut self.frames[0].lock().pending_resource_releases.lock());
     }
 
+    pub fn create_descriptor_pool(
+        &self,
+        create_info: &vk::DescriptorPoolCreateInfo,
+        name: &str,
+    ) -> Result<vk::DescriptorPool, BackendError> {
+        let pool = unsafe { self.raw.create_descriptor_pool(create_info, None)? };
+        self.track_created(ResourceKind::DescriptorPool, pool, || name.to_owned());
+        self.stats.descriptor_pool_created();
+        Ok(pool)
+    }
+
+    pub fn immediate_destroy_descriptor_pool(&self, pool: vk::DescriptorPool) {
+        unsafe {
+            self.raw.destroy_descriptor_pool(pool, None);
+        }
+        self.track_destroyed(ResourceKind::DescriptorPool, pool);
+        self.stats.descriptor_pool_destroyed();
+    }
+
+    /// Attributes the objects created on this thread to `creator` until the scope is dropped,
+    /// if resource tracking is enabled; see `live_resources`.
+    pub fn resource_creator_scope(
+        &self,
+        creator: impl FnOnce() -> String,
+    ) -> Option<ResourceCreatorScope> {
+        self.resource_tracker
+            .as_ref()
+            .map(|_| ResourceCreatorScope::new(creator()))
+    }
+
+    /// The objects created through the device and not destroyed yet, if tracking them is enabled,
+    /// which it is with graphics debugging. Reported at shutdown.
+    pub fn live_resources(&self) -> Option<Vec<LiveResource>> {
+        self.resource_tracker
+            .as_ref()
+            .map(ResourceTracker::live_resources)
+    }
+
+    /// Memory and objects currently held through the device.
+    pub fn stats(&self) -> DeviceStats {
+        self.stats.snapshot()
+    }
+
+    pub(crate) fn track_created(
+        &self,
+        kind: ResourceKind,
+        handle: impl vk::Handle,
+        name: impl FnOnce() -> String,
+    ) {
+        if let Some(tracker) = &self.resource_tracker {
+            tracker.created(kind, handle, name());
+        }
+    }
+
+    pub(crate) fn track_destroyed(&self, kind: ResourceKind, handle: impl vk::Handle) {
+        if let Some(tracker) = &self.resource_tracker {
+            tracker.destroyed(kind, handle);
+        }
+    }
+
+    /// Records commands via `callback`, submits them, and waits for them to finish.
+    ///
+    /// Can be called from multiple threads at once: each call records into its own command
+    /// buffer, and waits on its own fence rather than for the whole queue to go idle.
     pub fn with_setup_cb(
         &self,
         callback: impl FnOnce(vk::CommandBuffer),
     ) -> Result<(), BackendError> {
-        let cb = self.setup_cb.lock();
+        let cb = self.setup_cbs.lock().pop();
+        let cb = match cb {
+            Some(cb) => cb,
+            None => CommandBuffer::new(&self.raw, &self.universal_queue.family)?,
+        };
+
+        let result = self.submit_setup_cb(&cb, callback);
+
+        // On failure the GPU might still be using the command buffer, so it's not recycled.
+        if result.is_ok() {
+            self.setup_cbs.lock().push(cb);
+        }
+
+        result
+    }
 
+    fn submit_setup_cb(
+        &self,
+        cb: &CommandBuffer,
+        callback: impl FnOnce(vk::CommandBuffer),
+    ) -> Result<(), BackendError> {
         unsafe {
-            self.raw
-                .begin_command_buffer(
-                    cb.raw,
-                    &vk::CommandBufferBeginInfo::builder()
-                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
-                )
-                .unwrap();
+            self.raw.begin_command_buffer(
+                cb.raw,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
         }
 
         callback(cb.raw);
 
         unsafe {
-            self.raw.end_command_buffer(cb.raw).unwrap();
+            self.raw.end_command_buffer(cb.raw)?;
+            self.raw
+                .reset_fences(std::slice::from_ref(&cb.submit_done_fence))?;
 
             let submit_info =
                 vk::SubmitInfo::builder().command_buffers(std::slice::from_ref(&cb.raw));
 
-            self.raw
-                .queue_submit(
+            {
+                let _queue_lock = self.universal_queue.submit_lock.lock();
+
+                self.raw.queue_submit(
                     self.universal_queue.raw,
                     &[submit_info.build()],
-                    vk::Fence::null(),
-                )
-                .expect("queue submit failed.");
+                    cb.submit_done_fence,
+                )?;
+            }
 
-            log::trace!("device_wait_idle");
+            // Outside of the queue lock, so that frame submission isn't held up by the upload.
+            log::trace!("wait_for_fences (setup cb)");
 
-            Ok(self.raw.device_wait_idle()?)
+            Ok(self.raw.wait_for_fences(
+                std::slice::from_ref(&cb.submit_done_fence),
+                true,
+                u64::MAX,
+            )?)
         }
     }
 
@@ -625,9 +1171,121 @@ impl Device {
         )
     }
 
+    /// False if the device doesn't support ray tracing, or the `ray-tracing` feature is off.
     pub fn ray_tracing_enabled(&self) -> bool {
         self.ray_tracing_enabled
     }
+
+    pub fn external_interop_enabled(&self) -> bool {
+        self.external_interop_fns.is_some()
+    }
+
+    /// Set on implementations such as MoltenVK which don't support the full Vulkan spec.
+    pub fn portability_subset_enabled(&self) -> bool {
+        self.portability_subset_enabled
+    }
+
+    /// Whether images of `format` can be created with all of `usage`.
+    pub fn image_format_supports_usage(
+        &self,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        usage: vk::ImageUsageFlags,
+    ) -> bool {
+        let mut required = vk::FormatFeatureFlags::empty();
+        for (usage_flag, feature) in [
+            (
+                vk::ImageUsageFlags::SAMPLED,
+                vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            ),
+            (
+                vk::ImageUsageFlags::STORAGE,
+                vk::FormatFeatureFlags::STORAGE_IMAGE,
+            ),
+            (
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+            ),
+            (
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+            ),
+            (
+                vk::ImageUsageFlags::TRANSFER_SRC,
+                vk::FormatFeatureFlags::TRANSFER_SRC,
+            ),
+            (
+                vk::ImageUsageFlags::TRANSFER_DST,
+                vk::FormatFeatureFlags::TRANSFER_DST,
+            ),
+        ] {
+            if usage.contains(usage_flag) {
+                required |= feature;
+            }
+        }
+
+        let properties = unsafe {
+            self.instance
+                .raw
+                .get_physical_device_format_properties(self.pdevice.raw, format)
+        };
+
+        let supported = if tiling == vk::ImageTiling::LINEAR {
+            properties.linear_tiling_features
+        } else {
+            properties.optimal_tiling_features
+        };
+
+        supported.contains(required)
+    }
+
+    pub fn draw_indirect_count_enabled(&self) -> bool {
+        self.draw_indirect_count_enabled
+    }
+
+    /// Whether single-subpass render passes begin with `cmd_begin_rendering`, without
+    /// render pass and framebuffer objects; see `RenderPass::dynamic_rendering`.
+    pub fn dynamic_rendering_enabled(&self) -> bool {
+        self.dynamic_rendering_fns.is_some()
+    }
+
+    /// Requires `dynamic_rendering_enabled`.
+    ///
+    /// # Safety
+    ///
+    /// The attachments in `rendering_info` must be valid, and in the layouts it names.
+    pub unsafe fn cmd_begin_rendering(
+        &self,
+        cb: vk::CommandBuffer,
+        rendering_info: &RenderingInfoKHR,
+    ) {
+        self.dynamic_rendering_fns
+            .as_ref()
+            .expect("dynamic rendering is not enabled")
+            .cmd_begin_rendering(cb, rendering_info)
+    }
+
+    /// Ends rendering begun with `cmd_begin_rendering`.
+    ///
+    /// # Safety
+    ///
+    /// `cb` must be within `cmd_begin_rendering`.
+    pub unsafe fn cmd_end_rendering(&self, cb: vk::CommandBuffer) {
+        self.dynamic_rendering_fns
+            .as_ref()
+            .expect("dynamic rendering is not enabled")
+            .cmd_end_rendering(cb)
+    }
+
+    /// Whether `DEBUG_PRINTF` in shaders reaches the log; see `inc/gpu_debug.hlsl`.
+    pub fn shader_debug_printf_enabled(&self) -> bool {
+        self.shader_debug_printf_enabled
+    }
+
+    /// Whether raster pipelines can use `vk::PolygonMode::LINE`, e.g. for wireframes.
+    pub fn fill_mode_non_solid_enabled(&self) -> bool {
+        self.fill_mode_non_solid_enabled
+    }
 }
 
 impl Drop for Device {
@@ -636,6 +1294,26 @@ impl Drop for Device {
             log::trace!("device_wait_idle");
             let _ = self.raw.device_wait_idle();
         }
+
+        self.release_all_pending_resources();
+        self.staging_belt.lock().release_all(self);
+
+        unsafe {
+            self.raw.destroy_pipeline_cache(self.pipeline_cache, None);
+        }
+
+        if let Some(live_resources) = self.live_resources() {
+            if !live_resources.is_empty() {
+                log::warn!(
+                    "{} GPU objects are still alive at device shutdown:",
+                    live_resources.len()
+                );
+
+                for resource in live_resources {
+                    log::warn!("    {}", resource);
+                }
+            }
+        }
     }
 }
 
@@ -668,3 +1346,87 @@ pub struct SamplerDesc {
     pub mipmap_mode: vk::SamplerMipmapMode,
     pub address_modes: vk::SamplerAddressMode,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2204,
+            pipeline_cache_uuid: [7; vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    /// The header `vkGetPipelineCacheData` writes, followed by some opaque data.
+    fn cache_data(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(16 + vk::UUID_SIZE as u32).to_ne_bytes());
+        data.extend_from_slice(
+            &(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_ne_bytes(),
+        );
+        data.extend_from_slice(&properties.vendor_id.to_ne_bytes());
+        data.extend_from_slice(&properties.device_id.to_ne_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(&[0xab; 64]);
+        data
+    }
+
+    #[test]
+    fn pipeline_cache_compatible() {
+        let properties = properties();
+        let data = cache_data(&properties);
+
+        assert!(Device::is_pipeline_cache_compatible(&data, &properties));
+        assert!(Device::is_pipeline_cache_compatible(
+            &data[..16 + vk::UUID_SIZE],
+            &properties
+        ));
+    }
+
+    #[test]
+    fn pipeline_cache_truncated() {
+        let properties = properties();
+        let data = cache_data(&properties);
+
+        assert!(!Device::is_pipeline_cache_compatible(&[], &properties));
+        assert!(!Device::is_pipeline_cache_compatible(
+            &data[..16 + vk::UUID_SIZE - 1],
+            &properties
+        ));
+    }
+
+    #[test]
+    fn pipeline_cache_from_other_device() {
+        let properties = properties();
+        let data = cache_data(&properties);
+
+        let other_vendor = vk::PhysicalDeviceProperties {
+            vendor_id: 0x1002,
+            ..properties
+        };
+        let other_device = vk::PhysicalDeviceProperties {
+            device_id: 0x2206,
+            ..properties
+        };
+        let other_driver = vk::PhysicalDeviceProperties {
+            pipeline_cache_uuid: [8; vk::UUID_SIZE],
+            ..properties
+        };
+
+        assert!(!Device::is_pipeline_cache_compatible(&data, &other_vendor));
+        assert!(!Device::is_pipeline_cache_compatible(&data, &other_device));
+        assert!(!Device::is_pipeline_cache_compatible(&data, &other_driver));
+    }
+
+    #[test]
+    fn pipeline_cache_other_header_version() {
+        let properties = properties();
+        let mut data = cache_data(&properties);
+        data[4..8].copy_from_slice(&2u32.to_ne_bytes());
+
+        assert!(!Device::is_pipeline_cache_compatible(&data, &properties));
+    }
+}
@@ -0,0 +1,101 @@
+//! Running totals of the GPU memory and objects held through `Device`, for overlays and logs.
+//!
+//! Unlike `resource_tracking`, these are always kept up; they're just atomic adds.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DeviceStats {
+    /// Images with memory of their own, i.e. not the swapchain.
+    pub image_count: u64,
+    pub image_bytes: u64,
+
+    /// Excluding the backing buffers of acceleration structures.
+    pub buffer_count: u64,
+    pub buffer_bytes: u64,
+
+    /// Along with their backing buffers.
+    pub acceleration_structure_count: u64,
+    pub acceleration_structure_bytes: u64,
+
+    pub live_descriptor_pools: u64,
+    /// Since the device was created. Render graph passes create one each per frame.
+    pub total_descriptor_pools_created: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct DeviceStatsCounters {
+    image_count: AtomicU64,
+    image_bytes: AtomicU64,
+    buffer_count: AtomicU64,
+    buffer_bytes: AtomicU64,
+    acceleration_structure_count: AtomicU64,
+    acceleration_structure_bytes: AtomicU64,
+    live_descriptor_pools: AtomicU64,
+    total_descriptor_pools_created: AtomicU64,
+}
+
+impl DeviceStatsCounters {
+    pub fn image_created(&self, bytes: u64) {
+        self.image_count.fetch_add(1, Ordering::Relaxed);
+        self.image_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn image_destroyed(&self, bytes: u64) {
+        self.image_count.fetch_sub(1, Ordering::Relaxed);
+        self.image_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn buffer_created(&self, bytes: u64) {
+        self.buffer_count.fetch_add(1, Ordering::Relaxed);
+        self.buffer_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn buffer_destroyed(&self, bytes: u64) {
+        self.buffer_count.fetch_sub(1, Ordering::Relaxed);
+        self.buffer_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Moves the backing buffer, already counted by `buffer_created`, over to the acceleration structures.
+    pub fn acceleration_structure_created(&self, backing_buffer_bytes: u64) {
+        self.buffer_destroyed(backing_buffer_bytes);
+        self.acceleration_structure_count
+            .fetch_add(1, Ordering::Relaxed);
+        self.acceleration_structure_bytes
+            .fetch_add(backing_buffer_bytes, Ordering::Relaxed);
+    }
+
+    /// Moves the backing buffer back, for `buffer_destroyed` to count when it's destroyed.
+    pub fn acceleration_structure_destroyed(&self, backing_buffer_bytes: u64) {
+        self.acceleration_structure_count
+            .fetch_sub(1, Ordering::Relaxed);
+        self.acceleration_structure_bytes
+            .fetch_sub(backing_buffer_bytes, Ordering::Relaxed);
+        self.buffer_created(backing_buffer_bytes);
+    }
+
+    pub fn descriptor_pool_created(&self) {
+        self.live_descriptor_pools.fetch_add(1, Ordering::Relaxed);
+        self.total_descriptor_pools_created
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn descriptor_pool_destroyed(&self) {
+        self.live_descriptor_pools.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> DeviceStats {
+        DeviceStats {
+            image_count: self.image_count.load(Ordering::Relaxed),
+            image_bytes: self.image_bytes.load(Ordering::Relaxed),
+            buffer_count: self.buffer_count.load(Ordering::Relaxed),
+            buffer_bytes: self.buffer_bytes.load(Ordering::Relaxed),
+            acceleration_structure_count: self.acceleration_structure_count.load(Ordering::Relaxed),
+            acceleration_structure_bytes: self.acceleration_structure_bytes.load(Ordering::Relaxed),
+            live_descriptor_pools: self.live_descriptor_pools.load(Ordering::Relaxed),
+            total_descriptor_pools_created: self
+                .total_descriptor_pools_created
+                .load(Ordering::Relaxed),
+        }
+    }
+}
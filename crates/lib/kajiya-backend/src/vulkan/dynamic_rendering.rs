@@ -0,0 +1,202 @@
+//! `VK_KHR_dynamic_rendering`, which renders straight into image views, without
+//! render pass and framebuffer objects.
+//!
+//! The extension arrived in Vulkan headers 1.2.197, and `ash` 0.33 is built against 1.2.191,
+//! so its structs are declared here, and its commands loaded with `get_device_proc_addr`.
+//! Remove this once `ash` is upgraded.
+
+use ash::vk;
+use std::ffi::{c_void, CStr};
+
+pub fn name() -> &'static CStr {
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_KHR_dynamic_rendering\0") }
+}
+
+const STRUCTURE_TYPE_RENDERING_INFO_KHR: vk::StructureType =
+    vk::StructureType::from_raw(1000044000);
+const STRUCTURE_TYPE_RENDERING_ATTACHMENT_INFO_KHR: vk::StructureType =
+    vk::StructureType::from_raw(1000044001);
+const STRUCTURE_TYPE_PIPELINE_RENDERING_CREATE_INFO_KHR: vk::StructureType =
+    vk::StructureType::from_raw(1000044002);
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES_KHR: vk::StructureType =
+    vk::StructureType::from_raw(1000044003);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PhysicalDeviceDynamicRenderingFeaturesKHR {
+    pub s_type: vk::StructureType,
+    pub p_next: *mut c_void,
+    pub dynamic_rendering: vk::Bool32,
+}
+
+impl Default for PhysicalDeviceDynamicRenderingFeaturesKHR {
+    fn default() -> Self {
+        Self {
+            s_type: STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES_KHR,
+            p_next: std::ptr::null_mut(),
+            dynamic_rendering: vk::FALSE,
+        }
+    }
+}
+
+unsafe impl vk::ExtendsPhysicalDeviceFeatures2 for PhysicalDeviceDynamicRenderingFeaturesKHR {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RenderingAttachmentInfoKHR {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub image_view: vk::ImageView,
+    pub image_layout: vk::ImageLayout,
+    pub resolve_mode: vk::ResolveModeFlags,
+    pub resolve_image_view: vk::ImageView,
+    pub resolve_image_layout: vk::ImageLayout,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear_value: vk::ClearValue,
+}
+
+impl RenderingAttachmentInfoKHR {
+    /// Without a resolve, and clearing to zero with `vk::AttachmentLoadOp::CLEAR`,
+    /// as render passes do without clear values.
+    pub fn new(
+        image_view: vk::ImageView,
+        image_layout: vk::ImageLayout,
+        load_op: vk::AttachmentLoadOp,
+        store_op: vk::AttachmentStoreOp,
+    ) -> Self {
+        Self {
+            s_type: STRUCTURE_TYPE_RENDERING_ATTACHMENT_INFO_KHR,
+            p_next: std::ptr::null(),
+            image_view,
+            image_layout,
+            resolve_mode: vk::ResolveModeFlags::NONE,
+            resolve_image_view: vk::ImageView::null(),
+            resolve_image_layout: vk::ImageLayout::UNDEFINED,
+            load_op,
+            store_op,
+            clear_value: vk::ClearValue::default(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RenderingInfoKHR {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub flags: vk::Flags,
+    pub render_area: vk::Rect2D,
+    pub layer_count: u32,
+    pub view_mask: u32,
+    pub color_attachment_count: u32,
+    pub p_color_attachments: *const RenderingAttachmentInfoKHR,
+    pub p_depth_attachment: *const RenderingAttachmentInfoKHR,
+    pub p_stencil_attachment: *const RenderingAttachmentInfoKHR,
+}
+
+impl RenderingInfoKHR {
+    /// Only valid as long as the attachments are.
+    pub fn new(
+        render_area: vk::Rect2D,
+        color_attachments: &[RenderingAttachmentInfoKHR],
+        depth_attachment: Option<&RenderingAttachmentInfoKHR>,
+    ) -> Self {
+        Self {
+            s_type: STRUCTURE_TYPE_RENDERING_INFO_KHR,
+            p_next: std::ptr::null(),
+            flags: 0,
+            render_area,
+            layer_count: 1,
+            view_mask: 0,
+            color_attachment_count: color_attachments.len() as u32,
+            p_color_attachments: color_attachments.as_ptr(),
+            p_depth_attachment: depth_attachment.map_or(std::ptr::null(), |a| a as *const _),
+            p_stencil_attachment: std::ptr::null(),
+        }
+    }
+}
+
+/// Chained to `vk::GraphicsPipelineCreateInfo` in place of a render pass.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PipelineRenderingCreateInfoKHR {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub view_mask: u32,
+    pub color_attachment_count: u32,
+    pub p_color_attachment_formats: *const vk::Format,
+    pub depth_attachment_format: vk::Format,
+    pub stencil_attachment_format: vk::Format,
+}
+
+impl PipelineRenderingCreateInfoKHR {
+    /// Only valid as long as `color_attachment_formats` is.
+    pub fn new(
+        color_attachment_formats: &[vk::Format],
+        depth_attachment_format: vk::Format,
+    ) -> Self {
+        Self {
+            s_type: STRUCTURE_TYPE_PIPELINE_RENDERING_CREATE_INFO_KHR,
+            p_next: std::ptr::null(),
+            view_mask: 0,
+            color_attachment_count: color_attachment_formats.len() as u32,
+            p_color_attachment_formats: color_attachment_formats.as_ptr(),
+            depth_attachment_format,
+            stencil_attachment_format: vk::Format::UNDEFINED,
+        }
+    }
+}
+
+unsafe impl vk::ExtendsGraphicsPipelineCreateInfo for PipelineRenderingCreateInfoKHR {}
+
+#[allow(non_camel_case_types)]
+type PFN_vkCmdBeginRenderingKHR = unsafe extern "system" fn(
+    command_buffer: vk::CommandBuffer,
+    p_rendering_info: *const RenderingInfoKHR,
+);
+
+#[allow(non_camel_case_types)]
+type PFN_vkCmdEndRenderingKHR = unsafe extern "system" fn(command_buffer: vk::CommandBuffer);
+
+pub(crate) struct DynamicRenderingFns {
+    cmd_begin_rendering: PFN_vkCmdBeginRenderingKHR,
+    cmd_end_rendering: PFN_vkCmdEndRenderingKHR,
+}
+
+impl DynamicRenderingFns {
+    /// `None` if the device doesn't expose the commands, e.g. without the extension enabled.
+    pub(crate) fn new(instance: &ash::Instance, device: &ash::Device) -> Option<Self> {
+        let load = |name: &[u8]| unsafe {
+            instance.get_device_proc_addr(device.handle(), name.as_ptr() as *const _)
+        };
+
+        let cmd_begin_rendering = load(b"vkCmdBeginRenderingKHR\0")?;
+        let cmd_end_rendering = load(b"vkCmdEndRenderingKHR\0")?;
+
+        unsafe {
+            Some(Self {
+                cmd_begin_rendering: std::mem::transmute::<
+                    unsafe extern "system" fn(),
+                    PFN_vkCmdBeginRenderingKHR,
+                >(cmd_begin_rendering),
+                cmd_end_rendering: std::mem::transmute::<
+                    unsafe extern "system" fn(),
+                    PFN_vkCmdEndRenderingKHR,
+                >(cmd_end_rendering),
+            })
+        }
+    }
+
+    pub(crate) unsafe fn cmd_begin_rendering(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        rendering_info: &RenderingInfoKHR,
+    ) {
+        (self.cmd_begin_rendering)(command_buffer, rendering_info)
+    }
+
+    pub(crate) unsafe fn cmd_end_rendering(&self, command_buffer: vk::CommandBuffer) {
+        (self.cmd_end_rendering)(command_buffer)
+    }
+}
@@ -0,0 +1,173 @@
+//! Embedding the renderer in an application which already owns a Vulkan instance and device,
+//! such as an engine or an editor, rather than having the renderer create its own.
+//!
+//! The application creates its device with `Device::required_extensions`, and the features
+//! `Device::create` would enable, then wraps its handles with `Instance::from_raw`,
+//! `PhysicalDevice::from_raw`, and `Device::from_external`. The renderer never destroys
+//! them; the application does, once the renderer has shut down. Frames can then be rendered
+//! into the application's own images, wrapped with `Device::wrap_raw_image`, by
+//! `Renderer::draw_frame_to_image` of `kajiya-rg`.
+//!
+//! The other way around, the handles of the renderer are public: `Instance::raw`,
+//! `PhysicalDevice::raw`, `Device::raw`, and the queue in `Device::universal_queue`.
+
+use super::{
+    device::{Device, EnabledFeatures},
+    image::{Image, ImageDesc},
+    instance::Instance,
+    physical_device::PhysicalDevice,
+    resource_tracking::ResourceKind,
+};
+use anyhow::Result;
+use ash::{extensions::khr, vk};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::{
+    ffi::{CStr, CString},
+    sync::Arc,
+};
+
+/// A device created by the application, for `Device::from_external`.
+pub struct ExternalDeviceDesc {
+    pub raw: ash::Device,
+
+    /// Of a queue family supporting graphics and compute.
+    pub queue_family_index: u32,
+
+    /// The queue within the family which the renderer submits to.
+    pub queue_index: u32,
+
+    /// All those the device was created with. They must include `Device::required_extensions`;
+    /// optional ones, such as `Device::ray_tracing_extensions`, are used if present.
+    pub enabled_extensions: Vec<CString>,
+
+    /// The core features the device was created with.
+    pub enabled_features: vk::PhysicalDeviceFeatures,
+}
+
+impl Instance {
+    /// Wraps an instance created by the application for Vulkan 1.2 or later,
+    /// with `VK_KHR_get_physical_device_properties2` enabled.
+    ///
+    /// Validation is up to the layers the application enabled; the renderer doesn't name
+    /// its objects or label its passes on such instances.
+    pub fn from_raw(entry: ash::Entry, raw: ash::Instance) -> Arc<Self> {
+        Arc::new(Self {
+            entry,
+            raw,
+            debug_callback: None,
+            debug_loader: None,
+            debug_utils: None,
+            debug_messenger: None,
+            shader_debug_printf: false,
+        })
+    }
+
+    pub fn entry(&self) -> &ash::Entry {
+        &self.entry
+    }
+}
+
+impl Device {
+    /// Wraps a device created by the application on `pdevice`.
+    ///
+    /// # Safety
+    ///
+    /// `desc` must describe the device as it was created, with the features which
+    /// `Device::create` requires, and `bufferDeviceAddress`. The application must lock
+    /// `universal_queue.submit_lock` around its own submissions to the queue, and may only
+    /// destroy the device after the renderer has shut down, and the last reference to this
+    /// one is gone.
+    pub unsafe fn from_external(
+        pdevice: &Arc<PhysicalDevice>,
+        desc: ExternalDeviceDesc,
+    ) -> Result<Arc<Self>> {
+        let enabled = |ext: &CStr| desc.enabled_extensions.iter().any(|e| e.as_c_str() == ext);
+
+        let missing_extensions: Vec<_> = Self::required_extensions()
+            .into_iter()
+            .filter(|&ext| !enabled(ext))
+            .map(|ext| ext.to_string_lossy())
+            .collect();
+
+        if !missing_extensions.is_empty() {
+            anyhow::bail!(
+                "The device lacks required extensions: {}",
+                missing_extensions.join(", ")
+            );
+        }
+
+        let universal_queue = pdevice
+            .queue_families
+            .get(desc.queue_family_index as usize)
+            .copied()
+            .filter(|qf| {
+                qf.properties
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Queue family {} doesn't support graphics and compute",
+                    desc.queue_family_index
+                )
+            })?;
+
+        let ray_tracing = cfg!(feature = "ray-tracing")
+            && Self::ray_tracing_extensions()
+                .iter()
+                .all(|&ext| enabled(ext));
+
+        if !ray_tracing {
+            info!("Ray tracing is unavailable on the external device, or compiled out");
+        }
+
+        let enabled_features = EnabledFeatures {
+            ray_tracing,
+            draw_indirect_count: enabled(khr::DrawIndirectCount::name()),
+            fill_mode_non_solid: desc.enabled_features.fill_mode_non_solid != 0,
+            portability_subset: enabled(vk::KhrPortabilitySubsetFn::name()),
+            shader_debug_printf: false,
+            external_interop: super::external::required_extensions()
+                .iter()
+                .all(|&ext| enabled(ext)),
+            buffer_device_address: true,
+            // The feature may not be enabled along with the extension.
+            dynamic_rendering: false,
+        };
+
+        info!("Using an external Vulkan device");
+
+        Self::from_raw_parts(
+            pdevice,
+            desc.raw,
+            universal_queue,
+            desc.queue_index,
+            enabled_features,
+        )
+    }
+
+    /// Wraps an image owned by the application, e.g. to render into with `draw_frame_to_image`.
+    /// `desc` must match how it was created. Hand it back with `release_raw_image`, rather than
+    /// destroying it along with the renderer's resources.
+    pub fn wrap_raw_image(&self, raw: vk::Image, desc: ImageDesc) -> Image {
+        Image {
+            raw,
+            desc,
+            views: Default::default(),
+            allocation: None,
+            dedicated_memory: None,
+        }
+    }
+
+    /// Destroys the views the renderer created of a wrapped image, leaving the image itself
+    /// to the application. The GPU must be done with them.
+    pub fn release_raw_image(&self, image: Image) -> vk::Image {
+        for view in image.views.into_inner().into_values() {
+            unsafe { self.raw.destroy_image_view(view, None) };
+            self.track_destroyed(ResourceKind::ImageView, view);
+        }
+
+        image.raw
+    }
+}
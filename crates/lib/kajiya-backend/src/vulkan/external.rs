@@ -0,0 +1,368 @@
+//! Sharing images and semaphores with other APIs (e.g. CUDA or Direct3D) and processes,
+//! via `VK_KHR_external_memory_fd` and `VK_KHR_external_semaphore_fd` on Unix,
+//! and their `win32` counterparts on Windows.
+//!
+//! Exported and imported images are regular `Image`s, so they can be brought into
+//! the render graph with `import`, like any other persistent resource.
+
+use super::{device::Device, image::*, resource_tracking::ResourceKind};
+use crate::BackendError;
+#[cfg(unix)]
+use ash::extensions::khr;
+use ash::vk;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::ffi::CStr;
+
+/// An OS handle to shared memory or a semaphore.
+///
+/// Exported handles are owned by the caller; importing one consumes it on success.
+#[derive(Clone, Copy, Debug)]
+pub enum ExternalHandle {
+    #[cfg(unix)]
+    Fd(std::os::unix::io::RawFd),
+    #[cfg(windows)]
+    Win32(vk::HANDLE),
+}
+
+/// The dedicated allocation backing a shared image.
+///
+/// Other APIs need the size along with the handle, e.g. `cudaImportExternalMemory`,
+/// which should also be told that the allocation is dedicated.
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalMemory {
+    pub handle: ExternalHandle,
+    pub size: u64,
+}
+
+#[cfg(unix)]
+const MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+
+#[cfg(unix)]
+const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32;
+
+/// Device extensions needed for interop on this platform.
+#[cfg(unix)]
+pub(crate) fn required_extensions() -> [&'static CStr; 2] {
+    [
+        vk::KhrExternalMemoryFdFn::name(),
+        vk::KhrExternalSemaphoreFdFn::name(),
+    ]
+}
+
+/// Device extensions needed for interop on this platform.
+#[cfg(windows)]
+pub(crate) fn required_extensions() -> [&'static CStr; 2] {
+    [
+        vk::KhrExternalMemoryWin32Fn::name(),
+        vk::KhrExternalSemaphoreWin32Fn::name(),
+    ]
+}
+
+pub(crate) struct ExternalInteropFns {
+    #[cfg(unix)]
+    memory: khr::ExternalMemoryFd,
+    #[cfg(unix)]
+    semaphore: khr::ExternalSemaphoreFd,
+
+    #[cfg(windows)]
+    memory: vk::KhrExternalMemoryWin32Fn,
+    #[cfg(windows)]
+    semaphore: vk::KhrExternalSemaphoreWin32Fn,
+}
+
+impl ExternalInteropFns {
+    #[cfg(unix)]
+    pub(crate) fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        Self {
+            memory: khr::ExternalMemoryFd::new(instance, device),
+            semaphore: khr::ExternalSemaphoreFd::new(instance, device),
+        }
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        let load = |name: &CStr| unsafe {
+            std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+        };
+
+        Self {
+            memory: vk::KhrExternalMemoryWin32Fn::load(load),
+            semaphore: vk::KhrExternalSemaphoreWin32Fn::load(load),
+        }
+    }
+}
+
+impl Device {
+    fn external_interop_fns(&self) -> Result<&ExternalInteropFns, BackendError> {
+        self.external_interop_fns
+            .as_ref()
+            .ok_or(BackendError::ExternalInteropUnsupported)
+    }
+
+    /// Creates an image in its own allocation, which can be shared with other APIs via the returned handle.
+    pub fn create_exportable_image(
+        &self,
+        desc: ImageDesc,
+    ) -> Result<(Image, ExternalMemory), BackendError> {
+        let fns = self.external_interop_fns()?;
+        let (image, memory, size) = self.create_external_image(desc, |allocate_info| unsafe {
+            let mut export_info =
+                vk::ExportMemoryAllocateInfo::builder().handle_types(MEMORY_HANDLE_TYPE);
+
+            self.raw
+                .allocate_memory(&allocate_info.push_next(&mut export_info), None)
+        })?;
+
+        #[cfg(unix)]
+        let handle = unsafe {
+            fns.memory.get_memory_fd(
+                &vk::MemoryGetFdInfoKHR::builder()
+                    .memory(memory)
+                    .handle_type(MEMORY_HANDLE_TYPE),
+            )
+        }
+        .map(ExternalHandle::Fd);
+
+        #[cfg(windows)]
+        let handle = unsafe {
+            let mut handle = std::ptr::null_mut();
+            match fns.memory.get_memory_win32_handle_khr(
+                self.raw.handle(),
+                &vk::MemoryGetWin32HandleInfoKHR::builder()
+                    .memory(memory)
+                    .handle_type(MEMORY_HANDLE_TYPE)
+                    .build(),
+                &mut handle,
+            ) {
+                vk::Result::SUCCESS => Ok(ExternalHandle::Win32(handle)),
+                err => Err(err),
+            }
+        };
+
+        match handle {
+            Ok(handle) => Ok((image, ExternalMemory { handle, size })),
+            Err(err) => {
+                unsafe {
+                    self.raw.destroy_image(image.raw, None);
+                    self.raw.free_memory(memory, None);
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Creates an image backed by memory exported from another API or process.
+    /// The `desc` must match the one the memory was allocated for.
+    pub fn import_image(
+        &self,
+        desc: ImageDesc,
+        memory: ExternalMemory,
+    ) -> Result<Image, BackendError> {
+        self.external_interop_fns()?;
+
+        let (image, _, _) = self.create_external_image(desc, |allocate_info| unsafe {
+            #[cfg(unix)]
+            let ExternalHandle::Fd(fd) = memory.handle;
+            #[cfg(unix)]
+            let mut import_info = vk::ImportMemoryFdInfoKHR::builder()
+                .handle_type(MEMORY_HANDLE_TYPE)
+                .fd(fd);
+
+            #[cfg(windows)]
+            let ExternalHandle::Win32(handle) = memory.handle;
+            #[cfg(windows)]
+            let mut import_info = vk::ImportMemoryWin32HandleInfoKHR::builder()
+                .handle_type(MEMORY_HANDLE_TYPE)
+                .handle(handle);
+
+            self.raw.allocate_memory(
+                &allocate_info
+                    .allocation_size(memory.size)
+                    .push_next(&mut import_info),
+                None,
+            )
+        })?;
+
+        Ok(image)
+    }
+
+    /// Shared images must be bound to dedicated allocations; the import or export
+    /// info is added to the allocation by `allocate`.
+    fn create_external_image(
+        &self,
+        desc: ImageDesc,
+        allocate: impl FnOnce(
+            vk::MemoryAllocateInfoBuilder<'_>,
+        ) -> ash::prelude::VkResult<vk::DeviceMemory>,
+    ) -> Result<(Image, vk::DeviceMemory, u64), BackendError> {
+        let external_info =
+            vk::ExternalMemoryImageCreateInfo::builder().handle_types(MEMORY_HANDLE_TYPE);
+
+        let mut create_info = get_image_create_info(&desc, false);
+        create_info.p_next = &*external_info as *const _ as *const std::ffi::c_void;
+
+        let image = unsafe { self.raw.create_image(&create_info, None)? };
+        let requirements = unsafe { self.raw.get_image_memory_requirements(image) };
+
+        let memory_properties = &self.pdevice.memory_properties;
+        let memory_type_index = (0..memory_properties.memory_type_count).find(|&i| {
+            requirements.memory_type_bits & (1 << i) != 0
+                && memory_properties.memory_types[i as usize]
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+        });
+
+        let memory_type_index = if let Some(memory_type_index) = memory_type_index {
+            memory_type_index
+        } else {
+            unsafe { self.raw.destroy_image(image, None) };
+            return Err(BackendError::ResourceAccess {
+                info: "No device-local memory type for an external image".into(),
+            });
+        };
+
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder().image(image);
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut dedicated_info);
+
+        let memory = match allocate(allocate_info) {
+            Ok(memory) => memory,
+            Err(err) => {
+                unsafe { self.raw.destroy_image(image, None) };
+                return Err(err.into());
+            }
+        };
+
+        if let Err(err) = unsafe { self.raw.bind_image_memory(image, memory, 0) } {
+            unsafe {
+                self.raw.destroy_image(image, None);
+                self.raw.free_memory(memory, None);
+            }
+            return Err(err.into());
+        }
+
+        self.track_created(ResourceKind::Image, image, || {
+            format!("shared {:?} {:?}", desc.extent, desc.format)
+        });
+        self.stats.image_created(requirements.size);
+
+        Ok((
+            Image {
+                raw: image,
+                desc,
+                views: Default::default(),
+                allocation: None,
+                dedicated_memory: Some(memory),
+            },
+            memory,
+            requirements.size,
+        ))
+    }
+
+    /// Creates a binary semaphore which can be shared with other APIs via the returned handle,
+    /// e.g. to have them wait until a frame has been rendered to an exported image.
+    pub fn create_exportable_semaphore(
+        &self,
+    ) -> Result<(vk::Semaphore, ExternalHandle), BackendError> {
+        let fns = self.external_interop_fns()?;
+
+        let mut export_info =
+            vk::ExportSemaphoreCreateInfo::builder().handle_types(SEMAPHORE_HANDLE_TYPE);
+        let semaphore = unsafe {
+            self.raw.create_semaphore(
+                &vk::SemaphoreCreateInfo::builder().push_next(&mut export_info),
+                None,
+            )?
+        };
+
+        #[cfg(unix)]
+        let handle = unsafe {
+            fns.semaphore.get_semaphore_fd(
+                &vk::SemaphoreGetFdInfoKHR::builder()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE),
+            )
+        }
+        .map(ExternalHandle::Fd);
+
+        #[cfg(windows)]
+        let handle = unsafe {
+            let mut handle = std::ptr::null_mut();
+            match fns.semaphore.get_semaphore_win32_handle_khr(
+                self.raw.handle(),
+                &vk::SemaphoreGetWin32HandleInfoKHR::builder()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE)
+                    .build(),
+                &mut handle,
+            ) {
+                vk::Result::SUCCESS => Ok(ExternalHandle::Win32(handle)),
+                err => Err(err),
+            }
+        };
+
+        match handle {
+            Ok(handle) => Ok((semaphore, handle)),
+            Err(err) => {
+                unsafe { self.raw.destroy_semaphore(semaphore, None) };
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Creates a binary semaphore from one exported by another API or process.
+    pub fn import_semaphore(&self, handle: ExternalHandle) -> Result<vk::Semaphore, BackendError> {
+        let fns = self.external_interop_fns()?;
+
+        let semaphore = unsafe {
+            self.raw
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?
+        };
+
+        #[cfg(unix)]
+        let result = unsafe {
+            let ExternalHandle::Fd(fd) = handle;
+            fns.semaphore.import_semaphore_fd(
+                &vk::ImportSemaphoreFdInfoKHR::builder()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE)
+                    .fd(fd),
+            )
+        };
+
+        #[cfg(windows)]
+        let result = unsafe {
+            let ExternalHandle::Win32(handle) = handle;
+            match fns.semaphore.import_semaphore_win32_handle_khr(
+                self.raw.handle(),
+                &vk::ImportSemaphoreWin32HandleInfoKHR::builder()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE)
+                    .handle(handle)
+                    .build(),
+            ) {
+                vk::Result::SUCCESS => Ok(()),
+                err => Err(err),
+            }
+        };
+
+        match result {
+            Ok(()) => Ok(semaphore),
+            Err(err) => {
+                unsafe { self.raw.destroy_semaphore(semaphore, None) };
+                Err(err.into())
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::BackendError;
 
-use super::device::Device;
+use super::{device::Device, resource_tracking::ResourceKind};
 use ash::vk;
 use derive_builder::Builder;
 use gpu_allocator::{AllocationCreateDesc, MemoryLocation};
@@ -165,7 +165,10 @@ pub struct Image {
     pub raw: vk::Image,
     pub desc: ImageDesc,
     pub views: Mutex<HashMap<ImageViewDesc, vk::ImageView>>,
-    //allocation: gpu_allocator::SubAllocation,
+    // `None` for images not owned by the allocator, e.g. swapchain images
+    pub(crate) allocation: Option<gpu_allocator::SubAllocation>,
+    // Memory of images shared with other APIs, which can't be sub-allocated
+    pub(crate) dedicated_memory: Option<vk::DeviceMemory>,
 }
 unsafe impl Send for Image {}
 unsafe impl Sync for Image {}
@@ -254,6 +257,14 @@ impl Device {
     ) -> Result<Image, BackendError> {
         log::info!("Creating an image: {:?}", desc);
 
+        if !self.image_format_supports_usage(desc.format, desc.tiling, desc.usage) {
+            return Err(BackendError::UnsupportedFormat {
+                format: desc.format,
+                usage: desc.usage,
+                tiling: desc.tiling,
+            });
+        }
+
         let create_info = get_image_create_info(&desc, !initial_data.is_empty());
 
         /*let allocation_info = vk_mem::AllocationCreateInfo {
@@ -265,14 +276,10 @@ impl Device {
             .global_allocator
             .create_image(&create_info, &allocation_info)?;*/
 
-        let image = unsafe {
-            self.raw
-                .create_image(&create_info, None)
-                .expect("create_image")
-        };
+        let image = unsafe { self.raw.create_image(&create_info, None)? };
         let requirements = unsafe { self.raw.get_image_memory_requirements(image) };
 
-        let allocation = self
+        let allocation = match self
             .global_allocator
             .lock()
             .allocate(&AllocationCreateDesc {
@@ -280,18 +287,28 @@ impl Device {
                 requirements,
                 location: MemoryLocation::GpuOnly,
                 linear: false,
-            })
-            .map_err(|err| BackendError::Allocation {
-                inner: err,
-                name: "GpuOnly image".into(),
-            })?;
+            }) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { self.raw.destroy_image(image, None) };
+                return Err(BackendError::Allocation {
+                    inner: err,
+                    name: "GpuOnly image".into(),
+                });
+            }
+        };
 
         // Bind memory to the image
-        unsafe {
+        if let Err(err) = unsafe {
             self.raw
                 .bind_image_memory(image, allocation.memory(), allocation.offset())
-                .expect("bind_image_memory")
-        };
+        } {
+            unsafe { self.raw.destroy_image(image, None) };
+            if let Err(free_err) = self.global_allocator.lock().free(allocation) {
+                log::error!("Failed to free the memory of an image: {:?}", free_err);
+            }
+            return Err(err.into());
+        }
 
         if !initial_data.is_empty() {
             let total_initial_data_bytes = initial_data.iter().map(|d| d.data.len()).sum();
@@ -299,19 +316,37 @@ impl Device {
             let block_bytes: usize = match desc.format {
                 vk::Format::R8G8B8A8_UNORM => 1,
                 vk::Format::R8G8B8A8_SRGB => 1,
+                vk::Format::B8G8R8A8_UNORM => 1,
+                vk::Format::B8G8R8A8_SRGB => 1,
                 vk::Format::R32G32B32A32_SFLOAT => 1,
                 vk::Format::R16G16B16A16_SFLOAT => 1,
                 vk::Format::BC1_RGB_UNORM_BLOCK => 8,
                 vk::Format::BC1_RGB_SRGB_BLOCK => 8,
+                vk::Format::BC1_RGBA_UNORM_BLOCK => 8,
+                vk::Format::BC1_RGBA_SRGB_BLOCK => 8,
+                vk::Format::BC2_UNORM_BLOCK => 16,
+                vk::Format::BC2_SRGB_BLOCK => 16,
                 vk::Format::BC3_UNORM_BLOCK => 16,
                 vk::Format::BC3_SRGB_BLOCK => 16,
+                vk::Format::BC4_UNORM_BLOCK => 8,
+                vk::Format::BC4_SNORM_BLOCK => 8,
                 vk::Format::BC5_UNORM_BLOCK => 16,
                 vk::Format::BC5_SNORM_BLOCK => 16,
+                vk::Format::BC6H_UFLOAT_BLOCK => 16,
+                vk::Format::BC6H_SFLOAT_BLOCK => 16,
                 vk::Format::BC7_UNORM_BLOCK => 16,
                 vk::Format::BC7_SRGB_BLOCK => 16,
                 _ => todo!("{:?}", desc.format),
             };
 
+            // Each sub-resource contains the data for all the layers of one mip level
+            let layer_count = match desc.image_type {
+                ImageType::Tex1dArray | ImageType::Tex2dArray => desc.array_elements,
+                ImageType::Cube => 6,
+                ImageType::CubeArray => 6 * desc.array_elements,
+                _ => 1,
+            };
+
             let mut image_buffer = self.create_buffer(
                 super::buffer::BufferDesc::new_cpu_to_gpu(
                     total_initial_data_bytes,
@@ -336,7 +371,7 @@ impl Device {
                         .image_subresource(
                             vk::ImageSubresourceLayers::builder()
                                 .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                .layer_count(1)
+                                .layer_count(layer_count)
                                 .mip_level(level as _)
                                 .build(),
                         )
@@ -402,11 +437,17 @@ impl Device {
         });
 
         ImageHandle(handle)*/
+        self.track_created(ResourceKind::Image, image, || {
+            format!("{:?} {:?} {:?}", desc.image_type, desc.extent, desc.format)
+        });
+        self.stats.image_created(requirements.size);
+
         Ok(Image {
             raw: image,
-            //allocation,
             desc,
             views: Default::default(),
+            allocation: Some(allocation),
+            dedicated_memory: None,
         })
     }
 
@@ -430,7 +471,45 @@ impl Device {
             ..Image::view_desc_impl(desc, image_desc)
         };
 
-        Ok(unsafe { self.raw.create_image_view(&create_info, None)? })
+        let view = unsafe { self.raw.create_image_view(&create_info, None)? };
+        self.track_created(ResourceKind::ImageView, view, || {
+            format!(
+                "{:?} view of {:?} {:?}",
+                create_info.view_type, image_desc.extent, image_desc.format
+            )
+        });
+
+        Ok(view)
+    }
+
+    /// Destroys the image along with its views. The GPU must be done with it;
+    /// see `defer_release` otherwise.
+    pub fn immediate_destroy_image(&self, image: Image) {
+        if image.allocation.is_some() || image.dedicated_memory.is_some() {
+            self.stats
+                .image_destroyed(unsafe { self.raw.get_image_memory_requirements(image.raw) }.size);
+        }
+
+        unsafe {
+            for view in image.views.into_inner().into_values() {
+                self.raw.destroy_image_view(view, None);
+                self.track_destroyed(ResourceKind::ImageView, view);
+            }
+
+            self.raw.destroy_image(image.raw, None);
+            self.track_destroyed(ResourceKind::Image, image.raw);
+
+            if let Some(allocation) = image.allocation {
+                self.global_allocator
+                    .lock()
+                    .free(allocation)
+                    .expect("image memory deallocated");
+            }
+
+            if let Some(memory) = image.dedicated_memory {
+                self.raw.free_memory(memory, None);
+            }
+        }
     }
 
     /*pub fn get(&self, handle: ImageHandle) -> &Image {
@@ -8,6 +8,11 @@ use std::{
     sync::Arc,
 };
 
+// Not in this version of `ash` yet.
+const PORTABILITY_ENUMERATION_EXT_NAME: &[u8] = b"VK_KHR_portability_enumeration\0";
+const INSTANCE_CREATE_ENUMERATE_PORTABILITY: vk::InstanceCreateFlags =
+    vk::InstanceCreateFlags::from_raw(0x1);
+
 #[derive(Default)]
 pub struct DeviceBuilder {
     pub required_extensions: Vec<&'static CStr>,
@@ -39,6 +44,12 @@ pub struct Instance {
     #[allow(deprecated)]
     pub(crate) debug_loader: Option<ext::DebugReport>,
     pub(crate) debug_utils: Option<ash::extensions::ext::DebugUtils>,
+    #[allow(dead_code)]
+    pub(crate) debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
+
+    /// Validation layers print the messages of `printf` in shaders; debug builds
+    /// with graphics debugging only.
+    pub(crate) shader_debug_printf: bool,
 }
 
 impl Instance {
@@ -68,13 +79,37 @@ impl Instance {
 
     fn create(builder: DeviceBuilder) -> Result<Self> {
         let entry = unsafe { ash::Entry::new()? };
-        let instance_extensions = builder
+        let mut instance_extensions = builder
             .required_extensions
             .iter()
             .map(|ext| ext.as_ptr())
             .chain(Self::extension_names(&builder).into_iter())
             .collect::<Vec<_>>();
 
+        let supported_extensions = entry.enumerate_instance_extension_properties()?;
+        let extension_supported = |name: &[u8]| {
+            supported_extensions.iter().any(|ext| unsafe {
+                CStr::from_ptr(ext.extension_name.as_ptr()).to_bytes_with_nul() == name
+            })
+        };
+
+        // Portability implementations such as MoltenVK are only enumerated
+        // if the instance opts into them.
+        let portability_enumeration_supported =
+            extension_supported(PORTABILITY_ENUMERATION_EXT_NAME);
+
+        let mut instance_flags = vk::InstanceCreateFlags::empty();
+        if portability_enumeration_supported {
+            instance_extensions.push(PORTABILITY_ENUMERATION_EXT_NAME.as_ptr() as *const i8);
+            info!("Enumerating portability (e.g. MoltenVK) devices");
+            instance_flags |= INSTANCE_CREATE_ENUMERATE_PORTABILITY;
+        }
+
+        // Exposes the HDR surface color spaces.
+        if extension_supported(vk::ExtSwapchainColorspaceFn::name().to_bytes_with_nul()) {
+            instance_extensions.push(vk::ExtSwapchainColorspaceFn::name().as_ptr());
+        }
+
         let layer_names = Self::layer_names(&builder);
         let layer_names: Vec<*const i8> = layer_names
             .iter()
@@ -83,11 +118,21 @@ impl Instance {
 
         let app_desc = vk::ApplicationInfo::builder().api_version(vk::make_api_version(0, 1, 2, 0));
 
-        let instance_desc = vk::InstanceCreateInfo::builder()
+        let shader_debug_printf = builder.graphics_debugging && cfg!(debug_assertions);
+
+        let mut validation_features = vk::ValidationFeaturesEXT::builder()
+            .enabled_validation_features(&[vk::ValidationFeatureEnableEXT::DEBUG_PRINTF]);
+
+        let mut instance_desc = vk::InstanceCreateInfo::builder()
+            .flags(instance_flags)
             .application_info(&app_desc)
             .enabled_layer_names(&layer_names)
             .enabled_extension_names(&instance_extensions);
 
+        if shader_debug_printf {
+            instance_desc = instance_desc.push_next(&mut validation_features);
+        }
+
         let instance = unsafe { entry.create_instance(&instance_desc, None)? };
         info!("Created a Vulkan instance");
 
@@ -117,16 +162,77 @@ impl Instance {
             (None, None, None)
         };
 
+        // Debug printf messages are informational, which the debug report callback skips.
+        let debug_messenger = match &debug_utils {
+            Some(debug_utils) if shader_debug_printf => {
+                let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+                    .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::INFO)
+                    .message_type(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION)
+                    .pfn_user_callback(Some(shader_debug_printf_callback));
+
+                unsafe { debug_utils.create_debug_utils_messenger(&messenger_info, None) }
+                    .map_err(|err| warn!("Shader debug printf is unavailable: {:?}", err))
+                    .ok()
+            }
+            _ => None,
+        };
+
         Ok(Self {
             entry,
             raw: instance,
             debug_callback,
             debug_loader,
             debug_utils,
+            debug_messenger,
+            shader_debug_printf: debug_messenger.is_some(),
         })
     }
 }
 
+/// Logs the output of `printf` in shaders, attributed to the innermost command buffer label,
+/// which is the render graph pass.
+unsafe extern "system" fn shader_debug_printf_callback(
+    _severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    _message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _user_data: *mut c_void,
+) -> vk::Bool32 {
+    let callback_data = &*callback_data;
+
+    let is_printf = !callback_data.p_message_id_name.is_null()
+        && CStr::from_ptr(callback_data.p_message_id_name)
+            .to_string_lossy()
+            .contains("DEBUG-PRINTF");
+
+    if !is_printf || callback_data.p_message.is_null() {
+        return vk::FALSE;
+    }
+
+    let message = CStr::from_ptr(callback_data.p_message).to_string_lossy();
+
+    // The layers prefix the shader's message with the object and message ID.
+    let message = message.rsplit(" | ").next().unwrap_or(&message).trim_end();
+
+    let pass = if callback_data.cmd_buf_label_count > 0 && !callback_data.p_cmd_buf_labels.is_null()
+    {
+        let label = &*callback_data
+            .p_cmd_buf_labels
+            .add(callback_data.cmd_buf_label_count as usize - 1);
+
+        (!label.p_label_name.is_null())
+            .then(|| CStr::from_ptr(label.p_label_name).to_string_lossy())
+    } else {
+        None
+    };
+
+    match pass {
+        Some(pass) => info!(target: "shader", "[{}] {}", pass, message),
+        None => info!(target: "shader", "{}", message),
+    }
+
+    vk::FALSE
+}
+
 unsafe extern "system" fn vulkan_debug_callback(
     _flags: vk::DebugReportFlagsEXT,
     _obj_type: vk::DebugReportObjectTypeEXT,
@@ -1,15 +1,22 @@
 pub mod barrier;
 pub mod buffer;
 pub mod device;
+pub mod device_stats;
+pub mod dynamic_rendering;
+pub mod embedding;
 pub mod error;
+pub mod external;
 pub mod image;
 pub mod instance;
 pub mod physical_device;
 pub mod profiler;
 pub mod ray_tracing;
+pub mod resource_tracking;
 pub mod shader;
+pub mod staging_belt;
 pub mod surface;
 pub mod swapchain;
+pub mod vertex_input;
 
 use ash::vk;
 #[allow(unused_imports)]
@@ -17,14 +24,30 @@ use log::{debug, error, info, trace, warn};
 use raw_window_handle::HasRawWindowHandle;
 use std::sync::Arc;
 
-fn select_surface_format(formats: Vec<vk::SurfaceFormatKHR>) -> Option<vk::SurfaceFormatKHR> {
-    let preferred = vk::SurfaceFormatKHR {
+fn select_surface_format(
+    formats: Vec<vk::SurfaceFormatKHR>,
+    hdr_output: bool,
+) -> Option<vk::SurfaceFormatKHR> {
+    let hdr10 = vk::SurfaceFormatKHR {
+        format: vk::Format::A2B10G10R10_UNORM_PACK32,
+        color_space: vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    };
+
+    let sdr = vk::SurfaceFormatKHR {
         format: vk::Format::B8G8R8A8_UNORM,
         color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
     };
 
-    if formats.contains(&preferred) {
-        Some(preferred)
+    if hdr_output {
+        if formats.contains(&hdr10) {
+            return Some(hdr10);
+        }
+
+        warn!("HDR10 output is not supported by the surface; falling back to SDR");
+    }
+
+    if formats.contains(&sdr) {
+        Some(sdr)
     } else {
         None
     }
@@ -42,6 +65,9 @@ pub struct RenderBackendConfig {
     pub vsync: bool,
     pub graphics_debugging: bool,
     pub device_index: Option<usize>,
+
+    /// Presents in HDR10 (PQ-encoded Rec.2020) when the surface supports it.
+    pub hdr_output: bool,
 }
 
 impl RenderBackend {
@@ -59,37 +85,10 @@ impl RenderBackend {
         let physical_devices =
             enumerate_physical_devices(&instance)?.with_presentation_support(&surface);
 
-        info!(
-            "Available physical devices: {:#?}",
-            physical_devices
-                .iter()
-                .map(|dev| unsafe {
-                    ::std::ffi::CStr::from_ptr(
-                        dev.properties.device_name.as_ptr() as *const std::os::raw::c_char
-                    )
-                })
-                .collect::<Vec<_>>()
-        );
-
-        let physical_device = Arc::new(if let Some(device_index) = config.device_index {
-            physical_devices.into_iter().nth(device_index).unwrap()
-        } else {
-            physical_devices
-                .into_iter()
-                // If there are multiple devices with the same score, `max_by_key` would choose the last,
-                // and we want to preserve the order of devices from `enumerate_physical_devices`.
-                .rev()
-                .max_by_key(|device| match device.properties.device_type {
-                    vk::PhysicalDeviceType::INTEGRATED_GPU => 200,
-                    vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
-                    vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
-                    _ => 0,
-                })
-                .unwrap()
-        });
-
-        info!("Selected physical device: {:#?}", *physical_device);
-
+        let physical_device = Arc::new(select_physical_device(
+            physical_devices,
+            config.device_index,
+        )?);
         let device = device::Device::create(&physical_device)?;
         let surface_formats = swapchain::Swapchain::enumerate_surface_formats(&device, &surface)?;
 
@@ -99,7 +98,8 @@ impl RenderBackend {
             &device,
             &surface,
             swapchain::SwapchainDesc {
-                format: select_surface_format(surface_formats).expect("suitable surface format"),
+                format: select_surface_format(surface_formats, config.hdr_output)
+                    .expect("suitable surface format"),
                 dims: vk::Extent2D {
                     width: config.swapchain_extent[0],
                     height: config.swapchain_extent[1],
@@ -119,3 +119,65 @@ impl RenderBackend {
         self.images.maintain();
     }*/
 }
+
+#[derive(Clone, Copy, Default)]
+pub struct HeadlessDeviceConfig {
+    pub graphics_debugging: bool,
+    pub device_index: Option<usize>,
+}
+
+/// A device without a window, surface, or swapchain, for tools which only run compute work.
+/// Frames still go through `Device::begin_frame` and `Device::finish_frame`, but nothing is presented.
+pub fn create_headless_device(config: HeadlessDeviceConfig) -> anyhow::Result<Arc<device::Device>> {
+    let instance = instance::Instance::builder()
+        .graphics_debugging(config.graphics_debugging)
+        .build()?;
+
+    use physical_device::*;
+    let physical_devices = enumerate_physical_devices(&instance)?.without_presentation();
+
+    let physical_device = Arc::new(select_physical_device(
+        physical_devices,
+        config.device_index,
+    )?);
+
+    device::Device::create(&physical_device)
+}
+
+fn select_physical_device(
+    physical_devices: Vec<physical_device::PhysicalDevice>,
+    device_index: Option<usize>,
+) -> anyhow::Result<physical_device::PhysicalDevice> {
+    info!(
+        "Available physical devices: {:#?}",
+        physical_devices
+            .iter()
+            .map(|dev| unsafe {
+                ::std::ffi::CStr::from_ptr(
+                    dev.properties.device_name.as_ptr() as *const std::os::raw::c_char
+                )
+            })
+            .collect::<Vec<_>>()
+    );
+
+    let physical_device = if let Some(device_index) = device_index {
+        physical_devices.into_iter().nth(device_index)
+    } else {
+        physical_devices
+            .into_iter()
+            // If there are multiple devices with the same score, `max_by_key` would choose the last,
+            // and we want to preserve the order of devices from `enumerate_physical_devices`.
+            .rev()
+            .max_by_key(|device| match device.properties.device_type {
+                vk::PhysicalDeviceType::INTEGRATED_GPU => 200,
+                vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
+                vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+                _ => 0,
+            })
+    }
+    .ok_or_else(|| anyhow::anyhow!("No suitable physical device found"))?;
+
+    info!("Selected physical device: {:#?}", physical_device);
+
+    Ok(physical_device)
+}
@@ -40,60 +40,77 @@ impl std::fmt::Debug for PhysicalDevice {
     }
 }
 
-pub fn enumerate_physical_devices(instance: &Arc<Instance>) -> Result<Vec<PhysicalDevice>> {
-    unsafe {
-        let pdevices = instance.raw.enumerate_physical_devices()?;
-
-        Ok(pdevices
-            .into_iter()
-            .map(|pdevice| {
-                let properties = instance.raw.get_physical_device_properties(pdevice);
-                /*let properties = PhysicalDeviceProperties {
-                    api_version: properties.api_version,
-                    driver_version: properties.driver_version,
-                    vendor_id: properties.vendor_id,
-                    device_id: properties.device_id,
-                    device_type: properties.device_type,
-                    device_name: CStr::from_ptr(&properties.device_name[0])
-                        .to_str()
-                        .unwrap()
-                        .to_string(),
-                    pipeline_cache_uuid: properties.pipeline_cache_uuid,
-                    limits: properties.limits,
-                    sparse_properties: properties.sparse_properties,
-                };*/
-
-                let queue_families = instance
-                    .raw
-                    .get_physical_device_queue_family_properties(pdevice)
-                    .into_iter()
-                    .enumerate()
-                    .map(|(index, properties)| QueueFamily {
-                        index: index as _,
-                        properties,
-                    })
-                    .collect();
-
-                let memory_properties = instance.raw.get_physical_device_memory_properties(pdevice);
-
-                PhysicalDevice {
-                    raw: pdevice,
-                    queue_families,
-                    presentation_requested: true,
-                    instance: instance.clone(),
+impl PhysicalDevice {
+    /// Queries the properties of `raw`, e.g. one chosen by an application embedding the renderer.
+    pub fn from_raw(instance: &Arc<Instance>, raw: vk::PhysicalDevice) -> Self {
+        unsafe {
+            let properties = instance.raw.get_physical_device_properties(raw);
+            /*let properties = PhysicalDeviceProperties {
+                api_version: properties.api_version,
+                driver_version: properties.driver_version,
+                vendor_id: properties.vendor_id,
+                device_id: properties.device_id,
+                device_type: properties.device_type,
+                device_name: CStr::from_ptr(&properties.device_name[0])
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+                pipeline_cache_uuid: properties.pipeline_cache_uuid,
+                limits: properties.limits,
+                sparse_properties: properties.sparse_properties,
+            };*/
+
+            let queue_families = instance
+                .raw
+                .get_physical_device_queue_family_properties(raw)
+                .into_iter()
+                .enumerate()
+                .map(|(index, properties)| QueueFamily {
+                    index: index as _,
                     properties,
-                    memory_properties,
-                }
-            })
-            .collect())
+                })
+                .collect();
+
+            let memory_properties = instance.raw.get_physical_device_memory_properties(raw);
+
+            PhysicalDevice {
+                raw,
+                queue_families,
+                presentation_requested: true,
+                instance: instance.clone(),
+                properties,
+                memory_properties,
+            }
+        }
     }
 }
 
+pub fn enumerate_physical_devices(instance: &Arc<Instance>) -> Result<Vec<PhysicalDevice>> {
+    let pdevices = unsafe { instance.raw.enumerate_physical_devices()? };
+
+    Ok(pdevices
+        .into_iter()
+        .map(|pdevice| PhysicalDevice::from_raw(instance, pdevice))
+        .collect())
+}
+
 pub trait PhysicalDeviceList {
     fn with_presentation_support(self, surface: &Surface) -> Self;
+
+    /// For devices which will never present; the swapchain extension won't be required.
+    fn without_presentation(self) -> Self;
 }
 
 impl PhysicalDeviceList for Vec<PhysicalDevice> {
+    fn without_presentation(self) -> Self {
+        self.into_iter()
+            .map(|mut pdevice| {
+                pdevice.presentation_requested = false;
+                pdevice
+            })
+            .collect()
+    }
+
     fn with_presentation_support(self, surface: &Surface) -> Self {
         self.into_iter()
             .filter_map(|mut pdevice| {
@@ -11,6 +11,10 @@ pub struct VkProfilerData {
     gpu_profiler_query_ids: Vec<std::cell::Cell<GpuProfilerQueryId>>,
 }
 
+// `get_query_id` hands out each slot exactly once via the atomic counter, so no two threads
+// ever touch the same cell.
+unsafe impl Sync for VkProfilerData {}
+
 /*impl Drop for VkProfilerData {
     fn drop(&mut self) {
         unsafe {
@@ -4,9 +4,10 @@ use crate::{dynamic_constants::DynamicConstants, BackendError, MAX_DESCRIPTOR_SE
 
 use super::{
     device::Device,
+    resource_tracking::ResourceKind,
     shader::{
         merge_shader_stage_layouts, DescriptorSetLayoutOpts, PipelineShader, ShaderPipelineCommon,
-        ShaderPipelineStage,
+        ShaderPipelineStage, SpecializationData,
     },
 };
 use ash::vk;
@@ -35,14 +36,23 @@ pub struct RayTracingGeometryDesc {
     pub index_buffer: vk::DeviceAddress,
     pub vertex_format: vk::Format,
     pub vertex_stride: usize,
+    /// A `VkTransformMatrixKHR` applied to the vertices, aligned to 16 bytes.
+    pub transform_buffer: Option<vk::DeviceAddress>,
     pub parts: Vec<RayTracingGeometryPart>,
+    /// Non-opaque geometry invokes any hit shaders, e.g. for alpha testing.
+    pub opaque: bool,
 }
 
 #[derive(Clone)]
 pub struct RayTracingInstanceDesc {
-    pub blas: Arc<RayTracingAcceleration>,
+    /// `None` for an inactive instance, which rays never hit.
+    pub blas: Option<Arc<RayTracingAcceleration>>,
     pub transformation: Affine3A,
     pub mesh_index: u32,
+    /// Disables back-face culling of the instance by rays which request it.
+    pub double_sided: bool,
+    /// Rays only see the instance if this shares bits with their `TraceRay` instance mask.
+    pub mask: u8,
 }
 
 #[derive(Clone)]
@@ -54,6 +64,8 @@ pub struct RayTracingTopAccelerationDesc {
 #[derive(Clone, Debug)]
 pub struct RayTracingBottomAccelerationDesc {
     pub geometries: Vec<RayTracingGeometryDesc>,
+    /// Allows `Device::refit_ray_tracing_bottom_acceleration`, at a small cost in trace performance.
+    pub allow_update: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -68,29 +80,154 @@ pub struct RayTracingAcceleration {
     backing_buffer: super::buffer::Buffer,
 }
 
+impl RayTracingAcceleration {
+    pub fn backing_buffer_size(&self) -> usize {
+        self.backing_buffer.desc.size
+    }
+}
+
 #[derive(Clone)]
 pub struct RayTracingAccelerationScratchBuffer {
     buffer: Arc<Mutex<super::buffer::Buffer>>,
 }
 
+impl RayTracingAccelerationScratchBuffer {
+    pub fn size(&self) -> usize {
+        self.buffer.lock().desc.size
+    }
+}
+
 const RT_TLAS_SCRATCH_BUFFER_SIZE: usize = 256 * 1024;
 
+// Allowing updates lets the TLAS be refit when only instance transforms change.
+const TLAS_BUILD_FLAGS: vk::BuildAccelerationStructureFlagsKHR =
+    vk::BuildAccelerationStructureFlagsKHR::from_raw(
+        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE.as_raw()
+            | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE.as_raw(),
+    );
+
+fn blas_build_flags(
+    desc: &RayTracingBottomAccelerationDesc,
+) -> vk::BuildAccelerationStructureFlagsKHR {
+    if desc.allow_update {
+        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+            | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE
+    } else {
+        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+    }
+}
+
+// The geometries of a BLAS, their build ranges, and their primitive counts.
+fn bottom_acceleration_geometries(
+    desc: &RayTracingBottomAccelerationDesc,
+) -> (
+    Vec<vk::AccelerationStructureGeometryKHR>,
+    Vec<vk::AccelerationStructureBuildRangeInfoKHR>,
+    Vec<u32>,
+) {
+    let geometries = desc
+        .geometries
+        .iter()
+        .map(|desc| {
+            let part: RayTracingGeometryPart = desc.parts[0];
+
+            ash::vk::AccelerationStructureGeometryKHR::builder()
+                .geometry_type(ash::vk::GeometryTypeKHR::TRIANGLES)
+                .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
+                    triangles: ash::vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
+                        .vertex_data(ash::vk::DeviceOrHostAddressConstKHR {
+                            device_address: desc.vertex_buffer,
+                        })
+                        .vertex_stride(desc.vertex_stride as _)
+                        .max_vertex(part.max_vertex)
+                        .vertex_format(desc.vertex_format)
+                        .index_data(ash::vk::DeviceOrHostAddressConstKHR {
+                            device_address: desc.index_buffer,
+                        })
+                        .index_type(ash::vk::IndexType::UINT32) // TODO
+                        .transform_data(ash::vk::DeviceOrHostAddressConstKHR {
+                            device_address: desc.transform_buffer.unwrap_or(0),
+                        })
+                        .build(),
+                })
+                .flags(if desc.opaque {
+                    ash::vk::GeometryFlagsKHR::OPAQUE
+                } else {
+                    ash::vk::GeometryFlagsKHR::empty()
+                })
+                .build()
+        })
+        .collect();
+
+    let build_range_infos = desc
+        .geometries
+        .iter()
+        .map(|desc| {
+            ash::vk::AccelerationStructureBuildRangeInfoKHR::builder()
+                .primitive_count(desc.parts[0].index_count as u32 / 3)
+                .build()
+        })
+        .collect();
+
+    let max_primitive_counts = desc
+        .geometries
+        .iter()
+        .map(|desc| desc.parts[0].index_count as u32 / 3)
+        .collect();
+
+    (geometries, build_range_infos, max_primitive_counts)
+}
+
 impl Device {
     pub fn create_ray_tracing_acceleration_scratch_buffer(
         &self,
     ) -> Result<RayTracingAccelerationScratchBuffer, BackendError> {
-        let buffer = self.create_buffer(
+        self.create_ray_tracing_acceleration_scratch_buffer_with_size(RT_TLAS_SCRATCH_BUFFER_SIZE)
+    }
+
+    pub fn create_ray_tracing_acceleration_scratch_buffer_with_size(
+        &self,
+        size: usize,
+    ) -> Result<RayTracingAccelerationScratchBuffer, BackendError> {
+        Ok(RayTracingAccelerationScratchBuffer {
+            buffer: Arc::new(Mutex::new(self.create_scratch_buffer(size)?)),
+        })
+    }
+
+    /// Grows `scratch_buffer` to at least `size` bytes, if it's smaller. Builds do so by
+    /// themselves; this is for making room ahead of time.
+    pub fn ensure_ray_tracing_acceleration_scratch_size(
+        &self,
+        scratch_buffer: &RayTracingAccelerationScratchBuffer,
+        size: usize,
+    ) -> Result<(), BackendError> {
+        self.grow_scratch_buffer(&mut scratch_buffer.buffer.lock(), size)
+    }
+
+    fn create_scratch_buffer(&self, size: usize) -> Result<super::buffer::Buffer, BackendError> {
+        self.create_buffer(
             super::buffer::BufferDesc::new_gpu_only(
-                RT_TLAS_SCRATCH_BUFFER_SIZE,
+                size,
                 vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             ),
             "Acceleration structure scratch buffer",
             None,
-        )?;
+        )
+    }
 
-        Ok(RayTracingAccelerationScratchBuffer {
-            buffer: Arc::new(Mutex::new(buffer)),
-        })
+    // The old buffer is released once the GPU is done with the frames which could still use it.
+    fn grow_scratch_buffer(
+        &self,
+        buffer: &mut super::buffer::Buffer,
+        size: usize,
+    ) -> Result<(), BackendError> {
+        if size <= buffer.desc.size {
+            return Ok(());
+        }
+
+        let old = std::mem::replace(buffer, self.create_scratch_buffer(size)?);
+        self.defer_release(old);
+        Ok(())
     }
 
     pub fn create_ray_tracing_bottom_acceleration(
@@ -99,62 +236,16 @@ impl Device {
     ) -> Result<RayTracingAcceleration, BackendError> {
         //log::trace!("Creating ray tracing bottom acceleration: {:?}", desc);
 
-        let geometries: Result<Vec<ash::vk::AccelerationStructureGeometryKHR>, BackendError> = desc
-            .geometries
-            .iter()
-            .map(
-                |desc| -> Result<ash::vk::AccelerationStructureGeometryKHR, BackendError> {
-                    let part: RayTracingGeometryPart = desc.parts[0];
-
-                    let geometry = ash::vk::AccelerationStructureGeometryKHR::builder()
-                        .geometry_type(ash::vk::GeometryTypeKHR::TRIANGLES)
-                        .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
-                            triangles:
-                                ash::vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
-                                    .vertex_data(ash::vk::DeviceOrHostAddressConstKHR {
-                                        device_address: desc.vertex_buffer,
-                                    })
-                                    .vertex_stride(desc.vertex_stride as _)
-                                    .max_vertex(part.max_vertex)
-                                    .vertex_format(desc.vertex_format)
-                                    .index_data(ash::vk::DeviceOrHostAddressConstKHR {
-                                        device_address: desc.index_buffer,
-                                    })
-                                    .index_type(ash::vk::IndexType::UINT32) // TODO
-                                    .build(),
-                        })
-                        .flags(ash::vk::GeometryFlagsKHR::OPAQUE)
-                        .build();
-
-                    Ok(geometry)
-                },
-            )
-            .collect();
-        let geometries = geometries?;
-
-        let build_range_infos: Vec<ash::vk::AccelerationStructureBuildRangeInfoKHR> = desc
-            .geometries
-            .iter()
-            .map(|desc| {
-                ash::vk::AccelerationStructureBuildRangeInfoKHR::builder()
-                    .primitive_count(desc.parts[0].index_count as u32 / 3)
-                    .build()
-            })
-            .collect();
+        let (geometries, build_range_infos, max_primitive_counts) =
+            bottom_acceleration_geometries(desc);
 
         let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
             .ty(ash::vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
-            .flags(ash::vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .flags(blas_build_flags(desc))
             .geometries(geometries.as_slice())
             .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
             .build();
 
-        let max_primitive_counts: Vec<_> = desc
-            .geometries
-            .iter()
-            .map(|desc| desc.parts[0].index_count as u32 / 3)
-            .collect();
-
         // Create bottom-level acceleration structure
 
         let preallocate_bytes = 0;
@@ -168,6 +259,64 @@ impl Device {
         )
     }
 
+    /// Memory needed to build, or with `allow_update`, refit a BLAS with the geometry of `desc`.
+    pub fn ray_tracing_bottom_acceleration_build_sizes(
+        &self,
+        desc: &RayTracingBottomAccelerationDesc,
+    ) -> vk::AccelerationStructureBuildSizesInfoKHR {
+        let (geometries, _, max_primitive_counts) = bottom_acceleration_geometries(desc);
+
+        let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(ash::vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+            .flags(blas_build_flags(desc))
+            .geometries(geometries.as_slice())
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .build();
+
+        unsafe {
+            self.acceleration_structure_ext
+                .get_acceleration_structure_build_sizes(
+                    vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                    &geometry_info,
+                    &max_primitive_counts,
+                )
+        }
+    }
+
+    /// Updates a BLAS built with `allow_update` in-place for new vertex positions, e.g. of
+    /// a deformed mesh. `desc` must be the one it was built with, save for the vertex buffers.
+    pub fn refit_ray_tracing_bottom_acceleration(
+        &self,
+        cb: vk::CommandBuffer,
+        desc: &RayTracingBottomAccelerationDesc,
+        blas: &RayTracingAcceleration,
+        scratch_buffer: &RayTracingAccelerationScratchBuffer,
+    ) -> Result<(), BackendError> {
+        assert!(
+            desc.allow_update,
+            "the BLAS must be built with `allow_update`"
+        );
+
+        let (geometries, build_range_infos, max_primitive_counts) =
+            bottom_acceleration_geometries(desc);
+
+        let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(ash::vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+            .flags(blas_build_flags(desc))
+            .geometries(geometries.as_slice())
+            .mode(vk::BuildAccelerationStructureModeKHR::UPDATE)
+            .build();
+
+        self.rebuild_ray_tracing_acceleration(
+            cb,
+            geometry_info,
+            &build_range_infos,
+            &max_primitive_counts,
+            blas,
+            scratch_buffer,
+        )
+    }
+
     pub fn create_ray_tracing_top_acceleration(
         &self,
         desc: &RayTracingTopAccelerationDesc,
@@ -181,14 +330,14 @@ impl Device {
             .instances
             .iter()
             .map(|desc| {
-                let blas_address = unsafe {
+                let blas_address = desc.blas.as_ref().map_or(0, |blas| unsafe {
                     self.acceleration_structure_ext
                         .get_acceleration_structure_device_address(
                             &ash::vk::AccelerationStructureDeviceAddressInfoKHR::builder()
-                                .acceleration_structure(desc.blas.raw)
+                                .acceleration_structure(blas.raw)
                                 .build(),
                         )
-                };
+                });
 
                 let transform = [
                     desc.transformation.x_axis.x,
@@ -208,11 +357,14 @@ impl Device {
                 GeometryInstance::new(
                     transform,
                     desc.mesh_index, /* instance id */
-                    0xff,
+                    desc.mask,
                     0,
-                    /*ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
-                    | */
-                    ash::vk::GeometryInstanceFlagsKHR::FORCE_OPAQUE,
+                    // Opacity is up to the geometry flags of the BLAS.
+                    if desc.double_sided {
+                        ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
+                    } else {
+                        ash::vk::GeometryInstanceFlagsKHR::empty()
+                    },
                     blas_address,
                 )
             })
@@ -255,7 +407,7 @@ impl Device {
 
         let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
             .ty(ash::vk::AccelerationStructureTypeKHR::TOP_LEVEL)
-            .flags(ash::vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .flags(TLAS_BUILD_FLAGS)
             .geometries(std::slice::from_ref(&geometry))
             .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
             .build();
@@ -322,6 +474,10 @@ impl Device {
 
         let scratch_buffer = if let Some(scratch_buffer) = scratch_buffer {
             scratch_buffer_lock = scratch_buffer.buffer.lock();
+            self.grow_scratch_buffer(
+                &mut scratch_buffer_lock,
+                memory_requirements.build_scratch_size as usize,
+            )?;
             &mut *scratch_buffer_lock
         } else {
             tmp_scratch_buffer = Some(
@@ -349,10 +505,11 @@ impl Device {
                 //.context("create_acceleration_structure")?;
                 ?;
 
-                assert!(
-                    memory_requirements.build_scratch_size as usize <= scratch_buffer.desc.size,
-                    "TODO: resize scratch; see `RT_SCRATCH_BUFFER_SIZE`"
-                );
+                self.track_created(ResourceKind::AccelerationStructure, accel_raw, || {
+                    format!("{:?}", ty)
+                });
+                self.stats
+                    .acceleration_structure_created(self.buffer_memory_bytes(accel_buffer.raw));
 
                 geometry_info.dst_acceleration_structure = accel_raw;
                 geometry_info.scratch_data = ash::vk::DeviceOrHostAddressKHR {
@@ -405,6 +562,20 @@ impl Device {
         res
     }
 
+    /// Destroys the acceleration structure along with its backing buffer. The GPU must be
+    /// done with it; see `defer_release` otherwise.
+    pub fn immediate_destroy_acceleration(&self, accel: RayTracingAcceleration) {
+        unsafe {
+            self.acceleration_structure_ext
+                .destroy_acceleration_structure(accel.raw, None);
+        }
+        self.track_destroyed(ResourceKind::AccelerationStructure, accel.raw);
+        self.stats
+            .acceleration_structure_destroyed(self.buffer_memory_bytes(accel.backing_buffer.raw));
+
+        self.immediate_destroy_buffer(accel.backing_buffer);
+    }
+
     pub fn fill_ray_tracing_instance_buffer(
         &self,
         dynamic_constants: &mut DynamicConstants,
@@ -413,14 +584,15 @@ impl Device {
         let instance_buffer_address = dynamic_constants.current_device_address(self);
 
         dynamic_constants.push_from_iter(instances.iter().map(|desc| {
-            let blas_address = unsafe {
+            // A null address makes the instance inactive.
+            let blas_address = desc.blas.as_ref().map_or(0, |blas| unsafe {
                 self.acceleration_structure_ext
                     .get_acceleration_structure_device_address(
                         &ash::vk::AccelerationStructureDeviceAddressInfoKHR::builder()
-                            .acceleration_structure(desc.blas.raw)
+                            .acceleration_structure(blas.raw)
                             .build(),
                     )
-            };
+            });
 
             let transform = [
                 desc.transformation.x_axis.x,
@@ -440,11 +612,14 @@ impl Device {
             GeometryInstance::new(
                 transform,
                 desc.mesh_index, /* instance id */
-                0xff,
+                desc.mask,
                 0,
-                /*ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
-                | */
-                ash::vk::GeometryInstanceFlagsKHR::FORCE_OPAQUE,
+                // Opacity is up to the geometry flags of the BLAS.
+                if desc.double_sided {
+                    ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
+                } else {
+                    ash::vk::GeometryInstanceFlagsKHR::empty()
+                },
                 blas_address,
             )
         }));
@@ -452,6 +627,35 @@ impl Device {
         instance_buffer_address
     }
 
+    /// Memory needed to build a top-level acceleration structure with `instance_count` instances.
+    pub fn ray_tracing_top_acceleration_build_sizes(
+        &self,
+        instance_count: usize,
+    ) -> vk::AccelerationStructureBuildSizesInfoKHR {
+        let geometry = ash::vk::AccelerationStructureGeometryKHR::builder()
+            .geometry_type(ash::vk::GeometryTypeKHR::INSTANCES)
+            .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
+                instances: ash::vk::AccelerationStructureGeometryInstancesDataKHR::default(),
+            })
+            .build();
+
+        let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(ash::vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(TLAS_BUILD_FLAGS)
+            .geometries(std::slice::from_ref(&geometry))
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .build();
+
+        unsafe {
+            self.acceleration_structure_ext
+                .get_acceleration_structure_build_sizes(
+                    vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                    &geometry_info,
+                    &[instance_count as u32],
+                )
+        }
+    }
+
     pub fn rebuild_ray_tracing_top_acceleration(
         &self,
         cb: vk::CommandBuffer,
@@ -459,7 +663,46 @@ impl Device {
         instance_count: usize,
         tlas: &RayTracingAcceleration,
         scratch_buffer: &RayTracingAccelerationScratchBuffer,
-    ) {
+    ) -> Result<(), BackendError> {
+        self.build_ray_tracing_top_acceleration(
+            cb,
+            instance_buffer_address,
+            instance_count,
+            tlas,
+            scratch_buffer,
+            vk::BuildAccelerationStructureModeKHR::BUILD,
+        )
+    }
+
+    /// Updates the TLAS in-place for new instance transforms. The instances must be the same
+    /// as the last time it was built, in the same order.
+    pub fn refit_ray_tracing_top_acceleration(
+        &self,
+        cb: vk::CommandBuffer,
+        instance_buffer_address: vk::DeviceAddress,
+        instance_count: usize,
+        tlas: &RayTracingAcceleration,
+        scratch_buffer: &RayTracingAccelerationScratchBuffer,
+    ) -> Result<(), BackendError> {
+        self.build_ray_tracing_top_acceleration(
+            cb,
+            instance_buffer_address,
+            instance_count,
+            tlas,
+            scratch_buffer,
+            vk::BuildAccelerationStructureModeKHR::UPDATE,
+        )
+    }
+
+    fn build_ray_tracing_top_acceleration(
+        &self,
+        cb: vk::CommandBuffer,
+        instance_buffer_address: vk::DeviceAddress,
+        instance_count: usize,
+        tlas: &RayTracingAcceleration,
+        scratch_buffer: &RayTracingAccelerationScratchBuffer,
+        mode: vk::BuildAccelerationStructureModeKHR,
+    ) -> Result<(), BackendError> {
         let geometry = ash::vk::AccelerationStructureGeometryKHR::builder()
             .geometry_type(ash::vk::GeometryTypeKHR::INSTANCES)
             .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
@@ -477,9 +720,9 @@ impl Device {
 
         let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::builder()
             .ty(ash::vk::AccelerationStructureTypeKHR::TOP_LEVEL)
-            .flags(ash::vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .flags(TLAS_BUILD_FLAGS)
             .geometries(std::slice::from_ref(&geometry))
-            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .mode(mode)
             .build();
 
         let max_primitive_counts = [instance_count as u32];
@@ -504,7 +747,7 @@ impl Device {
         max_primitive_counts: &[u32],
         accel: &RayTracingAcceleration,
         scratch_buffer: &RayTracingAccelerationScratchBuffer,
-    ) {
+    ) -> Result<(), BackendError> {
         let memory_requirements = unsafe {
             self.acceleration_structure_ext
                 .get_acceleration_structure_build_sizes(
@@ -520,15 +763,22 @@ impl Device {
             "todo: backing"
         );
 
-        let scratch_buffer = scratch_buffer.buffer.lock();
+        let mut scratch_buffer = scratch_buffer.buffer.lock();
 
-        assert!(
-            memory_requirements.build_scratch_size as usize <= scratch_buffer.desc.size,
-            "todo: scratch"
-        );
+        let is_update = geometry_info.mode == vk::BuildAccelerationStructureModeKHR::UPDATE;
+        let scratch_size = if is_update {
+            memory_requirements.update_scratch_size
+        } else {
+            memory_requirements.build_scratch_size
+        };
+
+        self.grow_scratch_buffer(&mut scratch_buffer, scratch_size as usize)?;
 
         unsafe {
             geometry_info.dst_acceleration_structure = accel.raw;
+            if is_update {
+                geometry_info.src_acceleration_structure = accel.raw;
+            }
             geometry_info.scratch_data = ash::vk::DeviceOrHostAddressKHR {
                 device_address: self.raw.get_buffer_device_address(
                     &ash::vk::BufferDeviceAddressInfo::builder().buffer(scratch_buffer.raw),
@@ -561,6 +811,8 @@ impl Device {
                 &[],
             );
         }
+
+        Ok(())
     }
 
     fn create_ray_tracing_shader_table(
@@ -732,6 +984,12 @@ pub fn create_ray_tracing_pipeline(
         //desc.descriptor_set_layout_flags.unwrap_or(&[]),  // TODO: merge flags
         &desc.descriptor_set_opts,
     );
+    let mut guard = super::shader::PipelineCreationGuard::new(device, &descriptor_set_layouts);
+
+    let pipeline_error = |err| BackendError::PipelineCreation {
+        name: super::shader::pipeline_name(shaders),
+        err,
+    };
 
     unsafe {
         let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
@@ -741,7 +999,8 @@ pub fn create_ray_tracing_pipeline(
         let pipeline_layout = device
             .raw
             .create_pipeline_layout(&layout_create_info, None)
-            .unwrap();
+            .map_err(pipeline_error)?;
+        guard.pipeline_layout = pipeline_layout;
 
         let mut shader_groups: Vec<vk::RayTracingShaderGroupCreateInfoKHR> = Vec::new();
         let mut shader_stages: Vec<vk::PipelineShaderStageCreateInfo> = Vec::new();
@@ -753,17 +1012,18 @@ pub fn create_ray_tracing_pipeline(
         let mut miss_entry_count = 0;
         let mut hit_entry_count = 0;
 
-        let create_shader_module =
-            |desc: &PipelineShader<Bytes>| -> (ash::vk::ShaderModule, String) {
+        let mut create_shader_module =
+            |desc: &PipelineShader<Bytes>| -> Result<(vk::ShaderModule, String), BackendError> {
                 let shader_info = vk::ShaderModuleCreateInfo::builder()
                     .code(desc.code.as_slice_of::<u32>().unwrap());
 
                 let shader_module = device
                     .raw
                     .create_shader_module(&shader_info, None)
-                    .expect("Shader module error");
+                    .map_err(pipeline_error)?;
+                guard.shader_modules.push(shader_module);
 
-                (shader_module, desc.desc.entry.clone())
+                Ok((shader_module, desc.desc.entry.clone()))
             };
 
         let mut prev_stage: Option<ShaderPipelineStage> = None;
@@ -776,7 +1036,7 @@ pub fn create_ray_tracing_pipeline(
                     assert!(prev_stage == None || prev_stage == Some(ShaderPipelineStage::RayGen));
                     raygen_entry_count += 1;
 
-                    let (module, entry_point) = create_shader_module(desc);
+                    let (module, entry_point) = create_shader_module(desc)?;
 
                     entry_points.push(std::ffi::CString::new(entry_point).unwrap());
                     let entry_point = &**entry_points.last().unwrap();
@@ -805,7 +1065,7 @@ pub fn create_ray_tracing_pipeline(
                     );
                     miss_entry_count += 1;
 
-                    let (module, entry_point) = create_shader_module(desc);
+                    let (module, entry_point) = create_shader_module(desc)?;
 
                     entry_points.push(std::ffi::CString::new(entry_point).unwrap());
                     let entry_point = &**entry_points.last().unwrap();
@@ -831,10 +1091,11 @@ pub fn create_ray_tracing_pipeline(
                     assert!(
                         prev_stage == Some(ShaderPipelineStage::RayMiss)
                             || prev_stage == Some(ShaderPipelineStage::RayClosestHit)
+                            || prev_stage == Some(ShaderPipelineStage::RayAnyHit)
                     );
                     hit_entry_count += 1;
 
-                    let (module, entry_point) = create_shader_module(desc);
+                    let (module, entry_point) = create_shader_module(desc)?;
 
                     entry_points.push(std::ffi::CString::new(entry_point).unwrap());
                     let entry_point = &**entry_points.last().unwrap();
@@ -856,6 +1117,43 @@ pub fn create_ray_tracing_pipeline(
                     shader_stages.push(stage);
                     shader_groups.push(group);
                 }
+                ShaderPipelineStage::RayAnyHit => {
+                    assert!(
+                        prev_stage == Some(ShaderPipelineStage::RayMiss)
+                            || prev_stage == Some(ShaderPipelineStage::RayClosestHit)
+                            || prev_stage == Some(ShaderPipelineStage::RayAnyHit)
+                    );
+
+                    let (module, entry_point) = create_shader_module(desc)?;
+
+                    entry_points.push(std::ffi::CString::new(entry_point).unwrap());
+                    let entry_point = &**entry_points.last().unwrap();
+
+                    let stage = ash::vk::PipelineShaderStageCreateInfo::builder()
+                        .stage(ash::vk::ShaderStageFlags::ANY_HIT_KHR)
+                        .module(module)
+                        .name(entry_point)
+                        .build();
+
+                    if prev_stage == Some(ShaderPipelineStage::RayClosestHit) {
+                        // Joins the hit group of the closest hit shader right before it.
+                        shader_groups.last_mut().unwrap().any_hit_shader = group_idx as _;
+                    } else {
+                        hit_entry_count += 1;
+
+                        let group = ash::vk::RayTracingShaderGroupCreateInfoKHR::builder()
+                            .ty(ash::vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+                            .general_shader(ash::vk::SHADER_UNUSED_KHR)
+                            .closest_hit_shader(ash::vk::SHADER_UNUSED_KHR)
+                            .any_hit_shader(group_idx as _)
+                            .intersection_shader(ash::vk::SHADER_UNUSED_KHR)
+                            .build();
+
+                        shader_groups.push(group);
+                    }
+
+                    shader_stages.push(stage);
+                }
                 _ => unimplemented!(),
             }
 
@@ -865,11 +1163,31 @@ pub fn create_ray_tracing_pipeline(
         assert!(raygen_entry_count > 0);
         assert!(miss_entry_count > 0);
 
+        // Every shader contributed exactly one stage above.
+        let specializations: Vec<SpecializationData> = shaders
+            .iter()
+            .map(|desc| SpecializationData::new(&desc.desc.specialization_constants))
+            .collect();
+        let specialization_infos: Vec<vk::SpecializationInfo> = specializations
+            .iter()
+            .map(SpecializationData::info)
+            .collect();
+
+        for ((stage, specialization), specialization_info) in shader_stages
+            .iter_mut()
+            .zip(&specializations)
+            .zip(&specialization_infos)
+        {
+            if !specialization.is_empty() {
+                stage.p_specialization_info = specialization_info;
+            }
+        }
+
         let pipeline = device
             .ray_tracing_pipeline_ext
             .create_ray_tracing_pipelines(
                 vk::DeferredOperationKHR::null(),
-                vk::PipelineCache::null(),
+                device.pipeline_cache,
                 &[ash::vk::RayTracingPipelineCreateInfoKHR::builder()
                     .stages(&shader_stages)
                     .groups(&shader_groups)
@@ -878,7 +1196,8 @@ pub fn create_ray_tracing_pipeline(
                     .build()],
                 None,
             )
-            .expect("create_ray_tracing_pipelines")[0];
+            .map_err(pipeline_error)?[0];
+        guard.pipeline = pipeline;
 
         let mut descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
         for bindings in set_layout_info.iter() {
@@ -905,6 +1224,7 @@ pub fn create_ray_tracing_pipeline(
                 pipeline,
             )
             .map_err(|err| device.report_error(err))?;
+        guard.finish();
 
         Ok(RayTracingPipeline {
             common: ShaderPipelineCommon {
@@ -915,6 +1235,7 @@ pub fn create_ray_tracing_pipeline(
                 descriptor_pool_sizes,
                 descriptor_set_layouts,
                 pipeline_bind_point: vk::PipelineBindPoint::RAY_TRACING_KHR,
+                descriptor_update_templates: Default::default(),
             },
             sbt,
         })
@@ -0,0 +1,96 @@
+//! Debug tracking of the GPU objects created through `Device`, so that the ones still
+//! alive at shutdown can be reported. Enabled along with graphics debugging.
+//!
+//! Objects are attributed to the innermost `ResourceCreatorScope` on the creating thread,
+//! such as the render graph pass or asset they were created for.
+
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData};
+
+use ash::vk::Handle;
+use parking_lot::Mutex;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ResourceKind {
+    Buffer,
+    Image,
+    ImageView,
+    DescriptorPool,
+    AccelerationStructure,
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveResource {
+    pub kind: ResourceKind,
+    pub name: String,
+    /// The innermost `ResourceCreatorScope` when the object was created, if any.
+    pub creator: Option<String>,
+}
+
+impl std::fmt::Display for LiveResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {:?}, created by {}",
+            self.kind,
+            self.name,
+            self.creator.as_deref().unwrap_or("(unknown)")
+        )
+    }
+}
+
+thread_local! {
+    static CREATOR_SCOPES: RefCell<Vec<String>> = Default::default();
+}
+
+/// Attributes objects created on this thread to `creator` until dropped.
+#[must_use]
+pub struct ResourceCreatorScope {
+    // Popped on the thread which pushed it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ResourceCreatorScope {
+    pub fn new(creator: impl Into<String>) -> Self {
+        CREATOR_SCOPES.with(|scopes| scopes.borrow_mut().push(creator.into()));
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for ResourceCreatorScope {
+    fn drop(&mut self) {
+        CREATOR_SCOPES.with(|scopes| scopes.borrow_mut().pop());
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ResourceTracker {
+    live: Mutex<HashMap<(ResourceKind, u64), LiveResource>>,
+}
+
+impl ResourceTracker {
+    pub fn created(&self, kind: ResourceKind, handle: impl Handle, name: impl Into<String>) {
+        let creator = CREATOR_SCOPES.with(|scopes| scopes.borrow().last().cloned());
+
+        self.live.lock().insert(
+            (kind, handle.as_raw()),
+            LiveResource {
+                kind,
+                name: name.into(),
+                creator,
+            },
+        );
+    }
+
+    pub fn destroyed(&self, kind: ResourceKind, handle: impl Handle) {
+        self.live.lock().remove(&(kind, handle.as_raw()));
+    }
+
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        let mut resources: Vec<LiveResource> = self.live.lock().values().cloned().collect();
+        resources
+            .sort_by(|a, b| (a.creator.as_deref(), &a.name).cmp(&(b.creator.as_deref(), &b.name)));
+        resources
+    }
+}
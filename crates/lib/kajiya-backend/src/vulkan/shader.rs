@@ -1,13 +1,15 @@
 #![allow(dead_code)]
 
+pub use super::vertex_input::{VertexAttributeDesc, VertexBindingDesc, VertexInputDesc};
 use super::{
     device::{Device, SamplerDesc},
+    dynamic_rendering::PipelineRenderingCreateInfoKHR,
     image::ImageDesc,
 };
-use crate::{chunky_list::TempList, shader_compiler::get_cs_local_size_from_spirv};
+use crate::{chunky_list::TempList, shader_compiler::get_cs_local_size_from_spirv, BackendError};
 use arrayvec::ArrayVec;
 use ash::vk;
-use byte_slice_cast::AsSliceOf as _;
+use byte_slice_cast::{AsByteSlice as _, AsSliceOf as _};
 use bytes::Bytes;
 use derive_builder::Builder;
 use parking_lot::Mutex;
@@ -23,6 +25,9 @@ pub const MAX_DESCRIPTOR_SETS: usize = 4;
 type DescriptorSetLayout = HashMap<u32, rspirv_reflect::DescriptorInfo>;
 type StageDescriptorSetLayouts = HashMap<u32, DescriptorSetLayout>;
 
+/// A set index, and the binding index, type, and count of each descriptor written.
+pub type DescriptorUpdateTemplateKey = (u32, Vec<(u32, vk::DescriptorType, u32)>);
+
 pub struct ShaderPipelineCommon {
     pub pipeline_layout: vk::PipelineLayout,
     pub pipeline: vk::Pipeline,
@@ -30,6 +35,11 @@ pub struct ShaderPipelineCommon {
     pub descriptor_pool_sizes: Vec<vk::DescriptorPoolSize>,
     pub descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     pub pipeline_bind_point: vk::PipelineBindPoint,
+
+    /// Created as the sets of the pipeline get written, since which bindings are used
+    /// is only known then.
+    pub descriptor_update_templates:
+        Mutex<HashMap<DescriptorUpdateTemplateKey, vk::DescriptorUpdateTemplate>>,
 }
 pub struct ComputePipeline {
     pub common: ShaderPipelineCommon,
@@ -146,8 +156,14 @@ pub fn create_descriptor_set_layouts(
                             //.descriptor_count(binding.count)
                             .descriptor_count(1) // TODO
                             .descriptor_type(match binding.ty {
+                                // Constant buffers are fed from the dynamic constants, except
+                                // for `*_buf` ones, bound from render graph buffers.
                                 rspirv_reflect::DescriptorType::UNIFORM_BUFFER => {
-                                    vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+                                    if binding.name.ends_with("_buf") {
+                                        vk::DescriptorType::UNIFORM_BUFFER
+                                    } else {
+                                        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+                                    }
                                 }
                                 rspirv_reflect::DescriptorType::UNIFORM_TEXEL_BUFFER => {
                                     vk::DescriptorType::UNIFORM_TEXEL_BUFFER
@@ -248,6 +264,15 @@ pub fn create_descriptor_set_layouts(
                             panic!("{}", binding.name);
                         }
                     }
+                    rspirv_reflect::DescriptorType::INPUT_ATTACHMENT => bindings.push(
+                        vk::DescriptorSetLayoutBinding::builder()
+                            .binding(*binding_index)
+                            .descriptor_count(1)
+                            .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+                            // Only fragment shaders can read them.
+                            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                            .build(),
+                    ),
                     rspirv_reflect::DescriptorType::ACCELERATION_STRUCTURE_KHR => bindings.push(
                         vk::DescriptorSetLayoutBinding::builder()
                             .binding(*binding_index)
@@ -345,6 +370,45 @@ impl ShaderSource {
     }
 }
 
+/// Values of the `[[vk::constant_id(N)]]` constants of a shader, as `(N, value)` pairs.
+///
+/// Every constant is 32 bits wide; floats can be passed via `f32::to_bits`.
+pub type SpecializationConstants = Vec<(u32, u32)>;
+
+/// Owns the data which a `vk::SpecializationInfo` points to.
+pub(crate) struct SpecializationData {
+    map_entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u32>,
+}
+
+impl SpecializationData {
+    pub(crate) fn new(constants: &[(u32, u32)]) -> Self {
+        Self {
+            map_entries: constants
+                .iter()
+                .enumerate()
+                .map(|(i, &(constant_id, _))| vk::SpecializationMapEntry {
+                    constant_id,
+                    offset: (i * std::mem::size_of::<u32>()) as u32,
+                    size: std::mem::size_of::<u32>(),
+                })
+                .collect(),
+            data: constants.iter().map(|&(_, value)| value).collect(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.map_entries.is_empty()
+    }
+
+    pub(crate) fn info(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo::builder()
+            .map_entries(&self.map_entries)
+            .data(self.data.as_byte_slice())
+            .build()
+    }
+}
+
 #[derive(Builder, Clone)]
 #[builder(pattern = "owned", derive(Clone))]
 pub struct ComputePipelineDesc {
@@ -352,6 +416,8 @@ pub struct ComputePipelineDesc {
     pub descriptor_set_opts: [Option<(u32, DescriptorSetLayoutOpts)>; MAX_DESCRIPTOR_SETS],
     #[builder(default)]
     pub push_constants_bytes: usize,
+    #[builder(default)]
+    pub specialization_constants: SpecializationConstants,
     pub source: ShaderSource,
 }
 
@@ -384,11 +450,73 @@ impl ComputePipelineDesc {
     }
 }
 
+/// Vulkan objects created so far for a pipeline. Destroyed on drop, so that pipeline creation
+/// which bails out with an error doesn't leak them, unless handed over with `finish`.
+pub(crate) struct PipelineCreationGuard<'a> {
+    device: &'a Device,
+    pub descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub shader_modules: Vec<vk::ShaderModule>,
+    pub pipeline: vk::Pipeline,
+}
+
+impl<'a> PipelineCreationGuard<'a> {
+    pub(crate) fn new(
+        device: &'a Device,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    ) -> Self {
+        Self {
+            device,
+            descriptor_set_layouts: descriptor_set_layouts.to_vec(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            shader_modules: Vec::new(),
+            pipeline: vk::Pipeline::null(),
+        }
+    }
+
+    /// The pipeline owns everything but the shader modules, which aren't needed
+    /// once it's created.
+    pub(crate) fn finish(mut self) {
+        self.descriptor_set_layouts.clear();
+        self.pipeline_layout = vk::PipelineLayout::null();
+        self.pipeline = vk::Pipeline::null();
+    }
+}
+
+impl Drop for PipelineCreationGuard<'_> {
+    fn drop(&mut self) {
+        let raw = &self.device.raw;
+
+        unsafe {
+            if self.pipeline != vk::Pipeline::null() {
+                raw.destroy_pipeline(self.pipeline, None);
+            }
+
+            for shader_module in self.shader_modules.drain(..) {
+                raw.destroy_shader_module(shader_module, None);
+            }
+
+            if self.pipeline_layout != vk::PipelineLayout::null() {
+                raw.destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+
+            for set_layout in self.descriptor_set_layouts.drain(..) {
+                raw.destroy_descriptor_set_layout(set_layout, None);
+            }
+        }
+    }
+}
+
 pub fn create_compute_pipeline(
     device: &Device,
     spirv: &[u8],
     desc: &ComputePipelineDesc,
-) -> ComputePipeline {
+) -> Result<ComputePipeline, BackendError> {
+    let pipeline_error = |err| BackendError::PipelineCreation {
+        name: desc.source.entry().to_owned(),
+        err,
+    };
+
     let (descriptor_set_layouts, set_layout_info) = super::shader::create_descriptor_set_layouts(
         device,
         &rspirv_reflect::Reflection::new_from_spirv(spirv)
@@ -398,6 +526,7 @@ pub fn create_compute_pipeline(
         vk::ShaderStageFlags::COMPUTE,
         &desc.descriptor_set_opts,
     );
+    let mut guard = PipelineCreationGuard::new(device, &descriptor_set_layouts);
 
     // dbg!(&set_layout_info);
 
@@ -422,18 +551,27 @@ pub fn create_compute_pipeline(
                 &vk::ShaderModuleCreateInfo::builder().code(spirv.as_slice_of::<u32>().unwrap()),
                 None,
             )
-            .unwrap();
+            .map_err(pipeline_error)?;
+        guard.shader_modules.push(shader_module);
 
         let entry_name = CString::new(desc.source.entry()).unwrap();
-        let stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+        let specialization = SpecializationData::new(&desc.specialization_constants);
+        let specialization_info = specialization.info();
+
+        let mut stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
             .module(shader_module)
             .stage(vk::ShaderStageFlags::COMPUTE)
             .name(&entry_name);
 
+        if !specialization.is_empty() {
+            stage_create_info = stage_create_info.specialization_info(&specialization_info);
+        }
+
         let pipeline_layout = device
             .raw
             .create_pipeline_layout(&layout_create_info, None)
-            .unwrap();
+            .map_err(pipeline_error)?;
+        guard.pipeline_layout = pipeline_layout;
 
         let pipeline_info = vk::ComputePipelineCreateInfo::builder()
             .stage(stage_create_info.build())
@@ -441,9 +579,10 @@ pub fn create_compute_pipeline(
 
         let pipeline = device
             .raw
-            // TODO: pipeline cache
-            .create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_info.build()], None)
-            .expect("pipeline")[0];
+            .create_compute_pipelines(device.pipeline_cache, &[pipeline_info.build()], None)
+            .map_err(|(_, err)| pipeline_error(err))?[0];
+        guard.pipeline = pipeline;
+        guard.finish();
 
         let mut descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
         for bindings in set_layout_info.iter() {
@@ -460,7 +599,7 @@ pub fn create_compute_pipeline(
             }
         }
 
-        ComputePipeline {
+        Ok(ComputePipeline {
             common: ShaderPipelineCommon {
                 pipeline_layout,
                 pipeline,
@@ -468,9 +607,10 @@ pub fn create_compute_pipeline(
                 descriptor_pool_sizes,
                 descriptor_set_layouts,
                 pipeline_bind_point: vk::PipelineBindPoint::COMPUTE,
+                descriptor_update_templates: Default::default(),
             },
             group_size: get_cs_local_size_from_spirv(spirv.as_slice_of::<u32>().unwrap()).unwrap(),
-        }
+        })
     }
 }
 
@@ -481,6 +621,9 @@ pub enum ShaderPipelineStage {
     RayGen,
     RayMiss,
     RayClosestHit,
+    /// Joins the hit group of a closest hit shader directly preceding it,
+    /// and otherwise forms a hit group of its own.
+    RayAnyHit,
 }
 
 #[derive(Builder, Hash, PartialEq, Eq, Clone, Debug)]
@@ -493,6 +636,8 @@ pub struct PipelineShaderDesc {
     pub push_constants_bytes: usize,
     #[builder(default = "\"main\".to_owned()")]
     pub entry: String,
+    #[builder(default)]
+    pub specialization_constants: SpecializationConstants,
     pub source: ShaderSource,
 }
 
@@ -522,10 +667,28 @@ pub struct RasterPipelineDesc {
     #[builder(default)]
     pub descriptor_set_opts: [Option<(u32, DescriptorSetLayoutOpts)>; MAX_DESCRIPTOR_SETS],
     pub render_pass: Arc<RenderPass>,
+    /// Index into `RenderPassDesc::subpasses` of the subpass the pipeline is used in.
+    #[builder(default)]
+    pub subpass: u32,
     #[builder(default)]
     pub face_cull: bool,
     #[builder(default = "true")]
     pub depth_write: bool,
+    /// Depth is reversed, so the default passes fragments at or in front of the stored depth.
+    /// `EQUAL` only passes those matching a depth pre-pass.
+    #[builder(default = "vk::CompareOp::GREATER_OR_EQUAL")]
+    pub depth_compare_op: vk::CompareOp,
+    #[builder(default = "vk::PrimitiveTopology::TRIANGLE_LIST")]
+    pub topology: vk::PrimitiveTopology,
+    /// Vertex buffers read by fixed-function vertex input. None by default.
+    #[builder(default)]
+    pub vertex_input: VertexInputDesc,
+    /// `LINE` requires `Device::fill_mode_non_solid_enabled`.
+    #[builder(default = "vk::PolygonMode::FILL")]
+    pub polygon_mode: vk::PolygonMode,
+    /// Blends the color outputs over the attachments with premultiplied alpha.
+    #[builder(default)]
+    pub alpha_blend: bool,
     #[builder(default)]
     pub push_constants_bytes: usize,
 }
@@ -545,7 +708,7 @@ impl RasterPipelineDesc {
     //pub render_pass: Arc<RenderPass>,
 }*/
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct RenderPassAttachmentDesc {
     pub format: vk::Format,
     pub load_op: vk::AttachmentLoadOp,
@@ -628,7 +791,6 @@ pub struct FramebufferCache {
     entries: Mutex<HashMap<FramebufferCacheKey, vk::Framebuffer>>,
     attachment_desc: ArrayVec<[RenderPassAttachmentDesc; MAX_COLOR_ATTACHMENTS + 1]>,
     render_pass: vk::RenderPass,
-    color_attachment_count: usize,
 }
 
 impl FramebufferCache {
@@ -651,7 +813,6 @@ impl FramebufferCache {
             entries: Default::default(),
             attachment_desc,
             render_pass,
-            color_attachment_count: color_attachments.len(),
         }
     }
 
@@ -710,14 +871,123 @@ impl FramebufferCache {
 pub struct RenderPassDesc<'a> {
     pub color_attachments: &'a [RenderPassAttachmentDesc],
     pub depth_attachment: Option<RenderPassAttachmentDesc>,
+
+    /// Empty for a single subpass rendering to all the attachments.
+    pub subpasses: &'a [SubpassDesc<'a>],
+}
+
+/// One subpass of a `RenderPassDesc`. Attachments are referred to by index,
+/// with the depth attachment following the color ones.
+///
+/// Later subpasses can read what earlier ones wrote via input attachments, without
+/// the round trip through memory which a separate render pass would take on tiled GPUs.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SubpassDesc<'a> {
+    pub color_attachments: &'a [u32],
+
+    /// Read as `SubpassInput`s, with `vk::input_attachment_index` matching the position here.
+    /// Images read this way need `vk::ImageUsageFlags::INPUT_ATTACHMENT`.
+    ///
+    /// Color attachments are in the `GENERAL` layout during the subpass, which allows
+    /// also writing to them, for programmable blending. A depth attachment is read-only.
+    pub input_attachments: &'a [u32],
+
+    pub depth_attachment: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct SubpassCacheKey {
+    color_attachments: Vec<u32>,
+    input_attachments: Vec<u32>,
+    depth_attachment: bool,
+}
+
+impl SubpassCacheKey {
+    fn new(desc: &SubpassDesc<'_>) -> Self {
+        Self {
+            color_attachments: desc.color_attachments.to_vec(),
+            input_attachments: desc.input_attachments.to_vec(),
+            depth_attachment: desc.depth_attachment,
+        }
+    }
+
+    fn attachments(&self) -> impl Iterator<Item = u32> + '_ {
+        self.color_attachments
+            .iter()
+            .chain(self.input_attachments.iter())
+            .copied()
+    }
 }
 
 pub struct RenderPass {
     pub raw: vk::RenderPass,
     pub framebuffer_cache: FramebufferCache,
+    pub subpass_color_attachment_counts: Vec<usize>,
+
+    /// Set with `Device::dynamic_rendering_enabled`, for a single subpass drawing to all
+    /// the attachments. Rendering then begins with `Device::cmd_begin_rendering`, and pipelines
+    /// are created against the attachment formats. `raw` still identifies the pass in caches.
+    pub dynamic_rendering: Option<DynamicRenderingAttachments>,
+}
+
+/// The attachments of a `RenderPass` begun with dynamic rendering, in the same layouts
+/// as within a render pass object: `COLOR_ATTACHMENT_OPTIMAL` for color, and
+/// `DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL` for depth.
+#[derive(Clone)]
+pub struct DynamicRenderingAttachments {
+    pub color_attachments: ArrayVec<[RenderPassAttachmentDesc; MAX_COLOR_ATTACHMENTS]>,
+    pub depth_attachment: Option<RenderPassAttachmentDesc>,
+}
+
+/// Render passes with the same attachment formats, load/store ops, and subpasses are
+/// compatible, and get shared via `Device::render_pass_cache`.
+#[derive(PartialEq, Eq, Hash)]
+pub struct RenderPassCacheKey {
+    color_attachments: ArrayVec<[RenderPassAttachmentDesc; MAX_COLOR_ATTACHMENTS]>,
+    depth_attachment: Option<RenderPassAttachmentDesc>,
+    subpasses: Vec<SubpassCacheKey>,
 }
 
+impl RenderPassCacheKey {
+    fn new(desc: &RenderPassDesc<'_>) -> Self {
+        let mut color_attachments = ArrayVec::new();
+        color_attachments
+            .try_extend_from_slice(desc.color_attachments)
+            .expect("too many color attachments");
+
+        let subpasses = if desc.subpasses.is_empty() {
+            vec![SubpassCacheKey {
+                color_attachments: (0..desc.color_attachments.len() as u32).collect(),
+                input_attachments: Vec::new(),
+                depth_attachment: desc.depth_attachment.is_some(),
+            }]
+        } else {
+            desc.subpasses.iter().map(SubpassCacheKey::new).collect()
+        };
+
+        Self {
+            color_attachments,
+            depth_attachment: desc.depth_attachment,
+            subpasses,
+        }
+    }
+}
+
+/// Returns a render pass for the given attachments, creating it on first use.
 pub fn create_render_pass(device: &Device, desc: RenderPassDesc<'_>) -> Arc<RenderPass> {
+    let key = RenderPassCacheKey::new(&desc);
+
+    device
+        .render_pass_cache
+        .lock()
+        .entry(key)
+        .or_insert_with_key(|key| create_render_pass_uncached(device, key))
+        .clone()
+}
+
+fn create_render_pass_uncached(device: &Device, desc: &RenderPassCacheKey) -> Arc<RenderPass> {
+    let depth_attachment_idx = desc.color_attachments.len() as u32;
+
     let renderpass_attachments = desc
         .color_attachments
         .iter()
@@ -735,45 +1005,134 @@ pub fn create_render_pass(device: &Device, desc: RenderPassDesc<'_>) -> Arc<Rend
         }))
         .collect::<Vec<_>>();
 
-    let color_attachment_refs = (0..desc.color_attachments.len() as u32)
-        .map(|attachment| vk::AttachmentReference {
-            attachment,
-            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        })
-        .collect::<Vec<_>>();
+    // Within a subpass, each attachment has a single layout for all its uses.
+    let attachment_layout = |subpass: &SubpassCacheKey, attachment: u32| {
+        let is_input = subpass.input_attachments.contains(&attachment);
+
+        if attachment == depth_attachment_idx {
+            if is_input {
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            } else {
+                vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+            }
+        } else if is_input {
+            vk::ImageLayout::GENERAL
+        } else {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        }
+    };
 
-    let depth_attachment_ref = vk::AttachmentReference {
-        attachment: desc.color_attachments.len() as u32,
-        layout: vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL,
+    let attachment_refs = |subpass: &SubpassCacheKey, attachments: &[u32]| {
+        attachments
+            .iter()
+            .map(|&attachment| vk::AttachmentReference {
+                attachment,
+                layout: attachment_layout(subpass, attachment),
+            })
+            .collect::<Vec<_>>()
     };
 
-    // TODO: Calculate optimal dependencies. using implicit dependencies for now.
-    /*let dependencies = [vk::SubpassDependency {
-        src_subpass: vk::SUBPASS_EXTERNAL,
-        src_stage_mask: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
-            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
-            | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        ..Default::default()
-    }];*/
-
-    let mut subpass_description = vk::SubpassDescription::builder()
-        .color_attachments(&color_attachment_refs)
-        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
-
-    if desc.depth_attachment.is_some() {
-        subpass_description = subpass_description.depth_stencil_attachment(&depth_attachment_ref);
+    let color_attachment_refs: Vec<_> = desc
+        .subpasses
+        .iter()
+        .map(|subpass| attachment_refs(subpass, &subpass.color_attachments))
+        .collect();
+
+    let input_attachment_refs: Vec<_> = desc
+        .subpasses
+        .iter()
+        .map(|subpass| attachment_refs(subpass, &subpass.input_attachments))
+        .collect();
+
+    let depth_attachment_refs: Vec<_> = desc
+        .subpasses
+        .iter()
+        .map(|subpass| vk::AttachmentReference {
+            attachment: depth_attachment_idx,
+            layout: attachment_layout(subpass, depth_attachment_idx),
+        })
+        .collect();
+
+    let subpass_descriptions: Vec<_> = desc
+        .subpasses
+        .iter()
+        .enumerate()
+        .map(|(subpass_idx, subpass)| {
+            let mut subpass_description = vk::SubpassDescription::builder()
+                .color_attachments(&color_attachment_refs[subpass_idx])
+                .input_attachments(&input_attachment_refs[subpass_idx])
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+
+            if subpass.depth_attachment {
+                assert!(desc.depth_attachment.is_some(), "no depth attachment");
+
+                subpass_description = subpass_description
+                    .depth_stencil_attachment(&depth_attachment_refs[subpass_idx]);
+            }
+
+            subpass_description.build()
+        })
+        .collect();
+
+    // A single subpass relies on the implicit external dependencies, and the barriers
+    // recorded by the render graph. Subpasses sharing attachments are ordered, and make
+    // the earlier writes visible to the later reads, per pixel.
+    let attachment_writes =
+        vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    let attachment_stages = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+
+    let mut dependencies = Vec::new();
+    for (dst_idx, dst) in desc.subpasses.iter().enumerate() {
+        let dst_uses = |attachment: u32| {
+            dst.attachments().any(|a| a == attachment)
+                || (dst.depth_attachment && attachment == depth_attachment_idx)
+        };
+
+        for (src_idx, src) in desc.subpasses.iter().enumerate().take(dst_idx) {
+            let shares_attachments = src.attachments().any(dst_uses)
+                || (src.depth_attachment && dst_uses(depth_attachment_idx));
+
+            if shares_attachments {
+                dependencies.push(vk::SubpassDependency {
+                    src_subpass: src_idx as u32,
+                    dst_subpass: dst_idx as u32,
+                    src_stage_mask: attachment_stages,
+                    dst_stage_mask: attachment_stages | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    src_access_mask: attachment_writes,
+                    dst_access_mask: attachment_writes
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::INPUT_ATTACHMENT_READ,
+                    dependency_flags: vk::DependencyFlags::BY_REGION,
+                });
+            }
+        }
+
+        // Lets programmable blending read back what the subpass wrote,
+        // after a pipeline barrier within it.
+        if dst
+            .input_attachments
+            .iter()
+            .any(|a| dst.color_attachments.contains(a))
+        {
+            dependencies.push(vk::SubpassDependency {
+                src_subpass: dst_idx as u32,
+                dst_subpass: dst_idx as u32,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::INPUT_ATTACHMENT_READ,
+                dependency_flags: vk::DependencyFlags::BY_REGION,
+            });
+        }
     }
-    let subpass_description = subpass_description.build();
 
-    let subpasses = [subpass_description];
     let render_pass_create_info = vk::RenderPassCreateInfo::builder()
         .attachments(&renderpass_attachments)
-        .subpasses(&subpasses);
+        .subpasses(&subpass_descriptions)
+        .dependencies(&dependencies);
 
     let render_pass = unsafe {
         device
@@ -782,13 +1141,36 @@ pub fn create_render_pass(device: &Device, desc: RenderPassDesc<'_>) -> Arc<Rend
             .unwrap()
     };
 
+    // Subpasses and input attachments need render pass objects.
+    let single_subpass = desc.subpasses.len() == 1
+        && desc.subpasses[0].input_attachments.is_empty()
+        && desc.subpasses[0]
+            .color_attachments
+            .iter()
+            .copied()
+            .eq(0..desc.color_attachments.len() as u32)
+        && desc.subpasses[0].depth_attachment == desc.depth_attachment.is_some();
+
+    let dynamic_rendering = (device.dynamic_rendering_enabled() && single_subpass).then(|| {
+        DynamicRenderingAttachments {
+            color_attachments: desc.color_attachments.clone(),
+            depth_attachment: desc.depth_attachment,
+        }
+    });
+
     Arc::new(RenderPass {
         raw: render_pass,
         framebuffer_cache: FramebufferCache::new(
             render_pass,
-            desc.color_attachments,
+            &desc.color_attachments,
             desc.depth_attachment,
         ),
+        subpass_color_attachment_counts: desc
+            .subpasses
+            .iter()
+            .map(|subpass| subpass.color_attachments.len())
+            .collect(),
+        dynamic_rendering,
     })
 }
 
@@ -820,6 +1202,15 @@ impl<ShaderCode> PipelineShader<ShaderCode> {
     }
 }
 
+// Names a pipeline by the entry points of its shaders, for errors.
+pub(crate) fn pipeline_name<ShaderCode>(shaders: &[PipelineShader<ShaderCode>]) -> String {
+    shaders
+        .iter()
+        .map(|shader| shader.desc.entry.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 pub fn create_raster_pipeline(
     device: &Device,
     shaders: &[PipelineShader<Bytes>],
@@ -842,6 +1233,12 @@ pub fn create_raster_pipeline(
         //desc.descriptor_set_layout_flags.unwrap_or(&[]),  // TODO: merge flags
         &desc.descriptor_set_opts,
     );
+    let mut guard = PipelineCreationGuard::new(device, &descriptor_set_layouts);
+
+    let pipeline_error = |err| BackendError::PipelineCreation {
+        name: pipeline_name(shaders),
+        err,
+    };
 
     unsafe {
         let mut layout_create_info =
@@ -861,9 +1258,12 @@ pub fn create_raster_pipeline(
         let pipeline_layout = device
             .raw
             .create_pipeline_layout(&layout_create_info, None)
-            .unwrap();
+            .map_err(pipeline_error)?;
+        guard.pipeline_layout = pipeline_layout;
 
         let entry_names = TempList::new();
+        let specializations = TempList::new();
+        let specialization_infos = TempList::new();
         let shader_stage_create_infos: Vec<_> = shaders
             .iter()
             .map(|desc| {
@@ -873,7 +1273,8 @@ pub fn create_raster_pipeline(
                 let shader_module = device
                     .raw
                     .create_shader_module(&shader_info, None)
-                    .expect("Shader module error");
+                    .map_err(pipeline_error)?;
+                guard.shader_modules.push(shader_module);
 
                 let stage = match desc.desc.stage {
                     ShaderPipelineStage::Vertex => vk::ShaderStageFlags::VERTEX,
@@ -881,23 +1282,30 @@ pub fn create_raster_pipeline(
                     _ => unimplemented!(),
                 };
 
-                vk::PipelineShaderStageCreateInfo::builder()
+                let specialization = specializations
+                    .add(SpecializationData::new(&desc.desc.specialization_constants));
+
+                let mut stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
                     .module(shader_module)
                     .name(entry_names.add(CString::new(desc.desc.entry.as_str()).unwrap()))
-                    .stage(stage)
-                    .build()
+                    .stage(stage);
+
+                if !specialization.is_empty() {
+                    stage_create_info = stage_create_info
+                        .specialization_info(specialization_infos.add(specialization.info()));
+                }
+
+                Ok(stage_create_info.build())
             })
-            .collect();
+            .collect::<Result<_, BackendError>>()?;
 
-        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo {
-            vertex_attribute_description_count: 0,
-            p_vertex_attribute_descriptions: std::ptr::null(),
-            vertex_binding_description_count: 0,
-            p_vertex_binding_descriptions: std::ptr::null(),
-            ..Default::default()
-        };
+        let vertex_bindings = desc.vertex_input.vk_bindings();
+        let vertex_attributes = desc.vertex_input.vk_attributes();
+        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&vertex_bindings)
+            .vertex_attribute_descriptions(&vertex_attributes);
         let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: desc.topology,
             ..Default::default()
         };
 
@@ -908,7 +1316,7 @@ pub fn create_raster_pipeline(
         let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             line_width: 1.0,
-            polygon_mode: vk::PolygonMode::FILL,
+            polygon_mode: desc.polygon_mode,
             cull_mode: if desc.face_cull {
                 ash::vk::CullModeFlags::BACK
             } else {
@@ -930,16 +1338,28 @@ pub fn create_raster_pipeline(
         let depth_state_info = vk::PipelineDepthStencilStateCreateInfo {
             depth_test_enable: 1,
             depth_write_enable: if desc.depth_write { 1 } else { 0 },
-            depth_compare_op: vk::CompareOp::GREATER_OR_EQUAL,
+            depth_compare_op: desc.depth_compare_op,
             front: noop_stencil_state,
             back: noop_stencil_state,
             max_depth_bounds: 1.0,
             ..Default::default()
         };
 
-        let color_attachment_count = desc.render_pass.framebuffer_cache.color_attachment_count;
+        let color_attachment_count =
+            desc.render_pass.subpass_color_attachment_counts[desc.subpass as usize];
 
-        let color_blend_attachment_states = vec![
+        let color_blend_attachment_state = if desc.alpha_blend {
+            vk::PipelineColorBlendAttachmentState {
+                blend_enable: 1,
+                src_color_blend_factor: vk::BlendFactor::ONE,
+                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                alpha_blend_op: vk::BlendOp::ADD,
+                color_write_mask: vk::ColorComponentFlags::all(),
+            }
+        } else {
             vk::PipelineColorBlendAttachmentState {
                 blend_enable: 0,
                 src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
@@ -949,9 +1369,11 @@ pub fn create_raster_pipeline(
                 dst_alpha_blend_factor: vk::BlendFactor::ZERO,
                 alpha_blend_op: vk::BlendOp::ADD,
                 color_write_mask: vk::ColorComponentFlags::all(),
-            };
-            color_attachment_count
-        ];
+            }
+        };
+
+        let color_blend_attachment_states =
+            vec![color_blend_attachment_state; color_attachment_count];
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
             .attachments(&color_blend_attachment_states);
 
@@ -959,7 +1381,31 @@ pub fn create_raster_pipeline(
         let dynamic_state_info =
             vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_state);
 
-        let graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+        // With dynamic rendering, pipelines name the attachment formats instead of a render pass.
+        let dynamic_rendering_formats =
+            desc.render_pass
+                .dynamic_rendering
+                .as_ref()
+                .map(|attachments| {
+                    (
+                        attachments
+                            .color_attachments
+                            .iter()
+                            .map(|a| a.format)
+                            .collect::<ArrayVec<[_; MAX_COLOR_ATTACHMENTS]>>(),
+                        attachments
+                            .depth_attachment
+                            .map_or(vk::Format::UNDEFINED, |a| a.format),
+                    )
+                });
+        let mut pipeline_rendering_info =
+            dynamic_rendering_formats
+                .as_ref()
+                .map(|(color_formats, depth_format)| {
+                    PipelineRenderingCreateInfoKHR::new(color_formats, *depth_format)
+                });
+
+        let mut graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stage_create_infos)
             .vertex_input_state(&vertex_input_state_info)
             .input_assembly_state(&vertex_input_assembly_state_info)
@@ -969,17 +1415,26 @@ pub fn create_raster_pipeline(
             .depth_stencil_state(&depth_state_info)
             .color_blend_state(&color_blend_state)
             .dynamic_state(&dynamic_state_info)
-            .layout(pipeline_layout)
-            .render_pass(desc.render_pass.raw);
+            .layout(pipeline_layout);
+
+        graphic_pipeline_info = if let Some(rendering_info) = &mut pipeline_rendering_info {
+            graphic_pipeline_info.push_next(rendering_info)
+        } else {
+            graphic_pipeline_info
+                .render_pass(desc.render_pass.raw)
+                .subpass(desc.subpass)
+        };
 
         let pipeline = device
             .raw
             .create_graphics_pipelines(
-                vk::PipelineCache::null(),
+                device.pipeline_cache,
                 &[graphic_pipeline_info.build()],
                 None,
             )
-            .expect("Unable to create graphics pipeline")[0];
+            .map_err(|(_, err)| pipeline_error(err))?[0];
+        guard.pipeline = pipeline;
+        guard.finish();
 
         let mut descriptor_pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
         for bindings in set_layout_info.iter() {
@@ -1005,6 +1460,7 @@ pub fn create_raster_pipeline(
                 descriptor_pool_sizes,
                 descriptor_set_layouts,
                 pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+                descriptor_update_templates: Default::default(),
             },
         })
     }
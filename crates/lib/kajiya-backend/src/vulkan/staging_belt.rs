@@ -0,0 +1,180 @@
+use crate::BackendError;
+
+use super::{
+    buffer::{Buffer, BufferDesc},
+    device::Device,
+};
+use ash::vk;
+use std::sync::Arc;
+
+/// Size of the buffers which slices are sub-allocated from. Larger slices get a buffer of their own.
+pub const STAGING_BELT_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+// Free chunks beyond this are destroyed when recycled, so that a burst of uploads,
+// such as when loading a scene, doesn't keep host memory for the rest of the run.
+const MAX_FREE_CHUNKS: usize = 4;
+
+/// A CPU-writable part of a staging buffer, which the GPU may read until the end
+/// of the frame it was allocated in.
+pub struct StagingSlice {
+    pub buffer: Arc<Buffer>,
+    pub offset: u64,
+    pub size: usize,
+}
+
+impl StagingSlice {
+    pub fn mapped_slice_mut(&mut self) -> &mut [u8] {
+        // The belt doesn't hand out the same range again until the GPU is done with it,
+        // so this is the only reference to it.
+        unsafe {
+            let mapped = self.buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut u8;
+            std::slice::from_raw_parts_mut(mapped.add(self.offset as usize), self.size)
+        }
+    }
+
+    /// Copies `data` to the start of the slice.
+    pub fn write(&mut self, data: &[u8]) {
+        self.mapped_slice_mut()[..data.len()].copy_from_slice(data);
+    }
+}
+
+struct StagingChunk {
+    buffer: Arc<Buffer>,
+    used_bytes: usize,
+    // Of the last frame which may read the chunk; see `Device::current_frame_value`.
+    frame_value: u64,
+}
+
+/// Hands out transient slices of host-visible buffers, for data which the GPU reads
+/// within a frame: uploads, UI geometry, and the like.
+///
+/// Slices are sub-allocated linearly from shared chunks, which are recycled once
+/// `Device::completed_frame_value` reaches the frames that could have read them.
+/// The device owns the belt, and recycles it in `begin_frame`; see `Device::allocate_staging`.
+#[derive(Default)]
+pub struct StagingBelt {
+    current: Option<StagingChunk>,
+    in_flight: Vec<StagingChunk>,
+    free: Vec<StagingChunk>,
+}
+
+impl StagingBelt {
+    pub(crate) fn allocate(
+        &mut self,
+        device: &Device,
+        size: usize,
+        alignment: usize,
+        frame_value: u64,
+    ) -> Result<StagingSlice, BackendError> {
+        assert!(alignment > 0);
+
+        self.recycle(device, device.completed_frame_value());
+
+        if size > STAGING_BELT_CHUNK_SIZE {
+            let buffer = Arc::new(Self::create_chunk_buffer(device, size)?);
+            self.in_flight.push(StagingChunk {
+                buffer: buffer.clone(),
+                used_bytes: size,
+                frame_value,
+            });
+
+            return Ok(StagingSlice {
+                buffer,
+                offset: 0,
+                size,
+            });
+        }
+
+        let fits = |chunk: &StagingChunk| {
+            (chunk.used_bytes + alignment - 1) / alignment * alignment + size
+                <= STAGING_BELT_CHUNK_SIZE
+        };
+
+        if !self.current.as_ref().map_or(false, fits) {
+            if let Some(full) = self.current.take() {
+                self.in_flight.push(full);
+            }
+
+            self.current = Some(match self.free.pop() {
+                Some(chunk) => chunk,
+                None => StagingChunk {
+                    buffer: Arc::new(Self::create_chunk_buffer(device, STAGING_BELT_CHUNK_SIZE)?),
+                    used_bytes: 0,
+                    frame_value,
+                },
+            });
+        }
+
+        let chunk = self.current.as_mut().unwrap();
+        let offset = (chunk.used_bytes + alignment - 1) / alignment * alignment;
+        chunk.used_bytes = offset + size;
+        chunk.frame_value = chunk.frame_value.max(frame_value);
+
+        Ok(StagingSlice {
+            buffer: chunk.buffer.clone(),
+            offset: offset as u64,
+            size,
+        })
+    }
+
+    /// Makes the chunks the GPU is done with available again.
+    pub(crate) fn recycle(&mut self, device: &Device, completed_frame_value: u64) {
+        if let Some(current) = &mut self.current {
+            if current.frame_value <= completed_frame_value {
+                current.used_bytes = 0;
+            }
+        }
+
+        let (completed, in_flight) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|chunk| chunk.frame_value <= completed_frame_value);
+        self.in_flight = in_flight;
+
+        for mut chunk in completed {
+            if chunk.buffer.desc.size == STAGING_BELT_CHUNK_SIZE
+                && self.free.len() < MAX_FREE_CHUNKS
+            {
+                chunk.used_bytes = 0;
+                self.free.push(chunk);
+            } else {
+                Self::destroy_chunk(device, chunk);
+            }
+        }
+    }
+
+    /// Destroys all the chunks. The GPU must be idle.
+    pub(crate) fn release_all(&mut self, device: &Device) {
+        let chunks = self
+            .current
+            .take()
+            .into_iter()
+            .chain(self.in_flight.drain(..))
+            .chain(self.free.drain(..));
+
+        for chunk in chunks {
+            Self::destroy_chunk(device, chunk);
+        }
+    }
+
+    fn create_chunk_buffer(device: &Device, size: usize) -> Result<Buffer, BackendError> {
+        device.create_buffer(
+            BufferDesc::new_cpu_to_gpu(
+                size,
+                vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::UNIFORM_BUFFER
+                    | vk::BufferUsageFlags::INDEX_BUFFER
+                    | vk::BufferUsageFlags::VERTEX_BUFFER,
+            ),
+            "staging belt",
+            None,
+        )
+    }
+
+    fn destroy_chunk(device: &Device, chunk: StagingChunk) {
+        // Slices kept past their frame keep the buffer alive, and leak it.
+        if let Ok(buffer) = Arc::try_unwrap(chunk.buffer) {
+            device.immediate_destroy_buffer(buffer);
+        }
+    }
+}
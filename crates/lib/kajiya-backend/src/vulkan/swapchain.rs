@@ -1,4 +1,4 @@
-use super::{device::Device, surface::Surface};
+use super::{device::Device, resource_tracking::ResourceKind, surface::Surface};
 use anyhow::Result;
 use ash::{extensions::khr, vk};
 #[allow(unused_imports)]
@@ -73,8 +73,18 @@ impl Swapchain {
         log::info!("Swapchain image count: {}", desired_image_count);
 
         //dbg!(&surface_capabilities);
+        // Wayland leaves the extent up to the application.
         let surface_resolution = match surface_capabilities.current_extent.width {
-            std::u32::MAX => desc.dims,
+            std::u32::MAX => vk::Extent2D {
+                width: desc.dims.width.clamp(
+                    surface_capabilities.min_image_extent.width,
+                    surface_capabilities.max_image_extent.width,
+                ),
+                height: desc.dims.height.clamp(
+                    surface_capabilities.min_image_extent.height,
+                    surface_capabilities.max_image_extent.height,
+                ),
+            },
             _ => surface_capabilities.current_extent,
         };
 
@@ -82,6 +92,22 @@ impl Swapchain {
             anyhow::bail!("Swapchain resolution cannot be zero");
         }
 
+        if surface_resolution != desc.dims {
+            log::warn!(
+                "Requested a {}x{} swapchain, but the surface is {}x{}",
+                desc.dims.width,
+                desc.dims.height,
+                surface_resolution.width,
+                surface_resolution.height
+            );
+        }
+
+        // The images and `extent` need to match what was actually created.
+        let desc = SwapchainDesc {
+            dims: surface_resolution,
+            ..desc
+        };
+
         let present_mode_preference = if desc.vsync {
             vec![vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO]
         } else {
@@ -167,13 +193,15 @@ impl Swapchain {
                         image_type: crate::ImageType::Tex2d,
                         usage: vk::ImageUsageFlags::STORAGE,
                         flags: vk::ImageCreateFlags::empty(),
-                        format: vk::Format::B8G8R8A8_UNORM,
+                        format: desc.format.format,
                         extent: [desc.dims.width, desc.dims.height, 0],
                         tiling: vk::ImageTiling::OPTIMAL,
                         mip_levels: 1,
                         array_elements: 1,
                     },
                     views: Default::default(),
+                    allocation: None,
+                    dedicated_memory: None,
                 })
             })
             .collect();
@@ -219,6 +247,11 @@ impl Swapchain {
         [self.desc.dims.width, self.desc.dims.height]
     }
 
+    /// Whether the images are presented as PQ-encoded Rec.2020, rather than sRGB.
+    pub fn is_hdr10(&self) -> bool {
+        self.desc.format.color_space == vk::ColorSpaceKHR::HDR10_ST2084_EXT
+    }
+
     pub fn acquire_next_image(
         &mut self,
     ) -> std::result::Result<SwapchainImage, SwapchainAcquireImageErr> {
@@ -269,6 +302,8 @@ impl Swapchain {
             .swapchains(std::slice::from_ref(&self.raw))
             .image_indices(std::slice::from_ref(&image.image_index));
 
+        let _queue_lock = self.device.universal_queue.submit_lock.lock();
+
         unsafe {
             match self
                 .fns
@@ -292,6 +327,14 @@ impl Swapchain {
 impl Drop for Swapchain {
     fn drop(&mut self) {
         unsafe {
+            // The images belong to the swapchain, but their views were created by `Image::view`.
+            for image in &self.images {
+                for (_, view) in image.views.lock().drain() {
+                    self.device.raw.destroy_image_view(view, None);
+                    self.device.track_destroyed(ResourceKind::ImageView, view);
+                }
+            }
+
             self.fns.destroy_swapchain(self.raw, None);
         }
     }
@@ -0,0 +1,335 @@
+use anyhow::{bail, Result};
+use ash::vk;
+use byte_slice_cast::AsSliceOf as _;
+use rspirv::dr::{Instruction, Operand};
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct VertexBindingDesc {
+    pub binding: u32,
+    /// Bytes between consecutive elements in the buffer
+    pub stride: u32,
+    pub input_rate: vk::VertexInputRate,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct VertexAttributeDesc {
+    /// Of the vertex shader input
+    pub location: u32,
+    pub binding: u32,
+    pub format: vk::Format,
+    /// Bytes into each element of the binding
+    pub offset: u32,
+}
+
+/// The vertex buffers which a raster pipeline reads through fixed-function vertex input,
+/// and how their contents map to the inputs of the vertex shader.
+///
+/// Empty by default, for vertex shaders which pull their data from storage buffers.
+/// Checked against the inputs of the vertex shader when the pipeline is built.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct VertexInputDesc {
+    pub bindings: Vec<VertexBindingDesc>,
+    pub attributes: Vec<VertexAttributeDesc>,
+}
+
+impl VertexInputDesc {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a buffer binding which advances once per vertex.
+    pub fn vertex_binding(self, binding: u32, stride: u32) -> Self {
+        self.binding(binding, stride, vk::VertexInputRate::VERTEX)
+    }
+
+    /// Adds a buffer binding which advances once per instance.
+    pub fn instance_binding(self, binding: u32, stride: u32) -> Self {
+        self.binding(binding, stride, vk::VertexInputRate::INSTANCE)
+    }
+
+    pub fn binding(mut self, binding: u32, stride: u32, input_rate: vk::VertexInputRate) -> Self {
+        self.bindings.push(VertexBindingDesc {
+            binding,
+            stride,
+            input_rate,
+        });
+        self
+    }
+
+    /// Adds an attribute read from the binding added last.
+    pub fn attribute(mut self, location: u32, format: vk::Format, offset: u32) -> Self {
+        let binding = self
+            .bindings
+            .last()
+            .expect("vertex attributes must follow their binding")
+            .binding;
+
+        self.attributes.push(VertexAttributeDesc {
+            location,
+            binding,
+            format,
+            offset,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty() && self.attributes.is_empty()
+    }
+
+    pub(crate) fn vk_bindings(&self) -> Vec<vk::VertexInputBindingDescription> {
+        self.bindings
+            .iter()
+            .map(|binding| vk::VertexInputBindingDescription {
+                binding: binding.binding,
+                stride: binding.stride,
+                input_rate: binding.input_rate,
+            })
+            .collect()
+    }
+
+    pub(crate) fn vk_attributes(&self) -> Vec<vk::VertexInputAttributeDescription> {
+        self.attributes
+            .iter()
+            .map(|attribute| vk::VertexInputAttributeDescription {
+                location: attribute.location,
+                binding: attribute.binding,
+                format: attribute.format,
+                offset: attribute.offset,
+            })
+            .collect()
+    }
+
+    /// Checks that the bindings and attributes are consistent, and that they provide
+    /// every input of the vertex shader `entry` in `spirv`, with a matching numeric type.
+    pub(crate) fn validate(&self, shader_name: &str, spirv: &[u8], entry: &str) -> Result<()> {
+        let mut errors = Vec::new();
+
+        let mut bindings = HashSet::new();
+        for binding in &self.bindings {
+            if !bindings.insert(binding.binding) {
+                errors.push(format!("binding {} is declared twice", binding.binding));
+            }
+        }
+
+        let mut locations = HashSet::new();
+        for attribute in &self.attributes {
+            if !locations.insert(attribute.location) {
+                errors.push(format!("location {} is declared twice", attribute.location));
+            }
+
+            if !bindings.contains(&attribute.binding) {
+                errors.push(format!(
+                    "location {} reads the undeclared binding {}",
+                    attribute.location, attribute.binding
+                ));
+            }
+        }
+
+        for input in reflect_vertex_inputs(spirv, entry) {
+            match self
+                .attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+            {
+                Some(attribute) => {
+                    if let (Some(expected), Some(provided)) =
+                        (input.numeric_type, format_numeric_type(attribute.format))
+                    {
+                        if expected != provided {
+                            errors.push(format!(
+                                "location {} is {:?} in the shader, but the format is {:?}",
+                                input.location, expected, attribute.format
+                            ));
+                        }
+                    }
+                }
+                None => errors.push(format!(
+                    "location {} is read by the shader, but has no attribute",
+                    input.location
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(
+                "The vertex input of {} doesn't match the shader:\n{}",
+                shader_name,
+                errors.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum NumericType {
+    Float,
+    Sint,
+    Uint,
+}
+
+struct VertexShaderInput {
+    location: u32,
+    // `None` for matrices, arrays, and other types which aren't checked
+    numeric_type: Option<NumericType>,
+}
+
+// SPIR-V opcodes, decorations, and enumerants
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+
+/// The user-defined inputs of the vertex shader entry point, skipping the built-in ones.
+fn reflect_vertex_inputs(spirv: &[u8], entry: &str) -> Vec<VertexShaderInput> {
+    let mut loader = rspirv::dr::Loader::new();
+    rspirv::binary::parse_words(spirv.as_slice_of::<u32>().unwrap(), &mut loader).unwrap();
+    let module = loader.module();
+
+    let find_global = |id: u32| {
+        module
+            .global_inst_iter()
+            .find(|inst| inst.result_id == Some(id))
+    };
+
+    let decoration_of = |target: u32, decoration: u32| -> Option<Option<u32>> {
+        module.global_inst_iter().find_map(|inst| {
+            if inst.class.opcode as u32 != OP_DECORATE {
+                return None;
+            }
+
+            match inst.operands.as_slice() {
+                [Operand::IdRef(id), Operand::Decoration(dec), rest @ ..]
+                    if *id == target && *dec as u32 == decoration =>
+                {
+                    Some(match rest {
+                        [Operand::LiteralInt32(value), ..] => Some(*value),
+                        _ => None,
+                    })
+                }
+                _ => None,
+            }
+        })
+    };
+
+    let interface: Vec<u32> = module
+        .entry_points
+        .iter()
+        .find_map(|inst| match inst.operands.as_slice() {
+            [Operand::ExecutionModel(model), Operand::IdRef(_), Operand::LiteralString(name), interface @ ..]
+                if inst.class.opcode as u32 == OP_ENTRY_POINT
+                    && *model as u32 == EXECUTION_MODEL_VERTEX
+                    && name.as_str() == entry =>
+            {
+                Some(
+                    interface
+                        .iter()
+                        .filter_map(|operand| match operand {
+                            Operand::IdRef(id) => Some(*id),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let numeric_type = |type_inst: &Instruction| -> Option<NumericType> {
+        let scalar = if type_inst.class.opcode as u32 == OP_TYPE_VECTOR {
+            match type_inst.operands.as_slice() {
+                [Operand::IdRef(component), ..] => find_global(*component)?,
+                _ => return None,
+            }
+        } else {
+            type_inst
+        };
+
+        match (scalar.class.opcode as u32, scalar.operands.as_slice()) {
+            (OP_TYPE_FLOAT, _) => Some(NumericType::Float),
+            (OP_TYPE_INT, [_, Operand::LiteralInt32(0)]) => Some(NumericType::Uint),
+            (OP_TYPE_INT, [_, Operand::LiteralInt32(_)]) => Some(NumericType::Sint),
+            _ => None,
+        }
+    };
+
+    interface
+        .into_iter()
+        .filter_map(|id| {
+            let variable = find_global(id)?;
+            match variable.operands.as_slice() {
+                [Operand::StorageClass(class), ..]
+                    if variable.class.opcode as u32 == OP_VARIABLE
+                        && *class as u32 == STORAGE_CLASS_INPUT => {}
+                _ => return None,
+            }
+
+            if decoration_of(id, DECORATION_BUILT_IN).is_some() {
+                return None;
+            }
+
+            let location = decoration_of(id, DECORATION_LOCATION)??;
+
+            let pointee = find_global(variable.result_type?).and_then(|pointer| {
+                match pointer.operands.as_slice() {
+                    [_, Operand::IdRef(pointee)]
+                        if pointer.class.opcode as u32 == OP_TYPE_POINTER =>
+                    {
+                        find_global(*pointee)
+                    }
+                    _ => None,
+                }
+            });
+
+            Some(VertexShaderInput {
+                location,
+                numeric_type: pointee.and_then(numeric_type),
+            })
+        })
+        .collect()
+}
+
+/// How the shader sees the components of `format`, for the formats commonly used for vertices.
+fn format_numeric_type(format: vk::Format) -> Option<NumericType> {
+    match format {
+        vk::Format::R32_SFLOAT
+        | vk::Format::R32G32_SFLOAT
+        | vk::Format::R32G32B32_SFLOAT
+        | vk::Format::R32G32B32A32_SFLOAT
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SNORM
+        | vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SNORM
+        | vk::Format::A2B10G10R10_UNORM_PACK32 => Some(NumericType::Float),
+        vk::Format::R32_UINT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32B32_UINT
+        | vk::Format::R32G32B32A32_UINT
+        | vk::Format::R16G16_UINT
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R8G8B8A8_UINT => Some(NumericType::Uint),
+        vk::Format::R32_SINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32B32_SINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R16G16_SINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R8G8B8A8_SINT => Some(NumericType::Sint),
+        _ => None,
+    }
+}
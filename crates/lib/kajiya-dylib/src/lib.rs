@@ -0,0 +1,10 @@
+//! Links kajiya as a Rust `dylib`, so that an application and its custom pass plugins share
+//! one copy of it; see `kajiya::renderers::custom_pass_plugins`.
+//!
+//! Depend on this crate next to kajiya, and `use kajiya_dylib;` so that it gets linked. Plugins
+//! must be built in the application's workspace, to load the same library as the application.
+
+// Force linking of kajiya
+#[allow(unused_imports)]
+#[allow(clippy::single_component_path_imports)]
+use kajiya;
@@ -0,0 +1,432 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use kajiya::{
+    backend::{
+        ash::vk, vk_sync::AccessType, vulkan::shader::*, Device, Image, ImageDesc,
+        ImageSubResourceData, ImageViewDesc,
+    },
+    rg::{self, BindRgRef, IntoRenderPassPipelineBinding},
+    ui_renderer::UiRenderGraphCallback,
+};
+
+use crate::egui_input::EguiInput;
+
+/// Position and UV in points, then a premultiplied RGBA8 color; matches `egui_vs.hlsl`.
+type GpuVertex = [u32; 5];
+
+/// One `egui::ClippedMesh` within the shared vertex and index buffers.
+struct EguiDraw {
+    /// In physical pixels: offset and extent
+    scissor: vk::Rect2D,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+    /// Index into the textures used in the frame
+    texture_idx: usize,
+}
+
+/// Renders egui through a raster pass in the render graph, on top of any other UI.
+/// The pass is returned by `finish_frame`, and should be added to `UiRenderer::ui_graph_passes`.
+///
+/// Textures registered via `register_texture` are sampled as they are, so they should
+/// contain premultiplied colors in gamma space, like the ones egui itself produces.
+/// The clipboard is not hooked up.
+pub struct EguiBackend {
+    device: Arc<Device>,
+    ctx: egui::CtxRef,
+    input: EguiInput,
+    start_time: Instant,
+    render_pass: Arc<RenderPass>,
+
+    /// Version of the font atlas, and the image it was uploaded to
+    font_texture: Option<(u64, Arc<Image>)>,
+    user_textures: HashMap<u64, Arc<Image>>,
+    next_user_texture_id: u64,
+}
+
+impl EguiBackend {
+    pub fn new(device: Arc<Device>) -> Self {
+        let render_pass = create_render_pass(
+            &*device,
+            RenderPassDesc {
+                // Same as the ImGui target, so that egui can be drawn over it.
+                color_attachments: &[RenderPassAttachmentDesc::new(vk::Format::R8G8B8A8_UNORM)],
+                depth_attachment: None,
+                subpasses: &[],
+            },
+        );
+
+        Self {
+            device,
+            ctx: Default::default(),
+            input: Default::default(),
+            start_time: Instant::now(),
+            render_pass,
+            font_texture: None,
+            user_textures: Default::default(),
+            next_user_texture_id: 0,
+        }
+    }
+
+    pub fn ctx(&self) -> &egui::CtxRef {
+        &self.ctx
+    }
+
+    /// Makes `image` available to egui widgets via the returned ID.
+    pub fn register_texture(&mut self, image: Arc<Image>) -> egui::TextureId {
+        let id = self.next_user_texture_id;
+        self.next_user_texture_id += 1;
+        self.user_textures.insert(id, image);
+        egui::TextureId::User(id)
+    }
+
+    pub fn unregister_texture(&mut self, id: egui::TextureId) {
+        if let egui::TextureId::User(id) = id {
+            self.user_textures.remove(&id);
+        }
+    }
+
+    pub fn handle_event(
+        &mut self,
+        window: &winit::window::Window,
+        event: &winit::event::Event<'_, ()>,
+    ) {
+        self.input.handle_event(window.scale_factor() as f32, event);
+    }
+
+    /// Whether egui is using the mouse, e.g. because it's hovering over a window.
+    pub fn wants_pointer_input(&self) -> bool {
+        self.ctx.wants_pointer_input()
+    }
+
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.ctx.wants_keyboard_input()
+    }
+
+    pub fn begin_frame(&mut self, window: &winit::window::Window, dt: f32) -> egui::CtxRef {
+        let pixels_per_point = window.scale_factor() as f32;
+        let size = window.inner_size();
+
+        let mut raw_input = std::mem::take(&mut self.input.raw);
+        raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            Default::default(),
+            egui::vec2(size.width as f32, size.height as f32) / pixels_per_point,
+        ));
+        raw_input.pixels_per_point = Some(pixels_per_point);
+        raw_input.time = Some(self.start_time.elapsed().as_secs_f64());
+        raw_input.predicted_dt = dt;
+
+        // Modifiers persist across frames, unlike the events.
+        self.input.raw.modifiers = raw_input.modifiers;
+
+        self.ctx.begin_frame(raw_input);
+        self.ctx.clone()
+    }
+
+    pub fn finish_frame(
+        &mut self,
+        window: &winit::window::Window,
+    ) -> Option<UiRenderGraphCallback> {
+        let (output, shapes) = self.ctx.end_frame();
+        window.set_cursor_icon(translate_cursor_icon(output.cursor_icon));
+
+        let meshes = self.ctx.tessellate(shapes);
+        self.update_font_texture();
+
+        let pixels_per_point = window.scale_factor() as f32;
+        let size = window.inner_size();
+        let target_extent = [size.width, size.height];
+
+        let mut vertices: Vec<GpuVertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut textures: Vec<Arc<Image>> = Vec::new();
+        let mut draws: Vec<EguiDraw> = Vec::new();
+
+        for egui::ClippedMesh(clip_rect, mesh) in meshes {
+            if mesh.indices.is_empty() {
+                continue;
+            }
+
+            let texture = match self.texture(mesh.texture_id) {
+                Some(texture) => texture,
+                None => {
+                    log::warn!("Unknown egui texture {:?}", mesh.texture_id);
+                    continue;
+                }
+            };
+
+            let scissor = match clip_rect_to_scissor(clip_rect, pixels_per_point, target_extent) {
+                Some(scissor) => scissor,
+                None => continue,
+            };
+
+            let texture_idx = textures
+                .iter()
+                .position(|t| Arc::ptr_eq(t, &texture))
+                .unwrap_or_else(|| {
+                    textures.push(texture);
+                    textures.len() - 1
+                });
+
+            draws.push(EguiDraw {
+                scissor,
+                first_index: indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_offset: vertices.len() as i32,
+                texture_idx,
+            });
+
+            indices.extend_from_slice(&mesh.indices);
+            vertices.extend(mesh.vertices.iter().map(|v| {
+                [
+                    v.pos.x.to_bits(),
+                    v.pos.y.to_bits(),
+                    v.uv.x.to_bits(),
+                    v.uv.y.to_bits(),
+                    u32::from_le_bytes([v.color.r(), v.color.g(), v.color.b(), v.color.a()]),
+                ]
+            }));
+        }
+
+        if draws.is_empty() {
+            return None;
+        }
+
+        // The shader indexes the vertices from the start of the staging buffer,
+        // so they need to be at a whole number of vertices into it.
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let mut vertex_staging = self
+            .device
+            .allocate_staging(vertex_bytes.len(), std::mem::size_of::<GpuVertex>())
+            .expect("allocate_staging");
+        vertex_staging.write(vertex_bytes);
+
+        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        let mut index_staging = self
+            .device
+            .allocate_staging(index_bytes.len(), std::mem::size_of::<u32>())
+            .expect("allocate_staging");
+        index_staging.write(index_bytes);
+
+        let base_vertex = (vertex_staging.offset / std::mem::size_of::<GpuVertex>() as u64) as i32;
+        for draw in &mut draws {
+            draw.vertex_offset += base_vertex;
+        }
+
+        let vertex_buffer = vertex_staging.buffer;
+        let index_buffer = index_staging.buffer;
+        let index_buffer_offset = index_staging.offset;
+        let render_pass = self.render_pass.clone();
+        let screen_size_points = [
+            target_extent[0] as f32 / pixels_per_point,
+            target_extent[1] as f32 / pixels_per_point,
+        ];
+
+        Some(Box::new(
+            move |rg: &mut rg::RenderGraph, target: &mut rg::Handle<Image>| {
+                let vertex_buffer = rg.import(vertex_buffer, AccessType::Nothing);
+                let index_buffer = rg.import(index_buffer, AccessType::Nothing);
+                let textures: Vec<rg::Handle<Image>> = textures
+                    .into_iter()
+                    .map(|texture| {
+                        rg.import(
+                            texture,
+                            AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+                        )
+                    })
+                    .collect();
+
+                let mut pass = rg.add_pass("egui");
+
+                let pipeline = pass.register_raster_pipeline(
+                    &[
+                        PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                            .hlsl_source("/shaders/egui/egui_vs.hlsl")
+                            .build()
+                            .unwrap(),
+                        PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                            .hlsl_source("/shaders/egui/egui_ps.hlsl")
+                            .build()
+                            .unwrap(),
+                    ],
+                    RasterPipelineDesc::builder()
+                        .render_pass(render_pass.clone())
+                        .face_cull(false)
+                        .depth_write(false)
+                        .alpha_blend(true)
+                        .push_constants_bytes(std::mem::size_of::<[f32; 2]>()),
+                );
+
+                let vertex_buffer_ref =
+                    pass.read(&vertex_buffer, AccessType::VertexShaderReadOther);
+                let index_buffer_ref = pass.read(&index_buffer, AccessType::IndexBuffer);
+                let texture_refs: Vec<_> = textures
+                    .iter()
+                    .map(|texture| {
+                        pass.read(
+                            texture,
+                            AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer,
+                        )
+                    })
+                    .collect();
+
+                let target_ref = pass.raster(target, AccessType::ColorAttachmentWrite);
+
+                pass.render(move |api| {
+                    let [width, height, _] = target_ref.desc().extent;
+
+                    api.begin_render_pass(
+                        &*render_pass,
+                        [width, height],
+                        &[(target_ref, &ImageViewDesc::default())],
+                        None,
+                    )?;
+
+                    api.set_default_view_and_scissor([width, height]);
+
+                    let raw_device = &api.device().raw;
+                    let cb = api.cb.raw;
+
+                    unsafe {
+                        raw_device.cmd_bind_index_buffer(
+                            cb,
+                            api.resources.buffer(index_buffer_ref).raw,
+                            index_buffer_offset,
+                            vk::IndexType::UINT32,
+                        );
+                    }
+
+                    // Rebound whenever the texture changes; draws are in egui's order,
+                    // which needs to be kept for correct layering.
+                    let mut bound_texture_idx = None;
+
+                    for draw in &draws {
+                        if bound_texture_idx != Some(draw.texture_idx) {
+                            let pipeline =
+                                api.bind_raster_pipeline(pipeline.into_binding().descriptor_set(
+                                    0,
+                                    &[
+                                        vertex_buffer_ref.bind(),
+                                        texture_refs[draw.texture_idx].bind(),
+                                    ],
+                                ))?;
+
+                            pipeline.push_constants(
+                                cb,
+                                vk::ShaderStageFlags::ALL_GRAPHICS,
+                                0,
+                                bytemuck::cast_slice(&screen_size_points),
+                            );
+
+                            bound_texture_idx = Some(draw.texture_idx);
+                        }
+
+                        api.set_scissor(draw.scissor);
+
+                        unsafe {
+                            raw_device.cmd_draw_indexed(
+                                cb,
+                                draw.index_count,
+                                1,
+                                draw.first_index,
+                                draw.vertex_offset,
+                                0,
+                            );
+                        }
+                    }
+
+                    api.end_render_pass();
+
+                    Ok(())
+                });
+            },
+        ))
+    }
+
+    fn texture(&self, id: egui::TextureId) -> Option<Arc<Image>> {
+        match id {
+            egui::TextureId::Egui => self.font_texture.as_ref().map(|(_, image)| image.clone()),
+            egui::TextureId::User(id) => self.user_textures.get(&id).cloned(),
+        }
+    }
+
+    /// Re-uploads the font atlas whenever egui rebuilds it.
+    fn update_font_texture(&mut self) {
+        let font = self.ctx.texture();
+
+        if matches!(self.font_texture, Some((version, _)) if version == font.version) {
+            return;
+        }
+
+        // Coverage in the atlas becomes premultiplied white.
+        let pixels: Vec<u8> = font.pixels.iter().flat_map(|&a| [a, a, a, a]).collect();
+        let size = [font.width as u32, font.height as u32];
+
+        let image = self
+            .device
+            .create_image(
+                ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, size)
+                    .usage(vk::ImageUsageFlags::SAMPLED),
+                vec![ImageSubResourceData {
+                    data: &pixels,
+                    row_pitch: font.width * 4,
+                    slice_pitch: pixels.len(),
+                }],
+            )
+            .expect("create_image");
+
+        self.font_texture = Some((font.version, Arc::new(image)));
+    }
+}
+
+/// Converts a clip rectangle in points to a scissor in pixels within `target_extent`.
+/// Returns `None` if nothing would be visible.
+fn clip_rect_to_scissor(
+    clip_rect: egui::Rect,
+    pixels_per_point: f32,
+    target_extent: [u32; 2],
+) -> Option<vk::Rect2D> {
+    let min_x = (clip_rect.min.x * pixels_per_point).round().max(0.0) as u32;
+    let min_y = (clip_rect.min.y * pixels_per_point).round().max(0.0) as u32;
+    let max_x =
+        ((clip_rect.max.x * pixels_per_point).round().max(0.0) as u32).min(target_extent[0]);
+    let max_y =
+        ((clip_rect.max.y * pixels_per_point).round().max(0.0) as u32).min(target_extent[1]);
+
+    (max_x > min_x && max_y > min_y).then(|| vk::Rect2D {
+        offset: vk::Offset2D {
+            x: min_x as i32,
+            y: min_y as i32,
+        },
+        extent: vk::Extent2D {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    })
+}
+
+fn translate_cursor_icon(icon: egui::CursorIcon) -> winit::window::CursorIcon {
+    use egui::CursorIcon as E;
+    use winit::window::CursorIcon as W;
+
+    match icon {
+        E::PointingHand => W::Hand,
+        E::Text => W::Text,
+        E::VerticalText => W::VerticalText,
+        E::Crosshair => W::Crosshair,
+        E::Move | E::AllScroll => W::Move,
+        E::Grab => W::Grab,
+        E::Grabbing => W::Grabbing,
+        E::NotAllowed | E::NoDrop => W::NotAllowed,
+        E::ResizeHorizontal => W::EwResize,
+        E::ResizeVertical => W::NsResize,
+        E::ResizeNeSw => W::NeswResize,
+        E::ResizeNwSe => W::NwseResize,
+        E::Wait => W::Wait,
+        E::Progress => W::Progress,
+        E::Help => W::Help,
+        E::ZoomIn => W::ZoomIn,
+        E::ZoomOut => W::ZoomOut,
+        _ => W::Default,
+    }
+}
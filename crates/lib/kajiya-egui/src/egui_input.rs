@@ -0,0 +1,172 @@
+use egui::{Event as EguiEvent, Key, Modifiers, PointerButton, Pos2, RawInput};
+use winit::event::{
+    ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Points scrolled per line of mouse wheel motion
+const SCROLL_POINTS_PER_LINE: f32 = 50.0;
+
+/// Accumulates winit events into egui's `RawInput` between frames.
+#[derive(Default)]
+pub(crate) struct EguiInput {
+    pub raw: RawInput,
+    pointer_pos: Option<Pos2>,
+    modifiers: Modifiers,
+}
+
+impl EguiInput {
+    pub fn handle_event(&mut self, pixels_per_point: f32, event: &Event<'_, ()>) {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = Pos2::new(
+                    position.x as f32 / pixels_per_point,
+                    position.y as f32 / pixels_per_point,
+                );
+                self.pointer_pos = Some(pos);
+                self.raw.events.push(EguiEvent::PointerMoved(pos));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer_pos = None;
+                self.raw.events.push(EguiEvent::PointerGone);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let (Some(pos), Some(button)) = (self.pointer_pos, translate_button(*button)) {
+                    self.raw.events.push(EguiEvent::PointerButton {
+                        pos,
+                        button,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.raw.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        egui::vec2(*x, *y) * SCROLL_POINTS_PER_LINE
+                    }
+                    MouseScrollDelta::PixelDelta(pos) => {
+                        egui::vec2(pos.x as f32, pos.y as f32) / pixels_per_point
+                    }
+                };
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = translate_modifiers(*state);
+                self.raw.modifiers = self.modifiers;
+            }
+            WindowEvent::ReceivedCharacter(ch) => {
+                // Control characters come in as key events instead.
+                if !ch.is_control() {
+                    self.raw.events.push(EguiEvent::Text(ch.to_string()));
+                }
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let pressed = input.state == ElementState::Pressed;
+
+                match input.virtual_keycode {
+                    Some(VirtualKeyCode::C) if pressed && self.modifiers.command => {
+                        self.raw.events.push(EguiEvent::Copy)
+                    }
+                    Some(VirtualKeyCode::X) if pressed && self.modifiers.command => {
+                        self.raw.events.push(EguiEvent::Cut)
+                    }
+                    Some(key) => {
+                        if let Some(key) = translate_key(key) {
+                            self.raw.events.push(EguiEvent::Key {
+                                key,
+                                pressed,
+                                modifiers: self.modifiers,
+                            });
+                        }
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn translate_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn translate_modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: cfg!(target_os = "macos") && state.logo(),
+        command: if cfg!(target_os = "macos") {
+            state.logo()
+        } else {
+            state.ctrl()
+        },
+    }
+}
+
+fn translate_key(key: VirtualKeyCode) -> Option<Key> {
+    Some(match key {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Key::Num0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Key::Num1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Key::Num2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Key::Num3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Key::Num4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Key::Num5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Key::Num6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Key::Num7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Key::Num8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Key::Num9,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::B => Key::B,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::D => Key::D,
+        VirtualKeyCode::E => Key::E,
+        VirtualKeyCode::F => Key::F,
+        VirtualKeyCode::G => Key::G,
+        VirtualKeyCode::H => Key::H,
+        VirtualKeyCode::I => Key::I,
+        VirtualKeyCode::J => Key::J,
+        VirtualKeyCode::K => Key::K,
+        VirtualKeyCode::L => Key::L,
+        VirtualKeyCode::M => Key::M,
+        VirtualKeyCode::N => Key::N,
+        VirtualKeyCode::O => Key::O,
+        VirtualKeyCode::P => Key::P,
+        VirtualKeyCode::Q => Key::Q,
+        VirtualKeyCode::R => Key::R,
+        VirtualKeyCode::S => Key::S,
+        VirtualKeyCode::T => Key::T,
+        VirtualKeyCode::U => Key::U,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::W => Key::W,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
@@ -0,0 +1,5 @@
+mod egui_backend;
+mod egui_input;
+
+pub use egui;
+pub use egui_backend::*;
@@ -37,6 +37,11 @@ impl ImGuiBackend {
     ) -> Self {
         setup_imgui_style(imgui);
 
+        #[cfg(feature = "docking")]
+        {
+            imgui.io_mut().config_flags |= imgui::ConfigFlags::DOCKING_ENABLE;
+        }
+
         let mut imgui_platform = WinitPlatform::init(imgui);
         imgui_platform.attach_window(imgui.io_mut(), window, HiDpiMode::Locked(1.0));
 
@@ -340,6 +345,21 @@ fn create_imgui_framebuffer(
     (fb, Arc::new(tex))
 }
 
+/// Makes the whole window a dock space, leaving its central node transparent,
+/// so that panels can be docked to the edges of the rendered image.
+/// Must be called at the start of the frame, before any other windows are built.
+#[cfg(feature = "docking")]
+pub fn dockspace_over_main_viewport(_ui: &imgui::Ui<'_>) {
+    // No safe wrapper in imgui-rs 0.7; the `Ui` reference ensures a frame is active.
+    unsafe {
+        imgui::sys::igDockSpaceOverViewport(
+            imgui::sys::igGetMainViewport(),
+            imgui::sys::ImGuiDockNodeFlags_PassthruCentralNode as _,
+            std::ptr::null(),
+        );
+    }
+}
+
 // Based on https://github.com/ocornut/imgui/issues/707#issuecomment-430613104
 fn setup_imgui_style(ctx: &mut imgui::Context) {
     let hi = |v: f32| [0.502, 0.075, 0.256, v];
@@ -0,0 +1,144 @@
+//! Opt-in record of the barriers recorded by graph execution, for debugging
+//! synchronization issues. Disabled by default; when disabled, the only cost
+//! is an atomic load per transition.
+
+use kajiya_backend::{ash::vk, vk_sync, vulkan::barrier::get_access_info};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[derive(Clone, Debug)]
+pub struct BarrierLogEntry {
+    /// Counts graph executions since logging was enabled.
+    pub execution: u64,
+    /// Index of the resource within its graph, and its kind, size and format.
+    pub resource: String,
+    pub prev_access: vk_sync::AccessType,
+    pub next_access: vk_sync::AccessType,
+    /// `UNDEFINED` for buffers and acceleration structures.
+    pub prev_layout: vk::ImageLayout,
+    pub next_layout: vk::ImageLayout,
+    /// The previous pass to transition the resource in this execution, if any.
+    pub src_pass: Option<String>,
+    pub dst_pass: String,
+}
+
+impl std::fmt::Display for BarrierLogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {:?} ({:?}) -> {:?} ({:?}); {} -> {}",
+            self.execution,
+            self.resource,
+            self.prev_access,
+            self.prev_layout,
+            self.next_access,
+            self.next_layout,
+            self.src_pass.as_deref().unwrap_or("(graph start)"),
+            self.dst_pass,
+        )
+    }
+}
+
+struct BarrierLog {
+    capacity: usize,
+    entries: VecDeque<BarrierLogEntry>,
+    execution: u64,
+    last_pass_by_resource: HashMap<usize, String>,
+}
+
+const DEFAULT_CAPACITY: usize = 4096;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref BARRIER_LOG: Mutex<BarrierLog> = Mutex::new(BarrierLog {
+        capacity: DEFAULT_CAPACITY,
+        entries: Default::default(),
+        execution: 0,
+        last_pass_by_resource: Default::default(),
+    });
+}
+
+pub fn set_barrier_logging_enabled(enabled: bool) {
+    let was_enabled = ENABLED.swap(enabled, Ordering::Relaxed);
+    if enabled && !was_enabled {
+        let mut log = BARRIER_LOG.lock();
+        log.entries.clear();
+        log.execution = 0;
+    }
+}
+
+pub fn is_barrier_logging_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets how many of the latest barriers are kept.
+pub fn set_barrier_log_capacity(capacity: usize) {
+    let mut log = BARRIER_LOG.lock();
+    log.capacity = capacity.max(1);
+    while log.entries.len() > log.capacity {
+        log.entries.pop_front();
+    }
+}
+
+/// The latest barriers, oldest first.
+pub fn barrier_log_entries() -> Vec<BarrierLogEntry> {
+    BARRIER_LOG.lock().entries.iter().cloned().collect()
+}
+
+pub fn clear_barrier_log() {
+    BARRIER_LOG.lock().entries.clear();
+}
+
+pub(crate) fn begin_execution() {
+    if is_barrier_logging_enabled() {
+        let mut log = BARRIER_LOG.lock();
+        log.execution += 1;
+        log.last_pass_by_resource.clear();
+    }
+}
+
+pub(crate) fn record_barrier(
+    resource_idx: usize,
+    resource: impl FnOnce() -> String,
+    prev_access: vk_sync::AccessType,
+    next_access: vk_sync::AccessType,
+    is_image: bool,
+    dst_pass: &str,
+) {
+    if !is_barrier_logging_enabled() {
+        return;
+    }
+
+    let layout = |access| {
+        if is_image {
+            get_access_info(access).image_layout
+        } else {
+            vk::ImageLayout::UNDEFINED
+        }
+    };
+
+    let mut log = BARRIER_LOG.lock();
+    let src_pass = log
+        .last_pass_by_resource
+        .insert(resource_idx, dst_pass.to_owned());
+
+    let entry = BarrierLogEntry {
+        execution: log.execution,
+        resource: format!("#{} {}", resource_idx, resource()),
+        prev_access,
+        next_access,
+        prev_layout: layout(prev_access),
+        next_layout: layout(next_access),
+        src_pass,
+        dst_pass: dst_pass.to_owned(),
+    };
+
+    if log.entries.len() >= log.capacity {
+        log.entries.pop_front();
+    }
+    log.entries.push_back(entry);
+}
@@ -0,0 +1,50 @@
+//! The backend and the render graph without a window or the rest of the renderer, for tools
+//! such as LUT bakers and texture processors.
+//!
+//! Each `ComputeContext::run` records one graph and waits for the GPU to finish it.
+//! To get results back to the CPU, import a buffer created with `BufferDesc::new_gpu_to_cpu`,
+//! copy or write into it in the graph, and read its allocation once `run` returns.
+
+use std::sync::Arc;
+
+use kajiya_backend::{
+    vulkan::{create_headless_device, HeadlessDeviceConfig},
+    Device,
+};
+
+use crate::{renderer::Renderer, RenderGraph};
+
+pub struct ComputeContext {
+    renderer: Renderer,
+}
+
+impl ComputeContext {
+    pub fn new(config: HeadlessDeviceConfig) -> anyhow::Result<Self> {
+        let device = create_headless_device(config)?;
+
+        Ok(Self {
+            renderer: Renderer::with_device(&device)?,
+        })
+    }
+
+    /// For creating the resources to import into the graphs.
+    pub fn device(&self) -> &Arc<Device> {
+        self.renderer.device()
+    }
+
+    /// Records a graph with `record_graph`, and runs it to completion. Fails without running
+    /// anything if the pipelines of the graph can't be built, e.g. if a shader doesn't compile.
+    ///
+    /// Graphs get no frame constants, and can't use the swapchain.
+    pub fn run(&mut self, record_graph: impl FnOnce(&mut RenderGraph)) -> anyhow::Result<()> {
+        self.renderer.prepare_frame(|rg| record_graph(rg))?;
+        self.renderer.execute_without_presentation();
+        Ok(())
+    }
+
+    /// Destroys the GPU resources of the context. Resources created through `device`
+    /// are up to their owners.
+    pub fn shutdown(self) {
+        self.renderer.shutdown();
+    }
+}
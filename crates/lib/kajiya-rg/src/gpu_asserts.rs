@@ -0,0 +1,75 @@
+use kajiya_backend::{
+    ash::vk,
+    vulkan::buffer::{Buffer, BufferDesc},
+    Device,
+};
+
+// One per frame in flight, each at a valid dynamic storage buffer offset.
+const SLOT_COUNT: usize = 2;
+pub(crate) const SLOT_SIZE: usize = 256;
+
+// Must match `GPU_ASSERT_*` in `inc/gpu_debug.hlsl`
+const FAILURE_COUNT_OFFSET: usize = 0;
+const FIRST_LINE_OFFSET: usize = 4;
+const FIRST_VALUE_OFFSET: usize = 8;
+
+/// The buffer which `GPU_ASSERT` in shaders flags failures in, bound as `gpu_asserts_dyn`
+/// in the frame descriptor set, and checked on the CPU once the frame is done.
+pub(crate) struct GpuAsserts {
+    pub(crate) buffer: Buffer,
+    slot: usize,
+}
+
+impl GpuAsserts {
+    pub(crate) fn new(device: &Device) -> anyhow::Result<Self> {
+        let mut buffer = device.create_buffer(
+            BufferDesc::new_gpu_to_cpu(
+                SLOT_SIZE * SLOT_COUNT,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            ),
+            "gpu asserts",
+            None,
+        )?;
+
+        if let Some(mapped) = buffer.allocation.mapped_slice_mut() {
+            mapped.fill(0);
+        }
+
+        Ok(Self { buffer, slot: 0 })
+    }
+
+    /// Logs the failures of the frame which last used the next slot, clears it,
+    /// and returns its offset for the frame about to be recorded.
+    ///
+    /// Must be called once the GPU is done with that frame.
+    pub(crate) fn begin_frame(&mut self) -> u32 {
+        self.slot = (self.slot + 1) % SLOT_COUNT;
+        let slot_offset = self.slot * SLOT_SIZE;
+
+        if let Some(mapped) = self.buffer.allocation.mapped_slice_mut() {
+            let slot = &mut mapped[slot_offset..slot_offset + SLOT_SIZE];
+            let read_u32 = |offset: usize| {
+                u32::from_ne_bytes([
+                    slot[offset],
+                    slot[offset + 1],
+                    slot[offset + 2],
+                    slot[offset + 3],
+                ])
+            };
+
+            let failure_count = read_u32(FAILURE_COUNT_OFFSET);
+            if failure_count > 0 {
+                log::error!(
+                    "GPU assert failed {} times; first at shader line {} with value {}",
+                    failure_count,
+                    read_u32(FIRST_LINE_OFFSET),
+                    read_u32(FIRST_VALUE_OFFSET) as i32,
+                );
+
+                slot.fill(0);
+            }
+        }
+
+        slot_offset as u32
+    }
+}
@@ -0,0 +1,26 @@
+//! The GPU resources held across frames: memory and objects of the device, and pipelines.
+//! Updated by `Renderer::draw_frame`; the graph's own transients are in `memory_stats`.
+
+use kajiya_backend::{pipeline_cache::PipelineCacheStats, vulkan::device_stats::DeviceStats};
+use parking_lot::Mutex;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuResourceStats {
+    pub device: DeviceStats,
+    pub pipelines: PipelineCacheStats,
+    /// Frames drawn so far, for turning the running totals into rates.
+    pub frame_count: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<GpuResourceStats> = Default::default();
+}
+
+/// Stats as of the latest frame.
+pub fn gpu_resource_stats() -> GpuResourceStats {
+    *STATS.lock()
+}
+
+pub(crate) fn report_frame(stats: GpuResourceStats) {
+    *STATS.lock() = stats;
+}
@@ -3,10 +3,13 @@
 use crate::{renderer::FrameConstantsLayout, resource_registry::PendingRenderResourceInfo};
 
 use super::{
+    barrier_log, memory_stats, parallel_recording,
     pass_builder::PassBuilder,
+    pass_ordering::{self, PassId, PassOrdering},
+    pass_toggles,
     resource::*,
     resource_registry::{
-        AnyRenderResource, AnyRenderResourceRef, RegistryResource, ResourceRegistry,
+        AnyRenderResource, AnyRenderResourceRef, BufferReads, RegistryResource, ResourceRegistry,
     },
     RenderPassApi,
 };
@@ -26,8 +29,8 @@ use kajiya_backend::{
     vk_sync,
     vulkan::{
         barrier::{
-            get_access_info, image_aspect_mask_from_access_type_and_format, record_image_barrier,
-            ImageBarrier,
+            get_access_info, image_aspect_mask_from_access_type_and_format, is_read_only_access,
+            record_image_barrier, ImageBarrier,
         },
         device::{CommandBuffer, Device},
         image::ImageViewDesc,
@@ -138,6 +141,9 @@ pub struct RenderGraph {
 
     pub debug_hook: Option<GraphDebugHook>,
     pub debugged_resource: Option<Handle<Image>>,
+
+    // Of the passes added from now on
+    view_idx: usize,
 }
 
 pub trait ImportExportToRenderGraph
@@ -307,6 +313,7 @@ impl RenderGraph {
             predefined_descriptor_set_layouts: HashMap::new(),
             debug_hook: None,
             debugged_resource: None,
+            view_idx: 0,
         }
     }
 
@@ -390,9 +397,11 @@ struct ResourceInfo {
 
 pub struct RenderGraphExecutionParams<'a> {
     pub device: &'a Device,
-    pub pipeline_cache: &'a mut PipelineCache,
+    pub pipeline_cache: &'a PipelineCache,
     pub frame_descriptor_set: vk::DescriptorSet,
     pub frame_constants_layout: FrameConstantsLayout,
+    /// Of the frame's slot in the buffer bound as `gpu_asserts_dyn`.
+    pub gpu_asserts_offset: u32,
     pub profiler_data: &'a VkProfilerData,
 }
 
@@ -419,10 +428,82 @@ impl RenderGraph {
         PassBuilder {
             rg: self,
             pass_idx,
-            pass: Some(RecordedPass::new(name, pass_idx)),
+            pass: Some(RecordedPass::new(name, pass_idx, self.view_idx)),
+        }
+    }
+
+    /// Makes the passes added from now on bind the frame constants of view `view_idx`,
+    /// as laid out by `FrameConstantsLayout::view_globals_offset`. Zero is the main view.
+    pub fn set_view(&mut self, view_idx: usize) {
+        self.view_idx = view_idx;
+    }
+
+    pub fn view(&self) -> usize {
+        self.view_idx
+    }
+
+    /// Passes added so far. Pass indices are also the ids of their GPU profiler scopes.
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn pass_name(&self, pass_idx: usize) -> &str {
+        &self.passes[pass_idx].name
+    }
+
+    /// Makes the render functions of the passes from `first_pass_idx` on (see `pass_count`)
+    /// catch their panics, and report them to `on_panic` with the pass name rather than
+    /// unwinding out of the graph. Whatever the pass recorded before panicking stays recorded.
+    ///
+    /// For passes from code which shouldn't be able to take the renderer down, such as plugins.
+    pub fn catch_render_fn_panics(
+        &mut self,
+        first_pass_idx: usize,
+        on_panic: impl Fn(&str) + Clone + Send + 'static,
+    ) {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        for pass in &mut self.passes[first_pass_idx..] {
+            let name = pass.name.clone();
+            let on_panic = on_panic.clone();
+
+            pass.render_fn = pass.render_fn.take().map(|render_fn| match render_fn {
+                RenderFn::Send(render_fn) => RenderFn::Send(Box::new(move |api| {
+                    catch_unwind(AssertUnwindSafe(|| render_fn(api))).unwrap_or_else(|_| {
+                        on_panic(&name);
+                        Ok(())
+                    })
+                })),
+                RenderFn::Local(render_fn) => RenderFn::Local(Box::new(move |api| {
+                    catch_unwind(AssertUnwindSafe(|| render_fn(api))).unwrap_or_else(|_| {
+                        on_panic(&name);
+                        Ok(())
+                    })
+                })),
+            });
         }
     }
 
+    /// Adds a pass which only makes `previous_accesses` of any memory available to
+    /// `next_accesses`, with one memory barrier, and no image layout transitions.
+    ///
+    /// The graph already synchronizes the resources which passes declare. This is for
+    /// dependencies it can't see, such as between compute passes which write a buffer
+    /// with `PassBuilder::write_no_sync`, and read it in the next pass of the chain.
+    pub fn global_barrier(
+        &mut self,
+        previous_accesses: &[vk_sync::AccessType],
+        next_accesses: &[vk_sync::AccessType],
+    ) {
+        let previous_accesses = previous_accesses.to_vec();
+        let next_accesses = next_accesses.to_vec();
+
+        self.add_pass("global barrier").render(move |api| {
+            global_barrier(api.device(), api.cb, &previous_accesses, &next_accesses);
+            Ok(())
+        });
+    }
+
     fn calculate_resource_info(&self) -> ResourceInfo {
         let mut lifetimes: Vec<ResourceLifetime> = self
             .resources
@@ -543,7 +624,21 @@ impl RenderGraph {
         }
     }
 
-    pub fn compile(self, pipeline_cache: &mut PipelineCache) -> CompiledRenderGraph {
+    pub fn compile(mut self, pipeline_cache: &mut PipelineCache) -> CompiledRenderGraph {
+        if pass_ordering::is_pass_reordering_enabled() {
+            let resources = &self.resources;
+            pass_ordering::reorder_passes(&mut self.passes, |pass| {
+                pass.write.iter().any(|res| {
+                    matches!(
+                        resources[res.handle.id as usize],
+                        GraphResourceInfo::Imported(GraphResourceImportInfo::SwapchainImage)
+                    )
+                })
+            });
+        }
+
+        pass_toggles::apply(&mut self.passes);
+
         let resource_info = self.calculate_resource_info();
         // TODO: alias resources
 
@@ -658,6 +753,7 @@ fn image_access_mask_to_usage_flags(access_mask: vk::AccessFlags) -> vk::ImageUs
         vk::AccessFlags::SHADER_READ => vk::ImageUsageFlags::SAMPLED,
         vk::AccessFlags::SHADER_WRITE => vk::ImageUsageFlags::STORAGE,
         vk::AccessFlags::COLOR_ATTACHMENT_READ => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        vk::AccessFlags::INPUT_ATTACHMENT_READ => vk::ImageUsageFlags::INPUT_ATTACHMENT,
         vk::AccessFlags::COLOR_ATTACHMENT_WRITE => vk::ImageUsageFlags::COLOR_ATTACHMENT,
         vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ => {
             vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
@@ -719,14 +815,20 @@ impl CompiledRenderGraph {
                     GraphResourceDesc::Image(mut desc) => {
                         desc.usage = self.resource_info.image_usage_flags[resource_idx];
 
-                        let image = transient_resource_cache
-                            .get_image(&desc)
-                            .unwrap_or_else(|| device.create_image(desc, vec![]).unwrap());
+                        let image =
+                            transient_resource_cache
+                                .get_image(&desc)
+                                .unwrap_or_else(|| {
+                                    let _creator = device.resource_creator_scope(|| {
+                                        transient_resource_creator(&self.rg.passes, resource_idx)
+                                    });
+                                    device.create_image(desc, vec![]).unwrap()
+                                });
 
-                        RegistryResource {
-                            access_type: vk_sync::AccessType::Nothing,
-                            resource: AnyRenderResource::OwnedImage(image),
-                        }
+                        RegistryResource::new(
+                            AnyRenderResource::OwnedImage(image),
+                            vk_sync::AccessType::Nothing,
+                        )
                     }
                     GraphResourceDesc::Buffer(mut desc) => {
                         desc.usage = self.resource_info.buffer_usage_flags[resource_idx];
@@ -735,13 +837,16 @@ impl CompiledRenderGraph {
                             transient_resource_cache
                                 .get_buffer(&desc)
                                 .unwrap_or_else(|| {
+                                    let _creator = device.resource_creator_scope(|| {
+                                        transient_resource_creator(&self.rg.passes, resource_idx)
+                                    });
                                     device.create_buffer(desc, "rg buffer", None).unwrap()
                                 });
 
-                        RegistryResource {
-                            resource: AnyRenderResource::OwnedBuffer(buffer),
-                            access_type: vk_sync::AccessType::Nothing,
-                        }
+                        RegistryResource::new(
+                            AnyRenderResource::OwnedBuffer(buffer),
+                            vk_sync::AccessType::Nothing,
+                        )
                     }
                     GraphResourceDesc::RayTracingAcceleration(_) => {
                         unimplemented!();
@@ -751,61 +856,152 @@ impl CompiledRenderGraph {
                     GraphResourceImportInfo::Image {
                         resource,
                         access_type,
-                    } => RegistryResource {
-                        resource: AnyRenderResource::ImportedImage(resource.clone()),
-                        access_type: *access_type,
-                    },
+                    } => RegistryResource::new(
+                        AnyRenderResource::ImportedImage(resource.clone()),
+                        *access_type,
+                    ),
                     GraphResourceImportInfo::Buffer {
                         resource,
                         access_type,
-                    } => RegistryResource {
-                        resource: AnyRenderResource::ImportedBuffer(resource.clone()),
-                        access_type: *access_type,
-                    },
+                    } => RegistryResource::new(
+                        AnyRenderResource::ImportedBuffer(resource.clone()),
+                        *access_type,
+                    ),
                     GraphResourceImportInfo::RayTracingAcceleration {
                         resource,
                         access_type,
-                    } => RegistryResource {
-                        resource: AnyRenderResource::ImportedRayTracingAcceleration(
-                            resource.clone(),
-                        ),
-                        access_type: *access_type,
-                    },
-                    GraphResourceImportInfo::SwapchainImage => RegistryResource {
-                        resource: AnyRenderResource::Pending(PendingRenderResourceInfo {
+                    } => RegistryResource::new(
+                        AnyRenderResource::ImportedRayTracingAcceleration(resource.clone()),
+                        *access_type,
+                    ),
+                    GraphResourceImportInfo::SwapchainImage => RegistryResource::new(
+                        AnyRenderResource::Pending(PendingRenderResourceInfo {
                             resource: resource.clone(),
                         }),
-                        access_type: vk_sync::AccessType::ComputeShaderWrite,
-                    },
+                        vk_sync::AccessType::ComputeShaderWrite,
+                    ),
                 },
             })
             .collect();
 
-        let resource_registry = ResourceRegistry {
-            execution_params: params,
-            resources,
-            dynamic_constants,
-            pipelines: self.pipelines,
-        };
+        Self::report_transient_memory(device, &self.rg.passes, &resources);
 
         ExecutingRenderGraph {
-            resource_registry,
             passes: self.rg.passes.into(),
             resources: self.rg.resources,
             exported_resources: self.rg.exported_resources,
+            execution_params: params,
+            registry_resources: resources,
+            dynamic_constants,
+            pipelines: self.pipelines,
         }
     }
 }
 
+/// Transient resources are attributed to the first pass writing them,
+/// which is usually what they're created for.
+fn transient_resource_creator(passes: &[RecordedPass], resource_idx: usize) -> String {
+    let first_pass = passes.iter().find(|pass| {
+        pass.write
+            .iter()
+            .any(|resource_ref| resource_ref.handle.id as usize == resource_idx)
+    });
+
+    match first_pass {
+        Some(pass) => format!("render graph, for {:?}", pass.name),
+        None => "render graph".to_owned(),
+    }
+}
+
+impl CompiledRenderGraph {
+    fn report_transient_memory(
+        device: &Device,
+        passes: &[RecordedPass],
+        resources: &[RegistryResource],
+    ) {
+        let mut first_pass: Vec<Option<&str>> = vec![None; resources.len()];
+        for pass in passes {
+            for resource_ref in &pass.write {
+                first_pass[resource_ref.handle.id as usize].get_or_insert(&pass.name);
+            }
+        }
+
+        let resources = resources
+            .iter()
+            .zip(first_pass)
+            .filter_map(|(resource, first_pass)| {
+                let (desc, size_bytes) = match &resource.resource {
+                    AnyRenderResource::OwnedImage(image) => (
+                        format!("image {:?} {:?}", image.desc.extent, image.desc.format),
+                        memory_stats::image_size_bytes(device, image.raw),
+                    ),
+                    AnyRenderResource::OwnedBuffer(buffer) => (
+                        "buffer".to_owned(),
+                        memory_stats::buffer_size_bytes(device, buffer.raw),
+                    ),
+                    _ => return None,
+                };
+
+                Some(memory_stats::TransientResourceMemory {
+                    first_pass: first_pass.map(str::to_owned),
+                    desc,
+                    size_bytes,
+                })
+            })
+            .collect();
+
+        memory_stats::report_execution(resources);
+    }
+}
+
 pub struct ExecutingRenderGraph<'exec_params, 'constants> {
     passes: VecDeque<RecordedPass>,
     resources: Vec<GraphResourceInfo>,
     exported_resources: Vec<(ExportableGraphResource, vk_sync::AccessType)>,
-    resource_registry: ResourceRegistry<'exec_params, 'constants>,
+    execution_params: RenderGraphExecutionParams<'exec_params>,
+    registry_resources: Vec<RegistryResource>,
+    dynamic_constants: &'constants mut DynamicConstants,
+    pipelines: RenderGraphPipelines,
+}
+
+/// A pass with its barriers and GPU query resolved, so that it can be recorded on any thread.
+struct PreparedPass<RenderFnType> {
+    name: String,
+    barriers: Vec<PassBarrier>,
+    vk_query_idx: u32,
+    view_idx: usize,
+    render_fn: Option<RenderFnType>,
+}
+
+impl<RenderFnType> PreparedPass<RenderFnType> {
+    fn map_render_fn<Other>(self, f: impl FnOnce(RenderFnType) -> Other) -> PreparedPass<Other> {
+        PreparedPass {
+            name: self.name,
+            barriers: self.barriers,
+            vk_query_idx: self.vk_query_idx,
+            view_idx: self.view_idx,
+            render_fn: self.render_fn.map(f),
+        }
+    }
+}
+
+struct PassBarrier {
+    resource_idx: usize,
+    /// Only buffers wait on more than one access; see `BufferReads`.
+    prev_accesses: Vec<vk_sync::AccessType>,
+    next_access: vk_sync::AccessType,
 }
 
 impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
-    pub fn record_main_cb(&mut self, cb: &CommandBuffer) {
+    /// Records the passes which come before the first one writing to the swapchain.
+    ///
+    /// With more than one recording thread (see `set_recording_thread_count`), they may
+    /// be recorded into `parallel_cbs` instead. Returns how many of those were used;
+    /// they must be submitted right after `cb`, in order.
+    #[must_use]
+    pub fn record_main_cb(&mut self, cb: &CommandBuffer, parallel_cbs: &[CommandBuffer]) -> usize {
+        barrier_log::begin_execution();
+
         let mut first_presentation_pass: usize = self.passes.len();
 
         for (pass_idx, pass) in self.passes.iter().enumerate() {
@@ -838,19 +1034,19 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
                 }
             }
 
-            let params = &self.resource_registry.execution_params;
+            let params = &self.execution_params;
             for (resource_idx, access) in resource_first_access_states {
-                let resource = &mut self.resource_registry.resources[resource_idx as usize];
+                let resource = &mut self.registry_resources[resource_idx as usize];
                 Self::transition_resource(
                     params.device,
                     cb,
+                    resource_idx as usize,
                     resource,
                     PassResourceAccessType {
                         access_type: access.access_type,
                         sync_type: PassResourceAccessSyncType::SkipSyncIfSameAccessType,
                     },
-                    false,
-                    "",
+                    "(graph start)",
                 );
 
                 // Skip the sync when this pass is encountered later.
@@ -858,67 +1054,286 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
             }
         }
 
-        for pass in passes.drain(..first_presentation_pass) {
-            Self::record_pass_cb(pass, &mut self.resource_registry, cb);
-        }
+        let prepared_passes: Vec<_> = passes
+            .drain(..first_presentation_pass)
+            .map(|pass| {
+                Self::prepare_pass(pass, &self.execution_params, &mut self.registry_resources)
+            })
+            .collect();
 
         self.passes = passes.into();
+
+        let range_count = parallel_recording::recording_thread_count()
+            .min(parallel_cbs.len())
+            .min(prepared_passes.len() / parallel_recording::MIN_PASSES_PER_RANGE);
+
+        if range_count > 1 {
+            self.record_ranges_in_parallel(prepared_passes, &parallel_cbs[..range_count]);
+            range_count
+        } else {
+            let mut resource_registry = self.resource_registry();
+            for pass in prepared_passes {
+                Self::record_pass_cb(
+                    pass.map_render_fn(RenderFn::into_local),
+                    &mut resource_registry,
+                    cb,
+                );
+            }
+            0
+        }
+    }
+
+    /// Records `passes` into `cbs`, one contiguous range per command buffer.
+    fn record_ranges_in_parallel(
+        &mut self,
+        passes: Vec<PreparedPass<RenderFn>>,
+        cbs: &[CommandBuffer],
+    ) {
+        let passes_per_range = (passes.len() + cbs.len() - 1) / cbs.len();
+
+        let mut worker_ranges = Vec::with_capacity(cbs.len());
+        let mut local_ranges = Vec::new();
+
+        let mut passes = passes.into_iter();
+        for cb in cbs {
+            let range: Vec<_> = passes.by_ref().take(passes_per_range).collect();
+
+            if range
+                .iter()
+                .any(|pass| matches!(pass.render_fn, Some(RenderFn::Local(_))))
+            {
+                local_ranges.push((range, cb));
+            } else {
+                let range: Vec<PreparedPass<Box<DynRenderFn>>> = range
+                    .into_iter()
+                    .map(|pass| {
+                        pass.map_render_fn(|render_fn| match render_fn {
+                            RenderFn::Send(render_fn) => render_fn,
+                            RenderFn::Local(_) => unreachable!(),
+                        })
+                    })
+                    .collect();
+
+                worker_ranges.push((range, cb));
+            }
+        }
+
+        // Local ranges keep using the main allocator, which stays with this thread.
+        let bytes_per_worker =
+            self.dynamic_constants.remaining_frame_bytes() / (worker_ranges.len() + 1);
+        let worker_dynamic_constants: Vec<DynamicConstants> = worker_ranges
+            .iter()
+            .map(|_| self.dynamic_constants.fork(bytes_per_worker))
+            .collect();
+
+        let execution_params = &self.execution_params;
+        let resources = &self.registry_resources;
+        let pipelines = &self.pipelines;
+        let dynamic_constants = &mut *self.dynamic_constants;
+
+        crossbeam_utils::thread::scope(|scope| {
+            for ((range, cb), mut dynamic_constants) in
+                worker_ranges.into_iter().zip(worker_dynamic_constants)
+            {
+                scope.spawn(move |_| {
+                    puffin::profile_scope!("rg record range");
+
+                    let mut resource_registry = ResourceRegistry {
+                        execution_params,
+                        resources,
+                        dynamic_constants: &mut dynamic_constants,
+                        pipelines,
+                    };
+
+                    for pass in range {
+                        Self::record_pass_cb(
+                            pass.map_render_fn(|render_fn| render_fn as Box<DynLocalRenderFn>),
+                            &mut resource_registry,
+                            cb,
+                        );
+                    }
+                });
+            }
+
+            let mut resource_registry = ResourceRegistry {
+                execution_params,
+                resources,
+                dynamic_constants,
+                pipelines,
+            };
+
+            for (range, cb) in local_ranges {
+                for pass in range {
+                    Self::record_pass_cb(
+                        pass.map_render_fn(RenderFn::into_local),
+                        &mut resource_registry,
+                        cb,
+                    );
+                }
+            }
+        })
+        .expect("A render graph recording thread panicked");
     }
 
     #[must_use]
     pub fn record_presentation_cb(
-        mut self,
+        self,
         cb: &CommandBuffer,
         swapchain_image: Arc<Image>,
     ) -> RetiredRenderGraph {
-        let params = &self.resource_registry.execution_params;
+        self.record_remaining_cb(cb, Some(swapchain_image))
+    }
+
+    /// Records what `record_main_cb` left over, for graphs which don't use the swapchain.
+    #[must_use]
+    pub fn record_without_presentation(self, cb: &CommandBuffer) -> RetiredRenderGraph {
+        self.record_remaining_cb(cb, None)
+    }
+
+    fn record_remaining_cb(
+        mut self,
+        cb: &CommandBuffer,
+        swapchain_image: Option<Arc<Image>>,
+    ) -> RetiredRenderGraph {
+        let params = &self.execution_params;
 
         // Transition exported images to the requested access types
         for (resource_idx, access_type) in self.exported_resources {
             if access_type != vk_sync::AccessType::Nothing {
-                let resource =
-                    &mut self.resource_registry.resources[resource_idx.raw().id as usize];
+                let resource_idx = resource_idx.raw().id as usize;
+                let resource = &mut self.registry_resources[resource_idx];
                 Self::transition_resource(
                     params.device,
                     cb,
+                    resource_idx,
                     resource,
                     PassResourceAccessType {
                         access_type,
                         sync_type: PassResourceAccessSyncType::AlwaysSync,
                     },
-                    false,
-                    "",
+                    "(export)",
                 );
             }
         }
 
-        for res in &mut self.resource_registry.resources {
+        for res in &mut self.registry_resources {
             if let AnyRenderResource::Pending(pending) = &mut res.resource {
                 match pending.resource {
                     GraphResourceInfo::Imported(GraphResourceImportInfo::SwapchainImage) => {
-                        res.resource = AnyRenderResource::ImportedImage(swapchain_image.clone());
+                        let swapchain_image = swapchain_image
+                            .clone()
+                            .expect("The graph uses the swapchain, but isn't presenting");
+                        res.resource = AnyRenderResource::ImportedImage(swapchain_image);
                     }
                     _ => panic!("Only swapchain can be currently pending"),
                 }
             }
         }
 
-        let passes = self.passes;
-        for pass in passes {
-            Self::record_pass_cb(pass, &mut self.resource_registry, cb);
+        let prepared_passes: Vec<_> = std::mem::take(&mut self.passes)
+            .into_iter()
+            .map(|pass| {
+                Self::prepare_pass(pass, &self.execution_params, &mut self.registry_resources)
+            })
+            .collect();
+
+        let mut resource_registry = ResourceRegistry {
+            execution_params: &self.execution_params,
+            resources: &self.registry_resources,
+            dynamic_constants: &mut *self.dynamic_constants,
+            pipelines: &self.pipelines,
+        };
+
+        for pass in prepared_passes {
+            Self::record_pass_cb(
+                pass.map_render_fn(RenderFn::into_local),
+                &mut resource_registry,
+                cb,
+            );
+        }
+
+        // The next graph only knows the last access of each resource, so make it
+        // wait on any other reads of buffers still in flight too.
+        for (resource_idx, resource) in self.registry_resources.iter_mut().enumerate() {
+            let pending_reads = match &resource.buffer_reads {
+                Some(reads) if reads.read_accesses.len() > 1 => reads.read_accesses.clone(),
+                _ => continue,
+            };
+
+            let barrier = PassBarrier {
+                resource_idx,
+                prev_accesses: pending_reads,
+                next_access: resource.access_type,
+            };
+            Self::record_barrier(self.execution_params.device, cb, resource, &barrier);
+            resource.buffer_reads = None;
         }
 
         RetiredRenderGraph {
-            resources: self.resource_registry.resources,
+            resources: self.registry_resources,
         }
     }
 
-    fn record_pass_cb(
+    fn resource_registry(&mut self) -> ResourceRegistry<'_, '_> {
+        ResourceRegistry {
+            execution_params: &self.execution_params,
+            resources: &self.registry_resources,
+            dynamic_constants: &mut *self.dynamic_constants,
+            pipelines: &self.pipelines,
+        }
+    }
+
+    /// Resolves the barriers of `pass` in graph order, and allocates its GPU query.
+    fn prepare_pass(
         pass: RecordedPass,
+        params: &RenderGraphExecutionParams,
+        resources: &mut [RegistryResource],
+    ) -> PreparedPass<RenderFn> {
+        let query_id = gpu_profiler::create_gpu_query(
+            gpu_profiler::RenderScopeDesc {
+                name: pass.name.clone(),
+                id: pass.idx as _,
+            },
+            pass.idx,
+        );
+        let vk_query_idx = params.profiler_data.get_query_id(query_id);
+
+        // TODO: optimize the barriers
+        let barriers = pass
+            .read
+            .iter()
+            .chain(pass.write.iter())
+            .filter_map(|resource_ref| {
+                let resource_idx = resource_ref.handle.id as usize;
+                Self::resolve_transition(
+                    resource_idx,
+                    &mut resources[resource_idx],
+                    resource_ref.access,
+                    &pass.name,
+                )
+            })
+            .collect();
+
+        PreparedPass {
+            name: pass.name,
+            barriers,
+            vk_query_idx,
+            view_idx: pass.view_idx,
+            render_fn: pass.render_fn,
+        }
+    }
+
+    fn record_pass_cb(
+        pass: PreparedPass<Box<DynLocalRenderFn>>,
         resource_registry: &mut ResourceRegistry,
         cb: &CommandBuffer,
     ) {
-        let params = &resource_registry.execution_params;
+        let params = resource_registry.execution_params;
+
+        // Descriptor pools, and anything else the pass creates
+        let _creator = params
+            .device
+            .resource_creator_scope(|| format!("pass {:?}", pass.name));
 
         // Record a crash marker just before this pass
         params
@@ -933,68 +1348,52 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
             }
         }
 
-        let vk_query_idx = {
-            let query_id = gpu_profiler::create_gpu_query(
-                gpu_profiler::RenderScopeDesc {
-                    name: pass.name.clone(),
-                    id: pass.idx as _,
-                },
-                pass.idx,
+        unsafe {
+            params.device.raw.cmd_write_timestamp(
+                cb.raw,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                params.profiler_data.query_pool,
+                pass.vk_query_idx * 2,
             );
-            let vk_query_idx = params.profiler_data.get_query_id(query_id);
-
-            unsafe {
-                params.device.raw.cmd_write_timestamp(
-                    cb.raw,
-                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                    params.profiler_data.query_pool,
-                    vk_query_idx * 2,
-                );
-            }
+        }
 
-            vk_query_idx
-        };
+        // Images need a barrier each, for their layout transitions. Buffers only need their
+        // memory made available, which a single global barrier does for all of them.
+        let mut buffer_prev_accesses = Vec::new();
+        let mut buffer_next_accesses = Vec::new();
 
-        {
-            let params = &resource_registry.execution_params;
+        for barrier in &pass.barriers {
+            let resource = &resource_registry.resources[barrier.resource_idx];
 
-            let mut transitions: Vec<(usize, PassResourceAccessType)> = Vec::new();
-            for resource_ref in pass.read.iter() {
-                transitions.push((
-                    resource_ref.handle.id as usize,
-                    resource_ref.access,
-                    //format!("read {i}"),
-                ));
-            }
+            if matches!(resource.resource.borrow(), AnyRenderResourceRef::Buffer(_)) {
+                for access in &barrier.prev_accesses {
+                    if !buffer_prev_accesses.contains(access) {
+                        buffer_prev_accesses.push(*access);
+                    }
+                }
 
-            for resource_ref in pass.write.iter() {
-                transitions.push((
-                    resource_ref.handle.id as usize,
-                    resource_ref.access,
-                    //format!("write {i}"),
-                ));
+                if !buffer_next_accesses.contains(&barrier.next_access) {
+                    buffer_next_accesses.push(barrier.next_access);
+                }
+            } else {
+                Self::record_barrier(params.device, cb, resource, barrier);
             }
+        }
 
-            // TODO: optimize the barriers
-
-            for (resource_idx, access) in transitions {
-                let resource = &mut resource_registry.resources[resource_idx];
-
-                Self::transition_resource(
-                    params.device,
-                    cb,
-                    resource,
-                    access,
-                    //pass.name == "raster simple",
-                    false,
-                    "",
-                );
-            }
+        if !buffer_next_accesses.is_empty() {
+            global_barrier(
+                params.device,
+                cb,
+                &buffer_prev_accesses,
+                &buffer_next_accesses,
+            );
         }
 
         let mut api = RenderPassApi {
             cb,
             resources: resource_registry,
+            view_idx: pass.view_idx,
+            in_dynamic_rendering: false,
         };
 
         if let Some(render_fn) = pass.render_fn {
@@ -1003,14 +1402,12 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
             }
         }
 
-        let params = &resource_registry.execution_params;
-
         unsafe {
             params.device.raw.cmd_write_timestamp(
                 cb.raw,
                 vk::PipelineStageFlags::BOTTOM_OF_PIPE,
                 params.profiler_data.query_pool,
-                vk_query_idx * 2 + 1,
+                pass.vk_query_idx * 2 + 1,
             );
         }
 
@@ -1029,70 +1426,144 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
     fn transition_resource(
         device: &Device,
         cb: &CommandBuffer,
+        resource_idx: usize,
         resource: &mut RegistryResource,
         access: PassResourceAccessType,
-        debug: bool,
-        dbg_str: &str,
+        pass_name: &str,
     ) {
-        if unsafe { RG_ALLOW_PASS_OVERLAP }
+        if let Some(barrier) = Self::resolve_transition(resource_idx, resource, access, pass_name) {
+            Self::record_barrier(device, cb, resource, &barrier);
+        }
+    }
+
+    /// Tracks the new access type of `resource`, returning the barrier to record, if any.
+    fn resolve_transition(
+        resource_idx: usize,
+        resource: &mut RegistryResource,
+        access: PassResourceAccessType,
+        pass_name: &str,
+    ) -> Option<PassBarrier> {
+        let allow_pass_overlap = unsafe { RG_ALLOW_PASS_OVERLAP };
+
+        if allow_pass_overlap
             && resource.access_type == access.access_type
             && matches!(
                 access.sync_type,
                 PassResourceAccessSyncType::SkipSyncIfSameAccessType
             )
         {
-            return;
+            return None;
         }
 
-        if debug {
-            log::info!(
-                "\t{dbg_str}: {:?} -> {:?}",
-                resource.access_type,
-                access.access_type
-            );
-        }
+        let concurrent_read = allow_pass_overlap
+            && matches!(resource.resource.borrow(), AnyRenderResourceRef::Buffer(_))
+            && is_read_only_access(access.access_type);
+
+        let prev_accesses = if concurrent_read {
+            let reads = resource.buffer_reads.get_or_insert_with(|| BufferReads {
+                write_access: resource.access_type,
+                read_accesses: if is_read_only_access(resource.access_type) {
+                    vec![resource.access_type]
+                } else {
+                    Vec::new()
+                },
+            });
+
+            if reads.read_accesses.contains(&access.access_type) {
+                // Already visible to this kind of read.
+                resource.access_type = access.access_type;
+                return None;
+            }
+
+            reads.read_accesses.push(access.access_type);
+            vec![reads.write_access]
+        } else if let Some(reads) = resource.buffer_reads.take() {
+            reads.read_accesses
+        } else {
+            vec![resource.access_type]
+        };
 
         match resource.resource.borrow() {
             AnyRenderResourceRef::Image(image) => {
-                if debug {
-                    log::info!("\t(image {:?})", image.desc);
-                }
+                barrier_log::record_barrier(
+                    resource_idx,
+                    || format!("image {:?} {:?}", image.desc.extent, image.desc.format),
+                    prev_accesses[0],
+                    access.access_type,
+                    true,
+                    pass_name,
+                );
+            }
+            AnyRenderResourceRef::Buffer(buffer) => {
+                barrier_log::record_barrier(
+                    resource_idx,
+                    || format!("buffer of {} bytes", buffer.desc.size),
+                    prev_accesses[0],
+                    access.access_type,
+                    false,
+                    pass_name,
+                );
+            }
+            AnyRenderResourceRef::RayTracingAcceleration(_) => {
+                barrier_log::record_barrier(
+                    resource_idx,
+                    || "acceleration structure".to_owned(),
+                    prev_accesses[0],
+                    access.access_type,
+                    false,
+                    pass_name,
+                );
+            }
+        }
+
+        let barrier = PassBarrier {
+            resource_idx,
+            prev_accesses,
+            next_access: access.access_type,
+        };
 
+        resource.access_type = access.access_type;
+        Some(barrier)
+    }
+
+    fn record_barrier(
+        device: &Device,
+        cb: &CommandBuffer,
+        resource: &RegistryResource,
+        barrier: &PassBarrier,
+    ) {
+        match resource.resource.borrow() {
+            AnyRenderResourceRef::Image(image) => {
                 record_image_barrier(
                     device,
                     cb.raw,
                     ImageBarrier::new(
                         image.raw,
-                        resource.access_type,
-                        access.access_type,
+                        barrier.prev_accesses[0],
+                        barrier.next_access,
                         image_aspect_mask_from_access_type_and_format(
-                            access.access_type,
+                            barrier.next_access,
                             image.desc.format,
                         )
                         .unwrap_or_else(|| {
                             panic!(
                                 "Invalid image access {:?} :: {:?}",
-                                access.access_type, image.desc
+                                barrier.next_access, image.desc
                             )
                         }),
                     ),
                 );
-
-                resource.access_type = access.access_type;
             }
             AnyRenderResourceRef::Buffer(buffer) => {
-                if debug {
-                    log::info!("\t(buffer {:?})", buffer.desc);
-                }
-                //global_barrier(device, cb, &[resource.access_type], &[access.access_type]);
+                //global_barrier(device, cb, &barrier.prev_accesses, &[barrier.next_access]);
 
                 vk_sync::cmd::pipeline_barrier(
                     device.raw.fp_v1_0(),
                     cb.raw,
                     None,
                     &[vk_sync::BufferBarrier {
-                        previous_accesses: &[resource.access_type],
-                        next_accesses: &[access.access_type],
+                        previous_accesses: &barrier.prev_accesses,
+                        next_accesses: &[barrier.next_access],
                         src_queue_family_index: device.universal_queue.family.index,
                         dst_queue_family_index: device.universal_queue.family.index,
                         buffer: buffer.raw,
@@ -1101,28 +1572,20 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
                     }],
                     &[],
                 );
-
-                resource.access_type = access.access_type;
             }
             AnyRenderResourceRef::RayTracingAcceleration(_) => {
-                if debug {
-                    log::info!("\t(bvh)");
-                }
                 /*global_barrier(
                     device,
                     cb,
-                    &[resource.access_type],
-                    &[access_type],
+                    &barrier.prev_accesses,
+                    &[barrier.next_access],
                 );*/
                 // TODO
-
-                resource.access_type = access.access_type;
             }
         }
     }
 }
 
-#[allow(dead_code)]
 fn global_barrier(
     device: &Device,
     cb: &CommandBuffer,
@@ -1175,7 +1638,23 @@ impl RetiredRenderGraph {
     }
 }
 
-type DynRenderFn = dyn FnOnce(&mut RenderPassApi) -> Result<(), BackendError>;
+type DynRenderFn = dyn (FnOnce(&mut RenderPassApi) -> Result<(), BackendError>) + Send;
+type DynLocalRenderFn = dyn FnOnce(&mut RenderPassApi) -> Result<(), BackendError>;
+
+pub(crate) enum RenderFn {
+    Send(Box<DynRenderFn>),
+    /// Set with `PassBuilder::render_local`; always recorded on the graph's calling thread.
+    Local(Box<DynLocalRenderFn>),
+}
+
+impl RenderFn {
+    fn into_local(self) -> Box<DynLocalRenderFn> {
+        match self {
+            Self::Send(render_fn) => render_fn,
+            Self::Local(render_fn) => render_fn,
+        }
+    }
+}
 
 #[derive(Copy, Clone)]
 pub enum PassResourceAccessSyncType {
@@ -1207,19 +1686,25 @@ pub(crate) struct PassResourceRef {
 pub(crate) struct RecordedPass {
     pub read: Vec<PassResourceRef>,
     pub write: Vec<PassResourceRef>,
-    pub render_fn: Option<Box<DynRenderFn>>,
+    pub render_fn: Option<RenderFn>,
     pub name: String,
     pub idx: usize,
+    pub view_idx: usize,
+    pub ordering: PassOrdering,
+    pub keep_after: Option<PassId>,
 }
 
 impl RecordedPass {
-    fn new(name: &str, idx: usize) -> Self {
+    fn new(name: &str, idx: usize, view_idx: usize) -> Self {
         Self {
             read: Default::default(),
             write: Default::default(),
             render_fn: Default::default(),
             name: name.to_owned(),
             idx,
+            view_idx,
+            ordering: Default::default(),
+            keep_after: None,
         }
     }
 }
@@ -5,18 +5,24 @@ use kajiya_backend::{
     vulkan::{
         image::*,
         ray_tracing::{RayTracingAcceleration, RayTracingPipelineDesc},
-        shader::{ComputePipelineDesc, PipelineShaderDesc, ShaderPipelineStage, ShaderSource},
+        shader::{
+            ComputePipelineDesc, PipelineShaderDesc, RasterPipelineDescBuilder, RenderPass,
+            ShaderPipelineStage, ShaderSource, SpecializationConstants,
+        },
     },
 };
 
+use std::sync::Arc;
+
 use crate::Image;
 
 use super::{
-    BindRgRef, Buffer, GpuSrv, GpuUav, Handle, PassBuilder, Ref, RenderPassApi, RenderPassBinding,
-    Resource, RgComputePipelineHandle, RgRtPipelineHandle,
+    BindRgRef, BoundRasterPipeline, Buffer, GpuRt, GpuSrv, GpuUav, Handle, PassBuilder, PassId,
+    PassOrdering, Ref, RenderPassApi, RenderPassBinding, Resource, RgComputePipelineHandle,
+    RgRasterPipelineHandle, RgRtPipelineHandle,
 };
 
-pub trait ConstBlob {
+pub trait ConstBlob: Send {
     fn push_self(
         self: Box<Self>,
         dynamic_constants: &mut dynamic_constants::DynamicConstants,
@@ -25,7 +31,7 @@ pub trait ConstBlob {
 
 impl<T> ConstBlob for T
 where
-    T: Copy + 'static,
+    T: Copy + Send + 'static,
 {
     fn push_self(
         self: Box<Self>,
@@ -39,7 +45,7 @@ struct VecBlob<T>(Vec<T>);
 
 impl<T> ConstBlob for VecBlob<T>
 where
-    T: Copy + 'static,
+    T: Copy + Send + 'static,
 {
     fn push_self(
         self: Box<Self>,
@@ -104,6 +110,32 @@ where
 pub struct SimpleRenderPass<'rg, RgPipelineHandle> {
     pass: PassBuilder<'rg>,
     state: SimpleRenderPassState<RgPipelineHandle>,
+    attachments: RasterAttachments,
+}
+
+/// Render targets of raster passes; empty for the other kinds.
+#[derive(Default)]
+struct RasterAttachments {
+    render_pass: Option<Arc<RenderPass>>,
+    color: Vec<(Ref<Image, GpuRt>, ImageViewDesc)>,
+    depth: Option<(Ref<Image, GpuRt>, ImageViewDesc)>,
+    /// Binding indices in `RasterPipelineDesc::vertex_input`
+    vertex_buffers: Vec<(u32, Ref<Buffer, GpuSrv>)>,
+}
+
+impl RasterAttachments {
+    fn extent(&self) -> [u32; 2] {
+        let [width, height, _] = self
+            .color
+            .first()
+            .or(self.depth.as_ref())
+            .expect("a raster pass needs at least one attachment")
+            .0
+            .desc()
+            .extent;
+
+        [width, height]
+    }
 }
 
 impl<'rg> SimpleRenderPass<'rg, RgComputePipelineHandle> {
@@ -113,6 +145,28 @@ impl<'rg> SimpleRenderPass<'rg, RgComputePipelineHandle> {
         Self {
             pass,
             state: SimpleRenderPassState::new(pipeline),
+            attachments: Default::default(),
+        }
+    }
+
+    /// Like `new_compute`, but with values for the shader's `[[vk::constant_id]]` constants.
+    pub fn new_compute_specialized(
+        mut pass: PassBuilder<'rg>,
+        pipeline_path: &str,
+        specialization_constants: SpecializationConstants,
+    ) -> Self {
+        let pipeline = pass.register_compute_pipeline_with_desc(
+            ComputePipelineDesc::builder()
+                .compute_hlsl(pipeline_path)
+                .specialization_constants(specialization_constants)
+                .build()
+                .unwrap(),
+        );
+
+        Self {
+            pass,
+            state: SimpleRenderPassState::new(pipeline),
+            attachments: Default::default(),
         }
     }
 
@@ -127,6 +181,7 @@ impl<'rg> SimpleRenderPass<'rg, RgComputePipelineHandle> {
         Self {
             pass,
             state: SimpleRenderPassState::new(pipeline),
+            attachments: Default::default(),
         }
     }
 
@@ -160,12 +215,56 @@ impl<'rg> SimpleRenderPass<'rg, RgComputePipelineHandle> {
     }
 }
 
+/// Shaders invoked when a ray hits a triangle.
+pub struct RtHitGroup {
+    pub closest_hit: Option<ShaderSource>,
+    pub any_hit: Option<ShaderSource>,
+}
+
+impl RtHitGroup {
+    pub fn closest_hit(closest_hit: ShaderSource) -> Self {
+        Self {
+            closest_hit: Some(closest_hit),
+            any_hit: None,
+        }
+    }
+
+    pub fn any_hit(any_hit: ShaderSource) -> Self {
+        Self {
+            closest_hit: None,
+            any_hit: Some(any_hit),
+        }
+    }
+
+    pub fn with_any_hit(mut self, any_hit: ShaderSource) -> Self {
+        self.any_hit = Some(any_hit);
+        self
+    }
+}
+
+impl From<ShaderSource> for RtHitGroup {
+    fn from(closest_hit: ShaderSource) -> Self {
+        Self::closest_hit(closest_hit)
+    }
+}
+
 impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
-    pub fn new_rt(
+    pub fn new_rt<Hit: Into<RtHitGroup>>(
+        pass: PassBuilder<'rg>,
+        rgen: ShaderSource,
+        miss: impl IntoIterator<Item = ShaderSource>,
+        hit: impl IntoIterator<Item = Hit>,
+    ) -> Self {
+        Self::new_rt_specialized(pass, rgen, miss, hit, Default::default())
+    }
+
+    /// Like `new_rt`, but with values for the `[[vk::constant_id]]` constants of the ray generation shader.
+    pub fn new_rt_specialized<Hit: Into<RtHitGroup>>(
         mut pass: PassBuilder<'rg>,
         rgen: ShaderSource,
         miss: impl IntoIterator<Item = ShaderSource>,
-        hit: impl IntoIterator<Item = ShaderSource>,
+        hit: impl IntoIterator<Item = Hit>,
+        rgen_specialization_constants: SpecializationConstants,
     ) -> Self {
         let miss = miss.into_iter();
         let hit = hit.into_iter();
@@ -175,6 +274,7 @@ impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
         shaders.push(
             PipelineShaderDesc::builder(ShaderPipelineStage::RayGen)
                 .source(rgen)
+                .specialization_constants(rgen_specialization_constants)
                 .build()
                 .unwrap(),
         );
@@ -187,13 +287,34 @@ impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
             );
         }
 
-        for source in hit {
-            shaders.push(
-                PipelineShaderDesc::builder(ShaderPipelineStage::RayClosestHit)
-                    .source(source)
-                    .build()
-                    .unwrap(),
+        let mut prev_closest_hit_only = false;
+        for hit_group in hit {
+            let hit_group: RtHitGroup = hit_group.into();
+
+            // The backend would merge the any hit shader into the preceding group.
+            assert!(
+                !(prev_closest_hit_only && hit_group.closest_hit.is_none()),
+                "an any-hit-only group can't follow a closest-hit-only group"
             );
+            prev_closest_hit_only = hit_group.any_hit.is_none();
+
+            if let Some(closest_hit) = hit_group.closest_hit {
+                shaders.push(
+                    PipelineShaderDesc::builder(ShaderPipelineStage::RayClosestHit)
+                        .source(closest_hit)
+                        .build()
+                        .unwrap(),
+                );
+            }
+
+            if let Some(any_hit) = hit_group.any_hit {
+                shaders.push(
+                    PipelineShaderDesc::builder(ShaderPipelineStage::RayAnyHit)
+                        .source(any_hit)
+                        .build()
+                        .unwrap(),
+                );
+            }
         }
 
         let pipeline = pass.register_ray_tracing_pipeline(
@@ -204,6 +325,7 @@ impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
         Self {
             pass,
             state: SimpleRenderPassState::new(pipeline),
+            attachments: Default::default(),
         }
     }
 
@@ -252,7 +374,147 @@ impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
     }
 }
 
+impl<'rg> SimpleRenderPass<'rg, RgRasterPipelineHandle> {
+    /// The render pass must match the attachments added via `color_attachment`
+    /// and `depth_attachment`. `create_render_pass` is cached, so it can be called every frame.
+    pub fn new_raster(
+        mut pass: PassBuilder<'rg>,
+        render_pass: Arc<RenderPass>,
+        vertex: ShaderSource,
+        pixel: ShaderSource,
+        desc: RasterPipelineDescBuilder,
+    ) -> Self {
+        let pipeline = pass.register_raster_pipeline(
+            &[
+                PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                    .source(vertex)
+                    .build()
+                    .unwrap(),
+                PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                    .source(pixel)
+                    .build()
+                    .unwrap(),
+            ],
+            desc.render_pass(render_pass.clone()),
+        );
+
+        Self {
+            pass,
+            state: SimpleRenderPassState::new(pipeline),
+            attachments: RasterAttachments {
+                render_pass: Some(render_pass),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn color_attachment(self, handle: &mut Handle<Image>) -> Self {
+        self.color_attachment_view(handle, ImageViewDesc::default())
+    }
+
+    pub fn color_attachment_view(
+        mut self,
+        handle: &mut Handle<Image>,
+        view_desc: ImageViewDesc,
+    ) -> Self {
+        let handle_ref = self.pass.raster(handle, AccessType::ColorAttachmentWrite);
+        self.attachments.color.push((handle_ref, view_desc));
+        self
+    }
+
+    pub fn depth_attachment(mut self, handle: &mut Handle<Image>) -> Self {
+        assert!(self.attachments.depth.is_none());
+
+        let handle_ref = self
+            .pass
+            .raster(handle, AccessType::DepthAttachmentWriteStencilReadOnly);
+
+        self.attachments.depth = Some((
+            handle_ref,
+            ImageViewDesc::builder()
+                .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                .build()
+                .unwrap(),
+        ));
+        self
+    }
+
+    /// Reads the buffer through fixed-function vertex input, at `binding`
+    /// of `RasterPipelineDesc::vertex_input`. Bound before `draw`.
+    pub fn vertex_buffer(mut self, binding: u32, handle: &Handle<Buffer>) -> Self {
+        let handle_ref = self.pass.read(handle, AccessType::VertexBuffer);
+        self.attachments.vertex_buffers.push((binding, handle_ref));
+        self
+    }
+
+    /// Begins the render pass, binds the pipeline and vertex buffers, and leaves the draw calls
+    /// to `draw_fn`. The viewport and scissor cover the first attachment.
+    pub fn draw(self, draw_fn: impl FnOnce(&RenderPassApi, &BoundRasterPipeline) + Send + 'static) {
+        let mut state = self.state;
+        let attachments = self.attachments;
+
+        self.pass.render(move |api| {
+            state.patch_const_blobs(api);
+
+            let extent = attachments.extent();
+            let color_attachments = attachments
+                .color
+                .iter()
+                .map(|(handle_ref, view_desc)| (*handle_ref, view_desc))
+                .collect::<Vec<_>>();
+
+            api.begin_render_pass(
+                attachments.render_pass.as_ref().unwrap(),
+                extent,
+                &color_attachments,
+                attachments
+                    .depth
+                    .as_ref()
+                    .map(|(handle_ref, view_desc)| (*handle_ref, view_desc)),
+            )?;
+
+            api.set_default_view_and_scissor(extent);
+
+            {
+                let pipeline = api.bind_raster_pipeline(state.create_pipeline_binding())?;
+
+                for &(binding, buffer) in &attachments.vertex_buffers {
+                    pipeline.bind_vertex_buffers(binding, &[(buffer, 0)]);
+                }
+
+                draw_fn(api, &pipeline);
+            }
+
+            api.end_render_pass();
+
+            Ok(())
+        });
+    }
+
+    /// Draws a single triangle covering the attachments; the vertex shader
+    /// is expected to generate its corners from `SV_VertexID`.
+    pub fn draw_fullscreen(self) {
+        self.draw(|_api, pipeline| pipeline.draw(3, 1, 0, 0));
+    }
+}
+
 impl<'rg, RgPipelineHandle> SimpleRenderPass<'rg, RgPipelineHandle> {
+    pub fn id(&self) -> PassId {
+        self.pass.id()
+    }
+
+    /// See `PassBuilder::ordering`.
+    pub fn ordering(mut self, ordering: PassOrdering) -> Self {
+        self.pass.ordering(ordering);
+        self
+    }
+
+    /// See `PassBuilder::keep_after`.
+    pub fn keep_after(mut self, pass: PassId) -> Self {
+        self.pass.keep_after(pass);
+        self
+    }
+
     pub fn read<Res>(mut self, handle: &Handle<Res>) -> Self
     where
         Res: Resource + 'static,
@@ -268,6 +530,18 @@ impl<'rg, RgPipelineHandle> SimpleRenderPass<'rg, RgPipelineHandle> {
         self
     }
 
+    /// Binds `handle` as a uniform buffer, for shaders declaring it as
+    /// a `ConstantBuffer` named `*_buf`.
+    pub fn read_uniform(mut self, handle: &Handle<Buffer>) -> Self {
+        let handle_ref = self
+            .pass
+            .read(handle, AccessType::AnyShaderReadUniformBuffer);
+
+        self.state.bindings.push(BindRgRef::bind(&handle_ref));
+
+        self
+    }
+
     pub fn read_array(mut self, handles: &[Handle<Image>]) -> Self {
         assert!(!handles.is_empty());
 
@@ -372,7 +646,7 @@ impl<'rg, RgPipelineHandle> SimpleRenderPass<'rg, RgPipelineHandle> {
         self
     }
 
-    pub fn dynamic_storage_buffer_vec<T: Copy + 'static>(mut self, consts: Vec<T>) -> Self {
+    pub fn dynamic_storage_buffer_vec<T: Copy + Send + 'static>(mut self, consts: Vec<T>) -> Self {
         let binding_idx = self.state.bindings.len();
 
         self.state
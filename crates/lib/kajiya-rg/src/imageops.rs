@@ -1,6 +1,8 @@
 use crate::{self as rg, RenderGraph};
 use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::image::*};
 
+/// Clears to zero, which is the far plane: depth is reversed throughout, with an infinite
+/// far plane in perspective projections, and raster pipelines test with `GREATER_OR_EQUAL`.
 pub fn clear_depth(rg: &mut RenderGraph, img: &mut rg::Handle<Image>) {
     let mut pass = rg.add_pass("clear depth");
     let output_ref = pass.write(img, AccessType::TransferWrite);
@@ -1,18 +1,58 @@
+mod barrier_log;
+mod compute_context;
+mod gpu_asserts;
+mod gpu_resource_stats;
 mod graph;
 mod hl;
+mod memory_stats;
+mod parallel_recording;
 mod pass_api;
 mod pass_builder;
+mod pass_ordering;
+mod pass_toggles;
 mod resource;
 mod resource_registry;
+mod submit_stats;
 mod temporal;
 
 pub mod imageops;
 pub mod renderer;
 
+pub use barrier_log::*;
+pub use compute_context::ComputeContext;
+pub use gpu_resource_stats::{gpu_resource_stats, GpuResourceStats};
 pub use graph::*;
 pub use hl::*;
+pub use memory_stats::{transient_memory_stats, TransientMemoryStats, TransientResourceMemory};
+pub use parallel_recording::{recording_thread_count, set_recording_thread_count};
 pub use pass_api::*;
 pub use pass_builder::*;
+pub use pass_ordering::{is_pass_reordering_enabled, set_pass_reordering, PassId, PassOrdering};
+pub use pass_toggles::{enable_all_passes, is_pass_enabled, pass_toggles, set_pass_enabled};
 pub use resource::*;
 pub use resource_registry::ResourceRegistry;
+pub use submit_stats::{
+    frame_submit_stats, is_submit_split, set_split_submit, FrameSubmitStats, SubmitBatchInfo,
+};
 pub use temporal::*;
+
+/// The types for authoring render passes in other crates: `use kajiya_rg::prelude::*`.
+///
+/// Kept stable as the modules of this crate get reorganized, unlike the paths of
+/// the items at the crate root. The resource and view traits are sealed; the graph
+/// only knows how to track its own kinds of resources.
+pub mod prelude {
+    pub use crate::{
+        BindMutToSimpleRenderPass, BindRgRef, BindToSimpleRenderPass, Buffer, BufferDesc,
+        ConstBlob, ExportedHandle, GetOrCreateTemporal, GpuRt, GpuSrv, GpuUav, GpuViewType, Handle,
+        Image, ImageDesc, ImageViewDesc, ImportExportToRenderGraph, IntoRenderPassPipelineBinding,
+        PassBuilder, PassId, PassOrdering, RayTracingAccelerationDesc, ReadOnlyHandle, Ref,
+        RenderGraph, RenderPassApi, RenderPassBinding, Resource, ResourceDesc,
+        RgComputePipelineHandle, RgRasterPipelineHandle, RgRtPipelineHandle, RtHitGroup,
+        SimpleRenderPass, TemporalRenderGraph, TemporalResourceKey,
+    };
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
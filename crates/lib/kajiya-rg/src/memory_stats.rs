@@ -0,0 +1,53 @@
+//! Transient memory used by the latest graph execution, broken down per resource.
+//!
+//! The graph doesn't alias its transient resources, so they all stay alive from
+//! the start of the execution until it's retired; the watermark is their sum.
+
+use kajiya_backend::{ash::vk, vulkan::device::Device};
+use parking_lot::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct TransientResourceMemory {
+    /// The first pass to write the resource, which is usually what creates it.
+    pub first_pass: Option<String>,
+    /// Kind, size and format.
+    pub desc: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TransientMemoryStats {
+    /// Total of the latest execution.
+    pub watermark_bytes: u64,
+    /// Highest `watermark_bytes` seen so far.
+    pub peak_watermark_bytes: u64,
+    /// Largest first.
+    pub resources: Vec<TransientResourceMemory>,
+}
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<TransientMemoryStats> = Default::default();
+}
+
+/// Stats of the latest graph execution.
+pub fn transient_memory_stats() -> TransientMemoryStats {
+    STATS.lock().clone()
+}
+
+pub(crate) fn image_size_bytes(device: &Device, image: vk::Image) -> u64 {
+    unsafe { device.raw.get_image_memory_requirements(image) }.size
+}
+
+pub(crate) fn buffer_size_bytes(device: &Device, buffer: vk::Buffer) -> u64 {
+    unsafe { device.raw.get_buffer_memory_requirements(buffer) }.size
+}
+
+pub(crate) fn report_execution(mut resources: Vec<TransientResourceMemory>) {
+    resources.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes));
+    let watermark_bytes = resources.iter().map(|res| res.size_bytes).sum();
+
+    let mut stats = STATS.lock();
+    stats.peak_watermark_bytes = stats.peak_watermark_bytes.max(watermark_bytes);
+    stats.watermark_bytes = watermark_bytes;
+    stats.resources = resources;
+}
@@ -0,0 +1,34 @@
+//! Opt-in recording of the main command buffer on worker threads.
+//!
+//! The passes are split into contiguous ranges, each recorded into its own primary
+//! command buffer, and submitted in order with the main one. Barriers and GPU queries
+//! are resolved up front on the calling thread, so the ranges are independent.
+//!
+//! Secondary command buffers would be stitched with a single `vkCmdExecuteCommands`,
+//! but they can't begin render passes, which raster passes do from their render functions.
+//!
+//! Ranges containing passes rendered with `PassBuilder::render_local` are recorded
+//! on the calling thread. Each worker gets an equal share of the frame's remaining
+//! dynamic constants.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use kajiya_backend::vulkan::device::MAX_PARALLEL_COMMAND_BUFFERS;
+
+/// Ranges shorter than this aren't worth the thread handoff.
+pub(crate) const MIN_PASSES_PER_RANGE: usize = 8;
+
+static THREAD_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+/// Sets how many threads record the main command buffer. One disables parallel recording;
+/// clamped to `MAX_PARALLEL_COMMAND_BUFFERS`.
+pub fn set_recording_thread_count(count: usize) {
+    THREAD_COUNT.store(
+        count.clamp(1, MAX_PARALLEL_COMMAND_BUFFERS),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn recording_thread_count() -> usize {
+    THREAD_COUNT.load(Ordering::Relaxed)
+}
@@ -1,4 +1,4 @@
-use std::{cell::UnsafeCell, sync::Arc};
+use std::sync::Arc;
 
 use arrayvec::ArrayVec;
 
@@ -9,13 +9,13 @@ use super::{
 
 use kajiya_backend::{
     ash::vk,
-    chunky_list::TempList,
     dynamic_constants::{
         DynamicConstants, MAX_DYNAMIC_CONSTANTS_BYTES_PER_DISPATCH,
         MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES,
     },
     vulkan::{
         device::{CommandBuffer, Device},
+        dynamic_rendering::{RenderingAttachmentInfoKHR, RenderingInfoKHR},
         image::*,
         ray_tracing::{RayTracingAcceleration, RayTracingPipeline},
         shader::{
@@ -29,12 +29,19 @@ use kajiya_backend::{
 pub struct RenderPassApi<'a, 'exec_params, 'constants> {
     pub cb: &'a CommandBuffer,
     pub resources: &'a mut ResourceRegistry<'exec_params, 'constants>,
+    pub(crate) view_idx: usize,
+    // Whether `end_render_pass` ends dynamic rendering rather than a render pass object
+    pub(crate) in_dynamic_rendering: bool,
 }
 
 pub enum DescriptorSetBinding {
     Image(vk::DescriptorImageInfo),
     ImageArray(Vec<vk::DescriptorImageInfo>),
     Buffer(vk::DescriptorBufferInfo),
+    /// Written as a uniform buffer if the shader declares one at its binding,
+    /// and as a storage buffer otherwise.
+    ReadOnlyBuffer(vk::DescriptorBufferInfo),
+    InputAttachment(vk::DescriptorImageInfo),
     RayTracingAcceleration(vk::AccelerationStructureKHR),
     DynamicBuffer {
         buffer: vk::DescriptorBufferInfo,
@@ -185,7 +192,7 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
                         self.resources
                             .execution_params
                             .frame_constants_layout
-                            .globals_offset,
+                            .view_globals_offset(self.view_idx),
                         self.resources
                             .execution_params
                             .frame_constants_layout
@@ -194,6 +201,15 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
                             .execution_params
                             .frame_constants_layout
                             .triangle_lights_offset,
+                        self.resources
+                            .execution_params
+                            .frame_constants_layout
+                            .punctual_lights_offset,
+                        self.resources
+                            .execution_params
+                            .frame_constants_layout
+                            .rect_lights_offset,
+                        self.resources.execution_params.gpu_asserts_offset,
                     ],
                 );
             }
@@ -217,6 +233,17 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
                                 )
                                 .build(),
                         ),
+                        RenderPassBinding::InputAttachment(image) => {
+                            DescriptorSetBinding::InputAttachment(
+                                vk::DescriptorImageInfo::builder()
+                                    .image_layout(image.image_layout)
+                                    .image_view(
+                                        self.resources
+                                            .image_view(image.handle, &image.view_desc)?,
+                                    )
+                                    .build(),
+                            )
+                        }
                         RenderPassBinding::ImageArray(images) => DescriptorSetBinding::ImageArray(
                             images
                                 .iter()
@@ -231,16 +258,22 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
                                 })
                                 .collect::<Result<Vec<_>, BackendError>>()?,
                         ),
-                        RenderPassBinding::Buffer(buffer) => DescriptorSetBinding::Buffer(
-                            vk::DescriptorBufferInfo::builder()
+                        RenderPassBinding::Buffer(buffer) => {
+                            let info = vk::DescriptorBufferInfo::builder()
                                 .buffer(
                                     self.resources
                                         .buffer_from_raw_handle::<GpuSrv>(buffer.handle)
                                         .raw,
                                 )
                                 .range(vk::WHOLE_SIZE)
-                                .build(),
-                        ),
+                                .build();
+
+                            if buffer.read_only {
+                                DescriptorSetBinding::ReadOnlyBuffer(info)
+                            } else {
+                                DescriptorSetBinding::Buffer(info)
+                            }
+                        }
                         RenderPassBinding::RayTracingAcceleration(acc) => {
                             DescriptorSetBinding::RayTracingAcceleration(
                                 self.resources
@@ -277,7 +310,7 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
                 &pipeline,
                 set_idx,
                 &bindings,
-            );
+            )?;
         }
 
         for (set_idx, binding) in &binding.raw_bindings {
@@ -305,6 +338,9 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
         Ok(())
     }
 
+    /// Begins rendering into the attachments, with `VK_KHR_dynamic_rendering` where
+    /// the render pass allows it (see `RenderPass::dynamic_rendering`), or else with
+    /// the render pass object, and an imageless framebuffer.
     pub fn begin_render_pass(
         &mut self,
         render_pass: &kajiya_backend::vulkan::shader::RenderPass,
@@ -312,6 +348,15 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
         color_attachments: &[(Ref<Image, GpuRt>, &ImageViewDesc)],
         depth_attachment: Option<(Ref<Image, GpuRt>, &ImageViewDesc)>,
     ) -> Result<(), BackendError> {
+        if render_pass.dynamic_rendering.is_some() {
+            return self.begin_dynamic_rendering(
+                render_pass,
+                dims,
+                color_attachments,
+                depth_attachment,
+            );
+        }
+
         let device = self.resources.execution_params.device;
 
         let framebuffer = render_pass
@@ -370,10 +415,91 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
         Ok(())
     }
 
+    fn begin_dynamic_rendering(
+        &mut self,
+        render_pass: &kajiya_backend::vulkan::shader::RenderPass,
+        dims: [u32; 2],
+        color_attachments: &[(Ref<Image, GpuRt>, &ImageViewDesc)],
+        depth_attachment: Option<(Ref<Image, GpuRt>, &ImageViewDesc)>,
+    ) -> Result<(), BackendError> {
+        let device = self.resources.execution_params.device;
+        let attachments = render_pass.dynamic_rendering.as_ref().unwrap();
+
+        assert_eq!(
+            color_attachments.len(),
+            attachments.color_attachments.len(),
+            "color attachment count mismatch"
+        );
+
+        let color_attachment_infos: Result<
+            ArrayVec<[RenderingAttachmentInfoKHR; MAX_COLOR_ATTACHMENTS]>,
+            BackendError,
+        > = color_attachments
+            .iter()
+            .zip(attachments.color_attachments.iter())
+            .map(|((img, view), desc)| {
+                Ok(RenderingAttachmentInfoKHR::new(
+                    self.resources.image_view(img.handle, view)?,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    desc.load_op,
+                    desc.store_op,
+                ))
+            })
+            .collect();
+        let color_attachment_infos = color_attachment_infos?;
+
+        let depth_attachment_info = match (&depth_attachment, &attachments.depth_attachment) {
+            (Some((img, view)), Some(desc)) => Some(RenderingAttachmentInfoKHR::new(
+                self.resources.image_view(img.handle, view)?,
+                vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL,
+                desc.load_op,
+                desc.store_op,
+            )),
+            (None, None) => None,
+            _ => panic!("depth attachment mismatch"),
+        };
+
+        let [width, height] = dims;
+
+        let rendering_info = RenderingInfoKHR::new(
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D {
+                    width: width as _,
+                    height: height as _,
+                },
+            },
+            &color_attachment_infos,
+            depth_attachment_info.as_ref(),
+        );
+
+        unsafe {
+            device.cmd_begin_rendering(self.cb.raw, &rendering_info);
+        }
+
+        self.in_dynamic_rendering = true;
+
+        Ok(())
+    }
+
+    /// Moves on to the next subpass of the render pass begun with `begin_render_pass`.
+    pub fn next_subpass(&mut self) {
+        let device = self.resources.execution_params.device;
+        unsafe {
+            device
+                .raw
+                .cmd_next_subpass(self.cb.raw, vk::SubpassContents::INLINE);
+        }
+    }
+
     pub fn end_render_pass(&mut self) {
         let device = self.resources.execution_params.device;
         unsafe {
-            device.raw.cmd_end_render_pass(self.cb.raw);
+            if std::mem::take(&mut self.in_dynamic_rendering) {
+                device.cmd_end_rendering(self.cb.raw);
+            } else {
+                device.raw.cmd_end_render_pass(self.cb.raw);
+            }
         }
     }
 
@@ -408,6 +534,34 @@ impl<'a, 'exec_params, 'constants> RenderPassApi<'a, 'exec_params, 'constants> {
             );
         }
     }
+
+    /// Clips the following draws to `rect`, in framebuffer pixels, until set again.
+    /// Can be changed between any draws of a render pass, e.g. per UI element or atlas tile;
+    /// `set_default_view_and_scissor` goes back to the full target.
+    pub fn set_scissor(&self, rect: vk::Rect2D) {
+        // Negative offsets are invalid; clip those parts of the rectangle instead.
+        let clip_x = (-rect.offset.x).max(0) as u32;
+        let clip_y = (-rect.offset.y).max(0) as u32;
+
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D {
+                x: rect.offset.x.max(0),
+                y: rect.offset.y.max(0),
+            },
+            extent: vk::Extent2D {
+                width: rect.extent.width.saturating_sub(clip_x),
+                height: rect.extent.height.saturating_sub(clip_y),
+            },
+        };
+
+        unsafe {
+            self.resources
+                .execution_params
+                .device
+                .raw
+                .cmd_set_scissor(self.cb.raw, 0, &[rect]);
+        }
+    }
 }
 
 pub struct BoundComputePipeline<'api, 'a, 'exec_params, 'constants> {
@@ -485,6 +639,88 @@ impl<'api, 'a, 'exec_params, 'constants> BoundRasterPipeline<'api, 'a, 'exec_par
                 )
         }
     }
+
+    /// See `RenderPassApi::set_scissor`.
+    pub fn set_scissor(&self, rect: vk::Rect2D) {
+        self.api.set_scissor(rect);
+    }
+
+    /// Binds buffers to consecutive bindings of `RasterPipelineDesc::vertex_input`, starting
+    /// at `first_binding`, along with byte offsets into them. The buffers must be read
+    /// with `AccessType::VertexBuffer`.
+    pub fn bind_vertex_buffers(&self, first_binding: u32, buffers: &[(Ref<Buffer, GpuSrv>, u64)]) {
+        let (raw_buffers, offsets): (Vec<vk::Buffer>, Vec<u64>) = buffers
+            .iter()
+            .map(|(buffer, offset)| (self.api.resources.buffer(*buffer).raw, *offset))
+            .unzip();
+
+        unsafe {
+            self.api.device().raw.cmd_bind_vertex_buffers(
+                self.api.cb.raw,
+                first_binding,
+                &raw_buffers,
+                &offsets,
+            );
+        }
+    }
+
+    /// The buffer must be read with `AccessType::IndexBuffer`.
+    pub fn bind_index_buffer(
+        &self,
+        buffer: Ref<Buffer, GpuSrv>,
+        offset: u64,
+        index_type: vk::IndexType,
+    ) {
+        unsafe {
+            self.api.device().raw.cmd_bind_index_buffer(
+                self.api.cb.raw,
+                self.api.resources.buffer(buffer).raw,
+                offset,
+                index_type,
+            );
+        }
+    }
+
+    /// Draws `instance_count` instances of `vertex_count` vertices. Instance-rate
+    /// vertex buffers advance once per instance, starting at `first_instance`.
+    pub fn draw(
+        &self,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.api.device().raw.cmd_draw(
+                self.api.cb.raw,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance,
+            );
+        }
+    }
+
+    /// Like `draw`, with the vertices picked by the bound index buffer.
+    pub fn draw_indexed(
+        &self,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.api.device().raw.cmd_draw_indexed(
+                self.api.cb.raw,
+                index_count,
+                instance_count,
+                first_index,
+                vertex_offset,
+                first_instance,
+            );
+        }
+    }
 }
 
 pub struct RenderPassImageBinding {
@@ -495,6 +731,7 @@ pub struct RenderPassImageBinding {
 
 pub struct RenderPassBufferBinding {
     handle: GraphRawResourceHandle,
+    read_only: bool,
 }
 
 pub struct RenderPassRayTracingAccelerationBinding {
@@ -504,6 +741,7 @@ pub struct RenderPassRayTracingAccelerationBinding {
 pub enum RenderPassBinding {
     Image(RenderPassImageBinding),
     ImageArray(Vec<RenderPassImageBinding>),
+    InputAttachment(RenderPassImageBinding),
     Buffer(RenderPassBufferBinding),
     RayTracingAcceleration(RenderPassRayTracingAccelerationBinding),
     DynamicConstants(u32),
@@ -610,10 +848,33 @@ impl Ref<Image, GpuUav> {
     }
 }
 
+impl Ref<Image, GpuRt> {
+    /// Binds an attachment of the current render pass for reading as a `SubpassInput`,
+    /// in a subpass listing it in `SubpassDesc::input_attachments`. Depth is read
+    /// with a `DEPTH` aspect view.
+    pub fn bind_input_attachment(&self, view_desc: ImageViewDescBuilder) -> RenderPassBinding {
+        let view_desc = view_desc.build().unwrap();
+
+        // Must match the layouts in `create_render_pass`
+        let image_layout = if view_desc.aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        } else {
+            vk::ImageLayout::GENERAL
+        };
+
+        RenderPassBinding::InputAttachment(RenderPassImageBinding {
+            handle: self.handle,
+            view_desc,
+            image_layout,
+        })
+    }
+}
+
 impl BindRgRef for Ref<Buffer, GpuSrv> {
     fn bind(&self) -> RenderPassBinding {
         RenderPassBinding::Buffer(RenderPassBufferBinding {
             handle: self.handle,
+            read_only: true,
         })
     }
 }
@@ -622,6 +883,7 @@ impl BindRgRef for Ref<Buffer, GpuUav> {
     fn bind(&self) -> RenderPassBinding {
         RenderPassBinding::Buffer(RenderPassBufferBinding {
             handle: self.handle,
+            read_only: false,
         })
     }
 }
@@ -634,28 +896,41 @@ impl BindRgRef for Ref<RayTracingAcceleration, GpuSrv> {
     }
 }
 
+/// Images are bound as sampled or storage images depending on the layout their access puts them in.
+/// Other layouts, e.g. of attachments, can't be bound to descriptors.
+fn image_descriptor_type(
+    layout: vk::ImageLayout,
+    binding_idx: usize,
+) -> Result<vk::DescriptorType, BackendError> {
+    match layout {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => Ok(vk::DescriptorType::SAMPLED_IMAGE),
+        vk::ImageLayout::GENERAL => Ok(vk::DescriptorType::STORAGE_IMAGE),
+        _ => Err(BackendError::ResourceAccess {
+            info: format!(
+                "Image bound at binding {} in layout {:?}, which isn't usable from shaders",
+                binding_idx, layout
+            ),
+        }),
+    }
+}
+
 fn bind_descriptor_set(
     device: &Device,
     cb: &CommandBuffer,
     pipeline: &impl std::ops::Deref<Target = ShaderPipelineCommon>,
     set_index: u32,
     bindings: &[DescriptorSetBinding],
-) {
+) -> Result<(), BackendError> {
     let shader_set_info = if let Some(info) = pipeline.set_layout_info.get(set_index as usize) {
         info
     } else {
-        println!(
-            "bind_descriptor_set: set index {} does not exist",
+        log::warn!(
+            "Descriptor set {} is bound, but the pipeline doesn't use it",
             set_index
         );
-        return;
+        return Ok(());
     };
 
-    let image_info = TempList::new();
-    let buffer_info = TempList::new();
-    let accel_info: TempList<UnsafeCell<vk::WriteDescriptorSetAccelerationStructureKHR>> =
-        TempList::new();
-
     let raw_device = &device.raw;
 
     let descriptor_pool = {
@@ -663,7 +938,7 @@ fn bind_descriptor_set(
             .max_sets(1)
             .pool_sizes(&pipeline.descriptor_pool_sizes);
 
-        unsafe { raw_device.create_descriptor_pool(&descriptor_pool_create_info, None) }.unwrap()
+        device.create_descriptor_pool(&descriptor_pool_create_info, "pass descriptor pool")?
     };
     device.defer_release(descriptor_pool);
 
@@ -674,88 +949,131 @@ fn bind_descriptor_set(
                 &pipeline.descriptor_set_layouts[set_index as usize],
             ));
 
-        unsafe { raw_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }.unwrap()[0]
+        unsafe { raw_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }?[0]
     };
 
-    unsafe {
-        let mut dynamic_offsets: Vec<u32> = Vec::new();
-        let descriptor_writes: Vec<vk::WriteDescriptorSet> =
-            bindings
-                .iter()
-                .enumerate()
-                .filter(|(binding_idx, _)| shader_set_info.contains_key(&(*binding_idx as u32)))
-                .map(|(binding_idx, binding)| {
-                    let write = vk::WriteDescriptorSet::builder()
-                        .dst_set(descriptor_set)
-                        .dst_binding(binding_idx as _)
-                        .dst_array_element(0);
-
-                    match binding {
-                        DescriptorSetBinding::Image(image) => write
-                            .descriptor_type(match image.image_layout {
-                                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
-                                    vk::DescriptorType::SAMPLED_IMAGE
-                                }
-                                vk::ImageLayout::GENERAL => vk::DescriptorType::STORAGE_IMAGE,
-                                _ => unimplemented!("{:?}", image.image_layout),
-                            })
-                            .image_info(std::slice::from_ref(image_info.add(*image)))
-                            .build(),
-                        DescriptorSetBinding::ImageArray(images) => {
-                            assert!(!images.is_empty());
-
-                            write
-                                .descriptor_type(match images[0].image_layout {
-                                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
-                                        vk::DescriptorType::SAMPLED_IMAGE
-                                    }
-                                    vk::ImageLayout::GENERAL => vk::DescriptorType::STORAGE_IMAGE,
-                                    _ => unimplemented!("{:?}", images[0].image_layout),
-                                })
-                                .image_info(images.as_slice())
-                                .build()
-                        }
-                        DescriptorSetBinding::Buffer(buffer) => write
-                            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                            .buffer_info(std::slice::from_ref(buffer_info.add(*buffer)))
-                            .build(),
-                        DescriptorSetBinding::DynamicBuffer { buffer, offset } => {
-                            dynamic_offsets.push(*offset);
-                            write
-                                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
-                                .buffer_info(std::slice::from_ref(buffer_info.add(*buffer)))
-                                .build()
-                        }
-                        DescriptorSetBinding::DynamicStorageBuffer { buffer, offset } => {
-                            dynamic_offsets.push(*offset);
-                            write
-                                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
-                                .buffer_info(std::slice::from_ref(buffer_info.add(*buffer)))
-                                .build()
-                        }
-                        DescriptorSetBinding::RayTracingAcceleration(acc) => {
-                            let mut write = write
-                            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
-                            .push_next(
-                                accel_info.add(UnsafeCell::new(
-                                    vk::WriteDescriptorSetAccelerationStructureKHR::builder()
-                                        .acceleration_structures(std::slice::from_ref(acc))
-                                        .build(),
-                                )).get().as_mut().unwrap(),
-                            )
-                            .build();
+    // The descriptors are packed back to back, in the order of the template entries;
+    // the offsets thus only depend on the template key.
+    let mut template_data = DescriptorTemplateData::default();
+    let mut template_entries: Vec<vk::DescriptorUpdateTemplateEntry> = Vec::new();
+    let mut dynamic_offsets: Vec<u32> = Vec::new();
+
+    for (binding_idx, binding) in bindings
+        .iter()
+        .enumerate()
+        .filter(|(binding_idx, _)| shader_set_info.contains_key(&(*binding_idx as u32)))
+    {
+        let (descriptor_type, (offset, stride, descriptor_count)) = match binding {
+            DescriptorSetBinding::Image(image) => (
+                image_descriptor_type(image.image_layout, binding_idx)?,
+                template_data.push(std::slice::from_ref(image)),
+            ),
+            DescriptorSetBinding::InputAttachment(image) => (
+                vk::DescriptorType::INPUT_ATTACHMENT,
+                template_data.push(std::slice::from_ref(image)),
+            ),
+            DescriptorSetBinding::ImageArray(images) => {
+                assert!(!images.is_empty());
+
+                (
+                    image_descriptor_type(images[0].image_layout, binding_idx)?,
+                    template_data.push(images.as_slice()),
+                )
+            }
+            DescriptorSetBinding::Buffer(buffer) => {
+                assert_ne!(
+                    shader_set_info.get(&(binding_idx as u32)),
+                    Some(&vk::DescriptorType::UNIFORM_BUFFER),
+                    "Buffer written to at binding {}, which is a uniform buffer",
+                    binding_idx
+                );
 
-                            // This is only set by the builder for images, buffers, or views; need to set explicitly after
-                            write.descriptor_count = 1;
-                            write
-                        }
-                    }
-                })
-                .collect();
+                (
+                    vk::DescriptorType::STORAGE_BUFFER,
+                    template_data.push(std::slice::from_ref(buffer)),
+                )
+            }
+            DescriptorSetBinding::ReadOnlyBuffer(buffer) => (
+                match shader_set_info.get(&(binding_idx as u32)) {
+                    Some(vk::DescriptorType::UNIFORM_BUFFER) => vk::DescriptorType::UNIFORM_BUFFER,
+                    _ => vk::DescriptorType::STORAGE_BUFFER,
+                },
+                template_data.push(std::slice::from_ref(buffer)),
+            ),
+            DescriptorSetBinding::DynamicBuffer { buffer, offset } => {
+                dynamic_offsets.push(*offset);
+                (
+                    vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                    template_data.push(std::slice::from_ref(buffer)),
+                )
+            }
+            DescriptorSetBinding::DynamicStorageBuffer { buffer, offset } => {
+                dynamic_offsets.push(*offset);
+                (
+                    vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                    template_data.push(std::slice::from_ref(buffer)),
+                )
+            }
+            DescriptorSetBinding::RayTracingAcceleration(acc) => (
+                vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                template_data.push(std::slice::from_ref(acc)),
+            ),
+        };
+
+        template_entries.push(vk::DescriptorUpdateTemplateEntry {
+            dst_binding: binding_idx as _,
+            dst_array_element: 0,
+            descriptor_count,
+            descriptor_type,
+            offset,
+            stride,
+        });
+    }
 
-        device.raw.update_descriptor_sets(&descriptor_writes, &[]);
+    let template_key = (
+        set_index,
+        template_entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.dst_binding,
+                    entry.descriptor_type,
+                    entry.descriptor_count,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let template = match pipeline
+        .descriptor_update_templates
+        .lock()
+        .entry(template_key)
+    {
+        std::collections::hash_map::Entry::Occupied(entry) => *entry.get(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            let template_create_info = vk::DescriptorUpdateTemplateCreateInfo::builder()
+                .descriptor_update_entries(&template_entries)
+                .template_type(vk::DescriptorUpdateTemplateType::DESCRIPTOR_SET)
+                .descriptor_set_layout(pipeline.descriptor_set_layouts[set_index as usize]);
+
+            *entry.insert(unsafe {
+                raw_device.create_descriptor_update_template(&template_create_info, None)?
+            })
+        }
+    };
 
-        device.raw.cmd_bind_descriptor_sets(
+    unsafe {
+        {
+            puffin::profile_scope!("update descriptor set");
+
+            raw_device.update_descriptor_set_with_template(
+                descriptor_set,
+                template,
+                template_data.words.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+
+        raw_device.cmd_bind_descriptor_sets(
             cb.raw,
             pipeline.pipeline_bind_point,
             pipeline.pipeline_layout,
@@ -764,4 +1082,39 @@ fn bind_descriptor_set(
             dynamic_offsets.as_slice(),
         );
     }
+
+    Ok(())
+}
+
+/// The descriptors of a set, as read by its descriptor update template.
+/// In 64-bit words, so that every descriptor is aligned.
+#[derive(Default)]
+struct DescriptorTemplateData {
+    words: Vec<u64>,
+}
+
+impl DescriptorTemplateData {
+    /// Appends `descriptors`, returning their byte offset, stride, and count.
+    fn push<T: Copy>(&mut self, descriptors: &[T]) -> (usize, usize, u32) {
+        let stride = std::mem::size_of::<T>();
+        assert_eq!(stride % std::mem::size_of::<u64>(), 0);
+
+        let offset = self.words.len() * std::mem::size_of::<u64>();
+        let byte_count = stride * descriptors.len();
+
+        self.words.resize(
+            self.words.len() + byte_count / std::mem::size_of::<u64>(),
+            0,
+        );
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                descriptors.as_ptr() as *const u8,
+                (self.words.as_mut_ptr() as *mut u8).add(offset),
+                byte_count,
+            );
+        }
+
+        (offset, stride, descriptors.len() as u32)
+    }
 }
@@ -1,10 +1,10 @@
-use crate::{PassResourceAccessSyncType, RenderPassApi};
+use crate::{PassId, PassOrdering, PassResourceAccessSyncType, RenderPassApi};
 
 use super::{
     graph::{
-        PassResourceAccessType, PassResourceRef, RecordedPass, RenderGraph, RgComputePipeline,
-        RgComputePipelineHandle, RgRasterPipeline, RgRasterPipelineHandle, RgRtPipeline,
-        RgRtPipelineHandle, TypeEquals,
+        PassResourceAccessType, PassResourceRef, RecordedPass, RenderFn, RenderGraph,
+        RgComputePipeline, RgComputePipelineHandle, RgRasterPipeline, RgRasterPipelineHandle,
+        RgRtPipeline, RgRtPipelineHandle, TypeEquals,
     },
     resource::*,
 };
@@ -18,7 +18,6 @@ use std::{marker::PhantomData, path::Path};
 
 pub struct PassBuilder<'rg> {
     pub(crate) rg: &'rg mut RenderGraph,
-    #[allow(dead_code)]
     pub(crate) pass_idx: usize,
     pub(crate) pass: Option<RecordedPass>,
 }
@@ -30,6 +29,21 @@ impl<'s> Drop for PassBuilder<'s> {
 }
 
 impl<'rg> PassBuilder<'rg> {
+    pub fn id(&self) -> PassId {
+        PassId(self.pass_idx)
+    }
+
+    /// Where this pass prefers to go when the graph reorders passes; see `set_pass_reordering`.
+    pub fn ordering(&mut self, ordering: PassOrdering) {
+        self.pass.as_mut().unwrap().ordering = ordering;
+    }
+
+    /// Keeps this pass right after `pass` when the graph reorders passes, such as for
+    /// dependencies the graph can't see. `pass` must have been added before this one.
+    pub fn keep_after(&mut self, pass: PassId) {
+        self.pass.as_mut().unwrap().keep_after = Some(pass);
+    }
+
     pub fn create<Desc: ResourceDesc>(
         &mut self,
         desc: Desc,
@@ -323,15 +337,23 @@ impl<'rg> PassBuilder<'rg> {
     }
 
     pub fn render(
-        mut self,
+        self,
+        render: impl (FnOnce(&mut RenderPassApi) -> Result<(), BackendError>) + Send + 'static,
+    ) {
+        self.set_render_fn(RenderFn::Send(Box::new(render)));
+    }
+
+    /// Like `render`, for render functions which can't be sent to other threads.
+    /// With parallel recording, the passes using it are recorded on the calling thread.
+    pub fn render_local(
+        self,
         render: impl (FnOnce(&mut RenderPassApi) -> Result<(), BackendError>) + 'static,
     ) {
-        let prev = self
-            .pass
-            .as_mut()
-            .unwrap()
-            .render_fn
-            .replace(Box::new(render));
+        self.set_render_fn(RenderFn::Local(Box::new(render)));
+    }
+
+    fn set_render_fn(mut self, render_fn: RenderFn) {
+        let prev = self.pass.as_mut().unwrap().render_fn.replace(render_fn);
 
         assert!(prev.is_none());
     }
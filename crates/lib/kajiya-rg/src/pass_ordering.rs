@@ -0,0 +1,330 @@
+//! Optional reordering of passes when a graph is compiled, and the constraints passes
+//! can place on it beyond their data dependencies.
+//!
+//! Passes are recorded in the order they were added, unless reordering is enabled with
+//! `set_pass_reordering`. The passes are then scheduled breadth-first over their data
+//! dependencies: of the passes whose inputs are ready, the one which became ready the
+//! earliest goes next. That spreads producers and consumers apart, so that the barrier
+//! in front of a consumer is less likely to wait on work still in flight.
+//!
+//! The graph only sees the resources passes declare. Passes which declare none, such as
+//! `RenderGraph::global_barrier`, stay in place relative to all the others; dependencies
+//! through anything else must be made explicit with `PassBuilder::keep_after`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::graph::RecordedPass;
+
+/// Identifies a pass within its graph, for `PassBuilder::keep_after`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PassId(pub(crate) usize);
+
+/// Where a pass prefers to go when the graph reorders passes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PassOrdering {
+    /// Runs as soon as its inputs are ready, ahead of the default passes.
+    AsEarlyAsPossible,
+    Default,
+    /// Waits while any other passes are ready to run. Passes writing the swapchain
+    /// are scheduled like this regardless.
+    AsLateAsPossible,
+}
+
+impl Default for PassOrdering {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+static PASS_REORDERING: AtomicBool = AtomicBool::new(false);
+
+/// Takes effect the next time a graph is compiled.
+pub fn set_pass_reordering(enabled: bool) {
+    PASS_REORDERING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_pass_reordering_enabled() -> bool {
+    PASS_REORDERING.load(Ordering::Relaxed)
+}
+
+// Passes scheduled as one: a pass, followed by those kept after it.
+struct PassGroup {
+    passes: Vec<usize>,
+    ordering: PassOrdering,
+    // Groups which must go before this one
+    dependencies: Vec<usize>,
+}
+
+/// Reorders `passes`, given in recording order, or leaves them as they are if the
+/// constraints can't be satisfied.
+pub(crate) fn reorder_passes(
+    passes: &mut Vec<RecordedPass>,
+    writes_swapchain: impl Fn(&RecordedPass) -> bool,
+) {
+    let pass_count = passes.len();
+    if pass_count < 2 {
+        return;
+    }
+
+    let pass_dependencies = data_dependencies(passes);
+
+    // `keep_after` chains each pass onto the group of the pass it names.
+    let mut group_of_pass: Vec<usize> = Vec::with_capacity(pass_count);
+    let mut groups: Vec<PassGroup> = Vec::new();
+
+    for (pass_pos, pass) in passes.iter().enumerate() {
+        let chained_group = pass
+            .keep_after
+            .and_then(|leader| passes[..pass_pos].iter().position(|p| p.idx == leader.0))
+            .map(|leader_pos| group_of_pass[leader_pos])
+            // Only onto the last pass of the group, so that chains can't fork
+            .filter(|&group| {
+                let group_passes = &groups[group].passes;
+                passes[*group_passes.last().unwrap()].idx == pass.keep_after.unwrap().0
+            });
+
+        match chained_group {
+            Some(group) => {
+                groups[group].passes.push(pass_pos);
+                group_of_pass.push(group);
+            }
+            None => {
+                if pass.keep_after.is_some() {
+                    log::warn!(
+                        "Pass {:?} can't be kept after a pass which isn't the last of its chain, or doesn't precede it",
+                        pass.name
+                    );
+                }
+
+                group_of_pass.push(groups.len());
+                groups.push(PassGroup {
+                    passes: vec![pass_pos],
+                    ordering: if writes_swapchain(pass) {
+                        PassOrdering::AsLateAsPossible
+                    } else {
+                        pass.ordering
+                    },
+                    dependencies: Vec::new(),
+                });
+            }
+        }
+    }
+
+    for (group_idx, group) in groups.iter_mut().enumerate() {
+        let mut dependencies: Vec<usize> = group
+            .passes
+            .iter()
+            .flat_map(|&pass_pos| pass_dependencies[pass_pos].iter())
+            .map(|&dep_pos| group_of_pass[dep_pos])
+            .filter(|&dep_group| dep_group != group_idx)
+            .collect();
+
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        group.dependencies = dependencies;
+    }
+
+    let order = match schedule_groups(&groups) {
+        Some(order) => order,
+        None => {
+            log::warn!("Pass ordering constraints form a cycle; keeping the recorded order");
+            return;
+        }
+    };
+
+    let mut slots: Vec<Option<RecordedPass>> = passes.drain(..).map(Some).collect();
+    passes.extend(
+        order
+            .into_iter()
+            .flat_map(|group| groups[group].passes.iter())
+            .map(|&pass_pos| slots[pass_pos].take().unwrap()),
+    );
+}
+
+// For each pass, the earlier passes it must follow.
+fn data_dependencies(passes: &[RecordedPass]) -> Vec<Vec<usize>> {
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct ResourceAccesses {
+        last_write: Option<usize>,
+        reads_since_write: Vec<usize>,
+    }
+
+    let mut resources: HashMap<u32, ResourceAccesses> = HashMap::new();
+    let mut last_fence: Option<usize> = None;
+    let mut since_fence: Vec<usize> = Vec::new();
+
+    passes
+        .iter()
+        .enumerate()
+        .map(|(pass_pos, pass)| {
+            let mut dependencies = Vec::new();
+
+            // Passes declaring no resources may depend on, or affect, anything.
+            if pass.read.is_empty() && pass.write.is_empty() {
+                dependencies.extend(last_fence);
+                dependencies.append(&mut since_fence);
+                last_fence = Some(pass_pos);
+                return dependencies;
+            }
+
+            dependencies.extend(last_fence);
+            since_fence.push(pass_pos);
+
+            for read in &pass.read {
+                let accesses = resources.entry(read.handle.id).or_default();
+                dependencies.extend(accesses.last_write);
+                accesses.reads_since_write.push(pass_pos);
+            }
+
+            for write in &pass.write {
+                let accesses = resources.entry(write.handle.id).or_default();
+                dependencies.extend(accesses.last_write);
+                dependencies.extend(
+                    accesses
+                        .reads_since_write
+                        .drain(..)
+                        .filter(|&reader| reader != pass_pos),
+                );
+                accesses.last_write = Some(pass_pos);
+            }
+
+            dependencies
+        })
+        .collect()
+}
+
+// The groups in scheduled order, or `None` if they depend on each other in a cycle.
+fn schedule_groups(groups: &[PassGroup]) -> Option<Vec<usize>> {
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+    let mut unscheduled_dependencies: Vec<usize> = vec![0; groups.len()];
+
+    for (group_idx, group) in groups.iter().enumerate() {
+        unscheduled_dependencies[group_idx] = group.dependencies.len();
+        for &dep in &group.dependencies {
+            dependents[dep].push(group_idx);
+        }
+    }
+
+    // (group, position in the schedule at which it became ready)
+    let mut ready: Vec<(usize, usize)> = (0..groups.len())
+        .filter(|&group| unscheduled_dependencies[group] == 0)
+        .map(|group| (group, 0))
+        .collect();
+
+    let mut order = Vec::with_capacity(groups.len());
+
+    while !ready.is_empty() {
+        let next = (0..ready.len())
+            .min_by_key(|&i| {
+                let (group, ready_at) = ready[i];
+                (groups[group].ordering, ready_at, group)
+            })
+            .unwrap();
+        let (group, _) = ready.swap_remove(next);
+
+        order.push(group);
+
+        for &dependent in &dependents[group] {
+            unscheduled_dependencies[dependent] -= 1;
+            if unscheduled_dependencies[dependent] == 0 {
+                ready.push((dependent, order.len()));
+            }
+        }
+    }
+
+    (order.len() == groups.len()).then(|| order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graph::{PassResourceAccessSyncType, PassResourceAccessType, PassResourceRef},
+        resource::GraphRawResourceHandle,
+    };
+    use kajiya_backend::vk_sync::AccessType;
+
+    fn pass(idx: usize, reads: &[u32], writes: &[u32]) -> RecordedPass {
+        let resource_ref = |&id: &u32| PassResourceRef {
+            handle: GraphRawResourceHandle { id, version: 0 },
+            access: PassResourceAccessType::new(
+                AccessType::General,
+                PassResourceAccessSyncType::AlwaysSync,
+            ),
+        };
+
+        RecordedPass {
+            read: reads.iter().map(resource_ref).collect(),
+            write: writes.iter().map(resource_ref).collect(),
+            render_fn: None,
+            name: format!("pass {}", idx),
+            idx,
+            view_idx: 0,
+            ordering: PassOrdering::Default,
+            keep_after: None,
+        }
+    }
+
+    fn reordered(mut passes: Vec<RecordedPass>) -> Vec<usize> {
+        reorder_passes(&mut passes, |_| false);
+        passes.iter().map(|pass| pass.idx).collect()
+    }
+
+    #[test]
+    fn readers_follow_writers() {
+        let mut writer = pass(0, &[], &[0]);
+        writer.ordering = PassOrdering::AsLateAsPossible;
+        let mut reader = pass(1, &[0], &[]);
+        reader.ordering = PassOrdering::AsEarlyAsPossible;
+        let passes = vec![writer, reader, pass(2, &[], &[1])];
+
+        assert_eq!(data_dependencies(&passes), vec![vec![], vec![0], vec![]]);
+        assert_eq!(reordered(passes), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn writes_follow_earlier_reads() {
+        let passes = vec![pass(0, &[], &[0]), pass(1, &[0], &[]), pass(2, &[], &[0])];
+        assert_eq!(
+            data_dependencies(&passes),
+            vec![vec![], vec![0], vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn keep_after_chains_onto_its_leader() {
+        let mut follower = pass(2, &[], &[2]);
+        follower.ordering = PassOrdering::AsEarlyAsPossible;
+        follower.keep_after = Some(PassId(0));
+        let passes = vec![pass(0, &[], &[0]), pass(1, &[], &[1]), follower];
+
+        assert_eq!(reordered(passes), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn cycle_keeps_recorded_order() {
+        // The follower reads what the middle pass writes, which reads what the leader writes.
+        let mut follower = pass(2, &[1], &[]);
+        follower.ordering = PassOrdering::AsEarlyAsPossible;
+        follower.keep_after = Some(PassId(0));
+        let passes = vec![pass(0, &[], &[0]), pass(1, &[0], &[1]), follower];
+
+        assert_eq!(reordered(passes), vec![0, 1, 2]);
+
+        let groups = [
+            PassGroup {
+                passes: vec![0],
+                ordering: PassOrdering::Default,
+                dependencies: vec![1],
+            },
+            PassGroup {
+                passes: vec![1],
+                ordering: PassOrdering::Default,
+                dependencies: vec![0],
+            },
+        ];
+        assert_eq!(schedule_groups(&groups), None);
+    }
+}
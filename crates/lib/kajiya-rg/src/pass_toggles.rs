@@ -0,0 +1,78 @@
+//! Runtime switches for individual passes, by name, for A/B comparisons.
+//!
+//! A disabled pass keeps its place in the graph, along with its barriers, but records
+//! no commands, so whatever it writes keeps its previous contents. Passes sharing
+//! a name, e.g. across views, are toggled together.
+
+use std::collections::HashSet;
+
+use parking_lot::Mutex;
+
+use crate::graph::RecordedPass;
+
+#[derive(Default)]
+struct PassToggles {
+    /// In order of first appearance in the latest compiled graph.
+    known_passes: Vec<String>,
+    disabled_passes: HashSet<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref PASS_TOGGLES: Mutex<PassToggles> = Default::default();
+}
+
+/// Takes effect the next time a graph is compiled. Names don't need to be known yet.
+pub fn set_pass_enabled(name: &str, enabled: bool) {
+    let mut toggles = PASS_TOGGLES.lock();
+    if enabled {
+        toggles.disabled_passes.remove(name);
+    } else {
+        toggles.disabled_passes.insert(name.to_owned());
+    }
+}
+
+pub fn is_pass_enabled(name: &str) -> bool {
+    !PASS_TOGGLES.lock().disabled_passes.contains(name)
+}
+
+pub fn enable_all_passes() {
+    PASS_TOGGLES.lock().disabled_passes.clear();
+}
+
+/// The names of the passes in the latest compiled graph, in order, and whether they're enabled.
+pub fn pass_toggles() -> Vec<(String, bool)> {
+    let toggles = PASS_TOGGLES.lock();
+    toggles
+        .known_passes
+        .iter()
+        .map(|name| (name.clone(), !toggles.disabled_passes.contains(name)))
+        .collect()
+}
+
+/// Registers the names of `passes`, and drops the render functions of the disabled ones.
+pub(crate) fn apply(passes: &mut [RecordedPass]) {
+    let mut toggles = PASS_TOGGLES.lock();
+    let toggles = &mut *toggles;
+
+    let mut seen: HashSet<&str> = HashSet::with_capacity(passes.len());
+    let names: Vec<&str> = passes
+        .iter()
+        .map(|pass| pass.name.as_str())
+        .filter(|name| seen.insert(*name))
+        .collect();
+
+    // Usually the same as in the previous frame.
+    if !names.iter().eq(toggles.known_passes.iter()) {
+        toggles.known_passes = names.into_iter().map(str::to_owned).collect();
+    }
+
+    if toggles.disabled_passes.is_empty() {
+        return;
+    }
+
+    for pass in passes {
+        if toggles.disabled_passes.contains(&pass.name) {
+            pass.render_fn = None;
+        }
+    }
+}
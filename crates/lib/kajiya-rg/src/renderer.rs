@@ -1,4 +1,7 @@
 use crate::{
+    gpu_asserts::{self, GpuAsserts},
+    gpu_resource_stats::{self, GpuResourceStats},
+    submit_stats::{is_submit_split, FrameSubmitter, SubmitBatch},
     CompiledRenderGraph, ExecutingRenderGraph, ExportedTemporalRenderGraphState,
     PredefinedDescriptorSet, RenderGraphExecutionParams, TemporalRenderGraph,
     TemporalRenderGraphState, TemporalResourceState,
@@ -10,8 +13,8 @@ use kajiya_backend::{
     rspirv_reflect,
     transient_resource_cache::TransientResourceCache,
     vk_sync,
-    vulkan::{self, swapchain::Swapchain, RenderBackend},
-    Device,
+    vulkan::{self, device::CommandBuffer, swapchain::Swapchain, RenderBackend},
+    Device, Image,
 };
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -30,6 +33,15 @@ impl Default for TemporalRg {
     }
 }
 
+/// What the graph's swapchain image stands for in a frame.
+enum FrameTarget<'a> {
+    Swapchain(&'a mut Swapchain),
+    Image {
+        image: Arc<Image>,
+        access_type: vk_sync::AccessType,
+    },
+}
+
 pub struct Renderer {
     device: Arc<Device>,
 
@@ -37,9 +49,14 @@ pub struct Renderer {
     transient_resource_cache: TransientResourceCache,
     dynamic_constants: DynamicConstants,
     frame_descriptor_set: vk::DescriptorSet,
+    frame_descriptor_pool: vk::DescriptorPool,
+    gpu_asserts: GpuAsserts,
 
     compiled_rg: Option<CompiledRenderGraph>,
     temporal_rg_state: TemporalRg,
+
+    external_wait_semaphores: Vec<vk::Semaphore>,
+    external_signal_semaphores: Vec<vk::Semaphore>,
 }
 
 lazy_static::lazy_static! {
@@ -71,6 +88,33 @@ lazy_static::lazy_static! {
             name: Default::default(),
         },
     ),
+    // punctual_lights_dyn
+    (
+        3,
+        rspirv_reflect::DescriptorInfo {
+            ty: rspirv_reflect::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            dimensionality: rspirv_reflect::DescriptorDimensionality::Single,
+            name: Default::default(),
+        },
+    ),
+    // rect_lights_dyn
+    (
+        4,
+        rspirv_reflect::DescriptorInfo {
+            ty: rspirv_reflect::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            dimensionality: rspirv_reflect::DescriptorDimensionality::Single,
+            name: Default::default(),
+        },
+    ),
+    // gpu_asserts_dyn
+    (
+        5,
+        rspirv_reflect::DescriptorInfo {
+            ty: rspirv_reflect::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            dimensionality: rspirv_reflect::DescriptorDimensionality::Single,
+            name: Default::default(),
+        },
+    ),
     ]
     .iter()
     .cloned()
@@ -81,12 +125,34 @@ pub struct FrameConstantsLayout {
     pub globals_offset: u32,
     pub instance_dynamic_parameters_offset: u32,
     pub triangle_lights_offset: u32,
+    pub punctual_lights_offset: u32,
+    pub rect_lights_offset: u32,
+
+    /// Globals of the views other than the main one, starting at view 1.
+    /// The rest of the frame constants are shared by all views.
+    pub additional_view_globals_offsets: Vec<u32>,
+}
+
+impl FrameConstantsLayout {
+    pub fn view_globals_offset(&self, view_idx: usize) -> u32 {
+        if view_idx == 0 {
+            self.globals_offset
+        } else {
+            self.additional_view_globals_offsets[view_idx - 1]
+        }
+    }
 }
 
 impl Renderer {
     pub fn new(backend: &RenderBackend) -> anyhow::Result<Self> {
+        Self::with_device(&backend.device)
+    }
+
+    /// For devices without a swapchain, such as those from `create_headless_device`, whose
+    /// frames can only be run with `execute_without_presentation`.
+    pub fn with_device(device: &Arc<Device>) -> anyhow::Result<Self> {
         let dynamic_constants = DynamicConstants::new({
-            backend.device.create_buffer(
+            device.create_buffer(
                 BufferDesc::new_cpu_to_gpu(
                     DYNAMIC_CONSTANTS_SIZE_BYTES * DYNAMIC_CONSTANTS_BUFFER_COUNT,
                     vk::BufferUsageFlags::UNIFORM_BUFFER
@@ -98,27 +164,80 @@ impl Renderer {
             )?
         });
 
-        let frame_descriptor_set =
-            Self::create_frame_descriptor_set(backend, &dynamic_constants.buffer);
+        let gpu_asserts = GpuAsserts::new(device)?;
+
+        let (frame_descriptor_set, frame_descriptor_pool) = Self::create_frame_descriptor_set(
+            device,
+            &dynamic_constants.buffer,
+            &gpu_asserts.buffer,
+        );
 
         Ok(Renderer {
-            device: backend.device.clone(),
+            device: device.clone(),
             dynamic_constants,
             frame_descriptor_set,
+            frame_descriptor_pool,
+            gpu_asserts,
             pipeline_cache: PipelineCache::new(&LazyCache::create()),
             transient_resource_cache: Default::default(),
 
             compiled_rg: None,
             temporal_rg_state: Default::default(),
+
+            external_wait_semaphores: Default::default(),
+            external_signal_semaphores: Default::default(),
         })
     }
 
+    /// Makes the next frame wait for `semaphore` before rendering, e.g. until another API
+    /// is done reading a shared image. Applies to one frame only.
+    pub fn wait_semaphore_before_next_frame(&mut self, semaphore: vk::Semaphore) {
+        self.external_wait_semaphores.push(semaphore);
+    }
+
+    /// Signals `semaphore` once the GPU is done with the next frame, e.g. so that another API
+    /// can read a shared image. Applies to one frame only.
+    pub fn signal_semaphore_after_next_frame(&mut self, semaphore: vk::Semaphore) {
+        self.external_signal_semaphores.push(semaphore);
+    }
+
     pub fn draw_frame<PrepareFrameConstantsFn>(
         &mut self,
         prepare_frame_constants: PrepareFrameConstantsFn,
         swapchain: &mut Swapchain,
     ) where
         PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
+    {
+        self.draw_frame_impl(prepare_frame_constants, FrameTarget::Swapchain(swapchain));
+    }
+
+    /// Like `draw_frame`, but renders what the graph writes to the swapchain image into `image`
+    /// instead, without presenting; for applications embedding the renderer, which own the image.
+    ///
+    /// `image` must have been created with `STORAGE` usage. The application accesses it as
+    /// `access_type` outside of the frame: the frame waits for those accesses, and leaves it
+    /// ready for them. When the application reads it on another queue or API, it should sync
+    /// with `signal_semaphore_after_next_frame`.
+    pub fn draw_frame_to_image<PrepareFrameConstantsFn>(
+        &mut self,
+        prepare_frame_constants: PrepareFrameConstantsFn,
+        image: Arc<Image>,
+        access_type: vk_sync::AccessType,
+    ) where
+        PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
+    {
+        self.draw_frame_impl(
+            prepare_frame_constants,
+            FrameTarget::Image { image, access_type },
+        );
+    }
+
+    fn draw_frame_impl<PrepareFrameConstantsFn>(
+        &mut self,
+        prepare_frame_constants: PrepareFrameConstantsFn,
+        mut target: FrameTarget,
+    ) where
+        PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
     {
         let rg = if let Some(rg) = self.compiled_rg.take() {
             rg
@@ -131,11 +250,17 @@ impl Renderer {
 
         let current_frame = self.device.begin_frame();
 
-        // Both command buffers are accessible now, so begin recording.
+        // The GPU is done with the frame which last used this slot.
+        let gpu_asserts_offset = self.gpu_asserts.begin_frame();
+
+        // All the command buffers are accessible now, so begin recording.
         for cb in [
             &current_frame.main_command_buffer,
             &current_frame.presentation_command_buffer,
-        ] {
+        ]
+        .into_iter()
+        .chain(&current_frame.parallel_command_buffers)
+        {
             unsafe {
                 raw_device
                     .reset_command_buffer(cb.raw, vk::CommandBufferResetFlags::default())
@@ -156,6 +281,11 @@ impl Renderer {
 
         let mut executing_rg: ExecutingRenderGraph;
 
+        let mut submitter = FrameSubmitter::default();
+
+        // Unless submits are split, the main batch goes along with the presentation one.
+        let mut pending_main_batch: Option<SubmitBatch> = None;
+
         // Record and submit the main command buffer
         {
             let main_cb = &current_frame.main_command_buffer;
@@ -168,9 +298,10 @@ impl Renderer {
                 rg.begin_execute(
                     RenderGraphExecutionParams {
                         device: &self.device,
-                        pipeline_cache: &mut self.pipeline_cache,
+                        pipeline_cache: &self.pipeline_cache,
                         frame_descriptor_set: self.frame_descriptor_set,
                         frame_constants_layout,
+                        gpu_asserts_offset,
                         profiler_data: &current_frame.profiler_data,
                     },
                     &mut self.transient_resource_cache,
@@ -182,42 +313,73 @@ impl Renderer {
             unsafe {
                 puffin::profile_scope!("main cb");
 
-                {
+                let parallel_cbs = {
                     puffin::profile_scope!("rg::record_main_cb");
-                    executing_rg.record_main_cb(main_cb)
-                }
-
-                raw_device.end_command_buffer(main_cb.raw).unwrap();
-
-                let submit_info = [vk::SubmitInfo::builder()
-                    .command_buffers(std::slice::from_ref(&main_cb.raw))
-                    .build()];
+                    let parallel_cb_count = executing_rg
+                        .record_main_cb(main_cb, &current_frame.parallel_command_buffers);
+                    &current_frame.parallel_command_buffers[..parallel_cb_count]
+                };
 
-                raw_device
-                    .reset_fences(std::slice::from_ref(&main_cb.submit_done_fence))
-                    .expect("reset_fences");
+                for cb in std::iter::once(main_cb).chain(parallel_cbs) {
+                    raw_device.end_command_buffer(cb.raw).unwrap();
+                }
 
-                puffin::profile_scope!("submit main cb");
+                let command_buffers: Vec<vk::CommandBuffer> = std::iter::once(main_cb)
+                    .chain(parallel_cbs)
+                    .map(|cb| cb.raw)
+                    .collect();
+
+                let wait_semaphores = std::mem::take(&mut self.external_wait_semaphores);
+                let wait_dst_stage_mask =
+                    vec![vk::PipelineStageFlags::ALL_COMMANDS; wait_semaphores.len()];
+
+                let main_batch = SubmitBatch {
+                    name: "main",
+                    command_buffers,
+                    wait_semaphores,
+                    wait_dst_stage_mask,
+                    signal_semaphores: Vec::new(),
+                };
 
-                // Try to submit the command buffer to the GPU. We might encounter a GPU crash.
-                raw_device
-                    .queue_submit(
-                        self.device.universal_queue.raw,
-                        &submit_info,
-                        main_cb.submit_done_fence,
-                    )
-                    .map_err(|err| device.report_error(err.into()))
-                    .expect("main queue_submit failed");
+                if is_submit_split() {
+                    raw_device
+                        .reset_fences(std::slice::from_ref(&main_cb.submit_done_fence))
+                        .expect("reset_fences");
+
+                    puffin::profile_scope!("submit main cb");
+
+                    // Try to submit the command buffer to the GPU. We might encounter a GPU crash.
+                    submitter
+                        .submit(
+                            device,
+                            std::slice::from_ref(&main_batch),
+                            main_cb.submit_done_fence,
+                        )
+                        .map_err(|err| device.report_error(err.into()))
+                        .expect("main queue_submit failed");
+                } else {
+                    // The fence of the main command buffer is left signaled; the one
+                    // of the presentation command buffer covers both batches.
+                    pending_main_batch = Some(main_batch);
+                }
             };
         }
 
-        // Now that we've done the main submission and the GPU is busy, acquire the presentation image.
+        // If we've done the main submission, the GPU is busy now, so acquire the presentation image.
         // This can block, so we're doing it as late as possible.
 
-        let swapchain_image = swapchain
-            .acquire_next_image()
-            .ok()
-            .expect("swapchain image");
+        let (swapchain_image, target_image, target_access_type) = match &mut target {
+            FrameTarget::Swapchain(swapchain) => {
+                let swapchain_image = swapchain
+                    .acquire_next_image()
+                    .ok()
+                    .expect("swapchain image");
+                let image = swapchain_image.image.clone();
+
+                (Some(swapchain_image), image, vk_sync::AccessType::Present)
+            }
+            FrameTarget::Image { image, access_type } => (None, image.clone(), *access_type),
+        };
 
         // Execute the rest of the render graph, and submit the presentation command buffer.
         let retired_rg = {
@@ -225,13 +387,13 @@ impl Renderer {
 
             let presentation_cb = &current_frame.presentation_command_buffer;
 
-            // Transition the swapchain to CS write
+            // Transition the swapchain or target image to CS write
             vulkan::barrier::record_image_barrier(
                 device,
                 presentation_cb.raw,
                 vulkan::barrier::ImageBarrier::new(
-                    swapchain_image.image.raw,
-                    vk_sync::AccessType::Present,
+                    target_image.raw,
+                    target_access_type,
                     vk_sync::AccessType::ComputeShaderWrite,
                     vk::ImageAspectFlags::COLOR,
                 )
@@ -239,16 +401,16 @@ impl Renderer {
             );
 
             let retired_rg =
-                executing_rg.record_presentation_cb(presentation_cb, swapchain_image.image.clone());
+                executing_rg.record_presentation_cb(presentation_cb, target_image.clone());
 
-            // Transition the swapchain to present
+            // Transition the swapchain to present, or the target image back to the application
             vulkan::barrier::record_image_barrier(
                 device,
                 presentation_cb.raw,
                 vulkan::barrier::ImageBarrier::new(
-                    swapchain_image.image.raw,
+                    target_image.raw,
                     vk_sync::AccessType::ComputeShaderWrite,
-                    vk_sync::AccessType::Present,
+                    target_access_type,
                     vk::ImageAspectFlags::COLOR,
                 ),
             );
@@ -261,30 +423,50 @@ impl Renderer {
             unsafe {
                 raw_device.end_command_buffer(presentation_cb.raw).unwrap();
 
-                let submit_info = [vk::SubmitInfo::builder()
-                    .wait_semaphores(std::slice::from_ref(&swapchain_image.acquire_semaphore))
-                    .signal_semaphores(std::slice::from_ref(
-                        &swapchain_image.rendering_finished_semaphore,
-                    ))
-                    .wait_dst_stage_mask(&[vk::PipelineStageFlags::COMPUTE_SHADER])
-                    .command_buffers(std::slice::from_ref(&presentation_cb.raw))
-                    .build()];
+                let signal_semaphores: Vec<vk::Semaphore> = swapchain_image
+                    .iter()
+                    .map(|image| image.rendering_finished_semaphore)
+                    .chain(self.external_signal_semaphores.drain(..))
+                    .collect();
+
+                let wait_semaphores: Vec<vk::Semaphore> = swapchain_image
+                    .iter()
+                    .map(|image| image.acquire_semaphore)
+                    .collect();
+
+                let batches: Vec<SubmitBatch> = pending_main_batch
+                    .take()
+                    .into_iter()
+                    .chain(std::iter::once(SubmitBatch {
+                        name: "presentation",
+                        command_buffers: vec![presentation_cb.raw],
+                        wait_dst_stage_mask: vec![
+                            vk::PipelineStageFlags::COMPUTE_SHADER;
+                            wait_semaphores.len()
+                        ],
+                        wait_semaphores,
+                        signal_semaphores,
+                    }))
+                    .collect();
+
                 raw_device
                     .reset_fences(std::slice::from_ref(&presentation_cb.submit_done_fence))
                     .expect("reset_fences");
 
                 puffin::profile_scope!("submit presentation cb");
-                raw_device
-                    .queue_submit(
-                        self.device.universal_queue.raw,
-                        &submit_info,
-                        presentation_cb.submit_done_fence,
-                    )
+                submitter
+                    .submit(device, &batches, presentation_cb.submit_done_fence)
                     .map_err(|err| device.report_error(err.into()))
                     .expect("presentation queue_submit failed");
             }
 
-            swapchain.present_image(swapchain_image);
+            submitter.finish();
+
+            if let (FrameTarget::Swapchain(swapchain), Some(swapchain_image)) =
+                (target, swapchain_image)
+            {
+                swapchain.present_image(swapchain_image);
+            }
 
             retired_rg
         };
@@ -300,19 +482,206 @@ impl Renderer {
 
         self.dynamic_constants.advance_frame();
         self.device.finish_frame(current_frame);
+
+        gpu_resource_stats::report_frame(GpuResourceStats {
+            device: self.device.stats(),
+            pipelines: self.pipeline_cache.stats(),
+            frame_count: self.device.current_frame_value(),
+        });
+    }
+
+    /// Records and submits the graph from `prepare_frame` like `draw_frame` does, but without
+    /// presenting, then waits for the GPU to finish it. The graph must not use the swapchain.
+    ///
+    /// The frame constants are left empty, with all their offsets at zero.
+    pub fn execute_without_presentation(&mut self) {
+        let rg = if let Some(rg) = self.compiled_rg.take() {
+            rg
+        } else {
+            return;
+        };
+
+        let device = &*self.device;
+        let raw_device = &device.raw;
+
+        let current_frame = self.device.begin_frame();
+        let gpu_asserts_offset = self.gpu_asserts.begin_frame();
+
+        // The presentation command buffer takes what `record_main_cb` leaves over.
+        for cb in [
+            &current_frame.main_command_buffer,
+            &current_frame.presentation_command_buffer,
+        ]
+        .into_iter()
+        .chain(&current_frame.parallel_command_buffers)
+        {
+            unsafe {
+                raw_device
+                    .reset_command_buffer(cb.raw, vk::CommandBufferResetFlags::default())
+                    .unwrap();
+
+                raw_device
+                    .begin_command_buffer(
+                        cb.raw,
+                        &vk::CommandBufferBeginInfo::builder()
+                            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                    )
+                    .unwrap();
+            }
+        }
+
+        let main_cb = &current_frame.main_command_buffer;
+        let last_cb = &current_frame.presentation_command_buffer;
+
+        current_frame.profiler_data.begin_frame(device, main_cb.raw);
+
+        let mut executing_rg = rg.begin_execute(
+            RenderGraphExecutionParams {
+                device: &self.device,
+                pipeline_cache: &self.pipeline_cache,
+                frame_descriptor_set: self.frame_descriptor_set,
+                frame_constants_layout: FrameConstantsLayout {
+                    globals_offset: 0,
+                    instance_dynamic_parameters_offset: 0,
+                    triangle_lights_offset: 0,
+                    punctual_lights_offset: 0,
+                    rect_lights_offset: 0,
+                    additional_view_globals_offsets: Vec::new(),
+                },
+                gpu_asserts_offset,
+                profiler_data: &current_frame.profiler_data,
+            },
+            &mut self.transient_resource_cache,
+            &mut self.dynamic_constants,
+        );
+
+        let parallel_cb_count =
+            executing_rg.record_main_cb(main_cb, &current_frame.parallel_command_buffers);
+        let parallel_cbs = &current_frame.parallel_command_buffers[..parallel_cb_count];
+
+        let retired_rg = executing_rg.record_without_presentation(last_cb);
+
+        current_frame
+            .profiler_data
+            .finish_frame(device, last_cb.raw);
+
+        let command_buffers: Vec<&CommandBuffer> = std::iter::once(main_cb)
+            .chain(parallel_cbs)
+            .chain(std::iter::once(last_cb))
+            .collect();
+
+        unsafe {
+            for cb in &command_buffers {
+                raw_device.end_command_buffer(cb.raw).unwrap();
+            }
+
+            let wait_semaphores = std::mem::take(&mut self.external_wait_semaphores);
+            let wait_dst_stage_mask =
+                vec![vk::PipelineStageFlags::ALL_COMMANDS; wait_semaphores.len()];
+
+            let batch = SubmitBatch {
+                name: "main",
+                command_buffers: command_buffers.iter().map(|cb| cb.raw).collect(),
+                wait_semaphores,
+                wait_dst_stage_mask,
+                signal_semaphores: self.external_signal_semaphores.drain(..).collect(),
+            };
+
+            raw_device
+                .reset_fences(std::slice::from_ref(&last_cb.submit_done_fence))
+                .expect("reset_fences");
+
+            let mut submitter = FrameSubmitter::default();
+            submitter
+                .submit(
+                    device,
+                    std::slice::from_ref(&batch),
+                    last_cb.submit_done_fence,
+                )
+                .map_err(|err| device.report_error(err.into()))
+                .expect("queue_submit failed");
+            submitter.finish();
+
+            raw_device
+                .wait_for_fences(
+                    std::slice::from_ref(&last_cb.submit_done_fence),
+                    true,
+                    std::u64::MAX,
+                )
+                .map_err(|err| device.report_error(err.into()))
+                .expect("Wait for fence failed.");
+        }
+
+        self.temporal_rg_state = match std::mem::take(&mut self.temporal_rg_state) {
+            TemporalRg::Inert(_) => {
+                panic!("Trying to retire the render graph, but it's inert. Was prepare_frame not caled?");
+            }
+            TemporalRg::Exported(rg) => TemporalRg::Inert(rg.retire_temporal(&retired_rg)),
+        };
+
+        retired_rg.release_resources(&mut self.transient_resource_cache);
+
+        self.dynamic_constants.advance_frame();
+        self.device.finish_frame(current_frame);
+    }
+
+    /// Waits for the frames in flight, saves the pipeline cache, and destroys the renderer's
+    /// GPU resources. Call when closing the app, before dropping the swapchain and anything else
+    /// the frames may still be using; only the resources still referenced elsewhere survive.
+    pub fn shutdown(self) {
+        self.device.drain_frames();
+
+        if let Err(err) = self.device.save_pipeline_cache() {
+            warn!("Could not save the pipeline cache: {:#}", err);
+        }
+
+        let Renderer {
+            device,
+            mut transient_resource_cache,
+            dynamic_constants,
+            frame_descriptor_pool,
+            gpu_asserts,
+            compiled_rg,
+            temporal_rg_state,
+            ..
+        } = self;
+
+        // The graph references the temporal and transient resources.
+        drop(compiled_rg);
+
+        let temporal_rg_state = match temporal_rg_state {
+            TemporalRg::Inert(state) => state,
+            // Prepared, but not drawn
+            TemporalRg::Exported(state) => state.0,
+        };
+        temporal_rg_state.immediate_destroy_resources(&device);
+
+        transient_resource_cache.immediate_destroy_all(&device);
+
+        // The frame descriptor set points at the dynamic constants and the GPU asserts.
+        device.immediate_destroy_descriptor_pool(frame_descriptor_pool);
+        device.immediate_destroy_buffer(gpu_asserts.buffer);
+
+        if let Ok(buffer) = Arc::try_unwrap(dynamic_constants.buffer) {
+            device.immediate_destroy_buffer(buffer);
+        }
     }
 
     // Descriptor set for per-frame data
     fn create_frame_descriptor_set(
-        backend: &RenderBackend,
+        backend_device: &Device,
         dynamic_constants: &Buffer,
-    ) -> vk::DescriptorSet {
-        let device = &backend.device.raw;
+        gpu_asserts: &Buffer,
+    ) -> (vk::DescriptorSet, vk::DescriptorPool) {
+        let device = &backend_device.raw;
 
         let set_binding_flags = [
             vk::DescriptorBindingFlags::PARTIALLY_BOUND,
             vk::DescriptorBindingFlags::PARTIALLY_BOUND,
             vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND,
         ];
 
         let mut binding_flags_create_info =
@@ -346,6 +715,27 @@ impl Renderer {
                                 .stage_flags(vk::ShaderStageFlags::ALL)
                                 .binding(2)
                                 .build(),
+                            // punctual_lights_dyn
+                            vk::DescriptorSetLayoutBinding::builder()
+                                .descriptor_count(1)
+                                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                                .stage_flags(vk::ShaderStageFlags::ALL)
+                                .binding(3)
+                                .build(),
+                            // rect_lights_dyn
+                            vk::DescriptorSetLayoutBinding::builder()
+                                .descriptor_count(1)
+                                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                                .stage_flags(vk::ShaderStageFlags::ALL)
+                                .binding(4)
+                                .build(),
+                            // gpu_asserts_dyn
+                            vk::DescriptorSetLayoutBinding::builder()
+                                .descriptor_count(1)
+                                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                                .stage_flags(vk::ShaderStageFlags::ALL)
+                                .binding(5)
+                                .build(),
                         ])
                         .push_next(&mut binding_flags_create_info)
                         .build(),
@@ -361,7 +751,7 @@ impl Renderer {
             },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
-                descriptor_count: 2,
+                descriptor_count: 5,
             },
         ];
 
@@ -369,11 +759,9 @@ impl Renderer {
             .pool_sizes(&descriptor_sizes)
             .max_sets(1);
 
-        let descriptor_pool = unsafe {
-            device
-                .create_descriptor_pool(&descriptor_pool_info, None)
-                .unwrap()
-        };
+        let descriptor_pool = backend_device
+            .create_descriptor_pool(&descriptor_pool_info, "frame descriptor pool")
+            .unwrap();
 
         let set = unsafe {
             device
@@ -395,6 +783,10 @@ impl Renderer {
                 .buffer(dynamic_constants.raw)
                 .range(MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES as u64)
                 .build();
+            let gpu_asserts_info = vk::DescriptorBufferInfo::builder()
+                .buffer(gpu_asserts.raw)
+                .range(gpu_asserts::SLOT_SIZE as u64)
+                .build();
 
             let descriptor_set_writes = [
                 // `frame_constants`
@@ -418,12 +810,33 @@ impl Renderer {
                     .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
                     .buffer_info(std::slice::from_ref(&storage_buffer_info))
                     .build(),
+                // `punctual_lights_dyn`
+                vk::WriteDescriptorSet::builder()
+                    .dst_binding(3)
+                    .dst_set(set)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                    .buffer_info(std::slice::from_ref(&storage_buffer_info))
+                    .build(),
+                // `rect_lights_dyn`
+                vk::WriteDescriptorSet::builder()
+                    .dst_binding(4)
+                    .dst_set(set)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                    .buffer_info(std::slice::from_ref(&storage_buffer_info))
+                    .build(),
+                // `gpu_asserts_dyn`
+                vk::WriteDescriptorSet::builder()
+                    .dst_binding(5)
+                    .dst_set(set)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                    .buffer_info(std::slice::from_ref(&gpu_asserts_info))
+                    .build(),
             ];
 
             unsafe { device.update_descriptor_sets(&descriptor_set_writes, &[]) };
         }
 
-        set
+        (set, descriptor_pool)
     }
 
     pub fn prepare_frame<PrepareRenderGraphFn>(
@@ -5,14 +5,23 @@ pub use kajiya_backend::vulkan::{
 };
 use std::marker::PhantomData;
 
-use super::resource_registry::{AnyRenderResource, AnyRenderResourceRef};
+use super::{
+    resource_registry::{AnyRenderResource, AnyRenderResourceRef},
+    sealed::Sealed,
+};
 
-pub trait Resource {
+/// Implemented by the kinds of resources the graph knows how to track; sealed.
+pub trait Resource: Sealed {
     type Desc: ResourceDesc;
 
+    #[doc(hidden)]
     fn borrow_resource(res: &AnyRenderResource) -> &Self;
 }
 
+impl Sealed for Image {}
+impl Sealed for Buffer {}
+impl Sealed for RayTracingAcceleration {}
+
 impl Resource for Image {
     type Desc = ImageDesc;
 
@@ -74,10 +83,15 @@ impl From<RayTracingAccelerationDesc> for GraphResourceDesc {
     }
 }
 
-pub trait ResourceDesc: Clone + std::fmt::Debug + Into<GraphResourceDesc> {
+/// Sealed, like `Resource`.
+pub trait ResourceDesc: Sealed + Clone + std::fmt::Debug + Into<GraphResourceDesc> {
     type Resource: Resource;
 }
 
+impl Sealed for ImageDesc {}
+impl Sealed for BufferDesc {}
+impl Sealed for RayTracingAccelerationDesc {}
+
 impl ResourceDesc for ImageDesc {
     type Resource = Image;
 }
@@ -190,9 +204,14 @@ pub struct GpuSrv;
 pub struct GpuUav;
 pub struct GpuRt;
 
-pub trait GpuViewType {
+/// How a pass accesses a resource through a `Ref`; sealed.
+pub trait GpuViewType: Sealed {
     const IS_WRITABLE: bool;
 }
+impl Sealed for GpuSrv {}
+impl Sealed for GpuUav {}
+impl Sealed for GpuRt {}
+
 impl GpuViewType for GpuSrv {
     const IS_WRITABLE: bool = false;
 }
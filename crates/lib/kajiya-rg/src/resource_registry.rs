@@ -58,13 +58,36 @@ pub enum AnyRenderResourceRef<'a> {
 pub(crate) struct RegistryResource {
     pub resource: AnyRenderResource,
     pub access_type: vk_sync::AccessType,
+
+    /// Set while a buffer is only being read since its last write.
+    pub buffer_reads: Option<BufferReads>,
+}
+
+impl RegistryResource {
+    pub fn new(resource: AnyRenderResource, access_type: vk_sync::AccessType) -> Self {
+        Self {
+            resource,
+            access_type,
+            buffer_reads: None,
+        }
+    }
+}
+
+/// Buffers have no layouts, so reads of different kinds only need to wait on the last write,
+/// and not on each other. The next write waits on all of them instead.
+pub(crate) struct BufferReads {
+    /// The access before the reads; a read itself for buffers imported for reading.
+    pub write_access: vk_sync::AccessType,
+
+    /// Distinct read accesses since `write_access`, including the current `access_type`.
+    pub read_accesses: Vec<vk_sync::AccessType>,
 }
 
 pub struct ResourceRegistry<'exec_params, 'constants> {
-    pub execution_params: RenderGraphExecutionParams<'exec_params>,
-    pub(crate) resources: Vec<RegistryResource>,
+    pub execution_params: &'exec_params RenderGraphExecutionParams<'exec_params>,
+    pub(crate) resources: &'exec_params [RegistryResource],
     pub dynamic_constants: &'constants mut DynamicConstants,
-    pub pipelines: RenderGraphPipelines,
+    pub pipelines: &'exec_params RenderGraphPipelines,
 }
 
 impl<'exec_params, 'constants> ResourceRegistry<'exec_params, 'constants> {
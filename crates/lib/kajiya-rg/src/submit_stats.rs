@@ -0,0 +1,145 @@
+//! How `Renderer::draw_frame` hands a frame to the queue, and what that costs on the CPU.
+//!
+//! A frame consists of two batches, in this order:
+//!
+//! * "main": the main command buffer, followed by the parallel ones, if any. Waits at
+//!   `ALL_COMMANDS` for the semaphores from `Renderer::wait_semaphore_before_next_frame`.
+//! * "presentation": the presentation command buffer. Waits at `COMPUTE_SHADER` for the
+//!   swapchain image to be acquired, and signals the swapchain's rendering-finished
+//!   semaphore, followed by those from `Renderer::signal_semaphore_after_next_frame`.
+//!
+//! By default, both go in a single `vkQueueSubmit`, which is signaled through the fence of
+//! the presentation command buffer. With split submits, the main batch is submitted
+//! as soon as it's recorded instead, so that the GPU can start on it while the CPU
+//! acquires the swapchain image, which may block, and records the presentation batch.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use kajiya_backend::{
+    ash::{prelude::VkResult, vk},
+    Device,
+};
+use parking_lot::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct SubmitBatchInfo {
+    pub name: &'static str,
+    pub command_buffer_count: usize,
+    pub wait_semaphores: Vec<(vk::Semaphore, vk::PipelineStageFlags)>,
+    pub signal_semaphores: Vec<vk::Semaphore>,
+    /// Index of the `vkQueueSubmit` call within the frame which submitted this batch.
+    pub submit_idx: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FrameSubmitStats {
+    /// `vkQueueSubmit` calls made for the latest frame.
+    pub submit_count: u32,
+    /// Batches of the latest frame, in submission order.
+    pub batches: Vec<SubmitBatchInfo>,
+    /// Spent in `vkQueueSubmit` for the latest frame, including waiting for the queue lock.
+    pub cpu_time: Duration,
+    /// `vkQueueSubmit` calls made for all frames so far.
+    pub total_submit_count: u64,
+}
+
+static SPLIT_SUBMIT: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<FrameSubmitStats> = Default::default();
+}
+
+/// Submits the main batch of each frame on its own, ahead of the presentation one.
+/// Costs one extra `vkQueueSubmit` per frame.
+pub fn set_split_submit(split: bool) {
+    SPLIT_SUBMIT.store(split, Ordering::Relaxed);
+}
+
+pub fn is_submit_split() -> bool {
+    SPLIT_SUBMIT.load(Ordering::Relaxed)
+}
+
+/// Stats of the latest frame.
+pub fn frame_submit_stats() -> FrameSubmitStats {
+    STATS.lock().clone()
+}
+
+pub(crate) struct SubmitBatch {
+    pub name: &'static str,
+    pub command_buffers: Vec<vk::CommandBuffer>,
+    pub wait_semaphores: Vec<vk::Semaphore>,
+    pub wait_dst_stage_mask: Vec<vk::PipelineStageFlags>,
+    pub signal_semaphores: Vec<vk::Semaphore>,
+}
+
+/// Submits the batches of a frame, and keeps track of the calls.
+#[derive(Default)]
+pub(crate) struct FrameSubmitter {
+    submit_count: u32,
+    batches: Vec<SubmitBatchInfo>,
+    cpu_time: Duration,
+}
+
+impl FrameSubmitter {
+    /// Submits `batches` in one `vkQueueSubmit`, signaling `fence` once they're all done.
+    pub fn submit(
+        &mut self,
+        device: &Device,
+        batches: &[SubmitBatch],
+        fence: vk::Fence,
+    ) -> VkResult<()> {
+        let submit_infos: Vec<vk::SubmitInfo> = batches
+            .iter()
+            .map(|batch| {
+                vk::SubmitInfo::builder()
+                    .wait_semaphores(&batch.wait_semaphores)
+                    .wait_dst_stage_mask(&batch.wait_dst_stage_mask)
+                    .command_buffers(&batch.command_buffers)
+                    .signal_semaphores(&batch.signal_semaphores)
+                    .build()
+            })
+            .collect();
+
+        let start = Instant::now();
+        let result = {
+            let _queue_lock = device.universal_queue.submit_lock.lock();
+            unsafe {
+                device
+                    .raw
+                    .queue_submit(device.universal_queue.raw, &submit_infos, fence)
+            }
+        };
+        self.cpu_time += start.elapsed();
+
+        let submit_idx = self.submit_count;
+        self.submit_count += 1;
+
+        self.batches.extend(batches.iter().map(|batch| {
+            SubmitBatchInfo {
+                name: batch.name,
+                command_buffer_count: batch.command_buffers.len(),
+                wait_semaphores: batch
+                    .wait_semaphores
+                    .iter()
+                    .copied()
+                    .zip(batch.wait_dst_stage_mask.iter().copied())
+                    .collect(),
+                signal_semaphores: batch.signal_semaphores.clone(),
+                submit_idx,
+            }
+        }));
+
+        result
+    }
+
+    pub fn finish(self) {
+        let mut stats = STATS.lock();
+        stats.total_submit_count += self.submit_count as u64;
+        stats.submit_count = self.submit_count;
+        stats.batches = self.batches;
+        stats.cpu_time = self.cpu_time;
+    }
+}
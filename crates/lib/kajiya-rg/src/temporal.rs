@@ -22,6 +22,13 @@ impl<ResType: Resource> std::ops::Deref for ReadOnlyHandle<ResType> {
     }
 }
 
+// Nothing can be written through it, so sharing it can't reorder writes.
+impl<ResType: Resource> Clone for ReadOnlyHandle<ResType> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_unchecked())
+    }
+}
+
 impl<ResType: Resource> From<Handle<ResType>> for ReadOnlyHandle<ResType> {
     fn from(h: Handle<ResType>) -> Self {
         Self(h)
@@ -75,6 +82,30 @@ pub struct TemporalRenderGraphState {
 }
 
 impl TemporalRenderGraphState {
+    /// Destroys the resources which nothing else holds on to. The GPU must be done with them.
+    pub(crate) fn immediate_destroy_resources(self, device: &Device) {
+        for state in self.resources.into_values() {
+            let resource = match state {
+                TemporalResourceState::Inert { resource, .. }
+                | TemporalResourceState::Imported { resource, .. }
+                | TemporalResourceState::Exported { resource, .. } => resource,
+            };
+
+            match resource {
+                TemporalResource::Image(image) => {
+                    if let Ok(image) = Arc::try_unwrap(image) {
+                        device.immediate_destroy_image(image);
+                    }
+                }
+                TemporalResource::Buffer(buffer) => {
+                    if let Ok(buffer) = Arc::try_unwrap(buffer) {
+                        device.immediate_destroy_buffer(buffer);
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn clone_assuming_inert(&self) -> Self {
         Self {
             resources: self
@@ -107,6 +138,7 @@ pub struct TemporalRenderGraph {
     rg: RenderGraph,
     device: Arc<Device>,
     temporal_state: TemporalRenderGraphState,
+    temporal_scope: Option<String>,
 }
 
 impl std::ops::Deref for TemporalRenderGraph {
@@ -129,12 +161,27 @@ impl TemporalRenderGraph {
             rg: RenderGraph::new(),
             device,
             temporal_state: state,
+            temporal_scope: None,
         }
     }
 
     pub fn device(&self) -> &Device {
         self.device.as_ref()
     }
+
+    /// Keeps the temporal resources requested from now on apart from those with the same keys
+    /// in other scopes, so that e.g. secondary views don't share history with the main one.
+    /// `None` is the default scope.
+    pub fn set_temporal_scope(&mut self, scope: Option<String>) {
+        self.temporal_scope = scope;
+    }
+
+    fn scoped_key(&self, key: TemporalResourceKey) -> TemporalResourceKey {
+        match &self.temporal_scope {
+            Some(scope) => TemporalResourceKey(format!("{}/{}", scope, key.0)),
+            None => key,
+        }
+    }
 }
 
 pub trait GetOrCreateTemporal<Desc: ResourceDesc> {
@@ -155,12 +202,35 @@ impl GetOrCreateTemporal<ImageDesc> for TemporalRenderGraph {
         desc: ImageDesc,
         //) -> anyhow::Result<Handle<Image>> {
     ) -> anyhow::Result<Handle<Image>> {
-        let key = key.into();
+        let key = self.scoped_key(key.into());
 
         match self.temporal_state.resources.entry(key.clone()) {
             hash_map::Entry::Occupied(mut entry) => {
                 let state = entry.get_mut();
 
+                // The requested size can change at runtime, e.g. with dynamic resolution scaling.
+                // The history is lost then, same as on the first frame.
+                if let TemporalResourceState::Inert {
+                    resource: TemporalResource::Image(image),
+                    access_type,
+                } = state
+                {
+                    if image.desc != desc {
+                        let new_image = Arc::new(
+                            self.device
+                                .create_image(desc, vec![])
+                                .with_context(|| format!("Creating image {:?}", desc))?,
+                        );
+
+                        let prev_image = std::mem::replace(image, new_image);
+                        *access_type = AccessType::Nothing;
+
+                        if let Ok(prev_image) = Arc::try_unwrap(prev_image) {
+                            self.device.defer_release(prev_image);
+                        }
+                    }
+                }
+
                 match state {
                     TemporalResourceState::Inert {
                         resource,
@@ -223,7 +293,7 @@ impl GetOrCreateTemporal<BufferDesc> for TemporalRenderGraph {
         desc: BufferDesc,
         //) -> anyhow::Result<Handle<Image>> {
     ) -> anyhow::Result<Handle<Buffer>> {
-        let key = key.into();
+        let key = self.scoped_key(key.into());
 
         match self.temporal_state.resources.entry(key.clone()) {
             hash_map::Entry::Occupied(mut entry) => {
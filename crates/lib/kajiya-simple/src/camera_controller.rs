@@ -0,0 +1,381 @@
+//! Camera controllers turning keyboard and mouse input into camera motion.
+//!
+//! Raw input is first mapped to a `CameraInput` by a rebindable `CameraInputMap`,
+//! and then consumed by any `CameraController`. Wrap a controller in `SmoothedCamera`
+//! to filter its motion.
+
+use glam::{EulerRot, Quat, Vec2, Vec3};
+
+use crate::input::{InputAxis, KeyMap, KeyboardMap, KeyboardState, MouseState, VirtualKeyCode};
+
+pub const MOVE_FORWARD_AXIS: InputAxis = "move_fwd";
+pub const MOVE_RIGHT_AXIS: InputAxis = "move_right";
+pub const MOVE_UP_AXIS: InputAxis = "move_up";
+pub const BOOST_AXIS: InputAxis = "boost";
+
+/// Input for one frame, in the camera's terms.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CameraInput {
+    /// Velocity along the camera's right, up, and forward directions, in units per second.
+    pub velocity: Vec3,
+
+    /// Change of the yaw and pitch, in degrees.
+    pub look_delta_degrees: Vec2,
+
+    /// Mouse wheel lines scrolled; positive away from the user.
+    pub zoom: f32,
+}
+
+/// Maps keyboard and mouse state to `CameraInput`.
+pub struct CameraInputMap {
+    /// Drives the `MOVE_FORWARD_AXIS`, `MOVE_RIGHT_AXIS`, `MOVE_UP_AXIS`, and `BOOST_AXIS`.
+    pub keys: KeyboardMap,
+
+    /// Bit of the button in `MouseState::buttons_held` which needs to be held to look around.
+    pub look_button: u32,
+
+    /// Degrees per pixel of mouse motion
+    pub look_sensitivity: f32,
+
+    /// Units per second, before boosting.
+    pub move_speed: f32,
+
+    /// Speed multiplier for each unit of the boost axis.
+    pub boost_factor: f32,
+}
+
+impl Default for CameraInputMap {
+    fn default() -> Self {
+        Self::wasd()
+    }
+}
+
+impl CameraInputMap {
+    /// WASD to move, Q/E to go down/up, shift/ctrl to speed up/slow down,
+    /// and the right mouse button to look around.
+    pub fn wasd() -> Self {
+        let keys = KeyboardMap::new()
+            .bind(VirtualKeyCode::W, KeyMap::new(MOVE_FORWARD_AXIS, 1.0))
+            .bind(VirtualKeyCode::S, KeyMap::new(MOVE_FORWARD_AXIS, -1.0))
+            .bind(VirtualKeyCode::A, KeyMap::new(MOVE_RIGHT_AXIS, -1.0))
+            .bind(VirtualKeyCode::D, KeyMap::new(MOVE_RIGHT_AXIS, 1.0))
+            .bind(VirtualKeyCode::Q, KeyMap::new(MOVE_UP_AXIS, -1.0))
+            .bind(VirtualKeyCode::E, KeyMap::new(MOVE_UP_AXIS, 1.0))
+            .bind(
+                VirtualKeyCode::LShift,
+                KeyMap::new(BOOST_AXIS, 1.0).activation_time(0.25),
+            )
+            .bind(
+                VirtualKeyCode::LControl,
+                KeyMap::new(BOOST_AXIS, -1.0).activation_time(0.5),
+            );
+
+        Self {
+            keys,
+            look_button: 2,
+            look_sensitivity: 0.1,
+            move_speed: 2.5,
+            boost_factor: 4.0,
+        }
+    }
+
+    pub fn is_looking(&self, mouse: &MouseState) -> bool {
+        (mouse.buttons_held & (1 << self.look_button)) != 0
+    }
+
+    pub fn map(&mut self, keyboard: &KeyboardState, mouse: &MouseState, dt: f32) -> CameraInput {
+        let axes = self.keys.map(keyboard, dt);
+        let axis = |name: InputAxis| axes.get(name).copied().unwrap_or_default();
+
+        let direction = Vec3::new(
+            axis(MOVE_RIGHT_AXIS),
+            axis(MOVE_UP_AXIS),
+            axis(MOVE_FORWARD_AXIS),
+        )
+        .clamp_length_max(1.0);
+
+        let look_delta_degrees = if self.is_looking(mouse) {
+            -self.look_sensitivity * mouse.delta
+        } else {
+            Vec2::ZERO
+        };
+
+        CameraInput {
+            velocity: direction * self.move_speed * self.boost_factor.powf(axis(BOOST_AXIS)),
+            look_delta_degrees,
+            zoom: mouse.wheel_delta,
+        }
+    }
+}
+
+/// Rotation of a camera looking in the given direction, without roll.
+pub fn look_towards(direction: Vec3) -> Quat {
+    let forward = direction.normalize_or_zero();
+    let yaw = (-forward.x).atan2(-forward.z);
+    let pitch = forward.y.clamp(-1.0, 1.0).asin();
+    Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0)
+}
+
+pub trait CameraController {
+    fn update(&mut self, input: &CameraInput, dt: f32);
+
+    fn position(&self) -> Vec3;
+    fn rotation(&self) -> Quat;
+
+    /// Moves the camera to the given pose, e.g. from a preset or a recorded path.
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat);
+}
+
+impl<T: CameraController + ?Sized> CameraController for Box<T> {
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        (**self).update(input, dt)
+    }
+
+    fn position(&self) -> Vec3 {
+        (**self).position()
+    }
+
+    fn rotation(&self) -> Quat {
+        (**self).rotation()
+    }
+
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat) {
+        (**self).set_position_rotation(position, rotation)
+    }
+}
+
+/// Yaw and pitch in degrees, without roll; the view looks down -Z at zero rotation.
+#[derive(Clone, Copy, Default, Debug)]
+struct YawPitch {
+    yaw: f32,
+    pitch: f32,
+}
+
+impl YawPitch {
+    fn from_rotation(rotation: Quat) -> Self {
+        let (yaw, pitch, _) = rotation.to_euler(EulerRot::YXZ);
+        Self {
+            yaw: yaw.to_degrees(),
+            pitch: pitch.to_degrees(),
+        }
+    }
+
+    fn rotate(&mut self, delta_degrees: Vec2) {
+        self.yaw = (self.yaw + delta_degrees.x) % 720.0;
+        self.pitch = (self.pitch + delta_degrees.y).clamp(-90.0, 90.0);
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_euler(
+            EulerRot::YXZ,
+            self.yaw.to_radians(),
+            self.pitch.to_radians(),
+            0.0,
+        )
+    }
+}
+
+/// Moves freely in the direction the camera is facing.
+#[derive(Clone, Copy, Debug)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    yaw_pitch: YawPitch,
+}
+
+impl FlyCamera {
+    pub fn new(position: Vec3, rotation: Quat) -> Self {
+        Self {
+            position,
+            yaw_pitch: YawPitch::from_rotation(rotation),
+        }
+    }
+}
+
+impl CameraController for FlyCamera {
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.yaw_pitch.rotate(input.look_delta_degrees);
+
+        let velocity = input.velocity * Vec3::new(1.0, 1.0, -1.0);
+        self.position += self.rotation() * velocity * dt;
+    }
+
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn rotation(&self) -> Quat {
+        self.yaw_pitch.rotation()
+    }
+
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat) {
+        self.position = position;
+        self.yaw_pitch = YawPitch::from_rotation(rotation);
+    }
+}
+
+/// Walks on the horizontal plane regardless of the pitch, and moves up and down along world Y.
+#[derive(Clone, Copy, Debug)]
+pub struct FirstPersonCamera {
+    pub position: Vec3,
+    yaw_pitch: YawPitch,
+}
+
+impl FirstPersonCamera {
+    pub fn new(position: Vec3, rotation: Quat) -> Self {
+        Self {
+            position,
+            yaw_pitch: YawPitch::from_rotation(rotation),
+        }
+    }
+}
+
+impl CameraController for FirstPersonCamera {
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.yaw_pitch.rotate(input.look_delta_degrees);
+
+        let yaw = Quat::from_rotation_y(self.yaw_pitch.yaw.to_radians());
+        let velocity =
+            yaw * Vec3::new(input.velocity.x, 0.0, -input.velocity.z) + Vec3::Y * input.velocity.y;
+        self.position += velocity * dt;
+    }
+
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn rotation(&self) -> Quat {
+        self.yaw_pitch.rotation()
+    }
+
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat) {
+        self.position = position;
+        self.yaw_pitch = YawPitch::from_rotation(rotation);
+    }
+}
+
+/// Rotates around a target point, zooms with the mouse wheel, and pans the target
+/// with the movement keys.
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    yaw_pitch: YawPitch,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32, rotation: Quat) -> Self {
+        Self {
+            target,
+            distance,
+            yaw_pitch: YawPitch::from_rotation(rotation),
+        }
+    }
+
+    /// Orbits around the point `distance` in front of a camera at the given pose.
+    pub fn from_position_rotation(position: Vec3, rotation: Quat, distance: f32) -> Self {
+        let mut res = Self::new(Vec3::ZERO, distance, rotation);
+        res.set_position_rotation(position, rotation);
+        res
+    }
+}
+
+impl CameraController for OrbitCamera {
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.yaw_pitch.rotate(input.look_delta_degrees);
+
+        // Each line scrolled moves 10% closer.
+        self.distance = (self.distance * 0.9f32.powf(input.zoom)).max(1e-3);
+
+        let velocity = input.velocity * Vec3::new(1.0, 1.0, -1.0);
+        self.target += self.rotation() * velocity * dt;
+    }
+
+    fn position(&self) -> Vec3 {
+        self.target - self.rotation() * -Vec3::Z * self.distance
+    }
+
+    fn rotation(&self) -> Quat {
+        self.yaw_pitch.rotation()
+    }
+
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat) {
+        self.yaw_pitch = YawPitch::from_rotation(rotation);
+        self.target = position + self.rotation() * -Vec3::Z * self.distance;
+    }
+}
+
+/// Exponentially smooths the motion of another controller.
+pub struct SmoothedCamera<C> {
+    pub controller: C,
+
+    /// Roughly the time in seconds to catch up with the controller; zero disables smoothing.
+    pub position_smoothness: f32,
+    pub rotation_smoothness: f32,
+
+    position: Vec3,
+    rotation: Quat,
+}
+
+impl<C: CameraController> SmoothedCamera<C> {
+    pub fn new(controller: C, smoothness: f32) -> Self {
+        let position = controller.position();
+        let rotation = controller.rotation();
+
+        Self {
+            controller,
+            position_smoothness: smoothness,
+            rotation_smoothness: smoothness,
+            position,
+            rotation,
+        }
+    }
+
+    /// Jumps to the pose of the controller, skipping the smoothing.
+    pub fn snap(&mut self) {
+        self.position = self.controller.position();
+        self.rotation = self.controller.rotation();
+    }
+}
+
+impl<C: CameraController> CameraController for SmoothedCamera<C> {
+    fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.controller.update(input, dt);
+
+        // Fraction of the way towards the target covered this frame, independent of the frame rate.
+        let interp_t = |smoothness: f32| {
+            if smoothness > 0.0 {
+                1.0 - (-8.0 * dt / smoothness).exp()
+            } else {
+                1.0
+            }
+        };
+
+        let target_rotation = self.controller.rotation();
+        let target_rotation = if target_rotation.dot(self.rotation) < 0.0 {
+            -target_rotation
+        } else {
+            target_rotation
+        };
+
+        self.position = self.position.lerp(
+            self.controller.position(),
+            interp_t(self.position_smoothness),
+        );
+        self.rotation = self
+            .rotation
+            .slerp(target_rotation, interp_t(self.rotation_smoothness))
+            .normalize();
+    }
+
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    fn set_position_rotation(&mut self, position: Vec3, rotation: Quat) {
+        self.controller.set_position_rotation(position, rotation);
+    }
+}
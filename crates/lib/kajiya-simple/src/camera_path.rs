@@ -0,0 +1,286 @@
+//! Keyframed camera paths, for repeatable flythroughs.
+//!
+//! Positions, rotations, and fields of view are interpolated with Catmull-Rom splines
+//! over the key times. With a fixed time step, playback yields the same camera for
+//! the same frame index regardless of the frame rate, as needed for benchmarks and video capture.
+
+use anyhow::Context as _;
+use glam::{Quat, Vec3, Vec4};
+
+use crate::scene::SceneCameraPathDesc;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraPathKey {
+    /// In seconds from the start of the path
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub vertical_fov: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraPathSample {
+    pub position: Vec3,
+    pub rotation: Quat,
+
+    /// `None` if none of the keys specify one.
+    pub vertical_fov: Option<f32>,
+}
+
+#[derive(Clone)]
+pub struct CameraPath {
+    keys: Vec<CameraPathKey>,
+}
+
+impl CameraPath {
+    /// `keys` must be non-empty, and in the order of strictly increasing time.
+    pub fn new(mut keys: Vec<CameraPathKey>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!keys.is_empty(), "A camera path needs at least one key");
+
+        for pair in keys.windows(2) {
+            anyhow::ensure!(
+                pair[1].time > pair[0].time,
+                "Camera path key times must be strictly increasing; got {} after {}",
+                pair[1].time,
+                pair[0].time
+            );
+        }
+
+        // Keys without a field of view hold the previous one, or the first one specified.
+        let first_fov = keys.iter().find_map(|key| key.vertical_fov);
+        let mut prev_fov = first_fov;
+        for key in &mut keys {
+            prev_fov = key.vertical_fov.or(prev_fov);
+            key.vertical_fov = prev_fov;
+        }
+
+        // Take the shortest arc between consecutive keys.
+        for i in 1..keys.len() {
+            if keys[i].rotation.dot(keys[i - 1].rotation) < 0.0 {
+                keys[i].rotation = -keys[i].rotation;
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn from_desc(desc: &SceneCameraPathDesc) -> anyhow::Result<Self> {
+        Self::new(
+            desc.keys
+                .iter()
+                .map(|key| CameraPathKey {
+                    time: key.time,
+                    position: key.position(),
+                    rotation: key.rotation(),
+                    vertical_fov: key.vertical_fov,
+                })
+                .collect(),
+        )
+        .with_context(|| format!("Camera path {:?}", desc.name))
+    }
+
+    pub fn keys(&self) -> &[CameraPathKey] {
+        &self.keys
+    }
+
+    /// Time of the last key; the first one doesn't need to be at zero.
+    pub fn end_time(&self) -> f32 {
+        self.keys.last().unwrap().time
+    }
+
+    /// Samples the path at `time`, clamped to the time range of the keys.
+    pub fn sample(&self, time: f32) -> CameraPathSample {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+
+        // Index of the key which starts the segment `time` is in.
+        let i = keys
+            .partition_point(|key| key.time <= time)
+            .saturating_sub(1)
+            .min(last.saturating_sub(1));
+
+        let k1 = &keys[i];
+        let k2 = &keys[(i + 1).min(last)];
+
+        if k2.time <= k1.time || time <= k1.time {
+            return k1.into();
+        }
+
+        if time >= k2.time {
+            return k2.into();
+        }
+
+        let k0 = &keys[i.saturating_sub(1)];
+        let k3 = &keys[(i + 2).min(last)];
+
+        let t = (time - k1.time) / (k2.time - k1.time);
+        let interp = |f: &dyn Fn(&CameraPathKey) -> Vec4| {
+            catmull_rom([k0, k1, k2, k3].map(|k| (k.time, f(k))), t)
+        };
+
+        let position = interp(&|k| k.position.extend(0.0)).truncate();
+        let rotation = Quat::from_vec4(interp(&|k| Vec4::from(k.rotation))).normalize();
+        let vertical_fov = k1
+            .vertical_fov
+            .map(|_| interp(&|k| Vec4::splat(k.vertical_fov.unwrap_or_default())).x);
+
+        CameraPathSample {
+            position,
+            rotation,
+            vertical_fov,
+        }
+    }
+}
+
+impl From<&CameraPathKey> for CameraPathSample {
+    fn from(key: &CameraPathKey) -> Self {
+        Self {
+            position: key.position,
+            rotation: key.rotation,
+            vertical_fov: key.vertical_fov,
+        }
+    }
+}
+
+/// Cubic Hermite interpolation between the middle two of four points, with the tangents
+/// from finite differences over the neighboring points, so that uneven key spacing is handled.
+/// `t` is normalized to the middle segment.
+fn catmull_rom(points: [(f32, Vec4); 4], t: f32) -> Vec4 {
+    let [(t0, p0), (t1, p1), (t2, p2), (t3, p3)] = points;
+    let segment = t2 - t1;
+
+    let tangent = |ta: f32, pa: Vec4, tb: f32, pb: Vec4| {
+        if tb > ta {
+            (pb - pa) / (tb - ta) * segment
+        } else {
+            Vec4::ZERO
+        }
+    };
+
+    let m1 = tangent(t0, p0, t2, p2);
+    let m2 = tangent(t1, p1, t3, p3);
+
+    let t2_ = t * t;
+    let t3_ = t2_ * t;
+
+    p1 * (2.0 * t3_ - 3.0 * t2_ + 1.0)
+        + m1 * (t3_ - 2.0 * t2_ + t)
+        + p2 * (-2.0 * t3_ + 3.0 * t2_)
+        + m2 * (t3_ - t2_)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraPathTimeStep {
+    /// Advances by the frame time passed to `CameraPathPlayback::advance`.
+    RealTime,
+
+    /// Advances by a fixed amount each frame, ignoring the frame time.
+    Fixed { seconds: f32 },
+}
+
+impl CameraPathTimeStep {
+    pub fn fixed_fps(frames_per_second: f32) -> Self {
+        Self::Fixed {
+            seconds: 1.0 / frames_per_second.max(1e-3),
+        }
+    }
+}
+
+pub struct CameraPathPlayback {
+    path: CameraPath,
+    time_step: CameraPathTimeStep,
+    time: f32,
+    frame: u32,
+}
+
+impl CameraPathPlayback {
+    pub fn new(path: CameraPath, time_step: CameraPathTimeStep) -> Self {
+        let time = path.keys[0].time;
+
+        Self {
+            path,
+            time_step,
+            time,
+            frame: 0,
+        }
+    }
+
+    pub fn path(&self) -> &CameraPath {
+        &self.path
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Frames played back since the start, or since the last seek.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Number of frames it takes to play back the path with a fixed time step, including
+    /// both the first and the last key. `None` in real time.
+    pub fn frame_count(&self) -> Option<u32> {
+        match self.time_step {
+            CameraPathTimeStep::RealTime => None,
+            CameraPathTimeStep::Fixed { seconds } => {
+                let duration = self.path.end_time() - self.path.keys[0].time;
+                Some((duration / seconds).ceil() as u32 + 1)
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match self.frame_count() {
+            Some(frame_count) => self.frame >= frame_count,
+            None => self.time > self.path.end_time(),
+        }
+    }
+
+    /// Jumps to `time`, relative to the start of the path.
+    /// With a fixed time step, snaps to the nearest frame.
+    pub fn seek(&mut self, time: f32) {
+        match self.time_step {
+            CameraPathTimeStep::RealTime => {
+                self.frame = 0;
+                self.time = self.path.keys[0].time + time.max(0.0);
+            }
+            CameraPathTimeStep::Fixed { seconds } => {
+                self.seek_frame((time.max(0.0) / seconds).round() as u32);
+            }
+        }
+    }
+
+    /// Jumps to the given frame of fixed time step playback.
+    pub fn seek_frame(&mut self, frame: u32) {
+        let seconds = match self.time_step {
+            CameraPathTimeStep::RealTime => return,
+            CameraPathTimeStep::Fixed { seconds } => seconds,
+        };
+
+        self.frame = frame;
+
+        // Multiplied rather than accumulated, so that the same frame always gets the same time.
+        self.time = self.path.keys[0].time + frame as f32 * seconds;
+    }
+
+    /// Samples the path at the current time, and then steps forward.
+    /// Returns `None` once the end of the path has been played back.
+    pub fn advance(&mut self, dt: f32) -> Option<CameraPathSample> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let sample = self.path.sample(self.time);
+
+        match self.time_step {
+            CameraPathTimeStep::RealTime => {
+                self.frame += 1;
+                self.time += dt;
+            }
+            CameraPathTimeStep::Fixed { .. } => self.seek_frame(self.frame + 1),
+        }
+
+        Some(sample)
+    }
+}
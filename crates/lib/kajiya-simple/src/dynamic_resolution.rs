@@ -0,0 +1,127 @@
+use kajiya::backend::gpu_profiler::GpuProfilerStats;
+
+/// Bounds and target for `DynamicResolution`.
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicResolutionConfig {
+    /// The GPU frame time to hold, in milliseconds.
+    pub target_frame_time_ms: f32,
+
+    /// Smallest allowed fraction of the maximum internal resolution, per axis.
+    pub min_scale: f32,
+
+    /// Largest allowed fraction of the maximum internal resolution, per axis.
+    pub max_scale: f32,
+}
+
+impl Default for DynamicResolutionConfig {
+    fn default() -> Self {
+        Self {
+            target_frame_time_ms: 1000.0 / 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+/// Adjusts the internal rendering resolution based on measured GPU frame times.
+///
+/// Every pass which depends on the render extent is resized along with it, including
+/// temporal resources, so changes are kept infrequent to avoid constantly losing history.
+pub struct DynamicResolution {
+    pub config: DynamicResolutionConfig,
+    scale: f32,
+    gpu_time_filtered_ms: Option<f32>,
+    frames_since_change: u32,
+}
+
+impl DynamicResolution {
+    /// Don't re-evaluate the scale more often than this.
+    const MIN_FRAMES_BETWEEN_CHANGES: u32 = 30;
+
+    /// Don't bother changing the scale if the estimated new one differs by less than this.
+    const MIN_SCALE_CHANGE: f32 = 0.05;
+
+    /// Only scale up if the estimated frame time at the new resolution is under
+    /// this fraction of the budget, to avoid oscillating around the target.
+    const HEADROOM: f32 = 0.9;
+
+    pub fn new(config: DynamicResolutionConfig) -> Self {
+        Self {
+            config,
+            scale: config.max_scale,
+            gpu_time_filtered_ms: None,
+            frames_since_change: 0,
+        }
+    }
+
+    /// Current fraction of the maximum internal resolution, per axis.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn gpu_time_filtered_ms(&self) -> Option<f32> {
+        self.gpu_time_filtered_ms
+    }
+
+    /// Feed the latest GPU timings, and update the scale.
+    pub fn update(&mut self, gpu_stats: &GpuProfilerStats) {
+        let gpu_time_ms = gpu_stats
+            .get_ordered()
+            .iter()
+            .map(|(_, ms)| *ms as f32)
+            .sum::<f32>();
+
+        // Nothing was measured, e.g. a frame failed to render.
+        if gpu_time_ms <= 0.0 {
+            return;
+        }
+
+        let gpu_time_filtered_ms = self
+            .gpu_time_filtered_ms
+            .map_or(gpu_time_ms, |prev| prev + (gpu_time_ms - prev) * 0.1);
+        self.gpu_time_filtered_ms = Some(gpu_time_filtered_ms);
+
+        self.frames_since_change += 1;
+        if self.frames_since_change < Self::MIN_FRAMES_BETWEEN_CHANGES {
+            return;
+        }
+
+        // Assume the cost is proportional to the pixel count, i.e. `scale` squared.
+        let time_ratio = self.config.target_frame_time_ms / gpu_time_filtered_ms;
+        let new_scale = if time_ratio < 1.0 {
+            self.scale * time_ratio.sqrt()
+        } else {
+            self.scale * (time_ratio * Self::HEADROOM).sqrt().max(1.0)
+        };
+
+        let new_scale = new_scale.clamp(
+            self.config.min_scale,
+            self.config.max_scale.max(self.config.min_scale),
+        );
+
+        // Small changes are ignored, except when they reach one of the bounds.
+        let reached_bound =
+            new_scale == self.config.min_scale || new_scale == self.config.max_scale;
+        if (new_scale - self.scale).abs() >= Self::MIN_SCALE_CHANGE
+            || (reached_bound && new_scale != self.scale)
+        {
+            // The measured time no longer corresponds to the new resolution.
+            self.gpu_time_filtered_ms =
+                Some(gpu_time_filtered_ms * (new_scale / self.scale).powi(2));
+            self.scale = new_scale;
+            self.frames_since_change = 0;
+        }
+    }
+
+    /// The internal rendering resolution for `max_render_extent` at the current scale.
+    ///
+    /// Rounded to a multiple of 8 pixels, which keeps the half- and quarter-res passes aligned.
+    pub fn render_extent(&self, max_render_extent: [u32; 2]) -> [u32; 2] {
+        let scale_dim =
+            |dim: u32| ((dim as f32 * self.scale) as u32 / 8 * 8).clamp(8.min(dim), dim);
+        [
+            scale_dim(max_render_extent[0]),
+            scale_dim(max_render_extent[1]),
+        ]
+    }
+}
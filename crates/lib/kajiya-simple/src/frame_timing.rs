@@ -0,0 +1,213 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Delta times are filtered over _this many_ frames.
+const DT_FILTER_WIDTH: usize = 10;
+
+/// Measures frame times, smooths them for animation, and optionally caps the frame rate.
+pub struct FrameTiming {
+    /// Sleeps at the end of each frame to stay under this rate. `None` for no limit.
+    pub max_fps: Option<f32>,
+
+    /// Replaces the measured delta time, so that every frame advances by exactly this much,
+    /// as in benchmarks and video captures. `None` to follow the wall clock.
+    pub fixed_dt: Option<f32>,
+
+    frame_start: Instant,
+    dt_raw: f32,
+    dt_filtered: f32,
+
+    // Past delta times used for filtering
+    dt_queue: VecDeque<f32>,
+
+    // Fake the first frame's delta time. In the first frame, shaders
+    // and pipelines are be compiled, so it will most likely have a spike.
+    fake_dt_countdown: i32,
+}
+
+impl Default for FrameTiming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameTiming {
+    pub fn new() -> Self {
+        Self {
+            max_fps: None,
+            fixed_dt: None,
+            frame_start: Instant::now(),
+            dt_raw: 0.0,
+            dt_filtered: 0.0,
+            dt_queue: VecDeque::with_capacity(DT_FILTER_WIDTH),
+            fake_dt_countdown: 1,
+        }
+    }
+
+    /// Call at the start of each frame. Measures the time since the previous one,
+    /// and returns `dt_filtered`.
+    pub fn begin_frame(&mut self) -> f32 {
+        let now = Instant::now();
+        self.dt_raw = (now - self.frame_start).as_secs_f32();
+        self.frame_start = now;
+
+        // Filter the frame time before passing it to the application and renderer.
+        // Fluctuations in frame rendering times cause stutter in animations,
+        // and time-dependent effects (such as motion blur).
+        //
+        // Should applications need unfiltered delta time, they can use `dt_raw`,
+        // but it's good to pass the filtered time so users don't need to worry about it.
+        let dt_measured = {
+            // >= because rendering (and thus the spike) happens _after_ this.
+            if self.fake_dt_countdown >= 0 {
+                // First frame. Return the fake value.
+                self.fake_dt_countdown -= 1;
+                self.dt_raw.min(1.0 / 60.0)
+            } else {
+                // Not the first frame. Start averaging.
+
+                if self.dt_queue.len() >= DT_FILTER_WIDTH {
+                    self.dt_queue.pop_front();
+                }
+
+                self.dt_queue.push_back(self.dt_raw);
+                self.dt_queue.iter().copied().sum::<f32>() / self.dt_queue.len() as f32
+            }
+        };
+
+        self.dt_filtered = self.fixed_dt.unwrap_or(dt_measured);
+        self.dt_filtered
+    }
+
+    /// Seconds between the starts of the last two frames, as measured.
+    pub fn dt_raw(&self) -> f32 {
+        self.dt_raw
+    }
+
+    /// The smoothed delta time, or `fixed_dt` if set.
+    pub fn dt_filtered(&self) -> f32 {
+        self.dt_filtered
+    }
+
+    /// Call at the end of each frame. Sleeps for whatever is left of the frame time
+    /// allowed by `max_fps`.
+    pub fn pace(&self) {
+        let max_fps = match self.max_fps {
+            Some(max_fps) if max_fps > 0.0 => max_fps,
+            _ => return,
+        };
+
+        let target = self.frame_start + Duration::from_secs_f32(1.0 / max_fps);
+        let now = Instant::now();
+        if target > now {
+            std::thread::sleep(target - now);
+        }
+    }
+}
+
+/// Steps a simulation at a fixed rate, independent of the frame rate.
+///
+/// Each frame, feed the frame's delta time to `advance`, and step the simulation by
+/// `step_seconds` as many times as it returns. For smooth motion, draw the state
+/// interpolated between the last two steps by `interpolation_alpha`.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    step_seconds: f32,
+    accumulator: f32,
+    /// Frame hitches beyond this many steps are dropped rather than caught up with,
+    /// so that slow steps can't snowball.
+    pub max_steps_per_frame: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(step_seconds: f32) -> Self {
+        Self {
+            step_seconds: step_seconds.max(1e-6),
+            accumulator: 0.0,
+            max_steps_per_frame: 8,
+        }
+    }
+
+    pub fn from_rate(steps_per_second: f32) -> Self {
+        Self::new(1.0 / steps_per_second.max(1e-3))
+    }
+
+    pub fn step_seconds(&self) -> f32 {
+        self.step_seconds
+    }
+
+    /// Accumulates `dt`, and returns the number of whole steps to take.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt.max(0.0);
+
+        let steps = (self.accumulator / self.step_seconds).floor() as u32;
+        self.accumulator -= steps as f32 * self.step_seconds;
+
+        if steps > self.max_steps_per_frame {
+            self.accumulator = 0.0;
+            self.max_steps_per_frame
+        } else {
+            steps
+        }
+    }
+
+    /// How far into the next step the accumulated time is, from 0 to 1.
+    pub fn interpolation_alpha(&self) -> f32 {
+        (self.accumulator / self.step_seconds).clamp(0.0, 1.0)
+    }
+
+    /// Drops any accumulated time, as after a pause or seek.
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_timestep_carries_the_remainder() {
+        let mut timestep = FixedTimestep::new(0.25);
+
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.interpolation_alpha(), 0.5);
+
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.interpolation_alpha(), 0.0);
+
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.interpolation_alpha(), 0.5);
+
+        // Negative time is ignored.
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert_eq!(timestep.interpolation_alpha(), 0.5);
+    }
+
+    #[test]
+    fn fixed_timestep_clamps_at_max_steps() {
+        let mut timestep = FixedTimestep::new(0.25);
+        timestep.max_steps_per_frame = 4;
+
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.advance(10.0), 4);
+
+        // The hitch is dropped rather than caught up with.
+        assert_eq!(timestep.interpolation_alpha(), 0.0);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.interpolation_alpha(), 0.5);
+    }
+
+    #[test]
+    fn fixed_timestep_reset_drops_accumulated_time() {
+        let mut timestep = FixedTimestep::from_rate(4.0);
+        assert_eq!(timestep.step_seconds(), 0.25);
+
+        timestep.advance(0.125);
+        timestep.reset();
+        assert_eq!(timestep.interpolation_alpha(), 0.0);
+        assert_eq!(timestep.advance(0.125), 0);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 pub use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
 use winit::{
     dpi::PhysicalPosition,
-    event::{Event, WindowEvent},
+    event::{Event, MouseScrollDelta, WindowEvent},
 };
 
 #[derive(Clone)]
@@ -61,6 +61,9 @@ pub struct MouseState {
     pub buttons_held: u32,
     pub buttons_pressed: u32,
     pub buttons_released: u32,
+
+    /// Lines scrolled this frame; positive away from the user.
+    pub wheel_delta: f32,
 }
 
 impl Default for MouseState {
@@ -71,6 +74,7 @@ impl Default for MouseState {
             buttons_held: 0,
             buttons_pressed: 0,
             buttons_released: 0,
+            wheel_delta: 0.0,
         }
     }
 }
@@ -80,6 +84,7 @@ impl MouseState {
         self.buttons_pressed = 0;
         self.buttons_released = 0;
         self.delta = Vec2::ZERO;
+        self.wheel_delta = 0.0;
 
         for event in events {
             match event {
@@ -103,6 +108,13 @@ impl MouseState {
                             self.buttons_released |= 1 << button_id;
                         }
                     }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        self.wheel_delta += match delta {
+                            MouseScrollDelta::LineDelta(_, y) => *y,
+                            // Roughly the height of a line of text
+                            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0,
+                        };
+                    }
                     _ => (),
                 },
                 Event::DeviceEvent {
@@ -120,6 +132,7 @@ impl MouseState {
 
 pub type InputAxis = &'static str;
 
+#[derive(Clone, Copy, Debug)]
 pub struct KeyMap {
     axis: InputAxis,
     multiplier: f32,
@@ -139,6 +152,14 @@ impl KeyMap {
         self.activation_time = value;
         self
     }
+
+    pub fn axis(&self) -> InputAxis {
+        self.axis
+    }
+
+    pub fn multiplier(&self) -> f32 {
+        self.multiplier
+    }
 }
 
 struct KeyMapState {
@@ -174,6 +195,31 @@ impl KeyboardMap {
         self
     }
 
+    /// Binds `key` to `map`, replacing any bindings the key already has.
+    pub fn rebind(&mut self, key: VirtualKeyCode, map: KeyMap) {
+        self.unbind_key(key);
+        self.bindings.push((
+            key,
+            KeyMapState {
+                map,
+                activation: 0.0,
+            },
+        ));
+    }
+
+    pub fn unbind_key(&mut self, key: VirtualKeyCode) {
+        self.bindings.retain(|(vk, _)| *vk != key);
+    }
+
+    /// Removes all bindings driving `axis`.
+    pub fn unbind_axis(&mut self, axis: InputAxis) {
+        self.bindings.retain(|(_, s)| s.map.axis != axis);
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (VirtualKeyCode, &KeyMap)> + '_ {
+        self.bindings.iter().map(|(vk, s)| (*vk, &s.map))
+    }
+
     pub fn map(&mut self, keyboard: &KeyboardState, dt: f32) -> HashMap<InputAxis, f32> {
         let mut result: HashMap<InputAxis, f32> = HashMap::new();
 
@@ -1,6 +1,14 @@
+pub mod camera_controller;
+pub mod camera_path;
+mod dynamic_resolution;
+mod frame_timing;
 mod input;
 mod main_loop;
+mod renderer_config;
+pub mod scene;
 
+pub use dynamic_resolution::*;
+pub use frame_timing::*;
 pub use glam::*;
 pub use input::*;
 pub use kajiya::{
@@ -11,12 +19,18 @@ pub use kajiya::{
     camera::*,
     frame_desc::WorldFrameDesc,
     math::*,
-    world_renderer::{RenderDebugMode, RenderMode},
+    renderers::render_quality::{QualityPreset, RenderQuality},
+    world_renderer::{DeterministicMode, RenderDebugMode, RenderMode},
 };
 pub use log;
 pub use main_loop::*;
+pub use renderer_config::*;
+pub use structopt::{self, StructOpt};
 pub use winit::{
     self,
     event::{ElementState, KeyboardInput, MouseButton, WindowEvent},
     window::WindowBuilder,
 };
+
+#[cfg(feature = "egui-backend")]
+pub use kajiya_egui::egui;
@@ -1,16 +1,28 @@
-use std::collections::VecDeque;
+use std::{cell::Cell, path::PathBuf};
+
+use crate::{DynamicResolution, DynamicResolutionConfig, FrameTiming};
 
 use kajiya::{
     backend::{vulkan::RenderBackendConfig, *},
     frame_desc::WorldFrameDesc,
-    rg,
+    renderers::{
+        output_calibration::{final_blit, OutputEncoding, OutputScaling},
+        render_quality::RenderQuality,
+        render_target_formats::RenderTargetFormats,
+        working_color_space::WorkingColorSpace,
+    },
     ui_renderer::UiRenderer,
-    world_renderer::WorldRenderer,
+    world_renderer::{DeterministicMode, WorldRenderer},
 };
 
 #[cfg(feature = "dear-imgui")]
 use kajiya_imgui::ImGuiBackend;
 
+#[cfg(feature = "egui-backend")]
+use kajiya::ui_renderer::UiRenderGraphCallback;
+#[cfg(feature = "egui-backend")]
+use kajiya_egui::{egui, EguiBackend};
+
 use turbosloth::*;
 
 use winit::{
@@ -21,20 +33,34 @@ use winit::{
 };
 
 pub struct FrameContext<'a> {
+    /// Same as `frame_timing.dt_filtered()`
     pub dt_filtered: f32,
+    /// For changing the frame rate limit, or reading the unfiltered delta time.
+    pub frame_timing: &'a mut FrameTiming,
     pub render_extent: [u32; 2],
     pub events: &'a [Event<'static, ()>],
     pub world_renderer: &'a mut WorldRenderer,
     pub window: &'a winit::window::Window,
+    pub dynamic_resolution: Option<&'a mut DynamicResolution>,
 
     #[cfg(feature = "dear-imgui")]
     pub imgui: Option<ImguiContext<'a>>,
+
+    #[cfg(feature = "egui-backend")]
+    pub egui: Option<EguiContext<'a>>,
+
+    exit_requested: &'a Cell<bool>,
 }
 
 impl<'a> FrameContext<'a> {
     pub fn aspect_ratio(&self) -> f32 {
         self.render_extent[0] as f32 / self.render_extent[1] as f32
     }
+
+    /// Makes `SimpleMainLoop::run` return once this frame has been rendered.
+    pub fn request_exit(&self) {
+        self.exit_requested.set(true);
+    }
 }
 
 #[cfg(feature = "dear-imgui")]
@@ -58,6 +84,28 @@ impl<'a> ImguiContext<'a> {
     }
 }
 
+#[cfg(feature = "egui-backend")]
+pub struct EguiContext<'a> {
+    egui_backend: &'a mut EguiBackend,
+    ui_pass: &'a mut Option<UiRenderGraphCallback>,
+    window: &'a winit::window::Window,
+    dt_filtered: f32,
+}
+
+#[cfg(feature = "egui-backend")]
+impl<'a> EguiContext<'a> {
+    /// Textures to be shown via `egui::TextureId::User` need to be registered first.
+    pub fn backend(&mut self) -> &mut EguiBackend {
+        self.egui_backend
+    }
+
+    pub fn frame(self, callback: impl FnOnce(&egui::CtxRef)) {
+        let ctx = self.egui_backend.begin_frame(self.window, self.dt_filtered);
+        callback(&ctx);
+        *self.ui_pass = self.egui_backend.finish_frame(self.window);
+    }
+}
+
 struct MainLoopOptional {
     #[cfg(feature = "dear-imgui")]
     imgui_backend: ImGuiBackend,
@@ -65,6 +113,9 @@ struct MainLoopOptional {
     #[cfg(feature = "dear-imgui")]
     imgui: imgui::Context,
 
+    #[cfg(feature = "egui-backend")]
+    egui_backend: EguiBackend,
+
     #[cfg(feature = "puffin-server")]
     _puffin_server: puffin_http::Server,
 }
@@ -83,6 +134,43 @@ pub enum FullscreenMode {
     Exclusive,
 }
 
+/// FSR 2 quality presets, expressed as the ratio of output to internal resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fsr2QualityMode {
+    Quality,
+    Balanced,
+    Performance,
+    UltraPerformance,
+}
+
+impl Fsr2QualityMode {
+    pub fn upscale_ratio(self) -> f32 {
+        match self {
+            Fsr2QualityMode::Quality => 1.5,
+            Fsr2QualityMode::Balanced => 1.7,
+            Fsr2QualityMode::Performance => 2.0,
+            Fsr2QualityMode::UltraPerformance => 3.0,
+        }
+    }
+}
+
+impl std::str::FromStr for Fsr2QualityMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "quality" => Ok(Fsr2QualityMode::Quality),
+            "balanced" => Ok(Fsr2QualityMode::Balanced),
+            "performance" => Ok(Fsr2QualityMode::Performance),
+            "ultra-performance" => Ok(Fsr2QualityMode::UltraPerformance),
+            _ => Err(anyhow::anyhow!(
+                "Unknown FSR 2 quality mode {:?}; expected one of: quality, balanced, performance, ultra-performance",
+                s
+            )),
+        }
+    }
+}
+
 pub struct SimpleMainLoopBuilder {
     resolution: [u32; 2],
     vsync: bool,
@@ -92,6 +180,17 @@ pub struct SimpleMainLoopBuilder {
     default_log_level: log::LevelFilter,
     window_scale: WindowScale,
     temporal_upsampling: f32,
+    fsr2_quality_mode: Option<Fsr2QualityMode>,
+    dynamic_resolution: Option<DynamicResolutionConfig>,
+    render_quality: RenderQuality,
+    render_target_formats: RenderTargetFormats,
+    working_color_space: WorkingColorSpace,
+    deterministic: Option<DeterministicMode>,
+    max_fps: Option<f32>,
+    hdr_output: bool,
+    output_scaling: OutputScaling,
+    kajiya_root: Option<PathBuf>,
+    shader_root: Option<PathBuf>,
 }
 
 impl Default for SimpleMainLoopBuilder {
@@ -111,6 +210,17 @@ impl SimpleMainLoopBuilder {
             default_log_level: log::LevelFilter::Warn,
             window_scale: WindowScale::SystemNative,
             temporal_upsampling: 1.0,
+            fsr2_quality_mode: None,
+            dynamic_resolution: None,
+            render_quality: RenderQuality::default(),
+            render_target_formats: RenderTargetFormats::default(),
+            working_color_space: WorkingColorSpace::default(),
+            deterministic: None,
+            max_fps: None,
+            hdr_output: false,
+            output_scaling: OutputScaling::default(),
+            kajiya_root: None,
+            shader_root: None,
         }
     }
 
@@ -158,6 +268,84 @@ impl SimpleMainLoopBuilder {
         self
     }
 
+    /// Picks the internal rendering resolution from an FSR 2 quality preset,
+    /// overriding `temporal_upsampling`. The upscaling itself is done by FSR 2
+    /// when `kajiya` is built with the `fsr2` feature, and by TAA otherwise.
+    pub fn fsr2_quality_mode(mut self, fsr2_quality_mode: Option<Fsr2QualityMode>) -> Self {
+        self.fsr2_quality_mode = fsr2_quality_mode;
+        self
+    }
+
+    /// Enables scaling the internal rendering resolution down from the one implied by
+    /// `temporal_upsampling` when the GPU can't keep up with the target frame time.
+    pub fn dynamic_resolution(
+        mut self,
+        dynamic_resolution: Option<DynamicResolutionConfig>,
+    ) -> Self {
+        self.dynamic_resolution = dynamic_resolution;
+        self
+    }
+
+    /// Initial value of `WorldRenderer::render_quality`.
+    pub fn render_quality(mut self, render_quality: RenderQuality) -> Self {
+        self.render_quality = render_quality;
+        self
+    }
+
+    /// Initial value of `WorldRenderer::render_target_formats`.
+    pub fn render_target_formats(mut self, render_target_formats: RenderTargetFormats) -> Self {
+        self.render_target_formats = render_target_formats;
+        self
+    }
+
+    /// Initial value of `WorldRenderer::working_color_space`.
+    pub fn working_color_space(mut self, working_color_space: WorkingColorSpace) -> Self {
+        self.working_color_space = working_color_space;
+        self
+    }
+
+    /// Renders in `DeterministicMode`. Its fixed delta time is also passed to
+    /// the frame callback as `FrameContext::dt_filtered`, in place of the measured one.
+    pub fn deterministic(mut self, deterministic: Option<DeterministicMode>) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Initial value of `FrameTiming::max_fps`.
+    pub fn max_fps(mut self, max_fps: Option<f32>) -> Self {
+        self.max_fps = max_fps;
+        self
+    }
+
+    /// Presents in HDR10 where supported, falling back to SDR. The brightness of the output
+    /// is then set by `WorldRenderer::output_calibration`.
+    pub fn hdr_output(mut self, hdr_output: bool) -> Self {
+        self.hdr_output = hdr_output;
+        self
+    }
+
+    /// Initial value of `WorldRenderer::output_scaling`. The output stays at `resolution`
+    /// whatever the size of the window, and is only scaled to it in the final blit.
+    pub fn output_scaling(mut self, output_scaling: OutputScaling) -> Self {
+        self.output_scaling = output_scaling;
+        self
+    }
+
+    /// kajiya's root directory, with the `assets` in it; relative to the working directory.
+    /// If not given, the vfs is left as is, which by default has the root searched for;
+    /// see `resolve_kajiya_root`. The `KAJIYA_ROOT` environment variable overrides it.
+    pub fn kajiya_root(mut self, kajiya_root: Option<PathBuf>) -> Self {
+        self.kajiya_root = kajiya_root;
+        self
+    }
+
+    /// Where to load shaders from instead of `assets/shaders` under the root directory.
+    /// The `KAJIYA_SHADER_ROOT` environment variable overrides it.
+    pub fn shader_root(mut self, shader_root: Option<PathBuf>) -> Self {
+        self.shader_root = shader_root;
+        self
+    }
+
     pub fn build(self, window_builder: WindowBuilder) -> anyhow::Result<SimpleMainLoop> {
         SimpleMainLoop::build(self, window_builder)
     }
@@ -174,6 +362,8 @@ pub struct SimpleMainLoop {
     render_backend: RenderBackend,
     rg_renderer: kajiya::rg::renderer::Renderer,
     render_extent: [u32; 2],
+    dynamic_resolution: Option<DynamicResolution>,
+    frame_timing: FrameTiming,
 }
 
 impl SimpleMainLoop {
@@ -188,6 +378,15 @@ impl SimpleMainLoop {
         kajiya::logging::set_up_logging(builder.default_log_level)?;
         std::env::set_var("SMOL_THREADS", "64"); // HACK; TODO: get a real executor
 
+        // Otherwise the vfs is left as the app set it up, or as found by default.
+        if builder.kajiya_root.is_some() || builder.shader_root.is_some() {
+            mount_kajiya_roots(builder.kajiya_root.clone(), builder.shader_root.clone())?;
+        }
+
+        if let Ok(kajiya_root) = normalized_path_from_vfs("/kajiya") {
+            log::info!("kajiya root directory: {:?}", kajiya_root);
+        }
+
         // Note: asking for the logical size means that if the OS is using DPI scaling,
         // we'll get a physically larger window (with more pixels).
         // The internal rendering resolution will still be what was asked of the `builder`,
@@ -197,9 +396,41 @@ impl SimpleMainLoop {
             builder.resolution[1] as f64,
         ));
 
-        let event_loop = EventLoop::new();
+        let mut event_loop = EventLoop::new();
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        let is_wayland = {
+            use winit::platform::unix::EventLoopWindowTargetExtUnix;
+            let is_wayland = event_loop.is_wayland();
+            log::info!(
+                "Windowing system: {}",
+                if is_wayland { "Wayland" } else { "X11" }
+            );
+            is_wayland
+        };
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        let is_wayland = false;
+
+        if let Some(mut fullscreen) = builder.fullscreen {
+            // Wayland doesn't allow applications to change the video mode.
+            if is_wayland && matches!(fullscreen, FullscreenMode::Exclusive) {
+                log::warn!("Exclusive fullscreen is not available on Wayland; using borderless");
+                fullscreen = FullscreenMode::Borderless;
+            }
 
-        if let Some(fullscreen) = builder.fullscreen {
             window_builder = window_builder.with_fullscreen(match fullscreen {
                 FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
                 FullscreenMode::Exclusive => Some(Fullscreen::Exclusive(
@@ -215,13 +446,32 @@ impl SimpleMainLoop {
 
         let window = window_builder.build(&event_loop).expect("window");
 
+        // Wayland only reports the scale factor once the window has been mapped onto an output,
+        // resizing the window then. Process those events before sizing the swapchain after it.
+        event_loop.run_return(|event, _, control_flow| {
+            *control_flow = match event {
+                Event::MainEventsCleared => ControlFlow::Exit,
+                _ => ControlFlow::Poll,
+            };
+        });
+
+        log::info!(
+            "Window size: {:?} physical pixels; scale factor: {}",
+            window.inner_size(),
+            window.scale_factor()
+        );
+
         // Physical window extent in pixels
         let swapchain_extent = [window.inner_size().width, window.inner_size().height];
 
+        let temporal_upsampling = builder
+            .fsr2_quality_mode
+            .map_or(builder.temporal_upsampling, Fsr2QualityMode::upscale_ratio);
+
         // Find the internal rendering resolution
         let render_extent = [
-            (builder.resolution[0] as f32 / builder.temporal_upsampling) as u32,
-            (builder.resolution[1] as f32 / builder.temporal_upsampling) as u32,
+            (builder.resolution[0] as f32 / temporal_upsampling) as u32,
+            (builder.resolution[1] as f32 / temporal_upsampling) as u32,
         ];
 
         log::info!(
@@ -232,7 +482,7 @@ impl SimpleMainLoop {
 
         let temporal_upscale_extent = builder.resolution;
 
-        if builder.temporal_upsampling != 1.0 {
+        if temporal_upsampling != 1.0 {
             log::info!(
                 "Temporal upscaling extent: {}x{}",
                 temporal_upscale_extent[0],
@@ -247,16 +497,22 @@ impl SimpleMainLoop {
                 vsync: builder.vsync,
                 graphics_debugging: builder.graphics_debugging,
                 device_index: builder.physical_device_index,
+                hdr_output: builder.hdr_output,
             },
         )?;
 
         let lazy_cache = LazyCache::create();
-        let world_renderer = WorldRenderer::new(
+        let mut world_renderer = WorldRenderer::new(
             render_extent,
             temporal_upscale_extent,
             &render_backend,
             &lazy_cache,
         )?;
+        world_renderer.render_quality = builder.render_quality;
+        world_renderer.render_target_formats = builder.render_target_formats;
+        world_renderer.working_color_space = builder.working_color_space;
+        world_renderer.output_scaling = builder.output_scaling;
+        world_renderer.set_deterministic(builder.deterministic);
         let ui_renderer = UiRenderer::default();
 
         let rg_renderer = kajiya::rg::renderer::Renderer::new(&render_backend)?;
@@ -269,7 +525,7 @@ impl SimpleMainLoop {
             kajiya_imgui::ImGuiBackend::new(rg_renderer.device().clone(), &window, &mut imgui);
 
         #[cfg(feature = "dear-imgui")]
-        imgui_backend.create_graphics_resources(swapchain_extent);
+        imgui_backend.create_graphics_resources(render_backend.swapchain.extent());
 
         #[cfg(feature = "puffin-server")]
         let puffin_server = {
@@ -285,6 +541,8 @@ impl SimpleMainLoop {
             imgui_backend,
             #[cfg(feature = "dear-imgui")]
             imgui,
+            #[cfg(feature = "egui-backend")]
+            egui_backend: EguiBackend::new(rg_renderer.device().clone()),
             #[cfg(feature = "puffin-server")]
             _puffin_server: puffin_server,
         };
@@ -298,6 +556,11 @@ impl SimpleMainLoop {
             render_backend,
             rg_renderer,
             render_extent,
+            dynamic_resolution: builder.dynamic_resolution.map(DynamicResolution::new),
+            frame_timing: FrameTiming {
+                max_fps: builder.max_fps,
+                ..Default::default()
+            },
         })
     }
 
@@ -318,23 +581,24 @@ impl SimpleMainLoop {
             mut event_loop,
             mut render_backend,
             mut rg_renderer,
-            render_extent,
+            render_extent: max_render_extent,
+            mut dynamic_resolution,
+            mut frame_timing,
         } = self;
 
         let mut events = Vec::new();
 
-        let mut last_frame_instant = std::time::Instant::now();
         let mut last_error_text = None;
 
-        // Delta times are filtered over _this many_ frames.
-        const DT_FILTER_WIDTH: usize = 10;
-
-        // Past delta times used for filtering
-        let mut dt_queue: VecDeque<f32> = VecDeque::with_capacity(DT_FILTER_WIDTH);
+        let exit_requested = Cell::new(false);
 
-        // Fake the first frame's delta time. In the first frame, shaders
-        // and pipelines are be compiled, so it will most likely have a spike.
-        let mut fake_dt_countdown: i32 = 1;
+        // Physical extent in pixels. Fixed, as the swapchain doesn't get recreated.
+        let swapchain_extent = render_backend.swapchain.extent();
+        let output_encoding = if render_backend.swapchain.is_hdr10() {
+            OutputEncoding::Pq
+        } else {
+            OutputEncoding::Srgb
+        };
 
         let mut running = true;
         while running {
@@ -342,20 +606,41 @@ impl SimpleMainLoop {
             puffin::profile_scope!("main loop");
             puffin::GlobalProfiler::lock().new_frame();
 
-            event_loop.run_return(|event, _, control_flow| {
+            event_loop.run_return(|mut event, _, control_flow| {
                 puffin::profile_scope!("event handler");
 
+                // The swapchain can't be resized yet, so keep the window at its physical size
+                // when moved to a display with a different scale factor, rather than stretching.
+                if let Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                    ..
+                } = &mut event
+                {
+                    **new_inner_size =
+                        winit::dpi::PhysicalSize::new(swapchain_extent[0], swapchain_extent[1]);
+                }
+
                 let _ = &render_backend;
                 #[cfg(feature = "dear-imgui")]
                 optional
                     .imgui_backend
                     .handle_event(&window, &mut optional.imgui, &event);
 
+                #[cfg(feature = "egui-backend")]
+                optional.egui_backend.handle_event(&window, &event);
+
+                #[allow(unused_mut)]
+                let mut ui_wants_mouse = false;
+
                 #[cfg(feature = "dear-imgui")]
-                let ui_wants_mouse = optional.imgui.io().want_capture_mouse;
+                {
+                    ui_wants_mouse |= optional.imgui.io().want_capture_mouse;
+                }
 
-                #[cfg(not(feature = "dear-imgui"))]
-                let ui_wants_mouse = false;
+                #[cfg(feature = "egui-backend")]
+                {
+                    ui_wants_mouse |= optional.egui_backend.wants_pointer_input();
+                }
 
                 *control_flow = ControlFlow::Poll;
 
@@ -386,43 +671,27 @@ impl SimpleMainLoop {
 
             puffin::profile_scope!("MainEventsCleared");
 
-            // Filter the frame time before passing it to the application and renderer.
-            // Fluctuations in frame rendering times cause stutter in animations,
-            // and time-dependent effects (such as motion blur).
-            //
-            // Should applications need unfiltered delta time, they can calculate
-            // it themselves, but it's good to pass the filtered time so users
-            // don't need to worry about it.
-            let dt_filtered = {
-                let now = std::time::Instant::now();
-                let dt_duration = now - last_frame_instant;
-                last_frame_instant = now;
-
-                let dt_raw = dt_duration.as_secs_f32();
-
-                // >= because rendering (and thus the spike) happens _after_ this.
-                if fake_dt_countdown >= 0 {
-                    // First frame. Return the fake value.
-                    fake_dt_countdown -= 1;
-                    dt_raw.min(1.0 / 60.0)
-                } else {
-                    // Not the first frame. Start averaging.
-
-                    if dt_queue.len() >= DT_FILTER_WIDTH {
-                        dt_queue.pop_front();
-                    }
+            // Animations must not depend on wall-clock time in deterministic mode.
+            frame_timing.fixed_dt = world_renderer
+                .deterministic()
+                .map(|deterministic| deterministic.delta_time_seconds);
+            let dt_filtered = frame_timing.begin_frame();
 
-                    dt_queue.push_back(dt_raw);
-                    dt_queue.iter().copied().sum::<f32>() / dt_queue.len() as f32
-                }
-            };
+            let render_extent = dynamic_resolution
+                .as_ref()
+                .map_or(max_render_extent, |dr| dr.render_extent(max_render_extent));
+
+            #[cfg(feature = "egui-backend")]
+            let mut egui_pass = None;
 
             let frame_desc = frame_fn(FrameContext {
                 dt_filtered,
+                frame_timing: &mut frame_timing,
                 render_extent,
                 events: &events,
                 world_renderer: &mut world_renderer,
                 window: &window,
+                dynamic_resolution: dynamic_resolution.as_mut(),
 
                 #[cfg(feature = "dear-imgui")]
                 imgui: Some(ImguiContext {
@@ -432,38 +701,41 @@ impl SimpleMainLoop {
                     dt_filtered,
                     window: &window,
                 }),
+
+                #[cfg(feature = "egui-backend")]
+                egui: Some(EguiContext {
+                    egui_backend: &mut optional.egui_backend,
+                    ui_pass: &mut egui_pass,
+                    dt_filtered,
+                    window: &window,
+                }),
+
+                exit_requested: &exit_requested,
             });
 
-            events.clear();
+            #[cfg(feature = "egui-backend")]
+            ui_renderer.ui_graph_passes.extend(egui_pass);
 
-            // Physical window extent in pixels
-            let swapchain_extent = [window.inner_size().width, window.inner_size().height];
+            events.clear();
 
             let prepared_frame = {
                 puffin::profile_scope!("prepare_frame");
                 rg_renderer.prepare_frame(|rg| {
                     rg.debug_hook = world_renderer.rg_debug_hook.take();
                     let main_img = world_renderer.prepare_render_graph(rg, &frame_desc);
-                    let ui_img = ui_renderer.prepare_render_graph(rg);
+                    let ui_img = ui_renderer.prepare_render_graph(rg, swapchain_extent);
 
                     let mut swap_chain = rg.get_swap_chain();
-                    rg::SimpleRenderPass::new_compute(
-                        rg.add_pass("final blit"),
-                        "/shaders/final_blit.hlsl",
-                    )
-                    .read(&main_img)
-                    .read(&ui_img)
-                    .write(&mut swap_chain)
-                    .constants((
-                        main_img.desc().extent_inv_extent_2d(),
-                        [
-                            swapchain_extent[0] as f32,
-                            swapchain_extent[1] as f32,
-                            1.0 / swapchain_extent[0] as f32,
-                            1.0 / swapchain_extent[1] as f32,
-                        ],
-                    ))
-                    .dispatch([swapchain_extent[0], swapchain_extent[1], 1]);
+                    final_blit(
+                        rg,
+                        &main_img,
+                        &ui_img,
+                        &mut swap_chain,
+                        swapchain_extent,
+                        &world_renderer.output_calibration,
+                        world_renderer.output_scaling,
+                        output_encoding,
+                    );
                 })
             };
 
@@ -484,17 +756,49 @@ impl SimpleMainLoop {
                     last_error_text = None;
                 }
                 Err(e) => {
+                    // Most errors, such as shaders failing to compile, only cost frames
+                    // until fixed; a lost device won't come back.
+                    if e.downcast_ref::<BackendError>()
+                        .map_or(false, BackendError::is_device_lost)
+                    {
+                        running = false;
+                    }
+
                     let error_text = Some(format!("{:?}", e));
                     if error_text != last_error_text {
-                        println!("{}", error_text.as_ref().unwrap());
+                        log::error!("{}", error_text.as_ref().unwrap());
                         last_error_text = error_text;
                     }
                 }
             }
 
-            report_gpu_stats_to_puffin(&gpu_profiler::get_stats(), gpu_frame_start_ns);
+            let gpu_stats = gpu_profiler::get_stats();
+            report_gpu_stats_to_puffin(&gpu_stats, gpu_frame_start_ns);
+
+            if let Some(dynamic_resolution) = dynamic_resolution.as_mut() {
+                dynamic_resolution.update(&gpu_stats);
+            }
+
+            if exit_requested.get() {
+                running = false;
+            }
+
+            frame_timing.pace();
         }
 
+        // Tear down with no frames in flight, and users of the device before the device itself.
+        rg_renderer.shutdown();
+        world_renderer.flush_readbacks();
+
+        #[cfg(feature = "dear-imgui")]
+        optional.imgui_backend.destroy_graphics_resources();
+
+        drop(ui_renderer);
+        drop(world_renderer);
+
+        // The swapchain, and the last reference to the device.
+        drop(render_backend);
+
         Ok(())
     }
 }
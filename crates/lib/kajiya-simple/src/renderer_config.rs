@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+
+use kajiya::{
+    renderers::{
+        gbuffer_layout::{GbufferLayout, GbufferNormalEncoding, VelocityPrecision},
+        output_calibration::{OutputFilter, OutputScaling},
+        render_quality::{QualityPreset, RenderQuality},
+        render_target_formats::{
+            ColorPrecision, GiHistoryPrecision, NormalEncoding, RenderTargetFormats,
+        },
+        working_color_space::WorkingColorSpace,
+    },
+    world_renderer::DeterministicMode,
+};
+use structopt::StructOpt;
+
+use crate::{DynamicResolutionConfig, Fsr2QualityMode, FullscreenMode, SimpleMainLoopBuilder};
+
+/// Renderer settings common to `kajiya` apps.
+///
+/// Parsed from the command line via `RendererConfig::from_args`, or flattened into
+/// an app's own `StructOpt` options. Can also be built in code, starting from `default()`.
+#[derive(Clone, Debug, StructOpt)]
+pub struct RendererConfig {
+    /// Logical window size, and output resolution.
+    #[structopt(long, default_value = "1920")]
+    pub width: u32,
+
+    #[structopt(long, default_value = "1080")]
+    pub height: u32,
+
+    /// Output to internal rendering resolution ratio; the inverse of the internal render scale.
+    #[structopt(long, default_value = "1.0")]
+    pub temporal_upsampling: f32,
+
+    /// FSR 2 quality preset: quality, balanced, performance, or ultra-performance.
+    /// Overrides `--temporal-upsampling`.
+    #[structopt(long)]
+    pub fsr2_quality: Option<Fsr2QualityMode>,
+
+    /// Enables dynamic resolution scaling to hold this frame rate on the GPU.
+    #[structopt(long)]
+    pub target_fps: Option<f32>,
+
+    /// Lower bound for dynamic resolution scaling, as a fraction of the internal resolution.
+    #[structopt(long, default_value = "0.5")]
+    pub min_render_scale: f32,
+
+    /// Index of the GPU to use; the first suitable one if not specified.
+    #[structopt(long)]
+    pub physical_device_index: Option<usize>,
+
+    /// Enables the Vulkan validation layers.
+    #[structopt(long)]
+    pub graphics_debugging: bool,
+
+    /// Caps the frame rate by sleeping at the end of each frame.
+    #[structopt(long)]
+    pub max_fps: Option<f32>,
+
+    #[structopt(long = "no-vsync", parse(from_flag = std::ops::Not::not))]
+    pub vsync: bool,
+
+    /// Presents in HDR10 if the display supports it.
+    #[structopt(long)]
+    pub hdr: bool,
+
+    #[structopt(long)]
+    pub fullscreen: bool,
+
+    /// Fits the output into the window keeping its aspect ratio, rather than stretching it.
+    #[structopt(long)]
+    pub letterbox: bool,
+
+    /// Filter for scaling the output to the window size: bicubic, or nearest for pixel-exact
+    /// comparisons. With `--letterbox`, nearest scales by whole multiples where it can.
+    #[structopt(long, default_value = "bicubic")]
+    pub output_filter: OutputFilter,
+
+    #[structopt(long = "no-window-decorations", parse(from_flag = std::ops::Not::not))]
+    pub window_decorations: bool,
+
+    /// Quality preset: low, medium, high, or ultra. High if not specified, though apps
+    /// may restore settings from an earlier session instead.
+    #[structopt(long = "quality")]
+    pub quality_preset: Option<QualityPreset>,
+
+    /// Format of the lit scene: rgba16f, or r11g11b10f to save bandwidth.
+    #[structopt(long, default_value = "rgba16f")]
+    pub color_precision: ColorPrecision,
+
+    /// Format of the diffuse GI history: half, or full to reduce banding.
+    #[structopt(long, default_value = "half")]
+    pub gi_history_precision: GiHistoryPrecision,
+
+    /// Format of the G-buffer normals: unorm10, or unorm16 to reduce banding.
+    #[structopt(long, default_value = "unorm10")]
+    pub normal_encoding: NormalEncoding,
+
+    /// Packing of the shading normals in the G-buffer: xyz11-10-11, oct16, or oct32.
+    #[structopt(long, default_value = "xyz11-10-11")]
+    pub gbuffer_normal_encoding: GbufferNormalEncoding,
+
+    /// Of the 14 G-buffer bits shared by roughness and metalness, those for roughness.
+    #[structopt(long, default_value = "8")]
+    pub gbuffer_roughness_bits: u32,
+
+    /// Format of the motion vectors: half, or full.
+    #[structopt(long, default_value = "half")]
+    pub velocity_precision: VelocityPrecision,
+
+    /// Writes a material ID target along with the G-buffer, for custom passes to read.
+    #[structopt(long)]
+    pub gbuffer_material_id: bool,
+
+    /// Primaries lighting is computed in: srgb, or acescg to match ACES pipelines.
+    #[structopt(long, default_value = "srgb")]
+    pub working_color_space: WorkingColorSpace,
+
+    /// Renders deterministically, seeding the noise, ray sampling, and camera jitter with
+    /// this value, and advancing time at `--deterministic-fps` rather than the wall clock.
+    #[structopt(long)]
+    pub deterministic_seed: Option<u32>,
+
+    #[structopt(long, default_value = "60")]
+    pub deterministic_fps: f32,
+
+    /// Scene `.ron` file to load on startup.
+    #[structopt(long)]
+    pub scene: Option<PathBuf>,
+
+    /// Directory with kajiya's `assets`; searched for by default, starting in the working
+    /// directory, then next to the executable. Overridden by `KAJIYA_ROOT`.
+    #[structopt(long)]
+    pub kajiya_root: Option<PathBuf>,
+
+    /// Directory to load shaders from; `assets/shaders` by default. Overridden by
+    /// `KAJIYA_SHADER_ROOT`.
+    #[structopt(long)]
+    pub shader_root: Option<PathBuf>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            temporal_upsampling: 1.0,
+            fsr2_quality: None,
+            target_fps: None,
+            min_render_scale: 0.5,
+            physical_device_index: None,
+            graphics_debugging: false,
+            max_fps: None,
+            vsync: true,
+            hdr: false,
+            fullscreen: false,
+            letterbox: false,
+            output_filter: OutputFilter::Bicubic,
+            window_decorations: true,
+            quality_preset: None,
+            color_precision: ColorPrecision::default(),
+            gi_history_precision: GiHistoryPrecision::default(),
+            normal_encoding: NormalEncoding::default(),
+            gbuffer_normal_encoding: GbufferNormalEncoding::default(),
+            gbuffer_roughness_bits: GbufferLayout::default().roughness_bits,
+            velocity_precision: VelocityPrecision::default(),
+            gbuffer_material_id: false,
+            working_color_space: WorkingColorSpace::default(),
+            deterministic_seed: None,
+            deterministic_fps: 60.0,
+            scene: None,
+            kajiya_root: None,
+            shader_root: None,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn dynamic_resolution_config(&self) -> Option<DynamicResolutionConfig> {
+        self.target_fps.map(|fps| DynamicResolutionConfig {
+            target_frame_time_ms: 1000.0 / fps.max(1.0),
+            min_scale: self.min_render_scale.clamp(0.1, 1.0),
+            ..Default::default()
+        })
+    }
+
+    pub fn render_target_formats(&self) -> RenderTargetFormats {
+        RenderTargetFormats {
+            color: self.color_precision,
+            gi_history: self.gi_history_precision,
+            normals: self.normal_encoding,
+            gbuffer: GbufferLayout {
+                normal: self.gbuffer_normal_encoding,
+                roughness_bits: self.gbuffer_roughness_bits,
+                velocity: self.velocity_precision,
+                material_id: self.gbuffer_material_id,
+            },
+        }
+    }
+
+    pub fn deterministic_mode(&self) -> Option<DeterministicMode> {
+        self.deterministic_seed.map(|seed| DeterministicMode {
+            seed,
+            delta_time_seconds: 1.0 / self.deterministic_fps.max(1.0),
+        })
+    }
+
+    /// A main loop builder with all the settings applied, except for the window decorations,
+    /// which go on the `WindowBuilder`.
+    pub fn main_loop_builder(&self) -> SimpleMainLoopBuilder {
+        SimpleMainLoopBuilder::new()
+            .resolution([self.width, self.height])
+            .vsync(self.vsync)
+            .graphics_debugging(self.graphics_debugging)
+            .physical_device_index(self.physical_device_index)
+            .temporal_upsampling(self.temporal_upsampling)
+            .fsr2_quality_mode(self.fsr2_quality)
+            .dynamic_resolution(self.dynamic_resolution_config())
+            .fullscreen(self.fullscreen.then(|| FullscreenMode::Exclusive))
+            .render_quality(RenderQuality::from_preset(
+                self.quality_preset.unwrap_or_default(),
+            ))
+            .render_target_formats(self.render_target_formats())
+            .working_color_space(self.working_color_space)
+            .deterministic(self.deterministic_mode())
+            .max_fps(self.max_fps)
+            .hdr_output(self.hdr)
+            .output_scaling(OutputScaling {
+                letterbox: self.letterbox,
+                filter: self.output_filter,
+            })
+            .kajiya_root(self.kajiya_root.clone())
+            .shader_root(self.shader_root.clone())
+    }
+}
@@ -0,0 +1,583 @@
+//! A RON-based scene format listing meshes along with their transforms and material
+//! animations, lights, camera presets and paths, sun/sky settings and their animation over
+//! the time of day, an optional heightfield terrain, and baked light probes. See `assets/scenes/` for examples.
+
+use std::{fs::File, path::Path};
+
+use anyhow::Context as _;
+use glam::{Affine3A, EulerRot, Quat, Vec3};
+use kajiya::{
+    asset::{
+        mesh::PunctualLight,
+        terrain::{TerrainDesc, TerrainLayerDesc},
+    },
+    backend::file::canonical_path_from_vfs,
+    renderers::{
+        light_probes::{LightProbe, LightProbeHandle, LightProbeSh},
+        material_animation::{MaterialAnimation, MaterialCurve},
+        time_of_day::TimeOfDayKey,
+    },
+    world_renderer::{
+        InstanceHandle, MeshHandle, PunctualLightHandle, WorldRenderer,
+        EARTH_SUN_ANGULAR_DIAMETER_DEGREES,
+    },
+};
+
+use crate::camera_controller::look_towards;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneDesc {
+    pub instances: Vec<SceneInstanceDesc>,
+    #[serde(default)]
+    pub lights: Vec<SceneLightDesc>,
+    #[serde(default)]
+    pub cameras: Vec<SceneCameraDesc>,
+    #[serde(default)]
+    pub camera_paths: Vec<SceneCameraPathDesc>,
+    #[serde(default)]
+    pub sun: Option<SceneSunDesc>,
+    #[serde(default)]
+    pub sky: Option<SceneSkyDesc>,
+    /// Overrides `sun`, and the ambient of `sky`.
+    #[serde(default)]
+    pub time_of_day: Option<SceneTimeOfDayDesc>,
+    #[serde(default)]
+    pub terrain: Option<SceneTerrainDesc>,
+    #[serde(default)]
+    pub light_probes: Vec<SceneLightProbeDesc>,
+}
+
+fn default_instance_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_direction() -> [f32; 3] {
+    [0.0, -1.0, 0.0]
+}
+
+fn default_sun_angular_diameter_degrees() -> f32 {
+    EARTH_SUN_ANGULAR_DIAMETER_DEGREES
+}
+
+fn default_turbidity() -> f32 {
+    1.0
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneInstanceDesc {
+    pub position: [f32; 3],
+    #[serde(default = "default_instance_scale")]
+    pub scale: [f32; 3],
+    /// Euler angles in degrees, applied in the Y, X, Z order.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    pub mesh: String,
+    /// These apply to the mesh, and thus to all its instances.
+    #[serde(default)]
+    pub material_animations: Vec<SceneMaterialAnimationDesc>,
+}
+
+impl SceneInstanceDesc {
+    pub fn affine_transform(&self) -> Affine3A {
+        Affine3A::from_scale_rotation_translation(
+            self.scale.into(),
+            euler_degrees_to_quat(self.rotation),
+            self.position.into(),
+        )
+    }
+}
+
+/// See `kajiya::renderers::material_animation::MaterialAnimation`.
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneMaterialAnimationDesc {
+    /// Index of the material in the mesh's source asset.
+    pub material: usize,
+    /// UV offset per second
+    #[serde(default)]
+    pub uv_scroll: [f32; 2],
+    /// `[time, multiplier]` keys, with the time in seconds. Loops over the last key.
+    #[serde(default)]
+    pub emissive_intensity: Vec<[f32; 2]>,
+}
+
+impl SceneMaterialAnimationDesc {
+    pub fn material_animation(&self) -> MaterialAnimation {
+        MaterialAnimation {
+            uv_scroll: self.uv_scroll,
+            emissive_intensity: (!self.emissive_intensity.is_empty()).then(|| MaterialCurve {
+                keys: self.emissive_intensity.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SceneLightKind {
+    Point,
+    /// Cone angles are in degrees, measured from the direction of the light.
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneLightDesc {
+    pub kind: SceneLightKind,
+    pub position: [f32; 3],
+    /// Only used by spot lights.
+    #[serde(default = "default_light_direction")]
+    pub direction: [f32; 3],
+    #[serde(default = "default_light_color")]
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light's contribution is cut off. Zero means infinite.
+    #[serde(default)]
+    pub range: f32,
+    /// Size of the light source, softening its shadows. Zero means a point source.
+    #[serde(default)]
+    pub radius: f32,
+}
+
+impl SceneLightDesc {
+    pub fn punctual_light(&self) -> PunctualLight {
+        let color = Vec3::from(self.color) * self.intensity;
+
+        let light = match self.kind {
+            SceneLightKind::Point => PunctualLight::point(self.position.into(), color, self.range),
+            SceneLightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => PunctualLight::spot(
+                self.position.into(),
+                self.direction.into(),
+                color,
+                self.range,
+                inner_cone_angle.to_radians(),
+                outer_cone_angle.to_radians(),
+            ),
+        };
+
+        light.with_radius(self.radius)
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneCameraDesc {
+    pub name: String,
+    pub position: [f32; 3],
+    /// Euler angles in degrees, applied in the Y, X, Z order. Ignored if `look_at` is set.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default)]
+    pub look_at: Option<[f32; 3]>,
+    #[serde(default)]
+    pub vertical_fov: Option<f32>,
+}
+
+impl SceneCameraDesc {
+    pub fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+
+    pub fn rotation(&self) -> Quat {
+        camera_rotation(self.position(), self.rotation, self.look_at)
+    }
+}
+
+/// See `camera_path::CameraPath`.
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneCameraPathDesc {
+    pub name: String,
+    pub keys: Vec<SceneCameraPathKeyDesc>,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneCameraPathKeyDesc {
+    /// In seconds
+    pub time: f32,
+    pub position: [f32; 3],
+    /// Euler angles in degrees, applied in the Y, X, Z order. Ignored if `look_at` is set.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default)]
+    pub look_at: Option<[f32; 3]>,
+    /// Held from the previous key if not set.
+    #[serde(default)]
+    pub vertical_fov: Option<f32>,
+}
+
+impl SceneCameraPathKeyDesc {
+    pub fn position(&self) -> Vec3 {
+        self.position.into()
+    }
+
+    pub fn rotation(&self) -> Quat {
+        camera_rotation(self.position(), self.rotation, self.look_at)
+    }
+}
+
+fn camera_rotation(position: Vec3, rotation: [f32; 3], look_at: Option<[f32; 3]>) -> Quat {
+    if let Some(look_at) = look_at {
+        look_towards(Vec3::from(look_at) - position)
+    } else {
+        euler_degrees_to_quat(rotation)
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneSunDesc {
+    /// Direction towards the sun; doesn't need to be normalized.
+    pub towards_sun: [f32; 3],
+    /// Controls the softness of sun shadows; zero makes them hard.
+    #[serde(default = "default_sun_angular_diameter_degrees")]
+    pub angular_diameter_degrees: f32,
+    #[serde(default = "default_light_color")]
+    pub color_multiplier: [f32; 3],
+}
+
+impl SceneSunDesc {
+    pub fn towards_sun(&self) -> Vec3 {
+        Vec3::from(self.towards_sun).normalize_or_zero()
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneSkyDesc {
+    #[serde(default)]
+    pub ambient: [f32; 3],
+    /// Path to an `.exr` or `.hdr` image to use as an image-based light.
+    #[serde(default)]
+    pub ibl: Option<String>,
+}
+
+/// See `kajiya::renderers::time_of_day::TimeOfDay`.
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneTimeOfDayDesc {
+    /// Hours since midnight at which the scene starts.
+    pub hour: f32,
+    /// Hours which pass per second; zero holds the time.
+    #[serde(default)]
+    pub hours_per_second: f32,
+    pub keys: Vec<SceneTimeOfDayKeyDesc>,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneTimeOfDayKeyDesc {
+    pub hour: f32,
+    /// Of the sun above the horizon; negative below it.
+    pub sun_elevation_degrees: f32,
+    /// Of the sun around the vertical axis, from +Z towards +X.
+    pub sun_azimuth_degrees: f32,
+    #[serde(default = "default_light_color")]
+    pub sun_color_multiplier: [f32; 3],
+    #[serde(default)]
+    pub sky_ambient: [f32; 3],
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f32,
+}
+
+impl SceneTimeOfDayKeyDesc {
+    pub fn time_of_day_key(&self) -> TimeOfDayKey {
+        TimeOfDayKey {
+            hour: self.hour,
+            sun_elevation_degrees: self.sun_elevation_degrees,
+            sun_azimuth_degrees: self.sun_azimuth_degrees,
+            sun_color_multiplier: self.sun_color_multiplier.into(),
+            sky_ambient: self.sky_ambient.into(),
+            turbidity: self.turbidity,
+        }
+    }
+}
+
+/// See `kajiya::renderers::light_probes::LightProbe`.
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SceneLightProbeDesc {
+    pub position: [f32; 3],
+    pub radius: f32,
+    /// Written by baking; probes without it get baked when the scene is loaded.
+    #[serde(default)]
+    pub irradiance: Option<LightProbeSh>,
+}
+
+impl SceneLightProbeDesc {
+    pub fn light_probe(&self) -> LightProbe {
+        LightProbe {
+            position: self.position.into(),
+            radius: self.radius,
+            irradiance: self.irradiance,
+        }
+    }
+}
+
+impl From<&LightProbe> for SceneLightProbeDesc {
+    fn from(probe: &LightProbe) -> Self {
+        Self {
+            position: probe.position.into(),
+            radius: probe.radius,
+            irradiance: probe.irradiance,
+        }
+    }
+}
+
+fn default_terrain_chunk_count() -> u32 {
+    8
+}
+
+fn default_terrain_chunk_resolution() -> u32 {
+    64
+}
+
+fn default_terrain_layer_color() -> [f32; 3] {
+    [0.5, 0.5, 0.5]
+}
+
+fn default_terrain_layer_roughness() -> f32 {
+    1.0
+}
+
+fn default_terrain_layer_tile_size() -> f32 {
+    1.0
+}
+
+fn default_terrain_layer_height_range() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+fn default_terrain_layer_slope_range() -> [f32; 2] {
+    [0.0, 90.0]
+}
+
+/// See `kajiya::asset::terrain::TerrainDesc` for the meaning of the fields.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneTerrainDesc {
+    pub heightmap: String,
+    /// Of the center of the terrain.
+    #[serde(default)]
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    pub height_scale: f32,
+    #[serde(default = "default_terrain_chunk_count")]
+    pub chunk_count: u32,
+    #[serde(default = "default_terrain_chunk_resolution")]
+    pub chunk_resolution: u32,
+    #[serde(default)]
+    pub layers: Vec<SceneTerrainLayerDesc>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SceneTerrainLayerDesc {
+    #[serde(default)]
+    pub albedo_map: Option<String>,
+    #[serde(default)]
+    pub normal_map: Option<String>,
+    #[serde(default = "default_terrain_layer_color")]
+    pub base_color: [f32; 3],
+    #[serde(default = "default_terrain_layer_roughness")]
+    pub roughness: f32,
+    #[serde(default = "default_terrain_layer_tile_size")]
+    pub tile_size: f32,
+    #[serde(default = "default_terrain_layer_height_range")]
+    pub height_range: [f32; 2],
+    /// In degrees
+    #[serde(default = "default_terrain_layer_slope_range")]
+    pub slope_range: [f32; 2],
+}
+
+impl SceneTerrainDesc {
+    /// Resolves the heightmap and layer map paths through the VFS.
+    pub fn terrain_desc(&self) -> anyhow::Result<TerrainDesc> {
+        let map_path = |path: &Option<String>| -> anyhow::Result<_> {
+            path.as_ref()
+                .map(|path| {
+                    canonical_path_from_vfs(path)
+                        .with_context(|| format!("Terrain layer map: {:?}", path))
+                })
+                .transpose()
+        };
+
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                Ok(TerrainLayerDesc {
+                    albedo_map: map_path(&layer.albedo_map)?,
+                    normal_map: map_path(&layer.normal_map)?,
+                    base_color: layer.base_color,
+                    roughness: layer.roughness,
+                    tile_size: layer.tile_size,
+                    height_range: layer.height_range,
+                    slope_range: layer.slope_range,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(TerrainDesc {
+            heightmap: canonical_path_from_vfs(&self.heightmap)
+                .with_context(|| format!("Heightmap: {:?}", self.heightmap))?,
+            size: self.size,
+            height_scale: self.height_scale,
+            chunk_count: self.chunk_count,
+            chunk_resolution: self.chunk_resolution,
+            layers,
+        })
+    }
+
+    pub fn affine_transform(&self) -> Affine3A {
+        Affine3A::from_translation(self.position.into())
+    }
+}
+
+fn euler_degrees_to_quat(rotation: [f32; 3]) -> Quat {
+    Quat::from_euler(
+        EulerRot::YXZ,
+        rotation[1].to_radians(),
+        rotation[0].to_radians(),
+        rotation[2].to_radians(),
+    )
+}
+
+/// Handles to what a `SceneDesc` created in the world renderer.
+pub struct LoadedScene {
+    /// In the same order as `SceneDesc::instances`
+    pub instances: Vec<InstanceHandle>,
+    pub lights: Vec<PunctualLightHandle>,
+    /// In the same order as `SceneDesc::light_probes`
+    pub light_probes: Vec<LightProbeHandle>,
+}
+
+impl LoadedScene {
+    pub fn remove(self, world_renderer: &mut WorldRenderer) {
+        for inst in self.instances {
+            world_renderer.remove_instance(inst);
+        }
+
+        for light in self.lights {
+            world_renderer.remove_punctual_light(light);
+        }
+
+        for probe in self.light_probes {
+            world_renderer.light_probes.remove(probe);
+        }
+    }
+}
+
+impl SceneDesc {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Opening scene file {:?}", path))?;
+        ron::de::from_reader(file).with_context(|| format!("Parsing scene file {:?}", path))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Creating scene file {:?}", path))?;
+        ron::ser::to_writer_pretty(file, self, Default::default())
+            .with_context(|| format!("Writing scene file {:?}", path))
+    }
+
+    /// Replaces `light_probes` with the current ones of `world_renderer`, e.g. after baking them.
+    pub fn store_light_probes(&mut self, world_renderer: &WorldRenderer) {
+        self.light_probes = world_renderer
+            .light_probes
+            .iter()
+            .map(|(_, probe)| probe.into())
+            .collect();
+    }
+
+    /// Creates the instances, lights, and light probes of the scene, and applies its sun
+    /// and sky settings. Light probes which haven't been baked yet are queued for baking.
+    ///
+    /// Meshes are loaded via `load_mesh`, which gets called with each instance's `mesh` path,
+    /// and can e.g. bake the mesh, or use `WorldRenderer::add_baked_mesh`, or its
+    /// `add_baked_mesh_in_background` variant to bake and upload it without blocking.
+    /// The sun direction, camera presets and paths, and terrain are left for the caller to use;
+    /// the terrain needs baking via `kajiya_asset_pipe::process_terrain_asset` first.
+    pub fn instantiate(
+        &self,
+        world_renderer: &mut WorldRenderer,
+        mut load_mesh: impl FnMut(&mut WorldRenderer, &str) -> anyhow::Result<MeshHandle>,
+    ) -> anyhow::Result<LoadedScene> {
+        let mut instances = Vec::with_capacity(self.instances.len());
+        for instance in &self.instances {
+            let mesh = load_mesh(world_renderer, &instance.mesh)
+                .with_context(|| format!("Mesh path: {:?}", instance.mesh))?;
+
+            for animation in &instance.material_animations {
+                // Checked by `set_material_animation` once a mesh loading in the background is done
+                let material_count = world_renderer.mesh_material_count(mesh);
+                anyhow::ensure!(
+                    !world_renderer.is_mesh_loaded(mesh) || animation.material < material_count,
+                    "Material animation of {:?}: material {} out of {}",
+                    instance.mesh,
+                    animation.material,
+                    material_count
+                );
+
+                world_renderer.set_material_animation(
+                    mesh,
+                    animation.material,
+                    Some(animation.material_animation()),
+                );
+            }
+
+            instances.push(world_renderer.add_instance(mesh, instance.affine_transform()));
+        }
+
+        let lights = self
+            .lights
+            .iter()
+            .map(|light| world_renderer.add_punctual_light(light.punctual_light()))
+            .collect();
+
+        let light_probes = self
+            .light_probes
+            .iter()
+            .map(|probe| {
+                let handle = world_renderer.light_probes.add(probe.light_probe());
+                if probe.irradiance.is_none() {
+                    world_renderer.light_probes.bake(handle);
+                }
+                handle
+            })
+            .collect();
+
+        if let Some(sun) = &self.sun {
+            world_renderer.sun_angular_diameter_degrees = sun.angular_diameter_degrees;
+            world_renderer.sun_color_multiplier = sun.color_multiplier.into();
+        }
+
+        if let Some(sky) = &self.sky {
+            world_renderer.sky_ambient = sky.ambient.into();
+
+            if let Some(ibl) = &sky.ibl {
+                world_renderer
+                    .ibl
+                    .load_image(canonical_path_from_vfs(ibl)?)
+                    .with_context(|| format!("IBL path: {:?}", ibl))?;
+            }
+        }
+
+        if let Some(time_of_day) = &self.time_of_day {
+            let renderer_time_of_day = &mut world_renderer.time_of_day;
+            renderer_time_of_day.enabled = true;
+            renderer_time_of_day.hour = time_of_day.hour;
+            renderer_time_of_day.hours_per_second = time_of_day.hours_per_second;
+            renderer_time_of_day.keys = time_of_day
+                .keys
+                .iter()
+                .map(SceneTimeOfDayKeyDesc::time_of_day_key)
+                .collect();
+        }
+
+        Ok(LoadedScene {
+            instances,
+            lights,
+            light_probes,
+        })
+    }
+}
@@ -0,0 +1,435 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc,
+};
+
+use kajiya_asset::mesh::{AssetRef, GpuImage, PackedTriMesh};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{buffer::Buffer, image::*},
+    Device,
+};
+use parking_lot::Mutex;
+
+use crate::world_renderer::{
+    upload_mesh_geometry, AddMeshOptions, BindlessImageHandle, MeshHandle, UploadedMeshGeometry,
+};
+
+/// Reports how much of the requested asset data is still being loaded in the background.
+///
+/// Cheap to clone; all clones observe the same counters. Totals grow as more assets are requested.
+/// Meshes count as items, but not towards the bytes, as their size is only known once loaded.
+#[derive(Clone, Default)]
+pub struct LoadProgress {
+    counters: Arc<LoadProgressCounters>,
+}
+
+#[derive(Default)]
+struct LoadProgressCounters {
+    items_total: AtomicU64,
+    items_loaded: AtomicU64,
+    bytes_total: AtomicU64,
+    bytes_loaded: AtomicU64,
+}
+
+impl LoadProgress {
+    pub fn items_total(&self) -> u64 {
+        self.counters.items_total.load(Ordering::Relaxed)
+    }
+
+    // The counters are read separately, so a load may be seen finishing before it's seen added.
+    pub fn items_remaining(&self) -> u64 {
+        self.items_total()
+            .saturating_sub(self.counters.items_loaded.load(Ordering::Relaxed))
+    }
+
+    pub fn bytes_total(&self) -> u64 {
+        self.counters.bytes_total.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_remaining(&self) -> u64 {
+        self.bytes_total()
+            .saturating_sub(self.counters.bytes_loaded.load(Ordering::Relaxed))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.items_remaining() == 0
+    }
+
+    /// Fraction of the bytes loaded so far, in the `0..=1` range.
+    pub fn fraction(&self) -> f32 {
+        let total = self.bytes_total();
+        if total == 0 {
+            1.0
+        } else {
+            (total - self.bytes_remaining()) as f32 / total as f32
+        }
+    }
+
+    fn add_request(&self, bytes: u64) {
+        // Bytes first, so that `items_remaining` doesn't report done with bytes outstanding.
+        self.counters
+            .bytes_total
+            .fetch_add(bytes, Ordering::Relaxed);
+        self.counters.items_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn finish_request(&self, bytes: u64) {
+        self.counters
+            .bytes_loaded
+            .fetch_add(bytes, Ordering::Relaxed);
+        self.counters.items_loaded.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Mips no larger than this are loaded up front, and always kept resident.
+const RESIDENT_TAIL_MIP_SIZE: u32 = 64;
+
+pub(crate) struct StreamedImage {
+    pub handle: BindlessImageHandle,
+    pub image: Image,
+    pub first_mip: u32,
+}
+
+struct ImageRequest {
+    handle: BindlessImageHandle,
+    asset: AssetRef<GpuImage::Flat>,
+    first_mip: u32,
+    // `None` for requests which don't count towards `LoadProgress`
+    progress_bytes: Option<u64>,
+}
+
+/// Uploads baked images on a pool of background threads.
+///
+/// Finished images are collected on the render thread via `drain_loaded`,
+/// which is where they get swapped in for their placeholders.
+pub(crate) struct ImageStreamer {
+    requests: mpsc::Sender<ImageRequest>,
+    loaded: mpsc::Receiver<StreamedImage>,
+    progress: LoadProgress,
+}
+
+impl ImageStreamer {
+    pub fn new(device: Arc<Device>, progress: LoadProgress) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<ImageRequest>();
+        let (loaded_tx, loaded_rx) = mpsc::channel();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+
+        let worker_count = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+        for worker_idx in 0..worker_count {
+            let device = device.clone();
+            let request_rx = request_rx.clone();
+            let loaded_tx = loaded_tx.clone();
+            let progress = progress.clone();
+
+            std::thread::Builder::new()
+                .name(format!("image streaming {}", worker_idx))
+                .spawn(move || loop {
+                    // Bind first so that the lock is released before the upload.
+                    let request = request_rx.lock().recv();
+                    let request = match request {
+                        Ok(request) => request,
+                        // The streamer is gone
+                        Err(_) => break,
+                    };
+
+                    match load_gpu_image_asset(&device, request.asset, request.first_mip) {
+                        Ok(image) => {
+                            let _ = loaded_tx.send(StreamedImage {
+                                handle: request.handle,
+                                image,
+                                first_mip: request.first_mip,
+                            });
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "Failed to load image {:8.8x}: {:#}",
+                                request.asset.identity(),
+                                err
+                            );
+                        }
+                    }
+
+                    if let Some(bytes) = request.progress_bytes {
+                        progress.finish_request(bytes);
+                    }
+                })
+                .expect("failed to spawn an image streaming thread");
+        }
+
+        Self {
+            requests: request_tx,
+            loaded: loaded_rx,
+            progress,
+        }
+    }
+
+    /// Request the initial load of a texture, reported in `progress`.
+    pub fn request(&self, texture: &mut StreamedTexture, first_mip: u32) -> anyhow::Result<()> {
+        let size_bytes = texture.size_bytes(first_mip);
+        self.progress.add_request(size_bytes);
+        self.send(texture, first_mip, Some(size_bytes))
+    }
+
+    /// Request a texture to be re-created with a different set of resident mips.
+    pub fn request_mips(
+        &self,
+        texture: &mut StreamedTexture,
+        first_mip: u32,
+    ) -> anyhow::Result<()> {
+        self.send(texture, first_mip, None)
+    }
+
+    fn send(
+        &self,
+        texture: &mut StreamedTexture,
+        first_mip: u32,
+        progress_bytes: Option<u64>,
+    ) -> anyhow::Result<()> {
+        self.requests
+            .send(ImageRequest {
+                handle: texture.handle,
+                asset: texture.asset,
+                first_mip,
+                progress_bytes,
+            })
+            .map_err(|_| anyhow::anyhow!("image streaming threads are not running"))?;
+
+        texture.pending_first_mip = Some(first_mip);
+        Ok(())
+    }
+
+    pub fn drain_loaded(&self) -> impl Iterator<Item = StreamedImage> + '_ {
+        self.loaded.try_iter()
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress.clone()
+    }
+}
+
+/// Produces the baked data of a mesh, e.g. by baking its source file, or mapping a cached one.
+pub type MeshLoadFn =
+    Box<dyn FnOnce() -> anyhow::Result<&'static PackedTriMesh::Flat> + Send + 'static>;
+
+pub(crate) struct StreamedMesh {
+    pub handle: MeshHandle,
+    pub mesh: &'static PackedTriMesh::Flat,
+    pub opts: AddMeshOptions,
+    pub geometry: UploadedMeshGeometry,
+}
+
+struct MeshRequest {
+    handle: MeshHandle,
+    load: MeshLoadFn,
+    opts: AddMeshOptions,
+}
+
+// Baking is mostly parallel already, so a couple of meshes at a time keep the cores busy.
+const MESH_STREAMING_THREADS: usize = 2;
+
+/// Loads meshes, and uploads their geometry and acceleration structures on background threads.
+///
+/// The rest of a mesh, its materials, textures, and lights, is set up on the render thread,
+/// after `drain_loaded` hands it over; until then, the mesh is empty.
+pub(crate) struct MeshStreamer {
+    requests: mpsc::Sender<MeshRequest>,
+    loaded: mpsc::Receiver<StreamedMesh>,
+    progress: LoadProgress,
+}
+
+impl MeshStreamer {
+    pub fn new(
+        device: Arc<Device>,
+        vertex_buffer: Arc<Buffer>,
+        vertex_buffer_written: Arc<Mutex<u64>>,
+        progress: LoadProgress,
+    ) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<MeshRequest>();
+        let (loaded_tx, loaded_rx) = mpsc::channel();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+
+        for worker_idx in 0..MESH_STREAMING_THREADS {
+            let device = device.clone();
+            let vertex_buffer = vertex_buffer.clone();
+            let vertex_buffer_written = vertex_buffer_written.clone();
+            let request_rx = request_rx.clone();
+            let loaded_tx = loaded_tx.clone();
+            let progress = progress.clone();
+
+            std::thread::Builder::new()
+                .name(format!("mesh streaming {}", worker_idx))
+                .spawn(move || loop {
+                    // Bind first so that the lock is released before the load.
+                    let request = request_rx.lock().recv();
+                    let MeshRequest { handle, load, opts } = match request {
+                        Ok(request) => request,
+                        // The streamer is gone
+                        Err(_) => break,
+                    };
+
+                    let loaded = load().and_then(|mesh| {
+                        let geometry = upload_mesh_geometry(
+                            &device,
+                            &vertex_buffer,
+                            &vertex_buffer_written,
+                            mesh,
+                        )?;
+                        Ok(StreamedMesh {
+                            handle,
+                            mesh,
+                            opts,
+                            geometry,
+                        })
+                    });
+
+                    match loaded {
+                        Ok(loaded) => {
+                            let _ = loaded_tx.send(loaded);
+                        }
+                        Err(err) => {
+                            log::error!("Failed to load mesh {:?}: {:#}", handle, err);
+                        }
+                    }
+
+                    progress.finish_request(0);
+                })
+                .expect("failed to spawn a mesh streaming thread");
+        }
+
+        Self {
+            requests: request_tx,
+            loaded: loaded_rx,
+            progress,
+        }
+    }
+
+    pub fn request(
+        &self,
+        handle: MeshHandle,
+        load: MeshLoadFn,
+        opts: AddMeshOptions,
+    ) -> anyhow::Result<()> {
+        self.progress.add_request(0);
+        self.requests
+            .send(MeshRequest { handle, load, opts })
+            .map_err(|_| anyhow::anyhow!("mesh streaming threads are not running"))
+    }
+
+    pub fn drain_loaded(&self) -> impl Iterator<Item = StreamedMesh> + '_ {
+        self.loaded.try_iter()
+    }
+}
+
+/// Mip residency of a bindless texture whose image is streamed in the background.
+pub(crate) struct StreamedTexture {
+    pub asset: AssetRef<GpuImage::Flat>,
+    pub handle: BindlessImageHandle,
+
+    // Size of each mip level, summed over all array layers
+    mip_sizes: Vec<u64>,
+
+    /// The smallest mips, starting at this one, are always resident.
+    pub tail_first_mip: u32,
+
+    /// `None` while a placeholder is bound in place of the texture.
+    pub resident_first_mip: Option<u32>,
+    pub pending_first_mip: Option<u32>,
+    pub image: Option<Image>,
+}
+
+impl StreamedTexture {
+    pub fn new(
+        asset: AssetRef<GpuImage::Flat>,
+        handle: BindlessImageHandle,
+    ) -> anyhow::Result<Self> {
+        let image = mmapped_gpu_image_asset(asset)?;
+        let mip_sizes: Vec<u64> = image.mips.iter().map(|mip| mip.len() as u64).collect();
+        anyhow::ensure!(!mip_sizes.is_empty(), "image has no mips");
+
+        let max_extent = image.extent[0].max(image.extent[1]);
+        let tail_first_mip = (0..mip_sizes.len() as u32)
+            .find(|mip| (max_extent >> mip) <= RESIDENT_TAIL_MIP_SIZE)
+            .unwrap_or(mip_sizes.len() as u32 - 1);
+
+        Ok(Self {
+            asset,
+            handle,
+            mip_sizes,
+            tail_first_mip,
+            resident_first_mip: None,
+            pending_first_mip: None,
+            image: None,
+        })
+    }
+
+    /// Memory taken by the mips starting at `first_mip`.
+    pub fn size_bytes(&self, first_mip: u32) -> u64 {
+        self.mip_sizes[first_mip as usize..].iter().sum()
+    }
+
+    /// The first mip needed for the texture to look sharp when seen from `distance`,
+    /// given the distance at which the full-resolution mip is required.
+    pub fn desired_first_mip(&self, distance: f32, full_res_distance: f32) -> u32 {
+        if distance <= full_res_distance || full_res_distance <= 0.0 {
+            0
+        } else {
+            ((distance / full_res_distance).log2().ceil() as u32).min(self.tail_first_mip)
+        }
+    }
+}
+
+fn mmapped_gpu_image_asset(
+    asset: AssetRef<GpuImage::Flat>,
+) -> anyhow::Result<&'static GpuImage::Flat> {
+    crate::mmap::mmapped_asset::<GpuImage::Flat, _>(&format!(
+        "/cache/{:8.8x}.image",
+        asset.identity()
+    ))
+}
+
+/// Creates an image containing the mips starting at `first_mip`.
+fn load_gpu_image_asset(
+    device: &Device,
+    asset: AssetRef<GpuImage::Flat>,
+    first_mip: u32,
+) -> anyhow::Result<Image> {
+    let _creator =
+        device.resource_creator_scope(|| format!("image asset {:8.8x}", asset.identity()));
+
+    let asset = mmapped_gpu_image_asset(asset)?;
+    let mips = &asset.mips.as_slice()[first_mip as usize..];
+    let extent = [
+        (asset.extent[0] >> first_mip).max(1),
+        (asset.extent[1] >> first_mip).max(1),
+    ];
+
+    let desc = if asset.is_cube && asset.array_layers > 6 {
+        ImageDesc::new_cube(asset.format, extent[0])
+            .image_type(ImageType::CubeArray)
+            .array_elements(asset.array_layers / 6)
+    } else if asset.is_cube {
+        ImageDesc::new_cube(asset.format, extent[0])
+    } else if asset.array_layers > 1 {
+        ImageDesc::new_2d(asset.format, extent)
+            .image_type(ImageType::Tex2dArray)
+            .array_elements(asset.array_layers)
+    } else {
+        ImageDesc::new_2d(asset.format, extent)
+    }
+    .usage(vk::ImageUsageFlags::SAMPLED)
+    .mip_levels(mips.len() as _);
+
+    let initial_data = mips
+        .iter()
+        .enumerate()
+        .map(|(mip_level, mip)| ImageSubResourceData {
+            data: mip.as_slice(),
+            row_pitch: ((desc.extent[0] as usize) >> mip_level).max(1) * 4,
+            slice_pitch: 0,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(device.create_image(desc, initial_data)?)
+}
@@ -111,11 +111,9 @@ pub fn create_bindless_descriptor_set(device: &device::Device) -> vk::Descriptor
         .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
         .max_sets(1);
 
-    let descriptor_pool = unsafe {
-        raw_device
-            .create_descriptor_pool(&descriptor_pool_info, None)
-            .unwrap()
-    };
+    let descriptor_pool = device
+        .create_descriptor_pool(&descriptor_pool_info, "bindless descriptor pool")
+        .unwrap();
 
     let variable_descriptor_count = device.max_bindless_descriptor_count() as _;
     let mut variable_descriptor_count_allocate_info =
@@ -2,7 +2,10 @@ use kajiya_backend::{ash::vk, vulkan, BackendError};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use std::ops::Range;
-use vulkan::buffer::{Buffer, BufferDesc};
+use vulkan::{
+    buffer::{Buffer, BufferDesc},
+    staging_belt::STAGING_BELT_CHUNK_SIZE,
+};
 
 pub trait BufferDataSource {
     fn as_bytes(&self) -> &[u8];
@@ -86,24 +89,32 @@ impl BufferBuilder {
     pub fn upload(
         self,
         device: &kajiya_backend::Device,
-        target: &mut Buffer,
+        target: &Buffer,
         target_offset: u64,
     ) -> Result<(), BackendError> {
-        assert!(
-            self.pending_uploads
-                .iter()
-                .map(|chunk| chunk.source.as_bytes().len())
-                .sum::<usize>()
-                + target_offset as usize
-                <= target.desc.size
-        );
+        let total_bytes = self
+            .pending_uploads
+            .iter()
+            .map(|chunk| chunk.source.as_bytes().len())
+            .sum::<usize>();
+        assert!(total_bytes + target_offset as usize <= target.desc.size);
+
+        if total_bytes == 0 {
+            return Ok(());
+        }
+
         let target = target.raw;
 
-        // TODO: share a common staging buffer, don't leak
-        const STAGING_BYTES: usize = 16 * 1024 * 1024;
-        let mut staging_buffer = device.create_buffer(
-            BufferDesc::new_cpu_to_gpu(STAGING_BYTES, vk::BufferUsageFlags::TRANSFER_SRC),
-            "BufferBuilder staging",
+        // Reused for every chunk, since each copy is waited for. Not from the staging belt,
+        // which recycles its slices after a frame, as uploads from the `MeshStreamer`
+        // threads can take longer than that.
+        const STAGING_BYTES: usize = STAGING_BELT_CHUNK_SIZE;
+        let mut staging = device.create_buffer(
+            BufferDesc::new_cpu_to_gpu(
+                total_bytes.min(STAGING_BYTES),
+                vk::BufferUsageFlags::TRANSFER_SRC,
+            ),
+            "buffer builder staging",
             None,
         )?;
 
@@ -127,30 +138,27 @@ impl BufferBuilder {
             })
             .collect();
 
-        for UploadChunk {
-            pending_idx,
-            src_range,
-        } in chunks
-        {
-            let pending = &self.pending_uploads[pending_idx];
-            staging_buffer.allocation.mapped_slice_mut().unwrap()
-                [0..(src_range.end - src_range.start)]
-                .copy_from_slice(&pending.source.as_bytes()[src_range.start..src_range.end]);
+        let result = chunks.into_iter().try_for_each(|chunk| {
+            let pending = &self.pending_uploads[chunk.pending_idx];
+            let src_range = chunk.src_range;
+            staging.allocation.mapped_slice_mut().unwrap()[..src_range.len()]
+                .copy_from_slice(&pending.source.as_bytes()[src_range.clone()]);
 
             device.with_setup_cb(|cb| unsafe {
                 device.raw.cmd_copy_buffer(
                     cb,
-                    staging_buffer.raw,
+                    staging.raw,
                     target,
                     &[vk::BufferCopy::builder()
-                        .src_offset(0u64)
+                        .src_offset(0)
                         .dst_offset(target_offset + pending.offset + src_range.start as u64)
-                        .size((src_range.end - src_range.start) as u64)
+                        .size(src_range.len() as u64)
                         .build()],
                 );
-            })?;
-        }
+            })
+        });
+        device.immediate_destroy_buffer(staging);
 
-        Ok(())
+        result
     }
 }
@@ -42,6 +42,7 @@ pub struct CameraLens {
     pub near_plane_distance: f32,
     pub aspect_ratio: f32,
     pub vertical_fov: f32,
+    pub projection: CameraProjection,
 }
 
 impl Default for CameraLens {
@@ -50,6 +51,105 @@ impl Default for CameraLens {
             near_plane_distance: 0.01, // 1mm
             aspect_ratio: 1.0,
             vertical_fov: 52.0,
+            projection: CameraProjection::Perspective,
+        }
+    }
+}
+
+/// All projections map view space (looking down -Z) to reverse-Z clip space,
+/// with a depth of 1 at the near plane.
+#[derive(Clone, Copy)]
+pub enum CameraProjection {
+    /// Symmetric, infinite frustum defined by `vertical_fov` and `aspect_ratio`.
+    Perspective,
+
+    /// Infinite frustum with its sides at the given tangents of the view direction,
+    /// e.g. `left = -1.0, right = 1.0` for a 90 degree horizontal field of view.
+    /// `vertical_fov` and `aspect_ratio` are ignored.
+    OffCenterPerspective {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+
+    /// Parallel projection of a box `vertical_extent` units high, with the width
+    /// following from `aspect_ratio`. Depth spans from the near to the far plane.
+    Orthographic {
+        vertical_extent: f32,
+        far_plane_distance: f32,
+    },
+
+    /// User-supplied `view_to_clip` matrix, which must follow the same conventions.
+    Custom { view_to_clip: Mat4 },
+}
+
+/// Settings of a physical camera, from which exposure, depth of field, and the motion blur
+/// shutter angle are derived when `enabled`, so that they can be authored in photographic terms.
+///
+/// Scene lighting is calibrated such that the "sunny 16" settings (f/16, 1/100 s, ISO 100)
+/// match an EV shift of zero; light intensities can be authored relative to the sun.
+#[derive(Clone, Copy)]
+pub struct PhysicalCamera {
+    pub enabled: bool,
+    pub f_stop: f32,
+    pub shutter_time_seconds: f32,
+    pub iso: f32,
+
+    /// In meters. Drives the field of view via `PhysicalCamera::vertical_fov`.
+    pub focal_length: f32,
+
+    /// Width and height of the sensor, in meters. Defaults to full-frame 35mm.
+    pub sensor_size: Vec2,
+}
+
+impl Default for PhysicalCamera {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ..Self::SUNNY_16
+        }
+    }
+}
+
+impl PhysicalCamera {
+    const SUNNY_16: Self = Self {
+        enabled: true,
+        f_stop: 16.0,
+        shutter_time_seconds: 0.01,
+        iso: 100.0,
+        focal_length: 0.05,
+        sensor_size: glam::const_vec2!([0.036, 0.024]),
+    };
+
+    /// Exposure value of the settings at ISO 100. Higher values let in less light.
+    pub fn ev100(&self) -> f32 {
+        (self.f_stop * self.f_stop / self.shutter_time_seconds * 100.0 / self.iso).log2()
+    }
+
+    /// Exposure compensation relative to the "sunny 16" settings, in EV.
+    pub fn relative_ev(&self) -> f32 {
+        Self::SUNNY_16.ev100() - self.ev100()
+    }
+
+    /// Height of the part of the sensor which an image of `aspect_ratio` covers.
+    /// The image fills the sensor, cropping it on the sides or the top and bottom.
+    pub fn sensor_height_for_aspect_ratio(&self, aspect_ratio: f32) -> f32 {
+        self.sensor_size.y.min(self.sensor_size.x / aspect_ratio)
+    }
+
+    /// Vertical field of view in degrees, as used in `CameraLens`.
+    pub fn vertical_fov(&self, aspect_ratio: f32) -> f32 {
+        let sensor_height = self.sensor_height_for_aspect_ratio(aspect_ratio);
+        (2.0 * (0.5 * sensor_height / self.focal_length).atan()).to_degrees()
+    }
+
+    /// Exposure time as a fraction of `frame_time_seconds`, in degrees of a rotary shutter.
+    pub fn shutter_angle_degrees(&self, frame_time_seconds: f32) -> f32 {
+        if frame_time_seconds > 0.0 {
+            (360.0 * self.shutter_time_seconds / frame_time_seconds).min(360.0)
+        } else {
+            360.0
         }
     }
 }
@@ -86,36 +186,90 @@ impl CameraBodyMatrices {
 
 impl CameraLens {
     fn calc_matrices(&self) -> CameraLensMatrices {
-        let fov = self.vertical_fov.to_radians();
+        match self.projection {
+            CameraProjection::Perspective => {
+                let tan_half_fov = (0.5 * self.vertical_fov.to_radians()).tan();
+                let tan_half_hfov = tan_half_fov * self.aspect_ratio;
+
+                self.off_center_perspective(
+                    -tan_half_hfov,
+                    tan_half_hfov,
+                    -tan_half_fov,
+                    tan_half_fov,
+                )
+            }
+            CameraProjection::OffCenterPerspective {
+                left,
+                right,
+                bottom,
+                top,
+            } => self.off_center_perspective(left, right, bottom, top),
+            CameraProjection::Orthographic {
+                vertical_extent,
+                far_plane_distance,
+            } => self.orthographic(vertical_extent, far_plane_distance),
+            CameraProjection::Custom { view_to_clip } => CameraLensMatrices {
+                view_to_clip,
+                clip_to_view: view_to_clip.inverse(),
+            },
+        }
+    }
+
+    fn off_center_perspective(
+        &self,
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    ) -> CameraLensMatrices {
         let znear = self.near_plane_distance;
 
-        let h = (0.5 * fov).cos() / (0.5 * fov).sin();
-        let w = h / self.aspect_ratio;
+        let w = 2.0 / (right - left);
+        let h = 2.0 / (top - bottom);
+        let x_shift = (right + left) / (right - left);
+        let y_shift = (top + bottom) / (top - bottom);
 
-        /*let mut m = Mat4::ZERO;
-        m.m11 = w;
-        m.m22 = h;
-        m.m34 = znear;
-        m.m43 = -1.0;
-        m*/
         let view_to_clip = Mat4::from_cols(
             Vec4::new(w, 0.0, 0.0, 0.0),
             Vec4::new(0.0, h, 0.0, 0.0),
-            Vec4::new(0.0, 0.0, 0.0, -1.0),
+            Vec4::new(x_shift, y_shift, 0.0, -1.0),
             Vec4::new(0.0, 0.0, znear, 0.0),
         );
 
-        /*let mut m = Mat4::ZERO;
-        m.m11 = 1.0 / w;
-        m.m22 = 1.0 / h;
-        m.m34 = -1.0;
-        m.m43 = 1.0 / znear;
-        m*/
         let clip_to_view = Mat4::from_cols(
             Vec4::new(1.0 / w, 0.0, 0.0, 0.0),
             Vec4::new(0.0, 1.0 / h, 0.0, 0.0),
             Vec4::new(0.0, 0.0, 0.0, 1.0 / znear),
-            Vec4::new(0.0, 0.0, -1.0, 0.0),
+            Vec4::new(x_shift / w, y_shift / h, -1.0, 0.0),
+        );
+
+        CameraLensMatrices {
+            view_to_clip,
+            clip_to_view,
+        }
+    }
+
+    fn orthographic(&self, vertical_extent: f32, far_plane_distance: f32) -> CameraLensMatrices {
+        let half_height = 0.5 * vertical_extent;
+        let half_width = half_height * self.aspect_ratio;
+
+        let znear = self.near_plane_distance;
+        let zfar = far_plane_distance;
+        let depth_range = zfar - znear;
+
+        // Reverse-Z, but with a finite far plane mapped to zero depth.
+        let view_to_clip = Mat4::from_cols(
+            Vec4::new(1.0 / half_width, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 1.0 / half_height, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 1.0 / depth_range, 0.0),
+            Vec4::new(0.0, 0.0, zfar / depth_range, 1.0),
+        );
+
+        let clip_to_view = Mat4::from_cols(
+            Vec4::new(half_width, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, half_height, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, depth_range, 0.0),
+            Vec4::new(0.0, 0.0, -zfar, 1.0),
         );
 
         CameraLensMatrices {
@@ -0,0 +1,215 @@
+//! Immediate-mode debug lines, for visualizing things like probe grids, light bounds,
+//! and culling frusta.
+//!
+//! Primitives can be submitted from any thread, and are drawn by the next
+//! `WorldRenderer::prepare_render_graph`, on top of the final image, without depth testing.
+//! They're then cleared, so anything meant to stay on screen must be submitted every frame.
+//!
+//! Colors are linear RGBA, written to the output as-is, and blended with premultiplied alpha.
+
+use glam::{Mat4, Vec3, Vec4};
+use kajiya_backend::{
+    ash::vk,
+    dynamic_constants::MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES,
+    vk_sync::AccessType,
+    vulkan::{image::*, shader::*},
+    Device,
+};
+use kajiya_rg::{self as rg, IntoRenderPassPipelineBinding, RenderGraph, RenderPassBinding};
+use parking_lot::Mutex;
+
+use crate::math::{Aabb, BoundingSphere};
+
+/// Must match `DebugVertex` in `debug_draw_vs.hlsl`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugVertex {
+    position: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Anything submitted over this in a single frame is dropped.
+const MAX_VERTICES: usize = 1 << 18;
+
+/// The vertices are pulled from a dynamic constants storage buffer, which bounds
+/// the size of a single draw.
+const MAX_VERTICES_PER_DRAW: usize =
+    MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES / std::mem::size_of::<DebugVertex>();
+
+const SPHERE_SEGMENTS: usize = 32;
+
+lazy_static::lazy_static! {
+    static ref LINE_VERTICES: Mutex<Vec<DebugVertex>> = Default::default();
+}
+
+fn push_lines(lines: impl IntoIterator<Item = (Vec3, Vec3)>, color: Vec4) {
+    let mut vertices = LINE_VERTICES.lock();
+
+    for (a, b) in lines {
+        if vertices.len() + 2 > MAX_VERTICES {
+            return;
+        }
+
+        vertices.extend([a, b].map(|p| DebugVertex {
+            position: p.extend(1.0).into(),
+            color: color.into(),
+        }));
+    }
+}
+
+pub fn line(a: Vec3, b: Vec3, color: Vec4) {
+    push_lines([(a, b)], color);
+}
+
+/// Draws the edges of an axis-aligned box.
+pub fn aabb(aabb: &Aabb, color: Vec4) {
+    let corners = aabb.corners();
+    push_lines(box_edges().map(|(a, b)| (corners[a], corners[b])), color);
+}
+
+/// Draws three great circles, one around each axis.
+pub fn sphere(sphere: &BoundingSphere, color: Vec4) {
+    let BoundingSphere { center, radius } = *sphere;
+
+    let circle_point = |axis: usize, segment: usize| {
+        let angle = segment as f32 * std::f32::consts::TAU / SPHERE_SEGMENTS as f32;
+        let (sin, cos) = angle.sin_cos();
+        let mut p = [0.0; 3];
+        p[(axis + 1) % 3] = cos * radius;
+        p[(axis + 2) % 3] = sin * radius;
+        center + Vec3::from(p)
+    };
+
+    push_lines(
+        (0..3).flat_map(|axis| {
+            (0..SPHERE_SEGMENTS).map(move |i| (circle_point(axis, i), circle_point(axis, i + 1)))
+        }),
+        color,
+    );
+}
+
+/// Draws the frustum of a camera, with `clip_to_world` being the inverse of its
+/// combined view and projection matrices.
+///
+/// Depth is reversed, and the far plane usually at infinity, so the frustum is cut off
+/// `far_distance` past the near plane instead.
+pub fn frustum(clip_to_world: Mat4, far_distance: f32, color: Vec4) {
+    let unproject = |x: f32, y: f32, z: f32| clip_to_world.project_point3(Vec3::new(x, y, z));
+
+    let near_plane = unproject(0.0, 0.0, 1.0);
+    // Any depth between the planes; a quarter is finite with an infinite far plane.
+    let view_dir = (unproject(0.0, 0.0, 0.25) - near_plane).normalize();
+
+    let corner = |i: usize| {
+        let x = if i & 1 != 0 { 1.0 } else { -1.0 };
+        let y = if i & 2 != 0 { 1.0 } else { -1.0 };
+        let near = unproject(x, y, 1.0);
+
+        if i & 4 == 0 {
+            near
+        } else {
+            // Extend the ray through the corner, which works for orthographic
+            // projections as well as perspective ones.
+            let ray = unproject(x, y, 0.25) - near;
+            near + ray * (far_distance / ray.dot(view_dir))
+        }
+    };
+
+    push_lines(box_edges().map(|(a, b)| (corner(a), corner(b))), color);
+}
+
+/// The edges of a box whose corners are indexed as in `Aabb::corners`.
+fn box_edges() -> impl Iterator<Item = (usize, usize)> {
+    (0..8usize).flat_map(|i| {
+        [1usize, 2, 4]
+            .into_iter()
+            .filter(move |bit| i & bit == 0)
+            .map(move |bit| (i, i | bit))
+    })
+}
+
+/// Draws everything submitted since the last call over `output`, and clears it.
+pub(crate) fn raster_debug_draw(
+    rg: &mut RenderGraph,
+    device: &Device,
+    output: &mut rg::Handle<Image>,
+) {
+    let vertices = std::mem::take(&mut *LINE_VERTICES.lock());
+    if vertices.is_empty() {
+        return;
+    }
+
+    if vertices.len() + 2 > MAX_VERTICES {
+        log::warn!(
+            "Too many debug lines in one frame; only the first {} were drawn",
+            MAX_VERTICES / 2
+        );
+    }
+
+    let render_pass = create_render_pass(
+        device,
+        RenderPassDesc {
+            color_attachments: &[RenderPassAttachmentDesc::new(output.desc().format)],
+            depth_attachment: None,
+            subpasses: &[],
+        },
+    );
+
+    let mut pass = rg.add_pass("debug draw");
+
+    let pipeline = pass.register_raster_pipeline(
+        &[
+            PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                .hlsl_source("/shaders/debug_draw_vs.hlsl")
+                .build()
+                .unwrap(),
+            PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                .hlsl_source("/shaders/debug_draw_ps.hlsl")
+                .build()
+                .unwrap(),
+        ],
+        RasterPipelineDesc::builder()
+            .render_pass(render_pass.clone())
+            .depth_write(false)
+            .topology(vk::PrimitiveTopology::LINE_LIST)
+            .alpha_blend(true),
+    );
+
+    let output_ref = pass.raster(output, AccessType::ColorAttachmentWrite);
+
+    pass.render(move |api| {
+        let [width, height, _] = output_ref.desc().extent;
+
+        api.begin_render_pass(
+            &*render_pass,
+            [width, height],
+            &[(output_ref, &ImageViewDesc::default())],
+            None,
+        )?;
+
+        api.set_default_view_and_scissor([width, height]);
+
+        for chunk in vertices.chunks(MAX_VERTICES_PER_DRAW) {
+            let vertices_offset = api
+                .dynamic_constants()
+                .push_from_iter(chunk.iter().copied());
+
+            api.bind_raster_pipeline(pipeline.into_binding().descriptor_set(
+                0,
+                &[RenderPassBinding::DynamicConstantsStorageBuffer(
+                    vertices_offset,
+                )],
+            ))?;
+
+            unsafe {
+                api.device()
+                    .raw
+                    .cmd_draw(api.cb.raw, chunk.len() as u32, 1, 0, 0);
+            }
+        }
+
+        api.end_render_pass();
+
+        Ok(())
+    });
+}
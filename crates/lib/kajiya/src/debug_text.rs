@@ -0,0 +1,346 @@
+//! Text drawn over the final image without ImGui, so that minimal applications
+//! can still show stats. See `debug_text!`.
+//!
+//! Like `debug_draw`, text can be submitted from any thread, is drawn by the next
+//! `WorldRenderer::prepare_render_graph`, and then cleared.
+
+use std::sync::Arc;
+
+use kajiya_backend::{
+    ash::vk,
+    bytes::as_byte_slice,
+    dynamic_constants::MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES,
+    vk_sync::AccessType,
+    vulkan::{image::*, shader::*},
+    BackendError, Device,
+};
+use kajiya_rg::{
+    self as rg, BindRgRef, IntoRenderPassPipelineBinding, RenderGraph, RenderPassBinding,
+};
+use parking_lot::Mutex;
+
+/// Queues text with its top-left corner at `(x, y)` pixels from the top-left of the output.
+/// The rest of the arguments are as in `format!`.
+///
+/// ```ignore
+/// kajiya::debug_text!(8.0, 8.0, "{:.2} ms", frame_time_ms);
+/// ```
+#[macro_export]
+macro_rules! debug_text {
+    ($x:expr, $y:expr, $($arg:tt)+) => {
+        $crate::debug_text::text($x, $y, &format!($($arg)+))
+    };
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Pixels per font texel.
+const GLYPH_SCALE: f32 = 2.0;
+
+/// Vertical distance between lines of text, in pixels.
+pub const LINE_HEIGHT: f32 = (GLYPH_HEIGHT + 3) as f32 * GLYPH_SCALE;
+
+const GLYPH_ADVANCE: f32 = (GLYPH_WIDTH + 1) as f32 * GLYPH_SCALE;
+
+const FIRST_CHAR: u8 = b' ';
+
+/// Anything submitted over this in a single frame is dropped.
+const MAX_GLYPHS: usize = 1 << 16;
+
+const MAX_GLYPHS_PER_DRAW: usize =
+    MAX_DYNAMIC_CONSTANTS_STORAGE_BUFFER_BYTES / std::mem::size_of::<GlyphInstance>();
+
+/// Printable ASCII, one byte per row, with the leftmost pixel in bit 4.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_HEIGHT as usize]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+    [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+    [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+/// Must match `GlyphInstance` in `debug_text_vs.hlsl`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GlyphInstance {
+    position: [f32; 2],
+    glyph: u32,
+    pad: u32,
+}
+
+/// Must match the push constants in `debug_text_vs.hlsl`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugTextConstants {
+    inv_output_size: [f32; 2],
+    glyph_scale: f32,
+    pad: u32,
+}
+
+lazy_static::lazy_static! {
+    static ref GLYPHS: Mutex<Vec<GlyphInstance>> = Default::default();
+}
+
+/// Queues `text` with its top-left corner at `(x, y)` pixels from the top-left of the output.
+/// Breaks lines at `\n`, and shows characters outside of printable ASCII as `?`.
+pub fn text(x: f32, y: f32, text: &str) {
+    let mut glyphs = GLYPHS.lock();
+    let mut position = [x, y];
+
+    for c in text.chars() {
+        if c == '\n' {
+            position = [x, position[1] + LINE_HEIGHT];
+            continue;
+        }
+
+        if glyphs.len() >= MAX_GLYPHS {
+            return;
+        }
+
+        let c = if c.is_ascii() && !c.is_ascii_control() {
+            c as u8
+        } else {
+            b'?'
+        };
+
+        if c != b' ' {
+            glyphs.push(GlyphInstance {
+                position,
+                glyph: (c - FIRST_CHAR) as u32,
+                pad: 0,
+            });
+        }
+
+        position[0] += GLYPH_ADVANCE;
+    }
+}
+
+/// Owns the font atlas.
+pub struct DebugTextRenderer {
+    font_atlas: Arc<Image>,
+}
+
+impl DebugTextRenderer {
+    pub fn new(device: &Device) -> Result<Self, BackendError> {
+        // All the glyphs in a row.
+        let atlas_width = GLYPH_WIDTH * FONT.len() as u32;
+        let mut texels = vec![0u8; (atlas_width * GLYPH_HEIGHT) as usize];
+
+        for (glyph_idx, glyph) in FONT.iter().enumerate() {
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH as usize {
+                    if row & (1 << (GLYPH_WIDTH as usize - 1 - x)) != 0 {
+                        texels[y * atlas_width as usize + glyph_idx * GLYPH_WIDTH as usize + x] =
+                            255;
+                    }
+                }
+            }
+        }
+
+        let font_atlas = device.create_image(
+            ImageDesc::new_2d(vk::Format::R8_UNORM, [atlas_width, GLYPH_HEIGHT])
+                .usage(vk::ImageUsageFlags::SAMPLED),
+            vec![ImageSubResourceData {
+                data: &texels,
+                row_pitch: atlas_width as usize,
+                slice_pitch: 0,
+            }],
+        )?;
+
+        Ok(Self {
+            font_atlas: Arc::new(font_atlas),
+        })
+    }
+
+    /// Draws everything submitted since the last call over `output`, and clears it.
+    pub(crate) fn render(
+        &self,
+        rg: &mut RenderGraph,
+        device: &Device,
+        output: &mut rg::Handle<Image>,
+    ) {
+        let glyphs = std::mem::take(&mut *GLYPHS.lock());
+        if glyphs.is_empty() {
+            return;
+        }
+
+        let render_pass = create_render_pass(
+            device,
+            RenderPassDesc {
+                color_attachments: &[RenderPassAttachmentDesc::new(output.desc().format)],
+                depth_attachment: None,
+                subpasses: &[],
+            },
+        );
+
+        let font_atlas = rg.import(
+            self.font_atlas.clone(),
+            AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        );
+
+        let mut pass = rg.add_pass("debug text");
+
+        let pipeline = pass.register_raster_pipeline(
+            &[
+                PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                    .hlsl_source("/shaders/debug_text_vs.hlsl")
+                    .build()
+                    .unwrap(),
+                PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                    .hlsl_source("/shaders/debug_text_ps.hlsl")
+                    .build()
+                    .unwrap(),
+            ],
+            RasterPipelineDesc::builder()
+                .render_pass(render_pass.clone())
+                .depth_write(false)
+                .alpha_blend(true)
+                .push_constants_bytes(std::mem::size_of::<DebugTextConstants>()),
+        );
+
+        let font_atlas_ref = pass.read(
+            &font_atlas,
+            AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer,
+        );
+        let output_ref = pass.raster(output, AccessType::ColorAttachmentWrite);
+
+        pass.render(move |api| {
+            let [width, height, _] = output_ref.desc().extent;
+
+            api.begin_render_pass(
+                &*render_pass,
+                [width, height],
+                &[(output_ref, &ImageViewDesc::default())],
+                None,
+            )?;
+
+            api.set_default_view_and_scissor([width, height]);
+
+            let constants = DebugTextConstants {
+                inv_output_size: [1.0 / width as f32, 1.0 / height as f32],
+                glyph_scale: GLYPH_SCALE,
+                pad: 0,
+            };
+
+            for chunk in glyphs.chunks(MAX_GLYPHS_PER_DRAW) {
+                let glyphs_offset = api
+                    .dynamic_constants()
+                    .push_from_iter(chunk.iter().copied());
+
+                let bound_pipeline =
+                    api.bind_raster_pipeline(pipeline.into_binding().descriptor_set(
+                        0,
+                        &[
+                            RenderPassBinding::DynamicConstantsStorageBuffer(glyphs_offset),
+                            font_atlas_ref.bind(),
+                        ],
+                    ))?;
+
+                bound_pipeline.push_constants(
+                    api.cb.raw,
+                    vk::ShaderStageFlags::ALL_GRAPHICS,
+                    0,
+                    as_byte_slice(&constants),
+                );
+
+                bound_pipeline.draw(6, chunk.len() as u32, 0, 0);
+            }
+
+            api.end_render_pass();
+
+            Ok(())
+        });
+    }
+}
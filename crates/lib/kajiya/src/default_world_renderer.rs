@@ -1,10 +1,6 @@
 use std::sync::Arc;
 
-use crate::{image_cache::UploadGpuImage, world_renderer::WorldRenderer};
-use kajiya_asset::{
-    image::LoadImage,
-    mesh::{TexGamma, TexParams},
-};
+use crate::{renderers::blue_noise::BlueNoise, world_renderer::WorldRenderer};
 use kajiya_backend::vulkan::RenderBackend;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -23,24 +19,7 @@ impl WorldRenderer {
         world_renderer.add_image_lut(crate::lut_renderers::BrdfFgLutComputer, 0);
 
         {
-            let image =
-                LoadImage::from_path("/images/bluenoise/256_256/LDR_RGBA_0.png")?.into_lazy();
-            let blue_noise_img = smol::block_on(
-                UploadGpuImage {
-                    image,
-                    params: TexParams {
-                        gamma: TexGamma::Linear,
-                        use_mips: false,
-                        compression: kajiya_asset::mesh::TexCompressionMode::None,
-                        channel_swizzle: None,
-                    },
-                    device: backend.device.clone(),
-                }
-                .into_lazy()
-                .eval(lazy_cache),
-            )
-            .unwrap();
-
+            let blue_noise_img = BlueNoise::load_texture(&backend.device, lazy_cache)?;
             let handle = world_renderer.add_image(blue_noise_img);
 
             // BINDLESS_LUT_BLUE_NOISE_256_LDR_RGBA_0
@@ -50,9 +29,21 @@ impl WorldRenderer {
         // BINDLESS_LUT_BEZOLD_BRUCKE
         world_renderer.add_image_lut(crate::lut_renderers::BezoldBruckeLutComputer, 2);
 
+        // BINDLESS_LUT_LTC_GGX_INV_MATRIX
+        world_renderer.add_image_lut(crate::lut_renderers::LtcGgxLutComputer, 3);
+
+        // BINDLESS_LUT_ATMOSPHERE_TRANSMITTANCE
+        world_renderer.add_image_lut(crate::lut_renderers::AtmosphereTransmittanceLutComputer, 4);
+
+        // BINDLESS_LUT_ATMOSPHERE_MULTIPLE_SCATTERING
+        world_renderer.add_image_lut(
+            crate::lut_renderers::AtmosphereMultipleScatteringLutComputer,
+            5,
+        );
+
         // Build an empty TLAS to create the resources. We'll update it at runtime.
         if backend.device.ray_tracing_enabled() {
-            world_renderer.build_ray_tracing_top_level_acceleration();
+            world_renderer.build_ray_tracing_top_level_acceleration()?;
         }
 
         Ok(world_renderer)
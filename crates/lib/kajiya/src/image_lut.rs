@@ -1,17 +1,98 @@
 use std::sync::Arc;
 
-use kajiya_backend::{vk_sync, vulkan::image::*};
-use kajiya_rg as rg;
+use kajiya_backend::{
+    ash::vk, pipeline_cache::shader_generation, vk_sync, vulkan::image::*, Device,
+};
+use kajiya_rg::{self as rg, BindRgRef, IntoRenderPassPipelineBinding};
 
 pub trait ComputeImageLut: Send {
     fn create(&mut self, device: &kajiya_backend::Device) -> Image;
     fn compute(&mut self, rg: &mut rg::RenderGraph, img: &mut rg::Handle<Image>);
+
+    /// Whether the LUT is derived from `WorldRenderer::atmosphere`, and needs to be recomputed when it changes.
+    fn depends_on_atmosphere(&self) -> bool {
+        false
+    }
+}
+
+/// A LUT computed by a single compute shader, which writes the image at binding 0
+/// with one thread per texel. Saves defining a `ComputeImageLut` for each such LUT.
+pub struct ComputeShaderImageLut {
+    pub name: String,
+    pub shader: String,
+    pub desc: ImageDesc,
+    pub depends_on_atmosphere: bool,
+}
+
+impl ComputeShaderImageLut {
+    pub fn new(name: impl Into<String>, shader: impl Into<String>, desc: ImageDesc) -> Self {
+        Self {
+            name: name.into(),
+            shader: shader.into(),
+            desc,
+            depends_on_atmosphere: false,
+        }
+    }
+
+    pub fn depends_on_atmosphere(mut self, depends_on_atmosphere: bool) -> Self {
+        self.depends_on_atmosphere = depends_on_atmosphere;
+        self
+    }
+}
+
+impl ComputeImageLut for ComputeShaderImageLut {
+    fn create(&mut self, device: &Device) -> Image {
+        create_lut_image(device, self.desc)
+    }
+
+    fn compute(&mut self, rg: &mut rg::RenderGraph, img: &mut rg::Handle<Image>) {
+        compute_lut_with_shader(rg, img, &self.name, &self.shader);
+    }
+
+    fn depends_on_atmosphere(&self) -> bool {
+        self.depends_on_atmosphere
+    }
+}
+
+/// Creates the backing image of a LUT, usable as a storage image and sampled.
+pub fn create_lut_image(device: &Device, desc: ImageDesc) -> Image {
+    device
+        .create_image(
+            desc.usage(desc.usage | vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED),
+            vec![],
+        )
+        .expect("image")
+}
+
+/// Adds a pass running `shader` over every texel of `img`.
+pub fn compute_lut_with_shader(
+    rg: &mut rg::RenderGraph,
+    img: &mut rg::Handle<Image>,
+    pass_name: &str,
+    shader: &str,
+) {
+    let mut pass = rg.add_pass(pass_name);
+
+    let pipeline = pass.register_compute_pipeline(shader);
+    let img_ref = pass.write(img, vk_sync::AccessType::ComputeShaderWrite);
+
+    pass.render(move |api| {
+        let pipeline = api
+            .bind_compute_pipeline(pipeline.into_binding().descriptor_set(0, &[img_ref.bind()]))?;
+
+        pipeline.dispatch(img_ref.desc().extent);
+
+        Ok(())
+    });
 }
 
+/// A LUT baked once into a persistent image, and re-baked when invalidated,
+/// or when shaders are reloaded.
 pub struct ImageLut {
     image: Arc<Image>,
     computer: Box<dyn ComputeImageLut>,
-    computed: bool,
+    /// The `shader_generation` the LUT was last baked with, if any.
+    computed_with_shader_generation: Option<u64>,
 }
 
 impl ImageLut {
@@ -19,12 +100,14 @@ impl ImageLut {
         Self {
             image: Arc::new(computer.create(device)),
             computer,
-            computed: false,
+            computed_with_shader_generation: None,
         }
     }
 
     pub fn compute_if_needed(&mut self, rg: &mut rg::RenderGraph) {
-        if self.computed {
+        // Any shader reload counts, since we don't know which shaders the computer uses.
+        let shader_generation = shader_generation();
+        if self.computed_with_shader_generation == Some(shader_generation) {
             return;
         }
 
@@ -32,12 +115,44 @@ impl ImageLut {
 
         self.computer.compute(rg, &mut rg_image);
 
+        // Transition to the shader read state right away rather than at export time,
+        // so that passes later in this graph can sample the LUT too, via the bindless
+        // set or `import`.
+        rg.add_pass("lut ready").read(
+            &rg_image,
+            vk_sync::AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        );
+
         rg.export(
             rg_image,
             vk_sync::AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
         );
 
-        self.computed = true;
+        self.computed_with_shader_generation = Some(shader_generation);
+    }
+
+    /// Makes the next `compute_if_needed` recompute the LUT.
+    pub fn invalidate(&mut self) {
+        self.computed_with_shader_generation = None;
+    }
+
+    pub fn depends_on_atmosphere(&self) -> bool {
+        self.computer.depends_on_atmosphere()
+    }
+
+    /// Imports the LUT into `rg` for passes which bind it directly rather than
+    /// through the bindless set. Must be called after `compute_if_needed` for the same graph.
+    pub fn import(&self, rg: &mut rg::RenderGraph) -> rg::ReadOnlyHandle<Image> {
+        assert!(
+            self.computed_with_shader_generation.is_some(),
+            "LUT imported before being computed"
+        );
+
+        rg.import(
+            self.image.clone(),
+            vk_sync::AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        )
+        .into()
     }
 
     /// Note: contains garbage until `compute_if_needed` is called.
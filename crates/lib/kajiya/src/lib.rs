@@ -1,4 +1,7 @@
+pub mod asset_streaming;
 pub mod camera;
+pub mod debug_draw;
+pub mod debug_text;
 pub mod default_world_renderer;
 pub mod frame_desc;
 pub mod image_cache;
@@ -12,6 +15,7 @@ pub mod ui_renderer;
 pub mod world_render_passes;
 pub mod world_renderer;
 pub mod world_renderer_mmap_adapter;
+pub mod world_view;
 
 mod bindless_descriptor_set;
 mod buffer_builder;
@@ -1,23 +1,22 @@
-use kajiya_backend::{ash::vk, vk_sync::AccessType, ImageDesc};
-use kajiya_rg::{BindRgRef, IntoRenderPassPipelineBinding};
+use kajiya_backend::{ash::vk, ImageDesc};
 
-#[allow(unused_imports)]
-use kajiya_backend::{ash::vk::ImageUsageFlags, vulkan::image::*};
-
-use crate::image_lut::ComputeImageLut;
+use crate::image_lut::{compute_lut_with_shader, create_lut_image, ComputeImageLut};
 
 pub struct BrdfFgLutComputer;
 pub struct BezoldBruckeLutComputer;
+/// Inverse matrices of linearly transformed cosines fitted to the GGX BRDF, for area lights.
+pub struct LtcGgxLutComputer;
+/// Transmittance to the top of the atmosphere.
+pub struct AtmosphereTransmittanceLutComputer;
+/// Scattering orders past the first in the atmosphere.
+pub struct AtmosphereMultipleScatteringLutComputer;
 
 impl ComputeImageLut for BrdfFgLutComputer {
     fn create(&mut self, device: &kajiya_backend::Device) -> kajiya_backend::Image {
-        device
-            .create_image(
-                ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [64, 64])
-                    .usage(ImageUsageFlags::STORAGE | ImageUsageFlags::SAMPLED),
-                vec![],
-            )
-            .expect("image")
+        create_lut_image(
+            device,
+            ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [64, 64]),
+        )
     }
 
     fn compute(
@@ -25,32 +24,55 @@ impl ComputeImageLut for BrdfFgLutComputer {
         rg: &mut kajiya_rg::RenderGraph,
         img: &mut kajiya_rg::Handle<kajiya_backend::Image>,
     ) {
-        let mut pass = rg.add_pass("brdf_fg lut");
+        compute_lut_with_shader(rg, img, "brdf_fg lut", "/shaders/lut/brdf_fg.hlsl");
+    }
+}
 
-        let pipeline = pass.register_compute_pipeline("/shaders/lut/brdf_fg.hlsl");
-        let img_ref = pass.write(img, AccessType::ComputeShaderWrite);
+impl ComputeImageLut for BezoldBruckeLutComputer {
+    fn create(&mut self, device: &kajiya_backend::Device) -> kajiya_backend::Image {
+        create_lut_image(
+            device,
+            ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, [64, 1]),
+        )
+    }
 
-        pass.render(move |api| {
-            let pipeline = api.bind_compute_pipeline(
-                pipeline.into_binding().descriptor_set(0, &[img_ref.bind()]),
-            )?;
+    fn compute(
+        &mut self,
+        rg: &mut kajiya_rg::RenderGraph,
+        img: &mut kajiya_rg::Handle<kajiya_backend::Image>,
+    ) {
+        compute_lut_with_shader(
+            rg,
+            img,
+            "bezold_brucke lut",
+            "/shaders/lut/bezold_brucke.hlsl",
+        );
+    }
+}
 
-            pipeline.dispatch(img_ref.desc().extent);
+impl ComputeImageLut for LtcGgxLutComputer {
+    fn create(&mut self, device: &kajiya_backend::Device) -> kajiya_backend::Image {
+        create_lut_image(
+            device,
+            ImageDesc::new_2d(vk::Format::R32G32B32A32_SFLOAT, [64, 64]),
+        )
+    }
 
-            Ok(())
-        });
+    fn compute(
+        &mut self,
+        rg: &mut kajiya_rg::RenderGraph,
+        img: &mut kajiya_rg::Handle<kajiya_backend::Image>,
+    ) {
+        compute_lut_with_shader(rg, img, "ltc_ggx lut", "/shaders/lut/ltc_fit.hlsl");
     }
 }
 
-impl ComputeImageLut for BezoldBruckeLutComputer {
+impl ComputeImageLut for AtmosphereTransmittanceLutComputer {
     fn create(&mut self, device: &kajiya_backend::Device) -> kajiya_backend::Image {
-        device
-            .create_image(
-                ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, [64, 1])
-                    .usage(ImageUsageFlags::STORAGE | ImageUsageFlags::SAMPLED),
-                vec![],
-            )
-            .expect("image")
+        create_lut_image(
+            device,
+            ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [256, 64]),
+        )
     }
 
     fn compute(
@@ -58,19 +80,41 @@ impl ComputeImageLut for BezoldBruckeLutComputer {
         rg: &mut kajiya_rg::RenderGraph,
         img: &mut kajiya_rg::Handle<kajiya_backend::Image>,
     ) {
-        let mut pass = rg.add_pass("bezold_brucke lut");
+        compute_lut_with_shader(
+            rg,
+            img,
+            "atmosphere transmittance lut",
+            "/shaders/lut/atmosphere_transmittance.hlsl",
+        );
+    }
 
-        let pipeline = pass.register_compute_pipeline("/shaders/lut/bezold_brucke.hlsl");
-        let img_ref = pass.write(img, AccessType::ComputeShaderWrite);
+    fn depends_on_atmosphere(&self) -> bool {
+        true
+    }
+}
 
-        pass.render(move |api| {
-            let pipeline = api.bind_compute_pipeline(
-                pipeline.into_binding().descriptor_set(0, &[img_ref.bind()]),
-            )?;
+impl ComputeImageLut for AtmosphereMultipleScatteringLutComputer {
+    fn create(&mut self, device: &kajiya_backend::Device) -> kajiya_backend::Image {
+        create_lut_image(
+            device,
+            ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [32, 32]),
+        )
+    }
 
-            pipeline.dispatch(img_ref.desc().extent);
+    fn compute(
+        &mut self,
+        rg: &mut kajiya_rg::RenderGraph,
+        img: &mut kajiya_rg::Handle<kajiya_backend::Image>,
+    ) {
+        compute_lut_with_shader(
+            rg,
+            img,
+            "atmosphere multiple scattering lut",
+            "/shaders/lut/atmosphere_multiple_scattering.hlsl",
+        );
+    }
 
-            Ok(())
-        });
+    fn depends_on_atmosphere(&self) -> bool {
+        true
     }
 }
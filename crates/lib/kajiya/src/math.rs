@@ -1,4 +1,4 @@
-pub use glam::{Mat2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+pub use glam::{Affine3A, Mat2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
 
 #[allow(dead_code)]
 pub fn build_orthonormal_basis(n: Vec3) -> Mat3 {
@@ -19,3 +19,250 @@ pub fn build_orthonormal_basis(n: Vec3) -> Mat3 {
 
     Mat3::from_cols(b1, b2, n)
 }
+
+/// The largest scale `transform` applies along any of its axes; bounding spheres
+/// grow by this much under it.
+pub fn max_axis_scale(transform: &Affine3A) -> f32 {
+    transform
+        .x_axis
+        .length()
+        .max(transform.y_axis.length())
+        .max(transform.z_axis.length())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Centered on the bounding box of the points; not the tightest, but cheap.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vec3>,
+        I::IntoIter: Clone,
+    {
+        let points = points.into_iter();
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points.map(|p| p.distance(center)).fold(0.0f32, f32::max);
+
+        Self { center, radius }
+    }
+
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        Self {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * max_axis_scale(transform),
+        }
+    }
+
+    /// The smallest sphere enclosing both.
+    pub fn union(&self, other: &Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+
+        if distance + other.radius <= self.radius {
+            *self
+        } else if distance + self.radius <= other.radius {
+            *other
+        } else {
+            let radius = (distance + self.radius + other.radius) * 0.5;
+            let center = self.center + offset * ((radius - self.radius) / distance);
+            Self { center, radius }
+        }
+    }
+
+    /// Center in xyz, radius in w, as stored in GPU buffers.
+    pub fn to_vec4(self) -> Vec4 {
+        self.center.extend(self.radius)
+    }
+}
+
+/// Axis-aligned bounding box. Empty when `min` is greater than `max` on any axis.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self {
+        min: glam::const_vec3!([f32::MAX; 3]),
+        max: glam::const_vec3!([f32::MIN; 3]),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, p| Self {
+            min: aabb.min.min(p),
+            max: aabb.max.max(p),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Corner `i` is at `max` on the axes whose bits are set in `i`; x is bit 0, and z bit 2.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let mut corners = [Vec3::ZERO; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = Vec3::new(
+                if i & 1 != 0 { self.max.x } else { self.min.x },
+                if i & 2 != 0 { self.max.y } else { self.min.y },
+                if i & 4 != 0 { self.max.z } else { self.min.z },
+            );
+        }
+        corners
+    }
+
+    /// The box around the transformed corners of this one.
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        Self::from_points(self.corners().map(|p| transform.transform_point3(p)))
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::new(self.center(), self.half_extents().length())
+    }
+}
+
+/// Planes of a view frustum, normals pointing inwards, as `xyz` and the distance term in `w`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near, and far.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the rows of `world_to_clip`, so that any projection works.
+    ///
+    /// Depth is reversed, with `z/w` at 1 on the near plane, and 0 on the far one.
+    /// With the far plane at infinity, its plane is degenerate, and passes everything.
+    ///
+    /// `cull_instances.hlsl` tests the same planes on the GPU.
+    pub fn from_world_to_clip(world_to_clip: Mat4) -> Self {
+        let rows = world_to_clip.transpose();
+        let [x, y, z, w] = [rows.x_axis, rows.y_axis, rows.z_axis, rows.w_axis];
+
+        let normalize = |plane: Vec4| {
+            let length = plane.truncate().length();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        };
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w - z, z].map(normalize),
+        }
+    }
+
+    fn signed_distance(plane: Vec4, point: Vec3) -> f32 {
+        plane.truncate().dot(point) + plane.w
+    }
+
+    /// Conservative; spheres outside of the frustum, but near its corners, may pass.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|&plane| Self::signed_distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    /// Conservative in the same way as `intersects_sphere`.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|&plane| {
+            // The corner furthest along the plane normal
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            Self::signed_distance(plane, corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEAR: f32 = 0.1;
+
+    /// A 90-degree frustum with an infinite far plane and reverse-Z, as `CameraLens` builds them,
+    /// from an eye at `(0, 0, 5)` looking down -Z.
+    fn frustum() -> Frustum {
+        let view_to_clip = Mat4::from_cols(
+            Vec4::new(1.0, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 1.0, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, -1.0),
+            Vec4::new(0.0, 0.0, NEAR, 0.0),
+        );
+        let world_to_view = Mat4::from_translation(Vec3::new(0.0, 0.0, -5.0));
+
+        Frustum::from_world_to_clip(view_to_clip * world_to_view)
+    }
+
+    #[test]
+    fn infinite_far_plane_is_degenerate() {
+        let far = frustum().planes[5];
+        assert_eq!(far.truncate(), Vec3::ZERO);
+        assert!(far.w > 0.0);
+    }
+
+    #[test]
+    fn sphere() {
+        let frustum = frustum();
+        let sphere = |x, z, radius| {
+            frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(x, 0.0, z), radius))
+        };
+
+        assert!(sphere(0.0, 0.0, 1.0));
+
+        // Behind the eye, and between it and the near plane
+        assert!(!sphere(0.0, 7.0, 1.0));
+        assert!(!sphere(0.0, 5.0 - 0.5 * NEAR, 0.01));
+        assert!(sphere(0.0, 5.0 - 0.5 * NEAR, 0.1));
+
+        // Any distance in front passes the far plane.
+        assert!(sphere(0.0, -1.0e6, 1.0));
+
+        // Left of the frustum, and straddling its left plane
+        assert!(!sphere(-8.0, 0.0, 1.0));
+        assert!(sphere(-5.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn aabb() {
+        let frustum = frustum();
+        let aabb = |min: [f32; 3], max: [f32; 3]| {
+            frustum.intersects_aabb(&Aabb::new(Vec3::from(min), Vec3::from(max)))
+        };
+
+        assert!(aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]));
+
+        // Behind the eye, and between it and the near plane
+        assert!(!aabb([-1.0, -1.0, 6.0], [1.0, 1.0, 7.0]));
+        assert!(!aabb([-0.01, -0.01, 4.96], [0.01, 0.01, 4.97]));
+
+        // Any distance in front passes the far plane.
+        assert!(aabb([-1.0, -1.0, -1.0e6], [1.0, 1.0, -1.0e6 + 1.0]));
+
+        // Above the frustum, and straddling its top plane
+        assert!(!aabb([-1.0, 7.0, -1.0], [1.0, 9.0, 1.0]));
+        assert!(aabb([-1.0, 5.5, -1.0], [1.0, 6.5, 1.0]));
+    }
+}
@@ -1,10 +1,13 @@
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::{collections::HashMap, fs::File, path::PathBuf, time::SystemTime};
 
 use anyhow::Context;
 use parking_lot::Mutex;
 
 lazy_static::lazy_static! {
-    static ref ASSET_MMAPS: Mutex<HashMap<PathBuf, memmap2::Mmap>> = Mutex::new(HashMap::new());
+    // Keyed by modification time too, so that re-baked assets get mapped anew.
+    // Previous mappings are kept alive, since references to them are `'static`.
+    static ref ASSET_MMAPS: Mutex<HashMap<(PathBuf, Option<SystemTime>), memmap2::Mmap>> =
+        Mutex::new(HashMap::new());
 }
 
 pub fn mmapped_asset<T, P: Into<std::path::PathBuf>>(path: P) -> anyhow::Result<&'static T> {
@@ -12,8 +15,12 @@ pub fn mmapped_asset<T, P: Into<std::path::PathBuf>>(path: P) -> anyhow::Result<
     let path = kajiya_backend::canonical_path_from_vfs(&path)
         .with_context(|| format!("Can't mmap asset: file doesn't exist: {:?}", path))?;
 
+    let modified = std::fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
     let mut mmaps = ASSET_MMAPS.lock();
-    let data: &[u8] = mmaps.entry(path.clone()).or_insert_with(|| {
+    let data: &[u8] = mmaps.entry((path.clone(), modified)).or_insert_with(|| {
         let file =
             File::open(&path).unwrap_or_else(|e| panic!("Could not mmap {:?}: {:?}", path, e));
         unsafe { memmap2::MmapOptions::new().map(&file).unwrap() }
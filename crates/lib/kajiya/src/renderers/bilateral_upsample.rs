@@ -0,0 +1,78 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::GbufferDepth;
+
+/// Edge-stopping thresholds of `bilateral_upsample`, shared by all the effects using it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BilateralUpsampleParams {
+    /// How quickly low-res samples lose weight with their relative depth difference
+    /// to the full-res pixel. Higher values keep edges sharper, but alias more.
+    pub depth_sharpness: f32,
+
+    /// Exponent of the cosine between the normals of the low-res samples and the full-res pixel.
+    pub normal_power: f32,
+}
+
+impl Default for BilateralUpsampleParams {
+    fn default() -> Self {
+        Self {
+            depth_sharpness: 64.0,
+            normal_power: 8.0,
+        }
+    }
+}
+
+/// Which full-res pixel each texel of a low-res input was computed for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LowResSampling {
+    /// `lowres_px * divisor + divisor / 2`, as with `downsample_gi_inputs`.
+    Centered { divisor: u32 },
+
+    /// `lowres_px * 2 + HALFRES_SUBSAMPLE_OFFSET`, as with `GbufferDepth::half_depth`.
+    HalfResSubsample,
+}
+
+/// Upsamples `input` to the resolution of `gbuffer_depth`, blending the nearest 2x2 texels
+/// by their bilinear weights, scaled down across depth and normal discontinuities.
+///
+/// Sky pixels get `sky_value`.
+pub fn bilateral_upsample(
+    rg: &mut rg::RenderGraph,
+    pass_name: &str,
+    input: &rg::Handle<Image>,
+    gbuffer_depth: &GbufferDepth,
+    sampling: LowResSampling,
+    sky_value: [f32; 4],
+    params: &BilateralUpsampleParams,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(
+        input
+            .desc()
+            .extent(gbuffer_depth.gbuffer.desc().extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+
+    let (divisor, use_halfres_subsample_offset) = match sampling {
+        LowResSampling::Centered { divisor } => (divisor.max(1), false),
+        LowResSampling::HalfResSubsample => (2, true),
+    };
+
+    SimpleRenderPass::new_compute(rg.add_pass(pass_name), "/shaders/bilateral_upsample.hlsl")
+        .read(input)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(&gbuffer_depth.geometric_normal)
+        .write(&mut output)
+        .constants((
+            input.desc().extent_inv_extent_2d(),
+            output.desc().extent_inv_extent_2d(),
+            sky_value,
+            divisor,
+            use_halfres_subsample_offset as u32,
+            params.depth_sharpness,
+            params.normal_power,
+        ))
+        .dispatch(output.desc().extent);
+
+    output
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use kajiya_asset::{
+    image::LoadImage,
+    mesh::{TexCompressionMode, TexGamma, TexParams},
+};
+use kajiya_backend::{
+    ash::vk,
+    vk_sync,
+    vulkan::{buffer::*, image::*},
+    BackendError, Device,
+};
+use kajiya_rg::{self as rg, BindToSimpleRenderPass, SimpleRenderPass};
+use turbosloth::*;
+
+use crate::image_cache::UploadGpuImage;
+
+use blue_noise_sampler::spp64::*;
+
+/// Noise shared by all the stochastic passes, uploaded once.
+///
+/// The blue noise texture is at the bindless index `BINDLESS_LUT_BLUE_NOISE_256_LDR_RGBA_0`.
+/// It's animated over time by adding `FrameConstants::blue_noise_rotation` modulo one,
+/// which keeps each frame blue in space, and each pixel well distributed over time
+/// (see `blue_noise_for_pixel_animated` in `blue_noise.hlsl`).
+///
+/// The ranking, scrambling, and Sobol tables of `blue_noise_sampler` back
+/// `DEFINE_BLUE_NOISE_SAMPLER_BINDINGS` in shaders which need many dimensions per pixel.
+pub struct BlueNoise {
+    ranking_tile_buf: Arc<Buffer>,
+    scrambling_tile_buf: Arc<Buffer>,
+    sobol_buf: Arc<Buffer>,
+}
+
+/// The sampler tables, imported into a render graph.
+pub struct BlueNoiseSamplerTables {
+    pub ranking_tile: rg::Handle<Buffer>,
+    pub scrambling_tile: rg::Handle<Buffer>,
+    pub sobol: rg::Handle<Buffer>,
+}
+
+impl<'rg, RgPipelineHandle> BindToSimpleRenderPass<'rg, RgPipelineHandle>
+    for BlueNoiseSamplerTables
+{
+    fn bind(
+        &self,
+        pass: SimpleRenderPass<'rg, RgPipelineHandle>,
+    ) -> SimpleRenderPass<'rg, RgPipelineHandle> {
+        pass.read(&self.ranking_tile)
+            .read(&self.scrambling_tile)
+            .read(&self.sobol)
+    }
+}
+
+fn as_byte_slice_unchecked<T: Copy>(v: &[T]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * std::mem::size_of::<T>())
+    }
+}
+
+fn make_lut_buffer<T: Copy>(device: &Device, v: &[T]) -> Result<Arc<Buffer>, BackendError> {
+    Ok(Arc::new(device.create_buffer(
+        BufferDesc::new_gpu_only(
+            v.len() * std::mem::size_of::<T>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        ),
+        "lut buffer",
+        Some(as_byte_slice_unchecked(v)),
+    )?))
+}
+
+impl BlueNoise {
+    pub fn new(device: &Device) -> Result<Self, BackendError> {
+        Ok(Self {
+            ranking_tile_buf: make_lut_buffer(device, RANKING_TILE)?,
+            scrambling_tile_buf: make_lut_buffer(device, SCRAMBLING_TILE)?,
+            sobol_buf: make_lut_buffer(device, SOBOL)?,
+        })
+    }
+
+    /// Loads the blue noise texture, to be registered as a bindless image.
+    pub fn load_texture(
+        device: &Arc<Device>,
+        lazy_cache: &Arc<LazyCache>,
+    ) -> anyhow::Result<Arc<Image>> {
+        let image = LoadImage::from_path("/images/bluenoise/256_256/LDR_RGBA_0.png")?.into_lazy();
+
+        smol::block_on(
+            UploadGpuImage {
+                image,
+                params: TexParams {
+                    gamma: TexGamma::Linear,
+                    use_mips: false,
+                    compression: TexCompressionMode::None,
+                    channel_swizzle: None,
+                },
+                device: device.clone(),
+            }
+            .into_lazy()
+            .eval(lazy_cache),
+        )
+    }
+
+    pub fn sampler_tables(&self, rg: &mut rg::RenderGraph) -> BlueNoiseSamplerTables {
+        let import = |rg: &mut rg::RenderGraph, buf: &Arc<Buffer>| {
+            rg.import(
+                buf.clone(),
+                vk_sync::AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+            )
+        };
+
+        BlueNoiseSamplerTables {
+            ranking_tile: import(rg, &self.ranking_tile_buf),
+            scrambling_tile: import(rg, &self.scrambling_tile_buf),
+            sobol: import(rg, &self.sobol_buf),
+        }
+    }
+
+    /// Offset added to the blue noise in frame `frame_index`, modulo one. Steps by the
+    /// golden ratio, so that the values seen by any pixel are evenly spread over time.
+    pub fn rotation(frame_index: u32) -> f32 {
+        // 2^32 / golden ratio, with the fraction computed in fixed point to stay exact
+        // for any frame index.
+        const GOLDEN_RATIO_FRACT_U32: u32 = 2654435769;
+        (frame_index.wrapping_mul(GOLDEN_RATIO_FRACT_U32) >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::buffer::Buffer};
+use kajiya_rg as rg;
+
+// Limit of `vkCmdUpdateBuffer`
+const MAX_UPDATE_BYTES: usize = 65536;
+
+/// Overwrites parts of a buffer which the render graph doesn't track, such as the vertex
+/// and mesh buffers. Meant to be called at the start of the frame, before anything reads them.
+///
+/// `writes` are byte offsets into the buffer, along with the new data. Both must be 4-byte aligned.
+/// The buffer needs `TRANSFER_DST` usage.
+pub(crate) fn write_buffer(
+    rg: &mut rg::RenderGraph,
+    pass_name: &str,
+    buffer: Arc<Buffer>,
+    writes: Vec<(u64, Vec<u8>)>,
+) {
+    if writes.is_empty() {
+        return;
+    }
+
+    let pass = rg.add_pass(pass_name);
+    pass.render(move |api| {
+        let raw_device = &api.device().raw;
+        let cb = api.cb.raw;
+
+        // Synchronize manually: after the previous frame's reads, and before this one's.
+        unsafe {
+            raw_device.cmd_pipeline_barrier(
+                cb,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[],
+            );
+
+            for (offset, data) in &writes {
+                for (chunk_idx, chunk) in data.chunks(MAX_UPDATE_BYTES).enumerate() {
+                    raw_device.cmd_update_buffer(
+                        cb,
+                        buffer.raw,
+                        offset + (chunk_idx * MAX_UPDATE_BYTES) as u64,
+                        chunk,
+                    );
+                }
+            }
+
+            raw_device.cmd_pipeline_barrier(
+                cb,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::INDEX_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+        }
+
+        Ok(())
+    });
+}
+
+/// Fills a graph buffer with zeros. The buffer needs `TRANSFER_DST` usage.
+pub(crate) fn clear_buffer(
+    rg: &mut rg::RenderGraph,
+    pass_name: &str,
+    buffer: &mut rg::Handle<Buffer>,
+) {
+    let mut pass = rg.add_pass(pass_name);
+    let buffer_ref = pass.write(buffer, AccessType::TransferWrite);
+
+    pass.render(move |api| {
+        let raw_device = &api.device().raw;
+        let buffer = api.resources.buffer(buffer_ref);
+
+        unsafe {
+            raw_device.cmd_fill_buffer(api.cb.raw, buffer.raw, 0, vk::WHOLE_SIZE, 0);
+        }
+
+        Ok(())
+    });
+}
@@ -0,0 +1,62 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{hiz::HizPyramid, GbufferDepth};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ContactShadowParams {
+    pub enabled: bool,
+
+    /// World-space distance marched towards the sun from each pixel.
+    pub length: f32,
+
+    /// How far behind the depth buffer surfaces are assumed to extend, in world units.
+    /// Rays passing further behind them are not shadowed.
+    pub thickness: f32,
+
+    pub step_count: u32,
+}
+
+impl Default for ContactShadowParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            length: 0.25,
+            thickness: 0.05,
+            step_count: 16,
+        }
+    }
+}
+
+/// Darkens the sun `shadow_mask` where short screen-space rays towards the sun hit the depth
+/// buffer, recovering small-scale shadows lost by the ray-traced mask and its denoising.
+///
+/// Pixels whose rays can't pass behind anything, according to `hiz`, skip the march.
+pub fn contact_shadows(
+    rg: &mut rg::RenderGraph,
+    gbuffer_depth: &GbufferDepth,
+    hiz: &HizPyramid,
+    shadow_mask: &rg::Handle<Image>,
+    params: &ContactShadowParams,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(shadow_mask.desc().format(vk::Format::R8_UNORM));
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass("contact shadows"),
+        "/shaders/contact_shadows.hlsl",
+    )
+    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+    .read(&hiz.tex)
+    .read(shadow_mask)
+    .write(&mut output)
+    .constants((
+        output.desc().extent_inv_extent_2d(),
+        hiz.mip_count(),
+        params.step_count.max(1),
+        params.length.max(0.0),
+        params.thickness.max(0.0),
+    ))
+    .dispatch(output.desc().extent);
+
+    output
+}
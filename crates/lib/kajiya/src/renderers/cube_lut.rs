@@ -0,0 +1,151 @@
+use anyhow::Context;
+use std::path::Path;
+
+/// A 3D color lookup table in the Adobe/Resolve `.cube` format.
+///
+/// Entries are stored with red changing fastest, then green, then blue,
+/// which matches the layout of a 3D texture indexed by `(r, g, b)`.
+pub struct CubeLut {
+    pub title: Option<String>,
+    pub size: u32,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    pub data: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading color LUT from {:?}", path))?;
+        Self::parse(&text).with_context(|| format!("Parsing color LUT {:?}", path))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0f32; 3];
+        let mut domain_max = [1.0f32; 3];
+        let mut data = Vec::new();
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_floats = |values: &str| -> anyhow::Result<[f32; 3]> {
+                let values = values
+                    .split_whitespace()
+                    .map(|v| v.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("line {}", line_idx + 1))?;
+
+                match values[..] {
+                    [a, b, c] => Ok([a, b, c]),
+                    _ => Err(anyhow::anyhow!(
+                        "line {}: expected three values, got {}",
+                        line_idx + 1,
+                        values.len()
+                    )),
+                }
+            };
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            match keyword {
+                "TITLE" => title = Some(rest.trim_matches('"').to_owned()),
+                "LUT_3D_SIZE" => {
+                    size = Some(
+                        rest.parse::<u32>()
+                            .with_context(|| format!("line {}", line_idx + 1))?,
+                    )
+                }
+                "LUT_1D_SIZE" => anyhow::bail!("1D LUTs are not supported"),
+                "DOMAIN_MIN" => domain_min = parse_floats(rest)?,
+                "DOMAIN_MAX" => domain_max = parse_floats(rest)?,
+                // Other keywords (e.g. `LUT_3D_INPUT_RANGE`) are optional; skip them.
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => data.push(parse_floats(line)?),
+            }
+        }
+
+        let size = size.context("Missing LUT_3D_SIZE")?;
+        anyhow::ensure!(size >= 2, "LUT_3D_SIZE must be at least 2, got {}", size);
+
+        let expected_len = (size * size * size) as usize;
+        anyhow::ensure!(
+            data.len() == expected_len,
+            "Expected {} entries for a LUT of size {}, got {}",
+            expected_len,
+            size,
+            data.len()
+        );
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An identity LUT of size 2, red changing fastest.
+    const IDENTITY_2: &str = "0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    #[test]
+    fn parse_identity() {
+        let text = format!(
+            "# Created by hand\n\nTITLE \"Identity\"\n  LUT_3D_SIZE 2\n\n# Entries\n{}",
+            IDENTITY_2
+        );
+        let lut = CubeLut::parse(&text).unwrap();
+
+        assert_eq!(lut.title.as_deref(), Some("Identity"));
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.domain_min, [0.0; 3]);
+        assert_eq!(lut.domain_max, [1.0; 3]);
+        assert_eq!(lut.data.len(), 8);
+        assert_eq!(lut.data[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.data[6], [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn parse_domain() {
+        let text = format!(
+            "LUT_3D_SIZE 2\nDOMAIN_MIN -0.5 0 0\nDOMAIN_MAX 2 2 4.5\n{}",
+            IDENTITY_2
+        );
+        let lut = CubeLut::parse(&text).unwrap();
+
+        assert_eq!(lut.domain_min, [-0.5, 0.0, 0.0]);
+        assert_eq!(lut.domain_max, [2.0, 2.0, 4.5]);
+    }
+
+    #[test]
+    fn parse_wrong_entry_count() {
+        let text = format!("LUT_3D_SIZE 2\n{}0 0 0\n", IDENTITY_2);
+        assert!(CubeLut::parse(&text).is_err());
+
+        let text = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n";
+        assert!(CubeLut::parse(text).is_err());
+    }
+
+    #[test]
+    fn parse_missing_size() {
+        assert!(CubeLut::parse(IDENTITY_2).is_err());
+    }
+
+    #[test]
+    fn parse_1d_lut() {
+        let text = "LUT_1D_SIZE 2\n0 0 0\n1 1 1\n";
+        assert!(CubeLut::parse(text).is_err());
+    }
+}
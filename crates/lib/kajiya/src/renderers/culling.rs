@@ -0,0 +1,146 @@
+//! GPU-driven draws of the opaque geometry in the G-buffer.
+//!
+//! Rather than looping over the instances on the CPU, each frame goes through:
+//!
+//! 1. `cull_instances`: one compute thread per instance picks its LOD,
+//!    tests its bounding sphere against the view frustum, and optionally against the
+//!    `HizPyramid` of the previous frame, and appends a `VkDrawIndexedIndirectCommand` for the
+//!    survivors to `CulledDraws::draw_args`, bumping `CulledDraws::draw_counts`.
+//! 2. `raster_depth_prepass` and `raster_meshes` issue one `vkCmdDrawIndexedIndirectCount`
+//!    per pipeline, with the draw count read on the GPU. The instance index travels in
+//!    `firstInstance`, which `raster_simple_vs.hlsl` uses to look up the transforms and mesh.
+//! 3. `HizRenderer::build` reduces the G-buffer depth into the pyramid which
+//!    the next frame's occlusion test reads.
+//!
+//! The CPU never learns how many instances survived, so nothing waits on the GPU.
+//! Devices without `VK_KHR_draw_indirect_count` fall back to per-instance draws from the CPU,
+//! as do the forward-shaded views and debug passes.
+
+use kajiya_backend::{ash::vk, vulkan::buffer::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{
+    buffer_writes::clear_buffer,
+    hiz::HizPyramid,
+    raster_meshes::{MeshLodSelection, RasterMeshesData, UploadedTriMesh},
+};
+use crate::{math::BoundingSphere, world_renderer::MeshInstance};
+
+/// Size of a `VkDrawIndexedIndirectCommand`
+pub const DRAW_ARGS_STRIDE: u32 = 5 * std::mem::size_of::<u32>() as u32;
+
+// Must match `CullInstance` in `cull_instances.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuCullInstance {
+    bounding_sphere: [f32; 4],
+    first_index: u32,
+    index_count: u32,
+    double_sided: u32,
+    pad: u32,
+}
+
+impl GpuCullInstance {
+    fn new(
+        mesh: &UploadedTriMesh,
+        instance: &MeshInstance,
+        lod_selection: &MeshLodSelection,
+    ) -> Self {
+        let (index_buffer_offset, index_count) =
+            mesh.select_lod(lod_selection.max_mesh_space_error(mesh, &instance.transform));
+
+        Self {
+            bounding_sphere: motion_bounding_sphere(mesh, instance).to_vec4().to_array(),
+            first_index: (index_buffer_offset / std::mem::size_of::<u32>() as u64) as u32,
+            index_count,
+            double_sided: mesh.double_sided as u32,
+            pad: 0,
+        }
+    }
+}
+
+/// World-space sphere around the instance at both its current and previous transform.
+///
+/// Occlusion is tested against the previous frame's depth, where a moving instance was drawn
+/// at its previous transform; including that keeps instances from occluding themselves.
+fn motion_bounding_sphere(mesh: &UploadedTriMesh, instance: &MeshInstance) -> BoundingSphere {
+    let current = mesh.bounding_sphere.transformed(&instance.transform);
+    let previous = mesh.bounding_sphere.transformed(&instance.prev_transform);
+
+    current.union(&previous)
+}
+
+/// Compacted indirect draw arguments for the G-buffer pass.
+pub struct CulledDraws {
+    /// Two ranges of `max_draw_count` draws; double-sided meshes first, then single-sided ones.
+    pub draw_args: rg::Handle<Buffer>,
+
+    /// Number of surviving draws in each range of `draw_args`
+    pub draw_counts: rg::Handle<Buffer>,
+
+    pub max_draw_count: u32,
+}
+
+/// Frustum and occlusion culling of the instances drawn into the G-buffer,
+/// producing their indirect draw arguments.
+///
+/// With `use_occlusion`, instances are also tested against `hiz`, which must still hold
+/// the previous frame's G-buffer depth, so geometry which becomes disoccluded can show up
+/// a frame late.
+pub fn cull_instances(
+    rg: &mut rg::RenderGraph,
+    mesh_data: &RasterMeshesData<'_>,
+    hiz: &HizPyramid,
+    use_occlusion: bool,
+) -> CulledDraws {
+    let instances: Vec<GpuCullInstance> = mesh_data
+        .instances
+        .iter()
+        .map(|inst| {
+            GpuCullInstance::new(
+                &mesh_data.meshes[inst.mesh.0],
+                inst,
+                &mesh_data.lod_selection,
+            )
+        })
+        .collect();
+
+    let instance_count = instances.len() as u32;
+    let max_draw_count = instance_count.max(1);
+
+    let mut draw_args = rg.create(BufferDesc::new_gpu_only(
+        2 * (max_draw_count * DRAW_ARGS_STRIDE) as usize,
+        vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER,
+    ));
+
+    let mut draw_counts = rg.create(BufferDesc::new_gpu_only(
+        2 * std::mem::size_of::<u32>(),
+        vk::BufferUsageFlags::STORAGE_BUFFER
+            | vk::BufferUsageFlags::INDIRECT_BUFFER
+            | vk::BufferUsageFlags::TRANSFER_DST,
+    ));
+    clear_buffer(rg, "clear draw counts", &mut draw_counts);
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass("cull instances"),
+        "/shaders/cull_instances.hlsl",
+    )
+    .dynamic_storage_buffer_vec(instances)
+    .read(&hiz.tex)
+    .write(&mut draw_args)
+    .write(&mut draw_counts)
+    .constants((
+        instance_count,
+        max_draw_count,
+        hiz.depth_extent,
+        hiz.mip_count(),
+        use_occlusion as u32,
+    ))
+    .dispatch([instance_count, 1, 1]);
+
+    CulledDraws {
+        draw_args,
+        draw_counts,
+        max_draw_count,
+    }
+}
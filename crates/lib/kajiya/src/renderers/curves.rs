@@ -0,0 +1,140 @@
+use glam::Vec3;
+use kajiya_asset::mesh::PackedVertex;
+
+/// Sides of the tubes which stand in for the curves in ray tracing. Even, so that
+/// the vertex shader can find each point's center and radius from two opposite vertices.
+pub(crate) const CURVE_TUBE_SIDES: usize = 6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CurvePoint {
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+/// A polyline, such as a strand of hair, a cable, or a sampled spline.
+/// Curves with fewer than two points are skipped.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Curve {
+    pub points: Vec<CurvePoint>,
+}
+
+/// Shared by all the curves of a mesh; see `WorldRenderer::add_curves`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CurveMaterial {
+    pub base_color: [f32; 3],
+    pub roughness: f32,
+    pub metalness: f32,
+
+    /// Of the highlights, oriented along the curves, as on hair.
+    pub anisotropy: f32,
+
+    pub emissive: [f32; 3],
+}
+
+impl Default for CurveMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: [0.5; 3],
+            roughness: 0.5,
+            metalness: 0.0,
+            anisotropy: 0.0,
+            emissive: [0.0; 3],
+        }
+    }
+}
+
+/// The vertex streams of a curve mesh.
+///
+/// Each point of each curve gets a ring of `CURVE_TUBE_SIDES` vertices. The tube triangles
+/// between the rings are what rays hit. Rasterization draws ribbons instead, whose indices
+/// are `point * 3 + side`, for the left, middle, and right of the ribbon; the vertex shader
+/// turns them to face the eye, and finds the point's center and radius from its ring.
+pub(crate) struct CurveGeometry {
+    pub verts: Vec<PackedVertex>,
+    pub uvs: Vec<[f32; 2]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub material_ids: Vec<u32>,
+    pub tube_indices: Vec<u32>,
+    pub ribbon_indices: Vec<u32>,
+}
+
+impl CurveGeometry {
+    pub(crate) fn tessellate(curves: &[Curve]) -> Self {
+        let mut res = Self {
+            verts: Vec::new(),
+            uvs: Vec::new(),
+            tangents: Vec::new(),
+            material_ids: Vec::new(),
+            tube_indices: Vec::new(),
+            ribbon_indices: Vec::new(),
+        };
+
+        for curve in curves {
+            let points = &curve.points;
+            if points.len() < 2 {
+                continue;
+            }
+
+            let first_point = (res.verts.len() / CURVE_TUBE_SIDES) as u32;
+
+            let mut lengths = vec![0.0f32; points.len()];
+            for i in 1..points.len() {
+                lengths[i] = lengths[i - 1] + points[i].position.distance(points[i - 1].position);
+            }
+            let total_length = lengths[points.len() - 1].max(1e-20);
+
+            let mut normal: Option<Vec3> = None;
+
+            for (i, point) in points.iter().enumerate() {
+                let tangent = (points[(i + 1).min(points.len() - 1)].position
+                    - points[i.saturating_sub(1)].position)
+                    .try_normalize()
+                    .unwrap_or(Vec3::X);
+
+                // Transported along the curve, so that the rings don't twist.
+                let ring_normal = normal
+                    .and_then(|n| (n - tangent * n.dot(tangent)).try_normalize())
+                    .unwrap_or_else(|| tangent.any_orthonormal_vector());
+                let ring_bitangent = tangent.cross(ring_normal);
+                normal = Some(ring_normal);
+
+                let v = lengths[i] / total_length;
+
+                for side in 0..CURVE_TUBE_SIDES {
+                    let angle = side as f32 / CURVE_TUBE_SIDES as f32 * std::f32::consts::TAU;
+                    let dir = ring_normal * angle.cos() + ring_bitangent * angle.sin();
+                    let pos = point.position + dir * point.radius.max(0.0);
+
+                    res.verts.push(PackedVertex::new(pos.into(), dir.into()));
+                    res.uvs.push([side as f32 / CURVE_TUBE_SIDES as f32, v]);
+                    res.tangents.push([tangent.x, tangent.y, tangent.z, 1.0]);
+                    res.material_ids.push(0);
+                }
+            }
+
+            for i in 0..points.len() as u32 - 1 {
+                let ring0 = (first_point + i) * CURVE_TUBE_SIDES as u32;
+                let ring1 = ring0 + CURVE_TUBE_SIDES as u32;
+
+                for side in 0..CURVE_TUBE_SIDES as u32 {
+                    let next_side = (side + 1) % CURVE_TUBE_SIDES as u32;
+                    res.tube_indices.extend_from_slice(&[
+                        ring0 + side,
+                        ring0 + next_side,
+                        ring1 + side,
+                        ring0 + next_side,
+                        ring1 + next_side,
+                        ring1 + side,
+                    ]);
+                }
+
+                let [l0, m0, r0] = [0, 1, 2].map(|side| (first_point + i) * 3 + side);
+                let [l1, m1, r1] = [0, 1, 2].map(|side| (first_point + i + 1) * 3 + side);
+                res.ribbon_indices
+                    .extend_from_slice(&[l0, m0, l1, m0, m1, l1, m0, r0, m1, r0, r1, m1]);
+            }
+        }
+
+        res
+    }
+}
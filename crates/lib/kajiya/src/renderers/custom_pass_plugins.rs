@@ -0,0 +1,374 @@
+//! Custom passes loaded from dynamic libraries, and reloaded when those are rebuilt, for
+//! iterating on experimental passes without relinking the application.
+//!
+//! A plugin is a `dylib` crate depending on kajiya and `kajiya-dylib`, with a
+//! `CustomPass + Default` type exported by `export_custom_pass_plugin!`. With the `plugins`
+//! feature, applications load them with `CustomPassPlugins`, at the `CustomPassPoint` of
+//! their choosing. The application links kajiya through `kajiya-dylib` too, so that both
+//! share one copy of kajiya and its statics, such as the shader generation and the logger;
+//! a `cdylib` plugin would carry its own, and is refused when loaded.
+//!
+//! The plugin is reached through a `#[repr(C)]` vtable, so that loading a mismatched library
+//! fails cleanly rather than crashing. The pass itself still records into the render graph
+//! through its Rust API though, so plugins must be built with the same compiler, and against
+//! the same kajiya, as the application.
+//!
+//! Panics in the pass are contained both while it records its passes, and in the render
+//! functions of those passes, which run later while the graph executes. Either disables
+//! the plugin until it's reloaded.
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+
+use kajiya_rg as rg;
+
+use super::custom_passes::CustomPassResources;
+
+/// Bumped whenever `CustomPassPluginVtable` or `CustomPassPluginContext` change.
+pub const CUSTOM_PASS_PLUGIN_ABI_VERSION: u32 = 2;
+
+/// Checked along with the ABI version, as a proxy for the Rust types matching up.
+#[doc(hidden)]
+pub const KAJIYA_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Its address tells apart copies of kajiya, for checking that a plugin uses the application's.
+#[doc(hidden)]
+pub static KAJIYA_INSTANCE: u8 = 0;
+
+/// What `render` of the vtable receives, behind the `ctx` pointer.
+pub struct CustomPassPluginContext<'a, 'res> {
+    pub rg: &'a mut rg::TemporalRenderGraph,
+    pub resources: &'a mut CustomPassResources<'res>,
+}
+
+/// Returned by the `kajiya_custom_pass_plugin` entry point of a plugin.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CustomPassPluginVtable {
+    pub abi_version: u32,
+    /// Nul-terminated `KAJIYA_VERSION` of the plugin.
+    pub kajiya_version: *const c_char,
+    /// `KAJIYA_INSTANCE` as seen by the plugin.
+    pub kajiya_instance: *const u8,
+    /// Null if the pass panicked while being created.
+    pub create: unsafe extern "C" fn() -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(pass: *mut c_void),
+    /// `ctx` is a `CustomPassPluginContext`. Returns false if the pass panicked.
+    pub render: unsafe extern "C" fn(pass: *mut c_void, ctx: *mut c_void) -> bool,
+}
+
+/// Defines the entry point of a custom pass plugin, for a type implementing
+/// `CustomPass` and `Default`. Panics are caught rather than unwinding into the host.
+#[macro_export]
+macro_rules! export_custom_pass_plugin {
+    ($pass:ty) => {
+        #[no_mangle]
+        pub extern "C" fn kajiya_custom_pass_plugin(
+        ) -> $crate::renderers::custom_pass_plugins::CustomPassPluginVtable {
+            use ::std::{ffi::c_void, panic};
+            use $crate::renderers::custom_pass_plugins::{
+                CustomPassPluginContext, CustomPassPluginVtable, CUSTOM_PASS_PLUGIN_ABI_VERSION,
+                KAJIYA_INSTANCE, KAJIYA_VERSION,
+            };
+
+            unsafe extern "C" fn create() -> *mut c_void {
+                panic::catch_unwind(|| Box::into_raw(Box::new(<$pass as Default>::default())))
+                    .map_or(::std::ptr::null_mut(), |pass| pass as *mut c_void)
+            }
+
+            unsafe extern "C" fn destroy(pass: *mut c_void) {
+                drop(Box::from_raw(pass as *mut $pass));
+            }
+
+            unsafe extern "C" fn render(pass: *mut c_void, ctx: *mut c_void) -> bool {
+                let pass = &mut *(pass as *mut $pass);
+                let ctx = &mut *(ctx as *mut CustomPassPluginContext);
+
+                panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                    $crate::renderers::custom_passes::CustomPass::render(
+                        pass,
+                        ctx.rg,
+                        ctx.resources,
+                    )
+                }))
+                .is_ok()
+            }
+
+            CustomPassPluginVtable {
+                abi_version: CUSTOM_PASS_PLUGIN_ABI_VERSION,
+                kajiya_version: KAJIYA_VERSION.as_ptr() as *const _,
+                kajiya_instance: &KAJIYA_INSTANCE,
+                create,
+                destroy,
+                render,
+            }
+        }
+    };
+}
+
+#[cfg(feature = "plugins")]
+pub use host::CustomPassPlugins;
+
+#[cfg(feature = "plugins")]
+mod host {
+    use std::{
+        ffi::{c_void, CStr},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use anyhow::Context as _;
+    use kajiya_backend::file::watch_file;
+    use kajiya_rg as rg;
+    use libloading::Library;
+
+    use super::{
+        CustomPassPluginContext, CustomPassPluginVtable, CUSTOM_PASS_PLUGIN_ABI_VERSION,
+        KAJIYA_INSTANCE, KAJIYA_VERSION,
+    };
+    use crate::renderers::custom_passes::{
+        CustomPass, CustomPassPoint, CustomPassResources, CustomPasses,
+    };
+
+    const ENTRY_POINT: &[u8] = b"kajiya_custom_pass_plugin\0";
+
+    struct PluginPass {
+        name: String,
+        vtable: CustomPassPluginVtable,
+        pass: *mut c_void,
+        // Also set by the render functions of its passes, which run after `render` returns.
+        panicked: Arc<AtomicBool>,
+        // Outlives `pass`, which `destroy` of the library frees
+        _library: Arc<Library>,
+    }
+
+    impl CustomPass for PluginPass {
+        fn render(
+            &mut self,
+            rg: &mut rg::TemporalRenderGraph,
+            resources: &mut CustomPassResources,
+        ) {
+            if self.panicked.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let first_pass_idx = rg.pass_count();
+
+            let mut ctx = CustomPassPluginContext {
+                rg: &mut *rg,
+                resources,
+            };
+            let ok = unsafe {
+                (self.vtable.render)(self.pass, &mut ctx as *mut CustomPassPluginContext as _)
+            };
+
+            if !ok {
+                log::error!(
+                    "Custom pass plugin {:?} panicked; skipping it until it's reloaded",
+                    self.name
+                );
+                self.panicked.store(true, Ordering::Relaxed);
+            }
+
+            let name = self.name.clone();
+            let panicked = self.panicked.clone();
+            rg.catch_render_fn_panics(first_pass_idx, move |pass_name| {
+                log::error!(
+                    "Pass {:?} of custom pass plugin {:?} panicked; skipping the plugin until it's reloaded",
+                    pass_name,
+                    name
+                );
+                panicked.store(true, Ordering::Relaxed);
+            });
+        }
+    }
+
+    impl Drop for PluginPass {
+        fn drop(&mut self) {
+            unsafe { (self.vtable.destroy)(self.pass) };
+        }
+    }
+
+    struct LoadedPlugin {
+        name: String,
+        point: CustomPassPoint,
+        path: PathBuf,
+        library: Arc<Library>,
+        changed: Arc<AtomicBool>,
+    }
+
+    /// Custom passes from dynamic libraries, registered in `CustomPasses` under the file
+    /// names of their libraries, and reloaded by `reload_changed` when those are written to.
+    #[derive(Default)]
+    pub struct CustomPassPlugins {
+        plugins: Vec<LoadedPlugin>,
+        // Libraries are loaded from copies, as the originals are locked on some platforms,
+        // and wouldn't load again on others. Numbers the copies.
+        load_count: u32,
+        // Replaced libraries are kept loaded, as the last recorded frame may still hold
+        // closures from them, and Rust libraries generally can't be unloaded safely.
+        retired_libraries: Vec<Arc<Library>>,
+    }
+
+    impl CustomPassPlugins {
+        /// Loads the plugin at `path`, and registers its pass at `point`, enabled.
+        pub fn load(
+            &mut self,
+            passes: &mut CustomPasses,
+            point: CustomPassPoint,
+            path: impl Into<PathBuf>,
+        ) -> anyhow::Result<()> {
+            let path = path.into();
+            let name = plugin_name(&path);
+
+            let changed = Arc::new(AtomicBool::new(false));
+            let library = self.load_pass(passes, &name, point, &path, &changed)?;
+
+            let plugin = LoadedPlugin {
+                name,
+                point,
+                path,
+                library,
+                changed,
+            };
+
+            if let Some(idx) = self.plugins.iter().position(|p| p.name == plugin.name) {
+                let previous = std::mem::replace(&mut self.plugins[idx], plugin);
+                self.retired_libraries.push(previous.library);
+            } else {
+                self.plugins.push(plugin);
+            }
+
+            Ok(())
+        }
+
+        /// Reloads the plugins whose libraries have changed, replacing their passes. Call between
+        /// frames. If a reload fails, the previous version of the plugin stays in use.
+        pub fn reload_changed(&mut self, passes: &mut CustomPasses) {
+            for idx in 0..self.plugins.len() {
+                if !self.plugins[idx].changed.swap(false, Ordering::Relaxed) {
+                    continue;
+                }
+
+                let plugin = &self.plugins[idx];
+                let (name, point, path, changed) = (
+                    plugin.name.clone(),
+                    plugin.point,
+                    plugin.path.clone(),
+                    plugin.changed.clone(),
+                );
+
+                match self.load_pass(passes, &name, point, &path, &changed) {
+                    Ok(library) => {
+                        log::info!("Reloaded custom pass plugin {:?}", name);
+                        let previous = std::mem::replace(&mut self.plugins[idx].library, library);
+                        self.retired_libraries.push(previous);
+                    }
+                    Err(err) => {
+                        log::error!("Failed to reload custom pass plugin {:?}: {:#}", name, err)
+                    }
+                }
+            }
+        }
+
+        // Loads a fresh copy of the library at `path`, and registers its pass in place of any previous one.
+        fn load_pass(
+            &mut self,
+            passes: &mut CustomPasses,
+            name: &str,
+            point: CustomPassPoint,
+            path: &Path,
+            changed: &Arc<AtomicBool>,
+        ) -> anyhow::Result<Arc<Library>> {
+            // Build tools tend to replace their outputs, so the watch is renewed on every load.
+            let changed = changed.clone();
+            watch_file(path, move || changed.store(true, Ordering::Relaxed))?;
+
+            self.load_count += 1;
+            let copy_path = std::env::temp_dir().join(format!(
+                "kajiya-plugin-{}-{}-{}",
+                std::process::id(),
+                self.load_count,
+                path.file_name()
+                    .context("plugin path has no file name")?
+                    .to_string_lossy()
+            ));
+
+            std::fs::copy(path, &copy_path)
+                .with_context(|| format!("copying {:?} to {:?}", path, copy_path))?;
+            let library = unsafe { Library::new(&copy_path) };
+            // Fails while loaded on Windows; the copies are left in the temp dir there.
+            let _ = std::fs::remove_file(&copy_path);
+            let library = Arc::new(library.with_context(|| format!("loading {:?}", path))?);
+
+            let vtable = unsafe {
+                library.get::<unsafe extern "C" fn() -> CustomPassPluginVtable>(ENTRY_POINT)
+            }
+            .with_context(|| format!("{:?} doesn't export a custom pass plugin", path))?;
+            let vtable = unsafe { vtable() };
+
+            if vtable.abi_version != CUSTOM_PASS_PLUGIN_ABI_VERSION {
+                anyhow::bail!(
+                    "{:?} has the plugin ABI version {}, but {} is needed",
+                    path,
+                    vtable.abi_version,
+                    CUSTOM_PASS_PLUGIN_ABI_VERSION
+                );
+            }
+
+            let plugin_version = unsafe { CStr::from_ptr(vtable.kajiya_version) }.to_string_lossy();
+            let host_version = KAJIYA_VERSION.trim_end_matches('\0');
+            if plugin_version != host_version {
+                anyhow::bail!(
+                    "{:?} was built against kajiya {}, but this is {}",
+                    path,
+                    plugin_version,
+                    host_version
+                );
+            }
+
+            if vtable.kajiya_instance != &KAJIYA_INSTANCE as *const u8 {
+                anyhow::bail!(
+                    "{:?} has its own copy of kajiya; build it as a `dylib` depending on kajiya-dylib, \
+                    like the application",
+                    path
+                );
+            }
+
+            let pass = unsafe { (vtable.create)() };
+            if pass.is_null() {
+                anyhow::bail!("the pass of {:?} panicked while being created", path);
+            }
+
+            passes.register(
+                point,
+                name,
+                true,
+                PluginPass {
+                    name: name.to_owned(),
+                    vtable,
+                    pass,
+                    panicked: Default::default(),
+                    _library: library.clone(),
+                },
+            );
+
+            Ok(library)
+        }
+    }
+
+    // `libfoo.so` and `foo.dll` are both `foo`.
+    fn plugin_name(path: &Path) -> String {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        stem.strip_prefix(std::env::consts::DLL_PREFIX)
+            .unwrap_or(&stem)
+            .to_owned()
+    }
+}
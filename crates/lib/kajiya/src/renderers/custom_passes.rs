@@ -0,0 +1,135 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg as rg;
+
+use super::{post_fx::AsAnyMut, GbufferDepth};
+
+/// Where in the frame `CustomPasses` run, in `RenderMode::Standard`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CustomPassPoint {
+    /// Once the meshes and decals are in the G-buffer, before anything reads it.
+    AfterGbuffer,
+
+    /// On the lit scene, with transparent meshes and particles, before anti-aliasing
+    /// and post-processing; linear, pre-exposed radiance at the render resolution.
+    BeforePost,
+
+    /// On the tonemapped output, at the output resolution, before debug overlays.
+    AfterPost,
+}
+
+impl std::str::FromStr for CustomPassPoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "after-gbuffer" => Ok(Self::AfterGbuffer),
+            "before-post" => Ok(Self::BeforePost),
+            "after-post" => Ok(Self::AfterPost),
+            _ => Err(anyhow::anyhow!(
+                "Unknown custom pass point {:?}; expected one of: after-gbuffer, before-post, after-post",
+                s
+            )),
+        }
+    }
+}
+
+/// The standard resources of the frame, for the custom passes to read and write.
+pub struct CustomPassResources<'a> {
+    /// At the render resolution. Only written at `AfterGbuffer`; the renderer
+    /// derives other images from it afterwards.
+    pub gbuffer_depth: &'a mut GbufferDepth,
+
+    /// Screen-space motion of the G-buffer surfaces.
+    pub velocity: &'a rg::Handle<Image>,
+
+    /// The image at the point: the lit scene at `BeforePost`, and the output at `AfterPost`.
+    /// Replace it to substitute a new image.
+    pub color: Option<&'a mut rg::Handle<Image>>,
+
+    pub bindless_descriptor_set: vk::DescriptorSet,
+}
+
+/// Graph passes of the application, added to the frame at one of the `CustomPassPoint`s.
+pub trait CustomPass: AsAnyMut {
+    fn render(&mut self, rg: &mut rg::TemporalRenderGraph, resources: &mut CustomPassResources);
+}
+
+pub struct CustomPassEntry {
+    pub name: String,
+    pub point: CustomPassPoint,
+    pub enabled: bool,
+    pass: Box<dyn CustomPass>,
+}
+
+/// Lets applications add their own effects to the frame the world renderer builds,
+/// without forking it. Passes at the same point run in the order they were registered.
+#[derive(Default)]
+pub struct CustomPasses {
+    entries: Vec<CustomPassEntry>,
+}
+
+impl CustomPasses {
+    /// Appends a pass at `point`, or replaces the one with the same name.
+    pub fn register(
+        &mut self,
+        point: CustomPassPoint,
+        name: impl Into<String>,
+        enabled: bool,
+        pass: impl CustomPass + 'static,
+    ) {
+        let name = name.into();
+        let pass = Box::new(pass);
+
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.point = point;
+            entry.enabled = enabled;
+            entry.pass = pass;
+        } else {
+            self.entries.push(CustomPassEntry {
+                name,
+                point,
+                enabled,
+                pass,
+            });
+        }
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let len_before = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.len() != len_before
+    }
+
+    pub fn entries(&self) -> &[CustomPassEntry] {
+        &self.entries
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.enabled = enabled;
+        }
+    }
+
+    /// The first pass of type `T`, for tweaking its parameters.
+    pub fn get_mut<T: CustomPass + 'static>(&mut self) -> Option<&mut T> {
+        self.entries.iter_mut().find_map(|entry| {
+            <dyn CustomPass as AsAnyMut>::as_any_mut(entry.pass.as_mut()).downcast_mut::<T>()
+        })
+    }
+
+    /// Runs the enabled passes at `point`.
+    pub(crate) fn render(
+        &mut self,
+        point: CustomPassPoint,
+        rg: &mut rg::TemporalRenderGraph,
+        resources: &mut CustomPassResources,
+    ) {
+        for entry in self
+            .entries
+            .iter_mut()
+            .filter(|entry| entry.enabled && entry.point == point)
+        {
+            entry.pass.render(rg, resources);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use glam::{IVec3, Vec3};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
+};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, SimpleRenderPass};
+
+use super::GbufferDepth;
+
+/// How diffuse GI is computed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GiMode {
+    /// Rays traced from the visible surfaces, reused spatially and temporally (`RtdgiRenderer`).
+    Rtdgi,
+
+    /// A world-space grid of irradiance probes (`DdgiRenderer`). Blurrier, but stable under
+    /// camera motion and disocclusion, and its cost doesn't depend on the screen contents.
+    Ddgi,
+}
+
+impl Default for GiMode {
+    fn default() -> Self {
+        Self::Rtdgi
+    }
+}
+
+// Must match `ddgi_common.hlsl`
+const IRRADIANCE_PROBE_RES: u32 = 8;
+const DEPTH_PROBE_RES: u32 = 16;
+
+// Must match `DdgiConstants` in `ddgi_common.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuDdgiConstants {
+    grid_origin: [i32; 3],
+    probe_spacing: f32,
+
+    prev_grid_origin: [i32; 3],
+    history_valid: u32,
+
+    probe_counts: [u32; 3],
+    rays_per_probe: u32,
+
+    hysteresis: f32,
+}
+
+/// Placement of the probe grid in a given frame.
+#[derive(Clone, Copy, PartialEq)]
+struct DdgiVolume {
+    /// Integer coordinates of the probe at the minimum corner, in units of `probe_spacing`.
+    grid_origin: IVec3,
+    probe_counts: [u32; 3],
+    probe_spacing: f32,
+}
+
+/// Irradiance probe volume in the style of "Dynamic Diffuse Global Illumination with
+/// Ray-Traced Irradiance Fields" (Majercik et al. 2019).
+///
+/// Every probe traces `rays_per_probe` rays each frame, which are blended into octahedral
+/// irradiance and visibility (depth moments) maps. Probes stuck inside geometry are nudged
+/// out of it. The grid follows the camera in whole probe steps; probes scrolling into the
+/// volume start with no history.
+pub struct DdgiRenderer {
+    /// Distance between neighboring probes, in meters.
+    pub probe_spacing: f32,
+
+    /// Number of probes along each axis; up to 32 horizontally, and 16 vertically.
+    pub probe_counts: [u32; 3],
+
+    pub rays_per_probe: u32,
+
+    /// Fraction of the previous irradiance and depth kept each frame.
+    pub hysteresis: f32,
+
+    prev_volume: Option<DdgiVolume>,
+}
+
+impl Default for DdgiRenderer {
+    fn default() -> Self {
+        Self {
+            probe_spacing: 2.0,
+            probe_counts: [24, 12, 24],
+            rays_per_probe: 128,
+            hysteresis: 0.97,
+            prev_volume: None,
+        }
+    }
+}
+
+impl DdgiRenderer {
+    /// Updates the probes, and samples them for every pixel of `gbuffer_depth`.
+    ///
+    /// Returns an image compatible with the screen-space irradiance produced by `RtdgiRenderer`.
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        sky_cube: &rg::Handle<Image>,
+        bindless_descriptor_set: vk::DescriptorSet,
+        tlas: &rg::Handle<RayTracingAcceleration>,
+        eye_position: Vec3,
+    ) -> rg::Handle<Image> {
+        let probe_spacing = self.probe_spacing.max(0.1);
+        // Keeps the probe count within the maximum height of the ray texture.
+        let probe_counts = [
+            self.probe_counts[0].clamp(2, 32),
+            self.probe_counts[1].clamp(2, 16),
+            self.probe_counts[2].clamp(2, 32),
+        ];
+        let rays_per_probe = self.rays_per_probe.clamp(16, 512);
+
+        let grid_origin = (eye_position / probe_spacing).floor().as_ivec3()
+            - IVec3::new(
+                probe_counts[0] as i32 / 2,
+                probe_counts[1] as i32 / 2,
+                probe_counts[2] as i32 / 2,
+            );
+
+        let volume = DdgiVolume {
+            grid_origin,
+            probe_counts,
+            probe_spacing,
+        };
+
+        // Probes carry over when the grid scrolls, but not when its layout changes.
+        let prev_volume = self.prev_volume.filter(|prev| {
+            prev.probe_counts == volume.probe_counts && prev.probe_spacing == volume.probe_spacing
+        });
+        self.prev_volume = Some(volume);
+
+        let constants = GpuDdgiConstants {
+            grid_origin: grid_origin.to_array(),
+            probe_spacing,
+            prev_grid_origin: prev_volume
+                .map_or(grid_origin, |prev| prev.grid_origin)
+                .to_array(),
+            history_valid: prev_volume.is_some() as u32,
+            probe_counts,
+            rays_per_probe,
+            hysteresis: self.hysteresis.clamp(0.0, 0.999),
+        };
+
+        let probe_count = probe_counts.iter().product::<u32>();
+        let atlas_tiles = [probe_counts[0] * probe_counts[1], probe_counts[2]];
+        let atlas_desc = |format: vk::Format, probe_res: u32| {
+            ImageDesc::new_2d(
+                format,
+                [
+                    atlas_tiles[0] * (probe_res + 2),
+                    atlas_tiles[1] * (probe_res + 2),
+                ],
+            )
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
+        };
+
+        let mut irradiance_atlas = rg
+            .get_or_create_temporal(
+                "ddgi.irradiance",
+                atlas_desc(vk::Format::R16G16B16A16_SFLOAT, IRRADIANCE_PROBE_RES),
+            )
+            .unwrap();
+
+        let mut depth_atlas = rg
+            .get_or_create_temporal(
+                "ddgi.depth",
+                atlas_desc(vk::Format::R16G16_SFLOAT, DEPTH_PROBE_RES),
+            )
+            .unwrap();
+
+        let mut probe_offsets = rg
+            .get_or_create_temporal(
+                "ddgi.probe_offsets",
+                ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, atlas_tiles)
+                    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+            )
+            .unwrap();
+
+        // Radiance and hit distance of every ray, one row per probe.
+        let mut ray_tex = rg.create(ImageDesc::new_2d(
+            vk::Format::R16G16B16A16_SFLOAT,
+            [rays_per_probe, probe_count],
+        ));
+
+        SimpleRenderPass::new_rt(
+            rg.add_pass("ddgi trace"),
+            ShaderSource::hlsl("/shaders/ddgi/trace.rgen.hlsl"),
+            [
+                ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
+                ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+            ],
+            super::rt_hit_groups(),
+        )
+        .read(sky_cube)
+        .read(&irradiance_atlas)
+        .read(&depth_atlas)
+        .read(&probe_offsets)
+        .write(&mut ray_tex)
+        .constants(constants)
+        .raw_descriptor_set(1, bindless_descriptor_set)
+        .trace_rays(tlas, ray_tex.desc().extent);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("ddgi relocate"),
+            "/shaders/ddgi/relocate_probes.hlsl",
+        )
+        .read(&ray_tex)
+        .write(&mut probe_offsets)
+        .constants(constants)
+        .dispatch([probe_count, 1, 1]);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("ddgi update irradiance"),
+            "/shaders/ddgi/update_irradiance.hlsl",
+        )
+        .read(&ray_tex)
+        .write(&mut irradiance_atlas)
+        .constants(constants)
+        .dispatch([
+            atlas_tiles[0] * IRRADIANCE_PROBE_RES,
+            atlas_tiles[1] * IRRADIANCE_PROBE_RES,
+            1,
+        ]);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("ddgi update depth"),
+            "/shaders/ddgi/update_depth.hlsl",
+        )
+        .read(&ray_tex)
+        .write(&mut depth_atlas)
+        .constants(constants)
+        .dispatch([
+            atlas_tiles[0] * DEPTH_PROBE_RES,
+            atlas_tiles[1] * DEPTH_PROBE_RES,
+            1,
+        ]);
+
+        for (atlas, probe_res) in [
+            (&mut irradiance_atlas, IRRADIANCE_PROBE_RES),
+            (&mut depth_atlas, DEPTH_PROBE_RES),
+        ] {
+            let extent = atlas.desc().extent;
+
+            SimpleRenderPass::new_compute(
+                rg.add_pass("ddgi copy borders"),
+                "/shaders/ddgi/copy_borders.hlsl",
+            )
+            .write(atlas)
+            .constants(probe_res)
+            .dispatch(extent);
+        }
+
+        let mut irradiance_tex = rg.create(ImageDesc::new_2d(
+            vk::Format::R16G16B16A16_SFLOAT,
+            gbuffer_depth.gbuffer.desc().extent_2d(),
+        ));
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("ddgi sample"),
+            "/shaders/ddgi/sample_irradiance.hlsl",
+        )
+        .read(&gbuffer_depth.gbuffer)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(&irradiance_atlas)
+        .read(&depth_atlas)
+        .read(&probe_offsets)
+        .write(&mut irradiance_tex)
+        .constants((irradiance_tex.desc().extent_inv_extent_2d(), constants))
+        .dispatch(irradiance_tex.desc().extent);
+
+        irradiance_tex
+    }
+}
@@ -0,0 +1,157 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::GbufferDepth;
+
+/// Intermediate data shown on screen in place of the final image.
+///
+/// The views go through the final blit, but not through post-processing, so radiance
+/// is shown with a simple tonemap, and without exposure adjustments. The heatmaps don't
+/// depend on the exposure at all.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DebugView {
+    /// The final image
+    None,
+    Albedo,
+    Normals,
+    /// Perceptual roughness in red, metalness in green
+    RoughnessMetalness,
+    /// Screen-space motion, with mid-gray meaning no motion
+    MotionVectors,
+    /// Diffuse GI before the temporal and spatial filters
+    RawGi,
+    /// Diffuse GI as applied by the lighting pass
+    DenoisedGi,
+    AmbientOcclusion,
+    /// Denoised sun shadow visibility
+    ShadowVisibility,
+    /// Number of G-buffer fragments which passed the depth test, per pixel
+    Overdraw,
+    /// False-colored scene luminance, before exposure; see `LuminanceHeatmap`
+    Luminance,
+    /// Relative standard deviation of the diffuse GI over time, as seen by its temporal filter
+    GiVariance,
+    /// Samples accumulated by the diffuse GI's temporal filter; red where it has little history
+    GiHistoryLength,
+}
+
+impl Default for DebugView {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 13] = [
+        DebugView::None,
+        DebugView::Albedo,
+        DebugView::Normals,
+        DebugView::RoughnessMetalness,
+        DebugView::MotionVectors,
+        DebugView::RawGi,
+        DebugView::DenoisedGi,
+        DebugView::AmbientOcclusion,
+        DebugView::ShadowVisibility,
+        DebugView::Overdraw,
+        DebugView::Luminance,
+        DebugView::GiVariance,
+        DebugView::GiHistoryLength,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::None => "None",
+            DebugView::Albedo => "Albedo",
+            DebugView::Normals => "World normals",
+            DebugView::RoughnessMetalness => "Roughness / metalness",
+            DebugView::MotionVectors => "Motion vectors",
+            DebugView::RawGi => "Raw GI",
+            DebugView::DenoisedGi => "Denoised GI",
+            DebugView::AmbientOcclusion => "Ambient occlusion",
+            DebugView::ShadowVisibility => "Shadow visibility",
+            DebugView::Overdraw => "Overdraw",
+            DebugView::Luminance => "Luminance (EV)",
+            DebugView::GiVariance => "GI variance",
+            DebugView::GiHistoryLength => "GI history length",
+        }
+    }
+
+    // Must match `debug_view.hlsl`
+    fn shader_mode(self) -> u32 {
+        match self {
+            DebugView::None | DebugView::Albedo => 0,
+            DebugView::Normals => 1,
+            DebugView::RoughnessMetalness => 2,
+            DebugView::MotionVectors => 3,
+            DebugView::RawGi | DebugView::DenoisedGi => 4,
+            DebugView::AmbientOcclusion | DebugView::ShadowVisibility => 5,
+            DebugView::Overdraw => 6,
+            DebugView::Luminance => 7,
+            DebugView::GiVariance => 8,
+            DebugView::GiHistoryLength => 9,
+        }
+    }
+}
+
+/// Calibration of `DebugView::Luminance`, which maps exposure values between `min_ev`
+/// and `max_ev` to blue, cyan, green, yellow, and red. Darker pixels are black,
+/// and brighter ones white.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LuminanceHeatmap {
+    /// Luminance in nits of a radiance of 1.0 in the scene's units,
+    /// which are otherwise arbitrary.
+    pub nits_per_unit: f32,
+
+    /// EV100 of the luminance, as a spot meter would measure it; `log2(nits * 8)`.
+    pub min_ev: f32,
+    pub max_ev: f32,
+}
+
+impl Default for LuminanceHeatmap {
+    fn default() -> Self {
+        Self {
+            nits_per_unit: 1.0,
+            min_ev: -4.0,
+            max_ev: 12.0,
+        }
+    }
+}
+
+/// Visualizes `input` according to `view`. The G-buffer views don't need an input.
+pub fn render_debug_view(
+    rg: &mut rg::RenderGraph,
+    view: DebugView,
+    gbuffer_depth: &GbufferDepth,
+    input: Option<&rg::Handle<Image>>,
+    luminance_heatmap: &LuminanceHeatmap,
+) -> rg::Handle<Image> {
+    let dummy_input;
+    let input = match input {
+        Some(input) => input,
+        None => {
+            dummy_input = rg.create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]));
+            &dummy_input
+        }
+    };
+
+    let mut output = rg.create(ImageDesc::new_2d(
+        vk::Format::R16G16B16A16_SFLOAT,
+        gbuffer_depth.gbuffer.desc().extent_2d(),
+    ));
+
+    SimpleRenderPass::new_compute(rg.add_pass("debug view"), "/shaders/debug_view.hlsl")
+        .read(&gbuffer_depth.gbuffer)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(input)
+        .write(&mut output)
+        .constants((
+            output.desc().extent_inv_extent_2d(),
+            view.shader_mode(),
+            luminance_heatmap.nits_per_unit.max(1e-10),
+            luminance_heatmap.min_ev,
+            luminance_heatmap.max_ev.max(luminance_heatmap.min_ev + 0.1),
+        ))
+        .dispatch(output.desc().extent);
+
+    output
+}
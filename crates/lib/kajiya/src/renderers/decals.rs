@@ -0,0 +1,111 @@
+use glam::{Affine3A, Vec4};
+use kajiya_backend::ash::vk;
+use kajiya_rg::{self as rg};
+use rg::{RenderGraph, SimpleRenderPass};
+
+use crate::world_renderer::BindlessImageHandle;
+
+use super::GbufferDepth;
+
+/// A box projecting texture layers onto the G-buffer, along its local -Y axis.
+///
+/// Each layer is only applied if its map is set. The maps are sampled with
+/// `u` along the local X axis, and `v` along the local -Z axis.
+#[derive(Clone, Copy)]
+pub struct Decal {
+    /// Maps the `[-0.5, 0.5]` cube to the decal's box in world space.
+    pub transform: Affine3A,
+
+    pub albedo_map: Option<BindlessImageHandle>,
+    /// Tangent-space, in the red and green channels.
+    pub normal_map: Option<BindlessImageHandle>,
+    /// Perceptual roughness, in the red channel.
+    pub roughness_map: Option<BindlessImageHandle>,
+
+    /// Multiplies the albedo map. The alpha, along with the albedo map's,
+    /// is the coverage of all the layers.
+    pub albedo_mult: Vec4,
+    pub roughness_mult: f32,
+}
+
+impl Decal {
+    pub fn new(transform: Affine3A) -> Self {
+        Self {
+            transform,
+            albedo_map: None,
+            normal_map: None,
+            roughness_map: None,
+            albedo_mult: Vec4::ONE,
+            roughness_mult: 1.0,
+        }
+    }
+}
+
+// Must match `Decal` in `apply_decals.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuDecal {
+    world_to_decal: [f32; 12],
+    albedo_mult: [f32; 4],
+    albedo_map: u32,
+    normal_map: u32,
+    roughness_map: u32,
+    roughness_mult: f32,
+}
+
+const GPU_DECAL_NO_MAP: u32 = !0;
+
+impl GpuDecal {
+    fn new(decal: &Decal) -> Self {
+        let xform = decal.transform.inverse();
+        let map = |map: Option<BindlessImageHandle>| map.map_or(GPU_DECAL_NO_MAP, |map| map.0);
+
+        Self {
+            world_to_decal: [
+                xform.x_axis.x,
+                xform.y_axis.x,
+                xform.z_axis.x,
+                xform.translation.x,
+                xform.x_axis.y,
+                xform.y_axis.y,
+                xform.z_axis.y,
+                xform.translation.y,
+                xform.x_axis.z,
+                xform.y_axis.z,
+                xform.z_axis.z,
+                xform.translation.z,
+            ],
+            albedo_mult: decal.albedo_mult.into(),
+            albedo_map: map(decal.albedo_map),
+            normal_map: map(decal.normal_map),
+            roughness_map: map(decal.roughness_map),
+            roughness_mult: decal.roughness_mult,
+        }
+    }
+}
+
+/// Blends the decals over the G-buffer in order, so that later ones end up on top.
+pub fn apply_decals(
+    rg: &mut RenderGraph,
+    gbuffer_depth: &mut GbufferDepth,
+    decals: &[Decal],
+    bindless_descriptor_set: vk::DescriptorSet,
+) {
+    if decals.is_empty() {
+        return;
+    }
+
+    let gpu_decals: Vec<GpuDecal> = decals.iter().map(GpuDecal::new).collect();
+    let extent = gbuffer_depth.gbuffer.desc().extent;
+
+    SimpleRenderPass::new_compute(rg.add_pass("apply decals"), "/shaders/apply_decals.hlsl")
+        .write(&mut gbuffer_depth.gbuffer)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .dynamic_storage_buffer_vec(gpu_decals)
+        .constants((
+            gbuffer_depth.gbuffer.desc().extent_inv_extent_2d(),
+            decals.len() as u32,
+        ))
+        .raw_descriptor_set(1, bindless_descriptor_set)
+        .dispatch(extent);
+}
@@ -2,7 +2,9 @@ use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg};
 use rg::{RenderGraph, SimpleRenderPass};
 
-use super::{ircache::IrcacheRenderState, wrc::WrcRenderState, GbufferDepth};
+use super::{
+    ircache::IrcacheRenderState, light_clusters::LightClusters, wrc::WrcRenderState, GbufferDepth,
+};
 
 #[allow(clippy::too_many_arguments)]
 pub fn light_gbuffer(
@@ -17,10 +19,37 @@ pub fn light_gbuffer(
     output: &mut rg::Handle<Image>,
     sky_cube: &rg::Handle<Image>,
     convolved_sky_cube: &rg::Handle<Image>,
+    prefiltered_sky_cube: &rg::Handle<Image>,
+    punctual_lighting: Option<&rg::Handle<Image>>,
+    rect_lighting: Option<&rg::Handle<Image>>,
+    light_clusters: &LightClusters,
     bindless_descriptor_set: vk::DescriptorSet,
     debug_shading_mode: usize,
     debug_show_wrc: bool,
+    reflection_roughness_cutoff: f32,
 ) {
+    // Without ray tracing, punctual and rect lights are evaluated unshadowed in the pass itself;
+    // the punctual ones from `light_clusters`.
+    let punctual_lighting_placeholder;
+    let punctual_lighting_img = match punctual_lighting {
+        Some(img) => img,
+        None => {
+            punctual_lighting_placeholder =
+                rg.create(ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [1, 1]));
+            &punctual_lighting_placeholder
+        }
+    };
+
+    let rect_lighting_placeholder;
+    let rect_lighting_img = match rect_lighting {
+        Some(img) => img,
+        None => {
+            rect_lighting_placeholder =
+                rg.create(ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [1, 1]));
+            &rect_lighting_placeholder
+        }
+    };
+
     SimpleRenderPass::new_compute(rg.add_pass("light gbuffer"), "/shaders/light_gbuffer.hlsl")
         .read(&gbuffer_depth.gbuffer)
         .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -33,10 +62,18 @@ pub fn light_gbuffer(
         .write(output)
         .read(sky_cube)
         .read(convolved_sky_cube)
+        .read(prefiltered_sky_cube)
+        .read(punctual_lighting_img)
+        .read(rect_lighting_img)
+        .read(&light_clusters.lists)
         .constants((
+            light_clusters.constants,
             gbuffer_depth.gbuffer.desc().extent_inv_extent_2d(),
             debug_shading_mode as u32,
             debug_show_wrc as u32,
+            punctual_lighting.is_some() as u32,
+            rect_lighting.is_some() as u32,
+            reflection_roughness_cutoff,
         ))
         .raw_descriptor_set(1, bindless_descriptor_set)
         .dispatch(gbuffer_depth.gbuffer.desc().extent);
@@ -295,7 +295,7 @@ impl DlssRenderer {
         let ngx_params = self.ngx_params;
         let should_reset = self.frame_idx == 0;
 
-        pass.render(move |api| {
+        pass.render_local(move |api| {
             let cb = api.cb;
 
             let mut input = image_to_ngx(api, input_ref, ImageViewDesc::default());
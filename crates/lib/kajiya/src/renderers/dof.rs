@@ -2,14 +2,70 @@ use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg};
 use rg::{RenderGraph, SimpleRenderPass};
 
+/// Thin lens depth of field. The focal length follows from the camera's field of view
+/// and `sensor_height`, so the amount of blur matches a physical camera with the same settings.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DofParams {
+    pub enabled: bool,
+
+    /// Ratio of the focal length to the aperture diameter. Smaller values blur more.
+    pub f_stop: f32,
+
+    /// Distance to the focal plane, in meters. Ignored with `auto_focus`, unless the sky is in the center.
+    pub focus_distance: f32,
+
+    /// Focus on whatever is in the center of the screen.
+    pub auto_focus: bool,
+
+    /// Height of the sensor, in meters. Defaults to full-frame 35mm.
+    pub sensor_height: f32,
+
+    /// Limit on the radius of the bokeh, in output pixels. Also bounds the cost of the gather.
+    pub max_coc_radius_px: f32,
+}
+
+impl Default for DofParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            f_stop: 2.8,
+            focus_distance: 5.0,
+            auto_focus: true,
+            sensor_height: 0.024,
+            max_coc_radius_px: 16.0,
+        }
+    }
+}
+
+// Must match `DofConstants` in `dof_common.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuDofConstants {
+    f_stop: f32,
+    focus_distance: f32,
+    sensor_height: f32,
+    max_coc_radius_px: f32,
+    auto_focus: u32,
+}
+
+/// Blurs `input`, which is before tonemapping, and can be larger than `depth` with temporal upsampling.
 pub fn dof(
     rg: &mut RenderGraph,
     input: &rg::Handle<Image>,
     depth: &rg::Handle<Image>,
+    params: &DofParams,
 ) -> rg::Handle<Image> {
+    let constants = GpuDofConstants {
+        f_stop: params.f_stop.max(0.5),
+        focus_distance: params.focus_distance.max(0.01),
+        sensor_height: params.sensor_height.max(1e-3),
+        max_coc_radius_px: params.max_coc_radius_px.clamp(1.0, 64.0),
+        auto_focus: params.auto_focus as u32,
+    };
+
     let mut coc = rg.create(ImageDesc::new_2d(
         vk::Format::R16_SFLOAT,
-        input.desc().extent_2d(),
+        depth.desc().extent_2d(),
     ));
 
     let mut coc_tiles = rg.create(ImageDesc::new_2d(
@@ -21,21 +77,26 @@ pub fn dof(
         .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
         .write(&mut coc)
         .write(&mut coc_tiles)
+        .constants((
+            coc.desc().extent_inv_extent_2d(),
+            input.desc().extent_inv_extent_2d(),
+            constants,
+        ))
         .dispatch(coc.desc().extent);
 
-    let mut dof = rg.create(ImageDesc::new_2d(
-        vk::Format::R16G16B16A16_SFLOAT,
-        input.desc().extent_2d(),
-    ));
+    let mut output = rg.create(*input.desc());
 
     SimpleRenderPass::new_compute(rg.add_pass("dof gather"), "/shaders/dof/gather.hlsl")
-        .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
         .read(input)
         .read(&coc)
         .read(&coc_tiles)
-        .write(&mut dof)
-        .constants(dof.desc().extent_inv_extent_2d())
-        .dispatch(dof.desc().extent);
+        .write(&mut output)
+        .constants((
+            coc.desc().extent_inv_extent_2d(),
+            output.desc().extent_inv_extent_2d(),
+            constants,
+        ))
+        .dispatch(output.desc().extent);
 
-    dof
+    output
 }
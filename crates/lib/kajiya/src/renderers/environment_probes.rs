@@ -0,0 +1,211 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use glam::{Mat3, Quat, Vec3};
+use kajiya_backend::{ash::vk, vulkan::image::*, BackendError, Device};
+use rust_shaders_shared::camera::CameraMatrices;
+
+use crate::camera::{CameraLens, LookThroughCamera};
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct EnvironmentProbeHandle(pub usize);
+
+pub const ENVIRONMENT_PROBE_FACE_COUNT: usize = 6;
+const ALL_FACES_CAPTURED: u8 = (1 << ENVIRONMENT_PROBE_FACE_COUNT) - 1;
+
+/// Columns of the view-to-world rotation of the camera of each cube face, in the order of
+/// the array layers. They look the same way as `CUBE_MAP_FACE_ROTATIONS` in shaders, but their
+/// images come out mirrored horizontally; `store_probe_face.hlsl` flips them back.
+const FACE_ROTATIONS: [[[f32; 3]; 3]; ENVIRONMENT_PROBE_FACE_COUNT] = [
+    [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]],
+    [[0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+    [[-1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]],
+    [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+    [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+    [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnvironmentProbeRefresh {
+    /// Probes are captured when added, and then only when requested
+    /// via `WorldRenderer::capture_environment_probe`.
+    OnDemand,
+    /// All probes are re-captured continuously, cycling through their faces.
+    RoundRobin,
+}
+
+/// A cube map of the scene around `position`, rendered like a `WorldView`.
+pub struct EnvironmentProbe {
+    pub position: Vec3,
+
+    /// Linear HDR radiance, without pre-exposure.
+    pub(crate) cube: Arc<Image>,
+
+    // Bit per face
+    pub(crate) captured_faces: u8,
+}
+
+impl EnvironmentProbe {
+    pub fn resolution(&self) -> u32 {
+        self.cube.desc.extent[0]
+    }
+
+    /// Whether all the faces have been captured at least once.
+    pub fn is_captured(&self) -> bool {
+        self.captured_faces == ALL_FACES_CAPTURED
+    }
+
+    pub(crate) fn face_camera_matrices(&self, face: usize) -> CameraMatrices {
+        cube_face_camera_matrices(self.position, face)
+    }
+}
+
+/// The camera rendering `face` of a cube map centered at `position`, to be stored
+/// with `store_probe_face.hlsl`.
+pub(crate) fn cube_face_camera_matrices(position: Vec3, face: usize) -> CameraMatrices {
+    let rotation = Quat::from_mat3(&Mat3::from_cols_array_2d(&FACE_ROTATIONS[face]));
+
+    (position, rotation).through(&CameraLens {
+        aspect_ratio: 1.0,
+        vertical_fov: 90.0,
+        ..Default::default()
+    })
+}
+
+/// Scene-specific replacements for the sky cubes, captured at runtime.
+///
+/// While `enabled`, the nearest fully captured probe to the camera replaces the sky
+/// in the diffuse and specular image-based lighting, which is also what ray-traced
+/// effects fall back to. The visible sky is unaffected.
+pub struct EnvironmentProbes {
+    pub enabled: bool,
+    pub refresh: EnvironmentProbeRefresh,
+
+    /// Cube faces rendered per frame at most. Each is a full forward-shaded view.
+    /// The `TimeSlicer` of the renderer may allow fewer.
+    pub faces_per_frame: usize,
+
+    pub(crate) probes: Vec<(EnvironmentProbeHandle, EnvironmentProbe)>,
+    next_handle: usize,
+
+    // In capture order
+    pending_faces: VecDeque<(EnvironmentProbeHandle, usize)>,
+}
+
+impl Default for EnvironmentProbes {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refresh: EnvironmentProbeRefresh::OnDemand,
+            faces_per_frame: ENVIRONMENT_PROBE_FACE_COUNT,
+            probes: Default::default(),
+            next_handle: 0,
+            pending_faces: Default::default(),
+        }
+    }
+}
+
+impl EnvironmentProbes {
+    pub(crate) fn add(
+        &mut self,
+        device: &Device,
+        position: Vec3,
+        resolution: u32,
+    ) -> Result<EnvironmentProbeHandle, BackendError> {
+        let cube = device.create_image(
+            ImageDesc::new_cube(vk::Format::R16G16B16A16_SFLOAT, resolution)
+                .usage(vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED),
+            vec![],
+        )?;
+
+        let handle = EnvironmentProbeHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.probes.push((
+            handle,
+            EnvironmentProbe {
+                position,
+                cube: Arc::new(cube),
+                captured_faces: 0,
+            },
+        ));
+        self.request_capture(handle);
+
+        Ok(handle)
+    }
+
+    pub(crate) fn remove(&mut self, probe: EnvironmentProbeHandle) {
+        let index = self
+            .probes
+            .iter()
+            .position(|(handle, _)| *handle == probe)
+            .expect("no such probe");
+        self.probes.swap_remove(index);
+
+        self.pending_faces.retain(|(handle, _)| *handle != probe);
+    }
+
+    pub fn get(&self, probe: EnvironmentProbeHandle) -> &EnvironmentProbe {
+        self.probes
+            .iter()
+            .find(|(handle, _)| *handle == probe)
+            .map(|(_, probe)| probe)
+            .expect("no such probe")
+    }
+
+    pub(crate) fn get_mut(&mut self, probe: EnvironmentProbeHandle) -> &mut EnvironmentProbe {
+        self.probes
+            .iter_mut()
+            .find(|(handle, _)| *handle == probe)
+            .map(|(_, probe)| probe)
+            .expect("no such probe")
+    }
+
+    /// Queues all faces of `probe` which aren't queued yet.
+    pub(crate) fn request_capture(&mut self, probe: EnvironmentProbeHandle) {
+        for face in 0..ENVIRONMENT_PROBE_FACE_COUNT {
+            if !self.pending_faces.contains(&(probe, face)) {
+                self.pending_faces.push_back((probe, face));
+            }
+        }
+    }
+
+    /// How many faces could be captured this frame, within `faces_per_frame`.
+    pub(crate) fn frame_capture_count(&mut self) -> usize {
+        if self.pending_faces.is_empty() && self.refresh == EnvironmentProbeRefresh::RoundRobin {
+            for (handle, _) in &self.probes {
+                for face in 0..ENVIRONMENT_PROBE_FACE_COUNT {
+                    self.pending_faces.push_back((*handle, face));
+                }
+            }
+        }
+
+        self.faces_per_frame.min(self.pending_faces.len())
+    }
+
+    /// The next `count` faces to capture, out of `frame_capture_count`.
+    pub(crate) fn take_frame_captures(
+        &mut self,
+        count: usize,
+    ) -> Vec<(EnvironmentProbeHandle, usize)> {
+        let count = count.min(self.pending_faces.len());
+        self.pending_faces.drain(..count).collect()
+    }
+
+    /// The captured probe to light the scene with, if any.
+    pub(crate) fn nearest_captured(&self, position: Vec3) -> Option<EnvironmentProbeHandle> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.probes
+            .iter()
+            .filter(|(_, probe)| probe.is_captured())
+            .min_by(|(_, a), (_, b)| {
+                a.position
+                    .distance_squared(position)
+                    .partial_cmp(&b.position.distance_squared(position))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(handle, _)| *handle)
+    }
+}
@@ -0,0 +1,79 @@
+//! Freezes the output on a frame, for inspecting converged lighting, and for screenshots.
+//!
+//! While frozen, the camera, the sun, and the frame index driving noise and jitter stay as
+//! they were when the freeze began, and time stops for animations and particles. The frame
+//! is still rendered, so that the debug views can be switched, but the image post-processed
+//! is the one captured on the first frozen frame, rather than that re-render, which would
+//! lose detail as the temporal filters kept folding the same noise in.
+//!
+//! The pre-exposure is held too, so that the captured image stays pre-exposed like the
+//! frame constants claim; changes to the exposure go to post-processing instead.
+
+use glam::Vec3;
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, SimpleRenderPass};
+use rust_shaders_shared::camera::CameraMatrices;
+
+use crate::frame_desc::WorldFrameDesc;
+
+const FROZEN_IMAGE_KEY: &str = "freeze_frame.image";
+
+pub(crate) struct FrozenFrame {
+    camera_matrices: CameraMatrices,
+    sun_direction: Vec3,
+    frame_idx: u32,
+    // Of the captured image, which is kept as a temporal resource. Recaptured if the
+    // image post-processed changes, e.g. with the window size.
+    captured_desc: Option<ImageDesc>,
+}
+
+impl FrozenFrame {
+    /// Freezes on the frame about to be rendered, with the given stochastic frame index.
+    pub fn new(frame_desc: &WorldFrameDesc, frame_idx: u32) -> Self {
+        Self {
+            camera_matrices: frame_desc.camera_matrices,
+            sun_direction: frame_desc.sun_direction,
+            frame_idx,
+            captured_desc: None,
+        }
+    }
+
+    /// `live`, with the camera and sun of the frozen frame. The render extent is kept live.
+    pub fn frame_desc(&self, live: &WorldFrameDesc) -> WorldFrameDesc {
+        WorldFrameDesc {
+            camera_matrices: self.camera_matrices,
+            render_extent: live.render_extent,
+            sun_direction: self.sun_direction,
+        }
+    }
+
+    pub fn frame_idx(&self) -> u32 {
+        self.frame_idx
+    }
+
+    /// The image to post-process in place of `live`: the captured one, or `live` itself
+    /// while it's being captured.
+    pub fn substitute(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        live: rg::Handle<Image>,
+    ) -> rg::Handle<Image> {
+        let desc = live
+            .desc()
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE);
+
+        let mut frozen = rg.get_or_create_temporal(FROZEN_IMAGE_KEY, desc).unwrap();
+
+        if self.captured_desc == Some(desc) {
+            return frozen;
+        }
+
+        SimpleRenderPass::new_compute(rg.add_pass("freeze frame"), "/shaders/copy_color.hlsl")
+            .read(&live)
+            .write(&mut frozen)
+            .dispatch(desc.extent);
+
+        self.captured_desc = Some(desc);
+        live
+    }
+}
@@ -0,0 +1,247 @@
+use std::{intrinsics::transmute, ptr, time::Instant};
+
+use ffx_fsr2::*;
+use glam::{Mat4, Vec2};
+use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::image::*, RenderBackend};
+use kajiya_rg::{self as rg};
+
+pub struct Fsr2Renderer {
+    // Boxed, as FSR2 keeps pointers into the context.
+    context: Box<FfxFsr2Context>,
+    _scratch_buffer: Vec<u8>,
+    pub current_supersample_offset: Vec2,
+    /// Used instead of the measured time between dispatches if set.
+    pub fixed_frame_time_delta_ms: Option<f32>,
+    frame_idx: u32,
+    last_dispatch_time: Option<Instant>,
+}
+
+macro_rules! ffx_checked {
+    ($($t:tt)*) => {
+        assert_eq!(FFX_OK as FfxErrorCode, $($t)*)
+    };
+}
+
+impl Fsr2Renderer {
+    pub fn new(
+        backend: &RenderBackend,
+        input_resolution: [u32; 2],
+        target_resolution: [u32; 2],
+    ) -> Self {
+        unsafe {
+            let physical_device = backend.device.physical_device();
+
+            let scratch_size = ffxFsr2GetScratchMemorySizeVK(transmute(physical_device.raw));
+            let mut scratch_buffer = vec![0u8; scratch_size as usize];
+
+            let mut interface: FfxFsr2Interface = std::mem::zeroed();
+            ffx_checked!(ffxFsr2GetInterfaceVK(
+                &mut interface,
+                scratch_buffer.as_mut_ptr() as _,
+                scratch_size,
+                transmute(physical_device.raw),
+                transmute(physical_device.instance.raw.fp_v1_0().get_device_proc_addr),
+            ));
+
+            log::info!(
+                "Creating an FSR2 context to produce {:?} output from {:?} input",
+                target_resolution,
+                input_resolution
+            );
+
+            let context_desc = FfxFsr2ContextDescription {
+                flags: (FfxFsr2InitializationFlagBits_FFX_FSR2_ENABLE_HIGH_DYNAMIC_RANGE
+                    | FfxFsr2InitializationFlagBits_FFX_FSR2_ENABLE_DEPTH_INVERTED
+                    | FfxFsr2InitializationFlagBits_FFX_FSR2_ENABLE_DEPTH_INFINITE
+                    | FfxFsr2InitializationFlagBits_FFX_FSR2_ENABLE_AUTO_EXPOSURE)
+                    as _,
+                maxRenderSize: FfxDimensions2D {
+                    width: input_resolution[0],
+                    height: input_resolution[1],
+                },
+                displaySize: FfxDimensions2D {
+                    width: target_resolution[0],
+                    height: target_resolution[1],
+                },
+                callbacks: interface,
+                device: ffxGetDeviceVK(transmute(backend.device.raw.handle())),
+                ..std::mem::zeroed()
+            };
+
+            let mut context: Box<FfxFsr2Context> = Box::new(std::mem::zeroed());
+            ffx_checked!(ffxFsr2ContextCreate(&mut *context, &context_desc));
+
+            Self {
+                context,
+                _scratch_buffer: scratch_buffer,
+                current_supersample_offset: Vec2::ZERO,
+                fixed_frame_time_delta_ms: None,
+                frame_idx: 0,
+                last_dispatch_time: None,
+            }
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        input: &rg::Handle<Image>,
+        reprojection_map: &rg::Handle<Image>,
+        depth: &rg::Handle<Image>,
+        view_to_clip: Mat4,
+        output_extent: [u32; 2],
+    ) -> rg::Handle<Image> {
+        let mut output = rg.create(
+            ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, output_extent).usage(
+                vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            ),
+        );
+
+        // As with DLSS, `motionVectorScale` lets us use the reprojection map directly.
+        let motion_vectors = reprojection_map;
+
+        let mut pass = rg.add_pass("fsr2");
+        let input_ref = pass.read(
+            input,
+            AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        );
+        let depth_ref = pass.read(
+            depth,
+            AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        );
+        let motion_vectors_ref = pass.read(
+            motion_vectors,
+            AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+        );
+        let output_ref = pass.write(&mut output, AccessType::AnyShaderWrite);
+
+        let now = Instant::now();
+        let frame_time_delta_ms = self.fixed_frame_time_delta_ms.unwrap_or_else(|| {
+            self.last_dispatch_time
+                .map_or(0.0, |t| (now - t).as_secs_f32() * 1000.0)
+        });
+        self.last_dispatch_time = Some(now);
+
+        // Reverse-Z infinite projection; see `CameraLens::calc_matrices`.
+        let camera_near = view_to_clip.w_axis.z;
+        let camera_fov_vertical = 2.0 * (1.0 / view_to_clip.y_axis.y).atan();
+
+        let input_extent = input.desc().extent_2d();
+        let current_supersample_offset = self.current_supersample_offset;
+        let context: *mut FfxFsr2Context = &mut *self.context;
+        let should_reset = self.frame_idx == 0;
+
+        pass.render_local(move |api| {
+            let cb = api.cb;
+
+            let color = image_to_ffx(
+                api,
+                context,
+                input_ref,
+                ImageViewDesc::default(),
+                FfxResourceStates_FFX_RESOURCE_STATE_COMPUTE_READ,
+            );
+            let depth = image_to_ffx(
+                api,
+                context,
+                depth_ref,
+                ImageViewDesc {
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    ..Default::default()
+                },
+                FfxResourceStates_FFX_RESOURCE_STATE_COMPUTE_READ,
+            );
+            let motion_vectors = image_to_ffx(
+                api,
+                context,
+                motion_vectors_ref,
+                ImageViewDesc::default(),
+                FfxResourceStates_FFX_RESOURCE_STATE_COMPUTE_READ,
+            );
+            let output = image_to_ffx(
+                api,
+                context,
+                output_ref,
+                ImageViewDesc::default(),
+                FfxResourceStates_FFX_RESOURCE_STATE_UNORDERED_ACCESS,
+            );
+
+            unsafe {
+                let dispatch_desc = FfxFsr2DispatchDescription {
+                    commandList: ffxGetCommandListVK(transmute(cb.raw)),
+                    color,
+                    depth,
+                    motionVectors: motion_vectors,
+                    output,
+                    jitterOffset: FfxFloatCoords2D {
+                        x: -current_supersample_offset.x,
+                        y: current_supersample_offset.y,
+                    },
+                    motionVectorScale: FfxFloatCoords2D {
+                        x: input_extent[0] as f32,
+                        y: input_extent[1] as f32,
+                    },
+                    renderSize: FfxDimensions2D {
+                        width: input_extent[0],
+                        height: input_extent[1],
+                    },
+                    enableSharpening: false,
+                    sharpness: 0.0,
+                    frameTimeDelta: frame_time_delta_ms,
+                    preExposure: 1.0,
+                    reset: should_reset,
+                    cameraNear: camera_near,
+                    cameraFar: f32::MAX,
+                    cameraFovAngleVertical: camera_fov_vertical,
+                    // Leaves the optional exposure, reactive, and transparency inputs empty.
+                    ..std::mem::zeroed()
+                };
+
+                ffx_checked!(ffxFsr2ContextDispatch(context, &dispatch_desc));
+            }
+
+            Ok(())
+        });
+
+        self.frame_idx += 1;
+
+        output
+    }
+}
+
+impl Drop for Fsr2Renderer {
+    fn drop(&mut self) {
+        unsafe {
+            ffxFsr2ContextDestroy(&mut *self.context);
+        }
+    }
+}
+
+fn image_to_ffx<ViewType: rg::GpuViewType>(
+    api: &rg::RenderPassApi,
+    context: *mut FfxFsr2Context,
+    image_ref: rg::Ref<Image, ViewType>,
+    view_desc: ImageViewDesc,
+    state: FfxResourceStates,
+) -> FfxResource {
+    let device = api.device();
+    let image = api.resources.image(image_ref);
+
+    let view = image.view(device, &view_desc).unwrap();
+    let view_desc = image.view_desc(&view_desc);
+
+    unsafe {
+        ffxGetTextureResourceVK(
+            context,
+            transmute(image.raw),
+            transmute(view),
+            image.desc.extent[0],
+            image.desc.extent[1],
+            transmute(view_desc.format),
+            ptr::null_mut(),
+            state,
+        )
+    }
+}
@@ -0,0 +1,156 @@
+//! How the G-buffer is packed, and which extra targets the G-buffer pass writes.
+//!
+//! The shaders see the layout through `GBUFFER_LAYOUT_HEADER`, a header generated from it,
+//! which `inc/gbuffer.hlsl` includes, and so does everything packing or unpacking the G-buffer.
+//! When the layout changes, those shaders are rebuilt; until they are, a frame or two
+//! may unpack garbage.
+
+use kajiya_backend::ash::vk;
+
+/// The generated header, served from memory.
+pub const GBUFFER_LAYOUT_HEADER: &str = "/generated/gbuffer_layout.hlsl";
+
+/// Perceptual roughness and metalness share this many bits of the G-buffer.
+const ROUGHNESS_METALNESS_BITS: u32 = 14;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GbufferLayout {
+    pub normal: GbufferNormalEncoding,
+
+    /// Of the bits shared with metalness, those for perceptual roughness; the rest go
+    /// to metalness. Clamped to leave both at least one.
+    pub roughness_bits: u32,
+
+    pub velocity: VelocityPrecision,
+
+    /// Writes the material of each pixel to `GbufferDepth::material_id`: the mesh index
+    /// in the upper 16 bits, and the index of the material within the mesh in the lower.
+    pub material_id: bool,
+}
+
+impl Default for GbufferLayout {
+    fn default() -> Self {
+        Self {
+            normal: GbufferNormalEncoding::default(),
+            roughness_bits: 8,
+            velocity: VelocityPrecision::default(),
+            material_id: false,
+        }
+    }
+}
+
+impl GbufferLayout {
+    pub fn metalness_bits(&self) -> u32 {
+        ROUGHNESS_METALNESS_BITS - self.clamped_roughness_bits()
+    }
+
+    fn clamped_roughness_bits(&self) -> u32 {
+        self.roughness_bits.clamp(1, ROUGHNESS_METALNESS_BITS - 1)
+    }
+
+    pub fn material_id_format() -> vk::Format {
+        vk::Format::R32_UINT
+    }
+
+    /// The contents of `GBUFFER_LAYOUT_HEADER`.
+    pub fn header(&self) -> String {
+        format!(
+            "// Generated from `GbufferLayout`\n\
+            #define GBUFFER_NORMAL_ENCODING {}\n\
+            #define GBUFFER_ROUGHNESS_BITS {}\n\
+            #define GBUFFER_METALNESS_BITS {}\n\
+            #define GBUFFER_MATERIAL_ID {}\n",
+            self.normal.shader_index(),
+            self.clamped_roughness_bits(),
+            self.metalness_bits(),
+            self.material_id as u32,
+        )
+    }
+
+    /// Serves `header` to the shaders, rebuilding those which include it if it changed.
+    pub(crate) fn apply_to_shaders(&self) {
+        kajiya_backend::set_generated_file(GBUFFER_LAYOUT_HEADER, self.header());
+    }
+}
+
+/// How the shading normal is packed into 32 bits of the G-buffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GbufferNormalEncoding {
+    /// 11, 10, and 11 bits of the components.
+    Xyz11_10_11,
+    /// 8 bits per component of the octahedral encoding, leaving 16 bits unused.
+    Octahedral16,
+    /// 16 bits per component of the octahedral encoding; the most precise.
+    Octahedral32,
+}
+
+impl Default for GbufferNormalEncoding {
+    fn default() -> Self {
+        Self::Xyz11_10_11
+    }
+}
+
+impl GbufferNormalEncoding {
+    // Must match `GBUFFER_NORMAL_ENCODING_*` in `inc/gbuffer.hlsl`
+    fn shader_index(self) -> u32 {
+        match self {
+            Self::Xyz11_10_11 => 0,
+            Self::Octahedral16 => 1,
+            Self::Octahedral32 => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for GbufferNormalEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xyz11-10-11" => Ok(Self::Xyz11_10_11),
+            "oct16" => Ok(Self::Octahedral16),
+            "oct32" => Ok(Self::Octahedral32),
+            _ => Err(anyhow::anyhow!(
+                "Unknown G-buffer normal encoding {:?}; expected one of: xyz11-10-11, oct16, oct32",
+                s
+            )),
+        }
+    }
+}
+
+/// Shaders read and write the velocity as `float4` either way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VelocityPrecision {
+    Half,
+    /// Twice the bandwidth; for telling apart reprojection artifacts caused by the precision.
+    Full,
+}
+
+impl Default for VelocityPrecision {
+    fn default() -> Self {
+        Self::Half
+    }
+}
+
+impl VelocityPrecision {
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Half => vk::Format::R16G16B16A16_SFLOAT,
+            Self::Full => vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+}
+
+impl std::str::FromStr for VelocityPrecision {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "half" => Ok(Self::Half),
+            "full" => Ok(Self::Full),
+            _ => Err(anyhow::anyhow!(
+                "Unknown velocity precision {:?}; expected one of: half, full",
+                s
+            )),
+        }
+    }
+}
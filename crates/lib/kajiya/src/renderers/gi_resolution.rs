@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, shader::*},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{
+    bilateral_upsample::{bilateral_upsample, BilateralUpsampleParams, LowResSampling},
+    GbufferDepth,
+};
+
+/// Resolution at which diffuse GI and reflections are computed, relative to the
+/// internal rendering resolution.
+///
+/// `Full` feeds the full G-buffer to the GI passes, which trace at half-res internally,
+/// and resolve at full-res. The reduced settings run all of that on a point-sampled
+/// G-buffer instead, and upsample the results with an edge-aware filter.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GiResolution {
+    Full,
+    Half,
+    Quarter,
+}
+
+impl Default for GiResolution {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
+impl GiResolution {
+    pub fn divisor(self) -> u32 {
+        match self {
+            GiResolution::Full => 1,
+            GiResolution::Half => 2,
+            GiResolution::Quarter => 4,
+        }
+    }
+}
+
+/// Inputs of the GI and reflection passes, at a reduced resolution.
+pub struct DownsampledGiInputs {
+    pub gbuffer_depth: GbufferDepth,
+    pub reprojection_map: rg::Handle<Image>,
+    pub ssao: rg::Handle<Image>,
+    pub prev_radiance: rg::Handle<Image>,
+}
+
+pub fn downsample_gi_inputs(
+    rg: &mut rg::TemporalRenderGraph,
+    render_pass: Arc<RenderPass>,
+    divisor: u32,
+    gbuffer_depth: &GbufferDepth,
+    reprojection_map: &rg::Handle<Image>,
+    ssao: &rg::Handle<Image>,
+    prev_radiance: &rg::Handle<Image>,
+) -> DownsampledGiInputs {
+    let div_extent = [divisor, divisor, 1];
+
+    let mut geometric_normal = rg.create(
+        gbuffer_depth
+            .geometric_normal
+            .desc()
+            .div_extent(div_extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+    let mut gbuffer = rg.create(
+        gbuffer_depth
+            .gbuffer
+            .desc()
+            .div_extent(div_extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+    let mut depth = rg.create(
+        gbuffer_depth
+            .depth
+            .desc()
+            .div_extent(div_extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+    rg::imageops::clear_depth(rg, &mut depth);
+
+    SimpleRenderPass::new_raster(
+        rg.add_pass("gi downsample gbuffer"),
+        render_pass,
+        ShaderSource::hlsl("/shaders/gi_resolution/downsample_gbuffer_vs.hlsl"),
+        ShaderSource::hlsl("/shaders/gi_resolution/downsample_gbuffer_ps.hlsl"),
+        RasterPipelineDesc::builder()
+            .face_cull(false)
+            .push_constants_bytes(std::mem::size_of::<u32>()),
+    )
+    .read(&gbuffer_depth.geometric_normal)
+    .read(&gbuffer_depth.gbuffer)
+    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+    .color_attachment(&mut geometric_normal)
+    .color_attachment(&mut gbuffer)
+    .depth_attachment(&mut depth)
+    .draw(move |api, pipeline| {
+        pipeline.push_constants(
+            api.cb.raw,
+            vk::ShaderStageFlags::ALL_GRAPHICS,
+            0,
+            &divisor.to_ne_bytes(),
+        );
+
+        // Full-screen triangle
+        unsafe {
+            api.device().raw.cmd_draw(api.cb.raw, 3, 1, 0, 0);
+        }
+    });
+
+    let mut reprojection_map_out = rg.create(
+        reprojection_map
+            .desc()
+            .div_extent(div_extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+    let mut ssao_out = rg.create(
+        ssao.desc()
+            .extent(gbuffer.desc().extent)
+            .usage(vk::ImageUsageFlags::empty()),
+    );
+    let mut prev_radiance_out = rg.create(ImageDesc::new_2d(
+        vk::Format::R16G16B16A16_SFLOAT,
+        gbuffer.desc().extent_2d(),
+    ));
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass("gi downsample inputs"),
+        "/shaders/gi_resolution/downsample_inputs.hlsl",
+    )
+    .read(reprojection_map)
+    .read(ssao)
+    .read(prev_radiance)
+    .write(&mut reprojection_map_out)
+    .write(&mut ssao_out)
+    .write(&mut prev_radiance_out)
+    .constants((reprojection_map_out.desc().extent_inv_extent_2d(), divisor))
+    .dispatch(reprojection_map_out.desc().extent);
+
+    DownsampledGiInputs {
+        gbuffer_depth: GbufferDepth::new(geometric_normal, gbuffer, depth),
+        reprojection_map: reprojection_map_out,
+        ssao: ssao_out,
+        prev_radiance: prev_radiance_out,
+    }
+}
+
+/// Edge-aware upsampling of a GI output computed on the inputs from `downsample_gi_inputs`.
+pub fn upsample_gi_output(
+    rg: &mut rg::TemporalRenderGraph,
+    input: &rg::Handle<Image>,
+    gbuffer_depth: &GbufferDepth,
+    params: &BilateralUpsampleParams,
+) -> rg::Handle<Image> {
+    let divisor = gbuffer_depth.gbuffer.desc().extent[0] / input.desc().extent[0].max(1);
+
+    bilateral_upsample(
+        rg,
+        "gi upsample",
+        input,
+        gbuffer_depth,
+        LowResSampling::Centered { divisor },
+        [0.0; 4],
+        params,
+    )
+}
@@ -0,0 +1,149 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::PingPongTemporalResource;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GiTemporalParams {
+    pub enabled: bool,
+
+    /// Frames of history which the diffuse GI converges over.
+    pub diffuse_max_sample_count: f32,
+
+    /// Ditto for reflections, which reproject less accurately, as they move with the surface
+    /// rather than with what they reflect.
+    pub reflection_max_sample_count: f32,
+
+    /// Size of the neighborhood clamp box of the history, in standard deviations.
+    pub clamp_sigma: f32,
+}
+
+impl Default for GiTemporalParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            diffuse_max_sample_count: 16.0,
+            reflection_max_sample_count: 8.0,
+            clamp_sigma: 1.5,
+        }
+    }
+}
+
+/// Accumulates the full-resolution diffuse GI and reflections over time, after their own
+/// denoisers and upsampling, and before they're applied to the image.
+///
+/// This is independent of the anti-aliasing, so the signals stay stable with TAA disabled,
+/// or with FSR 2 and DLSS, which don't know about their noise.
+pub struct GiTemporalRenderer {
+    diffuse: GiAccumulation,
+    reflections: GiAccumulation,
+}
+
+impl Default for GiTemporalRenderer {
+    fn default() -> Self {
+        Self {
+            diffuse: GiAccumulation::new("gi_temporal.diffuse"),
+            reflections: GiAccumulation::new("gi_temporal.reflections"),
+        }
+    }
+}
+
+impl GiTemporalRenderer {
+    pub fn accumulate_diffuse(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        input: &rg::Handle<Image>,
+        reprojection_map: &rg::Handle<Image>,
+        reactive_mask: &rg::Handle<Image>,
+        params: &GiTemporalParams,
+    ) -> rg::Handle<Image> {
+        self.diffuse.accumulate(
+            rg,
+            "gi temporal diffuse",
+            input,
+            reprojection_map,
+            reactive_mask,
+            params.diffuse_max_sample_count,
+            params.clamp_sigma,
+        )
+    }
+
+    pub fn accumulate_reflections(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        input: &rg::Handle<Image>,
+        reprojection_map: &rg::Handle<Image>,
+        reactive_mask: &rg::Handle<Image>,
+        params: &GiTemporalParams,
+    ) -> rg::Handle<Image> {
+        self.reflections.accumulate(
+            rg,
+            "gi temporal reflections",
+            input,
+            reprojection_map,
+            reactive_mask,
+            params.reflection_max_sample_count,
+            params.clamp_sigma,
+        )
+    }
+}
+
+struct GiAccumulation {
+    history_tex: PingPongTemporalResource,
+    moments_tex: PingPongTemporalResource,
+}
+
+impl GiAccumulation {
+    fn new(name: &str) -> Self {
+        Self {
+            history_tex: PingPongTemporalResource::new(&format!("{}.history", name)),
+            moments_tex: PingPongTemporalResource::new(&format!("{}.moments", name)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn accumulate(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        pass_name: &str,
+        input: &rg::Handle<Image>,
+        reprojection_map: &rg::Handle<Image>,
+        reactive_mask: &rg::Handle<Image>,
+        max_sample_count: f32,
+        clamp_sigma: f32,
+    ) -> rg::Handle<Image> {
+        let extent = input.desc().extent_2d();
+        let usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE;
+
+        let (mut output_tex, history_tex) = self.history_tex.get_output_and_history(
+            rg,
+            ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, extent).usage(usage),
+        );
+
+        let (mut moments_output_tex, moments_history_tex) =
+            self.moments_tex.get_output_and_history(
+                rg,
+                ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, extent).usage(usage),
+            );
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass(pass_name),
+            "/shaders/gi_temporal/accumulate.hlsl",
+        )
+        .read(input)
+        .read(&history_tex)
+        .read(&moments_history_tex)
+        .read(reprojection_map)
+        .read(reactive_mask)
+        .write(&mut output_tex)
+        .write(&mut moments_output_tex)
+        .constants((
+            output_tex.desc().extent_inv_extent_2d(),
+            max_sample_count.max(0.0),
+            clamp_sigma.max(0.0),
+        ))
+        .dispatch(output_tex.desc().extent);
+
+        output_tex
+    }
+}
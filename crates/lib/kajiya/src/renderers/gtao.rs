@@ -0,0 +1,119 @@
+use super::{
+    bilateral_upsample::{bilateral_upsample, BilateralUpsampleParams, LowResSampling},
+    GbufferDepth, PingPongTemporalResource,
+};
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+/// Number of horizon slices and steps per slice direction, traded for noise.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GtaoQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl GtaoQuality {
+    fn slice_and_step_count(self) -> (u32, u32) {
+        match self {
+            GtaoQuality::Low => (1, 4),
+            GtaoQuality::Medium => (2, 6),
+            GtaoQuality::High => (4, 8),
+        }
+    }
+}
+
+/// Ground-truth ambient occlusion, as an alternative to the SSGI-based one.
+///
+/// Outputs the visibility in the red channel, like `SsgiRenderer`, and the view-space
+/// bent normal in the remaining three, for the GI resolve to narrow its lobe with.
+pub struct GtaoRenderer {
+    temporal_tex: PingPongTemporalResource,
+    pub quality: GtaoQuality,
+
+    /// World-space radius of the occlusion kernel.
+    pub radius: f32,
+}
+
+impl Default for GtaoRenderer {
+    fn default() -> Self {
+        Self {
+            temporal_tex: PingPongTemporalResource::new("gtao"),
+            quality: GtaoQuality::Medium,
+            radius: 1.0,
+        }
+    }
+}
+
+const TEX_FMT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+impl GtaoRenderer {
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        reprojection_map: &rg::Handle<Image>,
+        bindless_descriptor_set: vk::DescriptorSet,
+        upsample_params: &BilateralUpsampleParams,
+    ) -> rg::ReadOnlyHandle<Image> {
+        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
+        let half_view_normal_tex = gbuffer_depth.half_view_normal(rg);
+        let half_depth_tex = gbuffer_depth.half_depth(rg);
+
+        let mut gtao_tex = rg.create(
+            gbuffer_desc
+                .usage(vk::ImageUsageFlags::empty())
+                .half_res()
+                .format(TEX_FMT),
+        );
+
+        let (slice_count, step_count) = self.quality.slice_and_step_count();
+
+        SimpleRenderPass::new_compute(rg.add_pass("gtao"), "/shaders/gtao/gtao.hlsl")
+            .read(&*half_depth_tex)
+            .read(&*half_view_normal_tex)
+            .write(&mut gtao_tex)
+            .constants((
+                gbuffer_desc.extent_inv_extent_2d(),
+                gtao_tex.desc().extent_inv_extent_2d(),
+                slice_count,
+                step_count,
+                self.radius,
+            ))
+            .raw_descriptor_set(1, bindless_descriptor_set)
+            .dispatch(gtao_tex.desc().extent);
+
+        // Unoccluded, with the bent normal facing the camera
+        let upsampled_tex = bilateral_upsample(
+            rg,
+            "gtao upsample",
+            &gtao_tex,
+            gbuffer_depth,
+            LowResSampling::HalfResSubsample,
+            [1.0, 0.0, 0.0, 1.0],
+            upsample_params,
+        );
+
+        let (mut history_output_tex, history_tex) = self.temporal_tex.get_output_and_history(
+            rg,
+            ImageDesc::new_2d(TEX_FMT, gbuffer_desc.extent_2d())
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+        );
+
+        let mut filtered_output_tex = rg.create(gbuffer_desc.format(TEX_FMT));
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("gtao temporal"),
+            "/shaders/gtao/temporal_filter.hlsl",
+        )
+        .read(&upsampled_tex)
+        .read(&history_tex)
+        .read(reprojection_map)
+        .write(&mut filtered_output_tex)
+        .write(&mut history_output_tex)
+        .constants(history_output_tex.desc().extent_inv_extent_2d())
+        .dispatch(history_output_tex.desc().extent);
+
+        filtered_output_tex.into()
+    }
+}
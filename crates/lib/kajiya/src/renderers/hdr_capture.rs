@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{mpsc, Arc},
+};
+
+use glam::Mat4;
+use kajiya_backend::{
+    ash::vk,
+    vk_sync::AccessType,
+    vulkan::{
+        buffer::{Buffer, BufferDesc},
+        device::Device,
+        image::*,
+    },
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use crate::camera::PhysicalCamera;
+
+use super::READBACK_FRAME_LATENCY;
+
+/// The point in the pipeline an HDR capture is taken at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HdrCaptureSource {
+    /// Input to post-processing, after temporal anti-aliasing and upsampling,
+    /// depth of field, and motion blur.
+    PreTonemap,
+
+    /// Lit scene at the internal rendering resolution, before temporal anti-aliasing.
+    /// Same as `PreTonemap` in `RenderMode::Reference`.
+    PreTaa,
+
+    /// Output of post-processing, as displayed, but still linear, without the UI,
+    /// and not sRGB-encoded. The exposure is baked in.
+    PostTonemap,
+}
+
+impl Default for HdrCaptureSource {
+    fn default() -> Self {
+        Self::PreTonemap
+    }
+}
+
+/// An HDR capture kept in memory rather than written to a file.
+pub struct HdrCapture {
+    pub extent: [u32; 2],
+    /// Linear radiance, row by row, with the pre-exposure divided out.
+    /// In `RenderMode::Reference`, alpha holds the accumulated sample count.
+    pub pixels: Vec<[f32; 4]>,
+    /// Multiplier which takes `pixels` to what's fed to the tonemapper.
+    pub exposure: f32,
+    pub frame_idx: u32,
+}
+
+enum HdrCaptureDestination {
+    File {
+        path: PathBuf,
+        // Told when the file is written, or fails to be.
+        written: mpsc::Sender<anyhow::Result<()>>,
+    },
+    Memory,
+}
+
+/// State of the frame stored in the EXR header of a capture.
+#[derive(Clone, Copy)]
+pub(crate) struct HdrCaptureMetadata {
+    pub world_to_view: Mat4,
+    pub view_to_clip: Mat4,
+    pub frame_idx: u32,
+
+    /// Already applied to the captured buffer; divided out on readback.
+    pub pre_exposure: f32,
+
+    /// Multiplier which takes the saved radiance to what's fed to the tonemapper.
+    pub exposure: f32,
+
+    /// Only if enabled
+    pub physical_camera: Option<PhysicalCamera>,
+
+    /// The buffer is the path tracer's accumulator, with the sample count in alpha.
+    pub is_reference: bool,
+}
+
+struct PendingCapture {
+    destination: HdrCaptureDestination,
+    buffer: Arc<Buffer>,
+    extent: [u32; 2],
+    metadata: HdrCaptureMetadata,
+}
+
+/// Saves linear HDR color buffers to EXR files, with the camera and exposure
+/// in the header, for offline comparisons and grading.
+#[derive(Default)]
+pub(crate) struct HdrCaptures {
+    requested: Option<(HdrCaptureDestination, HdrCaptureSource)>,
+    pending: Vec<PendingCapture>,
+    finished_in_memory: Vec<HdrCapture>,
+}
+
+impl HdrCaptures {
+    /// The receiver gets the outcome of writing the file. It's disconnected without one
+    /// if the capture is dropped, e.g. superseded by another request before the next frame.
+    pub(crate) fn request(
+        &mut self,
+        path: PathBuf,
+        source: HdrCaptureSource,
+    ) -> mpsc::Receiver<anyhow::Result<()>> {
+        let (written, receiver) = mpsc::channel();
+        self.requested = Some((HdrCaptureDestination::File { path, written }, source));
+        receiver
+    }
+
+    pub(crate) fn request_in_memory(&mut self, source: HdrCaptureSource) {
+        self.requested = Some((HdrCaptureDestination::Memory, source));
+    }
+
+    pub(crate) fn take_in_memory(&mut self) -> Option<HdrCapture> {
+        if self.finished_in_memory.is_empty() {
+            None
+        } else {
+            Some(self.finished_in_memory.remove(0))
+        }
+    }
+
+    pub(crate) fn is_requested(&self, source: HdrCaptureSource) -> bool {
+        matches!(self.requested, Some((_, requested)) if requested == source)
+    }
+
+    /// Copies `img` to the CPU if a capture was requested since the last call.
+    pub(crate) fn record_readback(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        img: &rg::Handle<Image>,
+        metadata: HdrCaptureMetadata,
+    ) {
+        let destination = if let Some((destination, _)) = self.requested.take() {
+            destination
+        } else {
+            return;
+        };
+
+        let extent = img.desc().extent_2d();
+        let buffer = match rg.device().create_buffer(
+            BufferDesc::new_gpu_to_cpu(
+                (extent[0] * extent[1]) as usize * std::mem::size_of::<[f32; 4]>(),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            ),
+            "hdr capture readback",
+            None,
+        ) {
+            Ok(buffer) => Arc::new(buffer),
+            Err(err) => {
+                log::error!(
+                    "Could not create the HDR capture readback buffer: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let mut readback_buf = rg.import(buffer.clone(), AccessType::Nothing);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("hdr capture readback"),
+            "/shaders/copy_color_to_buffer.hlsl",
+        )
+        .read(img)
+        .write(&mut readback_buf)
+        .constants(extent)
+        .dispatch(img.desc().extent);
+
+        self.pending.push(PendingCapture {
+            destination,
+            buffer,
+            extent,
+            metadata,
+        });
+    }
+
+    /// Writes out the captures whose readbacks the GPU has finished, or keeps them
+    /// for `take_in_memory`. The encoding happens on a separate thread.
+    pub(crate) fn write_finished(&mut self, device: &Device, frame_idx: u32) {
+        let (finished, pending) =
+            std::mem::take(&mut self.pending)
+                .into_iter()
+                .partition(|capture| {
+                    frame_idx.wrapping_sub(capture.metadata.frame_idx) >= READBACK_FRAME_LATENCY
+                });
+        self.pending = pending;
+
+        for capture in finished {
+            let pixels: Option<Vec<[f32; 4]>> = capture
+                .buffer
+                .allocation
+                .mapped_slice()
+                .map(|src| bytemuck::checked::cast_slice::<u8, [f32; 4]>(src).to_vec());
+
+            // The render graph has released its reference by now.
+            if let Ok(buffer) = Arc::try_unwrap(capture.buffer) {
+                device.immediate_destroy_buffer(buffer);
+            }
+
+            let mut pixels = if let Some(pixels) = pixels {
+                pixels
+            } else {
+                log::error!("The HDR capture readback buffer is not host-visible");
+                continue;
+            };
+
+            let inv_pre_exposure = 1.0 / capture.metadata.pre_exposure.max(1e-10);
+            for px in &mut pixels {
+                for c in &mut px[0..3] {
+                    *c *= inv_pre_exposure;
+                }
+            }
+
+            let PendingCapture {
+                destination,
+                extent,
+                metadata,
+                ..
+            } = capture;
+
+            let (path, written) = match destination {
+                HdrCaptureDestination::File { path, written } => (path, written),
+                HdrCaptureDestination::Memory => {
+                    self.finished_in_memory.push(HdrCapture {
+                        extent,
+                        pixels,
+                        exposure: metadata.exposure,
+                        frame_idx: metadata.frame_idx,
+                    });
+                    continue;
+                }
+            };
+
+            std::thread::spawn(move || {
+                let result = match write_exr(&path, extent, &pixels, &metadata) {
+                    Ok(()) => {
+                        log::info!("Saved an HDR capture to {:?}", path);
+                        Ok(())
+                    }
+                    Err(err) => {
+                        log::error!("Failed to save {:?}: {}", path, err);
+                        Err(anyhow::anyhow!("Failed to save {:?}: {}", path, err))
+                    }
+                };
+
+                // Nobody may be waiting for it.
+                let _ = written.send(result);
+            });
+        }
+    }
+}
+
+fn write_exr(
+    path: &std::path::Path,
+    extent: [u32; 2],
+    pixels: &[[f32; 4]],
+    metadata: &HdrCaptureMetadata,
+) -> exr::error::UnitResult {
+    use exr::prelude::*;
+
+    let width = extent[0] as usize;
+    let height = extent[1] as usize;
+
+    let mut other: HashMap<Text, AttributeValue> = HashMap::new();
+    let mut insert = |name: &str, value: AttributeValue| {
+        other.insert(Text::from(name), value);
+    };
+
+    // Column-major, in kajiya's right-handed convention, looking down -Z.
+    insert(
+        "kajiya:world_to_view",
+        AttributeValue::Matrix4x4(metadata.world_to_view.to_cols_array()),
+    );
+    insert(
+        "kajiya:view_to_clip",
+        AttributeValue::Matrix4x4(metadata.view_to_clip.to_cols_array()),
+    );
+    insert(
+        "kajiya:frame_index",
+        AttributeValue::I32(metadata.frame_idx as i32),
+    );
+    insert(
+        "kajiya:exposure_multiplier",
+        AttributeValue::F32(metadata.exposure),
+    );
+
+    if metadata.is_reference {
+        // The alpha channel holds the accumulated sample count
+        let sample_count = pixels.first().map_or(0.0, |px| px[3]);
+        insert("kajiya:sample_count", AttributeValue::F32(sample_count));
+    }
+
+    let physical_camera = metadata.physical_camera;
+    let attributes = LayerAttributes {
+        software_name: Some(Text::from("kajiya")),
+        vertical_field_of_view: Some(
+            (2.0 * (1.0 / metadata.view_to_clip.y_axis.y).atan()).to_degrees(),
+        ),
+        aperture: physical_camera.map(|camera| camera.f_stop),
+        exposure: physical_camera.map(|camera| camera.shutter_time_seconds),
+        iso_speed: physical_camera.map(|camera| camera.iso),
+        other,
+        ..LayerAttributes::default()
+    };
+
+    let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+        let [r, g, b, _] = pixels[y * width + x];
+        (r, g, b)
+    });
+
+    Image::from_layer(Layer::new(
+        (width, height),
+        attributes,
+        Encoding::FAST_LOSSLESS,
+        channels,
+    ))
+    .write()
+    .to_file(path)
+}
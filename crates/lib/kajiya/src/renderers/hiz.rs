@@ -0,0 +1,125 @@
+//! The hierarchical depth (Hi-Z) pyramid of the G-buffer depth, shared by the techniques
+//! which need conservative depth bounds over screen regions, such as occlusion culling.
+//!
+//! The pyramid is built once per frame, after the G-buffer, and kept across frames,
+//! so that passes running before the G-buffer can use the previous frame's.
+
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, SimpleRenderPass};
+
+/// Min and max depth pyramid of a depth buffer.
+///
+/// Each texel holds the minimum depth in `x`, and the maximum in `y`, of the depth buffer
+/// texels it covers. Depth is reversed, so these are the farthest and the nearest surfaces.
+///
+/// The pyramid is padded to a power-of-two size, so that every texel of a level covers exactly
+/// 2x2 texels of the one below it. Mip 0 is half the padded size of the depth buffer, and
+/// covers 2x2 of its texels. Padding texels hold `(1, 0)`, which bounds nothing.
+pub struct HizPyramid {
+    pub tex: rg::Handle<Image>,
+
+    /// Extent of the depth buffer the pyramid is built from.
+    pub depth_extent: [u32; 2],
+}
+
+impl HizPyramid {
+    pub fn mip_count(&self) -> u32 {
+        self.tex.desc().mip_levels as u32
+    }
+}
+
+fn hiz_desc(depth_extent: [u32; 2]) -> ImageDesc {
+    let half_pow2 = |extent: u32| (extent.next_power_of_two() / 2).max(1);
+
+    ImageDesc::new_2d(
+        vk::Format::R32G32_SFLOAT,
+        [half_pow2(depth_extent[0]), half_pow2(depth_extent[1])],
+    )
+    .all_mip_levels()
+    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
+}
+
+/// Keeps the pyramid of the G-buffer depth across frames.
+#[derive(Default)]
+pub struct HizRenderer {
+    /// Frame index and depth extent which the stored pyramid can be used for.
+    history_valid_for: Option<(u32, [u32; 2])>,
+}
+
+impl HizRenderer {
+    /// The stored pyramid is only valid for the next frame; skipped frames invalidate it.
+    pub fn invalidate_history(&mut self) {
+        self.history_valid_for = None;
+    }
+
+    /// The pyramid for this frame's depth, to be built by `build` once the depth is rendered.
+    ///
+    /// Until then, it holds the previous frame's depth, if the returned flag is set.
+    pub fn prepare(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        depth_extent: [u32; 2],
+        frame_idx: u32,
+    ) -> (HizPyramid, bool) {
+        let tex = rg
+            .get_or_create_temporal("hiz", hiz_desc(depth_extent))
+            .unwrap();
+        let history_valid = self.history_valid_for == Some((frame_idx, depth_extent));
+
+        (HizPyramid { tex, depth_extent }, history_valid)
+    }
+
+    /// Reduces `depth` into `pyramid`.
+    pub fn build(
+        &mut self,
+        rg: &mut rg::RenderGraph,
+        pyramid: &mut HizPyramid,
+        depth: &rg::Handle<Image>,
+        frame_idx: u32,
+    ) {
+        assert_eq!(depth.desc().extent_2d(), pyramid.depth_extent);
+
+        build_hiz_pyramid(rg, depth, &mut pyramid.tex);
+        self.history_valid_for = Some((frame_idx.wrapping_add(1), pyramid.depth_extent));
+    }
+}
+
+/// Reduces `depth` into all the levels of `hiz`, which must be sized as described on `HizPyramid`.
+pub fn build_hiz_pyramid(
+    rg: &mut rg::RenderGraph,
+    depth: &rg::Handle<Image>,
+    hiz: &mut rg::Handle<Image>,
+) {
+    let hiz_desc = *hiz.desc();
+
+    SimpleRenderPass::new_compute(rg.add_pass("hiz 0"), "/shaders/hiz/reduce_depth.hlsl")
+        .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
+        .write_view(
+            hiz,
+            ImageViewDesc::builder()
+                .base_mip_level(0)
+                .level_count(Some(1)),
+        )
+        .constants(depth.desc().extent_2d())
+        .dispatch(hiz_desc.extent);
+
+    for mip in 1..hiz_desc.mip_levels as u32 {
+        SimpleRenderPass::new_compute(
+            rg.add_pass(&format!("hiz {}", mip)),
+            "/shaders/hiz/downsample.hlsl",
+        )
+        .read_view(
+            hiz,
+            ImageViewDesc::builder()
+                .base_mip_level(mip - 1)
+                .level_count(Some(1)),
+        )
+        .write_view(
+            hiz,
+            ImageViewDesc::builder()
+                .base_mip_level(mip)
+                .level_count(Some(1)),
+        )
+        .dispatch(hiz_desc.div_extent([1 << mip, 1 << mip, 1]).extent);
+    }
+}
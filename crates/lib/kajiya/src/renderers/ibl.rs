@@ -60,8 +60,9 @@ impl IblRenderer {
 
         if let Some(texture) = self.texture.clone() {
             let width = 1024u32;
-            let mut cube_tex =
-                rg.create(ImageDesc::new_cube(vk::Format::R16G16B16A16_SFLOAT, width));
+            let mut cube_tex = rg.create(
+                ImageDesc::new_cube(vk::Format::R16G16B16A16_SFLOAT, width).all_mip_levels(),
+            );
 
             let texture = rg.import(
                 texture,
@@ -72,11 +73,38 @@ impl IblRenderer {
                 .read(&texture)
                 .write_view(
                     &mut cube_tex,
-                    ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+                    ImageViewDesc::builder()
+                        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                        .level_count(Some(1)),
                 )
                 .constants(width)
                 .dispatch([width, width, 6]);
 
+            // Mips are needed for filtered importance sampling in `prefilter_specular_cube`
+            for target_mip in 1..(cube_tex.desc().mip_levels as u32) {
+                let mip_width = (width >> target_mip).max(1);
+
+                SimpleRenderPass::new_compute(
+                    rg.add_pass(&format!("ibl cube mip{}", target_mip)),
+                    "/shaders/ibl/downsample_cube.hlsl",
+                )
+                .read_view(
+                    &cube_tex,
+                    ImageViewDesc::builder()
+                        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                        .base_mip_level(target_mip - 1)
+                        .level_count(Some(1)),
+                )
+                .write_view(
+                    &mut cube_tex,
+                    ImageViewDesc::builder()
+                        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                        .base_mip_level(target_mip)
+                        .level_count(Some(1)),
+                )
+                .dispatch([mip_width, mip_width, 6]);
+            }
+
             Some(cube_tex.into())
         } else {
             None
@@ -84,6 +112,87 @@ impl IblRenderer {
     }
 }
 
+pub const PREFILTERED_CUBE_WIDTH: u32 = 128;
+pub const PREFILTERED_CUBE_MIP_COUNT: u32 = 6;
+
+/// Pre-filters a sky cube for specular image-based lighting.
+///
+/// Every mip of the output is convolved with GGX at increasing roughness:
+/// mip `i` corresponds to perceptual roughness `i / (PREFILTERED_CUBE_MIP_COUNT - 1)`.
+pub fn prefilter_specular_cube(
+    rg: &mut rg::RenderGraph,
+    input: &rg::Handle<Image>,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(prefiltered_cube_desc());
+    prefilter_specular_cube_mips(rg, input, &mut output, 0..PREFILTERED_CUBE_MIP_COUNT);
+    output
+}
+
+pub fn prefiltered_cube_desc() -> ImageDesc {
+    ImageDesc::new_cube(vk::Format::R16G16B16A16_SFLOAT, PREFILTERED_CUBE_WIDTH)
+        .mip_levels(PREFILTERED_CUBE_MIP_COUNT as _)
+}
+
+/// Like `prefilter_specular_cube`, but only refreshes `mips` of an existing `output`,
+/// such as when spreading the work over several frames.
+pub fn prefilter_specular_cube_mips(
+    rg: &mut rg::RenderGraph,
+    input: &rg::Handle<Image>,
+    output: &mut rg::Handle<Image>,
+    mips: impl Iterator<Item = u32>,
+) {
+    for mip in mips {
+        let mip_width = PREFILTERED_CUBE_WIDTH >> mip;
+        let perceptual_roughness = mip as f32 / (PREFILTERED_CUBE_MIP_COUNT - 1) as f32;
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass(&format!("ibl prefilter mip{}", mip)),
+            "/shaders/ibl/prefilter_cube.hlsl",
+        )
+        .read(input)
+        .write_view(
+            output,
+            ImageViewDesc::builder()
+                .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                .base_mip_level(mip)
+                .level_count(Some(1)),
+        )
+        .constants((mip_width, perceptual_roughness * perceptual_roughness))
+        .dispatch([mip_width, mip_width, 6]);
+    }
+}
+
+/// Which mips of a pre-filtered cube to refresh when that's spread over frames: any never
+/// filtered yet, and the next ones round robin.
+#[derive(Default)]
+pub(crate) struct PrefilterMipCycle {
+    next_mip: u32,
+    // Bit per mip
+    filled_mips: u32,
+}
+
+impl PrefilterMipCycle {
+    /// The mips to filter this frame; `count` of them, plus those never filtered.
+    pub fn take(&mut self, count: usize) -> Vec<u32> {
+        let mut mips: Vec<u32> = (0..PREFILTERED_CUBE_MIP_COUNT)
+            .filter(|mip| self.filled_mips & (1 << mip) == 0)
+            .collect();
+
+        for _ in 0..count.min(PREFILTERED_CUBE_MIP_COUNT as usize) {
+            let mip = self.next_mip;
+            self.next_mip = (self.next_mip + 1) % PREFILTERED_CUBE_MIP_COUNT;
+
+            if !mips.contains(&mip) {
+                mips.push(mip);
+            }
+        }
+
+        self.filled_mips = (1 << PREFILTERED_CUBE_MIP_COUNT) - 1;
+        mips.sort_unstable();
+        mips
+    }
+}
+
 pub struct ImageRgba16f {
     pub size: [u32; 2],
     pub data: Vec<f16>,
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, sync::Arc};
+
+use kajiya_backend::{
+    ash::vk,
+    vk_sync::AccessType,
+    vulkan::{
+        buffer::{Buffer, BufferDesc},
+        device::Device,
+        image::*,
+    },
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{buffer_writes::clear_buffer, READBACK_FRAME_LATENCY};
+
+/// Must match `IMAGE_STATS_BIN_COUNT` in `image_stats.hlsl`.
+pub const IMAGE_STATS_BIN_COUNT: usize = 256;
+
+// Of the reduction; each group writes one partial result.
+const GROUP_SIZE: u32 = 16;
+
+/// Key under which the world renderer records the lit scene before temporal anti-aliasing,
+/// at the internal rendering resolution, while watched. Only in `RenderMode::Standard`.
+pub const SCENE_IMAGE_STATS: &str = "scene";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageStatsChannel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Rec. 709 luminance of the color channels.
+    Luminance,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ImageStatsParams {
+    pub channel: ImageStatsChannel,
+
+    /// Divides the pre-exposure out of the values, as needed for radiance.
+    pub remove_pre_exposure: bool,
+
+    /// Bins the log2 of the values, rather than the values themselves.
+    pub log_histogram: bool,
+
+    /// Covered by the histogram bins, evenly; values outside land in the first or last bin.
+    pub histogram_range: [f32; 2],
+}
+
+impl Default for ImageStatsParams {
+    fn default() -> Self {
+        // Same as the exposure histogram.
+        Self {
+            channel: ImageStatsChannel::Luminance,
+            remove_pre_exposure: true,
+            log_histogram: true,
+            histogram_range: [-16.0, 16.0],
+        }
+    }
+}
+
+/// Statistics of one channel of an image, over its pixels with finite values.
+#[derive(Clone, Debug)]
+pub struct ImageStats {
+    /// Of the frame the image was rendered in.
+    pub frame_idx: u32,
+    pub params: ImageStatsParams,
+
+    /// With finite values, which the other statistics are of.
+    pub pixel_count: u64,
+    /// NaN and infinite ones.
+    pub non_finite_count: u64,
+
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+
+    /// Pixel counts in `IMAGE_STATS_BIN_COUNT` bins over `params.histogram_range`.
+    pub histogram: Vec<u32>,
+}
+
+impl ImageStats {
+    /// The value below which `fraction` of the pixels are, estimated from the histogram,
+    /// in the units of `params.histogram_range`.
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        let [range_min, range_max] = self.params.histogram_range;
+        let total: u64 = self.histogram.iter().map(|&count| count as u64).sum();
+        let target = (fraction.clamp(0.0, 1.0) as f64 * total as f64) as u64;
+
+        let mut below = 0u64;
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            below += count as u64;
+            if below > target {
+                let t = (bin as f32 + 0.5) / IMAGE_STATS_BIN_COUNT as f32;
+                return range_min + (range_max - range_min) * t;
+            }
+        }
+
+        range_max
+    }
+}
+
+struct PendingStats {
+    key: &'static str,
+    params: ImageStatsParams,
+    buffer: Arc<Buffer>,
+    group_count: usize,
+    total_pixel_count: u64,
+    frame_idx: u32,
+}
+
+/// Reduces graph images to their min, max, mean, and a histogram on the GPU,
+/// and reads the results back a few frames later, for debugging HDR ranges,
+/// and for algorithms which adapt to the image content.
+///
+/// Images are either recorded directly with `record`, or at the points in the pipeline
+/// which the world renderer provides, such as `SCENE_IMAGE_STATS`, once watched.
+/// Results replace the previous ones under the same key.
+#[derive(Default)]
+pub struct ImageStatsReadback {
+    watched: HashMap<&'static str, ImageStatsParams>,
+    pending: Vec<PendingStats>,
+    latest: HashMap<&'static str, ImageStats>,
+}
+
+impl ImageStatsReadback {
+    /// Records the image under `key` every frame, until `unwatch`.
+    pub fn watch(&mut self, key: &'static str, params: ImageStatsParams) {
+        self.watched.insert(key, params);
+    }
+
+    pub fn unwatch(&mut self, key: &'static str) {
+        self.watched.remove(key);
+        self.latest.remove(key);
+    }
+
+    pub fn is_watched(&self, key: &str) -> bool {
+        self.watched.contains_key(key)
+    }
+
+    /// The most recent results under `key`, lagging the rendered frames by
+    /// `READBACK_FRAME_LATENCY`.
+    pub fn latest(&self, key: &str) -> Option<&ImageStats> {
+        self.latest.get(key)
+    }
+
+    /// Records `img` under `key` if watched.
+    pub(crate) fn record_watched(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        key: &'static str,
+        img: &rg::Handle<Image>,
+        frame_idx: u32,
+    ) {
+        if let Some(params) = self.watched.get(key).copied() {
+            self.record(rg, key, img, params, frame_idx);
+        }
+    }
+
+    /// Computes the statistics of the first mip of a 2D color image.
+    pub fn record(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        key: &'static str,
+        img: &rg::Handle<Image>,
+        params: ImageStatsParams,
+        frame_idx: u32,
+    ) {
+        let extent = img.desc().extent_2d();
+        let group_count_x = (extent[0] + GROUP_SIZE - 1) / GROUP_SIZE;
+        let group_count_y = (extent[1] + GROUP_SIZE - 1) / GROUP_SIZE;
+        let group_count = (group_count_x * group_count_y) as usize;
+
+        // The histogram bins, followed by (min, max, sum, count) per group.
+        let buffer = match rg.device().create_buffer(
+            BufferDesc::new_gpu_to_cpu(
+                IMAGE_STATS_BIN_COUNT * std::mem::size_of::<u32>()
+                    + group_count * std::mem::size_of::<[f32; 4]>(),
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            ),
+            "image stats readback",
+            None,
+        ) {
+            Ok(buffer) => Arc::new(buffer),
+            Err(err) => {
+                log::error!(
+                    "Could not create the image stats readback buffer: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let mut readback_buf = rg.import(buffer.clone(), AccessType::Nothing);
+        clear_buffer(rg, "clear image stats", &mut readback_buf);
+
+        let [range_min, range_max] = params.histogram_range;
+        let flags = params.remove_pre_exposure as u32 | (params.log_histogram as u32) << 1;
+
+        SimpleRenderPass::new_compute(rg.add_pass("image stats"), "/shaders/image_stats.hlsl")
+            .read(img)
+            .write(&mut readback_buf)
+            .constants((
+                extent,
+                params.channel as u32,
+                flags,
+                range_min,
+                range_max.max(range_min + 1e-5),
+                group_count_x,
+            ))
+            .dispatch(img.desc().extent);
+
+        self.pending.push(PendingStats {
+            key,
+            params,
+            buffer,
+            group_count,
+            total_pixel_count: extent[0] as u64 * extent[1] as u64,
+            frame_idx,
+        });
+    }
+
+    /// Reduces the partial results of the readbacks the GPU has finished.
+    pub(crate) fn read_finished(&mut self, device: &Device, frame_idx: u32) {
+        let (finished, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|stats| frame_idx.wrapping_sub(stats.frame_idx) >= READBACK_FRAME_LATENCY);
+        self.pending = pending;
+
+        for stats in finished {
+            let contents: Option<Vec<u32>> = stats
+                .buffer
+                .allocation
+                .mapped_slice()
+                .map(|src| bytemuck::checked::cast_slice::<u8, u32>(src).to_vec());
+
+            // The render graph has released its reference by now.
+            if let Ok(buffer) = Arc::try_unwrap(stats.buffer) {
+                device.immediate_destroy_buffer(buffer);
+            }
+
+            let contents = if let Some(contents) = contents {
+                contents
+            } else {
+                log::error!("The image stats readback buffer is not host-visible");
+                continue;
+            };
+
+            let (histogram, partials) = contents.split_at(IMAGE_STATS_BIN_COUNT);
+
+            let mut min = f32::MAX;
+            let mut max = f32::MIN;
+            let mut sum = 0.0f64;
+            let mut pixel_count = 0u64;
+
+            for partial in partials.chunks_exact(4).take(stats.group_count) {
+                let count = f32::from_bits(partial[3]) as u64;
+                if count == 0 {
+                    continue;
+                }
+
+                min = min.min(f32::from_bits(partial[0]));
+                max = max.max(f32::from_bits(partial[1]));
+                sum += f32::from_bits(partial[2]) as f64;
+                pixel_count += count;
+            }
+
+            if pixel_count == 0 {
+                min = 0.0;
+                max = 0.0;
+            }
+
+            self.latest.insert(
+                stats.key,
+                ImageStats {
+                    frame_idx: stats.frame_idx,
+                    params: stats.params,
+                    pixel_count,
+                    non_finite_count: stats.total_pixel_count - pixel_count,
+                    min,
+                    max,
+                    mean: (sum / pixel_count.max(1) as f64) as f32,
+                    histogram: histogram.to_vec(),
+                },
+            );
+        }
+    }
+}
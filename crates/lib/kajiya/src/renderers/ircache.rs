@@ -105,6 +105,7 @@ impl IrcacheRenderer {
                     RenderPassAttachmentDesc::new(vk::Format::R32G32B32A32_SFLOAT),
                 ],
                 depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+                subpasses: &[],
             },
         );
 
@@ -397,7 +398,7 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            std::iter::empty(),
+            super::rt_shadow_hit_groups(),
         )
         .read(&self.ircache_spatial_buf)
         .read(&self.ircache_life_buf)
@@ -416,7 +417,7 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            super::rt_hit_groups(),
         )
         .read(&self.ircache_spatial_buf)
         .read(sky_cube)
@@ -442,7 +443,7 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            super::rt_hit_groups(),
         )
         .read(&self.ircache_spatial_buf)
         .read(sky_cube)
@@ -0,0 +1,75 @@
+use kajiya_backend::{ash::vk, vulkan::buffer::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+/// Of the screen-space tiles of the cluster grid, in pixels of the render extent.
+pub const LIGHT_CLUSTER_TILE_SIZE_PX: u32 = 64;
+
+/// Depth slices of the cluster grid, distributed exponentially between
+/// `LIGHT_CLUSTER_NEAR_DISTANCE` and `LIGHT_CLUSTER_FAR_DISTANCE`.
+pub const LIGHT_CLUSTER_SLICE_COUNT: u32 = 24;
+
+pub const LIGHT_CLUSTER_NEAR_DISTANCE: f32 = 0.25;
+
+/// Where the last slice starts; it extends to infinity.
+pub const LIGHT_CLUSTER_FAR_DISTANCE: f32 = 256.0;
+
+/// Per cluster; any further lights overlapping it are dropped from it.
+/// Must match `LIGHT_CLUSTER_MAX_LIGHTS` in `lights/clusters.hlsl`.
+pub const LIGHT_CLUSTER_MAX_LIGHTS: u32 = 127;
+
+// Must match `LightClusterConstants` in `lights/clusters.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct GpuLightClusterConstants {
+    dims: [u32; 3],
+    pad0: u32,
+
+    near_distance: f32,
+    far_distance: f32,
+    pad1: [u32; 2],
+}
+
+/// Lists of the punctual lights which can reach each cluster of the view frustum,
+/// so that shading only iterates the lights around a point, rather than all of them.
+///
+/// Each cluster has a count followed by `LIGHT_CLUSTER_MAX_LIGHTS` slots of indices into
+/// `punctual_lights_dyn`; see `lights/clusters.hlsl`. Lights are culled by the spheres of
+/// their ranges; directional lights and the ones without a range are in every cluster.
+pub struct LightClusters {
+    pub lists: rg::Handle<Buffer>,
+    pub(crate) constants: GpuLightClusterConstants,
+}
+
+/// Assigns the punctual lights in the frame constants to the clusters of a view
+/// rendered at `render_extent`.
+pub fn assign_light_clusters(rg: &mut rg::RenderGraph, render_extent: [u32; 2]) -> LightClusters {
+    let dims = [
+        (render_extent[0] + LIGHT_CLUSTER_TILE_SIZE_PX - 1) / LIGHT_CLUSTER_TILE_SIZE_PX,
+        (render_extent[1] + LIGHT_CLUSTER_TILE_SIZE_PX - 1) / LIGHT_CLUSTER_TILE_SIZE_PX,
+        LIGHT_CLUSTER_SLICE_COUNT,
+    ];
+
+    let constants = GpuLightClusterConstants {
+        dims,
+        pad0: 0,
+        near_distance: LIGHT_CLUSTER_NEAR_DISTANCE,
+        far_distance: LIGHT_CLUSTER_FAR_DISTANCE,
+        pad1: [0; 2],
+    };
+
+    let cluster_count = (dims[0] * dims[1] * dims[2]) as usize;
+    let mut lists = rg.create(BufferDesc::new_gpu_only(
+        cluster_count * (LIGHT_CLUSTER_MAX_LIGHTS as usize + 1) * std::mem::size_of::<u32>(),
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+    ));
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass("assign light clusters"),
+        "/shaders/lighting/assign_light_clusters.hlsl",
+    )
+    .write(&mut lists)
+    .constants(constants)
+    .dispatch(dims);
+
+    LightClusters { lists, constants }
+}
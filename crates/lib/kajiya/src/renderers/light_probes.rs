@@ -0,0 +1,352 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use glam::Vec3;
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{buffer::*, device::Device, image::*},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{GbufferDepth, READBACK_FRAME_LATENCY};
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct LightProbeHandle(pub usize);
+
+/// Incident radiance projected onto the first two spherical harmonics bands,
+/// one coefficient vector per color channel, in the order of `sh_eval` in `sh.hlsl`.
+/// Linear, without pre-exposure.
+pub type LightProbeSh = [[f32; 4]; 3];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LightProbe {
+    pub position: Vec3,
+
+    /// Distance at which the probe stops contributing.
+    pub radius: f32,
+
+    /// `None` until baked. Not updated when the probe is moved.
+    pub irradiance: Option<LightProbeSh>,
+}
+
+// Must match `LightProbe` in `light_probes/blend_baked_irradiance.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuLightProbe {
+    position_radius: [f32; 4],
+    sh: LightProbeSh,
+}
+
+struct PendingBake {
+    buffer: Arc<Buffer>,
+    probes: Vec<LightProbeHandle>,
+    frame_idx: u32,
+}
+
+/// Static irradiance probes, baked on demand, and blended into the diffuse GI.
+///
+/// Baking renders the surroundings of each probe into a cube map the same way as
+/// `EnvironmentProbes`, projects it onto spherical harmonics, and reads the result back,
+/// so that it can be saved with the scene and restored via `LightProbe::irradiance`.
+///
+/// The baked probes are a stable base layer under the real-time GI: within their radius,
+/// they're blended with it by `base_layer_weight`, and they stand in for it entirely where
+/// there's none. Every pixel goes through all the baked probes, so this is meant for up to
+/// a few hundred of them.
+pub struct LightProbes {
+    pub enabled: bool,
+
+    /// How much the baked irradiance replaces the real-time GI where probes cover the scene.
+    pub base_layer_weight: f32,
+
+    /// Width of the cube map faces rendered for each probe.
+    pub bake_resolution: u32,
+
+    /// Each takes six forward-shaded views. The `TimeSlicer` of the renderer may allow fewer.
+    pub probes_baked_per_frame: usize,
+
+    probes: Vec<(LightProbeHandle, LightProbe)>,
+    next_handle: usize,
+
+    pending_probes: VecDeque<LightProbeHandle>,
+    pending_readbacks: Vec<PendingBake>,
+}
+
+impl Default for LightProbes {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_layer_weight: 0.5,
+            bake_resolution: 32,
+            probes_baked_per_frame: 1,
+            probes: Default::default(),
+            next_handle: 0,
+            pending_probes: Default::default(),
+            pending_readbacks: Default::default(),
+        }
+    }
+}
+
+impl LightProbes {
+    pub fn add(&mut self, probe: LightProbe) -> LightProbeHandle {
+        let handle = LightProbeHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.probes.push((handle, probe));
+        handle
+    }
+
+    /// Places `counts` probes along each axis, evenly spaced between `min` and `max`,
+    /// and reaching two spacings out, which keeps the blend between them smooth.
+    pub fn add_grid(&mut self, min: Vec3, max: Vec3, counts: [u32; 3]) -> Vec<LightProbeHandle> {
+        let counts = [counts[0].max(1), counts[1].max(1), counts[2].max(1)];
+        let spacing = (max - min)
+            / Vec3::new(
+                (counts[0] - 1).max(1) as f32,
+                (counts[1] - 1).max(1) as f32,
+                (counts[2] - 1).max(1) as f32,
+            );
+        let radius = 2.0 * spacing.max_element().max(1e-3);
+
+        let mut handles = Vec::with_capacity(counts.iter().product::<u32>() as usize);
+        for z in 0..counts[2] {
+            for y in 0..counts[1] {
+                for x in 0..counts[0] {
+                    handles.push(self.add(LightProbe {
+                        position: min + spacing * Vec3::new(x as f32, y as f32, z as f32),
+                        radius,
+                        irradiance: None,
+                    }));
+                }
+            }
+        }
+
+        handles
+    }
+
+    pub fn remove(&mut self, probe: LightProbeHandle) {
+        let index = self
+            .probes
+            .iter()
+            .position(|(handle, _)| *handle == probe)
+            .expect("no such probe");
+        self.probes.swap_remove(index);
+
+        self.pending_probes.retain(|handle| *handle != probe);
+    }
+
+    pub fn get(&self, probe: LightProbeHandle) -> &LightProbe {
+        self.probes
+            .iter()
+            .find(|(handle, _)| *handle == probe)
+            .map(|(_, probe)| probe)
+            .expect("no such probe")
+    }
+
+    pub fn get_mut(&mut self, probe: LightProbeHandle) -> &mut LightProbe {
+        self.probes
+            .iter_mut()
+            .find(|(handle, _)| *handle == probe)
+            .map(|(_, probe)| probe)
+            .expect("no such probe")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (LightProbeHandle, &LightProbe)> {
+        self.probes.iter().map(|(handle, probe)| (*handle, probe))
+    }
+
+    pub fn len(&self) -> usize {
+        self.probes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    /// Queues the probe for baking, over the next frames in `RenderMode::Standard`.
+    /// Its previous irradiance stays in use until then.
+    pub fn bake(&mut self, probe: LightProbeHandle) {
+        if !self.pending_probes.contains(&probe) {
+            self.pending_probes.push_back(probe);
+        }
+    }
+
+    pub fn bake_all(&mut self) {
+        let handles: Vec<_> = self.probes.iter().map(|(handle, _)| *handle).collect();
+        for handle in handles {
+            self.bake(handle);
+        }
+    }
+
+    /// Whether any probes are queued for baking, or waiting for their results.
+    pub fn is_baking(&self) -> bool {
+        !self.pending_probes.is_empty() || !self.pending_readbacks.is_empty()
+    }
+
+    /// How many probes could be baked this frame, within `probes_baked_per_frame`.
+    pub(crate) fn frame_bake_count(&self) -> usize {
+        self.probes_baked_per_frame.min(self.pending_probes.len())
+    }
+
+    /// The next `count` probes to bake, out of `frame_bake_count`.
+    pub(crate) fn take_frame_bakes(&mut self, count: usize) -> Vec<LightProbeHandle> {
+        let count = count.min(self.pending_probes.len());
+        self.pending_probes.drain(..count).collect()
+    }
+
+    /// Tracks the readback of the spherical harmonics of `probes`, written to `buffer`
+    /// in the same order by `project_sh.hlsl` in frame `frame_idx`.
+    pub(crate) fn record_bake_readback(
+        &mut self,
+        buffer: Arc<Buffer>,
+        probes: Vec<LightProbeHandle>,
+        frame_idx: u32,
+    ) {
+        self.pending_readbacks.push(PendingBake {
+            buffer,
+            probes,
+            frame_idx,
+        });
+    }
+
+    /// Stores the irradiance of the probes whose bakes the GPU has finished.
+    pub(crate) fn read_finished_bakes(&mut self, device: &Device, frame_idx: u32) {
+        let (finished, pending) = std::mem::take(&mut self.pending_readbacks)
+            .into_iter()
+            .partition(|bake: &PendingBake| {
+                frame_idx.wrapping_sub(bake.frame_idx) >= READBACK_FRAME_LATENCY
+            });
+        self.pending_readbacks = pending;
+
+        for bake in finished {
+            let sh: Option<Vec<LightProbeSh>> = bake
+                .buffer
+                .allocation
+                .mapped_slice()
+                .map(|src| bytemuck::cast_slice::<u8, LightProbeSh>(src).to_vec());
+
+            // The render graph has released its reference by now.
+            if let Ok(buffer) = Arc::try_unwrap(bake.buffer) {
+                device.immediate_destroy_buffer(buffer);
+            }
+
+            let sh = if let Some(sh) = sh {
+                sh
+            } else {
+                log::error!("The light probe readback buffer is not host-visible");
+                continue;
+            };
+
+            // Probes removed since are skipped.
+            for (handle, sh) in bake.probes.into_iter().zip(sh) {
+                if let Some((_, probe)) = self.probes.iter_mut().find(|(h, _)| *h == handle) {
+                    probe.irradiance = Some(sh);
+                }
+            }
+        }
+    }
+
+    /// Blends the baked probes into `gi`, the pre-exposed irradiance of every pixel of
+    /// `gbuffer_depth`, if there's any real-time GI, or returns their irradiance alone otherwise.
+    pub(crate) fn blend_into_gi(
+        &self,
+        rg: &mut rg::RenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        gi: Option<rg::ReadOnlyHandle<Image>>,
+    ) -> Option<rg::ReadOnlyHandle<Image>> {
+        let gpu_probes: Vec<GpuLightProbe> = self
+            .probes
+            .iter()
+            .filter(|_| self.enabled)
+            .filter_map(|(_, probe)| {
+                Some(GpuLightProbe {
+                    position_radius: probe.position.extend(probe.radius.max(1e-3)).to_array(),
+                    sh: probe.irradiance?,
+                })
+            })
+            .collect();
+
+        if gpu_probes.is_empty() {
+            return gi;
+        }
+
+        let mut output = rg.create(ImageDesc::new_2d(
+            vk::Format::R16G16B16A16_SFLOAT,
+            gbuffer_depth.gbuffer.desc().extent_2d(),
+        ));
+
+        let has_gi = gi.is_some();
+        let gi = match gi {
+            Some(gi) => gi,
+            None => rg
+                .create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]))
+                .into(),
+        };
+
+        let probe_count = gpu_probes.len() as u32;
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("blend baked irradiance"),
+            "/shaders/light_probes/blend_baked_irradiance.hlsl",
+        )
+        .read(&gbuffer_depth.gbuffer)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(&gi)
+        .dynamic_storage_buffer_vec(gpu_probes)
+        .write(&mut output)
+        .constants((
+            output.desc().extent_inv_extent_2d(),
+            probe_count,
+            self.base_layer_weight.clamp(0.0, 1.0),
+            has_gi as u32,
+        ))
+        .dispatch(output.desc().extent);
+
+        Some(output.into())
+    }
+}
+
+/// Creates the buffer which `project_sh.hlsl` writes the spherical harmonics of
+/// `probe_count` probes to, and which `LightProbes::read_finished_bakes` reads.
+pub(crate) fn create_bake_readback_buffer(
+    device: &Device,
+    probe_count: usize,
+) -> Option<Arc<Buffer>> {
+    match device.create_buffer(
+        BufferDesc::new_gpu_to_cpu(
+            probe_count * std::mem::size_of::<LightProbeSh>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        ),
+        "light probe readback",
+        None,
+    ) {
+        Ok(buffer) => Some(Arc::new(buffer)),
+        Err(err) => {
+            log::error!(
+                "Could not create the light probe readback buffer: {:?}",
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Adds a pass projecting `cube`, as written by `store_probe_face.hlsl`, onto
+/// spherical harmonics, stored at `index` in `readback_buf`.
+pub(crate) fn project_cube_to_sh(
+    rg: &mut rg::RenderGraph,
+    cube: &rg::Handle<Image>,
+    readback_buf: &mut rg::Handle<Buffer>,
+    index: usize,
+) {
+    SimpleRenderPass::new_compute(
+        rg.add_pass("project light probe"),
+        "/shaders/light_probes/project_sh.hlsl",
+    )
+    .read_view(
+        cube,
+        ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+    )
+    .write(readback_buf)
+    .constants((cube.desc().extent[0], index as u32))
+    .dispatch([64, 1, 1]);
+}
@@ -0,0 +1,109 @@
+use super::{GbufferDepth, PingPongTemporalResource};
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass, TemporalRenderGraph};
+
+/// Denoises the ray-traced shadows of punctual and rect lights.
+///
+/// Expects the unshadowed lighting in RGB, and the visibility from a single ray per light in alpha.
+/// The visibility is accumulated temporally, and then blurred by a variance-guided filter.
+pub struct LocalLightShadowDenoiseRenderer {
+    accum: PingPongTemporalResource,
+    moments: PingPongTemporalResource,
+}
+
+impl LocalLightShadowDenoiseRenderer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            accum: PingPongTemporalResource::new(&format!("{}_accum", name)),
+            moments: PingPongTemporalResource::new(&format!("{}_moments", name)),
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        lighting: &rg::Handle<Image>,
+        reprojection_map: &rg::Handle<Image>,
+    ) -> rg::Handle<Image> {
+        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
+
+        let (mut moments_image, prev_moments_image) = self.moments.get_output_and_history(
+            rg,
+            gbuffer_desc
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
+                .format(vk::Format::R16G16B16A16_SFLOAT),
+        );
+
+        // Visibility and its variance
+        let visibility_image_desc =
+            ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, gbuffer_desc.extent_2d());
+
+        let (mut accum_image, prev_accum_image) = self.accum.get_output_and_history(
+            rg,
+            visibility_image_desc
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+        );
+
+        let mut temporal_output_image = rg.create(visibility_image_desc);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("local light shadow temporal"),
+            "/shaders/local_light_shadow_denoise/temporal_filter.hlsl",
+        )
+        .read(lighting)
+        .read(&prev_moments_image)
+        .read(&prev_accum_image)
+        .read(reprojection_map)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .write(&mut moments_image)
+        .write(&mut temporal_output_image)
+        .constants(gbuffer_desc.extent_inv_extent_2d())
+        .dispatch(gbuffer_desc.extent);
+
+        // The output of the first iteration becomes the history, as in SVGF.
+        Self::filter_spatial(
+            rg,
+            1,
+            &temporal_output_image,
+            &mut accum_image,
+            gbuffer_depth,
+        );
+
+        let mut temp = rg.create(visibility_image_desc);
+        Self::filter_spatial(rg, 2, &accum_image, &mut temp, gbuffer_depth);
+        Self::filter_spatial(rg, 4, &temp, &mut temporal_output_image, gbuffer_depth);
+
+        let mut output_image = rg.create(*lighting.desc());
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("local light shadow resolve"),
+            "/shaders/local_light_shadow_denoise/resolve.hlsl",
+        )
+        .read(lighting)
+        .read(&temporal_output_image)
+        .write(&mut output_image)
+        .dispatch(gbuffer_desc.extent);
+
+        output_image
+    }
+
+    fn filter_spatial(
+        rg: &mut TemporalRenderGraph,
+        step_size: u32,
+        input_image: &rg::Handle<Image>,
+        output_image: &mut rg::Handle<Image>,
+        gbuffer_depth: &GbufferDepth,
+    ) {
+        SimpleRenderPass::new_compute(
+            rg.add_pass("local light shadow spatial"),
+            "/shaders/local_light_shadow_denoise/spatial_filter.hlsl",
+        )
+        .read(input_image)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(&gbuffer_depth.geometric_normal)
+        .write(output_image)
+        .constants((output_image.desc().extent_inv_extent_2d(), step_size))
+        .dispatch(output_image.desc().extent);
+    }
+}
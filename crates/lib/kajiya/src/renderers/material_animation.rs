@@ -0,0 +1,79 @@
+use kajiya_asset::mesh::MeshMaterial;
+
+/// Piecewise-linear curve of `[time, value]` keys, with times in seconds, in increasing order.
+///
+/// Loops over the time of the last key; a single key is constant.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MaterialCurve {
+    pub keys: Vec<[f32; 2]>,
+}
+
+impl MaterialCurve {
+    pub fn evaluate(&self, time: f32) -> f32 {
+        let (first, last) = match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 1.0,
+        };
+
+        if last[0] <= 0.0 {
+            return last[1];
+        }
+
+        let time = time.rem_euclid(last[0]);
+        if time <= first[0] {
+            return first[1];
+        }
+
+        self.keys
+            .windows(2)
+            .find(|keys| time <= keys[1][0])
+            .map_or(last[1], |keys| {
+                let [t0, v0] = keys[0];
+                let [t1, v1] = keys[1];
+                let t = if t1 > t0 {
+                    (time - t0) / (t1 - t0)
+                } else {
+                    1.0
+                };
+                v0 + (v1 - v0) * t
+            })
+    }
+}
+
+/// Changes to a mesh material over time, for conveyor belts, screens, lava, and the like.
+///
+/// Animations apply to the mesh, and thus to all its instances. Emissive triangle lights
+/// created for the mesh don't follow the animated emission.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MaterialAnimation {
+    /// Offset of all the maps' UVs per second, after their own transforms.
+    pub uv_scroll: [f32; 2],
+
+    /// Multiplies the emissive color over time.
+    pub emissive_intensity: Option<MaterialCurve>,
+}
+
+impl MaterialAnimation {
+    pub(crate) fn apply(&self, material: &MeshMaterial, time: f32) -> MeshMaterial {
+        let mut res = *material;
+
+        // The maps repeat, so only the fractional part matters, and keeps precision over time.
+        let scroll = [
+            (self.uv_scroll[0] * time).fract(),
+            (self.uv_scroll[1] * time).fract(),
+        ];
+        for xform in &mut res.map_transforms {
+            xform[4] += scroll[0];
+            xform[5] += scroll[1];
+        }
+
+        if let Some(curve) = &self.emissive_intensity {
+            let intensity = curve.evaluate(time).max(0.0);
+            for c in &mut res.emissive {
+                *c *= intensity;
+            }
+        }
+
+        res
+    }
+}
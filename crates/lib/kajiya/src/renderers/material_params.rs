@@ -0,0 +1,71 @@
+use kajiya_asset::mesh::MeshMaterial;
+
+/// The parameters of a mesh material which can change after the mesh is uploaded;
+/// see `WorldRenderer::update_material`.
+///
+/// The maps and flags are fixed, as they decide how the mesh is drawn and traced.
+/// For the same reason, turning `transmission` on or off doesn't change whether the mesh
+/// is alpha blended.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MaterialParams {
+    /// Multiplies the albedo map; alpha is the opacity.
+    pub base_color: [f32; 4],
+    pub roughness: f32,
+    pub metalness: f32,
+    pub emissive: [f32; 3],
+
+    /// Of the normal, metalness-roughness, albedo, and emissive maps; a 2x3 matrix each.
+    pub map_transforms: [[f32; 6]; 4],
+
+    pub ior: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub anisotropy: f32,
+    pub anisotropy_rotation: f32,
+    pub alpha_cutoff: f32,
+    pub subsurface: f32,
+    pub subsurface_profile: u32,
+    pub parallax_scale: f32,
+}
+
+impl MaterialParams {
+    pub fn from_material(material: &MeshMaterial) -> Self {
+        Self {
+            base_color: material.base_color_mult,
+            roughness: material.roughness_mult,
+            metalness: material.metalness_factor,
+            emissive: material.emissive,
+            map_transforms: material.map_transforms,
+            ior: material.ior,
+            clearcoat: material.clearcoat,
+            clearcoat_roughness: material.clearcoat_roughness,
+            transmission: material.transmission,
+            anisotropy: material.anisotropy,
+            anisotropy_rotation: material.anisotropy_rotation,
+            alpha_cutoff: material.alpha_cutoff,
+            subsurface: material.subsurface,
+            subsurface_profile: material.subsurface_profile,
+            parallax_scale: material.parallax_scale,
+        }
+    }
+
+    /// Overwrites the parameters of `material`, keeping its maps and flags.
+    pub fn apply_to(&self, material: &mut MeshMaterial) {
+        material.base_color_mult = self.base_color;
+        material.roughness_mult = self.roughness;
+        material.metalness_factor = self.metalness;
+        material.emissive = self.emissive;
+        material.map_transforms = self.map_transforms;
+        material.ior = self.ior;
+        material.clearcoat = self.clearcoat;
+        material.clearcoat_roughness = self.clearcoat_roughness;
+        material.transmission = self.transmission;
+        material.anisotropy = self.anisotropy;
+        material.anisotropy_rotation = self.anisotropy_rotation;
+        material.alpha_cutoff = self.alpha_cutoff;
+        material.subsurface = self.subsurface;
+        material.subsurface_profile = self.subsurface_profile;
+        material.parallax_scale = self.parallax_scale;
+    }
+}
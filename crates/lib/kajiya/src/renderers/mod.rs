@@ -1,37 +1,110 @@
 use std::cell::{Ref, RefCell};
 
-use kajiya_backend::Image;
-use kajiya_rg::{self as rg, GetOrCreateTemporal};
+use kajiya_backend::{vulkan::shader::ShaderSource, Image};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, RtHitGroup};
 
+pub mod bilateral_upsample;
+pub mod blue_noise;
+pub mod buffer_writes;
+pub mod contact_shadows;
+pub mod cube_lut;
+pub mod culling;
+pub mod curves;
+pub mod custom_pass_plugins;
+pub mod custom_passes;
+pub mod ddgi;
+pub mod debug_view;
+pub mod decals;
 pub mod deferred;
 pub mod dof;
+pub mod environment_probes;
+pub mod freeze_frame;
+pub mod gbuffer_layout;
+pub mod gi_resolution;
+pub mod gi_temporal;
+pub mod gtao;
 pub mod half_res;
+pub mod hdr_capture;
+pub mod hiz;
 pub mod ibl;
+pub mod image_stats;
 pub mod ircache;
+pub mod light_clusters;
+pub mod light_probes;
 pub mod lighting;
+pub mod local_light_shadow_denoise;
+pub mod material_animation;
+pub mod material_params;
 pub mod motion_blur;
+pub mod output_calibration;
+pub mod particles;
+pub mod picking;
+pub mod planar_reflections;
 pub mod post;
+pub mod post_fx;
 pub mod prefix_scan;
+pub mod punctual_shadow_cache;
 pub mod raster_meshes;
+pub mod reactive_mask;
+pub mod rect_lights;
 pub mod reference;
+pub mod render_quality;
+pub mod render_target_formats;
 pub mod reprojection;
+pub mod rtao;
 pub mod rtdgi;
 pub mod rtr;
 pub mod shadow_denoise;
 pub mod shadows;
+pub mod skinning;
 pub mod sky;
 pub mod ssgi;
+pub mod subsurface;
 pub mod taa;
+pub mod tile_lists;
+pub mod time_of_day;
+pub mod time_slicing;
+pub mod triangle_lights;
 pub mod ussgi;
+pub mod volumetric_fog;
+pub mod water;
+pub mod working_color_space;
 pub mod wrc;
 
 #[cfg(feature = "dlss")]
 pub mod dlss;
+#[cfg(feature = "fsr2")]
+pub mod fsr2;
+
+/// Frames which can be in flight on the GPU at once; readbacks are only safe to map
+/// after this many frames.
+pub(crate) const READBACK_FRAME_LATENCY: u32 = 2;
+
+/// Hit groups of passes tracing rays via `rt.hlsl`, which uses the first one
+/// for gbuffer rays, and the second one for shadow rays.
+pub(crate) fn rt_hit_groups() -> [RtHitGroup; 2] {
+    [
+        RtHitGroup::closest_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+            .with_any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+        RtHitGroup::any_hit(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+    ]
+}
+
+/// Like `rt_hit_groups`, but for passes which only trace shadow rays.
+pub(crate) fn rt_shadow_hit_groups() -> [RtHitGroup; 2] {
+    // Duplicated because `rt.hlsl` hardcodes the shadow hit group index to 1
+    [
+        RtHitGroup::any_hit(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+        RtHitGroup::any_hit(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+    ]
+}
 
 pub struct GbufferDepth {
     pub geometric_normal: rg::Handle<Image>,
     pub gbuffer: rg::Handle<Image>,
     pub depth: rg::Handle<Image>,
+    /// Only with `GbufferLayout::material_id`.
+    pub material_id: Option<rg::Handle<Image>>,
     half_view_normal: RefCell<Option<rg::Handle<Image>>>,
     half_depth: RefCell<Option<rg::Handle<Image>>>,
 }
@@ -46,6 +119,7 @@ impl GbufferDepth {
             geometric_normal,
             gbuffer,
             depth,
+            material_id: None,
             half_view_normal: Default::default(),
             half_depth: Default::default(),
         }
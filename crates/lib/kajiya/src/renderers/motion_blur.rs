@@ -2,11 +2,32 @@ use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg};
 use rg::{RenderGraph, SimpleRenderPass};
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MotionBlurParams {
+    pub enabled: bool,
+
+    /// Exposure time as a fraction of the frame time, in degrees, as with a rotary film shutter.
+    /// 180 is the cinematic standard; 360 keeps the shutter open for the whole frame.
+    pub shutter_angle_degrees: f32,
+}
+
+impl Default for MotionBlurParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shutter_angle_degrees: 180.0,
+        }
+    }
+}
+
+/// Reconstruction filter after "A Reconstruction Filter for Plausible Motion Blur" by McGuire et al.,
+/// gathering along the largest velocity in the neighborhood, found via tile reduction and dilation.
 pub fn motion_blur(
     rg: &mut RenderGraph,
     input: &rg::Handle<Image>,
     depth: &rg::Handle<Image>,
     reprojection_map: &rg::Handle<Image>,
+    params: &MotionBlurParams,
 ) -> rg::Handle<Image> {
     const VELOCITY_TILE_SIZE: u32 = 16;
 
@@ -17,9 +38,9 @@ pub fn motion_blur(
             .format(vk::Format::R16G16_SFLOAT),
     );
 
-    SimpleRenderPass::new_compute_rust(
+    SimpleRenderPass::new_compute(
         rg.add_pass("velocity reduce x"),
-        "motion_blur::velocity_reduce_x",
+        "/shaders/motion_blur/velocity_reduce_x.hlsl",
     )
     .read(reprojection_map)
     .write(&mut velocity_reduced_x)
@@ -32,19 +53,19 @@ pub fn motion_blur(
                 .div_up_extent([1, VELOCITY_TILE_SIZE, 1]),
         );
 
-    SimpleRenderPass::new_compute_rust(
+    SimpleRenderPass::new_compute(
         rg.add_pass("velocity reduce y"),
-        "motion_blur::velocity_reduce_y",
+        "/shaders/motion_blur/velocity_reduce_y.hlsl",
     )
     .read(&velocity_reduced_x)
     .write(&mut velocity_reduced_y)
-    .dispatch(velocity_reduced_x.desc().extent);
+    .dispatch(velocity_reduced_y.desc().extent);
 
     let mut velocity_dilated = rg.create(*velocity_reduced_y.desc());
 
-    SimpleRenderPass::new_compute_rust(
+    SimpleRenderPass::new_compute(
         rg.add_pass("velocity dilate"),
-        "motion_blur::velocity_dilate",
+        "/shaders/motion_blur/velocity_dilate.hlsl",
     )
     .read(&velocity_reduced_y)
     .write(&mut velocity_dilated)
@@ -52,21 +73,23 @@ pub fn motion_blur(
 
     let mut output = rg.create(*input.desc());
 
-    // TODO: account for framerate like the HLSL version did
-    let motion_blur_scale: f32 = 1.0;
+    let shutter_fraction = params.shutter_angle_degrees.clamp(0.0, 360.0) / 360.0;
 
-    SimpleRenderPass::new_compute_rust(rg.add_pass("motion blur"), "motion_blur::motion_blur")
-        .read(input)
-        .read(reprojection_map)
-        .read(&velocity_dilated)
-        .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
-        .write(&mut output)
-        .constants((
-            depth.desc().extent_inv_extent_2d(),
-            output.desc().extent_inv_extent_2d(),
-            motion_blur_scale,
-        ))
-        .dispatch(output.desc().extent);
+    SimpleRenderPass::new_compute(
+        rg.add_pass("motion blur"),
+        "/shaders/motion_blur/motion_blur.hlsl",
+    )
+    .read(input)
+    .read(reprojection_map)
+    .read(&velocity_dilated)
+    .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
+    .write(&mut output)
+    .constants((
+        depth.desc().extent_inv_extent_2d(),
+        output.desc().extent_inv_extent_2d(),
+        shutter_fraction,
+    ))
+    .dispatch(output.desc().extent);
 
     output
 }
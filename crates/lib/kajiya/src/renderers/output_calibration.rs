@@ -0,0 +1,197 @@
+//! Calibration of the display-referred output of tonemapping to the display it's shown on.
+//!
+//! Tonemapping produces Rec.709 values with diffuse white at 1.0, independent of the display.
+//! The final blit maps those to absolute luminance via the paper white, clips them to the
+//! peak luminance and gamut of the display, and encodes them for the swapchain.
+//!
+//! The output resolution is independent of the swapchain; `OutputScaling` decides
+//! how the output is fit into the window.
+
+use kajiya_backend::vulkan::image::*;
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OutputCalibration {
+    /// Luminance of diffuse white, i.e. of 1.0 out of tonemapping, in nits.
+    pub paper_white_nits: f32,
+
+    /// Peak luminance of the display, in nits. Anything brighter is clipped.
+    ///
+    /// SDR displays show full code values at this luminance, so a paper white
+    /// below it dims the image rather than leaving headroom.
+    pub max_luminance_nits: f32,
+
+    /// Primaries of the display.
+    pub gamut: OutputGamut,
+}
+
+impl Default for OutputCalibration {
+    /// Passes SDR output through unchanged.
+    fn default() -> Self {
+        Self {
+            paper_white_nits: 200.0,
+            max_luminance_nits: 200.0,
+            gamut: OutputGamut::Rec709,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputGamut {
+    /// Same as sRGB.
+    Rec709,
+    DisplayP3,
+    Rec2020,
+}
+
+impl OutputGamut {
+    /// Matches the `OUTPUT_GAMUT_*` defines in `final_blit.hlsl`.
+    fn shader_index(self) -> u32 {
+        match self {
+            OutputGamut::Rec709 => 0,
+            OutputGamut::DisplayP3 => 1,
+            OutputGamut::Rec2020 => 2,
+        }
+    }
+}
+
+/// How the final blit fits the output into a swapchain of a different size.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OutputScaling {
+    /// Keeps the aspect ratio of the output, with black bars on the sides it leaves free,
+    /// rather than stretching it to the whole swapchain.
+    pub letterbox: bool,
+    pub filter: OutputFilter,
+}
+
+impl Default for OutputScaling {
+    fn default() -> Self {
+        Self {
+            letterbox: false,
+            filter: OutputFilter::Bicubic,
+        }
+    }
+}
+
+impl OutputScaling {
+    /// The `[x, y, width, height]` of the output image which the main image is blit to.
+    fn main_rect(self, main_extent: [u32; 2], output_extent: [u32; 2]) -> [f32; 4] {
+        let main_extent = [main_extent[0] as f32, main_extent[1] as f32];
+        let output_extent = [output_extent[0] as f32, output_extent[1] as f32];
+
+        if !self.letterbox {
+            return [0.0, 0.0, output_extent[0], output_extent[1]];
+        }
+
+        let mut scale = (output_extent[0] / main_extent[0]).min(output_extent[1] / main_extent[1]);
+
+        // Whole multiples cover every main image pixel with the same number of output pixels.
+        if self.filter == OutputFilter::Nearest && scale >= 1.0 {
+            scale = scale.floor();
+        }
+
+        let size = [
+            (main_extent[0] * scale).round(),
+            (main_extent[1] * scale).round(),
+        ];
+
+        [
+            ((output_extent[0] - size[0]) * 0.5).floor(),
+            ((output_extent[1] - size[1]) * 0.5).floor(),
+            size[0],
+            size[1],
+        ]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputFilter {
+    /// Keeps pixels sharp, for comparing images pixel by pixel.
+    Nearest,
+    /// Catmull-Rom, in perceptual space.
+    Bicubic,
+}
+
+impl OutputFilter {
+    /// Matches the `OUTPUT_FILTER_*` defines in `final_blit.hlsl`.
+    fn shader_index(self) -> u32 {
+        match self {
+            OutputFilter::Nearest => 0,
+            OutputFilter::Bicubic => 1,
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Self::Nearest),
+            "bicubic" => Ok(Self::Bicubic),
+            _ => Err(anyhow::anyhow!(
+                "Unknown output filter {:?}; expected one of: nearest, bicubic",
+                s
+            )),
+        }
+    }
+}
+
+/// How the swapchain images are encoded; determined by the surface format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputEncoding {
+    /// The sRGB curve, relative to the peak luminance.
+    Srgb,
+
+    /// HDR10: SMPTE ST 2084 (PQ) in absolute luminance, with Rec.2020 primaries.
+    Pq,
+}
+
+impl OutputEncoding {
+    /// Matches the `OUTPUT_ENCODING_*` defines in `final_blit.hlsl`.
+    fn shader_index(self) -> u32 {
+        match self {
+            OutputEncoding::Srgb => 0,
+            OutputEncoding::Pq => 1,
+        }
+    }
+}
+
+/// Calibrates and encodes `main_img` into `output`, compositing the UI on top at paper white.
+///
+/// `main_img` is resampled according to `scaling` if its size differs from `output_extent`.
+/// The UI is expected at `output_extent`, and isn't scaled.
+pub fn final_blit(
+    rg: &mut rg::RenderGraph,
+    main_img: &rg::Handle<Image>,
+    ui_img: &rg::Handle<Image>,
+    output: &mut rg::Handle<Image>,
+    output_extent: [u32; 2],
+    calibration: &OutputCalibration,
+    scaling: OutputScaling,
+    encoding: OutputEncoding,
+) {
+    let paper_white_nits = calibration.paper_white_nits.max(1.0);
+    let max_luminance_nits = calibration.max_luminance_nits.max(paper_white_nits);
+
+    SimpleRenderPass::new_compute(rg.add_pass("final blit"), "/shaders/final_blit.hlsl")
+        .read(main_img)
+        .read(ui_img)
+        .write(output)
+        .constants((
+            main_img.desc().extent_inv_extent_2d(),
+            [
+                output_extent[0] as f32,
+                output_extent[1] as f32,
+                1.0 / output_extent[0] as f32,
+                1.0 / output_extent[1] as f32,
+            ],
+            scaling.main_rect(main_img.desc().extent_2d(), output_extent),
+            encoding.shader_index(),
+            calibration.gamut.shader_index(),
+            paper_white_nits,
+            max_luminance_nits,
+            scaling.filter.shader_index(),
+        ))
+        .dispatch([output_extent[0], output_extent[1], 1]);
+}
@@ -0,0 +1,491 @@
+use std::{mem::size_of, ops::Range, sync::Arc};
+
+use glam::{Vec3, Vec4};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{buffer::*, image::*, ray_tracing::RayTracingAcceleration, shader::*},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+use log::warn;
+
+use super::{ircache::IrcacheRenderState, volumetric_fog::IntegratedFog};
+use crate::world_renderer::BindlessImageHandle;
+
+/// Slots shared by the particles of all the emitters. A power of two, for the sort.
+pub const MAX_PARTICLES: u32 = 16384;
+
+// Must match `Particle` in `particles/particle_common.hlsl`
+const PARTICLE_STRIDE: usize = 12 * size_of::<u32>();
+
+// Sorted in groupshared memory by `particles/sort_local.hlsl`
+const SORT_BLOCK_SIZE: u32 = 1024;
+
+const GPU_PARTICLE_NO_TEXTURE: u32 = !0;
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct ParticleEmitterHandle(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParticleBlendMode {
+    /// Covers what's behind the particles by their opacity.
+    Alpha,
+
+    /// Adds the particles' light without covering anything, for fire, sparks, and such.
+    Additive,
+}
+
+/// Spawns particles around a point at a steady rate, and moves them along ballistic paths.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ParticleEmitter {
+    pub position: Vec3,
+
+    /// Particles spawn at random points within this distance of `position`.
+    pub spawn_radius: f32,
+
+    /// Initial velocity of the particles, in world units per second.
+    pub velocity: Vec3,
+
+    /// Random speed added to `velocity` in any direction, up to this much.
+    pub velocity_randomness: f32,
+
+    /// Constant acceleration, such as gravity or buoyancy.
+    pub acceleration: Vec3,
+
+    /// Exponential decay rate of the velocity, per second.
+    pub drag: f32,
+
+    /// Particles per second. Zero stops spawning, and lets the live particles die out.
+    pub spawn_rate: f32,
+
+    /// Seconds each particle lives for.
+    pub lifetime: f32,
+
+    /// Most particles alive at once; beyond that, the oldest are replaced by new ones.
+    /// `spawn_rate * lifetime` keeps all of them.
+    pub max_particles: u32,
+
+    /// Width of the camera-facing quads at the beginning and at the end of the particles' lives.
+    pub size: [f32; 2],
+
+    /// Linear albedo and opacity at the beginning and at the end of the particles' lives.
+    pub color: [Vec4; 2],
+
+    /// Added on top of the reflected light, in the units of `MeshMaterial::emissive`.
+    pub emissive: Vec3,
+
+    pub blend_mode: ParticleBlendMode,
+
+    /// Multiplies the color and opacity across the quads. `None` draws soft round dots.
+    pub texture: Option<BindlessImageHandle>,
+}
+
+impl ParticleEmitter {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            spawn_radius: 0.1,
+            velocity: Vec3::Y,
+            velocity_randomness: 0.25,
+            acceleration: Vec3::ZERO,
+            drag: 0.0,
+            spawn_rate: 50.0,
+            lifetime: 2.0,
+            max_particles: 128,
+            size: [0.1, 0.2],
+            color: [Vec4::ONE, Vec4::new(1.0, 1.0, 1.0, 0.0)],
+            emissive: Vec3::ZERO,
+            blend_mode: ParticleBlendMode::Alpha,
+            texture: None,
+        }
+    }
+}
+
+// Must match `ParticleEmitter` in `particles/particle_common.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuParticleEmitter {
+    position: [f32; 3],
+    spawn_radius: f32,
+    velocity: [f32; 3],
+    velocity_randomness: f32,
+    acceleration: [f32; 3],
+    drag: f32,
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+    emissive: [f32; 3],
+    lifetime: f32,
+    size_start: f32,
+    size_end: f32,
+    first_slot: u32,
+    slot_count: u32,
+    spawn_slot: u32,
+    spawn_count: u32,
+    blend_mode: u32,
+    texture: u32,
+    reset: u32,
+    pad: [u32; 3],
+}
+
+struct EmitterState {
+    emitter: ParticleEmitter,
+
+    // In the particle pool. Empty if the pool was full.
+    slots: Range<u32>,
+    // `max_particles` which `slots` were allocated for
+    allocated_for: Option<u32>,
+    // Whether `slots` were allocated this frame, and hold another emitter's particles
+    reset: bool,
+
+    // Relative to `slots`. Particles spawn into a ring, replacing the oldest ones.
+    next_spawn_slot: u32,
+    // Fractional particles carried over to the next frame
+    spawn_remainder: f32,
+}
+
+impl EmitterState {
+    fn new(emitter: ParticleEmitter) -> Self {
+        Self {
+            emitter,
+            slots: 0..0,
+            allocated_for: None,
+            reset: false,
+            next_spawn_slot: 0,
+            spawn_remainder: 0.0,
+        }
+    }
+}
+
+/// GPU particles, simulated and drawn every frame after the transparent meshes.
+///
+/// The particles are lit per particle rather than per pixel, by the sun and local lights,
+/// and by the sky. With ray tracing, sunlight is shadowed, and indirect lighting comes from
+/// the irradiance cache instead of the sky. They're sorted back to front together, and fogged
+/// with the volumetric fog in front of them.
+///
+/// Only the main view draws the particles, and they don't cast shadows.
+pub struct ParticleSystem {
+    pub enabled: bool,
+
+    emitters: Vec<(ParticleEmitterHandle, EmitterState)>,
+    next_handle: usize,
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            emitters: Default::default(),
+            next_handle: 0,
+        }
+    }
+}
+
+impl ParticleSystem {
+    pub fn add(&mut self, emitter: ParticleEmitter) -> ParticleEmitterHandle {
+        let handle = ParticleEmitterHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.emitters.push((handle, EmitterState::new(emitter)));
+        handle
+    }
+
+    /// Removes the emitter along with its live particles.
+    pub fn remove(&mut self, emitter: ParticleEmitterHandle) {
+        let index = self
+            .emitters
+            .iter()
+            .position(|(handle, _)| *handle == emitter)
+            .expect("no such emitter");
+        self.emitters.swap_remove(index);
+    }
+
+    pub fn get(&self, emitter: ParticleEmitterHandle) -> &ParticleEmitter {
+        self.emitters
+            .iter()
+            .find(|(handle, _)| *handle == emitter)
+            .map(|(_, state)| &state.emitter)
+            .expect("no such emitter")
+    }
+
+    pub fn get_mut(&mut self, emitter: ParticleEmitterHandle) -> &mut ParticleEmitter {
+        self.emitters
+            .iter_mut()
+            .find(|(handle, _)| *handle == emitter)
+            .map(|(_, state)| &mut state.emitter)
+            .expect("no such emitter")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ParticleEmitterHandle, &ParticleEmitter)> {
+        self.emitters
+            .iter()
+            .map(|(handle, state)| (*handle, &state.emitter))
+    }
+
+    pub fn len(&self) -> usize {
+        self.emitters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty()
+    }
+
+    // Gives emitters without slots, or with a changed `max_particles`, their ranges of the pool.
+    fn allocate_slots(&mut self) {
+        for (_, state) in &mut self.emitters {
+            if state.allocated_for != Some(state.emitter.max_particles) {
+                state.slots = 0..0;
+            }
+        }
+
+        for idx in 0..self.emitters.len() {
+            let (handle, state) = &self.emitters[idx];
+            let max_particles = state.emitter.max_particles;
+            if state.allocated_for == Some(max_particles) {
+                continue;
+            }
+
+            let mut taken: Vec<Range<u32>> = self
+                .emitters
+                .iter()
+                .map(|(_, state)| state.slots.clone())
+                .filter(|slots| !slots.is_empty())
+                .collect();
+            taken.sort_by_key(|slots| slots.start);
+
+            // First fit
+            let mut start = 0;
+            for slots in &taken {
+                if slots.start - start >= max_particles {
+                    break;
+                }
+                start = slots.end;
+            }
+
+            let slots = if MAX_PARTICLES - start >= max_particles {
+                start..start + max_particles
+            } else {
+                warn!(
+                    "Particle pool full; emitter {:?} won't spawn any particles",
+                    handle
+                );
+                0..0
+            };
+
+            let state = &mut self.emitters[idx].1;
+            state.slots = slots;
+            state.allocated_for = Some(max_particles);
+            state.reset = true;
+            state.next_spawn_slot = 0;
+            state.spawn_remainder = 0.0;
+        }
+    }
+
+    fn prepare_gpu_emitters(&mut self, dt: f32) -> Vec<GpuParticleEmitter> {
+        self.allocate_slots();
+
+        self.emitters
+            .iter_mut()
+            .map(|(_, state)| {
+                let emitter = &state.emitter;
+                let slot_count = state.slots.len() as u32;
+
+                let spawn = state.spawn_remainder + emitter.spawn_rate.max(0.0) * dt;
+                let spawn_count = spawn.floor();
+                state.spawn_remainder = spawn - spawn_count;
+                let spawn_count = (spawn_count as u32).min(slot_count);
+
+                let spawn_slot = state.next_spawn_slot;
+                if slot_count > 0 {
+                    state.next_spawn_slot = (spawn_slot + spawn_count) % slot_count;
+                }
+
+                let reset = std::mem::take(&mut state.reset);
+
+                GpuParticleEmitter {
+                    position: emitter.position.into(),
+                    spawn_radius: emitter.spawn_radius.max(0.0),
+                    velocity: emitter.velocity.into(),
+                    velocity_randomness: emitter.velocity_randomness.max(0.0),
+                    acceleration: emitter.acceleration.into(),
+                    drag: emitter.drag.max(0.0),
+                    color_start: emitter.color[0].into(),
+                    color_end: emitter.color[1].into(),
+                    emissive: emitter.emissive.into(),
+                    lifetime: emitter.lifetime.max(1e-3),
+                    size_start: emitter.size[0].max(0.0),
+                    size_end: emitter.size[1].max(0.0),
+                    first_slot: state.slots.start,
+                    slot_count,
+                    spawn_slot,
+                    spawn_count,
+                    blend_mode: match emitter.blend_mode {
+                        ParticleBlendMode::Alpha => 0,
+                        ParticleBlendMode::Additive => 1,
+                    },
+                    texture: emitter
+                        .texture
+                        .map_or(GPU_PARTICLE_NO_TEXTURE, |texture| texture.0),
+                    reset: reset as u32,
+                    pad: [0; 3],
+                }
+            })
+            .collect()
+    }
+
+    /// Simulates the particles by `dt` seconds, lights them, and draws them over `output`,
+    /// which is expected to be lit and fogged already.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        render_pass: Arc<RenderPass>,
+        dt: f32,
+        depth: &mut rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+        responsive_mask: &mut rg::Handle<Image>,
+        convolved_sky_cube: &rg::Handle<Image>,
+        ircache: &mut IrcacheRenderState,
+        fog: Option<&IntegratedFog>,
+        bindless_descriptor_set: vk::DescriptorSet,
+        tlas: Option<&rg::Handle<RayTracingAcceleration>>,
+    ) {
+        if !self.enabled || self.emitters.is_empty() {
+            return;
+        }
+
+        let emitters = self.prepare_gpu_emitters(dt);
+
+        let mut particles = rg
+            .get_or_create_temporal(
+                "particles.pool",
+                BufferDesc::new_gpu_only(
+                    MAX_PARTICLES as usize * PARTICLE_STRIDE,
+                    vk::BufferUsageFlags::STORAGE_BUFFER,
+                ),
+            )
+            .unwrap();
+
+        // Distance from the eye, and particle index
+        let mut sort_keys = rg.create(BufferDesc::new_gpu_only(
+            MAX_PARTICLES as usize * size_of::<[u32; 2]>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        ));
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("particle simulate"),
+            "/shaders/particles/simulate.hlsl",
+        )
+        .write(&mut particles)
+        .write(&mut sort_keys)
+        .dynamic_storage_buffer_vec(emitters.clone())
+        .constants((emitters.len() as u32, dt))
+        .dispatch([MAX_PARTICLES, 1, 1]);
+
+        if let Some(tlas) = tlas {
+            SimpleRenderPass::new_rt(
+                rg.add_pass("particle light"),
+                ShaderSource::hlsl("/shaders/particles/light.rgen.hlsl"),
+                [
+                    // Duplicated because `rt.hlsl` hardcodes miss index to 1
+                    ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                    ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                ],
+                super::rt_shadow_hit_groups(),
+            )
+            .write(&mut particles)
+            .bind_mut(ircache)
+            .raw_descriptor_set(1, bindless_descriptor_set)
+            .trace_rays(tlas, [MAX_PARTICLES, 1, 1]);
+        } else {
+            SimpleRenderPass::new_compute(
+                rg.add_pass("particle light"),
+                "/shaders/particles/light.hlsl",
+            )
+            .write(&mut particles)
+            .read(convolved_sky_cube)
+            .dispatch([MAX_PARTICLES, 1, 1]);
+        }
+
+        sort_back_to_front(rg, &mut sort_keys);
+
+        // Not sampled without fog; only there for the binding.
+        let fog_placeholder;
+        let (fog_volume, fog_constants) = match fog {
+            Some(fog) => (&fog.volume, Some(fog.constants)),
+            None => {
+                fog_placeholder = rg.create(ImageDesc::new_3d(
+                    vk::Format::R16G16B16A16_SFLOAT,
+                    [1, 1, 1],
+                ));
+                (&fog_placeholder, None)
+            }
+        };
+
+        SimpleRenderPass::new_raster(
+            rg.add_pass("particle draw"),
+            render_pass,
+            ShaderSource::hlsl("/shaders/particles/draw_vs.hlsl"),
+            ShaderSource::hlsl("/shaders/particles/draw_ps.hlsl"),
+            RasterPipelineDesc::builder()
+                .face_cull(false)
+                .depth_write(false)
+                .alpha_blend(true),
+        )
+        .read(&particles)
+        .read(&sort_keys)
+        .dynamic_storage_buffer_vec(emitters)
+        .read(fog_volume)
+        .constants((
+            output.desc().extent_inv_extent_2d(),
+            fog_constants.unwrap_or_default(),
+            fog_constants.is_some() as u32,
+        ))
+        .raw_descriptor_set(1, bindless_descriptor_set)
+        .color_attachment(output)
+        .color_attachment(responsive_mask)
+        .depth_attachment(depth)
+        .draw(|_api, pipeline| {
+            // A quad instance per slot, in the sorted order; dead particles collapse to nothing.
+            pipeline.draw(6, MAX_PARTICLES, 0, 0);
+        });
+    }
+}
+
+/// Bitonic sort of all the keys, in decreasing order of distance. Blocks of `SORT_BLOCK_SIZE`
+/// are sorted and merged in groupshared memory, and only the wider merge steps go through
+/// separate passes.
+fn sort_back_to_front(rg: &mut rg::TemporalRenderGraph, keys: &mut rg::Handle<Buffer>) {
+    SimpleRenderPass::new_compute(
+        rg.add_pass("particle sort"),
+        "/shaders/particles/sort_local.hlsl",
+    )
+    .write(keys)
+    .constants(0u32)
+    .dispatch([MAX_PARTICLES / 2, 1, 1]);
+
+    let mut merge_size = SORT_BLOCK_SIZE * 2;
+    while merge_size <= MAX_PARTICLES {
+        let mut compare_distance = merge_size / 2;
+        while compare_distance >= SORT_BLOCK_SIZE {
+            SimpleRenderPass::new_compute(
+                rg.add_pass("particle sort"),
+                "/shaders/particles/sort_step.hlsl",
+            )
+            .write(keys)
+            .constants((merge_size, compare_distance))
+            .dispatch([MAX_PARTICLES / 2, 1, 1]);
+
+            compare_distance /= 2;
+        }
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("particle sort"),
+            "/shaders/particles/sort_local.hlsl",
+        )
+        .write(keys)
+        .constants(merge_size)
+        .dispatch([MAX_PARTICLES / 2, 1, 1]);
+
+        merge_size *= 2;
+    }
+}
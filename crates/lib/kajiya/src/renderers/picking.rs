@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use kajiya_backend::{
+    ash::vk,
+    vk_sync::AccessType,
+    vulkan::{
+        buffer::{Buffer, BufferDesc},
+        device::Device,
+        image::*,
+    },
+    BackendError,
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use crate::world_renderer::InstanceHandle;
+
+use super::READBACK_FRAME_LATENCY;
+
+const READBACK_SLOT_COUNT: usize = READBACK_FRAME_LATENCY as usize + 1;
+
+struct PickReadback {
+    buffer: Arc<Buffer>,
+
+    /// Pick position and frame the readback was recorded for, if in flight.
+    request: Option<([u32; 2], u32)>,
+
+    /// `instance_handles` of the world at the time; the G-buffer stores indices into it.
+    instance_handles: Vec<InstanceHandle>,
+}
+
+/// Reads back the instance ID target of the G-buffer pass under a single pixel per frame.
+pub(crate) struct GpuPicking {
+    slots: Vec<PickReadback>,
+    requested: Option<[u32; 2]>,
+    latest: Option<([u32; 2], Option<InstanceHandle>)>,
+}
+
+impl GpuPicking {
+    pub(crate) fn new(device: &Device) -> Result<Self, BackendError> {
+        let slots = (0..READBACK_SLOT_COUNT)
+            .map(|_| {
+                Ok(PickReadback {
+                    buffer: Arc::new(device.create_buffer(
+                        BufferDesc::new_gpu_to_cpu(
+                            std::mem::size_of::<u32>(),
+                            vk::BufferUsageFlags::STORAGE_BUFFER,
+                        ),
+                        "picking readback",
+                        None,
+                    )?),
+                    request: None,
+                    instance_handles: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, BackendError>>()?;
+
+        Ok(Self {
+            slots,
+            requested: None,
+            latest: None,
+        })
+    }
+
+    /// Requests a readback at `pos` for the next frame, and returns the most recent
+    /// finished result for the same position.
+    pub(crate) fn pick(&mut self, pos: [u32; 2]) -> Option<Option<InstanceHandle>> {
+        self.requested = Some(pos);
+
+        self.latest
+            .filter(|(latest_pos, _)| *latest_pos == pos)
+            .map(|(_, instance)| instance)
+    }
+
+    /// Copies the instance ID under the requested position to the CPU.
+    /// `pos` is relative to `output_extent`, which `instance_id_img` is scaled to.
+    pub(crate) fn record_readback(
+        &mut self,
+        rg: &mut rg::RenderGraph,
+        instance_id_img: &rg::Handle<Image>,
+        output_extent: [u32; 2],
+        instance_handles: &[InstanceHandle],
+        frame_idx: u32,
+    ) {
+        let pos = if let Some(pos) = self.requested.take() {
+            pos
+        } else {
+            return;
+        };
+
+        let id_extent = instance_id_img.desc().extent_2d();
+        if pos[0] >= output_extent[0] || pos[1] >= output_extent[1] {
+            self.latest = Some((pos, None));
+            return;
+        }
+
+        let id_pos = [
+            (pos[0] as u64 * id_extent[0] as u64 / output_extent[0] as u64) as u32,
+            (pos[1] as u64 * id_extent[1] as u64 / output_extent[1] as u64) as u32,
+        ];
+
+        let slot = &mut self.slots[frame_idx as usize % READBACK_SLOT_COUNT];
+        let mut readback_buf = rg.import(slot.buffer.clone(), AccessType::Nothing);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("picking readback"),
+            "/shaders/pick_instance.hlsl",
+        )
+        .read(instance_id_img)
+        .write(&mut readback_buf)
+        .constants(id_pos)
+        .dispatch([1, 1, 1]);
+
+        slot.request = Some((pos, frame_idx));
+        slot.instance_handles.clear();
+        slot.instance_handles.extend_from_slice(instance_handles);
+    }
+
+    /// Picks up the result of the readback the GPU has finished; called once per frame,
+    /// so there's at most one. Handles of instances removed since are not returned.
+    pub(crate) fn resolve_finished(
+        &mut self,
+        frame_idx: u32,
+        is_live: impl Fn(InstanceHandle) -> bool,
+    ) {
+        for slot in &mut self.slots {
+            let (pos, slot_frame_idx) = match slot.request {
+                Some(request) => request,
+                None => continue,
+            };
+
+            if frame_idx.wrapping_sub(slot_frame_idx) < READBACK_FRAME_LATENCY {
+                continue;
+            }
+
+            slot.request = None;
+
+            let id = if let Some(src) = slot.buffer.allocation.mapped_slice() {
+                bytemuck::checked::cast_slice::<u8, u32>(src)[0]
+            } else {
+                continue;
+            };
+
+            // Zero is the background; instances are stored off by one.
+            let instance = id
+                .checked_sub(1)
+                .and_then(|idx| slot.instance_handles.get(idx as usize).copied())
+                .filter(|&handle| is_live(handle));
+
+            self.latest = Some((pos, instance));
+        }
+    }
+}
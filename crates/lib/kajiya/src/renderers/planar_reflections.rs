@@ -0,0 +1,218 @@
+use glam::{Mat4, Vec3, Vec4};
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+use rust_shaders_shared::camera::CameraMatrices;
+
+use super::GbufferDepth;
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct PlanarReflectionHandle(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlanarReflection {
+    /// World-space plane of the mirror or water surface, as `dot(xyz, position) + w = 0`,
+    /// with the unit normal `xyz` pointing out of the reflective side.
+    pub plane: Vec4,
+
+    /// How far from the plane the G-buffer surfaces may be to use the reflection.
+    /// Fades out over the outer half.
+    pub max_distance: f32,
+
+    /// Rougher surfaces keep their ray traced or screen-space reflections.
+    /// Fades out over the upper half.
+    pub max_roughness: f32,
+
+    /// Offsets the reflection by the shading normal's departure from the plane,
+    /// in screen units, for ripples on water and warped mirrors.
+    pub normal_distortion: f32,
+
+    /// Geometry closer than this to the plane is clipped from the reflection,
+    /// so that the reflective surface itself doesn't show up in it.
+    pub clip_bias: f32,
+}
+
+impl PlanarReflection {
+    pub fn new(plane: Vec4) -> Self {
+        let normal_length = plane.truncate().length().max(1e-20);
+
+        Self {
+            plane: plane / normal_length,
+            max_distance: 0.05,
+            max_roughness: 0.2,
+            normal_distortion: 0.1,
+            clip_bias: 0.005,
+        }
+    }
+
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self::new(normal.extend(-normal.dot(point)))
+    }
+
+    /// Of the main camera mirrored through the plane.
+    ///
+    /// The reflection flips the handedness of the view, which is flipped back by mirroring
+    /// the image horizontally, so that triangles keep their winding; the reflection image is
+    /// thus sampled at `1 - u`.
+    pub(crate) fn mirrored_camera_matrices(&self, camera: &CameraMatrices) -> CameraMatrices {
+        let n = self.plane.truncate();
+        let d = self.plane.w;
+
+        let reflection = Mat4::from_cols(
+            (Vec3::X - 2.0 * n.x * n).extend(0.0),
+            (Vec3::Y - 2.0 * n.y * n).extend(0.0),
+            (Vec3::Z - 2.0 * n.z * n).extend(0.0),
+            (-2.0 * d * n).extend(1.0),
+        );
+        let flip_x = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+
+        CameraMatrices {
+            view_to_clip: camera.view_to_clip,
+            clip_to_view: camera.clip_to_view,
+            world_to_view: flip_x * camera.world_to_view * reflection,
+            view_to_world: reflection * camera.view_to_world * flip_x,
+        }
+    }
+
+    /// For the mirrored view; keeps what's on the reflective side.
+    pub(crate) fn clip_plane(&self) -> Vec4 {
+        self.plane - Vec4::new(0.0, 0.0, 0.0, self.clip_bias.max(0.0))
+    }
+
+    /// The reflection isn't visible from behind the plane.
+    pub(crate) fn faces(&self, eye_position: Vec3) -> bool {
+        self.plane.dot(eye_position.extend(1.0)) > 0.0
+    }
+}
+
+/// Flat mirrors and water, rendered by mirroring the main camera through their planes.
+///
+/// Each enabled reflection visible from the camera costs a forward-shaded view per frame,
+/// which replaces the ray traced or screen-space reflections of the smooth surfaces on its
+/// plane. For a few large, perfectly flat surfaces, that's both cheaper and sharper than
+/// tracing them; the view has no ray traced effects of its own though.
+pub struct PlanarReflections {
+    pub enabled: bool,
+
+    /// Of the reflection views, relative to the render extent of the main view.
+    pub resolution_scale: f32,
+
+    reflections: Vec<(PlanarReflectionHandle, PlanarReflection)>,
+    next_handle: usize,
+}
+
+impl Default for PlanarReflections {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolution_scale: 0.5,
+            reflections: Default::default(),
+            next_handle: 0,
+        }
+    }
+}
+
+impl PlanarReflections {
+    pub fn add(&mut self, reflection: PlanarReflection) -> PlanarReflectionHandle {
+        let handle = PlanarReflectionHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.reflections.push((handle, reflection));
+        handle
+    }
+
+    pub fn remove(&mut self, reflection: PlanarReflectionHandle) {
+        let index = self
+            .reflections
+            .iter()
+            .position(|(handle, _)| *handle == reflection)
+            .expect("no such planar reflection");
+        self.reflections.swap_remove(index);
+    }
+
+    pub fn get(&self, reflection: PlanarReflectionHandle) -> &PlanarReflection {
+        self.reflections
+            .iter()
+            .find(|(handle, _)| *handle == reflection)
+            .map(|(_, reflection)| reflection)
+            .expect("no such planar reflection")
+    }
+
+    pub fn get_mut(&mut self, reflection: PlanarReflectionHandle) -> &mut PlanarReflection {
+        self.reflections
+            .iter_mut()
+            .find(|(handle, _)| *handle == reflection)
+            .map(|(_, reflection)| reflection)
+            .expect("no such planar reflection")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PlanarReflectionHandle, &PlanarReflection)> {
+        self.reflections
+            .iter()
+            .map(|(handle, reflection)| (*handle, reflection))
+    }
+
+    pub fn len(&self) -> usize {
+        self.reflections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reflections.is_empty()
+    }
+
+    /// Those to render this frame, seen from `eye_position`.
+    pub(crate) fn visible(
+        &self,
+        eye_position: Vec3,
+    ) -> Vec<(PlanarReflectionHandle, PlanarReflection)> {
+        self.reflections
+            .iter()
+            .filter(|_| self.enabled)
+            .filter(|(_, reflection)| reflection.faces(eye_position))
+            .copied()
+            .collect()
+    }
+
+    pub(crate) fn view_extent(&self, render_extent: [u32; 2]) -> [u32; 2] {
+        let scale = self.resolution_scale.clamp(0.05, 1.0);
+        [
+            ((render_extent[0] as f32 * scale) as u32).max(1),
+            ((render_extent[1] as f32 * scale) as u32).max(1),
+        ]
+    }
+}
+
+/// Blends the rendered reflections into the reflected radiance of the G-buffer surfaces
+/// on their planes, ahead of `light_gbuffer`.
+pub(crate) fn composite_planar_reflections(
+    rg: &mut rg::RenderGraph,
+    gbuffer_depth: &GbufferDepth,
+    reflected_radiance: rg::Handle<Image>,
+    rendered: &[(PlanarReflectionHandle, PlanarReflection, rg::Handle<Image>)],
+) -> rg::Handle<Image> {
+    rendered
+        .iter()
+        .fold(reflected_radiance, |input, (_, reflection, image)| {
+            let mut output = rg.create(input.desc().usage(vk::ImageUsageFlags::empty()));
+
+            SimpleRenderPass::new_compute(
+                rg.add_pass("planar reflection"),
+                "/shaders/planar_reflection_composite.hlsl",
+            )
+            .read(&input)
+            .read(&gbuffer_depth.gbuffer)
+            .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+            .read(image)
+            .write(&mut output)
+            .constants((
+                output.desc().extent_inv_extent_2d(),
+                reflection.plane.to_array(),
+                reflection.max_distance.max(1e-5),
+                reflection.max_roughness.clamp(1e-3, 1.0),
+                reflection.normal_distortion,
+            ))
+            .dispatch(output.desc().extent);
+
+            output
+        })
+}
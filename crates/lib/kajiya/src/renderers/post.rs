@@ -1,10 +1,17 @@
-use std::sync::Arc;
+use anyhow::Context;
+use half::f16;
+use std::{path::Path, sync::Arc};
 
 use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::image::*, BackendError, Device};
 use kajiya_rg::{self as rg};
 use rg::{Buffer, BufferDesc, RenderGraph, SimpleRenderPass};
 
-use crate::world_renderer::HistogramClipping;
+use crate::world_renderer::{DynamicExposureState, DYNAMIC_EXPOSURE_BIAS};
+
+use super::cube_lut::CubeLut;
+use super::post_fx::{
+    ChromaticAberrationFx, FilmGrainFx, PostFx, PostFxContext, PostFxStack, VignetteFx,
+};
 
 pub fn blur_pyramid(rg: &mut RenderGraph, input: &rg::Handle<Image>) -> rg::Handle<Image> {
     let skip_n_bottom_mips = 1;
@@ -31,105 +38,466 @@ pub fn blur_pyramid(rg: &mut RenderGraph, input: &rg::Handle<Image>) -> rg::Hand
         )
         .dispatch(output.desc().extent);
 
-    for target_mip in 1..(output.desc().mip_levels as u32) {
+    blur_remaining_mips(rg, &mut output, "_blur");
+
+    output
+}
+
+/// Fills mips `1..` of `pyramid` by successively blurring and downsampling mip 0.
+fn blur_remaining_mips(rg: &mut RenderGraph, pyramid: &mut rg::Handle<Image>, pass_prefix: &str) {
+    for target_mip in 1..(pyramid.desc().mip_levels as u32) {
         let downsample_amount = 1 << target_mip;
 
         SimpleRenderPass::new_compute(
-            rg.add_pass(&format!("_blur{}", target_mip)),
+            rg.add_pass(&format!("{}{}", pass_prefix, target_mip)),
             "/shaders/blur.hlsl",
         )
         .read_view(
-            &output,
+            pyramid,
             ImageViewDesc::builder()
                 .base_mip_level(target_mip - 1)
                 .level_count(Some(1)),
         )
         .write_view(
-            &mut output,
+            pyramid,
             ImageViewDesc::builder()
                 .base_mip_level(target_mip)
                 .level_count(Some(1)),
         )
         .dispatch(
-            output
+            pyramid
                 .desc()
                 .div_extent([downsample_amount, downsample_amount, 1])
                 .extent,
         );
     }
+}
+
+/// Upper bound on the number of bloom mips. Fewer are used at low resolutions.
+pub const BLOOM_MAX_MIP_COUNT: usize = 8;
+
+/// Smallest dimension of the coarsest bloom mip, which limits the mip count.
+const BLOOM_MIN_MIP_SIZE: u32 = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BloomParams {
+    /// Fraction of the bloomed energy which gets spread out.
+    pub intensity: f32,
+
+    /// Brightness of the exposed image above which it blooms.
+    /// Zero lets everything bloom, which approximates glare in the eye.
+    pub threshold: f32,
+
+    /// Width of the soft transition around `threshold`.
+    pub knee: f32,
+
+    /// Contribution of each mip, starting from the half-resolution one.
+    /// Normalized over the mips in use, so only their ratios matter.
+    pub mip_intensities: [f32; BLOOM_MAX_MIP_COUNT],
+
+    /// Scale of the lens dirt texture's modulation of the bloom, if one is loaded.
+    pub lens_dirt_intensity: f32,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            intensity: 0.05,
+            threshold: 0.0,
+            knee: 0.5,
+            mip_intensities: [1.0, 0.7, 0.49, 0.34, 0.24, 0.17, 0.12, 0.08],
+            lens_dirt_intensity: 2.0,
+        }
+    }
+}
+
+/// Number of bloom mips for an image of the given size, such that the coarsest one
+/// is still a few pixels across.
+pub fn bloom_mip_count(extent: [u32; 2]) -> usize {
+    let half_res_min_dim = (extent[0].min(extent[1]) / 2).max(1);
+    let coarsest_mip = 31
+        - (half_res_min_dim / BLOOM_MIN_MIP_SIZE)
+            .max(1)
+            .leading_zeros();
+    let count = coarsest_mip as usize + 1;
+    count.min(BLOOM_MAX_MIP_COUNT)
+}
+
+/// Half-resolution pyramid of what's above the bloom threshold.
+fn bloom_threshold_pyramid(
+    rg: &mut RenderGraph,
+    input: &rg::Handle<Image>,
+    params: &BloomParams,
+    post_exposure_mult: f32,
+    mip_count: usize,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(
+        input
+            .desc()
+            .half_res()
+            .format(vk::Format::B10G11R11_UFLOAT_PACK32)
+            .mip_levels(mip_count as _),
+    );
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass("bloom prefilter"),
+        "/shaders/bloom/prefilter.hlsl",
+    )
+    .read(input)
+    .write_view(
+        &mut output,
+        ImageViewDesc::builder()
+            .base_mip_level(0)
+            .level_count(Some(1)),
+    )
+    .constants((
+        input.desc().extent_inv_extent_2d(),
+        output.desc().extent_inv_extent_2d(),
+        params.threshold / post_exposure_mult,
+        params.knee.max(1e-3) / post_exposure_mult,
+    ))
+    .dispatch(output.desc().extent);
+
+    blur_remaining_mips(rg, &mut output, "_bloom blur");
 
     output
 }
 
-pub fn rev_blur_pyramid(rg: &mut RenderGraph, in_pyramid: &rg::Handle<Image>) -> rg::Handle<Image> {
-    let mut output = rg.create(*in_pyramid.desc());
+/// Sums up the mips of `pyramid`, weighted by `mip_intensities`, into mip 0 of the output.
+fn bloom_upsample(
+    rg: &mut RenderGraph,
+    pyramid: &rg::Handle<Image>,
+    params: &BloomParams,
+    mip_count: usize,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(pyramid.desc().mip_levels(mip_count as _));
+
+    let mip_intensities = &params.mip_intensities[..mip_count];
+    let intensity_sum: f32 = mip_intensities.iter().map(|w| w.max(0.0)).sum();
+    let mip_weight = |mip: usize| mip_intensities[mip].max(0.0) / intensity_sum.max(1e-5);
+
+    let mip_extent_inv_extent = |mip: usize| {
+        pyramid
+            .desc()
+            .div_extent([1 << mip, 1 << mip, 1])
+            .extent_inv_extent_2d()
+    };
 
-    for target_mip in (0..(output.desc().mip_levels as u32 - 1)).rev() {
-        let downsample_amount = 1 << target_mip;
-        let output_extent: [u32; 3] = output
+    for target_mip in (0..mip_count).rev() {
+        let is_coarsest = target_mip + 1 == mip_count;
+        let output_extent = output
             .desc()
-            .div_extent([downsample_amount, downsample_amount, 1])
+            .div_extent([1 << target_mip, 1 << target_mip, 1])
             .extent;
-        let src_mip: u32 = target_mip + 1;
-        let self_weight = if src_mip == output.desc().mip_levels as u32 {
-            0.0f32
-        } else {
-            0.5f32
-        };
 
-        SimpleRenderPass::new_compute_rust(
-            rg.add_pass(&format!("_rev_blur{}", target_mip)),
-            "rev_blur::rev_blur_cs",
-        )
-        .read_view(
-            in_pyramid,
-            ImageViewDesc::builder()
-                .base_mip_level(target_mip)
-                .level_count(Some(1)),
+        let pass = SimpleRenderPass::new_compute(
+            rg.add_pass(&format!("_bloom upsample{}", target_mip)),
+            "/shaders/bloom/upsample.hlsl",
         )
         .read_view(
-            &output,
+            pyramid,
             ImageViewDesc::builder()
-                .base_mip_level(src_mip)
+                .base_mip_level(target_mip as u32)
                 .level_count(Some(1)),
-        )
-        .write_view(
+        );
+
+        // The coarsest mip has nothing below it; bind something valid, and give it zero weight.
+        let (pass, coarser_mip) = if is_coarsest {
+            (
+                pass.read_view(
+                    pyramid,
+                    ImageViewDesc::builder()
+                        .base_mip_level(target_mip as u32)
+                        .level_count(Some(1)),
+                ),
+                target_mip,
+            )
+        } else {
+            (
+                pass.read_view(
+                    &output,
+                    ImageViewDesc::builder()
+                        .base_mip_level(target_mip as u32 + 1)
+                        .level_count(Some(1)),
+                ),
+                target_mip + 1,
+            )
+        };
+
+        pass.write_view(
             &mut output,
             ImageViewDesc::builder()
-                .base_mip_level(target_mip)
+                .base_mip_level(target_mip as u32)
                 .level_count(Some(1)),
         )
-        .constants((output_extent[0], output_extent[1], self_weight))
+        .constants((
+            mip_extent_inv_extent(target_mip),
+            mip_extent_inv_extent(coarser_mip),
+            mip_weight(target_mip),
+            if is_coarsest { 0.0f32 } else { 1.0f32 },
+        ))
         .dispatch(output_extent);
     }
 
     output
 }
 
+/// Glare from the blur pyramid, or art-directable bloom if thresholded.
+#[derive(Default)]
+pub struct BloomFx {
+    pub params: BloomParams,
+    lens_dirt_image: Option<image::RgbaImage>,
+    lens_dirt_texture: Option<Arc<Image>>,
+}
+
+impl BloomFx {
+    /// Loads an image to modulate the bloom with, as if it was scattered by dirt on the lens.
+    pub fn load_lens_dirt(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let img = image::open(path)
+            .with_context(|| format!("Loading lens dirt from {:?}", path))?
+            .to_rgba8();
+
+        self.lens_dirt_image = Some(img);
+
+        // Force re-creation of the texture
+        self.lens_dirt_texture = None;
+
+        Ok(())
+    }
+
+    pub fn unload_lens_dirt(&mut self) {
+        self.lens_dirt_image = None;
+        self.lens_dirt_texture = None;
+    }
+
+    fn lens_dirt(&mut self, rg: &mut rg::TemporalRenderGraph) -> Option<rg::Handle<Image>> {
+        if self.lens_dirt_texture.is_none() {
+            const PIXEL_BYTES: u32 = 4;
+
+            if let Some(image) = self.lens_dirt_image.take() {
+                let size = [image.width(), image.height()];
+                self.lens_dirt_texture = Some(Arc::new(
+                    rg.device()
+                        .create_image(
+                            ImageDesc::new_2d(vk::Format::R8G8B8A8_SRGB, size)
+                                .usage(vk::ImageUsageFlags::SAMPLED),
+                            vec![ImageSubResourceData {
+                                data: image.as_raw(),
+                                row_pitch: (size[0] * PIXEL_BYTES) as usize,
+                                slice_pitch: (size[0] * size[1] * PIXEL_BYTES) as usize,
+                            }],
+                        )
+                        .expect("create_image"),
+                ));
+            }
+        }
+
+        self.lens_dirt_texture.clone().map(|texture| {
+            rg.import(
+                texture,
+                AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+            )
+        })
+    }
+}
+
+impl PostFx for BloomFx {
+    fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+    ) {
+        let bloom_mip_count = bloom_mip_count(input.desc().extent_2d())
+            .min(ctx.blur_pyramid.desc().mip_levels as usize);
+
+        // Without a threshold, the pyramid for the histogram has everything the bloom needs.
+        let bloom_tex = if self.params.threshold > 0.0 {
+            let threshold_pyramid = bloom_threshold_pyramid(
+                rg,
+                input,
+                &self.params,
+                ctx.post_exposure_mult,
+                bloom_mip_count,
+            );
+            bloom_upsample(rg, &threshold_pyramid, &self.params, bloom_mip_count)
+        } else {
+            bloom_upsample(rg, ctx.blur_pyramid, &self.params, bloom_mip_count)
+        };
+
+        let (lens_dirt_tex, lens_dirt_intensity) = match self.lens_dirt(rg) {
+            Some(tex) => (tex, self.params.lens_dirt_intensity),
+            None => {
+                let mut tex = rg.create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]));
+                rg::imageops::clear_color(rg, &mut tex, [0.0; 4]);
+                (tex, 0.0)
+            }
+        };
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("bloom composite"),
+            "/shaders/bloom/composite.hlsl",
+        )
+        .read(input)
+        .read(&bloom_tex)
+        .read(&lens_dirt_tex)
+        .write(output)
+        .constants((
+            output.desc().extent_inv_extent_2d(),
+            self.params.intensity,
+            self.params.threshold / ctx.post_exposure_mult,
+            self.params.knee.max(1e-3) / ctx.post_exposure_mult,
+            lens_dirt_intensity,
+        ))
+        .dispatch(output.desc().extent);
+    }
+}
+
 const LUMINANCE_HISTOGRAM_BIN_COUNT: usize = 256;
-const LUMINANCE_HISTOGRAM_MIN_LOG2: f64 = -16.0;
-const LUMINANCE_HISTOGRAM_MAX_LOG2: f64 = 16.0;
+
+/// Maps the exposed HDR image to the display.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TonemapOperator {
+    /// Hue-preserving display transform with perceptual gamut compression.
+    Neutral,
+    /// Stephen Hill's fit of the ACES RRT and sRGB ODT.
+    AcesFitted,
+    /// Troy Sobotka's AgX, via a polynomial fit of its base contrast curve.
+    AgX,
+    /// Reinhard on luminance, with colors scaled along.
+    Reinhard,
+}
+
+impl TonemapOperator {
+    /// Matches the `TONEMAP_*` defines in `post_combine.hlsl`.
+    fn shader_index(self) -> u32 {
+        match self {
+            TonemapOperator::Neutral => 0,
+            TonemapOperator::AcesFitted => 1,
+            TonemapOperator::AgX => 2,
+            TonemapOperator::Reinhard => 3,
+        }
+    }
+}
 
 pub struct PostProcessRenderer {
-    histogram_buffer: Arc<Buffer>,
+    exposure_state_buffer: Arc<Buffer>,
+    exposure_state_valid: bool,
     pub image_log2_lum: f32,
+    adapted_ev: f32,
+    pub stack: PostFxStack,
+    pub tonemap: TonemapOperator,
+    grading_lut: Option<CubeLut>,
+    grading_lut_texture: Option<(Arc<Image>, [f32; 3], [f32; 3])>,
 }
 
 impl PostProcessRenderer {
+    fn default_stack() -> PostFxStack {
+        let mut stack = PostFxStack::default();
+        stack.register("bloom", true, BloomFx::default());
+        stack.register(
+            "chromatic aberration",
+            false,
+            ChromaticAberrationFx::default(),
+        );
+        stack.register("vignette", true, VignetteFx::default());
+        stack.register("film grain", false, FilmGrainFx::default());
+        stack
+    }
+
     pub fn new(device: &Device) -> Result<Self, BackendError> {
         Ok(Self {
-            histogram_buffer: Arc::new(device.create_buffer(
+            exposure_state_buffer: Arc::new(device.create_buffer(
                 BufferDesc::new_gpu_to_cpu(
-                    std::mem::size_of::<u32>() * LUMINANCE_HISTOGRAM_BIN_COUNT,
+                    std::mem::size_of::<[f32; 4]>(),
                     vk::BufferUsageFlags::STORAGE_BUFFER,
                 ),
-                "luminance histogram",
+                "exposure state",
                 None,
             )?),
+            exposure_state_valid: false,
             image_log2_lum: 0.0,
+            adapted_ev: 0.0,
+            stack: Self::default_stack(),
+            tonemap: TonemapOperator::Neutral,
+            grading_lut: None,
+            grading_lut_texture: None,
         })
     }
 
+    /// Loads a `.cube` 3D LUT to grade the display-encoded image with, after tonemapping.
+    pub fn load_grading_lut(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.grading_lut = Some(CubeLut::load(path)?);
+
+        // Force re-creation of the texture
+        self.grading_lut_texture = None;
+
+        Ok(())
+    }
+
+    pub fn unload_grading_lut(&mut self) {
+        self.grading_lut = None;
+        self.grading_lut_texture = None;
+    }
+
+    /// The grading LUT texture and its input domain, if one is loaded.
+    fn grading_lut(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+    ) -> Option<(rg::Handle<Image>, [f32; 3], [f32; 3])> {
+        if self.grading_lut_texture.is_none() {
+            if let Some(lut) = self.grading_lut.take() {
+                let data: Vec<f16> = lut
+                    .data
+                    .iter()
+                    .flat_map(|&[r, g, b]| {
+                        [
+                            f16::from_f32(r),
+                            f16::from_f32(g),
+                            f16::from_f32(b),
+                            f16::ONE,
+                        ]
+                        .into_iter()
+                    })
+                    .collect();
+
+                const PIXEL_BYTES: u32 = 8;
+                let size = lut.size;
+
+                let texture = rg
+                    .device()
+                    .create_image(
+                        ImageDesc::new_3d(vk::Format::R16G16B16A16_SFLOAT, [size, size, size])
+                            .usage(vk::ImageUsageFlags::SAMPLED),
+                        vec![ImageSubResourceData {
+                            data: bytemuck::cast_slice(data.as_slice()),
+                            row_pitch: (size * PIXEL_BYTES) as usize,
+                            slice_pitch: (size * size * PIXEL_BYTES) as usize,
+                        }],
+                    )
+                    .expect("create_image");
+
+                self.grading_lut_texture =
+                    Some((Arc::new(texture), lut.domain_min, lut.domain_max));
+            }
+        }
+
+        self.grading_lut_texture
+            .clone()
+            .map(|(texture, domain_min, domain_max)| {
+                (
+                    rg.import(
+                        texture,
+                        AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+                    ),
+                    domain_min,
+                    domain_max,
+                )
+            })
+    }
+
     fn calculate_luminance_histogram(
         &mut self,
         rg: &mut RenderGraph,
@@ -169,83 +537,100 @@ impl PostProcessRenderer {
         .constants([mip_extent[0], mip_extent[1]])
         .dispatch(mip_extent);
 
-        let mut dst_histogram = rg.import(self.histogram_buffer.clone(), AccessType::Nothing);
+        tmp_histogram
+    }
+
+    /// Adapts the exposure towards this frame's histogram, entirely on the GPU.
+    fn adapt_exposure(
+        &mut self,
+        rg: &mut RenderGraph,
+        histogram: &rg::Handle<Buffer>,
+        dynamic_exposure: &DynamicExposureState,
+    ) {
+        let mut exposure_state = rg.import(self.exposure_state_buffer.clone(), AccessType::Nothing);
+
         SimpleRenderPass::new_compute(
-            rg.add_pass("_copy histogram"),
-            "/shaders/post/luminance_histogram_copy.hlsl",
+            rg.add_pass("adapt exposure"),
+            "/shaders/post/exposure_adapt.hlsl",
         )
-        .read(&tmp_histogram)
-        .write(&mut dst_histogram)
+        .read(histogram)
+        .write(&mut exposure_state)
+        .constants((
+            dynamic_exposure.histogram_clipping.low,
+            dynamic_exposure.histogram_clipping.high,
+            dynamic_exposure.speed_log2,
+            DYNAMIC_EXPOSURE_BIAS,
+            dynamic_exposure.ev_min,
+            dynamic_exposure.ev_max,
+            (!self.exposure_state_valid) as u32,
+        ))
         .dispatch([LUMINANCE_HISTOGRAM_BIN_COUNT as u32, 1, 1]);
 
-        tmp_histogram
+        self.exposure_state_valid = true;
     }
 
-    fn read_back_histogram(&mut self, exposure_histogram_clipping: HistogramClipping) {
-        let mut histogram = [0u32; LUMINANCE_HISTOGRAM_BIN_COUNT];
-        {
-            let src = if let Some(src) = self.histogram_buffer.allocation.mapped_slice() {
-                bytemuck::checked::cast_slice::<u8, u32>(src)
-            } else {
-                return;
-            };
-
-            histogram.copy_from_slice(src);
+    /// Reads back the adaptation state from a previous frame. The latency is fine,
+    /// since the result only feeds pre-exposure, which is blended over several frames anyway.
+    fn read_back_exposure_state(&mut self) {
+        if !self.exposure_state_valid {
+            return;
         }
 
-        // Reject this much from the bottom and top end
-        let outlier_frac_lo: f64 = exposure_histogram_clipping.low.min(1.0) as f64;
-        let outlier_frac_hi: f64 =
-            (exposure_histogram_clipping.high as f64).min(1.0 - outlier_frac_lo);
-
-        let total_entry_count: u32 = histogram.iter().copied().sum();
-        let reject_lo_entry_count = (total_entry_count as f64 * outlier_frac_lo) as u32;
-        let entry_count_to_use =
-            (total_entry_count as f64 * (1.0 - outlier_frac_lo - outlier_frac_hi)) as u32;
-
-        let mut sum = 0.0;
-        let mut used_count = 0;
+        if let Some(src) = self.exposure_state_buffer.allocation.mapped_slice() {
+            let state = bytemuck::checked::cast_slice::<u8, f32>(src);
 
-        let mut left_to_reject = reject_lo_entry_count;
-        let mut left_to_use = entry_count_to_use;
-
-        for (bin_idx, count) in histogram.into_iter().enumerate() {
-            let t = (bin_idx as f64 + 0.5) / LUMINANCE_HISTOGRAM_BIN_COUNT as f64;
-
-            let count_to_use = count.saturating_sub(left_to_reject).min(left_to_use);
-            left_to_reject = left_to_reject.saturating_sub(count);
-            left_to_use = left_to_use.saturating_sub(count_to_use);
-
-            sum += t * count_to_use as f64;
-            used_count += count_to_use;
+            self.image_log2_lum = state[0];
+            self.adapted_ev = (state[1] + state[2]) * 0.5;
         }
+    }
 
-        assert_eq!(entry_count_to_use, used_count);
-
-        let mean = sum / used_count.max(1) as f64;
-        self.image_log2_lum = (LUMINANCE_HISTOGRAM_MIN_LOG2
-            + mean * (LUMINANCE_HISTOGRAM_MAX_LOG2 - LUMINANCE_HISTOGRAM_MIN_LOG2))
-            as f32;
-
-        // log::info!("mean log lum: {}", self.image_log2_lum);
+    /// Exposure compensation found by the dynamic adaptation, in EV.
+    pub fn adapted_ev(&self) -> f32 {
+        self.adapted_ev
     }
 
     pub fn render(
         &mut self,
-        rg: &mut RenderGraph,
+        rg: &mut rg::TemporalRenderGraph,
         input: &rg::Handle<Image>,
         //debug_input: &rg::Handle<Image>,
         bindless_descriptor_set: vk::DescriptorSet,
         post_exposure_mult: f32,
         contrast: f32,
-        exposure_histogram_clipping: HistogramClipping,
+        dynamic_exposure: &DynamicExposureState,
     ) -> rg::Handle<Image> {
-        self.read_back_histogram(exposure_histogram_clipping);
+        self.read_back_exposure_state();
 
         let blur_pyramid = blur_pyramid(rg, input);
         let histogram = self.calculate_luminance_histogram(rg, &blur_pyramid);
 
-        let rev_blur_pyramid = rev_blur_pyramid(rg, &blur_pyramid);
+        if dynamic_exposure.enabled {
+            self.adapt_exposure(rg, &histogram, dynamic_exposure);
+        }
+
+        let stack_output = self.stack.render(
+            rg,
+            &PostFxContext {
+                blur_pyramid: &blur_pyramid,
+                post_exposure_mult,
+                bindless_descriptor_set,
+            },
+            input,
+        );
+        let input = stack_output.as_ref().unwrap_or(input);
+
+        let (grading_lut, lut_domain_min, lut_domain_max, lut_enabled) = match self.grading_lut(rg)
+        {
+            Some((lut, domain_min, domain_max)) => (lut, domain_min, domain_max, 1u32),
+            None => {
+                let mut lut = rg.create(ImageDesc::new_3d(
+                    vk::Format::R16G16B16A16_SFLOAT,
+                    [1, 1, 1],
+                ));
+                rg::imageops::clear_color(rg, &mut lut, [0.0; 4]);
+                (lut, [0.0; 3], [1.0; 3], 0u32)
+            }
+        };
 
         let mut output = rg.create(input.desc().format(vk::Format::B10G11R11_UFLOAT_PACK32));
 
@@ -255,8 +640,8 @@ impl PostProcessRenderer {
             .read(input)
             //.read(debug_input)
             .read(&blur_pyramid)
-            .read(&rev_blur_pyramid)
             .read(&histogram)
+            .read(&grading_lut)
             //.read(&blurred_luminance)
             .write(&mut output)
             .raw_descriptor_set(1, bindless_descriptor_set)
@@ -264,6 +649,20 @@ impl PostProcessRenderer {
                 output.desc().extent_inv_extent_2d(),
                 post_exposure_mult,
                 contrast,
+                self.tonemap.shader_index(),
+                lut_enabled,
+                [
+                    lut_domain_min[0],
+                    lut_domain_min[1],
+                    lut_domain_min[2],
+                    0.0f32,
+                ],
+                [
+                    lut_domain_max[0],
+                    lut_domain_max[1],
+                    lut_domain_max[2],
+                    0.0f32,
+                ],
             ))
             .dispatch(output.desc().extent);
 
@@ -0,0 +1,214 @@
+use std::any::Any;
+
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+/// What effects in the stack can use besides their input.
+pub struct PostFxContext<'a> {
+    /// Blurred and downsampled pyramid of the stack's input, before any effects were applied.
+    pub blur_pyramid: &'a rg::Handle<Image>,
+
+    /// Converts the linear input to exposed values, where 1 is white.
+    pub post_exposure_mult: f32,
+
+    pub bindless_descriptor_set: vk::DescriptorSet,
+}
+
+/// An effect applied to the linear HDR image, before tonemapping.
+pub trait PostFx: AsAnyMut {
+    /// Writes the processed `input` into `output`, which has the same description.
+    fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+    );
+}
+
+/// Lets `PostFxStack::get_mut` recover the concrete type of an effect.
+pub trait AsAnyMut {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAnyMut for T {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub struct PostFxEntry {
+    pub name: String,
+    pub enabled: bool,
+    effect: Box<dyn PostFx>,
+}
+
+/// Ordered list of post-processing effects, each feeding into the next one.
+#[derive(Default)]
+pub struct PostFxStack {
+    entries: Vec<PostFxEntry>,
+}
+
+impl PostFxStack {
+    /// Appends an effect to the end of the stack, or replaces the one with the same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        enabled: bool,
+        effect: impl PostFx + 'static,
+    ) {
+        let name = name.into();
+        let effect = Box::new(effect);
+
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.effect = effect;
+            entry.enabled = enabled;
+        } else {
+            self.entries.push(PostFxEntry {
+                name,
+                enabled,
+                effect,
+            });
+        }
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let len_before = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.len() != len_before
+    }
+
+    pub fn entries(&self) -> &[PostFxEntry] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut [PostFxEntry] {
+        &mut self.entries
+    }
+
+    /// Moves the entry at index `from` to index `to`, shifting the ones in between.
+    pub fn move_entry(&mut self, from: usize, to: usize) {
+        if from < self.entries.len() && to < self.entries.len() {
+            let entry = self.entries.remove(from);
+            self.entries.insert(to, entry);
+        }
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.name == name) {
+            entry.enabled = enabled;
+        }
+    }
+
+    /// The first effect of type `T`, for tweaking its parameters.
+    pub fn get_mut<T: PostFx + 'static>(&mut self) -> Option<&mut T> {
+        self.entries.iter_mut().find_map(|entry| {
+            <dyn PostFx as AsAnyMut>::as_any_mut(entry.effect.as_mut()).downcast_mut::<T>()
+        })
+    }
+
+    /// Runs the enabled effects in order. Returns `None` if none are enabled.
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+    ) -> Option<rg::Handle<Image>> {
+        let mut current: Option<rg::Handle<Image>> = None;
+
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            let entry_input = current.as_ref().unwrap_or(input);
+            let mut output = rg.create(*entry_input.desc());
+            entry.effect.render(rg, ctx, entry_input, &mut output);
+            current = Some(output);
+        }
+
+        current
+    }
+}
+
+pub struct ChromaticAberrationFx {
+    /// Offset of red and blue at the corners of the screen, relative to its size.
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberrationFx {
+    fn default() -> Self {
+        Self { intensity: 0.003 }
+    }
+}
+
+impl PostFx for ChromaticAberrationFx {
+    fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        _ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+    ) {
+        SimpleRenderPass::new_compute(
+            rg.add_pass("chromatic aberration"),
+            "/shaders/post/chromatic_aberration.hlsl",
+        )
+        .read(input)
+        .write(output)
+        .constants((output.desc().extent_inv_extent_2d(), self.intensity))
+        .dispatch(output.desc().extent);
+    }
+}
+
+pub struct VignetteFx {
+    pub intensity: f32,
+}
+
+impl Default for VignetteFx {
+    fn default() -> Self {
+        Self { intensity: 2.0 }
+    }
+}
+
+impl PostFx for VignetteFx {
+    fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        _ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+    ) {
+        SimpleRenderPass::new_compute(rg.add_pass("vignette"), "/shaders/post/vignette.hlsl")
+            .read(input)
+            .write(output)
+            .constants((output.desc().extent_inv_extent_2d(), self.intensity))
+            .dispatch(output.desc().extent);
+    }
+}
+
+pub struct FilmGrainFx {
+    pub intensity: f32,
+}
+
+impl Default for FilmGrainFx {
+    fn default() -> Self {
+        Self { intensity: 0.05 }
+    }
+}
+
+impl PostFx for FilmGrainFx {
+    fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        ctx: &PostFxContext,
+        input: &rg::Handle<Image>,
+        output: &mut rg::Handle<Image>,
+    ) {
+        SimpleRenderPass::new_compute(rg.add_pass("film grain"), "/shaders/post/film_grain.hlsl")
+            .read(input)
+            .write(output)
+            .constants((
+                output.desc().extent_inv_extent_2d(),
+                self.intensity,
+                ctx.post_exposure_mult,
+            ))
+            .dispatch(output.desc().extent);
+    }
+}
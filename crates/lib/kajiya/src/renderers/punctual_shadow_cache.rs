@@ -0,0 +1,197 @@
+use kajiya_asset::mesh::{PunctualLight, PunctualLightKind};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
+};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, SimpleRenderPass};
+
+/// Of each light's octahedral distance map. Must match `lights/punctual_shadow_cache.hlsl`.
+pub const PUNCTUAL_SHADOW_CACHE_RESOLUTION: u32 = 256;
+
+/// Lights with cached shadows at most; the others trace all their shadow rays.
+pub const PUNCTUAL_SHADOW_CACHE_SLOT_COUNT: usize = 32;
+
+#[derive(Clone, Copy, PartialEq)]
+struct CachedLight {
+    position: [f32; 3],
+    range: f32,
+    last_used_frame: u64,
+}
+
+// Must match `RefreshedSlot` in `punctual_shadow_cache_refresh.rgen.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuRefreshedSlot {
+    position: [f32; 3],
+    max_distance: f32,
+    slot: u32,
+    pad: [u32; 3],
+}
+
+/// The cache for one frame, for `trace_punctual_lighting`.
+pub struct PunctualShadowCacheFrame {
+    pub distances: rg::Handle<Image>,
+
+    /// Per punctual light, in the order of the frame constants: its cache slot plus one,
+    /// or zero if it's not cached.
+    pub light_slots: Vec<u32>,
+}
+
+/// Ray traced distances to the static shadow casters around the punctual lights,
+/// kept across frames.
+///
+/// Each cached light has an octahedral map of the distances from its center, traced against
+/// the instances marked static. Shadow rays then only need to test the other instances,
+/// which is a fraction of the work in scenes with mostly static geometry.
+///
+/// A light's map is retraced when it moves or changes range, and all of them when static
+/// geometry changes; at most `lights_refreshed_per_frame` at a time. Until then, the light
+/// traces its shadows against everything. Directional lights aren't cached.
+///
+/// The maps are sampled along the rays from points on spherical lights, but from their
+/// centers, so the soft shadows of static casters are somewhat approximate.
+pub struct PunctualShadowCache {
+    pub enabled: bool,
+    pub lights_refreshed_per_frame: usize,
+
+    slots: Vec<Option<CachedLight>>,
+    frame: u64,
+}
+
+impl Default for PunctualShadowCache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lights_refreshed_per_frame: 4,
+            slots: vec![None; PUNCTUAL_SHADOW_CACHE_SLOT_COUNT],
+            frame: 0,
+        }
+    }
+}
+
+impl PunctualShadowCache {
+    /// Drops all the cached shadows, such as after static geometry changed.
+    pub fn invalidate(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+    }
+
+    /// Assigns the lights their slots, and retraces the ones which are out of date.
+    pub(crate) fn prepare(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        lights: &[PunctualLight],
+        tlas: &rg::Handle<RayTracingAcceleration>,
+        bindless_descriptor_set: vk::DescriptorSet,
+    ) -> PunctualShadowCacheFrame {
+        if !self.enabled {
+            return PunctualShadowCacheFrame {
+                distances: rg.create(ImageDesc::new_2d(vk::Format::R32_SFLOAT, [1, 1])),
+                light_slots: vec![0; lights.len()],
+            };
+        }
+
+        self.frame += 1;
+        let frame = self.frame;
+
+        let mut distances = rg
+            .get_or_create_temporal(
+                "punctual_shadow_cache.distances",
+                ImageDesc::new_2d(
+                    vk::Format::R32_SFLOAT,
+                    [
+                        PUNCTUAL_SHADOW_CACHE_RESOLUTION,
+                        PUNCTUAL_SHADOW_CACHE_RESOLUTION * PUNCTUAL_SHADOW_CACHE_SLOT_COUNT as u32,
+                    ],
+                )
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+            )
+            .unwrap();
+
+        let mut refreshed: Vec<GpuRefreshedSlot> = Vec::new();
+
+        let light_slots = lights
+            .iter()
+            .map(|light| {
+                if light.kind == PunctualLightKind::Directional as u32 {
+                    return 0;
+                }
+
+                let matches = |cached: &CachedLight| {
+                    cached.position == light.position && cached.range == light.range
+                };
+
+                if let Some(slot) = self
+                    .slots
+                    .iter()
+                    .position(|cached| cached.as_ref().map_or(false, matches))
+                {
+                    self.slots[slot].as_mut().unwrap().last_used_frame = frame;
+                    return slot as u32 + 1;
+                }
+
+                if refreshed.len() >= self.lights_refreshed_per_frame {
+                    return 0;
+                }
+
+                // Empty slots first, then the least recently used one not needed this frame.
+                let slot = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, cached)| {
+                        cached.map_or(true, |cached| cached.last_used_frame != frame)
+                    })
+                    .min_by_key(|(_, cached)| cached.map_or(0, |cached| cached.last_used_frame))
+                    .map(|(slot, _)| slot);
+
+                match slot {
+                    Some(slot) => {
+                        self.slots[slot] = Some(CachedLight {
+                            position: light.position,
+                            range: light.range,
+                            last_used_frame: frame,
+                        });
+                        refreshed.push(GpuRefreshedSlot {
+                            position: light.position,
+                            max_distance: light.range,
+                            slot: slot as u32,
+                            pad: [0; 3],
+                        });
+                        slot as u32 + 1
+                    }
+                    None => 0,
+                }
+            })
+            .collect();
+
+        if !refreshed.is_empty() {
+            let refreshed_count = refreshed.len() as u32;
+
+            SimpleRenderPass::new_rt(
+                rg.add_pass("punctual shadow cache"),
+                ShaderSource::hlsl("/shaders/rt/punctual_shadow_cache_refresh.rgen.hlsl"),
+                [
+                    ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
+                    ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                ],
+                super::rt_hit_groups(),
+            )
+            .write(&mut distances)
+            .dynamic_storage_buffer_vec(refreshed)
+            .raw_descriptor_set(1, bindless_descriptor_set)
+            .trace_rays(
+                tlas,
+                [
+                    PUNCTUAL_SHADOW_CACHE_RESOLUTION,
+                    PUNCTUAL_SHADOW_CACHE_RESOLUTION,
+                    refreshed_count,
+                ],
+            );
+        }
+
+        PunctualShadowCacheFrame {
+            distances,
+            light_slots,
+        }
+    }
+}
@@ -1,122 +1,745 @@
 use std::sync::Arc;
 
+use glam::{Affine3A, Vec3};
+use kajiya_asset::mesh::MeshMaterial;
 use kajiya_backend::{
     ash::vk,
     vk_sync::AccessType,
     vulkan::{buffer::*, image::*, shader::*},
+    BackendError, Device,
 };
 use kajiya_rg::{self as rg};
-use rg::{IntoRenderPassPipelineBinding, RenderGraph, RenderPassBinding};
+use rg::{BindRgRef, IntoRenderPassPipelineBinding, RenderGraph, RenderPassBinding};
 
-use crate::world_renderer::MeshInstance;
+use crate::{
+    math::{max_axis_scale, BoundingSphere},
+    world_renderer::MeshInstance,
+};
+
+use super::{
+    culling::{CulledDraws, DRAW_ARGS_STRIDE},
+    GbufferDepth,
+};
 
-use super::GbufferDepth;
+#[derive(Clone)]
+pub struct UploadedMeshLod {
+    pub index_buffer_offset: u64,
+    pub index_count: u32,
+    pub error: f32,
+}
 
 #[derive(Clone)]
 pub struct UploadedTriMesh {
     pub index_buffer_offset: u64,
     pub index_count: u32,
+
+    /// Progressively coarser versions of the mesh; the full-detail one is not included.
+    pub lods: Vec<UploadedMeshLod>,
+
+    /// In mesh space
+    pub bounding_sphere: BoundingSphere,
+
+    /// Whether any of the materials are drawn by the forward transparent pass.
+    pub has_alpha_blend: bool,
+
+    /// Whether any of the materials are double-sided, which disables back-face culling
+    /// for the whole mesh. Back faces of its single-sided materials are discarded instead.
+    pub double_sided: bool,
+
+    /// Whether any of the materials need the subsurface scattering pass.
+    pub has_subsurface: bool,
+
+    /// As uploaded to the vertex buffer at `material_data_offset`, with bindless map ids.
+    /// Kept for material animation, which overwrites them with animated copies.
+    pub materials: Vec<MeshMaterial>,
+    pub material_data_offset: u64,
+
+    pub vertex_count: u32,
+}
+
+impl UploadedTriMesh {
+    /// Returns the index buffer offset and index count of the coarsest LOD
+    /// whose error is at most `max_error`.
+    ///
+    /// Only the rasterized geometry changes; see `MeshLodSelection::ray_traced`.
+    pub(super) fn select_lod(&self, max_error: f32) -> (u64, u32) {
+        self.lods
+            .iter()
+            .rev()
+            .find(|lod| lod.error <= max_error)
+            .map_or((self.index_buffer_offset, self.index_count), |lod| {
+                (lod.index_buffer_offset, lod.index_count)
+            })
+    }
 }
 
+#[derive(Clone, Copy)]
+pub struct MeshLodSelection {
+    pub eye_position: Vec3,
+
+    /// Screen-space size in pixels of a unit-sized object at unit distance
+    pub pixels_per_unit_at_unit_distance: f32,
+
+    /// Maximum screen-space error in pixels; zero or less disables LOD selection.
+    pub max_pixel_error: f32,
+
+    /// Whether the frame traces rays against the acceleration structures.
+    ///
+    /// Their BLASes are always built from the full-detail meshes, so the secondary rays
+    /// leaving a rasterized LOD would hit the full-detail surface wherever the two are further
+    /// apart than the ray bias, shadowing the LOD. The error is then clamped below the bias,
+    /// which keeps only the LODs indistinguishable from full detail to the rays.
+    pub ray_traced: bool,
+}
+
+impl MeshLodSelection {
+    pub(super) fn max_mesh_space_error(&self, mesh: &UploadedTriMesh, transform: &Affine3A) -> f32 {
+        if self.max_pixel_error <= 0.0 {
+            return 0.0;
+        }
+
+        let scale = max_axis_scale(transform);
+        let sphere = mesh.bounding_sphere.transformed(transform);
+        let distance = (sphere.center.distance(self.eye_position) - sphere.radius).max(0.0);
+
+        if distance <= 0.0 || scale <= 0.0 {
+            return 0.0;
+        }
+
+        let error =
+            self.max_pixel_error * distance / (self.pixels_per_unit_at_unit_distance * scale);
+
+        if self.ray_traced {
+            // The smallest offset of `biased_secondary_ray_origin_ws_with_normal`
+            // along the normal, at the center of the mesh.
+            let ray_bias = (sphere.center.abs().max_element() * 1e-6).max(1e-4);
+            error.min(0.5 * ray_bias / scale)
+        } else {
+            error
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct RasterMeshesData<'a> {
     pub meshes: &'a [UploadedTriMesh],
     pub instances: &'a [MeshInstance],
     pub vertex_buffer: Arc<Buffer>,
     pub bindless_descriptor_set: vk::DescriptorSet,
+    pub lod_selection: MeshLodSelection,
+}
+
+/// Reads of the GPU culling output, for indirect draws: the draw arguments,
+/// the draw counts, and the maximum draw count per pipeline.
+type IndirectDraws = (
+    rg::Ref<Buffer, rg::GpuSrv>,
+    rg::Ref<Buffer, rg::GpuSrv>,
+    u32,
+);
+
+/// The opaque mesh instances, drawn the same way by the depth pre-pass and the G-buffer pass,
+/// so that their depths match exactly.
+struct OpaqueDraws {
+    meshes: Vec<UploadedTriMesh>,
+    instances: Vec<MeshInstance>,
+    vertex_buffer: Arc<Buffer>,
+    bindless_descriptor_set: vk::DescriptorSet,
+    lod_selection: MeshLodSelection,
+    indirect_draws: Option<IndirectDraws>,
+}
+
+impl OpaqueDraws {
+    fn new(
+        pass: &mut rg::PassBuilder<'_>,
+        mesh_data: RasterMeshesData<'_>,
+        culled_draws: Option<&CulledDraws>,
+    ) -> Self {
+        let indirect_draws = culled_draws.map(|draws| {
+            (
+                pass.read(&draws.draw_args, AccessType::IndirectBuffer),
+                pass.read(&draws.draw_counts, AccessType::IndirectBuffer),
+                draws.max_draw_count,
+            )
+        });
+
+        Self {
+            meshes: mesh_data.meshes.to_vec(),
+            instances: mesh_data.instances.to_vec(),
+            vertex_buffer: mesh_data.vertex_buffer.clone(),
+            bindless_descriptor_set: mesh_data.bindless_descriptor_set,
+            lod_selection: mesh_data.lod_selection,
+            indirect_draws,
+        }
+    }
+
+    /// Registers the double-sided and single-sided pipelines, in drawing order.
+    fn register_pipelines(
+        &self,
+        pass: &mut rg::PassBuilder<'_>,
+        render_pass: &Arc<RenderPass>,
+        depth_only: bool,
+        depth_prepass: bool,
+    ) -> [(rg::RgRasterPipelineHandle, bool); 2] {
+        let mut register_pipeline = |face_cull: bool| {
+            pass.register_raster_pipeline(
+                &[
+                    PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                        // .rust_source("raster_simple::raster_simple_vs")
+                        .hlsl_source("/shaders/raster_simple_vs.hlsl")
+                        .specialization_constants(vec![(0, self.indirect_draws.is_some() as u32)])
+                        .build()
+                        .unwrap(),
+                    PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                        // .rust_source("raster_simple::raster_simple_fs")
+                        .hlsl_source("/shaders/raster_simple_ps.hlsl")
+                        .specialization_constants(vec![(0, depth_only as u32)])
+                        .build()
+                        .unwrap(),
+                ],
+                RasterPipelineDesc::builder()
+                    .render_pass(render_pass.clone())
+                    .face_cull(face_cull)
+                    .depth_write(!depth_prepass)
+                    .depth_compare_op(if depth_prepass {
+                        vk::CompareOp::EQUAL
+                    } else {
+                        vk::CompareOp::GREATER_OR_EQUAL
+                    })
+                    .push_constants_bytes(2 * std::mem::size_of::<u32>()),
+            )
+        };
+
+        [
+            (register_pipeline(false), true),
+            (register_pipeline(true), false),
+        ]
+    }
+
+    /// Call within the render pass.
+    fn draw(
+        &self,
+        api: &mut rg::RenderPassApi,
+        pipelines: [(rg::RgRasterPipelineHandle, bool); 2],
+    ) -> Result<(), BackendError> {
+        let instance_transforms_offset = api
+            .dynamic_constants()
+            .push_from_iter(self.instances.iter().map(pack_instance_transforms));
+
+        for (pipeline, double_sided) in pipelines {
+            let pipeline = api.bind_raster_pipeline(
+                pipeline
+                    .into_binding()
+                    .descriptor_set(
+                        0,
+                        &[RenderPassBinding::DynamicConstantsStorageBuffer(
+                            instance_transforms_offset,
+                        )],
+                    )
+                    .raw_descriptor_set(1, self.bindless_descriptor_set),
+            )?;
+
+            if let Some((draw_args_ref, draw_counts_ref, max_draw_count)) = self.indirect_draws {
+                // Culling picks the LODs, and writes their offsets into the draw arguments.
+                let range_idx = if double_sided { 0 } else { 1 };
+                let raw_device = &api.device().raw;
+                let draw_indirect_count_ext = &api.device().draw_indirect_count_ext;
+
+                unsafe {
+                    raw_device.cmd_bind_index_buffer(
+                        api.cb.raw,
+                        self.vertex_buffer.raw,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+
+                    draw_indirect_count_ext.cmd_draw_indexed_indirect_count(
+                        api.cb.raw,
+                        api.resources.buffer(draw_args_ref).raw,
+                        (range_idx * max_draw_count * DRAW_ARGS_STRIDE) as u64,
+                        api.resources.buffer(draw_counts_ref).raw,
+                        (range_idx * std::mem::size_of::<u32>() as u32) as u64,
+                        max_draw_count,
+                        DRAW_ARGS_STRIDE,
+                    );
+                }
+
+                continue;
+            }
+
+            let draws =
+                self.instances.iter().enumerate().filter(|(_, instance)| {
+                    self.meshes[instance.mesh.0].double_sided == double_sided
+                });
+
+            for (draw_idx, instance) in draws {
+                unsafe {
+                    draw_mesh_instance(
+                        api,
+                        &pipeline,
+                        &self.vertex_buffer,
+                        &self.meshes[instance.mesh.0],
+                        instance,
+                        draw_idx,
+                        &self.lod_selection,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
+/// Draws the depth of the opaque meshes ahead of `raster_meshes`, which then shades
+/// each pixel only once. Alpha-tested materials are still sampled here, but nothing else is.
+pub fn raster_depth_prepass(
+    rg: &mut RenderGraph,
+    render_pass: Arc<RenderPass>,
+    depth: &mut rg::Handle<Image>,
+    mesh_data: RasterMeshesData<'_>,
+    culled_draws: Option<&CulledDraws>,
+) {
+    let mut pass = rg.add_pass("depth prepass");
+
+    let draws = OpaqueDraws::new(&mut pass, mesh_data, culled_draws);
+    let pipelines = draws.register_pipelines(&mut pass, &render_pass, true, false);
+
+    let depth_ref = pass.raster(depth, AccessType::DepthAttachmentWriteStencilReadOnly);
+
+    pass.render(move |api| {
+        let [width, height, _] = depth_ref.desc().extent;
+
+        api.begin_render_pass(
+            &*render_pass,
+            [width, height],
+            &[],
+            Some((
+                depth_ref,
+                &ImageViewDesc::builder()
+                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .build()
+                    .unwrap(),
+            )),
+        )?;
+
+        api.set_default_view_and_scissor([width, height]);
+        draws.draw(api, pipelines)?;
+        api.end_render_pass();
+
+        Ok(())
+    });
+}
+
+/// Draws the meshes into the G-buffer. With `culled_draws`, only the instances which
+/// survived GPU culling are drawn, via indirect draws; otherwise all of them are,
+/// each with its own draw call.
+///
+/// With `depth_prepass`, the depth has already been drawn by `raster_depth_prepass`
+/// with the same `culled_draws`, and only the fragments matching it are shaded.
+#[allow(clippy::too_many_arguments)]
 pub fn raster_meshes(
     rg: &mut RenderGraph,
     render_pass: Arc<RenderPass>,
     gbuffer_depth: &mut GbufferDepth,
     velocity_img: &mut rg::Handle<Image>,
+    instance_id_img: &mut rg::Handle<Image>,
     mesh_data: RasterMeshesData<'_>,
+    culled_draws: Option<&CulledDraws>,
+    depth_prepass: bool,
 ) {
     let mut pass = rg.add_pass("raster simple");
 
+    let draws = OpaqueDraws::new(&mut pass, mesh_data, culled_draws);
+    let pipelines = draws.register_pipelines(&mut pass, &render_pass, false, depth_prepass);
+
+    let depth_ref = pass.raster(
+        &mut gbuffer_depth.depth,
+        AccessType::DepthAttachmentWriteStencilReadOnly,
+    );
+
+    let geometric_normal_ref = pass.raster(
+        &mut gbuffer_depth.geometric_normal,
+        AccessType::ColorAttachmentWrite,
+    );
+    let gbuffer_ref = pass.raster(&mut gbuffer_depth.gbuffer, AccessType::ColorAttachmentWrite);
+    let velocity_ref = pass.raster(velocity_img, AccessType::ColorAttachmentWrite);
+    let instance_id_ref = pass.raster(instance_id_img, AccessType::ColorAttachmentWrite);
+    let material_id_ref = gbuffer_depth
+        .material_id
+        .as_mut()
+        .map(|img| pass.raster(img, AccessType::ColorAttachmentWrite));
+
+    pass.render(move |api| {
+        let [width, height, _] = gbuffer_ref.desc().extent;
+
+        let view_desc = ImageViewDesc::default();
+        let mut color_attachments = vec![
+            (geometric_normal_ref, &view_desc),
+            (gbuffer_ref, &view_desc),
+            (velocity_ref, &view_desc),
+            (instance_id_ref, &view_desc),
+        ];
+        color_attachments.extend(material_id_ref.map(|material_id| (material_id, &view_desc)));
+
+        api.begin_render_pass(
+            &*render_pass,
+            [width, height],
+            &color_attachments,
+            Some((
+                depth_ref,
+                &ImageViewDesc::builder()
+                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .build()
+                    .unwrap(),
+            )),
+        )?;
+
+        api.set_default_view_and_scissor([width, height]);
+        draws.draw(api, pipelines)?;
+        api.end_render_pass();
+
+        Ok(())
+    });
+}
+
+/// Draws the opaque meshes like `raster_meshes`, but only counts the fragments passing
+/// the depth test, for the overdraw debug view. Alpha testing is not applied.
+pub fn raster_overdraw(
+    rg: &mut RenderGraph,
+    render_pass: Arc<RenderPass>,
+    extent: [u32; 2],
+    mesh_data: RasterMeshesData<'_>,
+) -> rg::Handle<Image> {
+    let mut depth_img = rg.create(ImageDesc::new_2d(vk::Format::D32_SFLOAT, extent));
+    rg::imageops::clear_depth(rg, &mut depth_img);
+
+    let mut output = rg.create(ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, extent));
+    rg::imageops::clear_color(rg, &mut output, [0.0; 4]);
+
+    let mut pass = rg.add_pass("raster overdraw");
+
+    let mut register_pipeline = |face_cull: bool| {
+        pass.register_raster_pipeline(
+            &[
+                PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                    .hlsl_source("/shaders/raster_simple_vs.hlsl")
+                    .build()
+                    .unwrap(),
+                PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                    .hlsl_source("/shaders/raster_overdraw_ps.hlsl")
+                    .build()
+                    .unwrap(),
+            ],
+            RasterPipelineDesc::builder()
+                .render_pass(render_pass.clone())
+                .face_cull(face_cull)
+                .alpha_blend(true)
+                .push_constants_bytes(2 * std::mem::size_of::<u32>()),
+        )
+    };
+
+    let double_sided_pipeline = register_pipeline(false);
+    let single_sided_pipeline = register_pipeline(true);
+
+    let meshes: Vec<UploadedTriMesh> = mesh_data.meshes.to_vec();
+    let instances: Vec<MeshInstance> = mesh_data.instances.to_vec();
+
+    let depth_ref = pass.raster(
+        &mut depth_img,
+        AccessType::DepthAttachmentWriteStencilReadOnly,
+    );
+    let output_ref = pass.raster(&mut output, AccessType::ColorAttachmentWrite);
+
+    let vertex_buffer = mesh_data.vertex_buffer.clone();
+    let bindless_descriptor_set = mesh_data.bindless_descriptor_set;
+    let lod_selection = mesh_data.lod_selection;
+
+    pass.render(move |api| {
+        let [width, height, _] = output_ref.desc().extent;
+
+        let instance_transforms_offset = api
+            .dynamic_constants()
+            .push_from_iter(instances.iter().map(pack_instance_transforms));
+
+        api.begin_render_pass(
+            &*render_pass,
+            [width, height],
+            &[(output_ref, &ImageViewDesc::default())],
+            Some((
+                depth_ref,
+                &ImageViewDesc::builder()
+                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .build()
+                    .unwrap(),
+            )),
+        )?;
+
+        api.set_default_view_and_scissor([width, height]);
+
+        // Same order as the G-buffer pass, so the same fragments pass the depth test.
+        for (pipeline, double_sided) in [
+            (double_sided_pipeline, true),
+            (single_sided_pipeline, false),
+        ] {
+            let pipeline = api.bind_raster_pipeline(
+                pipeline
+                    .into_binding()
+                    .descriptor_set(
+                        0,
+                        &[RenderPassBinding::DynamicConstantsStorageBuffer(
+                            instance_transforms_offset,
+                        )],
+                    )
+                    .raw_descriptor_set(1, bindless_descriptor_set),
+            )?;
+
+            let draws = instances
+                .iter()
+                .enumerate()
+                .filter(|(_, instance)| meshes[instance.mesh.0].double_sided == double_sided);
+
+            for (draw_idx, instance) in draws {
+                unsafe {
+                    draw_mesh_instance(
+                        api,
+                        &pipeline,
+                        &vertex_buffer,
+                        &meshes[instance.mesh.0],
+                        instance,
+                        draw_idx,
+                        &lod_selection,
+                    );
+                }
+            }
+        }
+
+        api.end_render_pass();
+
+        Ok(())
+    });
+
+    output
+}
+
+/// Draws the edges of all the mesh instances over `output`, at the same LODs as the G-buffer,
+/// for inspecting geometry density. Not depth-tested, so hidden edges show too.
+///
+/// Requires `Device::fill_mode_non_solid_enabled`.
+pub fn raster_wireframe(
+    rg: &mut RenderGraph,
+    device: &Device,
+    output: &mut rg::Handle<Image>,
+    mesh_data: RasterMeshesData<'_>,
+) {
+    let render_pass = create_render_pass(
+        device,
+        RenderPassDesc {
+            color_attachments: &[RenderPassAttachmentDesc::new(output.desc().format)],
+            depth_attachment: None,
+            subpasses: &[],
+        },
+    );
+
+    let mut pass = rg.add_pass("raster wireframe");
+
+    let draws = OpaqueDraws::new(&mut pass, mesh_data, None);
+
+    let mut register_pipeline = |face_cull: bool| {
+        pass.register_raster_pipeline(
+            &[
+                PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+                    .hlsl_source("/shaders/raster_simple_vs.hlsl")
+                    .build()
+                    .unwrap(),
+                PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+                    .hlsl_source("/shaders/raster_wireframe_ps.hlsl")
+                    .build()
+                    .unwrap(),
+            ],
+            RasterPipelineDesc::builder()
+                .render_pass(render_pass.clone())
+                .face_cull(face_cull)
+                .depth_write(false)
+                .polygon_mode(vk::PolygonMode::LINE)
+                .push_constants_bytes(2 * std::mem::size_of::<u32>()),
+        )
+    };
+
+    let pipelines = [
+        (register_pipeline(false), true),
+        (register_pipeline(true), false),
+    ];
+
+    let output_ref = pass.raster(output, AccessType::ColorAttachmentWrite);
+
+    pass.render(move |api| {
+        let [width, height, _] = output_ref.desc().extent;
+
+        api.begin_render_pass(
+            &*render_pass,
+            [width, height],
+            &[(output_ref, &ImageViewDesc::default())],
+            None,
+        )?;
+
+        api.set_default_view_and_scissor([width, height]);
+        draws.draw(api, pipelines)?;
+        api.end_render_pass();
+
+        Ok(())
+    });
+}
+
+/// Draws the instances of meshes with alpha-blended materials over the lit scene,
+/// back-to-front, depth-tested against the opaque ones.
+///
+/// Sorting is per instance rather than per triangle, so intersecting or nested
+/// transparent surfaces can still come out in the wrong order.
+#[allow(clippy::too_many_arguments)]
+pub fn raster_transparent_meshes(
+    rg: &mut RenderGraph,
+    render_pass: Arc<RenderPass>,
+    depth: &mut rg::Handle<Image>,
+    output: &mut rg::Handle<Image>,
+    responsive_mask: &mut rg::Handle<Image>,
+    convolved_sky_cube: &rg::Handle<Image>,
+    prefiltered_sky_cube: &rg::Handle<Image>,
+    mesh_data: RasterMeshesData<'_>,
+) {
+    let eye_position = mesh_data.lod_selection.eye_position;
+
+    let mut draws: Vec<(usize, f32)> = mesh_data
+        .instances
+        .iter()
+        .enumerate()
+        .filter_map(|(draw_idx, inst)| {
+            let mesh = &mesh_data.meshes[inst.mesh.0];
+            mesh.has_alpha_blend.then(|| {
+                let center = inst.transform.transform_point3(mesh.bounding_sphere.center);
+                (draw_idx, center.distance_squared(eye_position))
+            })
+        })
+        .collect();
+
+    if draws.is_empty() {
+        return;
+    }
+
+    draws.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    raster_forward(
+        rg.add_pass("raster transparent"),
+        render_pass,
+        depth,
+        output,
+        responsive_mask,
+        convolved_sky_cube,
+        prefiltered_sky_cube,
+        mesh_data,
+        draws.into_iter().map(|(draw_idx, _)| draw_idx).collect(),
+        false,
+    );
+}
+
+/// Shades the opaque and alpha-tested materials of all the instances with the forward
+/// pipeline of `raster_transparent_meshes`, writing depth. For views without a G-buffer;
+/// alpha-blended materials are left to `raster_transparent_meshes`, drawn after this.
+#[allow(clippy::too_many_arguments)]
+pub fn raster_forward_opaque_meshes(
+    rg: &mut RenderGraph,
+    render_pass: Arc<RenderPass>,
+    depth: &mut rg::Handle<Image>,
+    output: &mut rg::Handle<Image>,
+    responsive_mask: &mut rg::Handle<Image>,
+    convolved_sky_cube: &rg::Handle<Image>,
+    prefiltered_sky_cube: &rg::Handle<Image>,
+    mesh_data: RasterMeshesData<'_>,
+) {
+    let draws = (0..mesh_data.instances.len()).collect();
+
+    raster_forward(
+        rg.add_pass("raster forward"),
+        render_pass,
+        depth,
+        output,
+        responsive_mask,
+        convolved_sky_cube,
+        prefiltered_sky_cube,
+        mesh_data,
+        draws,
+        true,
+    );
+}
+
+/// Draws the instances at `draws`, in order, with `forward_transparent_ps.hlsl`.
+#[allow(clippy::too_many_arguments)]
+fn raster_forward(
+    mut pass: rg::PassBuilder<'_>,
+    render_pass: Arc<RenderPass>,
+    depth: &mut rg::Handle<Image>,
+    output: &mut rg::Handle<Image>,
+    responsive_mask: &mut rg::Handle<Image>,
+    convolved_sky_cube: &rg::Handle<Image>,
+    prefiltered_sky_cube: &rg::Handle<Image>,
+    mesh_data: RasterMeshesData<'_>,
+    draws: Vec<usize>,
+    opaque_materials: bool,
+) {
     let pipeline = pass.register_raster_pipeline(
         &[
             PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
-                // .rust_source("raster_simple::raster_simple_vs")
                 .hlsl_source("/shaders/raster_simple_vs.hlsl")
                 .build()
                 .unwrap(),
             PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
-                // .rust_source("raster_simple::raster_simple_fs")
-                .hlsl_source("/shaders/raster_simple_ps.hlsl")
+                .hlsl_source("/shaders/forward_transparent_ps.hlsl")
+                .specialization_constants(vec![(0, opaque_materials as u32)])
                 .build()
                 .unwrap(),
         ],
         RasterPipelineDesc::builder()
             .render_pass(render_pass.clone())
             .face_cull(false)
+            .depth_write(opaque_materials)
+            .alpha_blend(!opaque_materials)
             .push_constants_bytes(2 * std::mem::size_of::<u32>()),
     );
 
     let meshes: Vec<UploadedTriMesh> = mesh_data.meshes.to_vec();
     let instances: Vec<MeshInstance> = mesh_data.instances.to_vec();
 
-    let depth_ref = pass.raster(
-        &mut gbuffer_depth.depth,
-        AccessType::DepthAttachmentWriteStencilReadOnly,
+    let convolved_sky_cube_ref = pass.read(
+        convolved_sky_cube,
+        AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer,
     );
-
-    let geometric_normal_ref = pass.raster(
-        &mut gbuffer_depth.geometric_normal,
-        AccessType::ColorAttachmentWrite,
+    let prefiltered_sky_cube_ref = pass.read(
+        prefiltered_sky_cube,
+        AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer,
     );
-    let gbuffer_ref = pass.raster(&mut gbuffer_depth.gbuffer, AccessType::ColorAttachmentWrite);
-    let velocity_ref = pass.raster(velocity_img, AccessType::ColorAttachmentWrite);
+
+    // Matches the layout of the G-buffer pass; depth writes are up to the pipeline.
+    let depth_ref = pass.raster(depth, AccessType::DepthAttachmentWriteStencilReadOnly);
+    let output_ref = pass.raster(output, AccessType::ColorAttachmentWrite);
+    let responsive_mask_ref = pass.raster(responsive_mask, AccessType::ColorAttachmentWrite);
 
     let vertex_buffer = mesh_data.vertex_buffer.clone();
     let bindless_descriptor_set = mesh_data.bindless_descriptor_set;
+    let lod_selection = mesh_data.lod_selection;
 
     pass.render(move |api| {
-        let [width, height, _] = gbuffer_ref.desc().extent;
+        let [width, height, _] = output_ref.desc().extent;
 
-        let instance_transforms_offset =
-            api.dynamic_constants()
-                .push_from_iter(instances.iter().map(|inst| {
-                    let transform = [
-                        inst.transform.x_axis.x,
-                        inst.transform.y_axis.x,
-                        inst.transform.z_axis.x,
-                        inst.transform.translation.x,
-                        inst.transform.x_axis.y,
-                        inst.transform.y_axis.y,
-                        inst.transform.z_axis.y,
-                        inst.transform.translation.y,
-                        inst.transform.x_axis.z,
-                        inst.transform.y_axis.z,
-                        inst.transform.z_axis.z,
-                        inst.transform.translation.z,
-                    ];
-
-                    let prev_transform = [
-                        inst.prev_transform.x_axis.x,
-                        inst.prev_transform.y_axis.x,
-                        inst.prev_transform.z_axis.x,
-                        inst.prev_transform.translation.x,
-                        inst.prev_transform.x_axis.y,
-                        inst.prev_transform.y_axis.y,
-                        inst.prev_transform.z_axis.y,
-                        inst.prev_transform.translation.y,
-                        inst.prev_transform.x_axis.z,
-                        inst.prev_transform.y_axis.z,
-                        inst.prev_transform.z_axis.z,
-                        inst.prev_transform.translation.z,
-                    ];
-
-                    (transform, prev_transform)
-                }));
+        // Indexed by the original instance indices, like the per-instance dynamic parameters.
+        let instance_transforms_offset = api
+            .dynamic_constants()
+            .push_from_iter(instances.iter().map(pack_instance_transforms));
 
         api.begin_render_pass(
             &*render_pass,
             [width, height],
             &[
-                (geometric_normal_ref, &ImageViewDesc::default()),
-                (gbuffer_ref, &ImageViewDesc::default()),
-                (velocity_ref, &ImageViewDesc::default()),
+                (output_ref, &ImageViewDesc::default()),
+                (responsive_mask_ref, &ImageViewDesc::default()),
             ],
             Some((
                 depth_ref,
@@ -134,40 +757,30 @@ pub fn raster_meshes(
                 .into_binding()
                 .descriptor_set(
                     0,
-                    &[RenderPassBinding::DynamicConstantsStorageBuffer(
-                        instance_transforms_offset,
-                    )],
+                    &[
+                        RenderPassBinding::DynamicConstantsStorageBuffer(
+                            instance_transforms_offset,
+                        ),
+                        convolved_sky_cube_ref.bind(),
+                        prefiltered_sky_cube_ref.bind(),
+                    ],
                 )
                 .raw_descriptor_set(1, bindless_descriptor_set),
         )?;
 
-        unsafe {
-            let raw_device = &api.device().raw;
-            let cb = api.cb;
-
-            for (draw_idx, instance) in instances.into_iter().enumerate() {
-                let mesh = &meshes[instance.mesh.0];
-
-                raw_device.cmd_bind_index_buffer(
-                    cb.raw,
-                    vertex_buffer.raw,
-                    mesh.index_buffer_offset,
-                    vk::IndexType::UINT32,
-                );
-
-                let push_constants = (draw_idx as u32, instance.mesh.0 as u32);
+        for draw_idx in draws {
+            let instance = &instances[draw_idx];
 
-                pipeline.push_constants(
-                    cb.raw,
-                    vk::ShaderStageFlags::ALL_GRAPHICS,
-                    0,
-                    std::slice::from_raw_parts(
-                        &push_constants as *const _ as *const u8,
-                        std::mem::size_of_val(&push_constants),
-                    ),
+            unsafe {
+                draw_mesh_instance(
+                    api,
+                    &pipeline,
+                    &vertex_buffer,
+                    &meshes[instance.mesh.0],
+                    instance,
+                    draw_idx,
+                    &lod_selection,
                 );
-
-                raw_device.cmd_draw_indexed(cb.raw, mesh.index_count, 1, 0, 0, 0);
             }
         }
 
@@ -176,3 +789,80 @@ pub fn raster_meshes(
         Ok(())
     });
 }
+
+/// Draws the mesh of `instance` at the LOD picked by `lod_selection`.
+///
+/// `draw_idx` indexes the per-instance data such as the transforms.
+unsafe fn draw_mesh_instance(
+    api: &rg::RenderPassApi,
+    pipeline: &rg::BoundRasterPipeline,
+    vertex_buffer: &Buffer,
+    mesh: &UploadedTriMesh,
+    instance: &MeshInstance,
+    draw_idx: usize,
+    lod_selection: &MeshLodSelection,
+) {
+    let raw_device = &api.device().raw;
+    let cb = api.cb;
+
+    let (index_buffer_offset, index_count) =
+        mesh.select_lod(lod_selection.max_mesh_space_error(mesh, &instance.transform));
+
+    raw_device.cmd_bind_index_buffer(
+        cb.raw,
+        vertex_buffer.raw,
+        index_buffer_offset,
+        vk::IndexType::UINT32,
+    );
+
+    let push_constants = (draw_idx as u32, instance.mesh.0 as u32);
+
+    pipeline.push_constants(
+        cb.raw,
+        vk::ShaderStageFlags::ALL_GRAPHICS,
+        0,
+        std::slice::from_raw_parts(
+            &push_constants as *const _ as *const u8,
+            std::mem::size_of_val(&push_constants),
+        ),
+    );
+
+    raw_device.cmd_draw_indexed(cb.raw, index_count, 1, 0, 0, 0);
+}
+
+// Must match `InstanceTransform` in `raster_simple_vs.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuInstanceTransform {
+    /// Row-major 3x4 matrices
+    current: [f32; 12],
+    previous: [f32; 12],
+    mesh_index: u32,
+    pad: [u32; 3],
+}
+
+fn pack_instance_transforms(inst: &MeshInstance) -> GpuInstanceTransform {
+    let pack = |transform: &Affine3A| {
+        [
+            transform.x_axis.x,
+            transform.y_axis.x,
+            transform.z_axis.x,
+            transform.translation.x,
+            transform.x_axis.y,
+            transform.y_axis.y,
+            transform.z_axis.y,
+            transform.translation.y,
+            transform.x_axis.z,
+            transform.y_axis.z,
+            transform.z_axis.z,
+            transform.translation.z,
+        ]
+    };
+
+    GpuInstanceTransform {
+        current: pack(&inst.transform),
+        previous: pack(&inst.prev_transform),
+        mesh_index: inst.mesh.0 as u32,
+        pad: [0; 3],
+    }
+}
@@ -0,0 +1,74 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::PingPongTemporalResource;
+
+/// Marks the disoccluded pixels, whose history the temporal accumulators of TAA and diffuse GI
+/// then mostly drop, instead of smearing the surface which used to be there.
+///
+/// A pixel is disoccluded to the extent that its reprojection into the previous frame lands
+/// on a different instance, as told by the instance IDs of the G-buffer, or across a depth
+/// discontinuity, as found by `calculate_reprojection_map`.
+pub struct ReactiveMaskRenderer {
+    instance_id_tex: PingPongTemporalResource,
+}
+
+impl Default for ReactiveMaskRenderer {
+    fn default() -> Self {
+        Self {
+            instance_id_tex: PingPongTemporalResource::new("reactive_mask.instance_id"),
+        }
+    }
+}
+
+/// Instance IDs of this frame's G-buffer, and of the previous frame's.
+pub struct InstanceIds {
+    /// To be cleared, and drawn into by `raster_meshes`.
+    pub current: rg::Handle<Image>,
+    pub prev: rg::Handle<Image>,
+}
+
+impl ReactiveMaskRenderer {
+    pub fn instance_ids(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        extent: [u32; 2],
+    ) -> InstanceIds {
+        let (current, prev) = self.instance_id_tex.get_output_and_history(
+            rg,
+            ImageDesc::new_2d(vk::Format::R32_UINT, extent).usage(
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            ),
+        );
+
+        InstanceIds { current, prev }
+    }
+
+    /// One where the history is entirely invalid, zero where it's fully valid.
+    pub fn render(
+        &self,
+        rg: &mut rg::RenderGraph,
+        instance_ids: &InstanceIds,
+        reprojection_map: &rg::Handle<Image>,
+    ) -> rg::ReadOnlyHandle<Image> {
+        let mut output = rg.create(
+            instance_ids
+                .current
+                .desc()
+                .usage(vk::ImageUsageFlags::empty())
+                .format(vk::Format::R8_UNORM),
+        );
+
+        SimpleRenderPass::new_compute(rg.add_pass("reactive mask"), "/shaders/reactive_mask.hlsl")
+            .read(&instance_ids.current)
+            .read(&instance_ids.prev)
+            .read(reprojection_map)
+            .write(&mut output)
+            .constants(output.desc().extent_inv_extent_2d())
+            .dispatch(output.desc().extent);
+
+        output.into()
+    }
+}
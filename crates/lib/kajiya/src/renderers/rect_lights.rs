@@ -0,0 +1,63 @@
+use glam::{Affine3A, Vec3};
+
+use super::working_color_space::WorkingColorSpace;
+
+/// A rectangular area light with uniform emitted radiance.
+///
+/// Shaded with linearly transformed cosines, and shadowed by tracing rays towards
+/// random points on the light.
+#[derive(Clone, Copy)]
+pub struct RectLight {
+    /// Maps the `[-0.5, 0.5]` square in the XY plane to the light in world space.
+    /// Scaling along X and Y resizes the light. It emits along the local -Z axis.
+    pub transform: Affine3A,
+
+    /// Emitted radiance
+    pub color: Vec3,
+
+    /// Also emit along the local +Z axis
+    pub two_sided: bool,
+}
+
+impl RectLight {
+    pub fn new(transform: Affine3A, color: Vec3) -> Self {
+        Self {
+            transform,
+            color,
+            two_sided: false,
+        }
+    }
+
+    pub fn with_two_sided(self, two_sided: bool) -> Self {
+        Self { two_sided, ..self }
+    }
+}
+
+// Must match `RectLightPacked` in `lights/packed.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct GpuRectLight {
+    center: [f32; 3],
+    two_sided: u32,
+    half_x_axis: [f32; 4],
+    half_y_axis: [f32; 4],
+    radiance: [f32; 4],
+}
+
+impl GpuRectLight {
+    pub(crate) fn new(light: &RectLight, working_color_space: WorkingColorSpace) -> Self {
+        let half_x_axis = light.transform.transform_vector3(Vec3::X * 0.5);
+        let half_y_axis = light.transform.transform_vector3(Vec3::Y * 0.5);
+
+        Self {
+            center: Vec3::from(light.transform.translation).into(),
+            two_sided: light.two_sided as u32,
+            half_x_axis: half_x_axis.extend(0.0).into(),
+            half_y_axis: half_y_axis.extend(0.0).into(),
+            radiance: working_color_space
+                .from_rec709(light.color)
+                .extend(0.0)
+                .into(),
+        }
+    }
+}
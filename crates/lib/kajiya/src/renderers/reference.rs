@@ -18,7 +18,7 @@ pub fn reference_path_trace(
             ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+        super::rt_hit_groups(),
     )
     .write(output_img)
     .raw_descriptor_set(1, bindless_descriptor_set)
@@ -0,0 +1,147 @@
+use kajiya_backend::vulkan::shader::SpecializationConstants;
+
+use super::gi_resolution::GiResolution;
+
+// Must match `inc/render_quality.hlsl`
+const SUN_SHADOW_RAY_COUNT_ID: u32 = 0;
+const DIFFUSE_GI_RAY_COUNT_ID: u32 = 1;
+const REFLECTION_RAY_COUNT_ID: u32 = 2;
+const DIFFUSE_GI_HISTORY_LENGTH_ID: u32 = 3;
+const REFLECTION_HISTORY_LENGTH_ID: u32 = 4;
+
+/// Per-effect quality and performance settings.
+///
+/// The ray counts and history lengths are baked into the affected shaders as
+/// specialization constants, so changing them creates new pipelines, but doesn't
+/// recompile any shaders. The defaults match the hard-coded values from before
+/// these were configurable.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderQuality {
+    /// Shadow rays traced towards the sun per pixel; up to 16.
+    pub sun_shadow_rays_per_pixel: u32,
+
+    /// Diffuse GI candidate rays per half-res pixel; up to 8. Only the brightest
+    /// (in a stochastic sense) is kept for ReSTIR, so this reduces noise rather than
+    /// adding samples.
+    pub diffuse_gi_rays_per_pixel: u32,
+
+    /// Reflection candidate rays per half-res pixel; up to 8. Resampled like `diffuse_gi_rays_per_pixel`.
+    pub reflection_rays_per_pixel: u32,
+
+    /// Maximum number of frames accumulated by the diffuse GI temporal reservoirs.
+    /// Longer histories are less noisy, but slower to react to lighting changes.
+    pub diffuse_gi_history_length: f32,
+
+    /// Maximum number of frames accumulated by the reflection temporal reservoirs.
+    pub reflection_history_length: f32,
+
+    /// Surfaces rougher than this don't trace reflection rays, and use diffuse GI
+    /// for their specular lighting instead. Values around 0.6-0.7 save most of the
+    /// reflection cost in rough scenes with little visual change; 1.0 disables the cutoff.
+    pub reflection_roughness_cutoff: f32,
+
+    /// Resolution of diffuse GI and reflections.
+    pub gi_resolution: GiResolution,
+}
+
+impl Default for RenderQuality {
+    fn default() -> Self {
+        Self {
+            sun_shadow_rays_per_pixel: 1,
+            diffuse_gi_rays_per_pixel: 1,
+            reflection_rays_per_pixel: 1,
+            diffuse_gi_history_length: 20.0,
+            reflection_history_length: 8.0,
+            reflection_roughness_cutoff: 1.0,
+            gi_resolution: GiResolution::default(),
+        }
+    }
+}
+
+/// Starting points for `RenderQuality`, trading image quality for performance.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QualityPreset {
+    /// Quarter-res GI and reflections, without rays for rough surfaces.
+    Low,
+    /// Half-res GI and reflections, without rays for very rough surfaces.
+    Medium,
+    /// The defaults.
+    High,
+    /// More rays per pixel for sun shadows, GI, and reflections.
+    Ultra,
+}
+
+impl Default for QualityPreset {
+    fn default() -> Self {
+        Self::High
+    }
+}
+
+impl std::str::FromStr for QualityPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "ultra" => Ok(Self::Ultra),
+            _ => Err(anyhow::anyhow!(
+                "Unknown quality preset {:?}; expected one of: low, medium, high, ultra",
+                s
+            )),
+        }
+    }
+}
+
+impl RenderQuality {
+    pub fn from_preset(preset: QualityPreset) -> Self {
+        match preset {
+            QualityPreset::Low => Self {
+                reflection_roughness_cutoff: 0.6,
+                gi_resolution: GiResolution::Quarter,
+                ..Default::default()
+            },
+            QualityPreset::Medium => Self {
+                reflection_roughness_cutoff: 0.7,
+                gi_resolution: GiResolution::Half,
+                ..Default::default()
+            },
+            QualityPreset::High => Self::default(),
+            QualityPreset::Ultra => Self {
+                sun_shadow_rays_per_pixel: 4,
+                diffuse_gi_rays_per_pixel: 2,
+                reflection_rays_per_pixel: 2,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Values for all the constants in `inc/render_quality.hlsl`.
+    ///
+    /// Out-of-range settings are clamped.
+    pub fn specialization_constants(&self) -> SpecializationConstants {
+        vec![
+            (
+                SUN_SHADOW_RAY_COUNT_ID,
+                self.sun_shadow_rays_per_pixel.clamp(1, 16),
+            ),
+            (
+                DIFFUSE_GI_RAY_COUNT_ID,
+                self.diffuse_gi_rays_per_pixel.clamp(1, 8),
+            ),
+            (
+                REFLECTION_RAY_COUNT_ID,
+                self.reflection_rays_per_pixel.clamp(1, 8),
+            ),
+            (
+                DIFFUSE_GI_HISTORY_LENGTH_ID,
+                self.diffuse_gi_history_length.clamp(1.0, 64.0).to_bits(),
+            ),
+            (
+                REFLECTION_HISTORY_LENGTH_ID,
+                self.reflection_history_length.clamp(1.0, 64.0).to_bits(),
+            ),
+        ]
+    }
+}
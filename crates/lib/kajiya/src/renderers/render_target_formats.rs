@@ -0,0 +1,138 @@
+use kajiya_backend::ash::vk;
+
+use super::gbuffer_layout::GbufferLayout;
+
+/// Formats of the frame resources which trade memory bandwidth for banding.
+///
+/// Changes take effect on the next frame; temporal resources whose format changes
+/// lose their history, same as on a resize.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RenderTargetFormats {
+    /// The lit scene, from deferred lighting up to anti-aliasing.
+    pub color: ColorPrecision,
+
+    /// The temporally filtered diffuse GI history.
+    pub gi_history: GiHistoryPrecision,
+
+    /// The view-space geometric normals in the G-buffer.
+    pub normals: NormalEncoding,
+
+    /// The packing of the G-buffer proper, and the extra targets along with it.
+    pub gbuffer: GbufferLayout,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorPrecision {
+    Rgba16Float,
+    /// Half the size of `Rgba16Float`, but with fewer mantissa bits, and no sign or alpha.
+    R11G11B10Float,
+}
+
+impl Default for ColorPrecision {
+    fn default() -> Self {
+        Self::Rgba16Float
+    }
+}
+
+impl ColorPrecision {
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+            Self::R11G11B10Float => vk::Format::B10G11R11_UFLOAT_PACK32,
+        }
+    }
+}
+
+impl std::str::FromStr for ColorPrecision {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rgba16f" => Ok(Self::Rgba16Float),
+            "r11g11b10f" => Ok(Self::R11G11B10Float),
+            _ => Err(anyhow::anyhow!(
+                "Unknown color precision {:?}; expected one of: rgba16f, r11g11b10f",
+                s
+            )),
+        }
+    }
+}
+
+/// The history keeps a sample count in alpha, so it doesn't have a packed option.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GiHistoryPrecision {
+    /// 16-bit float channels.
+    Half,
+    /// 32-bit float channels; twice the bandwidth, but no banding in dark areas
+    /// with long histories.
+    Full,
+}
+
+impl Default for GiHistoryPrecision {
+    fn default() -> Self {
+        Self::Half
+    }
+}
+
+impl GiHistoryPrecision {
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Half => vk::Format::R16G16B16A16_SFLOAT,
+            Self::Full => vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+}
+
+impl std::str::FromStr for GiHistoryPrecision {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "half" => Ok(Self::Half),
+            "full" => Ok(Self::Full),
+            _ => Err(anyhow::anyhow!(
+                "Unknown GI history precision {:?}; expected one of: half, full",
+                s
+            )),
+        }
+    }
+}
+
+/// Both encode the normal as `n * 0.5 + 0.5`, so the shaders are the same for either.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NormalEncoding {
+    /// 10 bits per component.
+    Unorm10,
+    /// 16 bits per component, at twice the size.
+    Unorm16,
+}
+
+impl Default for NormalEncoding {
+    fn default() -> Self {
+        Self::Unorm10
+    }
+}
+
+impl NormalEncoding {
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Unorm10 => vk::Format::A2R10G10B10_UNORM_PACK32,
+            Self::Unorm16 => vk::Format::R16G16B16A16_UNORM,
+        }
+    }
+}
+
+impl std::str::FromStr for NormalEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unorm10" => Ok(Self::Unorm10),
+            "unorm16" => Ok(Self::Unorm16),
+            _ => Err(anyhow::anyhow!(
+                "Unknown normal encoding {:?}; expected one of: unorm10, unorm16",
+                s
+            )),
+        }
+    }
+}
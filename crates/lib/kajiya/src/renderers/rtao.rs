@@ -0,0 +1,104 @@
+use super::{shadow_denoise::ShadowDenoiseRenderer, GbufferDepth};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+/// Where the ambient occlusion which guides the GI comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AmbientOcclusionMode {
+    /// `SsgiRenderer` or `GtaoRenderer`, depending on `WorldRenderer::use_gtao`.
+    ScreenSpace,
+
+    /// `RtaoRenderer`, which also sees occluders outside of the view.
+    RayTraced,
+
+    /// The screen-space occlusion, blended towards the ray traced one by `RtaoRenderer::blend_weight`.
+    /// Keeps the bent normal of GTAO.
+    Blended,
+}
+
+impl Default for AmbientOcclusionMode {
+    fn default() -> Self {
+        Self::ScreenSpace
+    }
+}
+
+/// Ray traced ambient occlusion.
+///
+/// Traces a single short, cosine-distributed ray per pixel, and leaves the accumulation
+/// and filtering to the denoiser of the sun shadows, whose input is just as binary.
+/// Outputs the visibility in the red channel, and its variance in green.
+pub struct RtaoRenderer {
+    denoise: ShadowDenoiseRenderer,
+
+    /// World-space length of the rays; occluders further away don't count.
+    pub ray_length: f32,
+
+    /// With `AmbientOcclusionMode::Blended`, how far to go from the screen-space occlusion
+    /// towards the ray traced one.
+    pub blend_weight: f32,
+}
+
+impl Default for RtaoRenderer {
+    fn default() -> Self {
+        Self {
+            denoise: ShadowDenoiseRenderer::new("rtao_denoise"),
+            ray_length: 1.0,
+            blend_weight: 0.5,
+        }
+    }
+}
+
+impl RtaoRenderer {
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        reprojection_map: &rg::Handle<Image>,
+        tlas: &rg::Handle<RayTracingAcceleration>,
+        bindless_descriptor_set: vk::DescriptorSet,
+    ) -> rg::ReadOnlyHandle<Image> {
+        let mut visibility_img = rg.create(gbuffer_depth.depth.desc().format(vk::Format::R8_UNORM));
+
+        SimpleRenderPass::new_rt(
+            rg.add_pass("rtao trace"),
+            ShaderSource::hlsl("/shaders/rt/trace_ambient_occlusion.rgen.hlsl"),
+            [
+                // Duplicated because `rt.hlsl` hardcodes miss index to 1
+                ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+            ],
+            super::rt_shadow_hit_groups(),
+        )
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(&gbuffer_depth.geometric_normal)
+        .write(&mut visibility_img)
+        .constants(self.ray_length.max(1e-3))
+        .raw_descriptor_set(1, bindless_descriptor_set)
+        .trace_rays(tlas, visibility_img.desc().extent);
+
+        self.denoise
+            .render(rg, gbuffer_depth, &visibility_img, reprojection_map)
+    }
+
+    /// Blends the screen-space occlusion towards `ray_traced`, as with `AmbientOcclusionMode::Blended`.
+    pub fn blend(
+        &self,
+        rg: &mut rg::TemporalRenderGraph,
+        screen_space: &rg::Handle<Image>,
+        ray_traced: &rg::Handle<Image>,
+    ) -> rg::ReadOnlyHandle<Image> {
+        let mut output_img = rg.create(*screen_space.desc());
+
+        SimpleRenderPass::new_compute(rg.add_pass("rtao blend"), "/shaders/rtao/blend.hlsl")
+            .read(screen_space)
+            .read(ray_traced)
+            .write(&mut output_img)
+            .constants(self.blend_weight.clamp(0.0, 1.0))
+            .dispatch(output_img.desc().extent);
+
+        output_img.into()
+    }
+}
@@ -1,11 +1,17 @@
 use kajiya_backend::{
     ash::vk,
-    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
+    vulkan::{
+        buffer::{Buffer, BufferDesc},
+        image::*,
+        ray_tracing::RayTracingAcceleration,
+        shader::ShaderSource,
+    },
 };
 use kajiya_rg::{self as rg, SimpleRenderPass};
 
 use super::{
-    ircache::IrcacheRenderState, wrc::WrcRenderState, GbufferDepth, PingPongTemporalResource,
+    buffer_writes::clear_buffer, ircache::IrcacheRenderState, render_quality::RenderQuality,
+    wrc::WrcRenderState, GbufferDepth, PingPongTemporalResource,
 };
 
 pub struct RtdgiRenderer {
@@ -23,6 +29,18 @@ pub struct RtdgiRenderer {
 
     pub spatial_reuse_pass_count: u32,
     pub use_raytraced_reservoir_visibility: bool,
+
+    /// Spends the diffuse GI candidate rays where the previous frame was noisy: each half-res
+    /// pixel traces one, and the noisy ones up to `adaptive_max_rays_per_pixel`, in an indirect
+    /// trace over just those. Takes over from `RenderQuality::diffuse_gi_rays_per_pixel`.
+    pub adaptive_ray_count: bool,
+
+    /// Up to 8.
+    pub adaptive_max_rays_per_pixel: u32,
+
+    /// The relative deviation of the temporal filter's luminance at which pixels get
+    /// `adaptive_max_rays_per_pixel`; quieter ones get proportionally fewer.
+    pub adaptive_noise_threshold: f32,
 }
 
 const COLOR_BUFFER_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
@@ -41,6 +59,9 @@ impl Default for RtdgiRenderer {
             temporal_hit_normal_tex: PingPongTemporalResource::new("rtdgi.hit_normal"),
             spatial_reuse_pass_count: 2,
             use_raytraced_reservoir_visibility: false,
+            adaptive_ray_count: false,
+            adaptive_max_rays_per_pixel: 4,
+            adaptive_noise_threshold: 0.25,
         }
     }
 }
@@ -58,9 +79,24 @@ pub struct RtdgiCandidates {
 
 pub struct RtdgiOutput {
     pub screen_irradiance_tex: rg::ReadOnlyHandle<Image>,
+    /// The resolved irradiance before temporal and spatial filtering
+    pub raw_irradiance_tex: rg::ReadOnlyHandle<Image>,
+    /// The temporal filter's moments of the square root of luminance
+    pub variance_tex: rg::ReadOnlyHandle<Image>,
+    /// The temporal filter's history, with the accumulated sample count in alpha
+    pub history_tex: rg::ReadOnlyHandle<Image>,
     pub candidates: RtdgiCandidates,
 }
 
+// The half-res pixels which trace more than one candidate ray this frame
+struct RtdgiRayBudget {
+    ray_count_tex: rg::Handle<Image>,
+    /// `[pixel count, 1, 1, unused]`, for `trace_rays_indirect`
+    extra_ray_args_buf: rg::Handle<Buffer>,
+    /// As `x | y << 16`
+    extra_ray_px_buf: rg::Handle<Buffer>,
+}
+
 impl RtdgiRenderer {
     fn temporal_tex_desc(extent: [u32; 2]) -> ImageDesc {
         ImageDesc::new_2d(COLOR_BUFFER_FORMAT, extent)
@@ -76,15 +112,11 @@ impl RtdgiRenderer {
         reprojection_map: &rg::Handle<Image>,
         reprojected_history_tex: &rg::Handle<Image>,
         rt_history_invalidity_tex: &rg::Handle<Image>,
-        mut temporal_output_tex: rg::Handle<Image>,
+        reactive_mask: &rg::Handle<Image>,
+        variance_history_tex: &rg::Handle<Image>,
+        temporal_variance_output_tex: &mut rg::Handle<Image>,
+        temporal_output_tex: &mut rg::Handle<Image>,
     ) -> rg::Handle<Image> {
-        let (mut temporal_variance_output_tex, variance_history_tex) =
-            self.temporal2_variance_tex.get_output_and_history(
-                rg,
-                ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, input_color.desc().extent_2d())
-                    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
-            );
-
         let mut temporal_filtered_tex = rg.create(
             gbuffer_depth
                 .gbuffer
@@ -99,12 +131,13 @@ impl RtdgiRenderer {
         )
         .read(input_color)
         .read(reprojected_history_tex)
-        .read(&variance_history_tex)
+        .read(variance_history_tex)
         .read(reprojection_map)
         .read(rt_history_invalidity_tex)
+        .read(reactive_mask)
         .write(&mut temporal_filtered_tex)
-        .write(&mut temporal_output_tex)
-        .write(&mut temporal_variance_output_tex)
+        .write(temporal_output_tex)
+        .write(temporal_variance_output_tex)
         .constants((
             temporal_output_tex.desc().extent_inv_extent_2d(),
             gbuffer_depth.gbuffer.desc().extent_inv_extent_2d(),
@@ -114,6 +147,60 @@ impl RtdgiRenderer {
         temporal_filtered_tex
     }
 
+    // Uses the noise the temporal filter measured in the previous frame.
+    fn ray_budget(
+        &self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        reprojection_map: &rg::Handle<Image>,
+        reprojected_history_tex: &rg::Handle<Image>,
+        variance_history_tex: &rg::Handle<Image>,
+    ) -> RtdgiRayBudget {
+        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
+        let half_extent = gbuffer_desc.half_res().extent_2d();
+
+        let mut ray_count_tex = rg.create(gbuffer_desc.half_res().format(vk::Format::R8_UINT));
+
+        let mut extra_ray_args_buf = rg.create(BufferDesc::new_gpu_only(
+            4 * std::mem::size_of::<u32>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        ));
+        clear_buffer(rg, "clear rtdgi ray budget", &mut extra_ray_args_buf);
+
+        let mut extra_ray_px_buf = rg.create(BufferDesc::new_gpu_only(
+            (half_extent[0] * half_extent[1]) as usize * std::mem::size_of::<u32>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        ));
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("rtdgi ray budget"),
+            "/shaders/rtdgi/ray_budget.hlsl",
+        )
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .read(reprojected_history_tex)
+        .read(variance_history_tex)
+        .read(reprojection_map)
+        .write(&mut ray_count_tex)
+        .write(&mut extra_ray_args_buf)
+        .write(&mut extra_ray_px_buf)
+        .constants((
+            gbuffer_desc.extent_inv_extent_2d(),
+            ray_count_tex.desc().extent_inv_extent_2d(),
+            self.adaptive_max_rays_per_pixel.clamp(1, 8),
+            self.adaptive_noise_threshold,
+        ))
+        .dispatch(ray_count_tex.desc().extent);
+
+        RtdgiRayBudget {
+            ray_count_tex,
+            extra_ray_args_buf,
+            extra_ray_px_buf,
+        }
+    }
+
     fn spatial(
         rg: &mut rg::TemporalRenderGraph,
         input_color: &rg::Handle<Image>,
@@ -144,14 +231,15 @@ impl RtdgiRenderer {
         &mut self,
         rg: &mut rg::TemporalRenderGraph,
         reprojection_map: &rg::Handle<Image>,
+        history_format: vk::Format,
     ) -> ReprojectedRtdgi {
         let gbuffer_extent = reprojection_map.desc().extent_2d();
+        let history_desc = Self::temporal_tex_desc(gbuffer_extent).format(history_format);
 
-        let (temporal_output_tex, history_tex) = self
-            .temporal2_tex
-            .get_output_and_history(rg, Self::temporal_tex_desc(gbuffer_extent));
+        let (temporal_output_tex, history_tex) =
+            self.temporal2_tex.get_output_and_history(rg, history_desc);
 
-        let mut reprojected_history_tex = rg.create(Self::temporal_tex_desc(gbuffer_extent));
+        let mut reprojected_history_tex = rg.create(history_desc);
 
         SimpleRenderPass::new_compute(
             rg.add_pass("rtdgi reproject"),
@@ -175,7 +263,7 @@ impl RtdgiRenderer {
         rg: &mut rg::TemporalRenderGraph,
         ReprojectedRtdgi {
             reprojected_history_tex,
-            temporal_output_tex,
+            mut temporal_output_tex,
         }: ReprojectedRtdgi,
         gbuffer_depth: &GbufferDepth,
         reprojection_map: &rg::Handle<Image>,
@@ -185,7 +273,12 @@ impl RtdgiRenderer {
         wrc: &WrcRenderState,
         tlas: &rg::Handle<RayTracingAcceleration>,
         ssao_tex: &rg::Handle<Image>,
+        ssao_has_bent_normal: bool,
+        reactive_mask: &rg::Handle<Image>,
+        render_quality: &RenderQuality,
     ) -> RtdgiOutput {
+        let specialization_constants = render_quality.specialization_constants();
+
         let mut half_ssao_tex = rg.create(
             ssao_tex
                 .desc()
@@ -203,6 +296,35 @@ impl RtdgiRenderer {
 
         let gbuffer_desc = gbuffer_depth.gbuffer.desc();
 
+        let (mut temporal_variance_output_tex, variance_history_tex) =
+            self.temporal2_variance_tex.get_output_and_history(
+                rg,
+                ImageDesc::new_2d(vk::Format::R16G16_SFLOAT, gbuffer_desc.extent_2d())
+                    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+            );
+
+        let ray_budget = self.adaptive_ray_count.then(|| {
+            self.ray_budget(
+                rg,
+                gbuffer_depth,
+                reprojection_map,
+                &reprojected_history_tex,
+                &variance_history_tex,
+            )
+        });
+
+        // With the adaptive ray count, the base trace takes one candidate per pixel,
+        // and the extra ones come after.
+        let trace_specialization_constants = if ray_budget.is_some() {
+            RenderQuality {
+                diffuse_gi_rays_per_pixel: 1,
+                ..*render_quality
+            }
+            .specialization_constants()
+        } else {
+            specialization_constants.clone()
+        };
+
         let (mut hit_normal_output_tex, hit_normal_history_tex) =
             self.temporal_hit_normal_tex.get_output_and_history(
                 rg,
@@ -297,7 +419,7 @@ impl RtdgiRenderer {
                     ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                     ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                super::rt_hit_groups(),
             )
             .read(&*half_view_normal_tex)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -318,14 +440,15 @@ impl RtdgiRenderer {
             let mut rt_history_validity_input_tex =
                 rg.create(gbuffer_desc.half_res().format(vk::Format::R8_UNORM));
 
-            SimpleRenderPass::new_rt(
+            SimpleRenderPass::new_rt_specialized(
                 rg.add_pass("rtdgi trace"),
                 ShaderSource::hlsl("/shaders/rtdgi/trace_diffuse.rgen.hlsl"),
                 [
                     ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                     ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                super::rt_hit_groups(),
+                trace_specialization_constants,
             )
             .read(&*half_view_normal_tex)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -344,6 +467,33 @@ impl RtdgiRenderer {
             .raw_descriptor_set(1, bindless_descriptor_set)
             .trace_rays(tlas, candidate_radiance_tex.desc().extent);
 
+            if let Some(ray_budget) = &ray_budget {
+                SimpleRenderPass::new_rt_specialized(
+                    rg.add_pass("rtdgi trace extra"),
+                    ShaderSource::hlsl("/shaders/rtdgi/trace_diffuse_extra.rgen.hlsl"),
+                    [
+                        ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
+                        ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                    ],
+                    super::rt_hit_groups(),
+                    specialization_constants.clone(),
+                )
+                .read(&*half_view_normal_tex)
+                .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+                .read(&reprojected_history_tex)
+                .bind_mut(ircache)
+                .bind(wrc)
+                .read(sky_cube)
+                .read(&ray_budget.ray_count_tex)
+                .read(&ray_budget.extra_ray_px_buf)
+                .write(&mut candidate_radiance_tex)
+                .write(&mut candidate_normal_tex)
+                .write(&mut candidate_hit_tex)
+                .constants((gbuffer_desc.extent_inv_extent_2d(),))
+                .raw_descriptor_set(1, bindless_descriptor_set)
+                .trace_rays_indirect(tlas, &ray_budget.extra_ray_args_buf, 0);
+            }
+
             SimpleRenderPass::new_compute(
                 rg.add_pass("validity integrate"),
                 "/shaders/rtdgi/temporal_validity_integrate.hlsl",
@@ -360,9 +510,10 @@ impl RtdgiRenderer {
             ))
             .dispatch(invalidity_output_tex.desc().extent);
 
-            SimpleRenderPass::new_compute(
+            SimpleRenderPass::new_compute_specialized(
                 rg.add_pass("restir temporal"),
                 "/shaders/rtdgi/restir_temporal.hlsl",
+                specialization_constants.clone(),
             )
             .read(&*half_view_normal_tex)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -442,9 +593,10 @@ impl RtdgiRenderer {
                         0
                     };
 
-                SimpleRenderPass::new_compute(
+                SimpleRenderPass::new_compute_specialized(
                     rg.add_pass("restir spatial"),
                     "/shaders/rtdgi/restir_spatial.hlsl",
+                    specialization_constants.clone(),
                 )
                 .read(reservoir_input_tex)
                 .read(bounced_radiance_input_tex)
@@ -483,7 +635,7 @@ impl RtdgiRenderer {
                         ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                         ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                     ],
-                    [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                    super::rt_hit_groups(),
                 )
                 .read(&*half_depth_tex)
                 .read(&temporal_reservoir_packed_tex)
@@ -519,6 +671,7 @@ impl RtdgiRenderer {
             .constants((
                 gbuffer_desc.extent_inv_extent_2d(),
                 irradiance_output_tex.desc().extent_inv_extent_2d(),
+                ssao_has_bent_normal as u32,
             ))
             .dispatch(irradiance_output_tex.desc().extent);
 
@@ -532,7 +685,10 @@ impl RtdgiRenderer {
             reprojection_map,
             &reprojected_history_tex,
             &invalidity_output_tex,
-            temporal_output_tex,
+            reactive_mask,
+            &variance_history_tex,
+            &mut temporal_variance_output_tex,
+            &mut temporal_output_tex,
         );
 
         let filtered_tex = Self::spatial(
@@ -545,6 +701,9 @@ impl RtdgiRenderer {
 
         RtdgiOutput {
             screen_irradiance_tex: filtered_tex.into(),
+            raw_irradiance_tex: irradiance_tex.into(),
+            variance_tex: temporal_variance_output_tex.into(),
+            history_tex: temporal_output_tex.into(),
             candidates: RtdgiCandidates {
                 candidate_radiance_tex,
                 candidate_normal_tex,
@@ -1,19 +1,27 @@
-use std::sync::Arc;
-
 use kajiya_backend::{
     ash::vk,
-    vk_sync,
-    vulkan::{buffer::*, image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
-    BackendError, Device,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
 };
 use kajiya_rg::{self as rg, SimpleRenderPass};
 
 use super::{
-    ircache::IrcacheRenderState, rtdgi::RtdgiCandidates, wrc::WrcRenderState, GbufferDepth,
-    PingPongTemporalResource,
+    blue_noise::BlueNoiseSamplerTables, ircache::IrcacheRenderState, render_quality::RenderQuality,
+    rtdgi::RtdgiCandidates, wrc::WrcRenderState, GbufferDepth, PingPongTemporalResource,
 };
 
-use blue_noise_sampler::spp64::*;
+/// How reflection rays find what they hit.
+///
+/// Without ray tracing support, reflections are always screen-space.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReflectionQuality {
+    /// Screen-space only, with misses falling back to the sky.
+    Low,
+    /// Screen-space first, with rays traced for the misses. On-screen hits
+    /// see last frame's lighting, and can lose view-dependent detail.
+    Medium,
+    /// Ray-traced only.
+    High,
+}
 
 pub struct RtrRenderer {
     temporal_tex: PingPongTemporalResource,
@@ -26,33 +34,13 @@ pub struct RtrRenderer {
     temporal_rng_tex: PingPongTemporalResource,
     temporal_hit_normal_tex: PingPongTemporalResource,
 
-    ranking_tile_buf: Arc<Buffer>,
-    scambling_tile_buf: Arc<Buffer>,
-    sobol_buf: Arc<Buffer>,
-
     pub reuse_rtdgi_rays: bool,
-}
-
-fn as_byte_slice_unchecked<T: Copy>(v: &[T]) -> &[u8] {
-    unsafe {
-        std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * std::mem::size_of::<T>())
-    }
-}
-
-fn make_lut_buffer<T: Copy>(device: &Device, v: &[T]) -> Result<Arc<Buffer>, BackendError> {
-    Ok(Arc::new(device.create_buffer(
-        BufferDesc::new_gpu_only(
-            v.len() * std::mem::size_of::<T>(),
-            vk::BufferUsageFlags::STORAGE_BUFFER,
-        ),
-        "lut buffer",
-        Some(as_byte_slice_unchecked(v)),
-    )?))
+    pub quality: ReflectionQuality,
 }
 
 impl RtrRenderer {
-    pub fn new(device: &Device) -> Result<Self, BackendError> {
-        Ok(Self {
+    pub fn new() -> Self {
+        Self {
             temporal_tex: PingPongTemporalResource::new("rtr.temporal"),
             ray_len_tex: PingPongTemporalResource::new("rtr.ray_len"),
 
@@ -63,15 +51,30 @@ impl RtrRenderer {
             temporal_rng_tex: PingPongTemporalResource::new("rtr.rng"),
             temporal_hit_normal_tex: PingPongTemporalResource::new("rtr.hit_normal"),
 
-            ranking_tile_buf: make_lut_buffer(device, RANKING_TILE)?,
-            scambling_tile_buf: make_lut_buffer(device, SCRAMBLING_TILE)?,
-            sobol_buf: make_lut_buffer(device, SOBOL)?,
-
             reuse_rtdgi_rays: true,
-        })
+            quality: ReflectionQuality::High,
+        }
     }
 }
 
+impl Default for RtrRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Re-traces last frame's reservoirs, so that ReSTIR can reject stale ones. Needs ray tracing.
+struct ReflectionValidation<'a> {
+    tlas: &'a rg::Handle<RayTracingAcceleration>,
+    rtdgi_irradiance: &'a rg::ReadOnlyHandle<Image>,
+    sky_cube: &'a rg::Handle<Image>,
+    prev_radiance: &'a rg::Handle<Image>,
+    depth_pyramid: &'a rg::Handle<Image>,
+    use_ssr_first_hit_u32: u32,
+    ircache: &'a mut IrcacheRenderState,
+    wrc: &'a WrcRenderState,
+}
+
 pub struct TracedRtr {
     pub resolved_tex: rg::Handle<Image>,
     temporal_output_tex: rg::Handle<Image>,
@@ -93,12 +96,15 @@ impl RtrRenderer {
         gbuffer_depth: &GbufferDepth,
         reprojection_map: &rg::Handle<Image>,
         sky_cube: &rg::Handle<Image>,
+        prev_radiance: &rg::Handle<Image>,
         bindless_descriptor_set: vk::DescriptorSet,
         tlas: &rg::Handle<RayTracingAcceleration>,
         rtdgi_irradiance: &rg::ReadOnlyHandle<Image>,
         rtdgi_candidates: RtdgiCandidates,
         ircache: &mut IrcacheRenderState,
         wrc: &WrcRenderState,
+        blue_noise_sampler: &BlueNoiseSamplerTables,
+        render_quality: &RenderQuality,
     ) -> TracedRtr {
         let gbuffer_desc = gbuffer_depth.gbuffer.desc();
 
@@ -108,19 +114,6 @@ impl RtrRenderer {
             candidate_normal_tex: mut refl2_tex,
         } = rtdgi_candidates;
 
-        let ranking_tile_buf = rg.import(
-            self.ranking_tile_buf.clone(),
-            vk_sync::AccessType::ComputeShaderReadSampledImageOrUniformTexelBuffer,
-        );
-        let scambling_tile_buf = rg.import(
-            self.scambling_tile_buf.clone(),
-            vk_sync::AccessType::ComputeShaderReadSampledImageOrUniformTexelBuffer,
-        );
-        let sobol_buf = rg.import(
-            self.sobol_buf.clone(),
-            vk_sync::AccessType::ComputeShaderReadSampledImageOrUniformTexelBuffer,
-        );
-
         let (mut rng_output_tex, rng_history_tex) = self.temporal_rng_tex.get_output_and_history(
             rg,
             ImageDesc::new_2d(vk::Format::R32_UINT, gbuffer_desc.half_res().extent_2d())
@@ -128,21 +121,28 @@ impl RtrRenderer {
         );
 
         let reuse_rtdgi_rays_u32 = if self.reuse_rtdgi_rays { 1u32 } else { 0u32 };
+        let use_ssr_first_hit_u32 = if self.quality == ReflectionQuality::Medium {
+            1u32
+        } else {
+            0u32
+        };
+
+        let depth_pyramid = build_depth_pyramid(rg, &gbuffer_depth.depth);
+        let depth_pyramid_mip_count = depth_pyramid.desc().mip_levels as u32;
 
-        SimpleRenderPass::new_rt(
+        SimpleRenderPass::new_rt_specialized(
             rg.add_pass("reflection trace"),
             ShaderSource::hlsl("/shaders/rtr/reflection.rgen.hlsl"),
             [
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            super::rt_hit_groups(),
+            render_quality.specialization_constants(),
         )
         .read(&gbuffer_depth.gbuffer)
         .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
-        .read(&ranking_tile_buf)
-        .read(&scambling_tile_buf)
-        .read(&sobol_buf)
+        .bind(blue_noise_sampler)
         .read(rtdgi_irradiance)
         .read(sky_cube)
         .bind_mut(ircache)
@@ -151,10 +151,126 @@ impl RtrRenderer {
         .write(&mut refl1_tex)
         .write(&mut refl2_tex)
         .write(&mut rng_output_tex)
-        .constants((gbuffer_desc.extent_inv_extent_2d(), reuse_rtdgi_rays_u32))
+        .read(&depth_pyramid)
+        .read(prev_radiance)
+        .read(reprojection_map)
+        .constants((
+            gbuffer_desc.extent_inv_extent_2d(),
+            reuse_rtdgi_rays_u32,
+            use_ssr_first_hit_u32,
+            depth_pyramid_mip_count,
+            render_quality.reflection_roughness_cutoff,
+        ))
         .raw_descriptor_set(1, bindless_descriptor_set)
         .trace_rays(tlas, refl0_tex.desc().extent);
 
+        self.resolve_candidates(
+            rg,
+            gbuffer_depth,
+            reprojection_map,
+            bindless_descriptor_set,
+            [refl0_tex, refl1_tex, refl2_tex],
+            rng_output_tex,
+            rng_history_tex,
+            Some(ReflectionValidation {
+                tlas,
+                rtdgi_irradiance,
+                sky_cube,
+                prev_radiance,
+                depth_pyramid: &depth_pyramid,
+                use_ssr_first_hit_u32,
+                ircache,
+                wrc,
+            }),
+            render_quality,
+        )
+    }
+
+    /// Reflections without ray tracing, by marching the depth buffer.
+    /// Rays which miss on-screen geometry see the sky.
+    #[allow(clippy::too_many_arguments)]
+    pub fn trace_screen_space(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        reprojection_map: &rg::Handle<Image>,
+        sky_cube: &rg::Handle<Image>,
+        prev_radiance: &rg::Handle<Image>,
+        bindless_descriptor_set: vk::DescriptorSet,
+        render_quality: &RenderQuality,
+    ) -> TracedRtr {
+        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
+
+        // Same as the RTDGI candidates which the ray-traced path writes into
+        let mut refl0_tex = rg.create(
+            gbuffer_desc
+                .half_res()
+                .format(vk::Format::R16G16B16A16_SFLOAT),
+        );
+        let mut refl1_tex = rg.create(
+            gbuffer_desc
+                .half_res()
+                .format(vk::Format::R16G16B16A16_SFLOAT),
+        );
+        let mut refl2_tex = rg.create(gbuffer_desc.half_res().format(vk::Format::R8G8B8A8_SNORM));
+
+        let (mut rng_output_tex, rng_history_tex) = self.temporal_rng_tex.get_output_and_history(
+            rg,
+            ImageDesc::new_2d(vk::Format::R32_UINT, gbuffer_desc.half_res().extent_2d())
+                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+        );
+
+        let depth_pyramid = build_depth_pyramid(rg, &gbuffer_depth.depth);
+        let depth_pyramid_mip_count = depth_pyramid.desc().mip_levels as u32;
+
+        SimpleRenderPass::new_compute(rg.add_pass("reflection ssr"), "/shaders/rtr/ssr.hlsl")
+            .read(&gbuffer_depth.gbuffer)
+            .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+            .read(&depth_pyramid)
+            .read(prev_radiance)
+            .read(reprojection_map)
+            .read(sky_cube)
+            .write(&mut refl0_tex)
+            .write(&mut refl1_tex)
+            .write(&mut refl2_tex)
+            .write(&mut rng_output_tex)
+            .constants((
+                gbuffer_desc.extent_inv_extent_2d(),
+                depth_pyramid_mip_count,
+                render_quality.reflection_roughness_cutoff,
+            ))
+            .raw_descriptor_set(1, bindless_descriptor_set)
+            .dispatch(refl0_tex.desc().extent);
+
+        self.resolve_candidates(
+            rg,
+            gbuffer_depth,
+            reprojection_map,
+            bindless_descriptor_set,
+            [refl0_tex, refl1_tex, refl2_tex],
+            rng_output_tex,
+            rng_history_tex,
+            None,
+            render_quality,
+        )
+    }
+
+    /// Reuses the traced candidates spatio-temporally, and resolves them into the full-res image.
+    #[allow(clippy::too_many_arguments)]
+    fn resolve_candidates(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        gbuffer_depth: &GbufferDepth,
+        reprojection_map: &rg::Handle<Image>,
+        bindless_descriptor_set: vk::DescriptorSet,
+        [refl0_tex, refl1_tex, refl2_tex]: [rg::Handle<Image>; 3],
+        mut rng_output_tex: rg::Handle<Image>,
+        rng_history_tex: rg::Handle<Image>,
+        validation: Option<ReflectionValidation<'_>>,
+        render_quality: &RenderQuality,
+    ) -> TracedRtr {
+        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
+
         let half_view_normal_tex = gbuffer_depth.half_view_normal(rg);
         let half_depth_tex = gbuffer_depth.half_depth(rg);
 
@@ -205,35 +321,50 @@ impl RtrRenderer {
                         .format(vk::Format::R16G16B16A16_SFLOAT),
                 );
 
-            SimpleRenderPass::new_rt(
-                rg.add_pass("reflection validate"),
-                ShaderSource::hlsl("/shaders/rtr/reflection_validate.rgen.hlsl"),
-                [
-                    ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
-                    ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
-                ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
-            )
-            .read(&gbuffer_depth.gbuffer)
-            .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
-            .read(rtdgi_irradiance)
-            .read(sky_cube)
-            .write(&mut refl_restir_invalidity_tex)
-            .bind_mut(ircache)
-            .bind(wrc)
-            .read(&ray_orig_history_tex)
-            .read(&ray_history_tex)
-            .read(&rng_history_tex)
-            .write(&mut irradiance_history_tex)
-            .write(&mut reservoir_history_tex)
-            .constants((gbuffer_desc.extent_inv_extent_2d(),))
-            .raw_descriptor_set(1, bindless_descriptor_set)
-            .trace_rays(tlas, refl0_tex.desc().half_res().extent);
-            //.trace_rays(tlas, refl0_tex.desc().extent);
+            if let Some(validation) = validation {
+                let depth_pyramid_mip_count = validation.depth_pyramid.desc().mip_levels as u32;
 
-            SimpleRenderPass::new_compute(
+                SimpleRenderPass::new_rt(
+                    rg.add_pass("reflection validate"),
+                    ShaderSource::hlsl("/shaders/rtr/reflection_validate.rgen.hlsl"),
+                    [
+                        ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
+                        ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                    ],
+                    super::rt_hit_groups(),
+                )
+                .read(&gbuffer_depth.gbuffer)
+                .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+                .read(validation.rtdgi_irradiance)
+                .read(validation.sky_cube)
+                .write(&mut refl_restir_invalidity_tex)
+                .bind_mut(validation.ircache)
+                .bind(validation.wrc)
+                .read(&ray_orig_history_tex)
+                .read(&ray_history_tex)
+                .read(&rng_history_tex)
+                .write(&mut irradiance_history_tex)
+                .write(&mut reservoir_history_tex)
+                .read(validation.depth_pyramid)
+                .read(validation.prev_radiance)
+                .read(reprojection_map)
+                .constants((
+                    gbuffer_desc.extent_inv_extent_2d(),
+                    validation.use_ssr_first_hit_u32,
+                    depth_pyramid_mip_count,
+                    render_quality.reflection_roughness_cutoff,
+                ))
+                .raw_descriptor_set(1, bindless_descriptor_set)
+                .trace_rays(validation.tlas, refl0_tex.desc().half_res().extent);
+                //.trace_rays(tlas, refl0_tex.desc().extent);
+            } else {
+                rg::imageops::clear_color(rg, &mut refl_restir_invalidity_tex, [0.0; 4]);
+            }
+
+            SimpleRenderPass::new_compute_specialized(
                 rg.add_pass("rtr restir temporal"),
                 "/shaders/rtr/rtr_restir_temporal.hlsl",
+                render_quality.specialization_constants(),
             )
             .read(&gbuffer_depth.gbuffer)
             .read(&*half_view_normal_tex)
@@ -323,42 +454,64 @@ impl RtrRenderer {
             refl_restir_invalidity_tex,
         }
     }
+}
 
-    pub fn create_dummy_output(
-        &mut self,
-        rg: &mut rg::TemporalRenderGraph,
-        gbuffer_depth: &GbufferDepth,
-    ) -> TracedRtr {
-        let gbuffer_desc = gbuffer_depth.gbuffer.desc();
-
-        let resolved_tex = rg.create(
-            gbuffer_depth
-                .gbuffer
-                .desc()
-                .usage(vk::ImageUsageFlags::empty())
-                .format(vk::Format::R8G8B8A8_UNORM),
-        );
-
-        let (temporal_output_tex, history_tex) = self
-            .temporal_tex
-            .get_output_and_history(rg, Self::temporal_tex_desc(gbuffer_desc.extent_2d()));
+// Pyramid of the closest depth, for hierarchical screen-space ray marching.
+fn build_depth_pyramid(
+    rg: &mut rg::TemporalRenderGraph,
+    depth: &rg::Handle<Image>,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(
+        ImageDesc::new_2d(vk::Format::R32_SFLOAT, depth.desc().extent_2d()).all_mip_levels(),
+    );
 
-        let (ray_len_output_tex, _ray_len_history_tex) = self.ray_len_tex.get_output_and_history(
-            rg,
-            ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, gbuffer_desc.extent_2d())
-                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
-        );
+    SimpleRenderPass::new_compute(
+        rg.add_pass("depth pyramid0"),
+        "/shaders/copy_depth_to_r.hlsl",
+    )
+    .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
+    .write_view(
+        &mut output,
+        ImageViewDesc::builder()
+            .base_mip_level(0)
+            .level_count(Some(1)),
+    )
+    .dispatch(output.desc().extent);
 
-        let refl_restir_invalidity_tex = rg.create(ImageDesc::new_2d(vk::Format::R8_UNORM, [1, 1]));
+    for target_mip in 1..(output.desc().mip_levels as u32) {
+        let input_extent = output
+            .desc()
+            .div_extent([1 << (target_mip - 1), 1 << (target_mip - 1), 1])
+            .extent;
+        let output_extent = output
+            .desc()
+            .div_extent([1 << target_mip, 1 << target_mip, 1])
+            .extent;
 
-        TracedRtr {
-            resolved_tex,
-            temporal_output_tex,
-            history_tex,
-            ray_len_tex: ray_len_output_tex,
-            refl_restir_invalidity_tex,
-        }
+        SimpleRenderPass::new_compute(
+            rg.add_pass(&format!("depth pyramid{}", target_mip)),
+            "/shaders/rtr/depth_pyramid_downsample.hlsl",
+        )
+        .read_view(
+            &output,
+            ImageViewDesc::builder()
+                .base_mip_level(target_mip - 1)
+                .level_count(Some(1)),
+        )
+        .write_view(
+            &mut output,
+            ImageViewDesc::builder()
+                .base_mip_level(target_mip)
+                .level_count(Some(1)),
+        )
+        .constants((
+            [input_extent[0], input_extent[1]],
+            [output_extent[0], output_extent[1]],
+        ))
+        .dispatch(output_extent);
     }
+
+    output
 }
 
 impl TracedRtr {
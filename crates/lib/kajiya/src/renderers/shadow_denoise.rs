@@ -1,4 +1,7 @@
-use super::{GbufferDepth, PingPongTemporalResource};
+use super::{
+    tile_lists::{TileClass, TileLists},
+    GbufferDepth, PingPongTemporalResource,
+};
 use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg, SimpleRenderPass, TemporalRenderGraph};
 
@@ -9,14 +12,19 @@ pub struct ShadowDenoiseRenderer {
 
 impl Default for ShadowDenoiseRenderer {
     fn default() -> Self {
-        Self {
-            accum: PingPongTemporalResource::new("shadow_denoise_accum"),
-            moments: PingPongTemporalResource::new("shadow_denoise_moments"),
-        }
+        Self::new("shadow_denoise")
     }
 }
 
 impl ShadowDenoiseRenderer {
+    /// `name` prefixes the temporal resources, which must be unique per instance.
+    pub fn new(name: &str) -> Self {
+        Self {
+            accum: PingPongTemporalResource::new(&format!("{}_accum", name)),
+            moments: PingPongTemporalResource::new(&format!("{}_moments", name)),
+        }
+    }
+
     pub fn render(
         &mut self,
         rg: &mut rg::TemporalRenderGraph,
@@ -87,6 +95,20 @@ impl ShadowDenoiseRenderer {
         ))
         .dispatch(gbuffer_desc.extent);
 
+        // The temporal pass marks the tiles which are uniformly lit or shadowed; the spatial
+        // filter only needs to run on the others, which are usually few.
+        let mut tile_lists = TileLists::new(rg, gbuffer_desc.extent_2d());
+        tile_lists
+            .write(
+                SimpleRenderPass::new_compute(
+                    rg.add_pass("shadow classify tiles"),
+                    "/shaders/shadow_denoise/classify_tiles.hlsl",
+                )
+                .read(&metadata_image),
+            )
+            .constants(tile_lists.tile_count)
+            .dispatch([tile_lists.tile_count[0], tile_lists.tile_count[1], 1]);
+
         let mut temp = rg.create(spatial_image_desc);
         Self::filter_spatial(
             rg,
@@ -94,6 +116,7 @@ impl ShadowDenoiseRenderer {
             &spatial_input_image,
             &mut accum_image,
             &metadata_image,
+            &tile_lists,
             gbuffer_depth,
             bitpacked_shadow_mask_extent,
         );
@@ -104,6 +127,7 @@ impl ShadowDenoiseRenderer {
             &accum_image,
             &mut temp,
             &metadata_image,
+            &tile_lists,
             gbuffer_depth,
             bitpacked_shadow_mask_extent,
         );
@@ -114,6 +138,7 @@ impl ShadowDenoiseRenderer {
             &temp,
             &mut spatial_input_image,
             &metadata_image,
+            &tile_lists,
             gbuffer_depth,
             bitpacked_shadow_mask_extent,
         );
@@ -127,23 +152,36 @@ impl ShadowDenoiseRenderer {
         input_image: &rg::Handle<Image>,
         output_image: &mut rg::Handle<Image>,
         metadata_image: &rg::Handle<Image>,
+        tile_lists: &TileLists,
         gbuffer_depth: &GbufferDepth,
         bitpacked_shadow_mask_extent: [u32; 2],
     ) {
-        SimpleRenderPass::new_compute(
-            rg.add_pass("shadow spatial"),
-            "/shaders/shadow_denoise/spatial_filter.hlsl",
-        )
-        .read(input_image)
-        .read(metadata_image)
-        .read(&gbuffer_depth.geometric_normal)
-        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
-        .write(output_image)
-        .constants((
-            output_image.desc().extent_inv_extent_2d(),
-            bitpacked_shadow_mask_extent,
-            step_size,
-        ))
-        .dispatch(output_image.desc().extent);
+        tile_lists.dispatch(
+            SimpleRenderPass::new_compute(
+                rg.add_pass("shadow spatial simple"),
+                "/shaders/shadow_denoise/fill_simple_tiles.hlsl",
+            )
+            .read(metadata_image)
+            .write(output_image),
+            TileClass::Simple,
+        );
+
+        tile_lists.dispatch(
+            SimpleRenderPass::new_compute(
+                rg.add_pass("shadow spatial"),
+                "/shaders/shadow_denoise/spatial_filter.hlsl",
+            )
+            .read(input_image)
+            .read(metadata_image)
+            .read(&gbuffer_depth.geometric_normal)
+            .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+            .write(output_image)
+            .constants((
+                output_image.desc().extent_inv_extent_2d(),
+                bitpacked_shadow_mask_extent,
+                step_size,
+            )),
+            TileClass::Complex,
+        );
     }
 }
@@ -5,17 +5,21 @@ use kajiya_backend::{
 use kajiya_rg::{self as rg};
 use rg::{RenderGraph, SimpleRenderPass};
 
-use super::GbufferDepth;
+use super::{
+    light_clusters::LightClusters, punctual_shadow_cache::PunctualShadowCacheFrame,
+    render_quality::RenderQuality, GbufferDepth,
+};
 
 pub fn trace_sun_shadow_mask(
     rg: &mut RenderGraph,
     gbuffer_depth: &GbufferDepth,
     tlas: &rg::Handle<RayTracingAcceleration>,
     bindless_descriptor_set: vk::DescriptorSet,
+    render_quality: &RenderQuality,
 ) -> rg::Handle<Image> {
     let mut output_img = rg.create(gbuffer_depth.depth.desc().format(vk::Format::R8_UNORM));
 
-    SimpleRenderPass::new_rt(
+    SimpleRenderPass::new_rt_specialized(
         rg.add_pass("trace shadow mask"),
         ShaderSource::hlsl("/shaders/rt/trace_sun_shadow_mask.rgen.hlsl"),
         [
@@ -23,8 +27,85 @@ pub fn trace_sun_shadow_mask(
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        std::iter::empty(),
+        super::rt_shadow_hit_groups(),
+        render_quality.specialization_constants(),
+    )
+    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+    .read(&gbuffer_depth.geometric_normal)
+    .write(&mut output_img)
+    .raw_descriptor_set(1, bindless_descriptor_set)
+    .trace_rays(tlas, output_img.desc().extent);
+
+    output_img
+}
+
+/// Direct lighting from the punctual lights in each pixel's cluster, shadowed by tracing
+/// a ray towards each; only against the dynamic shadow casters for the lights in `shadow_cache`.
+pub fn trace_punctual_lighting(
+    rg: &mut RenderGraph,
+    gbuffer_depth: &GbufferDepth,
+    light_clusters: &LightClusters,
+    shadow_cache: PunctualShadowCacheFrame,
+    tlas: &rg::Handle<RayTracingAcceleration>,
+    bindless_descriptor_set: vk::DescriptorSet,
+) -> rg::Handle<Image> {
+    let mut output_img = rg.create(
+        gbuffer_depth
+            .gbuffer
+            .desc()
+            .format(vk::Format::R16G16B16A16_SFLOAT),
+    );
+
+    SimpleRenderPass::new_rt(
+        rg.add_pass("trace punctual lighting"),
+        ShaderSource::hlsl("/shaders/rt/trace_punctual_lighting.rgen.hlsl"),
+        [
+            // Duplicated because `rt.hlsl` hardcodes miss index to 1
+            ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+            ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+        ],
+        super::rt_shadow_hit_groups(),
+    )
+    .read(&gbuffer_depth.gbuffer)
+    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+    .read(&gbuffer_depth.geometric_normal)
+    .write(&mut output_img)
+    .read(&shadow_cache.distances)
+    .dynamic_storage_buffer_vec(shadow_cache.light_slots)
+    .read(&light_clusters.lists)
+    .constants(light_clusters.constants)
+    .raw_descriptor_set(1, bindless_descriptor_set)
+    .trace_rays(tlas, output_img.desc().extent);
+
+    output_img
+}
+
+/// Direct lighting from all rect lights, evaluated analytically, and shadowed
+/// by tracing a ray towards a random point on each.
+pub fn trace_rect_lighting(
+    rg: &mut RenderGraph,
+    gbuffer_depth: &GbufferDepth,
+    tlas: &rg::Handle<RayTracingAcceleration>,
+    bindless_descriptor_set: vk::DescriptorSet,
+) -> rg::Handle<Image> {
+    let mut output_img = rg.create(
+        gbuffer_depth
+            .gbuffer
+            .desc()
+            .format(vk::Format::R16G16B16A16_SFLOAT),
+    );
+
+    SimpleRenderPass::new_rt(
+        rg.add_pass("trace rect lighting"),
+        ShaderSource::hlsl("/shaders/rt/trace_rect_lighting.rgen.hlsl"),
+        [
+            // Duplicated because `rt.hlsl` hardcodes miss index to 1
+            ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+            ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+        ],
+        super::rt_shadow_hit_groups(),
     )
+    .read(&gbuffer_depth.gbuffer)
     .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
     .read(&gbuffer_depth.geometric_normal)
     .write(&mut output_img)
@@ -0,0 +1,137 @@
+//! Linear blend skinning of meshes on the GPU, for `WorldRenderer::set_mesh_skin`.
+//!
+//! The skinned vertices are written to the vertex buffer regions of the mesh's deformation,
+//! which the G-buffer raster, the motion vectors, and the refit of the mesh's BLAS all read,
+//! so that rays hit the same surface as is rasterized.
+
+use std::sync::Arc;
+
+use glam::Affine3A;
+use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::buffer::Buffer};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+/// Per vertex: four joint indices, as `u16`s, then their weights.
+/// Must match `skin_vertices.hlsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SkinVertex {
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
+// Must match the constants of `skin_vertices.hlsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SkinningConstants {
+    vertex_count: u32,
+    joint_count: u32,
+    rest_core_offset: u32,
+    // Zero if the mesh has no tangents
+    rest_tangent_offset: u32,
+    skin_offset: u32,
+    output_core_offset: u32,
+    output_tangent_offset: u32,
+    pad: u32,
+}
+
+/// A mesh to skin this frame. Offsets are in bytes, into the vertex buffer.
+pub(crate) struct SkinningDispatch {
+    pub vertex_count: u32,
+    pub rest_core_offset: u32,
+    pub rest_tangent_offset: u32,
+    pub skin_offset: u32,
+    pub output_core_offset: u32,
+    pub output_tangent_offset: u32,
+    pub joint_matrices: Vec<Affine3A>,
+}
+
+/// Rows of the joint matrices, as `row_major float3x4`.
+fn pack_joint_matrix(m: &Affine3A) -> [f32; 12] {
+    [
+        m.x_axis.x,
+        m.y_axis.x,
+        m.z_axis.x,
+        m.translation.x,
+        m.x_axis.y,
+        m.y_axis.y,
+        m.z_axis.y,
+        m.translation.y,
+        m.x_axis.z,
+        m.y_axis.z,
+        m.z_axis.z,
+        m.translation.z,
+    ]
+}
+
+/// Records a compute pass per mesh, writing into `vertex_buffer`, which the graph doesn't
+/// track otherwise. Meant to be called after the frame's vertex buffer writes, which upload
+/// the skin data, and before anything reads the skinned vertices.
+pub(crate) fn skin_meshes(
+    rg: &mut rg::RenderGraph,
+    vertex_buffer: Arc<Buffer>,
+    dispatches: Vec<SkinningDispatch>,
+) {
+    if dispatches.is_empty() {
+        return;
+    }
+
+    // The regions written were last read by the previous frame, anywhere in the pipeline.
+    let mut vertex_buffer = rg.import(vertex_buffer, AccessType::General);
+
+    let mut last_pass = None;
+    for dispatch in dispatches {
+        let constants = SkinningConstants {
+            vertex_count: dispatch.vertex_count,
+            joint_count: dispatch.joint_matrices.len() as u32,
+            rest_core_offset: dispatch.rest_core_offset,
+            rest_tangent_offset: dispatch.rest_tangent_offset,
+            skin_offset: dispatch.skin_offset,
+            output_core_offset: dispatch.output_core_offset,
+            output_tangent_offset: dispatch.output_tangent_offset,
+            pad: 0,
+        };
+
+        let pass = SimpleRenderPass::new_compute(
+            rg.add_pass("skin vertices"),
+            "/shaders/skin_vertices.hlsl",
+        )
+        // Meshes write disjoint regions, so their passes don't need to wait on each other.
+        .write_no_sync(&mut vertex_buffer)
+        .dynamic_storage_buffer_vec(
+            dispatch
+                .joint_matrices
+                .iter()
+                .map(pack_joint_matrix)
+                .collect::<Vec<_>>(),
+        )
+        .constants(constants);
+
+        last_pass = Some(pass.id());
+        pass.dispatch([dispatch.vertex_count, 1, 1]);
+    }
+
+    // Everything else reads the vertex buffer through the bindless set, without the graph.
+    let mut pass = rg.add_pass("skinned vertices barrier");
+    pass.keep_after(last_pass.unwrap());
+    pass.render(|api| {
+        unsafe {
+            api.device().raw.cmd_pipeline_barrier(
+                api.cb.raw,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(
+                        vk::AccessFlags::SHADER_READ
+                            | vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+                    )
+                    .build()],
+                &[],
+                &[],
+            );
+        }
+
+        Ok(())
+    });
+}
@@ -1,7 +1,77 @@
+use glam::Vec3;
 use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg, SimpleRenderPass};
+use rust_shaders_shared::atmosphere::AtmosphereConstants;
 
-pub fn render_sky_cube(rg: &mut rg::RenderGraph) -> rg::Handle<Image> {
+/// Parameters of the physically based atmosphere, which determines the color of the sky and the sun.
+///
+/// Changing them recomputes the atmosphere's lookup tables on the next frame.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AtmosphereParams {
+    pub planet_radius_km: f32,
+
+    /// Height of the top of the atmosphere above the ground.
+    pub atmosphere_height_km: f32,
+
+    /// Amount of aerosols, such as haze and dust, relative to a clear day on Earth.
+    /// Higher values make the sky paler, and the glow around the sun wider.
+    pub turbidity: f32,
+
+    /// Amount of ozone relative to Earth's. It absorbs orange light, keeping the sky blue at dusk.
+    pub ozone_density: f32,
+
+    /// Fraction of sunlight reflected by the ground, which brightens the sky near the horizon.
+    pub ground_albedo: f32,
+}
+
+impl Default for AtmosphereParams {
+    fn default() -> Self {
+        Self::EARTH
+    }
+}
+
+impl AtmosphereParams {
+    pub const EARTH: Self = Self {
+        planet_radius_km: 6360.0,
+        atmosphere_height_km: 100.0,
+        turbidity: 1.0,
+        ozone_density: 1.0,
+        ground_albedo: 0.3,
+    };
+
+    pub(crate) fn to_constants(&self) -> AtmosphereConstants {
+        // Coefficients per meter, from "A Scalable and Production Ready Sky and Atmosphere Rendering Technique"
+        const RAYLEIGH_SCATTERING: [f32; 3] = [5.802e-6, 13.558e-6, 33.1e-6];
+        const MIE_SCATTERING: f32 = 3.996e-6;
+        const MIE_ABSORPTION: f32 = 0.444e-6;
+        const OZONE_ABSORPTION: [f32; 3] = [0.650e-6, 1.881e-6, 0.085e-6];
+
+        let turbidity = self.turbidity.max(0.0);
+        let ozone_density = self.ozone_density.max(0.0);
+
+        AtmosphereConstants {
+            rayleigh_scattering: Vec3::from(RAYLEIGH_SCATTERING).extend(0.0),
+            mie_scattering: Vec3::splat(MIE_SCATTERING * turbidity).extend(0.0),
+            mie_absorption: Vec3::splat(MIE_ABSORPTION * turbidity).extend(0.0),
+            ozone_absorption: (Vec3::from(OZONE_ABSORPTION) * ozone_density).extend(0.0),
+
+            planet_radius: self.planet_radius_km.max(1.0) * 1000.0,
+            atmosphere_height: self.atmosphere_height_km.max(1.0) * 1000.0,
+            rayleigh_scale_height: 8000.0,
+            mie_scale_height: 1200.0,
+
+            mie_g: 0.8,
+            ozone_center_altitude: 25000.0,
+            ozone_half_width: 15000.0,
+            ground_albedo: self.ground_albedo.clamp(0.0, 1.0),
+        }
+    }
+}
+
+pub fn render_sky_cube(
+    rg: &mut rg::RenderGraph,
+    bindless_descriptor_set: vk::DescriptorSet,
+) -> rg::Handle<Image> {
     let width = 64;
     let mut sky_tex = rg.create(ImageDesc::new_cube(vk::Format::R16G16B16A16_SFLOAT, width));
 
@@ -10,6 +80,7 @@ pub fn render_sky_cube(rg: &mut rg::RenderGraph) -> rg::Handle<Image> {
             &mut sky_tex,
             ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
         )
+        .raw_descriptor_set(1, bindless_descriptor_set)
         .dispatch([width, width, 6]);
 
     sky_tex
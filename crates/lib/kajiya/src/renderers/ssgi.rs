@@ -1,4 +1,7 @@
-use super::{GbufferDepth, PingPongTemporalResource};
+use super::{
+    bilateral_upsample::{bilateral_upsample, BilateralUpsampleParams, LowResSampling},
+    GbufferDepth, PingPongTemporalResource,
+};
 use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg, SimpleRenderPass, TemporalRenderGraph};
 use rust_shaders_shared::ssgi::SsgiConstants;
@@ -29,6 +32,7 @@ impl SsgiRenderer {
         reprojection_map: &rg::Handle<Image>,
         prev_radiance: &rg::Handle<Image>,
         bindless_descriptor_set: vk::DescriptorSet,
+        upsample_params: &BilateralUpsampleParams,
     ) -> rg::ReadOnlyHandle<Image> {
         let gbuffer_desc = gbuffer_depth.gbuffer.desc();
         let half_view_normal_tex = gbuffer_depth.half_view_normal(rg);
@@ -77,6 +81,7 @@ impl SsgiRenderer {
             gbuffer_depth,
             reprojection_map,
             &mut self.ssgi_tex,
+            upsample_params,
         )
     }
 
@@ -86,6 +91,7 @@ impl SsgiRenderer {
         gbuffer_depth: &GbufferDepth,
         reprojection_map: &rg::Handle<Image>,
         temporal_tex: &mut PingPongTemporalResource,
+        upsample_params: &BilateralUpsampleParams,
     ) -> rg::ReadOnlyHandle<Image> {
         let gbuffer_desc = gbuffer_depth.gbuffer.desc();
         let half_view_normal_tex = gbuffer_depth.half_view_normal(rg);
@@ -116,11 +122,15 @@ impl SsgiRenderer {
             .write(&mut spatially_filtered_tex)
             .dispatch(spatially_filtered_tex.desc().extent);
 
-            Self::upsample_ssgi(
+            // Unoccluded
+            bilateral_upsample(
                 rg,
+                "ssao upsample",
                 &spatially_filtered_tex,
-                &gbuffer_depth.depth,
-                &gbuffer_depth.gbuffer,
+                gbuffer_depth,
+                LowResSampling::HalfResSubsample,
+                [1.0; 4],
+                upsample_params,
             )
         };
 
@@ -155,28 +165,4 @@ impl SsgiRenderer {
         ImageDesc::new_2d(INTERNAL_TEX_FMT, extent)
             .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
     }
-
-    fn upsample_ssgi(
-        rg: &mut rg::RenderGraph,
-        ssgi: &rg::Handle<Image>,
-        depth: &rg::Handle<Image>,
-        gbuffer: &rg::Handle<Image>,
-    ) -> rg::Handle<Image> {
-        let mut output_tex = rg.create(gbuffer.desc().format(INTERNAL_TEX_FMT));
-
-        if USE_RUST_SHADERS {
-            SimpleRenderPass::new_compute_rust(rg.add_pass("ssao upsample"), "ssgi::upsample_cs")
-        } else {
-            SimpleRenderPass::new_compute(
-                rg.add_pass("ssao upsample"),
-                "/shaders/ssgi/upsample.hlsl",
-            )
-        }
-        .read(ssgi)
-        .read_aspect(depth, vk::ImageAspectFlags::DEPTH)
-        .read(gbuffer)
-        .write(&mut output_tex)
-        .dispatch(output_tex.desc().extent);
-        output_tex
-    }
 }
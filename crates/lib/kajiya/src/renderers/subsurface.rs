@@ -0,0 +1,124 @@
+use kajiya_backend::{ash::vk, vulkan::image::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::GbufferDepth;
+
+/// Matches `GBUFFER_SUBSURFACE_PROFILE_COUNT` in `gbuffer.hlsl`.
+pub const SUBSURFACE_PROFILE_COUNT: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SubsurfaceProfile {
+    /// How far light scatters under the surface before leaving it, per color channel,
+    /// in world units. The standard deviation of a Gaussian falloff.
+    pub radius: [f32; 3],
+}
+
+impl SubsurfaceProfile {
+    pub const SKIN: Self = Self {
+        radius: [0.0037, 0.0014, 0.0007],
+    };
+
+    pub const WAX: Self = Self {
+        radius: [0.012, 0.009, 0.006],
+    };
+
+    pub const MARBLE: Self = Self {
+        radius: [0.008, 0.0065, 0.005],
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SubsurfaceParams {
+    pub enabled: bool,
+
+    /// Per direction of the separable blur.
+    pub sample_count: u32,
+
+    /// Indexed by `MeshMaterial::subsurface_profile`.
+    pub profiles: [SubsurfaceProfile; SUBSURFACE_PROFILE_COUNT],
+}
+
+impl Default for SubsurfaceParams {
+    fn default() -> Self {
+        let mut profiles = [SubsurfaceProfile::SKIN; SUBSURFACE_PROFILE_COUNT];
+        profiles[1] = SubsurfaceProfile::WAX;
+        profiles[2] = SubsurfaceProfile::MARBLE;
+
+        Self {
+            enabled: true,
+            sample_count: 11,
+            profiles,
+        }
+    }
+}
+
+/// Blurs the lit scene under the pixels whose G-buffer materials have subsurface scattering,
+/// by their profiles' radii, in two separable passes.
+///
+/// The samples follow the depth buffer, so that light doesn't bleed across silhouettes.
+/// Specular lighting is blurred too, which, over the short radii involved, barely shows.
+pub fn subsurface_scattering(
+    rg: &mut rg::RenderGraph,
+    gbuffer_depth: &GbufferDepth,
+    lit: &rg::Handle<Image>,
+    params: &SubsurfaceParams,
+) -> rg::Handle<Image> {
+    let profile_radii: Vec<[f32; 4]> = params
+        .profiles
+        .iter()
+        .map(|profile| {
+            let [r, g, b] = profile.radius;
+            [r.max(1e-5), g.max(1e-5), b.max(1e-5), 0.0]
+        })
+        .collect();
+
+    let horizontal = blur_pass(
+        rg,
+        "sss horizontal",
+        gbuffer_depth,
+        lit,
+        &profile_radii,
+        [1, 0],
+        params.sample_count,
+    );
+
+    blur_pass(
+        rg,
+        "sss vertical",
+        gbuffer_depth,
+        &horizontal,
+        &profile_radii,
+        [0, 1],
+        params.sample_count,
+    )
+}
+
+fn blur_pass(
+    rg: &mut rg::RenderGraph,
+    pass_name: &str,
+    gbuffer_depth: &GbufferDepth,
+    input: &rg::Handle<Image>,
+    profile_radii: &[[f32; 4]],
+    direction: [i32; 2],
+    sample_count: u32,
+) -> rg::Handle<Image> {
+    let mut output = rg.create(input.desc().usage(vk::ImageUsageFlags::empty()));
+
+    SimpleRenderPass::new_compute(
+        rg.add_pass(pass_name),
+        "/shaders/subsurface_scattering.hlsl",
+    )
+    .read(input)
+    .read(&gbuffer_depth.gbuffer)
+    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+    .dynamic_storage_buffer_vec(profile_radii.to_vec())
+    .write(&mut output)
+    .constants((
+        output.desc().extent_inv_extent_2d(),
+        direction,
+        sample_count.max(1),
+    ))
+    .dispatch(output.desc().extent);
+
+    output
+}
@@ -3,11 +3,49 @@ use glam::Vec2;
 use kajiya_backend::{ash::vk, vulkan::image::*};
 use kajiya_rg::{self as rg, SimpleRenderPass};
 
+/// Sub-pixel offsets to jitter the projection by, cycled through frame by frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TaaJitterSequence {
+    /// The first 128 points of the Halton (2, 3) sequence.
+    Halton,
+    /// Martin Roberts' R2 sequence, which covers the pixel more evenly over short windows.
+    R2,
+}
+
+impl TaaJitterSequence {
+    /// Offset in pixels, in the `[-0.5, 0.5)` range.
+    pub fn offset(self, frame_idx: u32) -> Vec2 {
+        match self {
+            TaaJitterSequence::Halton => {
+                let i = frame_idx % 128 + 1;
+                Vec2::new(radical_inverse(i, 2) - 0.5, radical_inverse(i, 3) - 0.5)
+            }
+            TaaJitterSequence::R2 => {
+                // The plastic constant; the generalization of the golden ratio to 2D.
+                const G: f64 = 1.324_717_957_244_746;
+                let n = (frame_idx % 4096) as f64;
+                let x = (0.5 + n / G).fract();
+                let y = (0.5 + n / (G * G)).fract();
+                Vec2::new(x as f32 - 0.5, y as f32 - 0.5)
+            }
+        }
+    }
+}
+
 pub struct TaaRenderer {
     temporal_tex: PingPongTemporalResource,
     temporal_velocity_tex: PingPongTemporalResource,
     temporal_smooth_var_tex: PingPongTemporalResource,
     pub current_supersample_offset: Vec2,
+    pub jitter_sequence: TaaJitterSequence,
+
+    /// Scales the color box around the neighborhood that history is clamped to.
+    /// Lower values ghost less, but flicker more.
+    pub clamp_box_scale: f32,
+
+    /// Strength of the contrast-adaptive sharpening applied to the output,
+    /// but not to the history. Zero skips the pass.
+    pub sharpening: f32,
 }
 
 impl Default for TaaRenderer {
@@ -23,6 +61,9 @@ impl TaaRenderer {
             temporal_velocity_tex: PingPongTemporalResource::new("taa.velocity"),
             temporal_smooth_var_tex: PingPongTemporalResource::new("taa.smooth_var"),
             current_supersample_offset: Vec2::ZERO,
+            jitter_sequence: TaaJitterSequence::Halton,
+            clamp_box_scale: 1.0,
+            sharpening: 0.0,
         }
     }
 }
@@ -38,12 +79,15 @@ impl TaaRenderer {
             .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &mut self,
         rg: &mut rg::TemporalRenderGraph,
         input_tex: &rg::Handle<Image>,
         reprojection_map: &rg::Handle<Image>,
         depth_tex: &rg::Handle<Image>,
+        responsive_mask: &rg::Handle<Image>,
+        reactive_mask: &rg::Handle<Image>,
         output_extent: [u32; 2],
     ) -> TaaOutput {
         //let input_extent = input_tex.desc().extent_2d();
@@ -174,6 +218,8 @@ impl TaaRenderer {
             .read_aspect(depth_tex, vk::ImageAspectFlags::DEPTH)
             .read(&smooth_var_history_tex)
             .read(&input_prob_img)
+            .read(responsive_mask)
+            .read(reactive_mask)
             .write(&mut temporal_output_tex)
             .write(&mut this_frame_output_img)
             .write(&mut smooth_var_output_tex)
@@ -181,12 +227,40 @@ impl TaaRenderer {
             .constants((
                 input_tex.desc().extent_inv_extent_2d(),
                 temporal_output_tex.desc().extent_inv_extent_2d(),
+                self.clamp_box_scale,
             ))
             .dispatch(temporal_output_tex.desc().extent);
 
+        let this_frame_output_img = if self.sharpening > 0.0 {
+            let mut sharpened_img = rg.create(*this_frame_output_img.desc());
+            SimpleRenderPass::new_compute(rg.add_pass("taa sharpen"), "/shaders/taa/sharpen.hlsl")
+                .read(&this_frame_output_img)
+                .write(&mut sharpened_img)
+                .constants((sharpened_img.desc().extent_inv_extent_2d(), self.sharpening))
+                .dispatch(sharpened_img.desc().extent);
+            sharpened_img
+        } else {
+            this_frame_output_img
+        };
+
         TaaOutput {
             temporal_out: temporal_output_tex.into(),
             this_frame_out: this_frame_output_img,
         }
     }
 }
+
+fn radical_inverse(mut n: u32, base: u32) -> f32 {
+    let mut val = 0.0f32;
+    let inv_base = 1.0f32 / base as f32;
+    let mut inv_bi = inv_base;
+
+    while n > 0 {
+        let d_i = n % base;
+        val += d_i as f32 * inv_bi;
+        n = (n as f32 * inv_base) as u32;
+        inv_bi *= inv_base;
+    }
+
+    val
+}
@@ -0,0 +1,95 @@
+//! Tile classification, for passes which only have real work to do in parts of the screen.
+//!
+//! A classification pass runs one thread per screen tile, and appends each tile to the list
+//! of its `TileClass` with `tile_lists_push` from `inc/tile_lists.hlsl`, which also counts
+//! the groups in the indirect dispatch arguments of the list. The passes which follow
+//! dispatch one group per tile of a class with `TileLists::dispatch`, and find their tile
+//! with `tile_lists_get`; tiles of the other classes cost nothing.
+
+use kajiya_backend::{ash::vk, vulkan::buffer::*};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::buffer_writes::clear_buffer;
+
+/// In pixels, on each side. Must match `TILE_LIST_TILE_SIZE` in `inc/tile_lists.hlsl`.
+pub const TILE_LIST_TILE_SIZE: u32 = 8;
+
+// Must match `inc/tile_lists.hlsl`
+const TILE_CLASS_COUNT: usize = 2;
+const DISPATCH_ARGS_STRIDE: usize = 4 * std::mem::size_of::<u32>();
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TileClass {
+    /// Uniform contents, which a cheap path handles.
+    Simple = 0,
+    /// Needs the full work.
+    Complex = 1,
+}
+
+impl TileClass {
+    fn dispatch_args_offset(self) -> u64 {
+        (self as usize * DISPATCH_ARGS_STRIDE) as u64
+    }
+}
+
+pub struct TileLists {
+    /// `[group count, 1, 1, unused]` per class
+    pub dispatch_args: rg::Handle<Buffer>,
+    /// Per class, the tiles as `x | y << 16`
+    pub tiles: [rg::Handle<Buffer>; TILE_CLASS_COUNT],
+    /// Tiles covering the extent passed to `new`
+    pub tile_count: [u32; 2],
+}
+
+impl TileLists {
+    /// Empty lists for the tiles covering `extent`.
+    pub fn new(rg: &mut rg::RenderGraph, extent: [u32; 2]) -> Self {
+        let tile_count = [
+            (extent[0] + TILE_LIST_TILE_SIZE - 1) / TILE_LIST_TILE_SIZE,
+            (extent[1] + TILE_LIST_TILE_SIZE - 1) / TILE_LIST_TILE_SIZE,
+        ];
+
+        let mut dispatch_args = rg.create(BufferDesc::new_gpu_only(
+            TILE_CLASS_COUNT * DISPATCH_ARGS_STRIDE,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        ));
+        clear_buffer(rg, "clear tile lists", &mut dispatch_args);
+
+        let tiles_desc = BufferDesc::new_gpu_only(
+            (tile_count[0] * tile_count[1]) as usize * std::mem::size_of::<u32>(),
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+        );
+
+        Self {
+            dispatch_args,
+            tiles: [rg.create(tiles_desc), rg.create(tiles_desc)],
+            tile_count,
+        }
+    }
+
+    /// Binds the dispatch arguments, then the list of each class in order, for a classification
+    /// pass to fill. Dispatch that with one thread per tile, over `tile_count`.
+    pub fn write<'rg>(
+        &mut self,
+        pass: SimpleRenderPass<'rg, rg::RgComputePipelineHandle>,
+    ) -> SimpleRenderPass<'rg, rg::RgComputePipelineHandle> {
+        let [simple_tiles, complex_tiles] = &mut self.tiles;
+
+        pass.write(&mut self.dispatch_args)
+            .write(simple_tiles)
+            .write(complex_tiles)
+    }
+
+    /// Binds the list of `class` after the other bindings of `pass`, and runs it with one
+    /// group of `TILE_LIST_TILE_SIZE`² threads per tile in the list.
+    pub fn dispatch(
+        &self,
+        pass: SimpleRenderPass<'_, rg::RgComputePipelineHandle>,
+        class: TileClass,
+    ) {
+        pass.read(&self.tiles[class as usize])
+            .dispatch_indirect(&self.dispatch_args, class.dispatch_args_offset());
+    }
+}
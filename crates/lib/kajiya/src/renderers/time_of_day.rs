@@ -0,0 +1,187 @@
+//! Animates the sun and the sky over the course of a day, along a curve of keys.
+//!
+//! While enabled, the sun direction of `WorldFrameDesc` is overridden, and so are
+//! `WorldRenderer::sun_color_multiplier`, `sky_ambient`, and the turbidity of the atmosphere.
+//! How much they change from frame to frame is reported to the temporal filters of the GI
+//! through `WorldRenderer::notify_lighting_change`, so that they don't smear the bounce
+//! light of the previous sun position around for seconds.
+
+use glam::Vec3;
+
+/// The sun and sky at an hour of the day.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeOfDayKey {
+    /// Hours since midnight, from zero to 24.
+    pub hour: f32,
+
+    /// Of the sun above the horizon; negative below it.
+    pub sun_elevation_degrees: f32,
+
+    /// Of the sun around the vertical axis, from +Z towards +X.
+    pub sun_azimuth_degrees: f32,
+
+    pub sun_color_multiplier: Vec3,
+    pub sky_ambient: Vec3,
+
+    /// See `AtmosphereParams::turbidity`. Changing it recomputes the atmosphere's lookup
+    /// tables, so animating it costs more than the other parameters.
+    pub turbidity: f32,
+}
+
+/// The lighting a `TimeOfDay` evaluates to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeOfDayLighting {
+    pub towards_sun: Vec3,
+    pub sun_color_multiplier: Vec3,
+    pub sky_ambient: Vec3,
+    pub turbidity: f32,
+}
+
+pub struct TimeOfDay {
+    pub enabled: bool,
+
+    /// Hours since midnight.
+    pub hour: f32,
+
+    /// Hours which pass per second; zero holds the time.
+    pub hours_per_second: f32,
+
+    /// In any order; the curve wraps around midnight. Without keys, nothing is animated.
+    pub keys: Vec<TimeOfDayKey>,
+
+    /// How much of the GI history to drop per degree of sun movement, or per percent of
+    /// change in the brightness of the sun or sky, in each frame.
+    pub history_desaturation: f32,
+
+    // Of the latest `advance`
+    lighting: Option<TimeOfDayLighting>,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        let key =
+            |hour, sun_elevation_degrees, sun_azimuth_degrees, sun: f32, turbidity| TimeOfDayKey {
+                hour,
+                sun_elevation_degrees,
+                sun_azimuth_degrees,
+                sun_color_multiplier: Vec3::splat(sun),
+                sky_ambient: Vec3::ZERO,
+                turbidity,
+            };
+
+        Self {
+            enabled: false,
+            hour: 12.0,
+            hours_per_second: 0.0,
+            keys: vec![
+                key(0.0, -40.0, 0.0, 0.0, 1.0),
+                key(6.0, 0.0, 90.0, 1.0, 2.0),
+                key(12.0, 60.0, 180.0, 1.0, 1.0),
+                key(18.0, 0.0, 270.0, 1.0, 2.0),
+            ],
+            history_desaturation: 0.2,
+            lighting: None,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Advances the time by `delta_time_seconds`, and returns the lighting at the new time,
+    /// along with how much it changed since the previous call, from zero to one.
+    ///
+    /// Returns `None` if disabled, or without keys.
+    pub fn advance(&mut self, delta_time_seconds: f32) -> Option<(TimeOfDayLighting, f32)> {
+        if !self.enabled {
+            self.lighting = None;
+            return None;
+        }
+
+        self.hour = (self.hour + delta_time_seconds * self.hours_per_second).rem_euclid(24.0);
+
+        let prev_lighting = self.lighting.take();
+        let lighting = self.evaluate(self.hour)?;
+        let change = prev_lighting.map_or(0.0, |prev| lighting_change(&prev, &lighting))
+            * self.history_desaturation;
+
+        self.lighting = Some(lighting);
+        Some((lighting, change.clamp(0.0, 1.0)))
+    }
+
+    /// As of the latest `advance`, if enabled then.
+    pub fn lighting(&self) -> Option<TimeOfDayLighting> {
+        self.lighting
+    }
+
+    /// The lighting at `hour`, interpolated between the keys around it.
+    pub fn evaluate(&self, hour: f32) -> Option<TimeOfDayLighting> {
+        if self.keys.is_empty() {
+            return None;
+        }
+
+        let mut keys: Vec<&TimeOfDayKey> = self.keys.iter().collect();
+        keys.sort_by(|a, b| {
+            a.hour
+                .partial_cmp(&b.hour)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let hour = hour.rem_euclid(24.0);
+
+        // The last key at or before `hour`, wrapping to the last of the previous day.
+        let next_idx = keys.partition_point(|key| key.hour <= hour);
+        let prev = keys[(next_idx + keys.len() - 1) % keys.len()];
+        let next = keys[next_idx % keys.len()];
+
+        let span = (next.hour - prev.hour).rem_euclid(24.0);
+        let t = if span > 0.0 {
+            (hour - prev.hour).rem_euclid(24.0) / span
+        } else {
+            0.0
+        };
+
+        // Along the shorter way around
+        let azimuth_delta =
+            (next.sun_azimuth_degrees - prev.sun_azimuth_degrees + 180.0).rem_euclid(360.0) - 180.0;
+
+        let elevation =
+            lerp(prev.sun_elevation_degrees, next.sun_elevation_degrees, t).to_radians();
+        let azimuth = (prev.sun_azimuth_degrees + azimuth_delta * t).to_radians();
+
+        Some(TimeOfDayLighting {
+            towards_sun: Vec3::new(
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                elevation.cos() * azimuth.cos(),
+            ),
+            sun_color_multiplier: prev.sun_color_multiplier.lerp(next.sun_color_multiplier, t),
+            sky_ambient: prev.sky_ambient.lerp(next.sky_ambient, t),
+            turbidity: lerp(prev.turbidity, next.turbidity, t),
+        })
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// In degrees of sun movement, or percents of brightness change, whichever is larger.
+fn lighting_change(prev: &TimeOfDayLighting, current: &TimeOfDayLighting) -> f32 {
+    let relative_change = |prev: Vec3, current: Vec3| {
+        let (prev, current) = (prev.max_element(), current.max_element());
+        (current - prev).abs() / prev.max(current).max(1e-5) * 100.0
+    };
+
+    let sun_degrees = prev
+        .towards_sun
+        .dot(current.towards_sun)
+        .clamp(-1.0, 1.0)
+        .acos()
+        .to_degrees();
+
+    sun_degrees
+        .max(relative_change(
+            prev.sun_color_multiplier,
+            current.sun_color_multiplier,
+        ))
+        .max(relative_change(prev.sky_ambient, current.sky_ambient))
+}
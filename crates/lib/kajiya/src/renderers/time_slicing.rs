@@ -0,0 +1,198 @@
+//! Spreads periodic work which doesn't have to finish within a frame over several frames,
+//! within a per-frame budget of GPU time, rather than doing it all at once in a spike.
+//!
+//! Work comes in items of a few kinds, such as the faces of environment probes. What each
+//! item costs is learned from the GPU timings of the passes recording it, which the profiler
+//! reports a few frames later; until a kind has been measured, one item of it runs per frame.
+
+use std::collections::{HashMap, VecDeque};
+
+use kajiya_backend::gpu_profiler;
+use kajiya_rg as rg;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeSlicedWork {
+    /// A light probe: six views, and the projection to spherical harmonics.
+    LightProbeBake = 0,
+    /// A face of an environment probe.
+    EnvironmentProbeFace = 1,
+    /// A mip of the specular pre-filtered sky cube.
+    SkyPrefilterMip = 2,
+}
+
+const WORK_KIND_COUNT: usize = 3;
+
+/// A kind of work which got nothing for this many frames in a row gets one item anyway,
+/// so that kinds costing more than the budget still make progress.
+const MAX_STARVED_FRAMES: u32 = 8;
+
+/// Frames whose timings are still awaited; older ones are assumed lost.
+const MAX_FRAMES_IN_FLIGHT: usize = 8;
+
+/// How quickly the cost estimates follow the measurements.
+const COST_ESTIMATE_BLEND: f32 = 0.25;
+
+// The passes which recorded some items of work in a frame.
+struct WorkSlice {
+    work: TimeSlicedWork,
+    item_count: usize,
+    first_pass: usize,
+    pass_names: Vec<String>,
+}
+
+pub struct TimeSlicer {
+    /// When disabled, every kind runs as many items per frame as its own limit allows,
+    /// such as `EnvironmentProbes::faces_per_frame`.
+    pub enabled: bool,
+
+    /// GPU time per frame for all the time-sliced work together, in microseconds.
+    pub budget_us: f32,
+
+    // Per item, by `TimeSlicedWork`
+    cost_estimates_us: [Option<f32>; WORK_KIND_COUNT],
+    starved_frames: [u32; WORK_KIND_COUNT],
+    spent_us: f32,
+
+    frame_slices: Vec<WorkSlice>,
+    // Oldest first
+    frames_in_flight: VecDeque<Vec<WorkSlice>>,
+}
+
+impl Default for TimeSlicer {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            budget_us: 2000.0,
+            cost_estimates_us: Default::default(),
+            starved_frames: Default::default(),
+            spent_us: 0.0,
+            frame_slices: Default::default(),
+            frames_in_flight: Default::default(),
+        }
+    }
+}
+
+impl TimeSlicer {
+    /// The learned GPU time per item of `work`, in microseconds, if measured yet.
+    pub fn cost_estimate_us(&self, work: TimeSlicedWork) -> Option<f32> {
+        self.cost_estimates_us[work as usize]
+    }
+
+    /// Starts handing out the budget of a new frame, and learns from the timings of the
+    /// previous ones which have come in.
+    pub(crate) fn begin_frame(&mut self) {
+        self.spent_us = 0.0;
+
+        let slices = std::mem::take(&mut self.frame_slices);
+        if !slices.is_empty() {
+            self.frames_in_flight.push_back(slices);
+        }
+
+        while self.frames_in_flight.len() > MAX_FRAMES_IN_FLIGHT {
+            self.frames_in_flight.pop_front();
+        }
+
+        self.learn_costs();
+    }
+
+    /// How many of the `pending` items of `work` to run this frame.
+    pub(crate) fn grant(&mut self, work: TimeSlicedWork, pending: usize) -> usize {
+        if pending == 0 || !self.enabled {
+            return pending;
+        }
+
+        let kind = work as usize;
+        let cost = self.cost_estimates_us[kind];
+
+        let mut granted = match cost {
+            // Unmeasured so far; run one to find out.
+            None => 1,
+            Some(cost) => {
+                let affordable = (self.budget_us - self.spent_us) / cost.max(1.0);
+                (affordable.max(0.0) as usize).min(pending)
+            }
+        };
+
+        if granted == 0 && self.starved_frames[kind] >= MAX_STARVED_FRAMES {
+            granted = 1;
+        }
+
+        if granted == 0 {
+            self.starved_frames[kind] += 1;
+        } else {
+            self.starved_frames[kind] = 0;
+        }
+
+        self.spent_us += granted as f32 * cost.unwrap_or(0.0);
+        granted
+    }
+
+    /// Attributes the passes added since `first_pass` to `item_count` items of `work`,
+    /// for measuring their cost.
+    pub(crate) fn record_slice(
+        &mut self,
+        rg: &rg::RenderGraph,
+        work: TimeSlicedWork,
+        first_pass: usize,
+        item_count: usize,
+    ) {
+        if item_count == 0 || first_pass == rg.pass_count() {
+            return;
+        }
+
+        self.frame_slices.push(WorkSlice {
+            work,
+            item_count,
+            first_pass,
+            pass_names: (first_pass..rg.pass_count())
+                .map(|pass_idx| rg.pass_name(pass_idx).to_owned())
+                .collect(),
+        });
+    }
+
+    // The profiler only keeps the timings of the latest frame it has heard back about, which
+    // isn't tied to a frame index; that's whichever frame in flight has the same passes at the
+    // same indices. Frames with the same structure are as good as one another.
+    fn learn_costs(&mut self) {
+        if self.frames_in_flight.is_empty() {
+            return;
+        }
+
+        let timings: HashMap<u64, (String, f64)> = gpu_profiler::get_stats()
+            .get_ordered()
+            .into_iter()
+            .map(|(scope, ms)| (scope.id, (scope.name, ms)))
+            .collect();
+
+        let matches = |slice: &WorkSlice| {
+            slice.pass_names.iter().enumerate().all(|(i, name)| {
+                timings
+                    .get(&((slice.first_pass + i) as u64))
+                    .map_or(false, |(timed_name, _)| timed_name == name)
+            })
+        };
+
+        let matched = match self
+            .frames_in_flight
+            .iter()
+            .position(|slices| slices.iter().all(matches))
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        let slices = self.frames_in_flight.drain(..=matched).last().unwrap();
+
+        for slice in slices {
+            let total_ms: f64 = (0..slice.pass_names.len())
+                .map(|i| timings[&((slice.first_pass + i) as u64)].1)
+                .sum();
+            let per_item_us = (total_ms * 1000.0) as f32 / slice.item_count as f32;
+
+            let estimate = &mut self.cost_estimates_us[slice.work as usize];
+            *estimate = Some(estimate.map_or(per_item_us, |prev| {
+                prev + (per_item_us - prev) * COST_ESTIMATE_BLEND
+            }));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use glam::Vec3;
+
+use crate::world_renderer::TriangleLight;
+
+// Must match `TriangleLightPacked` in `lights/packed.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct GpuTriangleLight {
+    light: TriangleLight,
+    alias_prob: f32,
+    alias_idx: u32,
+    selection_pmf: f32,
+    pad: u32,
+}
+
+/// Packs world-space triangle lights together with an alias table, which lets shaders
+/// pick one with probability proportional to its power in constant time
+/// (`select_triangle_light` in `lights/triangle_selection.hlsl`).
+pub(crate) fn build_gpu_triangle_lights(lights: Vec<TriangleLight>) -> Vec<GpuTriangleLight> {
+    let light_count = lights.len();
+    let powers: Vec<f32> = lights.iter().map(triangle_light_power).collect();
+    let total_power: f32 = powers.iter().sum();
+
+    // Selection probabilities scaled by the light count, so that the average is 1.
+    // Falls back to uniform selection if nothing emits any light.
+    let weights: Vec<f32> = if total_power > 0.0 {
+        powers
+            .iter()
+            .map(|power| power * light_count as f32 / total_power)
+            .collect()
+    } else {
+        vec![1.0; light_count]
+    };
+
+    // Vose's alias method
+    let mut alias_prob = weights.clone();
+    let mut alias_idx: Vec<u32> = (0..light_count as u32).collect();
+    let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+        (0..light_count).partition(|&i| weights[i] < 1.0);
+
+    while let (Some(s), Some(l)) = (small.last().copied(), large.last().copied()) {
+        small.pop();
+        alias_idx[s] = l as u32;
+        alias_prob[l] -= 1.0 - alias_prob[s];
+
+        if alias_prob[l] < 1.0 {
+            large.pop();
+            small.push(l);
+        }
+    }
+
+    // Whatever remains is only off from 1.0 due to rounding
+    for i in small.into_iter().chain(large) {
+        alias_prob[i] = 1.0;
+    }
+
+    lights
+        .into_iter()
+        .enumerate()
+        .map(|(i, light)| GpuTriangleLight {
+            light,
+            alias_prob: alias_prob[i],
+            alias_idx: alias_idx[i],
+            selection_pmf: weights[i] / light_count as f32,
+            pad: 0,
+        })
+        .collect()
+}
+
+/// Emitted luminance times area; what the light selection is proportional to.
+fn triangle_light_power(light: &TriangleLight) -> f32 {
+    let [v0, v1, v2] = light.verts.map(Vec3::from);
+    let area = 0.5 * (v1 - v0).cross(v2 - v0).length();
+    let luminance = Vec3::from(light.radiance).dot(Vec3::new(0.2126, 0.7152, 0.0722));
+
+    luminance.max(0.0) * area
+}
@@ -0,0 +1,169 @@
+use glam::Vec3;
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::ShaderSource},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{light_clusters::LightClusters, GbufferDepth, PingPongTemporalResource};
+
+/// Height fog, lit by the sun, the sky, and local lights, with volumetric shadows.
+/// Of the punctual lights, each froxel is lit by the ones in its light cluster.
+///
+/// The fog is evaluated in a frustum-aligned voxel grid ("froxels"), and applied to opaque surfaces,
+/// and to particles. Transparent meshes don't get fogged.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FogParams {
+    /// Extinction coefficient per meter, below `base_height`. Zero disables the fog.
+    pub density: f32,
+
+    /// Fraction of the extinction which is scattering rather than absorption.
+    pub albedo: Vec3,
+
+    /// Henyey-Greenstein asymmetry. Positive values scatter light forward,
+    /// making the fog glow around light sources which it's lit by from behind.
+    pub anisotropy: f32,
+
+    /// Exponential falloff of the density per meter above `base_height`.
+    pub height_falloff: f32,
+    pub base_height: f32,
+
+    /// View-space depth up to which the fog is evaluated.
+    pub max_distance: f32,
+}
+
+impl Default for FogParams {
+    fn default() -> Self {
+        Self {
+            density: 0.0,
+            albedo: Vec3::ONE,
+            anisotropy: 0.5,
+            height_falloff: 0.1,
+            base_height: 0.0,
+            max_distance: 128.0,
+        }
+    }
+}
+
+// Must match `FogConstants` in `fog_common.hlsl`
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct GpuFogConstants {
+    albedo: [f32; 3],
+    anisotropy: f32,
+
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    max_distance: f32,
+
+    froxel_dims: [u32; 3],
+    near_distance: f32,
+}
+
+const FROXEL_SIZE_PX: u32 = 8;
+const FROXEL_SLICE_COUNT: u32 = 64;
+const FROXEL_NEAR_DISTANCE: f32 = 0.25;
+
+/// The fog in front of each froxel, for fogging what's drawn after the opaque surfaces.
+pub struct IntegratedFog {
+    /// Light scattered towards the eye in RGB, and transmittance in alpha; see `volumetric_fog/apply.hlsl`.
+    pub volume: rg::Handle<Image>,
+    pub(crate) constants: GpuFogConstants,
+}
+
+pub struct VolumetricFogRenderer {
+    scattering: PingPongTemporalResource,
+}
+
+impl Default for VolumetricFogRenderer {
+    fn default() -> Self {
+        Self {
+            scattering: PingPongTemporalResource::new("volumetric_fog.scattering"),
+        }
+    }
+}
+
+impl VolumetricFogRenderer {
+    /// Fogs up `output`, which is the lit scene.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        params: &FogParams,
+        gbuffer_depth: &GbufferDepth,
+        convolved_sky_cube: &rg::Handle<Image>,
+        light_clusters: &LightClusters,
+        bindless_descriptor_set: vk::DescriptorSet,
+        tlas: &rg::Handle<RayTracingAcceleration>,
+        output: &mut rg::Handle<Image>,
+    ) -> IntegratedFog {
+        let extent = gbuffer_depth.depth.desc().extent;
+        let froxel_dims = [
+            (extent[0] + FROXEL_SIZE_PX - 1) / FROXEL_SIZE_PX,
+            (extent[1] + FROXEL_SIZE_PX - 1) / FROXEL_SIZE_PX,
+            FROXEL_SLICE_COUNT,
+        ];
+
+        let constants = GpuFogConstants {
+            albedo: params.albedo.into(),
+            anisotropy: params.anisotropy.clamp(-0.99, 0.99),
+            density: params.density,
+            height_falloff: params.height_falloff.max(0.0),
+            base_height: params.base_height,
+            max_distance: params.max_distance.max(FROXEL_NEAR_DISTANCE * 2.0),
+            froxel_dims,
+            near_distance: FROXEL_NEAR_DISTANCE,
+        };
+
+        let froxel_desc = ImageDesc::new_3d(vk::Format::R16G16B16A16_SFLOAT, froxel_dims)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE);
+
+        let (mut scattering_tex, prev_scattering_tex) =
+            self.scattering.get_output_and_history(rg, froxel_desc);
+
+        SimpleRenderPass::new_rt(
+            rg.add_pass("fog inject"),
+            ShaderSource::hlsl("/shaders/volumetric_fog/inject.rgen.hlsl"),
+            [
+                // Duplicated because `rt.hlsl` hardcodes miss index to 1
+                ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+            ],
+            super::rt_shadow_hit_groups(),
+        )
+        .read(&prev_scattering_tex)
+        .read(convolved_sky_cube)
+        .write(&mut scattering_tex)
+        .read(&light_clusters.lists)
+        .constants((constants, light_clusters.constants))
+        .raw_descriptor_set(1, bindless_descriptor_set)
+        .trace_rays(tlas, froxel_dims);
+
+        let mut integrated_tex = rg.create(froxel_desc);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("fog integrate"),
+            "/shaders/volumetric_fog/integrate.hlsl",
+        )
+        .read(&scattering_tex)
+        .write(&mut integrated_tex)
+        .constants(constants)
+        .dispatch([froxel_dims[0], froxel_dims[1], 1]);
+
+        SimpleRenderPass::new_compute(
+            rg.add_pass("fog apply"),
+            "/shaders/volumetric_fog/apply.hlsl",
+        )
+        .read(&integrated_tex)
+        .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+        .write(output)
+        .constants((output.desc().extent_inv_extent_2d(), constants))
+        .dispatch(output.desc().extent);
+
+        IntegratedFog {
+            volume: integrated_tex,
+            constants,
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use glam::{Vec2, Vec3};
+use kajiya_backend::{
+    ash::vk,
+    vulkan::{image::*, ray_tracing::RayTracingAcceleration, shader::*},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+use super::{
+    ircache::IrcacheRenderState,
+    planar_reflections::{PlanarReflection, PlanarReflectionHandle},
+    GbufferDepth,
+};
+
+/// Matches `WATER_WAVE_COUNT` in `water/water_common.hlsl`.
+pub const WATER_WAVE_COUNT: usize = 4;
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct WaterSurfaceHandle(pub usize);
+
+/// A sine wave traveling across the surface.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WaterWave {
+    /// Of travel, in the world XZ plane. Normalized when rendering.
+    pub direction: Vec2,
+    pub wavelength: f32,
+
+    /// Height of the crests above the mean level.
+    pub amplitude: f32,
+
+    /// Of the crests, in world units per second.
+    pub speed: f32,
+}
+
+impl WaterWave {
+    pub const NONE: Self = Self {
+        direction: Vec2::X,
+        wavelength: 1.0,
+        amplitude: 0.0,
+        speed: 0.0,
+    };
+}
+
+/// A horizontal, rectangular body of water.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WaterSurface {
+    /// Of the surface at its mean level.
+    pub center: Vec3,
+
+    /// Along world X and Z.
+    pub half_extent: Vec2,
+
+    /// Summed up for the displacement and normals of the surface.
+    pub waves: [WaterWave; WATER_WAVE_COUNT],
+
+    /// Of the light refracted through the water, per world unit it travels under the surface,
+    /// per color channel. What's absorbed is replaced by `scatter_color`.
+    pub absorption: [f32; 3],
+
+    /// Reflectance of deep water, lit by the sky and the sun.
+    pub scatter_color: [f32; 3],
+
+    pub roughness: f32,
+
+    /// How far the waves shift the refracted scene, in screen units.
+    pub refraction_strength: f32,
+
+    /// Reflects the view of this planar reflection, whose plane should be at the mean level.
+    /// Otherwise, reflections are ray traced, or sampled from the sky without ray tracing.
+    pub planar_reflection: Option<PlanarReflectionHandle>,
+}
+
+impl Default for WaterSurface {
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            half_extent: Vec2::splat(10.0),
+            waves: [
+                WaterWave {
+                    direction: Vec2::new(1.0, 0.3),
+                    wavelength: 2.3,
+                    amplitude: 0.02,
+                    speed: 0.9,
+                },
+                WaterWave {
+                    direction: Vec2::new(-0.4, 1.0),
+                    wavelength: 1.1,
+                    amplitude: 0.01,
+                    speed: 0.6,
+                },
+                WaterWave {
+                    direction: Vec2::new(0.8, -0.7),
+                    wavelength: 0.45,
+                    amplitude: 0.004,
+                    speed: 0.4,
+                },
+                WaterWave {
+                    direction: Vec2::new(-1.0, -0.2),
+                    wavelength: 0.2,
+                    amplitude: 0.0015,
+                    speed: 0.25,
+                },
+            ],
+            absorption: [0.45, 0.09, 0.05],
+            scatter_color: [0.0, 0.03, 0.04],
+            roughness: 0.02,
+            refraction_strength: 0.05,
+            planar_reflection: None,
+        }
+    }
+}
+
+// Must match `WaterConstants` in `water/water_common.hlsl`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuWaterConstants {
+    output_tex_size: [f32; 4],
+
+    center: [f32; 3],
+    max_amplitude: f32,
+
+    half_extent: [f32; 2],
+    roughness: f32,
+    refraction_strength: f32,
+
+    absorption: [f32; 4],
+    scatter_color: [f32; 4],
+
+    // Wave vector, amplitude, and angular frequency
+    waves: [[f32; 4]; WATER_WAVE_COUNT],
+
+    time: f32,
+    has_planar_reflection: u32,
+    planar_reflection_distortion: f32,
+    pad: u32,
+}
+
+/// Water surfaces, with waves, refraction, absorption, and reflections, drawn over the lit scene.
+///
+/// The surfaces are ray marched per pixel against the depth buffer, after lighting,
+/// and before fog and transparent meshes. Only seen from above the water, and neither
+/// in the depth buffer, nor in the ray traced scene; transparent meshes and particles
+/// under the water draw over it.
+pub struct WaterSurfaces {
+    pub enabled: bool,
+
+    surfaces: Vec<(WaterSurfaceHandle, WaterSurface)>,
+    next_handle: usize,
+
+    time: f32,
+}
+
+impl Default for WaterSurfaces {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            surfaces: Default::default(),
+            next_handle: 0,
+            time: 0.0,
+        }
+    }
+}
+
+impl WaterSurfaces {
+    pub fn add(&mut self, surface: WaterSurface) -> WaterSurfaceHandle {
+        let handle = WaterSurfaceHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.surfaces.push((handle, surface));
+        handle
+    }
+
+    pub fn remove(&mut self, surface: WaterSurfaceHandle) {
+        let index = self
+            .surfaces
+            .iter()
+            .position(|(handle, _)| *handle == surface)
+            .expect("no such water surface");
+        self.surfaces.swap_remove(index);
+    }
+
+    pub fn get(&self, surface: WaterSurfaceHandle) -> &WaterSurface {
+        self.surfaces
+            .iter()
+            .find(|(handle, _)| *handle == surface)
+            .map(|(_, surface)| surface)
+            .expect("no such water surface")
+    }
+
+    pub fn get_mut(&mut self, surface: WaterSurfaceHandle) -> &mut WaterSurface {
+        self.surfaces
+            .iter_mut()
+            .find(|(handle, _)| *handle == surface)
+            .map(|(_, surface)| surface)
+            .expect("no such water surface")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WaterSurfaceHandle, &WaterSurface)> {
+        self.surfaces
+            .iter()
+            .map(|(handle, surface)| (*handle, surface))
+    }
+
+    pub fn len(&self) -> usize {
+        self.surfaces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.surfaces.is_empty()
+    }
+
+    /// Draws the surfaces over `lit`, one pass each, and returns the result.
+    ///
+    /// `planar_reflections` are the ones rendered this frame; surfaces whose planar reflection
+    /// isn't among them fall back to the other reflections.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        dt: f32,
+        gbuffer_depth: &GbufferDepth,
+        lit: rg::Handle<Image>,
+        responsive_mask: &mut rg::Handle<Image>,
+        planar_reflections: &[(PlanarReflectionHandle, PlanarReflection, rg::Handle<Image>)],
+        convolved_sky_cube: &rg::Handle<Image>,
+        prefiltered_sky_cube: &rg::Handle<Image>,
+        ircache: &mut IrcacheRenderState,
+        bindless_descriptor_set: vk::DescriptorSet,
+        tlas: Option<&rg::Handle<RayTracingAcceleration>>,
+    ) -> rg::Handle<Image> {
+        // Wrapped, so that the phases keep their precision over long sessions.
+        self.time = (self.time + dt) % 3600.0;
+
+        if !self.enabled || self.surfaces.is_empty() {
+            return lit;
+        }
+
+        // Sampled without a planar reflection; only there for the binding.
+        let reflection_placeholder =
+            rg.create(ImageDesc::new_2d(vk::Format::R16G16B16A16_SFLOAT, [1, 1]));
+
+        let mut output = lit;
+
+        for (_, surface) in &self.surfaces {
+            let planar_reflection = surface.planar_reflection.and_then(|handle| {
+                planar_reflections
+                    .iter()
+                    .find(|(rendered, _, _)| *rendered == handle)
+            });
+
+            let constants = self.gpu_constants(
+                surface,
+                output.desc().extent_inv_extent_2d(),
+                planar_reflection.map(|(_, reflection, _)| reflection),
+            );
+            let reflection_image =
+                planar_reflection.map_or(&reflection_placeholder, |(_, _, image)| image);
+
+            let mut surface_output = rg.create(output.desc().usage(vk::ImageUsageFlags::empty()));
+
+            match tlas {
+                Some(tlas) => {
+                    SimpleRenderPass::new_rt(
+                        rg.add_pass("water"),
+                        ShaderSource::hlsl("/shaders/water/water.rgen.hlsl"),
+                        [
+                            ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
+                            ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
+                        ],
+                        super::rt_hit_groups(),
+                    )
+                    .read(&output)
+                    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+                    .read(reflection_image)
+                    .read(convolved_sky_cube)
+                    .read(prefiltered_sky_cube)
+                    .write(&mut surface_output)
+                    .write(responsive_mask)
+                    .bind_mut(ircache)
+                    .constants(constants)
+                    .raw_descriptor_set(1, bindless_descriptor_set)
+                    .trace_rays(tlas, surface_output.desc().extent);
+                }
+                None => {
+                    SimpleRenderPass::new_compute(
+                        rg.add_pass("water"),
+                        "/shaders/water/water.hlsl",
+                    )
+                    .read(&output)
+                    .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
+                    .read(reflection_image)
+                    .read(convolved_sky_cube)
+                    .read(prefiltered_sky_cube)
+                    .write(&mut surface_output)
+                    .write(responsive_mask)
+                    .constants(constants)
+                    .dispatch(surface_output.desc().extent);
+                }
+            }
+
+            output = surface_output;
+        }
+
+        output
+    }
+
+    fn gpu_constants(
+        &self,
+        surface: &WaterSurface,
+        output_tex_size: [f32; 4],
+        planar_reflection: Option<&PlanarReflection>,
+    ) -> GpuWaterConstants {
+        let waves = surface.waves.map(|wave| {
+            let wavenumber = std::f32::consts::TAU / wave.wavelength.max(1e-3);
+            let wave_vector = wave.direction.try_normalize().unwrap_or(Vec2::X) * wavenumber;
+            [
+                wave_vector.x,
+                wave_vector.y,
+                wave.amplitude.max(0.0),
+                wave.speed * wavenumber,
+            ]
+        });
+
+        let [ar, ag, ab] = surface.absorption;
+        let [sr, sg, sb] = surface.scatter_color;
+
+        GpuWaterConstants {
+            output_tex_size,
+            center: surface.center.to_array(),
+            max_amplitude: waves.iter().map(|wave| wave[2]).sum(),
+            half_extent: surface.half_extent.max(Vec2::ZERO).to_array(),
+            roughness: surface.roughness.clamp(0.0, 1.0),
+            refraction_strength: surface.refraction_strength,
+            absorption: [ar.max(0.0), ag.max(0.0), ab.max(0.0), 0.0],
+            scatter_color: [sr, sg, sb, 0.0],
+            waves,
+            time: self.time,
+            has_planar_reflection: planar_reflection.is_some() as u32,
+            planar_reflection_distortion: planar_reflection
+                .map_or(0.0, |reflection| reflection.normal_distortion),
+            pad: 0,
+        }
+    }
+}
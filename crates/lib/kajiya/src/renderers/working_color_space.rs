@@ -0,0 +1,90 @@
+use glam::Vec3;
+
+// Bradford-adapted from D65 to the ACES white point, as in the ACES CSC transforms.
+// Must match `working_primaries.hlsl`
+const REC709_TO_ACESCG: [[f32; 3]; 3] = [
+    [0.6130974, 0.3395231, 0.0473794],
+    [0.0701937, 0.9163539, 0.0134524],
+    [0.0206156, 0.1095698, 0.8698147],
+];
+
+const ACESCG_TO_REC709: [[f32; 3]; 3] = [
+    [1.7050510, -0.6217921, -0.0832589],
+    [-0.1302564, 1.1408047, -0.0105483],
+    [-0.0240033, -0.1289690, 1.1529723],
+];
+
+/// Primaries of the linear RGB which lighting is computed in.
+///
+/// Textures, materials, lights, the sky, and image-based lighting are all authored in
+/// linear Rec.709, and converted to the working space as they enter the renderer; the lit
+/// image is converted back to Rec.709 ahead of tonemapping. Lighting in ACEScg gives more
+/// saturated interreflections, and matches renders from offline ACES pipelines.
+///
+/// Everything before the output transform is in the working space, including HDR captures
+/// and image stats taken before tonemapping. Luminance-based heuristics, such as
+/// the exposure histogram and denoiser weights, keep using Rec.709 luminance weights.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WorkingColorSpace {
+    /// Rec.709 primaries, as used by sRGB.
+    LinearSrgb,
+
+    /// ACES AP1 primaries, with the ACES white point.
+    AcesCg,
+}
+
+impl Default for WorkingColorSpace {
+    fn default() -> Self {
+        Self::LinearSrgb
+    }
+}
+
+impl WorkingColorSpace {
+    /// Matches the `WORKING_COLOR_SPACE_*` defines in `working_primaries.hlsl`.
+    pub(crate) fn shader_index(self) -> u32 {
+        match self {
+            Self::LinearSrgb => 0,
+            Self::AcesCg => 1,
+        }
+    }
+
+    /// Converts a linear Rec.709 color to this space.
+    pub fn from_rec709(self, color: Vec3) -> Vec3 {
+        match self {
+            Self::LinearSrgb => color,
+            Self::AcesCg => mul(&REC709_TO_ACESCG, color),
+        }
+    }
+
+    /// Converts a color in this space to linear Rec.709. Colors outside of the Rec.709
+    /// gamut come out with negative components.
+    pub fn to_rec709(self, color: Vec3) -> Vec3 {
+        match self {
+            Self::LinearSrgb => color,
+            Self::AcesCg => mul(&ACESCG_TO_REC709, color),
+        }
+    }
+}
+
+fn mul(rows: &[[f32; 3]; 3], color: Vec3) -> Vec3 {
+    Vec3::new(
+        Vec3::from(rows[0]).dot(color),
+        Vec3::from(rows[1]).dot(color),
+        Vec3::from(rows[2]).dot(color),
+    )
+}
+
+impl std::str::FromStr for WorkingColorSpace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srgb" | "linear-srgb" | "rec709" => Ok(Self::LinearSrgb),
+            "acescg" => Ok(Self::AcesCg),
+            _ => Err(anyhow::anyhow!(
+                "Unknown working color space {:?}; expected one of: srgb, acescg",
+                s
+            )),
+        }
+    }
+}
@@ -50,7 +50,7 @@ pub fn wrc_trace(
             ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+        super::rt_hit_groups(),
     )
     .read(sky_cube)
     .bind_mut(ircache)
@@ -84,7 +84,7 @@ impl WrcRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            super::rt_hit_groups(),
         )
         .bind(self)
         .read(sky_cube)
@@ -10,27 +10,53 @@ use log::{debug, error, info, trace, warn};
 #[derive(Default)]
 pub struct UiRenderer {
     pub ui_frame: Option<(UiRenderCallback, Arc<Image>)>,
+
+    /// Passes drawing over the result of `ui_frame`, in order. For UI backends
+    /// which record their draws through the render graph.
+    pub ui_graph_passes: Vec<UiRenderGraphCallback>,
 }
 
 pub type UiRenderCallback =
     Box<dyn (FnOnce(vk::CommandBuffer) -> Result<(), BackendError>) + 'static>;
 
+pub type UiRenderGraphCallback =
+    Box<dyn FnOnce(&mut rg::RenderGraph, &mut rg::Handle<Image>) + 'static>;
+
 impl UiRenderer {
-    pub fn prepare_render_graph(&mut self, rg: &mut rg::TemporalRenderGraph) -> rg::Handle<Image> {
-        self.render_ui(rg)
+    /// `extent` is that of the swapchain, which the UI is drawn at.
+    pub fn prepare_render_graph(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        extent: [u32; 2],
+    ) -> rg::Handle<Image> {
+        let mut ui_tex = self.render_ui(rg, extent);
+
+        for pass in self.ui_graph_passes.drain(..) {
+            pass(rg, &mut ui_tex);
+        }
+
+        ui_tex
     }
 
-    fn render_ui(&mut self, rg: &mut rg::RenderGraph) -> rg::Handle<Image> {
+    fn render_ui(&mut self, rg: &mut rg::RenderGraph, extent: [u32; 2]) -> rg::Handle<Image> {
         if let Some((ui_renderer, image)) = self.ui_frame.take() {
             let mut ui_tex = rg.import(image, AccessType::Nothing);
             let mut pass = rg.add_pass("ui");
 
             pass.raster(&mut ui_tex, AccessType::ColorAttachmentWrite);
-            pass.render(move |api| ui_renderer(api.cb.raw));
+            pass.render_local(move |api| ui_renderer(api.cb.raw));
 
             ui_tex
         } else {
-            let mut blank_img = rg.create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]));
+            // Graph passes need a full-size target to draw into.
+            let blank_extent = if self.ui_graph_passes.is_empty() {
+                [1, 1]
+            } else {
+                extent
+            };
+
+            let mut blank_img =
+                rg.create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, blank_extent));
             rg::imageops::clear_color(rg, &mut blank_img, [0.0f32; 4]);
             blank_img
         }
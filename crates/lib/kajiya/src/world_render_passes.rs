@@ -1,22 +1,456 @@
 use crate::{
     frame_desc::WorldFrameDesc,
     renderers::{
-        deferred::light_gbuffer, motion_blur::motion_blur, raster_meshes::*,
-        reference::reference_path_trace, shadows::trace_sun_shadow_mask, GbufferDepth,
+        contact_shadows::contact_shadows,
+        culling::cull_instances,
+        custom_passes::{CustomPassPoint, CustomPassResources},
+        ddgi::GiMode,
+        debug_view::{render_debug_view, DebugView},
+        deferred::light_gbuffer,
+        dof::{dof, DofParams},
+        environment_probes::{
+            cube_face_camera_matrices, EnvironmentProbeHandle, EnvironmentProbes,
+            ENVIRONMENT_PROBE_FACE_COUNT,
+        },
+        gbuffer_layout::GbufferLayout,
+        gi_resolution::{downsample_gi_inputs, upsample_gi_output},
+        hdr_capture::{HdrCaptureMetadata, HdrCaptureSource},
+        image_stats::SCENE_IMAGE_STATS,
+        light_clusters::assign_light_clusters,
+        light_probes::{create_bake_readback_buffer, project_cube_to_sh},
+        motion_blur::{motion_blur, MotionBlurParams},
+        planar_reflections::{
+            composite_planar_reflections, PlanarReflection, PlanarReflectionHandle,
+        },
+        raster_meshes::*,
+        reference::reference_path_trace,
+        rtao::AmbientOcclusionMode,
+        rtr::ReflectionQuality,
+        shadows::{trace_punctual_lighting, trace_rect_lighting, trace_sun_shadow_mask},
+        subsurface::subsurface_scattering,
+        time_slicing::TimeSlicedWork,
+        GbufferDepth,
     },
     world_renderer::{RenderDebugMode, WorldRenderer},
 };
-use kajiya_backend::{ash::vk, vulkan::image::*};
-use kajiya_rg::{self as rg, GetOrCreateTemporal};
+use glam::Vec4;
+use kajiya_backend::{ash::vk, vk_sync::AccessType, vulkan::image::*};
+use kajiya_rg::{self as rg, GetOrCreateTemporal, SimpleRenderPass};
+use rust_shaders_shared::camera::CameraMatrices;
+
+/// The sky, rendered once per frame, and shared by the standard path and the world views.
+pub(super) struct SkyCubes {
+    pub sky: rg::ReadOnlyHandle<Image>,
+    pub convolved: rg::Handle<Image>,
+    pub prefiltered: rg::Handle<Image>,
+}
 
 impl WorldRenderer {
+    pub(super) fn prepare_sky_cubes(&mut self, rg: &mut rg::TemporalRenderGraph) -> SkyCubes {
+        let sky = self.ibl.render(rg).unwrap_or_else(|| {
+            crate::renderers::sky::render_sky_cube(rg, self.bindless_descriptor_set).into()
+        });
+
+        let convolved = crate::renderers::sky::convolve_cube(rg, &sky);
+
+        // Kept across frames, with only some of its mips refreshed in each.
+        let mut prefiltered = rg
+            .get_or_create_temporal(
+                "sky.prefiltered",
+                crate::renderers::ibl::prefiltered_cube_desc()
+                    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE),
+            )
+            .unwrap();
+
+        let first_pass = rg.pass_count();
+        let mip_count = self.time_slicer.grant(
+            TimeSlicedWork::SkyPrefilterMip,
+            crate::renderers::ibl::PREFILTERED_CUBE_MIP_COUNT as usize,
+        );
+        let mips = self.sky_prefilter_cycle.take(mip_count);
+        crate::renderers::ibl::prefilter_specular_cube_mips(
+            rg,
+            &sky,
+            &mut prefiltered,
+            mips.iter().copied(),
+        );
+        self.time_slicer
+            .record_slice(rg, TimeSlicedWork::SkyPrefilterMip, first_pass, mips.len());
+
+        SkyCubes {
+            sky,
+            convolved,
+            prefiltered,
+        }
+    }
+
+    /// Captures this frame's environment probe faces, and returns cubes to light the main view
+    /// with in place of `sky_cubes`, if there's a captured probe; see `EnvironmentProbes`.
+    pub(super) fn prepare_environment_probes(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        frame_desc: &WorldFrameDesc,
+        sky_cubes: &SkyCubes,
+    ) -> Option<SkyCubes> {
+        let capture_count = self.time_slicer.grant(
+            TimeSlicedWork::EnvironmentProbeFace,
+            self.environment_probes.frame_capture_count(),
+        );
+        let captures = self.environment_probes.take_frame_captures(capture_count);
+        let pre_exposure = self.exposure_state().pre_mult;
+        let first_pass = rg.pass_count();
+
+        let mut imported = Vec::new();
+
+        for &(handle, face) in &captures {
+            rg.set_temporal_scope(Some(format!("environment_probe{}", handle.0)));
+
+            let probe = self.environment_probes.get(handle);
+            let camera_matrices = probe.face_camera_matrices(face);
+            let resolution = probe.resolution();
+
+            let lit = self.render_secondary_view(
+                rg,
+                frame_desc,
+                camera_matrices,
+                [resolution, resolution],
+                None,
+                sky_cubes,
+            );
+
+            let cube_idx = import_probe_cube(rg, &mut imported, &self.environment_probes, handle);
+            SimpleRenderPass::new_compute(
+                rg.add_pass("store probe face"),
+                "/shaders/ibl/store_probe_face.hlsl",
+            )
+            .read(&lit)
+            .write_view(
+                &mut imported[cube_idx].1,
+                ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+            )
+            .constants((resolution, face as u32, 1.0 / pre_exposure))
+            .dispatch([resolution, resolution, 1]);
+
+            self.environment_probes.get_mut(handle).captured_faces |= 1 << face;
+        }
+
+        self.time_slicer.record_slice(
+            rg,
+            TimeSlicedWork::EnvironmentProbeFace,
+            first_pass,
+            captures.len(),
+        );
+
+        rg.set_temporal_scope(None);
+        rg.set_view(0);
+
+        let environment_cubes = self
+            .environment_probes
+            .nearest_captured(frame_desc.camera_matrices.eye_position())
+            .map(|handle| {
+                let cube_idx =
+                    import_probe_cube(rg, &mut imported, &self.environment_probes, handle);
+                let cube = &imported[cube_idx].1;
+                let resolution = cube.desc().extent[0];
+
+                let mut exposed = rg.create(ImageDesc::new_cube(
+                    vk::Format::R16G16B16A16_SFLOAT,
+                    resolution,
+                ));
+
+                SimpleRenderPass::new_compute(
+                    rg.add_pass("expose probe"),
+                    "/shaders/ibl/scale_cube.hlsl",
+                )
+                .read_view(
+                    cube,
+                    ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+                )
+                .write_view(
+                    &mut exposed,
+                    ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+                )
+                .constants(pre_exposure)
+                .dispatch([resolution, resolution, 6]);
+
+                SkyCubes {
+                    sky: sky_cubes.sky.clone(),
+                    convolved: crate::renderers::sky::convolve_cube(rg, &exposed),
+                    prefiltered: crate::renderers::ibl::prefilter_specular_cube(rg, &exposed),
+                }
+            });
+
+        for (_, cube) in imported {
+            rg.export(
+                cube,
+                AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+            );
+        }
+
+        environment_cubes
+    }
+
+    /// Bakes this frame's queued light probes; see `LightProbes`.
+    pub(super) fn prepare_light_probe_bakes(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        frame_desc: &WorldFrameDesc,
+        sky_cubes: &SkyCubes,
+    ) {
+        let bake_count = self.time_slicer.grant(
+            TimeSlicedWork::LightProbeBake,
+            self.light_probes.frame_bake_count(),
+        );
+        let bakes = self.light_probes.take_frame_bakes(bake_count);
+        if bakes.is_empty() {
+            return;
+        }
+
+        let buffer = match create_bake_readback_buffer(&self.device, bakes.len()) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let mut readback_buf = rg.import(buffer.clone(), AccessType::Nothing);
+
+        let resolution = self.light_probes.bake_resolution.max(4);
+        let pre_exposure = self.exposure_state().pre_mult;
+        let first_pass = rg.pass_count();
+
+        for (idx, handle) in bakes.iter().enumerate() {
+            rg.set_temporal_scope(Some(format!("light_probe{}", handle.0)));
+
+            let position = self.light_probes.get(*handle).position;
+            let mut cube = rg.create(ImageDesc::new_cube(
+                vk::Format::R16G16B16A16_SFLOAT,
+                resolution,
+            ));
+
+            for face in 0..ENVIRONMENT_PROBE_FACE_COUNT {
+                let lit = self.render_secondary_view(
+                    rg,
+                    frame_desc,
+                    cube_face_camera_matrices(position, face),
+                    [resolution, resolution],
+                    None,
+                    sky_cubes,
+                );
+
+                SimpleRenderPass::new_compute(
+                    rg.add_pass("store probe face"),
+                    "/shaders/ibl/store_probe_face.hlsl",
+                )
+                .read(&lit)
+                .write_view(
+                    &mut cube,
+                    ImageViewDesc::builder().view_type(vk::ImageViewType::TYPE_2D_ARRAY),
+                )
+                .constants((resolution, face as u32, 1.0 / pre_exposure))
+                .dispatch([resolution, resolution, 1]);
+            }
+
+            rg.set_temporal_scope(None);
+            rg.set_view(0);
+            project_cube_to_sh(rg, &cube, &mut readback_buf, idx);
+        }
+
+        self.time_slicer
+            .record_slice(rg, TimeSlicedWork::LightProbeBake, first_pass, bakes.len());
+
+        self.light_probes
+            .record_bake_readback(buffer, bakes, self.frame_idx);
+    }
+
+    /// Renders the enabled world views into their outputs; see `WorldView`.
+    pub(super) fn prepare_render_graph_views(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        frame_desc: &WorldFrameDesc,
+        sky_cubes: &SkyCubes,
+    ) {
+        let views: Vec<_> = self
+            .views
+            .iter()
+            .filter(|(_, view)| view.enabled)
+            .map(|(handle, view)| {
+                (
+                    *handle,
+                    view.camera_matrices,
+                    view.clip_plane,
+                    view.output(),
+                )
+            })
+            .collect();
+
+        for (handle, camera_matrices, clip_plane, output_image) in views {
+            rg.set_temporal_scope(Some(format!("view{}", handle.0)));
+
+            let lit = self.render_secondary_view(
+                rg,
+                frame_desc,
+                camera_matrices,
+                output_image.desc.extent_2d(),
+                clip_plane,
+                sky_cubes,
+            );
+
+            // The pre-exposure is the main camera's, as the sky cubes are shared,
+            // so it's swapped for the view's own exposure in post.
+            let pre_mult = self.exposure_state().pre_mult;
+            let bindless_descriptor_set = self.bindless_descriptor_set;
+            let view = self.view_mut(handle);
+
+            let post_processed = view.post.render(
+                rg,
+                &lit,
+                bindless_descriptor_set,
+                (view.ev_shift + view.dynamic_exposure.ev_smoothed()).exp2() / pre_mult,
+                view.contrast,
+                &view.dynamic_exposure,
+            );
+
+            let mut output = rg.import(output_image, AccessType::Nothing);
+            SimpleRenderPass::new_compute(rg.add_pass("view output"), "/shaders/copy_color.hlsl")
+                .read(&post_processed)
+                .write(&mut output)
+                .dispatch(output.desc().extent);
+
+            rg.export(
+                output,
+                AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer,
+            );
+        }
+
+        rg.set_temporal_scope(None);
+        rg.set_view(0);
+    }
+
+    /// Renders the mirrored views of the planar reflections facing the main camera.
+    fn prepare_planar_reflections(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        frame_desc: &WorldFrameDesc,
+        sky_cubes: &SkyCubes,
+    ) -> Vec<(PlanarReflectionHandle, PlanarReflection, rg::Handle<Image>)> {
+        let reflections = self
+            .planar_reflections
+            .visible(frame_desc.camera_matrices.eye_position());
+        if reflections.is_empty() {
+            return Vec::new();
+        }
+
+        let extent = self
+            .planar_reflections
+            .view_extent(frame_desc.render_extent);
+
+        let rendered = reflections
+            .into_iter()
+            .map(|(handle, reflection)| {
+                rg.set_temporal_scope(Some(format!("planar_reflection{}", handle.0)));
+
+                let lit = self.render_secondary_view(
+                    rg,
+                    frame_desc,
+                    reflection.mirrored_camera_matrices(&frame_desc.camera_matrices),
+                    extent,
+                    Some(reflection.clip_plane()),
+                    sky_cubes,
+                );
+
+                (handle, reflection, lit)
+            })
+            .collect();
+
+        rg.set_temporal_scope(None);
+        rg.set_view(0);
+
+        rendered
+    }
+
+    /// Adds a view of `camera_matrices` to the graph, with its own frame constants, as view
+    /// `1..`, and shades it forward. Returns the lit, pre-exposed HDR color; the passes
+    /// added afterwards are still in the view, until `rg.set_view(0)`.
+    ///
+    /// Callers set a temporal scope for the view beforehand, so that nothing it renders
+    /// touches the main camera's history.
+    pub(super) fn render_secondary_view(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        frame_desc: &WorldFrameDesc,
+        camera_matrices: CameraMatrices,
+        extent: [u32; 2],
+        clip_plane: Option<Vec4>,
+        sky_cubes: &SkyCubes,
+    ) -> rg::Handle<Image> {
+        self.rendered_views
+            .push((camera_matrices, extent, clip_plane.unwrap_or(Vec4::ZERO)));
+        rg.set_view(self.rendered_views.len());
+
+        let view_desc = WorldFrameDesc {
+            camera_matrices,
+            render_extent: extent,
+            sun_direction: frame_desc.sun_direction,
+        };
+
+        let mut depth = rg.create(ImageDesc::new_2d(vk::Format::D32_SFLOAT, extent));
+        rg::imageops::clear_depth(rg, &mut depth);
+
+        let mut color = rg.create(ImageDesc::new_2d(
+            self.render_target_formats.color.format(),
+            extent,
+        ));
+
+        SimpleRenderPass::new_compute(rg.add_pass("view sky"), "/shaders/view_sky.hlsl")
+            .read(&sky_cubes.sky)
+            .write(&mut color)
+            .constants(color.desc().extent_inv_extent_2d())
+            .dispatch(color.desc().extent);
+
+        // Written by the forward pipeline for TAA, which views don't have.
+        let mut responsive_mask = rg.create(ImageDesc::new_2d(vk::Format::R8_UNORM, extent));
+
+        let mesh_data = RasterMeshesData {
+            meshes: self.meshes.as_slice(),
+            instances: self.instances.as_slice(),
+            vertex_buffer: self.vertex_buffer.lock().clone(),
+            bindless_descriptor_set: self.bindless_descriptor_set,
+            lod_selection: self.mesh_lod_selection(&view_desc),
+        };
+
+        raster_forward_opaque_meshes(
+            rg,
+            self.forward_transparent_render_pass.clone(),
+            &mut depth,
+            &mut color,
+            &mut responsive_mask,
+            &sky_cubes.convolved,
+            &sky_cubes.prefiltered,
+            mesh_data.clone(),
+        );
+
+        raster_transparent_meshes(
+            rg,
+            self.forward_transparent_render_pass.clone(),
+            &mut depth,
+            &mut color,
+            &mut responsive_mask,
+            &sky_cubes.convolved,
+            &sky_cubes.prefiltered,
+            mesh_data,
+        );
+
+        color
+    }
+
     pub(super) fn prepare_render_graph_standard(
         &mut self,
         rg: &mut rg::TemporalRenderGraph,
         frame_desc: &WorldFrameDesc,
+        sky_cubes: &SkyCubes,
     ) -> rg::Handle<Image> {
+        let planar_reflections = self.prepare_planar_reflections(rg, frame_desc, sky_cubes);
+
         let tlas = if rg.device().ray_tracing_enabled() {
-            Some(self.prepare_top_level_acceleration(rg))
+            self.prepare_top_level_acceleration(rg)
         } else {
             None
         };
@@ -32,17 +466,16 @@ impl WorldRenderer {
             )
             .unwrap();
 
-        let sky_cube = self
-            .ibl
-            .render(rg)
-            .unwrap_or_else(|| crate::renderers::sky::render_sky_cube(rg).into());
-
-        let convolved_sky_cube = crate::renderers::sky::convolve_cube(rg, &sky_cube);
+        let SkyCubes {
+            sky: sky_cube,
+            convolved: convolved_sky_cube,
+            prefiltered: prefiltered_sky_cube,
+        } = sky_cubes;
 
-        let (gbuffer_depth, velocity_img) = {
+        let (mut gbuffer_depth, velocity_img, hiz, instance_ids) = {
             let mut gbuffer_depth = {
                 let normal = rg.create(ImageDesc::new_2d(
-                    vk::Format::A2R10G10B10_UNORM_PACK32,
+                    self.render_target_formats.normals.format(),
                     frame_desc.render_extent,
                 ));
 
@@ -57,28 +490,105 @@ impl WorldRenderer {
                 ));
                 rg::imageops::clear_depth(rg, &mut depth_img);
 
-                GbufferDepth::new(normal, gbuffer, depth_img)
+                let mut gbuffer_depth = GbufferDepth::new(normal, gbuffer, depth_img);
+
+                if self.render_target_formats.gbuffer.material_id {
+                    gbuffer_depth.material_id = Some(rg.create(ImageDesc::new_2d(
+                        GbufferLayout::material_id_format(),
+                        frame_desc.render_extent,
+                    )));
+                }
+
+                gbuffer_depth
             };
 
             let mut velocity_img = rg.create(ImageDesc::new_2d(
-                vk::Format::R16G16B16A16_SFLOAT,
+                self.render_target_formats.gbuffer.velocity.format(),
                 frame_desc.render_extent,
             ));
 
+            let mut instance_ids = self
+                .reactive_mask
+                .instance_ids(rg, frame_desc.render_extent);
+            rg::imageops::clear_color(rg, &mut instance_ids.current, [0.0; 4]);
+
+            let mesh_data = RasterMeshesData {
+                meshes: self.meshes.as_slice(),
+                instances: self.instances.as_slice(),
+                vertex_buffer: self.vertex_buffer.lock().clone(),
+                bindless_descriptor_set: self.bindless_descriptor_set,
+                lod_selection: self.mesh_lod_selection(frame_desc),
+            };
+
+            // Holds the previous frame's depth until rebuilt after the G-buffer.
+            let (mut hiz, hiz_history_valid) =
+                self.hiz
+                    .prepare(rg, frame_desc.render_extent, self.frame_idx);
+
+            let culled_draws =
+                if self.use_gpu_driven_draws && rg.device().draw_indirect_count_enabled() {
+                    Some(cull_instances(
+                        rg,
+                        &mesh_data,
+                        &hiz,
+                        self.use_occlusion_culling && hiz_history_valid,
+                    ))
+                } else {
+                    None
+                };
+
+            if self.use_depth_prepass {
+                raster_depth_prepass(
+                    rg,
+                    self.depth_prepass_render_pass.clone(),
+                    &mut gbuffer_depth.depth,
+                    mesh_data.clone(),
+                    culled_draws.as_ref(),
+                );
+            }
+
             raster_meshes(
                 rg,
                 self.raster_simple_render_pass.clone(),
                 &mut gbuffer_depth,
                 &mut velocity_img,
-                RasterMeshesData {
-                    meshes: self.meshes.as_slice(),
-                    instances: self.instances.as_slice(),
-                    vertex_buffer: self.vertex_buffer.lock().clone(),
+                &mut instance_ids.current,
+                mesh_data,
+                culled_draws.as_ref(),
+                self.use_depth_prepass,
+            );
+
+            let decals: Vec<_> = self.decals.iter().map(|(_, decal)| *decal).collect();
+            crate::renderers::decals::apply_decals(
+                rg,
+                &mut gbuffer_depth,
+                &decals,
+                self.bindless_descriptor_set,
+            );
+
+            self.custom_passes.render(
+                CustomPassPoint::AfterGbuffer,
+                rg,
+                &mut CustomPassResources {
+                    gbuffer_depth: &mut gbuffer_depth,
+                    velocity: &velocity_img,
+                    color: None,
                     bindless_descriptor_set: self.bindless_descriptor_set,
                 },
             );
 
-            (gbuffer_depth, velocity_img)
+            self.picking.record_readback(
+                rg,
+                &instance_ids.current,
+                self.temporal_upscale_extent,
+                &self.instance_handles,
+                self.frame_idx,
+            );
+
+            self.hiz
+                .build(rg, &mut hiz, &gbuffer_depth.depth, self.frame_idx);
+
+            (gbuffer_depth, velocity_img, hiz, instance_ids)
         };
 
         let reprojection_map = crate::renderers::reprojection::calculate_reprojection_map(
@@ -87,13 +597,59 @@ impl WorldRenderer {
             &velocity_img,
         );
 
-        let ssgi_tex = self.ssgi.render(
-            rg,
-            &gbuffer_depth,
-            &reprojection_map,
-            &accum_img,
-            self.bindless_descriptor_set,
-        );
+        let reactive_mask = self
+            .reactive_mask
+            .render(rg, &instance_ids, &reprojection_map);
+
+        let ao_mode = if tlas.is_some() {
+            self.ao_mode
+        } else {
+            AmbientOcclusionMode::ScreenSpace
+        };
+
+        let screen_space_ao = (ao_mode != AmbientOcclusionMode::RayTraced).then(|| {
+            if self.use_gtao {
+                self.gtao.render(
+                    rg,
+                    &gbuffer_depth,
+                    &reprojection_map,
+                    self.bindless_descriptor_set,
+                    &self.bilateral_upsample,
+                )
+            } else {
+                self.ssgi.render(
+                    rg,
+                    &gbuffer_depth,
+                    &reprojection_map,
+                    &accum_img,
+                    self.bindless_descriptor_set,
+                    &self.bilateral_upsample,
+                )
+            }
+        });
+
+        let ray_traced_ao = tlas
+            .as_ref()
+            .filter(|_| ao_mode != AmbientOcclusionMode::ScreenSpace)
+            .map(|tlas| {
+                self.rtao.render(
+                    rg,
+                    &gbuffer_depth,
+                    &reprojection_map,
+                    tlas,
+                    self.bindless_descriptor_set,
+                )
+            });
+
+        let ssgi_tex = match (screen_space_ao, ray_traced_ao) {
+            (Some(screen_space), Some(ray_traced)) => {
+                self.rtao.blend(rg, &screen_space, &ray_traced)
+            }
+            (Some(ao), None) | (None, Some(ao)) => ao,
+            (None, None) => unreachable!(),
+        };
+        // Only GTAO has one, and the blend keeps it.
+        let ssgi_has_bent_normal = self.use_gtao && ao_mode != AmbientOcclusionMode::RayTraced;
         //let ssgi_tex = rg.create(ImageDesc::new_2d(vk::Format::R8_UNORM, [1, 1]));
 
         let mut ircache_state = self.ircache.prepare(rg);
@@ -102,7 +658,7 @@ impl WorldRenderer {
             crate::renderers::wrc::wrc_trace(
                 rg,
                 &mut ircache_state,
-                &sky_cube,
+                sky_cube,
                 self.bindless_descriptor_set,
                 tlas,
             )
@@ -113,7 +669,7 @@ impl WorldRenderer {
         let traced_ircache = tlas.as_ref().map(|tlas| {
             ircache_state.trace_irradiance(
                 rg,
-                &convolved_sky_cube,
+                convolved_sky_cube,
                 self.bindless_descriptor_set,
                 tlas,
                 &wrc,
@@ -121,44 +677,144 @@ impl WorldRenderer {
         });
 
         let sun_shadow_mask = if let Some(tlas) = tlas.as_ref() {
-            trace_sun_shadow_mask(rg, &gbuffer_depth, tlas, self.bindless_descriptor_set)
+            trace_sun_shadow_mask(
+                rg,
+                &gbuffer_depth,
+                tlas,
+                self.bindless_descriptor_set,
+                &self.render_quality,
+            )
         } else {
             rg.create(gbuffer_depth.depth.desc().format(vk::Format::R8_UNORM))
         };
 
-        let reprojected_rtdgi = self.rtdgi.reproject(rg, &reprojection_map);
+        let light_clusters = assign_light_clusters(rg, gbuffer_depth.gbuffer.desc().extent_2d());
+
+        let punctual_lighting = match tlas.as_ref() {
+            Some(tlas) if self.has_punctual_lights() => {
+                let lights = self.frame_punctual_lights();
+                let shadow_cache = self.punctual_shadow_cache.prepare(
+                    rg,
+                    &lights,
+                    tlas,
+                    self.bindless_descriptor_set,
+                );
+
+                Some(trace_punctual_lighting(
+                    rg,
+                    &gbuffer_depth,
+                    &light_clusters,
+                    shadow_cache,
+                    tlas,
+                    self.bindless_descriptor_set,
+                ))
+            }
+            _ => None,
+        };
+
+        let rect_lighting = tlas
+            .as_ref()
+            .filter(|_| !self.rect_lights.is_empty())
+            .map(|tlas| {
+                trace_rect_lighting(rg, &gbuffer_depth, tlas, self.bindless_descriptor_set)
+            });
+
+        // Diffuse GI and reflections optionally run on a reduced-resolution copy of their inputs.
+        let gi_divisor = self.render_quality.gi_resolution.divisor();
+        let gi_inputs = (gi_divisor > 1).then(|| {
+            downsample_gi_inputs(
+                rg,
+                self.gi_downsample_render_pass.clone(),
+                gi_divisor,
+                &gbuffer_depth,
+                &reprojection_map,
+                &ssgi_tex,
+                &accum_img,
+            )
+        });
+        let (gi_gbuffer_depth, gi_reprojection_map, gi_ssao, gi_prev_radiance) = match &gi_inputs {
+            Some(inputs) => (
+                &inputs.gbuffer_depth,
+                &inputs.reprojection_map,
+                &inputs.ssao,
+                &inputs.prev_radiance,
+            ),
+            None => (&gbuffer_depth, &reprojection_map, &ssgi_tex, &accum_img),
+        };
+
+        let gi_history_format = self.render_target_formats.gi_history.format();
+        let reprojected_rtdgi = (self.gi_mode == GiMode::Rtdgi).then(|| {
+            self.rtdgi
+                .reproject(rg, gi_reprojection_map, gi_history_format)
+        });
+
+        let punctual_lighting = punctual_lighting.map(|lighting| {
+            self.punctual_shadow_denoise
+                .render(rg, &gbuffer_depth, &lighting, &reprojection_map)
+        });
+
+        let rect_lighting = rect_lighting.map(|lighting| {
+            self.rect_shadow_denoise
+                .render(rg, &gbuffer_depth, &lighting, &reprojection_map)
+        });
 
-        let denoised_shadow_mask = if self.sun_size_multiplier > 0.0f32 {
+        let denoised_shadow_mask = if self.sun_angular_diameter_degrees > 0.0f32 {
             self.shadow_denoise
                 .render(rg, &gbuffer_depth, &sun_shadow_mask, &reprojection_map)
         } else {
             sun_shadow_mask.into()
         };
 
+        // After denoising, which would blur the detail away again
+        let denoised_shadow_mask = if self.contact_shadows.enabled {
+            contact_shadows(
+                rg,
+                &gbuffer_depth,
+                &hiz,
+                &denoised_shadow_mask,
+                &self.contact_shadows,
+            )
+            .into()
+        } else {
+            denoised_shadow_mask
+        };
+
         if let Some(traced_ircache) = traced_ircache {
             ircache_state.sum_up_irradiance_for_sampling(rg, traced_ircache);
         }
 
         let rtdgi_irradiance;
+        let rtdgi_raw_irradiance;
+        let rtdgi_variance;
+        let rtdgi_history;
         let rtdgi_candidates;
 
-        if let Some(tlas) = tlas.as_ref() {
+        if let Some((tlas, reprojected_rtdgi)) = tlas.as_ref().zip(reprojected_rtdgi) {
             let rtdgi = self.rtdgi.render(
                 rg,
                 reprojected_rtdgi,
-                &gbuffer_depth,
-                &reprojection_map,
-                &convolved_sky_cube,
+                gi_gbuffer_depth,
+                gi_reprojection_map,
+                convolved_sky_cube,
                 self.bindless_descriptor_set,
                 &mut ircache_state,
                 &wrc,
                 tlas,
-                &ssgi_tex,
+                gi_ssao,
+                ssgi_has_bent_normal,
+                &reactive_mask,
+                &self.render_quality,
             );
             rtdgi_irradiance = Some(rtdgi.screen_irradiance_tex);
+            rtdgi_raw_irradiance = Some(rtdgi.raw_irradiance_tex);
+            rtdgi_variance = Some(rtdgi.variance_tex);
+            rtdgi_history = Some(rtdgi.history_tex);
             rtdgi_candidates = Some(rtdgi.candidates);
         } else {
             rtdgi_irradiance = None;
+            rtdgi_raw_irradiance = None;
+            rtdgi_variance = None;
+            rtdgi_history = None;
             rtdgi_candidates = None;
         }
 
@@ -172,21 +828,35 @@ impl WorldRenderer {
             .as_ref()
             .zip(rtdgi_irradiance.as_ref())
             .zip(rtdgi_candidates)
+            .filter(|_| self.rtr.quality != ReflectionQuality::Low)
         {
+            let blue_noise_sampler = self.blue_noise.sampler_tables(rg);
+
             self.rtr.trace(
                 rg,
-                &gbuffer_depth,
-                &reprojection_map,
-                &sky_cube,
+                gi_gbuffer_depth,
+                gi_reprojection_map,
+                sky_cube,
+                gi_prev_radiance,
                 self.bindless_descriptor_set,
                 tlas,
                 rtdgi_irradiance,
                 rtdgi_candidates,
                 &mut ircache_state,
                 &wrc,
+                &blue_noise_sampler,
+                &self.render_quality,
             )
         } else {
-            self.rtr.create_dummy_output(rg, &gbuffer_depth)
+            self.rtr.trace_screen_space(
+                rg,
+                gi_gbuffer_depth,
+                gi_reprojection_map,
+                sky_cube,
+                gi_prev_radiance,
+                self.bindless_descriptor_set,
+                &self.render_quality,
+            )
         };
 
         if any_triangle_lights {
@@ -195,17 +865,73 @@ impl WorldRenderer {
                 self.lighting.render_specular(
                     &mut rtr.resolved_tex,
                     rg,
-                    &gbuffer_depth,
+                    gi_gbuffer_depth,
                     self.bindless_descriptor_set,
                     tlas,
                 );
             }
         }
 
-        let rtr = rtr.filter_temporal(rg, &gbuffer_depth, &reprojection_map);
+        let rtr = rtr.filter_temporal(rg, gi_gbuffer_depth, gi_reprojection_map);
+
+        let (rtr, rtdgi_irradiance) = match &gi_inputs {
+            Some(_) => (
+                upsample_gi_output(rg, &rtr, &gbuffer_depth, &self.bilateral_upsample),
+                rtdgi_irradiance.map(|rtdgi| {
+                    upsample_gi_output(rg, &rtdgi, &gbuffer_depth, &self.bilateral_upsample).into()
+                }),
+            ),
+            None => (rtr, rtdgi_irradiance),
+        };
+
+        let (rtr, rtdgi_irradiance) = if self.gi_temporal.enabled {
+            (
+                self.gi_temporal_filter.accumulate_reflections(
+                    rg,
+                    &rtr,
+                    &reprojection_map,
+                    &reactive_mask,
+                    &self.gi_temporal,
+                ),
+                rtdgi_irradiance.map(|rtdgi| {
+                    self.gi_temporal_filter
+                        .accumulate_diffuse(
+                            rg,
+                            &rtdgi,
+                            &reprojection_map,
+                            &reactive_mask,
+                            &self.gi_temporal,
+                        )
+                        .into()
+                }),
+            )
+        } else {
+            (rtr, rtdgi_irradiance)
+        };
+
+        // The probe volume is sampled directly at full resolution.
+        let rtdgi_irradiance = match tlas.as_ref().filter(|_| self.gi_mode == GiMode::Ddgi) {
+            Some(tlas) => Some(
+                self.ddgi
+                    .render(
+                        rg,
+                        &gbuffer_depth,
+                        convolved_sky_cube,
+                        self.bindless_descriptor_set,
+                        tlas,
+                        frame_desc.camera_matrices.eye_position(),
+                    )
+                    .into(),
+            ),
+            None => rtdgi_irradiance,
+        };
+
+        let rtdgi_irradiance =
+            self.light_probes
+                .blend_into_gi(rg, &gbuffer_depth, rtdgi_irradiance);
 
         let mut debug_out_tex = rg.create(ImageDesc::new_2d(
-            vk::Format::R16G16B16A16_SFLOAT,
+            self.render_target_formats.color.format(),
             gbuffer_depth.gbuffer.desc().extent_2d(),
         ));
 
@@ -216,6 +942,9 @@ impl WorldRenderer {
                 .into(),
         };
 
+        // After the temporal filters, as the mirrored views are rendered sharp every frame.
+        let rtr = composite_planar_reflections(rg, &gbuffer_depth, rtr, &planar_reflections);
+
         light_gbuffer(
             rg,
             &gbuffer_depth,
@@ -226,13 +955,163 @@ impl WorldRenderer {
             &wrc,
             &mut accum_img,
             &mut debug_out_tex,
-            &sky_cube,
-            &convolved_sky_cube,
+            sky_cube,
+            convolved_sky_cube,
+            prefiltered_sky_cube,
+            punctual_lighting.as_ref(),
+            rect_lighting.as_ref(),
+            &light_clusters,
             self.bindless_descriptor_set,
             self.debug_shading_mode,
             self.debug_show_wrc,
+            self.render_quality.reflection_roughness_cutoff,
+        );
+
+        // TODO: don't iter over all the things
+        let any_subsurface = self
+            .instances
+            .iter()
+            .any(|inst| self.meshes[inst.mesh.0].has_subsurface);
+
+        if self.subsurface.enabled && any_subsurface {
+            debug_out_tex =
+                subsurface_scattering(rg, &gbuffer_depth, &debug_out_tex, &self.subsurface);
+        }
+
+        let mut taa_responsive_mask = rg.create(ImageDesc::new_2d(
+            vk::Format::R8_UNORM,
+            gbuffer_depth.gbuffer.desc().extent_2d(),
+        ));
+        rg::imageops::clear_color(rg, &mut taa_responsive_mask, [0.0; 4]);
+
+        debug_out_tex = self.water.render(
+            rg,
+            self.delta_time_seconds,
+            &gbuffer_depth,
+            debug_out_tex,
+            &mut taa_responsive_mask,
+            &planar_reflections,
+            convolved_sky_cube,
+            prefiltered_sky_cube,
+            &mut ircache_state,
+            self.bindless_descriptor_set,
+            tlas.as_ref(),
+        );
+
+        let debug_view_img = match self.debug_view {
+            DebugView::None => None,
+            DebugView::Overdraw => {
+                let overdraw = raster_overdraw(
+                    rg,
+                    self.overdraw_render_pass.clone(),
+                    gbuffer_depth.gbuffer.desc().extent_2d(),
+                    RasterMeshesData {
+                        meshes: self.meshes.as_slice(),
+                        instances: self.instances.as_slice(),
+                        vertex_buffer: self.vertex_buffer.lock().clone(),
+                        bindless_descriptor_set: self.bindless_descriptor_set,
+                        lod_selection: self.mesh_lod_selection(frame_desc),
+                    },
+                );
+                Some(render_debug_view(
+                    rg,
+                    self.debug_view,
+                    &gbuffer_depth,
+                    Some(&overdraw),
+                    &self.luminance_heatmap,
+                ))
+            }
+            view => {
+                let input = match view {
+                    DebugView::MotionVectors => Some(&reprojection_map),
+                    // Without ReSTIR (DDGI), there's no separate unfiltered result.
+                    DebugView::RawGi => Some(rtdgi_raw_irradiance.as_deref().unwrap_or(&*rtdgi)),
+                    DebugView::DenoisedGi => Some(&*rtdgi),
+                    DebugView::AmbientOcclusion => Some(&*ssgi_tex),
+                    DebugView::ShadowVisibility => Some(&*denoised_shadow_mask),
+                    DebugView::Luminance => Some(&debug_out_tex),
+                    // Without ReSTIR (DDGI), these are black.
+                    DebugView::GiVariance => rtdgi_variance.as_deref(),
+                    DebugView::GiHistoryLength => rtdgi_history.as_deref(),
+                    _ => None,
+                };
+                Some(render_debug_view(
+                    rg,
+                    view,
+                    &gbuffer_depth,
+                    input,
+                    &self.luminance_heatmap,
+                ))
+            }
+        };
+
+        let integrated_fog = tlas
+            .as_ref()
+            .filter(|_| self.fog.density > 0.0)
+            .map(|tlas| {
+                self.volumetric_fog.render(
+                    rg,
+                    &self.fog,
+                    &gbuffer_depth,
+                    convolved_sky_cube,
+                    &light_clusters,
+                    self.bindless_descriptor_set,
+                    tlas,
+                    &mut debug_out_tex,
+                )
+            });
+
+        raster_transparent_meshes(
+            rg,
+            self.forward_transparent_render_pass.clone(),
+            &mut gbuffer_depth.depth,
+            &mut debug_out_tex,
+            &mut taa_responsive_mask,
+            convolved_sky_cube,
+            prefiltered_sky_cube,
+            RasterMeshesData {
+                meshes: self.meshes.as_slice(),
+                instances: self.instances.as_slice(),
+                vertex_buffer: self.vertex_buffer.lock().clone(),
+                bindless_descriptor_set: self.bindless_descriptor_set,
+                lod_selection: self.mesh_lod_selection(frame_desc),
+            },
+        );
+
+        self.particles.render(
+            rg,
+            self.forward_transparent_render_pass.clone(),
+            self.delta_time_seconds,
+            &mut gbuffer_depth.depth,
+            &mut debug_out_tex,
+            &mut taa_responsive_mask,
+            convolved_sky_cube,
+            &mut ircache_state,
+            integrated_fog.as_ref(),
+            self.bindless_descriptor_set,
+            tlas.as_ref(),
+        );
+
+        self.custom_passes.render(
+            CustomPassPoint::BeforePost,
+            rg,
+            &mut CustomPassResources {
+                gbuffer_depth: &mut gbuffer_depth,
+                velocity: &velocity_img,
+                color: Some(&mut debug_out_tex),
+                bindless_descriptor_set: self.bindless_descriptor_set,
+            },
         );
 
+        if self.hdr_captures.is_requested(HdrCaptureSource::PreTaa) {
+            let metadata = self.hdr_capture_metadata(frame_desc, false);
+            self.hdr_captures
+                .record_readback(rg, &debug_out_tex, metadata);
+        }
+
+        self.image_stats
+            .record_watched(rg, SCENE_IMAGE_STATS, &debug_out_tex, self.frame_idx);
+
         #[allow(unused_mut)]
         let mut anti_aliased = None;
 
@@ -247,29 +1126,57 @@ impl WorldRenderer {
             ));
         }
 
-        //let dof = crate::renderers::dof::dof(rg, &debug_out_tex, &gbuffer_depth.depth);
+        #[cfg(feature = "fsr2")]
+        if self.use_fsr2 && anti_aliased.is_none() {
+            anti_aliased = Some(self.fsr2.render(
+                rg,
+                &debug_out_tex,
+                &reprojection_map,
+                &gbuffer_depth.depth,
+                frame_desc.camera_matrices.view_to_clip,
+                self.temporal_upscale_extent,
+            ));
+        }
 
         let anti_aliased = anti_aliased.unwrap_or_else(|| {
             self.taa
                 .render(
                     rg,
-                    //&dof,
                     &debug_out_tex,
                     &reprojection_map,
                     &gbuffer_depth.depth,
+                    &taa_responsive_mask,
+                    &reactive_mask,
                     self.temporal_upscale_extent,
                 )
                 .this_frame_out
         });
 
-        let mut final_post_input =
-            motion_blur(rg, &anti_aliased, &gbuffer_depth.depth, &reprojection_map);
+        let anti_aliased = if self.dof.enabled {
+            let dof_params = self.physical_camera_dof_params(frame_desc);
+            dof(rg, &anti_aliased, &gbuffer_depth.depth, &dof_params)
+        } else {
+            anti_aliased
+        };
+
+        let mut final_post_input = if self.motion_blur.enabled {
+            let motion_blur_params = self.physical_camera_motion_blur_params();
+            motion_blur(
+                rg,
+                &anti_aliased,
+                &gbuffer_depth.depth,
+                &reprojection_map,
+                &motion_blur_params,
+            )
+        } else {
+            anti_aliased
+        };
 
         if let Some(tlas) = tlas.as_ref() {
             if matches!(self.debug_mode, RenderDebugMode::WorldRadianceCache) {
                 wrc.see_through(
                     rg,
-                    &convolved_sky_cube,
+                    convolved_sky_cube,
                     &mut ircache_state,
                     self.bindless_descriptor_set,
                     tlas,
@@ -278,17 +1185,126 @@ impl WorldRenderer {
             }
         }
 
-        let post_processed = self.post.render(
+        if let Some(frozen_frame) = self.frozen_frame.as_mut() {
+            final_post_input = frozen_frame.substitute(rg, final_post_input);
+        }
+
+        if self.hdr_captures.is_requested(HdrCaptureSource::PreTonemap) {
+            let metadata = self.hdr_capture_metadata(frame_desc, false);
+            self.hdr_captures
+                .record_readback(rg, &final_post_input, metadata);
+        }
+
+        let mut post_processed = self.post.render(
             rg,
             &final_post_input,
             //&anti_aliased,
             self.bindless_descriptor_set,
             self.exposure_state().post_mult,
             self.contrast,
-            self.dynamic_exposure.histogram_clipping,
+            &self.dynamic_exposure,
+        );
+
+        self.custom_passes.render(
+            CustomPassPoint::AfterPost,
+            rg,
+            &mut CustomPassResources {
+                gbuffer_depth: &mut gbuffer_depth,
+                velocity: &velocity_img,
+                color: Some(&mut post_processed),
+                bindless_descriptor_set: self.bindless_descriptor_set,
+            },
         );
 
-        rg.debugged_resource.take().unwrap_or(post_processed)
+        self.record_post_tonemap_capture(rg, &post_processed, frame_desc);
+
+        let mut output = rg
+            .debugged_resource
+            .take()
+            .or(debug_view_img)
+            .unwrap_or(post_processed);
+
+        if self.show_wireframe && rg.device().fill_mode_non_solid_enabled() {
+            raster_wireframe(
+                rg,
+                &self.device,
+                &mut output,
+                RasterMeshesData {
+                    meshes: self.meshes.as_slice(),
+                    instances: self.instances.as_slice(),
+                    vertex_buffer: self.vertex_buffer.lock().clone(),
+                    bindless_descriptor_set: self.bindless_descriptor_set,
+                    lod_selection: self.mesh_lod_selection(frame_desc),
+                },
+            );
+        }
+
+        output
+    }
+
+    fn has_punctual_lights(&self) -> bool {
+        !self.punctual_lights.is_empty()
+            || self
+                .instances
+                .iter()
+                .any(|inst| !self.mesh_lights[inst.mesh.0].punctual_lights.is_empty())
+    }
+
+    fn hdr_capture_metadata(
+        &self,
+        frame_desc: &WorldFrameDesc,
+        is_reference: bool,
+    ) -> HdrCaptureMetadata {
+        let exposure = self.exposure_state();
+
+        HdrCaptureMetadata {
+            world_to_view: frame_desc.camera_matrices.world_to_view,
+            view_to_clip: frame_desc.camera_matrices.view_to_clip,
+            frame_idx: self.frame_idx,
+            pre_exposure: exposure.pre_mult,
+            exposure: exposure.pre_mult * exposure.post_mult,
+            physical_camera: Some(self.physical_camera).filter(|camera| camera.enabled),
+            is_reference,
+        }
+    }
+
+    /// The aperture and sensor size of the physical camera, if enabled, override the DOF ones.
+    fn physical_camera_dof_params(&self, frame_desc: &WorldFrameDesc) -> DofParams {
+        if !self.physical_camera.enabled {
+            return self.dof;
+        }
+
+        DofParams {
+            f_stop: self.physical_camera.f_stop,
+            sensor_height: self
+                .physical_camera
+                .sensor_height_for_aspect_ratio(frame_desc.camera_matrices.aspect_ratio()),
+            ..self.dof
+        }
+    }
+
+    fn physical_camera_motion_blur_params(&self) -> MotionBlurParams {
+        if !self.physical_camera.enabled {
+            return self.motion_blur;
+        }
+
+        MotionBlurParams {
+            shutter_angle_degrees: self
+                .physical_camera
+                .shutter_angle_degrees(self.delta_time_seconds),
+            ..self.motion_blur
+        }
+    }
+
+    fn mesh_lod_selection(&self, frame_desc: &WorldFrameDesc) -> MeshLodSelection {
+        MeshLodSelection {
+            eye_position: frame_desc.camera_matrices.eye_position(),
+            pixels_per_unit_at_unit_distance: frame_desc.camera_matrices.view_to_clip.y_axis.y
+                * frame_desc.render_extent[1] as f32
+                * 0.5,
+            max_pixel_error: self.mesh_lod_max_pixel_error,
+            ray_traced: self.device.ray_tracing_enabled(),
+        }
     }
 
     pub(super) fn prepare_render_graph_reference(
@@ -312,20 +1328,84 @@ impl WorldRenderer {
             rg::imageops::clear_color(rg, &mut accum_img, [0.0, 0.0, 0.0, 0.0]);
         }
 
-        if rg.device().ray_tracing_enabled() {
-            let tlas = self.prepare_top_level_acceleration(rg);
+        let tlas = if rg.device().ray_tracing_enabled() {
+            self.prepare_top_level_acceleration(rg)
+        } else {
+            None
+        };
 
+        if let Some(tlas) = tlas {
             reference_path_trace(rg, &mut accum_img, self.bindless_descriptor_set, &tlas);
         }
 
-        self.post.render(
+        // Any other capture source reads the accumulated image.
+        if !self
+            .hdr_captures
+            .is_requested(HdrCaptureSource::PostTonemap)
+        {
+            let metadata = self.hdr_capture_metadata(frame_desc, true);
+            self.hdr_captures.record_readback(rg, &accum_img, metadata);
+        }
+
+        let post_processed = self.post.render(
             rg,
             &accum_img,
             //&accum_img, // hack
             self.bindless_descriptor_set,
             self.exposure_state().post_mult,
             self.contrast,
-            self.dynamic_exposure.histogram_clipping,
-        )
+            &self.dynamic_exposure,
+        );
+
+        self.record_post_tonemap_capture(rg, &post_processed, frame_desc);
+
+        post_processed
+    }
+
+    fn record_post_tonemap_capture(
+        &mut self,
+        rg: &mut rg::TemporalRenderGraph,
+        post_processed: &rg::Handle<Image>,
+        frame_desc: &WorldFrameDesc,
+    ) {
+        if self
+            .hdr_captures
+            .is_requested(HdrCaptureSource::PostTonemap)
+        {
+            // Already exposed; there's nothing to divide out, or to apply later.
+            let metadata = HdrCaptureMetadata {
+                pre_exposure: 1.0,
+                exposure: 1.0,
+                ..self.hdr_capture_metadata(frame_desc, false)
+            };
+            self.hdr_captures
+                .record_readback(rg, post_processed, metadata);
+        }
+    }
+}
+
+/// Imports the cube of `handle` into `imported` unless it's already there, as each image
+/// may only be imported once per graph. Returns its index in `imported`.
+fn import_probe_cube(
+    rg: &mut rg::TemporalRenderGraph,
+    imported: &mut Vec<(EnvironmentProbeHandle, rg::Handle<Image>)>,
+    probes: &EnvironmentProbes,
+    handle: EnvironmentProbeHandle,
+) -> usize {
+    if let Some(idx) = imported
+        .iter()
+        .position(|(imported, _)| *imported == handle)
+    {
+        return idx;
     }
+
+    let probe = probes.get(handle);
+    let access = if probe.captured_faces == 0 {
+        AccessType::Nothing
+    } else {
+        AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer
+    };
+
+    imported.push((handle, rg.import(probe.cube.clone(), access)));
+    imported.len() - 1
 }
@@ -1,21 +1,82 @@
 use crate::{
+    asset_streaming::{
+        ImageStreamer, LoadProgress, MeshStreamer, StreamedImage, StreamedMesh, StreamedTexture,
+    },
     bindless_descriptor_set::{
         create_bindless_descriptor_set, BINDLESS_DESCRIPTOR_SET_LAYOUT,
         BINDLESS_TEXURES_BINDING_INDEX,
     },
     buffer_builder::BufferBuilder,
+    camera::PhysicalCamera,
+    debug_text::DebugTextRenderer,
     frame_desc::WorldFrameDesc,
     image_lut::{ComputeImageLut, ImageLut},
+    math::BoundingSphere,
     renderers::{
-        ibl::IblRenderer, ircache::IrcacheRenderer, lighting::LightingRenderer,
-        post::PostProcessRenderer, raster_meshes::*, rtdgi::RtdgiRenderer, rtr::*,
-        shadow_denoise::ShadowDenoiseRenderer, ssgi::*, taa::TaaRenderer,
+        bilateral_upsample::BilateralUpsampleParams,
+        blue_noise::BlueNoise,
+        buffer_writes::write_buffer,
+        contact_shadows::ContactShadowParams,
+        curves::{Curve, CurveGeometry, CurveMaterial, CURVE_TUBE_SIDES},
+        custom_passes::CustomPasses,
+        ddgi::{DdgiRenderer, GiMode},
+        debug_view::{DebugView, LuminanceHeatmap},
+        decals::Decal,
+        dof::DofParams,
+        environment_probes::{EnvironmentProbeHandle, EnvironmentProbes},
+        freeze_frame::FrozenFrame,
+        gbuffer_layout::GbufferLayout,
+        gi_temporal::{GiTemporalParams, GiTemporalRenderer},
+        gtao::GtaoRenderer,
+        hdr_capture::{HdrCapture, HdrCaptureSource, HdrCaptures},
+        hiz::HizRenderer,
+        ibl::{IblRenderer, PrefilterMipCycle},
+        image_stats::ImageStatsReadback,
+        ircache::IrcacheRenderer,
+        light_probes::LightProbes,
+        lighting::LightingRenderer,
+        local_light_shadow_denoise::LocalLightShadowDenoiseRenderer,
+        material_animation::MaterialAnimation,
+        material_params::MaterialParams,
+        motion_blur::MotionBlurParams,
+        output_calibration::{OutputCalibration, OutputScaling},
+        particles::ParticleSystem,
+        picking::GpuPicking,
+        planar_reflections::PlanarReflections,
+        post::PostProcessRenderer,
+        punctual_shadow_cache::PunctualShadowCache,
+        raster_meshes::*,
+        reactive_mask::ReactiveMaskRenderer,
+        rect_lights::{GpuRectLight, RectLight},
+        render_quality::RenderQuality,
+        render_target_formats::RenderTargetFormats,
+        rtao::{AmbientOcclusionMode, RtaoRenderer},
+        rtdgi::RtdgiRenderer,
+        rtr::*,
+        shadow_denoise::ShadowDenoiseRenderer,
+        skinning::{skin_meshes, SkinVertex, SkinningDispatch},
+        sky::AtmosphereParams,
+        ssgi::*,
+        subsurface::SubsurfaceParams,
+        taa::TaaRenderer,
+        time_of_day::TimeOfDay,
+        time_slicing::TimeSlicer,
+        triangle_lights::build_gpu_triangle_lights,
+        volumetric_fog::{FogParams, VolumetricFogRenderer},
+        water::WaterSurfaces,
+        working_color_space::WorkingColorSpace,
+        READBACK_FRAME_LATENCY,
     },
+    world_view::{WorldView, WorldViewHandle},
+};
+use glam::{Affine3A, Mat4, Vec2, Vec3, Vec4};
+use kajiya_asset::mesh::{
+    AssetRef, GpuImage, MeshMaterial, MeshMaterialFlags, PackedTriMesh, PackedVertex,
+    PunctualLight, QuantizedVertex, VertexDequantization,
 };
-use glam::{Affine3A, Vec2, Vec3};
-use kajiya_asset::mesh::{AssetRef, GpuImage, MeshMaterialFlags, PackedTriMesh, PackedVertex};
 use kajiya_backend::{
     ash::vk::{self, ImageView},
+    bytes::as_byte_slice,
     dynamic_constants::DynamicConstants,
     vk_sync::{self, AccessType},
     vulkan::{self, device, image::*, ray_tracing::*, shader::*, RenderBackend},
@@ -32,13 +93,23 @@ use rust_shaders_shared::{
     render_overrides::RenderOverrides,
     view_constants::ViewConstants,
 };
-use std::{collections::HashMap, mem::size_of, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+    path::PathBuf,
+    sync::Arc,
+};
 use vulkan::buffer::{Buffer, BufferDesc};
 
 const USE_TAA_JITTER: bool = true;
 
+// Limits the number of textures re-created at once when the camera moves.
+const MAX_TEXTURE_STREAMING_REQUESTS_PER_FRAME: usize = 16;
+
 #[cfg(feature = "dlss")]
 use crate::renderers::dlss::DlssRenderer;
+#[cfg(feature = "fsr2")]
+use crate::renderers::fsr2::Fsr2Renderer;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -51,6 +122,65 @@ struct GpuMesh {
 
     mat_data_offset: u32,
     index_offset: u32,
+
+    // Positions and normals of the previous frame, for motion vectors. Same as
+    // `vertex_core_offset`, unless the mesh is being deformed; see `set_mesh_vertices`.
+    vertex_prev_core_offset: u32,
+
+    // Non-zero for meshes from `add_curves`, whose raster indices refer to ribbons.
+    curve_tube_sides: u32,
+
+    // For the core and UV streams of meshes baked with `VertexQuantization`.
+    vertex_dequantization: VertexDequantization,
+}
+
+// Undoes the quantization of positions in acceleration structures.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct BlasVertexTransform([[f32; 4]; 3]);
+
+// Vertices of a mesh deformed with `WorldRenderer::set_mesh_vertices`,
+// or skinned with `WorldRenderer::set_mesh_skin`.
+//
+// Uses two regions of the vertex buffer in turn, so that the previous frame's vertices
+// are still there for the motion vectors while the current ones are written.
+struct MeshDeformation {
+    gpu_mesh: GpuMesh,
+    regions: [u32; 2],
+    // Index of the region to write next
+    next_region: usize,
+    // Waiting for the next frame
+    pending_verts: Option<Vec<PackedVertex>>,
+    // Mesh-space bounds of the vertices last written
+    bounding_sphere: BoundingSphere,
+    // Allocated once tangents are first replaced. Only the current frame's are needed,
+    // so there's just the one region.
+    tangent_region: Option<u32>,
+    pending_tangents: Option<Vec<[f32; 4]>>,
+    // The baked vertices, which skinning transforms. Zero tangent offset if there are none.
+    rest_core_offset: u32,
+    rest_tangent_offset: u32,
+    rest_bounding_sphere: BoundingSphere,
+    skinning: Option<MeshSkinning>,
+    // With ray tracing enabled
+    blas: Option<DeformedBlas>,
+}
+
+// Joint indices and weights of a mesh skinned on the GPU, in the vertex buffer.
+struct MeshSkinning {
+    skin_offset: u32,
+    // One past the highest joint index
+    joint_count: u32,
+    pending_skin: Option<Vec<u8>>,
+    pending_joint_matrices: Option<Vec<Affine3A>>,
+}
+
+// A BLAS built with `allow_update`, refit to the current region whenever the vertices change,
+// so that rays hit the same surface the G-buffer raster draws.
+struct DeformedBlas {
+    desc: RayTracingBottomAccelerationDesc,
+    blas: Arc<RayTracingAcceleration>,
+    scratch: RayTracingAccelerationScratchBuffer,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
@@ -73,29 +203,102 @@ impl Default for InstanceHandle {
     }
 }
 
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct PunctualLightHandle(pub usize);
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct DecalHandle(pub usize);
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct RectLightHandle(pub usize);
+
+/// One of the materials of a mesh, indexed in the order of its source asset.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct MaterialHandle {
+    pub mesh: MeshHandle,
+    pub index: usize,
+}
+
+/// Angular diameter of the sun as seen from Earth.
+pub const EARTH_SUN_ANGULAR_DIAMETER_DEGREES: f32 = 0.53;
+
 const MAX_GPU_MESHES: usize = 1024;
 const VERTEX_BUFFER_CAPACITY: usize = 1024 * 1024 * 1024;
 const TLAS_PREALLOCATE_BYTES: usize = 1024 * 1024 * 32;
 
+/// Per-instance overrides of material parameters, applied on top of all the instance's materials.
+/// Must match `InstanceDynamicConstants` in shaders.
 #[derive(Clone, Copy)]
+#[repr(C)]
 pub struct InstanceDynamicParameters {
+    /// Multiplies the base color
+    pub base_color_tint: [f32; 3],
     pub emissive_multiplier: f32,
+    /// Multiplies the perceptual roughness
+    pub roughness_multiplier: f32,
+    pad: [f32; 3],
 }
 
 impl Default for InstanceDynamicParameters {
     fn default() -> Self {
         Self {
+            base_color_tint: [1.0; 3],
             emissive_multiplier: 1.0,
+            roughness_multiplier: 1.0,
+            pad: [0.0; 3],
         }
     }
 }
 
+// What needs to happen to the TLAS before it's used in the next frame
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TlasUpdate {
+    None,
+    // Only instance transforms, or the vertices of deformed BLASes changed
+    Refit,
+    Rebuild,
+}
+
 #[derive(Clone, Copy)]
 pub struct MeshInstance {
     pub transform: Affine3A,
     pub prev_transform: Affine3A,
     pub mesh: MeshHandle,
     pub dynamic_parameters: InstanceDynamicParameters,
+    pub ray_visibility: RayVisibility,
+
+    /// Promises that the instance doesn't move or deform, so that its shadows can be cached;
+    /// see `WorldRenderer::set_instance_static`.
+    pub is_static: bool,
+}
+
+/// Which ray traced effects see a mesh instance. Rasterization, and thus the primary view,
+/// is unaffected; a first-person weapon can stay out of reflections and not cast shadows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RayVisibility {
+    pub cast_shadows: bool,
+    pub in_reflections: bool,
+    pub in_gi: bool,
+}
+
+impl Default for RayVisibility {
+    fn default() -> Self {
+        Self {
+            cast_shadows: true,
+            in_reflections: true,
+            in_gi: true,
+        }
+    }
+}
+
+impl RayVisibility {
+    // Must match the `RT_INSTANCE_MASK_*` constants in `rt.hlsl`.
+    fn instance_mask(&self, is_static: bool) -> u8 {
+        let shadow_bit = if is_static { 1 << 3 } else { 1 };
+        (self.cast_shadows as u8 * shadow_bit)
+            | (self.in_reflections as u8) << 1
+            | (self.in_gi as u8) << 2
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -112,17 +315,20 @@ pub struct TriangleLight {
 }
 
 impl TriangleLight {
-    pub fn transform(
-        self,
-        translation: Vec3,
-        rotation: impl core::ops::Mul<Vec3, Output = Vec3> + Copy,
-    ) -> Self {
+    pub fn transform(self, xform: &Affine3A) -> Self {
+        let [v0, v1, v2] = self
+            .verts
+            .map(|v| xform.transform_point3(Vec3::from(v)).into());
+
+        // Mirroring flips the winding, which the emitting side is derived from.
+        let verts = if xform.matrix3.determinant() < 0.0 {
+            [v0, v2, v1]
+        } else {
+            [v0, v1, v2]
+        };
+
         Self {
-            verts: [
-                (rotation * Vec3::from(self.verts[0]) + translation).into(),
-                (rotation * Vec3::from(self.verts[1]) + translation).into(),
-                (rotation * Vec3::from(self.verts[2]) + translation).into(),
-            ],
+            verts,
             radiance: self.radiance,
         }
     }
@@ -137,17 +343,31 @@ impl TriangleLight {
 
 pub struct MeshLightSet {
     pub lights: Vec<TriangleLight>,
+    pub punctual_lights: Vec<PunctualLight>,
 }
 
 pub struct WorldRenderer {
-    device: Arc<device::Device>,
+    pub(super) device: Arc<device::Device>,
 
     pub(super) raster_simple_render_pass: Arc<RenderPass>,
+    pub(super) forward_transparent_render_pass: Arc<RenderPass>,
+    pub(super) gi_downsample_render_pass: Arc<RenderPass>,
+    pub(super) overdraw_render_pass: Arc<RenderPass>,
+    pub(super) depth_prepass_render_pass: Arc<RenderPass>,
+    // What the render passes with frame resource attachments were created for
+    render_pass_formats: RenderTargetFormats,
     pub(super) bindless_descriptor_set: vk::DescriptorSet,
     pub(super) meshes: Vec<UploadedTriMesh>,
 
     pub(super) mesh_lights: Vec<MeshLightSet>,
 
+    material_animations: HashMap<(MeshHandle, usize), MaterialAnimation>,
+    // Updated, or no longer animated, and need their data written to the vertex buffer.
+    materials_to_write: Vec<(MeshHandle, usize)>,
+    material_animation_time: f32,
+
+    mesh_deformations: HashMap<MeshHandle, MeshDeformation>,
+
     // ----
     // SoA
     pub(super) instances: Vec<MeshInstance>,
@@ -157,59 +377,201 @@ pub struct WorldRenderer {
     // The `usize` indexes into `instances` and `instance_handles`
     pub(super) instance_handle_to_index: HashMap<InstanceHandle, usize>,
 
+    // Lights not attached to any mesh, in world space
+    pub(super) punctual_lights: Vec<(PunctualLightHandle, PunctualLight)>,
+    next_punctual_light_handle: usize,
+
+    pub(super) rect_lights: Vec<(RectLightHandle, RectLight)>,
+    next_rect_light_handle: usize,
+
+    // In the order they're applied
+    pub(super) decals: Vec<(DecalHandle, Decal)>,
+    next_decal_handle: usize,
+
+    pub(super) views: Vec<(WorldViewHandle, WorldView)>,
+    next_view_handle: usize,
+    // Cameras and extents of the views in the latest render graph, by their view index minus one.
+    // Camera, extent, and clip plane
+    pub(super) rendered_views: Vec<(CameraMatrices, [u32; 2], Vec4)>,
+
     pub(super) vertex_buffer: Mutex<Arc<Buffer>>,
-    vertex_buffer_written: u64,
+    // Shared with the `MeshStreamer` threads, which upload to the vertex buffer too.
+    vertex_buffer_written: Arc<Mutex<u64>>,
 
     mesh_buffer: Mutex<Arc<Buffer>>,
 
-    mesh_blas: Vec<Arc<RayTracingAcceleration>>,
+    // `None` while a mesh is loading, or without ray tracing
+    mesh_blas: Vec<Option<Arc<RayTracingAcceleration>>>,
     tlas: Option<Arc<RayTracingAcceleration>>,
+    tlas_update: TlasUpdate,
     accel_scratch: RayTracingAccelerationScratchBuffer,
 
     bindless_images: Vec<Arc<Image>>,
     next_bindless_image_id: usize,
+    image_streamer: ImageStreamer,
+    mesh_streamer: MeshStreamer,
+    // Added with `add_mesh_in_background`, and still loading
+    pending_meshes: HashSet<MeshHandle>,
+    streamed_textures: Vec<StreamedTexture>,
+    // Indices into `streamed_textures` used by each mesh
+    mesh_streamed_textures: Vec<Vec<usize>>,
+    streamed_texture_by_handle: HashMap<BindlessImageHandle, usize>,
+    // Bound in place of material maps until their images are streamed in;
+    // indexed like `MeshMaterial::maps`: albedo, normal, specular, emissive, height.
+    placeholder_images: [Arc<Image>; 5],
     next_instance_handle: usize,
     bindless_texture_sizes: Buffer,
 
-    image_luts: Vec<ImageLut>,
+    // With their bindless image ids
+    image_luts: Vec<(usize, ImageLut)>,
+    atmosphere_in_image_luts: Option<AtmosphereParams>,
     frame_idx: u32,
+    deterministic: Option<DeterministicMode>,
+    // Frames rendered since `deterministic` was set
+    deterministic_frame_idx: u32,
     prev_camera_matrices: Option<CameraMatrices>,
+    // Of the previous frame, as the render graph is built before the frame constants.
+    pub(crate) delta_time_seconds: f32,
     pub(crate) temporal_upscale_extent: [u32; 2],
 
-    supersample_offsets: Vec<Vec2>,
-
     pub rg_debug_hook: Option<rg::GraphDebugHook>,
     pub render_mode: RenderMode,
+    /// Keep presenting the current frame, while the debug views can still be switched.
+    /// Only in `RenderMode::Standard`; see `renderers::freeze_frame`.
+    pub freeze_frame: bool,
+    pub(super) frozen_frame: Option<FrozenFrame>,
     pub reset_reference_accumulation: bool,
+    pub(super) hdr_captures: HdrCaptures,
+    pub(super) picking: GpuPicking,
 
     pub post: PostProcessRenderer,
     pub ssgi: SsgiRenderer,
+    pub gtao: GtaoRenderer,
+    /// Use `gtao` rather than `ssgi` for the ambient occlusion which guides the GI.
+    pub use_gtao: bool,
+    pub rtao: RtaoRenderer,
+    /// Falls back to `AmbientOcclusionMode::ScreenSpace` without ray tracing.
+    pub ao_mode: AmbientOcclusionMode,
+    /// Cull the opaque instances on the GPU, and draw the survivors with indirect draws,
+    /// instead of issuing a draw per instance from the CPU. Ignored if the device doesn't
+    /// support `VK_KHR_draw_indirect_count`. See `renderers::culling`.
+    pub use_gpu_driven_draws: bool,
+    /// With `use_gpu_driven_draws`, also cull instances hidden behind the previous frame's depth.
+    pub use_occlusion_culling: bool,
+    /// Draw the depth of opaque meshes before the G-buffer, which then only shades
+    /// the visible fragments. Pays off in scenes with a lot of overdraw.
+    pub use_depth_prepass: bool,
+    /// Overlay the edges of all the meshes on the output. Ignored if the device
+    /// doesn't support `fillModeNonSolid`.
+    pub show_wireframe: bool,
+    pub(super) hiz: HizRenderer,
+    pub(super) gi_temporal_filter: GiTemporalRenderer,
+    pub(super) reactive_mask: ReactiveMaskRenderer,
+    pub(super) blue_noise: BlueNoise,
+    debug_text: DebugTextRenderer,
     pub rtr: RtrRenderer,
     pub lighting: LightingRenderer,
     pub ircache: IrcacheRenderer,
     pub rtdgi: RtdgiRenderer,
+    /// Selects between `rtdgi` and `ddgi` for diffuse GI.
+    pub gi_mode: GiMode,
+    pub ddgi: DdgiRenderer,
     pub taa: TaaRenderer,
     pub shadow_denoise: ShadowDenoiseRenderer,
+    pub punctual_shadow_denoise: LocalLightShadowDenoiseRenderer,
+    pub punctual_shadow_cache: PunctualShadowCache,
+    pub rect_shadow_denoise: LocalLightShadowDenoiseRenderer,
     pub ibl: IblRenderer,
+    pub environment_probes: EnvironmentProbes,
+    pub light_probes: LightProbes,
+    /// Spreads the probe updates and the sky pre-filtering over frames.
+    pub time_slicer: TimeSlicer,
+    sky_prefilter_cycle: PrefilterMipCycle,
+    pub planar_reflections: PlanarReflections,
+    pub water: WaterSurfaces,
+    pub particles: ParticleSystem,
+    pub volumetric_fog: VolumetricFogRenderer,
 
     #[cfg(feature = "dlss")]
     pub dlss: DlssRenderer,
     #[cfg(feature = "dlss")]
     pub use_dlss: bool,
 
+    #[cfg(feature = "fsr2")]
+    pub fsr2: Fsr2Renderer,
+    #[cfg(feature = "fsr2")]
+    pub use_fsr2: bool,
+
     pub debug_mode: RenderDebugMode,
+    pub debug_view: DebugView,
+    pub luminance_heatmap: LuminanceHeatmap,
+    /// Min, max, mean, and histograms of images in the pipeline, read back from the GPU.
+    pub image_stats: ImageStatsReadback,
+    /// Passes of the application, run at fixed points in the frame.
+    pub custom_passes: CustomPasses,
     pub debug_shading_mode: usize,
     pub debug_show_wrc: bool,
     pub ev_shift: f32,
     pub dynamic_exposure: DynamicExposureState,
+    pub physical_camera: PhysicalCamera,
     pub contrast: f32,
-
-    pub sun_size_multiplier: f32,
+    /// Brightness and gamut of the display, applied in the final blit.
+    pub output_calibration: OutputCalibration,
+    /// How the final blit fits the output into swapchains of a different size.
+    pub output_scaling: OutputScaling,
+    pub working_color_space: WorkingColorSpace,
+
+    /// Drives the cone which sun shadow rays are sampled in, and thus the width of the penumbrae.
+    /// Zero makes the shadows hard.
+    pub sun_angular_diameter_degrees: f32,
     pub sun_color_multiplier: Vec3,
+    /// Screen-space detail layered on the sun shadows.
+    pub contact_shadows: ContactShadowParams,
     pub sky_ambient: Vec3,
+    pub atmosphere: AtmosphereParams,
+    /// Overrides the sun direction, `sun_color_multiplier`, `sky_ambient`, and
+    /// the turbidity of `atmosphere` while enabled.
+    pub time_of_day: TimeOfDay,
+    // Reported by `notify_lighting_change` for the frame being prepared
+    lighting_change: f32,
+    pub fog: FogParams,
+    pub dof: DofParams,
+    pub motion_blur: MotionBlurParams,
 
     pub render_overrides: RenderOverrides,
 
+    /// World-space plane which cuts the main view, e.g. for architectural sections; only the side
+    /// where `dot(xyz, position) + w >= 0` is rasterized, and seen by rays.
+    pub clip_plane: Option<Vec4>,
+
+    /// Ray counts, history lengths, and resolution of the ray-traced effects.
+    pub render_quality: RenderQuality,
+
+    /// Precision of the lit scene, GI history, and G-buffer normals.
+    pub render_target_formats: RenderTargetFormats,
+
+    /// Edge-stopping of the upsampling of reduced-resolution GI and ambient occlusion.
+    pub bilateral_upsample: BilateralUpsampleParams,
+
+    /// Accumulation of diffuse GI and reflections at full resolution, independent of TAA.
+    pub gi_temporal: GiTemporalParams,
+
+    /// Screen-space subsurface scattering, and the profiles which materials refer to.
+    pub subsurface: SubsurfaceParams,
+
+    /// Screen-space error in pixels tolerated when selecting mesh LODs; zero disables LODs.
+    ///
+    /// With ray tracing, the acceleration structures only have the full-detail meshes,
+    /// and the error is further limited to a fraction of the ray bias, so that the rasterized
+    /// LODs don't self-shadow against them. Far fewer LODs get used then.
+    pub mesh_lod_max_pixel_error: f32,
+
+    /// Memory for streamed texture mips beyond the always-resident smallest ones.
+    pub texture_streaming_budget_bytes: u64,
+    /// Distance from the camera within which textures are streamed in at full resolution.
+    /// Each doubling of the distance drops one mip.
+    pub texture_streaming_full_res_distance: f32,
+
     // One for each render mode
     pub(crate) exposure_state: [ExposureState; 2],
 }
@@ -220,42 +582,42 @@ pub struct HistogramClipping {
     pub high: f32,
 }
 
-#[derive(Default)]
+/// Histogram-based auto exposure. The adaptation runs on the GPU, in `PostProcessRenderer`.
 pub struct DynamicExposureState {
     pub enabled: bool,
     pub speed_log2: f32,
     pub histogram_clipping: HistogramClipping,
 
-    ev_fast: f32,
-    ev_slow: f32,
+    /// Range of the exposure compensation the adaptation may apply, in EV.
+    pub ev_min: f32,
+    pub ev_max: f32,
+
+    ev_adapted: f32,
+}
+
+impl Default for DynamicExposureState {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed_log2: 0.0,
+            histogram_clipping: Default::default(),
+            ev_min: -16.0,
+            ev_max: 16.0,
+            ev_adapted: 0.0,
+        }
+    }
 }
 
-const DYNAMIC_EXPOSURE_BIAS: f32 = -2.0;
+pub(crate) const DYNAMIC_EXPOSURE_BIAS: f32 = -2.0;
 
 impl DynamicExposureState {
     pub fn ev_smoothed(&self) -> f32 {
         if self.enabled {
-            (self.ev_slow + self.ev_fast) * 0.5 + DYNAMIC_EXPOSURE_BIAS
+            self.ev_adapted
         } else {
             0.0
         }
     }
-
-    pub fn update(&mut self, ev: f32, dt: f32) {
-        if !self.enabled {
-            return;
-        }
-
-        let ev = ev.clamp(-16.0, 16.0);
-
-        let dt = dt * self.speed_log2.exp2();
-
-        let t_fast = 1.0 - (-1.0 * dt).exp();
-        self.ev_fast = (ev - self.ev_fast) * t_fast + self.ev_fast;
-
-        let t_slow = 1.0 - (-0.25 * dt).exp();
-        self.ev_slow = (ev - self.ev_slow) * t_slow + self.ev_slow;
-    }
 }
 
 #[derive(Clone, Copy)]
@@ -291,42 +653,44 @@ pub enum RenderMode {
     Reference = 1,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct BindlessImageHandle(pub u32);
-
-fn load_gpu_image_asset(
-    device: Arc<kajiya_backend::Device>,
-    asset: AssetRef<GpuImage::Flat>,
-) -> Arc<Image> {
-    let asset = crate::mmap::mmapped_asset::<GpuImage::Flat, _>(&format!(
-        "/cache/{:8.8x}.image",
-        asset.identity()
-    ))
-    .unwrap();
-
-    let desc = ImageDesc::new_2d(asset.format, [asset.extent[0], asset.extent[1]])
-        .usage(vk::ImageUsageFlags::SAMPLED)
-        .mip_levels(asset.mips.len() as _);
-
-    let initial_data = asset
-        .mips
-        .iter()
-        .enumerate()
-        .map(|(mip_level, mip)| ImageSubResourceData {
-            data: mip.as_slice(),
-            row_pitch: ((desc.extent[0] as usize) >> mip_level).max(1) * 4,
-            slice_pitch: 0,
-        })
-        .collect::<Vec<_>>();
+/// Fixed inputs for the renderer's stochastic and time-dependent processes,
+/// so that identical scenes and cameras produce identical images across runs.
+///
+/// The blue noise and ray RNG frame index, and the jitter sequence position
+/// are `seed` plus the number of frames rendered since the mode was enabled,
+/// rather than the renderer's running frame count. The delta time fed to
+/// motion blur, exposure adaptation, and temporal upsampling is fixed too.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DeterministicMode {
+    pub seed: u32,
+    pub delta_time_seconds: f32,
+}
 
-    Arc::new(device.create_image(desc, initial_data).unwrap())
+impl Default for DeterministicMode {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            delta_time_seconds: 1.0 / 60.0,
+        }
+    }
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BindlessImageHandle(pub u32);
+
+#[derive(Clone, Copy)]
 pub struct AddMeshOptions {
+    /// Turn triangles with emissive materials into lights sampled by the ray-traced GI,
+    /// reflections, and direct lighting. Enabled by default.
     pub use_lights: bool,
 }
 
+impl Default for AddMeshOptions {
+    fn default() -> Self {
+        Self { use_lights: true }
+    }
+}
+
 impl AddMeshOptions {
     pub fn new() -> Self {
         Self::default()
@@ -338,6 +702,237 @@ impl AddMeshOptions {
     }
 }
 
+/// Where the data of a triangle mesh went in the vertex buffer; see `upload_mesh_geometry`.
+pub(crate) struct UploadedMeshGeometry {
+    index_offset: u32,
+    core_offset: u32,
+    uv_offset: u32,
+    mat_offset: u32,
+    aux_offset: u32,
+    tangent_offset: u32,
+    // The materials as baked; `WorldRenderer::finish_mesh` rewrites them with bindless map ids.
+    mat_data_offset: u32,
+    lod_index_offset: u32,
+    blas: Option<RayTracingAcceleration>,
+}
+
+/// Uploads the geometry of a mesh, and builds its BLAS. Leaves the rest of the mesh
+/// to `WorldRenderer::finish_mesh`, so that this can run on the `MeshStreamer` threads.
+pub(crate) fn upload_mesh_geometry(
+    device: &device::Device,
+    vertex_buffer: &Buffer,
+    vertex_buffer_written: &Mutex<u64>,
+    mesh: &'static PackedTriMesh::Flat,
+) -> anyhow::Result<UploadedMeshGeometry> {
+    anyhow::ensure!(!mesh.indices.is_empty(), "mesh has no triangles");
+    let vertex_dequantization = mesh.vertex_dequantization();
+
+    let mut buffer_builder = BufferBuilder::new();
+    let index_offset = buffer_builder.append(mesh.indices.as_slice());
+    let core_offset = if vertex_dequantization.has_quantized_positions() {
+        buffer_builder.append(mesh.quantized_verts.as_slice())
+    } else {
+        buffer_builder.append(mesh.verts.as_slice())
+    };
+    let uv_offset = if vertex_dequantization.has_quantized_uvs() {
+        buffer_builder.append(mesh.quantized_uvs.as_slice())
+    } else {
+        buffer_builder.append(mesh.uvs.as_slice())
+    };
+    let mat_offset = buffer_builder.append(mesh.material_ids.as_slice());
+    let aux_offset = buffer_builder.append(mesh.colors.as_slice());
+    let tangent_offset = buffer_builder.append(mesh.tangents.as_slice());
+    let mat_data_offset = buffer_builder.append(mesh.materials.as_slice());
+    let lod_index_offset = buffer_builder.append(mesh.lod_indices.as_slice());
+    let blas_transform_offset = vertex_dequantization.has_quantized_positions().then(|| {
+        buffer_builder.append(vec![BlasVertexTransform(
+            vertex_dequantization.position_transform(),
+        )])
+    });
+
+    let vertex_data_offset =
+        allocate_vertex_buffer(vertex_buffer_written, buffer_builder.current_offset())?;
+    buffer_builder
+        .upload(device, vertex_buffer, vertex_data_offset)
+        .map_err(|err| device.report_error(err))?;
+
+    let offset = |relative: u64| (vertex_data_offset + relative) as u32;
+
+    let blas = if device.ray_tracing_enabled() {
+        let opaque = !mesh
+            .materials
+            .iter()
+            .any(|mat| mat.is_alpha_blended() || mat.is_alpha_tested());
+
+        Some(create_mesh_blas(
+            device,
+            vertex_buffer,
+            offset(core_offset),
+            blas_transform_offset.map(offset),
+            offset(index_offset),
+            mesh.indices.as_slice(),
+            opaque,
+        )?)
+    } else {
+        None
+    };
+
+    Ok(UploadedMeshGeometry {
+        index_offset: offset(index_offset),
+        core_offset: offset(core_offset),
+        uv_offset: offset(uv_offset),
+        mat_offset: offset(mat_offset),
+        aux_offset: offset(aux_offset),
+        tangent_offset: offset(tangent_offset),
+        mat_data_offset: offset(mat_data_offset),
+        lod_index_offset: offset(lod_index_offset),
+        blas,
+    })
+}
+
+/// Reserves `size` bytes of the vertex buffer, aligned for `BlasVertexTransform`.
+fn allocate_vertex_buffer(vertex_buffer_written: &Mutex<u64>, size: u64) -> anyhow::Result<u64> {
+    let mut written = vertex_buffer_written.lock();
+    let offset = (*written + 15) & !15;
+    anyhow::ensure!(
+        offset + size <= VERTEX_BUFFER_CAPACITY as u64,
+        "vertex buffer full"
+    );
+
+    *written = offset + size;
+    Ok(offset)
+}
+
+fn create_mesh_blas(
+    device: &device::Device,
+    vertex_buffer: &Buffer,
+    vertex_core_offset: u32,
+    // Present for quantized positions
+    transform_offset: Option<u32>,
+    index_offset: u32,
+    indices: &[u32],
+    opaque: bool,
+) -> Result<RayTracingAcceleration, BackendError> {
+    let max_vertex = indices
+        .iter()
+        .copied()
+        .max()
+        .expect("mesh must not be empty");
+
+    device.create_ray_tracing_bottom_acceleration(&mesh_blas_desc(
+        device,
+        vertex_buffer,
+        vertex_core_offset,
+        transform_offset,
+        index_offset,
+        indices.len(),
+        max_vertex,
+        opaque,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn mesh_blas_desc(
+    device: &device::Device,
+    vertex_buffer: &Buffer,
+    vertex_core_offset: u32,
+    transform_offset: Option<u32>,
+    index_offset: u32,
+    index_count: usize,
+    max_vertex: u32,
+    opaque: bool,
+) -> RayTracingBottomAccelerationDesc {
+    let base_da = vertex_buffer.device_address(device);
+
+    let (vertex_format, vertex_stride) = if transform_offset.is_some() {
+        (vk::Format::R16G16B16A16_SNORM, size_of::<QuantizedVertex>())
+    } else {
+        (vk::Format::R32G32B32_SFLOAT, size_of::<PackedVertex>())
+    };
+
+    RayTracingBottomAccelerationDesc {
+        geometries: vec![RayTracingGeometryDesc {
+            geometry_type: RayTracingGeometryType::Triangle,
+            vertex_buffer: base_da + vertex_core_offset as u64,
+            index_buffer: base_da + index_offset as u64,
+            vertex_format,
+            vertex_stride,
+            transform_buffer: transform_offset.map(|offset| base_da + offset as u64),
+            opaque,
+            parts: vec![RayTracingGeometryPart {
+                index_count,
+                index_offset: 0,
+                max_vertex,
+            }],
+        }],
+        allow_update: false,
+    }
+}
+
+/// The render passes whose attachments include frame resources with configurable formats:
+/// the G-buffer raster, forward transparent, and GI downsample passes.
+/// Render passes are cached by format, so switching back and forth is cheap.
+fn create_frame_render_passes(
+    device: &device::Device,
+    formats: RenderTargetFormats,
+) -> (Arc<RenderPass>, Arc<RenderPass>, Arc<RenderPass>) {
+    let mut raster_simple_attachments = vec![
+        // view-space geometry normal; * 2 - 1 to decode
+        RenderPassAttachmentDesc::new(formats.normals.format()).garbage_input(),
+        // gbuffer
+        RenderPassAttachmentDesc::new(vk::Format::R32G32B32A32_SFLOAT).garbage_input(),
+        // velocity
+        RenderPassAttachmentDesc::new(formats.gbuffer.velocity.format()).garbage_input(),
+        // instance index + 1, for picking
+        RenderPassAttachmentDesc::new(vk::Format::R32_UINT),
+    ];
+
+    if formats.gbuffer.material_id {
+        raster_simple_attachments.push(
+            RenderPassAttachmentDesc::new(GbufferLayout::material_id_format()).garbage_input(),
+        );
+    }
+
+    let raster_simple = create_render_pass(
+        device,
+        RenderPassDesc {
+            color_attachments: &raster_simple_attachments,
+            depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+            subpasses: &[],
+        },
+    );
+
+    let forward_transparent = create_render_pass(
+        device,
+        RenderPassDesc {
+            color_attachments: &[
+                // lit scene to blend over
+                RenderPassAttachmentDesc::new(formats.color.format()),
+                // TAA responsive mask
+                RenderPassAttachmentDesc::new(vk::Format::R8_UNORM),
+            ],
+            depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+            subpasses: &[],
+        },
+    );
+
+    let gi_downsample = create_render_pass(
+        device,
+        RenderPassDesc {
+            color_attachments: &[
+                // geometric normal
+                RenderPassAttachmentDesc::new(formats.normals.format()).garbage_input(),
+                // gbuffer
+                RenderPassAttachmentDesc::new(vk::Format::R32G32B32A32_SFLOAT).garbage_input(),
+            ],
+            depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+            subpasses: &[],
+        },
+    );
+
+    (raster_simple, forward_transparent, gi_downsample)
+}
+
 impl WorldRenderer {
     pub(crate) fn new_empty(
         // Internal render resolution, before any upsampling
@@ -345,26 +940,64 @@ impl WorldRenderer {
         temporal_upscale_extent: [u32; 2],
         backend: &RenderBackend,
     ) -> Result<Self, BackendError> {
-        let raster_simple_render_pass = create_render_pass(
+        // Pipelines whose shaders declare `FrameConstants` differently fail to build.
+        kajiya_backend::shader_layout::register_uniform_buffer_layout(
+            2,
+            0,
+            kajiya_backend::struct_layout!(FrameConstants {
+                view_constants,
+                sun_direction,
+                frame_index,
+                delta_time_seconds,
+                sun_angular_radius_cos,
+                triangle_light_count,
+                sun_color_multiplier,
+                sky_ambient,
+                pre_exposure,
+                pre_exposure_prev,
+                pre_exposure_delta,
+                punctual_light_count,
+                render_overrides,
+                rect_light_count,
+                blue_noise_rotation,
+                working_color_space,
+                lighting_change,
+                atmosphere,
+                ircache_grid_center,
+                ircache_cascades,
+            }),
+        );
+
+        let render_pass_formats = RenderTargetFormats::default();
+        render_pass_formats.gbuffer.apply_to_shaders();
+        let (raster_simple_render_pass, forward_transparent_render_pass, gi_downsample_render_pass) =
+            create_frame_render_passes(&backend.device, render_pass_formats);
+
+        let overdraw_render_pass = create_render_pass(
             &*backend.device,
             RenderPassDesc {
                 color_attachments: &[
-                    // view-space geometry normal; * 2 - 1 to decode
-                    RenderPassAttachmentDesc::new(vk::Format::A2R10G10B10_UNORM_PACK32)
-                        .garbage_input(),
-                    // gbuffer
-                    RenderPassAttachmentDesc::new(vk::Format::R32G32B32A32_SFLOAT).garbage_input(),
-                    // velocity
-                    RenderPassAttachmentDesc::new(vk::Format::R16G16B16A16_SFLOAT).garbage_input(),
+                    // fragment count
+                    RenderPassAttachmentDesc::new(vk::Format::R16G16B16A16_SFLOAT),
                 ],
                 depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+                subpasses: &[],
+            },
+        );
+
+        let depth_prepass_render_pass = create_render_pass(
+            &*backend.device,
+            RenderPassDesc {
+                color_attachments: &[],
+                depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
+                subpasses: &[],
             },
         );
 
         let mesh_buffer = backend.device.create_buffer(
             BufferDesc::new_cpu_to_gpu(
                 MAX_GPU_MESHES * size_of::<GpuMesh>(),
-                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
             ),
             "mesh buffer",
             None,
@@ -398,6 +1031,32 @@ impl WorldRenderer {
 
         let bindless_descriptor_set = create_bindless_descriptor_set(backend.device.as_ref());
 
+        let placeholder_images = [
+            [255, 255, 255, 255],
+            [127, 127, 255, 255],
+            // Rough, non-metallic
+            [255, 0, 127, 255],
+            [0, 0, 0, 255],
+            // Flat height map
+            [255, 255, 255, 255],
+        ]
+        .map(|texel: [u8; 4]| {
+            Arc::new(
+                backend
+                    .device
+                    .create_image(
+                        ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1])
+                            .usage(vk::ImageUsageFlags::SAMPLED),
+                        vec![ImageSubResourceData {
+                            data: &texel,
+                            row_pitch: 4,
+                            slice_pitch: 0,
+                        }],
+                    )
+                    .expect("placeholder image"),
+            )
+        });
+
         // `meshes`
         Self::write_descriptor_set_buffer(
             &backend.device.raw,
@@ -422,29 +1081,37 @@ impl WorldRenderer {
             &bindless_texture_sizes,
         );
 
-        let supersample_count = 128;
-        let supersample_offsets = (1..=supersample_count)
-            .map(|i| Vec2::new(radical_inverse(i, 2) - 0.5, radical_inverse(i, 3) - 0.5))
-            .collect();
-        //let supersample_offsets = vec![Vec2::new(0.0, -0.5), Vec2::new(0.0, 0.5)];
-        /*let supersample_offsets = vec![
-            Vec2::new(0.25, 0.25),
-            Vec2::new(0.25, -0.25),
-            Vec2::new(-0.25, 0.25),
-            Vec2::new(-0.25, -0.25),
-        ];*/
-
         let accel_scratch = backend
             .device
             .create_ray_tracing_acceleration_scratch_buffer()?;
 
+        let vertex_buffer = Arc::new(vertex_buffer);
+        let vertex_buffer_written = Arc::new(Mutex::new(0));
+        let load_progress = LoadProgress::default();
+        let mesh_streamer = MeshStreamer::new(
+            backend.device.clone(),
+            vertex_buffer.clone(),
+            vertex_buffer_written.clone(),
+            load_progress.clone(),
+        );
+
         #[cfg(feature = "dlss")]
         let dlss = DlssRenderer::new(backend, render_extent, temporal_upscale_extent);
 
+        #[cfg(feature = "fsr2")]
+        let fsr2 = Fsr2Renderer::new(backend, render_extent, temporal_upscale_extent);
+
         Ok(Self {
             raster_simple_render_pass,
+            forward_transparent_render_pass,
+            gi_downsample_render_pass,
+            overdraw_render_pass,
+            depth_prepass_render_pass,
+            render_pass_formats,
 
             reset_reference_accumulation: false,
+            hdr_captures: Default::default(),
+            picking: GpuPicking::new(&backend.device)?,
             //cube_index_buffer: Arc::new(cube_index_buffer),
             device: backend.device.clone(),
             meshes: Default::default(),
@@ -452,48 +1119,112 @@ impl WorldRenderer {
             instance_handles: Default::default(),
             instance_handle_to_index: Default::default(),
 
+            punctual_lights: Default::default(),
+            next_punctual_light_handle: 0,
+            rect_lights: Default::default(),
+            next_rect_light_handle: 0,
+            decals: Default::default(),
+            next_decal_handle: 0,
+            views: Default::default(),
+            next_view_handle: 0,
+            rendered_views: Default::default(),
+
             mesh_lights: Default::default(),
 
+            material_animations: Default::default(),
+            materials_to_write: Default::default(),
+            material_animation_time: 0.0,
+            mesh_deformations: Default::default(),
+
             mesh_blas: Default::default(),
             tlas: Default::default(),
+            tlas_update: TlasUpdate::Rebuild,
             accel_scratch,
 
             mesh_buffer: Mutex::new(Arc::new(mesh_buffer)),
-            vertex_buffer: Mutex::new(Arc::new(vertex_buffer)),
-            vertex_buffer_written: 0,
+            vertex_buffer: Mutex::new(vertex_buffer),
+            vertex_buffer_written,
             bindless_descriptor_set,
             bindless_images: Default::default(),
             image_luts: Default::default(),
+            atmosphere_in_image_luts: None,
 
             next_bindless_image_id: 0,
+            image_streamer: ImageStreamer::new(backend.device.clone(), load_progress),
+            mesh_streamer,
+            pending_meshes: Default::default(),
+            streamed_textures: Default::default(),
+            mesh_streamed_textures: Default::default(),
+            streamed_texture_by_handle: Default::default(),
+            placeholder_images,
             next_instance_handle: 0,
             bindless_texture_sizes,
 
             rg_debug_hook: None,
             render_mode: RenderMode::Standard,
+            freeze_frame: false,
+            frozen_frame: None,
             frame_idx: 0u32,
+            deterministic: None,
+            deterministic_frame_idx: 0,
             prev_camera_matrices: None,
-
-            supersample_offsets,
+            delta_time_seconds: 0.0,
 
             post: PostProcessRenderer::new(backend.device.as_ref())?,
             ssgi: SsgiRenderer::default(),
-            rtr: RtrRenderer::new(backend.device.as_ref())?,
+            gtao: GtaoRenderer::default(),
+            use_gtao: false,
+            rtao: RtaoRenderer::default(),
+            ao_mode: AmbientOcclusionMode::default(),
+            use_gpu_driven_draws: true,
+            use_occlusion_culling: true,
+            use_depth_prepass: false,
+            show_wireframe: false,
+            hiz: Default::default(),
+            gi_temporal_filter: Default::default(),
+            reactive_mask: Default::default(),
+            blue_noise: BlueNoise::new(backend.device.as_ref())?,
+            debug_text: DebugTextRenderer::new(backend.device.as_ref())?,
+            rtr: RtrRenderer::new(),
             lighting: LightingRenderer::new(),
             ircache: IrcacheRenderer::new(backend.device.as_ref()),
             rtdgi: RtdgiRenderer::default(),
+            gi_mode: GiMode::default(),
+            ddgi: DdgiRenderer::default(),
             taa: TaaRenderer::new(),
             shadow_denoise: ShadowDenoiseRenderer::default(),
+            punctual_shadow_denoise: LocalLightShadowDenoiseRenderer::new(
+                "punctual_shadow_denoise",
+            ),
+            rect_shadow_denoise: LocalLightShadowDenoiseRenderer::new("rect_shadow_denoise"),
             ibl: IblRenderer::default(),
+            environment_probes: Default::default(),
+            light_probes: Default::default(),
+            time_slicer: Default::default(),
+            sky_prefilter_cycle: Default::default(),
+            planar_reflections: Default::default(),
+            water: Default::default(),
+            punctual_shadow_cache: Default::default(),
+            particles: Default::default(),
+            volumetric_fog: VolumetricFogRenderer::default(),
 
             #[cfg(feature = "dlss")]
             dlss,
             #[cfg(feature = "dlss")]
             use_dlss: true,
 
+            #[cfg(feature = "fsr2")]
+            fsr2,
+            #[cfg(feature = "fsr2")]
+            use_fsr2: true,
+
             temporal_upscale_extent,
 
             debug_mode: RenderDebugMode::None,
+            debug_view: DebugView::None,
+            luminance_heatmap: Default::default(),
+            image_stats: Default::default(),
+            custom_passes: Default::default(),
             debug_shading_mode: if backend.device.ray_tracing_enabled() {
                 0
             } else {
@@ -503,13 +1234,34 @@ impl WorldRenderer {
             debug_show_wrc: false,
             ev_shift: 0.0,
             dynamic_exposure: Default::default(),
+            physical_camera: Default::default(),
             contrast: 1.0,
+            output_calibration: Default::default(),
+            output_scaling: Default::default(),
+            working_color_space: Default::default(),
 
-            sun_size_multiplier: 1.0, // Sun as seen from Earth
+            sun_angular_diameter_degrees: EARTH_SUN_ANGULAR_DIAMETER_DEGREES,
             sun_color_multiplier: Vec3::ONE,
+            contact_shadows: ContactShadowParams::default(),
             sky_ambient: Vec3::ZERO,
+            atmosphere: AtmosphereParams::default(),
+            time_of_day: TimeOfDay::default(),
+            lighting_change: 0.0,
+            fog: FogParams::default(),
+            dof: DofParams::default(),
+            motion_blur: MotionBlurParams::default(),
 
             render_overrides: Default::default(),
+            clip_plane: None,
+            render_quality: Default::default(),
+            render_target_formats: Default::default(),
+            bilateral_upsample: Default::default(),
+            gi_temporal: Default::default(),
+            subsurface: Default::default(),
+
+            mesh_lod_max_pixel_error: 1.0,
+            texture_streaming_budget_bytes: 2 << 30,
+            texture_streaming_full_res_distance: 4.0,
 
             exposure_state: Default::default(),
         })
@@ -542,6 +1294,12 @@ impl WorldRenderer {
         let handle = BindlessImageHandle(self.next_bindless_image_id as _);
         self.next_bindless_image_id += 1;
 
+        self.write_bindless_image_view(handle, view);
+
+        handle
+    }
+
+    fn write_bindless_image_view(&self, handle: BindlessImageHandle, view: ImageView) {
         let image_info = vk::DescriptorImageInfo::builder()
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .image_view(view)
@@ -560,76 +1318,329 @@ impl WorldRenderer {
                 .raw
                 .update_descriptor_sets(std::slice::from_ref(&write_descriptor_set), &[]);
         }
+    }
 
-        handle
+    fn write_bindless_texture_size(&mut self, handle: BindlessImageHandle, image: &Image) {
+        bytemuck::checked::cast_slice_mut::<u8, [f32; 4]>(
+            self.bindless_texture_sizes
+                .allocation
+                .mapped_slice_mut()
+                .unwrap(),
+        )[handle.0 as usize] = image.desc.extent_inv_extent_2d();
     }
 
     pub fn add_image_lut(&mut self, computer: impl ComputeImageLut + 'static, id: usize) {
-        self.image_luts
-            .push(ImageLut::new(self.device.as_ref(), Box::new(computer)));
+        let image_lut = ImageLut::new(self.device.as_ref(), Box::new(computer));
 
         let handle = self.add_bindless_image_view(
-            self.image_luts
-                .last()
-                .unwrap()
+            image_lut
                 .backing_image()
                 .view(self.device.as_ref(), &ImageViewDesc::default())
                 .unwrap(),
         );
 
         assert_eq!(handle.0 as usize, id);
+
+        self.image_luts.push((id, image_lut));
     }
 
-    pub fn add_image(&mut self, image: Arc<Image>) -> BindlessImageHandle {
-        let image_size: [f32; 4] = image.desc.extent_inv_extent_2d();
+    /// Imports the LUT added with `id` into this frame's graph, for passes which
+    /// bind it directly. LUTs are computed at the start of `prepare_render_graph`.
+    pub fn import_image_lut(
+        &self,
+        rg: &mut rg::RenderGraph,
+        id: usize,
+    ) -> rg::ReadOnlyHandle<Image> {
+        self.image_luts
+            .iter()
+            .find(|(lut_id, _)| *lut_id == id)
+            .unwrap_or_else(|| panic!("No image LUT with id {}", id))
+            .1
+            .import(rg)
+    }
 
+    pub fn add_image(&mut self, image: Arc<Image>) -> BindlessImageHandle {
         let handle = self.add_bindless_image_view(
             image
                 .view(self.device.as_ref(), &ImageViewDesc::default())
                 .unwrap(),
         );
 
+        self.write_bindless_texture_size(handle, &image);
         self.bindless_images.push(image);
 
-        bytemuck::checked::cast_slice_mut::<u8, [f32; 4]>(
-            self.bindless_texture_sizes
-                .allocation
-                .mapped_slice_mut()
-                .unwrap(),
-        )[handle.0 as usize] = image_size;
-
         handle
     }
 
+    /// Progress of the meshes from `add_mesh_in_background`, and of the textures being streamed
+    /// in the background for meshes added so far.
+    pub fn load_progress(&self) -> LoadProgress {
+        self.image_streamer.progress()
+    }
+
+    /// Bytes of mesh data uploaded so far, and the capacity of the vertex buffer they go to.
+    /// The buffer is allocated whole up front, so the device stats only see the latter.
+    pub fn vertex_buffer_usage(&self) -> (u64, u64) {
+        (
+            *self.vertex_buffer_written.lock(),
+            VERTEX_BUFFER_CAPACITY as u64,
+        )
+    }
+
+    /// Swap the images which finished streaming in for the ones previously bound.
+    fn apply_streamed_images(&mut self) {
+        let loaded: Vec<StreamedImage> = self.image_streamer.drain_loaded().collect();
+
+        for StreamedImage {
+            handle,
+            image,
+            first_mip,
+        } in loaded
+        {
+            self.write_bindless_image_view(
+                handle,
+                image
+                    .view(self.device.as_ref(), &ImageViewDesc::default())
+                    .unwrap(),
+            );
+            self.write_bindless_texture_size(handle, &image);
+
+            let texture = &mut self.streamed_textures[self.streamed_texture_by_handle[&handle]];
+            texture.resident_first_mip = Some(first_mip);
+            texture.pending_first_mip = None;
+
+            // `image` is `None` while a shared placeholder is bound, so those are never released.
+            if let Some(prev_image) = texture.image.replace(image) {
+                self.device.defer_release(prev_image);
+            }
+        }
+    }
+
+    /// Request higher-resolution mips for textures close to the camera, and drop them
+    /// from far-away ones, keeping the streamed mips within `texture_streaming_budget_bytes`.
+    fn update_texture_streaming(&mut self, eye_position: Vec3) {
+        let mut mesh_distances = vec![f32::MAX; self.meshes.len()];
+        for inst in &self.instances {
+            let mesh = &self.meshes[inst.mesh.0];
+            let sphere = mesh.bounding_sphere.transformed(&inst.transform);
+            let distance = (sphere.center.distance(eye_position) - sphere.radius).max(0.0);
+
+            let mesh_distance = &mut mesh_distances[inst.mesh.0];
+            *mesh_distance = mesh_distance.min(distance);
+        }
+
+        let mut texture_distances = vec![f32::MAX; self.streamed_textures.len()];
+        for (mesh_distance, textures) in mesh_distances.iter().zip(&self.mesh_streamed_textures) {
+            for &texture_idx in textures {
+                let texture_distance = &mut texture_distances[texture_idx];
+                *texture_distance = texture_distance.min(*mesh_distance);
+            }
+        }
+
+        // Hand out the budget to the closest textures first.
+        let mut by_distance: Vec<usize> = (0..self.streamed_textures.len()).collect();
+        by_distance.sort_by(|&a, &b| {
+            texture_distances[a]
+                .partial_cmp(&texture_distances[b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut budget_left = self.texture_streaming_budget_bytes;
+        let mut requests_left = MAX_TEXTURE_STREAMING_REQUESTS_PER_FRAME;
+
+        for texture_idx in by_distance {
+            let texture = &mut self.streamed_textures[texture_idx];
+            let tail_size = texture.size_bytes(texture.tail_first_mip);
+
+            let mut first_mip = texture.desired_first_mip(
+                texture_distances[texture_idx],
+                self.texture_streaming_full_res_distance,
+            );
+            while first_mip < texture.tail_first_mip
+                && texture.size_bytes(first_mip) - tail_size > budget_left
+            {
+                first_mip += 1;
+            }
+            budget_left -= texture.size_bytes(first_mip) - tail_size;
+
+            // Wait for the initial load, and for any request in flight.
+            if texture.resident_first_mip.is_none()
+                || texture.pending_first_mip.is_some()
+                || texture.resident_first_mip == Some(first_mip)
+                || requests_left == 0
+            {
+                continue;
+            }
+
+            requests_left -= 1;
+            if let Err(err) = self.image_streamer.request_mips(texture, first_mip) {
+                error!(
+                    "Failed to stream image {:8.8x}: {:#}",
+                    texture.asset.identity(),
+                    err
+                );
+            }
+        }
+    }
+
     pub fn add_mesh(
         &mut self,
         mesh: &'static PackedTriMesh::Flat,
         opts: AddMeshOptions,
     ) -> MeshHandle {
-        let mesh_idx = self.meshes.len();
+        let handle = self.add_pending_mesh();
+        let geometry = upload_mesh_geometry(
+            &self.device,
+            &self.vertex_buffer.lock(),
+            &self.vertex_buffer_written,
+            mesh,
+        )
+        .expect("mesh upload");
+
+        self.finish_mesh(handle, mesh, opts, geometry);
+        handle
+    }
+
+    /// Like `add_mesh`, but `load` runs on a background thread, e.g. to bake the mesh,
+    /// and so do the upload of its geometry and the build of its BLAS; see `load_progress`.
+    ///
+    /// The mesh can be instanced right away, but stays empty until it's loaded at the start
+    /// of a later frame; see `is_mesh_loaded`. If loading fails, the error is logged,
+    /// and the mesh stays empty.
+    pub fn add_mesh_in_background(
+        &mut self,
+        load: impl FnOnce() -> anyhow::Result<&'static PackedTriMesh::Flat> + Send + 'static,
+        opts: AddMeshOptions,
+    ) -> MeshHandle {
+        let handle = self.add_pending_mesh();
+        if let Err(err) = self.mesh_streamer.request(handle, Box::new(load), opts) {
+            error!("Failed to load mesh {:?}: {:#}", handle, err);
+        }
+
+        handle
+    }
+
+    /// Whether a mesh from `add_mesh_in_background` has been loaded. Meshes added
+    /// any other way are loaded right away.
+    pub fn is_mesh_loaded(&self, mesh: MeshHandle) -> bool {
+        !self.pending_meshes.contains(&mesh)
+    }
+
+    // Reserves a slot in the per-mesh data, which stays empty until `finish_mesh` fills it.
+    fn add_pending_mesh(&mut self) -> MeshHandle {
+        let handle = MeshHandle(self.meshes.len());
+        assert!(handle.0 < MAX_GPU_MESHES, "too many meshes");
+
+        self.write_gpu_mesh(
+            handle,
+            GpuMesh {
+                vertex_core_offset: 0,
+                vertex_uv_offset: 0,
+                vertex_mat_offset: 0,
+                vertex_aux_offset: 0,
+                vertex_tangent_offset: 0,
+                mat_data_offset: 0,
+                index_offset: 0,
+                vertex_prev_core_offset: 0,
+                curve_tube_sides: 0,
+                vertex_dequantization: Default::default(),
+            },
+        );
+
+        self.meshes.push(UploadedTriMesh {
+            index_buffer_offset: 0,
+            index_count: 0,
+            lods: Vec::new(),
+            bounding_sphere: BoundingSphere {
+                center: Vec3::ZERO,
+                radius: 0.0,
+            },
+            has_alpha_blend: false,
+            double_sided: false,
+            has_subsurface: false,
+            materials: Vec::new(),
+            material_data_offset: 0,
+            vertex_count: 0,
+        });
+        // Instances of a mesh without a BLAS are inactive in the TLAS.
+        self.mesh_blas.push(None);
+        self.mesh_streamed_textures.push(Vec::new());
+        self.mesh_lights.push(MeshLightSet {
+            lights: Vec::new(),
+            punctual_lights: Vec::new(),
+        });
+        self.pending_meshes.insert(handle);
+
+        handle
+    }
+
+    fn write_gpu_mesh(&self, mesh: MeshHandle, gpu_mesh: GpuMesh) {
+        unsafe {
+            let mesh_buffer = self.mesh_buffer.lock();
+            let mesh_buffer_dst =
+                mesh_buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut GpuMesh;
+            *mesh_buffer_dst.add(mesh.0) = gpu_mesh;
+        }
+    }
+
+    /// Fill the slot of a mesh whose geometry has been uploaded, and start streaming its textures.
+    fn finish_mesh(
+        &mut self,
+        handle: MeshHandle,
+        mesh: &'static PackedTriMesh::Flat,
+        opts: AddMeshOptions,
+        geometry: UploadedMeshGeometry,
+    ) {
         let mut unique_images: Vec<AssetRef<GpuImage::Flat>> = mesh.maps.as_slice().to_vec();
         unique_images.sort();
         unique_images.dedup();
 
-        let loaded_images = {
-            let device = self.device.clone();
-            easy_parallel::Parallel::new()
-                .each(unique_images.iter(), |&asset| {
-                    load_gpu_image_asset(device, asset)
-                })
-                .run()
-        };
-        /*let loaded_images = {
-            let device = self.device.clone();
-            unique_images
-                .iter()
-                .map(|&asset| load_gpu_image_asset(device.clone(), asset))
-                .collect::<Vec<_>>()
-        };*/
-        let loaded_images = loaded_images.into_iter().map(|img| self.add_image(img));
+        // Pick a placeholder for each image based on the first material slot it's used in.
+        let mut image_placeholder_idx: HashMap<AssetRef<GpuImage::Flat>, usize> = HashMap::new();
+        for mat in mesh.materials.iter() {
+            for (slot, &map) in mat.maps.iter().enumerate() {
+                image_placeholder_idx
+                    .entry(mesh.maps[map as usize])
+                    .or_insert(slot);
+            }
+        }
 
-        let material_map_to_image: HashMap<AssetRef<GpuImage::Flat>, BindlessImageHandle> =
-            unique_images.into_iter().zip(loaded_images).collect();
+        // Images are streamed in the background, and rendered with placeholders until they arrive.
+        // Only their smallest mips are loaded at first; the rest follow based on camera distance.
+        let mut material_map_to_image: HashMap<AssetRef<GpuImage::Flat>, BindlessImageHandle> =
+            HashMap::new();
+        let mut mesh_streamed_textures = Vec::new();
+        for asset in unique_images {
+            let placeholder = self.placeholder_images
+                [image_placeholder_idx.get(&asset).copied().unwrap_or(0)]
+            .clone();
+
+            let handle = self.add_image(placeholder);
+            material_map_to_image.insert(asset, handle);
+
+            let texture = StreamedTexture::new(asset, handle).and_then(|mut texture| {
+                let first_mip = texture.tail_first_mip;
+                self.image_streamer.request(&mut texture, first_mip)?;
+                Ok(texture)
+            });
+
+            match texture {
+                Ok(texture) => {
+                    let texture_idx = self.streamed_textures.len();
+                    self.streamed_texture_by_handle.insert(handle, texture_idx);
+                    self.streamed_textures.push(texture);
+                    mesh_streamed_textures.push(texture_idx);
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to stream image {:8.8x}: {:#}",
+                        asset.identity(),
+                        err
+                    );
+                }
+            }
+        }
+        self.mesh_streamed_textures[handle.0] = mesh_streamed_textures;
 
         let mut materials = mesh.materials.as_slice().to_vec();
         {
@@ -654,89 +1665,58 @@ impl WorldRenderer {
             }
         }
 
-        let vertex_data_offset = self.vertex_buffer_written as u32;
-
-        let mut buffer_builder = BufferBuilder::new();
-        let vertex_index_offset =
-            buffer_builder.append(mesh.indices.as_slice()) as u32 + vertex_data_offset;
-        let vertex_core_offset =
-            buffer_builder.append(mesh.verts.as_slice()) as u32 + vertex_data_offset;
-        let vertex_uv_offset =
-            buffer_builder.append(mesh.uvs.as_slice()) as u32 + vertex_data_offset;
-        let vertex_mat_offset =
-            buffer_builder.append(mesh.material_ids.as_slice()) as u32 + vertex_data_offset;
-        let vertex_aux_offset =
-            buffer_builder.append(mesh.colors.as_slice()) as u32 + vertex_data_offset;
-        let vertex_tangent_offset =
-            buffer_builder.append(mesh.tangents.as_slice()) as u32 + vertex_data_offset;
-        let mat_data_offset = buffer_builder.append(materials) as u32 + vertex_data_offset;
-
-        let total_buffer_size = buffer_builder.current_offset();
-        let mut vertex_buffer = self.vertex_buffer.lock();
-        buffer_builder
-            .upload(
-                self.device.as_ref(),
-                Arc::get_mut(&mut *vertex_buffer).expect("refs may not be retained"),
-                self.vertex_buffer_written,
-            )
-            .map_err(|err| self.device.report_error(err))
-            .unwrap();
-        self.vertex_buffer_written += total_buffer_size;
+        let has_alpha_blend = materials.iter().any(MeshMaterial::is_alpha_blended);
+        let double_sided = materials.iter().any(MeshMaterial::is_double_sided);
+        let has_subsurface = materials.iter().any(MeshMaterial::has_subsurface);
+
+        self.write_gpu_mesh(
+            handle,
+            GpuMesh {
+                vertex_core_offset: geometry.core_offset,
+                vertex_uv_offset: geometry.uv_offset,
+                vertex_mat_offset: geometry.mat_offset,
+                vertex_aux_offset: geometry.aux_offset,
+                vertex_tangent_offset: geometry.tangent_offset,
+                mat_data_offset: geometry.mat_data_offset,
+                index_offset: geometry.index_offset,
+                vertex_prev_core_offset: geometry.core_offset,
+                curve_tube_sides: 0,
+                vertex_dequantization: mesh.vertex_dequantization(),
+            },
+        );
 
-        let mesh_buffer_dst = unsafe {
-            let mut mesh_buffer = self.mesh_buffer.lock();
-            let mesh_buffer = Arc::get_mut(&mut *mesh_buffer).expect("refs may not be retained");
-            let mesh_buffer_dst =
-                mesh_buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut GpuMesh;
-            std::slice::from_raw_parts_mut(mesh_buffer_dst, MAX_GPU_MESHES)
-        };
+        let vertex_count = mesh.vertex_count();
+        let bounding_sphere = BoundingSphere::from_points(
+            (0..vertex_count).map(|idx| Vec3::from(mesh.vertex_position(idx))),
+        );
 
-        if self.device.ray_tracing_enabled() {
-            let base_da = vertex_buffer.device_address(&self.device);
-            let vertex_buffer_da = base_da + vertex_core_offset as u64;
-            let index_buffer_da = base_da + vertex_index_offset as u64;
+        // The materials were uploaded as baked, without the bindless ids of their maps.
+        self.materials_to_write
+            .extend((0..materials.len()).map(|material| (handle, material)));
 
-            let blas = self
-                .device
-                .create_ray_tracing_bottom_acceleration(&RayTracingBottomAccelerationDesc {
-                    geometries: vec![RayTracingGeometryDesc {
-                        geometry_type: RayTracingGeometryType::Triangle,
-                        vertex_buffer: vertex_buffer_da,
-                        index_buffer: index_buffer_da,
-                        vertex_format: vk::Format::R32G32B32_SFLOAT,
-                        vertex_stride: size_of::<PackedVertex>(),
-                        parts: vec![RayTracingGeometryPart {
-                            index_count: mesh.indices.len(),
-                            index_offset: 0,
-                            max_vertex: mesh
-                                .indices
-                                .as_slice()
-                                .iter()
-                                .copied()
-                                .max()
-                                .expect("mesh must not be empty"),
-                        }],
-                    }],
+        self.meshes[handle.0] = UploadedTriMesh {
+            index_buffer_offset: geometry.index_offset as u64,
+            index_count: mesh.indices.len() as _,
+            lods: mesh
+                .lods
+                .as_slice()
+                .iter()
+                .map(|lod| UploadedMeshLod {
+                    index_buffer_offset: geometry.lod_index_offset as u64
+                        + (lod.index_offset as usize * size_of::<u32>()) as u64,
+                    index_count: lod.index_count,
+                    error: lod.error,
                 })
-                .expect("blas");
-
-            self.mesh_blas.push(Arc::new(blas));
-        }
-
-        mesh_buffer_dst[mesh_idx] = GpuMesh {
-            vertex_core_offset,
-            vertex_uv_offset,
-            vertex_mat_offset,
-            vertex_aux_offset,
-            vertex_tangent_offset,
-            mat_data_offset,
-            index_offset: vertex_index_offset,
+                .collect(),
+            bounding_sphere,
+            has_alpha_blend,
+            double_sided,
+            has_subsurface,
+            materials,
+            material_data_offset: geometry.mat_data_offset as u64,
+            vertex_count: vertex_count as u32,
         };
-
-        self.meshes.push(UploadedTriMesh {
-            index_buffer_offset: vertex_index_offset as u64,
-            index_count: mesh.indices.len() as _,
-        });
+        self.mesh_blas[handle.0] = geometry.blas.map(Arc::new);
 
         let mesh_lights = if opts.use_lights {
             let emissive_materials = mesh
@@ -752,9 +1732,9 @@ impl WorldRenderer {
                     continue;
                 }
 
-                let v0 = mesh.verts[indices[0] as usize].pos;
-                let v1 = mesh.verts[indices[1] as usize].pos;
-                let v2 = mesh.verts[indices[2] as usize].pos;
+                let v0 = mesh.vertex_position(indices[0] as usize);
+                let v1 = mesh.vertex_position(indices[1] as usize);
+                let v2 = mesh.vertex_position(indices[2] as usize);
                 let radiance = mesh.materials[mat_idx].emissive;
 
                 mesh_lights.push(TriangleLight {
@@ -768,14 +1748,193 @@ impl WorldRenderer {
             Vec::new()
         };
 
-        self.mesh_lights.push(MeshLightSet {
+        self.mesh_lights[handle.0] = MeshLightSet {
             lights: mesh_lights,
+            punctual_lights: mesh.lights.as_slice().to_vec(),
+        };
+
+        if self.pending_meshes.remove(&handle) {
+            self.finish_pending_mesh(handle);
+        }
+    }
+
+    // Catch up on what was requested of a mesh while it was loading.
+    fn finish_pending_mesh(&mut self, handle: MeshHandle) {
+        let material_count = self.meshes[handle.0].materials.len();
+        self.material_animations.retain(|&(mesh, material), _| {
+            let valid = mesh != handle || material < material_count;
+            if !valid {
+                error!(
+                    "Material animation of {:?}: material {} out of {}",
+                    mesh, material, material_count
+                );
+            }
+            valid
+        });
+
+        // Its instances become active in the TLAS, which a refit can't do.
+        self.tlas_update = TlasUpdate::Rebuild;
+
+        if self
+            .instances
+            .iter()
+            .any(|inst| inst.mesh == handle && inst.is_static)
+        {
+            self.punctual_shadow_cache.invalidate();
+        }
+    }
+
+    /// Set up the meshes whose loading finished on the `MeshStreamer` threads.
+    fn apply_streamed_meshes(&mut self) {
+        let loaded: Vec<StreamedMesh> = self.mesh_streamer.drain_loaded().collect();
+
+        for StreamedMesh {
+            handle,
+            mesh,
+            opts,
+            geometry,
+        } in loaded
+        {
+            self.finish_mesh(handle, mesh, opts, geometry);
+        }
+    }
+
+    /// Adds a mesh made of `curves`, such as hair, wires, or splines, to be spawned
+    /// with `add_instance` like any other.
+    ///
+    /// The curves are rasterized as ribbons turned towards the eye, and shaded as round.
+    /// Rays hit six-sided tubes around them instead. Strands thinner
+    /// than a pixel break up, and rely on temporal anti-aliasing to resolve.
+    ///
+    /// The tube vertices can be replaced with `set_mesh_vertices`, in the order they're
+    /// generated in: a ring per point, of each curve with at least two points.
+    pub fn add_curves(&mut self, curves: &[Curve], material: CurveMaterial) -> MeshHandle {
+        let geometry = CurveGeometry::tessellate(curves);
+        assert!(!geometry.verts.is_empty(), "no curve has two points");
+
+        let mesh_idx = self.meshes.len();
+
+        // Flat normals and heights, and white elsewhere, so that the factors alone apply.
+        let white = self.add_image(self.placeholder_images[0].clone()).0;
+        let flat_normal = self.add_image(self.placeholder_images[1].clone()).0;
+        let flat_height = self.add_image(self.placeholder_images[4].clone()).0;
+
+        const DEFAULT_MAP_TRANSFORM: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+        let materials = vec![MeshMaterial {
+            base_color_mult: [
+                material.base_color[0],
+                material.base_color[1],
+                material.base_color[2],
+                1.0,
+            ],
+            maps: [flat_normal, white, white, white, flat_height],
+            roughness_mult: material.roughness,
+            metalness_factor: material.metalness,
+            emissive: material.emissive,
+            // The ribbons face either way.
+            flags: MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED,
+            map_transforms: [DEFAULT_MAP_TRANSFORM; 4],
+            ior: 1.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            transmission: 0.0,
+            anisotropy: material.anisotropy,
+            anisotropy_rotation: 0.0,
+            alpha_cutoff: 0.5,
+            subsurface: 0.0,
+            subsurface_profile: 0,
+            parallax_scale: 0.0,
+        }];
+
+        let bounding_sphere =
+            BoundingSphere::from_points(geometry.verts.iter().map(|v| Vec3::from(v.pos)));
+        let vertex_count = geometry.verts.len() as u32;
+        let ribbon_index_count = geometry.ribbon_indices.len() as u32;
+
+        let mut buffer_builder = BufferBuilder::new();
+        let vertex_index_offset = buffer_builder.append(geometry.tube_indices.clone());
+        let ribbon_index_offset = buffer_builder.append(geometry.ribbon_indices);
+        let vertex_core_offset = buffer_builder.append(geometry.verts);
+        let vertex_uv_offset = buffer_builder.append(geometry.uvs);
+        let vertex_mat_offset = buffer_builder.append(geometry.material_ids);
+        let vertex_tangent_offset = buffer_builder.append(geometry.tangents);
+        let mat_data_offset = buffer_builder.append(materials.clone());
+
+        let vertex_buffer = self.vertex_buffer.lock().clone();
+        let vertex_data_offset =
+            allocate_vertex_buffer(&self.vertex_buffer_written, buffer_builder.current_offset())
+                .expect("curve upload");
+        buffer_builder
+            .upload(self.device.as_ref(), &vertex_buffer, vertex_data_offset)
+            .map_err(|err| self.device.report_error(err))
+            .unwrap();
+
+        let offset = |relative: u64| (vertex_data_offset + relative) as u32;
+        let vertex_index_offset = offset(vertex_index_offset);
+        let ribbon_index_offset = offset(ribbon_index_offset);
+        let vertex_core_offset = offset(vertex_core_offset);
+        let mat_data_offset = offset(mat_data_offset);
+
+        let blas = self.device.ray_tracing_enabled().then(|| {
+            Arc::new(
+                create_mesh_blas(
+                    &self.device,
+                    &vertex_buffer,
+                    vertex_core_offset,
+                    None,
+                    vertex_index_offset,
+                    &geometry.tube_indices,
+                    true,
+                )
+                .expect("blas"),
+            )
+        });
+        self.mesh_blas.push(blas);
+
+        self.write_gpu_mesh(
+            MeshHandle(mesh_idx),
+            GpuMesh {
+                vertex_core_offset,
+                vertex_uv_offset: offset(vertex_uv_offset),
+                vertex_mat_offset: offset(vertex_mat_offset),
+                vertex_aux_offset: 0,
+                vertex_tangent_offset: offset(vertex_tangent_offset),
+                mat_data_offset,
+                index_offset: vertex_index_offset,
+                vertex_prev_core_offset: vertex_core_offset,
+                curve_tube_sides: CURVE_TUBE_SIDES as u32,
+                vertex_dequantization: Default::default(),
+            },
+        );
+
+        self.meshes.push(UploadedTriMesh {
+            index_buffer_offset: ribbon_index_offset as u64,
+            index_count: ribbon_index_count,
+            lods: Vec::new(),
+            bounding_sphere,
+            has_alpha_blend: false,
+            double_sided: true,
+            has_subsurface: false,
+            materials,
+            material_data_offset: mat_data_offset as u64,
+            vertex_count,
+        });
+
+        self.mesh_streamed_textures.push(Vec::new());
+        self.mesh_lights.push(MeshLightSet {
+            lights: Vec::new(),
+            punctual_lights: Vec::new(),
         });
 
         MeshHandle(mesh_idx)
     }
 
+    /// Spawn an instance of a mesh. It's picked up by the draw lists, the TLAS,
+    /// and per-instance GPU buffers starting with the next rendered frame.
     pub fn add_instance(&mut self, mesh: MeshHandle, transform: Affine3A) -> InstanceHandle {
+        assert!(mesh.0 < self.meshes.len(), "no such mesh");
+
         let handle = self.next_instance_handle;
         self.next_instance_handle += 1;
         let handle = InstanceHandle(handle);
@@ -787,21 +1946,26 @@ impl WorldRenderer {
             prev_transform: transform,
             mesh,
             dynamic_parameters: InstanceDynamicParameters::default(),
+            ray_visibility: RayVisibility::default(),
+            is_static: false,
         });
         self.instance_handles.push(handle);
 
         assert_eq!(self.instances.len(), self.instance_handles.len());
 
         self.instance_handle_to_index.insert(handle, index);
+        self.tlas_update = TlasUpdate::Rebuild;
 
         handle
     }
 
+    /// Despawn an instance. The handle becomes invalid, and must not be used afterwards.
     pub fn remove_instance(&mut self, inst: InstanceHandle) {
         let index = self
             .instance_handle_to_index
             .remove(&inst)
             .expect("no such instance");
+        self.invalidate_static_shadows(index);
         self.instances.swap_remove(index);
         self.instance_handles.swap_remove(index);
 
@@ -810,11 +1974,764 @@ impl WorldRenderer {
         if let Some(new_handle) = self.instance_handles.get(index).copied() {
             self.instance_handle_to_index.insert(new_handle, index);
         }
+
+        self.tlas_update = TlasUpdate::Rebuild;
     }
 
+    /// Move an instance. Can be called every frame; the transform from the previous frame
+    /// is kept for motion vectors.
     pub fn set_instance_transform(&mut self, inst: InstanceHandle, transform: Affine3A) {
         let index = self.instance_handle_to_index[&inst];
         self.instances[index].transform = transform;
+        self.invalidate_static_shadows(index);
+        self.tlas_update = self.tlas_update.max(TlasUpdate::Refit);
+    }
+
+    /// Like `set_instance_transform`, but without any motion this frame,
+    /// so that a sudden jump doesn't smear in temporal filters.
+    pub fn teleport_instance(&mut self, inst: InstanceHandle, transform: Affine3A) {
+        let index = self.instance_handle_to_index[&inst];
+        self.instances[index].transform = transform;
+        self.instances[index].prev_transform = transform;
+        self.invalidate_static_shadows(index);
+        self.tlas_update = self.tlas_update.max(TlasUpdate::Refit);
+    }
+
+    /// Point an instance at a different mesh, e.g. after the mesh was reloaded.
+    pub fn set_instance_mesh(&mut self, inst: InstanceHandle, mesh: MeshHandle) {
+        let index = self.instance_handle_to_index[&inst];
+        self.instances[index].mesh = mesh;
+        self.invalidate_static_shadows(index);
+        self.tlas_update = TlasUpdate::Rebuild;
+    }
+
+    /// Applied to the TLAS at the start of the next frame, with a refit.
+    pub fn set_instance_ray_visibility(&mut self, inst: InstanceHandle, visibility: RayVisibility) {
+        let index = self.instance_handle_to_index[&inst];
+        self.instances[index].ray_visibility = visibility;
+        self.invalidate_static_shadows(index);
+        self.tlas_update = self.tlas_update.max(TlasUpdate::Refit);
+    }
+
+    /// Static instances have their shadows cached for punctual lights; see `PunctualShadowCache`.
+    /// Changing one afterwards is allowed, but retraces the cached shadows of all the lights.
+    pub fn set_instance_static(&mut self, inst: InstanceHandle, is_static: bool) {
+        let index = self.instance_handle_to_index[&inst];
+        if self.instances[index].is_static != is_static {
+            self.instances[index].is_static = is_static;
+            self.punctual_shadow_cache.invalidate();
+            self.tlas_update = self.tlas_update.max(TlasUpdate::Refit);
+        }
+    }
+
+    fn invalidate_static_shadows(&mut self, index: usize) {
+        if self.instances[index].is_static {
+            self.punctual_shadow_cache.invalidate();
+        }
+    }
+
+    /// Add a light which isn't part of any mesh. Its position and direction are in world space.
+    pub fn add_punctual_light(&mut self, light: PunctualLight) -> PunctualLightHandle {
+        let handle = PunctualLightHandle(self.next_punctual_light_handle);
+        self.next_punctual_light_handle += 1;
+
+        self.punctual_lights.push((handle, light));
+
+        handle
+    }
+
+    pub fn remove_punctual_light(&mut self, light: PunctualLightHandle) {
+        let index = self
+            .punctual_lights
+            .iter()
+            .position(|(handle, _)| *handle == light)
+            .expect("no such light");
+        self.punctual_lights.swap_remove(index);
+    }
+
+    pub fn set_punctual_light(&mut self, light: PunctualLightHandle, value: PunctualLight) {
+        let (_, dst) = self
+            .punctual_lights
+            .iter_mut()
+            .find(|(handle, _)| *handle == light)
+            .expect("no such light");
+        *dst = value;
+    }
+
+    pub fn add_rect_light(&mut self, light: RectLight) -> RectLightHandle {
+        let handle = RectLightHandle(self.next_rect_light_handle);
+        self.next_rect_light_handle += 1;
+
+        self.rect_lights.push((handle, light));
+
+        handle
+    }
+
+    pub fn remove_rect_light(&mut self, light: RectLightHandle) {
+        let index = self
+            .rect_lights
+            .iter()
+            .position(|(handle, _)| *handle == light)
+            .expect("no such light");
+        self.rect_lights.swap_remove(index);
+    }
+
+    pub fn set_rect_light(&mut self, light: RectLightHandle, value: RectLight) {
+        let (_, dst) = self
+            .rect_lights
+            .iter_mut()
+            .find(|(handle, _)| *handle == light)
+            .expect("no such light");
+        *dst = value;
+    }
+
+    /// Move, rotate, or resize a rect light; see `RectLight::transform`.
+    pub fn set_rect_light_transform(&mut self, light: RectLightHandle, transform: Affine3A) {
+        let (_, dst) = self
+            .rect_lights
+            .iter_mut()
+            .find(|(handle, _)| *handle == light)
+            .expect("no such light");
+        dst.transform = transform;
+    }
+
+    /// Add a decal, which gets applied on top of the ones added before it.
+    pub fn add_decal(&mut self, decal: Decal) -> DecalHandle {
+        let handle = DecalHandle(self.next_decal_handle);
+        self.next_decal_handle += 1;
+
+        self.decals.push((handle, decal));
+
+        handle
+    }
+
+    pub fn remove_decal(&mut self, decal: DecalHandle) {
+        let index = self
+            .decals
+            .iter()
+            .position(|(handle, _)| *handle == decal)
+            .expect("no such decal");
+
+        // Not `swap_remove`, as the order matters
+        self.decals.remove(index);
+    }
+
+    pub fn set_decal(&mut self, decal: DecalHandle, value: Decal) {
+        let (_, dst) = self
+            .decals
+            .iter_mut()
+            .find(|(handle, _)| *handle == decal)
+            .expect("no such decal");
+        *dst = value;
+    }
+
+    /// Zero until the mesh is loaded; see `is_mesh_loaded`.
+    pub fn mesh_material_count(&self, mesh: MeshHandle) -> usize {
+        self.meshes[mesh.0].materials.len()
+    }
+
+    /// Animates a material of a mesh, or stops animating it with `None`; see `MaterialAnimation`.
+    ///
+    /// `material` indexes the mesh's materials, in the order of its source asset.
+    /// For meshes still loading, it's checked once they're loaded, and the animation
+    /// is dropped if there's no such material.
+    pub fn set_material_animation(
+        &mut self,
+        mesh: MeshHandle,
+        material: usize,
+        animation: Option<MaterialAnimation>,
+    ) {
+        assert!(
+            !self.is_mesh_loaded(mesh) || material < self.meshes[mesh.0].materials.len(),
+            "no such material"
+        );
+
+        if let Some(animation) = animation {
+            self.material_animations.insert((mesh, material), animation);
+        } else if self.material_animations.remove(&(mesh, material)).is_some()
+            && self.is_mesh_loaded(mesh)
+        {
+            self.materials_to_write.push((mesh, material));
+        }
+    }
+
+    pub fn material_params(&self, material: MaterialHandle) -> MaterialParams {
+        MaterialParams::from_material(&self.meshes[material.mesh.0].materials[material.index])
+    }
+
+    /// Rewrites just this material in the GPU buffers at the start of the next frame, for live
+    /// tweaking; the mesh, its instances, and the acceleration structures are left alone.
+    /// Affects all instances of the mesh, and animations apply on top of the new parameters.
+    ///
+    /// Emissive triangle lights created for the mesh don't follow the new emission.
+    pub fn update_material(&mut self, material: MaterialHandle, params: MaterialParams) {
+        let mesh = &mut self.meshes[material.mesh.0];
+        params.apply_to(
+            mesh.materials
+                .get_mut(material.index)
+                .expect("no such material"),
+        );
+        mesh.has_subsurface = mesh.materials.iter().any(MeshMaterial::has_subsurface);
+
+        if !self
+            .materials_to_write
+            .contains(&(material.mesh, material.index))
+        {
+            self.materials_to_write
+                .push((material.mesh, material.index));
+        }
+    }
+
+    fn update_materials(&mut self, rg: &mut rg::RenderGraph) {
+        if self.material_animations.is_empty() && self.materials_to_write.is_empty() {
+            return;
+        }
+
+        // The delta of the previous frame, as the frame constants aren't prepared yet.
+        self.material_animation_time += self.delta_time_seconds;
+
+        let meshes = &self.meshes;
+        let material_location = |mesh: MeshHandle, material: usize| {
+            let mesh = &meshes[mesh.0];
+            let offset = mesh.material_data_offset + (material * size_of::<MeshMaterial>()) as u64;
+            (offset, &mesh.materials[material])
+        };
+
+        let mut updates: Vec<(u64, MeshMaterial)> = self
+            .materials_to_write
+            .drain(..)
+            .map(|(mesh, material)| {
+                let (offset, material) = material_location(mesh, material);
+                (offset, *material)
+            })
+            .collect();
+
+        let pending_meshes = &self.pending_meshes;
+        updates.extend(
+            self.material_animations
+                .iter()
+                .filter(|((mesh, _), _)| !pending_meshes.contains(mesh))
+                .map(|(&(mesh, material), animation)| {
+                    let (offset, material) = material_location(mesh, material);
+                    (
+                        offset,
+                        animation.apply(material, self.material_animation_time),
+                    )
+                }),
+        );
+
+        let writes = updates
+            .into_iter()
+            .map(|(offset, material)| (offset, as_byte_slice(&material).to_vec()))
+            .collect();
+
+        write_buffer(
+            rg,
+            "material updates",
+            self.vertex_buffer.lock().clone(),
+            writes,
+        );
+    }
+
+    /// Replaces the positions and normals of a mesh's vertices from the next frame on, for skinning,
+    /// morph targets, cloth, and such, evaluated by the caller. Affects all instances of the mesh.
+    ///
+    /// The previous frame's vertices are kept around, so that motion vectors follow the deformation,
+    /// and TAA and the denoisers don't ghost. With ray tracing, the mesh's BLAS is rebuilt for updates
+    /// on the first call, and refit to the new vertices every frame they change. Refitting keeps
+    /// the original topology, so deformations which move triangles far apart slow down tracing.
+    /// Culling bounds follow the new vertices, covering the previous frame's too.
+    ///
+    /// `tangents` (xyz, and the bitangent sign in w) replace those used for normal mapping
+    /// and anisotropy. With `None`, the ones last set, or else the baked ones stay,
+    /// which only suits deformations that hardly rotate the surface.
+    ///
+    /// `verts` and `tangents` must match the vertex count and order of the mesh, which must not
+    /// have been baked with quantized positions. For skinning on the GPU instead, see `set_mesh_skin`.
+    pub fn set_mesh_vertices(
+        &mut self,
+        mesh: MeshHandle,
+        verts: Vec<PackedVertex>,
+        tangents: Option<Vec<[f32; 4]>>,
+    ) {
+        assert!(self.is_mesh_loaded(mesh), "mesh is still loading");
+        let vertex_count = self.meshes[mesh.0].vertex_count as usize;
+        assert_eq!(verts.len(), vertex_count, "vertex count mismatch");
+
+        let vertex_buffer_written = self.vertex_buffer_written.clone();
+        let deformation = self.mesh_deformation(mesh);
+        assert!(
+            deformation.skinning.is_none(),
+            "skinned meshes are deformed with set_mesh_joint_matrices"
+        );
+        deformation.pending_verts = Some(verts);
+
+        if let Some(tangents) = tangents {
+            assert_eq!(tangents.len(), vertex_count, "tangent count mismatch");
+
+            deformation.tangent_region.get_or_insert_with(|| {
+                allocate_vertex_buffer(
+                    &vertex_buffer_written,
+                    (vertex_count * size_of::<[f32; 4]>()) as u64,
+                )
+                .expect("mesh deformation") as u32
+            });
+            deformation.pending_tangents = Some(tangents);
+        }
+    }
+
+    /// Skins a mesh with linear blend skinning on the GPU, from the next frame on, using the
+    /// matrices passed to `set_mesh_joint_matrices`. Otherwise works like `set_mesh_vertices`,
+    /// the baked vertices and tangents being the bind pose.
+    ///
+    /// Each vertex is influenced by four joints, indexing the joint matrices. The weights
+    /// are normalized. Can be called again to replace the skin.
+    pub fn set_mesh_skin(
+        &mut self,
+        mesh: MeshHandle,
+        joints: Vec<[u16; 4]>,
+        weights: Vec<[f32; 4]>,
+    ) {
+        assert!(self.is_mesh_loaded(mesh), "mesh is still loading");
+        let vertex_count = self.meshes[mesh.0].vertex_count as usize;
+        assert_eq!(joints.len(), vertex_count, "joint count mismatch");
+        assert_eq!(weights.len(), vertex_count, "weight count mismatch");
+
+        let skin: Vec<SkinVertex> = joints
+            .iter()
+            .zip(&weights)
+            .map(|(&joints, &weights)| SkinVertex { joints, weights })
+            .collect();
+        let joint_count = joints.iter().flatten().copied().max().unwrap_or(0) as u32 + 1;
+
+        let vertex_buffer_written = self.vertex_buffer_written.clone();
+        let deformation = self.mesh_deformation(mesh);
+        assert!(
+            deformation.pending_verts.is_none(),
+            "mesh is deformed with set_mesh_vertices"
+        );
+
+        let skin_offset = deformation.skinning.as_ref().map_or_else(
+            || {
+                allocate_vertex_buffer(
+                    &vertex_buffer_written,
+                    (vertex_count * size_of::<SkinVertex>()) as u64,
+                )
+                .expect("mesh skin") as u32
+            },
+            |skinning| skinning.skin_offset,
+        );
+
+        if deformation.rest_tangent_offset != 0 {
+            deformation.tangent_region.get_or_insert_with(|| {
+                allocate_vertex_buffer(
+                    &vertex_buffer_written,
+                    (vertex_count * size_of::<[f32; 4]>()) as u64,
+                )
+                .expect("mesh skin") as u32
+            });
+        }
+
+        deformation.skinning = Some(MeshSkinning {
+            skin_offset,
+            joint_count,
+            pending_skin: Some(skin.iter().flat_map(as_byte_slice).copied().collect()),
+            pending_joint_matrices: None,
+        });
+    }
+
+    /// Poses a mesh skinned with `set_mesh_skin` for the next frame. Needs at least as many
+    /// matrices as the highest joint index of the skin, plus one. They are in mesh space,
+    /// each taking the bind pose to the current one.
+    pub fn set_mesh_joint_matrices(&mut self, mesh: MeshHandle, joint_matrices: Vec<Affine3A>) {
+        let skinning = self
+            .mesh_deformations
+            .get_mut(&mesh)
+            .and_then(|deformation| deformation.skinning.as_mut())
+            .expect("mesh has no skin");
+        assert!(
+            joint_matrices.len() >= skinning.joint_count as usize,
+            "not enough joint matrices"
+        );
+
+        skinning.pending_joint_matrices = Some(joint_matrices);
+    }
+
+    fn mesh_deformation(&mut self, mesh: MeshHandle) -> &mut MeshDeformation {
+        if !self.mesh_deformations.contains_key(&mesh) {
+            let deformation = self.create_mesh_deformation(mesh);
+            self.mesh_deformations.insert(mesh, deformation);
+        }
+
+        self.mesh_deformations.get_mut(&mesh).unwrap()
+    }
+
+    fn create_mesh_deformation(&mut self, mesh: MeshHandle) -> MeshDeformation {
+        let uploaded = &self.meshes[mesh.0];
+        let vertex_count = uploaded.vertex_count as usize;
+
+        let region_size = (vertex_count * size_of::<PackedVertex>()) as u64;
+        let first_region = allocate_vertex_buffer(&self.vertex_buffer_written, region_size * 2)
+            .expect("mesh deformation");
+
+        let gpu_mesh = unsafe {
+            let mesh_buffer = self.mesh_buffer.lock();
+            let mesh_buffer_src =
+                mesh_buffer.allocation.mapped_ptr().unwrap().as_ptr() as *const GpuMesh;
+            *mesh_buffer_src.add(mesh.0)
+        };
+        assert!(
+            !gpu_mesh.vertex_dequantization.has_quantized_positions(),
+            "meshes with quantized positions can't be deformed"
+        );
+
+        // Rays hit the indices at `GpuMesh::index_offset`. For curves, those are the tubes, with
+        // six per side of each segment, rather than the raster ribbons' twelve per segment.
+        let blas_index_count = if gpu_mesh.curve_tube_sides == 0 {
+            uploaded.index_count as usize
+        } else {
+            (uploaded.index_count / 12 * 6 * gpu_mesh.curve_tube_sides) as usize
+        };
+
+        let blas = self.device.ray_tracing_enabled().then(|| {
+            let opaque = !uploaded
+                .materials
+                .iter()
+                .any(|mat| mat.is_alpha_blended() || mat.is_alpha_tested());
+
+            let desc = RayTracingBottomAccelerationDesc {
+                allow_update: true,
+                ..mesh_blas_desc(
+                    &self.device,
+                    &self.vertex_buffer.lock(),
+                    gpu_mesh.vertex_core_offset,
+                    None,
+                    gpu_mesh.index_offset,
+                    blas_index_count,
+                    vertex_count as u32 - 1,
+                    opaque,
+                )
+            };
+
+            let blas = Arc::new(
+                self.device
+                    .create_ray_tracing_bottom_acceleration(&desc)
+                    .expect("mesh deformation blas"),
+            );
+            let scratch = self
+                .device
+                .create_ray_tracing_acceleration_scratch_buffer_with_size(
+                    self.device
+                        .ray_tracing_bottom_acceleration_build_sizes(&desc)
+                        .update_scratch_size as usize,
+                )
+                .expect("mesh deformation blas scratch");
+
+            DeformedBlas {
+                desc,
+                blas,
+                scratch,
+            }
+        });
+
+        if let Some(deformed) = &blas {
+            let prev = self.mesh_blas[mesh.0].replace(deformed.blas.clone());
+            // The previous frame's TLAS may still be using it.
+            if let Some(prev) = prev {
+                self.device.defer_release(prev);
+            }
+            self.tlas_update = TlasUpdate::Rebuild;
+        }
+
+        MeshDeformation {
+            gpu_mesh,
+            regions: [first_region as u32, (first_region + region_size) as u32],
+            next_region: 0,
+            pending_verts: None,
+            bounding_sphere: uploaded.bounding_sphere,
+            tangent_region: None,
+            pending_tangents: None,
+            rest_core_offset: gpu_mesh.vertex_core_offset,
+            rest_tangent_offset: gpu_mesh.vertex_tangent_offset,
+            rest_bounding_sphere: uploaded.bounding_sphere,
+            skinning: None,
+            blas,
+        }
+    }
+
+    fn update_mesh_deformations(&mut self, rg: &mut rg::RenderGraph) {
+        let mut vertex_writes: Vec<(u64, Vec<u8>)> = Vec::new();
+        let mut mesh_writes: Vec<(u64, Vec<u8>)> = Vec::new();
+        let mut blas_refits: Vec<(
+            RayTracingBottomAccelerationDesc,
+            Arc<RayTracingAcceleration>,
+            RayTracingAccelerationScratchBuffer,
+        )> = Vec::new();
+        let mut skinning_dispatches: Vec<SkinningDispatch> = Vec::new();
+
+        let vertex_buffer_address = self.vertex_buffer.lock().device_address(&self.device);
+
+        for (mesh, deformation) in &mut self.mesh_deformations {
+            let written = deformation.gpu_mesh;
+
+            // Without new vertices, both frames use the same ones.
+            let mut gpu_mesh = written;
+            gpu_mesh.vertex_prev_core_offset = written.vertex_core_offset;
+
+            let mut joint_matrices = None;
+            if let Some(skinning) = &mut deformation.skinning {
+                if let Some(skin) = skinning.pending_skin.take() {
+                    vertex_writes.push((skinning.skin_offset as u64, skin));
+                }
+                joint_matrices = skinning.pending_joint_matrices.take();
+            }
+
+            if deformation.pending_verts.is_some() || joint_matrices.is_some() {
+                let region = deformation.regions[deformation.next_region];
+                deformation.next_region ^= 1;
+                gpu_mesh.vertex_core_offset = region;
+
+                let bounding_sphere = if let Some(verts) = deformation.pending_verts.take() {
+                    vertex_writes.push((
+                        region as u64,
+                        verts.iter().flat_map(as_byte_slice).copied().collect(),
+                    ));
+
+                    BoundingSphere::from_points(verts.iter().map(|v| Vec3::from(v.pos)))
+                } else {
+                    let joint_matrices = joint_matrices.unwrap();
+                    let skinning = deformation.skinning.as_ref().unwrap();
+
+                    // Each skinned vertex is a weighted average of the vertex transformed by its
+                    // joints, so it's within the union of the bounds transformed by each joint.
+                    let bounding_sphere = joint_matrices[..skinning.joint_count as usize]
+                        .iter()
+                        .map(|m| deformation.rest_bounding_sphere.transformed(m))
+                        .reduce(|a, b| a.union(&b))
+                        .unwrap();
+
+                    let tangent_offset = deformation.tangent_region.unwrap_or(0);
+                    gpu_mesh.vertex_tangent_offset = tangent_offset;
+
+                    skinning_dispatches.push(SkinningDispatch {
+                        vertex_count: self.meshes[mesh.0].vertex_count,
+                        rest_core_offset: deformation.rest_core_offset,
+                        rest_tangent_offset: deformation.rest_tangent_offset,
+                        skin_offset: skinning.skin_offset,
+                        output_core_offset: region,
+                        output_tangent_offset: tangent_offset,
+                        joint_matrices,
+                    });
+
+                    bounding_sphere
+                };
+
+                // Culling tests the previous frame's depth too, so the bounds cover both.
+                self.meshes[mesh.0].bounding_sphere =
+                    bounding_sphere.union(&deformation.bounding_sphere);
+                deformation.bounding_sphere = bounding_sphere;
+
+                if let Some(deformed) = &deformation.blas {
+                    let mut desc = deformed.desc.clone();
+                    desc.geometries[0].vertex_buffer = vertex_buffer_address + region as u64;
+                    blas_refits.push((desc, deformed.blas.clone(), deformed.scratch.clone()));
+                }
+            } else {
+                self.meshes[mesh.0].bounding_sphere = deformation.bounding_sphere;
+            }
+
+            if let Some(tangents) = deformation.pending_tangents.take() {
+                let region = deformation.tangent_region.unwrap();
+                gpu_mesh.vertex_tangent_offset = region;
+
+                vertex_writes.push((
+                    region as u64,
+                    tangents.iter().flat_map(as_byte_slice).copied().collect(),
+                ));
+            }
+
+            if gpu_mesh.vertex_core_offset != written.vertex_core_offset
+                || gpu_mesh.vertex_prev_core_offset != written.vertex_prev_core_offset
+                || gpu_mesh.vertex_tangent_offset != written.vertex_tangent_offset
+            {
+                mesh_writes.push((
+                    (mesh.0 * size_of::<GpuMesh>()) as u64,
+                    as_byte_slice(&gpu_mesh).to_vec(),
+                ));
+                deformation.gpu_mesh = gpu_mesh;
+            }
+        }
+
+        // Through the command buffer rather than the mapped memory, as the previous frame
+        // may still be reading the mesh buffer on the GPU.
+        write_buffer(
+            rg,
+            "mesh deformation vertices",
+            self.vertex_buffer.lock().clone(),
+            vertex_writes,
+        );
+        skin_meshes(rg, self.vertex_buffer.lock().clone(), skinning_dispatches);
+        write_buffer(
+            rg,
+            "mesh deformation meshes",
+            self.mesh_buffer.lock().clone(),
+            mesh_writes,
+        );
+
+        if !blas_refits.is_empty() {
+            self.refit_deformed_blas(rg, blas_refits);
+            self.tlas_update = self.tlas_update.max(TlasUpdate::Refit);
+        }
+    }
+
+    // After the vertex writes and skinning, and before the TLAS update.
+    fn refit_deformed_blas(
+        &self,
+        rg: &mut rg::RenderGraph,
+        refits: Vec<(
+            RayTracingBottomAccelerationDesc,
+            Arc<RayTracingAcceleration>,
+            RayTracingAccelerationScratchBuffer,
+        )>,
+    ) {
+        let pass = rg.add_pass("refit deformed blas");
+        pass.render(move |api| {
+            let raw_device = &api.device().raw;
+            let cb = api.cb.raw;
+
+            // Neither the BLASes nor the vertices are tracked by the graph: after the previous
+            // frame's traces, and the vertex writes, which the builds read.
+            unsafe {
+                raw_device.cmd_pipeline_barrier(
+                    cb,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier::builder()
+                        .src_access_mask(
+                            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+                        )
+                        .dst_access_mask(
+                            vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR
+                                | vk::AccessFlags::SHADER_READ,
+                        )
+                        .build()],
+                    &[],
+                    &[],
+                );
+            }
+
+            for (desc, blas, scratch) in &refits {
+                api.device()
+                    .refit_ray_tracing_bottom_acceleration(cb, desc, blas, scratch)?;
+            }
+
+            unsafe {
+                raw_device.cmd_pipeline_barrier(
+                    cb,
+                    vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR)
+                        .dst_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR)
+                        .build()],
+                    &[],
+                    &[],
+                );
+            }
+
+            Ok(())
+        });
+    }
+
+    /// Add a camera to render along with the main one, into an image of `extent`; see `WorldView`.
+    pub fn add_view(
+        &mut self,
+        extent: [u32; 2],
+        camera_matrices: CameraMatrices,
+    ) -> Result<WorldViewHandle, BackendError> {
+        let output = self.device.create_image(
+            ImageDesc::new_2d(vk::Format::B10G11R11_UFLOAT_PACK32, extent).usage(
+                vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+            ),
+            vec![],
+        )?;
+
+        let handle = WorldViewHandle(self.next_view_handle);
+        self.next_view_handle += 1;
+
+        self.views.push((
+            handle,
+            WorldView {
+                camera_matrices,
+                ev_shift: 0.0,
+                dynamic_exposure: Default::default(),
+                contrast: 1.0,
+                post: PostProcessRenderer::new(&self.device)?,
+                enabled: true,
+                clip_plane: None,
+                output: Arc::new(output),
+            },
+        ));
+
+        Ok(handle)
+    }
+
+    pub fn remove_view(&mut self, view: WorldViewHandle) {
+        let index = self
+            .views
+            .iter()
+            .position(|(handle, _)| *handle == view)
+            .expect("no such view");
+        self.views.swap_remove(index);
+    }
+
+    pub fn view(&self, view: WorldViewHandle) -> &WorldView {
+        self.views
+            .iter()
+            .find(|(handle, _)| *handle == view)
+            .map(|(_, view)| view)
+            .expect("no such view")
+    }
+
+    pub fn view_mut(&mut self, view: WorldViewHandle) -> &mut WorldView {
+        self.views
+            .iter_mut()
+            .find(|(handle, _)| *handle == view)
+            .map(|(_, view)| view)
+            .expect("no such view")
+    }
+
+    /// Add a cube map capture of the scene around `position`, `resolution` texels wide,
+    /// to be used as the environment lighting nearby; see `EnvironmentProbes`.
+    pub fn add_environment_probe(
+        &mut self,
+        position: Vec3,
+        resolution: u32,
+    ) -> Result<EnvironmentProbeHandle, BackendError> {
+        self.environment_probes
+            .add(&self.device, position, resolution)
+    }
+
+    pub fn remove_environment_probe(&mut self, probe: EnvironmentProbeHandle) {
+        self.environment_probes.remove(probe);
+    }
+
+    /// Moves the probe without re-capturing it; see `capture_environment_probe`.
+    pub fn set_environment_probe_position(
+        &mut self,
+        probe: EnvironmentProbeHandle,
+        position: Vec3,
+    ) {
+        self.environment_probes.get_mut(probe).position = position;
+    }
+
+    /// Queues the probe for capture, over the next frames in `RenderMode::Standard`.
+    pub fn capture_environment_probe(&mut self, probe: EnvironmentProbeHandle) {
+        self.environment_probes.request_capture(probe);
     }
 
     pub fn get_instance_dynamic_parameters(
@@ -833,28 +2750,35 @@ impl WorldRenderer {
         &mut self.instances[index].dynamic_parameters
     }
 
-    pub(crate) fn build_ray_tracing_top_level_acceleration(&mut self) {
-        let tlas = self
-            .device
-            .create_ray_tracing_top_acceleration(
-                &RayTracingTopAccelerationDesc {
-                    //instances: self.mesh_blas.iter().collect::<Vec<_>>(),
-                    instances: self
-                        .instances
-                        .iter()
-                        .map(|inst| RayTracingInstanceDesc {
-                            blas: self.mesh_blas[inst.mesh.0].clone(),
-                            transformation: inst.transform,
-                            mesh_index: inst.mesh.0 as u32,
-                        })
-                        .collect::<Vec<_>>(),
-                    preallocate_bytes: TLAS_PREALLOCATE_BYTES,
-                },
-                &self.accel_scratch,
-            )
-            .expect("tlas");
+    pub(crate) fn build_ray_tracing_top_level_acceleration(&mut self) -> Result<(), BackendError> {
+        self.create_ray_tracing_top_level_acceleration(TLAS_PREALLOCATE_BYTES)
+    }
+
+    fn create_ray_tracing_top_level_acceleration(
+        &mut self,
+        preallocate_bytes: usize,
+    ) -> Result<(), BackendError> {
+        let tlas = self.device.create_ray_tracing_top_acceleration(
+            &RayTracingTopAccelerationDesc {
+                //instances: self.mesh_blas.iter().collect::<Vec<_>>(),
+                instances: self
+                    .instances
+                    .iter()
+                    .map(|inst| RayTracingInstanceDesc {
+                        blas: self.mesh_blas[inst.mesh.0].clone(),
+                        transformation: inst.transform,
+                        mesh_index: inst.mesh.0 as u32,
+                        double_sided: self.meshes[inst.mesh.0].double_sided,
+                        mask: inst.ray_visibility.instance_mask(inst.is_static),
+                    })
+                    .collect::<Vec<_>>(),
+                preallocate_bytes,
+            },
+            &self.accel_scratch,
+        )?;
 
         self.tlas = Some(Arc::new(tlas));
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -862,15 +2786,84 @@ impl WorldRenderer {
         self.frame_idx = 0;
     }
 
+    /// Enables or disables `DeterministicMode`. Setting it again restarts its sequence.
+    pub fn set_deterministic(&mut self, deterministic: Option<DeterministicMode>) {
+        self.deterministic = deterministic;
+        self.deterministic_frame_idx = 0;
+    }
+
+    pub fn deterministic(&self) -> Option<DeterministicMode> {
+        self.deterministic
+    }
+
+    /// Frame index driving noise, ray sampling, and camera jitter.
+    fn stochastic_frame_idx(&self) -> u32 {
+        if let Some(frozen_frame) = self.frozen_frame.as_ref() {
+            return frozen_frame.frame_idx();
+        }
+
+        match self.deterministic {
+            Some(deterministic) => deterministic
+                .seed
+                .wrapping_add(self.deterministic_frame_idx),
+            None => self.frame_idx,
+        }
+    }
+
+    /// Grow the TLAS and its scratch buffer if instances were added beyond what they can hold.
+    fn ensure_top_level_acceleration_capacity(&mut self) -> Result<(), BackendError> {
+        let sizes = self
+            .device
+            .ray_tracing_top_acceleration_build_sizes(self.instances.len());
+
+        let tlas_fits = self.tlas.as_ref().map_or(false, |tlas| {
+            sizes.acceleration_structure_size as usize <= tlas.backing_buffer_size()
+        });
+        let scratch_fits = sizes.build_scratch_size as usize <= self.accel_scratch.size();
+
+        if tlas_fits && scratch_fits {
+            return Ok(());
+        }
+
+        // Leave some headroom, so that spawning a few more instances doesn't do this again.
+        let sizes = self
+            .device
+            .ray_tracing_top_acceleration_build_sizes(self.instances.len() * 2);
+
+        self.device.ensure_ray_tracing_acceleration_scratch_size(
+            &self.accel_scratch,
+            sizes.build_scratch_size as usize,
+        )?;
+
+        self.create_ray_tracing_top_level_acceleration(
+            TLAS_PREALLOCATE_BYTES.max(sizes.acceleration_structure_size as usize),
+        )
+    }
+
+    /// `None` if the TLAS can't hold the instances and couldn't be grown, in which case
+    /// the frame is rendered without ray tracing.
     pub(super) fn prepare_top_level_acceleration(
         &mut self,
         rg: &mut rg::TemporalRenderGraph,
-    ) -> rg::Handle<RayTracingAcceleration> {
+    ) -> Option<rg::Handle<RayTracingAcceleration>> {
+        if let Err(err) = self.ensure_top_level_acceleration_capacity() {
+            log::error!(
+                "Could not grow the TLAS; rendering without ray tracing: {:#}",
+                err
+            );
+            return None;
+        }
+
         let mut tlas = rg.import(
             self.tlas.as_ref().unwrap().clone(),
             vk_sync::AccessType::AnyShaderReadOther,
         );
 
+        let update = std::mem::replace(&mut self.tlas_update, TlasUpdate::None);
+        if update == TlasUpdate::None {
+            return Some(tlas);
+        }
+
         let instances = self
             .instances
             .iter()
@@ -878,10 +2871,16 @@ impl WorldRenderer {
                 blas: self.mesh_blas[inst.mesh.0].clone(),
                 transformation: inst.transform,
                 mesh_index: inst.mesh.0 as u32,
+                double_sided: self.meshes[inst.mesh.0].double_sided,
+                mask: inst.ray_visibility.instance_mask(inst.is_static),
             })
             .collect::<Vec<_>>();
 
-        let mut pass = rg.add_pass("rebuild tlas");
+        let mut pass = rg.add_pass(if update == TlasUpdate::Refit {
+            "refit tlas"
+        } else {
+            "rebuild tlas"
+        });
         let tlas_ref = pass.write(&mut tlas, AccessType::TransferWrite);
 
         let accel_scratch = self.accel_scratch.clone();
@@ -896,18 +2895,28 @@ impl WorldRenderer {
             let tlas = api.resources.rt_acceleration(tlas_ref);
 
             let cb = api.cb;
-            api.device().rebuild_ray_tracing_top_acceleration(
-                cb.raw,
-                instance_buffer_address,
-                instances.len(),
-                tlas,
-                &accel_scratch,
-            );
+            if update == TlasUpdate::Refit {
+                api.device().refit_ray_tracing_top_acceleration(
+                    cb.raw,
+                    instance_buffer_address,
+                    instances.len(),
+                    tlas,
+                    &accel_scratch,
+                )?;
+            } else {
+                api.device().rebuild_ray_tracing_top_acceleration(
+                    cb.raw,
+                    instance_buffer_address,
+                    instances.len(),
+                    tlas,
+                    &accel_scratch,
+                )?;
+            }
 
             Ok(())
         });
 
-        tlas
+        Some(tlas)
     }
 
     fn store_prev_mesh_transforms(&mut self) {
@@ -917,10 +2926,17 @@ impl WorldRenderer {
     }
 
     fn update_pre_exposure(&mut self) {
-        let dt = 1.0 / 60.0; // TODO
+        self.dynamic_exposure.ev_adapted = self.post.adapted_ev();
 
-        self.dynamic_exposure.update(-self.post.image_log2_lum, dt);
-        let ev_mult = (self.ev_shift + self.dynamic_exposure.ev_smoothed()).exp2();
+        // Dynamic exposure takes precedence, so that the physical camera can still
+        // drive depth of field and motion blur with auto exposure on.
+        let camera_ev = if self.physical_camera.enabled && !self.dynamic_exposure.enabled {
+            self.physical_camera.relative_ev()
+        } else {
+            self.dynamic_exposure.ev_smoothed()
+        };
+        let ev_mult = (self.ev_shift + camera_ev).exp2();
+        let is_frozen = self.frozen_frame.is_some();
 
         let exposure_state = &mut self.exposure_state[self.render_mode as usize];
 
@@ -931,7 +2947,10 @@ impl WorldRenderer {
                 // Smoothly blend the pre-exposure.
                 // TODO: Ensure we correctly use the previous frame's pre-mult in temporal shaders,
                 // and then nuke/speed-up this blending.
-                exposure_state.pre_mult = exposure_state.pre_mult * 0.9 + ev_mult * 0.1;
+                // Held while frozen, as the frozen image was pre-exposed with it.
+                if !is_frozen {
+                    exposure_state.pre_mult = exposure_state.pre_mult * 0.9 + ev_mult * 0.1;
+                }
 
                 // Put the rest in post-exposure.
                 exposure_state.post_mult = ev_mult / exposure_state.pre_mult;
@@ -945,6 +2964,56 @@ impl WorldRenderer {
         }
 
         exposure_state.pre_mult_delta = exposure_state.pre_mult / exposure_state.pre_mult_prev;
+
+        for (_, view) in &mut self.views {
+            view.dynamic_exposure.ev_adapted = view.post.adapted_ev();
+        }
+    }
+
+    // Only the standard renderer freezes; the path tracer converges by itself while the camera is still.
+    fn update_frozen_frame(&mut self, frame_desc: &WorldFrameDesc) {
+        if !self.freeze_frame || self.render_mode != RenderMode::Standard {
+            self.frozen_frame = None;
+        } else if self.frozen_frame.is_none() {
+            self.frozen_frame = Some(FrozenFrame::new(
+                &self.override_frame_desc(frame_desc),
+                self.stochastic_frame_idx(),
+            ));
+        }
+    }
+
+    fn update_time_of_day(&mut self) {
+        if let Some((lighting, change)) = self.time_of_day.advance(self.delta_time_seconds) {
+            self.sun_color_multiplier = lighting.sun_color_multiplier;
+            self.sky_ambient = lighting.sky_ambient;
+            self.atmosphere.turbidity = lighting.turbidity;
+            self.notify_lighting_change(change);
+        }
+    }
+
+    /// `frame_desc`, with what the renderer overrides: the sun of the time of day,
+    /// or the camera and sun of the frozen frame.
+    fn override_frame_desc(&self, frame_desc: &WorldFrameDesc) -> WorldFrameDesc {
+        if let Some(frozen_frame) = self.frozen_frame.as_ref() {
+            return frozen_frame.frame_desc(frame_desc);
+        }
+
+        WorldFrameDesc {
+            sun_direction: self
+                .time_of_day
+                .lighting()
+                .map_or(frame_desc.sun_direction, |lighting| lighting.towards_sun),
+            ..*frame_desc
+        }
+    }
+
+    /// Lets the temporal filters of the GI know that the lighting changed by `amount`
+    /// since the previous frame, from zero to one, for them to drop that much of their
+    /// history, rather than keep showing the old lighting for a while. For sudden changes,
+    /// such as lights switched on or off, or the sun moved. The largest amount reported
+    /// before a frame's constants are prepared applies to that frame.
+    pub fn notify_lighting_change(&mut self, amount: f32) {
+        self.lighting_change = self.lighting_change.max(amount.clamp(0.0, 1.0));
     }
 
     pub fn exposure_state(&self) -> ExposureState {
@@ -956,7 +3025,17 @@ impl WorldRenderer {
         rg: &mut rg::TemporalRenderGraph,
         frame_desc: &WorldFrameDesc,
     ) -> rg::Handle<Image> {
+        self.update_time_of_day();
+        self.update_frozen_frame(frame_desc);
+        let frame_desc = &self.override_frame_desc(frame_desc);
+
         self.update_pre_exposure();
+        self.time_slicer.begin_frame();
+        self.apply_streamed_meshes();
+        self.apply_streamed_images();
+        self.update_texture_streaming(frame_desc.camera_matrices.eye_position());
+        self.update_materials(rg);
+        self.update_mesh_deformations(rg);
 
         rg.predefined_descriptor_set_layouts.insert(
             1,
@@ -965,15 +3044,44 @@ impl WorldRenderer {
             },
         );
 
-        for image_lut in self.image_luts.iter_mut() {
+        if self.render_pass_formats != self.render_target_formats {
+            self.render_target_formats.gbuffer.apply_to_shaders();
+
+            let (raster_simple, forward_transparent, gi_downsample) =
+                create_frame_render_passes(&self.device, self.render_target_formats);
+
+            self.raster_simple_render_pass = raster_simple;
+            self.forward_transparent_render_pass = forward_transparent;
+            self.gi_downsample_render_pass = gi_downsample;
+            self.render_pass_formats = self.render_target_formats;
+        }
+
+        if self.atmosphere_in_image_luts != Some(self.atmosphere) {
+            for (_, image_lut) in self.image_luts.iter_mut() {
+                if image_lut.depends_on_atmosphere() {
+                    image_lut.invalidate();
+                }
+            }
+
+            self.atmosphere_in_image_luts = Some(self.atmosphere);
+        }
+
+        for (_, image_lut) in self.image_luts.iter_mut() {
             image_lut.compute_if_needed(rg);
         }
 
-        match self.render_mode {
+        self.rendered_views.clear();
+
+        // The path tracer samples the sky itself, but the views still need the cubes.
+        let has_enabled_views = self.views.iter().any(|(_, view)| view.enabled);
+        let sky_cubes = (self.render_mode == RenderMode::Standard || has_enabled_views)
+            .then(|| self.prepare_sky_cubes(rg));
+
+        let mut output = match self.render_mode {
             RenderMode::Standard => {
                 if USE_TAA_JITTER {
-                    self.taa.current_supersample_offset = self.supersample_offsets
-                        [self.frame_idx as usize % self.supersample_offsets.len()];
+                    self.taa.current_supersample_offset =
+                        self.taa.jitter_sequence.offset(self.stochastic_frame_idx());
                 } else {
                     self.taa.current_supersample_offset = Vec2::ZERO;
                 }
@@ -983,7 +3091,23 @@ impl WorldRenderer {
                     self.dlss.current_supersample_offset = self.taa.current_supersample_offset;
                 }
 
-                self.prepare_render_graph_standard(rg, frame_desc)
+                #[cfg(feature = "fsr2")]
+                {
+                    self.fsr2.current_supersample_offset = self.taa.current_supersample_offset;
+                    self.fsr2.fixed_frame_time_delta_ms = self
+                        .deterministic
+                        .map(|deterministic| deterministic.delta_time_seconds * 1000.0);
+                }
+
+                let sky_cubes = sky_cubes.as_ref().unwrap();
+                self.prepare_light_probe_bakes(rg, frame_desc, sky_cubes);
+                let environment_cubes = self.prepare_environment_probes(rg, frame_desc, sky_cubes);
+
+                self.prepare_render_graph_standard(
+                    rg,
+                    frame_desc,
+                    environment_cubes.as_ref().unwrap_or(sky_cubes),
+                )
             }
             RenderMode::Reference => {
                 self.taa.current_supersample_offset = Vec2::ZERO;
@@ -993,9 +3117,23 @@ impl WorldRenderer {
                     self.dlss.current_supersample_offset = self.taa.current_supersample_offset;
                 }
 
+                #[cfg(feature = "fsr2")]
+                {
+                    self.fsr2.current_supersample_offset = self.taa.current_supersample_offset;
+                }
+
                 self.prepare_render_graph_reference(rg, frame_desc)
             }
+        };
+
+        crate::debug_draw::raster_debug_draw(rg, &self.device, &mut output);
+        self.debug_text.render(rg, &self.device, &mut output);
+
+        if let Some(sky_cubes) = sky_cubes.as_ref() {
+            self.prepare_render_graph_views(rg, frame_desc, sky_cubes);
         }
+
+        output
     }
 
     pub fn prepare_frame_constants(
@@ -1004,12 +3142,21 @@ impl WorldRenderer {
         frame_desc: &WorldFrameDesc,
         delta_time_seconds: f32,
     ) -> FrameConstantsLayout {
+        let frame_desc = &self.override_frame_desc(frame_desc);
+
+        let delta_time_seconds = self
+            .deterministic
+            .map_or(delta_time_seconds, |deterministic| {
+                deterministic.delta_time_seconds
+            });
+
         let mut view_constants = ViewConstants::builder(
             frame_desc.camera_matrices,
             self.prev_camera_matrices
                 .unwrap_or(frame_desc.camera_matrices),
             frame_desc.render_extent,
         )
+        .clip_plane(self.clip_plane.unwrap_or(Vec4::ZERO))
         .build();
 
         // Re-shuffle the jitter sequence if we've just used it up
@@ -1033,26 +3180,36 @@ impl WorldRenderer {
             frame_desc.render_extent.into(),
         );
 
+        let working_color_space = self.working_color_space;
         let triangle_lights: Vec<TriangleLight> = self
             .instances
             .iter()
             .flat_map(|inst| {
-                let (_scale, rotation, translation) =
-                    inst.transform.to_scale_rotation_translation();
-                let inst_position = translation;
-                let inst_rotation = rotation;
-
+                let xform = inst.transform;
                 let emissive_multiplier = Vec3::splat(inst.dynamic_parameters.emissive_multiplier);
 
                 self.mesh_lights[inst.mesh.0]
                     .lights
                     .iter()
                     .map(move |light: &TriangleLight| {
-                        light
-                            .transform(inst_position, inst_rotation)
-                            .scale_radiance(emissive_multiplier)
+                        light.transform(&xform).scale_radiance(emissive_multiplier)
                     })
             })
+            .map(|light| TriangleLight {
+                radiance: working_color_space
+                    .from_rec709(light.radiance.into())
+                    .into(),
+                ..light
+            })
+            .collect();
+
+        let punctual_lights: Vec<PunctualLight> = self
+            .frame_punctual_lights()
+            .into_iter()
+            .map(|light| PunctualLight {
+                color: working_color_space.from_rec709(light.color.into()).into(),
+                ..light
+            })
             .collect();
 
         // Initialize constants for the maximum allowed cascade count, even if we're not using them,
@@ -1068,14 +3225,12 @@ impl WorldRenderer {
             ircache_cascades[i] = c;
         }
 
-        let real_sun_angular_radius = 0.53f32.to_radians() * 0.5;
-
-        let globals_offset = dynamic_constants.push(&FrameConstants {
+        let frame_constants = FrameConstants {
             view_constants,
             sun_direction: frame_desc.sun_direction.extend(0.0),
-            frame_index: self.frame_idx,
+            frame_index: self.stochastic_frame_idx(),
             delta_time_seconds,
-            sun_angular_radius_cos: (self.sun_size_multiplier * real_sun_angular_radius).cos(),
+            sun_angular_radius_cos: (self.sun_angular_diameter_degrees.to_radians() * 0.5).cos(),
 
             sun_color_multiplier: self.sun_color_multiplier.extend(0.0),
             sky_ambient: self.sky_ambient.extend(0.0),
@@ -1084,46 +3239,156 @@ impl WorldRenderer {
             pre_exposure: self.exposure_state().pre_mult,
             pre_exposure_prev: self.exposure_state().pre_mult_prev,
             pre_exposure_delta: self.exposure_state().pre_mult_delta,
-            pad0: 0.0,
+            punctual_light_count: punctual_lights.len() as _,
 
             render_overrides: self.render_overrides,
 
+            rect_light_count: self.rect_lights.len() as _,
+            blue_noise_rotation: BlueNoise::rotation(self.stochastic_frame_idx()),
+            working_color_space: working_color_space.shader_index(),
+            lighting_change: self.lighting_change,
+
+            atmosphere: self.atmosphere.to_constants(),
+
             ircache_grid_center: self.ircache.grid_center().extend(1.0),
             ircache_cascades,
-        });
+        };
+        let globals_offset = dynamic_constants.push(&frame_constants);
+
+        let additional_view_globals_offsets = self
+            .rendered_views
+            .iter()
+            .map(|&(camera_matrices, extent, clip_plane)| {
+                dynamic_constants.push(&FrameConstants {
+                    view_constants: ViewConstants::builder(
+                        camera_matrices,
+                        camera_matrices,
+                        extent,
+                    )
+                    .clip_plane(clip_plane)
+                    .build(),
+                    ..frame_constants
+                })
+            })
+            .collect();
 
         let instance_dynamic_parameters_offset = dynamic_constants
             .push_from_iter(self.instances.iter().map(|inst| inst.dynamic_parameters));
 
-        let triangle_lights_offset: u32 =
-            dynamic_constants.push_from_iter(triangle_lights.into_iter());
+        let triangle_lights_offset: u32 = dynamic_constants
+            .push_from_iter(build_gpu_triangle_lights(triangle_lights).into_iter());
+
+        let punctual_lights_offset: u32 =
+            dynamic_constants.push_from_iter(punctual_lights.into_iter());
+
+        let rect_lights_offset: u32 = dynamic_constants.push_from_iter(
+            self.rect_lights
+                .iter()
+                .map(|(_, light)| GpuRectLight::new(light, working_color_space)),
+        );
 
         self.prev_camera_matrices = Some(frame_desc.camera_matrices);
+        self.lighting_change = 0.0;
+        // Animations and particles stop while frozen. The shaders still get the real delta,
+        // which some divide by.
+        self.delta_time_seconds = if self.frozen_frame.is_some() {
+            0.0
+        } else {
+            delta_time_seconds
+        };
 
         rg::renderer::FrameConstantsLayout {
             globals_offset,
             instance_dynamic_parameters_offset,
             triangle_lights_offset,
+            punctual_lights_offset,
+            rect_lights_offset,
+            additional_view_globals_offsets,
         }
     }
 
+    /// The lights of the instances' meshes, in world space, followed by the standalone ones;
+    /// in the order of `punctual_lights_dyn` in the shaders.
+    pub(crate) fn frame_punctual_lights(&self) -> Vec<PunctualLight> {
+        self.instances
+            .iter()
+            .flat_map(|inst| {
+                let xform = Mat4::from(inst.transform);
+
+                self.mesh_lights[inst.mesh.0]
+                    .punctual_lights
+                    .iter()
+                    .map(move |light: &PunctualLight| light.transform(xform))
+            })
+            .chain(self.punctual_lights.iter().map(|(_, light)| *light))
+            .collect()
+    }
+
+    /// Saves the linear HDR color of the next frame to an EXR file, before exposure
+    /// and tonemapping, for offline comparisons and grading. In `RenderMode::Reference`,
+    /// that's the image accumulated by the path tracer.
+    ///
+    /// The camera matrices and field of view, the exposure multiplier which would be
+    /// applied in post, the physical camera settings, and the frame index are stored
+    /// in the EXR header. The file is written a few frames later, once the GPU is done.
+    ///
+    /// The returned receiver gets the outcome once the file is written. It's disconnected
+    /// instead if the capture is dropped, e.g. when another one is requested for the same frame.
+    pub fn capture_hdr_exr(
+        &mut self,
+        path: impl Into<PathBuf>,
+        source: HdrCaptureSource,
+    ) -> std::sync::mpsc::Receiver<anyhow::Result<()>> {
+        self.hdr_captures.request(path.into(), source)
+    }
+
+    /// Like `capture_hdr_exr`, but the image is kept in memory, to be retrieved
+    /// with `take_hdr_capture` once the GPU is done with it.
+    pub fn request_hdr_capture(&mut self, source: HdrCaptureSource) {
+        self.hdr_captures.request_in_memory(source);
+    }
+
+    /// The oldest finished capture requested via `request_hdr_capture`, if any.
+    pub fn take_hdr_capture(&mut self) -> Option<HdrCapture> {
+        self.hdr_captures.take_in_memory()
+    }
+
+    /// Returns the instance visible at `x`, `y` in output pixels, as rasterized in the
+    /// G-buffer. The lookup goes through a GPU readback, so the result for a position is
+    /// only available a few frames after it's first picked; call this every frame with
+    /// the current cursor position, and `None` is returned until then.
+    ///
+    /// Only opaque geometry is considered, and only in `RenderMode::Standard`.
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<InstanceHandle> {
+        self.picking.pick([x, y]).flatten()
+    }
+
     pub fn retire_frame(&mut self) {
+        self.hdr_captures
+            .write_finished(&self.device, self.frame_idx);
+        self.image_stats.read_finished(&self.device, self.frame_idx);
+        self.light_probes
+            .read_finished_bakes(&self.device, self.frame_idx);
+
+        let instance_handle_to_index = &self.instance_handle_to_index;
+        self.picking.resolve_finished(self.frame_idx, |handle| {
+            instance_handle_to_index.contains_key(&handle)
+        });
+
         self.frame_idx = self.frame_idx.overflowing_add(1).0;
+        self.deterministic_frame_idx = self.deterministic_frame_idx.wrapping_add(1);
         self.store_prev_mesh_transforms();
     }
-}
 
-fn radical_inverse(mut n: u32, base: u32) -> f32 {
-    let mut val = 0.0f32;
-    let inv_base = 1.0f32 / base as f32;
-    let mut inv_bi = inv_base;
+    /// Completes the readbacks still in flight, such as HDR captures and light probe bakes,
+    /// without waiting for `READBACK_FRAME_LATENCY` more frames. The GPU must be done with
+    /// all the frames, e.g. after `Renderer::shutdown`.
+    pub fn flush_readbacks(&mut self) {
+        let frame_idx = self.frame_idx.wrapping_add(READBACK_FRAME_LATENCY);
 
-    while n > 0 {
-        let d_i = n % base;
-        val += d_i as f32 * inv_bi;
-        n = (n as f32 * inv_base) as u32;
-        inv_bi *= inv_base;
+        self.hdr_captures.write_finished(&self.device, frame_idx);
+        self.image_stats.read_finished(&self.device, frame_idx);
+        self.light_probes
+            .read_finished_bakes(&self.device, frame_idx);
     }
-
-    val
 }
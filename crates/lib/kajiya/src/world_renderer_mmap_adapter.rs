@@ -8,9 +8,28 @@ impl WorldRenderer {
         path: impl Into<std::path::PathBuf>,
         opts: AddMeshOptions,
     ) -> anyhow::Result<MeshHandle> {
+        let path = path.into();
+        let _creator = self
+            .device
+            .resource_creator_scope(|| format!("mesh {:?}", path));
+
         Ok(self.add_mesh(
             crate::mmap::mmapped_asset::<PackedTriMesh::Flat, _>(path)?,
             opts,
         ))
     }
+
+    /// Like `add_baked_mesh`, but with the path returned by `bake`, which runs on a background
+    /// thread along with the rest of the loading, and can e.g. bake the mesh if it's not cached.
+    /// See `add_mesh_in_background`.
+    pub fn add_baked_mesh_in_background(
+        &mut self,
+        bake: impl FnOnce() -> anyhow::Result<std::path::PathBuf> + Send + 'static,
+        opts: AddMeshOptions,
+    ) -> MeshHandle {
+        self.add_mesh_in_background(
+            move || crate::mmap::mmapped_asset::<PackedTriMesh::Flat, _>(bake()?),
+            opts,
+        )
+    }
 }
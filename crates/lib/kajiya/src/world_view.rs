@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use glam::Vec4;
+use kajiya_backend::vulkan::image::Image;
+use rust_shaders_shared::camera::CameraMatrices;
+
+use crate::{renderers::post::PostProcessRenderer, world_renderer::DynamicExposureState};
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct WorldViewHandle(pub usize);
+
+/// A camera rendered by `WorldRenderer` every frame in addition to the main one,
+/// e.g. for a planar mirror, a minimap, or a reflection probe.
+///
+/// Views share the meshes, lights and sky with the main camera, but have their own
+/// frame constants, exposure, post-processing, and output image. They're shaded forward,
+/// without a G-buffer, so there's no ray tracing, GI, decals, or temporal anti-aliasing.
+///
+/// Nothing a view renders feeds back into the main camera's state: its exposure adapts
+/// to its own image, and temporal resources created within it are kept per view.
+pub struct WorldView {
+    pub camera_matrices: CameraMatrices,
+
+    /// Exposure compensation of the view, in EV, on top of `dynamic_exposure`.
+    pub ev_shift: f32,
+
+    /// Adapts to the view's own image. Disabled by default.
+    pub dynamic_exposure: DynamicExposureState,
+
+    pub contrast: f32,
+
+    /// The view's own effects and tonemapper, configured independently of the main camera's.
+    pub post: PostProcessRenderer,
+
+    /// Disabled views aren't rendered, and their output keeps its last contents.
+    pub enabled: bool,
+
+    /// World-space plane; only the side where `dot(xyz, position) + w >= 0` is rendered.
+    /// A mirror's view clips at the mirror, so that what's behind it doesn't show up.
+    pub clip_plane: Option<Vec4>,
+
+    pub(crate) output: Arc<Image>,
+}
+
+impl WorldView {
+    pub fn extent(&self) -> [u32; 2] {
+        self.output.desc.extent_2d()
+    }
+
+    /// The tonemapped image, in the same space as the main output of `WorldRenderer`.
+    /// Sampled after the frame's render graph, it holds the latest rendered frame.
+    pub fn output(&self) -> Arc<Image> {
+        self.output.clone()
+    }
+}
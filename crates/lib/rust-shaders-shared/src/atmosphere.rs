@@ -0,0 +1,21 @@
+use macaw::Vec4;
+
+/// Parameters of the precomputed atmosphere, in meters. Must match `AtmosphereConstants` in `frame_constants.hlsl`.
+#[repr(C, align(16))]
+#[derive(Copy, Clone)]
+pub struct AtmosphereConstants {
+    pub rayleigh_scattering: Vec4,
+    pub mie_scattering: Vec4,
+    pub mie_absorption: Vec4,
+    pub ozone_absorption: Vec4,
+
+    pub planet_radius: f32,
+    pub atmosphere_height: f32,
+    pub rayleigh_scale_height: f32,
+    pub mie_scale_height: f32,
+
+    pub mie_g: f32,
+    pub ozone_center_altitude: f32,
+    pub ozone_half_width: f32,
+    pub ground_albedo: f32,
+}
@@ -1,4 +1,7 @@
-use crate::{render_overrides::RenderOverrides, view_constants::ViewConstants};
+use crate::{
+    atmosphere::AtmosphereConstants, render_overrides::RenderOverrides,
+    view_constants::ViewConstants,
+};
 use macaw::{IVec4, Vec4};
 
 pub const IRCACHE_CASCADE_COUNT: usize = 12;
@@ -28,10 +31,21 @@ pub struct FrameConstants {
     pub pre_exposure: f32,
     pub pre_exposure_prev: f32,
     pub pre_exposure_delta: f32,
-    pub pad0: f32,
+    pub punctual_light_count: u32,
 
     pub render_overrides: RenderOverrides,
 
+    pub rect_light_count: u32,
+    /// Added to the blue noise modulo one, animating it over time.
+    pub blue_noise_rotation: f32,
+    /// `WorkingColorSpace::shader_index` of the radiance and albedo in all the buffers.
+    pub working_color_space: u32,
+    /// How much the lighting changed since the previous frame, from zero to one,
+    /// for the temporal filters to drop that much of their history.
+    pub lighting_change: f32,
+
+    pub atmosphere: AtmosphereConstants,
+
     pub ircache_grid_center: Vec4,
     pub ircache_cascades: [IrcacheCascadeConstants; IRCACHE_CASCADE_COUNT],
 }
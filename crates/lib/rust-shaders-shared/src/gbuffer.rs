@@ -1,11 +1,10 @@
 use crate::util::*;
 use macaw::*;
 
-#[cfg(target_arch = "spirv")]
-use spirv_std::float::{f16x2_to_vec2, vec2_to_f16x2};
 #[cfg(target_arch = "spirv")]
 use spirv_std::num_traits::Float;
 
+/// Only matches the default `GbufferLayout`; `gbuffer.hlsl` follows the configured one.
 #[repr(C)]
 #[derive(Clone)]
 pub struct GbufferDataPacked {
@@ -19,6 +18,24 @@ pub struct GbufferData {
     pub normal: Vec3,
     pub roughness: f32,
     pub metalness: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub anisotropy: f32,
+    /// Only defined modulo PI; see `gbuffer.hlsl`.
+    pub anisotropy_angle: f32,
+}
+
+const ANISOTROPY_ANGLE_BITS: u32 = 6;
+
+fn pack_unorm_rounded(val: f32, bit_count: u32) -> u32 {
+    let max_val = (1u32 << bit_count) - 1;
+    (val.clamp(0.0, 1.0) * max_val as f32 + 0.5) as u32
+}
+
+fn unpack_unorm(pckd: u32, bit_count: u32) -> f32 {
+    let max_val = (1u32 << bit_count) - 1;
+    (pckd & max_val) as f32 / max_val as f32
 }
 
 pub fn roughness_to_perceptual_roughness(r: f32) -> f32 {
@@ -30,38 +47,54 @@ pub fn perceptual_roughness_to_roughness(r: f32) -> f32 {
 }
 
 impl GbufferData {
-    #[allow(clippy::unused_self)]
     pub fn pack(&self) -> GbufferDataPacked {
-        #[cfg(not(target_arch = "spirv"))]
-        return GbufferDataPacked { v: UVec4::ZERO };
-        #[cfg(target_arch = "spirv")]
         GbufferDataPacked {
             v: UVec4::new(
                 pack_color_888(self.albedo),
                 pack_normal_11_10_11(self.normal).to_bits(),
-                vec2_to_f16x2(vec2(
-                    roughness_to_perceptual_roughness(self.roughness),
-                    self.metalness,
-                )),
+                self.pack_material(),
                 float3_to_rgb9e5(self.emissive),
             ),
         }
     }
+
+    // Must match `GbufferData::pack` in `gbuffer.hlsl`
+    fn pack_material(&self) -> u32 {
+        let angle_steps = 1u32 << ANISOTROPY_ANGLE_BITS;
+        let anisotropy_angle =
+            (self.anisotropy_angle * core::f32::consts::FRAC_1_PI * angle_steps as f32 + 0.5)
+                as u32
+                % angle_steps;
+
+        pack_unorm_rounded(roughness_to_perceptual_roughness(self.roughness), 8)
+            | (pack_unorm_rounded(self.metalness, 6) << 8)
+            | (pack_unorm_rounded(self.clearcoat, 3) << 14)
+            | (pack_unorm_rounded(
+                roughness_to_perceptual_roughness(self.clearcoat_roughness),
+                3,
+            ) << 17)
+            | (pack_unorm_rounded(self.transmission, 3) << 20)
+            | (pack_unorm_rounded(self.anisotropy, 3) << 23)
+            | (anisotropy_angle << 26)
+    }
 }
 
 impl GbufferDataPacked {
     pub fn unpack(&self) -> GbufferData {
-        #[cfg(not(target_arch = "spirv"))]
-        let roughness_metalness: Vec2 = Vec2::new(0.0, 0.0);
-        #[cfg(target_arch = "spirv")]
-        let roughness_metalness: Vec2 = f16x2_to_vec2(self.v.z);
+        let material = self.v.z;
 
         GbufferData {
             albedo: self.unpack_albedo(),
             emissive: rgb9e5_to_float3(self.v.w),
             normal: self.unpack_normal(),
-            roughness: perceptual_roughness_to_roughness(roughness_metalness.x),
-            metalness: roughness_metalness.y,
+            roughness: perceptual_roughness_to_roughness(unpack_unorm(material, 8)),
+            metalness: unpack_unorm(material >> 8, 6),
+            clearcoat: unpack_unorm(material >> 14, 3),
+            clearcoat_roughness: perceptual_roughness_to_roughness(unpack_unorm(material >> 17, 3)),
+            transmission: unpack_unorm(material >> 20, 3),
+            anisotropy: unpack_unorm(material >> 23, 3),
+            anisotropy_angle: (material >> 26) as f32 * core::f32::consts::PI
+                / (1u32 << ANISOTROPY_ANGLE_BITS) as f32,
         }
     }
 
@@ -1,5 +1,6 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
+pub mod atmosphere;
 pub mod camera;
 pub mod frame_constants;
 pub mod gbuffer;
@@ -1,4 +1,4 @@
-use macaw::{Mat2, UVec4, Vec2, Vec4};
+use macaw::{Mat2, UVec4, Vec2, Vec3, Vec4};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -11,12 +11,21 @@ pub struct MeshDescriptor {
     pub vertex_tangent_offset: u32,
     pub mat_data_offset: u32,
     pub index_offset: u32,
+    pub vertex_prev_core_offset: u32,
+    pub curve_tube_sides: u32,
+    pub vertex_quantization_flags: u32,
+    pub position_scale: [f32; 3],
+    pub position_offset: [f32; 3],
+    pub uv_scale: [f32; 2],
+    pub uv_offset: [f32; 2],
 }
 
 #[repr(C, align(16))]
 #[derive(Copy, Clone)]
 pub struct InstanceDynamicConstants {
+    pub base_color_tint: Vec3,
     pub emissive_multiplier: f32,
+    pub roughness_multiplier: f32,
 }
 
 #[derive(Clone, Copy)]
@@ -68,21 +68,17 @@ pub fn signum_f32(x: f32) -> f32 {
 }
 
 pub fn depth_to_view_z(depth: f32, frame_constants: &FrameConstants) -> f32 {
-    (depth
-        * -frame_constants
-            .view_constants
-            .clip_to_view
-            .to_cols_array_2d()[2][3])
-        .recip()
+    let view_zw =
+        (frame_constants.view_constants.clip_to_view * Vec4::new(0.0, 0.0, depth, 1.0)).zw();
+    view_zw.x / view_zw.y
 }
 
 pub fn depth_to_view_z_vec4(depth: Vec4, frame_constants: &FrameConstants) -> Vec4 {
-    (depth
-        * -frame_constants
-            .view_constants
-            .clip_to_view
-            .to_cols_array_2d()[2][3])
-        .recip()
+    let m = frame_constants
+        .view_constants
+        .clip_to_view
+        .to_cols_array_2d();
+    (depth * m[2][2] + Vec4::splat(m[3][2])) / (depth * m[2][3] + Vec4::splat(m[3][3]))
 }
 
 // Note: `const_mat3` is initialized with columns, while `float3x3` in HLSL is row-order,
@@ -1,5 +1,5 @@
 use crate::camera::CameraMatrices;
-use macaw::{Mat4, UVec2, Vec2, Vec3};
+use macaw::{Mat4, UVec2, Vec2, Vec3, Vec4};
 
 #[derive(Clone, Copy)]
 #[repr(C, align(16))]
@@ -20,6 +20,10 @@ pub struct ViewConstants {
 
     pub sample_offset_pixels: Vec2,
     pub sample_offset_clip: Vec2,
+
+    /// World-space plane; only the side where `dot(xyz, position) + w >= 0` is rendered.
+    /// Zero disables clipping.
+    pub clip_plane: Vec4,
 }
 
 impl ViewConstants {
@@ -33,6 +37,7 @@ impl ViewConstants {
             camera_matrices: camera_matrices.into(),
             prev_camera_matrices: prev_camera_matrices.into(),
             pixel_offset: Vec2::ZERO,
+            clip_plane: Vec4::ZERO,
         }
     }
 
@@ -80,6 +85,7 @@ pub struct VieportConstantBuilder {
     camera_matrices: CameraMatrices,
     prev_camera_matrices: CameraMatrices,
     pixel_offset: Vec2,
+    clip_plane: Vec4,
 }
 
 impl VieportConstantBuilder {
@@ -89,6 +95,11 @@ impl VieportConstantBuilder {
         self
     }
 
+    pub fn clip_plane(mut self, v: Vec4) -> Self {
+        self.clip_plane = v;
+        self
+    }
+
     pub fn build(self) -> ViewConstants {
         let clip_to_prev_clip = self.prev_camera_matrices.view_to_clip
             * self.prev_camera_matrices.world_to_view
@@ -112,6 +123,8 @@ impl VieportConstantBuilder {
 
             sample_offset_pixels: Vec2::ZERO,
             sample_offset_clip: Vec2::ZERO,
+
+            clip_plane: self.clip_plane,
         };
 
         res.set_pixel_offset(self.pixel_offset, self.render_extent);